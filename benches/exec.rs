@@ -0,0 +1,190 @@
+//! Divan benchmarks for exec latency, output streaming, file writes and
+//! boot, end to end through [`Sandbox`].
+//!
+//! Two backends, same bench bodies:
+//! - **mock** (always): the host-side pipeline alone — exec tracking,
+//!   policy checks, event emission, output assembly. Regressions here are
+//!   pure host overhead.
+//! - **vm** (`--features bench-kvm`): a real guest over the control
+//!   channel, on KVM (or VZ on macOS). Needs `VOID_BOX_KERNEL` and
+//!   `VOID_BOX_INITRAMFS`, as for the VM test suites.
+//!
+//! Run with: `cargo bench --bench exec [--features bench-kvm]`
+
+use std::sync::Arc;
+
+use divan::{counter::BytesCount, Bencher};
+use tokio::runtime::Runtime;
+use void_box::sandbox::{MockScript, Sandbox};
+use void_box::ExecOutput;
+
+/// Output size of the streaming benches.
+const STREAM_BYTES: usize = 100 * 1024 * 1024;
+
+/// Sizes written by the `write_file` benches.
+const WRITE_SIZES: [usize; 4] = [4 * 1024, 256 * 1024, 4 * 1024 * 1024, 32 * 1024 * 1024];
+
+fn main() {
+    divan::main();
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime")
+}
+
+/// Run `program args` with streaming output and return how many stdout
+/// bytes arrived.
+async fn stream_stdout(sandbox: &Sandbox, program: &str, args: &[&str]) -> usize {
+    let (mut chunks, response) = sandbox
+        .exec_streaming(program, args, None)
+        .await
+        .expect("streaming exec starts");
+    let mut received = 0;
+    while let Some(chunk) = chunks.recv().await {
+        if chunk.stream == "stdout" {
+            received += chunk.data.len();
+        }
+    }
+    let response = response
+        .await
+        .expect("exec response")
+        .expect("exec succeeds");
+    assert_eq!(response.exit_code, 0);
+    received
+}
+
+// ---------------------------------------------------------------------------
+// Mock backend
+// ---------------------------------------------------------------------------
+
+mod mock {
+    use super::*;
+
+    fn sandbox() -> Arc<Sandbox> {
+        let script = MockScript::new().on_exec("head", |_| {
+            Ok(ExecOutput::new(vec![0u8; STREAM_BYTES], Vec::new(), 0))
+        });
+        Sandbox::mock()
+            .mock_script(script)
+            .build()
+            .expect("mock sandbox")
+    }
+
+    #[divan::bench]
+    fn exec_round_trip(bencher: Bencher) {
+        let rt = runtime();
+        let sandbox = sandbox();
+        bencher.bench_local(|| rt.block_on(sandbox.exec("echo", &["hi"])).unwrap());
+    }
+
+    #[divan::bench(sample_count = 10)]
+    fn exec_stream_stdout(bencher: Bencher) {
+        let rt = runtime();
+        let sandbox = sandbox();
+        bencher
+            .counter(BytesCount::new(STREAM_BYTES))
+            .bench_local(|| rt.block_on(stream_stdout(&sandbox, "head", &["-c", "104857600"])));
+    }
+
+    #[divan::bench(args = WRITE_SIZES)]
+    fn write_file(bencher: Bencher, size: usize) {
+        let rt = runtime();
+        let sandbox = sandbox();
+        let content = vec![0x5Au8; size];
+        bencher.counter(BytesCount::new(size)).bench_local(|| {
+            rt.block_on(sandbox.write_file("/tmp/bench", &content))
+                .unwrap()
+        });
+    }
+
+    #[divan::bench]
+    fn boot(bencher: Bencher) {
+        let rt = runtime();
+        bencher.bench_local(|| {
+            rt.block_on(async {
+                let sandbox = sandbox();
+                sandbox.start().await.unwrap();
+                sandbox.stop().await.unwrap();
+            })
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Real guest
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "bench-kvm")]
+mod vm {
+    use super::*;
+
+    /// A started sandbox, so per-iteration timings exclude the boot.
+    fn sandbox(rt: &Runtime) -> Arc<Sandbox> {
+        let sandbox = Sandbox::local()
+            .from_env()
+            .expect("bench-kvm needs VOID_BOX_KERNEL and VOID_BOX_INITRAMFS")
+            .memory_mb(512)
+            .build()
+            .expect("local sandbox");
+        rt.block_on(sandbox.start()).expect("sandbox boots");
+        sandbox
+    }
+
+    #[divan::bench(sample_count = 20)]
+    fn exec_round_trip(bencher: Bencher) {
+        let rt = runtime();
+        let sandbox = sandbox(&rt);
+        bencher.bench_local(|| rt.block_on(sandbox.exec("true", &[])).unwrap());
+        rt.block_on(sandbox.stop()).unwrap();
+    }
+
+    #[divan::bench(sample_count = 5, sample_size = 1)]
+    fn exec_stream_stdout(bencher: Bencher) {
+        let rt = runtime();
+        let sandbox = sandbox(&rt);
+        bencher
+            .counter(BytesCount::new(STREAM_BYTES))
+            .bench_local(|| {
+                let received = rt.block_on(stream_stdout(
+                    &sandbox,
+                    "head",
+                    &["-c", "104857600", "/dev/zero"],
+                ));
+                assert_eq!(received, STREAM_BYTES);
+            });
+        rt.block_on(sandbox.stop()).unwrap();
+    }
+
+    #[divan::bench(args = WRITE_SIZES, sample_count = 5, sample_size = 1)]
+    fn write_file(bencher: Bencher, size: usize) {
+        let rt = runtime();
+        let sandbox = sandbox(&rt);
+        let content = vec![0x5Au8; size];
+        bencher.counter(BytesCount::new(size)).bench_local(|| {
+            rt.block_on(sandbox.write_file("/tmp/bench", &content))
+                .unwrap()
+        });
+        rt.block_on(sandbox.stop()).unwrap();
+    }
+
+    /// Cold boot to a guest-agent that answers, then shutdown.
+    #[divan::bench(sample_count = 5, sample_size = 1)]
+    fn boot(bencher: Bencher) {
+        let rt = runtime();
+        bencher.bench_local(|| {
+            rt.block_on(async {
+                let sandbox = Sandbox::local()
+                    .from_env()
+                    .expect("bench-kvm needs VOID_BOX_KERNEL and VOID_BOX_INITRAMFS")
+                    .memory_mb(512)
+                    .build()
+                    .unwrap();
+                sandbox.start().await.unwrap();
+                sandbox.stop().await.unwrap();
+            })
+        });
+    }
+}
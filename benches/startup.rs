@@ -50,6 +50,10 @@ fn exec_request_bytes() -> Vec<u8> {
         env: Vec::new(),
         working_dir: None,
         timeout_secs: None,
+        exec_id: None,
+        policy: None,
+        user: None,
+        stdin_stream: false,
     })
     .expect("exec request serializes")
 }
@@ -102,6 +106,10 @@ fn exec_request_to_json(bencher: Bencher) {
         env: Vec::new(),
         working_dir: None,
         timeout_secs: None,
+        exec_id: None,
+        policy: None,
+        user: None,
+        stdin_stream: false,
     };
     bencher.bench_local(|| divan::black_box(serde_json::to_vec(divan::black_box(&req)).unwrap()));
 }
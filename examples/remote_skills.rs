@@ -119,6 +119,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
             void_box::skill::SkillKind::Mcp { .. } => "mcp",
             void_box::skill::SkillKind::Cli { .. } => "cli",
             void_box::skill::SkillKind::Oci { .. } => "oci",
+            void_box::skill::SkillKind::Registry { .. } => "registry",
             void_box::skill::SkillKind::Inline { .. } => "inline",
         };
         println!(
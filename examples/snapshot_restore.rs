@@ -74,6 +74,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         cid: 0, // overwritten by snapshot_internal()
         vsock_mmio_base: 0xd080_0000,
         network: enable_network,
+        virtio_mmio_base: None,
     };
 
     // ═══════════════════════════════════════════════════════════════
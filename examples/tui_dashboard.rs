@@ -0,0 +1,41 @@
+//! Live terminal dashboard for a sandbox.
+//!
+//! Boots a sandbox (KVM when `VOID_BOX_KERNEL` is set, mock otherwise),
+//! runs a command that prints for a while, and shows it in the dashboard.
+//! Press `q` to quit.
+//!
+//!   cargo run --example tui_dashboard --features voidbox-tui
+
+use std::error::Error;
+
+use void_box::observe::ObserveConfig;
+use void_box::sandbox::Sandbox;
+use void_box::tui::Dashboard;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let sandbox = if std::env::var_os("VOID_BOX_KERNEL").is_some() {
+        Sandbox::local()
+            .from_env()?
+            .observe(ObserveConfig::default())
+            .build()?
+    } else {
+        Sandbox::mock().build()?
+    };
+
+    let dashboard = Dashboard::new(sandbox.clone());
+    let feed = dashboard.output_feed();
+    let worker = sandbox.clone();
+    tokio::spawn(async move {
+        let script = "for i in $(seq 1 30); do echo \"tick $i\"; sleep 1; done";
+        let (mut chunks, _done) = worker.exec_streaming("sh", &["-c", script], None).await?;
+        while let Some(chunk) = chunks.recv().await {
+            feed.send(&chunk);
+        }
+        void_box::Result::Ok(())
+    });
+
+    dashboard.run().await?;
+    sandbox.stop().await?;
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Exec output routing and re-attachment.
+//!
+//! An exec's output chunks and final `ExecResponse` go to the connection
+//! and request id that started it. When that connection closes, its execs
+//! are detached so nothing is written to a descriptor that may since have
+//! been reused, and the commands keep running. A host that set
+//! [`ExecRequest::exec_id`](void_box_protocol::ExecRequest::exec_id) can
+//! then send an [`AttachExecRequest`] on a new connection to have the rest
+//! of the output, and the response, delivered there instead.
+
+use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+use void_box_protocol::{AttachExecRequest, ExecResponse, MessageType};
+
+/// Where a running exec's frames go.
+pub(crate) struct Output {
+    exec_id: Option<String>,
+    route: Mutex<Route>,
+}
+
+struct Route {
+    /// Connection and request id the host reads the exec's frames under;
+    /// `None` while detached.
+    target: Option<(RawFd, u32)>,
+    /// Set once the response has been sent, or dropped for want of a
+    /// connection; a late attach would wait for it forever.
+    finished: bool,
+}
+
+static OUTPUTS: Mutex<Vec<Arc<Output>>> = Mutex::new(Vec::new());
+
+fn outputs() -> MutexGuard<'static, Vec<Arc<Output>>> {
+    OUTPUTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Output {
+    fn route(&self) -> MutexGuard<'_, Route> {
+        self.route.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends a frame to the attached connection. Fails while detached.
+    pub(crate) fn send<T: Serialize>(
+        &self,
+        msg_type: MessageType,
+        payload: &T,
+    ) -> Result<(), String> {
+        match self.route().target {
+            Some((fd, request_id)) => crate::send_mux_response(fd, msg_type, request_id, payload),
+            None => Err("no connection attached".into()),
+        }
+    }
+
+    /// Sends the exec's final response; later attaches fail.
+    pub(crate) fn finish(&self, response: &ExecResponse) -> Result<(), String> {
+        let mut route = self.route();
+        route.finished = true;
+        match route.target {
+            Some((fd, request_id)) => {
+                crate::send_mux_response(fd, MessageType::ExecResponse, request_id, response)
+            }
+            None => Err("no connection attached".into()),
+        }
+    }
+}
+
+/// Keeps an exec's output routable until dropped, after its response.
+pub(crate) struct Registration {
+    output: Arc<Output>,
+}
+
+impl Registration {
+    pub(crate) fn output(&self) -> &Arc<Output> {
+        &self.output
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        outputs().retain(|output| !Arc::ptr_eq(output, &self.output));
+    }
+}
+
+/// Routes a new exec's frames to `request_id` on `fd`.
+pub(crate) fn register(exec_id: Option<&str>, fd: RawFd, request_id: u32) -> Registration {
+    let output = Arc::new(Output {
+        exec_id: exec_id.map(str::to_string),
+        route: Mutex::new(Route {
+            target: Some((fd, request_id)),
+            finished: false,
+        }),
+    });
+    outputs().push(Arc::clone(&output));
+    Registration { output }
+}
+
+/// Detaches every exec routed to `fd`; called before the connection's
+/// descriptor is closed.
+pub(crate) fn detach(fd: RawFd) {
+    for output in outputs().iter() {
+        let mut route = output.route();
+        if route.target.is_some_and(|(target_fd, _)| target_fd == fd) {
+            route.target = None;
+        }
+    }
+}
+
+/// Routes the running exec `request.exec_id` to `request_id` on `fd`.
+pub(crate) fn attach(
+    fd: RawFd,
+    request_id: u32,
+    request: &AttachExecRequest,
+) -> Result<(), String> {
+    let outputs = outputs();
+    let mut matching = outputs
+        .iter()
+        .filter(|output| output.exec_id.as_deref() == Some(request.exec_id.as_str()));
+    let output = match (matching.next(), matching.next()) {
+        (Some(output), None) => output,
+        (None, _) => return Err(format!("no running exec with id {}", request.exec_id)),
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "more than one exec runs with id {}",
+                request.exec_id
+            ))
+        }
+    };
+    let mut route = output.route();
+    if route.finished {
+        return Err(format!("exec {} has already finished", request.exec_id));
+    }
+    route.target = Some((fd, request_id));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attach_to(exec_id: &str, fd: RawFd, request_id: u32) -> Result<(), String> {
+        attach(
+            fd,
+            request_id,
+            &AttachExecRequest {
+                exec_id: exec_id.into(),
+            },
+        )
+    }
+
+    fn target(registration: &Registration) -> Option<(RawFd, u32)> {
+        registration.output().route().target
+    }
+
+    #[test]
+    fn detached_execs_can_be_attached_until_they_finish() {
+        let server = register(Some("attach-test-server"), 1001, 7);
+        let other = register(Some("attach-test-other"), 1002, 8);
+
+        detach(1001);
+        assert_eq!(target(&server), None);
+        assert_eq!(target(&other), Some((1002, 8)));
+        assert!(server
+            .output()
+            .send(MessageType::ExecOutputChunk, &())
+            .is_err());
+
+        attach_to("attach-test-server", 1003, 2).unwrap();
+        assert_eq!(target(&server), Some((1003, 2)));
+
+        detach(1003);
+        assert!(server.output().finish(&ExecResponse::default()).is_err());
+        let err = attach_to("attach-test-server", 1004, 3).unwrap_err();
+        assert!(err.contains("already finished"), "{err}");
+
+        drop(server);
+        let err = attach_to("attach-test-server", 1004, 3).unwrap_err();
+        assert!(err.contains("no running exec"), "{err}");
+    }
+
+    #[test]
+    fn ids_shared_by_several_execs_cannot_be_attached() {
+        let _first = register(Some("attach-test-shared"), 1011, 1);
+        let _second = register(Some("attach-test-shared"), 1012, 2);
+        let err = attach_to("attach-test-shared", 1013, 3).unwrap_err();
+        assert!(err.contains("more than one"), "{err}");
+    }
+}
@@ -0,0 +1,123 @@
+//! Boot phase timing and the fast-boot profile.
+//!
+//! The agent times the boot steps the host cannot see (loading modules,
+//! listening on vsock) and reports each as a `phase` boot-status line, which
+//! the host turns into `boot.*` spans. With `voidbox.fast_boot=1` on the
+//! kernel cmdline (`BootProfile::FastBoot`) it also skips optional modules
+//! for devices the host did not attach and polls for devices and the vsock
+//! listener at a finer interval.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use void_box_protocol::{BootStatus, GuestBootPhase};
+
+use crate::{boot_status, network_enabled_in};
+
+/// Polling intervals are divided by this under the fast-boot profile; the
+/// overall timeouts stay the same.
+const FAST_BOOT_POLL_DIVISOR: u32 = 10;
+
+/// Time since the guest kernel started (`CLOCK_BOOTTIME`).
+pub(crate) fn boottime() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Report the boot step that started at `start` (a [`boottime`]) and ends
+/// now.
+pub(crate) fn report_phase(name: &str, start: Duration) {
+    let phase = GuestBootPhase {
+        name: name.to_string(),
+        start,
+        duration: boottime().saturating_sub(start),
+    };
+    boot_status(BootStatus::Phase, &phase.to_detail());
+}
+
+/// Whether the host asked for the fast-boot profile.
+pub(crate) fn fast_boot() -> bool {
+    static FAST_BOOT: OnceLock<bool> = OnceLock::new();
+    *FAST_BOOT.get_or_init(|| {
+        let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+        parse_fast_boot(&cmdline)
+    })
+}
+
+fn parse_fast_boot(cmdline: &str) -> bool {
+    cmdline
+        .split_whitespace()
+        .any(|token| token == "voidbox.fast_boot=1")
+}
+
+/// How long to sleep between polls that normally sleep `interval`, and how
+/// many polls fit in the `attempts * interval` the caller budgets for.
+pub(crate) fn poll_schedule(interval: Duration, attempts: u32) -> (Duration, u32) {
+    if fast_boot() {
+        (
+            interval / FAST_BOOT_POLL_DIVISOR,
+            attempts * FAST_BOOT_POLL_DIVISOR,
+        )
+    } else {
+        (interval, attempts)
+    }
+}
+
+/// Whether the optional module `module` is worth loading on a fast boot:
+/// only when the cmdline announces a device or mount that needs it.
+/// virtio-rng and virtio-balloon are never loaded; the fast-boot profile
+/// does not attach those devices.
+pub(crate) fn fast_boot_wants_module(module: &str, cmdline: &str) -> bool {
+    let has = |prefix: &str| {
+        cmdline
+            .split_whitespace()
+            .any(|token| token.starts_with(prefix))
+    };
+    match module {
+        // virtio_mmio depends on these when they are not built in.
+        "virtio.ko" | "virtio_ring.ko" => true,
+        "failover.ko" | "net_failover.ko" | "virtio_net.ko" => network_enabled_in(cmdline),
+        "virtio_blk.ko" => has("voidbox.disk") || has("voidbox.oci_rootfs_dev=") || has("root="),
+        // VZ delivers the OCI rootfs over virtiofs.
+        "virtiofs.ko" => has("voidbox.mount") || has("voidbox.oci_rootfs="),
+        "netfs.ko" | "9pnet.ko" | "9p.ko" | "9pnet_virtio.ko" => has("voidbox.mount"),
+        "overlay.ko" => has("voidbox.oci_rootfs"),
+        "virtio_console.ko" => has(void_box_protocol::CONTROL_CMDLINE_ARG),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_boot_wants_module() {
+        let cmdline = "console=ttyS0 voidbox.fast_boot=1 voidbox.mount0=mount0:/data:ro";
+        assert!(parse_fast_boot(cmdline));
+        assert!(!parse_fast_boot("console=ttyS0 voidbox.fast_boot=0"));
+
+        assert!(fast_boot_wants_module("virtio.ko", cmdline));
+        assert!(fast_boot_wants_module("9pnet_virtio.ko", cmdline));
+        assert!(!fast_boot_wants_module("virtio_net.ko", cmdline));
+        assert!(fast_boot_wants_module(
+            "virtio_net.ko",
+            "virtio_mmio.device=512@0xd0000000:10 ipv6.disable=1"
+        ));
+        assert!(!fast_boot_wants_module("overlay.ko", cmdline));
+        assert!(!fast_boot_wants_module("virtio-rng.ko", cmdline));
+        assert!(fast_boot_wants_module(
+            "overlay.ko",
+            "voidbox.oci_rootfs_dev=/dev/vdb voidbox.oci_rootfs=/"
+        ));
+        assert!(!fast_boot_wants_module("virtio_console.ko", cmdline));
+        assert!(fast_boot_wants_module(
+            "virtio_console.ko",
+            "voidbox.fast_boot=1 voidbox.control=serial"
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+//! Frame reading for host connections.
+//!
+//! Everything [`handle_connection`](crate::handle_connection) decides before
+//! dispatching a request lives here, over any [`Read`]: framing, the size
+//! cap, the authentication gate and the multiplex `request_id` prefix. That
+//! keeps it free of sockets and globals, so the fuzz targets in
+//! `void-box-protocol/fuzz` and the tests below can drive it with arbitrary
+//! byte streams.
+
+use std::io::{self, Read};
+use std::os::unix::io::RawFd;
+
+use void_box_protocol::{MessageType, HEADER_SIZE, MAX_MESSAGE_SIZE};
+
+/// What the next frame on a connection turned out to be.
+#[derive(Debug)]
+pub(crate) enum Incoming {
+    /// The peer closed the connection, possibly mid-header.
+    Closed,
+    /// A type byte this agent does not know; the frame was consumed and the
+    /// connection can go on.
+    Unknown(u8),
+    Request(Frame),
+}
+
+/// A request ready to dispatch.
+#[derive(Debug)]
+pub(crate) struct Frame {
+    pub(crate) message_type: MessageType,
+    /// Zero for the Ping handshake, which has no multiplex prefix.
+    pub(crate) request_id: u32,
+    payload: Vec<u8>,
+    body_start: usize,
+}
+
+impl Frame {
+    /// The payload after the `request_id` prefix.
+    pub(crate) fn body(&self) -> &[u8] {
+        &self.payload[self.body_start..]
+    }
+}
+
+/// Read the next frame from `reader`.
+///
+/// Until the connection is `authenticated` only a Ping is accepted. An
+/// error means the stream can no longer be trusted and the connection must
+/// be dropped.
+pub(crate) fn read_request<R: Read + ?Sized>(
+    reader: &mut R,
+    authenticated: bool,
+) -> Result<Incoming, String> {
+    let mut header = [0u8; HEADER_SIZE];
+    if reader.read_exact(&mut header).is_err() {
+        return Ok(Incoming::Closed);
+    }
+
+    let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let msg_type = header[4];
+
+    // Reject oversized messages before allocating
+    if length > MAX_MESSAGE_SIZE {
+        return Err(format!(
+            "Payload too large: {} bytes (max {})",
+            length, MAX_MESSAGE_SIZE
+        ));
+    }
+
+    let mut payload = vec![0u8; length];
+    reader
+        .read_exact(&mut payload)
+        .map_err(|_| "Read failed".to_string())?;
+
+    if msg_type != MessageType::Ping as u8 && !authenticated {
+        return Err(format!(
+            "Connection not authenticated -- send Ping with session secret first \
+             (got message type {msg_type})"
+        ));
+    }
+
+    let Ok(message_type) = MessageType::try_from(msg_type) else {
+        return Ok(Incoming::Unknown(msg_type));
+    };
+
+    // Ping is the pre-multiplex handshake: its payload is the session
+    // secret + version + flags with no request_id prefix. Everything
+    // else speaks the multiplex frame: payload = [request_id:4 LE][body].
+    let (request_id, body_start) = if message_type == MessageType::Ping {
+        (0, 0)
+    } else if payload.len() < 4 {
+        return Err(format!(
+            "multiplex payload too short for {:?}: {} bytes",
+            message_type,
+            payload.len()
+        ));
+    } else {
+        let id = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        (id, 4)
+    };
+
+    Ok(Incoming::Request(Frame {
+        message_type,
+        request_id,
+        payload,
+        body_start,
+    }))
+}
+
+/// Reads from a borrowed socket fd.
+pub(crate) struct FdReader(pub(crate) RawFd);
+
+impl Read for FdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe { libc::read(self.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use void_box_protocol::fuzzing::{arbitrary_message, mutate_frame, FuzzRng};
+    use void_box_protocol::Message;
+
+    fn frame(msg_type: MessageType, request_id: Option<u32>, body: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        if let Some(id) = request_id {
+            payload.extend_from_slice(&id.to_le_bytes());
+        }
+        payload.extend_from_slice(body);
+        Message { msg_type, payload }.serialize()
+    }
+
+    /// Read frames until the stream ends or is rejected, checking what each
+    /// one decodes to against the bytes it came from.
+    fn drain(stream: &[u8], authenticated: bool) -> Result<usize, String> {
+        let mut reader = Cursor::new(stream);
+        let mut requests = 0;
+        loop {
+            let start = reader.position() as usize;
+            match read_request(&mut reader, authenticated)? {
+                Incoming::Closed => return Ok(requests),
+                Incoming::Unknown(msg_type) => {
+                    assert!(MessageType::try_from(msg_type).is_err());
+                }
+                Incoming::Request(request) => {
+                    let message = Message::deserialize(&stream[start..]).unwrap();
+                    assert_eq!(request.message_type, message.msg_type);
+                    assert!(authenticated || request.message_type == MessageType::Ping);
+                    assert!(message.payload.ends_with(request.body()));
+                    requests += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn requests_are_split_into_id_and_body() {
+        let mut stream = frame(MessageType::Ping, None, b"secret");
+        stream.extend(frame(MessageType::ExecRequest, Some(7), b"{}"));
+        let mut reader = Cursor::new(stream);
+
+        let Incoming::Request(ping) = read_request(&mut reader, false).unwrap() else {
+            panic!("expected Ping");
+        };
+        assert_eq!((ping.request_id, ping.body()), (0, &b"secret"[..]));
+
+        let Incoming::Request(exec) = read_request(&mut reader, true).unwrap() else {
+            panic!("expected ExecRequest");
+        };
+        assert_eq!(exec.message_type, MessageType::ExecRequest);
+        assert_eq!((exec.request_id, exec.body()), (7, &b"{}"[..]));
+
+        assert!(matches!(
+            read_request(&mut reader, true).unwrap(),
+            Incoming::Closed
+        ));
+    }
+
+    #[test]
+    fn hostile_frames_are_rejected() {
+        let exec = frame(MessageType::ExecRequest, Some(1), b"{}");
+        assert!(read_request(&mut Cursor::new(&exec), false).is_err());
+
+        let short = frame(MessageType::ExecRequest, None, b"ab");
+        assert!(read_request(&mut Cursor::new(&short), true).is_err());
+
+        let mut oversized = exec.clone();
+        oversized[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_request(&mut Cursor::new(&oversized), true).is_err());
+
+        let mut unknown = exec.clone();
+        unknown[4] = 200;
+        assert!(matches!(
+            read_request(&mut Cursor::new(&unknown), true).unwrap(),
+            Incoming::Unknown(200)
+        ));
+
+        let truncated = &exec[..exec.len() - 1];
+        assert!(read_request(&mut Cursor::new(truncated), true).is_err());
+        assert!(matches!(
+            read_request(&mut Cursor::new(&exec[..3]), true).unwrap(),
+            Incoming::Closed
+        ));
+    }
+
+    #[test]
+    fn fuzzed_streams_never_panic() {
+        for seed in 0..512 {
+            let mut rng = FuzzRng::new(seed);
+            let mut stream = Vec::new();
+            for _ in 0..rng.below(8) {
+                let message = arbitrary_message(&mut rng).serialize();
+                if rng.chance(4) {
+                    stream.extend(mutate_frame(&mut rng, &message));
+                } else {
+                    stream.extend(message);
+                }
+            }
+            let authenticated = rng.chance(2);
+            let _ = drain(&stream, authenticated);
+            let len = rng.below(64);
+            let _ = drain(&rng.bytes(len), authenticated);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Debug shell on the serial console.
+//!
+//! When the host boots with `voidbox.console_shell=<tty>` on the kernel
+//! cmdline (`SandboxBuilder::console_shell`), the agent runs a root
+//! `/bin/sh` on `/dev/<tty>` and starts a new one whenever it exits. The
+//! host reaches it through `Sandbox::attach_console`, which needs no vsock,
+//! so a boot can be inspected even when the control channel never comes up.
+
+use std::fs::OpenOptions;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use void_box_protocol::GUEST_PATH;
+
+use crate::kmsg;
+
+/// Pause before respawning, so a shell that cannot start does not spin.
+const RESPAWN_DELAY: Duration = Duration::from_secs(1);
+
+/// Start the console shell thread if the cmdline asks for one.
+pub(crate) fn spawn_from_cmdline() {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    let Some(tty) = parse_console_shell(&cmdline).map(str::to_string) else {
+        return;
+    };
+    kmsg(&format!("Console shell enabled on /dev/{}", tty));
+    let spawned = std::thread::Builder::new()
+        .name("console-shell".into())
+        .spawn(move || loop {
+            match run_shell(&tty) {
+                Ok(status) => kmsg(&format!("Console shell exited ({}), respawning", status)),
+                Err(e) => kmsg(&format!("Console shell on /dev/{} failed: {}", tty, e)),
+            }
+            std::thread::sleep(RESPAWN_DELAY);
+        });
+    if let Err(e) = spawned {
+        kmsg(&format!("Failed to spawn console shell thread: {}", e));
+    }
+}
+
+/// The tty named by `voidbox.console_shell=`, if any.
+fn parse_console_shell(cmdline: &str) -> Option<&str> {
+    cmdline
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix("voidbox.console_shell="))
+        .filter(|tty| !tty.is_empty() && !tty.contains('/'))
+}
+
+/// Run one shell with `/dev/<tty>` as its controlling terminal and wait for
+/// it to exit.
+fn run_shell(tty: &str) -> std::io::Result<ExitStatus> {
+    let console = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!("/dev/{}", tty))?;
+    let mut cmd = Command::new("/bin/sh");
+    cmd.env_clear()
+        .env("PATH", GUEST_PATH)
+        .env("HOME", "/root")
+        .env("TERM", "linux")
+        .current_dir("/")
+        .stdin(console.try_clone()?)
+        .stdout(console.try_clone()?)
+        .stderr(console);
+    // SAFETY: only async-signal-safe calls between fork and exec.
+    unsafe {
+        cmd.pre_exec(|| {
+            // A new session, with the console (already on fd 0) as its
+            // controlling terminal, so ^C and job control work.
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 1) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    cmd.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_console_shell() {
+        assert_eq!(
+            parse_console_shell("console=ttyS0 voidbox.console_shell=ttyS0 panic=1"),
+            Some("ttyS0")
+        );
+        assert_eq!(parse_console_shell("console=ttyS0 panic=1"), None);
+        assert_eq!(
+            parse_console_shell("voidbox.console_shell=../sda voidbox.clock=1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Serving the control channel on a virtio-console port.
+//!
+//! When the host boots with `voidbox.control=serial` on the kernel cmdline
+//! (`KvmBackend::with_serial_control`), it talks to the agent over the
+//! virtio-console port named `org.voidbox.control` instead of vsock. The
+//! vsock listener keeps running for PTY sessions.
+//!
+//! A serial port has no accept: the agent keeps the port open and starts a
+//! session once the host has sent something. The host ends a connection
+//! by closing its side, which the agent reads as end-of-file; closing and
+//! reopening the port then makes way for the next one.
+
+use std::ffi::CString;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use void_box_protocol::{CONTROL_CMDLINE_ARG, CONTROL_PORT_NAME};
+
+use crate::kmsg;
+
+/// How often to look for the port while the driver is still probing, and
+/// to recheck a port with no host connection.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Start the control port thread if the cmdline asks for it.
+pub(crate) fn spawn_from_cmdline() {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    if !enabled(&cmdline) {
+        return;
+    }
+    let spawned = std::thread::Builder::new()
+        .name("control-port".into())
+        .spawn(serve);
+    if let Err(e) = spawned {
+        kmsg(&format!("Failed to spawn control port thread: {}", e));
+    }
+}
+
+fn enabled(cmdline: &str) -> bool {
+    cmdline
+        .split_whitespace()
+        .any(|token| token == CONTROL_CMDLINE_ARG)
+}
+
+fn serve() {
+    let device = loop {
+        if let Some(device) = find_port(Path::new("/sys/class/virtio-ports"), CONTROL_PORT_NAME) {
+            break device;
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    };
+    kmsg(&format!("Serving control channel on {}", device.display()));
+    let path = CString::new(device.as_os_str().as_encoded_bytes()).unwrap();
+
+    loop {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd < 0 {
+            kmsg(&format!(
+                "Failed to open {}: {}",
+                device.display(),
+                std::io::Error::last_os_error()
+            ));
+            std::thread::sleep(RETRY_INTERVAL);
+            continue;
+        }
+        wait_for_host(fd);
+        // Authentication is per thread, so every session gets a fresh one.
+        let session = std::thread::Builder::new()
+            .name("conn".into())
+            .spawn(move || {
+                if let Err(e) = crate::handle_connection(fd) {
+                    eprintln!("Control port connection error: {}", e);
+                }
+            });
+        match session {
+            Ok(handle) => {
+                let _ = handle.join();
+            }
+            Err(e) => eprintln!("Failed to spawn connection thread: {}", e),
+        }
+        // As for vsock connections: commands started in the session keep
+        // running but must stop writing to the descriptor first.
+        crate::attach::detach(fd);
+        unsafe {
+            libc::close(fd);
+        }
+    }
+}
+
+/// Block until the host has data for the open port. Without a host
+/// connection the port polls as hung up, so recheck periodically.
+fn wait_for_host(fd: RawFd) {
+    loop {
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut pfd, 1, -1) };
+        if ret > 0 && pfd.revents & libc::POLLIN != 0 {
+            return;
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+/// The device node of the virtio-console port called `name`, from the
+/// port list under `sysfs_dir`.
+fn find_port(sysfs_dir: &Path, name: &str) -> Option<PathBuf> {
+    std::fs::read_dir(sysfs_dir)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            std::fs::read_to_string(entry.path().join("name"))
+                .is_ok_and(|port_name| port_name.trim_end() == name)
+        })
+        .map(|entry| Path::new("/dev").join(entry.file_name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_port_by_name() {
+        let sysfs = tempfile::tempdir().unwrap();
+        for (port, name) in [("vport0p0", ""), ("vport0p1", "org.voidbox.control\n")] {
+            std::fs::create_dir(sysfs.path().join(port)).unwrap();
+            std::fs::write(sysfs.path().join(port).join("name"), name).unwrap();
+        }
+        assert_eq!(
+            find_port(sysfs.path(), CONTROL_PORT_NAME),
+            Some(PathBuf::from("/dev/vport0p1"))
+        );
+        assert_eq!(find_port(sysfs.path(), "org.example"), None);
+
+        assert!(enabled("console=ttyS0 voidbox.control=serial"));
+        assert!(!enabled("console=ttyS0 voidbox.control=vsock"));
+    }
+}
@@ -0,0 +1,203 @@
+//! `/workspace` export as a streamed tar archive.
+//!
+//! The archive is produced in-process with the `tar` crate rather than by
+//! exec'ing `tar`: the exec path accumulates all of stdout into the final
+//! `ExecResponse`, which caps the archive at what fits in one message.
+//! Here the bytes leave in `EXPORT_CHUNK_SIZE` raw frames as they are
+//! produced, so the guest holds at most one chunk regardless of workspace
+//! size.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use void_box_protocol::{glob_match, ExportWorkspaceResponse, EXPORT_CHUNK_SIZE};
+
+/// Buffers archive bytes and hands them to `send` one full chunk at a time.
+struct ChunkWriter<F: FnMut(&[u8]) -> io::Result<()>> {
+    buf: Vec<u8>,
+    sent: u64,
+    send: F,
+}
+
+impl<F: FnMut(&[u8]) -> io::Result<()>> ChunkWriter<F> {
+    fn new(send: F) -> Self {
+        Self {
+            buf: Vec::with_capacity(EXPORT_CHUNK_SIZE),
+            sent: 0,
+            send,
+        }
+    }
+
+    fn send_buffered(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            (self.send)(&self.buf)?;
+            self.sent += self.buf.len() as u64;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<F: FnMut(&[u8]) -> io::Result<()>> Write for ChunkWriter<F> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let room = EXPORT_CHUNK_SIZE - self.buf.len();
+        let taken = data.len().min(room);
+        self.buf.extend_from_slice(&data[..taken]);
+        if self.buf.len() == EXPORT_CHUNK_SIZE {
+            self.send_buffered()?;
+        }
+        Ok(taken)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffered()
+    }
+}
+
+/// Archive the regular files and symlinks under `dir` whose relative path
+/// matches `path_filter`, passing the archive to `send` in chunks.
+///
+/// `dir` is where the tree is read from (a `/proc/self/fd/<n>` path for a
+/// guard-resolved root); archive paths are relative to it.
+pub(crate) fn export_tree<F>(
+    dir: &Path,
+    path_filter: Option<&str>,
+    send: F,
+) -> ExportWorkspaceResponse
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut writer = ChunkWriter::new(send);
+    let result = (|| {
+        let mut builder = tar::Builder::new(&mut writer);
+        builder.follow_symlinks(false);
+        let mut entries = 0u64;
+        for relative in collect_entries(dir)? {
+            let name = relative.to_string_lossy();
+            if path_filter.is_some_and(|pattern| !glob_match(pattern, &name)) {
+                continue;
+            }
+            builder.append_path_with_name(dir.join(&relative), &relative)?;
+            entries += 1;
+        }
+        builder.finish()?;
+        Ok::<_, io::Error>(entries)
+    })();
+    let result = result.and_then(|entries| writer.flush().map(|()| entries));
+
+    match result {
+        Ok(entries) => ExportWorkspaceResponse {
+            success: true,
+            entries,
+            archive_bytes: writer.sent,
+            error: None,
+        },
+        Err(e) => ExportWorkspaceResponse {
+            success: false,
+            entries: 0,
+            archive_bytes: writer.sent,
+            error: Some(format!("Failed to archive workspace: {}", e)),
+        },
+    }
+}
+
+/// Relative paths of regular files and symlinks under `dir`, sorted so the
+/// archive layout is stable across runs.
+fn collect_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            let child = relative.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(child);
+            } else if file_type.is_file() || file_type.is_symlink() {
+                found.push(child);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(dir: &Path, filter: Option<&str>) -> (ExportWorkspaceResponse, Vec<Vec<u8>>) {
+        let mut chunks = Vec::new();
+        let response = export_tree(dir, filter, |chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        });
+        (response, chunks)
+    }
+
+    fn archive_names(chunks: &[Vec<u8>]) -> Vec<String> {
+        let bytes = chunks.concat();
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_export_tree_archives_files_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("out/nested")).unwrap();
+        fs::write(dir.path().join("result.json"), b"{}").unwrap();
+        fs::write(dir.path().join("out/nested/log.txt"), b"log").unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", dir.path().join("link")).unwrap();
+
+        let (response, chunks) = export(dir.path(), None);
+        assert!(response.success, "{:?}", response.error);
+        assert_eq!(response.entries, 3);
+        assert_eq!(
+            response.archive_bytes,
+            chunks.iter().map(|c| c.len() as u64).sum::<u64>()
+        );
+        assert_eq!(
+            archive_names(&chunks),
+            vec!["link", "out/nested/log.txt", "result.json"]
+        );
+
+        let bytes = chunks.concat();
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        let link = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(link.header().entry_type(), tar::EntryType::Symlink);
+    }
+
+    #[test]
+    fn test_export_tree_applies_path_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("out")).unwrap();
+        fs::write(dir.path().join("out/a.json"), b"1").unwrap();
+        fs::write(dir.path().join("out/b.txt"), b"2").unwrap();
+        fs::write(dir.path().join("c.json"), b"3").unwrap();
+
+        let (response, chunks) = export(dir.path(), Some("**/*.json"));
+        assert_eq!(response.entries, 2);
+        assert_eq!(archive_names(&chunks), vec!["c.json", "out/a.json"]);
+    }
+
+    #[test]
+    fn test_chunk_writer_sends_full_chunks_then_remainder() {
+        let mut sizes = Vec::new();
+        let mut writer = ChunkWriter::new(|chunk: &[u8]| {
+            sizes.push(chunk.len());
+            Ok(())
+        });
+        writer
+            .write_all(&vec![0u8; EXPORT_CHUNK_SIZE * 2 + 10])
+            .unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.sent, (EXPORT_CHUNK_SIZE * 2 + 10) as u64);
+        drop(writer);
+        assert_eq!(sizes, vec![EXPORT_CHUNK_SIZE, EXPORT_CHUNK_SIZE, 10]);
+    }
+}
@@ -0,0 +1,475 @@
+//! Filesystem change reporting for the `FsDiff` RPC.
+//!
+//! Two sources of truth, picked per request:
+//!
+//! - **Overlay upper layer.** An OCI rootfs guest runs on overlayfs whose
+//!   writable upper directory holds exactly the paths that diverged from
+//!   the image, plus whiteouts for deletions. Walking it is proportional to
+//!   what changed, not to the size of the image. Both layers become
+//!   unreachable by path once `pivot_root` detaches the old root, so
+//!   `setup_oci_rootfs` hands us `O_PATH` fds opened beforehand and we walk
+//!   them through `/proc/self/fd/<n>`. The boot sequence itself writes into
+//!   the upper layer (staged tools, `resolv.conf`), so its state at the end
+//!   of setup is recorded and subtracted.
+//!
+//! - **Baseline manifest.** Everywhere else (initramfs root, or an explicit
+//!   directory) there is no record of the original state, so the first
+//!   request for a root stores a manifest of sizes, modes, and mtimes and
+//!   later requests compare against it. The walk never crosses into another
+//!   filesystem, which keeps `/proc`, `/sys`, and host mounts out of a
+//!   whole-root diff.
+//!
+//! Only non-directory entries are reported. Content hashes are computed for
+//! created and modified regular files at report time; manifests never hash,
+//! so recording a baseline stays cheap.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, Metadata, OpenOptions};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use sha2::{Digest, Sha256};
+use void_box_protocol::{FsChange, FsChangeKind, FsDiffResponse, MAX_FS_DIFF_CHANGES};
+
+use crate::kmsg;
+
+/// Extended attribute overlayfs sets on a directory that hides the lower
+/// layer's contents (a directory deleted and re-created in the upper layer).
+const OVERLAY_OPAQUE_XATTR: &[u8] = b"trusted.overlay.opaque\0";
+
+/// What identifies an unchanged entry between two walks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntrySig {
+    mode: u32,
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+impl From<&Metadata> for EntrySig {
+    fn from(meta: &Metadata) -> Self {
+        Self {
+            mode: meta.mode(),
+            size: meta.size(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+        }
+    }
+}
+
+/// Non-directory entries under a root, keyed by path relative to it.
+type Manifest = BTreeMap<PathBuf, EntrySig>;
+
+/// Overlay layer fds plus the upper layer's state when boot finished.
+struct OverlayLayers {
+    upper: OwnedFd,
+    lower: OwnedFd,
+    boot: Manifest,
+}
+
+static OVERLAY_LAYERS: OnceLock<OverlayLayers> = OnceLock::new();
+
+/// Baseline manifests keyed by the root path the host asked for.
+static BASELINES: Mutex<BTreeMap<String, Manifest>> = Mutex::new(BTreeMap::new());
+
+/// Open the overlay's upper and lower directories while they are still
+/// reachable by path (before `pivot_root`).
+pub(crate) fn open_overlay_layers(upper: &str, lower: &str) -> io::Result<(OwnedFd, OwnedFd)> {
+    let open = |path: &str| {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC)
+            .open(path)
+            .map(OwnedFd::from)
+    };
+    Ok((open(upper)?, open(lower)?))
+}
+
+/// Record the upper layer's current contents as the boot state and enable
+/// overlay diffs. Called once the rootfs switch has fully completed.
+pub(crate) fn record_overlay_boot_state(upper: OwnedFd, lower: OwnedFd) {
+    let boot = match scan(&fd_path(&upper)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            kmsg(&format!(
+                "WARNING: fs_diff: failed to record overlay boot state: {}",
+                e
+            ));
+            return;
+        }
+    };
+    let _ = OVERLAY_LAYERS.set(OverlayLayers { upper, lower, boot });
+}
+
+/// The overlay upper layer, once the OCI rootfs switch has completed. It is
+/// no longer reachable by path after `pivot_root`.
+pub(crate) fn overlay_upper_fd() -> Option<BorrowedFd<'static>> {
+    OVERLAY_LAYERS.get().map(|layers| layers.upper.as_fd())
+}
+
+/// Diff the whole root filesystem: the overlay upper layer when the guest
+/// runs on an OCI rootfs, otherwise `/` against its baseline.
+pub(crate) fn diff_root_filesystem() -> FsDiffResponse {
+    match OVERLAY_LAYERS.get() {
+        Some(layers) => {
+            let result = diff_overlay(
+                &fd_path(&layers.upper),
+                &fd_path(&layers.lower),
+                &layers.boot,
+            );
+            respond("/", result.map(|changes| (changes, false)))
+        }
+        None => diff_against_baseline("/", Path::new("/")),
+    }
+}
+
+/// Diff the tree at `dir` against the baseline recorded for `root`,
+/// recording one first if there is none. `dir` is where the tree is
+/// walked (typically `/proc/self/fd/<n>` for a guard-resolved root);
+/// `root` is the path reported to the host.
+pub(crate) fn diff_against_baseline(root: &str, dir: &Path) -> FsDiffResponse {
+    let current = match scan(dir) {
+        Ok(manifest) => manifest,
+        Err(e) => return failure(format!("Failed to scan {}: {}", root, e)),
+    };
+
+    let mut baselines = BASELINES.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(baseline) = baselines.get(root) else {
+        baselines.insert(root.to_string(), current);
+        return respond(root, Ok((Vec::new(), true)));
+    };
+
+    let changes = diff_manifests(baseline, &current, dir);
+    respond(root, Ok((changes, false)))
+}
+
+/// Build a failed response carrying `error`.
+pub(crate) fn failure(error: String) -> FsDiffResponse {
+    FsDiffResponse {
+        success: false,
+        root: String::new(),
+        changes: Vec::new(),
+        baseline_created: false,
+        truncated: false,
+        error: Some(error),
+    }
+}
+
+fn respond(root: &str, result: io::Result<(Vec<FsChange>, bool)>) -> FsDiffResponse {
+    let (mut changes, baseline_created) = match result {
+        Ok(ok) => ok,
+        Err(e) => return failure(format!("Failed to diff {}: {}", root, e)),
+    };
+    for change in &mut changes {
+        change.path = display_path(root, &change.path);
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    let truncated = changes.len() > MAX_FS_DIFF_CHANGES;
+    changes.truncate(MAX_FS_DIFF_CHANGES);
+    FsDiffResponse {
+        success: true,
+        root: root.to_string(),
+        changes,
+        baseline_created,
+        truncated,
+        error: None,
+    }
+}
+
+/// Join a root-relative path onto the root the host asked about.
+fn display_path(root: &str, relative: &str) -> String {
+    format!("{}/{}", root.trim_end_matches('/'), relative)
+}
+
+fn fd_path(fd: &OwnedFd) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+}
+
+/// Walk `dir` without following symlinks or leaving its filesystem.
+fn scan(dir: &Path) -> io::Result<Manifest> {
+    // `metadata` (not `symlink_metadata`) so a `/proc/self/fd/<n>` magic
+    // link resolves to the directory it names.
+    let device = fs::metadata(dir)?.dev();
+    let mut manifest = Manifest::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let entries = match fs::read_dir(dir.join(&relative)) {
+            Ok(entries) => entries,
+            // Directories can vanish or be unreadable mid-walk; skip them
+            // rather than failing the whole diff.
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let child = relative.join(entry.file_name());
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.dev() != device {
+                continue;
+            }
+            if meta.is_dir() {
+                pending.push(child);
+            } else {
+                manifest.insert(child, EntrySig::from(&meta));
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+/// Compare two manifests of the same root; `dir` is where current content
+/// lives for hashing.
+fn diff_manifests(baseline: &Manifest, current: &Manifest, dir: &Path) -> Vec<FsChange> {
+    let mut changes = Vec::new();
+    for (relative, sig) in current {
+        let kind = match baseline.get(relative) {
+            None => FsChangeKind::Created,
+            Some(old) if old != sig => FsChangeKind::Modified,
+            Some(_) => continue,
+        };
+        changes.push(present_change(dir, relative, sig, kind));
+    }
+    for (relative, sig) in baseline {
+        if !current.contains_key(relative) {
+            changes.push(deleted_change(relative, sig.size));
+        }
+    }
+    changes
+}
+
+/// Diff an overlay's upper layer against the lower layer and boot state.
+fn diff_overlay(upper: &Path, lower: &Path, boot: &Manifest) -> io::Result<Vec<FsChange>> {
+    // Fail up front if the layers are gone; everything below is best-effort.
+    fs::metadata(upper)?;
+    let mut changes = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let Ok(entries) = fs::read_dir(upper.join(&relative)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let child = relative.join(entry.file_name());
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            seen.insert(child.clone());
+
+            if is_whiteout(&meta) {
+                report_lower_deleted(lower, &child, None, &mut changes);
+            } else if meta.is_dir() {
+                if is_opaque(&upper.join(&child)) {
+                    report_lower_deleted(lower, &child, Some(upper), &mut changes);
+                }
+                pending.push(child);
+            } else {
+                let sig = EntrySig::from(&meta);
+                let kind = match boot.get(&child) {
+                    Some(boot_sig) if *boot_sig == sig => continue,
+                    Some(_) => FsChangeKind::Modified,
+                    None if fs::symlink_metadata(lower.join(&child)).is_ok() => {
+                        FsChangeKind::Modified
+                    }
+                    None => FsChangeKind::Created,
+                };
+                changes.push(present_change(upper, &child, &sig, kind));
+            }
+        }
+    }
+
+    // Files staged during boot exist only in the upper layer, so deleting
+    // them leaves no whiteout behind.
+    for (relative, sig) in boot {
+        if !seen.contains(relative) && fs::symlink_metadata(lower.join(relative)).is_err() {
+            changes.push(deleted_change(relative, sig.size));
+        }
+    }
+    Ok(changes)
+}
+
+/// Report the lower-layer entries at `relative` as deleted. With
+/// `shadowed_by`, only entries absent from that (upper) layer count —
+/// used for opaque directories, whose upper copies are reported by the
+/// main walk.
+fn report_lower_deleted(
+    lower: &Path,
+    relative: &Path,
+    shadowed_by: Option<&Path>,
+    changes: &mut Vec<FsChange>,
+) {
+    let Ok(meta) = fs::symlink_metadata(lower.join(relative)) else {
+        return;
+    };
+    if !meta.is_dir() {
+        changes.push(deleted_change(relative, meta.size()));
+        return;
+    }
+    let Ok(manifest) = scan(&lower.join(relative)) else {
+        return;
+    };
+    for (inner, sig) in manifest {
+        let path = relative.join(inner);
+        if let Some(upper) = shadowed_by {
+            if fs::symlink_metadata(upper.join(&path)).is_ok() {
+                continue;
+            }
+        }
+        changes.push(deleted_change(&path, sig.size));
+    }
+}
+
+/// overlayfs marks a deletion with a 0:0 character device.
+fn is_whiteout(meta: &Metadata) -> bool {
+    meta.file_type().is_char_device() && meta.rdev() == 0
+}
+
+fn is_opaque(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut value = [0u8; 1];
+    let len = unsafe {
+        libc::lgetxattr(
+            c_path.as_ptr(),
+            OVERLAY_OPAQUE_XATTR.as_ptr() as *const libc::c_char,
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+        )
+    };
+    len == 1 && value[0] == b'y'
+}
+
+fn present_change(dir: &Path, relative: &Path, sig: &EntrySig, kind: FsChangeKind) -> FsChange {
+    let is_regular = sig.mode & libc::S_IFMT == libc::S_IFREG;
+    FsChange {
+        path: relative.to_string_lossy().into_owned(),
+        kind,
+        size: sig.size,
+        sha256: is_regular
+            .then(|| sha256_file(&dir.join(relative)))
+            .flatten(),
+    }
+}
+
+fn deleted_change(relative: &Path, size: u64) -> FsChange {
+    FsChange {
+        path: relative.to_string_lossy().into_owned(),
+        kind: FsChangeKind::Deleted,
+        size,
+        sha256: None,
+    }
+}
+
+/// Hex SHA-256 of a regular file, refusing to follow a symlink swapped in
+/// since the walk.
+fn sha256_file(path: &Path) -> Option<String> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(path)
+        .ok()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(changes: &[FsChange]) -> Vec<(String, FsChangeKind)> {
+        let mut out: Vec<_> = changes.iter().map(|c| (c.path.clone(), c.kind)).collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out
+    }
+
+    #[test]
+    fn test_diff_manifests_reports_created_modified_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("keep"), b"same").unwrap();
+        fs::write(dir.path().join("edit"), b"old").unwrap();
+        fs::write(dir.path().join("gone"), b"bye").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let baseline = scan(dir.path()).unwrap();
+
+        fs::write(dir.path().join("edit"), b"newer content").unwrap();
+        fs::remove_file(dir.path().join("gone")).unwrap();
+        fs::write(dir.path().join("sub/new"), b"hello").unwrap();
+        let current = scan(dir.path()).unwrap();
+
+        let changes = diff_manifests(&baseline, &current, dir.path());
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                ("edit".to_string(), FsChangeKind::Modified),
+                ("gone".to_string(), FsChangeKind::Deleted),
+                ("sub/new".to_string(), FsChangeKind::Created),
+            ]
+        );
+
+        let created = changes.iter().find(|c| c.path == "sub/new").unwrap();
+        assert_eq!(created.size, 5);
+        assert_eq!(
+            created.sha256.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        let deleted = changes.iter().find(|c| c.path == "gone").unwrap();
+        assert_eq!(deleted.size, 3);
+        assert!(deleted.sha256.is_none());
+    }
+
+    #[test]
+    fn test_diff_overlay_uses_lower_and_boot_state() {
+        let upper = tempfile::tempdir().unwrap();
+        let lower = tempfile::tempdir().unwrap();
+        fs::write(lower.path().join("image-file"), b"image").unwrap();
+        fs::write(lower.path().join("untouched"), b"image").unwrap();
+        fs::write(upper.path().join("staged-at-boot"), b"tool").unwrap();
+        fs::write(upper.path().join("deleted-after-boot"), b"tool").unwrap();
+        let boot = scan(upper.path()).unwrap();
+
+        fs::write(upper.path().join("image-file"), b"edited").unwrap();
+        fs::write(upper.path().join("fresh"), b"new").unwrap();
+        fs::remove_file(upper.path().join("deleted-after-boot")).unwrap();
+
+        let changes = diff_overlay(upper.path(), lower.path(), &boot).unwrap();
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                ("deleted-after-boot".to_string(), FsChangeKind::Deleted),
+                ("fresh".to_string(), FsChangeKind::Created),
+                ("image-file".to_string(), FsChangeKind::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_respond_prefixes_root_and_truncates() {
+        let changes = (0..MAX_FS_DIFF_CHANGES + 1)
+            .map(|i| deleted_change(Path::new(&format!("f{:05}", i)), 0))
+            .collect();
+        let response = respond("/workspace/", Ok((changes, false)));
+        assert!(response.success);
+        assert!(response.truncated);
+        assert_eq!(response.changes.len(), MAX_FS_DIFF_CHANGES);
+        assert_eq!(response.changes[0].path, "/workspace/f00000");
+
+        let response = respond(
+            "/",
+            Ok((vec![deleted_change(Path::new("etc/x"), 0)], false)),
+        );
+        assert_eq!(response.changes[0].path, "/etc/x");
+    }
+}
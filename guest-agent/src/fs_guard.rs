@@ -19,8 +19,9 @@
 //!
 //! This module gates every path through `openat2(O_PATH)` with
 //! `RESOLVE_IN_ROOT | RESOLVE_NO_SYMLINKS` against an `O_PATH |
-//! O_DIRECTORY` fd cached for each entry in [`ALLOWED_WRITE_ROOTS`] /
-//! [`ALLOWED_READ_ROOTS`]. The kernel walks the path, refuses to cross
+//! O_DIRECTORY` fd cached for each entry in [`ALLOWED_WRITE_ROOTS`] (plus
+//! any `voidbox.write_roots=` the host booted with) / [`ALLOWED_READ_ROOTS`].
+//! The kernel walks the path, refuses to cross
 //! any symlink, and returns an fd anchored *inside* the allowed root.
 //! Callers use the resulting fd for the subsequent op (`write`, `read`,
 //! `fchown`, `fchmod`, `mkdirat`); they never re-open the path by
@@ -45,6 +46,8 @@ use nix::errno::Errno;
 use nix::fcntl::{openat2, OFlag, OpenHow, ResolveFlag};
 use nix::sys::stat::Mode;
 
+use void_box_protocol::{validate_write_root, WRITE_ROOTS_CMDLINE_KEY};
+
 use crate::{kmsg, kmsg_emerg, ALLOWED_WRITE_ROOTS};
 
 /// One allowlisted root and its cached `O_PATH` directory fd.
@@ -86,7 +89,8 @@ impl std::fmt::Display for FsGuardError {
     }
 }
 
-/// Lazily open every entry in `ALLOWED_WRITE_ROOTS`, probe `openat2`
+/// Lazily open every entry in `ALLOWED_WRITE_ROOTS` and the extra roots
+/// from `voidbox.write_roots=` on the kernel cmdline, probe `openat2`
 /// availability, and stash the fds in a process-lifetime static. Safe
 /// to call multiple times; subsequent calls are O(1) once init has
 /// succeeded.
@@ -111,7 +115,15 @@ pub(crate) fn init() {
         return;
     }
 
-    let write = match open_root_table(&ALLOWED_WRITE_ROOTS, "write") {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    let mut roots = ALLOWED_WRITE_ROOTS.to_vec();
+    for extra in parse_extra_write_roots(&cmdline) {
+        if !roots.contains(&extra.as_str()) {
+            // Held for the process lifetime, like the root fds.
+            roots.push(Box::leak(extra.into_boxed_str()));
+        }
+    }
+    let write = match open_root_table(&roots, "write") {
         Ok(v) => v,
         Err(msg) => fail_startup(&msg),
     };
@@ -144,7 +156,36 @@ pub(crate) fn init_read_roots(roots: &'static [&'static str]) {
     kmsg("fs_guard: cached root fds for read allowlist");
 }
 
-fn open_root_table(roots: &'static [&'static str], label: &str) -> Result<Vec<RootEntry>, String> {
+/// The roots `voidbox.write_roots=` adds. Invalid entries are dropped
+/// with a warning rather than failing the boot: they only ever narrow
+/// what the host asked for.
+fn parse_extra_write_roots(cmdline: &str) -> Vec<String> {
+    let Some(value) = cmdline
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix(WRITE_ROOTS_CMDLINE_KEY))
+    else {
+        return Vec::new();
+    };
+    value
+        .split(',')
+        .filter(|root| !root.is_empty())
+        .filter(|root| match validate_write_root(root) {
+            Ok(()) => true,
+            Err(e) => {
+                kmsg(&format!("fs_guard: ignoring {e}"));
+                false
+            }
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Paths of the write roots, for error messages.
+pub(crate) fn write_root_paths() -> Vec<&'static str> {
+    write_roots().iter().map(|entry| entry.path).collect()
+}
+
+fn open_root_table(roots: &[&'static str], label: &str) -> Result<Vec<RootEntry>, String> {
     let mut out = Vec::with_capacity(roots.len());
     for root in roots {
         // Make sure the directory exists; some allowlisted roots
@@ -766,4 +807,19 @@ mod tests {
             "resolved fd should point at a directory"
         );
     }
+
+    #[test]
+    fn extra_write_roots_come_from_the_cmdline() {
+        assert_eq!(
+            parse_extra_write_roots(
+                "console=ttyS0 voidbox.write_roots=/opt,/etc/systemd/system panic=1"
+            ),
+            vec!["/opt", "/etc/systemd/system"]
+        );
+        assert_eq!(
+            parse_extra_write_roots("voidbox.write_roots=/,/proc/self,/srv/../etc,/srv"),
+            vec!["/srv"]
+        );
+        assert!(parse_extra_write_roots("console=ttyS0").is_empty());
+    }
 }
@@ -9,10 +9,28 @@
 #[cfg(not(target_os = "linux"))]
 compile_error!("guest-agent is Linux-only (runs as PID 1 inside the micro-VM)");
 
+mod attach;
+mod boot;
+mod conn;
+mod console;
+mod control_port;
+mod export;
+mod fs_diff;
 mod fs_guard;
 mod pty;
-
+mod rosetta;
+mod shutdown;
+mod signal;
+mod stdin;
+mod sysinfo;
+mod test_report;
+mod watchdog;
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::RawFd;
 use std::path::Path;
@@ -25,10 +43,19 @@ use subtle::ConstantTimeEq;
 
 // Import shared wire-format types from the protocol crate (single source of truth).
 use void_box_protocol::{
-    ExecOutputChunk, ExecRequest, ExecResponse, FileStatRequest, FileStatResponse, MessageType,
-    MkdirPRequest, MkdirPResponse, ProcessMetrics, PtyOpenRequest, ReadFileRequest,
-    ReadFileResponse, SystemMetrics, TelemetryBatch, TelemetrySubscribeRequest, WriteFileRequest,
-    WriteFileResponse, MAX_MESSAGE_SIZE,
+    AttachExecRequest, BootStatus, ChmodRequest, ChmodResponse, CreateUserRequest,
+    CreateUserResponse, DiskUsage, EnterReadOnlyResponse, ExecDenial, ExecOutputChunk, ExecPolicy,
+    ExecRequest, ExecResponse, ExecStdinChunk, ExecStdinClose, ExecStdinResponse, ExecUser,
+    ExportWorkspaceRequest, ExportWorkspaceResponse, FileHash, FileStatRequest, FileStatResponse,
+    FsDiffRequest, FsDiffResponse, HashFilesRequest, HashFilesResponse, MessageType, MkdirPRequest,
+    MkdirPResponse, ProcessMetrics, PtyOpenRequest, ReadFileRequest, ReadFileResponse,
+    ReadLinkRequest, ReadLinkResponse, SetExecPolicyRequest, SetExecPolicyResponse,
+    ShutdownRequest, SignalExecRequest, SymlinkRequest, SymlinkResponse, SyncClockRequest,
+    SyncClockResponse, SystemMetrics, TelemetryBatch, TelemetryControlRequest,
+    TelemetryControlResponse, TelemetryProcessFilter, TelemetrySubscribeRequest, TopProcesses,
+    WriteFileChunkRequest, WriteFileChunkResponse, WriteFileFinalizeRequest, WriteFileRequest,
+    WriteFileResponse, BOOT_STATUS_MARKER, GUEST_PATH, MAX_HASH_FILES, MAX_PROCESS_CMDLINE,
+    OVERLAY_UPPER_DISK, SANDBOX_UID, TEST_REPORT_ENV,
 };
 
 /// vsock port we listen on
@@ -38,7 +65,9 @@ const LISTEN_PORT: u32 = 1234;
 #[allow(dead_code)]
 const HOST_CID: u32 = 2;
 
-const ALLOWED_WRITE_ROOTS: [&str; 3] = ["/workspace", "/home", "/etc/voidbox"];
+// Write roots every guest has. The host adds more with
+// `voidbox.write_roots=` on the kernel cmdline; see `fs_guard::init`.
+const ALLOWED_WRITE_ROOTS: [&str; 3] = void_box_protocol::DEFAULT_WRITE_ROOTS;
 
 // Mirrors `ALLOWED_WRITE_ROOTS` for the host-driven `ReadFile` RPC.
 // The current host call sites of `send_read_file` all read paths under
@@ -82,6 +111,22 @@ static OCI_SETUP_STATUS: AtomicU8 = AtomicU8::new(OCI_NOT_RUN);
 /// Stores the last OCI setup error detail (e.g., mount failure reasons).
 static OCI_SETUP_ERROR_DETAIL: std::sync::OnceLock<String> = std::sync::OnceLock::new();
 static NETWORK_DENY_LIST_APPLIED: AtomicBool = AtomicBool::new(false);
+/// Set by EnterReadOnly: file-write RPCs are refused until the VM stops.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Users added by CreateUser, by name.
+static GUEST_USERS: Mutex<std::collections::BTreeMap<String, GuestUser>> =
+    Mutex::new(std::collections::BTreeMap::new());
+/// uid (and gid) of the first user CreateUser adds.
+const FIRST_CREATED_UID: u32 = SANDBOX_UID + 1;
+
+/// Credentials an exec runs with.
+#[derive(Debug, Clone)]
+struct GuestUser {
+    uid: u32,
+    gid: u32,
+    home: String,
+}
 
 /// Serializes writes to the host vsock fd so concurrent writers never
 /// interleave bytes on the wire.
@@ -104,6 +149,11 @@ const NETWORK_DENY_LIST_PATH: &str = "/etc/voidbox/network_deny_list.json";
 /// needs write access to `/etc`. Kept in sync with `GUEST_HOSTS_PATH` host-side.
 const PROXY_HOSTS_CONFIG_PATH: &str = "/etc/voidbox/hosts";
 
+/// Suffix of the hidden sibling a chunked transfer stages into. Staging next
+/// to the destination keeps the final `renameat` on one filesystem (so the
+/// commit is atomic) and inside the same `fs_guard` write root.
+const PARTIAL_WRITE_SUFFIX: &str = ".voidbox-partial";
+
 fn oci_status_str(code: u8) -> &'static str {
     match code {
         OCI_NOT_RUN => "not-run",
@@ -149,7 +199,15 @@ fn trigger_oci_rootfs_setup_async() {
         std::thread::spawn(|| {
             kmsg("OCI setup: async rootfs setup thread started");
             setup_oci_rootfs();
-            let status = oci_status_str(OCI_SETUP_STATUS.load(Ordering::Acquire));
+            let code = OCI_SETUP_STATUS.load(Ordering::Acquire);
+            let status = oci_status_str(code);
+            if !matches!(code, OCI_OK | OCI_OK_SWITCH_ROOT) {
+                let detail = OCI_SETUP_ERROR_DETAIL.get().map(String::as_str);
+                boot_status(
+                    BootStatus::OciRootfsFailed,
+                    &format!("{} {}", status, detail.unwrap_or_default()),
+                );
+            }
             kmsg(&format!(
                 "OCI setup: async rootfs setup thread finished status={}",
                 status
@@ -220,8 +278,10 @@ impl Default for ResourceLimits {
 /// Loaded resource limits (parsed from /etc/voidbox/resource_limits.json or defaults).
 pub(crate) static RESOURCE_LIMITS: std::sync::OnceLock<ResourceLimits> = std::sync::OnceLock::new();
 
-/// Loaded command allowlist (parsed from /etc/voidbox/allowed_commands.json or empty = allow all).
-static COMMAND_ALLOWLIST: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+/// Exec policy: seeded from /etc/voidbox/allowed_commands.json, replaced at
+/// runtime by SetExecPolicy. Empty = allow all.
+static EXEC_POLICY: std::sync::RwLock<ExecPolicy> =
+    std::sync::RwLock::new(ExecPolicy { rules: Vec::new() });
 
 fn apply_network_deny_list() {
     if NETWORK_DENY_LIST_APPLIED.swap(true, Ordering::AcqRel) {
@@ -321,6 +381,17 @@ pub(crate) fn kmsg_emerg(msg: &str) {
     }
 }
 
+/// Report a boot failure (or the agent's start) to the host on the serial
+/// console; see [`BOOT_STATUS_MARKER`].
+pub(crate) fn boot_status(status: BootStatus, detail: &str) {
+    let line = format!("{} {} {}", BOOT_STATUS_MARKER, status.as_str(), detail);
+    kmsg_emerg(line.trim_end());
+}
+
+/// Set once a wrong session secret has been reported, so a host retrying
+/// its handshake does not flood the console.
+static SECRET_MISMATCH_REPORTED: AtomicBool = AtomicBool::new(false);
+
 /// Constant-time equality check between a peer-supplied secret slice and the
 /// expected 32-byte session secret.
 ///
@@ -362,6 +433,30 @@ fn parse_session_secret() -> Option<[u8; 32]> {
 /// Set the guest system clock from the `voidbox.clock=<epoch_secs>` kernel
 /// cmdline parameter.  Without this the guest starts at 1970-01-01 and TLS
 /// certificate validation fails.
+/// Set the system clock to the host's, reporting the clock it replaced.
+fn sync_clock(request: &SyncClockRequest) -> SyncClockResponse {
+    let mut before = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut before) };
+    let mut response = SyncClockResponse {
+        guest_unix_secs: before.tv_sec,
+        guest_nanos: before.tv_nsec as u32,
+        error: None,
+    };
+    let ts = libc::timespec {
+        tv_sec: request.unix_secs,
+        tv_nsec: request.nanos as libc::c_long,
+    };
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) } != 0 {
+        let error = format!("clock_settime failed: {}", std::io::Error::last_os_error());
+        kmsg(&format!("WARNING: {}", error));
+        response.error = Some(error);
+    }
+    response
+}
+
 fn sync_clock_from_cmdline() {
     let cmdline = match std::fs::read_to_string("/proc/cmdline") {
         Ok(c) => c,
@@ -396,16 +491,23 @@ fn main() {
     if std::process::id() == 1 {
         init_system();
     }
+    boot_status(BootStatus::AgentStarted, env!("CARGO_PKG_VERSION"));
 
     // Set the wall clock before anything that needs accurate time (e.g. TLS).
     if std::process::id() == 1 {
         sync_clock_from_cmdline();
     }
 
+    // The debug shell comes up before anything that can hang the boot.
+    console::spawn_from_cmdline();
+    watchdog::spawn_from_cmdline();
+
     // Load kernel modules needed for vsock (virtio_mmio + vsock transport)
     // and virtio-net (for SLIRP networking). Must happen after init_system()
     // so filesystems are mounted, but before network setup which needs the drivers.
+    let modules_started = boot::boottime();
     load_kernel_modules();
+    boot::report_phase("module-load", modules_started);
 
     // Mount shared directories (virtiofs or 9p) specified via kernel cmdline.
     // Must happen after module loading (9p needs 9pnet_virtio.ko).
@@ -413,6 +515,14 @@ fn main() {
     // setup_oci_rootfs() which mounts directly inside the overlay newroot.
     mount_shared_dirs();
 
+    // Mount data disks (scratch ext4 images) announced on the cmdline; like
+    // shared dirs, deferred into the overlay newroot in OCI rootfs mode.
+    mount_data_disks();
+
+    // Register the host's Rosetta translator for x86_64 binaries, if shared.
+    // binfmt_misc opens it now, so this also covers an OCI rootfs.
+    rosetta::setup_from_cmdline();
+
     // Set up networking after modules are loaded (virtio_net.ko creates eth0).
     // Skip when host did not configure a net virtio-mmio device.
     if std::process::id() == 1 {
@@ -441,6 +551,7 @@ fn main() {
         }
         None => {
             kmsg("WARNING: No session secret found in kernel cmdline -- all connections will be rejected");
+            boot_status(BootStatus::SecretMissing, "");
         }
     }
 
@@ -480,7 +591,8 @@ fn main() {
                     "Loaded command allowlist: {} commands",
                     allowlist.len()
                 ));
-                let _ = COMMAND_ALLOWLIST.set(allowlist);
+                *EXEC_POLICY.write().unwrap_or_else(|e| e.into_inner()) =
+                    ExecPolicy::from_allowlist(allowlist);
             }
             Err(e) => {
                 kmsg(&format!(
@@ -494,10 +606,16 @@ fn main() {
         }
     }
 
+    // The host may run the control channel over a virtio-console port
+    // instead; PTY sessions still arrive over vsock.
+    control_port::spawn_from_cmdline();
+
     // Create vsock listener, retrying since module loading + device probe takes time
+    let listen_started = boot::boottime();
     let listener_fd = {
+        let (interval, attempts) = boot::poll_schedule(std::time::Duration::from_millis(200), 30);
         let mut fd = -1i32;
-        for attempt in 0..30 {
+        for attempt in 0..attempts {
             fd = create_vsock_listener(LISTEN_PORT);
             if fd >= 0 {
                 kmsg(&format!(
@@ -508,17 +626,22 @@ fn main() {
             }
             let errno = std::io::Error::last_os_error();
             kmsg(&format!(
-                "vsock listener attempt {} failed: {} retrying in 200ms...",
+                "vsock listener attempt {} failed: {} retrying in {:?}...",
                 attempt + 1,
-                errno
+                errno,
+                interval
             ));
-            std::thread::sleep(std::time::Duration::from_millis(200));
+            std::thread::sleep(interval);
         }
         fd
     };
 
     if listener_fd < 0 {
         kmsg("Failed to create vsock listener after retries, entering idle loop (PID 1 must not exit)");
+        boot_status(
+            BootStatus::VsockListenFailed,
+            &std::io::Error::last_os_error().to_string(),
+        );
         // PID 1 must never exit or the kernel panics
         loop {
             std::thread::sleep(std::time::Duration::from_secs(3600));
@@ -526,6 +649,7 @@ fn main() {
     }
 
     kmsg(&format!("Listening on vsock port {}", LISTEN_PORT));
+    boot::report_phase("vsock-listen", listen_started);
 
     // Accept connections and handle requests (multi-threaded for concurrent telemetry + exec)
     loop {
@@ -541,6 +665,9 @@ fn main() {
                 if let Err(e) = handle_connection(client_fd) {
                     eprintln!("Connection error: {}", e);
                 }
+                // Commands started on this connection keep running; they
+                // must stop writing to its descriptor before it is reused.
+                attach::detach(client_fd);
                 unsafe {
                     libc::close(client_fd);
                 }
@@ -554,7 +681,7 @@ fn main() {
 /// Initialize the system when running as init (PID 1)
 fn init_system() {
     // Set PATH early - as PID 1, we inherit no environment
-    std::env::set_var("PATH", "/usr/local/bin:/usr/bin:/bin:/sbin:/usr/sbin");
+    std::env::set_var("PATH", GUEST_PATH);
     std::env::set_var("HOME", "/root");
     std::env::set_var("TERM", "linux");
 
@@ -693,6 +820,8 @@ fn load_kernel_modules() {
     }
 
     let virtio_mmio_params = virtio_mmio_params_from_cmdline();
+    let fast_boot = boot::fast_boot();
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
 
     // Load order matters: dependencies must be loaded first.
     // virtio_mmio needs explicit device= params since the cmdline params may not
@@ -710,6 +839,15 @@ fn load_kernel_modules() {
         ("failover.ko", String::new(), false),
         ("net_failover.ko", String::new(), false),
         ("virtio_net.ko", String::new(), false),
+        // Block device driver (OCI rootfs disk and data disks on KVM)
+        ("virtio_blk.ko", String::new(), false),
+        // Entropy from the host, so the CRNG is seeded before the first TLS
+        // handshake (optional — built in on slim kernels)
+        ("virtio-rng.ko", String::new(), false),
+        // Memory balloon so the host can reclaim idle guest pages (optional)
+        ("virtio_balloon.ko", String::new(), false),
+        // Control channel over a serial port (KvmBackend::with_serial_control)
+        ("virtio_console.ko", String::new(), false),
         // virtiofs module (for macOS/VZ host directory sharing — OCI rootfs)
         ("virtiofs.ko", String::new(), false),
         // 9p filesystem modules (for host directory sharing — optional, missing on macOS).
@@ -723,13 +861,20 @@ fn load_kernel_modules() {
     ];
 
     for (module_name, params, required) in modules {
+        // Fast boot: no finit_module for drivers of devices not attached.
+        if fast_boot && !required && !boot::fast_boot_wants_module(module_name, &cmdline) {
+            continue;
+        }
         let path = format!("/lib/modules/{}", module_name);
         match load_module_file(&path, &params) {
             Ok(()) => kmsg(&format!(
                 "Loaded module: {} (params='{}')",
                 module_name, params
             )),
-            Err(e) if required => kmsg(&format!("WARNING: failed to load {}: {}", module_name, e)),
+            Err(e) if required => {
+                kmsg(&format!("WARNING: failed to load {}: {}", module_name, e));
+                boot_status(BootStatus::ModuleFailed, &format!("{}: {}", module_name, e));
+            }
             Err(e) => kmsg(&format!(
                 "Optional module {} not loaded: {}",
                 module_name, e
@@ -1281,6 +1426,91 @@ fn mount_shared_dirs() {
     }
 }
 
+/// Parse data disk entries from a given kernel cmdline string.
+///
+/// Each `voidbox.disk<N>=<dev>:<guest_path>:<ro|rw>` parameter produces a
+/// `(dev, guest_path, read_only)` tuple. When the mode suffix is omitted the
+/// disk is mounted read-only.
+fn parse_data_disk_entries_from(cmdline: &str) -> Vec<(String, String, bool)> {
+    let mut disks = Vec::new();
+    for param in cmdline.split_whitespace() {
+        let Some(rest) = param.strip_prefix("voidbox.disk") else {
+            continue;
+        };
+        let Some((_, value)) = rest.split_once('=') else {
+            continue;
+        };
+        let parts: Vec<&str> = value.splitn(3, ':').collect();
+        if parts.len() >= 2 {
+            let read_only = parts.get(2).map(|&m| m != "rw").unwrap_or(true);
+            disks.push((parts[0].to_string(), parts[1].to_string(), read_only));
+        }
+    }
+    disks
+}
+
+/// Mount the ext4 filesystem on block device `dev` at `guest_path`. A
+/// writable disk's root is handed to the sandbox user.
+fn mount_data_disk(dev: &str, guest_path: &str, read_only: bool) -> Result<(), String> {
+    let dev_path = std::path::Path::new(dev);
+    let (interval, attempts) = boot::poll_schedule(std::time::Duration::from_millis(100), 40);
+    for _ in 0..attempts {
+        if dev_path.exists() {
+            break;
+        }
+        std::thread::sleep(interval);
+    }
+    if !dev_path.exists() {
+        return Err(format!("device not found: {}", dev));
+    }
+
+    std::fs::create_dir_all(guest_path).map_err(|e| e.to_string())?;
+    let dev_c = std::ffi::CString::new(dev).map_err(|e| e.to_string())?;
+    let path_c = std::ffi::CString::new(guest_path).map_err(|e| e.to_string())?;
+    let ext4_c = std::ffi::CString::new("ext4").unwrap();
+    let flags = if read_only { libc::MS_RDONLY } else { 0 };
+    let ret = unsafe {
+        libc::mount(
+            dev_c.as_ptr(),
+            path_c.as_ptr(),
+            ext4_c.as_ptr(),
+            flags as libc::c_ulong,
+            std::ptr::null(),
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    if !read_only && unsafe { libc::chown(path_c.as_ptr(), 1000, 1000) } != 0 {
+        kmsg(&format!(
+            "WARNING: chown {} to 1000:1000 failed: {}",
+            guest_path,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// Mount data disks specified via `voidbox.disk<N>` cmdline parameters.
+///
+/// Skipped in OCI rootfs mode: `setup_oci_rootfs()` mounts them inside the
+/// overlay newroot so they survive pivot_root.
+fn mount_data_disks() {
+    if oci_rootfs_requested() {
+        return;
+    }
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    for (dev, guest_path, read_only) in parse_data_disk_entries_from(&cmdline) {
+        match mount_data_disk(&dev, &guest_path, read_only) {
+            Ok(()) => kmsg(&format!("Mounted data disk {} at {}", dev, guest_path)),
+            Err(e) => kmsg(&format!(
+                "WARNING: failed to mount data disk {} at {}: {}",
+                dev, guest_path, e
+            )),
+        }
+    }
+}
+
 /// Set up an OCI base image rootfs via overlayfs + pivot_root.
 ///
 /// When the host sets `voidbox.oci_rootfs=<path>` on the kernel cmdline,
@@ -1482,6 +1712,18 @@ fn setup_oci_rootfs() {
 
     kmsg("Overlayfs mounted, preparing pivot_root...");
 
+    // Keep both layers reachable for FsDiff after pivot_root detaches them.
+    let overlay_layers = match fs_diff::open_overlay_layers(upper, &lowerdir) {
+        Ok(layers) => Some(layers),
+        Err(e) => {
+            kmsg(&format!(
+                "WARNING: failed to open overlay layers for fs diff: {}",
+                e
+            ));
+            None
+        }
+    };
+
     // Create mount points in the new root
     for dir in [
         "/proc",
@@ -1586,6 +1828,23 @@ fn setup_oci_rootfs() {
         }
     }
 
+    // Data disks, mounted inside newroot for the same reason as shared
+    // dirs. /dev has been MS_MOVEd, so the device nodes live under newroot.
+    for (dev, guest_path, read_only) in parse_data_disk_entries_from(&cmdline) {
+        let dev_path = format!("{}{}", newroot, dev);
+        let dst_path = format!("{}{}", newroot, guest_path);
+        match mount_data_disk(&dev_path, &dst_path, read_only) {
+            Ok(()) => kmsg(&format!(
+                "Mounted data disk {} at {} (inside newroot)",
+                dev, dst_path
+            )),
+            Err(e) => kmsg(&format!(
+                "WARNING: failed to mount data disk {} at {}: {}",
+                dev, dst_path, e
+            )),
+        }
+    }
+
     // Switch to overlay root via pivot_root.
     // pivot_root requires mount propagation to be private.
     unsafe {
@@ -1752,6 +2011,10 @@ fn setup_oci_rootfs() {
         ensure_mount_writable(guest_path, true);
     }
 
+    if let Some((upper_fd, lower_fd)) = overlay_layers {
+        fs_diff::record_overlay_boot_state(upper_fd, lower_fd);
+    }
+
     kmsg("OCI rootfs pivot_root complete — running on overlay filesystem");
     eprintln!("OCI rootfs pivot_root complete");
     OCI_SETUP_STATUS.store(OCI_OK, Ordering::Release);
@@ -1861,17 +2124,18 @@ fn mount_oci_block_lowerdir(dev: &str) -> Result<String, String> {
 
 /// Set up network interface.
 ///
-/// Tries DHCP first (for VZ NAT on macOS), falls back to static SLIRP
-/// addressing (for KVM/SLIRP on Linux).
+/// Tries DHCP first (for VZ NAT on macOS and KVM TAP devices on a bridged
+/// network), falls back to static SLIRP addressing (for KVM/SLIRP on Linux).
 fn setup_network() {
     kmsg("Setting up network...");
 
-    for i in 0..300 {
+    let (interval, attempts) = boot::poll_schedule(std::time::Duration::from_millis(200), 300);
+    for i in 0..attempts {
         if std::path::Path::new("/sys/class/net/eth0").exists() {
             kmsg(&format!("eth0 detected after {} attempts", i + 1));
             break;
         }
-        std::thread::sleep(std::time::Duration::from_millis(200));
+        std::thread::sleep(interval);
     }
 
     if !std::path::Path::new("/sys/class/net/eth0").exists() {
@@ -1894,8 +2158,9 @@ fn setup_network() {
     let dhcp_ok = dhcp_result.map(|o| o.status.success()).unwrap_or(false);
 
     if dhcp_ok {
-        kmsg("Network configured via DHCP (VZ NAT)");
-        ensure_resolv_conf("nameserver 8.8.8.8\n");
+        kmsg("Network configured via DHCP");
+        let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+        ensure_resolv_conf(&resolv_conf_for(&dns_servers_from_cmdline(&cmdline)));
         return;
     }
 
@@ -1923,6 +2188,10 @@ fn setup_network() {
 
 fn network_enabled_from_cmdline() -> bool {
     let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    network_enabled_in(&cmdline)
+}
+
+pub(crate) fn network_enabled_in(cmdline: &str) -> bool {
     for t in cmdline.split_whitespace() {
         // KVM: virtio_mmio device 10 is the network device
         if t == "virtio_mmio.device=512@0xd0000000:10" {
@@ -1936,6 +2205,28 @@ fn network_enabled_from_cmdline() -> bool {
     false
 }
 
+/// Resolvers the host configured with `voidbox.dns=<ip>,<ip>`. Set by VZ
+/// and by KVM in TAP mode; under SLIRP the guest always uses the SLIRP DNS
+/// server (10.0.2.3), which forwards to the configured resolvers itself.
+fn dns_servers_from_cmdline(cmdline: &str) -> Vec<std::net::Ipv4Addr> {
+    cmdline
+        .split_whitespace()
+        .find_map(|param| param.strip_prefix("voidbox.dns="))
+        .map(|list| list.split(',').filter_map(|ip| ip.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// `resolv.conf` for `servers`, falling back to 8.8.8.8.
+fn resolv_conf_for(servers: &[std::net::Ipv4Addr]) -> String {
+    if servers.is_empty() {
+        return "nameserver 8.8.8.8\n".to_string();
+    }
+    servers
+        .iter()
+        .map(|ip| format!("nameserver {ip}\n"))
+        .collect()
+}
+
 fn ensure_resolv_conf(contents: &str) {
     let _ = std::fs::create_dir_all("/etc");
     if let Ok(meta) = std::fs::symlink_metadata("/etc/resolv.conf") {
@@ -2044,73 +2335,55 @@ fn create_vsock_listener(port: u32) -> RawFd {
 /// Handle a connection – process messages in a loop until the peer disconnects
 /// or a terminal message (Shutdown) is received.
 fn handle_connection(fd: RawFd) -> Result<(), String> {
+    let mut reader = conn::FdReader(fd);
     loop {
-        // Read message header (4 bytes length + 1 byte type)
-        let mut header = [0u8; 5];
-        match read_exact(fd, &mut header) {
-            Ok(()) => {}
-            Err(_) => {
-                // Connection closed by peer (normal)
-                return Ok(());
+        let frame = match conn::read_request(&mut reader, AUTHENTICATED.with(|a| a.get()))? {
+            conn::Incoming::Closed => return Ok(()),
+            conn::Incoming::Unknown(msg_type) => {
+                eprintln!("Unknown message type: {}", msg_type);
+                continue;
             }
-        }
-
-        let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
-        let msg_type = header[4];
-
-        // Reject oversized messages before allocating
-        if length > MAX_MESSAGE_SIZE {
-            eprintln!(
-                "Rejecting oversized message: {} bytes (max {})",
-                length, MAX_MESSAGE_SIZE
-            );
-            return Err(format!(
-                "Payload too large: {} bytes (max {})",
-                length, MAX_MESSAGE_SIZE
-            ));
-        }
-
-        // Read payload
-        let mut payload = vec![0u8; length];
-        if length > 0 {
-            read_exact(fd, &mut payload)?;
-        }
-
-        if msg_type != MessageType::Ping as u8 && !AUTHENTICATED.with(|a| a.get()) {
-            eprintln!("Rejecting unauthenticated message type {}", msg_type);
-            return Err(
-                "Connection not authenticated -- send Ping with session secret first".into(),
-            );
-        }
-
-        let Ok(message_type) = MessageType::try_from(msg_type) else {
-            eprintln!("Unknown message type: {}", msg_type);
-            continue;
-        };
-
-        // Ping is the pre-multiplex handshake: its payload is the session
-        // secret + version + flags with no request_id prefix. Everything
-        // else speaks the multiplex frame: payload = [request_id:4 LE][body].
-        let (request_id, body) = if message_type == MessageType::Ping {
-            (0u32, payload.as_slice())
-        } else if payload.len() < 4 {
-            return Err(format!(
-                "multiplex payload too short for {:?}: {} bytes",
-                message_type,
-                payload.len()
-            ));
-        } else {
-            let id = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
-            (id, &payload[4..])
+            conn::Incoming::Request(frame) => frame,
         };
+        let (message_type, request_id, body) = (frame.message_type, frame.request_id, frame.body());
 
         match message_type {
             MessageType::ExecRequest => {
                 let request: ExecRequest = serde_json::from_slice(body)
                     .map_err(|e| format!("Failed to parse request: {}", e))?;
 
-                let response = execute_command(fd, request_id, &request);
-                send_mux_response(fd, MessageType::ExecResponse, request_id, &response)?;
+                // Like telemetry, a command can run for as long as the agent
+                // it hosts. Running it inline would leave heartbeats and
+                // every other RPC on the shared multiplex connection
+                // unanswered until it exits, so it gets its own thread;
+                // [`CONN_WRITE_LOCK`] serializes its writes.
+                let registration = attach::register(request.exec_id.as_deref(), fd, request_id);
+                std::thread::Builder::new()
+                    .name("exec".into())
+                    .spawn(move || {
+                        let output = registration.output();
+                        let response = execute_command(output, &request);
+                        if let Err(e) = output.finish(&response) {
+                            kmsg(&format!("Failed to send ExecResponse: {}", e));
+                        }
+                    })
+                    .map_err(|e| format!("spawn exec thread: {e}"))?;
+            }
+            MessageType::AttachExec => {
+                let request: AttachExecRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse AttachExecRequest: {}", e))?;
+                match attach::attach(fd, request_id, &request) {
+                    Ok(()) => kmsg(&format!("AttachExec {}: attached", request.exec_id)),
+                    Err(error) => {
+                        kmsg(&format!("AttachExec {}: {}", request.exec_id, error));
+                        let response = ExecResponse {
+                            exit_code: -1,
+                            error: Some(error),
+                            ..Default::default()
+                        };
+                        send_mux_response(fd, MessageType::ExecResponse, request_id, &response)?;
+                    }
+                }
             }
             MessageType::Ping => match SESSION_SECRET.get() {
                 Some(expected_secret) => {
@@ -2123,6 +2396,9 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
 
                     if !session_secret_matches(peer_secret, expected_secret) {
                         eprintln!("Authentication failed: invalid secret");
+                        if !SECRET_MISMATCH_REPORTED.swap(true, Ordering::Relaxed) {
+                            boot_status(BootStatus::SecretMismatch, "");
+                        }
                         return Err("Authentication failed: invalid session secret".into());
                     }
 
@@ -2164,9 +2440,34 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
                 }
             },
             MessageType::Shutdown => {
-                eprintln!("Shutdown requested");
-                unsafe {
-                    libc::reboot(libc::LINUX_REBOOT_CMD_POWER_OFF);
+                let request: ShutdownRequest = if body.is_empty() {
+                    ShutdownRequest::default()
+                } else {
+                    serde_json::from_slice(body)
+                        .map_err(|e| format!("Failed to parse ShutdownRequest: {}", e))?
+                };
+                kmsg(if request.reboot {
+                    "Reboot requested"
+                } else {
+                    "Shutdown requested"
+                });
+                let ack = shutdown::shutdown(&request);
+                kmsg(&format!(
+                    "Shutdown: terminated={} killed={} flushed={}B in {}ms",
+                    ack.processes_terminated,
+                    ack.processes_killed,
+                    ack.bytes_flushed,
+                    ack.duration_ms
+                ));
+                // Best effort: the host falls back to a hard stop if the
+                // ack never arrives, so a dead connection must not keep the
+                // guest up.
+                let _ = send_mux_response(fd, MessageType::ShutdownAck, request_id, &ack);
+                watchdog::stop();
+                if request.reboot {
+                    shutdown::restart();
+                } else {
+                    shutdown::power_off();
                 }
                 return Ok(());
             }
@@ -2187,17 +2488,46 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
                 // handler keep dispatching; [`CONN_WRITE_LOCK`] serializes
                 // writes between this thread and the handler.
                 let handler_fd = fd;
+                let generation = start_telemetry_subscription(&opts);
                 std::thread::Builder::new()
                     .name("telemetry".into())
-                    .spawn(move || telemetry_stream_loop(handler_fd, request_id, &opts))
+                    .spawn(move || telemetry_stream_loop(handler_fd, request_id, &opts, generation))
                     .map_err(|e| format!("spawn telemetry thread: {e}"))?;
             }
+            MessageType::TelemetryControl => {
+                let request: TelemetryControlRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse TelemetryControlRequest: {}", e))?;
+                let response = handle_telemetry_control(&request);
+                send_mux_response(
+                    fd,
+                    MessageType::TelemetryControlResponse,
+                    request_id,
+                    &response,
+                )?;
+            }
             MessageType::WriteFile => {
                 let request: WriteFileRequest = serde_json::from_slice(body)
                     .map_err(|e| format!("Failed to parse WriteFileRequest: {}", e))?;
                 let response = handle_write_file(&request);
                 send_mux_response(fd, MessageType::WriteFileResponse, request_id, &response)?;
             }
+            MessageType::WriteFileChunk => {
+                let request: WriteFileChunkRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse WriteFileChunkRequest: {}", e))?;
+                let response = handle_write_file_chunk(&request);
+                send_mux_response(
+                    fd,
+                    MessageType::WriteFileChunkResponse,
+                    request_id,
+                    &response,
+                )?;
+            }
+            MessageType::WriteFileFinalize => {
+                let request: WriteFileFinalizeRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse WriteFileFinalizeRequest: {}", e))?;
+                let response = handle_write_file_finalize(&request);
+                send_mux_response(fd, MessageType::WriteFileResponse, request_id, &response)?;
+            }
             MessageType::MkdirP => {
                 let request: MkdirPRequest = serde_json::from_slice(body)
                     .map_err(|e| format!("Failed to parse MkdirPRequest: {}", e))?;
@@ -2216,9 +2546,138 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
                 let response = handle_file_stat(&request);
                 send_mux_response(fd, MessageType::FileStatResponse, request_id, &response)?;
             }
+            MessageType::Symlink => {
+                let request: SymlinkRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse SymlinkRequest: {}", e))?;
+                let response = handle_symlink(&request);
+                send_mux_response(fd, MessageType::SymlinkResponse, request_id, &response)?;
+            }
+            MessageType::Chmod => {
+                let request: ChmodRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse ChmodRequest: {}", e))?;
+                let response = handle_chmod(&request);
+                send_mux_response(fd, MessageType::ChmodResponse, request_id, &response)?;
+            }
+            MessageType::ReadLink => {
+                let request: ReadLinkRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse ReadLinkRequest: {}", e))?;
+                let response = handle_read_link(&request);
+                send_mux_response(fd, MessageType::ReadLinkResponse, request_id, &response)?;
+            }
+            MessageType::HashFiles => {
+                let request: HashFilesRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse HashFilesRequest: {}", e))?;
+                let response = handle_hash_files(&request);
+                send_mux_response(fd, MessageType::HashFilesResponse, request_id, &response)?;
+            }
+            MessageType::SysInfo => {
+                let response = sysinfo::handle_sysinfo();
+                send_mux_response(fd, MessageType::SysInfoResponse, request_id, &response)?;
+            }
+            MessageType::FsDiff => {
+                let request: FsDiffRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse FsDiffRequest: {}", e))?;
+                let response = handle_fs_diff(&request);
+                send_mux_response(fd, MessageType::FsDiffResponse, request_id, &response)?;
+            }
+            MessageType::ExportWorkspace => {
+                let request: ExportWorkspaceRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse ExportWorkspaceRequest: {}", e))?;
+                let response = handle_export_workspace(fd, request_id, &request);
+                send_mux_response(
+                    fd,
+                    MessageType::ExportWorkspaceResponse,
+                    request_id,
+                    &response,
+                )?;
+            }
             MessageType::SnapshotReady => {
                 send_mux_raw(fd, MessageType::SnapshotReady, request_id, &[])?;
             }
+            MessageType::SignalExec => {
+                let request: SignalExecRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse SignalExecRequest: {}", e))?;
+                let response = signal::signal(&request);
+                kmsg(&format!(
+                    "SignalExec {:?} {}: delivered={}",
+                    request.signal, request.exec_id, response.delivered
+                ));
+                send_mux_response(fd, MessageType::SignalExecResponse, request_id, &response)?;
+            }
+            MessageType::SetExecPolicy => {
+                let request: SetExecPolicyRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse SetExecPolicyRequest: {}", e))?;
+                let rules = request.policy.rules.len();
+                *EXEC_POLICY.write().unwrap_or_else(|e| e.into_inner()) = request.policy;
+                kmsg(&format!("Exec policy replaced: {} rules", rules));
+                let response = SetExecPolicyResponse { rules, error: None };
+                send_mux_response(
+                    fd,
+                    MessageType::SetExecPolicyResponse,
+                    request_id,
+                    &response,
+                )?;
+            }
+            MessageType::CreateUser => {
+                let request: CreateUserRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse CreateUserRequest: {}", e))?;
+                let response = match create_user(&request.name) {
+                    Ok(user) => CreateUserResponse {
+                        uid: user.uid,
+                        gid: user.gid,
+                        home: user.home,
+                        error: None,
+                    },
+                    Err(e) => CreateUserResponse {
+                        error: Some(e),
+                        ..Default::default()
+                    },
+                };
+                send_mux_response(fd, MessageType::CreateUserResponse, request_id, &response)?;
+            }
+            MessageType::SyncClock => {
+                let request: SyncClockRequest = serde_json::from_slice(body)
+                    .map_err(|e| format!("Failed to parse SyncClockRequest: {}", e))?;
+                let response = sync_clock(&request);
+                send_mux_response(fd, MessageType::SyncClockResponse, request_id, &response)?;
+            }
+            MessageType::ExecStdinChunk | MessageType::ExecStdinClose => {
+                let body = body.to_vec();
+                // Writing blocks until the command reads, and a chunk may
+                // wait for its exec to spawn; keep both off the connection.
+                std::thread::Builder::new()
+                    .name("exec-stdin".into())
+                    .spawn(move || {
+                        let response = if message_type == MessageType::ExecStdinChunk {
+                            serde_json::from_slice::<ExecStdinChunk>(&body)
+                                .map(|request| stdin::write(&request))
+                        } else {
+                            serde_json::from_slice::<ExecStdinClose>(&body)
+                                .map(|request| stdin::close(&request))
+                        };
+                        let response = response.unwrap_or_else(|e| ExecStdinResponse {
+                            error: Some(format!("Failed to parse {:?}: {}", message_type, e)),
+                        });
+                        if let Err(e) = send_mux_response(
+                            fd,
+                            MessageType::ExecStdinResponse,
+                            request_id,
+                            &response,
+                        ) {
+                            kmsg(&format!("Failed to send ExecStdinResponse: {}", e));
+                        }
+                    })
+                    .map_err(|e| format!("spawn exec-stdin thread: {e}"))?;
+            }
+            MessageType::EnterReadOnly => {
+                let response = enter_read_only();
+                send_mux_response(
+                    fd,
+                    MessageType::EnterReadOnlyResponse,
+                    request_id,
+                    &response,
+                )?;
+            }
             MessageType::PtyOpen => {
                 let request: PtyOpenRequest = serde_json::from_slice(body)
                     .map_err(|e| format!("Failed to parse PtyOpenRequest: {}", e))?;
@@ -2235,11 +2694,28 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
             | MessageType::TelemetryData
             | MessageType::TelemetryAck
             | MessageType::WriteFileResponse
+            | MessageType::WriteFileChunkResponse
             | MessageType::MkdirPResponse
             | MessageType::ExecOutputChunk
             | MessageType::ExecOutputAck
             | MessageType::ReadFileResponse
             | MessageType::FileStatResponse
+            | MessageType::FsDiffResponse
+            | MessageType::ExportWorkspaceChunk
+            | MessageType::ExportWorkspaceResponse
+            | MessageType::ShutdownAck
+            | MessageType::SignalExecResponse
+            | MessageType::SetExecPolicyResponse
+            | MessageType::EnterReadOnlyResponse
+            | MessageType::CreateUserResponse
+            | MessageType::SyncClockResponse
+            | MessageType::ExecStdinResponse
+            | MessageType::SymlinkResponse
+            | MessageType::ChmodResponse
+            | MessageType::ReadLinkResponse
+            | MessageType::HashFilesResponse
+            | MessageType::SysInfoResponse
+            | MessageType::TelemetryControlResponse
             | MessageType::PtyOpened
             | MessageType::PtyClosed => {
                 eprintln!("Unexpected response-type message: {:?}", message_type);
@@ -2248,28 +2724,32 @@ fn handle_connection(fd: RawFd) -> Result<(), String> {
     }
 }
 
-/// Checks whether a program is permitted by the command allowlist.
+/// Checks whether a program is permitted by the exec policy.
 pub(crate) fn is_command_allowed(program: &str) -> bool {
-    match COMMAND_ALLOWLIST.get() {
-        None => true, // No allowlist loaded = allow all
-        Some(list) if list.is_empty() => true,
-        Some(list) => {
-            // Match against basename of the program path
-            let basename = Path::new(program)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or(program);
-            list.iter().any(|allowed| allowed == basename)
-        }
+    check_exec_policy(None, program, &[]).is_ok()
+}
+
+/// Checks a command against `policy`, or the guest's exec policy if `None`.
+fn check_exec_policy(
+    policy: Option<&ExecPolicy>,
+    program: &str,
+    args: &[String],
+) -> Result<(), ExecDenial> {
+    match policy {
+        Some(policy) => policy.check(program, args),
+        None => EXEC_POLICY
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .check(program, args),
     }
 }
 
 /// Execute a command, streaming stdout/stderr chunks via ExecOutputChunk
 /// messages, then return the final ExecResponse with full accumulated output.
 ///
-/// Every outgoing frame for this exec carries `request_id` so the host
-/// demultiplexer can route chunks back to the caller's stream receiver.
-fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecResponse {
+/// Chunks go through `output`, to whichever connection is attached to the
+/// exec when they are read.
+fn execute_command(output: &Arc<attach::Output>, request: &ExecRequest) -> ExecResponse {
     let start = std::time::Instant::now();
     {
         let status = oci_status_str(OCI_SETUP_STATUS.load(Ordering::Acquire));
@@ -2294,6 +2774,7 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
             exit_code: -1,
             error: Some(msg),
             duration_ms: Some(start.elapsed().as_millis() as u64),
+            ..Default::default()
         };
     }
     {
@@ -2301,23 +2782,39 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
         kmsg(&format!("Exec gate passed: oci_status={}", status));
     }
 
-    // Check command allowlist before spawning
-    if !is_command_allowed(&request.program) {
-        eprintln!("Command not allowed: {}", request.program);
-        kmsg(&format!("Command not allowed: {}", request.program));
+    // Check the exec policy (or this request's override) before spawning
+    if let Err(denial) = check_exec_policy(request.policy.as_ref(), &request.program, &request.args)
+    {
+        eprintln!("Command not allowed: {}: {}", request.program, denial);
+        kmsg(&format!(
+            "Command not allowed: {}: {}",
+            request.program, denial
+        ));
         return ExecResponse {
             stdout: Vec::new(),
-            stderr: format!(
-                "Command '{}' is not in the allowed commands list",
-                request.program
-            )
-            .into_bytes(),
+            stderr: format!("Command '{}' {}", request.program, denial).into_bytes(),
             exit_code: -1,
-            error: Some(format!("Command '{}' is not allowed", request.program)),
+            error: Some(format!("Command '{}' {}", request.program, denial)),
             duration_ms: Some(start.elapsed().as_millis() as u64),
+            ..Default::default()
         };
     }
 
+    let user = match resolve_exec_user(request.user.as_ref()) {
+        Ok(user) => user,
+        Err(e) => {
+            kmsg(&format!("Exec refused: {}", e));
+            return ExecResponse {
+                stdout: Vec::new(),
+                stderr: e.clone().into_bytes(),
+                exit_code: -1,
+                error: Some(e),
+                duration_ms: Some(start.elapsed().as_millis() as u64),
+                ..Default::default()
+            };
+        }
+    };
+
     let mut cmd = Command::new(&request.program);
     cmd.args(&request.args);
 
@@ -2330,24 +2827,31 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
         cmd.env("PATH", &path);
     }
 
-    // Child processes run as uid=1000 (sandbox user) but inherit HOME=/root
-    // from init. Since /root is not writable by uid=1000, set HOME to the
-    // sandbox user's home directory so tools like claude-code can write to
-    // $HOME/.claude/ for config and cache.
-    cmd.env("HOME", "/home/sandbox");
+    // Child processes run as the exec's user (uid=1000 sandbox user by
+    // default) but inherit HOME=/root from init. Since /root is not
+    // writable by them, set HOME to the user's home directory so tools like
+    // claude-code can write to $HOME/.claude/ for config and cache.
+    cmd.env("HOME", &user.home);
 
     // Set environment variables from request (may override PATH and HOME above)
     for (key, value) in &request.env {
         cmd.env(key, value);
     }
 
+    // Where a test adapter reports structured results; an exec that cannot
+    // get a report file (e.g. a read-only guest) runs without one.
+    let test_report = test_report::ReportFile::create(user.uid, user.gid).ok();
+    if let Some(ref report) = test_report {
+        cmd.env(TEST_REPORT_ENV, report.path());
+    }
+
     // Set working directory
     if let Some(ref dir) = request.working_dir {
         cmd.current_dir(dir);
     }
 
     // Set up stdin
-    if !request.stdin.is_empty() {
+    if request.stdin_stream || !request.stdin.is_empty() {
         cmd.stdin(Stdio::piped());
     } else {
         cmd.stdin(Stdio::null());
@@ -2356,16 +2860,22 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
 
-    // Drop privileges to sandbox user (uid=1000, gid=1000) for child processes.
-    // This is required because claude-code refuses --dangerously-skip-permissions as root.
-    // The guest-agent (PID 1) stays root, but child commands run as sandbox user.
+    // Drop privileges to the exec's user (sandbox user, uid=1000, unless the
+    // host asked for another) for child processes. This is required because
+    // claude-code refuses --dangerously-skip-permissions as root. The
+    // guest-agent (PID 1) stays root, but child commands run unprivileged
+    // unless the host explicitly asks for root.
     //
     // Also apply resource limits (setrlimit) to prevent fork bombs, OOM, and disk filling.
     use std::os::unix::process::CommandExt;
     unsafe {
-        cmd.pre_exec(|| {
-            // Always run child processes as sandbox user.
-            if libc::setgid(1000) != 0 || libc::setuid(1000) != 0 {
+        let (uid, gid) = (user.uid, user.gid);
+        cmd.pre_exec(move || {
+            // Drop root's supplementary groups before leaving root.
+            if uid != 0 && libc::setgroups(0, std::ptr::null()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::setgid(gid) != 0 || libc::setuid(uid) != 0 {
                 return Err(std::io::Error::last_os_error());
             }
 
@@ -2404,6 +2914,7 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
     }
 
     // Spawn the process
+    let oom_kills_before = read_oom_kill_count();
     let mut child = match cmd.spawn() {
         Ok(child) => child,
         Err(e) => {
@@ -2480,15 +2991,23 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
                 exit_code: -1,
                 error: Some(msg),
                 duration_ms: None,
+                ..Default::default()
             };
         }
     };
 
-    // Write stdin if provided, then close
-    if !request.stdin.is_empty() {
-        if let Some(mut stdin) = child.stdin.take() {
+    let spawn_ms = start.elapsed().as_millis() as u64;
+
+    // Write stdin if provided, then close it, or hand it over to
+    // ExecStdinChunk messages when streamed.
+    let mut stdin_registration = None;
+    if let Some(mut stdin) = child.stdin.take() {
+        if !request.stdin.is_empty() {
             let _ = stdin.write_all(&request.stdin);
         }
+        if let (true, Some(exec_id)) = (request.stdin_stream, request.exec_id.as_deref()) {
+            stdin_registration = Some(stdin::register(exec_id, stdin));
+        }
     }
 
     // Spawn a watchdog thread that SIGKILLs the child's process group
@@ -2498,6 +3017,10 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
     // and we can `join` it. Without this the watchdog would sleep the
     // full timeout and leak its OS thread until the VM shuts down.
     let child_pid = child.id() as i32;
+    let registration = request
+        .exec_id
+        .as_deref()
+        .map(|exec_id| signal::register(exec_id, child_pid));
     let timed_out = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let watchdog_wake = Arc::new((std::sync::Mutex::new(false), std::sync::Condvar::new()));
     let watchdog_handle = match request.timeout_secs {
@@ -2543,21 +3066,21 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
     let stdout_pipe = child.stdout.take();
     let stderr_pipe = child.stderr.take();
 
-    // Wrap fd in a Mutex so both streaming threads can send chunks safely
-    // without interleaving wire-format messages.
-    let fd_mutex = Arc::new(Mutex::new(fd));
-
-    let fd_for_stdout = fd_mutex.clone();
+    // Both streaming threads send through the exec's output, whose route
+    // lock keeps their wire-format messages from interleaving.
+    let output_for_stdout = Arc::clone(output);
     let stdout_handle =
-        std::thread::spawn(move || stream_pipe(fd_for_stdout, request_id, stdout_pipe, "stdout"));
+        std::thread::spawn(move || stream_pipe(&output_for_stdout, stdout_pipe, "stdout"));
 
-    let fd_for_stderr = fd_mutex.clone();
+    let output_for_stderr = Arc::clone(output);
     let stderr_handle =
-        std::thread::spawn(move || stream_pipe(fd_for_stderr, request_id, stderr_pipe, "stderr"));
+        std::thread::spawn(move || stream_pipe(&output_for_stderr, stderr_pipe, "stderr"));
 
-    // Wait for process to exit
-    let exit_code = match child.wait() {
-        Ok(status) => {
+    // Wait for process to exit. Reaping with wait4() rather than
+    // `child.wait()` hands back the child's rusage with its status.
+    let mut oom_killed = false;
+    let (exit_code, usage) = match wait_with_usage(child_pid) {
+        Ok((status, usage)) => {
             #[cfg(unix)]
             {
                 use std::os::unix::process::ExitStatusExt;
@@ -2566,13 +3089,20 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
                         "Process '{}' killed by signal {} (exit_status={:?})",
                         request.program, sig, status,
                     ));
+                    // The OOM killer sends SIGKILL; a kill counted while the
+                    // child ran is attributed to it.
+                    oom_killed = sig == libc::SIGKILL
+                        && matches!(
+                            (oom_kills_before, read_oom_kill_count()),
+                            (Some(before), Some(after)) if after > before
+                        );
                 }
             }
-            status.code().unwrap_or(-1)
+            (status.code().unwrap_or(-1), usage)
         }
         Err(e) => {
-            let stdout_bytes = stdout_handle.join().unwrap_or_default();
-            let stderr_bytes = stderr_handle.join().unwrap_or_default();
+            let (stdout_bytes, _) = stdout_handle.join().unwrap_or_default();
+            let (stderr_bytes, _) = stderr_handle.join().unwrap_or_default();
             let duration_ms = start.elapsed().as_millis() as u64;
             return ExecResponse {
                 stdout: stdout_bytes,
@@ -2580,13 +3110,19 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
                 exit_code: -1,
                 error: Some(format!("Failed to wait for process: {}", e)),
                 duration_ms: Some(duration_ms),
+                pid: Some(child_pid as u32),
+                spawn_ms: Some(spawn_ms),
+                ..Default::default()
             };
         }
     };
 
+    drop(registration);
+    drop(stdin_registration);
+
     // Collect accumulated output from streaming threads
-    let stdout_bytes = stdout_handle.join().unwrap_or_default();
-    let mut stderr_bytes = stderr_handle.join().unwrap_or_default();
+    let (stdout_bytes, first_stdout_at) = stdout_handle.join().unwrap_or_default();
+    let (mut stderr_bytes, _) = stderr_handle.join().unwrap_or_default();
 
     let duration_ms = start.elapsed().as_millis() as u64;
 
@@ -2611,6 +3147,8 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
             "Process killed after {}s timeout",
             request.timeout_secs.unwrap_or(0)
         ))
+    } else if oom_killed {
+        Some("Process killed by the guest OOM killer".to_string())
     } else if exit_code == -1 {
         Some("Process killed by signal (exit_code mapped to -1)".to_string())
     } else {
@@ -2631,21 +3169,71 @@ fn execute_command(fd: RawFd, request_id: u32, request: &ExecRequest) -> ExecRes
         exit_code,
         error: error_msg,
         duration_ms: Some(duration_ms),
+        pid: Some(child_pid as u32),
+        spawn_ms: Some(spawn_ms),
+        first_stdout_ms: first_stdout_at.map(|at| at.duration_since(start).as_millis() as u64),
+        timeout_ms: was_timed_out
+            .then(|| spawn_ms + request.timeout_secs.unwrap_or(0).saturating_mul(1000)),
+        cpu_time_ms: Some(usage.cpu_time_ms),
+        max_rss_bytes: Some(usage.max_rss_bytes),
+        io_bytes: Some(usage.io_bytes),
+        oom_killed,
+        test_results: test_report.map(|report| report.read()).unwrap_or_default(),
+    }
+}
+
+/// Resource usage of a reaped exec child, from `wait4()`.
+///
+/// Covers the child plus any descendants it waited for; processes it
+/// left running are not included.
+struct ChildUsage {
+    cpu_time_ms: u64,
+    max_rss_bytes: u64,
+    io_bytes: u64,
+}
+
+/// Reap `pid` and return its exit status together with its rusage.
+fn wait_with_usage(pid: i32) -> std::io::Result<(std::process::ExitStatus, ChildUsage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        if ret == pid {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EINTR) {
+            return Err(err);
+        }
     }
+
+    let timeval_ms = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+    Ok((
+        std::process::ExitStatus::from_raw(status),
+        ChildUsage {
+            cpu_time_ms: timeval_ms(usage.ru_utime) + timeval_ms(usage.ru_stime),
+            // Linux reports ru_maxrss in KiB and block I/O in 512-byte units.
+            max_rss_bytes: usage.ru_maxrss as u64 * 1024,
+            io_bytes: (usage.ru_inblock as u64 + usage.ru_oublock as u64) * 512,
+        },
+    ))
 }
 
 /// Reads from a pipe and sends ExecOutputChunk messages as data arrives.
 ///
 /// Returns the full accumulated output for the final ExecResponse so the
 /// host still gets a complete stdout/stderr summary even if a streaming
-/// send transiently fails.
+/// send transiently fails or no connection is attached, along with when
+/// the first byte arrived.
 fn stream_pipe(
-    fd: Arc<Mutex<RawFd>>,
-    request_id: u32,
+    output: &attach::Output,
     pipe: Option<impl Read>,
     stream_name: &str,
-) -> Vec<u8> {
+) -> (Vec<u8>, Option<std::time::Instant>) {
     let mut accumulated = Vec::new();
+    let mut first_byte_at = None;
     let mut seq = 0u64;
     let mut buf = [0u8; 4096];
 
@@ -2654,46 +3242,21 @@ fn stream_pipe(
             match pipe.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
+                    first_byte_at.get_or_insert_with(std::time::Instant::now);
                     accumulated.extend_from_slice(&buf[..n]);
                     let chunk = ExecOutputChunk {
                         stream: stream_name.to_string(),
                         data: buf[..n].to_vec(),
                         seq,
                     };
-                    if let Ok(locked_fd) = fd.lock() {
-                        let _ = send_mux_response(
-                            *locked_fd,
-                            MessageType::ExecOutputChunk,
-                            request_id,
-                            &chunk,
-                        );
-                    }
+                    let _ = output.send(MessageType::ExecOutputChunk, &chunk);
                     seq += 1;
                 }
                 Err(_) => break,
             }
         }
     }
-    accumulated
-}
-
-/// Read exactly `buf.len()` bytes from the socket
-fn read_exact(fd: RawFd, buf: &mut [u8]) -> Result<(), String> {
-    let mut total_read = 0;
-    while total_read < buf.len() {
-        let n = unsafe {
-            libc::read(
-                fd,
-                buf[total_read..].as_mut_ptr() as *mut libc::c_void,
-                buf.len() - total_read,
-            )
-        };
-        if n <= 0 {
-            return Err("Read failed".into());
-        }
-        total_read += n as usize;
-    }
-    Ok(())
+    (accumulated, first_byte_at)
 }
 
 /// Sends a JSON-serialized response framed with a multiplex request_id prefix.
@@ -2791,6 +3354,13 @@ fn write_framed(fd: RawFd, msg: &[u8]) -> Result<(), String> {
 /// resolved fd is used directly for the write — no path-string re-open
 /// after resolution, which is what would re-introduce a TOCTOU window.
 fn handle_write_file(request: &WriteFileRequest) -> WriteFileResponse {
+    if let Some(error) = read_only_refusal(&request.path) {
+        return WriteFileResponse {
+            success: false,
+            error: Some(error),
+        };
+    }
+
     // In OCI-rootfs mode the cached fs_guard root fds must be opened
     // post-pivot, otherwise resolution targets orphaned initramfs
     // inodes; mirrors the gate `handle_exec` already enforces.
@@ -2825,7 +3395,9 @@ fn handle_write_file(request: &WriteFileRequest) -> WriteFileResponse {
                 success: false,
                 error: Some(format!(
                     "Refusing write outside allowed roots {:?}: {} ({})",
-                    ALLOWED_WRITE_ROOTS, request.path, e
+                    fs_guard::write_root_paths(),
+                    request.path,
+                    e
                 )),
             };
         }
@@ -2928,30 +3500,275 @@ fn handle_write_file(request: &WriteFileRequest) -> WriteFileResponse {
     }
 }
 
-fn handle_read_file(request: &ReadFileRequest) -> ReadFileResponse {
+/// Name of the hidden staging file a chunked transfer to `basename` writes
+/// into, e.g. `model.bin` -> `.model.bin.voidbox-partial`.
+fn partial_write_name(basename: &OsStr) -> OsString {
+    let mut name = OsString::from(".");
+    name.push(basename);
+    name.push(PARTIAL_WRITE_SUFFIX);
+    name
+}
+
+/// Opens `name` relative to an `fs_guard`-resolved parent fd with
+/// `O_NOFOLLOW`, so a planted final-component symlink cannot redirect the
+/// op. The parent walk itself is already protected by
+/// `RESOLVE_NO_SYMLINKS`.
+fn open_leaf_at(parent_fd: &OwnedFd, name: &OsStr, flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let name_c = std::ffi::CString::new(name.as_bytes())
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+    let fd = unsafe {
+        libc::openat(
+            parent_fd.as_raw_fd(),
+            name_c.as_ptr(),
+            flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            0o644,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Returns the current size of the file behind `fd`.
+fn fd_size(fd: &OwnedFd) -> std::io::Result<u64> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.st_size as u64)
+}
+
+fn chunk_failure(error: String) -> WriteFileChunkResponse {
+    WriteFileChunkResponse {
+        success: false,
+        staged_size: 0,
+        error: Some(error),
+    }
+}
+
+/// Handle a WriteFileChunk request: `pwrite` one chunk into the staging
+/// sibling of the destination.
+///
+/// The chunk at offset 0 creates (or truncates) the staging file and, when
+/// requested, the parent directories; later chunks require the staging file
+/// to exist, so a chunk that arrives after the guest lost its state fails
+/// instead of silently producing a sparse file. Resolution goes through
+/// `fs_guard` exactly as in [`handle_write_file`].
+fn handle_write_file_chunk(request: &WriteFileChunkRequest) -> WriteFileChunkResponse {
+    if let Some(error) = read_only_refusal(&request.path) {
+        return chunk_failure(error);
+    }
     if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
-        return ReadFileResponse {
-            success: false,
-            content: Vec::new(),
-            error: Some(format!("OCI rootfs not ready: {}", e)),
-        };
+        return chunk_failure(format!("OCI rootfs not ready: {}", e));
     }
-    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
 
     let target = Path::new(&request.path);
-    let fd = match fs_guard::resolve_for_read(target) {
-        Ok(fd) => fd,
-        Err(e) => {
-            return ReadFileResponse {
-                success: false,
-                content: Vec::new(),
-                error: Some(format!(
-                    "Refusing read outside allowed roots {:?}: {} ({})",
-                    ALLOWED_READ_ROOTS, request.path, e
-                )),
-            };
-        }
-    };
+    let is_first_chunk = request.offset == 0;
+
+    if is_first_chunk && request.create_parents {
+        if let Some(parent) = target.parent() {
+            if let Err(e) = fs_guard::create_dirs_in_root(parent) {
+                return chunk_failure(format!(
+                    "Refusing mkdir for parents of {}: {}",
+                    request.path, e
+                ));
+            }
+            chown_recursive(parent);
+        }
+    }
+
+    let (parent_fd, basename) = match fs_guard::resolve_parent_for_write(target) {
+        Ok(pair) => pair,
+        Err(e) => {
+            return chunk_failure(format!(
+                "Refusing write outside allowed roots {:?}: {} ({})",
+                fs_guard::write_root_paths(),
+                request.path,
+                e
+            ));
+        }
+    };
+
+    let open_flags = if is_first_chunk {
+        libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+    } else {
+        libc::O_WRONLY
+    };
+    let staging = match open_leaf_at(&parent_fd, &partial_write_name(&basename), open_flags) {
+        Ok(fd) => fd,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return chunk_failure(format!(
+                "No chunked transfer in progress for {} (chunk at offset {} arrived before offset 0)",
+                request.path, request.offset
+            ));
+        }
+        Err(e) => {
+            return chunk_failure(format!(
+                "Failed to open staging file for {}: {}",
+                request.path, e
+            ));
+        }
+    };
+
+    let mut written = 0usize;
+    while written < request.data.len() {
+        let n = unsafe {
+            libc::pwrite(
+                staging.as_raw_fd(),
+                request.data[written..].as_ptr() as *const libc::c_void,
+                request.data.len() - written,
+                (request.offset + written as u64) as libc::off_t,
+            )
+        };
+        if n < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return chunk_failure(format!("Failed to write {}: {}", request.path, err));
+        }
+        if n == 0 {
+            break;
+        }
+        written += n as usize;
+    }
+
+    match fd_size(&staging) {
+        Ok(staged_size) => WriteFileChunkResponse {
+            success: true,
+            staged_size,
+            error: None,
+        },
+        Err(e) => chunk_failure(format!(
+            "Failed to stat staging file for {}: {}",
+            request.path, e
+        )),
+    }
+}
+
+/// Handle a WriteFileFinalize request: check the staged size against the
+/// sender's total, fix ownership, and `renameat` the staging file onto the
+/// destination.
+///
+/// On a size mismatch the staging file is left in place so the sender can
+/// resend the missing chunks and finalize again.
+fn handle_write_file_finalize(request: &WriteFileFinalizeRequest) -> WriteFileResponse {
+    let failure = |error: String| WriteFileResponse {
+        success: false,
+        error: Some(error),
+    };
+
+    if let Some(error) = read_only_refusal(&request.path) {
+        return failure(error);
+    }
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+
+    let target = Path::new(&request.path);
+    let (parent_fd, basename) = match fs_guard::resolve_parent_for_write(target) {
+        Ok(pair) => pair,
+        Err(e) => {
+            return failure(format!(
+                "Refusing write outside allowed roots {:?}: {} ({})",
+                fs_guard::write_root_paths(),
+                request.path,
+                e
+            ));
+        }
+    };
+
+    let partial_name = partial_write_name(&basename);
+    let staging = match open_leaf_at(&parent_fd, &partial_name, libc::O_RDONLY) {
+        Ok(fd) => fd,
+        Err(e) => {
+            return failure(format!(
+                "No staged transfer to finalize for {}: {}",
+                request.path, e
+            ));
+        }
+    };
+
+    match fd_size(&staging) {
+        Ok(size) if size == request.total_size => {}
+        Ok(size) => {
+            return failure(format!(
+                "Staged size mismatch for {}: expected {} bytes, have {}",
+                request.path, request.total_size, size
+            ));
+        }
+        Err(e) => {
+            return failure(format!(
+                "Failed to stat staging file for {}: {}",
+                request.path, e
+            ));
+        }
+    }
+
+    if unsafe { libc::fchown(staging.as_raw_fd(), 1000, 1000) } != 0 {
+        let err = std::io::Error::last_os_error();
+        kmsg(&format!("fchown({}) failed: {}", request.path, err));
+    }
+    if unsafe { libc::fchmod(staging.as_raw_fd(), 0o644) } != 0 {
+        let err = std::io::Error::last_os_error();
+        kmsg(&format!("fchmod({}) failed: {}", request.path, err));
+    }
+
+    let (Ok(partial_c), Ok(basename_c)) = (
+        std::ffi::CString::new(partial_name.into_vec()),
+        std::ffi::CString::new(basename.as_bytes()),
+    ) else {
+        return failure(format!("invalid basename in path: {}", request.path));
+    };
+    let renamed = unsafe {
+        libc::renameat(
+            parent_fd.as_raw_fd(),
+            partial_c.as_ptr(),
+            parent_fd.as_raw_fd(),
+            basename_c.as_ptr(),
+        )
+    };
+    if renamed != 0 {
+        let err = std::io::Error::last_os_error();
+        return failure(format!("Failed to commit {}: {}", request.path, err));
+    }
+
+    kmsg(&format!(
+        "Wrote {} bytes to {} (chunked)",
+        request.total_size, request.path
+    ));
+
+    WriteFileResponse {
+        success: true,
+        error: None,
+    }
+}
+
+fn handle_read_file(request: &ReadFileRequest) -> ReadFileResponse {
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return ReadFileResponse {
+            success: false,
+            content: Vec::new(),
+            error: Some(format!("OCI rootfs not ready: {}", e)),
+        };
+    }
+    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
+
+    let target = Path::new(&request.path);
+    let fd = match fs_guard::resolve_for_read(target) {
+        Ok(fd) => fd,
+        Err(e) => {
+            return ReadFileResponse {
+                success: false,
+                content: Vec::new(),
+                error: Some(format!(
+                    "Refusing read outside allowed roots {:?}: {} ({})",
+                    ALLOWED_READ_ROOTS, request.path, e
+                )),
+            };
+        }
+    };
 
     // Upgrade the resolved O_PATH fd to a real read fd by re-opening
     // through `/proc/self/fd/<n>`. This is the documented `O_PATH ->
@@ -3015,6 +3832,193 @@ fn handle_read_file(request: &ReadFileRequest) -> ReadFileResponse {
     }
 }
 
+/// Report filesystem changes, over the whole root or one readable root.
+///
+/// An explicit root goes through the same `fs_guard` resolution as
+/// `ReadFile`, and the tree is walked from the resolved fd, so a planted
+/// symlink cannot point the walk outside `ALLOWED_READ_ROOTS`.
+fn handle_fs_diff(request: &FsDiffRequest) -> FsDiffResponse {
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return fs_diff::failure(format!("OCI rootfs not ready: {}", e));
+    }
+    let Some(root) = request.root.as_deref() else {
+        return fs_diff::diff_root_filesystem();
+    };
+
+    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
+    let dir_fd = match fs_guard::resolve_for_read(Path::new(root)) {
+        Ok(fd) => fd,
+        Err(e) => {
+            return fs_diff::failure(format!(
+                "Refusing diff outside allowed roots {:?}: {} ({})",
+                ALLOWED_READ_ROOTS, root, e
+            ));
+        }
+    };
+    let dir = format!("/proc/self/fd/{}", dir_fd.as_raw_fd());
+    fs_diff::diff_against_baseline(root, Path::new(&dir))
+}
+
+/// Add a user with its own group and a 0700 home, and record it in
+/// `/etc/passwd` and `/etc/group` when those are writable.
+fn create_user(name: &str) -> Result<GuestUser, String> {
+    if !void_box_protocol::valid_user_name(name) {
+        return Err(format!("invalid user name '{}'", name));
+    }
+    wait_for_oci_setup_ready(std::time::Duration::from_secs(30))
+        .map_err(|e| format!("OCI rootfs not ready: {}", e))?;
+
+    let mut users = GUEST_USERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(user) = users.get(name) {
+        return Ok(user.clone());
+    }
+    let uid = FIRST_CREATED_UID + users.len() as u32;
+    let user = GuestUser {
+        uid,
+        gid: uid,
+        home: format!("/home/{}", name),
+    };
+
+    std::fs::create_dir_all(&user.home).map_err(|e| format!("mkdir {}: {}", user.home, e))?;
+    let home_c = std::ffi::CString::new(user.home.as_str()).map_err(|e| e.to_string())?;
+    if unsafe { libc::chown(home_c.as_ptr(), user.uid, user.gid) } != 0 {
+        return Err(format!(
+            "chown {}: {}",
+            user.home,
+            std::io::Error::last_os_error()
+        ));
+    }
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(&user.home, std::fs::Permissions::from_mode(0o700))
+        .map_err(|e| format!("chmod {}: {}", user.home, e))?;
+
+    // Lets tools that look names up (ls -l, whoami) resolve the user.
+    for (file, line) in [
+        (
+            "/etc/passwd",
+            format!(
+                "{}:x:{}:{}::{}:/bin/sh\n",
+                name, user.uid, user.gid, user.home
+            ),
+        ),
+        ("/etc/group", format!("{}:x:{}:\n", name, user.gid)),
+    ] {
+        let appended = std::fs::OpenOptions::new()
+            .append(true)
+            .open(file)
+            .and_then(|mut f| f.write_all(line.as_bytes()));
+        if let Err(e) = appended {
+            kmsg(&format!("WARNING: cannot add {} to {}: {}", name, file, e));
+        }
+    }
+
+    kmsg(&format!("Created user {} (uid {})", name, user.uid));
+    users.insert(name.to_string(), user.clone());
+    Ok(user)
+}
+
+/// Credentials for an exec's [`ExecUser`]; `None` is the sandbox user.
+fn resolve_exec_user(user: Option<&ExecUser>) -> Result<GuestUser, String> {
+    match user.unwrap_or(&ExecUser::Sandbox) {
+        ExecUser::Sandbox => Ok(GuestUser {
+            uid: SANDBOX_UID,
+            gid: SANDBOX_UID,
+            home: "/home/sandbox".to_string(),
+        }),
+        ExecUser::Root => Ok(GuestUser {
+            uid: 0,
+            gid: 0,
+            home: "/root".to_string(),
+        }),
+        ExecUser::Named(name) => GUEST_USERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("unknown user '{}'; create it first", name)),
+    }
+}
+
+/// Refuse all further file-write RPCs and remount `/workspace` read-only.
+///
+/// The bind is recursive so mounts below `/workspace` stay visible. The
+/// remount only covers `/workspace` itself; the host attaches its shares
+/// and volumes read-only in this mode.
+fn enter_read_only() -> EnterReadOnlyResponse {
+    READ_ONLY.store(true, Ordering::Release);
+    kmsg("Entering read-only mode");
+
+    let workspace = std::ffi::CString::new("/workspace").unwrap();
+    let bind = unsafe {
+        libc::mount(
+            workspace.as_ptr(),
+            workspace.as_ptr(),
+            std::ptr::null(),
+            libc::MS_BIND | libc::MS_REC,
+            std::ptr::null(),
+        )
+    };
+    let remount = bind == 0
+        && unsafe {
+            libc::mount(
+                std::ptr::null(),
+                workspace.as_ptr(),
+                std::ptr::null(),
+                libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY,
+                std::ptr::null(),
+            )
+        } == 0;
+    if remount {
+        EnterReadOnlyResponse { error: None }
+    } else {
+        let error = format!(
+            "remounting /workspace read-only failed: {}",
+            std::io::Error::last_os_error()
+        );
+        kmsg(&format!("WARNING: {}", error));
+        EnterReadOnlyResponse { error: Some(error) }
+    }
+}
+
+/// The error a file-write RPC for `path` gets in read-only mode.
+fn read_only_refusal(path: &str) -> Option<String> {
+    READ_ONLY
+        .load(Ordering::Acquire)
+        .then(|| format!("Refusing write to {}: sandbox is read-only", path))
+}
+
+/// Stream `/workspace` (optionally narrowed by a glob) to the host as
+/// `ExportWorkspaceChunk` frames; the caller sends the terminal response.
+///
+/// Like `handle_fs_diff`, the root is resolved through `fs_guard` and the
+/// tree is read from the resolved fd.
+fn handle_export_workspace(
+    fd: RawFd,
+    request_id: u32,
+    request: &ExportWorkspaceRequest,
+) -> ExportWorkspaceResponse {
+    let failure = |error: String| ExportWorkspaceResponse {
+        success: false,
+        entries: 0,
+        archive_bytes: 0,
+        error: Some(error),
+    };
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+
+    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
+    let dir_fd = match fs_guard::resolve_for_read(Path::new("/workspace")) {
+        Ok(fd) => fd,
+        Err(e) => return failure(format!("Cannot open /workspace: {}", e)),
+    };
+    let dir = format!("/proc/self/fd/{}", dir_fd.as_raw_fd());
+    export::export_tree(Path::new(&dir), request.path_filter.as_deref(), |chunk| {
+        send_mux_raw(fd, MessageType::ExportWorkspaceChunk, request_id, chunk)
+            .map_err(std::io::Error::other)
+    })
+}
+
 fn handle_file_stat(request: &FileStatRequest) -> FileStatResponse {
     match std::fs::metadata(&request.path) {
         Ok(meta) => FileStatResponse {
@@ -3078,6 +4082,12 @@ fn chown_recursive(path: &std::path::Path) {
 /// symlink at any level fails the resolve rather than redirecting the
 /// `mkdir`; the kernel never walks a path string we hand it.
 fn handle_mkdir_p(request: &MkdirPRequest) -> MkdirPResponse {
+    if let Some(error) = read_only_refusal(&request.path) {
+        return MkdirPResponse {
+            success: false,
+            error: Some(error),
+        };
+    }
     if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
         return MkdirPResponse {
             success: false,
@@ -3099,27 +4109,439 @@ fn handle_mkdir_p(request: &MkdirPRequest) -> MkdirPResponse {
             success: false,
             error: Some(format!(
                 "Refusing mkdir outside allowed roots {:?}: {} ({})",
-                ALLOWED_WRITE_ROOTS, request.path, e
+                fs_guard::write_root_paths(),
+                request.path,
+                e
             )),
         },
     }
 }
 
+/// Handle a HashFiles request: hash, size and mtime of each path.
+///
+/// Each path is resolved through `fs_guard` against the read roots, as
+/// for ReadFile, then inspected and read through the resolved fd.
+fn handle_hash_files(request: &HashFilesRequest) -> HashFilesResponse {
+    let failure = |error: String| HashFilesResponse {
+        success: false,
+        files: Vec::new(),
+        error: Some(error),
+    };
+    if request.paths.len() > MAX_HASH_FILES {
+        return failure(format!(
+            "{} paths requested, at most {} per request",
+            request.paths.len(),
+            MAX_HASH_FILES
+        ));
+    }
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
+
+    HashFilesResponse {
+        success: true,
+        files: request.paths.iter().map(|path| hash_file(path)).collect(),
+        error: None,
+    }
+}
+
+fn hash_file(path: &str) -> FileHash {
+    use sha2::{Digest, Sha256};
+
+    let mut hash = FileHash {
+        path: path.to_string(),
+        exists: false,
+        size: 0,
+        mtime_ms: None,
+        sha256: None,
+        error: None,
+    };
+    let fd = match fs_guard::resolve_for_read(Path::new(path)) {
+        Ok(fd) => fd,
+        Err(fs_guard::FsGuardError::Resolve(nix::errno::Errno::ENOENT)) => return hash,
+        Err(e) => {
+            hash.error = Some(format!(
+                "Refusing hash outside allowed roots {:?}: {} ({})",
+                ALLOWED_READ_ROOTS, path, e
+            ));
+            return hash;
+        }
+    };
+
+    // Same O_PATH -> real fd upgrade as handle_read_file.
+    let proc_path = format!("/proc/self/fd/{}", fd.as_raw_fd());
+    let meta = match std::fs::metadata(&proc_path) {
+        Ok(meta) => meta,
+        Err(e) => {
+            hash.error = Some(format!("Failed to stat {}: {}", path, e));
+            return hash;
+        }
+    };
+    hash.exists = true;
+    hash.size = meta.len();
+    hash.mtime_ms = u64::try_from(meta.mtime())
+        .ok()
+        .map(|secs| secs * 1000 + meta.mtime_nsec() as u64 / 1_000_000);
+    if !meta.is_file() {
+        return hash;
+    }
+
+    let digest = std::fs::File::open(&proc_path).and_then(|mut file| {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(hasher.finalize())
+    });
+    match digest {
+        Ok(digest) => hash.sha256 = Some(format!("{:x}", digest)),
+        Err(e) => hash.error = Some(format!("Failed to read {}: {}", path, e)),
+    }
+    hash
+}
+
+/// Handle a Symlink request: create `link_path -> target`.
+/// Runs as root like WriteFile; the link is chowned to uid 1000 after.
+///
+/// The link's parent is resolved through `fs_guard` as for a write and
+/// the link is created against that fd with `symlinkat`. The target is
+/// stored as given: every privileged FS RPC resolves with
+/// `RESOLVE_NO_SYMLINKS`, so a link cannot deflect them wherever it
+/// points.
+fn handle_symlink(request: &SymlinkRequest) -> SymlinkResponse {
+    let failure = |error: String| SymlinkResponse {
+        success: false,
+        error: Some(error),
+    };
+    if let Some(error) = read_only_refusal(&request.link_path) {
+        return failure(error);
+    }
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+
+    let (parent_fd, basename) =
+        match fs_guard::resolve_parent_for_write(Path::new(&request.link_path)) {
+            Ok(pair) => pair,
+            Err(e) => {
+                return failure(format!(
+                    "Refusing symlink outside allowed roots {:?}: {} ({})",
+                    fs_guard::write_root_paths(),
+                    request.link_path,
+                    e
+                ))
+            }
+        };
+    let (Ok(target_c), Ok(basename_c)) = (
+        std::ffi::CString::new(request.target.as_str()),
+        std::ffi::CString::new(basename.as_encoded_bytes()),
+    ) else {
+        return failure(format!(
+            "invalid symlink {} -> {}",
+            request.link_path, request.target
+        ));
+    };
+    let dir = parent_fd.as_raw_fd();
+
+    // Without AT_REMOVEDIR, unlinkat refuses directories.
+    if request.replace && unsafe { libc::unlinkat(dir, basename_c.as_ptr(), 0) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::NotFound {
+            return failure(format!("Failed to replace {}: {}", request.link_path, err));
+        }
+    }
+    if unsafe { libc::symlinkat(target_c.as_ptr(), dir, basename_c.as_ptr()) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return failure(format!(
+            "Failed to create symlink {}: {}",
+            request.link_path, err
+        ));
+    }
+    unsafe {
+        libc::fchownat(
+            dir,
+            basename_c.as_ptr(),
+            1000,
+            1000,
+            libc::AT_SYMLINK_NOFOLLOW,
+        );
+    }
+    kmsg(&format!(
+        "Created symlink {} -> {}",
+        request.link_path, request.target
+    ));
+    SymlinkResponse {
+        success: true,
+        error: None,
+    }
+}
+
+/// Handle a Chmod request: change the mode and/or owner of a path.
+///
+/// The path is resolved to an `O_PATH` fd through `fs_guard`, which
+/// refuses symlinks at every level including the last. Ownership changes
+/// go through `fchownat(AT_EMPTY_PATH)` on that fd; the mode through its
+/// `/proc/self/fd` magic link, since `fchmod` rejects `O_PATH` fds.
+fn handle_chmod(request: &ChmodRequest) -> ChmodResponse {
+    let failure = |error: String| ChmodResponse {
+        success: false,
+        error: Some(error),
+    };
+    if let Some(error) = read_only_refusal(&request.path) {
+        return failure(error);
+    }
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+
+    let fd = match fs_guard::resolve_for_write(Path::new(&request.path)) {
+        Ok(fd) => fd,
+        Err(e) => {
+            return failure(format!(
+                "Refusing chmod outside allowed roots {:?}: {} ({})",
+                fs_guard::write_root_paths(),
+                request.path,
+                e
+            ))
+        }
+    };
+
+    if request.uid.is_some() || request.gid.is_some() {
+        // -1 leaves that id unchanged.
+        let uid = request.uid.unwrap_or(u32::MAX);
+        let gid = request.gid.unwrap_or(u32::MAX);
+        let rc =
+            unsafe { libc::fchownat(fd.as_raw_fd(), c"".as_ptr(), uid, gid, libc::AT_EMPTY_PATH) };
+        if rc != 0 {
+            let err = std::io::Error::last_os_error();
+            return failure(format!("Failed to chown {}: {}", request.path, err));
+        }
+    }
+
+    if let Some(mode) = request.mode {
+        let proc_path = format!("/proc/self/fd/{}", fd.as_raw_fd());
+        let Ok(c_path) = std::ffi::CString::new(proc_path) else {
+            return failure(format!("invalid /proc fd path for {}", request.path));
+        };
+        if unsafe { libc::chmod(c_path.as_ptr(), (mode & 0o7777) as libc::mode_t) } != 0 {
+            let err = std::io::Error::last_os_error();
+            return failure(format!("Failed to chmod {}: {}", request.path, err));
+        }
+    }
+
+    ChmodResponse {
+        success: true,
+        error: None,
+    }
+}
+
+/// Handle a ReadLink request: return a symlink's target.
+///
+/// The link's parent is resolved against the read roots and the link
+/// read with `readlinkat`, so the link itself is never followed.
+fn handle_read_link(request: &ReadLinkRequest) -> ReadLinkResponse {
+    let failure = |error: String| ReadLinkResponse {
+        target: None,
+        error: Some(error),
+    };
+    if let Err(e) = wait_for_oci_setup_ready(std::time::Duration::from_secs(30)) {
+        return failure(format!("OCI rootfs not ready: {}", e));
+    }
+    fs_guard::init_read_roots(&ALLOWED_READ_ROOTS);
+
+    let path = Path::new(&request.path);
+    let (Some(parent), Some(basename)) = (path.parent(), path.file_name()) else {
+        return failure(format!("not a link path: {}", request.path));
+    };
+    let parent_fd = match fs_guard::resolve_for_read(parent) {
+        Ok(fd) => fd,
+        Err(e) => {
+            return failure(format!(
+                "Refusing readlink outside allowed roots {:?}: {} ({})",
+                ALLOWED_READ_ROOTS, request.path, e
+            ))
+        }
+    };
+    let Ok(basename_c) = std::ffi::CString::new(basename.as_encoded_bytes()) else {
+        return failure(format!("invalid basename in path: {}", request.path));
+    };
+
+    let mut buf = vec![0u8; libc::PATH_MAX as usize];
+    let n = unsafe {
+        libc::readlinkat(
+            parent_fd.as_raw_fd(),
+            basename_c.as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+        )
+    };
+    if n < 0 {
+        let err = std::io::Error::last_os_error();
+        return failure(format!("Failed to read link {}: {}", request.path, err));
+    }
+    buf.truncate(n as usize);
+    ReadLinkResponse {
+        target: Some(String::from_utf8_lossy(&buf).into_owned()),
+        error: None,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Telemetry: procfs parsing and streaming
 // ---------------------------------------------------------------------------
 
-/// Streams telemetry data to the host until the connection drops.
+/// Settings of the telemetry subscription, changed at runtime by
+/// `TelemetryControl`. Only the newest subscription streams: starting one
+/// bumps `generation`, which retires the loop of any earlier one (possibly
+/// paused on a connection that has since gone away).
+struct TelemetryControlState {
+    generation: u64,
+    interval: std::time::Duration,
+    paused: bool,
+    filter: TelemetryProcessFilter,
+    top: Option<TopProcesses>,
+}
+
+static TELEMETRY_CONTROL: Mutex<TelemetryControlState> = Mutex::new(TelemetryControlState {
+    generation: 0,
+    interval: std::time::Duration::from_secs(1),
+    paused: false,
+    filter: TelemetryProcessFilter {
+        names: Vec::new(),
+        pids: Vec::new(),
+    },
+    top: None,
+});
+
+/// Wakes the telemetry loop when its settings change.
+static TELEMETRY_WAKE: std::sync::Condvar = std::sync::Condvar::new();
+
+/// Floor for the telemetry collection interval.
+const MIN_TELEMETRY_INTERVAL_MS: u64 = 100;
+
+/// Reset the telemetry settings for a new subscription and return its
+/// generation.
+fn start_telemetry_subscription(opts: &TelemetrySubscribeRequest) -> u64 {
+    let mut state = TELEMETRY_CONTROL.lock().unwrap_or_else(|e| e.into_inner());
+    state.generation += 1;
+    state.interval =
+        std::time::Duration::from_millis(opts.interval_ms.max(MIN_TELEMETRY_INTERVAL_MS));
+    state.paused = false;
+    state.filter = TelemetryProcessFilter::default();
+    state.top = opts.top_processes.filter(|top| top.count > 0);
+    TELEMETRY_WAKE.notify_all();
+    state.generation
+}
+
+/// Handle a TelemetryControl request against the current subscription.
+fn handle_telemetry_control(request: &TelemetryControlRequest) -> TelemetryControlResponse {
+    let mut state = TELEMETRY_CONTROL.lock().unwrap_or_else(|e| e.into_inner());
+    if state.generation == 0 {
+        return TelemetryControlResponse {
+            success: false,
+            interval_ms: 0,
+            paused: false,
+            error: Some("no telemetry subscription".into()),
+        };
+    }
+    if let Some(interval_ms) = request.interval_ms {
+        state.interval =
+            std::time::Duration::from_millis(interval_ms.max(MIN_TELEMETRY_INTERVAL_MS));
+    }
+    if let Some(paused) = request.paused {
+        state.paused = paused;
+    }
+    if let Some(filter) = &request.process_filter {
+        state.filter = filter.clone();
+    }
+    if let Some(top) = request.top_processes {
+        state.top = Some(top).filter(|top| top.count > 0);
+    }
+    kmsg(&format!(
+        "Telemetry settings changed (interval={}ms, paused={}, filtered={}, top={:?})",
+        state.interval.as_millis(),
+        state.paused,
+        !state.filter.is_empty(),
+        state.top.map(|top| top.count)
+    ));
+    TELEMETRY_WAKE.notify_all();
+    TelemetryControlResponse {
+        success: true,
+        interval_ms: state.interval.as_millis() as u64,
+        paused: state.paused,
+        error: None,
+    }
+}
+
+/// Block until the next batch is due under the current settings and
+/// return the process filter and top-N limit to apply, or `None` once a
+/// newer subscription has replaced `generation`.
+///
+/// The interval counts from `last`; changing it takes effect immediately.
+/// While paused nothing is due, and `resumed` is set so the caller can
+/// restart its CPU baseline rather than average over the pause.
+fn wait_for_telemetry_tick(
+    generation: u64,
+    last: std::time::Instant,
+    resumed: &mut bool,
+) -> Option<(TelemetryProcessFilter, Option<TopProcesses>)> {
+    let mut state = TELEMETRY_CONTROL.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        if state.generation != generation {
+            return None;
+        }
+        if state.paused {
+            *resumed = true;
+            state = TELEMETRY_WAKE
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+            continue;
+        }
+        if *resumed {
+            return Some((state.filter.clone(), state.top));
+        }
+        let due = last + state.interval;
+        let now = std::time::Instant::now();
+        if now >= due {
+            return Some((state.filter.clone(), state.top));
+        }
+        state = TELEMETRY_WAKE
+            .wait_timeout(state, due - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
+
+/// Streams telemetry data to the host until the connection drops or a
+/// newer subscription replaces this one.
 ///
 /// All outgoing `TelemetryData` frames carry `request_id` so the host
 /// demultiplexer routes them back to the subscriber's stream receiver.
-fn telemetry_stream_loop(fd: RawFd, request_id: u32, opts: &TelemetrySubscribeRequest) {
-    let interval = std::time::Duration::from_millis(opts.interval_ms.max(100)); // floor at 100ms
+fn telemetry_stream_loop(
+    fd: RawFd,
+    request_id: u32,
+    opts: &TelemetrySubscribeRequest,
+    generation: u64,
+) {
     let mut seq: u64 = 0;
     let mut prev_cpu = read_cpu_jiffies();
+    let mut prev_proc_jiffies: HashMap<u32, u64> = HashMap::new();
+    let mut last = std::time::Instant::now();
 
     loop {
-        std::thread::sleep(interval);
+        let mut resumed = false;
+        let Some((filter, top)) = wait_for_telemetry_tick(generation, last, &mut resumed) else {
+            kmsg("Telemetry subscription ended (replaced)");
+            return;
+        };
+        let elapsed = last.elapsed();
+        last = std::time::Instant::now();
+        if resumed {
+            // Start a fresh interval so the first batch after a pause
+            // reports CPU for that interval, not the paused stretch.
+            prev_cpu = read_cpu_jiffies();
+            prev_proc_jiffies.clear();
+            continue;
+        }
 
         let curr_cpu = read_cpu_jiffies();
         let cpu_percent = compute_cpu_percent(&prev_cpu, &curr_cpu);
@@ -3129,7 +4551,16 @@ fn telemetry_stream_loop(fd: RawFd, request_id: u32, opts: &TelemetrySubscribeRe
         let (net_rx_bytes, net_tx_bytes) = read_netdev();
         let procs_running = read_procs_running();
         let open_fds = read_open_fds();
-        let processes = collect_process_metrics(opts.include_kernel_threads);
+        let disks = read_disk_usage();
+        let mut processes = collect_process_metrics(opts.include_kernel_threads);
+        // Deltas span every process, so a later filter or top-N change
+        // does not reset the baseline of the processes it brings back.
+        fill_process_cpu_percent(&mut processes, &prev_proc_jiffies, elapsed);
+        prev_proc_jiffies = processes.iter().map(|p| (p.pid, p.cpu_jiffies)).collect();
+        processes.retain(|process| filter.matches(process));
+        if let Some(top) = top {
+            top.apply(&mut processes);
+        }
 
         let batch = TelemetryBatch {
             seq,
@@ -3142,6 +4573,7 @@ fn telemetry_stream_loop(fd: RawFd, request_id: u32, opts: &TelemetrySubscribeRe
                 net_tx_bytes,
                 procs_running,
                 open_fds,
+                disks,
             }),
             processes,
             trace_context: None,
@@ -3208,6 +4640,35 @@ fn compute_cpu_percent(prev: &CpuJiffies, curr: &CpuJiffies) -> f64 {
     (busy_delta as f64 / total_delta as f64) * 100.0
 }
 
+/// Set each process's `cpu_percent` from its jiffies since `prev` over
+/// `elapsed`. Processes absent from `prev` (new, or the first batch) stay
+/// at zero.
+fn fill_process_cpu_percent(
+    processes: &mut [ProcessMetrics],
+    prev: &HashMap<u32, u64>,
+    elapsed: std::time::Duration,
+) {
+    let ticks = elapsed.as_secs_f64() * clock_ticks_per_sec();
+    if ticks <= 0.0 {
+        return;
+    }
+    for process in processes {
+        if let Some(&prev_jiffies) = prev.get(&process.pid) {
+            let delta = process.cpu_jiffies.saturating_sub(prev_jiffies);
+            process.cpu_percent = delta as f64 / ticks * 100.0;
+        }
+    }
+}
+
+fn clock_ticks_per_sec() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks <= 0 {
+        100.0
+    } else {
+        ticks as f64
+    }
+}
+
 /// Read memory info from /proc/meminfo. Returns (used_bytes, total_bytes).
 fn read_meminfo() -> (u64, u64) {
     let content = match std::fs::read_to_string("/proc/meminfo") {
@@ -3228,8 +4689,21 @@ fn read_meminfo() -> (u64, u64) {
     (used, total)
 }
 
+/// Read the kernel's cumulative OOM kill count from /proc/vmstat.
+fn read_oom_kill_count() -> Option<u64> {
+    parse_oom_kill_count(&std::fs::read_to_string("/proc/vmstat").ok()?)
+}
+
+/// Extract the `oom_kill` counter from /proc/vmstat content.
+fn parse_oom_kill_count(vmstat: &str) -> Option<u64> {
+    vmstat
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
 /// Parse a /proc/meminfo value line like "    12345 kB" -> 12345
-fn parse_meminfo_value(s: &str) -> u64 {
+pub(crate) fn parse_meminfo_value(s: &str) -> u64 {
     s.split_whitespace()
         .next()
         .and_then(|v| v.parse::<u64>().ok())
@@ -3279,6 +4753,47 @@ fn read_open_fds() -> u32 {
     parse_open_fds(&content)
 }
 
+/// Filesystems reported in `SystemMetrics::disks`, besides the overlay
+/// upper layer.
+const DISK_USAGE_MOUNTS: &[&str] = &["/", "/workspace"];
+
+/// Space usage of `/`, `/workspace`, and the overlay upper layer. Paths
+/// that don't exist are skipped.
+fn read_disk_usage() -> Vec<DiskUsage> {
+    let mut disks: Vec<DiskUsage> = DISK_USAGE_MOUNTS
+        .iter()
+        .filter_map(|mount| {
+            let path = std::ffi::CString::new(*mount).ok()?;
+            let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+            if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+                return None;
+            }
+            Some(disk_usage_from_statvfs(mount, &st))
+        })
+        .collect();
+
+    // Writes to an OCI rootfs land in the overlay's tmpfs upper layer,
+    // which is only reachable through the fd kept from before pivot_root.
+    if let Some(upper) = fs_diff::overlay_upper_fd() {
+        use std::os::fd::AsRawFd;
+        let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstatvfs(upper.as_raw_fd(), &mut st) } == 0 {
+            disks.push(disk_usage_from_statvfs(OVERLAY_UPPER_DISK, &st));
+        }
+    }
+    disks
+}
+
+fn disk_usage_from_statvfs(mount: &str, st: &libc::statvfs) -> DiskUsage {
+    let block = st.f_frsize;
+    DiskUsage {
+        mount: mount.to_string(),
+        used_bytes: st.f_blocks.saturating_sub(st.f_bfree) * block,
+        total_bytes: st.f_blocks * block,
+        available_bytes: st.f_bavail * block,
+    }
+}
+
 /// Collect per-process metrics by scanning /proc/[0-9]*/.
 ///
 /// When `include_kernel_threads` is false, kernel threads are filtered out.
@@ -3304,11 +4819,9 @@ fn collect_process_metrics(include_kernel_threads: bool) -> Vec<ProcessMetrics>
         let base = format!("/proc/{}", pid);
 
         // Filter kernel threads: they have an empty /proc/PID/cmdline
-        if !include_kernel_threads {
-            let cmdline = std::fs::read(format!("{}/cmdline", base)).unwrap_or_default();
-            if cmdline.is_empty() {
-                continue;
-            }
+        let cmdline = std::fs::read(format!("{}/cmdline", base)).unwrap_or_default();
+        if !include_kernel_threads && cmdline.is_empty() {
+            continue;
         }
 
         // Read comm
@@ -3331,8 +4844,8 @@ fn collect_process_metrics(include_kernel_threads: bool) -> Vec<ProcessMetrics>
             .unwrap_or(0)
             * page_size;
 
-        // Read state and cpu jiffies from stat
-        let (state, cpu_jiffies) = read_proc_stat_fields(&base);
+        // Read state, parent and cpu jiffies from stat
+        let (state, ppid, cpu_jiffies) = read_proc_stat_fields(&base);
 
         processes.push(ProcessMetrics {
             pid,
@@ -3340,31 +4853,58 @@ fn collect_process_metrics(include_kernel_threads: bool) -> Vec<ProcessMetrics>
             rss_bytes,
             cpu_jiffies,
             state,
+            ppid,
+            cmdline: format_cmdline(&cmdline),
+            cpu_percent: 0.0,
         });
     }
 
     processes
 }
 
-/// Read process state and CPU jiffies (utime + stime) from /proc/PID/stat.
-fn read_proc_stat_fields(base: &str) -> (char, u64) {
+/// Join the NUL-separated arguments of /proc/PID/cmdline with spaces, cut
+/// to [`MAX_PROCESS_CMDLINE`] bytes on a character boundary.
+fn format_cmdline(raw: &[u8]) -> String {
+    let args: Vec<_> = raw
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+    let mut cmdline = args.join(" ");
+    if cmdline.len() > MAX_PROCESS_CMDLINE {
+        let mut end = MAX_PROCESS_CMDLINE;
+        while !cmdline.is_char_boundary(end) {
+            end -= 1;
+        }
+        cmdline.truncate(end);
+    }
+    cmdline
+}
+
+/// Read process state, parent PID and CPU jiffies (utime + stime) from
+/// /proc/PID/stat.
+pub(crate) fn read_proc_stat_fields(base: &str) -> (char, u32, u64) {
     let content = match std::fs::read_to_string(format!("{}/stat", base)) {
         Ok(c) => c,
-        Err(_) => return ('?', 0),
+        Err(_) => return ('?', 0, 0),
     };
     parse_proc_stat_fields_content(&content)
 }
 
-fn parse_proc_stat_fields_content(content: &str) -> (char, u64) {
-    // /proc/PID/stat format: pid (comm) state ... utime(14) stime(15) ...
+fn parse_proc_stat_fields_content(content: &str) -> (char, u32, u64) {
+    // /proc/PID/stat format: pid (comm) state ppid ... utime(14) stime(15) ...
     // Find the closing ')' to skip the comm field (which may contain spaces/parens)
     let after_comm = match content.rfind(')') {
         Some(pos) => &content[pos + 1..],
-        None => return ('?', 0),
+        None => return ('?', 0, 0),
     };
     let fields: Vec<&str> = after_comm.split_whitespace().collect();
-    // fields[0] = state, fields[11] = utime, fields[12] = stime
+    // fields[0] = state, fields[1] = ppid, fields[11] = utime, fields[12] = stime
     let state = fields.first().and_then(|s| s.chars().next()).unwrap_or('?');
+    let ppid = fields
+        .get(1)
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0);
     let utime = fields
         .get(11)
         .and_then(|v| v.parse::<u64>().ok())
@@ -3373,7 +4913,7 @@ fn parse_proc_stat_fields_content(content: &str) -> (char, u64) {
         .get(12)
         .and_then(|v| v.parse::<u64>().ok())
         .unwrap_or(0);
-    (state, utime + stime)
+    (state, ppid, utime + stime)
 }
 
 fn parse_procs_running(content: &str) -> u32 {
@@ -3418,18 +4958,98 @@ mod tests {
     #[test]
     fn test_parse_proc_stat_fields_content_ok() {
         let line = "1234 (my(proc) name) S 1 2 3 4 5 6 7 8 9 10 100 200 0 0 0 0\n";
-        let (state, jiffies) = parse_proc_stat_fields_content(line);
+        let (state, ppid, jiffies) = parse_proc_stat_fields_content(line);
         assert_eq!(state, 'S');
+        assert_eq!(ppid, 1);
         assert_eq!(jiffies, 300);
     }
 
     #[test]
     fn test_parse_proc_stat_fields_content_malformed() {
-        let (state, jiffies) = parse_proc_stat_fields_content("not-a-valid-stat-line");
+        let (state, ppid, jiffies) = parse_proc_stat_fields_content("not-a-valid-stat-line");
         assert_eq!(state, '?');
+        assert_eq!(ppid, 0);
         assert_eq!(jiffies, 0);
     }
 
+    #[test]
+    fn test_format_cmdline_joins_and_truncates() {
+        assert_eq!(format_cmdline(b"sh\0-c\0echo hi\0"), "sh -c echo hi");
+        let long = "\u{e9}".repeat(MAX_PROCESS_CMDLINE);
+        let cmdline = format_cmdline(long.as_bytes());
+        assert!(cmdline.len() <= MAX_PROCESS_CMDLINE);
+        assert!(long.starts_with(&cmdline));
+    }
+
+    #[test]
+    fn test_fill_process_cpu_percent_uses_deltas() {
+        let ticks = clock_ticks_per_sec() as u64;
+        let mut processes = vec![
+            ProcessMetrics {
+                pid: 1,
+                cpu_jiffies: 100 + ticks / 2,
+                ..Default::default()
+            },
+            ProcessMetrics {
+                pid: 2,
+                cpu_jiffies: 500,
+                ..Default::default()
+            },
+        ];
+        let prev = HashMap::from([(1, 100)]);
+        fill_process_cpu_percent(&mut processes, &prev, std::time::Duration::from_secs(1));
+        assert!((processes[0].cpu_percent - 50.0).abs() < 1.0);
+        assert_eq!(processes[1].cpu_percent, 0.0);
+    }
+
+    #[test]
+    fn test_wait_with_usage_reports_exit_and_rusage() {
+        // Reaped by wait_with_usage below, not by `Child::wait`.
+        let pid = Command::new("sh")
+            .args([
+                "-c",
+                "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 3",
+            ])
+            .spawn()
+            .unwrap()
+            .id() as i32;
+        let (status, usage) = wait_with_usage(pid).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(usage.max_rss_bytes > 0);
+        assert_eq!(usage.max_rss_bytes % 1024, 0);
+        assert!(wait_with_usage(pid).is_err());
+    }
+
+    #[test]
+    fn test_parse_oom_kill_count() {
+        let vmstat = "nr_free_pages 1024\noom_kill 3\nnr_zone_active_anon 7\n";
+        assert_eq!(parse_oom_kill_count(vmstat), Some(3));
+        assert_eq!(parse_oom_kill_count("nr_free_pages 1024\n"), None);
+    }
+
+    #[test]
+    fn test_resolv_conf_from_cmdline_dns() {
+        let servers =
+            dns_servers_from_cmdline("console=hvc0 voidbox.dns=1.1.1.1,bogus,9.9.9.9 panic=1");
+        assert_eq!(
+            resolv_conf_for(&servers),
+            "nameserver 1.1.1.1\nnameserver 9.9.9.9\n"
+        );
+        assert_eq!(
+            resolv_conf_for(&dns_servers_from_cmdline("console=hvc0")),
+            "nameserver 8.8.8.8\n"
+        );
+    }
+
+    #[test]
+    fn test_read_disk_usage_reports_root() {
+        let disks = read_disk_usage();
+        let root = disks.iter().find(|d| d.mount == "/").unwrap();
+        assert!(root.total_bytes > 0);
+        assert!(root.used_bytes <= root.total_bytes);
+        assert!(root.available_bytes <= root.total_bytes);
+    }
+
     #[test]
     fn test_parse_procs_running() {
         let content = "cpu  1 2 3 4 5 6 7 8\nprocs_running 9\n";
@@ -3449,6 +5069,14 @@ mod tests {
     // root fds; the symlink/`..`/outside-root negative cases sit
     // alongside the helper they exercise.
 
+    #[test]
+    fn test_partial_write_name_is_hidden_sibling() {
+        assert_eq!(
+            partial_write_name(OsStr::new("model.bin")),
+            OsString::from(".model.bin.voidbox-partial")
+        );
+    }
+
     #[test]
     fn test_page_size_bytes_positive() {
         assert!(page_size_bytes() > 0);
@@ -3555,6 +5183,20 @@ mod tests {
         assert_eq!(mounts[0], ("tag0".into(), "/mnt/share".into(), true));
     }
 
+    #[test]
+    fn test_parse_data_disk_entries() {
+        let cmdline = "quiet voidbox.disk0=/dev/vdb:/scratch:rw voidbox.disk2=/dev/vdd:/data \
+                       voidbox.mount0=mount0:/workspace:rw";
+        let disks = parse_data_disk_entries_from(cmdline);
+        assert_eq!(
+            disks,
+            vec![
+                ("/dev/vdb".into(), "/scratch".into(), false),
+                ("/dev/vdd".into(), "/data".into(), true),
+            ]
+        );
+    }
+
     #[test]
     fn test_try_mount_9p_virtiofs_returns_err_without_device() {
         // Outside a VM, there's no virtio device — both virtiofs and 9p should
@@ -488,6 +488,9 @@ fn pty_reader_thread(vsock_fd: RawFd, master_fd: RawFd, close_requested: Arc<Ato
             | MessageType::SubscribeTelemetry
             | MessageType::WriteFile
             | MessageType::WriteFileResponse
+            | MessageType::WriteFileChunk
+            | MessageType::WriteFileChunkResponse
+            | MessageType::WriteFileFinalize
             | MessageType::MkdirP
             | MessageType::MkdirPResponse
             | MessageType::ExecOutputChunk
@@ -497,6 +500,38 @@ fn pty_reader_thread(vsock_fd: RawFd, master_fd: RawFd, close_requested: Arc<Ato
             | MessageType::ReadFileResponse
             | MessageType::FileStat
             | MessageType::FileStatResponse
+            | MessageType::FsDiff
+            | MessageType::FsDiffResponse
+            | MessageType::ExportWorkspace
+            | MessageType::ExportWorkspaceChunk
+            | MessageType::ExportWorkspaceResponse
+            | MessageType::ShutdownAck
+            | MessageType::SignalExec
+            | MessageType::SignalExecResponse
+            | MessageType::SetExecPolicy
+            | MessageType::SetExecPolicyResponse
+            | MessageType::EnterReadOnly
+            | MessageType::EnterReadOnlyResponse
+            | MessageType::CreateUser
+            | MessageType::CreateUserResponse
+            | MessageType::SyncClock
+            | MessageType::SyncClockResponse
+            | MessageType::ExecStdinChunk
+            | MessageType::ExecStdinClose
+            | MessageType::ExecStdinResponse
+            | MessageType::Symlink
+            | MessageType::SymlinkResponse
+            | MessageType::Chmod
+            | MessageType::ChmodResponse
+            | MessageType::ReadLink
+            | MessageType::ReadLinkResponse
+            | MessageType::HashFiles
+            | MessageType::HashFilesResponse
+            | MessageType::SysInfo
+            | MessageType::SysInfoResponse
+            | MessageType::TelemetryControl
+            | MessageType::TelemetryControlResponse
+            | MessageType::AttachExec
             | MessageType::PtyOpen
             | MessageType::PtyOpened
             | MessageType::PtyClosed => {}
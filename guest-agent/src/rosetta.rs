@@ -0,0 +1,110 @@
+//! Running x86_64 binaries through Rosetta.
+//!
+//! On Apple silicon, a host that boots with `voidbox.rosetta=1`
+//! (`SandboxBuilder::rosetta`) shares Apple's Rosetta translator over
+//! virtiofs. The agent mounts it and registers it with binfmt_misc as the
+//! interpreter for x86-64 ELF binaries. The registration uses the `F`
+//! flag, so the kernel opens the translator once, here, and binaries in an
+//! OCI rootfs entered later are translated too.
+
+use void_box_protocol::{ROSETTA_CMDLINE_KEY, ROSETTA_SHARE_TAG};
+
+use crate::kmsg;
+
+/// Where the Rosetta share is mounted.
+const MOUNT_POINT: &str = "/run/rosetta";
+
+const BINFMT_MISC: &str = "/proc/sys/fs/binfmt_misc";
+
+/// Magic and mask matching the ELF header of x86-64 executables and shared
+/// objects, as documented by Apple for Rosetta in Linux VMs.
+const X86_64_ELF_MAGIC: &str =
+    r"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x3e\x00";
+const X86_64_ELF_MASK: &str =
+    r"\xff\xff\xff\xff\xff\xfe\xfe\x00\xff\xff\xff\xff\xff\xff\xff\xff\xfe\xff\xff\xff";
+
+/// Mount Rosetta and register it if the cmdline asks for it.
+pub(crate) fn setup_from_cmdline() {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    if !requested(&cmdline) {
+        return;
+    }
+    match setup() {
+        Ok(()) => kmsg("Rosetta registered for x86_64 binaries"),
+        Err(e) => kmsg(&format!("WARNING: Rosetta setup failed: {}", e)),
+    }
+}
+
+fn setup() -> Result<(), String> {
+    std::fs::create_dir_all(MOUNT_POINT).map_err(|e| format!("create {}: {}", MOUNT_POINT, e))?;
+    crate::try_mount_9p_virtiofs(ROSETTA_SHARE_TAG, MOUNT_POINT, true)?;
+
+    let register = format!("{BINFMT_MISC}/register");
+    if !std::path::Path::new(&register).exists() {
+        mount_binfmt_misc()?;
+    }
+    if std::path::Path::new(&format!("{BINFMT_MISC}/rosetta")).exists() {
+        return Ok(());
+    }
+    std::fs::write(&register, registration(&format!("{MOUNT_POINT}/rosetta")))
+        .map_err(|e| format!("register with binfmt_misc: {}", e))
+}
+
+fn mount_binfmt_misc() -> Result<(), String> {
+    let source = std::ffi::CString::new("binfmt_misc").unwrap();
+    let target = std::ffi::CString::new(BINFMT_MISC).unwrap();
+    let ret = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            source.as_ptr(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "mount binfmt_misc: {}",
+            std::io::Error::last_os_error()
+        ))
+    }
+}
+
+/// Whether the cmdline carries `voidbox.rosetta=1`.
+fn requested(cmdline: &str) -> bool {
+    cmdline
+        .split_whitespace()
+        .any(|token| token.strip_prefix(ROSETTA_CMDLINE_KEY) == Some("1"))
+}
+
+/// The binfmt_misc registration line for `interpreter`: open the
+/// interpreter now (`F`) and take credentials from the binary rather than
+/// the interpreter (`C`).
+fn registration(interpreter: &str) -> String {
+    format!(":rosetta:M::{X86_64_ELF_MAGIC}:{X86_64_ELF_MASK}:{interpreter}:CF")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rosetta_is_requested_by_the_cmdline_flag() {
+        assert!(requested("console=hvc0 voidbox.rosetta=1 panic=1"));
+        assert!(!requested("console=hvc0 voidbox.rosetta=0"));
+        assert!(!requested("console=hvc0"));
+
+        let line = registration("/run/rosetta/rosetta");
+        let fields: Vec<_> = line.split(':').collect();
+        assert_eq!(fields[1], "rosetta");
+        assert_eq!(fields[2], "M");
+        assert_eq!(fields[6], "/run/rosetta/rosetta");
+        assert_eq!(fields[7], "CF");
+        // Magic and mask cover the same 20 header bytes; three of the
+        // magic's are the literal `ELF`.
+        assert_eq!(fields[4].matches(r"\x").count() + 3, 20);
+        assert_eq!(fields[5].matches(r"\x").count(), 20);
+    }
+}
@@ -0,0 +1,121 @@
+//! Graceful guest shutdown.
+//!
+//! The agent is PID 1, so `kill(-1, ..)` reaches every other process in the
+//! guest. Workloads get SIGTERM and a grace period, stragglers get SIGKILL,
+//! and dirty page cache is written back before the host is told the guest
+//! is safe to power off.
+
+use std::time::{Duration, Instant};
+
+use void_box_protocol::{ShutdownAck, ShutdownRequest, DEFAULT_SHUTDOWN_GRACE_MS};
+
+/// How often the process table is rescanned during the grace period.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Stop every process and sync filesystems. The caller sends the returned
+/// ack and then calls [`power_off`] or [`restart`].
+pub(crate) fn shutdown(request: &ShutdownRequest) -> ShutdownAck {
+    let started = Instant::now();
+    let grace = Duration::from_millis(request.grace_ms.unwrap_or(DEFAULT_SHUTDOWN_GRACE_MS));
+
+    let processes_terminated = count_user_processes();
+    let mut processes_killed = 0;
+    if processes_terminated > 0 {
+        unsafe { libc::kill(-1, libc::SIGTERM) };
+        let deadline = Instant::now() + grace;
+        while count_user_processes() > 0 && Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        processes_killed = count_user_processes();
+        if processes_killed > 0 {
+            unsafe { libc::kill(-1, libc::SIGKILL) };
+        }
+    }
+
+    let bytes_flushed = std::fs::read_to_string("/proc/meminfo")
+        .map(|meminfo| parse_pending_writeback(&meminfo))
+        .unwrap_or(0);
+    unsafe { libc::sync() };
+
+    ShutdownAck {
+        processes_terminated,
+        processes_killed,
+        bytes_flushed,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Power the guest off. Never returns on success.
+///
+/// x86 guests have no ACPI, so `POWER_OFF` would only halt the CPUs; a
+/// restart goes out through the keyboard controller (`reboot=k`), which the
+/// VMM treats as the guest leaving. On arm64 both reach the VMM as PSCI
+/// system events.
+pub(crate) fn power_off() {
+    #[cfg(target_arch = "x86_64")]
+    let cmd = libc::LINUX_REBOOT_CMD_RESTART;
+    #[cfg(not(target_arch = "x86_64"))]
+    let cmd = libc::LINUX_REBOOT_CMD_POWER_OFF;
+    unsafe {
+        libc::reboot(cmd);
+    }
+}
+
+/// Restart the guest kernel. Never returns on success.
+///
+/// Reaches the VMM the same way an x86 [`power_off`] does; the host only
+/// boots the guest again when it asked for the reboot.
+pub(crate) fn restart() {
+    unsafe {
+        libc::reboot(libc::LINUX_REBOOT_CMD_RESTART);
+    }
+}
+
+/// Live processes other than PID 1 and kernel threads. Zombies are not
+/// counted: they have already exited and hold nothing to flush.
+fn count_user_processes() -> u32 {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| pid != 1)
+        .filter(|pid| {
+            let base = format!("/proc/{}", pid);
+            // Kernel threads have an empty cmdline; zombies do too.
+            let has_cmdline = std::fs::read(format!("{}/cmdline", base))
+                .map(|c| !c.is_empty())
+                .unwrap_or(false);
+            has_cmdline && crate::read_proc_stat_fields(&base).0 != 'Z'
+        })
+        .count() as u32
+}
+
+/// Bytes of page cache that `sync` has to write back: `Dirty` plus
+/// `Writeback` from /proc/meminfo.
+fn parse_pending_writeback(meminfo: &str) -> u64 {
+    meminfo
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("Dirty:")
+                .or_else(|| line.strip_prefix("Writeback:"))
+        })
+        .map(|kb| crate::parse_meminfo_value(kb) * 1024)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pending_writeback() {
+        let meminfo = "MemTotal:        1024000 kB\n\
+                       Dirty:               12 kB\n\
+                       Writeback:            4 kB\n\
+                       WritebackTmp:         8 kB\n";
+        assert_eq!(parse_pending_writeback(meminfo), 16 * 1024);
+        assert_eq!(parse_pending_writeback("MemTotal: 1 kB\n"), 0);
+    }
+}
@@ -0,0 +1,81 @@
+//! Signals for running execs.
+//!
+//! A host that sets [`ExecRequest::exec_id`](void_box_protocol::ExecRequest::exec_id)
+//! can stop, resume, interrupt, terminate or kill the command while it
+//! runs, e.g. to hold an agent while a human approves its next tool call
+//! or to shut down a dev server gracefully. Every exec leads its own
+//! process group, so the signal reaches the processes it spawned too.
+//! Several running execs may share an id, e.g. all of a workflow step's, and
+//! a signal reaches them all.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use void_box_protocol::{ExecSignal, SignalExecRequest, SignalExecResponse};
+
+/// Process groups of running, signallable execs by exec id.
+static RUNNING: Mutex<BTreeMap<String, Vec<i32>>> = Mutex::new(BTreeMap::new());
+
+/// Keeps an exec addressable until dropped, which must happen once the
+/// child is reaped and its process group id may be reused.
+pub(crate) struct Registration {
+    exec_id: String,
+    pgid: i32,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Ok(mut running) = RUNNING.lock() {
+            if let Some(pgids) = running.get_mut(&self.exec_id) {
+                pgids.retain(|&pgid| pgid != self.pgid);
+                if pgids.is_empty() {
+                    running.remove(&self.exec_id);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn register(exec_id: &str, pgid: i32) -> Registration {
+    if let Ok(mut running) = RUNNING.lock() {
+        running.entry(exec_id.to_string()).or_default().push(pgid);
+    }
+    Registration {
+        exec_id: exec_id.to_string(),
+        pgid,
+    }
+}
+
+pub(crate) fn signal(request: &SignalExecRequest) -> SignalExecResponse {
+    let pgids = RUNNING
+        .lock()
+        .ok()
+        .and_then(|running| running.get(&request.exec_id).cloned())
+        .unwrap_or_default();
+    if pgids.is_empty() {
+        return SignalExecResponse {
+            delivered: false,
+            error: Some(format!("no running exec with id {}", request.exec_id)),
+        };
+    }
+    let signal = match request.signal {
+        ExecSignal::Stop => libc::SIGSTOP,
+        ExecSignal::Continue => libc::SIGCONT,
+        ExecSignal::Kill => libc::SIGKILL,
+        ExecSignal::Interrupt => libc::SIGINT,
+        ExecSignal::Terminate => libc::SIGTERM,
+    };
+    let mut error = None;
+    let mut delivered = false;
+    for pgid in pgids {
+        if unsafe { libc::killpg(pgid, signal) } == 0 {
+            delivered = true;
+        } else {
+            error = Some(std::io::Error::last_os_error().to_string());
+        }
+    }
+    SignalExecResponse {
+        delivered,
+        error: if delivered { None } else { error },
+    }
+}
@@ -0,0 +1,160 @@
+//! Streamed stdin for running execs.
+//!
+//! An exec started with [`ExecRequest::stdin_stream`](void_box_protocol::ExecRequest::stdin_stream)
+//! keeps its stdin pipe open, and the host feeds it with
+//! [`ExecStdinChunk`]s until an [`ExecStdinClose`]. Each chunk is answered
+//! once it is in the pipe, so a command that reads slowly slows the host
+//! down rather than piling data up in the agent.
+//!
+//! The host may send the first chunk before the exec thread has spawned
+//! the command, so a chunk for an unknown exec id waits for it to appear.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::process::ChildStdin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use void_box_protocol::{ExecStdinChunk, ExecStdinClose, ExecStdinResponse};
+
+/// How long a chunk waits for its exec to be spawned; covers the OCI
+/// rootfs gate in `execute_command`.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Exited execs remembered so that late chunks fail fast.
+const FINISHED_CAPACITY: usize = 64;
+
+type Pipe = Arc<Mutex<Option<ChildStdin>>>;
+
+struct Pipes {
+    open: BTreeMap<String, Pipe>,
+    finished: VecDeque<String>,
+}
+
+static PIPES: Mutex<Pipes> = Mutex::new(Pipes {
+    open: BTreeMap::new(),
+    finished: VecDeque::new(),
+});
+static ATTACHED: Condvar = Condvar::new();
+
+fn pipes() -> MutexGuard<'static, Pipes> {
+    PIPES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Keeps an exec's stdin addressable until dropped, once the child has
+/// exited.
+pub(crate) struct Registration {
+    exec_id: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut pipes = pipes();
+        pipes.open.remove(&self.exec_id);
+        if pipes.finished.len() == FINISHED_CAPACITY {
+            pipes.finished.pop_front();
+        }
+        pipes.finished.push_back(self.exec_id.clone());
+        ATTACHED.notify_all();
+    }
+}
+
+pub(crate) fn register(exec_id: &str, stdin: ChildStdin) -> Registration {
+    let mut pipes = pipes();
+    pipes.finished.retain(|id| id != exec_id);
+    pipes
+        .open
+        .insert(exec_id.to_string(), Arc::new(Mutex::new(Some(stdin))));
+    ATTACHED.notify_all();
+    Registration {
+        exec_id: exec_id.to_string(),
+    }
+}
+
+/// The pipe of `exec_id`, waiting for it to be registered. `Ok(None)` if
+/// the exec has already exited.
+fn lookup(exec_id: &str) -> Result<Option<Pipe>, String> {
+    let (pipes, _) = ATTACHED
+        .wait_timeout_while(pipes(), ATTACH_TIMEOUT, |pipes| {
+            !pipes.open.contains_key(exec_id) && !pipes.finished.iter().any(|id| id == exec_id)
+        })
+        .unwrap_or_else(|e| e.into_inner());
+    match pipes.open.get(exec_id) {
+        Some(pipe) => Ok(Some(pipe.clone())),
+        None if pipes.finished.iter().any(|id| id == exec_id) => Ok(None),
+        None => Err(format!("no running exec with id {exec_id}")),
+    }
+}
+
+pub(crate) fn write(request: &ExecStdinChunk) -> ExecStdinResponse {
+    let error = match lookup(&request.exec_id) {
+        Ok(Some(pipe)) => match pipe.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(stdin) => stdin.write_all(&request.data).err().map(|e| e.to_string()),
+            None => Some(format!("stdin of exec {} is closed", request.exec_id)),
+        },
+        Ok(None) => Some(format!("exec {} has exited", request.exec_id)),
+        Err(e) => Some(e),
+    };
+    ExecStdinResponse { error }
+}
+
+/// Close the stdin of `exec_id`. Closing twice, or after the command
+/// exited, is not an error.
+pub(crate) fn close(request: &ExecStdinClose) -> ExecStdinResponse {
+    let error = match lookup(&request.exec_id) {
+        Ok(Some(pipe)) => {
+            pipe.lock().unwrap_or_else(|e| e.into_inner()).take();
+            None
+        }
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+    ExecStdinResponse { error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn chunks_reach_the_command_until_closed() {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let registration = register("stdin-test", child.stdin.take().unwrap());
+
+        for data in [&b"hello "[..], b"world"] {
+            let chunk = ExecStdinChunk {
+                exec_id: "stdin-test".into(),
+                data: data.to_vec(),
+            };
+            assert!(write(&chunk).error.is_none());
+        }
+        let close_request = ExecStdinClose {
+            exec_id: "stdin-test".into(),
+        };
+        assert!(close(&close_request).error.is_none());
+
+        let mut stdout = String::new();
+        child
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        child.wait().unwrap();
+        assert_eq!(stdout, "hello world");
+
+        drop(registration);
+        let late = ExecStdinChunk {
+            exec_id: "stdin-test".into(),
+            data: b"late".to_vec(),
+        };
+        assert!(write(&late).error.unwrap().contains("has exited"));
+        assert!(close(&close_request).error.is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! One-shot system snapshot for the `SysInfo` RPC.
+//!
+//! Reads the same procfs files a user would `cat` one exec at a time and
+//! returns them parsed. Every source is optional: a file that cannot be read
+//! leaves its fields at zero rather than failing the whole request, since a
+//! partial snapshot is still more useful than none.
+
+use void_box_protocol::{MemoryInfo, MountInfo, SysInfo, SysInfoResponse};
+
+/// Handle a SysInfo request.
+pub(crate) fn handle_sysinfo() -> SysInfoResponse {
+    SysInfoResponse {
+        success: true,
+        info: Some(collect()),
+        error: None,
+    }
+}
+
+fn collect() -> SysInfo {
+    let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+    SysInfo {
+        kernel_version: read("/proc/sys/kernel/osrelease").trim().to_string(),
+        uptime_secs: parse_uptime(&read("/proc/uptime")),
+        load_average: parse_loadavg(&read("/proc/loadavg")),
+        cpus: std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(0),
+        memory: parse_meminfo(&read("/proc/meminfo")),
+        mounts: parse_mounts(&read("/proc/mounts")),
+    }
+}
+
+/// First field of /proc/uptime: seconds since boot.
+fn parse_uptime(content: &str) -> f64 {
+    content
+        .split_whitespace()
+        .next()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// First three fields of /proc/loadavg.
+fn parse_loadavg(content: &str) -> [f64; 3] {
+    let mut load = [0.0; 3];
+    for (slot, field) in load.iter_mut().zip(content.split_whitespace()) {
+        *slot = field.parse().unwrap_or(0.0);
+    }
+    load
+}
+
+fn parse_meminfo(content: &str) -> MemoryInfo {
+    let mut memory = MemoryInfo::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let field = match key {
+            "MemTotal" => &mut memory.total_bytes,
+            "MemFree" => &mut memory.free_bytes,
+            "MemAvailable" => &mut memory.available_bytes,
+            "Buffers" => &mut memory.buffers_bytes,
+            "Cached" => &mut memory.cached_bytes,
+            "Shmem" => &mut memory.shmem_bytes,
+            "SwapTotal" => &mut memory.swap_total_bytes,
+            "SwapFree" => &mut memory.swap_free_bytes,
+            _ => continue,
+        };
+        *field = crate::parse_meminfo_value(value) * 1024;
+    }
+    memory
+}
+
+fn parse_mounts(content: &str) -> Vec<MountInfo> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(MountInfo {
+                source: unescape_mount_field(fields.next()?),
+                target: unescape_mount_field(fields.next()?),
+                fs_type: fields.next()?.to_string(),
+                options: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Undo the kernel's octal escaping of space, tab, newline and backslash
+/// (`\040` and friends) in /proc/mounts fields.
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 4])
+                .ok()
+                .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+            {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uptime_and_loadavg() {
+        assert_eq!(parse_uptime("1234.56 4321.00\n"), 1234.56);
+        assert_eq!(parse_uptime(""), 0.0);
+        assert_eq!(
+            parse_loadavg("0.52 0.31 0.12 2/118 431\n"),
+            [0.52, 0.31, 0.12]
+        );
+    }
+
+    #[test]
+    fn test_parse_meminfo_breakdown() {
+        let memory = parse_meminfo(
+            "MemTotal:        2000 kB\nMemFree:          500 kB\nMemAvailable:    1500 kB\n\
+             Buffers:           10 kB\nCached:           700 kB\nSwapCached:         0 kB\n\
+             Shmem:            300 kB\nSwapTotal:          0 kB\nSwapFree:           0 kB\n",
+        );
+        assert_eq!(memory.total_bytes, 2000 * 1024);
+        assert_eq!(memory.free_bytes, 500 * 1024);
+        assert_eq!(memory.available_bytes, 1500 * 1024);
+        assert_eq!(memory.buffers_bytes, 10 * 1024);
+        assert_eq!(memory.cached_bytes, 700 * 1024);
+        assert_eq!(memory.shmem_bytes, 300 * 1024);
+        assert_eq!(memory.swap_total_bytes, 0);
+    }
+
+    #[test]
+    fn test_parse_mounts_unescapes_paths() {
+        let mounts = parse_mounts(
+            "/dev/vda / ext4 ro,relatime 0 0\n\
+             mount0 /mnt/my\\040dir virtiofs rw,relatime 0 0\n",
+        );
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].source, "/dev/vda");
+        assert!(mounts[0].read_only());
+        assert_eq!(mounts[1].target, "/mnt/my dir");
+        assert_eq!(mounts[1].fs_type, "virtiofs");
+        assert!(!mounts[1].read_only());
+    }
+}
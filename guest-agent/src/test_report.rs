@@ -0,0 +1,148 @@
+//! Structured test results for execs.
+//!
+//! Every exec gets an empty file, owned by the exec's user and named by
+//! [`TEST_REPORT_ENV`] in its environment. A test adapter such as
+//! `void-test` appends one [`TestCaseResult`] JSON line per case; once the
+//! process has exited the agent reads the file back into
+//! [`ExecResponse::test_results`](void_box_protocol::ExecResponse::test_results)
+//! and removes it.
+//!
+//! The file lives in a directory the exec can write to, so it may have been
+//! swapped for a symlink or a FIFO by the time it is read: it is opened
+//! without following links or blocking, and only read if it is still a
+//! regular file owned by the exec's user.
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use void_box_protocol::{TestCaseResult, MAX_TEST_RESULTS};
+
+/// Where report files are created.
+const REPORT_DIR: &str = "/tmp";
+
+/// Bytes of a report file read back; the rest is ignored.
+const MAX_REPORT_BYTES: u64 = 16 * 1024 * 1024;
+
+static NEXT_REPORT: AtomicU64 = AtomicU64::new(0);
+
+/// An exec's report file, removed when dropped.
+pub(crate) struct ReportFile {
+    path: PathBuf,
+    uid: u32,
+}
+
+impl ReportFile {
+    /// Create an empty report file that a process running as `uid`/`gid`
+    /// can append to.
+    pub(crate) fn create(uid: u32, gid: u32) -> std::io::Result<Self> {
+        let path = Path::new(REPORT_DIR).join(format!(
+            "voidbox-test-report-{}-{}.jsonl",
+            std::process::id(),
+            NEXT_REPORT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // A file left behind by an earlier boot of a snapshot.
+        let _ = std::fs::remove_file(&path);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        let report = Self { path, uid };
+        std::os::unix::fs::chown(&report.path, Some(uid), Some(gid))?;
+        Ok(report)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The cases reported so far, up to [`MAX_TEST_RESULTS`]. Lines that
+    /// are not a [`TestCaseResult`] are skipped.
+    pub(crate) fn read(&self) -> Vec<TestCaseResult> {
+        let Ok(file) = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+            .open(&self.path)
+        else {
+            return Vec::new();
+        };
+        match file.metadata() {
+            Ok(meta) if meta.is_file() && meta.uid() == self.uid => {}
+            _ => return Vec::new(),
+        }
+        parse(BufReader::new(file.take(MAX_REPORT_BYTES)))
+    }
+}
+
+impl Drop for ReportFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn parse(reader: impl BufRead) -> Vec<TestCaseResult> {
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .take(MAX_TEST_RESULTS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use void_box_protocol::TestStatus;
+
+    use super::*;
+
+    #[test]
+    fn reads_back_reported_cases_and_cleans_up() {
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        let report = ReportFile::create(uid, gid).unwrap();
+        let path = report.path().to_path_buf();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(
+            file,
+            r#"{{"suite":"tests/api.rs","name":"ok_case","status":"passed","duration_ms":2.0}}"#
+        )
+        .unwrap();
+        writeln!(file, "not json").unwrap();
+        writeln!(
+            file,
+            r#"{{"suite":"tests/api.rs","name":"bad_case","status":"failed","message":"boom"}}"#
+        )
+        .unwrap();
+
+        let cases = report.read();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name, "ok_case");
+        assert_eq!(cases[1].status, TestStatus::Failed);
+
+        drop(report);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn ignores_a_report_swapped_for_a_symlink() {
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("elsewhere");
+        std::fs::write(
+            &target,
+            r#"{"suite":"s","name":"n","status":"passed"}"#.to_string() + "\n",
+        )
+        .unwrap();
+
+        let report = ReportFile::create(uid, gid).unwrap();
+        std::fs::remove_file(report.path()).unwrap();
+        std::os::unix::fs::symlink(&target, report.path()).unwrap();
+        assert!(report.read().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Petting the host watchdog.
+//!
+//! When the host boots with `voidbox.watchdog=<secs>` on the kernel cmdline
+//! (`SandboxBuilder::watchdog`), the agent pets the VMM's watchdog device
+//! from a dedicated thread several times per timeout. The thread does not
+//! depend on the RPC loop, so a pet only goes missing when the guest as a
+//! whole stops being scheduled, e.g. because its kernel hung. The first pet
+//! arms the device; [`stop`] disarms it before a deliberate shutdown.
+//!
+//! The registers are I/O ports on x86_64 and a page of physical memory on
+//! aarch64, reached through `/dev/mem`; see `void_box_protocol::WATCHDOG_*`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use void_box_protocol::WATCHDOG_CMDLINE_KEY;
+
+use crate::kmsg;
+
+/// Pets per timeout, so one late wakeup does not trip the host.
+const PETS_PER_TIMEOUT: u32 = 3;
+
+/// Set by [`stop`]; the pet thread exits instead of re-arming.
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Which register a write goes to.
+#[derive(Clone, Copy)]
+enum Register {
+    Pet,
+    Stop,
+}
+
+/// Start the pet thread if the cmdline announces a watchdog.
+pub(crate) fn spawn_from_cmdline() {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    let Some(timeout) = parse_timeout(&cmdline) else {
+        return;
+    };
+    let interval = timeout / PETS_PER_TIMEOUT;
+    kmsg(&format!(
+        "Watchdog enabled: {}s timeout, petting every {}ms",
+        timeout.as_secs(),
+        interval.as_millis()
+    ));
+    let spawned = std::thread::Builder::new()
+        .name("watchdog".into())
+        .spawn(move || {
+            while !STOPPED.load(Ordering::SeqCst) {
+                if let Err(e) = write(Register::Pet) {
+                    kmsg(&format!("Watchdog pet failed, giving up: {}", e));
+                    return;
+                }
+                std::thread::sleep(interval);
+            }
+        });
+    if let Err(e) = spawned {
+        kmsg(&format!("Failed to spawn watchdog thread: {}", e));
+    }
+}
+
+/// Disarm the watchdog and stop petting it, ahead of a shutdown or reboot.
+pub(crate) fn stop() {
+    if !STOPPED.swap(true, Ordering::SeqCst) {
+        // Without a watchdog the write goes to an unclaimed port or page,
+        // which the VMM ignores.
+        let _ = write(Register::Stop);
+    }
+}
+
+/// The timeout announced by `voidbox.watchdog=`, if any.
+fn parse_timeout(cmdline: &str) -> Option<Duration> {
+    cmdline
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix(WATCHDOG_CMDLINE_KEY))
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn write(register: Register) -> std::io::Result<()> {
+    use void_box_protocol::{WATCHDOG_PET_PORT, WATCHDOG_STOP_PORT};
+
+    let port = match register {
+        Register::Pet => WATCHDOG_PET_PORT,
+        Register::Stop => WATCHDOG_STOP_PORT,
+    };
+    // I/O permissions are per thread, so take them on every write rather
+    // than once for whichever thread happens to ask first.
+    if unsafe { libc::ioperm(port as libc::c_ulong, 1, 1) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    unsafe {
+        std::arch::asm!("out dx, al", in("dx") port, in("al") 1u8, options(nomem, nostack));
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "aarch64")]
+fn write(register: Register) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    use void_box_protocol::{WATCHDOG_MMIO_ADDR, WATCHDOG_MMIO_PET, WATCHDOG_MMIO_STOP};
+
+    const PAGE_SIZE: usize = 0x1000;
+    let offset = match register {
+        Register::Pet => WATCHDOG_MMIO_PET,
+        Register::Stop => WATCHDOG_MMIO_STOP,
+    };
+    let mem = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_SYNC)
+        .open("/dev/mem")?;
+    let page = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            PAGE_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            mem.as_raw_fd(),
+            WATCHDOG_MMIO_ADDR as libc::off_t,
+        )
+    };
+    if page == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error());
+    }
+    unsafe {
+        std::ptr::write_volatile(page.cast::<u8>().add(offset as usize).cast::<u32>(), 1);
+        libc::munmap(page, PAGE_SIZE);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(
+            parse_timeout("console=ttyS0 voidbox.watchdog=30 panic=1"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_timeout("console=ttyS0"), None);
+        assert_eq!(parse_timeout("voidbox.watchdog=0"), None);
+        assert_eq!(parse_timeout("voidbox.watchdog=soon"), None);
+    }
+}
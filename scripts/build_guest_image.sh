@@ -59,6 +59,10 @@ echo "[void-box] Building void-mcp (release, static, target=$GUEST_TARGET)..."
 cargo build --release -p void-mcp --target "$GUEST_TARGET"
 VOID_MCP_BIN="target/$GUEST_TARGET/release/void-mcp"
 
+echo "[void-box] Building void-test (release, static, target=$GUEST_TARGET)..."
+cargo build --release -p void-test --target "$GUEST_TARGET"
+VOID_TEST_BIN="target/$GUEST_TARGET/release/void-test"
+
 # ── Assemble rootfs ──────────────────────────────────────────────────────────
 
 prepare_rootfs
@@ -74,6 +78,10 @@ echo "[void-box] Installing void-mcp MCP bridge at /usr/local/bin/void-mcp..."
 cp "$VOID_MCP_BIN" "$OUT_DIR/usr/local/bin/void-mcp"
 chmod +x "$OUT_DIR/usr/local/bin/void-mcp"
 
+echo "[void-box] Installing void-test adapter at /usr/local/bin/void-test..."
+cp "$VOID_TEST_BIN" "$OUT_DIR/usr/local/bin/void-test"
+chmod +x "$OUT_DIR/usr/local/bin/void-test"
+
 # Claude-code: install binary, then platform-specific shared libraries
 if install_claude_code_binary; then
   if [[ "$HOST_OS" == "Darwin" ]]; then
@@ -8,6 +8,7 @@ set -euo pipefail
 #   - guest-agent binary
 #   - initramfs (via build_guest_image.sh)
 #   - kernel (via download_kernel.sh)
+#   - manifest.json naming the bundle's target (arch + backend)
 #   - Staged dist/ directory ready for nfpm packaging
 #   - .tar.gz tarball for shell installer
 #
@@ -79,11 +80,16 @@ else
   echo "[void-box] WARNING: kernel not found at $KERNEL_FILE"
 fi
 
+# Target metadata, so voidbox only picks this bundle on a matching host
+cat > "$DIST_DIR/manifest.json" <<EOF
+{"arch": "$ARCH", "backend": "kvm", "kernel": "vmlinuz", "initramfs": "initramfs.cpio.gz"}
+EOF
+
 # ── 5. Build tarball ─────────────────────────────────────────────────────────
 
 TARBALL="$RELEASE_DIR/voidbox-${VERSION}-linux-${ARCH}.tar.gz"
 echo "[void-box] Creating tarball: $TARBALL"
-tar -czf "$TARBALL" -C "$DIST_DIR" voidbox vmlinuz initramfs.cpio.gz
+tar -czf "$TARBALL" -C "$DIST_DIR" voidbox vmlinuz initramfs.cpio.gz manifest.json
 
 # ── 6. Generate checksums ────────────────────────────────────────────────────
 
@@ -216,6 +216,10 @@ echo "[slim-kernel] Applying void-box additions to config..."
     # devices (vsock, net, 9p, OCI rootfs) via `virtio_mmio.device=...` args.
     # Firecracker's config leaves this off because they use virtio-pci.
     scripts/config --enable CONFIG_VIRTIO_MMIO_CMDLINE_DEVICES
+    # virtio-console carries the control channel for
+    # KvmBackend::with_serial_control (and, per RFC-0004, hosts without
+    # vsock). Built in so the port exists without a module load.
+    scripts/config --enable CONFIG_VIRTIO_CONSOLE
     # Apple Virtualization.framework (macOS) exposes all virtio devices over
     # PCI on arm64 — Firecracker's config has `# CONFIG_PCI is not set`, so
     # without these the kernel boots on VZ but enumerates zero devices and
@@ -7,7 +7,7 @@ set -euo pipefail
 # replaced by `claudio` — a deterministic mock used by the e2e suites
 # (e2e_telemetry, e2e_skill_pipeline, e2e_mount, e2e_service_mode,
 # e2e_sidecar). Everything else (guest-agent, void-message, void-mcp,
-# busybox, kernel modules) uses the shared helpers in scripts/lib/.
+# void-test, busybox, kernel modules) uses the shared helpers in scripts/lib/.
 #
 # Usage:
 #   scripts/build_test_image.sh
@@ -69,6 +69,10 @@ echo "[test-image] Building void-mcp (release, static, target=$GUEST_TARGET)..."
 cargo build --release -p void-mcp --target "$GUEST_TARGET"
 VOID_MCP_BIN="target/$GUEST_TARGET/release/void-mcp"
 
+echo "[test-image] Building void-test (release, static, target=$GUEST_TARGET)..."
+cargo build --release -p void-test --target "$GUEST_TARGET"
+VOID_TEST_BIN="target/$GUEST_TARGET/release/void-test"
+
 # ── Assemble rootfs ──────────────────────────────────────────────────────────
 
 prepare_rootfs
@@ -88,6 +92,10 @@ echo "[test-image] Installing void-mcp MCP bridge at /usr/local/bin/void-mcp..."
 cp "$VOID_MCP_BIN" "$OUT_DIR/usr/local/bin/void-mcp"
 chmod +x "$OUT_DIR/usr/local/bin/void-mcp"
 
+echo "[test-image] Installing void-test adapter at /usr/local/bin/void-test..."
+cp "$VOID_TEST_BIN" "$OUT_DIR/usr/local/bin/void-test"
+chmod +x "$OUT_DIR/usr/local/bin/void-test"
+
 if [[ "$HOST_OS" == "Darwin" ]]; then
   ensure_busybox_macos
 fi
@@ -12,6 +12,7 @@ set -eu
 #   /usr/local/lib/voidbox/vmlinuz     (Linux)
 #   /usr/local/lib/voidbox/vmlinux     (macOS)
 #   /usr/local/lib/voidbox/initramfs.cpio.gz
+#   /usr/local/lib/voidbox/manifest.json
 
 REPO="the-void-ia/void-box"
 INSTALL_BIN="/usr/local/bin"
@@ -108,6 +109,11 @@ download_and_install() {
 
     $SUDO install -m 644 "${TMPDIR_INSTALL}/initramfs.cpio.gz" "$INSTALL_LIB/initramfs.cpio.gz"
 
+    # Target metadata (arch + backend); absent from older release tarballs
+    if [ -f "${TMPDIR_INSTALL}/manifest.json" ]; then
+        $SUDO install -m 644 "${TMPDIR_INSTALL}/manifest.json" "$INSTALL_LIB/manifest.json"
+    fi
+
     if [ "$PLATFORM" = "darwin" ]; then
         codesign_macos_binary "$SUDO" "$TMPDIR_INSTALL"
     fi
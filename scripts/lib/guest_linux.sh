@@ -137,6 +137,9 @@ install_kernel_modules_from_deb() {
     "lib/modules/${kmod_version}-generic/kernel/net/9p/9pnet.ko"
     "lib/modules/${kmod_version}-generic/kernel/net/9p/9pnet_virtio.ko"
     "lib/modules/${kmod_version}-generic/kernel/fs/overlayfs/overlay.ko"
+    "lib/modules/${kmod_version}-generic/kernel/drivers/char/hw_random/virtio-rng.ko"
+    "lib/modules/${kmod_version}-generic/kernel/drivers/virtio/virtio_balloon.ko"
+    "lib/modules/${kmod_version}-generic/kernel/drivers/char/virtio_console.ko"
   )
 
   # Data tarball may be compressed as .zst, .xz, or .gz
@@ -239,4 +242,7 @@ install_kernel_modules_linux() {
   _install_kmod "$moddir/net/9p/9pnet"                                   "$dest"
   _install_kmod "$moddir/net/9p/9pnet_virtio"                            "$dest"
   _install_kmod "$moddir/fs/overlayfs/overlay"                           "$dest"
+  _install_kmod "$moddir/drivers/char/hw_random/virtio-rng"              "$dest"
+  _install_kmod "$moddir/drivers/virtio/virtio_balloon"                  "$dest"
+  _install_kmod "$moddir/drivers/char/virtio_console"                    "$dest"
 }
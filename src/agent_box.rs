@@ -43,18 +43,29 @@ use secrecy::{ExposeSecret, SecretString};
 use tracing::{debug, error, info, warn};
 
 use crate::backend::guest_host_gateway;
+use crate::budget::Budget;
+use crate::idle::IdlePolicy;
 use crate::llm::LlmProvider;
-use crate::observe::claude::AgentExecOpts;
+use crate::observe::claude::{AgentExecOpts, AgentExecResult, ClaudeToolCall};
+use crate::observe::pricing::PricingTable;
+use crate::observe::provenance::{RunKind, SkillDigest};
 use crate::observe::telemetry::TelemetryBuffer;
+use crate::observe::{ObserveConfig, ObservedResult, Observer, RunManifest};
+use crate::output_schema::{OutputSchema, DEFAULT_SCHEMA_RETRIES};
 use crate::pipeline::StageResult;
 use crate::proxy::{
     assert_no_real_credential, build_guest_provisioning, render_guest_hosts, start_proxy,
     ProxiedUpstream, ProxyCa, ProxyHandle, ProxyToken, SandboxContext, StaticApiKeyInjector,
     GUEST_HOSTS_PATH,
 };
-use crate::sandbox::Sandbox;
+use crate::sandbox::{ExecPolicy, GitWorkspace, HealthCheck, RestartPolicy, Sandbox};
+use crate::secret::{Secret, SecretSource};
+use crate::session::{AgentSession, SessionTurn, SESSION_FORMAT_VERSION};
 use crate::skill::{Skill, SkillKind};
+use crate::skill_lint::SkillReport;
+use crate::skill_registry::SkillRegistry;
 use crate::spec::AgentMode;
+use crate::tool_hook::{ToolDecision, ToolHook};
 use crate::Result;
 
 /// Project-scoped config directory. Claude Code reads skills, settings, and
@@ -66,6 +77,59 @@ const CLAUDE_HOME: &str = "/workspace/.claude";
 const MCP_CONFIG_PATH: &str = "/workspace/.mcp.json";
 const CLAUDE_ONBOARDING_PATH: &str = "/home/sandbox/.claude.json";
 
+/// Each earlier prompt and answer replayed from a resumed session is cut to
+/// this many bytes, so a long session does not crowd out the new prompt.
+const HISTORY_TURN_MAX_BYTES: usize = 4000;
+
+/// `next`, an answer to a schema retry, with the usage and tool calls of
+/// the attempts before it added in.
+fn merge_attempts(earlier: AgentExecResult, mut next: AgentExecResult) -> AgentExecResult {
+    next.total_cost_usd += earlier.total_cost_usd;
+    next.cost_estimated |= earlier.cost_estimated;
+    next.input_tokens += earlier.input_tokens;
+    next.output_tokens += earlier.output_tokens;
+    next.cache_creation_input_tokens += earlier.cache_creation_input_tokens;
+    next.cache_read_input_tokens += earlier.cache_read_input_tokens;
+    next.num_turns += earlier.num_turns;
+    next.duration_ms += earlier.duration_ms;
+    next.duration_api_ms += earlier.duration_api_ms;
+    let mut tool_calls = earlier.tool_calls;
+    tool_calls.append(&mut next.tool_calls);
+    next.tool_calls = tool_calls;
+    let mut thinking = earlier.thinking;
+    thinking.append(&mut next.thinking);
+    next.thinking = thinking;
+    let mut messages = earlier.messages;
+    messages.append(&mut next.messages);
+    next.messages = messages;
+    let mut subagents = earlier.subagents;
+    subagents.append(&mut next.subagents);
+    next.subagents = subagents;
+    next
+}
+
+/// SHA-256 of the file at `path`, read in chunks.
+fn file_digest(path: &std::path::Path) -> Result<Vec<u8>> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// `text` cut to at most `max` bytes on a char boundary, marked if cut.
+fn truncate_chars(text: &str, max: usize) -> std::borrow::Cow<'_, str> {
+    if text.len() <= max {
+        return text.into();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...(truncated)", &text[..end]).into()
+}
+
 /// Whether `key` carries a real provider API key that must be withheld from the
 /// guest when the credential proxy is active (the proxy injects it host-side).
 fn is_withheld_secret_env(key: &str) -> bool {
@@ -178,7 +242,7 @@ pub struct ServiceStageHandle {
 
 /// Result of running an agent — either a terminal task result or a service handle.
 pub enum AgentRunOutcome {
-    Task(crate::pipeline::StageResult),
+    Task(Box<crate::pipeline::StageResult>),
     Service(ServiceStageHandle),
 }
 
@@ -196,6 +260,87 @@ pub struct VoidBox {
     sandbox: Option<Arc<Sandbox>>,
     /// Builder config (before build)
     config: BoxConfig,
+    /// Completed runs of this session, carried over by `resume_session`.
+    history: Vec<SessionTurn>,
+    /// The agent's id for the last conversation, if it reported one.
+    agent_session_id: Option<String>,
+    /// Conversation `chat` resumes with the provider's `--resume`; only
+    /// valid in the VM that ran it.
+    chat_session_id: Option<String>,
+    /// Whether `chat` has provisioned the current VM.
+    chat_provisioned: bool,
+    /// Totals across `chat` turns.
+    chat_usage: ChatUsage,
+}
+
+/// Token and cost totals across the turns of a [`VoidBox::chat`]
+/// conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChatUsage {
+    pub turns: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_cost_usd: f64,
+    pub duration_ms: u64,
+}
+
+impl ChatUsage {
+    fn add(&mut self, result: &AgentExecResult) {
+        self.turns += 1;
+        self.input_tokens += result.input_tokens;
+        self.output_tokens += result.output_tokens;
+        self.total_cost_usd += result.total_cost_usd;
+        self.duration_ms += result.duration_ms;
+    }
+}
+
+/// What provisioning a Box would do, from [`VoidBox::dry_run`].
+#[derive(Debug, Clone)]
+pub struct BoxDryRun {
+    /// Name of the Box.
+    pub name: String,
+    /// [`Skill::validate`] report for each skill, in install order.
+    pub skills: Vec<SkillReport>,
+    /// Guest paths provisioning would write.
+    pub files: Vec<String>,
+    /// MCP servers provisioning would start, as `name: command args`.
+    pub mcp_servers: Vec<String>,
+}
+
+impl BoxDryRun {
+    /// Whether every skill passed validation.
+    pub fn is_ok(&self) -> bool {
+        self.skills.iter().all(SkillReport::is_ok)
+    }
+}
+
+impl std::fmt::Display for BoxDryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "box {}:", self.name)?;
+        for report in &self.skills {
+            for line in report.to_string().lines() {
+                writeln!(f, "  skill {line}")?;
+            }
+        }
+        for server in &self.mcp_servers {
+            writeln!(f, "  mcp {server}")?;
+        }
+        for file in &self.files {
+            writeln!(f, "  write {file}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One turn of a [`VoidBox::chat`] conversation.
+#[derive(Debug, Clone)]
+pub struct ChatTurn {
+    /// 1-based turn number.
+    pub turn: u32,
+    /// The agent's answer and this turn's tokens, cost and tool calls.
+    pub result: AgentExecResult,
+    /// Totals across the conversation, this turn included.
+    pub usage: ChatUsage,
 }
 
 /// Internal configuration before the Box is built.
@@ -209,6 +354,10 @@ struct BoxConfig {
     env: Vec<(String, String)>,
     /// Host directory mounts into the guest.
     mounts: Vec<crate::backend::MountConfig>,
+    /// Extra guest directories the host may write to.
+    write_roots: Vec<String>,
+    /// Run x86_64 binaries through Rosetta (VZ on Apple silicon).
+    rosetta: bool,
     /// Guest path where an OCI rootfs is mounted (triggers pivot_root in guest-agent).
     oci_rootfs: Option<String>,
     /// OCI rootfs block device in guest (e.g. /dev/vda).
@@ -234,6 +383,35 @@ struct BoxConfig {
     mode: AgentMode,
     /// Optional staged Claude personal credentials to copy into the guest.
     claude_credentials_host_path: Option<PathBuf>,
+    /// Persistent volume mounted at `/workspace`.
+    workspace_volume: Option<String>,
+    /// Repository checked out into the guest before the agent starts.
+    git_workspace: Option<GitWorkspace>,
+    /// Diff the workspace around each run into `StageResult::patch`.
+    capture_patch: bool,
+    /// Where the session is saved after every run.
+    session_file: Option<PathBuf>,
+    /// Heartbeat schedule for the guest-agent.
+    health_check: Option<HealthCheck>,
+    /// Whether a dead guest is replaced and the run re-provisioned.
+    restart_policy: RestartPolicy,
+    /// Decides on each tool call the agent makes.
+    tool_hook: Option<ToolHook>,
+    /// Cost, token and turn limits for each run.
+    budget: Option<Budget>,
+    /// When a run whose agent has gone quiet is ended.
+    idle: Option<IdlePolicy>,
+    /// Rates for pricing runs whose agent reports no cost.
+    pricing: Option<PricingTable>,
+    skill_registry: Option<Arc<SkillRegistry>>,
+    /// JSON Schema the final answer must match.
+    output_schema: Option<serde_json::Value>,
+    /// Extra attempts when the answer does not match `output_schema`.
+    schema_retries: u32,
+    /// Secrets injected into the guest.
+    secrets: Vec<Secret>,
+    /// Commands the guest may run.
+    exec_policy: Option<ExecPolicy>,
 }
 
 impl Default for BoxConfig {
@@ -246,6 +424,8 @@ impl Default for BoxConfig {
             initramfs: None,
             env: Vec::new(),
             mounts: Vec::new(),
+            write_roots: Vec::new(),
+            rosetta: false,
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
@@ -257,6 +437,21 @@ impl Default for BoxConfig {
             timeout_secs: None,
             mode: AgentMode::default(),
             claude_credentials_host_path: None,
+            workspace_volume: None,
+            git_workspace: None,
+            capture_patch: false,
+            session_file: None,
+            health_check: None,
+            restart_policy: RestartPolicy::Never,
+            tool_hook: None,
+            budget: None,
+            idle: None,
+            pricing: None,
+            skill_registry: None,
+            output_schema: None,
+            schema_retries: DEFAULT_SCHEMA_RETRIES,
+            secrets: Vec::new(),
+            exec_policy: None,
         }
     }
 }
@@ -270,9 +465,73 @@ impl VoidBox {
             skills: Vec::new(),
             sandbox: None,
             config: BoxConfig::default(),
+            history: Vec::new(),
+            agent_session_id: None,
+            chat_session_id: None,
+            chat_provisioned: false,
+            chat_usage: ChatUsage::default(),
         }
     }
 
+    /// Rebuild a Box from a session saved by [`save_session`](Self::save_session)
+    /// or [`session_file`](Self::session_file), so it continues the same
+    /// conversation on the same workspace volume.
+    ///
+    /// Name, prompt, skills, LLM provider, workspace volume and history are
+    /// restored, and later runs keep saving to `path`. Everything else
+    /// (kernel, memory, API keys) is set again before [`build`](Self::build).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::agent_box::VoidBox;
+    ///
+    /// # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let vbox = VoidBox::resume_session("sessions/analyst.json")?
+    ///     .prompt("Now chart the results")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume_session(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let session = AgentSession::load(&path)?;
+        let mut vbox = Self::new(session.name);
+        vbox.prompt = session.prompt;
+        vbox.skills = session.skills;
+        vbox.config.llm = session.llm;
+        vbox.config.workspace_volume = session.workspace_volume;
+        vbox.config.session_file = Some(path);
+        vbox.history = session.history;
+        vbox.agent_session_id = session.agent_session_id;
+        Ok(vbox)
+    }
+
+    /// Snapshot of this Box's session: see [`AgentSession`].
+    pub fn session(&self) -> AgentSession {
+        AgentSession {
+            version: SESSION_FORMAT_VERSION,
+            name: self.name.clone(),
+            prompt: self.prompt.clone(),
+            llm: self.config.llm.clone(),
+            skills: self.skills.clone(),
+            workspace_volume: self.config.workspace_volume.clone(),
+            agent_session_id: self.agent_session_id.clone(),
+            history: self.history.clone(),
+            saved_at_ms: crate::session::now_ms(),
+        }
+    }
+
+    /// Write this Box's session to `path` for [`resume_session`](Self::resume_session).
+    pub fn save_session(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        self.session().save(path)
+    }
+
+    /// Completed runs of this session, oldest first.
+    pub fn history(&self) -> &[SessionTurn] {
+        &self.history
+    }
+
     // -- Builder methods --
 
     /// Add a Skill to this Box.
@@ -281,6 +540,26 @@ impl VoidBox {
         self
     }
 
+    /// Resolve [`Skill::registry`] skills with `registry`. Share one
+    /// registry (and its lockfile) across boxes with an `Arc`.
+    pub fn skill_registry(mut self, registry: impl Into<Arc<SkillRegistry>>) -> Self {
+        self.config.skill_registry = Some(registry.into());
+        self
+    }
+
+    /// Add versioned skills from the [skill registry](Self::skill_registry),
+    /// e.g. `["rust-refactor@^1"]`. They are resolved, and the lockfile
+    /// updated, when the Box is provisioned.
+    pub fn skills_from_registry<I, S>(mut self, specs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.skills
+            .extend(specs.into_iter().map(|spec| Skill::registry(spec)));
+        self
+    }
+
     /// Set the prompt that defines this Box's purpose.
     pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
         self.prompt = prompt.into();
@@ -323,6 +602,27 @@ impl VoidBox {
         self
     }
 
+    /// Inject the secret `name`, read from `source` when the Box is built
+    /// (see [`SandboxBuilder::secret`](crate::sandbox::SandboxBuilder::secret)).
+    pub fn secret(self, name: impl Into<String>, source: SecretSource) -> Self {
+        self.secret_with(Secret::new(name, source))
+    }
+
+    /// Inject `secret`, e.g. delivered as a file; see [`crate::secret`].
+    pub fn secret_with(mut self, secret: Secret) -> Self {
+        self.config.secrets.push(secret);
+        self
+    }
+
+    /// Restrict which commands the Box may run (see
+    /// [`SandboxBuilder::exec_policy`](crate::sandbox::SandboxBuilder::exec_policy)).
+    /// The policy applies to the agent binary and the Box's own file
+    /// plumbing too, so it must allow them.
+    pub fn exec_policy(mut self, policy: ExecPolicy) -> Self {
+        self.config.exec_policy = Some(policy);
+        self
+    }
+
     /// Set the output file path the agent should write to.
     /// Defaults to `/workspace/output.json`.
     pub fn output_file(mut self, path: impl Into<String>) -> Self {
@@ -402,6 +702,21 @@ impl VoidBox {
         self
     }
 
+    /// Let the host write under `dir` in the guest, on top of `/workspace`,
+    /// `/home` and `/etc/voidbox`; see
+    /// [`SandboxBuilder::allow_write_root`](crate::sandbox::SandboxBuilder::allow_write_root).
+    pub fn allow_write_root(mut self, dir: impl Into<String>) -> Self {
+        self.config.write_roots.push(dir.into());
+        self
+    }
+
+    /// Run x86_64 binaries through Rosetta; see
+    /// [`SandboxBuilder::rosetta`](crate::sandbox::SandboxBuilder::rosetta).
+    pub fn rosetta(mut self, enabled: bool) -> Self {
+        self.config.rosetta = enabled;
+        self
+    }
+
     /// Set the OCI rootfs guest path (triggers pivot_root in guest-agent).
     pub fn oci_rootfs(mut self, guest_path: impl Into<String>) -> Self {
         self.config.oci_rootfs = Some(guest_path.into());
@@ -426,12 +741,351 @@ impl VoidBox {
         self
     }
 
+    /// Mount the persistent [`Volume`](crate::volume::Volume) `name` at
+    /// `/workspace`, so the agent's files outlive the VM and a resumed
+    /// session finds them again.
+    pub fn workspace_volume(mut self, name: impl Into<String>) -> Self {
+        self.config.workspace_volume = Some(name.into());
+        self
+    }
+
+    /// Check out `rev` of the repository at `url` into `/workspace` before
+    /// the agent starts (see
+    /// [`SandboxBuilder::git_workspace`](crate::sandbox::SandboxBuilder::git_workspace)).
+    pub fn git_workspace(self, url: impl Into<String>, rev: &str) -> Self {
+        self.git_workspace_with(GitWorkspace::new(url, Some(rev)))
+    }
+
+    /// Seed the workspace from `workspace`, with its clone options.
+    pub fn git_workspace_with(mut self, workspace: GitWorkspace) -> Self {
+        self.config.git_workspace = Some(workspace);
+        self
+    }
+
+    /// Record what the agent changes in the workspace as a
+    /// [`GitPatch`](crate::sandbox::GitPatch) in
+    /// [`StageResult::patch`]: the workspace (skills and input already in
+    /// place) is recorded as the baseline right before the agent starts,
+    /// and diffed once it is done. Needs `git` in the guest; if the patch
+    /// cannot be taken, the run still succeeds, without one.
+    ///
+    /// ```no_run
+    /// use void_box::agent_box::VoidBox;
+    ///
+    /// # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let result = VoidBox::new("fixer")
+    ///     .git_workspace("https://github.com/the-void-ia/void-box", "main")
+    ///     .capture_patch(true)
+    ///     .prompt("Fix the failing test")
+    ///     .build()?
+    ///     .run(None, None)
+    ///     .await?;
+    /// if let Some(patch) = &result.patch {
+    ///     for file in &patch.files {
+    ///         println!("{:?} {} (+{} -{})", file.status, file.path, file.additions, file.deletions);
+    ///     }
+    ///     patch.apply("/path/to/checkout").await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture_patch(mut self, enable: bool) -> Self {
+        self.config.capture_patch = enable;
+        self
+    }
+
+    /// Save the session to `path` after every run, for
+    /// [`resume_session`](Self::resume_session).
+    pub fn session_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.session_file = Some(path.into());
+        self
+    }
+
+    /// Heartbeat the guest-agent on `check`'s schedule (see
+    /// [`SandboxBuilder::health_check`](crate::sandbox::SandboxBuilder::health_check)).
+    pub fn health_check(mut self, check: HealthCheck) -> Self {
+        self.config.health_check = Some(check);
+        self
+    }
+
+    /// Replace a guest that crashed or stopped answering heartbeats. A run
+    /// interrupted by the crash provisions its skills and input again on
+    /// the fresh VM and restarts the agent.
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.config.restart_policy = policy;
+        self
+    }
+
+    /// Decide on each tool call as the agent makes it: allow it, deny it
+    /// (failing the run with [`Error::ToolDenied`](crate::Error::ToolDenied)),
+    /// or pause the agent in the guest until a host-side approval settles.
+    /// See [`tool_hook`](crate::tool_hook) for the guarantees.
+    ///
+    /// ```no_run
+    /// use void_box::agent_box::VoidBox;
+    /// use void_box::tool_hook::ToolDecision;
+    ///
+    /// let vbox = VoidBox::new("ops").on_tool_use(|call| {
+    ///     if call.tool_name != "Bash" {
+    ///         return ToolDecision::Allow;
+    ///     }
+    ///     let (approve_tx, approve_rx) = tokio::sync::oneshot::channel::<bool>();
+    ///     // Hand `approve_tx` to whoever approves `call.tool_summary()`.
+    ///     # drop(approve_tx);
+    ///     ToolDecision::pause(async move {
+    ///         match approve_rx.await {
+    ///             Ok(true) => ToolDecision::Allow,
+    ///             _ => ToolDecision::deny("not approved"),
+    ///         }
+    ///     })
+    /// });
+    /// ```
+    pub fn on_tool_use<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ClaudeToolCall) -> ToolDecision + Send + Sync + 'static,
+    {
+        self.config.tool_hook = Some(ToolHook::new(hook));
+        self
+    }
+
+    /// Stop each run that goes over `budget`, failing it with
+    /// [`Error::BudgetExceeded`](crate::Error::BudgetExceeded). See
+    /// [`budget`](crate::budget) for how usage is counted.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.config.budget = Some(budget);
+        self
+    }
+
+    /// Finalize each run once its agent has gone idle under `policy`,
+    /// keeping what it produced, for agent CLIs that wait for more input
+    /// instead of exiting. See [`idle`](crate::idle).
+    pub fn idle_policy(mut self, policy: IdlePolicy) -> Self {
+        self.config.idle = Some(policy);
+        self
+    }
+
+    /// Price runs whose agent reports no cost (OpenAI-compatible providers,
+    /// older CLI versions) from their tokens at `pricing`; see
+    /// [`AgentExecResult::fill_missing_cost`]. Local providers stay free.
+    pub fn pricing(mut self, pricing: PricingTable) -> Self {
+        self.config.pricing = Some(pricing);
+        self
+    }
+
+    /// Require the agent's final answer to be JSON matching `schema`.
+    ///
+    /// The schema is added to the prompt and each answer is validated; a
+    /// mismatch is sent back to the agent to correct, up to
+    /// [`schema_retries`](Self::schema_retries) times. The valid value is in
+    /// [`StageResult::structured_output`]; [`run_json`](Self::run_json)
+    /// returns it deserialized. See [`output_schema`](crate::output_schema).
+    ///
+    /// ```no_run
+    /// use serde_json::json;
+    /// use void_box::agent_box::VoidBox;
+    ///
+    /// let analyst = VoidBox::new("analyst")
+    ///     .prompt("Rate AAPL's last quarter")
+    ///     .expect_json_schema(json!({
+    ///         "type": "object",
+    ///         "required": ["ticker", "score"],
+    ///         "properties": {
+    ///             "ticker": {"type": "string"},
+    ///             "score": {"type": "number", "minimum": 0, "maximum": 1}
+    ///         }
+    ///     }));
+    /// ```
+    pub fn expect_json_schema(mut self, schema: serde_json::Value) -> Self {
+        self.config.output_schema = Some(schema);
+        self
+    }
+
+    /// How many times to re-ask the agent after an answer that does not
+    /// match the [output schema](Self::expect_json_schema). Default 2.
+    pub fn schema_retries(mut self, retries: u32) -> Self {
+        self.config.schema_retries = retries;
+        self
+    }
+
+    /// Tighten this box's budget to fit within `budget`.
+    pub(crate) fn cap_budget(mut self, budget: Budget) -> Self {
+        self.config.budget = Some(match self.config.budget {
+            Some(own) => own.min(budget),
+            None => budget,
+        });
+        self
+    }
+
     /// Use a mock sandbox (for testing without KVM).
     pub fn mock(mut self) -> Self {
         self.config.mock = true;
         self
     }
 
+    /// Report what provisioning this Box would install, without booting a VM.
+    ///
+    /// Every skill is checked with [`Skill::validate`]. Remote and registry
+    /// skills are listed by name; their content is fetched, and checked,
+    /// when the Box runs.
+    pub fn dry_run(&self) -> BoxDryRun {
+        let mut files = Vec::new();
+        let mut mcp_servers = Vec::new();
+        if self.config.llm.supports_claude_settings() {
+            files.push(CLAUDE_ONBOARDING_PATH.to_string());
+            files.push(format!("{}/settings.json", CLAUDE_HOME));
+        }
+        for skill in &self.skills {
+            match &skill.kind {
+                SkillKind::File { .. }
+                | SkillKind::Remote { .. }
+                | SkillKind::Registry { .. }
+                | SkillKind::Inline { .. } => {
+                    files.push(format!("{}/skills/{}.md", CLAUDE_HOME, skill.name));
+                }
+                SkillKind::Oci { .. } => {
+                    files.push(format!("{}/skills/{}_path.sh", CLAUDE_HOME, skill.name));
+                }
+                SkillKind::Mcp { command, args, .. } => {
+                    let mut server = format!("{}: {}", skill.name, command);
+                    for arg in args {
+                        server.push(' ');
+                        server.push_str(arg);
+                    }
+                    mcp_servers.push(server);
+                }
+                SkillKind::Cli { .. } | SkillKind::Agent { .. } => {}
+            }
+        }
+        if !mcp_servers.is_empty() {
+            files.push(MCP_CONFIG_PATH.to_string());
+            if !self.config.llm.supports_claude_settings() {
+                files.push("/home/sandbox/.codex/config.toml".to_string());
+            }
+        }
+        BoxDryRun {
+            name: self.name.clone(),
+            skills: self.skills.iter().map(Skill::validate).collect(),
+            files,
+            mcp_servers,
+        }
+    }
+
+    /// Content address of a run of this Box on `input`, for
+    /// [`Pipeline::with_cache`](crate::pipeline::Pipeline::with_cache).
+    ///
+    /// Covers everything that shapes the agent's answer: the prompt and
+    /// input, each skill (with the content of local skill files), the
+    /// provider and guest environment, and the digests of the kernel,
+    /// initramfs and OCI rootfs disk. Remote and registry skills contribute
+    /// their id or spec, not the content they resolve to.
+    ///
+    /// `None` when a run cannot be cached: the git workspace is checked out
+    /// at a branch, tag or `HEAD` rather than a full commit id, so the same
+    /// key could name different trees.
+    pub(crate) fn cache_key(&self, input: Option<&[u8]>) -> Result<Option<String>> {
+        use sha2::{Digest, Sha256};
+
+        if let Some(workspace) = &self.config.git_workspace {
+            if !workspace.is_pinned() {
+                return Ok(None);
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        let mut field = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(b"void-box stage v1");
+        field(self.name.as_bytes());
+        field(self.prompt.as_bytes());
+        match input {
+            Some(input) => {
+                field(b"input");
+                field(input);
+            }
+            None => field(b"no input"),
+        }
+        for skill in &self.skills {
+            field(&serde_json::to_vec(&skill.kind)?);
+            if let SkillKind::File { path } = &skill.kind {
+                let content = std::fs::read(path).map_err(|e| {
+                    crate::Error::Config(format!(
+                        "Failed to read skill file {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                field(&content);
+            }
+        }
+        field(&serde_json::to_vec(&self.config.llm)?);
+        field(self.config.output_file.as_bytes());
+        field(format!("{:?}", self.config.mode).as_bytes());
+        field(&[self.config.mock as u8, self.config.network as u8]);
+        for (key, value) in &self.config.env {
+            field(key.as_bytes());
+            field(value.as_bytes());
+        }
+        // Names only: rotating a key does not change what the agent does.
+        for secret in &self.config.secrets {
+            field(format!("{}:{:?}", secret.name(), secret.delivery()).as_bytes());
+        }
+        for mount in &self.config.mounts {
+            field(format!("{mount:?}").as_bytes());
+        }
+        field(format!("{:?}", self.config.oci_rootfs).as_bytes());
+        field(format!("{:?}", self.config.workspace_volume).as_bytes());
+        field(format!("{:?}", self.config.git_workspace).as_bytes());
+        field(format!("{:?}", self.config.exec_policy).as_bytes());
+        field(&[self.config.capture_patch as u8]);
+        for image in [
+            &self.config.kernel,
+            &self.config.initramfs,
+            &self.config.oci_rootfs_disk,
+        ] {
+            match image {
+                Some(path) => field(&file_digest(path)?),
+                None => field(b"none"),
+            }
+        }
+        Ok(Some(format!("{:x}", hasher.finalize())))
+    }
+
+    /// Add this Box's skills and sandbox to `manifest`. Parts that cannot be
+    /// hashed are left out with a warning rather than failing the run.
+    pub(crate) fn record_provenance(&self, manifest: &mut RunManifest) {
+        for skill in &self.skills {
+            match SkillDigest::of(skill) {
+                Ok(digest) => manifest.add_skill(digest),
+                Err(e) => warn!(
+                    "[vm:{}] run manifest is missing skill '{}': {}",
+                    self.name, skill.name, e
+                ),
+            }
+        }
+        if let Some(sandbox) = &self.sandbox {
+            match sandbox.manifest() {
+                Ok(sandbox) => manifest.add_sandbox(sandbox),
+                Err(e) => warn!(
+                    "[vm:{}] run manifest is missing the sandbox: {}",
+                    self.name, e
+                ),
+            }
+        }
+    }
+
+    /// Log `report`'s warnings and fail on its errors.
+    fn check_skill_report(&self, report: SkillReport) -> Result<()> {
+        for issue in report.warnings() {
+            warn!(
+                "[vm:{}] skill '{}': {}",
+                self.name, report.skill, issue.message
+            );
+        }
+        report.into_result().map(drop)
+    }
+
     /// Build the Box, creating the underlying sandbox.
     pub fn build(mut self) -> Result<Self> {
         let sandbox = self.create_sandbox()?;
@@ -487,6 +1141,10 @@ impl VoidBox {
         for m in &self.config.mounts {
             builder = builder.mount(m.clone());
         }
+        for dir in &self.config.write_roots {
+            builder = builder.allow_write_root(dir.clone());
+        }
+        builder = builder.rosetta(self.config.rosetta);
 
         // OCI rootfs pivot_root
         if let Some(ref path) = self.config.oci_rootfs {
@@ -504,6 +1162,27 @@ impl VoidBox {
             builder = builder.snapshot(snap);
         }
 
+        if let Some(ref volume) = self.config.workspace_volume {
+            builder = builder.volume(volume, "/workspace");
+        }
+
+        for secret in &self.config.secrets {
+            builder = builder.secret_with(secret.clone());
+        }
+
+        if let Some(ref workspace) = self.config.git_workspace {
+            builder = builder.git_workspace_with(workspace.clone());
+        }
+
+        if let Some(ref policy) = self.config.exec_policy {
+            builder = builder.exec_policy(policy.clone());
+        }
+
+        if let Some(check) = self.config.health_check {
+            builder = builder.health_check(check);
+        }
+        builder = builder.restart_policy(self.config.restart_policy);
+
         builder.build()
     }
 
@@ -784,6 +1463,30 @@ impl VoidBox {
     async fn provision_skills(&self, sandbox: &Sandbox) -> Result<()> {
         let tag = &self.name;
 
+        for skill in &self.skills {
+            self.check_skill_report(skill.validate())?;
+        }
+
+        // Resolve registry skills together so they share one lockfile update
+        let registry_specs: Vec<&str> = self
+            .skills
+            .iter()
+            .filter_map(|skill| match &skill.kind {
+                SkillKind::Registry { spec } => Some(spec.as_str()),
+                _ => None,
+            })
+            .collect();
+        let registry_skills = match (&self.config.skill_registry, registry_specs.is_empty()) {
+            (_, true) => Vec::new(),
+            (Some(registry), false) => registry.resolve(&registry_specs).await?,
+            (None, false) => {
+                return Err(crate::Error::Config(format!(
+                    "skills {} need a skill registry; set one with VoidBox::skill_registry",
+                    registry_specs.join(", ")
+                )))
+            }
+        };
+
         // Collect MCP servers for mcp.json
         let mut mcp_servers = serde_json::Map::new();
 
@@ -816,6 +1519,10 @@ impl VoidBox {
                     );
                     match skill.fetch_remote_content().await {
                         Ok(content) => {
+                            self.check_skill_report(crate::skill_lint::validate_content(
+                                &skill.name,
+                                &content,
+                            ))?;
                             Self::write_skill_file(sandbox, &skill.name, content.as_bytes())
                                 .await?;
                             eprintln!("[vm:{}] Installed remote skill '{}'", tag, skill.name);
@@ -927,6 +1634,22 @@ impl VoidBox {
                         tag, skill.name, profile_path
                     );
                 }
+                SkillKind::Registry { .. } => {
+                    let Some(resolved) = registry_skills.iter().find(|r| r.name == skill.name)
+                    else {
+                        continue;
+                    };
+                    self.check_skill_report(crate::skill_lint::validate_content(
+                        &skill.name,
+                        &resolved.content,
+                    ))?;
+                    Self::write_skill_file(sandbox, &skill.name, resolved.content.as_bytes())
+                        .await?;
+                    eprintln!(
+                        "[vm:{}] Installing registry skill '{}@{}' from {}",
+                        tag, skill.name, resolved.version, resolved.source
+                    );
+                }
                 SkillKind::Inline { content } => {
                     Self::write_skill_file(sandbox, &skill.name, content.as_bytes()).await?;
                     eprintln!(
@@ -990,10 +1713,11 @@ impl VoidBox {
     }
 
     fn build_full_prompt(&self, input: Option<&[u8]>) -> String {
+        let prompt = self.prompt_with_history(&self.prompt);
         let Some(data) = input else {
             return format!(
                 "{}\n\nWrite your output to {}.",
-                self.prompt, self.config.output_file
+                prompt, self.config.output_file
             );
         };
         let input_text = String::from_utf8_lossy(data);
@@ -1009,10 +1733,28 @@ impl VoidBox {
             "{}\n\n--- Previous stage output ---\n{}\n--- End previous stage output ---\n\n\
              The above data is also available at /workspace/input.json.\n\
              Write your output to {}.",
-            self.prompt, inline, self.config.output_file
+            prompt, inline, self.config.output_file
         )
     }
 
+    /// `prompt`, preceded by the session's earlier turns when resuming.
+    fn prompt_with_history(&self, next: &str) -> String {
+        if self.history.is_empty() {
+            return next.to_string();
+        }
+        let mut prompt = String::from("--- Conversation so far ---\n");
+        for turn in &self.history {
+            prompt.push_str(&format!(
+                "User: {}\nAgent: {}\n\n",
+                truncate_chars(&turn.prompt, HISTORY_TURN_MAX_BYTES),
+                truncate_chars(&turn.response, HISTORY_TURN_MAX_BYTES)
+            ));
+        }
+        prompt.push_str("--- End conversation so far ---\n\n");
+        prompt.push_str(next);
+        prompt
+    }
+
     /// Run this Box: provision skills, execute the agent, return the result.
     ///
     /// If `input` is provided, it's written to `/workspace/input.json` before
@@ -1022,8 +1764,11 @@ impl VoidBox {
     /// is stopped gracefully before returning — on success and on error.
     /// Without that, teardown falls to the VM's `Drop` safety net, which
     /// logs an error on every one-shot run.
+    ///
+    /// With a [`session_file`](Self::session_file), the run is appended to
+    /// the session history and the session saved before returning.
     pub async fn run(
-        self,
+        mut self,
         input: Option<&[u8]>,
         telemetry_buffer: Option<TelemetryBuffer>,
     ) -> Result<StageResult> {
@@ -1031,7 +1776,22 @@ impl VoidBox {
         if let Some(sandbox) = &self.sandbox {
             let _ = sandbox.stop().await;
         }
-        result
+        let stage = result?;
+        self.history
+            .push(SessionTurn::new(&self.prompt, &stage.agent_result));
+        if !stage.agent_result.session_id.is_empty() {
+            self.agent_session_id = Some(stage.agent_result.session_id.clone());
+        }
+        if let Some(path) = &self.config.session_file {
+            self.save_session(path)?;
+        }
+        Ok(stage)
+    }
+
+    /// [`run`](Self::run) a Box with an
+    /// [output schema](Self::expect_json_schema) and deserialize its answer.
+    pub async fn run_json<T: serde::de::DeserializeOwned>(self, input: Option<&[u8]>) -> Result<T> {
+        self.run(input, None).await?.output_as()
     }
 
     async fn run_inner(
@@ -1043,51 +1803,26 @@ impl VoidBox {
             crate::Error::Config("VoidBox not built — call .build() first".into())
         })?;
 
-        // Provision security configuration (resource limits, command allowlist)
-        self.provision_security(sandbox).await?;
-
-        // Start guest telemetry (best-effort, don't fail the run)
-        let tag = &self.name;
-        match sandbox.start_telemetry(telemetry_buffer).await {
-            Ok(agg) => {
-                agg.set_current_stage(&self.name);
-                eprintln!("[vm:{}] Guest telemetry started", tag);
-            }
-            Err(e) => {
-                eprintln!("[vm:{}] Guest telemetry unavailable: {}", tag, e);
+        loop {
+            match self
+                .run_attempt(sandbox, input, telemetry_buffer.clone())
+                .await
+            {
+                // A restarted VM starts empty: provision everything again.
+                Err(e) if matches!(sandbox.recover(&e).await, Ok(true)) => {
+                    eprintln!(
+                        "[vm:{}] Guest restarted after: {} -- re-provisioning and re-running",
+                        self.name, e
+                    );
+                }
+                other => return other,
             }
         }
+    }
 
-        // Provision skills into the guest
-        self.provision_skills(sandbox).await?;
-
-        self.provision_claude_bootstrap(sandbox).await?;
-
-        // Start the credential proxy (opt-in) and capture the guest env to
-        // inject at exec time.
-        let active_proxy = self.maybe_setup_credential_proxy(sandbox).await?;
-
-        let tag = &self.name;
-
-        // Write input data if provided
-        if let Some(data) = input {
-            sandbox.write_file("/workspace/input.json", data).await?;
-            eprintln!(
-                "[vm:{}] Writing input ({} bytes) to /workspace/input.json",
-                tag,
-                data.len()
-            );
-        }
-
-        let full_prompt = self.build_full_prompt(input);
-
-        eprintln!(
-            "[vm:{}] Executing agent | llm={} | prompt_len={} chars",
-            tag,
-            self.config.llm.description(),
-            full_prompt.len()
-        );
-
+    /// CLI args every agent launch gets: Claude settings and, with MCP
+    /// skills, the MCP config.
+    fn agent_extra_args(&self) -> Vec<String> {
         let mut extra_args: Vec<String> = Vec::new();
         if self.config.llm.supports_claude_settings() {
             extra_args.extend([
@@ -1102,28 +1837,46 @@ impl VoidBox {
                 | SkillKind::Remote { .. }
                 | SkillKind::File { .. }
                 | SkillKind::Oci { .. }
+                | SkillKind::Registry { .. }
                 | SkillKind::Inline { .. } => false,
             });
             if has_mcp {
                 extra_args.extend(["--mcp-config".to_string(), MCP_CONFIG_PATH.to_string()]);
             }
         }
+        extra_args
+    }
+
+    /// Launch the agent once with `prompt`, behind the credential proxy if
+    /// one is configured.
+    async fn exec_agent(
+        &self,
+        sandbox: &Sandbox,
+        prompt: &str,
+        extra_args: Vec<String>,
+    ) -> Result<AgentExecResult> {
+        // Start the credential proxy (opt-in) and capture the guest env to
+        // inject at exec time.
+        let active_proxy = self.maybe_setup_credential_proxy(sandbox).await?;
 
         let proxy_env = active_proxy
             .as_ref()
             .map(|p| p.exec_env.clone())
             .unwrap_or_default();
 
-        let tag_clone = tag.to_string();
+        let tag_clone = self.name.clone();
         let exec_outcome = sandbox
             .exec_agent_streaming(
                 &self.config.llm,
-                &full_prompt,
+                prompt,
                 AgentExecOpts {
                     dangerously_skip_permissions: true,
                     extra_args,
                     timeout_secs: self.config.timeout_secs,
                     env: proxy_env,
+                    tool_hook: self.config.tool_hook.clone(),
+                    budget: self.config.budget,
+                    idle: self.config.idle,
                 },
                 |event| match event {
                     crate::observe::claude::AgentStreamEvent::ToolUse(ref tc) => {
@@ -1148,11 +1901,13 @@ impl VoidBox {
         // still reports a dollar amount using Anthropic pricing, so zero it.
         if self.config.llm.is_local() {
             agent_result.total_cost_usd = 0.0;
+        } else if let Some(pricing) = &self.config.pricing {
+            agent_result.fill_missing_cost(pricing);
         }
 
         eprintln!(
             "[vm:{}] Agent finished | tokens={}in/{}out | tools={} | cost=${:.4} | error={}",
-            tag,
+            self.name,
             agent_result.input_tokens,
             agent_result.output_tokens,
             agent_result.tool_calls.len(),
@@ -1160,6 +1915,118 @@ impl VoidBox {
             agent_result.is_error,
         );
 
+        Ok(agent_result)
+    }
+
+    /// One provision-and-run pass of [`run_inner`](Self::run_inner).
+    async fn run_attempt(
+        &self,
+        sandbox: &Arc<Sandbox>,
+        input: Option<&[u8]>,
+        telemetry_buffer: Option<TelemetryBuffer>,
+    ) -> Result<StageResult> {
+        // Provision security configuration (resource limits, command allowlist)
+        self.provision_security(sandbox).await?;
+
+        // Start guest telemetry (best-effort, don't fail the run)
+        let tag = &self.name;
+        match sandbox.start_telemetry(telemetry_buffer).await {
+            Ok(agg) => {
+                agg.set_current_stage(&self.name);
+                eprintln!("[vm:{}] Guest telemetry started", tag);
+            }
+            Err(e) => {
+                eprintln!("[vm:{}] Guest telemetry unavailable: {}", tag, e);
+            }
+        }
+
+        // Provision skills into the guest
+        self.provision_skills(sandbox).await?;
+
+        self.provision_claude_bootstrap(sandbox).await?;
+
+        let tag = &self.name;
+
+        // Write input data if provided
+        if let Some(data) = input {
+            sandbox.write_file("/workspace/input.json", data).await?;
+            eprintln!(
+                "[vm:{}] Writing input ({} bytes) to /workspace/input.json",
+                tag,
+                data.len()
+            );
+        }
+
+        let baseline = if self.config.capture_patch {
+            match sandbox.git_baseline().await {
+                Ok(base) => Some(base),
+                Err(e) => {
+                    eprintln!("[vm:{}] Workspace patch unavailable: {}", tag, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let schema = self
+            .config
+            .output_schema
+            .as_ref()
+            .map(OutputSchema::compile)
+            .transpose()?;
+        let mut full_prompt = self.build_full_prompt(input);
+        if let Some(schema) = &schema {
+            full_prompt.push_str("\n\n");
+            full_prompt.push_str(&schema.instructions());
+        }
+
+        eprintln!(
+            "[vm:{}] Executing agent | llm={} | prompt_len={} chars",
+            tag,
+            self.config.llm.description(),
+            full_prompt.len()
+        );
+
+        let extra_args = self.agent_extra_args();
+
+        let mut agent_result = self
+            .exec_agent(sandbox, &full_prompt, extra_args.clone())
+            .await?;
+        let mut structured_output = None;
+        if let Some(schema) = &schema {
+            let mut attempt = 0;
+            while !agent_result.is_error {
+                let errors = match schema.check(&agent_result.result_text) {
+                    Ok(value) => {
+                        structured_output = Some(value);
+                        break;
+                    }
+                    Err(errors) => errors,
+                };
+                attempt += 1;
+                if attempt > self.config.schema_retries {
+                    agent_result.is_error = true;
+                    agent_result.error = Some(format!(
+                        "answer does not match the output schema after {} attempts: {}",
+                        attempt,
+                        errors.join("; ")
+                    ));
+                    break;
+                }
+                eprintln!(
+                    "[vm:{}] Answer does not match the output schema ({}); retry {}/{}",
+                    tag,
+                    errors.join("; "),
+                    attempt,
+                    self.config.schema_retries
+                );
+                let retry = schema.retry_prompt(&agent_result.result_text, &errors);
+                let next = self.exec_agent(sandbox, &retry, extra_args.clone()).await?;
+                agent_result = merge_attempts(agent_result, next);
+            }
+        }
+
         // Try to read the output file
         let file_output = match sandbox.read_file(&self.config.output_file).await {
             Ok(data) if !data.is_empty() => {
@@ -1174,13 +2041,166 @@ impl VoidBox {
             _ => None,
         };
 
+        let patch = match baseline {
+            Some(_) => match sandbox.git_patch().await {
+                Ok(patch) => {
+                    eprintln!(
+                        "[vm:{}] Workspace patch: {} files (+{} -{})",
+                        tag,
+                        patch.files.len(),
+                        patch.additions(),
+                        patch.deletions()
+                    );
+                    Some(patch)
+                }
+                Err(e) => {
+                    eprintln!("[vm:{}] Workspace patch unavailable: {}", tag, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
         Ok(StageResult {
             box_name: self.name.clone(),
             agent_result,
             file_output,
+            structured_output,
+            patch,
+            cached: false,
         })
     }
 
+    /// Send one turn of a multi-turn conversation with the agent.
+    ///
+    /// Unlike [`run()`](Self::run), the Box and its VM stay up between
+    /// turns: skills are provisioned on the first turn, and later turns
+    /// continue the same agent conversation (`--resume` for claude-code
+    /// based providers). The Box's own [`prompt`](Self::prompt), if set,
+    /// opens the conversation. Providers that cannot resume, and turns after
+    /// the VM was replaced under a [`RestartPolicy`], get the earlier turns
+    /// replayed in the prompt instead.
+    ///
+    /// Each turn returns the agent's result with the cumulative
+    /// [`ChatUsage`], observed by the sandbox's observer. Call
+    /// [`stop()`](Self::stop) when the conversation is over.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::agent_box::VoidBox;
+    ///
+    /// # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut vbox = VoidBox::new("pair").build()?;
+    /// let first = vbox.chat("Write a failing test for parse_date").await?;
+    /// let second = vbox.chat("Now make it pass").await?;
+    /// println!("{} (${:.4} so far)", second.result.result.result_text, second.result.usage.total_cost_usd);
+    /// # let _ = first;
+    /// vbox.stop().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn chat(&mut self, prompt: impl Into<String>) -> Result<ObservedResult<ChatTurn>> {
+        let prompt = prompt.into();
+        let started_at = std::time::SystemTime::now();
+        let started = std::time::Instant::now();
+        let sandbox = self.sandbox.clone().ok_or_else(|| {
+            crate::Error::Config("VoidBox not built — call .build() first".into())
+        })?;
+
+        let result = loop {
+            match self.chat_attempt(&sandbox, &prompt).await {
+                // The conversation lived in the old guest: provision the new
+                // one and replay the turns so far.
+                Err(e) if matches!(sandbox.recover(&e).await, Ok(true)) => {
+                    eprintln!(
+                        "[vm:{}] Guest restarted after: {} -- re-provisioning and replaying the conversation",
+                        self.name, e
+                    );
+                    self.chat_provisioned = false;
+                    self.chat_session_id = None;
+                }
+                other => break other?,
+            }
+        };
+
+        if !result.session_id.is_empty() {
+            self.chat_session_id = Some(result.session_id.clone());
+            self.agent_session_id = Some(result.session_id.clone());
+        }
+        self.history.push(SessionTurn::new(&prompt, &result));
+        self.chat_usage.add(&result);
+        if let Some(path) = &self.config.session_file {
+            self.save_session(path)?;
+        }
+
+        let observer = sandbox
+            .observer()
+            .cloned()
+            .unwrap_or_else(|| Observer::new(ObserveConfig::default()));
+        let mut manifest =
+            RunManifest::new(RunKind::Chat, &self.name, started_at, started.elapsed());
+        manifest.prompt = Some(prompt);
+        self.record_provenance(&mut manifest);
+        Ok(ObservedResult::new(
+            ChatTurn {
+                turn: self.chat_usage.turns,
+                result,
+                usage: self.chat_usage,
+            },
+            &observer,
+        )
+        .with_manifest(manifest, &observer))
+    }
+
+    /// One [`chat`](Self::chat) turn on the current VM.
+    async fn chat_attempt(&mut self, sandbox: &Sandbox, prompt: &str) -> Result<AgentExecResult> {
+        if !self.chat_provisioned {
+            self.provision_security(sandbox).await?;
+            self.provision_skills(sandbox).await?;
+            self.provision_claude_bootstrap(sandbox).await?;
+            self.chat_provisioned = true;
+        }
+
+        let mut extra_args = self.agent_extra_args();
+        let resume = self
+            .chat_session_id
+            .as_deref()
+            .and_then(|id| self.config.llm.resume_args(id));
+        let full_prompt = match resume {
+            Some(args) => {
+                extra_args.extend(args);
+                prompt.to_string()
+            }
+            None if self.history.is_empty() && !self.prompt.is_empty() => {
+                format!("{}\n\n{}", self.prompt, prompt)
+            }
+            None => self.prompt_with_history(prompt),
+        };
+
+        eprintln!(
+            "[vm:{}] Chat turn {} | llm={} | prompt_len={} chars",
+            self.name,
+            self.chat_usage.turns + 1,
+            self.config.llm.description(),
+            full_prompt.len()
+        );
+        self.exec_agent(sandbox, &full_prompt, extra_args).await
+    }
+
+    /// Totals across this Box's [`chat`](Self::chat) turns so far.
+    pub fn chat_usage(&self) -> ChatUsage {
+        self.chat_usage
+    }
+
+    /// Stop the Box's VM, ending a [`chat`](Self::chat) conversation.
+    pub async fn stop(&self) -> Result<()> {
+        match &self.sandbox {
+            Some(sandbox) => sandbox.stop().await,
+            None => Ok(()),
+        }
+    }
+
     /// Run this Box as a long-running service.
     ///
     /// Provisions skills and launches the agent identically to [`run()`](Self::run),
@@ -1250,29 +2270,12 @@ impl VoidBox {
 
         // ── Build CLI args ─────────────────────────────────────────────
 
-        let mut extra_args: Vec<String> = Vec::new();
-        if self.config.llm.supports_claude_settings() {
-            extra_args.extend([
-                "--settings".to_string(),
-                r#"{"skipWebFetchPreflight":true}"#.to_string(),
-            ]);
-
-            let has_mcp = self.skills.iter().any(|s| match &s.kind {
-                SkillKind::Mcp { .. } => true,
-                SkillKind::Cli { .. }
-                | SkillKind::Agent { .. }
-                | SkillKind::Remote { .. }
-                | SkillKind::File { .. }
-                | SkillKind::Oci { .. }
-                | SkillKind::Inline { .. } => false,
-            });
-            if has_mcp {
-                extra_args.extend(["--mcp-config".to_string(), MCP_CONFIG_PATH.to_string()]);
-            }
-        }
+        let extra_args = self.agent_extra_args();
 
         let is_local_llm = self.config.llm.is_local();
         let llm_provider = self.config.llm.clone();
+        let tool_hook = self.config.tool_hook.clone();
+        let budget = self.config.budget;
         let output_file = self.config.output_file.clone();
         let box_name = self.name.clone();
 
@@ -1309,6 +2312,8 @@ impl VoidBox {
                     dangerously_skip_permissions: true,
                     extra_args,
                     timeout_secs: Some(0),
+                    tool_hook,
+                    budget,
                     ..Default::default()
                 },
                 |event| match event {
@@ -1728,4 +2733,173 @@ mod tests {
         let result = ab.run(None, None).await.unwrap();
         assert_eq!(result.box_name, "test_box");
     }
+
+    #[tokio::test]
+    async fn test_capture_patch_attaches_workspace_patch() {
+        let build = |capture: bool| {
+            VoidBox::new("patcher")
+                .skill(Skill::agent("claude-code"))
+                .prompt("Edit the code")
+                .capture_patch(capture)
+                .mock()
+                .build()
+                .unwrap()
+        };
+
+        let captured = build(true).run(None, None).await.unwrap();
+        assert_eq!(captured.patch, Some(crate::sandbox::GitPatch::default()));
+        let plain = build(false).run(None, None).await.unwrap();
+        assert!(plain.patch.is_none());
+        assert_ne!(
+            build(true).cache_key(None).unwrap(),
+            build(false).cache_key(None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cache_key_needs_a_pinned_git_workspace() {
+        let build = |rev: &str| {
+            VoidBox::new("seeded")
+                .skill(Skill::agent("claude-code"))
+                .prompt("Review the code")
+                .git_workspace("https://example.com/r.git", rev)
+                .mock()
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(build("main").cache_key(None).unwrap(), None);
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        assert!(build(commit).cache_key(None).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_budget_stops_run_with_partial_result() {
+        let ab = VoidBox::new("budgeted")
+            .skill(Skill::agent("claude-code"))
+            .prompt("Do something")
+            .budget(Budget::default().max_tokens(1))
+            .mock()
+            .build()
+            .unwrap();
+
+        let err = ab.run(None, None).await.unwrap_err();
+        let crate::Error::BudgetExceeded(exceeded) = err else {
+            panic!("expected BudgetExceeded, got {err:?}");
+        };
+        assert_eq!(
+            exceeded.limit,
+            crate::budget::BudgetLimit::Tokens { limit: 1, used: 2 }
+        );
+        assert_eq!(exceeded.partial.session_id, "mock_sess");
+        assert!(exceeded.partial.is_error);
+    }
+
+    #[tokio::test]
+    async fn test_output_schema_retries_then_fails_stage() {
+        let schema = serde_json::json!({"type": "object", "required": ["score"]});
+        let build = || {
+            VoidBox::new("rater")
+                .skill(Skill::agent("claude-code"))
+                .prompt("Rate it")
+                .expect_json_schema(schema.clone())
+                .schema_retries(1)
+                .mock()
+                .build()
+                .unwrap()
+        };
+
+        // The mock agent answers with prose, never JSON.
+        let stage = build().run(None, None).await.unwrap();
+        assert!(stage.agent_result.is_error);
+        let error = stage.agent_result.error.as_deref().unwrap();
+        assert!(error.contains("after 2 attempts"), "{error}");
+        assert_eq!(stage.agent_result.input_tokens, 2);
+        assert!(stage.structured_output.is_none());
+
+        let err = build()
+            .run_json::<serde_json::Value>(None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::StructuredOutput(_)), "{err:?}");
+
+        let invalid = VoidBox::new("rater")
+            .expect_json_schema(serde_json::json!({"type": 12}))
+            .mock()
+            .build()
+            .unwrap();
+        let err = invalid.run(None, None).await.unwrap_err();
+        assert!(
+            err.to_string().contains("invalid output JSON schema"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_saved_after_run_and_resumed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+
+        let ab = VoidBox::new("analyst")
+            .skill(Skill::agent("claude-code"))
+            .llm(LlmProvider::ollama("qwen3-coder"))
+            .workspace_volume("analyst-ws")
+            .session_file(&path)
+            .prompt("Fetch the data")
+            .mock()
+            .build()
+            .unwrap();
+        let result = ab.run(None, None).await.unwrap();
+
+        let resumed = VoidBox::resume_session(&path).unwrap().prompt("Chart it");
+        assert_eq!(resumed.name, "analyst");
+        assert_eq!(resumed.skills.len(), 1);
+        assert!(matches!(resumed.config.llm, LlmProvider::Ollama { .. }));
+        assert_eq!(
+            resumed.config.workspace_volume.as_deref(),
+            Some("analyst-ws")
+        );
+        assert_eq!(resumed.history().len(), 1);
+        assert_eq!(resumed.history()[0].prompt, "Fetch the data");
+        assert_eq!(
+            resumed.history()[0].response,
+            result.agent_result.result_text
+        );
+
+        let prompt = resumed.build_full_prompt(None);
+        assert!(prompt.starts_with("--- Conversation so far ---\nUser: Fetch the data\n"));
+        assert!(prompt.contains("--- End conversation so far ---\n\nChart it"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_resumes_agent_session_and_accumulates_usage() {
+        let mut ab = VoidBox::new("pair")
+            .skill(Skill::agent("claude-code"))
+            .mock()
+            .build()
+            .unwrap();
+
+        let first = ab.chat("Write a failing test").await.unwrap();
+        assert_eq!(first.result.turn, 1);
+        assert_eq!(first.result.result.session_id, "mock_sess");
+        assert_eq!(ab.chat_session_id.as_deref(), Some("mock_sess"));
+
+        // Resumed turns send only the new prompt; the agent keeps the rest.
+        let second = ab.chat("Now make it pass").await.unwrap();
+        assert_eq!(second.result.turn, 2);
+        assert_eq!(second.result.result.result_text, "[mock] Now make it pass");
+        assert_eq!(second.result.usage.turns, 2);
+        assert_eq!(second.result.usage.input_tokens, 2);
+        assert_eq!(second.result.usage.output_tokens, 2);
+        assert_eq!(ab.chat_usage(), second.result.usage);
+        assert_eq!(ab.history().len(), 2);
+
+        ab.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_truncate_chars_respects_char_boundaries() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("héllo", 2), "h...(truncated)");
+    }
 }
@@ -0,0 +1,604 @@
+//! Agent CLIs driven inside the sandbox.
+//!
+//! An [`AgentRunner`] tells the sandbox how to start one agent CLI and how
+//! to read what it prints: [`build_command`](AgentRunner::build_command)
+//! turns a prompt into a guest command line,
+//! [`parse_event`](AgentRunner::parse_event) folds each stdout line into an
+//! [`AgentExecResult`] and [`summarize`](AgentRunner::summarize) settles the
+//! result once the CLI has exited. Everything downstream — tool hooks,
+//! budgets, [`AgentStreamEvent`]s, OTel spans, pipelines — works on that
+//! result and so on any runner.
+//!
+//! - [`ClaudeCodeRunner`] — claude-code's `--output-format stream-json`,
+//!   used by the Claude, Ollama, LM Studio and custom providers
+//! - [`CodexRunner`] — `codex exec --json`
+//! - [`JsonlRunner`] — any other CLI printing one JSON object per line
+//!   (aider, open-interpreter, in-house agents), configured with JSON
+//!   pointers to the fields it reports
+//!
+//! [`LlmProvider::runner`](crate::llm::LlmProvider::runner) picks the runner
+//! for a provider; [`Sandbox::exec_runner`](crate::sandbox::Sandbox::exec_runner)
+//! runs any runner directly.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use void_box::agent_runner::JsonlRunner;
+//! use void_box::observe::claude::AgentExecOpts;
+//! use void_box::sandbox::Sandbox;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let sandbox = Sandbox::local().build()?;
+//! let runner = JsonlRunner::new("my-agent", ["run", "--json", "{prompt}"])
+//!     .result_text("/result")
+//!     .tokens("/usage/input_tokens", "/usage/output_tokens")
+//!     .tool_call("/tool/name", "/tool/input");
+//! let result = sandbox
+//!     .exec_runner(&runner, "fix the failing test", AgentExecOpts::default(), |_| {})
+//!     .await?;
+//! println!("{} tool calls", result.tool_calls.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::budget::{BudgetUsage, UsageCounter};
+use crate::llm::LlmProvider;
+use crate::observe::claude::{
+    parse_jsonl_line, AgentExecOpts, AgentExecResult, AgentStreamEvent, ClaudeToolCall,
+};
+use crate::{Error, Result};
+
+/// Tracing target for agent stdout lines. Runners whose output is not
+/// already telemetry (Codex, generic JSONL CLIs) forward every stdout line
+/// through this target for structured logging.
+pub(crate) const AGENT_STDOUT_TARGET: &str = "agent_stdout";
+
+/// Placeholder in [`JsonlRunner`] args replaced by the prompt.
+pub const PROMPT_PLACEHOLDER: &str = "{prompt}";
+
+/// A guest command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// What a run has produced so far, threaded through
+/// [`AgentRunner::parse_event`].
+#[derive(Debug, Clone, Default)]
+pub struct AgentRunState {
+    pub result: AgentExecResult,
+    /// Tool-use id → index into `result.tool_calls`, for matching results
+    /// to their calls.
+    pub tool_index: HashMap<String, usize>,
+    /// Per-message usage, for runners that override
+    /// [`AgentRunner::usage`].
+    pub usage: UsageCounter,
+}
+
+impl AgentRunState {
+    /// Whether nothing at all has been parsed.
+    pub fn is_empty(&self) -> bool {
+        let result = &self.result;
+        result.session_id.is_empty()
+            && result.model.is_empty()
+            && result.result_text.is_empty()
+            && result.tool_calls.is_empty()
+            && result.input_tokens == 0
+            && result.output_tokens == 0
+            && !result.is_error
+    }
+}
+
+/// How the agent process ended.
+#[derive(Debug, Clone, Default)]
+pub struct AgentExit {
+    /// `-1` when the exec itself failed.
+    pub exit_code: i32,
+    pub stderr: String,
+    /// Error reported by the exec layer rather than the agent.
+    pub error: Option<String>,
+}
+
+/// Starts one agent CLI and reads its output.
+pub trait AgentRunner: Send + Sync {
+    /// The guest command running `prompt`.
+    fn build_command(&self, prompt: &str, opts: &AgentExecOpts) -> AgentCommand;
+
+    /// Fold one stdout line into `state`, returning the events to emit now.
+    fn parse_event(&self, line: &str, state: &mut AgentRunState) -> Vec<AgentStreamEvent>;
+
+    /// The run's result once the agent has exited.
+    fn summarize(&self, state: AgentRunState, exit: &AgentExit) -> Result<AgentExecResult>;
+
+    /// Whether tool calls are reported before they run, so a
+    /// [`ToolHook`](crate::tool_hook::ToolHook) can still stop them. Hooks
+    /// are rejected for runners that only report finished calls.
+    fn streams_tool_calls(&self) -> bool {
+        false
+    }
+
+    /// Usage so far, checked against the run's
+    /// [`Budget`](crate::budget::Budget) after every line.
+    fn usage(&self, state: &AgentRunState) -> BudgetUsage {
+        BudgetUsage::of(&state.result)
+    }
+
+    /// When set, the sandbox checks the program is in the guest `PATH`
+    /// before running it and fails with this hint if it is not.
+    fn install_hint(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// claude-code's `--output-format stream-json`.
+#[derive(Debug, Clone)]
+pub struct ClaudeCodeRunner {
+    provider: LlmProvider,
+}
+
+impl ClaudeCodeRunner {
+    /// `provider` should be claude-code based: Claude, ClaudePersonal,
+    /// Ollama, LM Studio or a custom endpoint.
+    pub fn new(provider: LlmProvider) -> Self {
+        Self { provider }
+    }
+}
+
+impl AgentRunner for ClaudeCodeRunner {
+    fn build_command(&self, prompt: &str, opts: &AgentExecOpts) -> AgentCommand {
+        AgentCommand {
+            program: self.provider.binary_name().to_string(),
+            args: self.provider.build_exec_args(
+                prompt,
+                opts.dangerously_skip_permissions,
+                &opts.extra_args,
+            ),
+        }
+    }
+
+    fn parse_event(&self, line: &str, state: &mut AgentRunState) -> Vec<AgentStreamEvent> {
+        let events = parse_jsonl_line(line, &mut state.result, &mut state.tool_index);
+        let Ok(event) = serde_json::from_str::<Value>(line.trim()) else {
+            return events;
+        };
+        match event.get("type").and_then(Value::as_str) {
+            Some("assistant") => {
+                if let Some(message) = event.get("message") {
+                    let str_field = |key| message.get(key).and_then(Value::as_str).unwrap_or("");
+                    let tokens = ["input_tokens", "output_tokens"]
+                        .iter()
+                        .filter_map(|key| message.get("usage")?.get(key)?.as_u64())
+                        .sum();
+                    state
+                        .usage
+                        .message(str_field("id"), str_field("model"), tokens);
+                }
+            }
+            Some("result") => state.usage.report(BudgetUsage::of(&state.result)),
+            _ => {}
+        }
+        events
+    }
+
+    fn summarize(&self, state: AgentRunState, exit: &AgentExit) -> Result<AgentExecResult> {
+        // claude-code exits 1 when the task fails but still produces valid
+        // stream-json, so only a silent run is an exec failure.
+        if state.is_empty() {
+            let stderr = exit.stderr.trim();
+            let error = exit.error.as_deref().unwrap_or_default().trim();
+            return Err(Error::Guest(format!(
+                "claude-code returned no stream-json events (exit_code={}). stderr: {}. error: {}",
+                exit.exit_code,
+                if stderr.is_empty() { "(empty)" } else { stderr },
+                if error.is_empty() { "(empty)" } else { error },
+            )));
+        }
+        let mut result = state.result;
+        if result.is_error && result.error.as_deref().is_none_or(str::is_empty) {
+            result.error = Some(fallback_error_message(
+                &exit.stderr,
+                &result.result_text,
+                exit.error.as_deref(),
+                self.provider.binary_name(),
+            ));
+        }
+        Ok(result)
+    }
+
+    fn streams_tool_calls(&self) -> bool {
+        true
+    }
+
+    fn usage(&self, state: &AgentRunState) -> BudgetUsage {
+        let mut usage = state.usage.usage();
+        // Local models are free.
+        if self.provider.is_local() {
+            usage.cost_usd = 0.0;
+        }
+        usage
+    }
+
+    fn install_hint(&self) -> Option<&str> {
+        Some(
+            "Build a production guest image with claude-code and set VOID_BOX_INITRAMFS: \
+`scripts/build_claude_rootfs.sh` then `export VOID_BOX_INITRAMFS=target/void-box-rootfs.cpio.gz`.",
+        )
+    }
+}
+
+/// `codex exec --json`. Codex reports tool calls once they have run.
+#[derive(Debug, Clone)]
+pub struct CodexRunner {
+    provider: LlmProvider,
+}
+
+impl CodexRunner {
+    pub fn new(provider: LlmProvider) -> Self {
+        Self { provider }
+    }
+}
+
+impl AgentRunner for CodexRunner {
+    fn build_command(&self, prompt: &str, opts: &AgentExecOpts) -> AgentCommand {
+        AgentCommand {
+            program: self.provider.binary_name().to_string(),
+            args: self.provider.build_exec_args(
+                prompt,
+                opts.dangerously_skip_permissions,
+                &opts.extra_args,
+            ),
+        }
+    }
+
+    fn parse_event(&self, line: &str, state: &mut AgentRunState) -> Vec<AgentStreamEvent> {
+        if !line.trim().is_empty() {
+            tracing::info!(target: AGENT_STDOUT_TARGET, "{}", line.trim_end());
+        }
+        crate::observe::codex::parse_codex_line(line, &mut state.result);
+        Vec::new()
+    }
+
+    fn summarize(&self, state: AgentRunState, exit: &AgentExit) -> Result<AgentExecResult> {
+        let mut result = state.result;
+        if exit.exit_code != 0 {
+            result.is_error = true;
+        }
+        Ok(result)
+    }
+}
+
+/// Any CLI printing one JSON object per line.
+///
+/// Each field is read through a JSON pointer (RFC 6901, e.g.
+/// `/usage/input_tokens`) on every line it resolves in. Text fields keep
+/// the last value seen; token counts are added up, and each line reporting
+/// tokens counts as a turn. Lines that are not JSON are appended to the
+/// result text when no [`result_text`](Self::result_text) pointer is set,
+/// so plain-text CLIs still report their answer.
+///
+/// A non-zero exit marks the result as an error.
+#[derive(Debug, Clone, Default)]
+pub struct JsonlRunner {
+    program: String,
+    args: Vec<String>,
+    result_text: Option<String>,
+    session_id: Option<String>,
+    model: Option<String>,
+    input_tokens: Option<String>,
+    output_tokens: Option<String>,
+    tool_name: Option<String>,
+    tool_input: Option<String>,
+    tool_id: Option<String>,
+    error: Option<String>,
+    streams_tool_calls: bool,
+}
+
+impl JsonlRunner {
+    /// Run `program` with `args`. An arg containing [`PROMPT_PLACEHOLDER`]
+    /// has it replaced by the prompt; without one the prompt is passed as
+    /// the last arg. [`AgentExecOpts::extra_args`] go after `args`.
+    pub fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn result_text(mut self, pointer: impl Into<String>) -> Self {
+        self.result_text = Some(pointer.into());
+        self
+    }
+
+    pub fn session_id(mut self, pointer: impl Into<String>) -> Self {
+        self.session_id = Some(pointer.into());
+        self
+    }
+
+    pub fn model(mut self, pointer: impl Into<String>) -> Self {
+        self.model = Some(pointer.into());
+        self
+    }
+
+    pub fn tokens(mut self, input: impl Into<String>, output: impl Into<String>) -> Self {
+        self.input_tokens = Some(input.into());
+        self.output_tokens = Some(output.into());
+        self
+    }
+
+    /// A line where `name` resolves to a string is a tool call, with its
+    /// input at `input`.
+    pub fn tool_call(mut self, name: impl Into<String>, input: impl Into<String>) -> Self {
+        self.tool_name = Some(name.into());
+        self.tool_input = Some(input.into());
+        self
+    }
+
+    pub fn tool_id(mut self, pointer: impl Into<String>) -> Self {
+        self.tool_id = Some(pointer.into());
+        self
+    }
+
+    /// A line where `pointer` resolves to a string marks the run as failed
+    /// with that message.
+    pub fn error(mut self, pointer: impl Into<String>) -> Self {
+        self.error = Some(pointer.into());
+        self
+    }
+
+    /// Declare that the CLI prints each tool call before running it, which
+    /// lets [tool hooks](crate::tool_hook) hold or deny it.
+    pub fn streams_tool_calls(mut self, streams: bool) -> Self {
+        self.streams_tool_calls = streams;
+        self
+    }
+}
+
+/// The string at `pointer` in `event`, if both are there.
+fn text_at(event: &Value, pointer: Option<&str>) -> Option<String> {
+    Some(event.pointer(pointer?)?.as_str()?.to_string())
+}
+
+fn count_at(event: &Value, pointer: Option<&str>) -> Option<u64> {
+    event.pointer(pointer?)?.as_u64()
+}
+
+impl AgentRunner for JsonlRunner {
+    fn build_command(&self, prompt: &str, opts: &AgentExecOpts) -> AgentCommand {
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(PROMPT_PLACEHOLDER, prompt))
+            .collect();
+        args.extend(opts.extra_args.iter().cloned());
+        if !self.args.iter().any(|arg| arg.contains(PROMPT_PLACEHOLDER)) {
+            args.push(prompt.to_string());
+        }
+        AgentCommand {
+            program: self.program.clone(),
+            args,
+        }
+    }
+
+    fn parse_event(&self, line: &str, state: &mut AgentRunState) -> Vec<AgentStreamEvent> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Vec::new();
+        }
+        tracing::info!(target: AGENT_STDOUT_TARGET, "{}", line.trim_end());
+
+        let result = &mut state.result;
+        let event = match serde_json::from_str::<Value>(trimmed) {
+            Ok(event) if event.is_object() => event,
+            _ => {
+                if self.result_text.is_none() {
+                    if !result.result_text.is_empty() {
+                        result.result_text.push('\n');
+                    }
+                    result.result_text.push_str(line.trim_end());
+                }
+                return Vec::new();
+            }
+        };
+
+        if let Some(text) = text_at(&event, self.result_text.as_deref()) {
+            result.result_text = text;
+        }
+        if let Some(session_id) = text_at(&event, self.session_id.as_deref()) {
+            result.session_id = session_id;
+        }
+        if let Some(model) = text_at(&event, self.model.as_deref()) {
+            result.model = model;
+        }
+        let input = count_at(&event, self.input_tokens.as_deref());
+        let output = count_at(&event, self.output_tokens.as_deref());
+        if input.is_some() || output.is_some() {
+            result.input_tokens += input.unwrap_or(0);
+            result.output_tokens += output.unwrap_or(0);
+            result.num_turns += 1;
+        }
+        if let Some(error) = text_at(&event, self.error.as_deref()) {
+            result.is_error = true;
+            result.error = Some(error);
+        }
+
+        let Some(tool_name) = text_at(&event, self.tool_name.as_deref()) else {
+            return Vec::new();
+        };
+        let call = ClaudeToolCall {
+            tool_name,
+            tool_use_id: text_at(&event, self.tool_id.as_deref()).unwrap_or_default(),
+            input: self
+                .tool_input
+                .as_deref()
+                .and_then(|pointer| event.pointer(pointer))
+                .cloned()
+                .unwrap_or(Value::Null),
+            output: None,
+            ..Default::default()
+        };
+        if !call.tool_use_id.is_empty() {
+            state
+                .tool_index
+                .insert(call.tool_use_id.clone(), result.tool_calls.len());
+        }
+        result.tool_calls.push(call.clone());
+        vec![AgentStreamEvent::ToolUse(call)]
+    }
+
+    fn summarize(&self, state: AgentRunState, exit: &AgentExit) -> Result<AgentExecResult> {
+        let mut result = state.result;
+        if exit.exit_code != 0 {
+            result.is_error = true;
+        }
+        if result.is_error && result.error.as_deref().is_none_or(str::is_empty) {
+            result.error = Some(fallback_error_message(
+                &exit.stderr,
+                &result.result_text,
+                exit.error.as_deref(),
+                &self.program,
+            ));
+        }
+        Ok(result)
+    }
+
+    fn streams_tool_calls(&self) -> bool {
+        self.streams_tool_calls
+    }
+}
+
+/// Produce a human-readable fallback error message when the agent reported
+/// `is_error=true` but left `error` empty.  Tries, in order: guest stderr,
+/// agent `result_text`, the optional exec-layer error (e.g. from the
+/// streaming `response.error` field), and a default `"<binary> exited with
+/// an unspecified error"` string.  Callers pass whichever signals are
+/// available for their code path.
+pub(crate) fn fallback_error_message(
+    stderr: &str,
+    result_text: &str,
+    response_error: Option<&str>,
+    binary_name: &str,
+) -> String {
+    let trimmed_stderr = stderr.trim();
+    if !trimmed_stderr.is_empty() {
+        return trimmed_stderr.to_string();
+    }
+    let trimmed_result = result_text.trim();
+    if !trimmed_result.is_empty() {
+        return trimmed_result.to_string();
+    }
+    if let Some(err) = response_error {
+        let trimmed = err.trim();
+        if !trimmed.is_empty() {
+            return trimmed.to_string();
+        }
+    }
+    format!("{} exited with an unspecified error", binary_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(runner: &dyn AgentRunner, lines: &[&str]) -> (AgentRunState, usize) {
+        let mut state = AgentRunState::default();
+        let events = lines
+            .iter()
+            .map(|line| runner.parse_event(line, &mut state).len())
+            .sum();
+        (state, events)
+    }
+
+    #[test]
+    fn test_jsonl_runner_places_prompt() {
+        let opts = AgentExecOpts {
+            extra_args: vec!["--yes".into()],
+            ..Default::default()
+        };
+        let placed = JsonlRunner::new("agent", ["run", "--message={prompt}"]);
+        assert_eq!(
+            placed.build_command("hi", &opts).args,
+            ["run", "--message=hi", "--yes"]
+        );
+        let trailing = JsonlRunner::new("aider", ["--no-pretty"]);
+        assert_eq!(
+            trailing.build_command("hi", &opts).args,
+            ["--no-pretty", "--yes", "hi"]
+        );
+    }
+
+    #[test]
+    fn test_jsonl_runner_reads_pointers() {
+        let runner = JsonlRunner::new("agent", ["{prompt}"])
+            .result_text("/result")
+            .session_id("/session")
+            .tokens("/usage/in", "/usage/out")
+            .tool_call("/tool/name", "/tool/args")
+            .tool_id("/tool/id");
+        let (state, events) = run(
+            &runner,
+            &[
+                r#"{"session":"s1","usage":{"in":10,"out":2}}"#,
+                "warming up",
+                r#"{"tool":{"id":"t1","name":"shell","args":{"cmd":"ls"}},"usage":{"in":5,"out":1}}"#,
+                r#"{"result":"done"}"#,
+            ],
+        );
+        assert_eq!(events, 1);
+        let result = runner
+            .summarize(state, &AgentExit::default())
+            .expect("summary");
+        assert_eq!(result.session_id, "s1");
+        assert_eq!(result.result_text, "done");
+        assert_eq!(result.input_tokens + result.output_tokens, 18);
+        assert_eq!(result.num_turns, 2);
+        assert_eq!(result.tool_calls[0].tool_name, "shell");
+        assert_eq!(result.tool_calls[0].input["cmd"], "ls");
+        assert!(!result.is_error);
+    }
+
+    #[test]
+    fn test_jsonl_runner_plain_text_and_exit_code() {
+        let runner = JsonlRunner::new("aider", ["--message"]);
+        let (state, _) = run(&runner, &["Applied edit to main.py", "Commit abc123"]);
+        let result = runner
+            .summarize(
+                state,
+                &AgentExit {
+                    exit_code: 2,
+                    stderr: "rate limited\n".into(),
+                    error: None,
+                },
+            )
+            .expect("summary");
+        assert_eq!(result.result_text, "Applied edit to main.py\nCommit abc123");
+        assert!(result.is_error);
+        assert_eq!(result.error.as_deref(), Some("rate limited"));
+    }
+
+    #[test]
+    fn test_claude_runner_counts_usage_and_requires_output() {
+        let runner = ClaudeCodeRunner::new(LlmProvider::Claude);
+        let assistant = r#"{"type":"assistant","message":{"id":"m1","model":"claude-sonnet-4","content":[],"usage":{"input_tokens":400,"output_tokens":100}}}"#;
+        let (state, _) = run(&runner, &[assistant, assistant]);
+        let usage = runner.usage(&state);
+        assert_eq!((usage.tokens, usage.turns), (500, 1));
+        assert!((usage.cost_usd - 0.0075).abs() < 1e-9);
+
+        let local = ClaudeCodeRunner::new(LlmProvider::ollama("qwen3-coder"));
+        assert_eq!(local.usage(&state).cost_usd, 0.0);
+
+        let err = runner
+            .summarize(AgentRunState::default(), &AgentExit::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("no stream-json events"));
+    }
+}
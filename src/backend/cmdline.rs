@@ -0,0 +1,165 @@
+//! User-supplied guest kernel cmdline fragments.
+//!
+//! A fragment is one whitespace-free argument, `key` or `key=value`, that
+//! the backend appends after the arguments it generates itself (console,
+//! virtio-mmio windows, `voidbox.*` settings for the guest-agent). Both
+//! backends run the same checks against their own generated arguments:
+//!
+//! - `voidbox.*` keys belong to the guest-agent and are refused, as are
+//!   `init`/`rdinit`, which would replace it as PID 1, and `--`, which ends
+//!   the kernel's arguments.
+//! - A key the backend already sets conflicts unless the value is the same
+//!   (the fragment is then dropped) or the key may repeat (`console`).
+//! - Two fragments may not set one key to different values.
+//!
+//! Quoted values are not supported.
+
+use std::collections::HashMap;
+
+use crate::{Error, Result};
+
+/// Key prefix reserved for the guest-agent's settings.
+const RESERVED_PREFIX: &str = "voidbox.";
+
+/// Keys that would stop the guest-agent from running as init.
+const RESERVED_KEYS: &[&str] = &["init", "rdinit"];
+
+/// Keys the kernel accepts more than once, each adding to the last.
+const REPEATABLE_KEYS: &[&str] = &["console"];
+
+/// Split `args` into fragments, so `"quiet transparent_hugepage=never"`
+/// gives two.
+pub fn split(args: &str) -> impl Iterator<Item = String> + '_ {
+    args.split_whitespace().map(str::to_string)
+}
+
+/// Check `extra` against itself and against `generated`, the arguments the
+/// backend builds on its own.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] naming the first fragment that is malformed,
+/// reserved, or conflicts.
+pub fn validate(generated: &[String], extra: &[String]) -> Result<()> {
+    let generated = keyed(generated);
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for arg in extra {
+        let (key, _) = split_arg(arg);
+        if arg.is_empty() || arg.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(Error::Config(format!(
+                "kernel cmdline fragment {arg:?} must be a single argument"
+            )));
+        }
+        if arg.contains('"') {
+            return Err(Error::Config(format!(
+                "kernel cmdline fragment {arg:?} is quoted; quoting is not supported"
+            )));
+        }
+        if arg == "--" {
+            return Err(Error::Config(
+                "kernel cmdline fragment \"--\" would pass the rest to init".into(),
+            ));
+        }
+        if key.starts_with(RESERVED_PREFIX) || RESERVED_KEYS.contains(&key) {
+            return Err(Error::Config(format!(
+                "kernel cmdline key {key:?} is reserved by void-box"
+            )));
+        }
+        if REPEATABLE_KEYS.contains(&key) {
+            continue;
+        }
+        if let Some(existing) = generated.get(key).filter(|existing| **existing != arg) {
+            return Err(Error::Config(format!(
+                "kernel cmdline fragment {arg:?} conflicts with {existing:?} set by the backend"
+            )));
+        }
+        if let Some(existing) = seen.insert(key, arg).filter(|existing| *existing != arg) {
+            return Err(Error::Config(format!(
+                "kernel cmdline fragments {existing:?} and {arg:?} conflict"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Append `extra` to `cmdline`, skipping fragments it already contains.
+/// Expects `extra` to have passed [`validate`].
+pub fn extend(cmdline: &mut Vec<String>, extra: &[String]) {
+    for arg in extra {
+        if REPEATABLE_KEYS.contains(&split_arg(arg).0) || !cmdline.contains(arg) {
+            cmdline.push(arg.clone());
+        }
+    }
+}
+
+fn split_arg(arg: &str) -> (&str, Option<&str>) {
+    match arg.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (arg, None),
+    }
+}
+
+/// The last argument for each key in `args`.
+fn keyed(args: &[String]) -> HashMap<&str, &str> {
+    args.iter()
+        .map(|arg| (split_arg(arg).0, arg.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_plain_fragments_are_accepted_and_deduplicated() {
+        let generated = args(&["console=ttyS0", "panic=1", "nokaslr"]);
+        let extra = args(&[
+            "transparent_hugepage=never",
+            "quiet",
+            "nokaslr",
+            "console=hvc0",
+        ]);
+        validate(&generated, &extra).unwrap();
+
+        let mut cmdline = generated.clone();
+        extend(&mut cmdline, &extra);
+        assert_eq!(
+            cmdline.join(" "),
+            "console=ttyS0 panic=1 nokaslr transparent_hugepage=never quiet console=hvc0"
+        );
+    }
+
+    #[test]
+    fn test_reserved_keys_are_refused() {
+        for arg in [
+            "voidbox.secret=00",
+            "voidbox.network=1",
+            "init=/bin/sh",
+            "--",
+        ] {
+            assert!(validate(&[], &args(&[arg])).is_err(), "{arg}");
+        }
+    }
+
+    #[test]
+    fn test_conflicts_are_refused() {
+        let generated = args(&["panic=1", "loglevel=0"]);
+        let err = validate(&generated, &args(&["panic=10"])).unwrap_err();
+        assert!(err.to_string().contains("\"panic=1\""), "{err}");
+        assert!(validate(&[], &args(&["mitigations=off", "mitigations=auto"])).is_err());
+        assert!(validate(&[], &args(&["a b"])).is_err());
+        assert!(validate(&[], &args(&["acpi=\"off\""])).is_err());
+    }
+
+    #[test]
+    fn test_split_breaks_on_whitespace() {
+        assert_eq!(
+            split(" quiet  transparent_hugepage=never ").collect::<Vec<_>>(),
+            args(&["quiet", "transparent_hugepage=never"])
+        );
+    }
+}
@@ -22,7 +22,7 @@
 
 use std::io::{self, Read, Write};
 use std::os::unix::io::RawFd;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
 
@@ -31,13 +31,35 @@ use tracing::{debug, info, warn};
 use void_box_protocol::SessionSecret;
 
 use crate::backend::multiplex::{FrameSender, MultiplexChannel, Terminator};
+use crate::backend::protocol_tap::ProtocolTap;
 use crate::guest::protocol::{
-    ExecOutputChunk, ExecRequest, ExecResponse, FileStatRequest, FileStatResponse, Message,
-    MessageType, MkdirPRequest, MkdirPResponse, PtyOpenRequest, ReadFileRequest, ReadFileResponse,
-    TelemetryBatch, TelemetrySubscribeRequest, WriteFileRequest, WriteFileResponse,
+    AttachExecRequest, ChmodRequest, ChmodResponse, CreateUserRequest, CreateUserResponse,
+    EnterReadOnlyResponse, ExecOutputChunk, ExecPolicy, ExecRequest, ExecResponse, ExecSignal,
+    ExecStdinChunk, ExecStdinClose, ExecStdinResponse, ExportWorkspaceRequest,
+    ExportWorkspaceResponse, FileStatRequest, FileStatResponse, FsDiffRequest, FsDiffResponse,
+    HashFilesRequest, HashFilesResponse, Message, MessageType, MkdirPRequest, MkdirPResponse,
+    PtyOpenRequest, ReadFileRequest, ReadFileResponse, ReadLinkRequest, ReadLinkResponse,
+    SetExecPolicyRequest, SetExecPolicyResponse, ShutdownAck, ShutdownRequest, SignalExecRequest,
+    SignalExecResponse, SymlinkRequest, SymlinkResponse, SyncClockRequest, SyncClockResponse,
+    SysInfoResponse, TelemetryBatch, TelemetryControlRequest, TelemetryControlResponse,
+    TelemetrySubscribeRequest, WriteFileChunkRequest, WriteFileChunkResponse,
+    WriteFileFinalizeRequest, WriteFileRequest, WriteFileResponse,
 };
 use crate::{Error, Result};
 
+/// Upper bound on a whole `ExportWorkspace` stream, from request to the
+/// terminal response.
+const EXPORT_WORKSPACE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Times in a row an exec or telemetry stream is resumed after its
+/// connection dropped before the drop is treated as fatal. Each resume
+/// reconnects within the usual connect deadline.
+const MAX_STREAM_RESUMES: u32 = 3;
+
+/// Pause before resubscribing to telemetry, multiplied by the number of
+/// resumes in a row.
+const TELEMETRY_RESUME_BACKOFF: Duration = Duration::from_millis(100);
+
 /// Initial per-attempt read timeout for the handshake Pong.
 ///
 /// The handshake runs exactly once per sandbox — on first RPC or when
@@ -92,6 +114,15 @@ fn connect_deadline() -> Duration {
     Duration::from_secs(secs)
 }
 
+/// What the connect/handshake loop fails with at its deadline.
+const HANDSHAKE_DEADLINE_ERROR: &str = "control_channel: deadline reached (connect or handshake)";
+
+/// Whether `err` is the connect/handshake loop giving up: the guest-agent
+/// never answered.
+pub(crate) fn is_handshake_deadline(err: &Error) -> bool {
+    matches!(err, Error::Guest(msg) if msg == HANDSHAKE_DEADLINE_ERROR)
+}
+
 /// Resolve the read timeout for an exec request.
 ///
 /// Service mode passes `Some(0)` to mean "wait forever" (no timeout). Any other
@@ -150,6 +181,14 @@ pub type GuestConnector = Arc<dyn Fn() -> Result<Box<dyn GuestStream>> + Send +
 /// [`PROTO_FLAG_SUPPORTS_MULTIPLEX`] during the handshake or channel
 /// establishment fails.
 ///
+/// Reconnecting re-authenticates with the session secret. Streams that
+/// outlive a dropped connection pick up on the new one: the telemetry
+/// subscription is renewed with the settings it was last given, and an
+/// exec started with [`ExecRequest::exec_id`] set is re-attached, so a
+/// long-running service keeps reporting to its caller. Other in-flight
+/// calls fail with the drop. [`close`](Self::close) stops all of this when
+/// the VM goes away.
+///
 /// PTY sessions open their own dedicated connection (one connection per
 /// interactive shell) but that connection's framing is identical: every
 /// message carries an in-payload request_id.
@@ -171,6 +210,29 @@ pub struct ControlChannel {
     boot_wait: Duration,
     /// Lazily-established multiplex channel. Re-established on death.
     channel: Arc<AsyncMutex<Option<MultiplexChannel>>>,
+    /// When the first multiplex channel finished its handshake.
+    first_connected: std::sync::OnceLock<Instant>,
+    /// Bytes carried by every multiplex channel this control channel has
+    /// opened.
+    traffic: Arc<TrafficCounters>,
+    /// Set by [`Self::close`]; no connection is made afterwards.
+    closed: Arc<AtomicBool>,
+    /// Telemetry settings changed since the subscription started, replayed
+    /// when it is renewed on a new connection.
+    telemetry_settings: Arc<StdMutex<Option<TelemetryControlRequest>>>,
+}
+
+/// Bytes a [`ControlChannel`] has exchanged with the guest-agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelTraffic {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct TrafficCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
 }
 
 impl ControlChannel {
@@ -200,6 +262,10 @@ impl ControlChannel {
             boot_wait_done: Arc::new(AtomicBool::new(false)),
             boot_wait,
             channel: Arc::new(AsyncMutex::new(None)),
+            first_connected: std::sync::OnceLock::new(),
+            traffic: Arc::default(),
+            closed: Arc::default(),
+            telemetry_settings: Arc::default(),
         }
     }
 
@@ -211,9 +277,22 @@ impl ControlChannel {
             boot_wait_done: Arc::new(AtomicBool::new(true)),
             boot_wait: Duration::ZERO,
             channel: Arc::new(AsyncMutex::new(None)),
+            first_connected: std::sync::OnceLock::new(),
+            traffic: Arc::default(),
+            closed: Arc::default(),
+            telemetry_settings: Arc::default(),
         }
     }
 
+    /// Records every frame on this channel's guest connections in `tap`,
+    /// when one is given.
+    pub fn with_protocol_tap(mut self, tap: Option<&ProtocolTap>) -> Self {
+        if let Some(tap) = tap {
+            self.connector = tap.wrap(self.connector);
+        }
+        self
+    }
+
     /// Sends a one-shot RPC through the multiplex channel and awaits a
     /// single response, bounded by `timeout`.
     ///
@@ -249,6 +328,9 @@ impl ControlChannel {
     ///
     /// [`PROTO_FLAG_SUPPORTS_MULTIPLEX`]: void_box_protocol::PROTO_FLAG_SUPPORTS_MULTIPLEX
     async fn get_or_establish_channel(&self) -> Result<MultiplexChannel> {
+        if self.is_closed() {
+            return Err(Error::Guest("control channel closed".into()));
+        }
         let mut guard = self.channel.lock().await;
 
         if let Some(channel) = guard.as_ref() {
@@ -263,6 +345,7 @@ impl ControlChannel {
         let session_secret = self.session_secret.clone();
         let boot_wait_done = Arc::clone(&self.boot_wait_done);
         let boot_wait = self.boot_wait;
+        let traffic = Arc::clone(&self.traffic);
 
         let channel = tokio::task::spawn_blocking(move || {
             establish_multiplex_channel(
@@ -271,16 +354,33 @@ impl ControlChannel {
                 &boot_wait_done,
                 boot_wait,
                 HANDSHAKE_READ_TIMEOUT,
+                &traffic,
                 "multiplex-establish",
             )
         })
         .await
         .map_err(|e| Error::Guest(format!("multiplex establish task panicked: {e}")))??;
 
+        self.first_connected.get_or_init(Instant::now);
         *guard = Some(channel.clone());
         Ok(channel)
     }
 
+    /// Bytes sent to and received from the guest-agent so far, across
+    /// reconnects. The connect handshake is not counted.
+    pub fn traffic(&self) -> ChannelTraffic {
+        ChannelTraffic {
+            bytes_sent: self.traffic.sent.load(Ordering::Relaxed),
+            bytes_received: self.traffic.received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// When the guest-agent first completed its handshake on this
+    /// channel, for boot timing. `None` until it has.
+    pub fn first_connected_at(&self) -> Option<Instant> {
+        self.first_connected.get().copied()
+    }
+
     /// Eagerly establishes the persistent multiplex channel.
     ///
     /// After `MicroVm::from_snapshot` the guest kernel is in HLT/NOHZ-idle
@@ -295,6 +395,34 @@ impl ControlChannel {
         let _ = self.get_or_establish_channel().await;
     }
 
+    /// Drops the current connection so the next call connects afresh.
+    ///
+    /// For when the guest-agent on the other end is known to be gone, as
+    /// across a guest reboot, before the reader thread has noticed.
+    pub async fn reset(&self) {
+        *self.channel.lock().await = None;
+    }
+
+    /// Stops the channel for good: later calls fail instead of connecting,
+    /// and streams that would resume after a dropped connection end.
+    ///
+    /// For when the VM is going away and a dropped connection is expected.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the guest-agent has completed the handshake.
+    ///
+    /// Unlike [`warm_handshake`](Self::warm_handshake), establishment
+    /// failures are returned to the caller.
+    pub async fn ensure_connected(&self) -> Result<()> {
+        self.get_or_establish_channel().await.map(|_| ())
+    }
+
     /// Sends an exec request and waits for the response.
     ///
     /// Routes through the persistent multiplex channel: allocates a fresh
@@ -312,8 +440,18 @@ impl ControlChannel {
             )
             .await?;
 
+        let mut resumes = 0;
         let drain = async {
-            while let Some(msg) = rx.recv().await {
+            loop {
+                let Some(msg) = rx.recv().await else {
+                    match self.reattach_exec(request, &mut resumes).await {
+                        Some(resumed) => {
+                            rx = resumed;
+                            continue;
+                        }
+                        None => break,
+                    }
+                };
                 match msg.msg_type {
                     MessageType::ExecOutputChunk => continue,
                     MessageType::ExecResponse => {
@@ -360,8 +498,18 @@ impl ControlChannel {
             )
             .await?;
 
+        let mut resumes = 0;
         let drain = async {
-            while let Some(msg) = rx.recv().await {
+            loop {
+                let Some(msg) = rx.recv().await else {
+                    match self.reattach_exec(request, &mut resumes).await {
+                        Some(resumed) => {
+                            rx = resumed;
+                            continue;
+                        }
+                        None => break,
+                    }
+                };
                 match msg.msg_type {
                     MessageType::ExecOutputChunk => {
                         match serde_json::from_slice::<ExecOutputChunk>(&msg.payload) {
@@ -411,8 +559,18 @@ impl ControlChannel {
             )
             .await?;
 
+        let mut resumes = 0;
         let drain = async {
-            while let Some(msg) = rx.recv().await {
+            loop {
+                let Some(msg) = rx.recv().await else {
+                    match self.reattach_exec(request, &mut resumes).await {
+                        Some(resumed) => {
+                            rx = resumed;
+                            continue;
+                        }
+                        None => break,
+                    }
+                };
                 match msg.msg_type {
                     MessageType::ExecOutputChunk => {
                         match serde_json::from_slice::<ExecOutputChunk>(&msg.payload) {
@@ -447,6 +605,53 @@ impl ControlChannel {
         apply_exec_timeout(timeout, drain).await
     }
 
+    /// Continues the stream of `request` after it ended without an
+    /// `ExecResponse`, which happens when its connection drops.
+    ///
+    /// A command started with an exec id is still running in the guest:
+    /// reconnects and sends `AttachExec`, so the rest of its output and its
+    /// response arrive on the returned stream. `None` when the command has
+    /// no id, the channel is closed, or `resumes` has reached
+    /// [`MAX_STREAM_RESUMES`].
+    async fn reattach_exec(
+        &self,
+        request: &ExecRequest,
+        resumes: &mut u32,
+    ) -> Option<tokio::sync::mpsc::Receiver<Message>> {
+        let exec_id = request.exec_id.as_deref()?;
+        if self.is_closed() || *resumes == MAX_STREAM_RESUMES {
+            return None;
+        }
+        *resumes += 1;
+        info!(
+            "control_channel: exec {} lost its connection, re-attaching (attempt {})",
+            exec_id, resumes
+        );
+        let attached = async {
+            let body = serde_json::to_vec(&AttachExecRequest {
+                exec_id: exec_id.to_string(),
+            })?;
+            self.get_or_establish_channel()
+                .await?
+                .call_stream(
+                    MessageType::AttachExec,
+                    body,
+                    Terminator::OnMessageType(MessageType::ExecResponse),
+                )
+                .await
+        };
+        match attached.await {
+            Ok(rx) => Some(rx),
+            Err(e) => {
+                warn!(
+                    "control_channel: re-attaching to exec {} failed: {}",
+                    exec_id, e
+                );
+                None
+            }
+        }
+    }
+
     /// Writes a file to the guest filesystem using the native WriteFile protocol.
     pub async fn send_write_file(&self, path: &str, content: &[u8]) -> Result<WriteFileResponse> {
         let body = serde_json::to_vec(&WriteFileRequest {
@@ -466,6 +671,180 @@ impl ControlChannel {
         Ok(serde_json::from_slice(&msg.payload)?)
     }
 
+    /// Writes one chunk of a chunked file transfer at `offset`.
+    pub async fn send_write_file_chunk(
+        &self,
+        path: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<WriteFileChunkResponse> {
+        let body = serde_json::to_vec(&WriteFileChunkRequest {
+            path: path.to_string(),
+            offset,
+            data: data.to_vec(),
+            create_parents: true,
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::WriteFileChunk,
+                body,
+                Duration::from_secs(30),
+                "WriteFileChunk",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::WriteFileChunkResponse, "WriteFileChunk")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Commits a chunked file transfer once every chunk has been written.
+    pub async fn send_write_file_finalize(
+        &self,
+        path: &str,
+        total_size: u64,
+    ) -> Result<WriteFileResponse> {
+        let body = serde_json::to_vec(&WriteFileFinalizeRequest {
+            path: path.to_string(),
+            total_size,
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::WriteFileFinalize,
+                body,
+                Duration::from_secs(30),
+                "WriteFileFinalize",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::WriteFileResponse, "WriteFileFinalize")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Signals the process group of a running exec started with
+    /// [`ExecRequest::exec_id`] set.
+    pub async fn send_signal_exec(
+        &self,
+        exec_id: &str,
+        signal: ExecSignal,
+    ) -> Result<SignalExecResponse> {
+        let body = serde_json::to_vec(&SignalExecRequest {
+            exec_id: exec_id.to_string(),
+            signal,
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::SignalExec,
+                body,
+                Duration::from_secs(10),
+                "SignalExec",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::SignalExecResponse, "SignalExec")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Writes `data` (at most [`EXEC_STDIN_CHUNK_SIZE`](void_box_protocol::EXEC_STDIN_CHUNK_SIZE)
+    /// bytes) to the stdin of a running exec started with
+    /// [`ExecRequest::stdin_stream`] set. Answered once the data is in the
+    /// pipe.
+    pub async fn send_exec_stdin(&self, exec_id: &str, data: &[u8]) -> Result<ExecStdinResponse> {
+        let body = serde_json::to_vec(&ExecStdinChunk {
+            exec_id: exec_id.to_string(),
+            data: data.to_vec(),
+        })?;
+        // The command may take its time reading.
+        let msg = self
+            .multiplex_call(
+                MessageType::ExecStdinChunk,
+                body,
+                Duration::from_secs(300),
+                "ExecStdinChunk",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::ExecStdinResponse, "ExecStdinChunk")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Closes the stdin of a running exec started with
+    /// [`ExecRequest::stdin_stream`] set.
+    pub async fn send_exec_stdin_close(&self, exec_id: &str) -> Result<ExecStdinResponse> {
+        let body = serde_json::to_vec(&ExecStdinClose {
+            exec_id: exec_id.to_string(),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::ExecStdinClose,
+                body,
+                Duration::from_secs(90),
+                "ExecStdinClose",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::ExecStdinResponse, "ExecStdinClose")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Replaces the guest-agent's exec policy.
+    pub async fn send_set_exec_policy(&self, policy: &ExecPolicy) -> Result<SetExecPolicyResponse> {
+        let body = serde_json::to_vec(&SetExecPolicyRequest {
+            policy: policy.clone(),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::SetExecPolicy,
+                body,
+                Duration::from_secs(10),
+                "SetExecPolicy",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::SetExecPolicyResponse, "SetExecPolicy")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Creates a guest user, or returns the existing one.
+    pub async fn send_create_user(&self, name: &str) -> Result<CreateUserResponse> {
+        let body = serde_json::to_vec(&CreateUserRequest {
+            name: name.to_string(),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::CreateUser,
+                body,
+                Duration::from_secs(30),
+                "CreateUser",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::CreateUserResponse, "CreateUser")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Sets the guest's wall clock, returning the clock it replaced.
+    pub async fn send_sync_clock(&self, request: &SyncClockRequest) -> Result<SyncClockResponse> {
+        let body = serde_json::to_vec(request)?;
+        let msg = self
+            .multiplex_call(
+                MessageType::SyncClock,
+                body,
+                Duration::from_secs(10),
+                "SyncClock",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::SyncClockResponse, "SyncClock")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Makes the guest read-only: file-write RPCs are refused from now on
+    /// and `/workspace` is remounted read-only.
+    pub async fn send_enter_read_only(&self) -> Result<EnterReadOnlyResponse> {
+        let msg = self
+            .multiplex_call(
+                MessageType::EnterReadOnly,
+                Vec::new(),
+                Duration::from_secs(10),
+                "EnterReadOnly",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::EnterReadOnlyResponse, "EnterReadOnly")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
     /// Creates directories in the guest filesystem (mkdir -p).
     pub async fn send_mkdir_p(&self, path: &str) -> Result<MkdirPResponse> {
         let body = serde_json::to_vec(&MkdirPRequest {
@@ -495,6 +874,154 @@ impl ControlChannel {
         Ok(serde_json::from_slice(&msg.payload)?)
     }
 
+    /// Hashes a batch of guest files.
+    ///
+    /// The guest reads every regular file in full, so this allows as long
+    /// as an FsDiff.
+    pub async fn send_hash_files(&self, paths: &[String]) -> Result<HashFilesResponse> {
+        let body = serde_json::to_vec(&HashFilesRequest {
+            paths: paths.to_vec(),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::HashFiles,
+                body,
+                Duration::from_secs(120),
+                "HashFiles",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::HashFilesResponse, "HashFiles")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Reads the guest's kernel, load, memory and mount state.
+    pub async fn send_sysinfo(&self) -> Result<SysInfoResponse> {
+        let msg = self
+            .multiplex_call(
+                MessageType::SysInfo,
+                Vec::new(),
+                Duration::from_secs(10),
+                "SysInfo",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::SysInfoResponse, "SysInfo")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Creates a symbolic link in the guest filesystem.
+    pub async fn send_symlink(&self, request: &SymlinkRequest) -> Result<SymlinkResponse> {
+        let body = serde_json::to_vec(request)?;
+        let msg = self
+            .multiplex_call(
+                MessageType::Symlink,
+                body,
+                Duration::from_secs(10),
+                "Symlink",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::SymlinkResponse, "Symlink")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Changes the mode and/or owner of a path in the guest filesystem.
+    pub async fn send_chmod(&self, request: &ChmodRequest) -> Result<ChmodResponse> {
+        let body = serde_json::to_vec(request)?;
+        let msg = self
+            .multiplex_call(MessageType::Chmod, body, Duration::from_secs(10), "Chmod")
+            .await?;
+        ensure_response_type(&msg, MessageType::ChmodResponse, "Chmod")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Reads the target of a symbolic link in the guest filesystem.
+    pub async fn send_read_link(&self, path: &str) -> Result<ReadLinkResponse> {
+        let body = serde_json::to_vec(&ReadLinkRequest {
+            path: path.to_string(),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::ReadLink,
+                body,
+                Duration::from_secs(10),
+                "ReadLink",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::ReadLinkResponse, "ReadLink")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Asks the guest which files changed under `root` (whole rootfs if `None`).
+    ///
+    /// The guest walks and hashes the changed files before answering, so
+    /// this allows far longer than the metadata RPCs.
+    pub async fn send_fs_diff(&self, root: Option<&str>) -> Result<FsDiffResponse> {
+        let body = serde_json::to_vec(&FsDiffRequest {
+            root: root.map(String::from),
+        })?;
+        let msg = self
+            .multiplex_call(
+                MessageType::FsDiff,
+                body,
+                Duration::from_secs(120),
+                "FsDiff",
+            )
+            .await?;
+        ensure_response_type(&msg, MessageType::FsDiffResponse, "FsDiff")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
+    }
+
+    /// Exports `/workspace` (optionally narrowed by a glob) as a tar archive.
+    ///
+    /// The guest streams the archive as raw `ExportWorkspaceChunk` frames and
+    /// finishes with an `ExportWorkspaceResponse`; the chunks are concatenated
+    /// in arrival order.
+    pub async fn send_export_workspace(
+        &self,
+        path_filter: Option<&str>,
+    ) -> Result<(Vec<u8>, ExportWorkspaceResponse)> {
+        let body = serde_json::to_vec(&ExportWorkspaceRequest {
+            path_filter: path_filter.map(String::from),
+        })?;
+        let channel = self.get_or_establish_channel().await?;
+        let mut rx = channel
+            .call_stream(
+                MessageType::ExportWorkspace,
+                body,
+                Terminator::OnMessageType(MessageType::ExportWorkspaceResponse),
+            )
+            .await?;
+
+        let drain = async {
+            let mut archive = Vec::new();
+            while let Some(msg) = rx.recv().await {
+                match msg.msg_type {
+                    MessageType::ExportWorkspaceChunk => archive.extend_from_slice(&msg.payload),
+                    MessageType::ExportWorkspaceResponse => {
+                        let response: ExportWorkspaceResponse =
+                            serde_json::from_slice(&msg.payload)?;
+                        return Ok((archive, response));
+                    }
+                    other => {
+                        return Err(Error::Guest(format!(
+                            "Unexpected response type: {:?}",
+                            other
+                        )));
+                    }
+                }
+            }
+            Err(Error::Guest(
+                "export stream closed without ExportWorkspaceResponse".into(),
+            ))
+        };
+
+        match tokio::time::timeout(EXPORT_WORKSPACE_TIMEOUT, drain).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Guest(format!(
+                "ExportWorkspace timed out after {EXPORT_WORKSPACE_TIMEOUT:?}"
+            ))),
+        }
+    }
+
     /// Reads a file from the guest filesystem.
     pub async fn send_read_file(&self, path: &str) -> Result<ReadFileResponse> {
         let body = serde_json::to_vec(&ReadFileRequest {
@@ -515,9 +1042,11 @@ impl ControlChannel {
     /// Opens a persistent telemetry subscription through the multiplex channel.
     ///
     /// Allocates a request_id for the subscription, sends
-    /// `SubscribeTelemetry`, and runs a background task that forwards
-    /// every [`TelemetryBatch`] frame to `on_batch` until the channel
-    /// dies or the subscription is cancelled by a channel-lifetime end.
+    /// `SubscribeTelemetry`, and forwards every [`TelemetryBatch`] frame to
+    /// `on_batch`. When the connection drops, the subscription is renewed on
+    /// a new one and the settings last sent with
+    /// [`send_telemetry_control`](Self::send_telemetry_control) are applied
+    /// again. Returns once the channel is closed or cannot be re-established.
     pub async fn subscribe_telemetry<F>(
         &self,
         opts: &TelemetrySubscribeRequest,
@@ -528,33 +1057,158 @@ impl ControlChannel {
     {
         let body = serde_json::to_vec(opts).unwrap_or_default();
         let interval_ms = opts.interval_ms;
-        let channel = self.get_or_establish_channel().await?;
-        let mut rx = channel
+        // A new subscription starts from the guest's defaults.
+        *self
+            .telemetry_settings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        let mut rx = self.open_telemetry_stream(&body).await?;
+
+        info!("Telemetry subscription active (interval={}ms)", interval_ms);
+
+        let mut resumes = 0;
+        loop {
+            while let Some(msg) = rx.recv().await {
+                if msg.msg_type != MessageType::TelemetryData {
+                    warn!(
+                        "Unexpected message type in telemetry stream: {:?}",
+                        msg.msg_type
+                    );
+                    continue;
+                }
+                resumes = 0;
+                match serde_json::from_slice::<TelemetryBatch>(&msg.payload) {
+                    Ok(batch) => on_batch(batch),
+                    Err(e) => warn!("Failed to parse TelemetryBatch: {}", e),
+                }
+            }
+            if self.is_closed() || resumes == MAX_STREAM_RESUMES {
+                break;
+            }
+            resumes += 1;
+            info!(
+                "Telemetry connection dropped, resubscribing (attempt {})",
+                resumes
+            );
+            tokio::time::sleep(TELEMETRY_RESUME_BACKOFF * resumes).await;
+            rx = match self.resume_telemetry(&body).await {
+                Ok(rx) => rx,
+                Err(e) => {
+                    if !self.is_closed() {
+                        warn!("Telemetry subscription could not be resumed: {}", e);
+                    }
+                    break;
+                }
+            };
+        }
+
+        info!("Telemetry subscription ended");
+        Ok(())
+    }
+
+    async fn open_telemetry_stream(
+        &self,
+        body: &[u8],
+    ) -> Result<tokio::sync::mpsc::Receiver<Message>> {
+        self.get_or_establish_channel()
+            .await?
             .call_stream(
                 MessageType::SubscribeTelemetry,
-                body,
+                body.to_vec(),
                 Terminator::ChannelLifetime,
             )
-            .await?;
-
-        info!("Telemetry subscription active (interval={}ms)", interval_ms);
+            .await
+    }
 
-        while let Some(msg) = rx.recv().await {
-            if msg.msg_type != MessageType::TelemetryData {
+    /// Subscribes again on a new connection and replays the settings the
+    /// dropped subscription had been given.
+    async fn resume_telemetry(&self, body: &[u8]) -> Result<tokio::sync::mpsc::Receiver<Message>> {
+        let rx = self.open_telemetry_stream(body).await?;
+        let settings = self
+            .telemetry_settings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(settings) = settings {
+            let response = self.send_telemetry_control(&settings).await?;
+            if !response.success {
                 warn!(
-                    "Unexpected message type in telemetry stream: {:?}",
-                    msg.msg_type
+                    "Telemetry settings not restored after resubscribing: {}",
+                    response.error.unwrap_or_default()
                 );
-                continue;
-            }
-            match serde_json::from_slice::<TelemetryBatch>(&msg.payload) {
-                Ok(batch) => on_batch(batch),
-                Err(e) => warn!("Failed to parse TelemetryBatch: {}", e),
             }
         }
+        Ok(rx)
+    }
 
-        info!("Telemetry subscription ended");
-        Ok(())
+    /// Changes the interval, process filter or paused state of the running
+    /// telemetry subscription.
+    pub async fn send_telemetry_control(
+        &self,
+        request: &TelemetryControlRequest,
+    ) -> Result<TelemetryControlResponse> {
+        let body = serde_json::to_vec(request)?;
+        let msg = self
+            .multiplex_call(
+                MessageType::TelemetryControl,
+                body,
+                Duration::from_secs(10),
+                "TelemetryControl",
+            )
+            .await?;
+        ensure_response_type(
+            &msg,
+            MessageType::TelemetryControlResponse,
+            "TelemetryControl",
+        )?;
+        let response: TelemetryControlResponse = serde_json::from_slice(&msg.payload)?;
+        if response.success {
+            let mut settings = self
+                .telemetry_settings
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let settings = settings.get_or_insert_with(Default::default);
+            settings.interval_ms = request.interval_ms.or(settings.interval_ms);
+            settings.paused = request.paused.or(settings.paused);
+            settings.process_filter = request
+                .process_filter
+                .clone()
+                .or(settings.process_filter.take());
+            settings.top_processes = request.top_processes.or(settings.top_processes);
+        }
+        Ok(response)
+    }
+
+    /// Asks the guest to shut down cleanly and waits up to `timeout` for its
+    /// [`ShutdownAck`].
+    ///
+    /// Guest processes get `grace` between SIGTERM and SIGKILL; the guest
+    /// then syncs filesystems, acks, and powers off.
+    pub async fn send_shutdown(&self, grace: Duration, timeout: Duration) -> Result<ShutdownAck> {
+        self.shutdown_call(grace, false, timeout).await
+    }
+
+    /// Like [`send_shutdown`](Self::send_shutdown), but the guest restarts
+    /// its kernel instead of powering off.
+    pub async fn send_reboot(&self, grace: Duration, timeout: Duration) -> Result<ShutdownAck> {
+        self.shutdown_call(grace, true, timeout).await
+    }
+
+    async fn shutdown_call(
+        &self,
+        grace: Duration,
+        reboot: bool,
+        timeout: Duration,
+    ) -> Result<ShutdownAck> {
+        let body = serde_json::to_vec(&ShutdownRequest {
+            grace_ms: Some(grace.as_millis() as u64),
+            reboot,
+        })?;
+        let msg = self
+            .multiplex_call(MessageType::Shutdown, body, timeout, "Shutdown")
+            .await?;
+        ensure_response_type(&msg, MessageType::ShutdownAck, "Shutdown")?;
+        Ok(serde_json::from_slice(&msg.payload)?)
     }
 
     /// Waits for the guest to signal snapshot readiness.
@@ -575,6 +1229,28 @@ impl ControlChannel {
         Ok(())
     }
 
+    /// Round-trips a liveness probe through the multiplex channel.
+    ///
+    /// The guest-agent answers `SnapshotReady` with a bare echo and no side
+    /// effects, so it doubles as a heartbeat without a protocol change.
+    /// Unlike the other RPCs, `timeout` also bounds (re)establishing the
+    /// channel: a hung agent must fail the probe, not stall it.
+    pub async fn heartbeat(&self, timeout: Duration) -> Result<()> {
+        let probe = async {
+            let msg = self
+                .multiplex_call(MessageType::SnapshotReady, Vec::new(), timeout, "heartbeat")
+                .await?;
+            ensure_response_type(&msg, MessageType::SnapshotReady, "heartbeat")
+        };
+        tokio::time::timeout(timeout, probe)
+            .await
+            .unwrap_or_else(|_| {
+                Err(Error::Guest(format!(
+                    "multiplex heartbeat timed out after {timeout:?}"
+                )))
+            })
+    }
+
     /// Opens a PTY session on the guest, returning a [`super::pty_session::PtySession`] that owns the connection.
     pub async fn open_pty(
         &self,
@@ -642,9 +1318,7 @@ pub(crate) fn connect_with_handshake_sync(
                 "control_channel[{context}]: deadline reached after {} connect/handshake attempts",
                 attempt
             );
-            return Err(Error::Guest(
-                "control_channel: deadline reached (connect or handshake)".into(),
-            ));
+            return Err(Error::Guest(HANDSHAKE_DEADLINE_ERROR.into()));
         }
 
         attempt += 1;
@@ -754,6 +1428,7 @@ pub(crate) fn establish_multiplex_channel(
     boot_wait_done: &AtomicBool,
     boot_wait: Duration,
     handshake_timeout: Duration,
+    traffic: &Arc<TrafficCounters>,
     context: &str,
 ) -> Result<MultiplexChannel> {
     let stream = connect_with_handshake_sync(
@@ -764,7 +1439,7 @@ pub(crate) fn establish_multiplex_channel(
         handshake_timeout,
         context,
     )?;
-    upgrade_stream_to_multiplex(stream, context)
+    upgrade_stream_to_multiplex(stream, traffic, context)
 }
 
 /// Upgrades an already-handshaken [`GuestStream`] into a [`MultiplexChannel`].
@@ -773,6 +1448,7 @@ pub(crate) fn establish_multiplex_channel(
 /// writer each own a distinct fd backed by the same kernel socket.
 fn upgrade_stream_to_multiplex(
     writer_stream: Box<dyn GuestStream>,
+    traffic: &Arc<TrafficCounters>,
     context: &str,
 ) -> Result<MultiplexChannel> {
     let reader_stream = writer_stream.try_clone_box().map_err(|e| {
@@ -789,9 +1465,11 @@ fn upgrade_stream_to_multiplex(
 
     let reader: Box<dyn Read + Send> = Box::new(GuestStreamReader {
         inner: reader_stream,
+        traffic: Arc::clone(traffic),
     });
     let sender: Arc<dyn FrameSender> = Arc::new(StreamFrameSender {
         stream: StdMutex::new(writer_stream),
+        traffic: Arc::clone(traffic),
     });
 
     Ok(MultiplexChannel::new(reader, sender))
@@ -801,11 +1479,14 @@ fn upgrade_stream_to_multiplex(
 /// multiplex reader thread.
 struct GuestStreamReader {
     inner: Box<dyn GuestStream>,
+    traffic: Arc<TrafficCounters>,
 }
 
 impl Read for GuestStreamReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        let n = self.inner.read(buf)?;
+        self.traffic.received.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
     }
 }
 
@@ -818,6 +1499,7 @@ impl Read for GuestStreamReader {
 /// typically < 64 KiB, so contention is minimal.
 struct StreamFrameSender {
     stream: StdMutex<Box<dyn GuestStream>>,
+    traffic: Arc<TrafficCounters>,
 }
 
 impl FrameSender for StreamFrameSender {
@@ -829,6 +1511,9 @@ impl FrameSender for StreamFrameSender {
         guard
             .write_all(frame)
             .map_err(|e| Error::Guest(format!("frame send failed: {e}")))?;
+        self.traffic
+            .sent
+            .fetch_add(frame.len() as u64, Ordering::Relaxed);
         Ok(())
     }
 }
@@ -869,6 +1554,221 @@ fn ensure_response_type(msg: &Message, expected: MessageType, context: &'static
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::VecDeque;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    struct TestStream(UnixStream);
+
+    impl Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for TestStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl GuestStream for TestStream {
+        fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            self.0.set_read_timeout(timeout)
+        }
+
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+
+        fn try_clone_box(&self) -> io::Result<Box<dyn GuestStream>> {
+            Ok(Box::new(TestStream(self.0.try_clone()?)))
+        }
+    }
+
+    type GuestScript = Box<dyn FnOnce(&mut UnixStream) + Send>;
+
+    /// A connector whose n-th connection is served, after the handshake, by
+    /// the n-th script on its own thread. Returning from a script drops the
+    /// connection.
+    fn scripted_guest(scripts: Vec<GuestScript>) -> GuestConnector {
+        let scripts = StdMutex::new(VecDeque::from(scripts));
+        Arc::new(move || {
+            let script = scripts
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| Error::Guest("no more guest connections".into()))?;
+            let (host, mut guest) = UnixStream::pair()?;
+            std::thread::spawn(move || {
+                let ping = Message::read_from_sync(&mut guest).unwrap();
+                assert_eq!(ping.msg_type, MessageType::Ping);
+                let pong = Message {
+                    msg_type: MessageType::Pong,
+                    payload: void_box_protocol::build_pong_payload(
+                        void_box_protocol::PROTO_FLAG_SUPPORTS_MULTIPLEX,
+                    ),
+                };
+                guest.write_all(&pong.serialize()).unwrap();
+                script(&mut guest);
+            });
+            Ok(Box::new(TestStream(host)) as Box<dyn GuestStream>)
+        })
+    }
+
+    fn read_request(guest: &mut UnixStream, expected: MessageType) -> (u32, Vec<u8>) {
+        let msg = Message::read_from_sync(guest).unwrap();
+        assert_eq!(msg.msg_type, expected);
+        let request_id = u32::from_le_bytes(msg.payload[..4].try_into().unwrap());
+        (request_id, msg.payload[4..].to_vec())
+    }
+
+    fn reply<T: serde::Serialize>(
+        guest: &mut UnixStream,
+        msg_type: MessageType,
+        request_id: u32,
+        body: &T,
+    ) {
+        let mut payload = request_id.to_le_bytes().to_vec();
+        payload.extend(serde_json::to_vec(body).unwrap());
+        guest
+            .write_all(&Message { msg_type, payload }.serialize())
+            .unwrap();
+    }
+
+    fn chunk(data: &str, seq: u64) -> ExecOutputChunk {
+        ExecOutputChunk {
+            stream: "stdout".into(),
+            data: data.as_bytes().to_vec(),
+            seq,
+        }
+    }
+
+    fn batch(seq: u64) -> TelemetryBatch {
+        TelemetryBatch {
+            seq,
+            timestamp_ms: 0,
+            system: None,
+            processes: Vec::new(),
+            trace_context: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn exec_with_an_id_is_reattached_after_a_dropped_connection() {
+        let connector = scripted_guest(vec![
+            Box::new(|guest| {
+                let (id, _) = read_request(guest, MessageType::ExecRequest);
+                reply(guest, MessageType::ExecOutputChunk, id, &chunk("one\n", 0));
+            }),
+            Box::new(|guest| {
+                let (id, body) = read_request(guest, MessageType::AttachExec);
+                let attach: AttachExecRequest = serde_json::from_slice(&body).unwrap();
+                assert_eq!(attach.exec_id, "server");
+                reply(guest, MessageType::ExecOutputChunk, id, &chunk("two\n", 1));
+                let response = ExecResponse {
+                    stdout: b"one\ntwo\n".to_vec(),
+                    ..Default::default()
+                };
+                reply(guest, MessageType::ExecResponse, id, &response);
+            }),
+        ]);
+        let channel = ControlChannel::new(connector, SessionSecret::new([7; 32]));
+        let mut request =
+            crate::guest::protocol::build_exec_request("server", &[], &[], &[], None, None, None);
+        request.exec_id = Some("server".into());
+
+        let chunks = Arc::new(StdMutex::new(Vec::new()));
+        let seen = Arc::clone(&chunks);
+        let response = channel
+            .send_exec_request_streaming(&request, move |chunk| {
+                seen.lock().unwrap().push(chunk.data);
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response.exit_code, 0);
+        assert_eq!(response.stdout, b"one\ntwo\n");
+        assert_eq!(
+            *chunks.lock().unwrap(),
+            [b"one\n".to_vec(), b"two\n".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_without_an_id_fails_when_its_connection_drops() {
+        let connector = scripted_guest(vec![Box::new(|guest| {
+            read_request(guest, MessageType::ExecRequest);
+        })]);
+        let channel = ControlChannel::new(connector, SessionSecret::new([7; 32]));
+        let request =
+            crate::guest::protocol::build_exec_request("true", &[], &[], &[], None, None, None);
+
+        let err = channel.send_exec_request(&request).await.unwrap_err();
+        assert!(err.to_string().contains("without ExecResponse"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn telemetry_is_resubscribed_with_its_settings_after_a_dropped_connection() {
+        let connector = scripted_guest(vec![
+            Box::new(|guest| {
+                let (subscription, _) = read_request(guest, MessageType::SubscribeTelemetry);
+                reply(guest, MessageType::TelemetryData, subscription, &batch(1));
+                let (id, _) = read_request(guest, MessageType::TelemetryControl);
+                let response = TelemetryControlResponse {
+                    success: true,
+                    interval_ms: 500,
+                    paused: false,
+                    error: None,
+                };
+                reply(guest, MessageType::TelemetryControlResponse, id, &response);
+            }),
+            Box::new(|guest| {
+                let (subscription, _) = read_request(guest, MessageType::SubscribeTelemetry);
+                let (id, body) = read_request(guest, MessageType::TelemetryControl);
+                let replayed: TelemetryControlRequest = serde_json::from_slice(&body).unwrap();
+                assert_eq!(replayed.interval_ms, Some(500));
+                let response = TelemetryControlResponse {
+                    success: true,
+                    interval_ms: 500,
+                    paused: false,
+                    error: None,
+                };
+                reply(guest, MessageType::TelemetryControlResponse, id, &response);
+                reply(guest, MessageType::TelemetryData, subscription, &batch(2));
+                // Stay connected until the host goes away.
+                let _ = Message::read_from_sync(guest);
+            }),
+        ]);
+        let channel = Arc::new(ControlChannel::new(connector, SessionSecret::new([7; 32])));
+
+        let (batch_tx, mut batches) = tokio::sync::mpsc::unbounded_channel();
+        let subscriber = Arc::clone(&channel);
+        let subscription = tokio::spawn(async move {
+            subscriber
+                .subscribe_telemetry(&TelemetrySubscribeRequest::default(), move |batch| {
+                    let _ = batch_tx.send(batch.seq);
+                })
+                .await
+        });
+
+        assert_eq!(batches.recv().await, Some(1));
+        channel
+            .send_telemetry_control(&TelemetryControlRequest {
+                interval_ms: Some(500),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(batches.recv().await, Some(2));
+
+        channel.close();
+        subscription.abort();
+    }
 
     /// Env mutation is process-global; this is the only test touching the
     /// variable, and it restores the unset state before returning.
@@ -13,16 +13,22 @@ use tracing::{debug, info, warn};
 use void_box_protocol::SessionSecret;
 
 use crate::backend::control_channel::{ControlChannel, GuestStream, GUEST_AGENT_PORT};
-use crate::backend::{BackendConfig, GuestConsoleSink, VmmBackend};
+use crate::backend::{
+    Backend, BackendConfig, BootProfile, ConnectionObserver, ConsoleInput, ConsoleObserver,
+    GuestConsoleSink, ProtocolTap, ResourcePolicy, VmmBackend,
+};
 use crate::devices::virtio_vsock::VsockStream;
 use crate::guest::protocol::{
-    build_exec_request, ExecOutputChunk, ExecResponse, PtyOpenRequest, TelemetrySubscribeRequest,
+    build_exec_request, ExecOutputChunk, ExecResponse, PtyOpenRequest, ShutdownAck,
+    TelemetrySubscribeRequest,
 };
+use crate::observe::boot::BootTimeline;
 use crate::observe::telemetry::{TelemetryAggregator, TelemetryBuffer};
 use crate::observe::tracer::SpanContext;
 use crate::observe::Observer;
 use crate::vmm::arch::VirtioSlot;
 use crate::vmm::config::{SecurityConfig, VoidBoxConfig, VsockBackendType};
+use crate::vmm::host_stats::VmmStatsProbe;
 use crate::vmm::MicroVm;
 use crate::{Error, ExecOutput, Result};
 
@@ -55,6 +61,10 @@ pub struct KvmBackend {
     cid: u32,
     /// Active span context for TRACEPARENT propagation.
     span_context: Option<SpanContext>,
+    /// Callback for outbound guest TCP connections, handed to SLIRP at boot.
+    connection_observer: Option<ConnectionObserver>,
+    /// Callback tapping guest serial output alongside the host sink.
+    console_observer: Option<ConsoleObserver>,
     /// Background task draining guest serial output to the configured host sink.
     guest_console_task: Option<JoinHandle<()>>,
     /// VM memory in megabytes (cached from `BackendConfig` for snapshot).
@@ -63,6 +73,14 @@ pub struct KvmBackend {
     vcpus: usize,
     /// Whether networking is enabled (cached from `BackendConfig` for snapshot).
     network: bool,
+    /// Run the control channel over the userspace virtio-vsock device even
+    /// for cold boots; set when the host has no `/dev/vhost-vsock`.
+    userspace_vsock: bool,
+    /// Run the control channel over a virtio-console port instead of vsock.
+    serial_control: bool,
+    /// Frame recorder for the control channel (cached from `BackendConfig`
+    /// for snapshot restores).
+    protocol_tap: Option<ProtocolTap>,
 }
 
 impl Default for KvmBackend {
@@ -79,12 +97,35 @@ impl KvmBackend {
             control_channel: None,
             cid: 0,
             span_context: None,
+            connection_observer: None,
+            console_observer: None,
             guest_console_task: None,
             memory_mb: 0,
             vcpus: 0,
             network: false,
+            userspace_vsock: false,
+            serial_control: false,
+            protocol_tap: None,
         }
     }
+
+    /// Use the userspace virtio-vsock device, reached through a host Unix
+    /// socket, instead of the kernel's vhost-vsock. Slower per RPC, but
+    /// needs no host module; [`Backend::detect`](super::Backend::detect)
+    /// picks it when `/dev/vhost-vsock` is missing.
+    pub fn with_userspace_vsock(mut self) -> Self {
+        self.userspace_vsock = true;
+        self
+    }
+
+    /// Carry the control channel over a virtio-console port, the transport
+    /// hypervisors without vsock use (RFC-0004), instead of vsock. PTY
+    /// sessions still use vsock. Cold boots only: the port is not
+    /// snapshotted.
+    pub fn with_serial_control(mut self) -> Self {
+        self.serial_control = true;
+        self
+    }
 }
 
 fn open_guest_console_writer(sink: &GuestConsoleSink) -> Box<dyn Write + Send> {
@@ -125,6 +166,7 @@ fn open_guest_console_file(path: &Path) -> io::Result<std::fs::File> {
 fn spawn_guest_console_task(
     mut serial_output: mpsc::Receiver<u8>,
     sink: GuestConsoleSink,
+    observer: Option<ConsoleObserver>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         let mut writer = open_guest_console_writer(&sink);
@@ -139,6 +181,9 @@ fn spawn_guest_console_task(
                 }
             }
 
+            if let Some(observer) = &observer {
+                observer.notify(&buffer);
+            }
             if let Err(err) = writer.write_all(&buffer) {
                 warn!("KvmBackend: failed writing guest console output: {}", err);
                 break;
@@ -149,14 +194,63 @@ fn spawn_guest_console_task(
     })
 }
 
+/// Record `host.*` metrics for the VM behind `probe` every `interval`
+/// until it stops.
+fn spawn_host_stats_sampler(
+    probe: VmmStatsProbe,
+    observer: Observer,
+    cid: u32,
+    interval: std::time::Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let cid_str = cid.to_string();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if !probe.is_running() {
+                break;
+            }
+            // procfs reads plus two brief device locks; cheap enough to
+            // run on the worker, like the daemon's host metrics task.
+            probe
+                .sample()
+                .record(observer.metrics(), &[("vm_cid", &cid_str)]);
+        }
+    })
+}
+
 #[async_trait::async_trait]
 impl VmmBackend for KvmBackend {
     async fn start(&mut self, config: BackendConfig) -> Result<()> {
         if let Some(warning) = config.initramfs_memory_warning() {
             warn!("KvmBackend: {}", warning);
         }
+        self.capabilities()
+            .require(config.required_caps(), self.kind())?;
+        if config.snapshot.is_none() {
+            config.check_kernel_arch()?;
+        }
+        self.protocol_tap = config.protocol_tap.clone();
+        if self.serial_control && (config.enable_snapshots || config.snapshot.is_some()) {
+            return Err(Error::Config(
+                "the serial control channel does not support snapshots".into(),
+            ));
+        }
         // Snapshot restore path: skip cold boot entirely
         if let Some(ref snapshot_dir) = config.snapshot {
+            if config.resource_policy != ResourcePolicy::default() {
+                return Err(Error::Config(
+                    "resource policies are not applied to snapshot restores".into(),
+                ));
+            }
+            if config.watchdog.is_some() {
+                return Err(Error::Config("a restored VM has no watchdog device".into()));
+            }
+            if !config.extra_cmdline.is_empty() || !config.security.write_roots.is_empty() {
+                return Err(Error::Config(
+                    "a restored VM keeps the kernel cmdline it was booted with".into(),
+                ));
+            }
             info!("Restoring VM from snapshot: {}", snapshot_dir.display());
             let mut vm = MicroVm::from_snapshot(snapshot_dir).await?;
             self.cid = vm.cid();
@@ -180,7 +274,10 @@ impl VmmBackend for KvmBackend {
                 let stream = VsockStream::connect_unix(&socket_path, GUEST_AGENT_PORT)?;
                 Ok(Box::new(stream))
             });
-            let channel = Arc::new(ControlChannel::new_restored(connector, session_secret));
+            let channel = Arc::new(
+                ControlChannel::new_restored(connector, session_secret)
+                    .with_protocol_tap(self.protocol_tap.as_ref()),
+            );
             let channel_for_warmup = Arc::clone(&channel);
             tokio::spawn(async move {
                 channel_for_warmup.warm_handshake().await;
@@ -190,6 +287,7 @@ impl VmmBackend for KvmBackend {
                 self.guest_console_task = Some(spawn_guest_console_task(
                     serial_output,
                     config.guest_console.clone(),
+                    self.console_observer.clone(),
                 ));
             }
             self.memory_mb = snap.config.memory_mb;
@@ -208,8 +306,9 @@ impl VmmBackend for KvmBackend {
         // compatibility (from_snapshot always restores into
         // VirtioVsockUserspace). Cold-boot-only runs use Vhost-vsock for
         // lower per-RPC latency (in-kernel IRQ injection, no eventfd
-        // bridge thread).
-        let needs_userspace_vsock = config.enable_snapshots || config.snapshot.is_some();
+        // bridge thread), unless the host has no vhost-vsock.
+        let needs_userspace_vsock =
+            self.userspace_vsock || config.enable_snapshots || config.snapshot.is_some();
         let vsock_backend = if needs_userspace_vsock {
             VsockBackendType::Userspace
         } else {
@@ -221,7 +320,9 @@ impl VmmBackend for KvmBackend {
             .kernel(&config.kernel)
             .network(config.network)
             .enable_vsock(config.enable_vsock)
-            .vsock_backend(vsock_backend);
+            .vsock_backend(vsock_backend)
+            .control_port(self.serial_control)
+            .resource_policy(config.resource_policy.clone());
 
         if let Some(ref initramfs) = config.initramfs {
             vm_config = vm_config.initramfs(initramfs);
@@ -238,6 +339,9 @@ impl VmmBackend for KvmBackend {
         vm_config.oci_rootfs = config.oci_rootfs.clone();
         vm_config.oci_rootfs_dev = config.oci_rootfs_dev.clone();
         vm_config.oci_rootfs_disk = config.oci_rootfs_disk.clone();
+        vm_config.disks = config.disks.clone();
+        vm_config.watchdog = config.watchdog;
+        vm_config.extra_cmdline = config.extra_cmdline.clone();
 
         // Apply security config
         vm_config.security = SecurityConfig {
@@ -245,10 +349,26 @@ impl VmmBackend for KvmBackend {
             command_allowlist: config.security.command_allowlist,
             resource_limits: Default::default(),
             network_deny_list: config.security.network_deny_list,
+            network_policy: config.security.network_policy,
             max_connections_per_second: config.security.max_connections_per_second,
             max_concurrent_connections: config.security.max_concurrent_connections,
             seccomp: config.security.seccomp,
+            write_roots: config.security.write_roots,
         };
+        vm_config.dns = config.dns;
+        vm_config.network_mode = config.network_mode;
+        vm_config.connection_observer = self.connection_observer.clone();
+        if config.console_shell {
+            vm_config
+                .agent_cmdline
+                .push("voidbox.console_shell=ttyS0".to_string());
+        }
+        if config.boot_profile == BootProfile::FastBoot {
+            vm_config = vm_config.enable_rng(false).enable_balloon(false);
+            vm_config
+                .agent_cmdline
+                .push("voidbox.fast_boot=1".to_string());
+        }
 
         let mut vm = MicroVm::new(vm_config).await?;
         self.cid = vm.cid();
@@ -257,10 +377,17 @@ impl VmmBackend for KvmBackend {
         self.network = config.network;
 
         let session_secret = config.security.session_secret;
-        let connector = vm
-            .vsock_connector()
-            .expect("vsock device must be present when enable_vsock is true");
-        let channel = Arc::new(ControlChannel::new(connector, session_secret));
+        let connector = if self.serial_control {
+            vm.control_port_connector()
+                .expect("virtio-console device must be present when control_port is set")
+        } else {
+            vm.vsock_connector()
+                .expect("vsock device must be present when enable_vsock is true")
+        };
+        let channel = Arc::new(
+            ControlChannel::new(connector, session_secret)
+                .with_protocol_tap(self.protocol_tap.as_ref()),
+        );
         let channel_for_warmup = Arc::clone(&channel);
         tokio::spawn(async move {
             channel_for_warmup.warm_handshake().await;
@@ -270,6 +397,7 @@ impl VmmBackend for KvmBackend {
             self.guest_console_task = Some(spawn_guest_console_task(
                 serial_output,
                 config.guest_console.clone(),
+                self.console_observer.clone(),
             ));
         }
         self.vm = Some(vm);
@@ -298,11 +426,7 @@ impl VmmBackend for KvmBackend {
             self.span_context.as_ref(),
         );
         let response = cc.send_exec_request(&request).await?;
-        Ok(ExecOutput::new(
-            response.stdout,
-            response.stderr,
-            response.exit_code,
-        ))
+        ExecOutput::from_response(program, response)
     }
 
     async fn exec_streaming(
@@ -312,6 +436,7 @@ impl VmmBackend for KvmBackend {
         env: &[(String, String)],
         working_dir: Option<&str>,
         timeout_secs: Option<u64>,
+        exec_id: Option<&str>,
     ) -> Result<(
         mpsc::Receiver<ExecOutputChunk>,
         oneshot::Receiver<Result<ExecResponse>>,
@@ -321,7 +446,7 @@ impl VmmBackend for KvmBackend {
             .as_ref()
             .ok_or(Error::VmNotRunning)?
             .clone();
-        let request = build_exec_request(
+        let mut request = build_exec_request(
             program,
             args,
             &[],
@@ -330,6 +455,9 @@ impl VmmBackend for KvmBackend {
             timeout_secs,
             self.span_context.as_ref(),
         );
+        if let Some(exec_id) = exec_id {
+            request.exec_id = Some(exec_id.to_string());
+        }
 
         let (chunk_tx, chunk_rx) = mpsc::channel(256);
         let (response_tx, response_rx) = oneshot::channel();
@@ -360,6 +488,35 @@ impl VmmBackend for KvmBackend {
         }
     }
 
+    async fn write_file_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Result<u64> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+
+        let response = cc.send_write_file_chunk(path, offset, data).await?;
+        if response.success {
+            Ok(response.staged_size)
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to write file chunk at offset {}: {}",
+                offset,
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn finalize_write_file(&self, path: &str, total_size: u64) -> Result<()> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+
+        let response = cc.send_write_file_finalize(path, total_size).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to finalize file write: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
     async fn mkdir_p(&self, path: &str) -> Result<()> {
         let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
 
@@ -392,6 +549,94 @@ impl VmmBackend for KvmBackend {
         }
     }
 
+    async fn hash_files(&self, paths: &[String]) -> Result<Vec<crate::guest::protocol::FileHash>> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_hash_files(paths).await?;
+        if response.success {
+            Ok(response.files)
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to hash files: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn sysinfo(&self) -> Result<crate::guest::protocol::SysInfo> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_sysinfo().await?;
+        match response.info {
+            Some(info) if response.success => Ok(info),
+            _ => Err(Error::Guest(format!(
+                "Failed to read system info: {}",
+                response.error.unwrap_or_default()
+            ))),
+        }
+    }
+
+    async fn symlink(&self, request: crate::guest::protocol::SymlinkRequest) -> Result<()> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_symlink(&request).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to create symlink: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn chmod(&self, request: crate::guest::protocol::ChmodRequest) -> Result<()> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_chmod(&request).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to change file mode: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_read_link(path).await?;
+        response.target.ok_or_else(|| {
+            Error::Guest(format!(
+                "Failed to read link: {}",
+                response.error.unwrap_or_default()
+            ))
+        })
+    }
+
+    async fn fs_diff(&self, root: Option<&str>) -> Result<crate::guest::protocol::FsDiffResponse> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let response = cc.send_fs_diff(root).await?;
+        if response.success {
+            Ok(response)
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to diff filesystem: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn export_workspace(&self, path_filter: Option<&str>) -> Result<Vec<u8>> {
+        let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
+        let (archive, response) = cc.send_export_workspace(path_filter).await?;
+        if response.success {
+            Ok(archive)
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to export workspace: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
     async fn start_telemetry(
         &mut self,
         observer: Observer,
@@ -404,10 +649,20 @@ impl VmmBackend for KvmBackend {
             .ok_or(Error::VmNotRunning)?
             .clone();
 
+        if let Some(vm) = &self.vm {
+            spawn_host_stats_sampler(
+                vm.stats_probe().with_control_channel(cc.clone()),
+                observer.clone(),
+                self.cid,
+                std::time::Duration::from_millis(opts.interval_ms.max(100)),
+            );
+        }
+
         let aggregator = Arc::new(match ring_buffer {
             Some(rb) => TelemetryAggregator::with_ring_buffer(observer, self.cid, rb),
             None => TelemetryAggregator::new(observer, self.cid),
         });
+        aggregator.attach_control_channel(cc.clone());
         let agg_clone = aggregator.clone();
 
         tokio::spawn(async move {
@@ -429,6 +684,33 @@ impl VmmBackend for KvmBackend {
         self.span_context = Some(ctx);
     }
 
+    fn set_connection_observer(&mut self, observer: ConnectionObserver) {
+        self.connection_observer = Some(observer);
+    }
+
+    fn set_console_observer(&mut self, observer: ConsoleObserver) {
+        self.console_observer = Some(observer);
+    }
+
+    fn console_input(&self) -> Option<ConsoleInput> {
+        let serial = self.vm.as_ref()?.serial();
+        Some(ConsoleInput::new(move |bytes| serial.queue_input(bytes)))
+    }
+
+    fn record_boot_phases(&self, timeline: &mut BootTimeline) {
+        if let Some(vm) = &self.vm {
+            vm.record_boot_phases(timeline);
+        }
+    }
+
+    fn control_channel(&self) -> Option<Arc<ControlChannel>> {
+        self.control_channel.clone()
+    }
+
+    fn watchdog_expired(&self) -> Option<std::time::Duration> {
+        self.vm.as_ref()?.watchdog()?.check()
+    }
+
     async fn attach_pty(&self, request: PtyOpenRequest) -> Result<super::pty_session::PtySession> {
         let cc = self.control_channel.as_ref().ok_or(Error::VmNotRunning)?;
         cc.open_pty(request).await
@@ -439,14 +721,42 @@ impl VmmBackend for KvmBackend {
     }
 
     async fn stop(&mut self) -> Result<()> {
+        self.shutdown(super::DEFAULT_SHUTDOWN_TIMEOUT)
+            .await
+            .map(|_| ())
+    }
+
+    async fn shutdown(&mut self, timeout: std::time::Duration) -> Result<Option<ShutdownAck>> {
+        let mut ack = None;
         if let Some(mut vm) = self.vm.take() {
-            vm.stop().await?;
+            ack = vm.shutdown(timeout).await?;
         }
         if let Some(task) = self.guest_console_task.take() {
             let _ = task.await;
         }
         self.control_channel = None;
-        Ok(())
+        Ok(ack)
+    }
+
+    async fn reboot(&mut self, timeout: std::time::Duration) -> Result<ShutdownAck> {
+        let vm = self.vm.as_mut().ok_or(Error::VmNotRunning)?;
+        let ack = vm.reboot(timeout).await?;
+        if let Some(channel) = &self.control_channel {
+            channel.reset().await;
+            channel.ensure_connected().await?;
+        }
+        Ok(ack)
+    }
+
+    fn set_memory_target(&self, mb: usize) -> Result<()> {
+        self.vm
+            .as_ref()
+            .ok_or(Error::VmNotRunning)?
+            .set_memory_target(mb)
+    }
+
+    fn memory_actual_mb(&self) -> Option<usize> {
+        self.vm.as_ref()?.memory_actual_mb()
     }
 
     async fn create_auto_snapshot(
@@ -468,8 +778,9 @@ impl VmmBackend for KvmBackend {
             memory_mb: self.memory_mb,
             vcpus: self.vcpus,
             cid: 0, // overwritten by snapshot_internal
-            vsock_mmio_base: VirtioSlot::Vsock.mmio_base(),
+            vsock_mmio_base: vm.memory_layout().mmio_base(VirtioSlot::Vsock),
             network: vm.has_network(),
+            virtio_mmio_base: vm.memory_layout().custom_virtio_base(),
         };
 
         let snap_path = vm.snapshot(snapshot_dir, config_hash, snap_config).await?;
@@ -493,7 +804,10 @@ impl VmmBackend for KvmBackend {
             let stream = VsockStream::connect_unix(&socket_path, GUEST_AGENT_PORT)?;
             Ok(Box::new(stream))
         });
-        let channel = Arc::new(ControlChannel::new_restored(connector, session_secret));
+        let channel = Arc::new(
+            ControlChannel::new_restored(connector, session_secret)
+                .with_protocol_tap(self.protocol_tap.as_ref()),
+        );
         let channel_for_warmup = Arc::clone(&channel);
         tokio::spawn(async move {
             channel_for_warmup.warm_handshake().await;
@@ -513,6 +827,7 @@ impl VmmBackend for KvmBackend {
             self.guest_console_task = Some(spawn_guest_console_task(
                 serial_output,
                 GuestConsoleSink::Stderr,
+                self.console_observer.clone(),
             ));
         }
 
@@ -524,6 +839,14 @@ impl VmmBackend for KvmBackend {
     fn cid(&self) -> u32 {
         self.cid
     }
+
+    fn kind(&self) -> Backend {
+        if self.userspace_vsock {
+            Backend::KvmUserspaceVsock
+        } else {
+            Backend::Kvm
+        }
+    }
 }
 
 #[cfg(test)]
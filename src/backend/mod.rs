@@ -8,8 +8,11 @@
 //! - **Linux**: `KvmBackend` — KVM micro-VMs with virtio-mmio devices
 //! - **macOS**: `VzBackend` — Apple Virtualization.framework
 
+pub mod cmdline;
 pub mod control_channel;
 pub mod multiplex;
+pub mod network_policy;
+pub mod protocol_tap;
 pub mod pty_session;
 
 #[cfg(target_os = "linux")]
@@ -22,23 +25,33 @@ use std::io::{Read, Seek, SeekFrom};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use void_box_protocol::SessionSecret;
 
 use crate::error::Result;
-use crate::guest::protocol::{ExecOutputChunk, ExecResponse, TelemetrySubscribeRequest};
+use crate::guest::protocol::{
+    ExecOutputChunk, ExecResponse, ShutdownAck, TelemetrySubscribeRequest,
+};
+use crate::observe::network::ConnectionRecord;
 use crate::observe::telemetry::{TelemetryAggregator, TelemetryBuffer};
 use crate::observe::tracer::SpanContext;
 use crate::observe::Observer;
 use crate::ExecOutput;
 
+pub use network_policy::{NetworkPolicy, NetworkRule, PolicyAction, RuleTarget};
+pub use protocol_tap::{FrameDirection, FrameRecord, ProtocolTap};
+
 /// Extra bytes needed beyond the initramfs footprint: Linux kernel image in
 /// memory (~80 MB for Ubuntu arm64 6.8) plus slack for page tables, heap,
 /// and the init process (~128 MB).  Both the compressed initramfs (bootloader
 /// places it in guest RAM) and the decompressed tmpfs content coexist during
 /// early-boot extraction, so the caller adds both on top of this constant.
 const INITRAMFS_OVERHEAD_BYTES: u64 = 208 * 1024 * 1024;
+/// How long [`VmmBackend::stop`] waits for the guest to shut down cleanly
+/// before stopping the VM hard.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 #[cfg(target_os = "linux")]
 const LINUX_GUEST_HOST_GATEWAY: &str = "10.0.2.2";
 #[cfg(target_os = "macos")]
@@ -51,6 +64,100 @@ fn session_secret_hex(session_secret: &[u8; 32]) -> String {
         .collect()
 }
 
+type ConnectionClosedFn = Arc<dyn Fn(&ConnectionRecord) + Send + Sync>;
+
+/// Callback for each outbound TCP connection the guest opens.
+///
+/// Receives the destination as the guest addressed it (before any NAT
+/// translation). An optional [`on_close`](Self::on_close) callback also
+/// receives a [`ConnectionRecord`] for every outbound TCP/UDP flow once it
+/// closes or is refused by network policy. Both are called on the network
+/// poll thread, so they must not block.
+#[derive(Clone)]
+pub struct ConnectionObserver {
+    opened: Arc<dyn Fn(Ipv4Addr, u16) + Send + Sync>,
+    closed: Option<ConnectionClosedFn>,
+}
+
+impl ConnectionObserver {
+    pub fn new(f: impl Fn(Ipv4Addr, u16) + Send + Sync + 'static) -> Self {
+        Self {
+            opened: Arc::new(f),
+            closed: None,
+        }
+    }
+
+    /// Also call `f` with each flow's record when it closes.
+    pub fn on_close(mut self, f: impl Fn(&ConnectionRecord) + Send + Sync + 'static) -> Self {
+        self.closed = Some(Arc::new(f));
+        self
+    }
+
+    pub fn notify(&self, dst_ip: Ipv4Addr, dst_port: u16) {
+        (self.opened)(dst_ip, dst_port)
+    }
+
+    pub fn notify_closed(&self, record: &ConnectionRecord) {
+        if let Some(closed) = &self.closed {
+            closed(record)
+        }
+    }
+}
+
+impl std::fmt::Debug for ConnectionObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ConnectionObserver")
+    }
+}
+
+/// Callback for each chunk of guest serial console output.
+///
+/// Called from the console drain task alongside the
+/// [`GuestConsoleSink`], so it must not block.
+#[derive(Clone)]
+pub struct ConsoleObserver(Arc<ConsoleCallback>);
+
+type ConsoleCallback = dyn Fn(&[u8]) + Send + Sync;
+
+impl ConsoleObserver {
+    pub fn new(f: impl Fn(&[u8]) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn notify(&self, bytes: &[u8]) {
+        (self.0)(bytes)
+    }
+}
+
+impl std::fmt::Debug for ConsoleObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ConsoleObserver")
+    }
+}
+
+/// Writes host input to the guest serial console.
+///
+/// Returned by [`VmmBackend::console_input`]. Bytes are queued on the
+/// console's receive buffer; writing never blocks.
+#[derive(Clone)]
+pub struct ConsoleInput(Arc<ConsoleCallback>);
+
+impl ConsoleInput {
+    pub fn new(f: impl Fn(&[u8]) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn write(&self, bytes: &[u8]) {
+        (self.0)(bytes)
+    }
+}
+
+impl std::fmt::Debug for ConsoleInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ConsoleInput")
+    }
+}
+
 /// A single host→guest directory mount.
 #[derive(Debug, Clone)]
 pub struct MountConfig {
@@ -62,6 +169,21 @@ pub struct MountConfig {
     pub read_only: bool,
 }
 
+/// A host file attached to the guest as a virtio-blk data disk (KVM).
+///
+/// Disks appear in the guest as `/dev/vdX` in attach order, after the OCI
+/// rootfs disk when one is present.
+#[derive(Debug, Clone)]
+pub struct DiskConfig {
+    /// Host path to the raw disk image.
+    pub path: PathBuf,
+    /// Reject guest writes.
+    pub read_only: bool,
+    /// Where the guest-agent mounts the disk's ext4 filesystem; `None`
+    /// leaves it an unmounted block device.
+    pub guest_path: Option<String>,
+}
+
 /// Host-side routing for the guest serial console.
 #[derive(Debug, Clone)]
 pub enum GuestConsoleSink {
@@ -73,6 +195,30 @@ pub enum GuestConsoleSink {
     File(PathBuf),
 }
 
+/// Trade-offs a boot makes between startup latency and guest features.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BootProfile {
+    /// Every device and kernel module the guest may need.
+    #[default]
+    Standard,
+    /// Boot for latency: no virtio-rng or virtio-balloon device, and the
+    /// guest-agent (`voidbox.fast_boot=1`) loads only the optional kernel
+    /// modules for devices and mounts the host attached, and polls for
+    /// devices and its vsock listener every 20 ms instead of every
+    /// 100-200 ms. Without virtio-rng the guest CRNG may take longer to
+    /// seed, and without the balloon the host cannot reclaim idle guest memory.
+    FastBoot,
+}
+
+impl BootProfile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootProfile::Standard => "standard",
+            BootProfile::FastBoot => "fast_boot",
+        }
+    }
+}
+
 /// Configuration passed to [`VmmBackend::start`].
 ///
 /// This is a backend-agnostic description of what the caller wants.
@@ -91,10 +237,18 @@ pub struct BackendConfig {
     pub rootfs: Option<PathBuf>,
     /// Enable networking.
     pub network: bool,
+    /// How guest networking is provided when `network` is enabled.
+    pub network_mode: NetworkMode,
     /// Enable vsock for host-guest communication.
     pub enable_vsock: bool,
     /// Host-side routing for guest serial console output.
     pub guest_console: GuestConsoleSink,
+    /// Run a root shell on the guest serial console, for
+    /// [`VmmBackend::console_input`]. Backends without console input ignore
+    /// it.
+    pub console_shell: bool,
+    /// Latency/feature trade-offs for the boot.
+    pub boot_profile: BootProfile,
     /// Host directory to share with guest (virtiofs on macOS, future on Linux).
     pub shared_dir: Option<PathBuf>,
     /// Host directory mounts into the guest.
@@ -105,8 +259,12 @@ pub struct BackendConfig {
     pub oci_rootfs_dev: Option<String>,
     /// Host path to OCI rootfs disk image to attach via virtio-blk (KVM).
     pub oci_rootfs_disk: Option<PathBuf>,
+    /// Data disks attached via virtio-blk (KVM).
+    pub disks: Vec<DiskConfig>,
     /// Environment variables to inject into guest commands.
     pub env: Vec<(String, String)>,
+    /// Guest DNS resolvers and static host entries.
+    pub dns: DnsConfig,
     /// Security configuration.
     pub security: BackendSecurityConfig,
     /// Path to a snapshot directory to restore from (skips cold boot).
@@ -119,6 +277,19 @@ pub struct BackendConfig {
     /// time.  Restore path implies it; auto-snapshot callers set it
     /// explicitly.
     pub enable_snapshots: bool,
+    /// Host-enforced vCPU pinning and CPU quota.
+    pub resource_policy: ResourcePolicy,
+    /// Record control-channel frames for debugging; see [`ProtocolTap`].
+    pub protocol_tap: Option<ProtocolTap>,
+    /// Attach a watchdog the guest-agent must pet within this timeout
+    /// (KVM only; see [`crate::devices::watchdog`]).
+    pub watchdog: Option<std::time::Duration>,
+    /// User kernel cmdline fragments, appended after the backend's own
+    /// arguments; see [`cmdline`].
+    pub extra_cmdline: Vec<String>,
+    /// Share Apple's Rosetta translator so the arm64 guest runs x86_64
+    /// binaries (VZ on Apple silicon only; see [`rosetta_availability`]).
+    pub rosetta: bool,
 }
 
 impl BackendConfig {
@@ -139,12 +310,17 @@ impl BackendConfig {
             network: false,
             enable_vsock: true,
             guest_console: GuestConsoleSink::Stderr,
+            console_shell: false,
+            boot_profile: BootProfile::Standard,
             shared_dir: None,
             mounts: Vec::new(),
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
             env: Vec::new(),
+            dns: DnsConfig::default(),
+            network_mode: Default::default(),
             security: BackendSecurityConfig {
                 session_secret: SessionSecret::new(bytes),
                 command_allowlist: DEFAULT_COMMAND_ALLOWLIST
@@ -152,12 +328,19 @@ impl BackendConfig {
                     .map(|s| s.to_string())
                     .collect(),
                 network_deny_list: Vec::new(),
+                network_policy: None,
                 max_connections_per_second: 0,
                 max_concurrent_connections: 0,
                 seccomp: false,
+                write_roots: Vec::new(),
             },
             snapshot: None,
             enable_snapshots: false,
+            resource_policy: ResourcePolicy::default(),
+            protocol_tap: None,
+            watchdog: None,
+            extra_cmdline: Vec::new(),
+            rosetta: false,
         }
     }
 
@@ -173,6 +356,47 @@ impl BackendConfig {
         self
     }
 
+    /// The optional backend features this configuration asks for.
+    pub fn required_caps(&self) -> BackendCaps {
+        let mut caps = BackendCaps::empty();
+        if self.snapshot.is_some() || self.enable_snapshots {
+            caps |= BackendCaps::SNAPSHOTS;
+        }
+        if !self.disks.is_empty() {
+            caps |= BackendCaps::DATA_DISKS;
+        }
+        if self.resource_policy != ResourcePolicy::default() {
+            caps |= BackendCaps::RESOURCE_POLICY;
+        }
+        if self.watchdog.is_some() {
+            caps |= BackendCaps::WATCHDOG;
+        }
+        if self.network && self.network_mode != NetworkMode::Slirp {
+            caps |= BackendCaps::TAP_NETWORK;
+        }
+        if self.security.network_policy.is_some() {
+            caps |= BackendCaps::NETWORK_POLICY;
+        }
+        if self.rosetta {
+            caps |= BackendCaps::ROSETTA;
+        }
+        caps
+    }
+
+    /// Refuse a [`kernel`](Self::kernel) built for another CPU: both
+    /// backends run guests natively, so an x86_64 kernel cannot boot under
+    /// VZ on Apple silicon, nor an arm64 one under KVM on x86_64. Kernels
+    /// that cannot be read or whose header does not name an architecture
+    /// are left to the boot itself.
+    pub fn check_kernel_arch(&self) -> Result<()> {
+        match crate::image::verify_kernel_arch(&self.kernel, std::env::consts::ARCH) {
+            Err(e @ crate::image::ImageError::KernelArchMismatch { .. }) => {
+                Err(crate::Error::Config(e.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Check whether the configured memory is likely sufficient for the initramfs.
     ///
     /// Reads the gzip ISIZE field (last 4 bytes) for the uncompressed size and
@@ -219,8 +443,9 @@ impl BackendConfig {
 ///
 /// The caller owns the platform-specific prefix (console device, virtio
 /// discovery, rootfs device wiring). This helper appends the common suffix:
-/// session secret, boot clock, optional guest networking flags, mount
-/// descriptors, and OCI rootfs selectors.
+/// session secret, boot clock, optional guest networking flags and DNS
+/// resolvers, mount descriptors, OCI rootfs selectors, and extra write
+/// roots.
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn append_common_guest_kernel_args(
     cmdline_parts: &mut Vec<String>,
@@ -228,9 +453,11 @@ pub(crate) fn append_common_guest_kernel_args(
     epoch_secs: u64,
     network_enabled: bool,
     include_guest_network_flag: bool,
+    dns_servers: &[Ipv4Addr],
     mounts: &[MountConfig],
     oci_rootfs: Option<&str>,
     oci_rootfs_dev: Option<&str>,
+    write_roots: &[String],
 ) {
     cmdline_parts.push(format!(
         "voidbox.secret={}",
@@ -243,6 +470,10 @@ pub(crate) fn append_common_guest_kernel_args(
             cmdline_parts.push("voidbox.network=1".to_string());
         }
         cmdline_parts.push("ipv6.disable=1".to_string());
+        if !dns_servers.is_empty() {
+            let servers: Vec<String> = dns_servers.iter().map(Ipv4Addr::to_string).collect();
+            cmdline_parts.push(format!("voidbox.dns={}", servers.join(",")));
+        }
     }
 
     for (mount_index, mount) in mounts.iter().enumerate() {
@@ -260,6 +491,14 @@ pub(crate) fn append_common_guest_kernel_args(
     if let Some(oci_rootfs_device) = oci_rootfs_dev {
         cmdline_parts.push(format!("voidbox.oci_rootfs_dev={}", oci_rootfs_device));
     }
+
+    if !write_roots.is_empty() {
+        cmdline_parts.push(format!(
+            "{}{}",
+            void_box_protocol::WRITE_ROOTS_CMDLINE_KEY,
+            write_roots.join(",")
+        ));
+    }
 }
 
 /// Host-reachable gateway address as seen from inside the guest VM.
@@ -281,6 +520,29 @@ pub fn guest_host_gateway() -> &'static str {
     }
 }
 
+/// Whether the host can run x86_64 guest binaries through Rosetta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosettaAvailability {
+    /// Not a Mac with Apple silicon, or macOS is older than 13.
+    NotSupported,
+    /// Supported, but Rosetta has not been installed on the host.
+    NotInstalled,
+    /// [`BackendConfig::rosetta`] can be set.
+    Installed,
+}
+
+/// Check whether [`BackendConfig::rosetta`] can be used on this host.
+pub fn rosetta_availability() -> RosettaAvailability {
+    #[cfg(target_os = "macos")]
+    {
+        vz::rosetta::availability()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        RosettaAvailability::NotSupported
+    }
+}
+
 /// Build a guest-visible HTTP URL to a host-local service.
 pub fn guest_host_url(port: u16) -> String {
     format!("http://{}:{}", guest_host_gateway(), port)
@@ -302,6 +564,72 @@ pub fn guest_accessible_bind_addr(port: u16) -> SocketAddr {
     }
 }
 
+/// How the guest's virtio-net device reaches the host network (KVM only).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// User-mode networking through the built-in SLIRP stack. Needs no host
+    /// privileges and enforces the deny list, [`NetworkPolicy`], DNS host
+    /// aliases and connection observers.
+    #[default]
+    Slirp,
+    /// Attach the guest NIC directly to host TAP device `name`, creating it
+    /// if it does not exist, and enslave it to `bridge` if given.
+    ///
+    /// Runs at close to line rate but requires `CAP_NET_ADMIN` and host-side
+    /// bridging or routing; the guest configures itself over DHCP. None of
+    /// the SLIRP-level controls apply, and a [`NetworkPolicy`] is rejected.
+    Tap {
+        name: String,
+        bridge: Option<String>,
+    },
+    /// Like [`Tap`](Self::Tap), but the kernel's vhost-net driver moves
+    /// frames between the guest's virtqueues and the TAP device, skipping
+    /// the userspace copy and poll thread. Falls back to
+    /// [`Slirp`](Self::Slirp) when `/dev/vhost-net` cannot be opened. Not
+    /// captured in snapshots.
+    VhostNet {
+        name: String,
+        bridge: Option<String>,
+    },
+}
+
+/// Guest DNS settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// Upstream resolvers. Empty keeps the defaults: the host's
+    /// `/etc/resolv.conf` servers behind the SLIRP DNS server on KVM,
+    /// `8.8.8.8` on VZ.
+    pub servers: Vec<Ipv4Addr>,
+    /// Names the SLIRP DNS server answers itself with a fixed `A` record,
+    /// without asking an upstream resolver (KVM only).
+    pub host_aliases: Vec<(String, Ipv4Addr)>,
+}
+
+impl DnsConfig {
+    /// Parse resolver and host-alias addresses given as strings.
+    pub fn parse(servers: &[String], host_aliases: &[(String, String)]) -> Result<Self> {
+        let parse_ip = |ip: &str| {
+            ip.parse::<Ipv4Addr>()
+                .map_err(|e| crate::Error::Config(format!("invalid DNS address '{ip}': {e}")))
+        };
+        Ok(Self {
+            servers: servers
+                .iter()
+                .map(|ip| parse_ip(ip))
+                .collect::<Result<_>>()?,
+            host_aliases: host_aliases
+                .iter()
+                .map(|(name, ip)| {
+                    Ok((
+                        name.trim_end_matches('.').to_ascii_lowercase(),
+                        parse_ip(ip)?,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
 /// Security-relevant settings for the backend.
 #[derive(Debug, Clone)]
 pub struct BackendSecurityConfig {
@@ -311,12 +639,18 @@ pub struct BackendSecurityConfig {
     pub command_allowlist: Vec<String>,
     /// Network deny list in CIDR notation.
     pub network_deny_list: Vec<String>,
+    /// Egress policy enforced on top of the deny list (KVM only).
+    pub network_policy: Option<NetworkPolicy>,
     /// Maximum new TCP connections per second.
     pub max_connections_per_second: u32,
     /// Maximum concurrent TCP connections.
     pub max_concurrent_connections: usize,
     /// Whether to install seccomp-bpf (Linux only, ignored on macOS).
     pub seccomp: bool,
+    /// Guest directories the agent's write RPCs may touch on top of
+    /// [`DEFAULT_WRITE_ROOTS`](void_box_protocol::DEFAULT_WRITE_ROOTS),
+    /// e.g. `/opt` for tool installs. Passed on the kernel cmdline.
+    pub write_roots: Vec<String>,
 }
 
 /// Absolute guest path where the network deny list is materialized for
@@ -380,6 +714,8 @@ pub trait VmmBackend: Send + Sync {
     /// Execute a command with streaming output chunks.
     ///
     /// Returns a channel of `ExecOutputChunk` and a oneshot for the final response.
+    /// With an `exec_id`, the running command can be signalled through
+    /// [`ControlChannel::send_signal_exec`](control_channel::ControlChannel::send_signal_exec).
     async fn exec_streaming(
         &self,
         program: &str,
@@ -387,6 +723,7 @@ pub trait VmmBackend: Send + Sync {
         env: &[(String, String)],
         working_dir: Option<&str>,
         timeout_secs: Option<u64>,
+        exec_id: Option<&str>,
     ) -> Result<(
         tokio::sync::mpsc::Receiver<ExecOutputChunk>,
         tokio::sync::oneshot::Receiver<Result<ExecResponse>>,
@@ -395,6 +732,15 @@ pub trait VmmBackend: Send + Sync {
     /// Write a file to the guest filesystem.
     async fn write_file(&self, path: &str, content: &[u8]) -> Result<()>;
 
+    /// Write one chunk of a chunked file transfer at `offset`.
+    ///
+    /// Returns the size of the guest-side staging file after the write.
+    /// Nothing appears at `path` until [`finalize_write_file`](Self::finalize_write_file).
+    async fn write_file_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Result<u64>;
+
+    /// Commit a chunked file transfer of `total_size` bytes onto `path`.
+    async fn finalize_write_file(&self, path: &str, total_size: u64) -> Result<()>;
+
     /// Create directories in the guest filesystem (mkdir -p).
     async fn mkdir_p(&self, path: &str) -> Result<()>;
 
@@ -404,6 +750,27 @@ pub trait VmmBackend: Send + Sync {
     /// Reads a file from the guest filesystem.
     async fn read_file_native(&self, path: &str) -> Result<Vec<u8>>;
 
+    /// SHA-256, size and mtime of each of `paths`, in order.
+    async fn hash_files(&self, paths: &[String]) -> Result<Vec<crate::guest::protocol::FileHash>>;
+
+    /// Creates a symbolic link in the guest filesystem.
+    async fn symlink(&self, request: crate::guest::protocol::SymlinkRequest) -> Result<()>;
+
+    /// Changes the mode and/or owner of a path in the guest filesystem.
+    async fn chmod(&self, request: crate::guest::protocol::ChmodRequest) -> Result<()>;
+
+    /// Reads the target of a symbolic link in the guest filesystem.
+    async fn read_link(&self, path: &str) -> Result<String>;
+
+    /// Kernel, load, memory and mount state of the guest.
+    async fn sysinfo(&self) -> Result<crate::guest::protocol::SysInfo>;
+
+    /// Reports files changed under `root` in the guest (whole rootfs if `None`).
+    async fn fs_diff(&self, root: Option<&str>) -> Result<crate::guest::protocol::FsDiffResponse>;
+
+    /// Exports `/workspace` (optionally narrowed by a glob) as tar bytes.
+    async fn export_workspace(&self, path_filter: Option<&str>) -> Result<Vec<u8>>;
+
     /// Start a telemetry subscription from the guest.
     async fn start_telemetry(
         &mut self,
@@ -415,6 +782,42 @@ pub trait VmmBackend: Send + Sync {
     /// Set the active span context for TRACEPARENT propagation.
     fn set_span_context(&mut self, ctx: SpanContext);
 
+    /// Register a callback for outbound guest TCP connections.
+    ///
+    /// Takes effect at the next cold boot via [`start`](Self::start).
+    /// Backends whose network stack does not expose individual connections
+    /// (VZ's NAT) ignore it.
+    fn set_connection_observer(&mut self, _observer: ConnectionObserver) {}
+
+    /// Register a callback for guest serial console output.
+    ///
+    /// Takes effect at the next [`start`](Self::start) (cold boot or
+    /// snapshot restore). Backends that hand the console straight to the
+    /// hypervisor (VZ) ignore it.
+    fn set_console_observer(&mut self, _observer: ConsoleObserver) {}
+
+    /// Input side of the guest serial console, once the VM is started.
+    ///
+    /// `None` when the VM is not running or the backend hands the console
+    /// straight to the hypervisor (VZ).
+    fn console_input(&self) -> Option<ConsoleInput> {
+        None
+    }
+
+    /// Add the host-side phases of the last [`start`](Self::start) that the
+    /// backend timed (kernel load, vCPU start) to `timeline`.
+    fn record_boot_phases(&self, _timeline: &mut crate::observe::boot::BootTimeline) {}
+
+    /// Control channel to the guest-agent, once the VM is started.
+    fn control_channel(&self) -> Option<Arc<control_channel::ControlChannel>>;
+
+    /// How long the guest has gone without petting its watchdog, once that
+    /// exceeds the configured timeout. Reported once per expiry; always
+    /// `None` for a VM started without [`BackendConfig::watchdog`].
+    fn watchdog_expired(&self) -> Option<std::time::Duration> {
+        None
+    }
+
     /// Opens a PTY session on the guest, returning a handle for interactive I/O.
     async fn attach_pty(
         &self,
@@ -427,6 +830,50 @@ pub trait VmmBackend: Send + Sync {
     /// Stop the VM and clean up resources.
     async fn stop(&mut self) -> Result<()>;
 
+    /// Ask the guest to stop its processes and sync filesystems, wait up to
+    /// `timeout`, then stop the VM as [`stop`](Self::stop) does.
+    ///
+    /// Returns the guest's summary, or `None` when it did not acknowledge
+    /// in time. Backends without a graceful path just stop.
+    async fn shutdown(&mut self, _timeout: Duration) -> Result<Option<ShutdownAck>> {
+        self.stop().await.map(|()| None)
+    }
+
+    /// Restart the guest kernel in place, keeping the VM, its devices and
+    /// its vsock CID, and wait for the new boot's guest-agent.
+    ///
+    /// Returns the guest's summary of stopping its processes.
+    async fn reboot(&mut self, _timeout: Duration) -> Result<ShutdownAck> {
+        Err(self.unsupported(BackendCaps::REBOOT))
+    }
+
+    /// Ask the guest to shrink to about `mb` MiB of usable memory through
+    /// its memory balloon, handing the rest back to the host. Passing the
+    /// VM's full `memory_mb` deflates the balloon. Backends without a
+    /// balloon return [`Error::Unsupported`](crate::Error::Unsupported),
+    /// VMs booted with [`BootProfile::FastBoot`]
+    /// [`Error::Config`](crate::Error::Config).
+    fn set_memory_target(&self, _mb: usize) -> Result<()> {
+        Err(self.unsupported(BackendCaps::MEMORY_BALLOON))
+    }
+
+    /// Guest memory not held by the balloon, in MiB, as last reported by
+    /// the guest. `None` when the backend cannot tell.
+    fn memory_actual_mb(&self) -> Option<usize> {
+        None
+    }
+
+    /// Stop the guest's vCPUs, keeping its memory and devices, until
+    /// [`resume`](Self::resume).
+    async fn suspend(&self) -> Result<()> {
+        Err(self.unsupported(BackendCaps::SUSPEND))
+    }
+
+    /// Let a guest stopped by [`suspend`](Self::suspend) run again.
+    async fn resume(&self) -> Result<()> {
+        Err(self.unsupported(BackendCaps::SUSPEND))
+    }
+
     /// Take a snapshot of the running VM, save it, then restore from it so
     /// the VM continues running (~500 ms stop-and-restart overhead).
     async fn create_auto_snapshot(
@@ -439,6 +886,24 @@ pub trait VmmBackend: Send + Sync {
 
     /// Get the vsock CID for this VM.
     fn cid(&self) -> u32;
+
+    /// Which strategy this backend runs VMs with.
+    fn kind(&self) -> Backend;
+
+    /// The optional features this backend has. Methods and
+    /// [`BackendConfig`] fields needing one it lacks fail with
+    /// [`Error::Unsupported`](crate::Error::Unsupported).
+    fn capabilities(&self) -> BackendCaps {
+        self.kind().capabilities()
+    }
+
+    /// The error for asking this backend for `feature`.
+    fn unsupported(&self, feature: BackendCaps) -> crate::Error {
+        crate::Error::Unsupported {
+            feature,
+            backend: self.kind(),
+        }
+    }
 }
 
 /// Default command allowlist for guest execution.
@@ -513,21 +978,323 @@ impl Default for ResourceLimits {
     }
 }
 
-/// Create the platform-appropriate backend.
+/// Host-side CPU limits for a sandbox's vCPU threads.
+///
+/// Unlike [`ResourceLimits`], which the guest applies to its own processes,
+/// these are enforced by the VMM on the host, so a guest cannot lift them.
+/// Memory needs no host-side knob: guest RAM is a fixed `memory_mb` mapping
+/// with no hotplug, so `memory_mb` is already a hard cap. Running out of it
+/// kills the command in the guest, and the host reports that as
+/// [`Error::GuestOom`](crate::Error::GuestOom).
+///
+/// Enforced by the KVM backend; the VZ backend rejects a non-default policy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourcePolicy {
+    /// Host CPU time the whole VM may use, in CPUs (`1.5` = one and a half
+    /// host CPUs). Split evenly across vCPUs and enforced per scheduling
+    /// period. `None` leaves the vCPUs unthrottled.
+    #[serde(default)]
+    pub cpu_quota: Option<f64>,
+    /// Host CPUs to pin vCPU threads to: vCPU `i` runs on
+    /// `cpu_affinity[i % len]`. Empty leaves placement to the host scheduler.
+    #[serde(default)]
+    pub cpu_affinity: Vec<usize>,
+}
+
+impl ResourcePolicy {
+    /// Check that the quota is positive and the CPU indices are addressable.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(quota) = self.cpu_quota {
+            if !quota.is_finite() || quota <= 0.0 {
+                return Err(crate::Error::Config(format!(
+                    "cpu_quota must be a positive number of CPUs, got {}",
+                    quota
+                )));
+            }
+        }
+        if let Some(&cpu) = self.cpu_affinity.iter().find(|&&cpu| cpu >= MAX_HOST_CPU) {
+            return Err(crate::Error::Config(format!(
+                "cpu_affinity entry {} is out of range (max {})",
+                cpu,
+                MAX_HOST_CPU - 1
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Host CPUs addressable by a `cpu_set_t`.
+const MAX_HOST_CPU: usize = 1024;
+
+/// Optional features a backend may have, as a bitset; see
+/// [`VmmBackend::capabilities`].
 ///
-/// On Linux, returns a [`KvmBackend`](kvm::KvmBackend).
-/// On macOS, returns a `VzBackend`.
+/// Asking a backend for a feature it lacks fails with
+/// [`Error::Unsupported`](crate::Error::Unsupported), rather than the
+/// feature being ignored or failing further down.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BackendCaps(u32);
+
+impl BackendCaps {
+    /// Save and restore VM snapshots.
+    pub const SNAPSHOTS: Self = Self(1 << 0);
+    /// Diff snapshots holding only the pages dirtied since a base.
+    pub const DIFF_SNAPSHOTS: Self = Self(1 << 1);
+    /// Restart the guest kernel in place; see [`VmmBackend::reboot`].
+    pub const REBOOT: Self = Self(1 << 2);
+    /// Pause and resume the VM; see [`VmmBackend::suspend`].
+    pub const SUSPEND: Self = Self(1 << 3);
+    /// Reclaim guest memory; see [`VmmBackend::set_memory_target`].
+    pub const MEMORY_BALLOON: Self = Self(1 << 4);
+    /// Share host directories over virtiofs.
+    pub const VIRTIOFS: Self = Self(1 << 5);
+    /// Attach [`BackendConfig::disks`].
+    pub const DATA_DISKS: Self = Self(1 << 6);
+    /// Enforce [`BackendConfig::resource_policy`].
+    pub const RESOURCE_POLICY: Self = Self(1 << 7);
+    /// Attach [`BackendConfig::watchdog`].
+    pub const WATCHDOG: Self = Self(1 << 8);
+    /// Type into the guest's serial console; see [`VmmBackend::console_input`].
+    pub const CONSOLE_INPUT: Self = Self(1 << 9);
+    /// [`NetworkMode::Tap`] and [`NetworkMode::VhostNet`].
+    pub const TAP_NETWORK: Self = Self(1 << 10);
+    /// Enforce [`BackendSecurityConfig::network_policy`].
+    pub const NETWORK_POLICY: Self = Self(1 << 11);
+    /// Run x86_64 guest binaries; see [`BackendConfig::rosetta`].
+    pub const ROSETTA: Self = Self(1 << 12);
+
+    const NAMES: [(Self, &'static str); 13] = [
+        (Self::SNAPSHOTS, "snapshots"),
+        (Self::DIFF_SNAPSHOTS, "diff snapshots"),
+        (Self::REBOOT, "reboot"),
+        (Self::SUSPEND, "suspend"),
+        (Self::MEMORY_BALLOON, "memory balloon"),
+        (Self::VIRTIOFS, "virtiofs"),
+        (Self::DATA_DISKS, "data disks"),
+        (Self::RESOURCE_POLICY, "resource policies"),
+        (Self::WATCHDOG, "watchdog"),
+        (Self::CONSOLE_INPUT, "console input"),
+        (Self::TAP_NETWORK, "TAP networking"),
+        (Self::NETWORK_POLICY, "network policies"),
+        (Self::ROSETTA, "Rosetta"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every feature in `other` is in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The features of `required` that `self` lacks.
+    pub const fn missing(self, required: Self) -> Self {
+        Self(required.0 & !self.0)
+    }
+
+    /// Names of the features in the set, e.g. `["snapshots", "reboot"]`.
+    pub fn names(self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(cap, _)| self.contains(*cap))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// `Ok` if `self` has all of `required`, otherwise
+    /// [`Error::Unsupported`](crate::Error::Unsupported) naming what
+    /// `backend` lacks.
+    pub fn require(self, required: Self, backend: Backend) -> Result<()> {
+        let missing = self.missing(required);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::Unsupported {
+                feature: missing,
+                backend,
+            })
+        }
+    }
+}
+
+impl std::ops::BitOr for BackendCaps {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for BackendCaps {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl std::fmt::Display for BackendCaps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.names().join(", "))
+    }
+}
+
+impl std::fmt::Debug for BackendCaps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+/// How this host runs VMs; see [`Backend::detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// KVM, with the control channel on the kernel's vhost-vsock.
+    Kvm,
+    /// KVM on a host without `/dev/vhost-vsock`, such as WSL2's stock
+    /// kernel: the control channel runs over void-box's userspace
+    /// virtio-vsock device, reached through a host Unix socket.
+    KvmUserspaceVsock,
+    /// Apple Virtualization.framework.
+    Vz,
+}
+
+impl Backend {
+    /// Short name for messages: `kvm` or `vz`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Kvm | Self::KvmUserspaceVsock => "kvm",
+            Self::Vz => "vz",
+        }
+    }
+
+    /// What a backend using this strategy can do on this host.
+    pub fn capabilities(self) -> BackendCaps {
+        match self {
+            Self::Kvm | Self::KvmUserspaceVsock => {
+                BackendCaps::SNAPSHOTS
+                    | BackendCaps::DIFF_SNAPSHOTS
+                    | BackendCaps::REBOOT
+                    | BackendCaps::MEMORY_BALLOON
+                    | BackendCaps::DATA_DISKS
+                    | BackendCaps::RESOURCE_POLICY
+                    | BackendCaps::WATCHDOG
+                    | BackendCaps::CONSOLE_INPUT
+                    | BackendCaps::TAP_NETWORK
+                    | BackendCaps::NETWORK_POLICY
+            }
+            Self::Vz => {
+                let mut caps = BackendCaps::SNAPSHOTS
+                    | BackendCaps::SUSPEND
+                    | BackendCaps::MEMORY_BALLOON
+                    | BackendCaps::VIRTIOFS;
+                // Starting a VM that asks for it explains how to install it.
+                if rosetta_availability() != RosettaAvailability::NotSupported {
+                    caps |= BackendCaps::ROSETTA;
+                }
+                caps
+            }
+        }
+    }
+
+    /// Pick the strategy this host supports.
+    ///
+    /// On Linux this needs a usable `/dev/kvm` (under WSL2, nested
+    /// virtualization must be enabled) and falls back to
+    /// [`KvmUserspaceVsock`](Self::KvmUserspaceVsock) when
+    /// `/dev/vhost-vsock` cannot be opened. Like the vhost-net fallback,
+    /// the choice is logged, not an error.
+    #[cfg(target_os = "linux")]
+    pub fn detect() -> Result<Self> {
+        if let Err(e) = open_rw("/dev/kvm") {
+            let hint = if is_wsl() {
+                "; under WSL2, set nestedVirtualization=true in .wslconfig and \
+                 make sure your user can open /dev/kvm (e.g. add it to the kvm group)"
+            } else {
+                "; make sure KVM is enabled and your user can open /dev/kvm"
+            };
+            return Err(crate::Error::Backend(format!(
+                "/dev/kvm is not usable: {e}{hint}"
+            )));
+        }
+        match open_rw("/dev/vhost-vsock") {
+            Ok(()) => Ok(Self::Kvm),
+            Err(e) => {
+                tracing::debug!(
+                    "/dev/vhost-vsock unavailable ({}); using the userspace vsock device{}",
+                    e,
+                    if is_wsl() { " (WSL2)" } else { "" }
+                );
+                Ok(Self::KvmUserspaceVsock)
+            }
+        }
+    }
+
+    /// Pick the strategy this host supports: always
+    /// [`Vz`](Self::Vz) on macOS.
+    #[cfg(target_os = "macos")]
+    pub fn detect() -> Result<Self> {
+        Ok(Self::Vz)
+    }
+
+    /// A new backend using this strategy.
+    #[cfg(target_os = "linux")]
+    pub fn create(self) -> Result<Box<dyn VmmBackend>> {
+        match self {
+            Self::Kvm => Ok(Box::new(kvm::KvmBackend::new())),
+            Self::KvmUserspaceVsock => Ok(Box::new(kvm::KvmBackend::new().with_userspace_vsock())),
+            Self::Vz => Err(crate::Error::Backend(
+                "Virtualization.framework is only available on macOS".into(),
+            )),
+        }
+    }
+
+    /// A new backend using this strategy.
+    #[cfg(target_os = "macos")]
+    pub fn create(self) -> Result<Box<dyn VmmBackend>> {
+        match self {
+            Self::Vz => Ok(Box::new(vz::VzBackend::new())),
+            Self::Kvm | Self::KvmUserspaceVsock => Err(crate::Error::Backend(
+                "KVM is only available on Linux".into(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether this is a WSL2 (or WSL1) Linux, whose kernel reports a
+/// Microsoft build.
+#[cfg(target_os = "linux")]
+pub fn is_wsl() -> bool {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .is_ok_and(|release| release.to_ascii_lowercase().contains("microsoft"))
+}
+
 #[cfg(target_os = "linux")]
-pub fn create_backend() -> Box<dyn VmmBackend> {
-    Box::new(kvm::KvmBackend::new())
+fn open_rw(path: &str) -> std::io::Result<()> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map(drop)
 }
 
-/// Create the platform-appropriate backend.
+/// Create the platform-appropriate backend, as picked by
+/// [`Backend::detect`].
 ///
-/// On macOS, returns a `VzBackend`.
-#[cfg(target_os = "macos")]
-pub fn create_backend() -> Box<dyn VmmBackend> {
-    Box::new(vz::VzBackend::new())
+/// On Linux this is a [`KvmBackend`](kvm::KvmBackend); on a host without
+/// usable KVM it fails with `detect`'s error, which under WSL2 explains how
+/// to enable nested virtualization. On macOS it is a `VzBackend`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn create_backend() -> Result<Box<dyn VmmBackend>> {
+    Backend::detect().and_then(Backend::create)
 }
 
 #[cfg(test)]
@@ -539,12 +1306,27 @@ mod tests {
             session_secret: SessionSecret::new([0xAB; 32]),
             command_allowlist: vec!["sh".to_string()],
             network_deny_list: Vec::new(),
+            network_policy: None,
             max_connections_per_second: 0,
             max_concurrent_connections: 0,
             seccomp: false,
+            write_roots: Vec::new(),
         }
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn detect_falls_back_without_vhost_vsock() {
+        let Ok(backend) = Backend::detect() else {
+            eprintln!("skipping: /dev/kvm is not usable");
+            return;
+        };
+        let vhost_vsock = open_rw("/dev/vhost-vsock").is_ok();
+        assert_eq!(backend == Backend::Kvm, vhost_vsock);
+        assert_eq!(backend == Backend::KvmUserspaceVsock, !vhost_vsock);
+        assert!(Backend::Vz.create().is_err());
+    }
+
     /// `format!("{:?}", BackendSecurityConfig)` must not contain the secret in
     /// any plausible textual form: the raw `0xAB` byte literals (the way
     /// `[u8; 32]` derives `Debug`), the lowercase hex (`abab...`, the form the
@@ -589,15 +1371,25 @@ mod tests {
             network: false,
             enable_vsock: true,
             guest_console: GuestConsoleSink::Disabled,
+            console_shell: false,
+            boot_profile: BootProfile::Standard,
             shared_dir: None,
             mounts: Vec::new(),
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
             env: Vec::new(),
+            dns: DnsConfig::default(),
+            network_mode: Default::default(),
             security,
             snapshot: None,
             enable_snapshots: false,
+            resource_policy: ResourcePolicy::default(),
+            protocol_tap: None,
+            watchdog: None,
+            extra_cmdline: Vec::new(),
+            rosetta: false,
         };
         let rendered = format!("{:?}", config);
         let secret_lower_hex = "ab".repeat(32);
@@ -616,4 +1408,65 @@ mod tests {
             "BackendConfig Debug leaked byte-array form of secret: {rendered}"
         );
     }
+
+    #[test]
+    fn config_features_a_backend_lacks_are_unsupported() {
+        let mut config = BackendConfig::minimal("/tmp/vmlinuz", 256, 1);
+        assert!(config.required_caps().is_empty());
+
+        config.disks.push(DiskConfig {
+            path: "/tmp/data.img".into(),
+            read_only: false,
+            guest_path: None,
+        });
+        config.rosetta = true;
+        let required = config.required_caps();
+        assert_eq!(required.names(), ["data disks", "Rosetta"]);
+
+        let kvm = Backend::Kvm.capabilities();
+        assert!(kvm.contains(BackendCaps::SNAPSHOTS | BackendCaps::DATA_DISKS));
+        assert!(!kvm.contains(BackendCaps::SUSPEND));
+        assert_eq!(kvm.missing(required), BackendCaps::ROSETTA);
+
+        let err = kvm.require(required, Backend::Kvm).unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Unsupported {
+                feature: BackendCaps::ROSETTA,
+                backend: Backend::Kvm,
+            }
+        ));
+        assert_eq!(err.to_string(), "kvm backend does not support Rosetta");
+        kvm.require(BackendCaps::DATA_DISKS, Backend::Kvm).unwrap();
+    }
+
+    #[test]
+    fn dns_config_parses_and_reaches_guest_cmdline() {
+        let dns = DnsConfig::parse(
+            &["1.1.1.1".to_string(), "9.9.9.9".to_string()],
+            &[("API.internal.".to_string(), "10.0.2.100".to_string())],
+        )
+        .unwrap();
+        assert_eq!(
+            dns.host_aliases,
+            vec![("api.internal".to_string(), Ipv4Addr::new(10, 0, 2, 100))]
+        );
+        assert!(DnsConfig::parse(&["dns.google".to_string()], &[]).is_err());
+
+        let mut cmdline = Vec::new();
+        append_common_guest_kernel_args(
+            &mut cmdline,
+            &[0; 32],
+            0,
+            true,
+            true,
+            &dns.servers,
+            &[],
+            None,
+            None,
+            &["/opt".to_string(), "/srv/app".to_string()],
+        );
+        assert!(cmdline.contains(&"voidbox.dns=1.1.1.1,9.9.9.9".to_string()));
+        assert!(cmdline.contains(&"voidbox.write_roots=/opt,/srv/app".to_string()));
+    }
 }
@@ -195,11 +195,7 @@ impl MultiplexChannel {
             pending.slots.insert(request_id, Dispatch::Oneshot(tx));
         }
 
-        let frame = build_frame(msg_type, request_id, &body);
-        if let Err(e) = self.inner.writer.send(&frame) {
-            let _ = self.remove_slot(request_id);
-            return Err(e);
-        }
+        self.send(msg_type, request_id, &body)?;
 
         match rx.await {
             Ok(msg) => Ok(msg),
@@ -257,11 +253,7 @@ impl MultiplexChannel {
             );
         }
 
-        let frame = build_frame(msg_type, request_id, &body);
-        if let Err(e) = self.inner.writer.send(&frame) {
-            let _ = self.remove_slot(request_id);
-            return Err(e);
-        }
+        self.send(msg_type, request_id, &body)?;
 
         // For ChannelLifetime streams there is no terminal; hand the
         // chunks receiver back directly. No forwarder task needed.
@@ -333,6 +325,22 @@ impl MultiplexChannel {
         }
     }
 
+    /// Writes the request frame, logging its `request_id` so it can be
+    /// matched with the `dispatch` line of its response. A failed write
+    /// frees the slot registered for it.
+    fn send(&self, msg_type: MessageType, request_id: u32, body: &[u8]) -> Result<()> {
+        debug!(
+            "multiplex: send msg_type={msg_type:?} request_id={request_id} body_len={}",
+            body.len()
+        );
+        let frame = build_frame(msg_type, request_id, body);
+        if let Err(e) = self.inner.writer.send(&frame) {
+            let _ = self.remove_slot(request_id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
     fn remove_slot(&self, request_id: u32) -> Result<()> {
         let mut pending = self.lock_pending()?;
         pending.slots.remove(&request_id);
@@ -0,0 +1,320 @@
+//! Egress policy for guest network connections.
+//!
+//! A [`NetworkPolicy`] decides whether the guest may open an outbound TCP
+//! connection or UDP flow to a destination. Rules match by CIDR or DNS name
+//! and can be restricted to ports. Deny rules win over allow rules; a
+//! destination no rule matches gets the policy's default: allowed for a
+//! deny-list policy ([`NetworkPolicy::allow_all`]), refused for an
+//! allowlist ([`NetworkPolicy::deny_all`]).
+//!
+//! DNS-name rules match the names the guest resolved through the SLIRP DNS
+//! server: the network stack remembers which name each answered address
+//! came from and checks those names when the guest connects. A guest that
+//! resolves names elsewhere (its own DoH client, a hard-coded IP) only
+//! matches CIDR rules.
+//!
+//! A [`log_only`](NetworkPolicy::log_only) policy never refuses anything;
+//! would-be denials are logged and flagged on the connection's
+//! [`ConnectionRecord`](crate::observe::network::ConnectionRecord), which
+//! makes it safe to audit a new policy against a real workload first.
+//!
+//! Enforced by the KVM backend's SLIRP stack, on top of the CIDR
+//! `network_deny_list`. Connections to the SLIRP gateway (the host) are
+//! exempt, so host-side proxies stay reachable. VZ ignores the policy.
+
+use std::net::Ipv4Addr;
+
+use crate::{Error, Result};
+
+/// What a policy does with a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+}
+
+/// What a [`NetworkRule`] matches on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleTarget {
+    /// An IPv4 network; a single address is a `/32`.
+    Cidr { network: Ipv4Addr, prefix_len: u8 },
+    /// A DNS name. `*.example.com` matches any subdomain of `example.com`
+    /// but not `example.com` itself.
+    Domain(String),
+}
+
+/// One allow or deny rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkRule {
+    target: RuleTarget,
+    /// Destination ports the rule applies to; empty means any port.
+    ports: Vec<u16>,
+}
+
+impl NetworkRule {
+    /// Match an IPv4 network such as `10.0.0.0/8`, or a single address.
+    pub fn cidr(cidr: &str) -> Result<Self> {
+        let (addr, prefix_len) = match cidr.split_once('/') {
+            Some((addr, len)) => (
+                addr,
+                len.parse::<u8>()
+                    .ok()
+                    .filter(|len| *len <= 32)
+                    .ok_or_else(|| Error::Config(format!("invalid CIDR prefix in '{cidr}'")))?,
+            ),
+            None => (cidr, 32),
+        };
+        let addr: Ipv4Addr = addr
+            .parse()
+            .map_err(|e| Error::Config(format!("invalid CIDR '{cidr}': {e}")))?;
+        Ok(Self::network(addr, prefix_len))
+    }
+
+    /// Match a DNS name, or its subdomains with a leading `*.`.
+    pub fn domain(name: impl Into<String>) -> Self {
+        let name = name.into().trim_end_matches('.').to_ascii_lowercase();
+        Self {
+            target: RuleTarget::Domain(name),
+            ports: Vec::new(),
+        }
+    }
+
+    /// Parse a CIDR, an IPv4 address, or otherwise a DNS name.
+    pub fn parse(target: &str) -> Result<Self> {
+        let looks_numeric = target
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == '/');
+        if looks_numeric {
+            Self::cidr(target)
+        } else if target.is_empty() {
+            Err(Error::Config("empty network rule".into()))
+        } else {
+            Ok(Self::domain(target))
+        }
+    }
+
+    /// Restrict the rule to these destination ports.
+    pub fn ports(mut self, ports: impl IntoIterator<Item = u16>) -> Self {
+        self.ports.extend(ports);
+        self
+    }
+
+    pub fn target(&self) -> &RuleTarget {
+        &self.target
+    }
+
+    fn network(addr: Ipv4Addr, prefix_len: u8) -> Self {
+        let network = Ipv4Addr::from(u32::from(addr) & prefix_mask(prefix_len));
+        Self {
+            target: RuleTarget::Cidr {
+                network,
+                prefix_len,
+            },
+            ports: Vec::new(),
+        }
+    }
+
+    /// Whether a connection to `ip:port`, known to the guest by `names`,
+    /// falls under this rule.
+    pub fn matches(&self, ip: Ipv4Addr, port: u16, names: &[String]) -> bool {
+        if !self.ports.is_empty() && !self.ports.contains(&port) {
+            return false;
+        }
+        match &self.target {
+            RuleTarget::Cidr {
+                network,
+                prefix_len,
+            } => u32::from(ip) & prefix_mask(*prefix_len) == u32::from(*network),
+            RuleTarget::Domain(pattern) => names.iter().any(|name| domain_matches(pattern, name)),
+        }
+    }
+}
+
+impl From<Ipv4Addr> for NetworkRule {
+    fn from(addr: Ipv4Addr) -> Self {
+        Self::network(addr, 32)
+    }
+}
+
+fn prefix_mask(prefix_len: u8) -> u32 {
+    u32::MAX
+        .checked_shl(32 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+fn domain_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(parent) => name
+            .strip_suffix(parent)
+            .is_some_and(|prefix| prefix.ends_with('.')),
+        None => pattern == name,
+    }
+}
+
+/// Egress rules for a sandbox. See the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkPolicy {
+    default_action: PolicyAction,
+    allow: Vec<NetworkRule>,
+    deny: Vec<NetworkRule>,
+    log_only: bool,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl NetworkPolicy {
+    /// Deny-list mode: everything not matched by a [`deny`](Self::deny)
+    /// rule is allowed.
+    pub fn allow_all() -> Self {
+        Self {
+            default_action: PolicyAction::Allow,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            log_only: false,
+        }
+    }
+
+    /// Allowlist mode: only destinations matched by an
+    /// [`allow`](Self::allow) rule are reachable.
+    pub fn deny_all() -> Self {
+        Self {
+            default_action: PolicyAction::Deny,
+            ..Self::allow_all()
+        }
+    }
+
+    /// Add an allow rule.
+    pub fn allow(mut self, rule: NetworkRule) -> Self {
+        self.allow.push(rule);
+        self
+    }
+
+    /// Add a deny rule; deny rules take precedence over allow rules.
+    pub fn deny(mut self, rule: NetworkRule) -> Self {
+        self.deny.push(rule);
+        self
+    }
+
+    /// Audit mode: report would-be denials without refusing connections.
+    pub fn log_only(mut self) -> Self {
+        self.log_only = true;
+        self
+    }
+
+    pub fn is_log_only(&self) -> bool {
+        self.log_only
+    }
+
+    pub fn default_action(&self) -> PolicyAction {
+        self.default_action
+    }
+
+    /// Whether any rule matches by DNS name, so the network stack needs to
+    /// track which names the guest resolved.
+    pub fn has_domain_rules(&self) -> bool {
+        self.allow
+            .iter()
+            .chain(&self.deny)
+            .any(|rule| matches!(rule.target, RuleTarget::Domain(_)))
+    }
+
+    /// Decide a connection to `ip:port` that the guest resolved from
+    /// `names` (empty when it connected by address).
+    pub fn evaluate(&self, ip: Ipv4Addr, port: u16, names: &[String]) -> PolicyAction {
+        if self.deny.iter().any(|rule| rule.matches(ip, port, names)) {
+            PolicyAction::Deny
+        } else if self.allow.iter().any(|rule| rule.matches(ip, port, names)) {
+            PolicyAction::Allow
+        } else {
+            self.default_action
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rule_parse_and_match() {
+        let net = NetworkRule::parse("10.1.0.0/16").unwrap();
+        assert!(net.matches(Ipv4Addr::new(10, 1, 200, 3), 22, &[]));
+        assert!(!net.matches(Ipv4Addr::new(10, 2, 0, 1), 22, &[]));
+        assert_eq!(
+            NetworkRule::parse("10.1.2.3/16").unwrap(),
+            NetworkRule::parse("10.1.0.0/16").unwrap()
+        );
+        assert!(NetworkRule::parse("0.0.0.0/0").unwrap().matches(
+            Ipv4Addr::new(8, 8, 8, 8),
+            53,
+            &[]
+        ));
+        assert!(NetworkRule::parse("10.0.0.0/33").is_err());
+        assert!(NetworkRule::parse("300.0.0.1").is_err());
+
+        let wildcard = NetworkRule::parse("*.Example.com.").unwrap().ports([443]);
+        let ip = Ipv4Addr::new(93, 184, 216, 34);
+        assert!(wildcard.matches(ip, 443, &names(&["api.example.com"])));
+        assert!(!wildcard.matches(ip, 80, &names(&["api.example.com"])));
+        assert!(!wildcard.matches(ip, 443, &names(&["example.com"])));
+        assert!(!wildcard.matches(ip, 443, &names(&["badexample.com"])));
+        assert!(!wildcard.matches(ip, 443, &[]));
+    }
+
+    #[test]
+    fn test_allowlist_policy() {
+        let policy = NetworkPolicy::deny_all()
+            .allow(NetworkRule::domain("github.com").ports([443]))
+            .allow(NetworkRule::parse("10.0.0.0/8").unwrap())
+            .deny(NetworkRule::from(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(policy.has_domain_rules());
+
+        let gh = Ipv4Addr::new(140, 82, 112, 3);
+        assert_eq!(
+            policy.evaluate(gh, 443, &names(&["github.com"])),
+            PolicyAction::Allow
+        );
+        assert_eq!(
+            policy.evaluate(gh, 22, &names(&["github.com"])),
+            PolicyAction::Deny
+        );
+        assert_eq!(policy.evaluate(gh, 443, &[]), PolicyAction::Deny);
+        assert_eq!(
+            policy.evaluate(Ipv4Addr::new(10, 9, 9, 9), 5432, &[]),
+            PolicyAction::Allow
+        );
+        assert_eq!(
+            policy.evaluate(Ipv4Addr::new(10, 0, 0, 1), 5432, &[]),
+            PolicyAction::Deny
+        );
+    }
+
+    #[test]
+    fn test_denylist_policy_defaults_to_allow() {
+        let policy = NetworkPolicy::allow_all()
+            .deny(NetworkRule::domain("*.internal.corp"))
+            .log_only();
+        assert!(policy.is_log_only());
+        assert_eq!(
+            policy.evaluate(Ipv4Addr::new(1, 1, 1, 1), 443, &[]),
+            PolicyAction::Allow
+        );
+        assert_eq!(
+            policy.evaluate(
+                Ipv4Addr::new(172, 16, 0, 4),
+                443,
+                &names(&["db.internal.corp"])
+            ),
+            PolicyAction::Deny
+        );
+        assert!(!NetworkPolicy::default().has_domain_rules());
+    }
+}
@@ -0,0 +1,708 @@
+//! Wire capture of host↔guest control-channel frames.
+//!
+//! A [`ProtocolTap`] wraps every connection the [`ControlChannel`] opens to
+//! the guest-agent — AF_VSOCK or the userspace vsock socket on KVM, the
+//! `VZVirtioSocketConnection` fd on macOS — and records each frame that
+//! crosses it in either direction: message type, payload size, timestamp,
+//! `request_id`, and the first bytes of the payload. Frames are kept in an
+//! in-memory ring and, optionally, appended to a capture file as they
+//! complete, so a host killed mid-hang still leaves the trace behind.
+//!
+//! A frame still incomplete when its connection is dropped is recorded with
+//! [`FrameRecord::complete`] unset: that is usually the frame a hang is
+//! stuck on.
+//!
+//! The session secret in the Ping handshake is never captured, but other
+//! payloads are recorded as sent, including exec environments and file
+//! contents; use [`ProtocolTap::max_payload_bytes`]`(0)` to keep metadata
+//! only. A sandbox configured with secrets does that on its own.
+//!
+//! [`ControlChannel`]: super::control_channel::ControlChannel
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use void_box_protocol::{MessageType, HEADER_SIZE};
+
+use super::control_channel::{GuestConnector, GuestStream};
+use crate::{Error, Result};
+
+/// Frames kept in memory by default.
+pub const DEFAULT_TAP_CAPACITY: usize = 1024;
+
+/// Payload bytes kept per frame by default.
+pub const DEFAULT_TAP_PAYLOAD_BYTES: usize = 256;
+
+/// First bytes of every capture file.
+const CAPTURE_MAGIC: &[u8; 8] = b"VBTAP\0\0\x01";
+
+/// Bytes of a Ping payload that hold the session secret.
+const PING_SECRET_LEN: usize = 32;
+
+const FLAG_COMPLETE: u8 = 1;
+const FLAG_REQUEST_ID: u8 = 2;
+
+/// Which way a frame travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    HostToGuest,
+    GuestToHost,
+}
+
+impl fmt::Display for FrameDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::HostToGuest => "host->guest",
+            Self::GuestToHost => "guest->host",
+        })
+    }
+}
+
+/// One frame seen on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRecord {
+    /// When the frame's first byte crossed the tap.
+    pub timestamp: SystemTime,
+    /// Which guest connection carried it, numbered from 1 in the order the
+    /// tap saw them.
+    pub connection: u64,
+    pub direction: FrameDirection,
+    /// Type byte from the header; see [`message_type`](Self::message_type).
+    pub msg_type: u8,
+    /// Payload length from the header, including the `request_id` prefix.
+    pub size: usize,
+    /// Multiplex `request_id`; `None` for the Ping/Pong handshake.
+    pub request_id: Option<u32>,
+    /// The payload after the `request_id`, cut to the tap's
+    /// [`max_payload_bytes`](ProtocolTap::max_payload_bytes). A Ping
+    /// starts after its session secret.
+    pub payload: Vec<u8>,
+    /// Unset when the connection closed before the whole frame crossed.
+    pub complete: bool,
+}
+
+impl FrameRecord {
+    /// The decoded message type, if the byte is one this host knows.
+    pub fn message_type(&self) -> Option<MessageType> {
+        MessageType::try_from(self.msg_type).ok()
+    }
+}
+
+impl fmt::Display for FrameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}.{:06} #{} {} ",
+            since_epoch.as_secs(),
+            since_epoch.subsec_micros(),
+            self.connection,
+            self.direction
+        )?;
+        match self.message_type() {
+            Some(msg_type) => write!(f, "{msg_type:?}")?,
+            None => write!(f, "type {}", self.msg_type)?,
+        }
+        if let Some(request_id) = self.request_id {
+            write!(f, " id={request_id}")?;
+        }
+        write!(f, " {} B", self.size)?;
+        if !self.complete {
+            f.write_str(" (incomplete)")?;
+        }
+        if !self.payload.is_empty() {
+            write!(
+                f,
+                " {}",
+                String::from_utf8_lossy(&self.payload).escape_debug()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Records control-channel frames; see the [module docs](self).
+///
+/// Clones share one trace.
+///
+/// # Examples
+///
+/// ```no_run
+/// use void_box::backend::ProtocolTap;
+/// use void_box::sandbox::Sandbox;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox = Sandbox::local()
+///     .from_env()?
+///     .protocol_tap(ProtocolTap::new().capture_to("/tmp/voidbox.tap")?)
+///     .build()?;
+/// sandbox.exec("echo", &["hi"]).await?;
+/// for frame in sandbox.protocol_trace() {
+///     println!("{frame}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ProtocolTap {
+    capacity: usize,
+    max_payload_bytes: usize,
+    state: Arc<Mutex<TapState>>,
+    connections: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct TapState {
+    frames: VecDeque<FrameRecord>,
+    evicted: u64,
+    capture: Option<File>,
+}
+
+impl ProtocolTap {
+    /// A tap keeping the last [`DEFAULT_TAP_CAPACITY`] frames in memory.
+    pub fn new() -> Self {
+        Self {
+            capacity: DEFAULT_TAP_CAPACITY,
+            max_payload_bytes: DEFAULT_TAP_PAYLOAD_BYTES,
+            state: Arc::default(),
+            connections: Arc::default(),
+        }
+    }
+
+    /// Keep the last `frames` frames in memory; older ones are evicted.
+    pub fn capacity(mut self, frames: usize) -> Self {
+        self.capacity = frames;
+        self
+    }
+
+    /// Keep at most `bytes` of each payload.
+    pub fn max_payload_bytes(mut self, bytes: usize) -> Self {
+        self.max_payload_bytes = bytes;
+        self
+    }
+
+    /// Also append every frame to a capture file at `path`, truncating it;
+    /// read it back with [`read_capture`](Self::read_capture).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file cannot be created.
+    pub fn capture_to(self, path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(CAPTURE_MAGIC)?;
+        self.state.lock().unwrap().capture = Some(file);
+        Ok(self)
+    }
+
+    /// The frames in memory, oldest first.
+    pub fn frames(&self) -> Vec<FrameRecord> {
+        self.state.lock().unwrap().frames.iter().cloned().collect()
+    }
+
+    /// Frames evicted from memory to stay within the capacity.
+    pub fn evicted(&self) -> u64 {
+        self.state.lock().unwrap().evicted
+    }
+
+    /// Forget the frames in memory. A capture file keeps them.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.frames.clear();
+        state.evicted = 0;
+    }
+
+    /// Parse a file written by [`capture_to`](Self::capture_to). A record
+    /// cut off by a crash mid-write is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file cannot be read and
+    /// [`Error::Config`] if it is not a capture file.
+    pub fn read_capture(path: impl AsRef<Path>) -> Result<Vec<FrameRecord>> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let Some(mut rest) = data.strip_prefix(CAPTURE_MAGIC) else {
+            return Err(Error::Config(format!(
+                "{} is not a protocol capture",
+                path.display()
+            )));
+        };
+        let mut frames = Vec::new();
+        while let Some((frame, next)) = decode_record(rest) {
+            frames.push(frame);
+            rest = next;
+        }
+        Ok(frames)
+    }
+
+    /// `connector` with every stream it opens recorded by this tap.
+    pub(crate) fn wrap(&self, connector: GuestConnector) -> GuestConnector {
+        let tap = self.clone();
+        Arc::new(move || {
+            let inner = connector()?;
+            let connection = tap.connections.fetch_add(1, Ordering::Relaxed) + 1;
+            Ok(Box::new(TappedStream::new(inner, tap.clone(), connection)) as Box<dyn GuestStream>)
+        })
+    }
+
+    fn record(&self, frame: FrameRecord) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(file) = &mut state.capture {
+            // One write per record, unbuffered, so the file is current
+            // even if the host is killed during the hang being debugged.
+            if let Err(e) = file.write_all(&encode_record(&frame)) {
+                tracing::warn!("protocol tap: capture write failed, stopping capture: {e}");
+                state.capture = None;
+            }
+        }
+        if self.capacity == 0 {
+            state.evicted += 1;
+            return;
+        }
+        while state.frames.len() >= self.capacity {
+            state.frames.pop_front();
+            state.evicted += 1;
+        }
+        state.frames.push_back(frame);
+    }
+}
+
+impl Default for ProtocolTap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ProtocolTap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("ProtocolTap")
+            .field("capacity", &self.capacity)
+            .field("max_payload_bytes", &self.max_payload_bytes)
+            .field("frames", &state.frames.len())
+            .field("capturing", &state.capture.is_some())
+            .finish()
+    }
+}
+
+fn encode_record(frame: &FrameRecord) -> Vec<u8> {
+    let micros = frame
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let mut flags = 0;
+    if frame.complete {
+        flags |= FLAG_COMPLETE;
+    }
+    if frame.request_id.is_some() {
+        flags |= FLAG_REQUEST_ID;
+    }
+    let mut buf = Vec::with_capacity(31 + frame.payload.len());
+    buf.extend_from_slice(&micros.to_le_bytes());
+    buf.extend_from_slice(&frame.connection.to_le_bytes());
+    buf.push(match frame.direction {
+        FrameDirection::HostToGuest => 0,
+        FrameDirection::GuestToHost => 1,
+    });
+    buf.push(frame.msg_type);
+    buf.push(flags);
+    buf.extend_from_slice(&frame.request_id.unwrap_or(0).to_le_bytes());
+    buf.extend_from_slice(&(frame.size as u32).to_le_bytes());
+    buf.extend_from_slice(&(frame.payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&frame.payload);
+    buf
+}
+
+fn decode_record(data: &[u8]) -> Option<(FrameRecord, &[u8])> {
+    fn take<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+        let (head, rest) = data.split_first_chunk::<N>()?;
+        *data = rest;
+        Some(*head)
+    }
+
+    let mut data = data;
+    let micros = u64::from_le_bytes(take(&mut data)?);
+    let connection = u64::from_le_bytes(take(&mut data)?);
+    let [direction, msg_type, flags] = take(&mut data)?;
+    let request_id = u32::from_le_bytes(take(&mut data)?);
+    let size = u32::from_le_bytes(take(&mut data)?) as usize;
+    let captured = u32::from_le_bytes(take(&mut data)?) as usize;
+    if data.len() < captured {
+        return None;
+    }
+    let (payload, rest) = data.split_at(captured);
+    let frame = FrameRecord {
+        timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+        connection,
+        direction: if direction == 0 {
+            FrameDirection::HostToGuest
+        } else {
+            FrameDirection::GuestToHost
+        },
+        msg_type,
+        size,
+        request_id: (flags & FLAG_REQUEST_ID != 0).then_some(request_id),
+        payload: payload.to_vec(),
+        complete: flags & FLAG_COMPLETE != 0,
+    };
+    Some((frame, rest))
+}
+
+/// Reassembles frames from the bytes one direction of a stream carries.
+#[derive(Default)]
+struct FrameParser {
+    header: [u8; HEADER_SIZE],
+    header_len: usize,
+    started: Option<SystemTime>,
+    /// Payload bytes still to come.
+    remaining: usize,
+    /// Leading payload bytes that are not recorded: the `request_id` or
+    /// the Ping secret.
+    skip: usize,
+    /// Leading payload bytes seen, up to `skip` plus the payload limit.
+    captured: Vec<u8>,
+}
+
+impl FrameParser {
+    fn feed(
+        &mut self,
+        mut data: &[u8],
+        max_payload_bytes: usize,
+        mut emit: impl FnMut(FrameRecord),
+    ) {
+        while !data.is_empty() {
+            if self.header_len < HEADER_SIZE {
+                self.started.get_or_insert_with(SystemTime::now);
+                let n = (HEADER_SIZE - self.header_len).min(data.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                self.header_len += n;
+                data = &data[n..];
+                if self.header_len < HEADER_SIZE {
+                    return;
+                }
+                self.remaining = self.size();
+                self.skip = match MessageType::try_from(self.header[4]) {
+                    Ok(MessageType::Ping) => PING_SECRET_LEN,
+                    Ok(MessageType::Pong) => 0,
+                    _ => 4,
+                };
+            } else {
+                let n = self.remaining.min(data.len());
+                let keep = (self.skip + max_payload_bytes).saturating_sub(self.captured.len());
+                self.captured.extend_from_slice(&data[..n.min(keep)]);
+                self.remaining -= n;
+                data = &data[n..];
+            }
+            if self.remaining == 0 {
+                emit(self.finish(true, max_payload_bytes));
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        u32::from_le_bytes([
+            self.header[0],
+            self.header[1],
+            self.header[2],
+            self.header[3],
+        ]) as usize
+    }
+
+    /// The frame in progress, if any bytes of one have crossed.
+    fn take_partial(&mut self, max_payload_bytes: usize) -> Option<FrameRecord> {
+        (self.header_len > 0).then(|| self.finish(false, max_payload_bytes))
+    }
+
+    fn finish(&mut self, complete: bool, max_payload_bytes: usize) -> FrameRecord {
+        let parser = std::mem::take(self);
+        let header_complete = parser.header_len == HEADER_SIZE;
+        let request_id = (header_complete && parser.skip == 4 && parser.captured.len() >= 4)
+            .then(|| u32::from_le_bytes(parser.captured[..4].try_into().unwrap()));
+        let size = if header_complete { parser.size() } else { 0 };
+        let mut payload = parser.captured;
+        payload.drain(..parser.skip.min(payload.len()));
+        payload.truncate(max_payload_bytes);
+        FrameRecord {
+            timestamp: parser.started.unwrap_or_else(SystemTime::now),
+            connection: 0,
+            direction: FrameDirection::HostToGuest,
+            msg_type: if header_complete { parser.header[4] } else { 0 },
+            size,
+            request_id,
+            payload,
+            complete,
+        }
+    }
+}
+
+/// A [`GuestStream`] that reports the frames it carries to a tap.
+struct TappedStream {
+    inner: Box<dyn GuestStream>,
+    tap: ProtocolTap,
+    connection: u64,
+    reads: FrameParser,
+    writes: FrameParser,
+}
+
+impl TappedStream {
+    fn new(inner: Box<dyn GuestStream>, tap: ProtocolTap, connection: u64) -> Self {
+        Self {
+            inner,
+            tap,
+            connection,
+            reads: FrameParser::default(),
+            writes: FrameParser::default(),
+        }
+    }
+
+    fn emit(
+        tap: &ProtocolTap,
+        connection: u64,
+        direction: FrameDirection,
+    ) -> impl FnMut(FrameRecord) + '_ {
+        move |mut frame| {
+            frame.connection = connection;
+            frame.direction = direction;
+            tap.record(frame);
+        }
+    }
+}
+
+impl Read for TappedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.reads.feed(
+            &buf[..n],
+            self.tap.max_payload_bytes,
+            Self::emit(&self.tap, self.connection, FrameDirection::GuestToHost),
+        );
+        Ok(n)
+    }
+}
+
+impl Write for TappedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.writes.feed(
+            &buf[..n],
+            self.tap.max_payload_bytes,
+            Self::emit(&self.tap, self.connection, FrameDirection::HostToGuest),
+        );
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl GuestStream for TappedStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+
+    fn try_clone_box(&self) -> io::Result<Box<dyn GuestStream>> {
+        // Each half parses only what it carries itself.
+        Ok(Box::new(TappedStream::new(
+            self.inner.try_clone_box()?,
+            self.tap.clone(),
+            self.connection,
+        )))
+    }
+}
+
+impl Drop for TappedStream {
+    fn drop(&mut self) {
+        let max = self.tap.max_payload_bytes;
+        for (parser, direction) in [
+            (&mut self.reads, FrameDirection::GuestToHost),
+            (&mut self.writes, FrameDirection::HostToGuest),
+        ] {
+            if let Some(frame) = parser.take_partial(max) {
+                Self::emit(&self.tap, self.connection, direction)(frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use void_box_protocol::Message;
+
+    /// An in-memory guest that answers with canned bytes.
+    struct FakeStream {
+        incoming: io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            // Dribble bytes out so frames straddle reads.
+            let len = buf.len().min(3);
+            self.incoming.read(&mut buf[..len])
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len().min(7))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl GuestStream for FakeStream {
+        fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn as_raw_fd(&self) -> RawFd {
+            -1
+        }
+
+        fn try_clone_box(&self) -> io::Result<Box<dyn GuestStream>> {
+            Ok(Box::new(FakeStream {
+                incoming: io::Cursor::new(Vec::new()),
+            }))
+        }
+    }
+
+    fn mux_frame(msg_type: MessageType, request_id: u32, body: &[u8]) -> Vec<u8> {
+        let mut payload = request_id.to_le_bytes().to_vec();
+        payload.extend_from_slice(body);
+        Message { msg_type, payload }.serialize()
+    }
+
+    #[test]
+    fn frames_are_recorded_in_both_directions() {
+        let tap = ProtocolTap::new().max_payload_bytes(4);
+        let mut incoming = Message {
+            msg_type: MessageType::Pong,
+            payload: vec![2, 0, 0, 0, 1],
+        }
+        .serialize();
+        incoming.extend(mux_frame(
+            MessageType::ExecResponse,
+            9,
+            b"{\"exit_code\":0}",
+        ));
+        // The guest dies halfway through a third frame.
+        incoming.extend(&mux_frame(MessageType::ExecOutputChunk, 9, b"partial")[..10]);
+        let connector: GuestConnector = Arc::new(move || {
+            Ok(Box::new(FakeStream {
+                incoming: io::Cursor::new(incoming.clone()),
+            }) as Box<dyn GuestStream>)
+        });
+
+        let mut stream = tap.wrap(connector)().unwrap();
+        let ping = Message {
+            msg_type: MessageType::Ping,
+            payload: void_box_protocol::build_ping_payload(&[0xAA; 32], 1),
+        };
+        stream.write_all(&ping.serialize()).unwrap();
+        stream
+            .write_all(&mux_frame(
+                MessageType::ExecRequest,
+                9,
+                b"{\"program\":\"echo\"}",
+            ))
+            .unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        drop(stream);
+
+        let frames = tap.frames();
+        let summary: Vec<_> = frames
+            .iter()
+            .map(|f| (f.direction, f.message_type(), f.request_id, f.complete))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    FrameDirection::HostToGuest,
+                    Some(MessageType::Ping),
+                    None,
+                    true
+                ),
+                (
+                    FrameDirection::HostToGuest,
+                    Some(MessageType::ExecRequest),
+                    Some(9),
+                    true
+                ),
+                (
+                    FrameDirection::GuestToHost,
+                    Some(MessageType::Pong),
+                    None,
+                    true
+                ),
+                (
+                    FrameDirection::GuestToHost,
+                    Some(MessageType::ExecResponse),
+                    Some(9),
+                    true
+                ),
+                (
+                    FrameDirection::GuestToHost,
+                    Some(MessageType::ExecOutputChunk),
+                    Some(9),
+                    false
+                ),
+            ]
+        );
+        // The secret never reaches the trace; payloads are cut to the limit.
+        assert!(!frames[0].payload.contains(&0xAA));
+        assert_eq!(frames[1].payload, b"{\"pr");
+        assert_eq!(frames[3].size, 4 + 15);
+        assert!(frames.iter().all(|f| f.connection == 1));
+        assert!(frames[1].to_string().contains("ExecRequest id=9 22 B"));
+    }
+
+    #[test]
+    fn ring_evicts_and_capture_file_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.tap");
+        let tap = ProtocolTap::new().capacity(2).capture_to(&path).unwrap();
+        let connector: GuestConnector = Arc::new(|| {
+            Ok(Box::new(FakeStream {
+                incoming: io::Cursor::new(Vec::new()),
+            }) as Box<dyn GuestStream>)
+        });
+        let mut stream = tap.wrap(connector)().unwrap();
+        for id in 1..=3 {
+            stream
+                .write_all(&mux_frame(MessageType::FileStat, id, b"{}"))
+                .unwrap();
+        }
+
+        let kept: Vec<_> = tap.frames().iter().map(|f| f.request_id).collect();
+        assert_eq!(kept, [Some(2), Some(3)]);
+        assert_eq!(tap.evicted(), 1);
+
+        let captured = ProtocolTap::read_capture(&path).unwrap();
+        let ids: Vec<_> = captured.iter().map(|f| f.request_id).collect();
+        assert_eq!(ids, [Some(1), Some(2), Some(3)]);
+        assert!(captured.iter().all(|f| f.complete && f.payload == b"{}"));
+
+        std::fs::write(&path, b"not a capture").unwrap();
+        assert!(ProtocolTap::read_capture(&path).is_err());
+    }
+}
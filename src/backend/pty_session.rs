@@ -289,54 +289,206 @@ impl PtySession {
                     continue;
                 };
 
-                match incoming_msg.msg_type {
-                    MessageType::PtyData => {
-                        let _ = stdout.write_all(body);
+                match pty_event(incoming_msg.msg_type, body) {
+                    Some(PtyEvent::Data(data)) => {
+                        let _ = stdout.write_all(&data);
                         let _ = stdout.flush();
                     }
-                    MessageType::PtyClosed => {
-                        let closed_response: PtyClosedResponse = serde_json::from_slice(body)
-                            .unwrap_or(PtyClosedResponse { exit_code: -1 });
-                        debug!(
-                            "pty_session: finished with exit_code={}",
-                            closed_response.exit_code
-                        );
-                        return Ok(closed_response.exit_code);
-                    }
-                    MessageType::ExecRequest
-                    | MessageType::ExecResponse
-                    | MessageType::Ping
-                    | MessageType::Pong
-                    | MessageType::Shutdown
-                    | MessageType::FileTransfer
-                    | MessageType::FileTransferResponse
-                    | MessageType::TelemetryData
-                    | MessageType::TelemetryAck
-                    | MessageType::SubscribeTelemetry
-                    | MessageType::WriteFile
-                    | MessageType::WriteFileResponse
-                    | MessageType::MkdirP
-                    | MessageType::MkdirPResponse
-                    | MessageType::ExecOutputChunk
-                    | MessageType::ExecOutputAck
-                    | MessageType::SnapshotReady
-                    | MessageType::ReadFile
-                    | MessageType::ReadFileResponse
-                    | MessageType::FileStat
-                    | MessageType::FileStatResponse
-                    | MessageType::PtyOpen
-                    | MessageType::PtyOpened
-                    | MessageType::PtyResize
-                    | MessageType::PtyClose => {
-                        debug!(
-                            "pty_session: ignoring unexpected message {:?}",
-                            incoming_msg.msg_type
-                        );
+                    Some(PtyEvent::Closed(exit_code)) => {
+                        debug!("pty_session: finished with exit_code={}", exit_code);
+                        return Ok(exit_code);
                     }
+                    None => {}
                 }
             }
         }
     }
+
+    /// Splits the session into halves that can be driven from different
+    /// threads, for relaying the PTY somewhere other than the host
+    /// terminal (see [`crate::ssh`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Guest`] if the connection cannot be duplicated.
+    pub fn split(self) -> Result<(PtyReader, PtyWriter)> {
+        let writer = self
+            .stream
+            .try_clone_box()
+            .map_err(|e| Error::Guest(format!("failed to clone PTY stream: {e}")))?;
+        Ok((
+            PtyReader {
+                stream: self.stream,
+            },
+            PtyWriter {
+                stream: writer,
+                closed: false,
+            },
+        ))
+    }
+}
+
+/// Receiving half of a [`PtySession::split`] session.
+pub struct PtyReader {
+    stream: Box<dyn super::control_channel::GuestStream>,
+}
+
+impl PtyReader {
+    /// Blocks until the guest sends PTY output or reports that the process
+    /// exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Guest`] if the connection fails.
+    pub fn next_event(&mut self) -> Result<PtyEvent> {
+        loop {
+            let incoming_msg = Message::read_from_sync(&mut *self.stream)
+                .map_err(|e| Error::Guest(format!("pty read error: {e}")))?;
+            let Some((_id, body)) = decode_payload(&incoming_msg.payload) else {
+                continue;
+            };
+            if let Some(event) = pty_event(incoming_msg.msg_type, body) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// Sending half of a [`PtySession::split`] session. Dropping it closes the
+/// session, which hangs up the guest process.
+pub struct PtyWriter {
+    stream: Box<dyn super::control_channel::GuestStream>,
+    closed: bool,
+}
+
+impl PtyWriter {
+    /// Writes `data` to the PTY, as if typed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Guest`] if the connection fails.
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.send(MessageType::PtyData, data)
+    }
+
+    /// Resizes the guest terminal.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Guest`] if the connection fails.
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        let body = serde_json::to_vec(&PtyResizeRequest { cols, rows })?;
+        self.send(MessageType::PtyResize, &body)
+    }
+
+    /// Closes the session, hanging up the guest process.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Guest`] if the connection fails.
+    pub fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.send(MessageType::PtyClose, &[])
+    }
+
+    fn send(&mut self, msg_type: MessageType, body: &[u8]) -> Result<()> {
+        self.stream
+            .write_all(&build_frame(msg_type, PTY_REQUEST_ID, body))
+            .map_err(|e| Error::Guest(format!("failed to send {msg_type:?}: {e}")))
+    }
+}
+
+impl Drop for PtyWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// What a guest frame on a PTY connection means to the host side.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PtyEvent {
+    /// Output from the PTY.
+    Data(Vec<u8>),
+    /// The PTY process exited with this code.
+    Closed(i32),
+}
+
+/// Classifies a guest frame, or `None` for frames a PTY connection ignores.
+fn pty_event(msg_type: MessageType, body: &[u8]) -> Option<PtyEvent> {
+    match msg_type {
+        MessageType::PtyData => Some(PtyEvent::Data(body.to_vec())),
+        MessageType::PtyClosed => {
+            let closed_response: PtyClosedResponse =
+                serde_json::from_slice(body).unwrap_or(PtyClosedResponse { exit_code: -1 });
+            Some(PtyEvent::Closed(closed_response.exit_code))
+        }
+        MessageType::ExecRequest
+        | MessageType::ExecResponse
+        | MessageType::Ping
+        | MessageType::Pong
+        | MessageType::Shutdown
+        | MessageType::FileTransfer
+        | MessageType::FileTransferResponse
+        | MessageType::TelemetryData
+        | MessageType::TelemetryAck
+        | MessageType::SubscribeTelemetry
+        | MessageType::WriteFile
+        | MessageType::WriteFileResponse
+        | MessageType::WriteFileChunk
+        | MessageType::WriteFileChunkResponse
+        | MessageType::WriteFileFinalize
+        | MessageType::MkdirP
+        | MessageType::MkdirPResponse
+        | MessageType::ExecOutputChunk
+        | MessageType::ExecOutputAck
+        | MessageType::SnapshotReady
+        | MessageType::ReadFile
+        | MessageType::ReadFileResponse
+        | MessageType::FileStat
+        | MessageType::FileStatResponse
+        | MessageType::FsDiff
+        | MessageType::FsDiffResponse
+        | MessageType::ExportWorkspace
+        | MessageType::ExportWorkspaceChunk
+        | MessageType::ExportWorkspaceResponse
+        | MessageType::ShutdownAck
+        | MessageType::SignalExec
+        | MessageType::SignalExecResponse
+        | MessageType::SetExecPolicy
+        | MessageType::SetExecPolicyResponse
+        | MessageType::EnterReadOnly
+        | MessageType::EnterReadOnlyResponse
+        | MessageType::CreateUser
+        | MessageType::CreateUserResponse
+        | MessageType::SyncClock
+        | MessageType::SyncClockResponse
+        | MessageType::ExecStdinChunk
+        | MessageType::ExecStdinClose
+        | MessageType::ExecStdinResponse
+        | MessageType::Symlink
+        | MessageType::SymlinkResponse
+        | MessageType::Chmod
+        | MessageType::ChmodResponse
+        | MessageType::ReadLink
+        | MessageType::ReadLinkResponse
+        | MessageType::HashFiles
+        | MessageType::HashFilesResponse
+        | MessageType::SysInfo
+        | MessageType::SysInfoResponse
+        | MessageType::TelemetryControl
+        | MessageType::TelemetryControlResponse
+        | MessageType::AttachExec
+        | MessageType::PtyOpen
+        | MessageType::PtyOpened
+        | MessageType::PtyResize
+        | MessageType::PtyClose => {
+            debug!("pty_session: ignoring unexpected message {:?}", msg_type);
+            None
+        }
+    }
 }
 
 fn io_error_from_errno(err: Errno) -> io::Error {
@@ -6,7 +6,8 @@
 //!    - `VZLinuxBootLoader` (kernel, initrd, cmdline)
 //!    - `VZVirtioSocketDeviceConfiguration` (for host↔guest control channel)
 //!    - `VZNATNetworkDeviceAttachment` (if networking enabled)
-//!    - `VZVirtioFileSystemDeviceConfiguration` (if shared_dir provided)
+//!    - `VZVirtioFileSystemDeviceConfiguration` (one per shared directory)
+//!    - `VZVirtioTraditionalMemoryBalloonDeviceConfiguration` (unless fast boot)
 //!
 //!    If `config.snapshot` is `Some`, restores from a VZ snapshot instead of cold-booting.
 //! 2. `exec()`, `write_file()`, etc.: Delegate to `ControlChannel` over vsock fd
@@ -41,7 +42,7 @@ use tracing::{debug, error, info, warn};
 use void_box_protocol::SessionSecret;
 
 use crate::backend::control_channel::{ControlChannel, GuestConnector, GUEST_AGENT_PORT};
-use crate::backend::{BackendConfig, GuestConsoleSink, VmmBackend};
+use crate::backend::{Backend, BackendConfig, GuestConsoleSink, VmmBackend};
 use crate::error::Result;
 use crate::guest::protocol::{
     build_exec_request, ExecOutputChunk, ExecResponse, TelemetrySubscribeRequest,
@@ -154,6 +155,7 @@ struct StartedConfigInfo {
     vcpus: usize,
     network: bool,
     boot_clock_secs: u64,
+    memory_balloon: bool,
 }
 
 // Safety: The ObjC `vm` and `socket_device` handles are only mutated in
@@ -295,15 +297,25 @@ fn sidecar_restore_config(config: BackendConfig, meta: &VzSnapshotMeta) -> Backe
         rootfs,
         enable_vsock,
         guest_console,
+        console_shell,
+        boot_profile,
         shared_dir,
         mounts,
         oci_rootfs,
         oci_rootfs_dev,
         oci_rootfs_disk,
+        disks,
         env,
+        dns,
+        network_mode,
         security,
         snapshot,
         enable_snapshots,
+        resource_policy,
+        protocol_tap,
+        watchdog,
+        extra_cmdline,
+        rosetta,
     } = config;
 
     if caller_memory_mb != meta.memory_mb {
@@ -336,15 +348,25 @@ fn sidecar_restore_config(config: BackendConfig, meta: &VzSnapshotMeta) -> Backe
         rootfs,
         enable_vsock,
         guest_console,
+        console_shell,
+        boot_profile,
         shared_dir,
         mounts,
         oci_rootfs,
         oci_rootfs_dev,
         oci_rootfs_disk,
+        disks,
         env,
+        dns,
+        network_mode,
         security,
         snapshot,
         enable_snapshots,
+        resource_policy,
+        protocol_tap,
+        watchdog,
+        extra_cmdline,
+        rosetta,
     }
 }
 
@@ -418,12 +440,14 @@ impl VzBackend {
     /// saved snapshot so that `restoreMachineStateFromURL:` accepts the
     /// reconstructed config. `None` generates a fresh identifier (cold boot).
     /// The returned `Vec<u8>` is the `dataRepresentation` of the identifier in
-    /// effect, ready to persist in the snapshot sidecar.
+    /// effect, ready to persist in the snapshot sidecar. `memory_balloon`
+    /// attaches a virtio balloon; a restore must match the saved VM.
     fn configure_vm(
         config: &BackendConfig,
         boot_clock_secs: u64,
         machine_identifier_data: Option<&[u8]>,
         validate_save_restore: bool,
+        memory_balloon: bool,
     ) -> Result<(Retained<VZVirtualMachineConfiguration>, Vec<u8>)> {
         // 1. Boot loader
         let kernel_url =
@@ -540,56 +564,36 @@ impl VzBackend {
             vm_config.setSerialPorts(&serial_configs);
         }
 
+        // 5a. Memory balloon, for set_memory_target
+        if memory_balloon {
+            let balloon = unsafe { VZVirtioTraditionalMemoryBalloonDeviceConfiguration::new() };
+            let balloon_configs: Retained<NSArray<VZMemoryBalloonDeviceConfiguration>> =
+                NSArray::arrayWithObject(&balloon);
+            unsafe {
+                vm_config.setMemoryBalloonDevices(&balloon_configs);
+            }
+        }
+
         // 6. Shared directories (virtiofs)
         {
             let mut fs_configs: Vec<Retained<VZVirtioFileSystemDeviceConfiguration>> = Vec::new();
 
-            // Legacy single shared_dir
-            if let Some(ref shared_dir) = config.shared_dir {
-                if let Some(path_str) = shared_dir.to_str() {
-                    let tag = NSString::from_str("shared");
-                    let url = NSURL::fileURLWithPath(&NSString::from_str(path_str));
-                    let share = unsafe {
-                        VZSharedDirectory::initWithURL_readOnly(
-                            VZSharedDirectory::alloc(),
-                            &url,
-                            false,
-                        )
-                    };
-                    let single = unsafe {
-                        VZSingleDirectoryShare::initWithDirectory(
-                            VZSingleDirectoryShare::alloc(),
-                            &share,
-                        )
-                    };
-                    let fs = unsafe {
-                        VZVirtioFileSystemDeviceConfiguration::initWithTag(
-                            VZVirtioFileSystemDeviceConfiguration::alloc(),
-                            &tag,
-                        )
-                    };
-                    unsafe { fs.setShare(Some(&single)) };
-                    debug!("VzBackend: virtiofs share 'shared' -> {}", path_str);
-                    fs_configs.push(fs);
-                }
-            }
-
-            // Named mounts from config.mounts
-            for (i, mount) in config.mounts.iter().enumerate() {
-                let tag_str = format!("mount{}", i);
-                let tag = NSString::from_str(&tag_str);
-                let url = NSURL::fileURLWithPath(&NSString::from_str(&mount.host_path));
-                let share = unsafe {
+            // Legacy shared_dir and named mounts, one device per tag
+            for share in config::directory_shares(config) {
+                let tag = NSString::from_str(&share.tag);
+                let url =
+                    NSURL::fileURLWithPath(&NSString::from_str(&share.host_path.to_string_lossy()));
+                let directory = unsafe {
                     VZSharedDirectory::initWithURL_readOnly(
                         VZSharedDirectory::alloc(),
                         &url,
-                        mount.read_only,
+                        share.read_only,
                     )
                 };
                 let single = unsafe {
                     VZSingleDirectoryShare::initWithDirectory(
                         VZSingleDirectoryShare::alloc(),
-                        &share,
+                        &directory,
                     )
                 };
                 let fs = unsafe {
@@ -601,11 +605,19 @@ impl VzBackend {
                 unsafe { fs.setShare(Some(&single)) };
                 debug!(
                     "VzBackend: virtiofs share '{}' -> {} (ro={})",
-                    tag_str, mount.host_path, mount.read_only
+                    share.tag,
+                    share.host_path.display(),
+                    share.read_only
                 );
                 fs_configs.push(fs);
             }
 
+            // Rosetta, for x86_64 binaries
+            if config.rosetta {
+                debug!("VzBackend: virtiofs share 'rosetta' -> Rosetta");
+                fs_configs.push(super::rosetta::share_device()?);
+            }
+
             if !fs_configs.is_empty() {
                 // Build NSArray of VZDirectorySharingDeviceConfiguration
                 let configs_refs: Vec<&VZDirectorySharingDeviceConfiguration> = fs_configs
@@ -658,7 +670,13 @@ impl VzBackend {
         let socket_device = SendSyncDevice(socket_device);
 
         let connector = Self::build_connector(&socket_device, &self.vz_queue);
-        let control_channel = Arc::new(ControlChannel::new(connector, session_secret.clone()));
+        let protocol_tap = self
+            .start_config
+            .as_ref()
+            .and_then(|config| config.protocol_tap.as_ref());
+        let control_channel = Arc::new(
+            ControlChannel::new(connector, session_secret.clone()).with_protocol_tap(protocol_tap),
+        );
 
         self.socket_device = Some(socket_device);
         self.control_channel = Some(control_channel);
@@ -762,6 +780,34 @@ impl VzBackend {
         Ok(())
     }
 
+    /// Point the memory balloon at `mb` MiB of usable guest memory.
+    fn set_balloon_target(&self, mb: usize) -> Result<()> {
+        let vm = self.vm.as_ref().ok_or(crate::Error::VmNotRunning)?;
+        let info = self
+            .started_config
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        if !info.memory_balloon {
+            return Err(crate::Error::Config(
+                "virtio-balloon is disabled for this VM".into(),
+            ));
+        }
+        let target = config::memory_target_bytes(info.memory_mb, mb)?;
+        let vm_ptr = Retained::as_ptr(vm) as usize;
+        // Like the VM, its devices may only be used on the VZ queue.
+        self.vz_queue.exec_sync(move || {
+            let vm_ref = unsafe { &*(vm_ptr as *const VZVirtualMachine) };
+            let devices = unsafe { vm_ref.memoryBalloonDevices() };
+            if let Some(device) = devices.firstObject() {
+                let balloon: Retained<VZVirtioTraditionalMemoryBalloonDevice> =
+                    unsafe { Retained::cast_unchecked(device) };
+                unsafe { balloon.setTargetVirtualMachineMemorySize(target) };
+            }
+        });
+        debug!("VzBackend: memory balloon target set to {} MiB", mb);
+        Ok(())
+    }
+
     /// Create a snapshot of the running VM.
     ///
     /// Pauses the VM, saves state to Apple's opaque file + our JSON sidecar,
@@ -863,6 +909,7 @@ impl VzBackend {
             boot_clock_secs: config_info.boot_clock_secs,
             config_hash,
             machine_identifier: self.machine_identifier.clone(),
+            memory_balloon: config_info.memory_balloon,
         };
         if let Err(e) = meta.save(dir) {
             error!("VzBackend: sidecar save failed: {}", e);
@@ -874,7 +921,9 @@ impl VzBackend {
 
     fn clear_runtime_state(&mut self) {
         self.running.store(false, Ordering::SeqCst);
-        self.control_channel = None;
+        if let Some(channel) = self.control_channel.take() {
+            channel.close();
+        }
         self.socket_device = None;
         self.vm = None;
         self.session_secret = None;
@@ -890,6 +939,20 @@ impl VmmBackend for VzBackend {
         if let Some(warning) = config.initramfs_memory_warning() {
             warn!("VzBackend: {}", warning);
         }
+        self.capabilities()
+            .require(config.required_caps(), self.kind())?;
+        if config.snapshot.is_none() {
+            config.check_kernel_arch()?;
+        }
+        if config.snapshot.is_some()
+            && (!config.extra_cmdline.is_empty() || !config.security.write_roots.is_empty())
+        {
+            return Err(crate::Error::Config(
+                "a restored VM keeps the kernel cmdline it was booted with".into(),
+            ));
+        }
+        config::validate_extra_cmdline(&config)?;
+        config::validate_directory_shares(&config)?;
         // All ObjC types are !Send, so we run the entire VM setup
         // synchronously via block_in_place to avoid holding them across
         // an .await point.
@@ -928,12 +991,15 @@ impl VmmBackend for VzBackend {
                 // reconstructed `VZVirtualMachineConfiguration` with a single
                 // opaque `VZErrorRestore` ("invalid argument"), so diverging
                 // here would surface to users as an unactionable error.
+                // The balloon, too, follows the sidecar rather than the
+                // caller's boot profile.
                 let effective_config = sidecar_restore_config(config.clone(), &meta);
                 let (vm_config, machine_identifier_bytes) = Self::configure_vm(
                     &effective_config,
                     meta.boot_clock_secs,
                     Some(saved_machine_identifier),
                     true,
+                    meta.memory_balloon,
                 )?;
 
                 // 3. Create VM on the VZ queue
@@ -1010,6 +1076,7 @@ impl VmmBackend for VzBackend {
                     vcpus: meta.vcpus,
                     network: meta.network,
                     boot_clock_secs: meta.boot_clock_secs,
+                    memory_balloon: meta.memory_balloon,
                 });
                 self.machine_identifier = Some(machine_identifier_bytes);
                 self.setup_control_channel(session_secret);
@@ -1041,8 +1108,14 @@ impl VmmBackend for VzBackend {
             }
 
             let boot_clock_secs = config::current_epoch_secs();
-            let (vm_config, machine_identifier_bytes) =
-                Self::configure_vm(&config, boot_clock_secs, None, config.enable_snapshots)?;
+            let memory_balloon = config::memory_balloon(&config);
+            let (vm_config, machine_identifier_bytes) = Self::configure_vm(
+                &config,
+                boot_clock_secs,
+                None,
+                config.enable_snapshots,
+                memory_balloon,
+            )?;
 
             // Create and start the VM on a dedicated serial dispatch queue.
             //
@@ -1083,6 +1156,7 @@ impl VmmBackend for VzBackend {
                 vcpus: config.vcpus,
                 network: config.network,
                 boot_clock_secs,
+                memory_balloon,
             });
             self.machine_identifier = Some(machine_identifier_bytes);
             self.setup_control_channel(config.security.session_secret.clone());
@@ -1119,11 +1193,7 @@ impl VmmBackend for VzBackend {
             self.span_context.as_ref(),
         );
         let response = cc.send_exec_request(&request).await?;
-        Ok(ExecOutput::new(
-            response.stdout,
-            response.stderr,
-            response.exit_code,
-        ))
+        ExecOutput::from_response(program, response)
     }
 
     async fn exec_streaming(
@@ -1133,6 +1203,7 @@ impl VmmBackend for VzBackend {
         env: &[(String, String)],
         working_dir: Option<&str>,
         timeout_secs: Option<u64>,
+        exec_id: Option<&str>,
     ) -> Result<(
         tokio::sync::mpsc::Receiver<ExecOutputChunk>,
         tokio::sync::oneshot::Receiver<Result<ExecResponse>>,
@@ -1142,7 +1213,7 @@ impl VmmBackend for VzBackend {
             .as_ref()
             .ok_or_else(|| crate::Error::Backend("VM not started".into()))?
             .clone();
-        let request = build_exec_request(
+        let mut request = build_exec_request(
             program,
             args,
             &[],
@@ -1151,6 +1222,9 @@ impl VmmBackend for VzBackend {
             timeout_secs,
             self.span_context.as_ref(),
         );
+        if let Some(exec_id) = exec_id {
+            request.exec_id = Some(exec_id.to_string());
+        }
 
         let (chunk_tx, chunk_rx) = tokio::sync::mpsc::channel(256);
         let (done_tx, done_rx) = tokio::sync::oneshot::channel();
@@ -1181,6 +1255,39 @@ impl VmmBackend for VzBackend {
         Ok(())
     }
 
+    async fn write_file_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Result<u64> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or_else(|| crate::Error::Backend("VM not started".into()))?;
+
+        let resp = cc.send_write_file_chunk(path, offset, data).await?;
+        if !resp.success {
+            return Err(crate::Error::Backend(format!(
+                "write_file_chunk failed at offset {}: {}",
+                offset,
+                resp.error.unwrap_or_default()
+            )));
+        }
+        Ok(resp.staged_size)
+    }
+
+    async fn finalize_write_file(&self, path: &str, total_size: u64) -> Result<()> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or_else(|| crate::Error::Backend("VM not started".into()))?;
+
+        let resp = cc.send_write_file_finalize(path, total_size).await?;
+        if !resp.success {
+            return Err(crate::Error::Backend(format!(
+                "finalize_write_file failed: {}",
+                resp.error.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
     async fn mkdir_p(&self, path: &str) -> Result<()> {
         let cc = self
             .control_channel
@@ -1221,6 +1328,115 @@ impl VmmBackend for VzBackend {
         }
     }
 
+    async fn hash_files(&self, paths: &[String]) -> Result<Vec<crate::guest::protocol::FileHash>> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_hash_files(paths).await?;
+        if response.success {
+            Ok(response.files)
+        } else {
+            Err(crate::Error::Backend(format!(
+                "Failed to hash files: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn sysinfo(&self) -> Result<crate::guest::protocol::SysInfo> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_sysinfo().await?;
+        match response.info {
+            Some(info) if response.success => Ok(info),
+            _ => Err(crate::Error::Backend(format!(
+                "Failed to read system info: {}",
+                response.error.unwrap_or_default()
+            ))),
+        }
+    }
+
+    async fn symlink(&self, request: crate::guest::protocol::SymlinkRequest) -> Result<()> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_symlink(&request).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(crate::Error::Backend(format!(
+                "Failed to create symlink: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn chmod(&self, request: crate::guest::protocol::ChmodRequest) -> Result<()> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_chmod(&request).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(crate::Error::Backend(format!(
+                "Failed to change file mode: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_read_link(path).await?;
+        response.target.ok_or_else(|| {
+            crate::Error::Backend(format!(
+                "Failed to read link: {}",
+                response.error.unwrap_or_default()
+            ))
+        })
+    }
+
+    async fn fs_diff(&self, root: Option<&str>) -> Result<crate::guest::protocol::FsDiffResponse> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let response = cc.send_fs_diff(root).await?;
+        if response.success {
+            Ok(response)
+        } else {
+            Err(crate::Error::Backend(format!(
+                "Failed to diff filesystem: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    async fn export_workspace(&self, path_filter: Option<&str>) -> Result<Vec<u8>> {
+        let cc = self
+            .control_channel
+            .as_ref()
+            .ok_or(crate::Error::VmNotRunning)?;
+        let (archive, response) = cc.send_export_workspace(path_filter).await?;
+        if response.success {
+            Ok(archive)
+        } else {
+            Err(crate::Error::Backend(format!(
+                "Failed to export workspace: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
     async fn start_telemetry(
         &mut self,
         observer: Observer,
@@ -1237,6 +1453,7 @@ impl VmmBackend for VzBackend {
             Some(rb) => TelemetryAggregator::with_ring_buffer(observer, self.cid, rb),
             None => TelemetryAggregator::new(observer, self.cid),
         });
+        aggregator.attach_control_channel(cc.clone());
         let agg_clone = aggregator.clone();
 
         tokio::spawn(async move {
@@ -1257,6 +1474,10 @@ impl VmmBackend for VzBackend {
         self.span_context = Some(ctx);
     }
 
+    fn control_channel(&self) -> Option<Arc<ControlChannel>> {
+        self.control_channel.clone()
+    }
+
     async fn attach_pty(
         &self,
         request: void_box_protocol::PtyOpenRequest,
@@ -1290,6 +1511,18 @@ impl VmmBackend for VzBackend {
         })
     }
 
+    fn set_memory_target(&self, mb: usize) -> Result<()> {
+        self.set_balloon_target(mb)
+    }
+
+    async fn suspend(&self) -> Result<()> {
+        tokio::task::block_in_place(|| self.pause())
+    }
+
+    async fn resume(&self) -> Result<()> {
+        tokio::task::block_in_place(|| VzBackend::resume(self))
+    }
+
     async fn create_auto_snapshot(
         &mut self,
         snapshot_dir: &std::path::Path,
@@ -1342,6 +1575,10 @@ impl VmmBackend for VzBackend {
     fn cid(&self) -> u32 {
         self.cid
     }
+
+    fn kind(&self) -> Backend {
+        Backend::Vz
+    }
 }
 
 #[cfg(test)]
@@ -1378,9 +1615,11 @@ mod tests {
             session_secret: SessionSecret::new([7u8; 32]),
             command_allowlist: Vec::new(),
             network_deny_list: Vec::new(),
+            network_policy: None,
             max_connections_per_second: 0,
             max_concurrent_connections: 0,
             seccomp: false,
+            write_roots: Vec::new(),
         }
     }
 
@@ -1394,15 +1633,25 @@ mod tests {
             network: false,
             enable_vsock: true,
             guest_console: sink,
+            console_shell: false,
+            boot_profile: Default::default(),
             shared_dir: None,
             mounts: Vec::new(),
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
             env: Vec::new(),
+            dns: Default::default(),
+            network_mode: Default::default(),
             security: test_security_config(),
             snapshot: None,
             enable_snapshots: false,
+            resource_policy: Default::default(),
+            protocol_tap: None,
+            watchdog: None,
+            extra_cmdline: Vec::new(),
+            rosetta: false,
         }
     }
 
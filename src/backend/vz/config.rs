@@ -3,7 +3,10 @@
 //! Translates VoidBox's platform-agnostic configuration into the
 //! Virtualization.framework objects needed to boot a VM.
 
-use crate::backend::{append_common_guest_kernel_args, BackendConfig};
+use std::path::PathBuf;
+
+use crate::backend::{append_common_guest_kernel_args, cmdline, BackendConfig, BootProfile};
+use crate::Result;
 
 pub(crate) fn current_epoch_secs() -> u64 {
     std::time::SystemTime::now()
@@ -24,6 +27,17 @@ pub fn build_kernel_cmdline(config: &BackendConfig) -> String {
 
 /// Build the kernel command line for a VZ-based VM with an explicit boot clock.
 pub fn build_kernel_cmdline_with_clock(config: &BackendConfig, epoch_secs: u64) -> String {
+    let mut parts = generated_kernel_args(config, epoch_secs);
+    cmdline::extend(&mut parts, &config.extra_cmdline);
+    parts.join(" ")
+}
+
+/// Check [`BackendConfig::extra_cmdline`] against the arguments VZ generates.
+pub fn validate_extra_cmdline(config: &BackendConfig) -> Result<()> {
+    cmdline::validate(&generated_kernel_args(config, 0), &config.extra_cmdline)
+}
+
+fn generated_kernel_args(config: &BackendConfig, epoch_secs: u64) -> Vec<String> {
     let mut parts = vec![
         "console=hvc0".to_string(),
         "loglevel=0".to_string(),
@@ -42,12 +56,63 @@ pub fn build_kernel_cmdline_with_clock(config: &BackendConfig, epoch_secs: u64)
         epoch_secs,
         config.network,
         true,
+        &config.dns.servers,
         &config.mounts,
         config.oci_rootfs.as_deref(),
         None,
+        &config.security.write_roots,
     );
+    if config.boot_profile == BootProfile::FastBoot {
+        parts.push("voidbox.fast_boot=1".to_string());
+    }
+    if config.rosetta {
+        parts.push(format!("{}1", void_box_protocol::ROSETTA_CMDLINE_KEY));
+    }
+    parts
+}
 
-    parts.join(" ")
+/// A host directory shared with the guest over virtiofs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryShare {
+    /// virtiofs tag the guest-agent mounts.
+    pub tag: String,
+    pub host_path: PathBuf,
+    pub read_only: bool,
+}
+
+/// The directories to share: the legacy `shared_dir` as `shared`, then each
+/// of `config.mounts` as `mount<N>`, the tag its `voidbox.mount<N>=` cmdline
+/// entry names. Unlike KVM's single 9p device, every mount gets a device.
+pub fn directory_shares(config: &BackendConfig) -> Vec<DirectoryShare> {
+    let shared = config.shared_dir.iter().map(|dir| DirectoryShare {
+        tag: "shared".to_string(),
+        host_path: dir.clone(),
+        read_only: false,
+    });
+    let mounts = config
+        .mounts
+        .iter()
+        .enumerate()
+        .map(|(i, mount)| DirectoryShare {
+            tag: format!("mount{}", i),
+            host_path: PathBuf::from(&mount.host_path),
+            read_only: mount.read_only,
+        });
+    shared.chain(mounts).collect()
+}
+
+/// Check that every shared host path is a directory; VZ would otherwise
+/// reject the whole configuration with an opaque validation error.
+pub fn validate_directory_shares(config: &BackendConfig) -> Result<()> {
+    for share in directory_shares(config) {
+        if !share.host_path.is_dir() {
+            return Err(crate::Error::Config(format!(
+                "shared directory {} does not exist or is not a directory",
+                share.host_path.display()
+            )));
+        }
+    }
+    Ok(())
 }
 
 /// Compute memory size in bytes from the config's megabytes.
@@ -55,10 +120,28 @@ pub fn memory_bytes(config: &BackendConfig) -> u64 {
     (config.memory_mb as u64) * 1024 * 1024
 }
 
+/// Whether to attach a memory balloon. [`BootProfile::FastBoot`] skips it,
+/// as on KVM.
+pub fn memory_balloon(config: &BackendConfig) -> bool {
+    config.boot_profile != BootProfile::FastBoot
+}
+
+/// The balloon target for `mb` MiB of usable memory in a VM of
+/// `memory_mb` MiB, in bytes.
+pub fn memory_target_bytes(memory_mb: usize, mb: usize) -> Result<u64> {
+    if mb == 0 || mb > memory_mb {
+        return Err(crate::Error::Config(format!(
+            "memory target {} MiB must be between 1 and {} MiB",
+            mb, memory_mb
+        )));
+    }
+    Ok((mb as u64) * 1024 * 1024)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::backend::{BackendConfig, BackendSecurityConfig, GuestConsoleSink};
+    use crate::backend::{BackendConfig, BackendSecurityConfig, GuestConsoleSink, MountConfig};
     use std::path::PathBuf;
     use void_box_protocol::SessionSecret;
 
@@ -74,22 +157,34 @@ mod tests {
             network: true,
             enable_vsock: true,
             guest_console: GuestConsoleSink::Stderr,
+            console_shell: false,
+            boot_profile: Default::default(),
             shared_dir: None,
             mounts: vec![],
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
             env: vec![],
+            dns: Default::default(),
+            network_mode: Default::default(),
             security: BackendSecurityConfig {
                 session_secret: SessionSecret::new([0xAB; 32]),
                 command_allowlist: vec![],
                 network_deny_list: vec![],
+                network_policy: None,
                 max_connections_per_second: 50,
                 max_concurrent_connections: 64,
                 seccomp: false,
+                write_roots: Vec::new(),
             },
             snapshot: None,
             enable_snapshots: false,
+            resource_policy: Default::default(),
+            protocol_tap: None,
+            watchdog: None,
+            extra_cmdline: Vec::new(),
+            rosetta: false,
         }
     }
 
@@ -131,6 +226,78 @@ mod tests {
         assert!(cmdline.contains("ipv6.disable=1"));
     }
 
+    #[test]
+    fn cmdline_appends_validated_extras() {
+        let mut config = test_config();
+        config.extra_cmdline = vec!["quiet".into(), "console=tty0".into()];
+        validate_extra_cmdline(&config).unwrap();
+        let cmdline = build_kernel_cmdline(&config);
+        assert!(cmdline.ends_with(" quiet console=tty0"));
+
+        config.extra_cmdline = vec!["panic=10".into()];
+        assert!(validate_extra_cmdline(&config).is_err());
+    }
+
+    #[test]
+    fn cmdline_enables_rosetta_when_requested() {
+        let mut config = test_config();
+        assert!(!build_kernel_cmdline(&config).contains("voidbox.rosetta"));
+        config.rosetta = true;
+        assert!(build_kernel_cmdline(&config).contains(" voidbox.rosetta=1"));
+    }
+
+    #[test]
+    fn every_mount_gets_the_tag_its_cmdline_entry_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.mounts = vec![
+            MountConfig {
+                host_path: dir.path().display().to_string(),
+                guest_path: "/workspace/src".into(),
+                read_only: true,
+            },
+            MountConfig {
+                host_path: dir.path().display().to_string(),
+                guest_path: "/workspace/out".into(),
+                read_only: false,
+            },
+        ];
+        let shares = directory_shares(&config);
+        assert_eq!(shares.len(), 2);
+        assert_eq!(
+            (shares[0].tag.as_str(), shares[0].read_only),
+            ("mount0", true)
+        );
+        assert_eq!(
+            (shares[1].tag.as_str(), shares[1].read_only),
+            ("mount1", false)
+        );
+
+        let cmdline = build_kernel_cmdline(&config);
+        assert!(cmdline.contains("voidbox.mount0=mount0:/workspace/src:ro"));
+        assert!(cmdline.contains("voidbox.mount1=mount1:/workspace/out:rw"));
+        validate_directory_shares(&config).unwrap();
+
+        config.mounts[1].host_path = dir.path().join("missing").display().to_string();
+        assert!(matches!(
+            validate_directory_shares(&config),
+            Err(crate::Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn memory_target_stays_within_the_vm() {
+        let mut config = test_config();
+        assert!(memory_balloon(&config));
+        assert_eq!(memory_target_bytes(256, 128).unwrap(), 128 * 1024 * 1024);
+        assert_eq!(memory_target_bytes(256, 256).unwrap(), 256 * 1024 * 1024);
+        assert!(memory_target_bytes(256, 0).is_err());
+        assert!(memory_target_bytes(256, 257).is_err());
+
+        config.boot_profile = BootProfile::FastBoot;
+        assert!(!memory_balloon(&config));
+    }
+
     #[test]
     fn memory_bytes_conversion() {
         let config = test_config();
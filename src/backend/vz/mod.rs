@@ -8,10 +8,14 @@
 //! - **Boot**: `VZLinuxBootLoader` with custom aarch64 kernel, initrd, and cmdline
 //! - **Networking**: `VZNATNetworkDeviceAttachment` (macOS manages NAT)
 //! - **Host↔Guest control**: `VZVirtioSocketDevice` → raw fd → `GuestStream` adapter
-//! - **Shared files**: `VZVirtioFileSystemDevice` for virtiofs skill provisioning (future)
+//! - **Shared files**: one `VZVirtioFileSystemDevice` per mount, tagged `mount<N>`
+//!   (see [`config::directory_shares`])
+//! - **Elasticity**: `VZVirtioTraditionalMemoryBalloonDevice` behind
+//!   `set_memory_target`, and VM pause/resume behind `suspend`/`resume`
 
 mod backend;
 pub mod config;
+pub(crate) mod rosetta;
 pub mod snapshot;
 pub mod vsock;
 
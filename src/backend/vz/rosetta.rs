@@ -0,0 +1,68 @@
+//! Rosetta translation for x86_64 binaries in the arm64 guest.
+//!
+//! Virtualization.framework shares Apple's Rosetta translator with Linux
+//! guests as a directory. The guest-agent mounts it and registers it with
+//! binfmt_misc when the cmdline carries `voidbox.rosetta=1`.
+
+use objc2::rc::Retained;
+use objc2_virtualization::*;
+
+use crate::backend::RosettaAvailability;
+use crate::Result;
+
+/// Whether this Mac can share Rosetta with guests.
+#[cfg(target_arch = "aarch64")]
+pub(crate) fn availability() -> RosettaAvailability {
+    let availability = unsafe { VZLinuxRosettaDirectoryShare::availability() };
+    if availability == VZLinuxRosettaAvailability::Installed {
+        RosettaAvailability::Installed
+    } else if availability == VZLinuxRosettaAvailability::NotInstalled {
+        RosettaAvailability::NotInstalled
+    } else {
+        RosettaAvailability::NotSupported
+    }
+}
+
+/// Whether this Mac can share Rosetta with guests: never, on Intel.
+#[cfg(not(target_arch = "aarch64"))]
+pub(crate) fn availability() -> RosettaAvailability {
+    RosettaAvailability::NotSupported
+}
+
+/// The virtiofs device sharing Rosetta under
+/// [`ROSETTA_SHARE_TAG`](void_box_protocol::ROSETTA_SHARE_TAG).
+pub(crate) fn share_device() -> Result<Retained<VZVirtioFileSystemDeviceConfiguration>> {
+    match availability() {
+        RosettaAvailability::Installed => new_share_device(),
+        RosettaAvailability::NotInstalled => Err(crate::Error::Config(
+            "Rosetta is not installed; run `softwareupdate --install-rosetta`".into(),
+        )),
+        RosettaAvailability::NotSupported => Err(crate::Error::Config(
+            "Rosetta for Linux VMs needs macOS 13 or later on Apple silicon".into(),
+        )),
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn new_share_device() -> Result<Retained<VZVirtioFileSystemDeviceConfiguration>> {
+    use objc2::AnyThread;
+    use objc2_foundation::NSString;
+
+    let share = unsafe {
+        VZLinuxRosettaDirectoryShare::initWithError(VZLinuxRosettaDirectoryShare::alloc())
+    }
+    .map_err(|e| crate::Error::Backend(format!("VZ Rosetta share: {}", e)))?;
+    let fs = unsafe {
+        VZVirtioFileSystemDeviceConfiguration::initWithTag(
+            VZVirtioFileSystemDeviceConfiguration::alloc(),
+            &NSString::from_str(void_box_protocol::ROSETTA_SHARE_TAG),
+        )
+    };
+    unsafe { fs.setShare(Some(&share)) };
+    Ok(fs)
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn new_share_device() -> Result<Retained<VZVirtioFileSystemDeviceConfiguration>> {
+    unreachable!("Rosetta is never installed on Intel Macs")
+}
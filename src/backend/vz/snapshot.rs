@@ -58,6 +58,10 @@ pub struct VzSnapshotMeta {
     /// be restored and must be recreated.
     #[serde(default)]
     pub machine_identifier: Option<Vec<u8>>,
+    /// Whether the VM had a memory balloon device. Snapshots written before
+    /// VZ VMs got one have none.
+    #[serde(default)]
+    pub memory_balloon: bool,
 }
 
 impl VzSnapshotMeta {
@@ -22,11 +22,19 @@
 //!   kernel boot, vsock handshake, guest-agent ready, one exec RTT
 //! - `stop`  — `Sandbox::stop()` shutdown
 //! - `total` — sum of the above (what the user waits for end-to-end)
+//!
+//! Cold boots also report each boot phase (`kernel_load`, `vcpu_start`,
+//! `first_serial_byte`, `module_load`, `vsock_listen`, `handshake`; see
+//! `void_box::observe::boot`). `--fast-boot` boots with
+//! `BootProfile::FastBoot`; run once with and once without it to measure
+//! what the profile saves on a given kernel and initramfs.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
-use void_box::backend::GuestConsoleSink;
+use void_box::backend::{BootProfile, GuestConsoleSink};
+use void_box::observe::boot::BootTimeline;
 use void_box::sandbox::Sandbox;
 
 const DEFAULT_ITERS: usize = 20;
@@ -60,6 +68,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let warm_only = args.iter().any(|a| a == "--warm-only");
     let cold_only = args.iter().any(|a| a == "--cold-only");
     let breakdown = args.iter().any(|a| a == "--breakdown");
+    let profile = if args.iter().any(|a| a == "--fast-boot") {
+        BootProfile::FastBoot
+    } else {
+        BootProfile::Standard
+    };
     let console_file: Option<PathBuf> = args
         .iter()
         .position(|a| a == "--console-file")
@@ -67,16 +80,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(PathBuf::from);
 
     eprintln!(
-        "voidbox-startup-bench: pid={} iters={} memory_mb={}",
+        "voidbox-startup-bench: pid={} iters={} memory_mb={} profile={}",
         std::process::id(),
         iters,
         memory_mb,
+        profile.as_str(),
     );
     eprintln!("attach with: $HOME/.local/bin/perf-agent --pid {} --profile --offcpu --pmu --duration 60s --profile-output profile.pb.gz --offcpu-output offcpu.pb.gz --pmu-output pmu.txt", std::process::id());
 
     // Warmup — first boot amortizes cold page cache, module loads, etc.
     // We report it separately so the user can see both.
-    let warmup = bench_once(memory_mb).await?;
+    let (warmup, _) = bench_once_with_console(memory_mb, profile, None).await?;
     eprintln!(
         "warmup: build={:>7.1?} boot={:>7.1?} stop={:>7.1?} total={:>7.1?}",
         warmup.build, warmup.boot, warmup.stop, warmup.total
@@ -85,6 +99,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if !warm_only {
         eprintln!("\n-- cold boot --");
         let mut cold: Vec<Sample> = Vec::with_capacity(iters);
+        let mut phases: BTreeMap<String, Vec<Duration>> = BTreeMap::new();
         for i in 0..iters {
             // Route console to a file only on the very first iteration so we
             // have a trace to inspect without amortizing file-write cost
@@ -94,11 +109,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             } else {
                 None
             };
-            let s = bench_once_with_console(memory_mb, cf).await?;
+            let (s, timeline) = bench_once_with_console(memory_mb, profile, cf).await?;
             eprintln!(
                 "cold[{:>2}]: build={:>7.1?} boot={:>7.1?} stop={:>7.1?} total={:>7.1?}",
                 i, s.build, s.boot, s.stop, s.total
             );
+            for phase in timeline.iter().flat_map(BootTimeline::phases) {
+                if breakdown {
+                    eprintln!(
+                        "  ^ {:<17} at={:>7.1?} took={:>7.1?}",
+                        phase.name, phase.start, phase.duration
+                    );
+                }
+                phases.entry(phase.name).or_default().push(phase.duration);
+            }
             cold.push(s);
         }
 
@@ -106,6 +130,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         report("cold.boot", cold.iter().map(|s| s.boot));
         report("cold.stop", cold.iter().map(|s| s.stop));
         report("cold.total", cold.iter().map(|s| s.total));
+        for (name, durations) in phases {
+            report(&format!("cold.{name}"), durations.into_iter());
+        }
     }
 
     if !cold_only {
@@ -143,7 +170,7 @@ async fn capture_snapshot(
     // this, the cold boot uses vhost-vsock and the snapshot file
     // captures vhost-shaped state — but `from_snapshot` always
     // restores into the userspace backend, producing a mismatch that
-    // surfaces as `control_channel: deadline reached` on the warm
+    // surfaces as `Error::BootFailed` (handshake deadline) on the warm
     // phase (vhost's vring state lives in the host kernel's
     // vhost-vsock module and isn't part of our snapshot at all).
     let sandbox = Sandbox::local()
@@ -227,19 +254,17 @@ struct Sample {
     total: Duration,
 }
 
-async fn bench_once(memory_mb: usize) -> Result<Sample, Box<dyn std::error::Error>> {
-    bench_once_with_console(memory_mb, None).await
-}
-
 async fn bench_once_with_console(
     memory_mb: usize,
+    profile: BootProfile,
     console_file: Option<&std::path::Path>,
-) -> Result<Sample, Box<dyn std::error::Error>> {
+) -> Result<(Sample, Option<BootTimeline>), Box<dyn std::error::Error>> {
     let t0 = Instant::now();
     let mut builder = Sandbox::local()
         .from_env()?
         .memory_mb(memory_mb)
-        .network(false);
+        .network(false)
+        .boot_profile(profile);
     if let Some(path) = console_file {
         builder = builder.guest_console(GuestConsoleSink::File(path.to_path_buf()));
     }
@@ -259,16 +284,20 @@ async fn bench_once_with_console(
         .into());
     }
     let t2 = Instant::now();
+    let timeline = sandbox.boot_timeline();
 
     sandbox.stop().await?;
     let t3 = Instant::now();
 
-    Ok(Sample {
-        build: t1 - t0,
-        boot: t2 - t1,
-        stop: t3 - t2,
-        total: t3 - t0,
-    })
+    Ok((
+        Sample {
+            build: t1 - t0,
+            boot: t2 - t1,
+            stop: t3 - t2,
+            total: t3 - t0,
+        },
+        timeline,
+    ))
 }
 
 fn report(label: &str, samples: impl Iterator<Item = Duration>) {
@@ -14,6 +14,9 @@ use void_box_protocol::PtyOpenRequest;
 
 const GUEST_CONSOLE_LOG_FILENAME: &str = "guest-console.log";
 
+/// Detaches from the serial console (`Ctrl-]`, as in telnet and virsh).
+const CONSOLE_ESCAPE: u8 = 0x1d;
+
 /// Attaches to a running VM by run ID (not yet implemented).
 pub async fn cmd_attach(
     _run_id: &str,
@@ -53,6 +56,8 @@ pub struct ShellOpts<'a> {
     pub env_vars: &'a [String],
     /// Directory for interactive runtime logs.
     pub log_dir: &'a Path,
+    /// Attach to the guest serial console instead of a PTY.
+    pub console: bool,
 }
 
 /// Boots a VM from a spec file or ephemeral config and attaches an interactive PTY.
@@ -128,6 +133,9 @@ pub async fn cmd_shell(opts: ShellOpts<'_>) -> Result<i32, Box<dyn std::error::E
     if let Some(path) = &initramfs {
         builder = builder.initramfs(path);
     }
+    if opts.console {
+        builder = builder.console_shell(true);
+    }
 
     let mut auto_snapshot_pending = false;
 
@@ -195,6 +203,12 @@ pub async fn cmd_shell(opts: ShellOpts<'_>) -> Result<i32, Box<dyn std::error::E
         info!("Auto-snapshot: saved for next run");
     }
 
+    if opts.console {
+        let console_result = attach_serial_console(&sandbox).await;
+        let _ = sandbox.stop().await;
+        return console_result;
+    }
+
     let program_base = match Path::new(opts.program).file_name() {
         Some(name) => name.to_str().unwrap_or(opts.program),
         None => opts.program,
@@ -265,6 +279,49 @@ pub async fn cmd_shell(opts: ShellOpts<'_>) -> Result<i32, Box<dyn std::error::E
     pty_result
 }
 
+/// Connects the terminal to the guest serial console until the user presses
+/// [`CONSOLE_ESCAPE`] or the VM stops.
+async fn attach_serial_console(
+    sandbox: &void_box::sandbox::Sandbox,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let console = sandbox.attach_console().await?;
+    eprintln!("Connected to the guest serial console; press Ctrl-] to detach.");
+    let guard = RawModeGuard::engage(0).map_err(|e| format!("failed to enter raw mode: {e}"))?;
+    let (mut from_guest, mut to_guest) = tokio::io::split(console);
+
+    let output = async {
+        let mut stdout = tokio::io::stdout();
+        tokio::io::copy(&mut from_guest, &mut stdout).await
+    };
+    let input = async {
+        let mut stdin = tokio::io::stdin();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stdin.read(&mut buf).await?;
+            if n == 0 {
+                return Ok::<(), std::io::Error>(());
+            }
+            match buf[..n].iter().position(|&b| b == CONSOLE_ESCAPE) {
+                Some(end) => {
+                    to_guest.write_all(&buf[..end]).await?;
+                    return Ok(());
+                }
+                None => to_guest.write_all(&buf[..n]).await?,
+            }
+        }
+    };
+    let result = tokio::select! {
+        r = output => r.map(|_| ()),
+        r = input => r,
+    };
+    drop(guard);
+    eprintln!();
+    result?;
+    Ok(0)
+}
+
 /// Resolve kernel and initramfs for `voidbox shell`, using the same
 /// fallback chain as `voidbox run`: spec → env var → installed paths →
 /// auto-download from GitHub Releases.
@@ -336,6 +393,9 @@ fn build_ephemeral_spec(memory_mb: usize, vcpus: usize, network: bool) -> RunSpe
             image: None,
             guest_image: None,
             snapshot: None,
+            write_roots: Vec::new(),
+            rosetta: false,
+            ssh: false,
         },
         llm: None,
         observe: None,
@@ -538,6 +598,9 @@ mod tests {
                 image: None,
                 guest_image: None,
                 snapshot: None,
+                write_roots: Vec::new(),
+                rosetta: false,
+                ssh: false,
             },
             llm: None,
             observe: None,
@@ -569,6 +632,9 @@ mod tests {
                 image: None,
                 guest_image: None,
                 snapshot: None,
+                write_roots: Vec::new(),
+                rosetta: false,
+                ssh: false,
             },
             llm: None,
             observe: None,
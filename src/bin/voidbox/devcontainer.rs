@@ -0,0 +1,173 @@
+//! `voidbox devcontainer`: editor attach files for a sandbox's SSH bridge.
+
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use void_box::ssh::devcontainer;
+pub use void_box::ssh::devcontainer::DEFAULT_WORKSPACE_FOLDER;
+use void_box::ssh::SshAccess;
+
+use crate::output::{self, OutputFormat};
+
+pub struct DevcontainerOpts<'a> {
+    /// Bridge name; the only running bridge when `None`.
+    pub name: Option<&'a str>,
+    pub out: &'a Path,
+    pub workspace: &'a str,
+    pub install_ssh_config: bool,
+    pub open: bool,
+}
+
+#[derive(serde::Serialize)]
+struct DevcontainerResult {
+    sandbox: String,
+    ssh_host: String,
+    devcontainer_json: PathBuf,
+    ssh_config: PathBuf,
+    /// `~/.ssh/config`, when `--install-ssh-config` added the include
+    installed_include: Option<PathBuf>,
+    folder_uri: String,
+}
+
+pub fn cmd_devcontainer(
+    format: OutputFormat,
+    opts: DevcontainerOpts<'_>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let access = find_bridge(&void_box::ssh::default_ssh_dir(), opts.name)?;
+    if !is_listening(&access) {
+        return Err(format!(
+            "SSH bridge '{}' is not running ({}:{} refused); restart the sandbox with `sandbox.ssh: true`",
+            access.name, access.host, access.port
+        )
+        .into());
+    }
+
+    let files = devcontainer::write_attach_files(&access, opts.out, opts.workspace)?;
+    let installed_include = if opts.install_ssh_config {
+        let user_config = user_ssh_config()?;
+        devcontainer::install_include(&user_config, &access.config_file)?;
+        Some(user_config)
+    } else {
+        None
+    };
+    let folder_uri = devcontainer::vscode_folder_uri(&access, opts.workspace);
+
+    let result = DevcontainerResult {
+        sandbox: access.name.clone(),
+        ssh_host: access.host_alias.clone(),
+        devcontainer_json: files.devcontainer_json,
+        ssh_config: files.ssh_config,
+        installed_include,
+        folder_uri,
+    };
+    output::print_json_or_human(format, &result, |r| {
+        println!("wrote {}", r.devcontainer_json.display());
+        println!("wrote {}", r.ssh_config.display());
+        match &r.installed_include {
+            Some(path) => println!("{} now includes host {}", path.display(), r.ssh_host),
+            None => println!(
+                "VS Code resolves hosts from ~/.ssh/config; rerun with --install-ssh-config to add {}",
+                r.ssh_host
+            ),
+        }
+        println!("open with: code --folder-uri {}", r.folder_uri);
+    });
+
+    if opts.open {
+        let status = std::process::Command::new("code")
+            .arg("--folder-uri")
+            .arg(&result.folder_uri)
+            .status()
+            .map_err(|e| format!("failed to run `code`: {e}"))?;
+        if !status.success() {
+            return Err(format!("`code` exited with {status}").into());
+        }
+    }
+    Ok(())
+}
+
+/// The bridge called `name`, or the only one under `root`.
+fn find_bridge(root: &Path, name: Option<&str>) -> Result<SshAccess, Box<dyn std::error::Error>> {
+    let bridges = devcontainer::list_bridges(root);
+    if let Some(name) = name {
+        return bridges.into_iter().find(|b| b.name == name).ok_or_else(|| {
+            format!("no SSH bridge named '{name}' under {}", root.display()).into()
+        });
+    }
+    match bridges.len() {
+        0 => Err(format!(
+            "no SSH bridges under {}; start a sandbox with `sandbox.ssh: true`",
+            root.display()
+        )
+        .into()),
+        1 => Ok(bridges.into_iter().next().expect("one bridge")),
+        _ => {
+            let names: Vec<&str> = bridges.iter().map(|b| b.name.as_str()).collect();
+            Err(format!(
+                "several SSH bridges are running ({}); pick one with --name",
+                names.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+/// Whether anything listens at the bridge's address. A sandbox that died
+/// without stopping leaves its `access.json` behind.
+fn is_listening(access: &SshAccess) -> bool {
+    let addrs: Vec<SocketAddr> = match (access.host.as_str(), access.port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(_) => return false,
+    };
+    addrs
+        .iter()
+        .any(|addr| TcpStream::connect_timeout(addr, Duration::from_secs(1)).is_ok())
+}
+
+fn user_ssh_config() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let home = std::env::var("HOME").map_err(|_| "HOME is not set")?;
+    Ok(PathBuf::from(home).join(".ssh").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_access(root: &Path, name: &str) {
+        let dir = root.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let access = SshAccess {
+            name: name.into(),
+            host: "127.0.0.1".into(),
+            port: 1,
+            user: "sandbox".into(),
+            host_alias: format!("voidbox-{name}"),
+            identity_file: dir.join("id_ed25519"),
+            known_hosts_file: dir.join("known_hosts"),
+            config_file: dir.join("ssh_config"),
+            audit_log: dir.join("audit.jsonl"),
+            host_key_fingerprint: "SHA256:x".into(),
+        };
+        std::fs::write(
+            dir.join("access.json"),
+            serde_json::to_vec(&access).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn picks_the_only_bridge_or_the_named_one() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(find_bridge(root.path(), None).is_err());
+
+        write_access(root.path(), "one");
+        assert_eq!(find_bridge(root.path(), None).unwrap().name, "one");
+
+        write_access(root.path(), "two");
+        let err = find_bridge(root.path(), None).unwrap_err().to_string();
+        assert!(err.contains("one, two"), "{err}");
+        assert_eq!(find_bridge(root.path(), Some("two")).unwrap().name, "two");
+        assert!(find_bridge(root.path(), Some("three")).is_err());
+    }
+}
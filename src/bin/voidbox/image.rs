@@ -36,22 +36,22 @@ async fn cmd_pull(cache_root: &Path, flavor: &str) -> Result<(), Box<dyn std::er
     let arch = void_box::image::detect_arch()?;
 
     if flavor == "all" {
-        for f in KNOWN_FLAVORS {
-            if *f == "kernel" {
-                let name = void_box::image::kernel_artifact_name(arch);
-                pull_one(cache_root, &name).await?;
-            } else {
-                let name = void_box::image::initramfs_artifact_name(f, arch);
-                pull_one(cache_root, &name).await?;
-            }
-        }
-        return Ok(());
+        let names: Vec<String> = KNOWN_FLAVORS
+            .iter()
+            .map(|f| {
+                if *f == "kernel" {
+                    void_box::image::kernel_artifact_name(arch)
+                } else {
+                    void_box::image::initramfs_artifact_name(f, arch)
+                }
+            })
+            .collect();
+        return pull(cache_root, &names).await;
     }
 
     if flavor == "kernel" {
         let name = void_box::image::kernel_artifact_name(arch);
-        pull_one(cache_root, &name).await?;
-        return Ok(());
+        return pull(cache_root, &[name]).await;
     }
 
     if !KNOWN_FLAVORS.contains(&flavor) {
@@ -63,20 +63,26 @@ async fn cmd_pull(cache_root: &Path, flavor: &str) -> Result<(), Box<dyn std::er
     }
 
     let name = void_box::image::initramfs_artifact_name(flavor, arch);
-    pull_one(cache_root, &name).await?;
-    Ok(())
+    pull(cache_root, &[name]).await
 }
 
-async fn pull_one(
+/// Download the artifacts not cached yet, concurrently.
+async fn pull(
     cache_root: &Path,
-    artifact_name: &str,
+    artifact_names: &[String],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(cached) = void_box::image::check_cache(cache_root, artifact_name) {
-        eprintln!("{} — already cached at {}", artifact_name, cached.display());
-        return Ok(());
+    let mut missing = Vec::new();
+    for name in artifact_names {
+        match void_box::image::check_cache(cache_root, name) {
+            Some(cached) => eprintln!("{} — already cached at {}", name, cached.display()),
+            None => missing.push(name.clone()),
+        }
+    }
+    let fetcher = void_box::image::ArtifactFetcher::from_env()?;
+    let paths = fetcher.fetch_all(cache_root, &missing).await?;
+    for (name, path) in missing.iter().zip(paths) {
+        eprintln!("{} — cached at {}", name, path.display());
     }
-    let path = void_box::image::download_and_cache(cache_root, artifact_name).await?;
-    eprintln!("{} — cached at {}", artifact_name, path.display());
     Ok(())
 }
 
@@ -2,6 +2,7 @@ mod attach;
 mod backend;
 mod banner;
 mod cli_config;
+mod devcontainer;
 mod image;
 mod output;
 mod snapshot;
@@ -241,6 +242,32 @@ enum Command {
         /// Set guest env var (KEY=VALUE, repeatable).
         #[arg(long = "env")]
         env_vars: Vec<String>,
+        /// Attach to the guest serial console, with a root shell on it,
+        /// instead of running `--program` in a PTY. Works before the
+        /// guest-agent is up, for debugging boots. Ctrl-] detaches. KVM only.
+        #[arg(long)]
+        console: bool,
+    },
+
+    /// Write devcontainer.json and an SSH config for opening a running
+    /// sandbox's workspace in VS Code over its SSH bridge.
+    Devcontainer {
+        /// Sandbox (SSH bridge) name. Default: the only running one.
+        #[arg(long)]
+        name: Option<String>,
+        /// Directory to write devcontainer.json and ssh_config to.
+        #[arg(long, default_value = ".devcontainer")]
+        out: PathBuf,
+        /// Guest folder to open.
+        #[arg(long, default_value = devcontainer::DEFAULT_WORKSPACE_FOLDER)]
+        workspace: String,
+        /// Add an `Include` of the bridge's SSH config to ~/.ssh/config,
+        /// where VS Code looks up hosts.
+        #[arg(long)]
+        install_ssh_config: bool,
+        /// Open the workspace with `code` afterwards.
+        #[arg(long)]
+        open: bool,
     },
 }
 
@@ -350,6 +377,23 @@ async fn run(
         Command::Image { command } => image::handle(command).await.map(|_| 0),
         Command::Config { command } => cmd_config(command, output, config).map(|_| 0),
         Command::Version => cmd_version(output).map(|_| 0),
+        Command::Devcontainer {
+            name,
+            out,
+            workspace,
+            install_ssh_config,
+            open,
+        } => devcontainer::cmd_devcontainer(
+            output,
+            devcontainer::DevcontainerOpts {
+                name: name.as_deref(),
+                out: &out,
+                workspace: &workspace,
+                install_ssh_config,
+                open,
+            },
+        )
+        .map(|_| 0),
         Command::Serve { listen, token_file } => {
             cmd_serve(listen.as_deref(), token_file.as_deref())
                 .await
@@ -379,6 +423,7 @@ async fn run(
             auto_snapshot,
             mounts,
             env_vars,
+            console,
         } => {
             attach::cmd_shell(attach::ShellOpts {
                 file: file.as_deref(),
@@ -394,6 +439,7 @@ async fn run(
                 mounts: &mounts,
                 env_vars: &env_vars,
                 log_dir: &config.paths.log_dir,
+                console,
             })
             .await
         }
@@ -1059,6 +1105,46 @@ mod cli_parse_tests {
         }
     }
 
+    #[test]
+    fn devcontainer_defaults_and_flags() {
+        let cli = Cli::try_parse_from(["voidbox", "devcontainer"]).unwrap();
+        match cli.command {
+            Command::Devcontainer {
+                name,
+                out,
+                workspace,
+                install_ssh_config,
+                open,
+            } => {
+                assert_eq!(name, None);
+                assert_eq!(out, PathBuf::from(".devcontainer"));
+                assert_eq!(workspace, "/workspace");
+                assert!(!install_ssh_config && !open);
+            }
+            _ => panic!("expected Devcontainer"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "voidbox",
+            "devcontainer",
+            "--name",
+            "review",
+            "--install-ssh-config",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Devcontainer {
+                name,
+                install_ssh_config,
+                ..
+            } => {
+                assert_eq!(name.as_deref(), Some("review"));
+                assert!(install_ssh_config);
+            }
+            _ => panic!("expected Devcontainer"),
+        }
+    }
+
     #[test]
     fn inspect_file() {
         let cli = Cli::try_parse_from(["voidbox", "inspect", "--file", "spec.yaml"]).unwrap();
@@ -189,8 +189,11 @@ async fn cmd_snapshot_create_linux(
             memory_mb,
             vcpus,
             cid: vm.cid(),
-            vsock_mmio_base: void_box::vmm::arch::VirtioSlot::Vsock.mmio_base(),
+            vsock_mmio_base: vm
+                .memory_layout()
+                .mmio_base(void_box::vmm::arch::VirtioSlot::Vsock),
             network,
+            virtio_mmio_base: vm.memory_layout().custom_virtio_base(),
         };
 
         let snap_dir = vm
@@ -268,8 +271,11 @@ async fn cmd_snapshot_create_linux(
             memory_mb,
             vcpus,
             cid: vm.cid(),
-            vsock_mmio_base: void_box::vmm::arch::VirtioSlot::Vsock.mmio_base(),
+            vsock_mmio_base: vm
+                .memory_layout()
+                .mmio_base(void_box::vmm::arch::VirtioSlot::Vsock),
             network: config.network,
+            virtio_mmio_base: vm.memory_layout().custom_virtio_base(),
         };
 
         let snap_dir = vm
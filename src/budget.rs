@@ -0,0 +1,326 @@
+//! Cost, token and turn limits for agent runs.
+//!
+//! [`AgentExecResult`] reports what a run cost once it is over. A
+//! [`Budget`] set on a [`VoidBox`](crate::agent_box::VoidBox::budget) or a
+//! [`Pipeline`](crate::pipeline::Pipeline::budget) is checked as the
+//! agent's events arrive instead: the first limit crossed kills
+//! the agent in the guest and fails the run with
+//! [`Error::BudgetExceeded`](crate::Error::BudgetExceeded), which carries
+//! what the agent had produced so far.
+//!
+//! How usage is counted is up to the run's
+//! [`AgentRunner`](crate::agent_runner::AgentRunner). claude-code's tokens
+//! and turns are counted from each assistant message; it reports cost only
+//! in its final event, so until then the cost is estimated from the tokens
+//! at the model's list price (see [`UsageCounter`]). Models without a known
+//! price are held to their reported cost only. Local providers are free and
+//! never exceed a cost limit.
+
+use std::fmt;
+
+use crate::observe::claude::AgentExecResult;
+use crate::observe::pricing::PricingTable;
+
+/// Limits for one agent run. Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub max_cost_usd: Option<f64>,
+    /// Input plus output tokens.
+    pub max_tokens: Option<u64>,
+    /// Model responses, each of which may call tools.
+    pub max_turns: Option<u32>,
+}
+
+impl Budget {
+    pub fn max_cost_usd(mut self, usd: f64) -> Self {
+        self.max_cost_usd = Some(usd);
+        self
+    }
+
+    pub fn max_tokens(mut self, tokens: u64) -> Self {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    pub fn max_turns(mut self, turns: u32) -> Self {
+        self.max_turns = Some(turns);
+        self
+    }
+
+    /// The tighter of each limit in `self` and `other`.
+    pub fn min(self, other: Budget) -> Budget {
+        fn tighter<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+                (a, b) => a.or(b),
+            }
+        }
+        Budget {
+            max_cost_usd: tighter(self.max_cost_usd, other.max_cost_usd),
+            max_tokens: tighter(self.max_tokens, other.max_tokens),
+            max_turns: tighter(self.max_turns, other.max_turns),
+        }
+    }
+
+    /// What is left after `spent`.
+    pub(crate) fn remaining(self, spent: &BudgetUsage) -> Budget {
+        Budget {
+            max_cost_usd: self.max_cost_usd.map(|max| (max - spent.cost_usd).max(0.0)),
+            max_tokens: self.max_tokens.map(|max| max.saturating_sub(spent.tokens)),
+            max_turns: self.max_turns.map(|max| max.saturating_sub(spent.turns)),
+        }
+    }
+
+    /// An even share for each of `n` runs going on at once.
+    pub(crate) fn split(self, n: usize) -> Budget {
+        let n = n.max(1);
+        Budget {
+            max_cost_usd: self.max_cost_usd.map(|max| max / n as f64),
+            max_tokens: self.max_tokens.map(|max| max / n as u64),
+            max_turns: self.max_turns.map(|max| max / n as u32),
+        }
+    }
+
+    /// The first limit `usage` is over, if any.
+    pub fn exceeded_by(&self, usage: &BudgetUsage) -> Option<BudgetLimit> {
+        if let Some(limit) = self.max_cost_usd {
+            if usage.cost_usd > limit {
+                return Some(BudgetLimit::Cost {
+                    limit,
+                    spent: usage.cost_usd,
+                });
+            }
+        }
+        if let Some(limit) = self.max_tokens {
+            if usage.tokens > limit {
+                return Some(BudgetLimit::Tokens {
+                    limit,
+                    used: usage.tokens,
+                });
+            }
+        }
+        if let Some(limit) = self.max_turns {
+            if usage.turns > limit {
+                return Some(BudgetLimit::Turns {
+                    limit,
+                    used: usage.turns,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// What a run, or a pipeline so far, has used.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetUsage {
+    pub cost_usd: f64,
+    pub tokens: u64,
+    pub turns: u32,
+}
+
+impl BudgetUsage {
+    pub fn of(result: &AgentExecResult) -> Self {
+        Self {
+            cost_usd: result.total_cost_usd,
+            tokens: result.input_tokens + result.output_tokens,
+            turns: result.num_turns,
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: &BudgetUsage) {
+        self.cost_usd += other.cost_usd;
+        self.tokens += other.tokens;
+        self.turns += other.turns;
+    }
+}
+
+/// The limit a run went over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    Cost { limit: f64, spent: f64 },
+    Tokens { limit: u64, used: u64 },
+    Turns { limit: u32, used: u32 },
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetLimit::Cost { limit, spent } => {
+                write!(f, "cost ${spent:.4} over the ${limit:.4} limit")
+            }
+            BudgetLimit::Tokens { limit, used } => {
+                write!(f, "{used} tokens over the {limit} token limit")
+            }
+            BudgetLimit::Turns { limit, used } => {
+                write!(f, "{used} turns over the {limit} turn limit")
+            }
+        }
+    }
+}
+
+/// A run stopped for going over its [`Budget`].
+#[derive(Debug, Clone)]
+pub struct BudgetExceeded {
+    pub limit: BudgetLimit,
+    /// What the agent produced before it was stopped, with the usage
+    /// counted so far. `is_error` is set.
+    pub partial: AgentExecResult,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.limit)
+    }
+}
+
+impl BudgetExceeded {
+    /// Mark `partial` as stopped over `limit`, with the usage counted so far.
+    pub(crate) fn new(
+        limit: BudgetLimit,
+        mut partial: AgentExecResult,
+        usage: &BudgetUsage,
+    ) -> Self {
+        partial.is_error = true;
+        partial.error = Some(format!("budget exceeded: {limit}"));
+        partial.num_turns = usage.turns;
+        partial.total_cost_usd = usage.cost_usd;
+        BudgetExceeded { limit, partial }
+    }
+}
+
+/// Usage counted from the per-message reports a run streams, for runners
+/// whose agent reports cost only once it is done.
+///
+/// claude-code repeats a message's usage on every content block it streams
+/// separately, so each message is counted once, at its largest report.
+/// The final totals, once reported, replace the running count.
+#[derive(Debug, Clone, Default)]
+pub struct UsageCounter {
+    /// Tokens of the messages before the current one.
+    closed_tokens: u64,
+    /// Id and tokens of the latest message.
+    current: Option<(String, u64)>,
+    turns: u32,
+    model: String,
+    reported: Option<BudgetUsage>,
+}
+
+impl UsageCounter {
+    /// Count a model message of `tokens` input plus output tokens. Messages
+    /// without an id are each counted as a new turn.
+    pub fn message(&mut self, id: &str, model: &str, tokens: u64) {
+        match &mut self.current {
+            Some((current_id, current)) if !id.is_empty() && current_id == id => {
+                *current = (*current).max(tokens);
+            }
+            _ => {
+                if let Some((_, previous)) = self.current.take() {
+                    self.closed_tokens += previous;
+                }
+                self.current = Some((id.to_string(), tokens));
+                self.turns += 1;
+            }
+        }
+        if !model.is_empty() {
+            self.model = model.to_string();
+        }
+    }
+
+    /// Record the run's final totals.
+    pub fn report(&mut self, usage: BudgetUsage) {
+        self.reported = Some(usage);
+    }
+
+    /// Usage so far. Until the totals are reported, cost is estimated from
+    /// the tokens at the model's list price; models without a known price
+    /// count as free.
+    pub fn usage(&self) -> BudgetUsage {
+        if let Some(reported) = self.reported {
+            return reported;
+        }
+        let tokens = self.closed_tokens + self.current.as_ref().map_or(0, |(_, t)| *t);
+        BudgetUsage {
+            cost_usd: estimate_cost_usd(&self.model, tokens).unwrap_or(0.0),
+            tokens,
+            turns: self.turns,
+        }
+    }
+}
+
+/// List-price estimate for `tokens` on `model`, in USD. Every token is
+/// priced as output, so the estimate errs high.
+fn estimate_cost_usd(model: &str, tokens: u64) -> Option<f64> {
+    PricingTable::anthropic_list_prices()
+        .pricing_for(model)
+        .map(|pricing| tokens as f64 * pricing.output_usd_per_mtok / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_counts_each_message_once() {
+        let mut counter = UsageCounter::default();
+        counter.message("m1", "claude-sonnet-4", 110);
+        // A second content block of the same message.
+        counter.message("m1", "claude-sonnet-4", 110);
+        assert_eq!(counter.usage().tokens, 110);
+        counter.message("m2", "claude-sonnet-4", 220);
+        counter.message("", "claude-sonnet-4", 5);
+        let usage = counter.usage();
+        assert_eq!(usage.tokens, 335);
+        assert_eq!(usage.turns, 3);
+        assert_eq!(
+            Budget::default().max_turns(2).exceeded_by(&usage),
+            Some(BudgetLimit::Turns { limit: 2, used: 3 })
+        );
+    }
+
+    #[test]
+    fn test_counter_estimates_cost_until_reported() {
+        let mut counter = UsageCounter::default();
+        // 500 tokens at sonnet's $15/Mtok output price.
+        counter.message("m1", "claude-sonnet-4", 500);
+        assert!((counter.usage().cost_usd - 0.0075).abs() < 1e-9);
+        counter.report(BudgetUsage {
+            cost_usd: 0.02,
+            tokens: 500,
+            turns: 1,
+        });
+        assert_eq!(
+            Budget::default()
+                .max_cost_usd(0.01)
+                .exceeded_by(&counter.usage()),
+            Some(BudgetLimit::Cost {
+                limit: 0.01,
+                spent: 0.02
+            })
+        );
+
+        let mut unknown = UsageCounter::default();
+        unknown.message("m1", "qwen3-coder", 500_000);
+        assert_eq!(unknown.usage().cost_usd, 0.0);
+    }
+
+    #[test]
+    fn test_remaining_and_split() {
+        let budget = Budget::default().max_cost_usd(1.0).max_tokens(1000);
+        let spent = BudgetUsage {
+            cost_usd: 0.25,
+            tokens: 1200,
+            turns: 3,
+        };
+        let left = budget.remaining(&spent);
+        assert_eq!(left.max_cost_usd, Some(0.75));
+        assert_eq!(left.max_tokens, Some(0));
+        assert_eq!(left.max_turns, None);
+        assert_eq!(left.split(3).max_cost_usd, Some(0.25));
+
+        let tighter = budget.min(Budget::default().max_tokens(10).max_turns(4));
+        assert_eq!(tighter.max_tokens, Some(10));
+        assert_eq!(tighter.max_turns, Some(4));
+        assert_eq!(tighter.max_cost_usd, Some(1.0));
+    }
+}
@@ -2807,6 +2807,9 @@ mod tests {
                 image: None,
                 guest_image: None,
                 snapshot: None,
+                write_roots: Vec::new(),
+                rosetta: false,
+                ssh: false,
             },
             llm: Some(LlmSpec {
                 provider: "claude".into(),
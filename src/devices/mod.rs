@@ -3,16 +3,26 @@
 //! This module contains device implementations:
 //! - Serial console (8250 UART)
 //! - virtio-vsock for host-guest communication
-//! - virtio-net for networking (SLIRP-based user-mode NAT)
+//! - virtio-net for networking (SLIRP-based user-mode NAT, or vhost-net over TAP)
 //! - virtio-blk for block devices (optional)
+//! - virtio-rng for guest entropy
+//! - virtio-balloon for host-driven memory reclaim
+//! - virtio-console for the control channel, where vsock is not used for it
+//! - a watchdog the guest-agent pets, for detecting hung guests
 
 pub mod serial;
+pub(crate) mod vhost;
 pub mod virtio_9p;
+pub mod virtio_balloon;
 pub mod virtio_blk;
+pub mod virtio_console;
 pub mod virtio_net;
+pub mod virtio_net_vhost;
+pub mod virtio_rng;
 pub mod virtio_vsock;
 pub mod virtio_vsock_mmio;
 pub mod virtio_vsock_userspace;
 pub mod virtqueue;
 pub mod vsock_backend;
 pub mod vsock_connection;
+pub mod watchdog;
@@ -2,7 +2,13 @@
 //!
 //! Provides a simple serial console using vm-superio's Serial device.
 //! The serial port handles I/O at ports 0x3f8-0x3ff (COM1).
+//!
+//! Host input queued with [`SerialDevice::queue_input`] raises the
+//! received-data interrupt, and the transmitter-empty interrupt fires as
+//! soon as the guest enables it or writes a byte, so an interactive guest
+//! tty works; console `printk`s still poll the line-status register.
 
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
 use tokio::sync::mpsc;
@@ -18,13 +24,15 @@ struct SerialInner {
     /// Output channel for serial data
     output_tx: mpsc::Sender<u8>,
     /// Input buffer (for guest reading)
-    input_buffer: Vec<u8>,
+    input_buffer: VecDeque<u8>,
+    /// Raises the UART's interrupt line; `None` leaves the device polled.
+    interrupt: Option<Arc<dyn Fn() + Send + Sync>>,
+    /// A transmitter-empty interrupt is pending until IIR is read.
+    thr_empty_pending: bool,
     /// Line Status Register
     lsr: u8,
     /// Interrupt Enable Register
     ier: u8,
-    /// Interrupt Identification Register
-    iir: u8,
     /// FIFO Control Register
     fcr: u8,
     /// Line Control Register
@@ -49,6 +57,24 @@ mod lsr {
     pub const TEMT: u8 = 1 << 6;
 }
 
+/// Interrupt Enable Register bits
+mod ier {
+    /// Received data available
+    pub const RDI: u8 = 1 << 0;
+    /// Transmitter holding register empty
+    pub const THRI: u8 = 1 << 1;
+}
+
+/// Interrupt Identification Register values
+mod iir {
+    /// No interrupt pending
+    pub const NONE: u8 = 0x01;
+    /// Transmitter holding register empty
+    pub const THR_EMPTY: u8 = 0x02;
+    /// Received data available
+    pub const RX_DATA: u8 = 0x04;
+}
+
 /// Line Control Register bits
 mod lcr {
     /// Divisor Latch Access Bit
@@ -61,10 +87,11 @@ impl SerialDevice {
         Self {
             inner: Arc::new(Mutex::new(SerialInner {
                 output_tx,
-                input_buffer: Vec::new(),
+                input_buffer: VecDeque::new(),
+                interrupt: None,
+                thr_empty_pending: false,
                 lsr: lsr::THRE | lsr::TEMT, // Transmitter ready
                 ier: 0,
-                iir: 0x01, // No interrupt pending
                 fcr: 0,
                 lcr: 0,
                 mcr: 0,
@@ -75,6 +102,12 @@ impl SerialDevice {
         }
     }
 
+    /// Raise the UART's interrupt line with `interrupt` (edge-triggered).
+    pub fn with_interrupt(self, interrupt: Arc<dyn Fn() + Send + Sync>) -> Self {
+        self.inner.lock().unwrap().interrupt = Some(interrupt);
+        self
+    }
+
     /// Write to a serial port register
     pub fn write(&mut self, offset: u8, value: u8) {
         let mut inner = self.inner.lock().unwrap();
@@ -93,6 +126,9 @@ impl SerialDevice {
 
                     // Send to output channel
                     let _ = inner.output_tx.try_send(value);
+                    // Transmission is instant, so the holding register is
+                    // empty again.
+                    inner.thr_empty();
                 }
             }
             1 => {
@@ -101,7 +137,14 @@ impl SerialDevice {
                     inner.dlh = value;
                 } else {
                     // Interrupt Enable Register
+                    let enabled = value & !inner.ier;
                     inner.ier = value;
+                    if enabled & ier::THRI != 0 {
+                        inner.thr_empty();
+                    }
+                    if enabled & ier::RDI != 0 && !inner.input_buffer.is_empty() {
+                        inner.raise();
+                    }
                 }
             }
             2 => {
@@ -140,7 +183,7 @@ impl SerialDevice {
                     inner.dll
                 } else {
                     // Receive Buffer Register
-                    if let Some(byte) = inner.input_buffer.pop() {
+                    if let Some(byte) = inner.input_buffer.pop_front() {
                         if inner.input_buffer.is_empty() {
                             inner.lsr &= !lsr::DR;
                         }
@@ -160,8 +203,16 @@ impl SerialDevice {
                 }
             }
             2 => {
-                // Interrupt Identification Register
-                inner.iir
+                // Interrupt Identification Register; reading it
+                // acknowledges a transmitter-empty interrupt.
+                if inner.ier & ier::RDI != 0 && !inner.input_buffer.is_empty() {
+                    iir::RX_DATA
+                } else if inner.ier & ier::THRI != 0 && inner.thr_empty_pending {
+                    inner.thr_empty_pending = false;
+                    iir::THR_EMPTY
+                } else {
+                    iir::NONE
+                }
             }
             3 => {
                 // Line Control Register
@@ -193,9 +244,12 @@ impl SerialDevice {
     /// Queue input data for the guest to read
     pub fn queue_input(&self, data: &[u8]) {
         let mut inner = self.inner.lock().unwrap();
-        inner.input_buffer.extend(data.iter().rev()); // Reverse for FIFO behavior
+        inner.input_buffer.extend(data);
         if !inner.input_buffer.is_empty() {
             inner.lsr |= lsr::DR;
+            if inner.ier & ier::RDI != 0 {
+                inner.raise();
+            }
         }
     }
 
@@ -206,6 +260,21 @@ impl SerialDevice {
     }
 }
 
+impl SerialInner {
+    fn raise(&self) {
+        if let Some(interrupt) = &self.interrupt {
+            interrupt();
+        }
+    }
+
+    fn thr_empty(&mut self) {
+        if self.ier & ier::THRI != 0 {
+            self.thr_empty_pending = true;
+            self.raise();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -249,6 +318,48 @@ mod tests {
         // Read it back (FIFO order)
         assert_eq!(serial.read(0), b'h');
         assert_eq!(serial.read(0), b'e');
+
+        // Later input queues behind what is left
+        serial.queue_input(b"!");
+        let rest: Vec<u8> = (0..4).map(|_| serial.read(0)).collect();
+        assert_eq!(rest, b"llo!");
+        assert!(!serial.has_input());
+        assert_eq!(serial.read(5) & lsr::DR, 0);
+    }
+
+    #[test]
+    fn test_serial_interrupts() {
+        let (tx, _rx) = mpsc::channel(16);
+        let raised = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = raised.clone();
+        let mut serial = SerialDevice::new(tx).with_interrupt(Arc::new(move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }));
+        let raised = || raised.load(std::sync::atomic::Ordering::SeqCst);
+
+        // Disabled interrupts stay quiet
+        serial.queue_input(b"a");
+        serial.write(0, b'x');
+        assert_eq!(raised(), 0);
+        assert_eq!(serial.read(2), iir::NONE);
+
+        // Enabling receive interrupts with data waiting raises one
+        serial.write(1, ier::RDI);
+        assert_eq!(raised(), 1);
+        assert_eq!(serial.read(2), iir::RX_DATA);
+        assert_eq!(serial.read(0), b'a');
+        assert_eq!(serial.read(2), iir::NONE);
+
+        // Transmitter-empty fires on enable and after each byte, until acked
+        serial.write(1, ier::RDI | ier::THRI);
+        assert_eq!(raised(), 2);
+        assert_eq!(serial.read(2), iir::THR_EMPTY);
+        assert_eq!(serial.read(2), iir::NONE);
+        serial.write(0, b'y');
+        assert_eq!(raised(), 3);
+        serial.queue_input(b"b");
+        assert_eq!(raised(), 4);
+        assert_eq!(serial.read(2), iir::RX_DATA);
     }
 
     #[test]
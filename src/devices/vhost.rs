@@ -0,0 +1,193 @@
+//! Shared plumbing for kernel vhost backends (vhost-vsock, vhost-net).
+//!
+//! Every vhost driver hands the guest's virtqueues to the kernel the same
+//! way: claim the vhost fd, describe guest memory, then program each vring's
+//! size, ring addresses, base index and kick/call eventfds.
+
+use std::os::unix::io::RawFd;
+
+use tracing::debug;
+use vm_memory::{Address, GuestAddress, GuestMemory, GuestMemoryRegion};
+
+use crate::{Error, Result};
+
+/// vhost ioctl constants (Linux include/uapi/linux/vhost.h).
+/// On x86_64: _IO = 0x0000, _IOW = 0x4000, _IOR = 0x8000, _IOWR = 0xC000 (in upper 16 bits).
+/// Format: direction(2) | size(14) | type(8) | nr(8)
+pub(crate) mod ioctl {
+    use std::os::raw::c_uint;
+    // _IOR(0xAF, 0x00, __u64)
+    pub const VHOST_GET_FEATURES: c_uint = 0x8008_AF00;
+    // _IOW(0xAF, 0x00, __u64)
+    pub const VHOST_SET_FEATURES: c_uint = 0x4008_AF00;
+    // _IO(0xAF, 0x01)
+    pub const VHOST_SET_OWNER: c_uint = 0x0000_AF01;
+    // _IOW(0xAF, 0x03, struct vhost_memory) - sizeof=8
+    pub const VHOST_SET_MEM_TABLE: c_uint = 0x4008_AF03;
+    // _IOW(0xAF, 0x10, struct vhost_vring_state) - sizeof=8
+    pub const VHOST_SET_VRING_NUM: c_uint = 0x4008_AF10;
+    // _IOW(0xAF, 0x11, struct vhost_vring_addr) - sizeof=40
+    pub const VHOST_SET_VRING_ADDR: c_uint = 0x4028_AF11;
+    // _IOW(0xAF, 0x12, struct vhost_vring_state) - sizeof=8
+    pub const VHOST_SET_VRING_BASE: c_uint = 0x4008_AF12;
+    // _IOW(0xAF, 0x20, struct vhost_vring_file) - sizeof=8
+    pub const VHOST_SET_VRING_KICK: c_uint = 0x4008_AF20;
+    // _IOW(0xAF, 0x21, struct vhost_vring_file) - sizeof=8
+    pub const VHOST_SET_VRING_CALL: c_uint = 0x4008_AF21;
+    // _IOW(0xAF, 0x30, struct vhost_vring_file) - sizeof=8
+    pub const VHOST_NET_SET_BACKEND: c_uint = 0x4008_AF30;
+}
+
+#[repr(C)]
+struct VhostMemoryRegion {
+    guest_phys_addr: u64,
+    memory_size: u64,
+    userspace_addr: u64,
+}
+
+#[repr(C)]
+struct VhostVringState {
+    index: u32,
+    num: u32,
+}
+
+#[repr(C)]
+struct VhostVringAddr {
+    index: u32,
+    flags: u32,
+    desc_user_addr: u64,
+    used_user_addr: u64,
+    avail_user_addr: u64,
+    log_guest_addr: u64,
+}
+
+/// `struct vhost_vring_file`: a per-vring fd (kick, call or net backend).
+#[repr(C)]
+pub(crate) struct VhostVringFile {
+    pub index: u32,
+    pub fd: i32,
+}
+
+/// Claim the vhost device for this process. `EBUSY` (already owned by us)
+/// is not an error.
+pub(crate) fn set_owner(fd: RawFd) -> Result<()> {
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_OWNER as _) };
+    if ret < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EBUSY) {
+            return Err(Error::Device(format!("VHOST_SET_OWNER: {}", err)));
+        }
+    }
+    Ok(())
+}
+
+/// Describe every guest memory region to the vhost backend so it can
+/// translate ring and buffer addresses.
+pub(crate) fn set_mem_table(fd: RawFd, guest_memory: &vm_memory::GuestMemoryMmap) -> Result<()> {
+    let nregions = guest_memory.iter().count() as u32;
+    let size = 8 + nregions as usize * std::mem::size_of::<VhostMemoryRegion>();
+    let mut buf = vec![0u8; size];
+    let hdr = buf.as_mut_ptr() as *mut u32;
+    unsafe {
+        *hdr = nregions;
+    }
+    let regions_ptr = unsafe { buf.as_mut_ptr().add(8) as *mut VhostMemoryRegion };
+    for (i, region) in guest_memory.iter().enumerate() {
+        let host_addr = guest_memory
+            .get_host_address(region.start_addr())
+            .map_err(|e| Error::Device(format!("get_host_address: {}", e)))?;
+        let reg = unsafe { &mut *regions_ptr.add(i) };
+        reg.guest_phys_addr = region.start_addr().raw_value();
+        reg.memory_size = region.len();
+        reg.userspace_addr = host_addr as u64;
+    }
+
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_MEM_TABLE as _, buf.as_ptr()) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_MEM_TABLE: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// Program vring `index`: size, ring addresses (guest-physical, translated
+/// here), a zero base index, and its kick/call eventfds.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn set_vring(
+    fd: RawFd,
+    index: u32,
+    num: u32,
+    desc: u64,
+    avail: u64,
+    used: u64,
+    guest_memory: &vm_memory::GuestMemoryMmap,
+    kick_fd: RawFd,
+    call_fd: RawFd,
+) -> Result<()> {
+    let desc_host = guest_memory
+        .get_host_address(GuestAddress(desc))
+        .map_err(|e| Error::Device(format!("desc host addr: {}", e)))? as u64;
+    let avail_host = guest_memory
+        .get_host_address(GuestAddress(avail))
+        .map_err(|e| Error::Device(format!("avail host addr: {}", e)))? as u64;
+    let used_host = guest_memory
+        .get_host_address(GuestAddress(used))
+        .map_err(|e| Error::Device(format!("used host addr: {}", e)))? as u64;
+
+    let state = VhostVringState { index, num };
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_VRING_NUM as _, &state) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_VRING_NUM: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    let addr = VhostVringAddr {
+        index,
+        flags: 0,
+        desc_user_addr: desc_host,
+        used_user_addr: used_host,
+        avail_user_addr: avail_host,
+        log_guest_addr: 0,
+    };
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_VRING_ADDR as _, &addr) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_VRING_ADDR: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    let base_state = VhostVringState { index, num: 0 };
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_VRING_BASE as _, &base_state) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_VRING_BASE: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    let kick_file = VhostVringFile { index, fd: kick_fd };
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_VRING_KICK as _, &kick_file) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_VRING_KICK: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    let call_file = VhostVringFile { index, fd: call_fd };
+    let ret = unsafe { libc::ioctl(fd, ioctl::VHOST_SET_VRING_CALL as _, &call_file) };
+    if ret < 0 {
+        return Err(Error::Device(format!(
+            "VHOST_SET_VRING_CALL: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    debug!("vhost vring {} programmed (num={})", index, num);
+    Ok(())
+}
@@ -0,0 +1,500 @@
+//! virtio-balloon MMIO device (host-driven guest memory reclaim).
+//!
+//! The host sets a target balloon size in 4 KiB pages; the guest driver
+//! allocates that many pages and reports their PFNs on the inflate queue.
+//! The device then drops the backing host pages with `MADV_DONTNEED`, so a
+//! warm sandbox that is idle between runs stops pinning its full
+//! `memory_mb`. Deflating just hands the PFNs back — the next guest touch
+//! faults in a fresh host page.
+
+use tracing::{debug, trace, warn};
+use vm_memory::{Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion};
+
+use crate::devices::virtio_net::mmio;
+use crate::devices::virtqueue::{SplitVirtqueue, VRING_DESC_F_WRITE};
+use crate::vmm::snapshot::{BalloonSnapshotState, QueueSnapshotState};
+
+pub const VIRTIO_BALLOON_DEVICE_TYPE: u32 = 5;
+
+/// Let the guest deflate under memory pressure instead of OOM-killing.
+const VIRTIO_BALLOON_F_DEFLATE_ON_OOM: u64 = 1 << 2;
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// Balloon PFNs are always in 4 KiB units, whatever the guest page size.
+pub const BALLOON_PAGE_SIZE: u64 = 4096;
+
+const QUEUE_MAX_SIZE: u16 = 256;
+const INFLATE_QUEUE: usize = 0;
+const DEFLATE_QUEUE: usize = 1;
+const NUM_QUEUES: usize = 2;
+
+/// Config space: `num_pages` (host target) then `actual` (guest report).
+const CONFIG_NUM_PAGES: u64 = mmio::CONFIG;
+const CONFIG_ACTUAL: u64 = mmio::CONFIG + 4;
+
+/// Interrupt status bit for a configuration change.
+const VIRTIO_MMIO_INT_CONFIG: u32 = 1 << 1;
+
+#[derive(Debug, Default, Clone)]
+struct QueueState {
+    num_max: u16,
+    num: u16,
+    ready: bool,
+    desc_addr: u64,
+    driver_addr: u64,
+    device_addr: u64,
+    avail_idx: u16,
+    used_idx: u16,
+}
+
+pub struct VirtioBalloonDevice {
+    mmio_base: u64,
+    device_features_sel: u32,
+    driver_features: u64,
+    driver_features_sel: u32,
+    queue_sel: u32,
+    queues: [QueueState; NUM_QUEUES],
+    interrupt_status: u32,
+    status: u32,
+    config_generation: u32,
+    /// Target balloon size requested by the host.
+    num_pages: u32,
+    /// Balloon size the guest driver reports having reached.
+    actual: u32,
+}
+
+impl Default for VirtioBalloonDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtioBalloonDevice {
+    pub fn new() -> Self {
+        let queue = QueueState {
+            num_max: QUEUE_MAX_SIZE,
+            ..Default::default()
+        };
+        Self {
+            mmio_base: 0,
+            device_features_sel: 0,
+            driver_features: 0,
+            driver_features_sel: 0,
+            queue_sel: 0,
+            queues: [queue.clone(), queue],
+            interrupt_status: 0,
+            status: 0,
+            config_generation: 0,
+            num_pages: 0,
+            actual: 0,
+        }
+    }
+
+    pub fn set_mmio_base(&mut self, base: u64) {
+        self.mmio_base = base;
+        debug!("virtio-balloon MMIO base set to {:#x}", base);
+    }
+
+    pub fn mmio_base(&self) -> u64 {
+        self.mmio_base
+    }
+
+    pub fn mmio_size(&self) -> u64 {
+        0x200
+    }
+
+    pub fn handles_mmio(&self, addr: u64) -> bool {
+        addr >= self.mmio_base && addr < self.mmio_base + self.mmio_size()
+    }
+
+    pub fn has_pending_interrupt(&self) -> bool {
+        self.interrupt_status != 0
+    }
+
+    /// Ask the guest to grow or shrink the balloon to `pages` 4 KiB pages.
+    ///
+    /// Raises a config-change interrupt; the caller is responsible for
+    /// injecting it, since the vCPUs may be halted.
+    pub fn set_target_pages(&mut self, pages: u32) {
+        if pages == self.num_pages {
+            return;
+        }
+        self.num_pages = pages;
+        self.config_generation = self.config_generation.wrapping_add(1);
+        self.interrupt_status |= VIRTIO_MMIO_INT_CONFIG;
+        debug!("virtio-balloon: target set to {} pages", pages);
+    }
+
+    /// Balloon size the host last requested, in 4 KiB pages.
+    pub fn target_pages(&self) -> u32 {
+        self.num_pages
+    }
+
+    /// Balloon size the guest reports holding, in 4 KiB pages.
+    pub fn actual_pages(&self) -> u32 {
+        self.actual
+    }
+
+    pub fn mmio_read(&self, offset: u64, data: &mut [u8]) {
+        let value: u32 = match offset {
+            mmio::MAGIC_VALUE => mmio::MAGIC,
+            mmio::VERSION => mmio::VERSION_2,
+            mmio::DEVICE_ID => VIRTIO_BALLOON_DEVICE_TYPE,
+            mmio::VENDOR_ID => 0x554d4551,
+            mmio::DEVICE_FEATURES => {
+                let features = VIRTIO_F_VERSION_1 | VIRTIO_BALLOON_F_DEFLATE_ON_OOM;
+                if self.device_features_sel == 0 {
+                    features as u32
+                } else {
+                    (features >> 32) as u32
+                }
+            }
+            mmio::QUEUE_NUM_MAX => self.selected_queue().map(|q| q.num_max as u32).unwrap_or(0),
+            mmio::QUEUE_READY => self.selected_queue().map(|q| q.ready as u32).unwrap_or(0),
+            mmio::INTERRUPT_STATUS => self.interrupt_status,
+            mmio::STATUS => self.status,
+            mmio::CONFIG_GENERATION => self.config_generation,
+            CONFIG_NUM_PAGES => self.num_pages,
+            CONFIG_ACTUAL => self.actual,
+            _ => {
+                trace!(
+                    "virtio-balloon: unhandled MMIO read at offset {:#x}",
+                    offset
+                );
+                0
+            }
+        };
+
+        let bytes = value.to_le_bytes();
+        let len = data.len().min(4);
+        data[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    pub fn mmio_write(&mut self, offset: u64, data: &[u8], guest_mem: Option<&GuestMemoryMmap>) {
+        if data.is_empty() {
+            return;
+        }
+        let mut bytes = [0u8; 4];
+        let len = data.len().min(4);
+        bytes[..len].copy_from_slice(&data[..len]);
+        let value = u32::from_le_bytes(bytes);
+
+        match offset {
+            mmio::DEVICE_FEATURES_SEL => self.device_features_sel = value,
+            mmio::DRIVER_FEATURES => {
+                if self.driver_features_sel == 0 {
+                    self.driver_features =
+                        (self.driver_features & 0xFFFF_FFFF_0000_0000) | value as u64;
+                } else {
+                    self.driver_features =
+                        (self.driver_features & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+                }
+            }
+            mmio::DRIVER_FEATURES_SEL => self.driver_features_sel = value,
+            mmio::QUEUE_SEL => self.queue_sel = value,
+            mmio::QUEUE_NOTIFY => {
+                if let Some(mem) = guest_mem {
+                    match value as usize {
+                        INFLATE_QUEUE => self.process_queue(INFLATE_QUEUE, mem),
+                        DEFLATE_QUEUE => self.process_queue(DEFLATE_QUEUE, mem),
+                        _ => {}
+                    }
+                }
+            }
+            mmio::INTERRUPT_ACK => self.interrupt_status &= !value,
+            mmio::STATUS => {
+                self.status = value;
+                if value == 0 {
+                    self.reset();
+                }
+            }
+            CONFIG_ACTUAL => {
+                self.actual = value;
+                debug!("virtio-balloon: guest reports {} pages", value);
+            }
+            _ => {
+                let Some(q) = self.selected_queue_mut() else {
+                    trace!(
+                        "virtio-balloon: write at offset {:#x} to invalid queue",
+                        offset
+                    );
+                    return;
+                };
+                match offset {
+                    mmio::QUEUE_NUM => q.num = value as u16,
+                    mmio::QUEUE_READY => q.ready = value != 0,
+                    mmio::QUEUE_DESC_LOW => {
+                        q.desc_addr = (q.desc_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DESC_HIGH => {
+                        q.desc_addr = (q.desc_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    mmio::QUEUE_DRIVER_LOW => {
+                        q.driver_addr = (q.driver_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DRIVER_HIGH => {
+                        q.driver_addr =
+                            (q.driver_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    mmio::QUEUE_DEVICE_LOW => {
+                        q.device_addr = (q.device_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DEVICE_HIGH => {
+                        q.device_addr =
+                            (q.device_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    _ => {
+                        trace!(
+                            "virtio-balloon: unhandled MMIO write at offset {:#x}, value={:#x}",
+                            offset,
+                            value
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn selected_queue(&self) -> Option<&QueueState> {
+        self.queues.get(self.queue_sel as usize)
+    }
+
+    fn selected_queue_mut(&mut self) -> Option<&mut QueueState> {
+        self.queues.get_mut(self.queue_sel as usize)
+    }
+
+    fn reset(&mut self) {
+        // The host's target survives a driver reset: a rebooting guest
+        // should re-inflate to it.
+        *self = Self {
+            mmio_base: self.mmio_base,
+            num_pages: self.num_pages,
+            ..Self::new()
+        };
+    }
+
+    /// Drain the inflate or deflate queue. Each buffer is an array of
+    /// little-endian u32 PFNs.
+    fn process_queue(&mut self, index: usize, mem: &GuestMemoryMmap) {
+        let q = &self.queues[index];
+        if !q.ready || q.num == 0 {
+            return;
+        }
+        let mut vq = SplitVirtqueue::new(q.num, q.desc_addr, q.driver_addr, q.device_addr, -1, -1);
+        vq.last_avail_idx = q.avail_idx;
+        vq.last_used_idx = q.used_idx;
+
+        let mut completed = false;
+        while let Some(chain) = vq.pop_avail(mem) {
+            if index == INFLATE_QUEUE {
+                for desc in &chain.descriptors {
+                    if desc.flags & VRING_DESC_F_WRITE != 0 {
+                        continue;
+                    }
+                    discard_pfns(mem, desc.addr, desc.len);
+                }
+            }
+            vq.push_used(mem, chain.head_index, 0);
+            completed = true;
+        }
+
+        let q = &mut self.queues[index];
+        q.avail_idx = vq.last_avail_idx;
+        q.used_idx = vq.last_used_idx;
+        if completed {
+            self.interrupt_status |= 1;
+        }
+    }
+
+    /// Capture device state for snapshot.
+    pub fn snapshot_state(&self) -> BalloonSnapshotState {
+        BalloonSnapshotState {
+            driver_features: self.driver_features,
+            status: self.status,
+            interrupt_status: self.interrupt_status,
+            config_generation: self.config_generation,
+            num_pages: self.num_pages,
+            actual: self.actual,
+            queues: self
+                .queues
+                .iter()
+                .map(|q| QueueSnapshotState {
+                    num_max: q.num_max,
+                    num: q.num,
+                    ready: q.ready,
+                    desc_addr: q.desc_addr,
+                    driver_addr: q.driver_addr,
+                    device_addr: q.device_addr,
+                    last_avail_idx: Some(q.avail_idx),
+                    last_used_idx: Some(q.used_idx),
+                })
+                .collect(),
+        }
+    }
+
+    /// Restore device state from a snapshot.
+    pub fn restore_state(&mut self, state: &BalloonSnapshotState) {
+        self.driver_features = state.driver_features;
+        self.status = state.status;
+        self.interrupt_status = state.interrupt_status;
+        self.config_generation = state.config_generation;
+        self.num_pages = state.num_pages;
+        self.actual = state.actual;
+        for (q, s) in self.queues.iter_mut().zip(&state.queues) {
+            *q = QueueState {
+                num_max: s.num_max,
+                num: s.num,
+                ready: s.ready,
+                desc_addr: s.desc_addr,
+                driver_addr: s.driver_addr,
+                device_addr: s.device_addr,
+                avail_idx: s.last_avail_idx.unwrap_or(0),
+                used_idx: s.last_used_idx.unwrap_or(0),
+            };
+        }
+    }
+}
+
+/// Release the host pages behind a buffer of PFNs, coalescing adjacent
+/// PFNs into one `madvise` call.
+fn discard_pfns(mem: &GuestMemoryMmap, addr: u64, len: u32) {
+    let mut run: Option<(u64, u64)> = None;
+    for i in 0..(len / 4) as u64 {
+        let pfn: u32 = match mem.read_obj(GuestAddress(addr + i * 4)) {
+            Ok(pfn) => pfn,
+            Err(_) => {
+                warn!(
+                    "virtio-balloon: PFN array at {:#x} outside guest memory",
+                    addr
+                );
+                break;
+            }
+        };
+        let gpa = u64::from(pfn) * BALLOON_PAGE_SIZE;
+        run = match run {
+            Some((start, n)) if start + n * BALLOON_PAGE_SIZE == gpa => Some((start, n + 1)),
+            Some((start, n)) => {
+                discard_range(mem, start, n * BALLOON_PAGE_SIZE);
+                Some((gpa, 1))
+            }
+            None => Some((gpa, 1)),
+        };
+    }
+    if let Some((start, n)) = run {
+        discard_range(mem, start, n * BALLOON_PAGE_SIZE);
+    }
+}
+
+/// `MADV_DONTNEED` the host mapping of `[gpa, gpa + len)`, shrunk to host
+/// page boundaries (a 64 KiB-page host cannot drop a lone 4 KiB page).
+fn discard_range(mem: &GuestMemoryMmap, gpa: u64, len: u64) {
+    // SAFETY: sysconf has no preconditions.
+    let host_page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    let start = gpa.next_multiple_of(host_page);
+    let end = (gpa + len) / host_page * host_page;
+    if end <= start {
+        return;
+    }
+    // The range must lie within one region so the host mapping is contiguous.
+    if mem.find_region(GuestAddress(start)).map(|r| r.start_addr())
+        != mem
+            .find_region(GuestAddress(end - 1))
+            .map(|r| r.start_addr())
+    {
+        warn!(
+            "virtio-balloon: range {:#x}+{:#x} spans regions",
+            start,
+            end - start
+        );
+        return;
+    }
+    let Ok(host) = mem.get_host_address(GuestAddress(start)) else {
+        warn!(
+            "virtio-balloon: PFN {:#x} outside guest memory",
+            start / BALLOON_PAGE_SIZE
+        );
+        return;
+    };
+    // SAFETY: `host` points into the guest memory mapping and the range was
+    // checked to lie within a single region. The guest gave these pages up,
+    // so dropping their contents is what it asked for.
+    let ret = unsafe {
+        libc::madvise(
+            host as *mut libc::c_void,
+            (end - start) as usize,
+            libc::MADV_DONTNEED,
+        )
+    };
+    if ret != 0 {
+        warn!(
+            "virtio-balloon: madvise failed: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESC: u64 = 0x1000;
+    const AVAIL: u64 = 0x2000;
+    const USED: u64 = 0x3000;
+    const PFNS: u64 = 0x4000;
+    const PAGE: u64 = 0x10_0000;
+
+    fn write_reg(dev: &mut VirtioBalloonDevice, offset: u64, value: u32, mem: &GuestMemoryMmap) {
+        dev.mmio_write(offset, &value.to_le_bytes(), Some(mem));
+    }
+
+    fn read_reg(dev: &VirtioBalloonDevice, offset: u64) -> u32 {
+        let mut buf = [0u8; 4];
+        dev.mmio_read(offset, &mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    #[test]
+    fn test_inflate_discards_reported_pages() {
+        let mem = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 4 * 1024 * 1024)]).unwrap();
+        let mut dev = VirtioBalloonDevice::new();
+
+        dev.set_target_pages(2);
+        assert!(dev.has_pending_interrupt());
+        assert_eq!(read_reg(&dev, CONFIG_NUM_PAGES), 2);
+        write_reg(&mut dev, mmio::INTERRUPT_ACK, VIRTIO_MMIO_INT_CONFIG, &mem);
+
+        write_reg(&mut dev, mmio::QUEUE_SEL, INFLATE_QUEUE as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_NUM, 8, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DESC_LOW, DESC as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DRIVER_LOW, AVAIL as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DEVICE_LOW, USED as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_READY, 1, &mem);
+
+        // Dirty the two pages the guest is about to give up.
+        mem.write_slice(&[0xAA; 8192], GuestAddress(PAGE)).unwrap();
+        let first = (PAGE / BALLOON_PAGE_SIZE) as u32;
+        mem.write_obj(first, GuestAddress(PFNS)).unwrap();
+        mem.write_obj(first + 1, GuestAddress(PFNS + 4)).unwrap();
+        mem.write_obj(PFNS, GuestAddress(DESC)).unwrap();
+        mem.write_obj(8u32, GuestAddress(DESC + 8)).unwrap();
+        mem.write_obj(0u16, GuestAddress(AVAIL + 4)).unwrap();
+        mem.write_obj(1u16, GuestAddress(AVAIL + 2)).unwrap();
+        write_reg(&mut dev, mmio::QUEUE_NOTIFY, INFLATE_QUEUE as u32, &mem);
+
+        let used_idx: u16 = mem.read_obj(GuestAddress(USED + 2)).unwrap();
+        assert_eq!(used_idx, 1);
+        assert!(dev.has_pending_interrupt());
+        let mut page = [0xFFu8; 8192];
+        mem.read_slice(&mut page, GuestAddress(PAGE)).unwrap();
+        assert_eq!(page, [0u8; 8192], "anonymous pages read back zeroed");
+
+        write_reg(&mut dev, CONFIG_ACTUAL, 2, &mem);
+        assert_eq!(dev.actual_pages(), 2);
+
+        let mut restored = VirtioBalloonDevice::new();
+        restored.restore_state(&dev.snapshot_state());
+        assert_eq!(restored.target_pages(), 2);
+        assert_eq!(restored.actual_pages(), 2);
+        assert_eq!(restored.queues[INFLATE_QUEUE].used_idx, 1);
+    }
+}
@@ -1,9 +1,10 @@
-//! Minimal virtio-blk MMIO device (read-only raw file backend).
+//! Minimal virtio-blk MMIO device (raw file backend).
 //!
-//! This device is used to present OCI rootfs disk artifacts as a block device
-//! to the guest on Linux/KVM.
+//! This device presents OCI rootfs disk artifacts (read-only) and data disks
+//! — scratch images and caller-attached files, optionally writable — as block
+//! devices to the guest on Linux/KVM.
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::os::unix::fs::FileExt;
 use std::path::Path;
 
@@ -19,12 +20,14 @@ const VIRTQ_DESC_F_WRITE: u16 = 2;
 
 const VIRTIO_F_VERSION_1: u64 = 1 << 32;
 const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
 
 const QUEUE_MAX_SIZE: u16 = 128;
 const SECTOR_SIZE: u64 = 512;
 
 const VIRTIO_BLK_T_IN: u32 = 0;
 const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
 const VIRTIO_BLK_S_OK: u8 = 0;
 const VIRTIO_BLK_S_IOERR: u8 = 1;
 const VIRTIO_BLK_S_UNSUPP: u8 = 2;
@@ -51,14 +54,26 @@ pub struct VirtioBlkDevice {
     avail_idx: u16,
     used_idx: u16,
     disk: File,
+    read_only: bool,
     capacity_sectors: u64,
 }
 
 impl VirtioBlkDevice {
+    /// Open `path` as a read-only disk.
     pub fn new(path: &Path) -> crate::Result<Self> {
-        let disk = File::open(path).map_err(|e| {
-            crate::Error::Device(format!("virtio-blk open {}: {}", path.display(), e))
-        })?;
+        Self::open(path, true)
+    }
+
+    /// Open `path` as a disk; unless `read_only`, guest writes and flushes
+    /// go through to the file.
+    pub fn open(path: &Path, read_only: bool) -> crate::Result<Self> {
+        let disk = OpenOptions::new()
+            .read(true)
+            .write(!read_only)
+            .open(path)
+            .map_err(|e| {
+                crate::Error::Device(format!("virtio-blk open {}: {}", path.display(), e))
+            })?;
         let size = disk
             .metadata()
             .map_err(|e| {
@@ -68,10 +83,11 @@ impl VirtioBlkDevice {
         let capacity_sectors = size / SECTOR_SIZE;
 
         debug!(
-            "Creating virtio-blk device: path={}, size={} bytes, sectors={}",
+            "Creating virtio-blk device: path={}, size={} bytes, sectors={}, ro={}",
             path.display(),
             size,
-            capacity_sectors
+            capacity_sectors,
+            read_only
         );
 
         Ok(Self {
@@ -89,12 +105,17 @@ impl VirtioBlkDevice {
             avail_idx: 0,
             used_idx: 0,
             disk,
+            read_only,
             capacity_sectors,
         })
     }
 
     fn device_features(&self) -> u64 {
-        VIRTIO_F_VERSION_1 | VIRTIO_BLK_F_RO
+        if self.read_only {
+            VIRTIO_F_VERSION_1 | VIRTIO_BLK_F_RO
+        } else {
+            VIRTIO_F_VERSION_1 | VIRTIO_BLK_F_FLUSH
+        }
     }
 
     pub fn set_mmio_base(&mut self, base: u64) {
@@ -330,6 +351,7 @@ impl VirtioBlkDevice {
             }
         }
 
+        // A flush is header + status; reads and writes carry data between.
         if descs.len() < 2 {
             return Ok((VIRTIO_BLK_S_IOERR, 0));
         }
@@ -355,7 +377,7 @@ impl VirtioBlkDevice {
         let mut total_written = 0usize;
 
         let status = match req_type {
-            VIRTIO_BLK_T_IN => {
+            VIRTIO_BLK_T_IN => 'read: {
                 trace!(
                     "virtio-blk: READ request sector={} descs={}",
                     sector,
@@ -364,7 +386,7 @@ impl VirtioBlkDevice {
                 let mut file_off = offset;
                 for d in data_descs {
                     if (d.flags & VIRTQ_DESC_F_WRITE) == 0 {
-                        return Ok((VIRTIO_BLK_S_IOERR, total_written));
+                        break 'read VIRTIO_BLK_S_IOERR;
                     }
                     let mut buf = vec![0u8; d.len as usize];
                     let mut n = 0usize;
@@ -376,7 +398,7 @@ impl VirtioBlkDevice {
                             Ok(0) => break, // EOF: keep remaining bytes zero-filled
                             Ok(read_now) => n += read_now,
                             Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
-                            Err(_) => return Ok((VIRTIO_BLK_S_IOERR, total_written)),
+                            Err(_) => break 'read VIRTIO_BLK_S_IOERR,
                         }
                     }
                     if n < buf.len() {
@@ -391,14 +413,45 @@ impl VirtioBlkDevice {
                 }
                 VIRTIO_BLK_S_OK
             }
-            VIRTIO_BLK_T_OUT => {
-                // Read-only backend
+            VIRTIO_BLK_T_OUT if self.read_only => {
                 warn!(
                     "virtio-blk: rejecting write request sector={} (ro backend)",
                     sector
                 );
-                VIRTIO_BLK_S_UNSUPP
+                VIRTIO_BLK_S_IOERR
+            }
+            VIRTIO_BLK_T_OUT => 'write: {
+                trace!(
+                    "virtio-blk: WRITE request sector={} descs={}",
+                    sector,
+                    data_descs.len()
+                );
+                let mut file_off = offset;
+                for d in data_descs {
+                    // Writes never grow the backing file past its capacity.
+                    let end = file_off.saturating_add(d.len as u64);
+                    if (d.flags & VIRTQ_DESC_F_WRITE) != 0
+                        || end > self.capacity_sectors * SECTOR_SIZE
+                    {
+                        break 'write VIRTIO_BLK_S_IOERR;
+                    }
+                    let mut buf = vec![0u8; d.len as usize];
+                    mem.read(&mut buf, GuestAddress(d.addr))
+                        .map_err(|e| crate::Error::Memory(e.to_string()))?;
+                    if self.disk.write_all_at(&buf, file_off).is_err() {
+                        break 'write VIRTIO_BLK_S_IOERR;
+                    }
+                    file_off = end;
+                }
+                VIRTIO_BLK_S_OK
             }
+            VIRTIO_BLK_T_FLUSH if !self.read_only => match self.disk.sync_data() {
+                Ok(()) => VIRTIO_BLK_S_OK,
+                Err(e) => {
+                    warn!("virtio-blk: flush failed: {}", e);
+                    VIRTIO_BLK_S_IOERR
+                }
+            },
             _ => VIRTIO_BLK_S_UNSUPP,
         };
 
@@ -409,3 +462,131 @@ impl VirtioBlkDevice {
         Ok((status, total_written))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESC: u64 = 0x1000;
+    const AVAIL: u64 = 0x2000;
+    const USED: u64 = 0x3000;
+    const HDR: u64 = 0x4000;
+    const DATA: u64 = 0x5000;
+    const STATUS: u64 = 0x6000;
+
+    fn setup_test_memory() -> GuestMemoryMmap {
+        GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 1024 * 1024)]).unwrap()
+    }
+
+    fn write_reg(dev: &mut VirtioBlkDevice, offset: u64, value: u32) {
+        dev.mmio_write(offset, &value.to_le_bytes(), None);
+    }
+
+    fn ready_queue(dev: &mut VirtioBlkDevice) {
+        write_reg(dev, mmio::QUEUE_NUM, 8);
+        write_reg(dev, mmio::QUEUE_DESC_LOW, DESC as u32);
+        write_reg(dev, mmio::QUEUE_DRIVER_LOW, AVAIL as u32);
+        write_reg(dev, mmio::QUEUE_DEVICE_LOW, USED as u32);
+        write_reg(dev, mmio::QUEUE_READY, 1);
+    }
+
+    fn write_desc(mem: &GuestMemoryMmap, idx: u64, addr: u64, len: u32, flags: u16, next: u16) {
+        let base = GuestAddress(DESC + idx * 16);
+        mem.write_obj(addr, base).unwrap();
+        mem.write_obj(len, base.unchecked_add(8)).unwrap();
+        mem.write_obj(flags, base.unchecked_add(12)).unwrap();
+        mem.write_obj(next, base.unchecked_add(14)).unwrap();
+    }
+
+    /// Queue one request (header, optional one-sector data buffer, status)
+    /// as the `n`th avail entry, notify, and return the status byte.
+    fn submit(
+        dev: &mut VirtioBlkDevice,
+        mem: &GuestMemoryMmap,
+        n: u16,
+        req_type: u32,
+        sector: u64,
+        data_flags: Option<u16>,
+    ) -> u8 {
+        mem.write_obj(req_type, GuestAddress(HDR)).unwrap();
+        mem.write_obj(0u32, GuestAddress(HDR + 4)).unwrap();
+        mem.write_obj(sector, GuestAddress(HDR + 8)).unwrap();
+        write_desc(mem, 0, HDR, 16, VIRTQ_DESC_F_NEXT, 1);
+        match data_flags {
+            Some(flags) => {
+                write_desc(mem, 1, DATA, 512, flags | VIRTQ_DESC_F_NEXT, 2);
+                write_desc(mem, 2, STATUS, 1, VIRTQ_DESC_F_WRITE, 0);
+            }
+            None => write_desc(mem, 1, STATUS, 1, VIRTQ_DESC_F_WRITE, 0),
+        }
+        mem.write_obj(0u16, GuestAddress(AVAIL + 4 + u64::from(n) * 2))
+            .unwrap();
+        mem.write_obj(n + 1, GuestAddress(AVAIL + 2)).unwrap();
+        dev.mmio_write(mmio::QUEUE_NOTIFY, &0u32.to_le_bytes(), Some(mem));
+        mem.read_obj(GuestAddress(STATUS)).unwrap()
+    }
+
+    #[test]
+    fn test_writable_disk_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        file.as_file().set_len(8 * SECTOR_SIZE).unwrap();
+        let mut dev = VirtioBlkDevice::open(file.path(), false).unwrap();
+        assert_eq!(dev.device_features() & VIRTIO_BLK_F_RO, 0);
+        assert_ne!(dev.device_features() & VIRTIO_BLK_F_FLUSH, 0);
+
+        let mem = setup_test_memory();
+        ready_queue(&mut dev);
+
+        mem.write_slice(&[0xab; 512], GuestAddress(DATA)).unwrap();
+        let status = submit(&mut dev, &mem, 0, VIRTIO_BLK_T_OUT, 3, Some(0));
+        assert_eq!(status, VIRTIO_BLK_S_OK);
+        assert!(dev.has_pending_interrupt());
+
+        let status = submit(&mut dev, &mem, 1, VIRTIO_BLK_T_FLUSH, 0, None);
+        assert_eq!(status, VIRTIO_BLK_S_OK);
+
+        let mut on_disk = [0u8; 512];
+        file.as_file()
+            .read_exact_at(&mut on_disk, 3 * SECTOR_SIZE)
+            .unwrap();
+        assert_eq!(on_disk, [0xab; 512]);
+
+        mem.write_slice(&[0u8; 512], GuestAddress(DATA)).unwrap();
+        let status = submit(
+            &mut dev,
+            &mem,
+            2,
+            VIRTIO_BLK_T_IN,
+            3,
+            Some(VIRTQ_DESC_F_WRITE),
+        );
+        assert_eq!(status, VIRTIO_BLK_S_OK);
+        let mut read_back = [0u8; 512];
+        mem.read_slice(&mut read_back, GuestAddress(DATA)).unwrap();
+        assert_eq!(read_back, [0xab; 512]);
+
+        // Past the last sector: rejected rather than growing the file.
+        let status = submit(&mut dev, &mem, 3, VIRTIO_BLK_T_OUT, 8, Some(0));
+        assert_eq!(status, VIRTIO_BLK_S_IOERR);
+        assert_eq!(file.as_file().metadata().unwrap().len(), 8 * SECTOR_SIZE);
+    }
+
+    #[test]
+    fn test_read_only_disk_rejects_writes() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        file.as_file().set_len(8 * SECTOR_SIZE).unwrap();
+        let mut dev = VirtioBlkDevice::new(file.path()).unwrap();
+        assert_ne!(dev.device_features() & VIRTIO_BLK_F_RO, 0);
+
+        let mem = setup_test_memory();
+        ready_queue(&mut dev);
+
+        mem.write_slice(&[0xab; 512], GuestAddress(DATA)).unwrap();
+        let status = submit(&mut dev, &mem, 0, VIRTIO_BLK_T_OUT, 0, Some(0));
+        assert_eq!(status, VIRTIO_BLK_S_IOERR);
+
+        let mut on_disk = [0u8; 512];
+        file.as_file().read_exact_at(&mut on_disk, 0).unwrap();
+        assert_eq!(on_disk, [0u8; 512]);
+    }
+}
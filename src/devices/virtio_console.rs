@@ -0,0 +1,995 @@
+//! virtio-console MMIO device carrying the guest-agent control channel.
+//!
+//! An alternative to vsock for hypervisors that have none (see RFC-0004).
+//! The device negotiates `VIRTIO_CONSOLE_F_MULTIPORT` and adds a single
+//! named port, [`CONTROL_PORT_NAME`], which the guest-agent serves when the
+//! cmdline carries [`CONTROL_CMDLINE_ARG`]. Port 0 (the console port) is
+//! never added, so the guest gets no `hvc` console from it.
+//!
+//! The host end of the port is one half of a Unix socket pair handed out by
+//! [`connector`]. A serial port has no connect/accept, so each new host
+//! connection is announced to the guest by closing the port
+//! (`PORT_OPEN 0`) and reopening it (`PORT_OPEN 1`): the guest-agent reads
+//! end-of-file on the old connection, closes the port and opens it again
+//! with a fresh, unauthenticated session.
+//!
+//! [`CONTROL_PORT_NAME`]: void_box_protocol::CONTROL_PORT_NAME
+//! [`CONTROL_CMDLINE_ARG`]: void_box_protocol::CONTROL_CMDLINE_ARG
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, trace, warn};
+use vm_memory::{Bytes, GuestAddress, GuestMemoryMmap};
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::backend::control_channel::{GuestConnector, GuestStream};
+use crate::devices::virtio_net::mmio;
+use crate::devices::virtqueue::{SplitVirtqueue, VirtqDesc, VRING_DESC_F_WRITE};
+use crate::{Error, Result};
+
+pub const VIRTIO_CONSOLE_DEVICE_TYPE: u32 = 3;
+
+const VIRTIO_CONSOLE_F_MULTIPORT: u64 = 1 << 1;
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+const QUEUE_MAX_SIZE: u16 = 128;
+/// Queues 0 and 1 belong to port 0, which is never added.
+const CONTROL_RECEIVEQ: usize = 2;
+const CONTROL_TRANSMITQ: usize = 3;
+const PORT_RECEIVEQ: usize = 4;
+const PORT_TRANSMITQ: usize = 5;
+const NUM_QUEUES: usize = 6;
+
+/// Port 0 plus the control port.
+const MAX_NR_PORTS: u32 = 2;
+const CONTROL_PORT_ID: u32 = 1;
+
+/// Config space: `cols` and `rows` (u16 each), `max_nr_ports`, `emerg_wr`.
+const CONFIG_MAX_NR_PORTS: u64 = mmio::CONFIG + 4;
+
+// Control message events (`struct virtio_console_control`).
+const DEVICE_READY: u16 = 0;
+const DEVICE_ADD: u16 = 1;
+const PORT_READY: u16 = 3;
+const PORT_OPEN: u16 = 6;
+const PORT_NAME: u16 = 7;
+
+/// `{ id: u32, event: u16, value: u16 }`, little-endian.
+const CONTROL_MSG_LEN: usize = 8;
+
+/// Upper bound on bytes taken from one guest descriptor, so a guest-chosen
+/// length cannot make the host allocate without limit.
+const MAX_DESC_LEN: usize = 64 * 1024;
+
+/// Guest output buffered for a host that is not reading; past this the
+/// device stops taking buffers off the transmit queue.
+const MAX_TX_PENDING: usize = 256 * 1024;
+
+/// How long a reconnect waits for the guest-agent to close the port after
+/// the previous connection was dropped.
+const GUEST_CLOSE_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default, Clone)]
+struct QueueState {
+    num_max: u16,
+    num: u16,
+    ready: bool,
+    desc_addr: u64,
+    driver_addr: u64,
+    device_addr: u64,
+    avail_idx: u16,
+    used_idx: u16,
+}
+
+impl QueueState {
+    fn virtqueue(&self) -> Option<SplitVirtqueue> {
+        if !self.ready || self.num == 0 {
+            return None;
+        }
+        let mut vq = SplitVirtqueue::new(
+            self.num,
+            self.desc_addr,
+            self.driver_addr,
+            self.device_addr,
+            -1,
+            -1,
+        );
+        vq.last_avail_idx = self.avail_idx;
+        vq.last_used_idx = self.used_idx;
+        Some(vq)
+    }
+
+    fn save(&mut self, vq: &SplitVirtqueue) {
+        self.avail_idx = vq.last_avail_idx;
+        self.used_idx = vq.last_used_idx;
+    }
+}
+
+pub struct VirtioConsoleDevice {
+    mmio_base: u64,
+    device_features_sel: u32,
+    driver_features: u64,
+    driver_features_sel: u32,
+    queue_sel: u32,
+    queues: [QueueState; NUM_QUEUES],
+    interrupt_status: u32,
+    /// Set with every new interrupt until [`take_interrupt`](Self::take_interrupt).
+    interrupt_raised: bool,
+    status: u32,
+    /// Control messages waiting for a buffer on the control receive queue.
+    control_out: VecDeque<Vec<u8>>,
+    /// The guest driver has set up the control port.
+    port_ready: bool,
+    /// The guest has the control port open.
+    guest_open: bool,
+    /// The guest closed the port since the host connection last changed.
+    guest_closed: bool,
+    /// The guest still has the port open from before the host connection
+    /// last changed. Its traffic belongs to the old connection: a writer
+    /// blocked on the closed port would otherwise flush old frames into
+    /// the new one.
+    stale_session: bool,
+    /// Device end of the current host connection (non-blocking).
+    host: Option<UnixStream>,
+    /// Host bytes read but not yet placed in guest buffers.
+    rx_pending: Vec<u8>,
+    /// Guest bytes the host socket has not accepted yet.
+    tx_pending: Vec<u8>,
+    /// Wakes the I/O thread when the host connection changes. Shared so a
+    /// reset keeps the descriptor the thread is polling.
+    wake: Arc<EventFd>,
+}
+
+impl VirtioConsoleDevice {
+    pub fn new() -> Result<Self> {
+        let wake = EventFd::new(libc::EFD_NONBLOCK)
+            .map_err(|e| Error::Device(format!("virtio-console: eventfd: {e}")))?;
+        Ok(Self::with_wake(Arc::new(wake)))
+    }
+
+    fn with_wake(wake: Arc<EventFd>) -> Self {
+        let queue = QueueState {
+            num_max: QUEUE_MAX_SIZE,
+            ..Default::default()
+        };
+        Self {
+            mmio_base: 0,
+            device_features_sel: 0,
+            driver_features: 0,
+            driver_features_sel: 0,
+            queue_sel: 0,
+            queues: std::array::from_fn(|_| queue.clone()),
+            interrupt_status: 0,
+            interrupt_raised: false,
+            status: 0,
+            control_out: VecDeque::new(),
+            port_ready: false,
+            guest_open: false,
+            guest_closed: false,
+            stale_session: false,
+            host: None,
+            rx_pending: Vec::new(),
+            tx_pending: Vec::new(),
+            wake,
+        }
+    }
+
+    pub fn set_mmio_base(&mut self, base: u64) {
+        self.mmio_base = base;
+        debug!("virtio-console MMIO base set to {:#x}", base);
+    }
+
+    pub fn mmio_base(&self) -> u64 {
+        self.mmio_base
+    }
+
+    pub fn mmio_size(&self) -> u64 {
+        0x200
+    }
+
+    pub fn handles_mmio(&self, addr: u64) -> bool {
+        addr >= self.mmio_base && addr < self.mmio_base + self.mmio_size()
+    }
+
+    pub fn has_pending_interrupt(&self) -> bool {
+        self.interrupt_status != 0
+    }
+
+    /// Whether an interrupt was raised since the last call. Unlike
+    /// [`has_pending_interrupt`](Self::has_pending_interrupt) this also
+    /// reports an interrupt raised while an earlier one is still unacked.
+    pub fn take_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.interrupt_raised)
+    }
+
+    /// Eventfd signalled when the I/O thread should call
+    /// [`process_host_io`](Self::process_host_io) outside of a host socket
+    /// event.
+    pub fn wake_fd(&self) -> RawFd {
+        self.wake.as_raw_fd()
+    }
+
+    /// The host socket and the `poll(2)` events the device is waiting for
+    /// on it, if any.
+    pub fn host_poll_events(&self) -> Option<(RawFd, libc::c_short)> {
+        let host = self.host.as_ref()?;
+        let mut events = 0;
+        if self.session_open() && self.rx_pending.is_empty() {
+            events |= libc::POLLIN;
+        }
+        if !self.tx_pending.is_empty() {
+            events |= libc::POLLOUT;
+        }
+        Some((host.as_raw_fd(), events))
+    }
+
+    pub fn mmio_read(&self, offset: u64, data: &mut [u8]) {
+        let value: u32 = match offset {
+            mmio::MAGIC_VALUE => mmio::MAGIC,
+            mmio::VERSION => mmio::VERSION_2,
+            mmio::DEVICE_ID => VIRTIO_CONSOLE_DEVICE_TYPE,
+            mmio::VENDOR_ID => 0x554d4551,
+            mmio::DEVICE_FEATURES => {
+                let features = VIRTIO_F_VERSION_1 | VIRTIO_CONSOLE_F_MULTIPORT;
+                if self.device_features_sel == 0 {
+                    features as u32
+                } else {
+                    (features >> 32) as u32
+                }
+            }
+            mmio::QUEUE_NUM_MAX => self.selected_queue().map(|q| q.num_max as u32).unwrap_or(0),
+            mmio::QUEUE_READY => self.selected_queue().map(|q| q.ready as u32).unwrap_or(0),
+            mmio::INTERRUPT_STATUS => self.interrupt_status,
+            mmio::STATUS => self.status,
+            mmio::CONFIG_GENERATION => 0,
+            CONFIG_MAX_NR_PORTS => MAX_NR_PORTS,
+            _ => {
+                trace!(
+                    "virtio-console: unhandled MMIO read at offset {:#x}",
+                    offset
+                );
+                0
+            }
+        };
+
+        let bytes = value.to_le_bytes();
+        let len = data.len().min(4);
+        data[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    pub fn mmio_write(&mut self, offset: u64, data: &[u8], guest_mem: Option<&GuestMemoryMmap>) {
+        if data.is_empty() {
+            return;
+        }
+        let mut bytes = [0u8; 4];
+        let len = data.len().min(4);
+        bytes[..len].copy_from_slice(&data[..len]);
+        let value = u32::from_le_bytes(bytes);
+
+        match offset {
+            mmio::DEVICE_FEATURES_SEL => self.device_features_sel = value,
+            mmio::DRIVER_FEATURES => {
+                if self.driver_features_sel == 0 {
+                    self.driver_features =
+                        (self.driver_features & 0xFFFF_FFFF_0000_0000) | value as u64;
+                } else {
+                    self.driver_features =
+                        (self.driver_features & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+                }
+            }
+            mmio::DRIVER_FEATURES_SEL => self.driver_features_sel = value,
+            mmio::QUEUE_SEL => self.queue_sel = value,
+            mmio::QUEUE_NOTIFY => {
+                if let Some(mem) = guest_mem {
+                    if value as usize == CONTROL_TRANSMITQ {
+                        self.process_control(mem);
+                    }
+                    self.process_host_io(mem);
+                }
+            }
+            mmio::INTERRUPT_ACK => self.interrupt_status &= !value,
+            mmio::STATUS => {
+                self.status = value;
+                if value == 0 {
+                    self.reset();
+                }
+            }
+            _ => {
+                let Some(q) = self.selected_queue_mut() else {
+                    trace!(
+                        "virtio-console: write at offset {:#x} to invalid queue",
+                        offset
+                    );
+                    return;
+                };
+                match offset {
+                    mmio::QUEUE_NUM => q.num = value as u16,
+                    mmio::QUEUE_READY => q.ready = value != 0,
+                    mmio::QUEUE_DESC_LOW => {
+                        q.desc_addr = (q.desc_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DESC_HIGH => {
+                        q.desc_addr = (q.desc_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    mmio::QUEUE_DRIVER_LOW => {
+                        q.driver_addr = (q.driver_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DRIVER_HIGH => {
+                        q.driver_addr =
+                            (q.driver_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    mmio::QUEUE_DEVICE_LOW => {
+                        q.device_addr = (q.device_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+                    }
+                    mmio::QUEUE_DEVICE_HIGH => {
+                        q.device_addr =
+                            (q.device_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+                    }
+                    _ => {
+                        trace!(
+                            "virtio-console: unhandled MMIO write at offset {:#x}, value={:#x}",
+                            offset,
+                            value
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn selected_queue(&self) -> Option<&QueueState> {
+        self.queues.get(self.queue_sel as usize)
+    }
+
+    fn selected_queue_mut(&mut self) -> Option<&mut QueueState> {
+        self.queues.get_mut(self.queue_sel as usize)
+    }
+
+    fn reset(&mut self) {
+        // A reset after the port came up is a rebooting guest: drop the host
+        // connection so the control channel reconnects to the new agent.
+        // The reset at driver probe keeps a host that connected early.
+        let host = if self.port_ready {
+            None
+        } else {
+            self.host.take()
+        };
+        *self = Self {
+            mmio_base: self.mmio_base,
+            host,
+            ..Self::with_wake(Arc::clone(&self.wake))
+        };
+    }
+
+    fn raise_interrupt(&mut self) {
+        self.interrupt_status |= 1;
+        self.interrupt_raised = true;
+    }
+
+    /// Replace the host connection with `stream`, announcing it to the
+    /// guest if the port is up.
+    fn attach_host(&mut self, stream: UnixStream) -> io::Result<()> {
+        stream.set_nonblocking(true)?;
+        self.host = Some(stream);
+        self.rx_pending.clear();
+        self.tx_pending.clear();
+        self.guest_closed = false;
+        if self.port_ready {
+            self.queue_control(PORT_OPEN, 1, &[]);
+        }
+        let _ = self.wake.write(1);
+        Ok(())
+    }
+
+    /// The guest has the port open for the current host connection.
+    fn session_open(&self) -> bool {
+        self.guest_open && !self.stale_session
+    }
+
+    /// Drop the host connection. Returns whether the guest had the port
+    /// open and so has a session to close first.
+    fn detach_host(&mut self) -> bool {
+        if self.host.take().is_none() {
+            return false;
+        }
+        self.rx_pending.clear();
+        self.tx_pending.clear();
+        self.guest_closed = false;
+        self.stale_session = self.guest_open;
+        if self.port_ready {
+            self.queue_control(PORT_OPEN, 0, &[]);
+        }
+        let _ = self.wake.write(1);
+        self.guest_open
+    }
+
+    fn queue_control(&mut self, event: u16, value: u16, payload: &[u8]) {
+        let mut msg = Vec::with_capacity(CONTROL_MSG_LEN + payload.len());
+        msg.extend_from_slice(&CONTROL_PORT_ID.to_le_bytes());
+        msg.extend_from_slice(&event.to_le_bytes());
+        msg.extend_from_slice(&value.to_le_bytes());
+        msg.extend_from_slice(payload);
+        self.control_out.push_back(msg);
+    }
+
+    /// Handle the guest's control messages.
+    fn process_control(&mut self, mem: &GuestMemoryMmap) {
+        let Some(mut vq) = self.queues[CONTROL_TRANSMITQ].virtqueue() else {
+            return;
+        };
+        let mut completed = false;
+        while let Some(chain) = vq.pop_avail(mem) {
+            let msg = read_chain(mem, &chain.descriptors);
+            vq.push_used(mem, chain.head_index, 0);
+            completed = true;
+            if msg.len() < CONTROL_MSG_LEN {
+                warn!("virtio-console: short control message ({} bytes)", msg.len());
+                continue;
+            }
+            let id = u32::from_le_bytes([msg[0], msg[1], msg[2], msg[3]]);
+            let event = u16::from_le_bytes([msg[4], msg[5]]);
+            let value = u16::from_le_bytes([msg[6], msg[7]]);
+            self.handle_control(id, event, value);
+        }
+        self.queues[CONTROL_TRANSMITQ].save(&vq);
+        if completed {
+            self.raise_interrupt();
+        }
+    }
+
+    fn handle_control(&mut self, id: u32, event: u16, value: u16) {
+        trace!(
+            "virtio-console: control id={} event={} value={}",
+            id,
+            event,
+            value
+        );
+        match event {
+            DEVICE_READY if value == 1 => {
+                self.queue_control(DEVICE_ADD, 0, &[]);
+            }
+            DEVICE_READY => warn!("virtio-console: guest driver failed to initialize"),
+            PORT_READY if id == CONTROL_PORT_ID && value == 1 => {
+                self.port_ready = true;
+                self.queue_control(
+                    PORT_NAME,
+                    0,
+                    void_box_protocol::CONTROL_PORT_NAME.as_bytes(),
+                );
+                if self.host.is_some() {
+                    self.queue_control(PORT_OPEN, 1, &[]);
+                }
+                debug!("virtio-console: control port ready");
+            }
+            PORT_READY => warn!("virtio-console: guest failed to add port {}", id),
+            PORT_OPEN if id == CONTROL_PORT_ID => {
+                self.guest_open = value == 1;
+                if !self.guest_open {
+                    self.guest_closed = true;
+                    self.stale_session = false;
+                }
+                debug!(
+                    "virtio-console: guest {} the control port",
+                    if self.guest_open { "opened" } else { "closed" }
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Move data between the host connection and the guest: deliver
+    /// control messages, forward guest output to the host, and fill the
+    /// guest's receive buffers with host input. Called on queue notifies
+    /// and by the I/O thread when the host socket or the wake eventfd is
+    /// ready.
+    pub fn process_host_io(&mut self, mem: &GuestMemoryMmap) {
+        let _ = self.wake.read();
+        self.deliver_control(mem);
+        self.flush_tx();
+        self.process_tx(mem);
+        self.flush_tx();
+        self.read_host();
+        self.deliver_rx(mem);
+        // The control queue may have gained messages from a dropped host.
+        self.deliver_control(mem);
+    }
+
+    fn deliver_control(&mut self, mem: &GuestMemoryMmap) {
+        if self.control_out.is_empty() {
+            return;
+        }
+        let Some(mut vq) = self.queues[CONTROL_RECEIVEQ].virtqueue() else {
+            return;
+        };
+        let mut completed = false;
+        while !self.control_out.is_empty() {
+            let Some(chain) = vq.pop_avail(mem) else {
+                break;
+            };
+            let msg = self.control_out.pop_front().unwrap_or_default();
+            let written = write_chain(mem, &chain.descriptors, &msg);
+            if written < msg.len() {
+                warn!("virtio-console: control buffer too small, message truncated");
+            }
+            vq.push_used(mem, chain.head_index, written as u32);
+            completed = true;
+        }
+        self.queues[CONTROL_RECEIVEQ].save(&vq);
+        if completed {
+            self.raise_interrupt();
+        }
+    }
+
+    /// Take the guest's output off the transmit queue. Output with no host
+    /// connection to go to, or from a stale session, is dropped.
+    fn process_tx(&mut self, mem: &GuestMemoryMmap) {
+        let Some(mut vq) = self.queues[PORT_TRANSMITQ].virtqueue() else {
+            return;
+        };
+        let mut completed = false;
+        while self.tx_pending.len() < MAX_TX_PENDING {
+            let Some(chain) = vq.pop_avail(mem) else {
+                break;
+            };
+            let data = read_chain(mem, &chain.descriptors);
+            if self.host.is_some() && !self.stale_session {
+                self.tx_pending.extend_from_slice(&data);
+            }
+            vq.push_used(mem, chain.head_index, 0);
+            completed = true;
+        }
+        self.queues[PORT_TRANSMITQ].save(&vq);
+        if completed {
+            self.raise_interrupt();
+        }
+    }
+
+    fn flush_tx(&mut self) {
+        while !self.tx_pending.is_empty() {
+            let Some(host) = self.host.as_mut() else {
+                self.tx_pending.clear();
+                return;
+            };
+            match host.write(&self.tx_pending) {
+                Ok(0) => {
+                    self.detach_host();
+                    return;
+                }
+                Ok(n) => {
+                    self.tx_pending.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    debug!("virtio-console: host write failed: {}", e);
+                    self.detach_host();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Read host input, but only once the guest has the port open for this
+    /// connection: the guest driver discards data that arrives on a closed
+    /// port. Until then, only notice a host that went away.
+    fn read_host(&mut self) {
+        let readable = self.session_open() && self.rx_pending.is_empty();
+        let Some(host) = self.host.as_mut() else {
+            return;
+        };
+        if !readable {
+            let mut byte = 0u8;
+            // SAFETY: a one-byte peek into a local buffer.
+            let n = unsafe {
+                libc::recv(
+                    host.as_raw_fd(),
+                    (&mut byte as *mut u8).cast(),
+                    1,
+                    libc::MSG_PEEK | libc::MSG_DONTWAIT,
+                )
+            };
+            if n == 0 {
+                debug!("virtio-console: host closed the control connection");
+                self.detach_host();
+            }
+            return;
+        }
+        let mut buf = vec![0u8; MAX_DESC_LEN];
+        loop {
+            match host.read(&mut buf) {
+                Ok(0) => {
+                    debug!("virtio-console: host closed the control connection");
+                    self.detach_host();
+                    return;
+                }
+                Ok(n) => {
+                    self.rx_pending.extend_from_slice(&buf[..n]);
+                    return;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    debug!("virtio-console: host read failed: {}", e);
+                    self.detach_host();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn deliver_rx(&mut self, mem: &GuestMemoryMmap) {
+        if self.rx_pending.is_empty() {
+            return;
+        }
+        let Some(mut vq) = self.queues[PORT_RECEIVEQ].virtqueue() else {
+            return;
+        };
+        let mut completed = false;
+        while !self.rx_pending.is_empty() {
+            let Some(chain) = vq.pop_avail(mem) else {
+                break;
+            };
+            let written = write_chain(mem, &chain.descriptors, &self.rx_pending);
+            self.rx_pending.drain(..written);
+            vq.push_used(mem, chain.head_index, written as u32);
+            completed = true;
+        }
+        self.queues[PORT_RECEIVEQ].save(&vq);
+        if completed {
+            self.raise_interrupt();
+        }
+    }
+}
+
+/// The device-readable bytes of a descriptor chain.
+fn read_chain(mem: &GuestMemoryMmap, descriptors: &[VirtqDesc]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for desc in descriptors {
+        if desc.flags & VRING_DESC_F_WRITE != 0 {
+            continue;
+        }
+        let mut buf = vec![0u8; (desc.len as usize).min(MAX_DESC_LEN)];
+        if mem.read_slice(&mut buf, GuestAddress(desc.addr)).is_err() {
+            warn!(
+                "virtio-console: buffer at {:#x} outside guest memory",
+                desc.addr
+            );
+            break;
+        }
+        data.extend_from_slice(&buf);
+    }
+    data
+}
+
+/// Copy as much of `data` as fits into the device-writable buffers of a
+/// descriptor chain; returns the bytes written.
+fn write_chain(
+    mem: &GuestMemoryMmap,
+    descriptors: &[VirtqDesc],
+    data: &[u8],
+) -> usize {
+    let mut written = 0;
+    for desc in descriptors {
+        if written == data.len() {
+            break;
+        }
+        if desc.flags & VRING_DESC_F_WRITE == 0 {
+            continue;
+        }
+        let n = (desc.len as usize).min(data.len() - written);
+        if mem
+            .write_slice(&data[written..written + n], GuestAddress(desc.addr))
+            .is_err()
+        {
+            warn!(
+                "virtio-console: buffer at {:#x} outside guest memory",
+                desc.addr
+            );
+            break;
+        }
+        written += n;
+    }
+    written
+}
+
+/// Host end of the control port, as the control channel's transport.
+pub struct ControlPortStream(UnixStream);
+
+impl Read for ControlPortStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ControlPortStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl GuestStream for ControlPortStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+
+    fn try_clone_box(&self) -> io::Result<Box<dyn GuestStream>> {
+        Ok(Box::new(Self(self.0.try_clone()?)))
+    }
+}
+
+/// A [`GuestConnector`] that opens a new connection over the control port
+/// of `device` on every call, closing the previous one.
+///
+/// Each call hands out a single byte stream, so only one connection is
+/// live at a time; PTY sessions keep using vsock.
+pub fn connector(device: Arc<Mutex<VirtioConsoleDevice>>) -> GuestConnector {
+    Arc::new(move || {
+        let (host, device_end) = UnixStream::pair()
+            .map_err(|e| Error::Device(format!("virtio-console: socketpair: {e}")))?;
+        let guest_had_session = device.lock().unwrap().detach_host();
+        if guest_had_session {
+            // Announcing the new connection before the guest-agent has
+            // closed the old session would feed the new handshake into it.
+            let deadline = Instant::now() + GUEST_CLOSE_WAIT;
+            while !device.lock().unwrap().guest_closed && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        device
+            .lock()
+            .unwrap()
+            .attach_host(device_end)
+            .map_err(|e| Error::Device(format!("virtio-console: {e}")))?;
+        Ok(Box::new(ControlPortStream(host)) as Box<dyn GuestStream>)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Guest-physical layout: one page of rings per queue, then buffers.
+    const RINGS: u64 = 0x1_0000;
+    const BUFS: u64 = 0x10_0000;
+    const BUF_LEN: u32 = 4096;
+
+    struct Guest {
+        mem: GuestMemoryMmap,
+        /// Next avail index per queue.
+        avail: [u16; NUM_QUEUES],
+        /// Used index consumed per queue.
+        used: [u16; NUM_QUEUES],
+    }
+
+    impl Guest {
+        fn new(dev: &mut VirtioConsoleDevice) -> Self {
+            let mem = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 4 * 1024 * 1024)]).unwrap();
+            let guest = Self {
+                mem,
+                avail: [0; NUM_QUEUES],
+                used: [0; NUM_QUEUES],
+            };
+            for q in CONTROL_RECEIVEQ..NUM_QUEUES {
+                let base = RINGS + q as u64 * 0x4000;
+                guest.write_reg(dev, mmio::QUEUE_SEL, q as u32);
+                guest.write_reg(dev, mmio::QUEUE_NUM, 16);
+                guest.write_reg(dev, mmio::QUEUE_DESC_LOW, base as u32);
+                guest.write_reg(dev, mmio::QUEUE_DRIVER_LOW, (base + 0x1000) as u32);
+                guest.write_reg(dev, mmio::QUEUE_DEVICE_LOW, (base + 0x2000) as u32);
+                guest.write_reg(dev, mmio::QUEUE_READY, 1);
+            }
+            guest
+        }
+
+        fn write_reg(&self, dev: &mut VirtioConsoleDevice, offset: u64, value: u32) {
+            dev.mmio_write(offset, &value.to_le_bytes(), Some(&self.mem));
+        }
+
+        fn buf_addr(q: usize, slot: u16) -> u64 {
+            BUFS + (q as u64 * 16 + u64::from(slot % 16)) * u64::from(BUF_LEN)
+        }
+
+        /// Queue one buffer on `q`: device-writable and empty, or holding
+        /// `data` for the device to read.
+        fn post(&mut self, dev: &mut VirtioConsoleDevice, q: usize, data: Option<&[u8]>) {
+            let base = RINGS + q as u64 * 0x4000;
+            let slot = self.avail[q] % 16;
+            let addr = Self::buf_addr(q, slot);
+            let desc = base + u64::from(slot) * 16;
+            let (len, flags) = match data {
+                Some(data) => {
+                    self.mem.write_slice(data, GuestAddress(addr)).unwrap();
+                    (data.len() as u32, 0)
+                }
+                None => (BUF_LEN, VRING_DESC_F_WRITE),
+            };
+            self.mem.write_obj(addr, GuestAddress(desc)).unwrap();
+            self.mem.write_obj(len, GuestAddress(desc + 8)).unwrap();
+            self.mem.write_obj(flags, GuestAddress(desc + 12)).unwrap();
+            let avail = base + 0x1000;
+            self.mem
+                .write_obj(slot, GuestAddress(avail + 4 + u64::from(slot) * 2))
+                .unwrap();
+            self.avail[q] = self.avail[q].wrapping_add(1);
+            self.mem
+                .write_obj(self.avail[q], GuestAddress(avail + 2))
+                .unwrap();
+            self.write_reg(dev, mmio::QUEUE_NOTIFY, q as u32);
+        }
+
+        /// Buffers the device has returned on `q` since the last call.
+        fn take_used(&mut self, q: usize) -> Vec<Vec<u8>> {
+            let used = RINGS + q as u64 * 0x4000 + 0x2000;
+            let idx: u16 = self.mem.read_obj(GuestAddress(used + 2)).unwrap();
+            let mut out = Vec::new();
+            while self.used[q] != idx {
+                let elem = used + 4 + u64::from(self.used[q] % 16) * 8;
+                let id: u32 = self.mem.read_obj(GuestAddress(elem)).unwrap();
+                let len: u32 = self.mem.read_obj(GuestAddress(elem + 4)).unwrap();
+                let mut buf = vec![0u8; len as usize];
+                self.mem
+                    .read_slice(&mut buf, GuestAddress(Self::buf_addr(q, id as u16)))
+                    .unwrap();
+                out.push(buf);
+                self.used[q] = self.used[q].wrapping_add(1);
+            }
+            out
+        }
+
+        fn send_control(&mut self, dev: &mut VirtioConsoleDevice, id: u32, event: u16, value: u16) {
+            let mut msg = id.to_le_bytes().to_vec();
+            msg.extend_from_slice(&event.to_le_bytes());
+            msg.extend_from_slice(&value.to_le_bytes());
+            self.post(dev, CONTROL_TRANSMITQ, Some(&msg));
+        }
+
+        /// Control messages from the device as `(id, event, value, payload)`.
+        fn control_messages(&mut self) -> Vec<(u32, u16, u16, Vec<u8>)> {
+            self.take_used(CONTROL_RECEIVEQ)
+                .into_iter()
+                .map(|m| {
+                    (
+                        u32::from_le_bytes(m[0..4].try_into().unwrap()),
+                        u16::from_le_bytes(m[4..6].try_into().unwrap()),
+                        u16::from_le_bytes(m[6..8].try_into().unwrap()),
+                        m[8..].to_vec(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    fn shared(dev: VirtioConsoleDevice) -> Arc<Mutex<VirtioConsoleDevice>> {
+        Arc::new(Mutex::new(dev))
+    }
+
+    #[test]
+    fn test_control_port_handshake_and_data() {
+        let device = shared(VirtioConsoleDevice::new().unwrap());
+        let connect = connector(Arc::clone(&device));
+        let mut dev = device.lock().unwrap();
+        let mut guest = Guest::new(&mut dev);
+        assert_eq!(
+            {
+                let mut buf = [0u8; 4];
+                dev.mmio_read(CONFIG_MAX_NR_PORTS, &mut buf);
+                u32::from_le_bytes(buf)
+            },
+            MAX_NR_PORTS
+        );
+
+        for _ in 0..4 {
+            guest.post(&mut dev, CONTROL_RECEIVEQ, None);
+        }
+        guest.send_control(&mut dev, u32::MAX, DEVICE_READY, 1);
+        assert_eq!(
+            guest.control_messages(),
+            vec![(CONTROL_PORT_ID, DEVICE_ADD, 0, vec![])]
+        );
+
+        // The port comes up before any host connects: no PORT_OPEN yet.
+        guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_READY, 1);
+        assert_eq!(
+            guest.control_messages(),
+            vec![(
+                CONTROL_PORT_ID,
+                PORT_NAME,
+                0,
+                void_box_protocol::CONTROL_PORT_NAME.as_bytes().to_vec()
+            )]
+        );
+        drop(dev);
+
+        let mut stream = connect().unwrap();
+        let mut dev = device.lock().unwrap();
+        dev.process_host_io(&guest.mem);
+        assert_eq!(
+            guest.control_messages(),
+            vec![(CONTROL_PORT_ID, PORT_OPEN, 1, vec![])]
+        );
+
+        // Host input waits until the guest opens the port.
+        stream.write_all(b"ping").unwrap();
+        guest.post(&mut dev, PORT_RECEIVEQ, None);
+        dev.process_host_io(&guest.mem);
+        assert!(guest.take_used(PORT_RECEIVEQ).is_empty());
+        guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_OPEN, 1);
+        dev.process_host_io(&guest.mem);
+        assert_eq!(guest.take_used(PORT_RECEIVEQ), vec![b"ping".to_vec()]);
+        assert!(dev.take_interrupt());
+
+        guest.post(&mut dev, PORT_TRANSMITQ, Some(b"pong"));
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"pong");
+    }
+
+    #[test]
+    fn test_reconnect_closes_the_previous_connection() {
+        let device = shared(VirtioConsoleDevice::new().unwrap());
+        let connect = connector(Arc::clone(&device));
+        let mut guest = {
+            let mut dev = device.lock().unwrap();
+            let mut guest = Guest::new(&mut dev);
+            for _ in 0..8 {
+                guest.post(&mut dev, CONTROL_RECEIVEQ, None);
+            }
+            guest.send_control(&mut dev, u32::MAX, DEVICE_READY, 1);
+            guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_READY, 1);
+            guest
+        };
+        let mut first = connect().unwrap();
+        {
+            let mut dev = device.lock().unwrap();
+            guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_OPEN, 1);
+        }
+        guest.control_messages();
+
+        // The next connection closes the port, then waits for the
+        // guest-agent to close its session before reopening it.
+        let second = std::thread::spawn(move || connect().unwrap());
+        while device.lock().unwrap().host.is_some() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        {
+            let mut dev = device.lock().unwrap();
+            dev.process_host_io(&guest.mem);
+            // A write the old session still had in flight.
+            guest.post(&mut dev, PORT_TRANSMITQ, Some(b"stale"));
+            guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_OPEN, 0);
+        }
+        let mut second = second.join().unwrap();
+        {
+            let mut dev = device.lock().unwrap();
+            dev.process_host_io(&guest.mem);
+            guest.send_control(&mut dev, CONTROL_PORT_ID, PORT_OPEN, 1);
+            guest.post(&mut dev, PORT_TRANSMITQ, Some(b"fresh"));
+        }
+        let mut reply = [0u8; 5];
+        second.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"fresh");
+
+        let mut buf = [0u8; 1];
+        assert_eq!(first.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            guest.control_messages(),
+            vec![
+                (CONTROL_PORT_ID, PORT_OPEN, 0, vec![]),
+                (CONTROL_PORT_ID, PORT_OPEN, 1, vec![])
+            ]
+        );
+    }
+}
@@ -495,6 +495,31 @@ impl VirtioNetDevice {
     /// synchronous TX-queue handler used from the MMIO write path,
     /// just exposed under a different name so callers outside this
     /// module can drive it.
+    /// TX descriptor chains the guest has posted that the device has not
+    /// yet consumed. Zero before the driver sets the queue up.
+    pub fn tx_pending<M: GuestMemory + ?Sized>(&self, mem: &M) -> u16 {
+        let q = &self.tx_queue;
+        if !q.ready || q.num == 0 {
+            return 0;
+        }
+        let mut idx_buf = [0u8; 2];
+        if mem
+            .read(
+                &mut idx_buf,
+                GuestAddress(q.driver_addr).unchecked_add(2u64),
+            )
+            .is_err()
+        {
+            return 0;
+        }
+        u16::from_le_bytes(idx_buf).wrapping_sub(self.tx_avail_idx)
+    }
+
+    /// Frames queued for the guest that are still waiting for RX buffers.
+    pub fn rx_backlog(&self) -> usize {
+        self.rx_buffer.len() + self.pending_rx.len()
+    }
+
     pub fn process_tx_queue_external<M: GuestMemory + ?Sized>(&mut self, mem: &M) -> Result<()> {
         self.process_tx_queue(mem)
     }
@@ -0,0 +1,428 @@
+//! virtio-net MMIO device backed by kernel vhost-net
+//!
+//! Presents the same eth0 as [`VirtioNetDevice`](super::virtio_net::VirtioNetDevice)
+//! but hands both virtqueues to the kernel's vhost-net driver, which moves
+//! frames between guest memory and a host TAP device without a userspace
+//! copy or the net-poll thread. Only the MMIO register file and interrupt
+//! status stay in userspace.
+//!
+//! Requires `/dev/vhost-net` and `CAP_NET_ADMIN` for the TAP device. Device
+//! state is not captured in snapshots.
+
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+use rustix::event::{eventfd, EventfdFlags};
+use rustix::fs::{open, Mode, OFlags};
+use tracing::{debug, trace, warn};
+
+use crate::devices::vhost::{self, VhostVringFile};
+use crate::devices::virtio_net::{features, mmio, VIRTIO_NET_DEVICE_TYPE};
+use crate::network::slirp::GUEST_MAC;
+use crate::network::TapDevice;
+use crate::{Error, Result};
+
+/// Device status bits (virtio spec 2.1).
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_DRIVER_OK: u32 = 4;
+
+/// Queue state for virtio-net (rx=0, tx=1)
+#[derive(Default)]
+struct QueueState {
+    num_max: u16,
+    num: u16,
+    ready: bool,
+    desc_addr: u64,
+    driver_addr: u64,
+    device_addr: u64,
+}
+
+impl QueueState {
+    fn new() -> Self {
+        Self {
+            num_max: 256,
+            ..Default::default()
+        }
+    }
+}
+
+/// Virtio-net MMIO device backed by kernel vhost-net and a host TAP device
+pub struct VhostNetDevice {
+    vhost: OwnedFd,
+    tap: TapDevice,
+    kick_eventfds: [OwnedFd; 2],
+    call_eventfds: [OwnedFd; 2],
+    mac: [u8; 6],
+    /// Features vhost-net itself implements; what the guest acks is masked
+    /// by these before `VHOST_SET_FEATURES`.
+    vhost_features: u64,
+    device_features: u64,
+    driver_features: u64,
+    features_sel: u32,
+    queue_sel: u32,
+    status: u32,
+    interrupt_status: u32,
+    config_generation: u32,
+    rx_queue: QueueState,
+    tx_queue: QueueState,
+    mmio_base: u64,
+    mmio_size: u64,
+    mem_table_set: bool,
+}
+
+impl VhostNetDevice {
+    /// Open `/dev/vhost-net` and TAP device `tap_name` (enslaved to
+    /// `bridge` if given).
+    ///
+    /// Returns `Ok(None)` when `/dev/vhost-net` cannot be opened, so the
+    /// caller can fall back to the userspace virtio-net path; TAP and
+    /// vhost setup failures are errors.
+    pub fn new(tap_name: &str, bridge: Option<&str>) -> Result<Option<Self>> {
+        let vhost = match open(
+            Path::new("/dev/vhost-net"),
+            OFlags::RDWR | OFlags::CLOEXEC | OFlags::NONBLOCK,
+            Mode::empty(),
+        ) {
+            Ok(fd) => fd,
+            Err(e) => {
+                debug!("vhost-net not available: {}", e);
+                return Ok(None);
+            }
+        };
+        vhost::set_owner(vhost.as_raw_fd())?;
+
+        let mut vhost_features: u64 = 0;
+        let ret = unsafe {
+            libc::ioctl(
+                vhost.as_raw_fd(),
+                vhost::ioctl::VHOST_GET_FEATURES as _,
+                &mut vhost_features,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::Device(format!(
+                "VHOST_GET_FEATURES: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        if vhost_features & features::VIRTIO_F_VERSION_1 == 0 {
+            return Err(Error::Device(
+                "vhost-net does not support VIRTIO_F_VERSION_1, required for virtio-mmio v2".into(),
+            ));
+        }
+
+        let tap = TapDevice::create_with_vnet_hdr(tap_name)?;
+        tap.bring_up(bridge)?;
+
+        let new_eventfd = || {
+            eventfd(0, EventfdFlags::NONBLOCK | EventfdFlags::CLOEXEC)
+                .map_err(|e| Error::Device(format!("eventfd: {}", e)))
+        };
+
+        Ok(Some(Self {
+            vhost,
+            tap,
+            kick_eventfds: [new_eventfd()?, new_eventfd()?],
+            call_eventfds: [new_eventfd()?, new_eventfd()?],
+            mac: GUEST_MAC,
+            vhost_features,
+            device_features: features::VIRTIO_NET_F_MAC
+                | features::VIRTIO_NET_F_STATUS
+                | features::VIRTIO_F_VERSION_1,
+            driver_features: 0,
+            features_sel: 0,
+            queue_sel: 0,
+            status: 0,
+            interrupt_status: 0,
+            config_generation: 0,
+            rx_queue: QueueState::new(),
+            tx_queue: QueueState::new(),
+            mmio_base: 0,
+            mmio_size: 0x200,
+            mem_table_set: false,
+        }))
+    }
+
+    /// Name of the TAP device vhost-net forwards to.
+    pub fn tap_name(&self) -> &str {
+        self.tap.name()
+    }
+
+    pub fn set_mmio_base(&mut self, base: u64) {
+        self.mmio_base = base;
+        debug!("vhost-net MMIO base set to {:#x}", base);
+    }
+
+    pub fn mmio_base(&self) -> u64 {
+        self.mmio_base
+    }
+
+    pub fn mmio_size(&self) -> u64 {
+        self.mmio_size
+    }
+
+    pub fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    /// Raw FDs of the call eventfds vhost-net signals on used-buffer
+    /// updates (index 0 = rx, 1 = tx).
+    pub fn call_eventfds(&self) -> Vec<RawFd> {
+        self.call_eventfds.iter().map(AsRawFd::as_raw_fd).collect()
+    }
+
+    /// Set interrupt status bits (called by the IRQ handler thread).
+    pub fn set_interrupt_status(&mut self, bits: u32) {
+        self.interrupt_status |= bits;
+    }
+
+    pub fn handles_mmio(&self, addr: u64) -> bool {
+        addr >= self.mmio_base && addr < self.mmio_base + self.mmio_size
+    }
+
+    fn current_queue(&self) -> &QueueState {
+        match self.queue_sel {
+            1 => &self.tx_queue,
+            _ => &self.rx_queue,
+        }
+    }
+
+    fn current_queue_mut(&mut self) -> &mut QueueState {
+        match self.queue_sel {
+            1 => &mut self.tx_queue,
+            _ => &mut self.rx_queue,
+        }
+    }
+
+    fn set_features(&self) -> Result<()> {
+        let acked = self.driver_features & self.vhost_features;
+        let ret = unsafe {
+            libc::ioctl(
+                self.vhost.as_raw_fd(),
+                vhost::ioctl::VHOST_SET_FEATURES as _,
+                &acked,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::Device(format!(
+                "VHOST_SET_FEATURES({:#x}): {}",
+                acked,
+                std::io::Error::last_os_error()
+            )));
+        }
+        debug!("vhost-net features set to {:#x}", acked);
+        Ok(())
+    }
+
+    /// Attach the TAP device to both vrings, starting the kernel datapath,
+    /// or detach it with `fd = -1`.
+    fn set_backend(&self, fd: RawFd) -> Result<()> {
+        for index in 0..2 {
+            let file = VhostVringFile { index, fd };
+            let ret = unsafe {
+                libc::ioctl(
+                    self.vhost.as_raw_fd(),
+                    vhost::ioctl::VHOST_NET_SET_BACKEND as _,
+                    &file,
+                )
+            };
+            if ret < 0 {
+                return Err(Error::Device(format!(
+                    "VHOST_NET_SET_BACKEND(vring {}, fd {}): {}",
+                    index,
+                    fd,
+                    std::io::Error::last_os_error()
+                )));
+            }
+        }
+        debug!(
+            "vhost-net backend {}",
+            if fd < 0 { "detached" } else { "attached" }
+        );
+        Ok(())
+    }
+
+    pub fn mmio_read(&self, offset: u64, data: &mut [u8]) {
+        let value: u32 = match offset {
+            mmio::MAGIC_VALUE => mmio::MAGIC,
+            mmio::VERSION => mmio::VERSION_2,
+            mmio::DEVICE_ID => VIRTIO_NET_DEVICE_TYPE,
+            mmio::VENDOR_ID => 0x554d4551,
+            mmio::DEVICE_FEATURES => {
+                if self.features_sel == 0 {
+                    self.device_features as u32
+                } else {
+                    (self.device_features >> 32) as u32
+                }
+            }
+            mmio::QUEUE_NUM_MAX => self.current_queue().num_max as u32,
+            mmio::QUEUE_READY => self.current_queue().ready as u32,
+            mmio::INTERRUPT_STATUS => self.interrupt_status,
+            mmio::STATUS => self.status,
+            mmio::CONFIG_GENERATION => self.config_generation,
+            // Device config: MAC address, then link status
+            o if (mmio::CONFIG..mmio::CONFIG + 6).contains(&o) => {
+                self.mac[(o - mmio::CONFIG) as usize] as u32
+            }
+            o if o == mmio::CONFIG + 6 => 1,
+            _ => {
+                trace!("vhost-net: unhandled MMIO read offset {:#x}", offset);
+                0
+            }
+        };
+        let bytes = value.to_le_bytes();
+        let len = data.len().min(4);
+        data[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    pub fn mmio_write(
+        &mut self,
+        offset: u64,
+        data: &[u8],
+        guest_memory: &vm_memory::GuestMemoryMmap,
+    ) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut bytes = [0u8; 4];
+        let len = data.len().min(4);
+        bytes[..len].copy_from_slice(&data[..len]);
+        let value = u32::from_le_bytes(bytes);
+
+        match offset {
+            mmio::DEVICE_FEATURES_SEL => self.features_sel = value,
+            mmio::DRIVER_FEATURES => {
+                if self.features_sel == 0 {
+                    self.driver_features =
+                        (self.driver_features & 0xFFFF_FFFF_0000_0000) | (value as u64);
+                } else {
+                    self.driver_features =
+                        (self.driver_features & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+                }
+            }
+            mmio::DRIVER_FEATURES_SEL => self.features_sel = value,
+            mmio::QUEUE_SEL => self.queue_sel = value,
+            mmio::QUEUE_NUM => self.current_queue_mut().num = value as u16,
+            mmio::QUEUE_READY => {
+                let idx = self.queue_sel;
+                let q = self.current_queue_mut();
+                q.ready = value != 0;
+                if q.ready && idx < 2 {
+                    let (num, desc, driver, device) =
+                        (q.num as u32, q.desc_addr, q.driver_addr, q.device_addr);
+                    if !self.mem_table_set {
+                        vhost::set_mem_table(self.vhost.as_raw_fd(), guest_memory)?;
+                        self.mem_table_set = true;
+                    }
+                    vhost::set_vring(
+                        self.vhost.as_raw_fd(),
+                        idx,
+                        num,
+                        desc,
+                        driver,
+                        device,
+                        guest_memory,
+                        self.kick_eventfds[idx as usize].as_raw_fd(),
+                        self.call_eventfds[idx as usize].as_raw_fd(),
+                    )?;
+                }
+            }
+            mmio::QUEUE_NOTIFY => self.notify_queue(value),
+            mmio::INTERRUPT_ACK => self.interrupt_status &= !value,
+            mmio::STATUS => {
+                let previous = self.status;
+                self.status = value;
+                if value == 0 {
+                    if previous & STATUS_DRIVER_OK != 0 {
+                        self.set_backend(-1)?;
+                    }
+                    self.reset();
+                } else {
+                    let newly = value & !previous;
+                    if newly & STATUS_FEATURES_OK != 0 {
+                        self.set_features()?;
+                    }
+                    if newly & STATUS_DRIVER_OK != 0 {
+                        self.set_backend(self.tap.fd())?;
+                    }
+                }
+            }
+            mmio::QUEUE_DESC_LOW => {
+                let q = self.current_queue_mut();
+                q.desc_addr = (q.desc_addr & 0xFFFF_FFFF_0000_0000) | (value as u64);
+            }
+            mmio::QUEUE_DESC_HIGH => {
+                let q = self.current_queue_mut();
+                q.desc_addr = (q.desc_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+            }
+            mmio::QUEUE_DRIVER_LOW => {
+                let q = self.current_queue_mut();
+                q.driver_addr = (q.driver_addr & 0xFFFF_FFFF_0000_0000) | (value as u64);
+            }
+            mmio::QUEUE_DRIVER_HIGH => {
+                let q = self.current_queue_mut();
+                q.driver_addr = (q.driver_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+            }
+            mmio::QUEUE_DEVICE_LOW => {
+                let q = self.current_queue_mut();
+                q.device_addr = (q.device_addr & 0xFFFF_FFFF_0000_0000) | (value as u64);
+            }
+            mmio::QUEUE_DEVICE_HIGH => {
+                let q = self.current_queue_mut();
+                q.device_addr = (q.device_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+            }
+            _ => {
+                trace!(
+                    "vhost-net: unhandled MMIO write offset {:#x} value={:#x}",
+                    offset,
+                    value
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        debug!("vhost-net: device reset");
+        self.status = 0;
+        self.interrupt_status = 0;
+        self.driver_features = 0;
+        self.rx_queue = QueueState::new();
+        self.tx_queue = QueueState::new();
+    }
+
+    fn notify_queue(&mut self, queue_index: u32) {
+        let Some(kick) = self.kick_eventfds.get(queue_index as usize) else {
+            warn!("vhost-net: invalid queue notify {}", queue_index);
+            return;
+        };
+        let val: u64 = 1;
+        let ret = unsafe {
+            libc::write(
+                kick.as_raw_fd(),
+                &val as *const _ as *const libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        if ret < 0 {
+            trace!("vhost-net: kick write failed for queue {}", queue_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_vhost_net_means_fallback() {
+        if Path::new("/dev/vhost-net").exists() {
+            return;
+        }
+        // No TAP is created when vhost-net itself is unavailable.
+        assert!(VhostNetDevice::new("vbtest-vhost0", None)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,330 @@
+//! virtio-rng MMIO device (entropy source backed by the host's getrandom).
+//!
+//! A fresh guest has almost no entropy: until its CRNG is seeded, TLS
+//! handshakes and key generation block. With this device the guest's
+//! `hw_random` core pulls seed material from the host kernel instead of
+//! waiting on interrupt timing.
+
+use tracing::{debug, trace, warn};
+use vm_memory::{Bytes, GuestAddress, GuestMemoryMmap};
+
+use crate::devices::virtio_net::mmio;
+use crate::devices::virtqueue::{SplitVirtqueue, VRING_DESC_F_WRITE};
+use crate::vmm::snapshot::{QueueSnapshotState, RngSnapshotState};
+
+pub const VIRTIO_RNG_DEVICE_TYPE: u32 = 4;
+
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+const QUEUE_MAX_SIZE: u16 = 64;
+
+/// Upper bound on bytes handed out per descriptor, so a guest-chosen
+/// buffer length cannot make the host allocate without limit.
+const MAX_FILL_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+struct QueueState {
+    num_max: u16,
+    num: u16,
+    ready: bool,
+    desc_addr: u64,
+    driver_addr: u64,
+    device_addr: u64,
+}
+
+pub struct VirtioRngDevice {
+    mmio_base: u64,
+    device_features_sel: u32,
+    driver_features: u64,
+    driver_features_sel: u32,
+    queue: QueueState,
+    interrupt_status: u32,
+    status: u32,
+    avail_idx: u16,
+    used_idx: u16,
+}
+
+impl Default for VirtioRngDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtioRngDevice {
+    pub fn new() -> Self {
+        Self {
+            mmio_base: 0,
+            device_features_sel: 0,
+            driver_features: 0,
+            driver_features_sel: 0,
+            queue: QueueState {
+                num_max: QUEUE_MAX_SIZE,
+                ..Default::default()
+            },
+            interrupt_status: 0,
+            status: 0,
+            avail_idx: 0,
+            used_idx: 0,
+        }
+    }
+
+    pub fn set_mmio_base(&mut self, base: u64) {
+        self.mmio_base = base;
+        debug!("virtio-rng MMIO base set to {:#x}", base);
+    }
+
+    pub fn mmio_base(&self) -> u64 {
+        self.mmio_base
+    }
+
+    pub fn mmio_size(&self) -> u64 {
+        0x200
+    }
+
+    pub fn handles_mmio(&self, addr: u64) -> bool {
+        addr >= self.mmio_base && addr < self.mmio_base + self.mmio_size()
+    }
+
+    pub fn has_pending_interrupt(&self) -> bool {
+        self.interrupt_status != 0
+    }
+
+    pub fn mmio_read(&self, offset: u64, data: &mut [u8]) {
+        let value: u32 = match offset {
+            mmio::MAGIC_VALUE => mmio::MAGIC,
+            mmio::VERSION => mmio::VERSION_2,
+            mmio::DEVICE_ID => VIRTIO_RNG_DEVICE_TYPE,
+            mmio::VENDOR_ID => 0x554d4551,
+            mmio::DEVICE_FEATURES => {
+                if self.device_features_sel == 0 {
+                    VIRTIO_F_VERSION_1 as u32
+                } else {
+                    (VIRTIO_F_VERSION_1 >> 32) as u32
+                }
+            }
+            mmio::QUEUE_NUM_MAX => self.queue.num_max as u32,
+            mmio::QUEUE_READY => self.queue.ready as u32,
+            mmio::INTERRUPT_STATUS => self.interrupt_status,
+            mmio::STATUS => self.status,
+            mmio::CONFIG_GENERATION => 0,
+            _ => {
+                trace!("virtio-rng: unhandled MMIO read at offset {:#x}", offset);
+                0
+            }
+        };
+
+        let bytes = value.to_le_bytes();
+        let len = data.len().min(4);
+        data[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    pub fn mmio_write(&mut self, offset: u64, data: &[u8], guest_mem: Option<&GuestMemoryMmap>) {
+        if data.is_empty() {
+            return;
+        }
+        let mut bytes = [0u8; 4];
+        let len = data.len().min(4);
+        bytes[..len].copy_from_slice(&data[..len]);
+        let value = u32::from_le_bytes(bytes);
+
+        match offset {
+            mmio::DEVICE_FEATURES_SEL => self.device_features_sel = value,
+            mmio::DRIVER_FEATURES => {
+                if self.driver_features_sel == 0 {
+                    self.driver_features =
+                        (self.driver_features & 0xFFFF_FFFF_0000_0000) | value as u64;
+                } else {
+                    self.driver_features =
+                        (self.driver_features & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32);
+                }
+            }
+            mmio::DRIVER_FEATURES_SEL => self.driver_features_sel = value,
+            mmio::QUEUE_SEL => {}
+            mmio::QUEUE_NUM => self.queue.num = value as u16,
+            mmio::QUEUE_READY => self.queue.ready = value != 0,
+            mmio::QUEUE_NOTIFY => {
+                if let Some(mem) = guest_mem {
+                    self.process_queue(mem);
+                }
+            }
+            mmio::INTERRUPT_ACK => self.interrupt_status &= !value,
+            mmio::STATUS => {
+                self.status = value;
+                if value == 0 {
+                    self.reset();
+                }
+            }
+            mmio::QUEUE_DESC_LOW => {
+                self.queue.desc_addr =
+                    (self.queue.desc_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+            }
+            mmio::QUEUE_DESC_HIGH => {
+                self.queue.desc_addr =
+                    (self.queue.desc_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+            }
+            mmio::QUEUE_DRIVER_LOW => {
+                self.queue.driver_addr =
+                    (self.queue.driver_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+            }
+            mmio::QUEUE_DRIVER_HIGH => {
+                self.queue.driver_addr =
+                    (self.queue.driver_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+            }
+            mmio::QUEUE_DEVICE_LOW => {
+                self.queue.device_addr =
+                    (self.queue.device_addr & 0xFFFF_FFFF_0000_0000) | (value as u64)
+            }
+            mmio::QUEUE_DEVICE_HIGH => {
+                self.queue.device_addr =
+                    (self.queue.device_addr & 0x0000_0000_FFFF_FFFF) | ((value as u64) << 32)
+            }
+            _ => {
+                trace!(
+                    "virtio-rng: unhandled MMIO write at offset {:#x}, value={:#x}",
+                    offset,
+                    value
+                );
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            mmio_base: self.mmio_base,
+            ..Self::new()
+        };
+    }
+
+    /// Fill every device-writable buffer the guest queued with host entropy.
+    fn process_queue(&mut self, mem: &GuestMemoryMmap) {
+        if !self.queue.ready || self.queue.num == 0 {
+            return;
+        }
+        let mut vq = SplitVirtqueue::new(
+            self.queue.num,
+            self.queue.desc_addr,
+            self.queue.driver_addr,
+            self.queue.device_addr,
+            -1,
+            -1,
+        );
+        vq.last_avail_idx = self.avail_idx;
+        vq.last_used_idx = self.used_idx;
+
+        let mut completed = false;
+        while let Some(chain) = vq.pop_avail(mem) {
+            let mut written = 0u32;
+            for desc in &chain.descriptors {
+                if desc.flags & VRING_DESC_F_WRITE == 0 {
+                    continue;
+                }
+                let mut buf = vec![0u8; (desc.len as usize).min(MAX_FILL_LEN)];
+                if let Err(e) = getrandom::fill(&mut buf) {
+                    warn!("virtio-rng: getrandom failed: {}", e);
+                    break;
+                }
+                if mem.write_slice(&buf, GuestAddress(desc.addr)).is_err() {
+                    warn!(
+                        "virtio-rng: buffer at {:#x} outside guest memory",
+                        desc.addr
+                    );
+                    break;
+                }
+                written += buf.len() as u32;
+            }
+            vq.push_used(mem, chain.head_index, written);
+            completed = true;
+        }
+
+        self.avail_idx = vq.last_avail_idx;
+        self.used_idx = vq.last_used_idx;
+        if completed {
+            self.interrupt_status |= 1;
+        }
+    }
+
+    /// Capture device state for snapshot.
+    pub fn snapshot_state(&self) -> RngSnapshotState {
+        RngSnapshotState {
+            driver_features: self.driver_features,
+            status: self.status,
+            interrupt_status: self.interrupt_status,
+            queue: QueueSnapshotState {
+                num_max: self.queue.num_max,
+                num: self.queue.num,
+                ready: self.queue.ready,
+                desc_addr: self.queue.desc_addr,
+                driver_addr: self.queue.driver_addr,
+                device_addr: self.queue.device_addr,
+                last_avail_idx: Some(self.avail_idx),
+                last_used_idx: Some(self.used_idx),
+            },
+        }
+    }
+
+    /// Restore device state from a snapshot.
+    pub fn restore_state(&mut self, state: &RngSnapshotState) {
+        self.driver_features = state.driver_features;
+        self.status = state.status;
+        self.interrupt_status = state.interrupt_status;
+        let q = &state.queue;
+        self.queue = QueueState {
+            num_max: q.num_max,
+            num: q.num,
+            ready: q.ready,
+            desc_addr: q.desc_addr,
+            driver_addr: q.driver_addr,
+            device_addr: q.device_addr,
+        };
+        self.avail_idx = q.last_avail_idx.unwrap_or(0);
+        self.used_idx = q.last_used_idx.unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESC: u64 = 0x1000;
+    const AVAIL: u64 = 0x2000;
+    const USED: u64 = 0x3000;
+    const BUF: u64 = 0x4000;
+
+    fn write_reg(dev: &mut VirtioRngDevice, offset: u64, value: u32, mem: &GuestMemoryMmap) {
+        dev.mmio_write(offset, &value.to_le_bytes(), Some(mem));
+    }
+
+    #[test]
+    fn test_fills_guest_buffer_with_entropy() {
+        let mem = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 1024 * 1024)]).unwrap();
+        let mut dev = VirtioRngDevice::new();
+        write_reg(&mut dev, mmio::QUEUE_NUM, 8, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DESC_LOW, DESC as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DRIVER_LOW, AVAIL as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_DEVICE_LOW, USED as u32, &mem);
+        write_reg(&mut dev, mmio::QUEUE_READY, 1, &mem);
+
+        // One 64-byte device-writable buffer.
+        mem.write_obj(BUF, GuestAddress(DESC)).unwrap();
+        mem.write_obj(64u32, GuestAddress(DESC + 8)).unwrap();
+        mem.write_obj(VRING_DESC_F_WRITE, GuestAddress(DESC + 12))
+            .unwrap();
+        mem.write_obj(0u16, GuestAddress(AVAIL + 4)).unwrap();
+        mem.write_obj(1u16, GuestAddress(AVAIL + 2)).unwrap();
+        write_reg(&mut dev, mmio::QUEUE_NOTIFY, 0, &mem);
+
+        let used_idx: u16 = mem.read_obj(GuestAddress(USED + 2)).unwrap();
+        let used_len: u32 = mem.read_obj(GuestAddress(USED + 8)).unwrap();
+        assert_eq!(used_idx, 1);
+        assert_eq!(used_len, 64);
+        let mut buf = [0u8; 64];
+        mem.read_slice(&mut buf, GuestAddress(BUF)).unwrap();
+        assert_ne!(buf, [0u8; 64]);
+        assert!(dev.has_pending_interrupt());
+
+        let mut restored = VirtioRngDevice::new();
+        restored.restore_state(&dev.snapshot_state());
+        assert_eq!(restored.used_idx, 1);
+        assert!(restored.queue.ready);
+    }
+}
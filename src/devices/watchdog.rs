@@ -0,0 +1,172 @@
+//! Watchdog device for detecting hung guests.
+//!
+//! The guest-agent pets the device from its own thread every fraction of
+//! the timeout announced on the kernel cmdline. A guest whose kernel has
+//! wedged (not just an agent that stopped answering RPCs) stops petting,
+//! and the host notices from the device alone, without a round trip to the
+//! guest. The device does nothing to the guest itself; the sandbox polls
+//! [`WatchdogDevice::check`] and runs its crash-report and restart path.
+//!
+//! On x86_64 the registers are the ib700-style I/O ports
+//! [`WATCHDOG_PET_PORT`] and [`WATCHDOG_STOP_PORT`]; on aarch64 they sit in
+//! the page at [`WATCHDOG_MMIO_ADDR`]. Any write to the pet register arms
+//! the watchdog and restarts its countdown; any write to the stop register
+//! disarms it. Reads return zero.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+#[cfg(target_arch = "aarch64")]
+use void_box_protocol::{WATCHDOG_MMIO_ADDR, WATCHDOG_MMIO_PET, WATCHDOG_MMIO_STOP};
+use void_box_protocol::{WATCHDOG_PET_PORT, WATCHDOG_STOP_PORT};
+
+/// Size of the aarch64 register page.
+pub const WATCHDOG_MMIO_SIZE: u64 = 0x1000;
+
+pub struct WatchdogDevice {
+    timeout: Duration,
+    state: Mutex<WatchdogState>,
+    pets: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct WatchdogState {
+    /// Time of the last pet while armed; `None` until the guest first pets
+    /// it, and after it disarms it.
+    last_pet: Option<Instant>,
+    /// Set once [`WatchdogDevice::check`] has reported an expiry, so it is
+    /// reported once.
+    fired: bool,
+}
+
+impl WatchdogDevice {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            state: Mutex::new(WatchdogState::default()),
+            pets: AtomicU64::new(0),
+        }
+    }
+
+    /// How long the guest may go without petting once armed.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Pets received over the device's lifetime.
+    pub fn pets(&self) -> u64 {
+        self.pets.load(Ordering::Relaxed)
+    }
+
+    /// Arm the watchdog, or restart its countdown.
+    pub fn pet(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.last_pet.is_none() {
+            debug!("Watchdog armed ({:?})", self.timeout);
+        }
+        state.last_pet = Some(Instant::now());
+        state.fired = false;
+        self.pets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Disarm the watchdog until the next pet.
+    pub fn disarm(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.last_pet.take().is_some() {
+            debug!("Watchdog disarmed");
+        }
+    }
+
+    /// How long the guest has gone without petting, if that exceeds the
+    /// timeout. Reported once per expiry: `None` again until the guest
+    /// pets and then misses another deadline.
+    pub fn check(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let silent = state.last_pet?.elapsed();
+        if state.fired || silent < self.timeout {
+            return None;
+        }
+        state.fired = true;
+        warn!("Watchdog expired: no pet for {:?}", silent);
+        Some(silent)
+    }
+
+    /// Handle a port write if `port` is one of the watchdog's.
+    pub fn io_out(&self, port: u16) -> bool {
+        match port {
+            WATCHDOG_PET_PORT => self.pet(),
+            WATCHDOG_STOP_PORT => self.disarm(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handle a port read if `port` is one of the watchdog's.
+    pub fn io_in(&self, port: u16, data: &mut [u8]) -> bool {
+        if port != WATCHDOG_PET_PORT && port != WATCHDOG_STOP_PORT {
+            return false;
+        }
+        data.iter_mut().for_each(|b| *b = 0);
+        true
+    }
+
+    /// Handle an MMIO write if `addr` falls in the register page.
+    #[cfg(target_arch = "aarch64")]
+    pub fn mmio_write(&self, addr: u64) -> bool {
+        match addr.checked_sub(WATCHDOG_MMIO_ADDR) {
+            Some(WATCHDOG_MMIO_PET) => self.pet(),
+            Some(WATCHDOG_MMIO_STOP) => self.disarm(),
+            Some(offset) if offset < WATCHDOG_MMIO_SIZE => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handle an MMIO read if `addr` falls in the register page.
+    #[cfg(target_arch = "aarch64")]
+    pub fn mmio_read(&self, addr: u64, data: &mut [u8]) -> bool {
+        if !(WATCHDOG_MMIO_ADDR..WATCHDOG_MMIO_ADDR + WATCHDOG_MMIO_SIZE).contains(&addr) {
+            return false;
+        }
+        data.iter_mut().for_each(|b| *b = 0);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unarmed_watchdog_never_expires() {
+        let watchdog = WatchdogDevice::new(Duration::ZERO);
+        assert_eq!(watchdog.check(), None);
+
+        watchdog.pet();
+        watchdog.disarm();
+        assert_eq!(watchdog.check(), None);
+    }
+
+    #[test]
+    fn expiry_is_reported_once_per_missed_deadline() {
+        let watchdog = WatchdogDevice::new(Duration::ZERO);
+        assert!(watchdog.io_out(WATCHDOG_PET_PORT));
+        assert!(watchdog.check().is_some());
+        assert_eq!(watchdog.check(), None);
+
+        watchdog.pet();
+        assert!(watchdog.check().is_some());
+        assert_eq!(watchdog.pets(), 2);
+    }
+
+    #[test]
+    fn pets_within_the_timeout_keep_it_quiet() {
+        let watchdog = WatchdogDevice::new(Duration::from_secs(60));
+        watchdog.pet();
+        assert_eq!(watchdog.check(), None);
+        assert!(watchdog.io_out(WATCHDOG_STOP_PORT));
+        assert!(!watchdog.io_out(0x3f8));
+    }
+}
@@ -201,6 +201,19 @@ pub enum Error {
     #[error("Guest communication error: {0}")]
     Guest(String),
 
+    /// A guest command was killed by the guest kernel's OOM killer
+    #[error("Guest out of memory: {0}")]
+    GuestOom(String),
+
+    /// The guest kernel panicked or the VM exited under a running exec
+    #[error("Guest crashed: {0}")]
+    GuestCrashed(Box<crate::observe::crash::CrashReport>),
+
+    /// The guest never completed its handshake; the diagnostics say what
+    /// the serial console showed
+    #[error("Boot failed: {0}")]
+    BootFailed(Box<crate::observe::boot::BootDiagnostics>),
+
     /// Network-related errors
     #[error("Network error: {0}")]
     Network(String),
@@ -217,6 +230,55 @@ pub enum Error {
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    /// Too many operations already in flight; retry later
+    #[error("Busy: {0}")]
+    Busy(String),
+
+    /// An agent run went over its [`Budget`](crate::budget::Budget)
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(Box<crate::budget::BudgetExceeded>),
+
+    /// An agent's answer did not provide the JSON its
+    /// [output schema](crate::output_schema) asked for
+    #[error("Structured output error: {0}")]
+    StructuredOutput(String),
+
+    /// A [`ToolHook`](crate::tool_hook::ToolHook) denied the agent a tool
+    #[error("Tool call denied: {tool}: {reason}")]
+    ToolDenied { tool: String, reason: String },
+
+    /// The sandbox's [`ExecPolicy`](crate::sandbox::ExecPolicy) refused a
+    /// command; `rule` names the rule that decided it
+    #[error("Exec denied: {command}: {rule}")]
+    ExecDenied { command: String, rule: String },
+
+    /// A workflow step ran past its deadline, or the workflow's; `limit`
+    /// is how long the step was given. See
+    /// [`WorkflowBuilder::step_timeout`](crate::workflow::WorkflowBuilder::step_timeout)
+    #[error("Step \"{step}\" exceeded its deadline of {limit:?}")]
+    DeadlineExceeded {
+        step: String,
+        limit: std::time::Duration,
+    },
+
+    /// A workflow step was stopped because its run was cancelled; see
+    /// [`WorkflowHandle::cancel`](crate::workflow::WorkflowHandle::cancel)
+    #[error("Step \"{step}\" was cancelled")]
+    Cancelled { step: String },
+
+    /// A file write in a [read-only](crate::sandbox::SandboxBuilder::read_only)
+    /// sandbox
+    #[error("Sandbox is read-only: refused {op} {path}")]
+    ReadOnly { op: String, path: String },
+
+    /// The backend lacks a feature the caller asked for; see
+    /// [`VmmBackend::capabilities`](crate::backend::VmmBackend::capabilities)
+    #[error("{backend} backend does not support {feature}")]
+    Unsupported {
+        feature: crate::backend::BackendCaps,
+        backend: crate::backend::Backend,
+    },
+
     /// VM is not running
     #[error("VM is not running")]
     VmNotRunning,
@@ -263,6 +325,68 @@ pub enum Error {
     Protocol(#[from] void_box_protocol::ProtocolError),
 }
 
+/// Whose fault a failure was; see [`Error::failure_domain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureDomain {
+    /// The command itself failed: a non-zero exit, a policy denial, a
+    /// deadline. Running it again on a fresh VM fails the same way.
+    Command,
+    /// The VM or the connection to it failed under the command: a guest
+    /// crash, a boot failure, a dropped vsock stream. A fresh VM may
+    /// succeed.
+    Infrastructure,
+}
+
+/// Guest communication errors that mean the transport went away rather
+/// than the command failing.
+const TRANSPORT_FAILURES: &[&str] = &[
+    "channel closed",
+    "stream closed",
+    "connection reset",
+    "broken pipe",
+    "heartbeat timed out",
+    "Failed to connect",
+];
+
+impl Error {
+    /// Classify the failure, e.g. to decide whether a workflow step is
+    /// worth re-running on a replacement sandbox. Running out of guest
+    /// memory counts as the command's: a replacement of the same size
+    /// would run out too.
+    pub fn failure_domain(&self) -> FailureDomain {
+        use std::io::ErrorKind;
+
+        match self {
+            #[cfg(target_os = "linux")]
+            Error::Kvm(_) | Error::System(_) => FailureDomain::Infrastructure,
+            Error::Backend(_)
+            | Error::Memory(_)
+            | Error::Boot(_)
+            | Error::Device(_)
+            | Error::Vcpu(_)
+            | Error::GuestCrashed(_)
+            | Error::BootFailed(_)
+            | Error::VmNotRunning => FailureDomain::Infrastructure,
+            Error::Io(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::ConnectionReset
+                        | ErrorKind::ConnectionAborted
+                        | ErrorKind::BrokenPipe
+                        | ErrorKind::NotConnected
+                        | ErrorKind::UnexpectedEof
+                ) =>
+            {
+                FailureDomain::Infrastructure
+            }
+            Error::Guest(msg) if TRANSPORT_FAILURES.iter().any(|m| msg.contains(m)) => {
+                FailureDomain::Infrastructure
+            }
+            _ => FailureDomain::Command,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -277,6 +401,32 @@ mod tests {
         assert_eq!(parsed["retryable"], false);
     }
 
+    #[test]
+    fn test_failure_domains() {
+        assert_eq!(
+            Error::VmNotRunning.failure_domain(),
+            FailureDomain::Infrastructure
+        );
+        assert_eq!(
+            Error::Guest("exec streaming channel closed without ExecResponse".into())
+                .failure_domain(),
+            FailureDomain::Infrastructure
+        );
+        assert_eq!(
+            Error::Io(std::io::ErrorKind::BrokenPipe.into()).failure_domain(),
+            FailureDomain::Infrastructure
+        );
+        assert_eq!(
+            Error::Guest("Command failed with exit code 2: make: *** [all]".into())
+                .failure_domain(),
+            FailureDomain::Command
+        );
+        assert_eq!(
+            Error::GuestOom("make".into()).failure_domain(),
+            FailureDomain::Command
+        );
+    }
+
     #[test]
     fn test_invalid_params_not_retryable() {
         let err = ApiError::invalid_params("bad param");
@@ -22,6 +22,7 @@ pub fn build_exec_request(
     timeout_secs: Option<u64>,
     span_context: Option<&SpanContext>,
 ) -> ExecRequest {
+    let overrides = current_exec_overrides();
     let mut exec_env = env.to_vec();
     if let Some(ctx) = span_context {
         if !exec_env.iter().any(|(k, _)| k == "TRACEPARENT") {
@@ -35,9 +36,120 @@ pub fn build_exec_request(
         env: exec_env,
         working_dir: working_dir.map(String::from),
         timeout_secs,
+        exec_id: overrides.exec_id,
+        policy: overrides.policy,
+        user: overrides.user,
+        stdin_stream: overrides.stdin_stream,
     }
 }
 
+/// Per-exec settings carried by a task-local scope rather than threaded
+/// through every exec signature.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExecOverrides {
+    pub(crate) policy: Option<ExecPolicy>,
+    pub(crate) user: Option<ExecUser>,
+    pub(crate) exec_id: Option<String>,
+    /// Working directory in place of the sandbox's default.
+    pub(crate) working_dir: Option<String>,
+    /// Applied over the sandbox's environment.
+    pub(crate) env: Vec<(String, String)>,
+    /// Keep stdin open for an [`crate::sandbox::ExecStdin`]; needs `exec_id`.
+    pub(crate) stdin_stream: bool,
+}
+
+tokio::task_local! {
+    static EXEC_OVERRIDES: ExecOverrides;
+}
+
+/// Run `fut` with `policy` sent along with every exec it starts (on the
+/// same task), in place of the guest's own exec policy.
+pub(crate) async fn scope_exec_policy<F: std::future::Future>(
+    policy: ExecPolicy,
+    fut: F,
+) -> F::Output {
+    let overrides = ExecOverrides {
+        policy: Some(policy),
+        ..current_exec_overrides()
+    };
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// Run `fut` with every exec it starts (on the same task) running as
+/// `user`.
+pub(crate) async fn scope_exec_user<F: std::future::Future>(user: ExecUser, fut: F) -> F::Output {
+    let overrides = ExecOverrides {
+        user: Some(user),
+        ..current_exec_overrides()
+    };
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// Run `fut` with the exec it starts (on the same task) addressable as
+/// `exec_id`, e.g. by `Sandbox::signal`.
+pub(crate) async fn scope_exec_id<F: std::future::Future>(exec_id: String, fut: F) -> F::Output {
+    let overrides = ExecOverrides {
+        exec_id: Some(exec_id),
+        ..current_exec_overrides()
+    };
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// Run `fut` with every exec it starts (on the same task) running in
+/// `working_dir`.
+pub(crate) async fn scope_exec_working_dir<F: std::future::Future>(
+    working_dir: String,
+    fut: F,
+) -> F::Output {
+    let overrides = ExecOverrides {
+        working_dir: Some(working_dir),
+        ..current_exec_overrides()
+    };
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// Run `fut` with `env` set for every exec it starts (on the same task),
+/// after the sandbox's own environment.
+pub(crate) async fn scope_exec_env<F: std::future::Future>(
+    env: Vec<(String, String)>,
+    fut: F,
+) -> F::Output {
+    let mut overrides = current_exec_overrides();
+    overrides.env.extend(env);
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// Run `fut` with the exec it starts (on the same task) keeping its stdin
+/// open for chunks addressed to `exec_id`.
+pub(crate) async fn scope_exec_stdin_stream<F: std::future::Future>(
+    exec_id: String,
+    fut: F,
+) -> F::Output {
+    let overrides = ExecOverrides {
+        exec_id: Some(exec_id),
+        stdin_stream: true,
+        ..current_exec_overrides()
+    };
+    EXEC_OVERRIDES.scope(overrides, fut).await
+}
+
+/// [`scope_exec_user`] when `user` is set; otherwise just `fut`.
+pub(crate) async fn maybe_scope_exec_user<F: std::future::Future>(
+    user: Option<ExecUser>,
+    fut: F,
+) -> F::Output {
+    match user {
+        Some(user) => scope_exec_user(user, fut).await,
+        None => fut.await,
+    }
+}
+
+/// The overrides made active by [`scope_exec_policy`] and
+/// [`scope_exec_user`].
+pub(crate) fn current_exec_overrides() -> ExecOverrides {
+    EXEC_OVERRIDES.try_with(Clone::clone).unwrap_or_default()
+}
+
 /// Read a complete [`Message`] from an async tokio stream.
 ///
 /// This is the host-side async counterpart of [`Message::read_from_sync`].
@@ -91,6 +203,10 @@ mod tests {
             env: Vec::new(),
             working_dir: None,
             timeout_secs: Some(30),
+            exec_id: None,
+            policy: None,
+            user: None,
+            stdin_stream: false,
         };
 
         let json = serde_json::to_string(&req).unwrap();
@@ -100,6 +216,14 @@ mod tests {
         assert_eq!(decoded.args, vec!["hello"]);
     }
 
+    #[tokio::test]
+    async fn test_scoped_exec_id_names_the_request() {
+        let build = || build_exec_request("sleep", &["60"], &[], &[], None, None, None);
+        assert!(build().exec_id.is_none());
+        let request = scope_exec_id("dev-server".into(), async { build() }).await;
+        assert_eq!(request.exec_id.as_deref(), Some("dev-server"));
+    }
+
     #[test]
     fn test_telemetry_batch_serialize() {
         let batch = TelemetryBatch {
@@ -113,6 +237,7 @@ mod tests {
                 net_tx_bytes: 2000,
                 procs_running: 3,
                 open_fds: 128,
+                disks: Vec::new(),
             }),
             processes: vec![ProcessMetrics {
                 pid: 1,
@@ -120,6 +245,7 @@ mod tests {
                 rss_bytes: 4096,
                 cpu_jiffies: 100,
                 state: 'S',
+                ..Default::default()
             }],
             trace_context: None,
         };
@@ -0,0 +1,617 @@
+//! Build guest initramfs images from Rust.
+//!
+//! [`GuestImageBuilder`] assembles the same rootfs as
+//! `scripts/build_guest_image.sh` — directory skeleton, DHCP script,
+//! guest-agent as `/init`, BusyBox and kernel modules — plus whatever
+//! tooling the caller adds, and packs it as a gzip-compressed `newc` cpio
+//! archive without external tools:
+//!
+//! ```no_run
+//! use void_box::guest_image::GuestImageBuilder;
+//!
+//! # fn demo() -> void_box::Result<()> {
+//! let cpio = GuestImageBuilder::new()
+//!     .guest_agent("target/x86_64-unknown-linux-musl/release/guest-agent")
+//!     .busybox()
+//!     .with_binary("/opt/tools/jq")
+//!     .with_module("overlay.ko")
+//!     .build_cpio()?;
+//! std::fs::write("/tmp/custom-initramfs.cpio.gz", cpio)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every entry is owned by root and dated to the epoch, so the same inputs
+//! always produce the same image. Binaries must be static or come with
+//! their libraries ([`with_file`](GuestImageBuilder::with_file)); the
+//! guest-agent loads the modules it knows from `/lib/modules` at boot.
+//!
+//! [`GuestImageBuilder::overlay`] builds an archive to append to an existing
+//! initramfs instead: the kernel unpacks concatenated archives in order, so
+//! its files replace the base image's. This is how
+//! [`SandboxBuilder::initramfs_overlay`](crate::sandbox::SandboxBuilder::initramfs_overlay)
+//! injects a host directory into the guest.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// Directories of the rootfs skeleton.
+const SKELETON: &[&str] = &[
+    "bin",
+    "sbin",
+    "proc",
+    "sys",
+    "dev",
+    "tmp",
+    "usr/local/bin",
+    "etc",
+    "etc/udhcpc",
+    "usr/share/udhcpc",
+    "lib/modules",
+];
+
+/// udhcpc hook that applies the leased address, route and DNS servers.
+const DHCP_SCRIPT: &str = r#"#!/bin/sh
+case "$1" in
+  bound|renew)
+    /bin/ip addr flush dev "$interface" 2>/dev/null
+    /bin/ip addr add "$ip/$mask" dev "$interface"
+    if [ -n "$router" ]; then
+      /bin/ip route add default via "$router" dev "$interface"
+    fi
+    if [ -n "$dns" ]; then
+      : > /etc/resolv.conf
+      for d in $dns; do
+        echo "nameserver $d" >> /etc/resolv.conf
+      done
+    fi
+    ;;
+esac
+"#;
+
+/// Commands linked to BusyBox in `/bin`. `ping` is left out: BusyBox's
+/// needs raw sockets.
+const BUSYBOX_APPLETS: &[&str] = &[
+    "sh", "echo", "cat", "tr", "test", "base64", "uname", "ls", "mkdir", "rm", "cp", "mv", "pwd",
+    "id", "hostname", "ip", "ifconfig", "route", "sed", "grep", "awk", "env", "wget", "nc",
+    "udhcpc", "dd", "stat", "chmod", "wc", "touch", "head", "tail", "sort", "uniq", "date", "df",
+    "du", "find", "xargs", "which", "basename", "dirname", "readlink", "realpath", "sleep",
+];
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Contents of a regular file in the image.
+#[derive(Debug, Clone)]
+enum FileData {
+    Host(PathBuf),
+    Bytes(Vec<u8>),
+    /// A kernel module, looked up when the image is built.
+    Module(String),
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Dir,
+    File { data: FileData, mode: u32 },
+    Symlink(String),
+}
+
+/// Where BusyBox comes from.
+#[derive(Debug, Clone)]
+enum BusyBox {
+    /// `$BUSYBOX`, else `busybox` on the host's `PATH`.
+    Host,
+    Path(PathBuf),
+}
+
+/// Builder for a guest initramfs; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct GuestImageBuilder {
+    entries: BTreeMap<String, Entry>,
+    busybox: Option<BusyBox>,
+    modules_dir: Option<PathBuf>,
+    /// Host directory trees, with the guest directory each is copied to.
+    dirs: Vec<(PathBuf, String)>,
+    has_init: bool,
+    /// Built by [`overlay`](GuestImageBuilder::overlay).
+    overlay: bool,
+}
+
+impl Default for GuestImageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestImageBuilder {
+    /// The rootfs skeleton with the DHCP client script.
+    pub fn new() -> Self {
+        let mut builder = Self {
+            entries: BTreeMap::new(),
+            busybox: None,
+            modules_dir: None,
+            dirs: Vec::new(),
+            has_init: false,
+            overlay: false,
+        };
+        for dir in SKELETON {
+            builder.entries.insert(dir.to_string(), Entry::Dir);
+        }
+        for path in [
+            "usr/share/udhcpc/default.script",
+            "etc/udhcpc/default.script",
+        ] {
+            builder = builder.with_file(path, DHCP_SCRIPT, 0o755);
+        }
+        builder
+    }
+
+    /// An archive to append to a base initramfs: no skeleton and no `/init`
+    /// required, only what is added.
+    pub fn overlay() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            busybox: None,
+            modules_dir: None,
+            dirs: Vec::new(),
+            has_init: false,
+            overlay: true,
+        }
+    }
+
+    /// Install the guest-agent binary as `/init` (PID 1) and
+    /// `/sbin/guest-agent`. Required.
+    pub fn guest_agent(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self = self.with_binary_at(&path, "/init");
+        self.has_init = true;
+        self.with_binary_at(path, "/sbin/guest-agent")
+    }
+
+    /// Install BusyBox from `$BUSYBOX` or the host's `PATH` as `/bin/busybox`,
+    /// with `/bin/sh` and the usual commands linked to it. The binary must
+    /// be static.
+    pub fn busybox(mut self) -> Self {
+        self.busybox = Some(BusyBox::Host);
+        self
+    }
+
+    /// [`busybox`](Self::busybox) with the binary at `path`.
+    pub fn busybox_at(mut self, path: impl Into<PathBuf>) -> Self {
+        self.busybox = Some(BusyBox::Path(path.into()));
+        self
+    }
+
+    /// Install the host binary `path` as `/usr/local/bin/<file name>`.
+    pub fn with_binary(self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.with_binary_at(path, format!("/usr/local/bin/{name}"))
+    }
+
+    /// Install the host binary `path` at `guest_path`.
+    pub fn with_binary_at(mut self, path: impl Into<PathBuf>, guest_path: impl AsRef<str>) -> Self {
+        self.insert(
+            guest_path.as_ref(),
+            Entry::File {
+                data: FileData::Host(path.into()),
+                mode: 0o755,
+            },
+        );
+        self
+    }
+
+    /// Add a file with `contents` at `guest_path`.
+    pub fn with_file(
+        mut self,
+        guest_path: impl AsRef<str>,
+        contents: impl Into<Vec<u8>>,
+        mode: u32,
+    ) -> Self {
+        self.insert(
+            guest_path.as_ref(),
+            Entry::File {
+                data: FileData::Bytes(contents.into()),
+                mode: mode & 0o7777,
+            },
+        );
+        self
+    }
+
+    /// Add a symlink at `guest_path` pointing to `target`.
+    pub fn with_symlink(mut self, guest_path: impl AsRef<str>, target: impl Into<String>) -> Self {
+        self.insert(guest_path.as_ref(), Entry::Symlink(target.into()));
+        self
+    }
+
+    /// Copy the host directory tree `dir` into `guest_dir`, keeping file
+    /// permissions and symlinks. The tree is read when the image is built.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>, guest_dir: impl Into<String>) -> Self {
+        self.dirs.push((dir.into(), guest_dir.into()));
+        self
+    }
+
+    /// Install the kernel module `name` (e.g. `overlay.ko`) in
+    /// `/lib/modules`. It is looked up in the [`modules_dir`](Self::modules_dir),
+    /// uncompressed or as `.ko.zst`, when the image is built.
+    pub fn with_module(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.insert(
+            &format!("/lib/modules/{name}"),
+            Entry::File {
+                data: FileData::Module(name),
+                mode: 0o644,
+            },
+        );
+        self
+    }
+
+    /// Directory searched (recursively) for [`with_module`](Self::with_module);
+    /// defaults to the running kernel's `/lib/modules/$(uname -r)`, which
+    /// only fits a guest booting the host's kernel.
+    pub fn modules_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.modules_dir = Some(dir.into());
+        self
+    }
+
+    /// Build the image as a gzip-compressed `newc` cpio archive.
+    pub fn build_cpio(self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write_cpio(&mut out)?;
+        Ok(out)
+    }
+
+    /// [`build_cpio`](Self::build_cpio), streamed to `out`.
+    pub fn write_cpio<W: Write>(mut self, out: W) -> Result<()> {
+        if !self.has_init && !self.overlay {
+            return Err(Error::Config(
+                "guest image has no /init; call guest_agent(path)".into(),
+            ));
+        }
+        if let Some(busybox) = self.busybox.take() {
+            let path = match busybox {
+                BusyBox::Path(path) => path,
+                BusyBox::Host => find_busybox().ok_or_else(|| {
+                    Error::Config("busybox not found; set BUSYBOX or use busybox_at(path)".into())
+                })?,
+            };
+            self = self.with_binary_at(path, "/bin/busybox");
+            for applet in BUSYBOX_APPLETS {
+                self = self.with_symlink(format!("/bin/{applet}"), "busybox");
+            }
+        }
+        for (dir, guest_dir) in std::mem::take(&mut self.dirs) {
+            self.insert_tree(&dir, guest_dir.trim_matches('/'))?;
+        }
+
+        let mut writer = CpioWriter::new(flate2::write::GzEncoder::new(
+            out,
+            flate2::Compression::default(),
+        ));
+        for (path, entry) in &self.entries {
+            match entry {
+                Entry::Dir => writer.entry(path, S_IFDIR | 0o755, &[])?,
+                Entry::Symlink(target) => writer.entry(path, S_IFLNK | 0o777, target.as_bytes())?,
+                Entry::File { data, mode } => {
+                    let contents = match data {
+                        FileData::Bytes(bytes) => bytes.clone(),
+                        FileData::Host(host) => std::fs::read(host).map_err(|e| {
+                            Error::Config(format!("cannot read {}: {}", host.display(), e))
+                        })?,
+                        FileData::Module(name) => self.read_module(name)?,
+                    };
+                    writer.entry(path, S_IFREG | mode, &contents)?;
+                }
+            }
+        }
+        writer.finish()?.finish()?;
+        Ok(())
+    }
+
+    /// Add `entry` at `guest_path`, with any missing parent directories.
+    fn insert(&mut self, guest_path: &str, entry: Entry) {
+        let path = guest_path.trim_matches('/');
+        let mut parent = Path::new(path).parent();
+        while let Some(dir) = parent.filter(|d| !d.as_os_str().is_empty()) {
+            self.entries
+                .entry(dir.to_string_lossy().into_owned())
+                .or_insert(Entry::Dir);
+            parent = dir.parent();
+        }
+        self.entries.insert(path.to_string(), entry);
+    }
+
+    /// Add the contents of the host directory `dir` under `guest_dir`.
+    fn insert_tree(&mut self, dir: &Path, guest_dir: &str) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| Error::Config(format!("cannot read {}: {}", dir.display(), e)))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let guest_path = match guest_dir {
+                "" => name,
+                parent => format!("{parent}/{name}"),
+            };
+            let metadata = std::fs::symlink_metadata(&path)?;
+            if metadata.is_symlink() {
+                let target = std::fs::read_link(&path)?;
+                self.insert(
+                    &guest_path,
+                    Entry::Symlink(target.to_string_lossy().into_owned()),
+                );
+            } else if metadata.is_dir() {
+                self.insert(&guest_path, Entry::Dir);
+                self.insert_tree(&path, &guest_path)?;
+            } else if metadata.is_file() {
+                self.insert(
+                    &guest_path,
+                    Entry::File {
+                        data: FileData::Host(path),
+                        mode: metadata.permissions().mode() & 0o7777,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn read_module(&self, name: &str) -> Result<Vec<u8>> {
+        let dir = match &self.modules_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let release = std::process::Command::new("uname").arg("-r").output()?;
+                PathBuf::from("/lib/modules").join(String::from_utf8_lossy(&release.stdout).trim())
+            }
+        };
+        if let Some(path) = find_file(&dir, name) {
+            return Ok(std::fs::read(&path)?);
+        }
+        if let Some(path) = find_file(&dir, &format!("{name}.zst")) {
+            let compressed = std::fs::File::open(&path)?;
+            return Ok(zstd::stream::decode_all(compressed)?);
+        }
+        let hint = match find_file(&dir, &format!("{name}.xz")) {
+            Some(path) => format!(
+                "; only {} exists, decompress it and pass that directory",
+                path.display()
+            ),
+            None => String::new(),
+        };
+        Err(Error::Config(format!(
+            "kernel module {} not found under {}{}",
+            name,
+            dir.display(),
+            hint
+        )))
+    }
+}
+
+/// One overlay archive holding the host directories `dirs`, each copied
+/// to the guest's `/`; later directories win.
+pub(crate) fn overlay_dirs(dirs: &[PathBuf]) -> Result<Vec<u8>> {
+    dirs.iter()
+        .fold(GuestImageBuilder::overlay(), |image, dir| {
+            image.with_dir(dir, "/")
+        })
+        .build_cpio()
+}
+
+/// `$BUSYBOX`, else `busybox` on `PATH`.
+fn find_busybox() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("BUSYBOX").map(PathBuf::from) {
+        return path.is_file().then_some(path);
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join("busybox"))
+        .find(|path| path.is_file())
+}
+
+/// The first file named `name` under `dir`, searched depth-first.
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir).ok()?.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in &entries {
+        let path = entry.path();
+        if entry.file_name() == name && path.is_file() {
+            return Some(path);
+        }
+    }
+    entries
+        .iter()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .find_map(|e| find_file(&e.path(), name))
+}
+
+/// Writer for the `newc` (SVR4, no CRC) cpio format the kernel unpacks.
+struct CpioWriter<W: Write> {
+    out: W,
+    next_ino: u32,
+}
+
+impl<W: Write> CpioWriter<W> {
+    fn new(out: W) -> Self {
+        Self { out, next_ino: 1 }
+    }
+
+    fn entry(&mut self, path: &str, mode: u32, data: &[u8]) -> Result<()> {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let nlink = if mode & S_IFDIR == S_IFDIR { 2 } else { 1 };
+        self.header(path, ino, mode, nlink, data.len())?;
+        self.out.write_all(data)?;
+        self.pad(data.len())?;
+        Ok(())
+    }
+
+    fn header(&mut self, name: &str, ino: u32, mode: u32, nlink: u32, size: usize) -> Result<()> {
+        let fields = [
+            ino,
+            mode,
+            0, // uid
+            0, // gid
+            nlink,
+            0, // mtime
+            size as u32,
+            0, // devmajor
+            0, // devminor
+            0, // rdevmajor
+            0, // rdevminor
+            name.len() as u32 + 1,
+            0, // check
+        ];
+        let mut header = String::from("070701");
+        for field in fields {
+            header.push_str(&format!("{:08x}", field));
+        }
+        self.out.write_all(header.as_bytes())?;
+        self.out.write_all(name.as_bytes())?;
+        self.out.write_all(&[0])?;
+        self.pad(header.len() + name.len() + 1)
+    }
+
+    /// Pad to a 4-byte boundary after `len` bytes.
+    fn pad(&mut self, len: usize) -> Result<()> {
+        let padding = (4 - len % 4) % 4;
+        self.out.write_all(&[0u8; 3][..padding])?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.header("TRAILER!!!", 0, 0, 1, 0)?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// (name, mode, data) of each entry in a gzip-compressed newc archive.
+    fn unpack(cpio_gz: &[u8]) -> Vec<(String, u32, Vec<u8>)> {
+        let mut raw = Vec::new();
+        flate2::read::GzDecoder::new(cpio_gz)
+            .read_to_end(&mut raw)
+            .unwrap();
+        let field = |at: usize| {
+            u32::from_str_radix(std::str::from_utf8(&raw[at..at + 8]).unwrap(), 16).unwrap()
+                as usize
+        };
+        let align = |n: usize| n.div_ceil(4) * 4;
+        let mut entries = Vec::new();
+        let mut at = 0;
+        loop {
+            assert_eq!(&raw[at..at + 6], b"070701");
+            let mode = field(at + 6 + 8) as u32;
+            let size = field(at + 6 + 6 * 8);
+            let name_len = field(at + 6 + 11 * 8);
+            let name_at = at + 110;
+            let name = String::from_utf8(raw[name_at..name_at + name_len - 1].to_vec()).unwrap();
+            let data_at = align(name_at + name_len);
+            if name == "TRAILER!!!" {
+                return entries;
+            }
+            entries.push((name, mode, raw[data_at..data_at + size].to_vec()));
+            at = align(data_at + size);
+        }
+    }
+
+    #[test]
+    fn test_image_contains_agent_tools_and_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = dir.path().join("guest-agent");
+        std::fs::write(&agent, b"agent").unwrap();
+        let busybox = dir.path().join("busybox");
+        std::fs::write(&busybox, b"busybox").unwrap();
+        let modules = dir.path().join("modules/kernel/fs/overlayfs");
+        std::fs::create_dir_all(&modules).unwrap();
+        std::fs::write(
+            modules.join("overlay.ko.zst"),
+            zstd::encode_all(&b"module"[..], 0).unwrap(),
+        )
+        .unwrap();
+
+        let build = || {
+            GuestImageBuilder::new()
+                .guest_agent(&agent)
+                .busybox_at(&busybox)
+                .with_file("/etc/motd", "hi\n", 0o644)
+                .modules_dir(dir.path().join("modules"))
+                .with_module("overlay.ko")
+                .build_cpio()
+                .unwrap()
+        };
+        let image = build();
+        assert_eq!(image, build(), "images are reproducible");
+
+        let entries = unpack(&image);
+        let find = |name: &str| entries.iter().find(|(n, _, _)| n == name).unwrap();
+        assert_eq!(
+            find("init"),
+            &("init".into(), S_IFREG | 0o755, b"agent".to_vec())
+        );
+        assert_eq!(find("bin/sh").1, S_IFLNK | 0o777);
+        assert_eq!(find("bin/sh").2, b"busybox");
+        assert_eq!(find("lib/modules/overlay.ko").2, b"module");
+        assert_eq!(find("etc/motd").1, S_IFREG | 0o644);
+        let names: Vec<_> = entries.iter().map(|(n, _, _)| n.as_str()).collect();
+        assert!(
+            names.iter().position(|n| *n == "usr").unwrap()
+                < names.iter().position(|n| *n == "usr/local/bin").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_overlay_copies_host_tree() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("usr/local/bin")).unwrap();
+        let tool = dir.path().join("usr/local/bin/tool");
+        std::fs::write(&tool, b"#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o750)).unwrap();
+        std::os::unix::fs::symlink("tool", dir.path().join("usr/local/bin/t")).unwrap();
+
+        let entries = unpack(&overlay_dirs(&[dir.path().to_path_buf()]).unwrap());
+        let names: Vec<_> = entries.iter().map(|(n, _, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "usr",
+                "usr/local",
+                "usr/local/bin",
+                "usr/local/bin/t",
+                "usr/local/bin/tool"
+            ]
+        );
+        assert_eq!(entries[3].1, S_IFLNK | 0o777);
+        assert_eq!(entries[3].2, b"tool");
+        assert_eq!(entries[4].1, S_IFREG | 0o750);
+    }
+
+    #[test]
+    fn test_missing_inputs_fail_the_build() {
+        assert!(GuestImageBuilder::new().build_cpio().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let agent = dir.path().join("guest-agent");
+        std::fs::write(&agent, b"agent").unwrap();
+        let missing_module = GuestImageBuilder::new()
+            .guest_agent(&agent)
+            .modules_dir(dir.path())
+            .with_module("overlay.ko")
+            .build_cpio();
+        assert!(matches!(missing_module, Err(Error::Config(_))));
+    }
+}
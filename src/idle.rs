@@ -0,0 +1,98 @@
+//! Finalizing agent runs that have gone quiet.
+//!
+//! Some agent CLIs finish their work and then wait for more input instead
+//! of exiting, holding the run open until its timeout. An [`IdlePolicy`] on
+//! [`AgentExecOpts`](crate::observe::claude::AgentExecOpts) or a
+//! [`VoidBox`](crate::agent_box::VoidBox::idle_policy) ends such a run
+//! once the agent has been idle long enough: no output, no tool call
+//! waiting for its result, and no guest CPU above
+//! [`max_cpu_percent`](IdlePolicy::max_cpu_percent). The agent is sent
+//! `SIGTERM`, then `SIGKILL` if it lingers, and the run returns what it
+//! produced with [`idle_finalized`](crate::observe::claude::AgentExecResult::idle_finalized)
+//! set, so a box's session is saved as after any other run.
+//!
+//! CPU is read from guest telemetry and is only checked while
+//! [telemetry is running](crate::sandbox::Sandbox::start_telemetry).
+//! Pending tool calls are only known for runners that
+//! [stream their tool calls](crate::agent_runner::AgentRunner::streams_tool_calls).
+//! The policy applies to agents running in the guest; the host-side loop
+//! of OpenAI-compatible providers never idles.
+
+use std::time::Duration;
+
+use crate::observe::telemetry::TelemetryStats;
+
+/// Mean guest CPU at or below which an agent counts as idle.
+pub const DEFAULT_IDLE_CPU_PERCENT: f64 = 5.0;
+
+/// How long a finalized agent gets to exit after `SIGTERM` before it is
+/// killed.
+pub const IDLE_STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// When to consider a running agent done.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdlePolicy {
+    /// Quiet time before the agent is finalized.
+    pub after: Duration,
+    /// Highest mean guest CPU over the quiet time that still counts as
+    /// idle.
+    pub max_cpu_percent: f64,
+}
+
+impl IdlePolicy {
+    /// Finalize after `after` without activity.
+    pub fn after(after: Duration) -> Self {
+        Self {
+            after,
+            max_cpu_percent: DEFAULT_IDLE_CPU_PERCENT,
+        }
+    }
+
+    /// Override the mean guest CPU percentage below which the agent counts as idle.
+    pub fn max_cpu_percent(mut self, percent: f64) -> Self {
+        self.max_cpu_percent = percent;
+        self
+    }
+
+    /// Whether an agent that has printed nothing for [`after`](Self::after)
+    /// is idle, given its tool calls still waiting for a result and the
+    /// guest CPU over that time, if telemetry saw any.
+    pub fn is_idle(&self, pending_tool_calls: usize, cpu: Option<&TelemetryStats>) -> bool {
+        if pending_tool_calls > 0 {
+            return false;
+        }
+        match cpu {
+            Some(stats) if stats.batches > 0 => stats.cpu_avg_percent <= self.max_cpu_percent,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu(percent: f64) -> TelemetryStats {
+        TelemetryStats {
+            batches: 3,
+            cpu_avg_percent: percent,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_quiet_agent_is_idle() {
+        let policy = IdlePolicy::after(Duration::from_secs(30));
+        assert!(policy.is_idle(0, None));
+        assert!(policy.is_idle(0, Some(&cpu(2.0))));
+        assert!(policy.is_idle(0, Some(&TelemetryStats::default())));
+    }
+
+    #[test]
+    fn test_busy_agent_is_not_idle() {
+        let policy = IdlePolicy::after(Duration::from_secs(30)).max_cpu_percent(10.0);
+        assert!(!policy.is_idle(1, None));
+        assert!(!policy.is_idle(0, Some(&cpu(40.0))));
+        assert!(policy.is_idle(0, Some(&cpu(10.0))));
+    }
+}
@@ -1,13 +1,17 @@
 //! Auto image resolution — download, cache, and verify pre-built artifacts.
 //!
-//! Downloads kernel and initramfs from GitHub Releases, verifies SHA-256
-//! checksums, and caches under `~/.void-box/images/<version>/`.
+//! Downloads kernel and initramfs from GitHub Releases (or another
+//! [`ArtifactSource`]), verifies SHA-256 checksums, and caches under
+//! `~/.void-box/images/<version>/`. See [`ArtifactFetcher::from_env`] for
+//! mirrors, pinned checksums and offline mode.
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tracing::{info, warn};
 
@@ -34,7 +38,9 @@ const BASE_BACKOFF: Duration = Duration::from_secs(1);
 pub fn flavor_for_provider(provider: &str) -> Option<&'static str> {
     match provider.to_ascii_lowercase().as_str() {
         "codex" => Some("codex"),
-        "claude" | "claude-personal" | "ollama" | "lm-studio" | "custom" => Some("claude"),
+        "claude" | "claude-personal" | "ollama" | "lm-studio" | "custom" | "openai" => {
+            Some("claude")
+        }
         _ => None,
     }
 }
@@ -134,97 +140,383 @@ pub fn verify_checksum(file_path: &Path, expected_hex: &str) -> Result<(), Image
 // Download + cache
 // ---------------------------------------------------------------------------
 
-/// Download a single artifact with retries, checksum verification, and progress.
+/// Download a single artifact from the [`ArtifactFetcher::from_env`]
+/// source, with retries, checksum verification, and progress.
 ///
 /// Returns the cached file path on success.
 pub async fn download_and_cache(
     cache_root: &Path,
     artifact_name: &str,
 ) -> Result<PathBuf, ImageError> {
-    let version_tag = format!("v{}", VERSION);
-    let ver_dir = version_cache_dir_in(cache_root);
-    fs::create_dir_all(&ver_dir)
-        .map_err(|e| ImageError::CacheDir(format!("{}: {}", ver_dir.display(), e)))?;
-
-    let artifact_url = download_url(&version_tag, artifact_name);
-    let checksum_url = format!("{}.sha256", artifact_url);
-    let dest = ver_dir.join(artifact_name);
-
-    for attempt in 0..MAX_ATTEMPTS {
-        if attempt > 0 {
-            let backoff = BASE_BACKOFF * 2u32.pow(attempt - 1);
-            warn!(attempt, "retrying download after {:?}", backoff);
-            tokio::time::sleep(backoff).await;
+    ArtifactFetcher::from_env()?
+        .fetch(cache_root, artifact_name)
+        .await
+}
+
+// ---------------------------------------------------------------------------
+// Artifact sources
+// ---------------------------------------------------------------------------
+
+/// Where release artifacts are downloaded from.
+///
+/// Every source serves the layout of a GitHub release: artifact `name` of
+/// release `version_tag`, next to its `<name>.sha256` and optionally a
+/// `SHA256SUMS` manifest.
+#[async_trait::async_trait]
+pub trait ArtifactSource: Send + Sync {
+    /// Where the source points, for logs and errors.
+    fn describe(&self) -> String;
+
+    /// Whether fetching needs the network; offline mode refuses sources
+    /// that do.
+    fn needs_network(&self) -> bool {
+        true
+    }
+
+    /// Fetch `name` into `dest`. If `dest` already holds the start of the
+    /// file from an interrupted fetch, the source may append the rest
+    /// instead of starting over.
+    async fn fetch(&self, version_tag: &str, name: &str, dest: &Path) -> Result<(), ImageError>;
+}
+
+/// Artifacts served over HTTP(S) in the GitHub release layout:
+/// `<base>/<version_tag>/<name>`.
+pub struct HttpSource {
+    base_url: String,
+    client: reqwest::Client,
+    progress: indicatif::MultiProgress,
+}
+
+impl HttpSource {
+    /// A mirror of the GitHub releases at `base_url`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            progress: indicatif::MultiProgress::new(),
         }
+    }
 
-        let last_attempt = attempt + 1 == MAX_ATTEMPTS;
-        let checksum_dest = ver_dir.join(format!("{}.sha256", artifact_name));
+    /// The void-box GitHub releases.
+    pub fn github() -> Self {
+        Self::new(GITHUB_RELEASES_URL)
+    }
 
-        match download_file(&artifact_url, &dest).await {
-            Ok(()) => {}
-            Err(e) if e.is_retryable() && !last_attempt => {
-                warn!(%artifact_url, error = %e, "download failed, will retry");
-                continue;
-            }
-            Err(e) => return Err(e),
+    /// A public (or presigned) S3 bucket holding one release per
+    /// `<prefix>/<version_tag>/` directory. Requests are not signed.
+    pub fn s3(bucket: &str, region: Option<&str>, prefix: &str) -> Self {
+        let host = match region {
+            Some(region) => format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+            None => format!("https://{}.s3.amazonaws.com", bucket),
+        };
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            Self::new(host)
+        } else {
+            Self::new(format!("{}/{}", host, prefix))
+        }
+    }
+
+    /// The URL of `name` in release `version_tag`.
+    pub fn url(&self, version_tag: &str, name: &str) -> String {
+        format!("{}/{}/{}", self.base_url, version_tag, name)
+    }
+}
+
+#[async_trait::async_trait]
+impl ArtifactSource for HttpSource {
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn fetch(&self, version_tag: &str, name: &str, dest: &Path) -> Result<(), ImageError> {
+        download_file(
+            &self.client,
+            &self.progress,
+            &self.url(version_tag, name),
+            dest,
+        )
+        .await
+    }
+}
+
+/// Artifacts in a local directory, e.g. an air-gapped copy of a release:
+/// `<dir>/<version_tag>/<name>`, or `<dir>/<name>`.
+pub struct LocalDirSource {
+    dir: PathBuf,
+}
+
+impl LocalDirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl ArtifactSource for LocalDirSource {
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn needs_network(&self) -> bool {
+        false
+    }
+
+    async fn fetch(&self, version_tag: &str, name: &str, dest: &Path) -> Result<(), ImageError> {
+        let versioned = self.dir.join(version_tag).join(name);
+        let src = if versioned.exists() {
+            versioned
+        } else {
+            self.dir.join(name)
+        };
+        if !src.exists() {
+            return Err(ImageError::NotFound(src.display().to_string()));
         }
+        fs::copy(&src, dest).map_err(|e| ImageError::Io(src.clone(), e))?;
+        Ok(())
+    }
+}
+
+/// Parse an artifact source location: an `http(s)://` mirror URL,
+/// `s3://<bucket>[/<prefix>]` (region from `AWS_REGION` or
+/// `AWS_DEFAULT_REGION`), or a local directory as `file://<dir>` or an
+/// absolute path.
+pub fn parse_artifact_source(location: &str) -> Result<Box<dyn ArtifactSource>, ImageError> {
+    if location.starts_with("https://") || location.starts_with("http://") {
+        return Ok(Box::new(HttpSource::new(location)));
+    }
+    if let Some(rest) = location.strip_prefix("s3://") {
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(ImageError::Source(format!("no bucket in '{}'", location)));
+        }
+        let region = std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .ok();
+        return Ok(Box::new(HttpSource::s3(bucket, region.as_deref(), prefix)));
+    }
+    let dir = location.strip_prefix("file://").unwrap_or(location);
+    if Path::new(dir).is_absolute() {
+        return Ok(Box::new(LocalDirSource::new(dir)));
+    }
+    Err(ImageError::Source(format!(
+        "'{}' is not an http(s):// or s3:// URL or an absolute directory",
+        location
+    )))
+}
+
+/// Expected SHA-256 digests by artifact name, as in a `SHA256SUMS` file
+/// (`sha256sum` output).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    entries: BTreeMap<String, String>,
+}
 
-        match download_file(&checksum_url, &checksum_dest).await {
-            Ok(()) => {}
-            Err(e) if e.is_retryable() && !last_attempt => {
-                let _ = fs::remove_file(&dest);
-                warn!("checksum download failed, will retry: {}", e);
+impl ChecksumManifest {
+    /// Parse `sha256sum` output; blank lines and `#` comments are skipped.
+    pub fn parse(content: &str) -> Result<Self, ImageError> {
+        let mut entries = BTreeMap::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
                 continue;
             }
-            Err(e) => {
-                let _ = fs::remove_file(&dest);
-                return Err(e);
-            }
+            let (hex, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| ImageError::ChecksumParse(line.to_string()))?;
+            // `sha256sum -b` marks binary files with a leading '*'.
+            let name = name.trim().trim_start_matches('*');
+            entries.insert(name.to_string(), parse_checksum_hex(hex)?);
         }
+        Ok(Self { entries })
+    }
 
-        let checksum_content = match fs::read_to_string(&checksum_dest) {
-            Ok(c) => c,
-            Err(e) => {
-                let _ = fs::remove_file(&dest);
-                let _ = fs::remove_file(&checksum_dest);
-                return Err(ImageError::Io(checksum_dest, e));
-            }
+    /// Read and parse the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Self, ImageError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ImageError::Io(path.to_path_buf(), e))?;
+        Self::parse(&content)
+    }
+
+    /// The expected digest of `artifact_name`, if listed.
+    pub fn get(&self, artifact_name: &str) -> Option<&str> {
+        self.entries.get(artifact_name).map(String::as_str)
+    }
+}
+
+/// Downloads artifacts into the cache from an [`ArtifactSource`].
+///
+/// Each artifact is checked against, in order of preference: a pinned
+/// [`ChecksumManifest`], the source's `SHA256SUMS`, or its `<name>.sha256`.
+/// Only a pinned manifest protects against a compromised source; the others
+/// catch corruption. Interrupted downloads resume from the partial file on
+/// the next attempt.
+pub struct ArtifactFetcher {
+    source: Box<dyn ArtifactSource>,
+    version_tag: String,
+    offline: bool,
+    pinned: Option<ChecksumManifest>,
+}
+
+impl ArtifactFetcher {
+    /// Fetch this void-box version's artifacts from `source`.
+    pub fn new(source: Box<dyn ArtifactSource>) -> Self {
+        Self {
+            source,
+            version_tag: format!("v{}", VERSION),
+            offline: false,
+            pinned: None,
+        }
+    }
+
+    /// The fetcher configured by the environment:
+    ///
+    /// - `VOID_BOX_ARTIFACT_SOURCE`: where to download from (see
+    ///   [`parse_artifact_source`]); the GitHub releases by default.
+    /// - `VOID_BOX_ARTIFACT_SHA256SUMS`: path of a pinned checksum manifest.
+    /// - `VOID_BOX_OFFLINE=1`: never touch the network.
+    pub fn from_env() -> Result<Self, ImageError> {
+        let source = match std::env::var("VOID_BOX_ARTIFACT_SOURCE") {
+            Ok(location) if !location.is_empty() => parse_artifact_source(&location)?,
+            _ => Box::new(HttpSource::github()),
         };
-        let expected_hex = match parse_checksum_hex(&checksum_content) {
-            Ok(hex) => hex,
-            Err(e) => {
-                let _ = fs::remove_file(&dest);
-                let _ = fs::remove_file(&checksum_dest);
-                if !last_attempt {
-                    warn!("checksum parse failed, will retry: {}", e);
-                    continue;
+        let mut fetcher = Self::new(source)
+            .offline(std::env::var("VOID_BOX_OFFLINE").is_ok_and(|v| v == "1" || v == "true"));
+        if let Some(path) = std::env::var_os("VOID_BOX_ARTIFACT_SHA256SUMS") {
+            fetcher = fetcher.pinned_checksums(ChecksumManifest::load(Path::new(&path))?);
+        }
+        Ok(fetcher)
+    }
+
+    /// Fail instead of downloading when the source needs the network.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Require every artifact to match `manifest`; unlisted artifacts fail.
+    pub fn pinned_checksums(mut self, manifest: ChecksumManifest) -> Self {
+        self.pinned = Some(manifest);
+        self
+    }
+
+    /// Download `artifact_name` into the cache and verify it. Returns the
+    /// cached file path.
+    pub async fn fetch(
+        &self,
+        cache_root: &Path,
+        artifact_name: &str,
+    ) -> Result<PathBuf, ImageError> {
+        if self.offline && self.source.needs_network() {
+            return Err(ImageError::Offline(artifact_name.to_string()));
+        }
+        let ver_dir = version_cache_dir_in(cache_root);
+        fs::create_dir_all(&ver_dir)
+            .map_err(|e| ImageError::CacheDir(format!("{}: {}", ver_dir.display(), e)))?;
+
+        let dest = ver_dir.join(artifact_name);
+        let partial = ver_dir.join(format!("{}.part", artifact_name));
+
+        for attempt in 0..MAX_ATTEMPTS {
+            if attempt > 0 {
+                let backoff = BASE_BACKOFF * 2u32.pow(attempt - 1);
+                warn!(attempt, "retrying download after {:?}", backoff);
+                tokio::time::sleep(backoff).await;
+            }
+            let last_attempt = attempt + 1 == MAX_ATTEMPTS;
+
+            let result = async {
+                let expected_hex = self.expected_checksum(&ver_dir, artifact_name).await?;
+                self.source
+                    .fetch(&self.version_tag, artifact_name, &partial)
+                    .await?;
+                verify_checksum(&partial, &expected_hex)
+            }
+            .await;
+
+            match result {
+                Ok(()) => {
+                    fs::rename(&partial, &dest).map_err(|e| ImageError::Io(dest.clone(), e))?;
+                    info!(artifact = artifact_name, "checksum verified");
+                    return Ok(dest);
+                }
+                Err(e) => {
+                    // Keep a partial download to resume; a bad one starts over.
+                    if matches!(e, ImageError::ChecksumMismatch { .. }) {
+                        let _ = fs::remove_file(&partial);
+                    }
+                    if e.is_retryable() && !last_attempt {
+                        warn!(source = %self.source.describe(), error = %e, "download failed, will retry");
+                        continue;
+                    }
+                    return Err(e);
                 }
-                return Err(e);
             }
-        };
+        }
 
-        match verify_checksum(&dest, &expected_hex) {
+        unreachable!("retry loop should return or error")
+    }
+
+    /// [`fetch`](Self::fetch) several artifacts concurrently.
+    pub async fn fetch_all(
+        &self,
+        cache_root: &Path,
+        artifact_names: &[String],
+    ) -> Result<Vec<PathBuf>, ImageError> {
+        futures_util::future::try_join_all(
+            artifact_names
+                .iter()
+                .map(|name| self.fetch(cache_root, name)),
+        )
+        .await
+    }
+
+    /// The digest `artifact_name` must have.
+    async fn expected_checksum(
+        &self,
+        ver_dir: &Path,
+        artifact_name: &str,
+    ) -> Result<String, ImageError> {
+        if let Some(pinned) = &self.pinned {
+            return pinned
+                .get(artifact_name)
+                .map(str::to_string)
+                .ok_or_else(|| ImageError::ChecksumMissing(artifact_name.to_string()));
+        }
+        let sums_dest = ver_dir.join(format!("SHA256SUMS.{}", artifact_name));
+        let _ = fs::remove_file(&sums_dest);
+        match self
+            .source
+            .fetch(&self.version_tag, "SHA256SUMS", &sums_dest)
+            .await
+        {
             Ok(()) => {
-                info!(artifact = artifact_name, "checksum verified");
-                return Ok(dest);
-            }
-            Err(e) => {
-                let _ = fs::remove_file(&dest);
-                let _ = fs::remove_file(&checksum_dest);
-                if !last_attempt {
-                    warn!("checksum mismatch, will retry: {}", e);
-                    continue;
+                let manifest = ChecksumManifest::load(&sums_dest);
+                let _ = fs::remove_file(&sums_dest);
+                if let Some(hex) = manifest?.get(artifact_name) {
+                    return Ok(hex.to_string());
                 }
-                return Err(e);
             }
+            Err(ImageError::HttpStatus(..) | ImageError::NotFound(_)) => {}
+            Err(e) => return Err(e),
         }
-    }
 
-    unreachable!("retry loop should return or error")
+        let checksum_dest = ver_dir.join(format!("{}.sha256", artifact_name));
+        let _ = fs::remove_file(&checksum_dest);
+        self.source
+            .fetch(
+                &self.version_tag,
+                &format!("{}.sha256", artifact_name),
+                &checksum_dest,
+            )
+            .await?;
+        let content = fs::read_to_string(&checksum_dest)
+            .map_err(|e| ImageError::Io(checksum_dest.clone(), e))?;
+        parse_checksum_hex(&content)
+    }
 }
 
 /// Resolve the kernel path, following the resolution chain:
-/// 1. `--kernel` flag / `VOID_BOX_KERNEL` env var → use it
+/// 1. `--kernel` flag / `VOID_BOX_KERNEL` env var → check its architecture → use it
 /// 2. Linux: `/boot/vmlinuz-$(uname -r)` → use it (no download)
 /// 3. Cache hit → use it
 /// 4. Download from GitHub release → verify → cache → use it
@@ -235,6 +527,9 @@ pub async fn resolve_kernel(
     // Step 1: explicit override
     if let Some(path) = explicit {
         if path.exists() {
+            if let Ok(arch) = detect_arch() {
+                verify_kernel_arch(path, arch)?;
+            }
             return Ok(path.to_path_buf());
         }
         return Err(ImageError::NotFound(path.display().to_string()));
@@ -300,33 +595,48 @@ pub async fn resolve_initramfs(
     download_and_cache(cache_root, &artifact).await
 }
 
-/// Downloads a file from `url` to `dest` with streaming I/O and a progress bar.
-async fn download_file(url: &str, dest: &Path) -> Result<(), ImageError> {
+/// Downloads a file from `url` to `dest` with streaming I/O and a progress
+/// bar, resuming from the bytes already in `dest`.
+async fn download_file(
+    client: &reqwest::Client,
+    progress: &indicatif::MultiProgress,
+    url: &str,
+    dest: &Path,
+) -> Result<(), ImageError> {
     use futures_util::StreamExt;
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
+    let offset = fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    let resp = request
         .send()
         .await
         .map_err(|e| ImageError::Network(url.to_string(), e.to_string()))?;
 
     let status = resp.status();
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+        // Already complete; the checksum decides whether it is intact.
+        return Ok(());
+    }
     if status.is_client_error() {
         return Err(ImageError::HttpStatus(url.to_string(), status.as_u16()));
     }
     if status.is_server_error() {
         return Err(ImageError::HttpRetryable(url.to_string(), status.as_u16()));
     }
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
 
-    let total_size = resp.content_length().unwrap_or(0);
+    let total_size = resp.content_length().unwrap_or(0) + if resumed { offset } else { 0 };
     let filename = dest
         .file_name()
         .and_then(|n| n.to_str())
-        .unwrap_or("artifact");
+        .unwrap_or("artifact")
+        .trim_end_matches(".part");
 
     let pb = if total_size > 0 {
-        let pb = indicatif::ProgressBar::new(total_size);
+        let pb = progress.add(indicatif::ProgressBar::new(total_size));
         pb.set_style(
             indicatif::ProgressStyle::default_bar()
                 .template("[{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}")
@@ -334,13 +644,21 @@ async fn download_file(url: &str, dest: &Path) -> Result<(), ImageError> {
                 .progress_chars("=> "),
         );
         pb.set_message(filename.to_string());
+        if resumed {
+            pb.set_position(offset);
+        }
         Some(pb)
     } else {
         eprintln!("[download] {}", filename);
         None
     };
 
-    let mut file = fs::File::create(dest).map_err(|e| ImageError::Io(dest.to_path_buf(), e))?;
+    let mut file = if resumed {
+        fs::OpenOptions::new().append(true).open(dest)
+    } else {
+        fs::File::create(dest)
+    }
+    .map_err(|e| ImageError::Io(dest.to_path_buf(), e))?;
     let mut stream = resp.bytes_stream();
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| ImageError::Network(url.to_string(), e.to_string()))?;
@@ -388,7 +706,10 @@ pub fn list_cached(cache_root: &Path) -> Vec<CachedArtifact> {
         };
         for file_entry in files.flatten() {
             let fname = file_entry.file_name().to_string_lossy().to_string();
-            if fname.ends_with(".sha256") {
+            if fname.ends_with(".sha256")
+                || fname.ends_with(".part")
+                || fname.starts_with("SHA256SUMS")
+            {
                 continue;
             }
             let size = file_entry.metadata().map(|m| m.len()).unwrap_or(0);
@@ -473,6 +794,133 @@ fn dir_size(path: &Path) -> u64 {
     total
 }
 
+// ---------------------------------------------------------------------------
+// Artifact targets
+// ---------------------------------------------------------------------------
+
+/// File in an artifact bundle naming its [`ArtifactTarget`]; see
+/// [`ArtifactManifest`].
+pub const ARTIFACT_MANIFEST: &str = "manifest.json";
+
+/// The architecture and backend a kernel + initramfs bundle was built for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactTarget {
+    /// Guest CPU architecture: `x86_64` or `aarch64`.
+    pub arch: String,
+    /// Backend the kernel boots under: `kvm` or `vz`.
+    pub backend: String,
+}
+
+impl ArtifactTarget {
+    /// What this host boots: guests of its own architecture, under VZ on
+    /// macOS and KVM elsewhere.
+    pub fn host() -> Result<Self, ImageError> {
+        let backend = if cfg!(target_os = "macos") {
+            "vz"
+        } else {
+            "kvm"
+        };
+        Ok(Self {
+            arch: detect_arch()?.to_string(),
+            backend: backend.to_string(),
+        })
+    }
+
+    /// Name of this target's bundle directory, e.g. `aarch64-vz`.
+    pub fn dir_name(&self) -> String {
+        format!("{}-{}", self.arch, self.backend)
+    }
+}
+
+impl std::fmt::Display for ArtifactTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.arch, self.backend)
+    }
+}
+
+/// The [`ARTIFACT_MANIFEST`] of an artifact bundle:
+///
+/// ```json
+/// {"arch": "aarch64", "backend": "vz", "kernel": "vmlinux", "initramfs": "initramfs.cpio.gz"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactManifest {
+    #[serde(flatten)]
+    pub target: ArtifactTarget,
+    /// Kernel filename, relative to the bundle directory.
+    pub kernel: String,
+    /// Initramfs filename, relative to the bundle directory.
+    pub initramfs: String,
+}
+
+impl ArtifactManifest {
+    /// Read and parse the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Self, ImageError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ImageError::Io(path.to_path_buf(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| ImageError::Manifest(path.display().to_string(), e.to_string()))
+    }
+}
+
+/// The CPU architecture a kernel image runs on, read from its header: an
+/// ELF `vmlinux`, an x86 `bzImage` or an arm64 `Image`. `None` for images
+/// whose header does not say, such as a compressed arm64 kernel.
+pub fn kernel_arch(path: &Path) -> Result<Option<&'static str>, ImageError> {
+    use std::io::Read;
+
+    let file = fs::File::open(path).map_err(|e| ImageError::Io(path.to_path_buf(), e))?;
+    let mut header = Vec::with_capacity(KERNEL_HEADER_LEN);
+    file.take(KERNEL_HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .map_err(|e| ImageError::Io(path.to_path_buf(), e))?;
+    Ok(kernel_arch_from_header(&header))
+}
+
+/// Bytes of a kernel image [`kernel_arch`] reads: through the x86 boot
+/// protocol's `HdrS` magic at 0x202.
+const KERNEL_HEADER_LEN: usize = 0x206;
+
+fn kernel_arch_from_header(header: &[u8]) -> Option<&'static str> {
+    const EM_X86_64: u16 = 62;
+    const EM_AARCH64: u16 = 183;
+
+    if header.starts_with(b"\x7fELF") && header.len() >= 20 {
+        let machine = [header[18], header[19]];
+        // EI_DATA: 2 is big-endian.
+        let machine = if header[5] == 2 {
+            u16::from_be_bytes(machine)
+        } else {
+            u16::from_le_bytes(machine)
+        };
+        return match machine {
+            EM_X86_64 => Some("x86_64"),
+            EM_AARCH64 => Some("aarch64"),
+            _ => None,
+        };
+    }
+    if header.get(56..60) == Some(b"ARM\x64") {
+        return Some("aarch64");
+    }
+    if header.get(0x202..0x206) == Some(b"HdrS") {
+        return Some("x86_64");
+    }
+    None
+}
+
+/// Fail unless the kernel at `path` runs on `arch`. Kernels whose header
+/// does not name an architecture pass.
+pub fn verify_kernel_arch(path: &Path, arch: &str) -> Result<(), ImageError> {
+    match kernel_arch(path)? {
+        Some(actual) if actual != arch => Err(ImageError::KernelArchMismatch {
+            kernel: path.display().to_string(),
+            expected: arch.to_string(),
+            actual: actual.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Installed artifact detection (migrated from artifacts.rs)
 // ---------------------------------------------------------------------------
@@ -484,17 +932,61 @@ pub struct ArtifactPaths {
     pub initramfs: PathBuf,
 }
 
-/// Try to resolve artifacts from well-known installation paths.
+/// Try to resolve artifacts for this host from well-known installation
+/// paths; see [`select_bundle`].
 pub fn resolve_installed_artifacts() -> Option<ArtifactPaths> {
-    let candidates = installed_artifact_dirs();
-    for dir in &candidates {
-        let kernel = dir.join(installed_kernel_name());
-        let initramfs = dir.join("initramfs.cpio.gz");
-        if kernel.exists() && initramfs.exists() {
-            return Some(ArtifactPaths { kernel, initramfs });
+    let target = ArtifactTarget::host().ok()?;
+    select_bundle(&installed_artifact_dirs(), &target)
+}
+
+/// The first artifact bundle in `dirs` built for `target`.
+///
+/// Each directory may hold per-target bundles in `<arch>-<backend>/`
+/// subdirectories (see [`ArtifactTarget::dir_name`]) or be a bundle
+/// itself. A bundle with an [`ARTIFACT_MANIFEST`] is used only if the
+/// manifest names `target`; one without (packages predating manifests)
+/// only if its kernel's header does not name another architecture.
+pub fn select_bundle(dirs: &[PathBuf], target: &ArtifactTarget) -> Option<ArtifactPaths> {
+    dirs.iter().find_map(|dir| {
+        bundle_for_target(&dir.join(target.dir_name()), target)
+            .or_else(|| bundle_for_target(dir, target))
+    })
+}
+
+fn bundle_for_target(dir: &Path, target: &ArtifactTarget) -> Option<ArtifactPaths> {
+    let manifest_path = dir.join(ARTIFACT_MANIFEST);
+    let (kernel, initramfs) = if manifest_path.exists() {
+        let manifest = match ArtifactManifest::load(&manifest_path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!(error = %e, "skipping artifact bundle");
+                return None;
+            }
+        };
+        if manifest.target != *target {
+            warn!(
+                bundle = %dir.display(),
+                built_for = %manifest.target,
+                host = %target,
+                "skipping artifact bundle built for another target"
+            );
+            return None;
         }
+        (dir.join(&manifest.kernel), dir.join(&manifest.initramfs))
+    } else {
+        (
+            dir.join(installed_kernel_name()),
+            dir.join("initramfs.cpio.gz"),
+        )
+    };
+    if !kernel.exists() || !initramfs.exists() {
+        return None;
     }
-    None
+    if let Err(e) = verify_kernel_arch(&kernel, &target.arch) {
+        warn!(error = %e, "skipping artifact bundle");
+        return None;
+    }
+    Some(ArtifactPaths { kernel, initramfs })
 }
 
 fn installed_artifact_dirs() -> Vec<PathBuf> {
@@ -507,7 +999,10 @@ fn installed_artifact_dirs() -> Vec<PathBuf> {
     }
     #[cfg(not(target_os = "macos"))]
     {
-        vec![PathBuf::from("/usr/lib/voidbox")]
+        vec![
+            PathBuf::from("/usr/lib/voidbox"),
+            PathBuf::from("/usr/local/lib/voidbox"),
+        ]
     }
 }
 
@@ -553,6 +1048,25 @@ pub enum ImageError {
     #[error("checksum parse error: {0}")]
     ChecksumParse(String),
 
+    #[error("no pinned checksum for {0}")]
+    ChecksumMissing(String),
+
+    #[error("{0} is not cached and VOID_BOX_OFFLINE is set; run `voidbox image pull` while online or set VOID_BOX_KERNEL / VOID_BOX_INITRAMFS")]
+    Offline(String),
+
+    #[error("invalid artifact source: {0}")]
+    Source(String),
+
+    #[error("invalid artifact manifest {0}: {1}")]
+    Manifest(String, String),
+
+    #[error("kernel {kernel} is built for {actual}, but this host boots {expected} guests")]
+    KernelArchMismatch {
+        kernel: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("checksum mismatch for {artifact}: expected {expected}, got {actual}")]
     ChecksumMismatch {
         artifact: String,
@@ -646,6 +1160,7 @@ mod tests {
         assert_eq!(flavor_for_provider("ollama"), Some("claude"));
         assert_eq!(flavor_for_provider("lm-studio"), Some("claude"));
         assert_eq!(flavor_for_provider("custom"), Some("claude"));
+        assert_eq!(flavor_for_provider("openai"), Some("claude"));
         assert_eq!(flavor_for_provider("unknown-thing"), None);
     }
 
@@ -713,6 +1228,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_checksum_manifest_parse() {
+        let hex = "abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890";
+        let manifest = ChecksumManifest::parse(&format!(
+            "# v0.1.2\n{hex}  vmlinuz-x86_64\n\n{hex} *void-box-claude-x86_64.cpio.gz\n"
+        ))
+        .unwrap();
+        assert_eq!(manifest.get("vmlinuz-x86_64"), Some(hex));
+        assert_eq!(manifest.get("void-box-claude-x86_64.cpio.gz"), Some(hex));
+        assert_eq!(manifest.get("vmlinux-aarch64"), None);
+        assert!(ChecksumManifest::parse("not-a-checksum  vmlinuz-x86_64").is_err());
+    }
+
+    #[test]
+    fn test_parse_artifact_source() {
+        let mirror = parse_artifact_source("https://mirror.example.com/void-box/").unwrap();
+        assert_eq!(mirror.describe(), "https://mirror.example.com/void-box");
+        let s3 = HttpSource::s3("artifacts", Some("eu-west-1"), "/void-box/");
+        assert_eq!(
+            s3.url("v0.1.2", "vmlinuz-x86_64"),
+            "https://artifacts.s3.eu-west-1.amazonaws.com/void-box/v0.1.2/vmlinuz-x86_64"
+        );
+        let local = parse_artifact_source("file:///srv/void-box").unwrap();
+        assert!(!local.needs_network());
+        assert!(parse_artifact_source("relative/dir").is_err());
+        assert!(parse_artifact_source("s3://").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offline_fetch_fails_fast() {
+        let (_tmp, cache) = temp_cache_dir();
+        let fetcher = ArtifactFetcher::new(Box::new(HttpSource::github())).offline(true);
+        let err = fetcher.fetch(&cache, "vmlinuz-x86_64").await.unwrap_err();
+        assert!(matches!(err, ImageError::Offline(_)));
+    }
+
+    #[tokio::test]
+    async fn test_local_source_fetch_verifies_checksums() {
+        let (_tmp, cache) = temp_cache_dir();
+        let release = tempfile::tempdir().unwrap();
+        fs::write(release.path().join("vmlinuz-x86_64"), b"kernel").unwrap();
+        let hex = format!("{:x}", Sha256::digest(b"kernel"));
+        fs::write(
+            release.path().join("SHA256SUMS"),
+            format!("{hex}  vmlinuz-x86_64\n"),
+        )
+        .unwrap();
+
+        let fetcher =
+            ArtifactFetcher::new(Box::new(LocalDirSource::new(release.path()))).offline(true);
+        let path = fetcher
+            .fetch_all(&cache, &["vmlinuz-x86_64".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path[0]).unwrap(), b"kernel");
+        assert_eq!(check_cache(&cache, "vmlinuz-x86_64"), Some(path[0].clone()));
+
+        let pinned =
+            ChecksumManifest::parse(&format!("{}  vmlinuz-x86_64", "0".repeat(64))).unwrap();
+        let fetcher = ArtifactFetcher::new(Box::new(LocalDirSource::new(release.path())))
+            .pinned_checksums(pinned);
+        let err = fetcher.fetch(&cache, "vmlinuz-x86_64").await.unwrap_err();
+        assert!(matches!(err, ImageError::ChecksumMismatch { .. }));
+        let err = fetcher.fetch(&cache, "vmlinux-aarch64").await.unwrap_err();
+        assert!(matches!(err, ImageError::ChecksumMissing(_)));
+    }
+
     #[test]
     fn test_list_cached_empty() {
         let (_tmp, cache) = temp_cache_dir();
@@ -787,6 +1369,107 @@ mod tests {
         assert!(!dirs.is_empty());
     }
 
+    /// A kernel header naming `arch`, in the format each arch ships in.
+    fn fake_kernel(arch: &str) -> Vec<u8> {
+        let mut header = vec![0u8; KERNEL_HEADER_LEN];
+        match arch {
+            "aarch64" => header[56..60].copy_from_slice(b"ARM\x64"),
+            _ => header[0x202..0x206].copy_from_slice(b"HdrS"),
+        }
+        header
+    }
+
+    fn other_arch(arch: &str) -> &'static str {
+        if arch == "aarch64" {
+            "x86_64"
+        } else {
+            "aarch64"
+        }
+    }
+
+    #[test]
+    fn test_kernel_arch_from_header() {
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[5] = 1;
+        elf[18..20].copy_from_slice(&183u16.to_le_bytes());
+        assert_eq!(kernel_arch_from_header(&elf), Some("aarch64"));
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes());
+        assert_eq!(kernel_arch_from_header(&elf), Some("x86_64"));
+
+        assert_eq!(
+            kernel_arch_from_header(&fake_kernel("x86_64")),
+            Some("x86_64")
+        );
+        assert_eq!(
+            kernel_arch_from_header(&fake_kernel("aarch64")),
+            Some("aarch64")
+        );
+        assert_eq!(kernel_arch_from_header(b"\x1f\x8b\x08\x00"), None);
+    }
+
+    #[test]
+    fn test_select_bundle_follows_manifest_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = ArtifactTarget {
+            arch: "aarch64".to_string(),
+            backend: "vz".to_string(),
+        };
+        let write_bundle = |bundle: &Path, arch: &str, backend: &str| {
+            fs::create_dir_all(bundle).unwrap();
+            fs::write(bundle.join("kernel"), fake_kernel(arch)).unwrap();
+            fs::write(bundle.join("initramfs.cpio.gz"), b"initramfs").unwrap();
+            let manifest = ArtifactManifest {
+                target: ArtifactTarget {
+                    arch: arch.to_string(),
+                    backend: backend.to_string(),
+                },
+                kernel: "kernel".to_string(),
+                initramfs: "initramfs.cpio.gz".to_string(),
+            };
+            fs::write(
+                bundle.join(ARTIFACT_MANIFEST),
+                serde_json::to_string(&manifest).unwrap(),
+            )
+            .unwrap();
+        };
+
+        write_bundle(dir.path(), "x86_64", "kvm");
+        let dirs = vec![dir.path().to_path_buf()];
+        assert!(select_bundle(&dirs, &target).is_none());
+
+        write_bundle(&dir.path().join("aarch64-vz"), "aarch64", "vz");
+        let paths = select_bundle(&dirs, &target).expect("per-target bundle");
+        assert_eq!(paths.kernel, dir.path().join("aarch64-vz/kernel"));
+    }
+
+    #[test]
+    fn test_select_bundle_checks_kernel_without_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = ArtifactTarget::host().unwrap();
+        fs::write(dir.path().join("initramfs.cpio.gz"), b"initramfs").unwrap();
+        let kernel = dir.path().join(installed_kernel_name());
+        let dirs = vec![dir.path().to_path_buf()];
+
+        fs::write(&kernel, fake_kernel(other_arch(&target.arch))).unwrap();
+        assert!(select_bundle(&dirs, &target).is_none());
+
+        fs::write(&kernel, fake_kernel(&target.arch)).unwrap();
+        assert_eq!(select_bundle(&dirs, &target).unwrap().kernel, kernel);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_kernel_rejects_foreign_explicit_kernel() {
+        let (_dir, cache) = temp_cache_dir();
+        let kernel = cache.join("vmlinux");
+        fs::write(&kernel, fake_kernel(other_arch(detect_arch().unwrap()))).unwrap();
+        let err = resolve_kernel(Some(&kernel), &cache).await.unwrap_err();
+        assert!(
+            matches!(err, ImageError::KernelArchMismatch { .. }),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_installed_kernel_name() {
         let name = installed_kernel_name();
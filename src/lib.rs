@@ -86,23 +86,41 @@ pub mod workflow;
 // Cross-platform snapshot directory management
 pub mod snapshot_store;
 
+// Host-managed persistent volumes
+pub mod volume;
+
 // Agent(Skills) + Isolation = VoidBox
 pub mod agent_box;
+pub mod agent_runner;
+pub mod budget;
 pub mod credentials;
 pub mod daemon;
 pub mod daemon_listen;
+pub mod guest_image;
+pub mod idle;
 pub mod image;
 pub mod llm;
+pub mod openai_agent;
+pub mod output_schema;
 pub mod persistence;
 pub mod pipeline;
 pub mod proxy;
 pub mod runtime;
+pub mod secret;
+pub mod session;
 pub mod sidecar;
 pub mod skill;
+pub mod skill_lint;
+pub mod skill_registry;
 pub mod spec;
+pub mod ssh;
+pub mod test_report;
+pub mod tool_hook;
+#[cfg(feature = "voidbox-tui")]
+pub mod tui;
 
 // Re-exports for convenience
-pub use error::{Error, Result};
+pub use error::{Error, FailureDomain, Result};
 #[cfg(target_os = "linux")]
 pub use vmm::config::VoidBoxConfig;
 #[cfg(target_os = "linux")]
@@ -130,6 +148,69 @@ pub struct ExecOutput {
     pub stderr: Vec<u8>,
     /// Exit code of the command
     pub exit_code: i32,
+    /// Guest-side process timeline, when the guest-agent reported one
+    pub timeline: ExecTimeline,
+    /// Resources the process consumed, when the guest-agent reported them
+    pub usage: ExecUsage,
+    /// Test cases the process reported through
+    /// [`TEST_REPORT_ENV`](test_report::TEST_REPORT_ENV), if any
+    pub test_report: Option<test_report::TestReport>,
+}
+
+/// Resources consumed by an exec'd guest process, from `wait4()` rusage.
+///
+/// Includes descendants the process waited for. Fields are `None` for
+/// guest-agents that predate resource accounting or when the process
+/// never ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ExecUsage {
+    /// User + system CPU time in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    /// Peak resident set size in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rss_bytes: Option<u64>,
+    /// Block I/O, read plus written, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_bytes: Option<u64>,
+}
+
+impl From<&guest::protocol::ExecResponse> for ExecUsage {
+    fn from(response: &guest::protocol::ExecResponse) -> Self {
+        Self {
+            cpu_time_ms: response.cpu_time_ms,
+            max_rss_bytes: response.max_rss_bytes,
+            io_bytes: response.io_bytes,
+        }
+    }
+}
+
+/// When things happened to an exec'd guest process, in milliseconds since
+/// the guest-agent received the request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecTimeline {
+    /// Guest PID of the process
+    pub pid: Option<u32>,
+    /// Process spawned
+    pub spawn_ms: Option<u64>,
+    /// First byte written to stdout
+    pub first_stdout_ms: Option<u64>,
+    /// Timeout watchdog killed the process
+    pub timeout_ms: Option<u64>,
+    /// Process exited
+    pub exit_ms: Option<u64>,
+}
+
+impl From<&guest::protocol::ExecResponse> for ExecTimeline {
+    fn from(response: &guest::protocol::ExecResponse) -> Self {
+        Self {
+            pid: response.pid,
+            spawn_ms: response.spawn_ms,
+            first_stdout_ms: response.first_stdout_ms,
+            timeout_ms: response.timeout_ms,
+            exit_ms: response.duration_ms,
+        }
+    }
 }
 
 impl ExecOutput {
@@ -139,6 +220,9 @@ impl ExecOutput {
             stdout,
             stderr,
             exit_code,
+            timeline: ExecTimeline::default(),
+            usage: ExecUsage::default(),
+            test_report: None,
         }
     }
 
@@ -156,6 +240,34 @@ impl ExecOutput {
     pub fn success(&self) -> bool {
         self.exit_code == 0
     }
+
+    /// Convert a guest exec response, failing with [`Error::GuestOom`] when
+    /// the guest kernel's OOM killer took the command down.
+    pub(crate) fn from_response(
+        program: &str,
+        response: guest::protocol::ExecResponse,
+    ) -> Result<Self> {
+        if response.oom_killed {
+            return Err(Error::GuestOom(format!(
+                "'{}' was killed after exhausting guest memory",
+                program
+            )));
+        }
+        Ok(Self::from(response))
+    }
+}
+
+impl From<guest::protocol::ExecResponse> for ExecOutput {
+    fn from(response: guest::protocol::ExecResponse) -> Self {
+        Self {
+            timeline: ExecTimeline::from(&response),
+            usage: ExecUsage::from(&response),
+            stdout: response.stdout,
+            stderr: response.stderr,
+            exit_code: response.exit_code,
+            test_report: test_report::TestReport::from_cases(response.test_results),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -170,6 +282,53 @@ mod tests {
         assert_eq!(output.stderr_str(), "error\n");
     }
 
+    #[test]
+    fn test_exec_output_from_response_carries_usage() {
+        let output = ExecOutput::from(guest::protocol::ExecResponse {
+            cpu_time_ms: Some(250),
+            max_rss_bytes: Some(1 << 20),
+            ..guest::protocol::ExecResponse::success(Vec::new(), Vec::new(), 0, 300)
+        });
+        assert_eq!(
+            output.usage,
+            ExecUsage {
+                cpu_time_ms: Some(250),
+                max_rss_bytes: Some(1 << 20),
+                io_bytes: None,
+            }
+        );
+        assert_eq!(output.timeline.exit_ms, Some(300));
+        assert!(output.test_report.is_none());
+    }
+
+    #[test]
+    fn test_exec_output_from_response_carries_test_results() {
+        let case = guest::protocol::TestCaseResult {
+            suite: "tests/api.rs".into(),
+            name: "lists_runs".into(),
+            status: guest::protocol::TestStatus::Passed,
+            duration_ms: None,
+            message: None,
+        };
+        let output = ExecOutput::from(guest::protocol::ExecResponse {
+            test_results: vec![case.clone()],
+            ..guest::protocol::ExecResponse::success(Vec::new(), Vec::new(), 0, 10)
+        });
+        assert_eq!(output.test_report.unwrap().cases, vec![case]);
+    }
+
+    #[test]
+    fn test_exec_output_from_oom_killed_response_is_guest_oom() {
+        let response = guest::protocol::ExecResponse {
+            oom_killed: true,
+            ..guest::protocol::ExecResponse::success(Vec::new(), Vec::new(), -1, 40)
+        };
+        assert!(matches!(
+            ExecOutput::from_response("stress", response),
+            Err(Error::GuestOom(msg)) if msg.contains("stress")
+        ));
+    }
+
     #[test]
     fn test_exec_output_failure() {
         let output = ExecOutput::new(vec![], b"failed\n".to_vec(), 1);
@@ -18,6 +18,12 @@
 //!
 //! The guest binary, output format, and parser remain unchanged.
 //!
+//! Servers that speak only the OpenAI chat-completions API (OpenAI itself,
+//! vLLM, llama.cpp, LiteLLM, ...) use [`LlmProvider::OpenAi`] instead. No
+//! agent binary runs in the guest for it: the agent loop runs on the host
+//! (see [`crate::openai_agent`]) and executes the model's tool calls in the
+//! sandbox.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -38,6 +44,10 @@
 //! ```
 
 use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+/// Default base URL for [`LlmProvider::OpenAi`].
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
 
 /// The guest binary name for Claude Code and all Claude-compatible
 /// providers (Ollama, LmStudio, Custom, ClaudePersonal). These all route
@@ -78,7 +88,10 @@ impl std::fmt::Debug for ApiKey {
 ///
 /// Determines which LLM service the agent talks to. The provider is
 /// translated into environment variables injected into the guest VM.
-#[derive(Debug, Default, Clone)]
+///
+/// Serializes (for saved sessions) without its API key.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
 pub enum LlmProvider {
     /// Anthropic Claude API (default).
     ///
@@ -129,7 +142,8 @@ pub enum LlmProvider {
     Custom {
         /// Base URL of the API (e.g. `"https://openrouter.ai/api/v1"`).
         base_url: String,
-        /// API key (optional for local services).
+        /// API key (optional for local services). Never serialized.
+        #[serde(skip)]
         api_key: Option<ApiKey>,
         /// Model name override.
         model: Option<String>,
@@ -149,13 +163,31 @@ pub enum LlmProvider {
     /// Output is emitted as JSONL and parsed via the Codex observer
     /// (`crate::observe::codex::parse_codex_line`).
     Codex,
+
+    /// Any OpenAI-compatible chat-completions endpoint.
+    ///
+    /// The agent loop runs on the host ([`crate::openai_agent`]): it
+    /// streams completions from `base_url` and runs the model's `Bash`,
+    /// `Read` and `Write` tool calls in the sandbox. The API key stays on
+    /// the host; when unset, the host's `OPENAI_API_KEY` is used.
+    #[serde(rename = "openai")]
+    OpenAi {
+        /// Base URL up to and including `/v1`, as seen from the host.
+        /// Default: `https://api.openai.com/v1`.
+        base_url: Option<String>,
+        /// API key (optional for local servers). Never serialized.
+        #[serde(skip)]
+        api_key: Option<ApiKey>,
+        /// Model name (e.g. `"gpt-4.1"`, `"qwen2.5-coder-32b"`).
+        model: String,
+    },
 }
 
-/// Stream observer dispatcher for `Sandbox::exec_agent_streaming`.
+/// Which stream format a provider's agent speaks.
 ///
-/// Each provider tells the sandbox which parser to use for its agent's
-/// stdout. The sandbox dispatches to the matching `parse_*_line` function
-/// from the appropriate `observe::*` module.
+/// Each kind maps to the [`AgentRunner`](crate::agent_runner::AgentRunner)
+/// [`LlmProvider::runner`] returns, which dispatches to the matching
+/// `parse_*_line` function from the appropriate `observe::*` module.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObserverKind {
     /// Claude Code's `--output-format stream-json` JSONL events.
@@ -164,6 +196,10 @@ pub enum ObserverKind {
     /// Codex's `exec --json` JSONL events.
     /// Parsed by `crate::observe::codex::parse_codex_line`.
     Codex,
+    /// OpenAI chat-completions SSE chunks, consumed on the host by
+    /// `crate::openai_agent`. Parsed by
+    /// `crate::observe::openai::parse_openai_sse_line`.
+    OpenAiChat,
 }
 
 impl LlmProvider {
@@ -212,6 +248,30 @@ impl LlmProvider {
         }
     }
 
+    /// Create an OpenAI provider with the given model name.
+    ///
+    /// ```
+    /// use void_box::llm::LlmProvider;
+    /// let provider = LlmProvider::openai("gpt-4.1");
+    /// ```
+    pub fn openai(model: impl Into<String>) -> Self {
+        LlmProvider::OpenAi {
+            base_url: None,
+            api_key: None,
+            model: model.into(),
+        }
+    }
+
+    /// Create a provider for any OpenAI-compatible server, e.g. vLLM at
+    /// `http://localhost:8000/v1`.
+    pub fn openai_compatible(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        LlmProvider::OpenAi {
+            base_url: Some(base_url.into()),
+            api_key: None,
+            model: model.into(),
+        }
+    }
+
     /// Create a custom provider with the given base URL.
     pub fn custom(base_url: impl Into<String>) -> Self {
         LlmProvider::Custom {
@@ -223,10 +283,13 @@ impl LlmProvider {
 
     // -- Builder methods --
 
-    /// Set the API key (for Custom provider).
+    /// Set the API key (for Custom and OpenAi providers).
     pub fn api_key(mut self, key: impl Into<String>) -> Self {
         if let LlmProvider::Custom {
             ref mut api_key, ..
+        }
+        | LlmProvider::OpenAi {
+            ref mut api_key, ..
         } = self
         {
             *api_key = Some(ApiKey::new(key));
@@ -247,6 +310,9 @@ impl LlmProvider {
             }
             LlmProvider::LmStudio {
                 model: ref mut m, ..
+            }
+            | LlmProvider::OpenAi {
+                model: ref mut m, ..
             } => {
                 *m = name.into();
             }
@@ -262,6 +328,8 @@ impl LlmProvider {
     /// Used by `Sandbox::exec_agent_streaming` to resolve which bundled
     /// agent binary to run inside the VM. Each flavor's `build_*_rootfs.sh`
     /// script installs the matching binary into `/usr/local/bin/`.
+    /// [`OpenAi`](LlmProvider::OpenAi) runs no guest binary; its name only
+    /// labels the run.
     pub fn binary_name(&self) -> &'static str {
         match self {
             LlmProvider::Claude => CLAUDE_CODE_BINARY,
@@ -270,6 +338,7 @@ impl LlmProvider {
             LlmProvider::LmStudio { .. } => CLAUDE_CODE_BINARY,
             LlmProvider::Custom { .. } => CLAUDE_CODE_BINARY,
             LlmProvider::Codex => "codex",
+            LlmProvider::OpenAi { .. } => "openai",
         }
     }
 
@@ -277,7 +346,8 @@ impl LlmProvider {
     ///
     /// Maps each provider to the pre-built initramfs artifact name:
     /// - `"codex"` → `void-box-codex-<arch>.cpio.gz`
-    /// - `"claude"` → `void-box-claude-<arch>.cpio.gz` (all Claude-compatible providers)
+    /// - `"claude"` → `void-box-claude-<arch>.cpio.gz` (all Claude-compatible
+    ///   providers, and OpenAi, whose tools need only the base userland)
     ///
     /// Used by [`crate::image`] to construct the download URL and cache path.
     pub fn image_flavor(&self) -> &'static str {
@@ -287,14 +357,15 @@ impl LlmProvider {
             | LlmProvider::ClaudePersonal
             | LlmProvider::Ollama { .. }
             | LlmProvider::LmStudio { .. }
-            | LlmProvider::Custom { .. } => "claude",
+            | LlmProvider::Custom { .. }
+            | LlmProvider::OpenAi { .. } => "claude",
         }
     }
 
     /// Stream observer to use for this provider's agent stdout.
     ///
-    /// Drives dispatch in `Sandbox::exec_agent_streaming`: each
-    /// [`ObserverKind`] maps to a different `parse_*_line` function.
+    /// Drives [`runner`](Self::runner): each [`ObserverKind`] maps to a
+    /// different `parse_*_line` function.
     pub fn observer_kind(&self) -> ObserverKind {
         match self {
             LlmProvider::Claude
@@ -303,6 +374,19 @@ impl LlmProvider {
             | LlmProvider::LmStudio { .. }
             | LlmProvider::Custom { .. } => ObserverKind::ClaudeStreamJson,
             LlmProvider::Codex => ObserverKind::Codex,
+            LlmProvider::OpenAi { .. } => ObserverKind::OpenAiChat,
+        }
+    }
+
+    /// The runner that drives this provider's agent CLI in the guest, or
+    /// `None` for OpenAI-compatible providers, whose agent loop runs on the
+    /// host (see [`openai_agent`](crate::openai_agent)).
+    pub fn runner(&self) -> Option<Box<dyn crate::agent_runner::AgentRunner>> {
+        use crate::agent_runner::{ClaudeCodeRunner, CodexRunner};
+        match self.observer_kind() {
+            ObserverKind::ClaudeStreamJson => Some(Box::new(ClaudeCodeRunner::new(self.clone()))),
+            ObserverKind::Codex => Some(Box::new(CodexRunner::new(self.clone()))),
+            ObserverKind::OpenAiChat => None,
         }
     }
 
@@ -310,7 +394,7 @@ impl LlmProvider {
     /// and `--mcp-config` CLI flags.
     ///
     /// Claude and Claude-compatible proxies (Ollama, LmStudio, Custom)
-    /// return `true`; Codex returns `false`. Used by `agent_box.rs` to gate
+    /// return `true`; Codex and OpenAi return `false`. Used by `agent_box.rs` to gate
     /// flag emission on the exec command line.
     pub fn supports_claude_settings(&self) -> bool {
         match self {
@@ -319,7 +403,26 @@ impl LlmProvider {
             | LlmProvider::Ollama { .. }
             | LlmProvider::LmStudio { .. }
             | LlmProvider::Custom { .. } => true,
-            LlmProvider::Codex => false,
+            LlmProvider::Codex | LlmProvider::OpenAi { .. } => false,
+        }
+    }
+
+    /// Extra CLI args that continue the agent conversation `session_id`
+    /// (as reported in `AgentExecResult::session_id`), or `None` when the
+    /// provider's CLI cannot resume one.
+    ///
+    /// The conversation is stored in the guest, so this only works within
+    /// the VM that ran it.
+    pub fn resume_args(&self, session_id: &str) -> Option<Vec<String>> {
+        match self {
+            LlmProvider::Claude
+            | LlmProvider::ClaudePersonal
+            | LlmProvider::Ollama { .. }
+            | LlmProvider::LmStudio { .. }
+            | LlmProvider::Custom { .. } => {
+                Some(vec!["--resume".to_string(), session_id.to_string()])
+            }
+            LlmProvider::Codex | LlmProvider::OpenAi { .. } => None,
         }
     }
 
@@ -341,6 +444,8 @@ impl LlmProvider {
     /// - `extra_args`: caller-supplied extra args appended after the
     ///   provider-specific args and (for Codex) before the trailing prompt
     ///   positional.
+    ///
+    /// OpenAi has no guest command line and returns no args.
     pub fn build_exec_args(
         &self,
         prompt: &str,
@@ -386,6 +491,7 @@ impl LlmProvider {
                 args.push(prompt.to_string());
                 args
             }
+            LlmProvider::OpenAi { .. } => Vec::new(),
         }
     }
 
@@ -399,7 +505,10 @@ impl LlmProvider {
     /// arbitrary Ollama model names when `ANTHROPIC_API_KEY` is empty.
     pub(crate) fn cli_args(&self) -> Vec<String> {
         match self {
-            LlmProvider::Claude | LlmProvider::ClaudePersonal | LlmProvider::Codex => Vec::new(),
+            LlmProvider::Claude
+            | LlmProvider::ClaudePersonal
+            | LlmProvider::Codex
+            | LlmProvider::OpenAi { .. } => Vec::new(),
             LlmProvider::Ollama { model, .. } => {
                 vec!["--model".into(), model.clone()]
             }
@@ -482,6 +591,9 @@ impl LlmProvider {
                 }
                 vars
             }
+            // The key is used by the host-side agent loop and never enters
+            // the guest.
+            LlmProvider::OpenAi { .. } => vec![("HOME".into(), "/home/sandbox".into())],
         }
     }
 
@@ -524,8 +636,34 @@ impl LlmProvider {
                 format!("Custom ({} @ {})", m, base_url)
             }
             LlmProvider::Codex => "Codex (OpenAI API)".into(),
+            LlmProvider::OpenAi {
+                base_url, model, ..
+            } => {
+                let b = base_url.as_deref().unwrap_or(OPENAI_BASE_URL);
+                format!("OpenAI-compatible ({} @ {})", model, b)
+            }
         }
     }
+
+    /// Chat-completions endpoint and API key for
+    /// [`OpenAi`](LlmProvider::OpenAi), or `None` for other providers.
+    pub(crate) fn openai_endpoint(&self) -> Option<(String, Option<String>)> {
+        let LlmProvider::OpenAi {
+            base_url, api_key, ..
+        } = self
+        else {
+            return None;
+        };
+        let base = base_url.as_deref().unwrap_or(OPENAI_BASE_URL);
+        let key = match api_key {
+            Some(key) => Some(key.expose_secret().to_string()),
+            None => std::env::var("OPENAI_API_KEY").ok(),
+        };
+        Some((
+            format!("{}/chat/completions", base.trim_end_matches('/')),
+            key,
+        ))
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -855,6 +993,15 @@ mod tests {
         assert!(LlmProvider::ClaudePersonal.supports_claude_settings());
     }
 
+    #[test]
+    fn test_resume_args() {
+        assert_eq!(
+            LlmProvider::ollama("qwen3-coder").resume_args("sess-1"),
+            Some(vec!["--resume".to_string(), "sess-1".to_string()])
+        );
+        assert_eq!(LlmProvider::Codex.resume_args("sess-1"), None);
+    }
+
     #[test]
     fn test_codex_is_not_local() {
         assert!(!LlmProvider::Codex.is_local());
@@ -897,6 +1044,38 @@ mod tests {
         assert_eq!(LlmProvider::custom("x").image_flavor(), "claude");
     }
 
+    #[test]
+    fn test_openai_key_stays_on_host() {
+        let provider =
+            LlmProvider::openai_compatible("http://localhost:8000/v1/", "qwen").api_key("sk-x");
+        let vars: std::collections::HashMap<_, _> = provider.env_vars().into_iter().collect();
+        assert_eq!(vars.len(), 1);
+        assert_eq!(vars.get("HOME").unwrap(), "/home/sandbox");
+        assert_eq!(
+            provider.openai_endpoint(),
+            Some((
+                "http://localhost:8000/v1/chat/completions".to_string(),
+                Some("sk-x".to_string())
+            ))
+        );
+        assert_eq!(LlmProvider::Claude.openai_endpoint(), None);
+    }
+
+    #[test]
+    fn test_openai_dispatch() {
+        let provider = LlmProvider::openai("gpt-4.1").model("gpt-4.1-mini");
+        assert_eq!(provider.observer_kind(), ObserverKind::OpenAiChat);
+        assert!(!provider.supports_claude_settings());
+        assert!(provider.resume_args("s").is_none());
+        assert!(provider.build_exec_args("hi", true, &[]).is_empty());
+        assert_eq!(
+            provider.description(),
+            "OpenAI-compatible (gpt-4.1-mini @ https://api.openai.com/v1)"
+        );
+        let json = serde_json::to_value(&provider).unwrap();
+        assert_eq!(json["provider"], "openai");
+    }
+
     /// `format!("{:?}", LlmProvider::Custom { api_key: Some(...) })` must not
     /// contain the API key in plaintext. Uses a known-distinctive value so the
     /// substring search is unambiguous against any future formatter quirks.
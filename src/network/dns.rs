@@ -0,0 +1,253 @@
+//! Minimal DNS message handling for the SLIRP resolver.
+//!
+//! The SLIRP stack relays guest DNS queries to upstream resolvers without
+//! interpreting them. Domain rules in a
+//! [`NetworkPolicy`](crate::backend::NetworkPolicy) need to know which name
+//! an address came from, so responses relayed back to the guest are read
+//! here for their question name and IPv4 answers. Host aliases from a
+//! [`DnsConfig`](crate::backend::DnsConfig) are answered locally with
+//! responses built here.
+
+use std::net::Ipv4Addr;
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+/// QTYPE `ANY`.
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Guard against compression-pointer loops.
+const MAX_POINTER_HOPS: usize = 16;
+
+/// The queried name and the IPv4 addresses a DNS response answered it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DnsAnswers {
+    /// Question name, lowercased, without the trailing dot.
+    pub name: String,
+    /// `A` records in the answer section with their TTLs in seconds.
+    pub addresses: Vec<(Ipv4Addr, u32)>,
+}
+
+/// Parse a DNS response carrying one question. `None` for anything
+/// malformed, truncated, or not a response.
+///
+/// Every `A` record in the answer section is attributed to the question
+/// name, so a CNAME chain (`api.example.com` → `edge.cdn.net` → address)
+/// maps the final address back to the name the guest asked for.
+pub(crate) fn parse_answers(response: &[u8]) -> Option<DnsAnswers> {
+    if response.len() < HEADER_LEN || response[2] & 0x80 == 0 {
+        return None;
+    }
+    let qdcount = read_u16(response, 4)?;
+    let ancount = read_u16(response, 6)?;
+    if qdcount != 1 {
+        return None;
+    }
+
+    let (name, mut pos) = read_name(response, HEADER_LEN)?;
+    pos += 4; // QTYPE + QCLASS
+
+    let mut addresses = Vec::new();
+    for _ in 0..ancount {
+        let (_, after_name) = read_name(response, pos)?;
+        let rtype = read_u16(response, after_name)?;
+        let class = read_u16(response, after_name + 2)?;
+        let ttl = read_u32(response, after_name + 4)?;
+        let rdlen = read_u16(response, after_name + 8)? as usize;
+        let rdata = after_name + 10;
+        let rdata_bytes = response.get(rdata..rdata + rdlen)?;
+        if rtype == TYPE_A && class == CLASS_IN && rdlen == 4 {
+            let octets: [u8; 4] = rdata_bytes.try_into().ok()?;
+            addresses.push((Ipv4Addr::from(octets), ttl));
+        }
+        pos = rdata + rdlen;
+    }
+
+    Some(DnsAnswers { name, addresses })
+}
+
+/// The single question of a DNS query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DnsQuestion {
+    /// Lowercased, without the trailing dot.
+    pub name: String,
+    pub qtype: u16,
+    /// Offset just past QCLASS.
+    end: usize,
+}
+
+/// Parse a standard query carrying one question. `None` for responses and
+/// anything malformed.
+pub(crate) fn parse_question(query: &[u8]) -> Option<DnsQuestion> {
+    if query.len() < HEADER_LEN || query[2] & 0x80 != 0 || read_u16(query, 4)? != 1 {
+        return None;
+    }
+    let (name, pos) = read_name(query, HEADER_LEN)?;
+    let qtype = read_u16(query, pos)?;
+    read_u16(query, pos + 2)?;
+    Some(DnsQuestion {
+        name,
+        qtype,
+        end: pos + 4,
+    })
+}
+
+/// Answer `query` with a fixed address for its question name: one `A`
+/// record when it asks for `A` (or `ANY`), an empty successful answer for
+/// any other type so the resolver does not go looking elsewhere.
+pub(crate) fn static_response(
+    query: &[u8],
+    question: &DnsQuestion,
+    addr: Ipv4Addr,
+    ttl: u32,
+) -> Vec<u8> {
+    let answer = matches!(question.qtype, TYPE_A | TYPE_ANY);
+    let mut response = Vec::with_capacity(question.end + 16);
+    response.extend_from_slice(&query[..2]);
+    // QR + AA, RD echoed from the query, RA.
+    response.extend_from_slice(&[0x84 | (query[2] & 0x01), 0x80]);
+    response.extend_from_slice(&[0x00, 0x01, 0x00, u8::from(answer), 0, 0, 0, 0]);
+    response.extend_from_slice(&query[HEADER_LEN..question.end]);
+    if answer {
+        // Owner name: pointer to the question name.
+        response.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+        response.extend_from_slice(&TYPE_A.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&ttl.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&addr.octets());
+    }
+    response
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(buf.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(buf.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Decode the (possibly compressed) name at `pos`. Returns the lowercased
+/// dotted name and the offset just past it in the original position.
+fn read_name(buf: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut hops = 0;
+    loop {
+        let len = *buf.get(pos)? as usize;
+        match len & 0xC0 {
+            0x00 if len == 0 => {
+                end.get_or_insert(pos + 1);
+                break;
+            }
+            0x00 => {
+                let label = buf.get(pos + 1..pos + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                pos += 1 + len;
+            }
+            0xC0 => {
+                hops += 1;
+                if hops > MAX_POINTER_HOPS {
+                    return None;
+                }
+                let target = (read_u16(buf, pos)? & 0x3FFF) as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            _ => return None,
+        }
+    }
+    Some((labels.join("."), end?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response for `API.Example.com` with a CNAME to `edge.cdn.net` and
+    /// two `A` records owned by the CNAME target (compressed names).
+    fn cname_response() -> Vec<u8> {
+        let mut r = vec![
+            0x12, 0x34, 0x81, 0x80, // id, flags: response, RD, RA
+            0x00, 0x01, 0x00, 0x03, // qdcount 1, ancount 3
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        // Question at offset 12.
+        r.extend_from_slice(b"\x03API\x07Example\x03com\x00");
+        r.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        // CNAME: owner -> ptr 12, target edge.cdn.net
+        let cname_rdata = b"\x04edge\x03cdn\x03net\x00";
+        r.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01]);
+        r.extend_from_slice(&300u32.to_be_bytes());
+        r.extend_from_slice(&(cname_rdata.len() as u16).to_be_bytes());
+        let cname_target = r.len();
+        r.extend_from_slice(cname_rdata);
+        for (ip, ttl) in [([93, 184, 216, 34], 60u32), ([93, 184, 216, 35], 30)] {
+            r.extend_from_slice(&[0xC0, cname_target as u8, 0x00, 0x01, 0x00, 0x01]);
+            r.extend_from_slice(&ttl.to_be_bytes());
+            r.extend_from_slice(&[0x00, 0x04]);
+            r.extend_from_slice(&ip);
+        }
+        r
+    }
+
+    #[test]
+    fn test_parse_answers_follows_cname_chain() {
+        let answers = parse_answers(&cname_response()).unwrap();
+        assert_eq!(answers.name, "api.example.com");
+        assert_eq!(
+            answers.addresses,
+            vec![
+                (Ipv4Addr::new(93, 184, 216, 34), 60),
+                (Ipv4Addr::new(93, 184, 216, 35), 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_answers_rejects_queries_and_truncation() {
+        let mut query = cname_response();
+        query[2] &= 0x7F;
+        assert!(parse_answers(&query).is_none());
+
+        let response = cname_response();
+        assert!(parse_answers(&response[..response.len() - 2]).is_none());
+        assert!(parse_answers(&response[..8]).is_none());
+    }
+
+    fn query(name: &[u8], qtype: u16) -> Vec<u8> {
+        let mut q = vec![0xab, 0xcd, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0];
+        q.extend_from_slice(name);
+        q.extend_from_slice(&qtype.to_be_bytes());
+        q.extend_from_slice(&CLASS_IN.to_be_bytes());
+        q
+    }
+
+    #[test]
+    fn test_static_response_answers_a_queries() {
+        let addr = Ipv4Addr::new(10, 0, 2, 100);
+        let a_query = query(b"\x03Api\x08internal\x00", TYPE_A);
+        let question = parse_question(&a_query).unwrap();
+        assert_eq!(question.name, "api.internal");
+
+        let response = static_response(&a_query, &question, addr, 60);
+        assert_eq!(&response[..2], &[0xab, 0xcd]);
+        let answers = parse_answers(&response).unwrap();
+        assert_eq!(answers.name, "api.internal");
+        assert_eq!(answers.addresses, vec![(addr, 60)]);
+
+        let aaaa_query = query(b"\x03api\x08internal\x00", 28);
+        let question = parse_question(&aaaa_query).unwrap();
+        let answers = parse_answers(&static_response(&aaaa_query, &question, addr, 60)).unwrap();
+        assert!(answers.addresses.is_empty());
+
+        assert!(parse_question(&response).is_none());
+    }
+
+    #[test]
+    fn test_read_name_rejects_pointer_loop() {
+        let mut buf = vec![0u8; HEADER_LEN];
+        buf.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+        assert!(read_name(&buf, HEADER_LEN).is_none());
+    }
+}
@@ -6,14 +6,17 @@
 //! - virtio-net configuration
 //! - Network isolation and NAT
 
+pub(crate) mod dns;
 pub(crate) mod epoll_dispatch;
 pub mod nat;
 pub mod slirp;
+pub mod tap;
 #[cfg(all(target_os = "linux", feature = "io-uring"))]
 pub(crate) mod uring;
 
 use std::ffi::CString;
 use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 
 use crate::{Error, Result};
 
@@ -115,6 +118,27 @@ pub trait NetworkBackend: Send {
     /// The default is a no-op; `SlirpBackend` overrides this.
     #[cfg(target_os = "linux")]
     fn push_ready_events(&self, _events: &[epoll_dispatch::EpollEvent]) {}
+
+    /// Return the host sockets this backend holds open for the guest.
+    ///
+    /// Only `SlirpBackend` relays guest flows through host sockets; the
+    /// default reports none.
+    fn socket_counts(&self) -> SocketCounts {
+        SocketCounts::default()
+    }
+}
+
+/// Host sockets a [`NetworkBackend`] holds open on the guest's behalf.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketCounts {
+    /// Relayed TCP connections.
+    pub tcp: usize,
+    /// Relayed UDP flows.
+    pub udp: usize,
+    /// ICMP echo sockets.
+    pub icmp: usize,
+    /// Port-forward listeners.
+    pub listeners: usize,
 }
 
 /// TAP device handle
@@ -126,9 +150,31 @@ pub struct TapDevice {
 impl TapDevice {
     /// Create a new TAP device
     pub fn create(name: Option<&str>) -> Result<Self> {
+        Self::open(name.unwrap_or("void-tap0"), 0)
+    }
+
+    /// Create a TAP device whose frames carry a 12-byte `virtio_net_hdr`,
+    /// the layout vhost-net exchanges with a VIRTIO 1.0 guest.
+    pub fn create_with_vnet_hdr(name: &str) -> Result<Self> {
+        // TUNSETVNETHDRSZ ioctl: from <linux/if_tun.h>
+        const TUNSETVNETHDRSZ: libc::c_ulong = 0x4004_54d8;
+        const VNET_HDR_LEN: libc::c_int = 12;
+
+        let tap = Self::open(name, libc::IFF_VNET_HDR)?;
+        let ret = unsafe { libc::ioctl(tap.fd, TUNSETVNETHDRSZ as _, &VNET_HDR_LEN) };
+        if ret < 0 {
+            return Err(Error::Device(format!(
+                "failed to set vnet header size on TAP device '{}': {}",
+                name,
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(tap)
+    }
+
+    fn open(name: &str, extra_flags: libc::c_int) -> Result<Self> {
         // TAP creation requires /dev/net/tun and typically CAP_NET_ADMIN.
         // We try to create the device and surface any OS error back to the caller.
-        let name = name.unwrap_or("void-tap0");
 
         // Open /dev/net/tun
         let fd = unsafe {
@@ -156,7 +202,8 @@ impl TapDevice {
             libc::strncpy(ifr.ifr_name.as_mut_ptr(), cname.as_ptr(), libc::IFNAMSIZ);
 
             // Set flags to create a TAP device without extra packet info header.
-            ifr.ifr_ifru.ifru_flags = (libc::IFF_TAP | libc::IFF_NO_PI) as libc::c_short;
+            ifr.ifr_ifru.ifru_flags =
+                (libc::IFF_TAP | libc::IFF_NO_PI | extra_flags) as libc::c_short;
 
             // TUNSETIFF ioctl: from <linux/if_tun.h>
             const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
@@ -186,6 +233,92 @@ impl TapDevice {
     pub fn fd(&self) -> i32 {
         self.fd
     }
+
+    /// Switch the TAP fd to non-blocking mode, so reads return `EAGAIN`
+    /// once the host has no more frames queued.
+    pub fn set_nonblocking(&self) -> Result<()> {
+        // SAFETY: F_GETFL/F_SETFL on an fd we own.
+        let ret = unsafe {
+            let flags = libc::fcntl(self.fd, libc::F_GETFL);
+            if flags < 0 {
+                flags
+            } else {
+                libc::fcntl(self.fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+            }
+        };
+        if ret < 0 {
+            return Err(Error::Device(format!(
+                "failed to make TAP device '{}' non-blocking: {}",
+                self.name,
+                io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bring the interface up, first enslaving it to `bridge` if given.
+    ///
+    /// The bridge must already exist; creating and addressing it is left to
+    /// the host's network configuration.
+    pub fn bring_up(&self, bridge: Option<&str>) -> Result<()> {
+        // SIOCBRADDIF from <linux/sockios.h>; not exported by libc on glibc.
+        const SIOCBRADDIF: libc::c_ulong = 0x89a2;
+
+        // SAFETY: plain socket(2); ownership moves into OwnedFd immediately.
+        let sock = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if sock < 0 {
+            return Err(Error::Device(format!(
+                "failed to open interface control socket: {}",
+                io::Error::last_os_error()
+            )));
+        }
+        // SAFETY: `sock` is a fresh, valid fd owned by nobody else.
+        let sock = unsafe { OwnedFd::from_raw_fd(sock) };
+        let ioctl = |request: libc::c_ulong, ifr: &mut libc::ifreq, what: &str| -> Result<()> {
+            // SAFETY: every request used here reads or writes a single ifreq.
+            if unsafe { libc::ioctl(sock.as_raw_fd(), request as _, ifr as *mut libc::ifreq) } < 0 {
+                return Err(Error::Device(format!(
+                    "failed to {} for TAP device '{}': {}",
+                    what,
+                    self.name,
+                    io::Error::last_os_error()
+                )));
+            }
+            Ok(())
+        };
+
+        if let Some(bridge) = bridge {
+            let mut ifr = ifreq_for(&self.name)?;
+            ioctl(libc::SIOCGIFINDEX, &mut ifr, "look up interface index")?;
+            let mut br = ifreq_for(bridge)?;
+            // SAFETY: SIOCGIFINDEX filled the ifindex member of the union.
+            br.ifr_ifru.ifru_ifindex = unsafe { ifr.ifr_ifru.ifru_ifindex };
+            ioctl(
+                SIOCBRADDIF,
+                &mut br,
+                &format!("attach to bridge '{bridge}'"),
+            )?;
+        }
+
+        let mut ifr = ifreq_for(&self.name)?;
+        ioctl(libc::SIOCGIFFLAGS, &mut ifr, "read interface flags")?;
+        // SAFETY: SIOCGIFFLAGS filled the flags member of the union.
+        unsafe { ifr.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short };
+        ioctl(libc::SIOCSIFFLAGS, &mut ifr, "bring interface up")
+    }
+}
+
+/// A zeroed `ifreq` naming interface `name`.
+fn ifreq_for(name: &str) -> Result<libc::ifreq> {
+    if name.is_empty() || name.len() >= libc::IFNAMSIZ || name.contains('\0') {
+        return Err(Error::Device(format!("invalid interface name '{name}'")));
+    }
+    // SAFETY: ifreq is plain old data; all-zero is a valid value.
+    let mut ifr: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, src) in ifr.ifr_name.iter_mut().zip(name.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    Ok(ifr)
 }
 
 impl Drop for TapDevice {
@@ -39,8 +39,10 @@ use std::time::{Duration, Instant};
 
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 
+use crate::backend::{ConnectionObserver, DnsConfig, NetworkPolicy, PolicyAction};
 use crate::network::epoll_dispatch::{EpollDispatch, EpollEvent, RegisterMode, Waker};
-use crate::network::{nat, NetworkBackend};
+use crate::network::{dns, nat, NetworkBackend, SocketCounts};
+use crate::observe::network::{ConnectionRecord, NetworkProtocol};
 
 /// Cached DNS response with expiry.
 struct DnsCacheEntry {
@@ -48,6 +50,29 @@ struct DnsCacheEntry {
     expires: Instant,
 }
 
+/// A name the guest resolved to an address, for domain policy rules.
+struct ResolvedName {
+    name: String,
+    expires: Instant,
+}
+
+/// How long an address stays attributed to a name the guest resolved, at
+/// minimum. The DNS cache and guest resolvers serve answers past short
+/// record TTLs, so the connection can come well after the answer.
+const RESOLVED_NAME_MIN_TTL: Duration = Duration::from_secs(300);
+
+/// Tracked addresses above which expired names are pruned on insert.
+const MAX_RESOLVED_ADDRESSES: usize = 4096;
+
+/// Outcome of checking a new flow against the egress policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PolicyVerdict {
+    Allowed,
+    /// Denied by a log-only policy; the flow goes ahead.
+    Violation,
+    Denied,
+}
+
 /// A DNS query waiting to be resolved on the net-poll thread.
 struct PendingDnsQuery {
     query: Vec<u8>,
@@ -59,6 +84,9 @@ struct PendingDnsQuery {
 /// while keeping the implementation simple.
 const DNS_CACHE_TTL_SECS: u64 = 60;
 
+/// TTL on answers for host aliases, which never change while the VM runs.
+const HOST_ALIAS_TTL_SECS: u32 = 60;
+
 /// Initial capacity for the ready-event scratch buffers.  Sized to
 /// `EpollDispatch`'s typical per-wait batch so the buffers fit a
 /// busy-loop wakeup without reallocating; oversized batches grow
@@ -325,6 +353,49 @@ struct TcpNatEntry {
     /// `Instant::now() - RECV_WINDOW_TTL` at construction forces
     /// the first emit to populate the cache before advertising.
     cached_recv_window_at: Instant,
+    /// Connection-log accounting. `None` for inbound port-forward flows,
+    /// which the guest didn't open.
+    accounting: Option<FlowAccounting>,
+}
+
+/// Bytes and lifetime of an outbound flow, reported to the connection
+/// observer's close callback when the flow leaves the flow table.
+struct FlowAccounting {
+    opened_at: Instant,
+    bytes_to_guest: u64,
+    bytes_from_guest: u64,
+    policy_violation: bool,
+}
+
+impl FlowAccounting {
+    fn new(now: Instant, verdict: PolicyVerdict) -> Self {
+        Self {
+            opened_at: now,
+            bytes_to_guest: 0,
+            bytes_from_guest: 0,
+            policy_violation: verdict == PolicyVerdict::Violation,
+        }
+    }
+
+    fn record(
+        &self,
+        protocol: NetworkProtocol,
+        dst_ip: Ipv4Address,
+        dst_port: u16,
+        now: Instant,
+    ) -> ConnectionRecord {
+        ConnectionRecord {
+            policy_violation: self.policy_violation,
+            ..ConnectionRecord::closed(
+                protocol,
+                dst_ip.into(),
+                dst_port,
+                self.bytes_to_guest,
+                self.bytes_from_guest,
+                now.saturating_duration_since(self.opened_at),
+            )
+        }
+    }
 }
 
 /// Key for the ICMP echo NAT table: (guest ICMP id, destination IP).
@@ -380,6 +451,7 @@ struct UdpFlowEntry {
     /// via `next_flow_token(PROTO_TAG_UDP)` and stored here so unregister
     /// sites never need to recompute it.
     flow_token: u64,
+    accounting: FlowAccounting,
 }
 
 /// Unified flow-table key. Each variant wraps the protocol-specific
@@ -632,8 +704,11 @@ pub struct SlirpBackend {
     connection_timestamps: VecDeque<Instant>,
     /// Stateless outbound translation rules (deny-list, gateway loopback, port forwards).
     nat: nat::Rules,
-    /// Host DNS servers (parsed from /etc/resolv.conf, fallback to public)
+    /// Upstream DNS servers: configured ones, else parsed from the host's
+    /// /etc/resolv.conf, falling back to public resolvers.
     dns_servers: Vec<String>,
+    /// Names answered locally with a fixed address (lowercased).
+    host_aliases: HashMap<String, Ipv4Addr>,
     /// DNS response cache keyed by the raw query bytes (question section)
     dns_cache: HashMap<Vec<u8>, DnsCacheEntry>,
     /// DNS queries waiting to be resolved on the net-poll thread.
@@ -732,6 +807,13 @@ pub struct SlirpBackend {
     /// ≥ 60 s, UDP idle, DNS cache TTL ≥ seconds — so consumers
     /// see no behavioral change.
     cached_now: Instant,
+    /// Notified for each outbound TCP connection attempt the guest makes.
+    connection_observer: Option<ConnectionObserver>,
+    /// Egress policy checked for each new outbound flow.
+    network_policy: Option<NetworkPolicy>,
+    /// Names the guest resolved through our DNS, by answered address.
+    /// Only populated when the policy has domain rules.
+    resolved_names: HashMap<Ipv4Addr, Vec<ResolvedName>>,
 }
 
 impl SlirpBackend {
@@ -827,6 +909,7 @@ impl SlirpBackend {
             connection_timestamps: VecDeque::new(),
             nat,
             dns_servers,
+            host_aliases: HashMap::new(),
             dns_cache: HashMap::new(),
             pending_dns: Vec::new(),
             flow_table: FxHashMap::default(),
@@ -843,9 +926,99 @@ impl SlirpBackend {
             relay_frames_scratch: Vec::new(),
             flow_keys_scratch: Vec::new(),
             cached_now: Instant::now(),
+            connection_observer: None,
+            network_policy: None,
+            resolved_names: HashMap::new(),
         })
     }
 
+    /// Report each outbound TCP connection attempt to `observer`.
+    pub fn set_connection_observer(&mut self, observer: ConnectionObserver) {
+        self.connection_observer = Some(observer);
+    }
+
+    /// Forward guest DNS queries to `dns.servers` instead of the host's
+    /// resolvers, and answer `dns.host_aliases` locally.
+    pub fn set_dns_config(&mut self, dns: &DnsConfig) {
+        if !dns.servers.is_empty() {
+            self.dns_servers = dns.servers.iter().map(|ip| format!("{ip}:53")).collect();
+            self.dns_cache.clear();
+        }
+        self.host_aliases = dns.host_aliases.iter().cloned().collect();
+    }
+
+    /// Check new outbound flows against `policy`, after the deny list.
+    pub fn set_network_policy(&mut self, policy: NetworkPolicy) {
+        self.network_policy = Some(policy);
+    }
+
+    /// Check a new outbound flow against the egress policy. The SLIRP
+    /// gateway is exempt so host-side services stay reachable.
+    fn check_policy(&self, dst_ip: Ipv4Address, dst_port: u16) -> PolicyVerdict {
+        let Some(policy) = &self.network_policy else {
+            return PolicyVerdict::Allowed;
+        };
+        if dst_ip == SLIRP_GATEWAY_IP {
+            return PolicyVerdict::Allowed;
+        }
+        let ip = Ipv4Addr::from(dst_ip);
+        let names: Vec<String> = self
+            .resolved_names
+            .get(&ip)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter(|n| n.expires > self.cached_now)
+                    .map(|n| n.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        match policy.evaluate(ip, dst_port, &names) {
+            PolicyAction::Allow => PolicyVerdict::Allowed,
+            PolicyAction::Deny if policy.is_log_only() => {
+                warn!(
+                    "SLIRP: egress policy would deny {}:{} (names: {:?}); log-only, allowing",
+                    ip, dst_port, names
+                );
+                PolicyVerdict::Violation
+            }
+            PolicyAction::Deny => PolicyVerdict::Denied,
+        }
+    }
+
+    /// Remember which name each address in a DNS response answered, for
+    /// domain rules in the egress policy.
+    fn learn_resolved_names(&mut self, response: &[u8]) {
+        if !self
+            .network_policy
+            .as_ref()
+            .is_some_and(NetworkPolicy::has_domain_rules)
+        {
+            return;
+        }
+        let Some(answers) = dns::parse_answers(response) else {
+            return;
+        };
+        let now = self.cached_now;
+        if self.resolved_names.len() >= MAX_RESOLVED_ADDRESSES {
+            self.resolved_names.retain(|_, names| {
+                names.retain(|n| n.expires > now);
+                !names.is_empty()
+            });
+        }
+        for (addr, ttl) in answers.addresses {
+            let expires = now + Duration::from_secs(ttl.into()).max(RESOLVED_NAME_MIN_TTL);
+            let names = self.resolved_names.entry(addr).or_default();
+            match names.iter_mut().find(|n| n.name == answers.name) {
+                Some(known) => known.expires = known.expires.max(expires),
+                None => names.push(ResolvedName {
+                    name: answers.name.clone(),
+                    expires,
+                }),
+            }
+        }
+    }
+
     /// Returns the wall-clock instant captured at the start of the
     /// current relay cycle.
     ///
@@ -900,6 +1073,41 @@ impl SlirpBackend {
     ///
     /// Drains until `WouldBlock` so that a burst of connections arriving
     /// between two epoll wakeups is not spread across multiple ticks.
+    /// Report an outbound flow leaving the flow table to the connection
+    /// observer. Inbound port-forward and ICMP flows aren't logged.
+    fn report_flow_closed(&self, key: &FlowKey, entry: &FlowEntry) {
+        let Some(observer) = &self.connection_observer else {
+            return;
+        };
+        let record = match (key, entry) {
+            (FlowKey::Tcp(key), FlowEntry::Tcp(entry)) => entry.accounting.as_ref().map(|a| {
+                a.record(
+                    NetworkProtocol::Tcp,
+                    key.dst_ip,
+                    key.dst_port,
+                    self.cached_now,
+                )
+            }),
+            (FlowKey::Udp(key), FlowEntry::Udp(entry)) => Some(entry.accounting.record(
+                NetworkProtocol::Udp,
+                key.dst_ip,
+                key.dst_port,
+                self.cached_now,
+            )),
+            _ => None,
+        };
+        if let Some(record) = record {
+            observer.notify_closed(&record);
+        }
+    }
+
+    /// Report a flow refused by network policy to the connection observer.
+    fn report_flow_denied(&self, protocol: NetworkProtocol, dst_ip: Ipv4Address, dst_port: u16) {
+        if let Some(observer) = &self.connection_observer {
+            observer.notify_closed(&ConnectionRecord::denied(protocol, dst_ip.into(), dst_port));
+        }
+    }
+
     fn process_listener_readiness(&mut self, ready: &[EpollEvent]) {
         // Accepted connections are collected here first so that the borrow on
         // `port_forward_listeners` ends before we call `accept_sender.send`.
@@ -1003,6 +1211,7 @@ impl SlirpBackend {
                 guest_window_scale: 0,
                 cached_recv_window,
                 cached_recv_window_at: self.cached_now,
+                accounting: None,
             };
             let host_fd = entry.host_stream.as_raw_fd();
             let flow_key = FlowKey::Tcp(key);
@@ -1232,6 +1441,21 @@ impl SlirpBackend {
         Some(query[12..pos].to_vec())
     }
 
+    /// A local answer for `query` if it asks about a host alias.
+    fn host_alias_response(&self, query: &[u8]) -> Option<Vec<u8>> {
+        if self.host_aliases.is_empty() {
+            return None;
+        }
+        let question = dns::parse_question(query)?;
+        let addr = self.host_aliases.get(&question.name)?;
+        Some(dns::static_response(
+            query,
+            &question,
+            *addr,
+            HOST_ALIAS_TTL_SECS,
+        ))
+    }
+
     /// Drains the pending DNS queue and resolves each query. Called from
     /// `poll()` on the net-poll thread, never from a vCPU thread.
     fn resolve_pending_dns(&mut self) {
@@ -1241,6 +1465,7 @@ impl SlirpBackend {
         let queries: Vec<PendingDnsQuery> = self.pending_dns.drain(..).collect();
         for pending in queries {
             if let Some(response) = self.forward_dns_query(&pending.query) {
+                self.learn_resolved_names(&response);
                 let frame = self.build_udp_response(
                     SLIRP_DNS_IP,
                     SLIRP_GUEST_IP,
@@ -1442,6 +1667,15 @@ impl SlirpBackend {
             query.len()
         );
 
+        // Host aliases are answered locally and never reach an upstream.
+        if let Some(resp) = self.host_alias_response(query) {
+            debug!("SLIRP DNS: answered host alias");
+            self.learn_resolved_names(&resp);
+            let frame = self.build_udp_response(SLIRP_DNS_IP, SLIRP_GUEST_IP, 53, src_port, &resp);
+            self.inject_to_guest.push(frame);
+            return Ok(());
+        }
+
         // Fast path: serve from cache (safe on vCPU thread)
         if let Some(key) = Self::dns_cache_key(query) {
             if let Some(entry) = self.dns_cache.get(&key) {
@@ -1452,6 +1686,7 @@ impl SlirpBackend {
                         resp[1] = query[1];
                     }
                     debug!("SLIRP DNS: cache hit (vCPU fast path)");
+                    self.learn_resolved_names(&resp);
                     let frame =
                         self.build_udp_response(SLIRP_DNS_IP, SLIRP_GUEST_IP, 53, src_port, &resp);
                     self.inject_to_guest.push(frame);
@@ -1506,11 +1741,27 @@ impl SlirpBackend {
                         key.dst_port,
                         key.guest_src_port
                     );
+                    self.report_flow_denied(NetworkProtocol::Udp, key.dst_ip, key.dst_port);
                     return Ok(());
                 }
             };
 
         let flow_key = FlowKey::Udp(key);
+        let verdict = if self.flow_table.contains_key(&flow_key) {
+            PolicyVerdict::Allowed
+        } else {
+            self.check_policy(key.dst_ip, key.dst_port)
+        };
+        if verdict == PolicyVerdict::Denied {
+            trace!(
+                "SLIRP UDP: egress policy reject dst={}:{} from guest_port={}",
+                key.dst_ip,
+                key.dst_port,
+                key.guest_src_port
+            );
+            self.report_flow_denied(NetworkProtocol::Udp, key.dst_ip, key.dst_port);
+            return Ok(());
+        }
         // Track whether this is a new entry so we can register it with epoll.
         let mut new_host_fd: Option<std::os::fd::RawFd> = None;
         let mut new_token: u64 = 0;
@@ -1534,6 +1785,7 @@ impl SlirpBackend {
                     sock,
                     last_activity: self.cached_now,
                     flow_token: token,
+                    accounting: FlowAccounting::new(self.cached_now, verdict),
                 })) {
                     FlowEntry::Udp(e) => e,
                     _ => unreachable!(),
@@ -1557,8 +1809,9 @@ impl SlirpBackend {
             self.epoll_waker.wake();
         }
 
-        if let Err(e) = entry.sock.send(&payload) {
-            trace!("SLIRP UDP: send failed: {e}");
+        match entry.sock.send(&payload) {
+            Ok(n) => entry.accounting.bytes_from_guest += n as u64,
+            Err(e) => trace!("SLIRP UDP: send failed: {e}"),
         }
         Ok(())
     }
@@ -1834,6 +2087,9 @@ impl SlirpBackend {
                     }
                 }
                 entry.bytes_in_flight = entry.bytes_in_flight.wrapping_sub(drained);
+                if let Some(accounting) = &mut entry.accounting {
+                    accounting.bytes_to_guest += u64::from(drained);
+                }
                 trace!(
                     "SLIRP TCP: ACK consumed {} bytes from kernel (in_flight now={}, segment_ack={})",
                     drained, entry.bytes_in_flight, segment_ack
@@ -1872,6 +2128,9 @@ impl SlirpBackend {
             };
 
             if n_written > 0 {
+                if let Some(accounting) = &mut entry.accounting {
+                    accounting.bytes_from_guest += n_written as u64;
+                }
                 let ack_seq = payload_seq.wrapping_add(n_written as u32);
                 entry.guest_ack = ack_seq;
                 let ack_frame = build_tcp_packet_static(
@@ -2058,6 +2317,7 @@ impl SlirpBackend {
                     "SLIRP TCP: connection to {}:{} denied by network deny list",
                     dst_ip, dst_port
                 );
+                self.report_flow_denied(NetworkProtocol::Tcp, dst_ip, dst_port);
                 let rst = build_tcp_packet_static(
                     dst_ip,
                     SLIRP_GUEST_IP,
@@ -2075,6 +2335,29 @@ impl SlirpBackend {
             }
         };
 
+        let verdict = self.check_policy(dst_ip, dst_port);
+        if verdict == PolicyVerdict::Denied {
+            warn!(
+                "SLIRP TCP: connection to {}:{} denied by egress policy",
+                dst_ip, dst_port
+            );
+            self.report_flow_denied(NetworkProtocol::Tcp, dst_ip, dst_port);
+            let rst = build_tcp_packet_static(
+                dst_ip,
+                SLIRP_GUEST_IP,
+                dst_port,
+                src_port,
+                0,
+                seq + 1,
+                TcpControl::Rst,
+                &[],
+                65535,
+                None,
+            );
+            self.inject_to_guest.push(rst);
+            return Ok(());
+        }
+
         let mut tcp_flow_count = 0;
         for flow_key in self.flow_table.keys() {
             if matches!(flow_key, FlowKey::Tcp(_)) {
@@ -2086,6 +2369,7 @@ impl SlirpBackend {
                 "SLIRP TCP: max concurrent connections ({}) reached, rejecting SYN to {}:{}",
                 self.max_concurrent_connections, dst_ip, dst_port
             );
+            self.report_flow_denied(NetworkProtocol::Tcp, dst_ip, dst_port);
             let rst = build_tcp_packet_static(
                 dst_ip,
                 SLIRP_GUEST_IP,
@@ -2107,6 +2391,7 @@ impl SlirpBackend {
                 "SLIRP TCP: connection rate limit ({}/s) exceeded, rejecting SYN to {}:{}",
                 self.max_connections_per_second, dst_ip, dst_port
             );
+            self.report_flow_denied(NetworkProtocol::Tcp, dst_ip, dst_port);
             let rst = build_tcp_packet_static(
                 dst_ip,
                 SLIRP_GUEST_IP,
@@ -2123,11 +2408,11 @@ impl SlirpBackend {
             return Ok(());
         }
 
-        if let Some(FlowEntry::Tcp(stale)) = self.flow_table.get(&FlowKey::Tcp(key)) {
+        if let Some(FlowEntry::Tcp(stale)) = self.flow_table.remove(&FlowKey::Tcp(key)) {
             self.token_to_key.remove(&stale.flow_token);
             self.epoll.unregister(stale.host_stream.as_raw_fd()).ok();
+            self.report_flow_closed(&FlowKey::Tcp(key), &FlowEntry::Tcp(stale));
         }
-        self.flow_table.remove(&FlowKey::Tcp(key));
 
         let socket = match Socket::new(
             Domain::IPV4,
@@ -2156,6 +2441,9 @@ impl SlirpBackend {
                 return Ok(());
             }
         };
+        if let Some(observer) = &self.connection_observer {
+            observer.notify(dst_ip.into(), dst_port);
+        }
         let sockaddr = SockAddr::from(dst_addr);
         match socket.connect(&sockaddr) {
             Ok(()) => {
@@ -2180,6 +2468,7 @@ impl SlirpBackend {
                     guest_window_scale: syn_window_scale,
                     cached_recv_window,
                     cached_recv_window_at: self.cached_now,
+                    accounting: Some(FlowAccounting::new(self.cached_now, verdict)),
                 };
                 self.flow_table.insert(flow_key, FlowEntry::Tcp(entry));
                 self.token_to_key.insert(token, flow_key);
@@ -2234,6 +2523,7 @@ impl SlirpBackend {
                     guest_window_scale: syn_window_scale,
                     cached_recv_window,
                     cached_recv_window_at: self.cached_now,
+                    accounting: Some(FlowAccounting::new(self.cached_now, verdict)),
                 };
                 self.flow_table.insert(flow_key, FlowEntry::Tcp(entry));
                 self.token_to_key.insert(token, flow_key);
@@ -2689,7 +2979,10 @@ impl SlirpBackend {
         self.inject_to_guest.append(&mut frames_to_inject);
 
         for flow_key in to_remove_set {
-            if let Some(FlowEntry::Tcp(entry)) = self.flow_table.get(&flow_key) {
+            let Some(removed) = self.flow_table.remove(&flow_key) else {
+                continue;
+            };
+            if let FlowEntry::Tcp(entry) = &removed {
                 self.token_to_key.remove(&entry.flow_token);
                 self.epoll.unregister(entry.host_stream.as_raw_fd()).ok();
                 // Connecting entries that timed out never received a SYN-ACK,
@@ -2712,7 +3005,7 @@ impl SlirpBackend {
                     }
                 }
             }
-            self.flow_table.remove(&flow_key);
+            self.report_flow_closed(&flow_key, &removed);
         }
         self.inject_to_guest.append(&mut frames_to_inject);
         // Both `append` calls drained `frames_to_inject` but
@@ -2882,11 +3175,13 @@ impl SlirpBackend {
             }
         }
         for flow_key in flow_keys.drain(..) {
-            if let Some(FlowEntry::Udp(entry)) = self.flow_table.get(&flow_key) {
-                self.token_to_key.remove(&entry.flow_token);
-                self.epoll.unregister(entry.sock.as_raw_fd()).ok();
+            if let Some(removed) = self.flow_table.remove(&flow_key) {
+                if let FlowEntry::Udp(entry) = &removed {
+                    self.token_to_key.remove(&entry.flow_token);
+                    self.epoll.unregister(entry.sock.as_raw_fd()).ok();
+                }
+                self.report_flow_closed(&flow_key, &removed);
             }
-            self.flow_table.remove(&flow_key);
         }
 
         for event in ready {
@@ -2910,6 +3205,7 @@ impl SlirpBackend {
                 match entry.sock.recv(&mut buf) {
                     Ok(n) => {
                         entry.last_activity = now;
+                        entry.accounting.bytes_to_guest += n as u64;
                         Self::build_udp_reply_to_guest(
                             key.dst_ip,
                             key.dst_port,
@@ -3064,6 +3360,21 @@ impl NetworkBackend for SlirpBackend {
     fn push_ready_events(&self, events: &[crate::network::epoll_dispatch::EpollEvent]) {
         SlirpBackend::push_ready_events(self, events)
     }
+
+    fn socket_counts(&self) -> SocketCounts {
+        let mut counts = SocketCounts {
+            listeners: self.port_forward_listeners.len(),
+            ..SocketCounts::default()
+        };
+        for entry in self.flow_table.values() {
+            match entry {
+                FlowEntry::Tcp(_) => counts.tcp += 1,
+                FlowEntry::Udp(_) => counts.udp += 1,
+                FlowEntry::IcmpEcho(_) => counts.icmp += 1,
+            }
+        }
+        counts
+    }
 }
 
 /// Refresh interval for the per-flow `cached_recv_window`. Bounding the
@@ -3356,6 +3667,19 @@ impl Default for SlirpBackend {
     }
 }
 
+impl Drop for SlirpBackend {
+    /// Flows still open when the VM stops close with it; log them too.
+    fn drop(&mut self) {
+        if self.connection_observer.is_none() {
+            return;
+        }
+        self.refresh_now();
+        for (key, entry) in &self.flow_table {
+            self.report_flow_closed(key, entry);
+        }
+    }
+}
+
 impl SlirpBackend {
     /// Re-register every live host FD in `flow_table` with the current epoll
     /// dispatcher and rebuild `token_to_key`.  Called from snapshot restore:
@@ -3472,6 +3796,7 @@ impl SlirpBackend {
             guest_window_scale: 0,
             cached_recv_window,
             cached_recv_window_at: self.cached_now,
+            accounting: None,
         };
         self.flow_table.insert(flow_key, FlowEntry::Tcp(entry));
         self.token_to_key.insert(token, flow_key);
@@ -3596,6 +3921,7 @@ impl SlirpBackend {
             guest_window_scale: 0,
             cached_recv_window,
             cached_recv_window_at: self.cached_now,
+            accounting: None,
         };
         self.flow_table
             .insert(FlowKey::Tcp(key), FlowEntry::Tcp(entry));
@@ -3670,6 +3996,7 @@ impl SlirpBackend {
             guest_window_scale: 0,
             cached_recv_window,
             cached_recv_window_at: self.cached_now,
+            accounting: None,
         };
         self.flow_table.insert(flow_key, FlowEntry::Tcp(entry));
         self.token_to_key.insert(token, flow_key);
@@ -3924,4 +4251,157 @@ mod tests {
             "one listener for one TCP port-forward rule"
         );
     }
+
+    /// Refused SYNs are logged as denied right away; flows still open when
+    /// the stack is dropped are logged with their lifetime.
+    #[test]
+    fn connection_observer_receives_denied_and_closed_records() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = records.clone();
+        let mut slirp =
+            SlirpBackend::with_security(64, 50, &["169.254.0.0/16".to_string()], &[]).unwrap();
+        slirp.set_connection_observer(
+            ConnectionObserver::new(|_, _| {})
+                .on_close(move |record| sink.lock().unwrap().push(record.clone())),
+        );
+
+        let metadata = Ipv4Address::new(169, 254, 169, 254);
+        slirp
+            .process_guest_frame(&build_guest_tcp_frame(
+                metadata,
+                40000,
+                80,
+                1000,
+                0,
+                TcpControl::Syn,
+                false,
+            ))
+            .unwrap();
+        {
+            let records = records.lock().unwrap();
+            assert_eq!(records.len(), 1);
+            assert!(records[0].denied);
+            assert_eq!(records[0].dst_ip, Ipv4Addr::new(169, 254, 169, 254));
+            assert_eq!(records[0].dst_port, 80);
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        slirp
+            .process_guest_frame(&build_guest_tcp_frame(
+                SLIRP_GATEWAY_IP,
+                40001,
+                port,
+                2000,
+                0,
+                TcpControl::Syn,
+                false,
+            ))
+            .unwrap();
+        drop(slirp);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].protocol, NetworkProtocol::Tcp);
+        assert_eq!(records[1].dst_port, port);
+        assert!(!records[1].denied);
+    }
+
+    /// DNS response for `name` answering `addr`, uncompressed.
+    fn dns_a_response(name: &str, addr: [u8; 4]) -> Vec<u8> {
+        let mut qname = Vec::new();
+        for label in name.split('.') {
+            qname.push(label.len() as u8);
+            qname.extend_from_slice(label.as_bytes());
+        }
+        qname.push(0);
+        let mut r = vec![0x00, 0x01, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        r.extend_from_slice(&qname);
+        r.extend_from_slice(&[0, 1, 0, 1]);
+        r.extend_from_slice(&qname);
+        r.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+        r.extend_from_slice(&addr);
+        r
+    }
+
+    #[test]
+    fn dns_config_overrides_servers_and_answers_host_aliases() {
+        let mut slirp = SlirpBackend::new().unwrap();
+        slirp.set_dns_config(&DnsConfig {
+            servers: vec![Ipv4Addr::new(1, 1, 1, 1)],
+            host_aliases: vec![("api.internal".to_string(), Ipv4Addr::new(10, 0, 2, 100))],
+        });
+        assert_eq!(slirp.dns_servers, vec!["1.1.1.1:53".to_string()]);
+
+        let query = |name: &str| {
+            let mut q = dns_a_response(name, [0; 4]);
+            q.truncate(12 + name.len() + 2 + 4);
+            q[2] = 0x01;
+            q[3] = 0x00;
+            q[7] = 0;
+            q
+        };
+        let response = slirp.host_alias_response(&query("API.internal")).unwrap();
+        let answers = dns::parse_answers(&response).unwrap();
+        assert_eq!(
+            answers.addresses,
+            vec![(Ipv4Addr::new(10, 0, 2, 100), HOST_ALIAS_TTL_SECS)]
+        );
+        assert!(slirp.host_alias_response(&query("example.com")).is_none());
+    }
+
+    /// Domain allow rules match addresses the guest resolved through our
+    /// DNS; everything else is refused, unless the policy is log-only.
+    #[test]
+    fn network_policy_enforces_resolved_domains() {
+        let mut slirp = SlirpBackend::new().unwrap();
+        slirp.set_network_policy(
+            NetworkPolicy::deny_all()
+                .allow(crate::backend::NetworkRule::domain("*.example.com").ports([443])),
+        );
+        let addr = Ipv4Address::new(198, 51, 100, 7);
+        assert_eq!(slirp.check_policy(addr, 443), PolicyVerdict::Denied);
+
+        slirp.learn_resolved_names(&dns_a_response("api.example.com", [198, 51, 100, 7]));
+        assert_eq!(slirp.check_policy(addr, 443), PolicyVerdict::Allowed);
+        assert_eq!(slirp.check_policy(addr, 80), PolicyVerdict::Denied);
+        assert_eq!(
+            slirp.check_policy(SLIRP_GATEWAY_IP, 8080),
+            PolicyVerdict::Allowed
+        );
+
+        slirp.cached_now += RESOLVED_NAME_MIN_TTL + Duration::from_secs(1);
+        assert_eq!(slirp.check_policy(addr, 443), PolicyVerdict::Denied);
+
+        slirp.set_network_policy(NetworkPolicy::deny_all().log_only());
+        assert_eq!(slirp.check_policy(addr, 443), PolicyVerdict::Violation);
+    }
+
+    #[test]
+    fn network_policy_refuses_syn_with_denied_record() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = records.clone();
+        let mut slirp = SlirpBackend::new().unwrap();
+        slirp.set_connection_observer(
+            ConnectionObserver::new(|_, _| {})
+                .on_close(move |record| sink.lock().unwrap().push(record.clone())),
+        );
+        slirp.set_network_policy(NetworkPolicy::deny_all());
+        slirp
+            .process_guest_frame(&build_guest_tcp_frame(
+                Ipv4Address::new(198, 51, 100, 7),
+                40002,
+                443,
+                3000,
+                0,
+                TcpControl::Syn,
+                false,
+            ))
+            .unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].denied);
+        assert!(slirp.flow_table.is_empty());
+    }
 }
@@ -0,0 +1,381 @@
+//! Diagnostics for guests that never finish booting.
+//!
+//! Until the handshake completes, the serial console is the guest's only
+//! channel to the host: a missed handshake deadline alone cannot tell a
+//! kernel panic from a missing module or an agent that never started. The
+//! guest-agent therefore writes a boot-status line to the console for each
+//! failure it can detect before the control channel exists (see
+//! [`BOOT_STATUS_MARKER`]), and the sandbox classifies the console's
+//! [`ConsoleTail`] into [`BootDiagnostics`], returned as
+//! [`Error::BootFailed`](crate::Error::BootFailed).
+//!
+//! Boots that do complete are timed into a [`BootTimeline`]: the host
+//! measures kernel load, vCPU start, the first serial byte and the
+//! handshake, the guest-agent reports module loading and its vsock listener
+//! as `phase` boot-status lines, and the timeline is recorded as `boot.*`
+//! spans on the sandbox's observer.
+
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Serialize;
+use void_box_protocol::{BootStatus, GuestBootPhase, BOOT_STATUS_MARKER};
+
+use super::crash::ConsoleTail;
+use super::tracer::{Span, Tracer};
+
+/// Loading the kernel and initramfs into guest memory.
+pub const PHASE_KERNEL_LOAD: &str = "kernel_load";
+/// Creating the vCPUs and starting their threads.
+pub const PHASE_VCPU_START: &str = "vcpu_start";
+/// From the vCPUs starting to the guest's first byte on the serial console.
+pub const PHASE_FIRST_SERIAL_BYTE: &str = "first_serial_byte";
+/// The guest-agent loading kernel modules (guest-reported).
+pub const PHASE_MODULE_LOAD: &str = "module_load";
+/// The guest-agent creating its vsock listener (guest-reported).
+pub const PHASE_VSOCK_LISTEN: &str = "vsock_listen";
+/// From the VM running to the guest-agent completing its handshake.
+pub const PHASE_HANDSHAKE: &str = "handshake";
+
+/// Why a boot failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootFailureKind {
+    /// The serial console stayed empty: a kernel that does not boot on this
+    /// machine, or one that hung before its console came up.
+    NoKernelOutput,
+    /// The kernel panicked, e.g. on a missing or corrupt initramfs.
+    KernelPanic,
+    /// A kernel module the control channel needs did not load.
+    ModuleLoadFailed,
+    /// The guest-agent could not listen on vsock.
+    VsockListenFailed,
+    /// The guest booted without the session secret on its cmdline.
+    SecretMissing,
+    /// The guest-agent rejected the host's session secret.
+    SecretMismatch,
+    /// Setting up the OCI rootfs (mount, overlay or pivot_root) failed.
+    OciRootfsFailed,
+    /// The guest-agent started and reported no failure, but never answered.
+    AgentUnresponsive,
+    /// The console shows neither a failure nor the guest-agent starting,
+    /// e.g. an initramfs whose `/init` is not the void-box guest-agent.
+    Unknown,
+}
+
+impl BootFailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootFailureKind::NoKernelOutput => "no_kernel_output",
+            BootFailureKind::KernelPanic => "kernel_panic",
+            BootFailureKind::ModuleLoadFailed => "module_load_failed",
+            BootFailureKind::VsockListenFailed => "vsock_listen_failed",
+            BootFailureKind::SecretMissing => "secret_missing",
+            BootFailureKind::SecretMismatch => "secret_mismatch",
+            BootFailureKind::OciRootfsFailed => "oci_rootfs_failed",
+            BootFailureKind::AgentUnresponsive => "agent_unresponsive",
+            BootFailureKind::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for BootFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the serial console showed about a boot that failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct BootDiagnostics {
+    /// The sandbox's `sandbox` metrics label.
+    pub sandbox_id: String,
+    pub kind: BootFailureKind,
+    /// The boot-status or panic line the kind was read from, if any.
+    pub detail: Option<String>,
+    /// The last [`CRASH_CONSOLE_TAIL_BYTES`](super::crash::CRASH_CONSOLE_TAIL_BYTES)
+    /// of serial output.
+    pub console_tail: String,
+    /// The error the boot failed with.
+    pub cause: String,
+}
+
+impl BootDiagnostics {
+    /// Classify the console captured in `tail`. The first failure reported
+    /// wins, since later ones usually follow from it (a vsock module that
+    /// did not load leaves the agent unable to listen).
+    pub fn new(sandbox_id: &str, tail: &ConsoleTail, cause: String) -> Self {
+        let console_tail = tail.contents();
+        let (kind, detail) = classify(&console_tail, tail.panic_line());
+        Self {
+            sandbox_id: sandbox_id.to_string(),
+            kind,
+            detail,
+            console_tail,
+            cause,
+        }
+    }
+
+    /// Redact secret values from the console transcript.
+    pub(crate) fn redact(&mut self, redactor: &crate::secret::Redactor) {
+        if let Some(detail) = &self.detail {
+            self.detail = Some(redactor.redact(detail).into_owned());
+        }
+        self.console_tail = redactor.redact(&self.console_tail).into_owned();
+    }
+}
+
+impl fmt::Display for BootDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in sandbox {}", self.kind, self.sandbox_id)?;
+        match &self.detail {
+            Some(detail) => write!(f, ": {}", detail),
+            None => write!(f, ": {}", self.cause),
+        }
+    }
+}
+
+/// One timed step of a boot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BootPhase {
+    /// One of the `PHASE_*` names.
+    pub name: String,
+    /// When the phase started, relative to the start of the boot.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// The timed phases of one boot.
+#[derive(Debug, Clone)]
+pub struct BootTimeline {
+    started: Instant,
+    started_at: SystemTime,
+    phases: Vec<BootPhase>,
+}
+
+impl BootTimeline {
+    /// A timeline for the boot that started at `started`.
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            started_at: SystemTime::now() - started.elapsed(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Record the phase `name` that ran from `start` to `end`.
+    pub fn record(&mut self, name: &str, start: Instant, end: Instant) {
+        self.phases.push(BootPhase {
+            name: name.to_string(),
+            start: start.saturating_duration_since(self.started),
+            duration: end.saturating_duration_since(start),
+        });
+    }
+
+    /// Record the phases the guest-agent reported on `console`.
+    ///
+    /// Guest times count from the guest kernel starting, which is taken to
+    /// be the end of [`PHASE_VCPU_START`] (or the start of the boot, for
+    /// backends that do not report it), so they are accurate to within the
+    /// vCPU start time.
+    pub fn record_guest_phases(&mut self, console: &str) {
+        let origin = self.guest_origin();
+        for line in console.lines() {
+            let Some((BootStatus::Phase, detail)) = BootStatus::parse_line(line) else {
+                continue;
+            };
+            let Some(phase) = GuestBootPhase::parse_detail(detail) else {
+                continue;
+            };
+            self.phases.push(BootPhase {
+                name: phase.name.replace('-', "_"),
+                start: origin + phase.start,
+                duration: phase.duration,
+            });
+        }
+    }
+
+    /// When the guest kernel started, relative to the start of the boot.
+    pub fn guest_origin(&self) -> Duration {
+        self.phase(PHASE_VCPU_START)
+            .map(|phase| phase.start + phase.duration)
+            .unwrap_or_default()
+    }
+
+    pub fn phase(&self, name: &str) -> Option<&BootPhase> {
+        self.phases.iter().find(|phase| phase.name == name)
+    }
+
+    /// Phases in the order they started.
+    pub fn phases(&self) -> Vec<BootPhase> {
+        let mut phases = self.phases.clone();
+        phases.sort_by_key(|phase| phase.start);
+        phases
+    }
+
+    /// From the start of the boot to the end of its last phase.
+    pub fn total(&self) -> Duration {
+        self.phases
+            .iter()
+            .map(|phase| phase.start + phase.duration)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Record a `boot` span with a `boot.<phase>` child per phase.
+    pub fn record_spans(&self, tracer: &Tracer, attributes: &[(&str, &str)]) {
+        let mut root = Span::new("boot");
+        root.start_time = self.started_at;
+        root.duration = Some(self.total());
+        for (key, value) in attributes {
+            root.set_attribute(*key, *value);
+        }
+        for phase in self.phases() {
+            let mut span = Span::child(&format!("boot.{}", phase.name), &root.context);
+            span.start_time = self.started_at + phase.start;
+            span.duration = Some(phase.duration);
+            tracer.finish_span(span);
+        }
+        tracer.finish_span(root);
+    }
+}
+
+fn classify(console: &str, panic_line: Option<String>) -> (BootFailureKind, Option<String>) {
+    let mut agent_started = false;
+    for line in console.lines() {
+        let Some((status, detail)) = BootStatus::parse_line(line) else {
+            continue;
+        };
+        let kind = match status {
+            BootStatus::AgentStarted => {
+                agent_started = true;
+                continue;
+            }
+            BootStatus::Phase => continue,
+            BootStatus::ModuleFailed => BootFailureKind::ModuleLoadFailed,
+            BootStatus::VsockListenFailed => BootFailureKind::VsockListenFailed,
+            BootStatus::SecretMissing => BootFailureKind::SecretMissing,
+            BootStatus::SecretMismatch => BootFailureKind::SecretMismatch,
+            BootStatus::OciRootfsFailed => BootFailureKind::OciRootfsFailed,
+        };
+        let start = line.find(BOOT_STATUS_MARKER).unwrap_or_default();
+        let detail = match detail {
+            "" => line[start..].trim().to_string(),
+            detail => detail.to_string(),
+        };
+        return (kind, Some(detail));
+    }
+    if let Some(line) = panic_line {
+        return (BootFailureKind::KernelPanic, Some(line));
+    }
+    if console.trim().is_empty() {
+        (BootFailureKind::NoKernelOutput, None)
+    } else if agent_started {
+        (BootFailureKind::AgentUnresponsive, None)
+    } else {
+        (BootFailureKind::Unknown, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose(console: &[u8]) -> BootDiagnostics {
+        let tail = ConsoleTail::new();
+        tail.feed(console);
+        BootDiagnostics::new("sb-1", &tail, "deadline reached".into())
+    }
+
+    #[test]
+    fn test_boot_timeline_records_host_and_guest_phases_as_spans() {
+        let started = Instant::now();
+        let mut timeline = BootTimeline::new(started);
+        timeline.record(
+            PHASE_KERNEL_LOAD,
+            started,
+            started + Duration::from_millis(20),
+        );
+        timeline.record(
+            PHASE_VCPU_START,
+            started + Duration::from_millis(20),
+            started + Duration::from_millis(25),
+        );
+        timeline.record_guest_phases(
+            "guest-agent: voidbox-boot: agent-started 0.1.0\n\
+             guest-agent: voidbox-boot: phase module-load 100000 30000\n\
+             guest-agent: voidbox-boot: phase vsock-listen 140000 1000\n",
+        );
+
+        let module_load = timeline.phase(PHASE_MODULE_LOAD).unwrap();
+        assert_eq!(module_load.start, Duration::from_millis(125));
+        assert_eq!(module_load.duration, Duration::from_millis(30));
+        assert_eq!(timeline.total(), Duration::from_millis(166));
+        let names: Vec<String> = timeline.phases().into_iter().map(|p| p.name).collect();
+        assert_eq!(
+            names,
+            [
+                PHASE_KERNEL_LOAD,
+                PHASE_VCPU_START,
+                PHASE_MODULE_LOAD,
+                PHASE_VSOCK_LISTEN
+            ]
+        );
+
+        let tracer = Tracer::new(crate::observe::TracerConfig::in_memory());
+        timeline.record_spans(&tracer, &[("boot.profile", "standard")]);
+        let spans = tracer.get_spans();
+        let root = spans.iter().find(|span| span.name == "boot").unwrap();
+        assert_eq!(root.duration, Some(Duration::from_millis(166)));
+        assert_eq!(root.attributes["boot.profile"], "standard");
+        let vsock = spans
+            .iter()
+            .find(|span| span.name == "boot.vsock_listen")
+            .unwrap();
+        assert_eq!(
+            vsock.context.parent_span_id.as_ref(),
+            Some(&root.context.span_id)
+        );
+        assert_eq!(vsock.duration, Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_boot_failures_are_classified_from_the_console() {
+        let report = diagnose(
+            b"guest-agent: voidbox-boot: agent-started 0.1.0\r\n\
+              guest-agent: voidbox-boot: module-failed vsock.ko: No such file or directory\r\n\
+              guest-agent: voidbox-boot: vsock-listen-failed Address family not supported\r\n",
+        );
+        assert_eq!(report.kind, BootFailureKind::ModuleLoadFailed);
+        assert_eq!(
+            report.detail.as_deref(),
+            Some("vsock.ko: No such file or directory")
+        );
+        assert_eq!(
+            report.to_string(),
+            "module_load_failed in sandbox sb-1: vsock.ko: No such file or directory"
+        );
+
+        let report = diagnose(b"guest-agent: voidbox-boot: secret-mismatch\n");
+        assert_eq!(report.kind, BootFailureKind::SecretMismatch);
+        assert_eq!(
+            report.detail.as_deref(),
+            Some("voidbox-boot: secret-mismatch")
+        );
+
+        let report = diagnose(
+            b"Kernel panic - not syncing: VFS: Unable to mount root fs on unknown-block(0,0)\n",
+        );
+        assert_eq!(report.kind, BootFailureKind::KernelPanic);
+
+        assert_eq!(diagnose(b"").kind, BootFailureKind::NoKernelOutput);
+        assert_eq!(
+            diagnose(b"guest-agent: voidbox-boot: agent-started 0.1.0\n").kind,
+            BootFailureKind::AgentUnresponsive
+        );
+        assert_eq!(
+            diagnose(b"Hello from /init\n").kind,
+            BootFailureKind::Unknown
+        );
+    }
+}
@@ -25,6 +25,10 @@ pub struct AgentExecResult {
     pub session_id: String,
     /// Total cost in USD.
     pub total_cost_usd: f64,
+    /// Whether `total_cost_usd` was priced from the tokens by a
+    /// [`PricingTable`](super::pricing::PricingTable) rather than reported.
+    #[serde(default)]
+    pub cost_estimated: bool,
     /// Wall-clock duration in milliseconds.
     pub duration_ms: u64,
     /// API-only duration in milliseconds.
@@ -35,16 +39,250 @@ pub struct AgentExecResult {
     pub input_tokens: u64,
     /// Total output tokens produced.
     pub output_tokens: u64,
+    /// Input tokens written to the prompt cache.
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache.
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
     /// Whether the execution ended in error.
     pub is_error: bool,
     /// Error message (if `is_error` is true).
     pub error: Option<String>,
-    /// Tool calls made during the session, in order.
+    /// Tool calls made during the session, in order. Calls made by a
+    /// subagent carry its `parent_tool_use_id`.
     pub tool_calls: Vec<ClaudeToolCall>,
+    /// Extended thinking blocks, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thinking: Vec<ThinkingBlock>,
+    /// Usage of each API message, in order. Claude-code repeats a
+    /// message's usage on every event of that message; each message is
+    /// counted once here.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<MessageUsage>,
+    /// Subagent sessions started through the `Task` tool, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subagents: Vec<SubagentSession>,
+    /// Whether the run was ended by its [`IdlePolicy`](crate::idle::IdlePolicy)
+    /// rather than by the agent exiting.
+    #[serde(default)]
+    pub idle_finalized: bool,
+}
+
+/// Token counts of one API message, or a sum of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Read a `usage` object; missing counts are zero.
+    fn from_json(usage: &serde_json::Value) -> Self {
+        let count = |key| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        Self {
+            input_tokens: count("input_tokens"),
+            output_tokens: count("output_tokens"),
+            cache_creation_input_tokens: count("cache_creation_input_tokens"),
+            cache_read_input_tokens: count("cache_read_input_tokens"),
+        }
+    }
+
+    /// Merge a later report of the same message's usage. Counts only grow
+    /// (`message_delta` output counts are cumulative), so keep the larger.
+    fn merge(&mut self, other: Self) {
+        self.input_tokens = self.input_tokens.max(other.input_tokens);
+        self.output_tokens = self.output_tokens.max(other.output_tokens);
+        self.cache_creation_input_tokens = self
+            .cache_creation_input_tokens
+            .max(other.cache_creation_input_tokens);
+        self.cache_read_input_tokens = self
+            .cache_read_input_tokens
+            .max(other.cache_read_input_tokens);
+    }
+
+    fn add(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+/// Usage of one API message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageUsage {
+    /// Message ID (e.g. "msg_01"); empty when the event carried none.
+    pub message_id: String,
+    /// Model that produced the message; empty when the event named none.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub model: String,
+    /// The `Task` tool call whose subagent sent the message, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+}
+
+/// An extended thinking block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThinkingBlock {
+    /// The thinking text; empty when the block was redacted.
+    pub text: String,
+    /// Whether the API returned the block encrypted (`redacted_thinking`).
+    #[serde(default)]
+    pub redacted: bool,
+    /// The `Task` tool call whose subagent thought this, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
+}
+
+/// A subagent session started through the `Task` tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubagentSession {
+    /// ID of the `Task` tool call; the subagent's own messages and tool
+    /// calls carry it as `parent_tool_use_id`.
+    pub tool_use_id: String,
+    /// The `subagent_type` the task asked for.
+    #[serde(default)]
+    pub subagent_type: String,
+    /// The task's short description.
+    #[serde(default)]
+    pub description: String,
+    /// Tool calls the subagent made.
+    #[serde(default)]
+    pub tool_calls: usize,
+    /// Tokens the subagent's messages used.
+    #[serde(default)]
+    pub usage: TokenUsage,
+}
+
+/// Tokens attributed to one tool; see [`AgentExecResult::tool_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolUsage {
+    /// Calls made to the tool.
+    pub calls: u32,
+    /// Tokens attributed to those calls.
+    pub usage: TokenUsage,
+}
+
+impl AgentExecResult {
+    /// Tokens per tool name. A call is charged its share of the output
+    /// tokens of the message that made it (split evenly between the calls
+    /// of that message); a `Task` call is also charged everything its
+    /// subagent used.
+    pub fn tool_usage(&self) -> std::collections::BTreeMap<String, ToolUsage> {
+        let mut per_tool: std::collections::BTreeMap<String, ToolUsage> = Default::default();
+        let mut calls_per_message: HashMap<&str, u64> = HashMap::new();
+        for call in &self.tool_calls {
+            if let Some(id) = call.message_id.as_deref() {
+                *calls_per_message.entry(id).or_default() += 1;
+            }
+        }
+        for call in &self.tool_calls {
+            let entry = per_tool.entry(call.tool_name.clone()).or_default();
+            entry.calls += 1;
+            if let Some(id) = call.message_id.as_deref() {
+                if let Some(message) = self.messages.iter().find(|m| m.message_id == id) {
+                    entry.usage.output_tokens +=
+                        message.usage.output_tokens / calls_per_message[id];
+                }
+            }
+            if let Some(subagent) = self
+                .subagents
+                .iter()
+                .find(|s| s.tool_use_id == call.tool_use_id)
+            {
+                entry.usage.add(subagent.usage);
+            }
+        }
+        per_tool
+    }
+
+    /// Track the subagent session a tool call starts or belongs to.
+    fn record_tool_call(&mut self, call: &ClaudeToolCall) {
+        if let Some(parent) = call.parent_tool_use_id.as_deref() {
+            if let Some(subagent) = self.subagents.iter_mut().find(|s| s.tool_use_id == parent) {
+                subagent.tool_calls += 1;
+            }
+        }
+        // Newer claude-code releases call the Task tool "Agent".
+        if matches!(call.tool_name.as_str(), "Task" | "Agent") {
+            let field = |key| {
+                call.input
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            self.subagents.push(SubagentSession {
+                tool_use_id: call.tool_use_id.clone(),
+                subagent_type: field("subagent_type"),
+                description: field("description"),
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Record a report of a message's usage and refresh the totals.
+    fn record_message_usage(
+        &mut self,
+        message_id: &str,
+        model: &str,
+        parent_tool_use_id: Option<&str>,
+        usage: TokenUsage,
+    ) {
+        let existing = if message_id.is_empty() {
+            None
+        } else {
+            self.messages
+                .iter_mut()
+                .find(|m| m.message_id == message_id)
+        };
+        match existing {
+            Some(message) => {
+                message.usage.merge(usage);
+                if !model.is_empty() {
+                    message.model = model.to_string();
+                }
+            }
+            None => self.messages.push(MessageUsage {
+                message_id: message_id.to_string(),
+                model: model.to_string(),
+                parent_tool_use_id: parent_tool_use_id.map(String::from),
+                usage,
+            }),
+        }
+        self.refresh_usage_totals();
+    }
+
+    fn refresh_usage_totals(&mut self) {
+        let mut total = TokenUsage::default();
+        for subagent in &mut self.subagents {
+            subagent.usage = TokenUsage::default();
+        }
+        for message in &self.messages {
+            total.add(message.usage);
+            if let Some(parent) = message.parent_tool_use_id.as_deref() {
+                if let Some(subagent) = self.subagents.iter_mut().find(|s| s.tool_use_id == parent)
+                {
+                    subagent.usage.add(message.usage);
+                }
+            }
+        }
+        self.input_tokens = total.input_tokens;
+        self.output_tokens = total.output_tokens;
+        self.cache_creation_input_tokens = total.cache_creation_input_tokens;
+        self.cache_read_input_tokens = total.cache_read_input_tokens;
+    }
 }
 
 /// A single tool call made by claude-code.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClaudeToolCall {
     /// Tool name (e.g. "Bash", "Read", "Write").
     pub tool_name: String,
@@ -54,6 +292,12 @@ pub struct ClaudeToolCall {
     pub input: serde_json::Value,
     /// Tool result/output (if captured).
     pub output: Option<String>,
+    /// ID of the API message that made the call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// The `Task` tool call whose subagent made this call, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
 }
 
 impl ClaudeToolCall {
@@ -144,6 +388,9 @@ pub fn parse_jsonl_line(
     };
 
     let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    // Set on events from a subagent's session: the Task tool call that
+    // started it.
+    let parent_tool_use_id = event.get("parent_tool_use_id").and_then(|v| v.as_str());
     let mut events = Vec::new();
 
     match event_type {
@@ -157,51 +404,112 @@ pub fn parse_jsonl_line(
         }
         "assistant" => {
             if let Some(msg) = event.get("message") {
+                let message_id = msg.get("id").and_then(|v| v.as_str()).unwrap_or("");
                 if let Some(usage) = msg.get("usage") {
-                    state.input_tokens += usage
-                        .get("input_tokens")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0);
-                    state.output_tokens += usage
-                        .get("output_tokens")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0);
+                    state.record_message_usage(
+                        message_id,
+                        msg.get("model").and_then(|v| v.as_str()).unwrap_or(""),
+                        parent_tool_use_id,
+                        TokenUsage::from_json(usage),
+                    );
                 }
 
                 if let Some(content) = msg.get("content").and_then(|v| v.as_array()) {
                     for block in content {
                         let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                        if block_type == "tool_use" {
-                            let tool = ClaudeToolCall {
-                                tool_name: block
-                                    .get("name")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("unknown")
-                                    .to_string(),
-                                tool_use_id: block
-                                    .get("id")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("")
-                                    .to_string(),
-                                input: block
-                                    .get("input")
-                                    .cloned()
-                                    .unwrap_or(serde_json::Value::Null),
-                                output: None,
-                            };
-                            let idx = state.tool_calls.len();
-                            tool_id_map.insert(tool.tool_use_id.clone(), idx);
-                            events.push(AgentStreamEvent::ToolUse(tool.clone()));
-                            state.tool_calls.push(tool);
+                        match block_type {
+                            "tool_use" => {
+                                let tool = ClaudeToolCall {
+                                    tool_name: block
+                                        .get("name")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("unknown")
+                                        .to_string(),
+                                    tool_use_id: block
+                                        .get("id")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("")
+                                        .to_string(),
+                                    input: block
+                                        .get("input")
+                                        .cloned()
+                                        .unwrap_or(serde_json::Value::Null),
+                                    output: None,
+                                    message_id: (!message_id.is_empty())
+                                        .then(|| message_id.to_string()),
+                                    parent_tool_use_id: parent_tool_use_id.map(String::from),
+                                };
+                                state.record_tool_call(&tool);
+                                let idx = state.tool_calls.len();
+                                tool_id_map.insert(tool.tool_use_id.clone(), idx);
+                                events.push(AgentStreamEvent::ToolUse(tool.clone()));
+                                state.tool_calls.push(tool);
+                            }
+                            "thinking" | "redacted_thinking" => {
+                                state.thinking.push(ThinkingBlock {
+                                    text: block
+                                        .get("thinking")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("")
+                                        .to_string(),
+                                    redacted: block_type == "redacted_thinking",
+                                    parent_tool_use_id: parent_tool_use_id.map(String::from),
+                                });
+                            }
+                            _ => {}
                         }
                     }
                 }
 
-                if let Some(model) = msg.get("model").and_then(|v| v.as_str()) {
-                    if !model.is_empty() {
-                        state.model = model.to_string();
+                if parent_tool_use_id.is_none() {
+                    if let Some(model) = msg.get("model").and_then(|v| v.as_str()) {
+                        if !model.is_empty() {
+                            state.model = model.to_string();
+                        }
+                    }
+                }
+            }
+        }
+        "stream_event" => {
+            // Partial messages (`--include-partial-messages`): usage arrives
+            // in `message_start` and grows with each `message_delta`.
+            let Some(inner) = event.get("event") else {
+                return events;
+            };
+            match inner.get("type").and_then(|v| v.as_str()) {
+                Some("message_start") => {
+                    if let Some(msg) = inner.get("message") {
+                        if let Some(usage) = msg.get("usage") {
+                            let message_id = msg.get("id").and_then(|v| v.as_str()).unwrap_or("");
+                            state.record_message_usage(
+                                message_id,
+                                msg.get("model").and_then(|v| v.as_str()).unwrap_or(""),
+                                parent_tool_use_id,
+                                TokenUsage::from_json(usage),
+                            );
+                        }
+                    }
+                }
+                Some("message_delta") => {
+                    if let Some(usage) = inner.get("usage") {
+                        // Deltas carry no message ID; they belong to the
+                        // latest message of the same session.
+                        let message_id = state
+                            .messages
+                            .iter()
+                            .rev()
+                            .find(|m| m.parent_tool_use_id.as_deref() == parent_tool_use_id)
+                            .map(|m| m.message_id.clone())
+                            .unwrap_or_default();
+                        state.record_message_usage(
+                            &message_id,
+                            "",
+                            parent_tool_use_id,
+                            TokenUsage::from_json(usage),
+                        );
                     }
                 }
+                _ => {}
             }
         }
         "user" => {
@@ -255,13 +563,21 @@ pub fn parse_jsonl_line(
                 .and_then(|v| v.as_str())
                 .map(String::from);
 
+            // Result-level usage is authoritative when present.
             if let Some(usage) = event.get("usage") {
-                if let Some(it) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
+                let count = |key| usage.get(key).and_then(|v| v.as_u64());
+                if let Some(it) = count("input_tokens") {
                     state.input_tokens = it;
                 }
-                if let Some(ot) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
+                if let Some(ot) = count("output_tokens") {
                     state.output_tokens = ot;
                 }
+                if let Some(cc) = count("cache_creation_input_tokens") {
+                    state.cache_creation_input_tokens = cc;
+                }
+                if let Some(cr) = count("cache_read_input_tokens") {
+                    state.cache_read_input_tokens = cr;
+                }
             }
         }
         _ => {}
@@ -282,6 +598,14 @@ pub struct AgentExecOpts {
     /// Per-request timeout in seconds.
     /// `None` means use the system default (1200s).
     pub timeout_secs: Option<u64>,
+    /// Decides on each tool call as it streams in (runners that
+    /// [stream tool calls](crate::agent_runner::AgentRunner::streams_tool_calls)
+    /// and OpenAI-compatible providers).
+    pub tool_hook: Option<crate::tool_hook::ToolHook>,
+    /// Limits enforced as the run streams in.
+    pub budget: Option<crate::budget::Budget>,
+    /// Ends the run once the agent goes idle (see [`idle`](crate::idle)).
+    pub idle: Option<crate::idle::IdlePolicy>,
 }
 
 // ---------------------------------------------------------------------------
@@ -293,166 +617,13 @@ pub struct AgentExecOpts {
 /// Returns a `AgentExecResult` with all extracted telemetry.
 pub fn parse_stream_json(stdout: &[u8]) -> AgentExecResult {
     let text = String::from_utf8_lossy(stdout);
-    let mut result = AgentExecResult {
-        result_text: String::new(),
-        model: String::new(),
-        session_id: String::new(),
-        total_cost_usd: 0.0,
-        duration_ms: 0,
-        duration_api_ms: 0,
-        num_turns: 0,
-        input_tokens: 0,
-        output_tokens: 0,
-        is_error: false,
-        error: None,
-        tool_calls: Vec::new(),
-    };
+    let mut result = AgentExecResult::default();
 
     // Map tool_use_id -> index in tool_calls for matching results
     let mut tool_id_map: HashMap<String, usize> = HashMap::new();
 
     for line in text.lines() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-
-        let event: serde_json::Value = match serde_json::from_str(line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
-        let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
-
-        match event_type {
-            "system" => {
-                if let Some(sid) = event.get("session_id").and_then(|v| v.as_str()) {
-                    result.session_id = sid.to_string();
-                }
-                if let Some(model) = event.get("model").and_then(|v| v.as_str()) {
-                    result.model = model.to_string();
-                }
-            }
-            "assistant" => {
-                if let Some(msg) = event.get("message") {
-                    // Extract token usage from this turn
-                    if let Some(usage) = msg.get("usage") {
-                        result.input_tokens += usage
-                            .get("input_tokens")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(0);
-                        result.output_tokens += usage
-                            .get("output_tokens")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(0);
-                    }
-
-                    // Extract tool_use content blocks
-                    if let Some(content) = msg.get("content").and_then(|v| v.as_array()) {
-                        for block in content {
-                            let block_type =
-                                block.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                            if block_type == "tool_use" {
-                                let tool = ClaudeToolCall {
-                                    tool_name: block
-                                        .get("name")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("unknown")
-                                        .to_string(),
-                                    tool_use_id: block
-                                        .get("id")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("")
-                                        .to_string(),
-                                    input: block
-                                        .get("input")
-                                        .cloned()
-                                        .unwrap_or(serde_json::Value::Null),
-                                    output: None,
-                                };
-                                let idx = result.tool_calls.len();
-                                tool_id_map.insert(tool.tool_use_id.clone(), idx);
-                                result.tool_calls.push(tool);
-                            }
-                        }
-                    }
-
-                    // Update model from message if present
-                    if let Some(model) = msg.get("model").and_then(|v| v.as_str()) {
-                        if !model.is_empty() {
-                            result.model = model.to_string();
-                        }
-                    }
-                }
-            }
-            "user" => {
-                // Match tool_result blocks to previous tool_use
-                if let Some(msg) = event.get("message") {
-                    if let Some(content) = msg.get("content").and_then(|v| v.as_array()) {
-                        for block in content {
-                            let block_type =
-                                block.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                            if block_type == "tool_result" {
-                                let tool_use_id = block
-                                    .get("tool_use_id")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("");
-
-                                let output_text = extract_tool_result_text(block);
-
-                                if let Some(&idx) = tool_id_map.get(tool_use_id) {
-                                    if let Some(tc) = result.tool_calls.get_mut(idx) {
-                                        tc.output = Some(output_text);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            "result" => {
-                result.result_text = event
-                    .get("result")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                result.total_cost_usd = event
-                    .get("total_cost_usd")
-                    .and_then(|v| v.as_f64())
-                    .unwrap_or(0.0);
-                result.duration_ms = event
-                    .get("duration_ms")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0);
-                result.duration_api_ms = event
-                    .get("duration_api_ms")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0);
-                result.num_turns =
-                    event.get("num_turns").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
-                result.is_error = event
-                    .get("is_error")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                result.error = event
-                    .get("error")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-
-                // Override tokens from result-level usage if present
-                if let Some(usage) = event.get("usage") {
-                    if let Some(it) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
-                        result.input_tokens = it;
-                    }
-                    if let Some(ot) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
-                        result.output_tokens = ot;
-                    }
-                }
-            }
-            _ => {
-                // Ignore stream_event and other types
-            }
-        }
+        parse_jsonl_line(line, &mut result, &mut tool_id_map);
     }
 
     result
@@ -552,12 +723,31 @@ pub fn create_otel_spans(
             .join(","),
     );
     exec_span.set_attribute("claude.tools_count", result.tool_calls.len().to_string());
+    exec_span.set_attribute(
+        "claude.cache_creation_input_tokens",
+        result.cache_creation_input_tokens.to_string(),
+    );
+    exec_span.set_attribute(
+        "claude.cache_read_input_tokens",
+        result.cache_read_input_tokens.to_string(),
+    );
+    if !result.thinking.is_empty() {
+        exec_span.set_attribute("claude.thinking_blocks", result.thinking.len().to_string());
+    }
+    if !result.subagents.is_empty() {
+        exec_span.set_attribute("claude.subagents", result.subagents.len().to_string());
+    }
 
     let exec_ctx = exec_span.context.clone();
 
-    // Set duration from the result
+    // Set duration from the result; the run ended just now, so it started
+    // `duration_ms` ago.
     if result.duration_ms > 0 {
-        exec_span.duration = Some(std::time::Duration::from_millis(result.duration_ms));
+        let duration = std::time::Duration::from_millis(result.duration_ms);
+        if let Some(start) = exec_span.start_time.checked_sub(duration) {
+            exec_span.start_time = start;
+        }
+        exec_span.duration = Some(duration);
     }
 
     // Create child spans for each tool call
@@ -571,6 +761,9 @@ pub fn create_otel_spans(
         // Custom tool attributes (no semconv equivalent yet)
         tool_span.set_attribute("tool.name", &tool.tool_name);
         tool_span.set_attribute("tool.use_id", &tool.tool_use_id);
+        if let Some(parent) = &tool.parent_tool_use_id {
+            tool_span.set_attribute("tool.parent_use_id", parent);
+        }
 
         // Truncate input for span attributes (avoid huge payloads)
         let input_str = tool.input.to_string();
@@ -708,6 +901,7 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             input: serde_json::json!({"command": "git clone https://github.com/example/repo.git"}),
             output: None,
+            ..Default::default()
         };
         assert_eq!(
             tc.tool_summary(),
@@ -723,6 +917,7 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             input: serde_json::json!({"command": long_cmd}),
             output: None,
+            ..Default::default()
         };
         let summary = tc.tool_summary();
         assert!(summary.len() <= 83); // 80 + "..."
@@ -736,6 +931,7 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             input: serde_json::json!({"file_path": "/workspace/src/main.rs"}),
             output: None,
+            ..Default::default()
         };
         assert_eq!(tc.tool_summary(), "/workspace/src/main.rs");
     }
@@ -747,6 +943,7 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             input: serde_json::json!({"pattern": "fn main"}),
             output: None,
+            ..Default::default()
         };
         assert_eq!(tc.tool_summary(), "fn main");
     }
@@ -758,26 +955,14 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             input: serde_json::json!({}),
             output: None,
+            ..Default::default()
         };
         assert_eq!(tc.tool_summary(), "");
     }
 
     #[test]
     fn test_parse_jsonl_line_tool_events() {
-        let mut state = AgentExecResult {
-            result_text: String::new(),
-            model: String::new(),
-            session_id: String::new(),
-            total_cost_usd: 0.0,
-            duration_ms: 0,
-            duration_api_ms: 0,
-            num_turns: 0,
-            input_tokens: 0,
-            output_tokens: 0,
-            is_error: false,
-            error: None,
-            tool_calls: Vec::new(),
-        };
+        let mut state = AgentExecResult::default();
         let mut tool_id_map = HashMap::new();
 
         // System line
@@ -820,20 +1005,7 @@ mod tests {
         let jsonl = sample_session_jsonl();
         let batch_result = parse_stream_json(jsonl.as_bytes());
 
-        let mut incr_result = AgentExecResult {
-            result_text: String::new(),
-            model: String::new(),
-            session_id: String::new(),
-            total_cost_usd: 0.0,
-            duration_ms: 0,
-            duration_api_ms: 0,
-            num_turns: 0,
-            input_tokens: 0,
-            output_tokens: 0,
-            is_error: false,
-            error: None,
-            tool_calls: Vec::new(),
-        };
+        let mut incr_result = AgentExecResult::default();
         let mut tool_id_map = HashMap::new();
         for line in jsonl.lines() {
             parse_jsonl_line(line, &mut incr_result, &mut tool_id_map);
@@ -850,4 +1022,70 @@ mod tests {
             assert_eq!(a.output, b.output);
         }
     }
+
+    #[test]
+    fn test_parse_thinking_subagents_and_usage_deltas() {
+        let jsonl = r#"{"type":"system","subtype":"init","session_id":"s1","model":"opus"}
+{"type":"stream_event","parent_tool_use_id":null,"event":{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":100,"output_tokens":1,"cache_creation_input_tokens":500,"cache_read_input_tokens":2000}}}}
+{"type":"stream_event","parent_tool_use_id":null,"event":{"type":"message_delta","usage":{"output_tokens":40}}}
+{"type":"assistant","parent_tool_use_id":null,"message":{"id":"msg_1","model":"opus","content":[{"type":"thinking","thinking":"Delegate the search.","signature":"sig"}],"usage":{"input_tokens":100,"output_tokens":1,"cache_creation_input_tokens":500,"cache_read_input_tokens":2000}}}
+{"type":"assistant","parent_tool_use_id":null,"message":{"id":"msg_1","model":"opus","content":[{"type":"tool_use","id":"toolu_task","name":"Task","input":{"description":"Find TODOs","subagent_type":"general-purpose","prompt":"grep"}}],"usage":{"input_tokens":100,"output_tokens":1,"cache_creation_input_tokens":500,"cache_read_input_tokens":2000}}}
+{"type":"assistant","parent_tool_use_id":"toolu_task","message":{"id":"msg_2","model":"haiku","content":[{"type":"redacted_thinking","data":"opaque"},{"type":"tool_use","id":"toolu_grep","name":"Grep","input":{"pattern":"TODO"}}],"usage":{"input_tokens":30,"output_tokens":12,"cache_read_input_tokens":300}}}
+{"type":"user","parent_tool_use_id":"toolu_task","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_grep","content":"src/lib.rs"}]}}
+{"type":"user","parent_tool_use_id":null,"message":{"content":[{"type":"tool_result","tool_use_id":"toolu_task","content":"One TODO in src/lib.rs"}]}}
+{"type":"assistant","parent_tool_use_id":null,"message":{"id":"msg_3","model":"opus","content":[{"type":"text","text":"Found one."}],"usage":{"input_tokens":20,"output_tokens":8,"cache_read_input_tokens":2500}}}"#;
+
+        let result = parse_stream_json(jsonl.as_bytes());
+
+        // msg_1 is counted once, with the delta's cumulative output count.
+        assert_eq!(result.messages.len(), 3);
+        assert_eq!(result.messages[0].usage.output_tokens, 40);
+        assert_eq!(result.input_tokens, 150);
+        assert_eq!(result.output_tokens, 60);
+        assert_eq!(result.cache_creation_input_tokens, 500);
+        assert_eq!(result.cache_read_input_tokens, 4800);
+        // The subagent's model does not replace the session's.
+        assert_eq!(result.model, "opus");
+
+        assert_eq!(result.thinking.len(), 2);
+        assert_eq!(result.thinking[0].text, "Delegate the search.");
+        assert!(result.thinking[1].redacted);
+        assert_eq!(
+            result.thinking[1].parent_tool_use_id.as_deref(),
+            Some("toolu_task")
+        );
+
+        assert_eq!(result.subagents.len(), 1);
+        let subagent = &result.subagents[0];
+        assert_eq!(subagent.tool_use_id, "toolu_task");
+        assert_eq!(subagent.subagent_type, "general-purpose");
+        assert_eq!(subagent.description, "Find TODOs");
+        assert_eq!(subagent.tool_calls, 1);
+        assert_eq!(subagent.usage.input_tokens, 30);
+        assert_eq!(subagent.usage.cache_read_input_tokens, 300);
+        assert_eq!(
+            result.tool_calls[1].parent_tool_use_id.as_deref(),
+            Some("toolu_task")
+        );
+        assert_eq!(result.tool_calls[1].message_id.as_deref(), Some("msg_2"));
+
+        let usage = result.tool_usage();
+        assert_eq!(usage["Task"].calls, 1);
+        // Its message's 40 output tokens plus the subagent's 12.
+        assert_eq!(usage["Task"].usage.output_tokens, 52);
+        assert_eq!(usage["Task"].usage.input_tokens, 30);
+        assert_eq!(usage["Grep"].usage.output_tokens, 12);
+
+        // A result event's usage still overrides the summed counts.
+        let mut result = result;
+        let mut tool_id_map = HashMap::new();
+        parse_jsonl_line(
+            r#"{"type":"result","result":"Found one.","usage":{"input_tokens":200,"output_tokens":70,"cache_read_input_tokens":5000}}"#,
+            &mut result,
+            &mut tool_id_map,
+        );
+        assert_eq!(result.input_tokens, 200);
+        assert_eq!(result.cache_read_input_tokens, 5000);
+        assert_eq!(result.cache_creation_input_tokens, 500);
+    }
 }
@@ -115,6 +115,7 @@ fn apply_item_completed(item: &serde_json::Value, result: &mut AgentExecResult)
                 tool_use_id: id,
                 input: serde_json::json!({ "changes": changes }),
                 output: Some(status),
+                ..Default::default()
             });
         }
         "command_execution" => {
@@ -133,6 +134,7 @@ fn apply_item_completed(item: &serde_json::Value, result: &mut AgentExecResult)
                 tool_use_id: id,
                 input: serde_json::json!({ "command": command }),
                 output: Some(aggregated_output),
+                ..Default::default()
             });
         }
         unknown => {
@@ -141,6 +143,7 @@ fn apply_item_completed(item: &serde_json::Value, result: &mut AgentExecResult)
                 tool_use_id: id,
                 input: serde_json::json!({}),
                 output: None,
+                ..Default::default()
             });
         }
     }
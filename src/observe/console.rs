@@ -0,0 +1,310 @@
+//! Guest serial console capture.
+//!
+//! With [`ObserveConfig::capture_console`](super::ObserveConfig::capture_console)
+//! set, a sandbox tees the guest's serial output into a [`ConsoleCapture`].
+//! The capture splits it into lines, tags each with the [`BootPhase`] it was
+//! printed in, and records it as a `console` [`LogEntry`](super::LogEntry)
+//! on the sandbox's [`Observer`]. Once boot completes or fails, everything
+//! printed so far is also attached to a `sandbox.boot` span as a
+//! `boot-console` event, so a failed boot can be diagnosed from the trace.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::{Observer, SpanStatus};
+use crate::secret::Redactor;
+
+/// `LogEntry::source` of captured console lines.
+pub const CONSOLE_LOG_SOURCE: &str = "console";
+
+/// Name of the span event carrying the boot console transcript.
+pub const BOOT_CONSOLE_EVENT: &str = "boot-console";
+
+/// Lines longer than this are split, so a guest printing without newlines
+/// can't grow the pending buffer without bound.
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Cap on the `boot-console` transcript; the oldest lines are dropped first.
+const MAX_BOOT_CONSOLE_BYTES: usize = 64 * 1024;
+
+/// Where the guest was in its boot when a console line was printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BootPhase {
+    /// Kernel initialization, before `/init` runs.
+    Kernel,
+    /// `/init` is running but the guest-agent hasn't logged yet.
+    Init,
+    /// The guest-agent is setting up (mounts, network, OCI rootfs).
+    Agent,
+    /// The host completed its handshake with the guest-agent.
+    Ready,
+}
+
+impl BootPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootPhase::Kernel => "kernel",
+            BootPhase::Init => "init",
+            BootPhase::Agent => "agent",
+            BootPhase::Ready => "ready",
+        }
+    }
+
+    /// The phase a console line announces, if any.
+    fn entered_by(line: &str) -> Option<Self> {
+        if line.contains("guest-agent:") {
+            Some(BootPhase::Agent)
+        } else if line.contains("Run /init") || line.contains("Run /sbin/init") {
+            Some(BootPhase::Init)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for BootPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+struct CaptureState {
+    pending: Vec<u8>,
+    phase: BootPhase,
+    started: SystemTime,
+    boot_transcript: String,
+    boot_lines: usize,
+    boot_recorded: bool,
+}
+
+/// Line-splits guest console bytes into an [`Observer`].
+pub struct ConsoleCapture {
+    observer: Observer,
+    redactor: Option<Arc<Redactor>>,
+    state: Mutex<CaptureState>,
+}
+
+impl ConsoleCapture {
+    pub fn new(observer: Observer) -> Self {
+        Self {
+            observer,
+            redactor: None,
+            state: Mutex::new(CaptureState {
+                pending: Vec::new(),
+                phase: BootPhase::Kernel,
+                started: SystemTime::now(),
+                boot_transcript: String::new(),
+                boot_lines: 0,
+                boot_recorded: false,
+            }),
+        }
+    }
+
+    /// Redact secret values from every line before it is recorded.
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// The observer receiving console lines.
+    pub fn observer(&self) -> &Observer {
+        &self.observer
+    }
+
+    /// The boot phase of the most recent line.
+    pub fn phase(&self) -> BootPhase {
+        self.state.lock().unwrap().phase
+    }
+
+    /// Consume raw serial bytes; complete lines are recorded immediately.
+    pub fn feed(&self, bytes: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.extend_from_slice(bytes);
+        while let Some(end) = state.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = state.pending.drain(..=end).collect();
+            self.record_line(&mut state, &line[..end]);
+        }
+        if state.pending.len() >= MAX_LINE_BYTES {
+            let line = std::mem::take(&mut state.pending);
+            self.record_line(&mut state, &line);
+        }
+    }
+
+    /// Record any unterminated trailing line.
+    pub fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.flush_pending(&mut state);
+    }
+
+    /// The guest-agent is up: close the boot transcript.
+    pub fn mark_ready(&self) {
+        self.finish_boot(None);
+    }
+
+    /// Boot failed: close the boot transcript with `error` as the span status.
+    pub fn mark_failed(&self, error: &str) {
+        self.finish_boot(Some(error));
+    }
+
+    fn flush_pending(&self, state: &mut CaptureState) {
+        if !state.pending.is_empty() {
+            let line = std::mem::take(&mut state.pending);
+            self.record_line(state, &line);
+        }
+    }
+
+    fn record_line(&self, state: &mut CaptureState, raw: &[u8]) {
+        let text = String::from_utf8_lossy(raw);
+        let text = match &self.redactor {
+            Some(redactor) => Cow::Owned(redactor.redact(&text).into_owned()),
+            None => text,
+        };
+        let line = text.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            return;
+        }
+        if let Some(phase) = BootPhase::entered_by(line) {
+            state.phase = state.phase.max(phase);
+        }
+        self.observer
+            .logger()
+            .log_console(line, state.phase.as_str());
+
+        if !state.boot_recorded {
+            state.boot_lines += 1;
+            state
+                .boot_transcript
+                .push_str(&format!("[{}] {}\n", state.phase, line));
+            if state.boot_transcript.len() > MAX_BOOT_CONSOLE_BYTES {
+                let excess = state.boot_transcript.len() - MAX_BOOT_CONSOLE_BYTES;
+                let cut = state.boot_transcript.as_bytes()[excess..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(state.boot_transcript.len(), |i| excess + i + 1);
+                state.boot_transcript.drain(..cut);
+            }
+        }
+    }
+
+    fn finish_boot(&self, error: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        if state.boot_recorded {
+            return;
+        }
+        self.flush_pending(&mut state);
+        state.boot_recorded = true;
+
+        let tracer = self.observer.tracer();
+        let mut span = tracer.start_span("sandbox.boot");
+        span.start_time = state.started;
+        span.set_attribute("backend_type", super::backend_type());
+        span.set_attribute("boot.phase", state.phase.as_str());
+        span.add_event_with_attrs(
+            BOOT_CONSOLE_EVENT,
+            HashMap::from([
+                ("lines".to_string(), state.boot_lines.to_string()),
+                (
+                    "console".to_string(),
+                    std::mem::take(&mut state.boot_transcript),
+                ),
+            ]),
+        );
+        span.status = match error {
+            Some(error) => SpanStatus::Error(error.to_string()),
+            None => SpanStatus::Ok,
+        };
+        if error.is_none() {
+            state.phase = BootPhase::Ready;
+        }
+        tracer.finish_span(span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_lines_tagged_with_boot_phase() {
+        let capture = ConsoleCapture::new(Observer::test());
+        capture.feed(b"[    0.000000] Linux version 6.12\r\n[    0.41] Run /init as ");
+        capture.feed(b"init process\nguest-agent: starting\nguest-agent: OCI");
+        assert_eq!(capture.phase(), BootPhase::Agent);
+        capture.mark_ready();
+        capture.feed(b"late line\n");
+
+        let logs = capture
+            .observer()
+            .logger()
+            .get_entries_by_source(CONSOLE_LOG_SOURCE);
+        let tagged: Vec<_> = logs
+            .iter()
+            .map(|e| (e.attributes["boot_phase"].as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            tagged,
+            vec![
+                ("kernel", "[    0.000000] Linux version 6.12"),
+                ("init", "[    0.41] Run /init as init process"),
+                ("agent", "guest-agent: starting"),
+                ("agent", "guest-agent: OCI"),
+                ("ready", "late line"),
+            ]
+        );
+
+        let spans = capture.observer().get_traces();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].status, SpanStatus::Ok);
+        let event = &spans[0].events[0];
+        assert_eq!(event.name, BOOT_CONSOLE_EVENT);
+        assert_eq!(event.attributes["lines"], "4");
+        assert!(event.attributes["console"].ends_with("[agent] guest-agent: OCI\n"));
+        assert!(!event.attributes["console"].contains("late line"));
+    }
+
+    #[test]
+    fn test_secrets_redacted_from_console() {
+        use crate::secret::{Secret, SecretSource};
+        let secret = Secret::new("API_KEY", SecretSource::value("sk-live-123"))
+            .resolve()
+            .unwrap();
+        let capture =
+            ConsoleCapture::new(Observer::test()).with_redactor(Arc::new(Redactor::new(&[secret])));
+        capture.feed(b"init: API_KEY=sk-live-");
+        capture.feed(b"123 exported\n");
+        capture.mark_failed("boot failed");
+
+        let logs = capture
+            .observer()
+            .logger()
+            .get_entries_by_source(CONSOLE_LOG_SOURCE);
+        assert_eq!(logs[0].message, "init: API_KEY=[REDACTED:API_KEY] exported");
+        let console = &capture.observer().get_traces()[0].events[0].attributes["console"];
+        assert!(!console.contains("sk-live-123"), "{console}");
+    }
+
+    #[test]
+    fn test_boot_failure_keeps_transcript_tail() {
+        let capture = ConsoleCapture::new(Observer::test());
+        for i in 0..(MAX_BOOT_CONSOLE_BYTES / 16) {
+            capture.feed(format!("kernel line {:04}\n", i).as_bytes());
+        }
+        capture.feed(b"Kernel panic - not syncing: VFS\n");
+        capture.mark_failed("guest-agent handshake timed out");
+        capture.mark_ready();
+
+        let spans = capture.observer().get_traces();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(
+            spans[0].status,
+            SpanStatus::Error("guest-agent handshake timed out".into())
+        );
+        let console = &spans[0].events[0].attributes["console"];
+        assert!(console.len() <= MAX_BOOT_CONSOLE_BYTES);
+        assert!(console.starts_with("[kernel] kernel line"));
+        assert!(console.ends_with("[kernel] Kernel panic - not syncing: VFS\n"));
+        assert_eq!(capture.phase(), BootPhase::Kernel);
+    }
+}
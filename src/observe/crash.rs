@@ -0,0 +1,555 @@
+//! Crash reports for guests that die under a running exec.
+//!
+//! A guest kernel panic (including the guest-agent dying, which as PID 1
+//! panics the kernel) closes no connection the host would notice: an exec
+//! waiting on the dead guest would hang until its timeout. Every local
+//! sandbox therefore keeps a [`ConsoleTail`] of the last
+//! [`CRASH_CONSOLE_TAIL_BYTES`] of serial output. When it sees the panic
+//! line, or an exec fails after the VM has exited, the sandbox builds a
+//! [`CrashReport`] from the tail and the execs still in flight. The failing
+//! exec returns it as [`Error::GuestCrashed`](crate::Error::GuestCrashed),
+//! and it is handed to the sandbox's [`CrashSink`] if one is configured.
+//!
+//! The serial console is the one channel that survives a dying kernel, so
+//! the tail also keeps the kernel's own account of the crash: the oops that
+//! led to it (registers, call trace) and the panic report, up to the
+//! kernel's end marker or its `panic=` reboot notice. The report's
+//! [`backtrace`](CrashReport::backtrace) holds those lines on their own, so
+//! they are not lost among the rest of the output, and a report built as
+//! soon as the panic line appears waits briefly for the rest of them.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::{Error, Result};
+
+/// Serial output kept for a crash report.
+pub const CRASH_CONSOLE_TAIL_BYTES: usize = 16 * 1024;
+
+/// Prefix of the line the Linux kernel prints when it panics.
+const KERNEL_PANIC_MARKER: &str = "Kernel panic - not syncing";
+
+/// What the kernel says when PID 1 (the guest-agent) exits.
+const INIT_DIED_MARKER: &str = "Attempted to kill init";
+
+/// First lines of a kernel oops report, on x86_64 and arm64.
+const OOPS_MARKERS: &[&str] = &[
+    "BUG: ",
+    "kernel BUG at",
+    "Oops: ",
+    "general protection fault",
+    "Unable to handle kernel",
+    "Internal error: ",
+];
+
+/// Closes an oops report.
+const OOPS_END_MARKER: &str = "---[ end trace";
+
+/// Lines after which an oops is taken to have ended. Some reports, like
+/// `BUG: soft lockup`, never print [`OOPS_END_MARKER`].
+const MAX_OOPS_LINES: usize = 128;
+
+/// Close a panic report: the kernel's end marker, or the notice it prints
+/// instead when `panic=<secs>` reboots the guest.
+const PANIC_END_MARKERS: &[&str] = &["---[ end Kernel panic", "Rebooting in "];
+
+/// Lines kept in a [`CrashReport::backtrace`].
+pub const MAX_BACKTRACE_LINES: usize = 256;
+
+/// How many unrelated lines may separate an oops from the panic it caused
+/// for the two to be reported together.
+const OOPS_PANIC_GAP_LINES: usize = 4;
+
+/// Lines longer than this are split, so output without newlines can't grow
+/// the pending line without bound.
+const MAX_LINE_BYTES: usize = 4096;
+
+/// How the guest died.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashKind {
+    /// The guest kernel panicked.
+    KernelPanic,
+    /// The guest-agent exited; the kernel panics when PID 1 dies.
+    AgentDied,
+    /// The VM exited without a panic on the console (e.g. a triple fault,
+    /// or a backend that does not expose the console).
+    VmExited,
+    /// The guest-agent stopped answering heartbeats (see
+    /// [`HealthCheck`](crate::sandbox::health::HealthCheck)).
+    Unresponsive,
+    /// The guest stopped petting its watchdog device, typically because
+    /// its kernel hung (see
+    /// [`SandboxBuilder::watchdog`](crate::sandbox::SandboxBuilder::watchdog)).
+    WatchdogExpired,
+}
+
+impl CrashKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CrashKind::KernelPanic => "kernel_panic",
+            CrashKind::AgentDied => "agent_died",
+            CrashKind::VmExited => "vm_exited",
+            CrashKind::Unresponsive => "unresponsive",
+            CrashKind::WatchdogExpired => "watchdog_expired",
+        }
+    }
+}
+
+impl fmt::Display for CrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An exec that was running when the guest died.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingExec {
+    /// Pairs with the exec's `ExecStarted` sandbox event.
+    pub exec_id: u64,
+    pub program: String,
+    pub args: Vec<String>,
+    /// How long the exec had been running.
+    pub running_ms: u64,
+}
+
+/// What was known about a guest when it died.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashReport {
+    /// The sandbox's `sandbox` metrics label.
+    pub sandbox_id: String,
+    pub kind: CrashKind,
+    /// The kernel's `Kernel panic - not syncing` line, if it printed one.
+    pub panic_line: Option<String>,
+    /// The kernel's report of the crash, line by line: the oops that
+    /// caused the panic, if any, then the panic with its call trace. At
+    /// most [`MAX_BACKTRACE_LINES`]; empty when the kernel printed none.
+    pub backtrace: Vec<String>,
+    /// The last [`CRASH_CONSOLE_TAIL_BYTES`] of serial output.
+    pub console_tail: String,
+    /// Execs in flight when the crash was detected.
+    pub pending_execs: Vec<PendingExec>,
+    /// The error the exec that detected the crash failed with, if any.
+    pub cause: Option<String>,
+    /// Unix time of detection in milliseconds.
+    pub timestamp_ms: u64,
+}
+
+impl CrashReport {
+    /// Build a report from `tail` and the execs still in flight. Without a
+    /// panic line the kind is [`CrashKind::VmExited`]; telling that apart
+    /// from an ordinary exec failure is up to the caller.
+    pub fn new(
+        sandbox_id: &str,
+        tail: &ConsoleTail,
+        pending_execs: Vec<PendingExec>,
+        cause: Option<String>,
+    ) -> Self {
+        let panic_line = tail.panic_line();
+        let kind = match &panic_line {
+            Some(line) if line.contains(INIT_DIED_MARKER) => CrashKind::AgentDied,
+            Some(_) => CrashKind::KernelPanic,
+            None => CrashKind::VmExited,
+        };
+        Self {
+            sandbox_id: sandbox_id.to_string(),
+            kind,
+            panic_line,
+            backtrace: tail.backtrace(),
+            console_tail: tail.contents(),
+            pending_execs,
+            cause,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Redact secret values from the console transcript.
+    pub(crate) fn redact(&mut self, redactor: &crate::secret::Redactor) {
+        if let Some(line) = &self.panic_line {
+            self.panic_line = Some(redactor.redact(line).into_owned());
+        }
+        for line in &mut self.backtrace {
+            *line = redactor.redact(line).into_owned();
+        }
+        self.console_tail = redactor.redact(&self.console_tail).into_owned();
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in sandbox {}", self.kind, self.sandbox_id)?;
+        match (&self.panic_line, &self.cause) {
+            (Some(line), _) => write!(f, ": {}", line),
+            (None, Some(cause)) => write!(f, ": {}", cause),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Destination for [`CrashReport`]s.
+///
+/// A failing sink is logged; the exec still fails with the report.
+#[async_trait::async_trait]
+pub trait CrashSink: Send + Sync + fmt::Debug {
+    /// Store one report.
+    async fn store(&self, report: &CrashReport) -> Result<()>;
+}
+
+/// Sink that writes each report as `crash-<sandbox>-<timestamp>.json`
+/// under a directory, creating it if needed.
+#[derive(Debug, Clone)]
+pub struct DirCrashSink {
+    dir: PathBuf,
+}
+
+impl DirCrashSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Where `report` is written.
+    pub fn path_for(&self, report: &CrashReport) -> PathBuf {
+        self.dir.join(format!(
+            "crash-{}-{}.json",
+            report.sandbox_id, report.timestamp_ms
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl CrashSink for DirCrashSink {
+    async fn store(&self, report: &CrashReport) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let body = serde_json::to_vec_pretty(report)?;
+        tokio::fs::write(self.path_for(report), body).await?;
+        Ok(())
+    }
+}
+
+/// Sink that hands each report to a caller-supplied closure.
+#[derive(Clone)]
+pub struct CallbackCrashSink {
+    callback: Arc<dyn Fn(&CrashReport) + Send + Sync>,
+}
+
+impl CallbackCrashSink {
+    /// Create a sink invoking `callback` for every report.
+    pub fn new(callback: impl Fn(&CrashReport) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+impl fmt::Debug for CallbackCrashSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackCrashSink").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl CrashSink for CallbackCrashSink {
+    async fn store(&self, report: &CrashReport) -> Result<()> {
+        (self.callback)(report);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct TailState {
+    bytes: Vec<u8>,
+    line: Vec<u8>,
+    /// The last oops and the panic report; see [`ConsoleTail::backtrace`].
+    backtrace: Vec<String>,
+    /// Whether lines are going into `backtrace`.
+    in_report: bool,
+    /// Lines in the oops being collected.
+    oops_lines: usize,
+    /// Lines since the last report ended.
+    since_report: usize,
+}
+
+/// The last [`CRASH_CONSOLE_TAIL_BYTES`] of guest serial output, watching
+/// for the kernel's panic line and collecting its crash report.
+pub struct ConsoleTail {
+    state: Mutex<TailState>,
+    panic_line: watch::Sender<Option<String>>,
+    /// Set once the panic report has ended.
+    panic_finished: watch::Sender<bool>,
+}
+
+impl Default for ConsoleTail {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleTail {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(TailState {
+                bytes: Vec::with_capacity(CRASH_CONSOLE_TAIL_BYTES),
+                ..TailState::default()
+            }),
+            panic_line: watch::channel(None).0,
+            panic_finished: watch::channel(false).0,
+        }
+    }
+
+    /// Consume raw serial bytes.
+    pub fn feed(&self, bytes: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        state.bytes.extend_from_slice(bytes);
+        if state.bytes.len() > CRASH_CONSOLE_TAIL_BYTES {
+            let excess = state.bytes.len() - CRASH_CONSOLE_TAIL_BYTES;
+            state.bytes.drain(..excess);
+        }
+
+        for &byte in bytes {
+            if byte == b'\n' || state.line.len() >= MAX_LINE_BYTES {
+                let line = std::mem::take(&mut state.line);
+                self.check_line(&mut state, &line);
+            }
+            if byte != b'\n' {
+                state.line.push(byte);
+            }
+        }
+    }
+
+    fn check_line(&self, state: &mut TailState, raw: &[u8]) {
+        if *self.panic_finished.borrow() {
+            return;
+        }
+        let text = String::from_utf8_lossy(raw);
+        let text = text.trim_end();
+        let panicked = self.panic_line.borrow().is_some();
+        if !panicked {
+            if let Some(start) = text.find(KERNEL_PANIC_MARKER) {
+                // An oops that is still open or just ended is what the
+                // kernel is panicking over; keep it in front.
+                if !state.in_report && state.since_report > OOPS_PANIC_GAP_LINES {
+                    state.backtrace.clear();
+                }
+                state.in_report = true;
+                self.panic_line
+                    .send_replace(Some(text[start..].to_string()));
+            } else if !state.in_report && OOPS_MARKERS.iter().any(|m| text.contains(m)) {
+                state.backtrace.clear();
+                state.in_report = true;
+                state.oops_lines = 0;
+            }
+        }
+        if !state.in_report {
+            state.since_report += 1;
+            return;
+        }
+        if state.backtrace.len() < MAX_BACKTRACE_LINES {
+            state.backtrace.push(text.to_string());
+        }
+        let panicked = self.panic_line.borrow().is_some();
+        if panicked && PANIC_END_MARKERS.iter().any(|m| text.contains(m)) {
+            state.in_report = false;
+            self.panic_finished.send_replace(true);
+        } else if !panicked {
+            state.oops_lines += 1;
+            if text.contains(OOPS_END_MARKER) || state.oops_lines >= MAX_OOPS_LINES {
+                state.in_report = false;
+                state.since_report = 0;
+            }
+        }
+    }
+
+    /// The captured output, lossily decoded.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.state.lock().unwrap().bytes).into_owned()
+    }
+
+    /// The kernel panic line, once one has been seen.
+    pub fn panic_line(&self) -> Option<String> {
+        self.panic_line.borrow().clone()
+    }
+
+    /// The kernel's report of the last oops and the panic, line by line.
+    /// Complete once [`panic_finished`](Self::panic_finished) resolves.
+    pub fn backtrace(&self) -> Vec<String> {
+        self.state.lock().unwrap().backtrace.clone()
+    }
+
+    /// Resolve once the kernel has finished printing its panic report.
+    pub async fn panic_finished(&self) {
+        let mut rx = self.panic_finished.subscribe();
+        let _ = rx.wait_for(|finished| *finished).await;
+    }
+
+    /// Resolve once the kernel prints its panic line.
+    pub async fn panicked(&self) -> String {
+        let mut rx = self.panic_line.subscribe();
+        let line = rx
+            .wait_for(Option::is_some)
+            .await
+            .expect("sender is owned by self");
+        line.clone().unwrap_or_default()
+    }
+
+    /// Forget everything captured, e.g. before the VM is booted again.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.bytes.clear();
+        state.line.clear();
+        state.backtrace.clear();
+        state.in_report = false;
+        state.oops_lines = 0;
+        state.since_report = 0;
+        self.panic_line.send_replace(None);
+        self.panic_finished.send_replace(false);
+    }
+}
+
+/// Deliver `report` to `sink`, logging a failure instead of returning it.
+pub(crate) async fn store_report(sink: &dyn CrashSink, report: &CrashReport) {
+    if let Err(e) = sink.store(report).await {
+        tracing::warn!("crash sink {:?} failed: {}", sink, e);
+    }
+}
+
+/// The error an exec fails with when the guest crashed under it.
+pub(crate) fn crashed(report: CrashReport) -> Error {
+    Error::GuestCrashed(Box::new(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_tail_keeps_last_bytes_and_finds_panic() {
+        let tail = ConsoleTail::new();
+        tail.feed(&vec![b'x'; CRASH_CONSOLE_TAIL_BYTES]);
+        tail.feed(b"\n[    1.234] Kernel panic - not syncing: Attempted to kill init! exitcode=0x0000000b\r\n");
+        tail.feed(b"---[ end Kernel panic ]---\n");
+
+        let contents = tail.contents();
+        assert_eq!(contents.len(), CRASH_CONSOLE_TAIL_BYTES);
+        assert!(contents.ends_with("---[ end Kernel panic ]---\n"));
+
+        let report = CrashReport::new("sb-1", &tail, Vec::new(), None);
+        assert_eq!(report.kind, CrashKind::AgentDied);
+        assert_eq!(
+            report.panic_line.as_deref(),
+            Some("Kernel panic - not syncing: Attempted to kill init! exitcode=0x0000000b")
+        );
+        assert!(report
+            .to_string()
+            .starts_with("agent_died in sandbox sb-1: Kernel panic"));
+
+        tail.reset();
+        assert_eq!(tail.panic_line(), None);
+        assert!(tail.contents().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_console_tail_collects_oops_and_panic_report() {
+        let tail = ConsoleTail::new();
+        tail.feed(b"[    0.500] BUG: kernel NULL pointer dereference, address: 0000000000000008\n");
+        tail.feed(b"[    0.500] RIP: 0010:vsock_connect+0x1c/0x90\n");
+        tail.feed(b"[    0.501] ---[ end trace 0000000000000000 ]---\n");
+        tail.feed(b"guest-agent: still here\n");
+        tail.feed(b"[    0.502] Kernel panic - not syncing: Fatal exception\n");
+        tail.feed(b"[    0.502] Call Trace:\n");
+        assert_eq!(
+            tail.panic_line().as_deref(),
+            Some("Kernel panic - not syncing: Fatal exception")
+        );
+        tail.feed(b"[    0.503] Rebooting in 1 seconds..\n");
+        tail.feed(b"after the report\n");
+        tail.panic_finished().await;
+
+        let report = CrashReport::new("sb-4", &tail, Vec::new(), None);
+        assert_eq!(report.kind, CrashKind::KernelPanic);
+        assert_eq!(report.backtrace.len(), 6);
+        assert!(report.backtrace[0].contains("BUG: kernel NULL pointer"));
+        assert!(report.backtrace[3].ends_with("Kernel panic - not syncing: Fatal exception"));
+        assert_eq!(report.backtrace[5], "[    0.503] Rebooting in 1 seconds..");
+    }
+
+    #[test]
+    fn test_console_tail_drops_a_stale_oops_from_the_panic_report() {
+        let tail = ConsoleTail::new();
+        tail.feed(b"Oops: 0000 [#1] SMP\n---[ end trace 1 ]---\n");
+        tail.feed("line\n".repeat(OOPS_PANIC_GAP_LINES + 1).as_bytes());
+        tail.feed(b"Kernel panic - not syncing: Attempted to kill init!\n");
+        assert_eq!(
+            tail.backtrace(),
+            vec!["Kernel panic - not syncing: Attempted to kill init!"]
+        );
+    }
+
+    #[test]
+    fn test_console_tail_keeps_an_open_oops_when_the_panic_starts() {
+        let tail = ConsoleTail::new();
+        tail.feed(b"[   22.100] watchdog: BUG: soft lockup - CPU#0 stuck for 22s! [make:41]\n");
+        tail.feed(b"[   22.100] RIP: 0010:native_safe_halt+0xe/0x10\n");
+        tail.feed(b"[   22.101] Kernel panic - not syncing: softlockup: hung tasks\n");
+        let backtrace = tail.backtrace();
+        assert_eq!(backtrace.len(), 3);
+        assert!(backtrace[0].contains("BUG: soft lockup"));
+        assert!(backtrace[2].ends_with("Kernel panic - not syncing: softlockup: hung tasks"));
+    }
+
+    #[test]
+    fn test_console_tail_closes_an_oops_without_end_marker() {
+        let tail = ConsoleTail::new();
+        tail.feed(b"watchdog: BUG: soft lockup - CPU#0 stuck for 22s!\n");
+        tail.feed("trace\n".repeat(MAX_OOPS_LINES + 10).as_bytes());
+        assert_eq!(tail.backtrace().len(), MAX_OOPS_LINES);
+
+        tail.feed("line\n".repeat(OOPS_PANIC_GAP_LINES).as_bytes());
+        tail.feed(b"Kernel panic - not syncing: Attempted to kill init!\n");
+        assert_eq!(
+            tail.backtrace(),
+            vec!["Kernel panic - not syncing: Attempted to kill init!"]
+        );
+    }
+
+    #[test]
+    fn test_crash_report_without_panic_is_vm_exit() {
+        let tail = ConsoleTail::new();
+        tail.feed(b"guest-agent: ready\n");
+        let pending = vec![PendingExec {
+            exec_id: 7,
+            program: "make".into(),
+            args: vec!["-j8".into()],
+            running_ms: 1200,
+        }];
+        let report = CrashReport::new("sb-2", &tail, pending, Some("channel closed".into()));
+        assert_eq!(report.kind, CrashKind::VmExited);
+        assert_eq!(
+            report.to_string(),
+            "vm_exited in sandbox sb-2: channel closed"
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["kind"], "vm_exited");
+        assert_eq!(json["pending_execs"][0]["program"], "make");
+    }
+
+    #[tokio::test]
+    async fn test_dir_crash_sink_writes_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = DirCrashSink::new(dir.path().join("crashes"));
+        let report = CrashReport::new("sb-3", &ConsoleTail::new(), Vec::new(), None);
+        sink.store(&report).await.unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(sink.path_for(&report)).unwrap()).unwrap();
+        assert_eq!(written["sandbox_id"], "sb-3");
+    }
+}
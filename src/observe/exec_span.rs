@@ -0,0 +1,269 @@
+//! Per-exec spans with a guest process timeline.
+//!
+//! [`Observer::start_exec_span`](super::Observer::start_exec_span) opens an
+//! `exec:<program>` span when a command is sent to the guest, as a child of
+//! the active [`SpanContext`] (the workflow step, under the scheduler).
+//! [`ExecSpan::finish`] closes it with child events placed at the times the
+//! guest-agent reported (`spawn`, `first-stdout-byte`, `timeout-fired`,
+//! `exit`). When a [`TelemetryAggregator`] is attached, it also adds a
+//! `process-sample` event for each CPU/RSS reading of the child PID taken
+//! while the exec ran.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
+
+use super::telemetry::{ProcessSample, TelemetryAggregator};
+use super::tracer::SpanEvent;
+use super::{Span, SpanContext, SpanStatus, Tracer};
+use crate::ExecTimeline;
+
+/// Guest clock ticks per second (`USER_HZ`), for converting CPU jiffies.
+const GUEST_CLOCK_TICKS: f64 = 100.0;
+
+/// An exec span awaiting its result.
+pub struct ExecSpan {
+    span: Span,
+    tracer: Arc<Tracer>,
+    telemetry: Option<Weak<TelemetryAggregator>>,
+}
+
+impl ExecSpan {
+    pub(crate) fn start(tracer: Arc<Tracer>, program: &str, args: &[&str]) -> Self {
+        let name = format!("exec:{}", program);
+        let mut span = match SpanContext::current() {
+            Some(parent) => tracer.start_span_with_parent(&name, &parent),
+            None => tracer.start_span(&name),
+        };
+        span.set_attribute("exec", format!("{} {}", program, args.join(" ")));
+        span.set_attribute("backend_type", super::backend_type());
+        Self {
+            span,
+            tracer,
+            telemetry: None,
+        }
+    }
+
+    /// Context of this span, propagated to the guest process.
+    pub fn context(&self) -> SpanContext {
+        self.span.context.clone()
+    }
+
+    /// Attach sampled CPU/RSS of the child process from `telemetry` on
+    /// finish.
+    pub fn with_telemetry(mut self, telemetry: Weak<TelemetryAggregator>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Close the span. `exit_code` is `None` when the exec itself failed.
+    pub fn finish(mut self, exit_code: Option<i32>, error: Option<&str>, timeline: &ExecTimeline) {
+        let started = self.span.start_time;
+        let at = |ms: u64| started + Duration::from_millis(ms);
+        let pid = timeline.pid.map(|p| p.to_string());
+
+        if let Some(ms) = timeline.spawn_ms {
+            let mut attrs = HashMap::new();
+            if let Some(pid) = &pid {
+                attrs.insert("pid".to_string(), pid.clone());
+            }
+            self.push_event("spawn", at(ms), attrs);
+        }
+        if let Some(ms) = timeline.first_stdout_ms {
+            self.push_event("first-stdout-byte", at(ms), HashMap::new());
+        }
+        if let Some(ms) = timeline.timeout_ms {
+            self.push_event("timeout-fired", at(ms), HashMap::new());
+        }
+        let mut exit_attrs = HashMap::new();
+        if let Some(code) = exit_code {
+            exit_attrs.insert("exit_code".to_string(), code.to_string());
+            self.span.set_attribute("exit_code", code.to_string());
+        }
+        let exit_at = timeline.exit_ms.map(at).unwrap_or_else(SystemTime::now);
+        self.push_event("exit", exit_at, exit_attrs);
+
+        if let Some(pid) = &pid {
+            self.span.set_attribute("process.pid", pid.clone());
+        }
+        if let (Some(pid), Some(telemetry)) = (
+            timeline.pid,
+            self.telemetry.as_ref().and_then(Weak::upgrade),
+        ) {
+            let since_ms = started
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            self.attach_samples(&telemetry.process_samples(pid, since_ms));
+        }
+
+        self.span.events.sort_by_key(|e| e.timestamp);
+        self.span.status = match (exit_code, error) {
+            (_, Some(error)) => SpanStatus::Error(error.to_string()),
+            (Some(0), None) => SpanStatus::Ok,
+            (Some(code), None) => SpanStatus::Error(format!("exited with code {}", code)),
+            (None, None) => SpanStatus::Unset,
+        };
+        self.tracer.finish_span(self.span);
+    }
+
+    fn push_event(
+        &mut self,
+        name: &str,
+        timestamp: SystemTime,
+        attributes: HashMap<String, String>,
+    ) {
+        self.span.events.push(SpanEvent {
+            name: name.to_string(),
+            timestamp,
+            attributes,
+        });
+    }
+
+    fn attach_samples(&mut self, samples: &[ProcessSample]) {
+        if samples.is_empty() {
+            return;
+        }
+        let mut previous: Option<&ProcessSample> = None;
+        for sample in samples {
+            let mut attrs =
+                HashMap::from([("rss_bytes".to_string(), sample.rss_bytes.to_string())]);
+            if let Some(prev) = previous {
+                let elapsed_ms = sample.timestamp_ms.saturating_sub(prev.timestamp_ms);
+                if elapsed_ms > 0 {
+                    let cpu_secs = sample.cpu_jiffies.saturating_sub(prev.cpu_jiffies) as f64
+                        / GUEST_CLOCK_TICKS;
+                    let percent = cpu_secs * 100_000.0 / elapsed_ms as f64;
+                    attrs.insert("cpu_percent".to_string(), format!("{:.1}", percent));
+                }
+            }
+            self.push_event(
+                "process-sample",
+                SystemTime::UNIX_EPOCH + Duration::from_millis(sample.timestamp_ms),
+                attrs,
+            );
+            previous = Some(sample);
+        }
+
+        let max_rss = samples.iter().map(|s| s.rss_bytes).max().unwrap_or(0);
+        let cpu_jiffies = samples[samples.len() - 1]
+            .cpu_jiffies
+            .saturating_sub(samples[0].cpu_jiffies);
+        self.span
+            .set_attribute("process.samples", samples.len().to_string());
+        self.span
+            .set_attribute("process.max_rss_bytes", max_rss.to_string());
+        self.span.set_attribute(
+            "process.cpu_seconds",
+            format!("{:.2}", cpu_jiffies as f64 / GUEST_CLOCK_TICKS),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guest::protocol::{ProcessMetrics, TelemetryBatch};
+    use crate::observe::Observer;
+
+    fn batch(timestamp_ms: u64, pid: u32, rss_bytes: u64, cpu_jiffies: u64) -> TelemetryBatch {
+        TelemetryBatch {
+            seq: 0,
+            timestamp_ms,
+            system: None,
+            processes: vec![ProcessMetrics {
+                pid,
+                comm: "python3".into(),
+                rss_bytes,
+                cpu_jiffies,
+                state: 'R',
+                ..Default::default()
+            }],
+            trace_context: None,
+        }
+    }
+
+    #[test]
+    fn test_exec_span_timeline_events() {
+        let observer = Observer::test();
+        let span = observer.start_exec_span("sleep", &["5"]);
+        span.finish(
+            None,
+            Some("Process killed after 2s timeout"),
+            &ExecTimeline {
+                pid: Some(7),
+                spawn_ms: Some(4),
+                first_stdout_ms: None,
+                timeout_ms: Some(2004),
+                exit_ms: Some(2010),
+            },
+        );
+
+        let spans = observer.get_traces();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.name, "exec:sleep");
+        assert_eq!(span.attributes["exec"], "sleep 5");
+        assert_eq!(span.attributes["process.pid"], "7");
+        let names: Vec<_> = span.events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["spawn", "timeout-fired", "exit"]);
+        assert_eq!(
+            span.events[1]
+                .timestamp
+                .duration_since(span.start_time)
+                .unwrap(),
+            Duration::from_millis(2004)
+        );
+        assert_eq!(
+            span.status,
+            SpanStatus::Error("Process killed after 2s timeout".into())
+        );
+    }
+
+    #[test]
+    fn test_exec_span_attaches_process_samples() {
+        let observer = Observer::test();
+        let aggregator = Arc::new(TelemetryAggregator::new(Observer::test(), 3));
+        let span = observer
+            .start_exec_span("python3", &["train.py"])
+            .with_telemetry(Arc::downgrade(&aggregator));
+        let now_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        aggregator.ingest(&batch(now_ms - 60_000, 42, 1, 0));
+        aggregator.ingest(&batch(now_ms + 1000, 42, 1_000_000, 100));
+        aggregator.ingest(&batch(now_ms + 2000, 42, 3_000_000, 150));
+        aggregator.ingest(&batch(now_ms + 2000, 43, 9_000_000, 900));
+
+        span.finish(
+            Some(0),
+            None,
+            &ExecTimeline {
+                pid: Some(42),
+                spawn_ms: Some(1),
+                first_stdout_ms: Some(20),
+                exit_ms: Some(2500),
+                ..Default::default()
+            },
+        );
+
+        let span = &observer.get_traces()[0];
+        assert_eq!(span.status, SpanStatus::Ok);
+        assert_eq!(span.attributes["process.samples"], "2");
+        assert_eq!(span.attributes["process.max_rss_bytes"], "3000000");
+        assert_eq!(span.attributes["process.cpu_seconds"], "0.50");
+        let samples: Vec<_> = span
+            .events
+            .iter()
+            .filter(|e| e.name == "process-sample")
+            .collect();
+        assert_eq!(samples.len(), 2);
+        assert!(!samples[0].attributes.contains_key("cpu_percent"));
+        assert_eq!(samples[1].attributes["cpu_percent"], "50.0");
+        assert!(span
+            .events
+            .windows(2)
+            .all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+}
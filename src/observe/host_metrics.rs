@@ -3,10 +3,17 @@
 //! Collects daemon-process metrics from the host OS. On Linux, reads
 //! `/proc/self/statm`, `/proc/self/stat`, and `/proc/self/io`. On macOS, uses
 //! Mach task APIs for RSS and `getrusage()` for CPU time.
+//!
+//! [`VmmStats`] adds what the VMM does for one VM — vCPU threads, virtio
+//! queues, vsock traffic, SLIRP sockets — recorded as `host.*` metrics next
+//! to the guest's own `guest.*` telemetry, so a slow run can be pinned on
+//! one side or the other.
 
 #[cfg(target_os = "macos")]
 use std::time::Instant;
 
+use super::metrics::MetricsCollector;
+
 /// Snapshot of host daemon metrics.
 #[derive(Debug, Clone, Default)]
 pub struct HostSnapshot {
@@ -16,6 +23,98 @@ pub struct HostSnapshot {
     pub io_write_bytes: u64,
 }
 
+/// Host-side resource usage attributable to one VM. Fields a backend cannot
+/// observe (vCPU threads and virtio queues are in-process only on KVM) stay
+/// zero or empty.
+#[derive(Debug, Clone, Default)]
+pub struct VmmStats {
+    /// The VMM process as a whole, shared by every VM it runs.
+    pub process: HostSnapshot,
+    /// Share of one host CPU each vCPU thread used since the previous
+    /// sample, in percent, indexed by vCPU.
+    pub vcpu_percent: Vec<f64>,
+    /// virtio-net TX descriptor chains posted by the guest and not yet
+    /// consumed.
+    pub net_tx_pending: u64,
+    /// Frames waiting for the guest to post virtio-net RX buffers.
+    pub net_rx_backlog: u64,
+    /// Control-channel bytes sent to the guest, cumulative.
+    pub vsock_bytes_sent: u64,
+    /// Control-channel bytes received from the guest, cumulative.
+    pub vsock_bytes_received: u64,
+    /// Host sockets SLIRP holds open for the guest, by kind (`tcp`, `udp`,
+    /// `icmp`, `listener`).
+    pub slirp_sockets: Vec<(&'static str, u64)>,
+}
+
+impl VmmStats {
+    /// Record these stats into `metrics` as `host.*` gauges carrying
+    /// `labels`.
+    pub fn record(&self, metrics: &MetricsCollector, labels: &[(&str, &str)]) {
+        metrics.set_gauge(
+            "host.process.rss_bytes",
+            self.process.rss_bytes as f64,
+            labels,
+        );
+        metrics.set_gauge("host.process.cpu_percent", self.process.cpu_percent, labels);
+        for (vcpu, percent) in self.vcpu_percent.iter().enumerate() {
+            let vcpu = vcpu.to_string();
+            let vcpu_labels: Vec<(&str, &str)> = labels
+                .iter()
+                .copied()
+                .chain([("vcpu", vcpu.as_str())])
+                .collect();
+            metrics.set_gauge("host.vcpu.cpu_percent", *percent, &vcpu_labels);
+        }
+        metrics.set_gauge(
+            "host.virtio.net.tx_pending",
+            self.net_tx_pending as f64,
+            labels,
+        );
+        metrics.set_gauge(
+            "host.virtio.net.rx_backlog",
+            self.net_rx_backlog as f64,
+            labels,
+        );
+        metrics.set_gauge(
+            "host.vsock.bytes_sent",
+            self.vsock_bytes_sent as f64,
+            labels,
+        );
+        metrics.set_gauge(
+            "host.vsock.bytes_received",
+            self.vsock_bytes_received as f64,
+            labels,
+        );
+        for (kind, count) in &self.slirp_sockets {
+            let kind_labels: Vec<(&str, &str)> =
+                labels.iter().copied().chain([("kind", *kind)]).collect();
+            metrics.set_gauge("host.slirp.sockets", *count as f64, &kind_labels);
+        }
+    }
+}
+
+/// Cumulative CPU time (utime + stime, in clock ticks) of thread `tid` of
+/// this process.
+#[cfg(target_os = "linux")]
+pub fn thread_cpu_ticks(tid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/self/task/{tid}/stat")).ok()?;
+    parse_stat_cpu_ticks(&stat)
+}
+
+/// utime + stime from a `/proc/.../stat` line.
+#[cfg(target_os = "linux")]
+fn parse_stat_cpu_ticks(stat: &str) -> Option<u64> {
+    // Fields after the comm (which may contain spaces and parens):
+    // skip past the closing paren
+    let after_comm = stat.rsplit_once(')')?.1;
+    let fields: Vec<&str> = after_comm.split_whitespace().collect();
+    // field[11] = utime, field[12] = stime (0-indexed from after comm+state)
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
 /// Collects host-side daemon metrics. Maintains previous CPU tick values for
 /// delta-based CPU percentage calculation.
 #[derive(Default)]
@@ -70,14 +169,7 @@ impl HostMetricsCollector {
     fn read_cpu_percent(&self) -> Option<f64> {
         // Read /proc/self/stat for utime + stime (fields 14, 15, 1-indexed)
         let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
-        // Fields after the comm (which may contain spaces and parens):
-        // skip past the closing paren
-        let after_comm = stat.rsplit_once(')')?.1;
-        let fields: Vec<&str> = after_comm.split_whitespace().collect();
-        // field[11] = utime, field[12] = stime (0-indexed from after comm+state)
-        let utime: u64 = fields.get(11)?.parse().ok()?;
-        let stime: u64 = fields.get(12)?.parse().ok()?;
-        let process_ticks = utime + stime;
+        let process_ticks = parse_stat_cpu_ticks(&stat)?;
 
         // Read /proc/stat for total CPU ticks
         let proc_stat = std::fs::read_to_string("/proc/stat").ok()?;
@@ -236,6 +328,7 @@ fn timeval_to_nanos(timeval: libc::timeval) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::observe::metrics::MetricValue;
 
     #[test]
     fn test_host_metrics_collector_returns_snapshot() {
@@ -260,4 +353,47 @@ mod tests {
         assert!(snap2.cpu_percent >= 0.0);
         assert!(snap2.cpu_percent <= 100.0);
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_thread_cpu_ticks_reads_current_thread() {
+        let tid = unsafe { libc::gettid() } as u32;
+        assert!(thread_cpu_ticks(tid).is_some());
+        assert!(thread_cpu_ticks(u32::MAX).is_none());
+    }
+
+    #[test]
+    fn test_vmm_stats_record_host_gauges() {
+        let metrics = MetricsCollector::new(Default::default());
+        let stats = VmmStats {
+            vcpu_percent: vec![12.5, 40.0],
+            net_rx_backlog: 3,
+            slirp_sockets: vec![("tcp", 2)],
+            ..Default::default()
+        };
+        stats.record(&metrics, &[("vm_cid", "3")]);
+
+        let snapshot = metrics.snapshot();
+        let vcpus: Vec<_> = snapshot
+            .metrics
+            .values()
+            .filter(|m| m.name == "host.vcpu.cpu_percent")
+            .collect();
+        assert_eq!(vcpus.len(), 2);
+        assert!(vcpus
+            .iter()
+            .all(|m| m.labels.get("vm_cid").map(String::as_str) == Some("3")));
+        let backlog = snapshot
+            .metrics
+            .values()
+            .find(|m| m.name == "host.virtio.net.rx_backlog")
+            .unwrap();
+        assert!(matches!(backlog.value, MetricValue::Gauge(v) if v == 3.0));
+        let sockets = snapshot
+            .metrics
+            .values()
+            .find(|m| m.name == "host.slirp.sockets")
+            .unwrap();
+        assert_eq!(sockets.labels.get("kind").map(String::as_str), Some("tcp"));
+    }
 }
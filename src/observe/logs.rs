@@ -309,6 +309,19 @@ impl StructuredLogger {
         }
     }
 
+    /// Log a line of guest serial console output
+    pub fn log_console(&self, line: &str, boot_phase: &str) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let mut entry = LogEntry::new(LogLevel::Info, line).with_source("console");
+        entry
+            .attributes
+            .insert("boot_phase".to_string(), boot_phase.to_string());
+        self.record_entry(entry);
+    }
+
     fn log(&self, level: LogLevel, message: &str, attrs: &[(&str, &str)]) {
         if !self.config.enabled || level < self.config.level {
             return;
@@ -1,13 +1,13 @@
 //! Prometheus-compatible Metrics Collection
 //!
 //! Provides metrics collection for workflow execution:
-//! - Step duration histograms
+//! - Step duration histograms, with percentile estimates
 //! - Memory usage gauges
 //! - CPU usage gauges
 //! - Network I/O counters
 //! - Custom application metrics
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 use std::time::Duration;
 
@@ -28,8 +28,16 @@ pub struct MetricsConfig {
     pub pushgateway_endpoint: Option<String>,
     /// Enable in-memory collection (for testing)
     pub in_memory: bool,
+    /// Bucket upper bounds, in milliseconds, for duration histograms
+    pub duration_buckets: Vec<f64>,
 }
 
+/// Default duration histogram buckets, in milliseconds (1ms to 10min)
+pub const DEFAULT_DURATION_BUCKETS_MS: &[f64] = &[
+    1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0, 30000.0, 60000.0, 300000.0,
+    600000.0,
+];
+
 impl Default for MetricsConfig {
     fn default() -> Self {
         Self {
@@ -40,6 +48,7 @@ impl Default for MetricsConfig {
             network_io: true,
             pushgateway_endpoint: None,
             in_memory: false,
+            duration_buckets: DEFAULT_DURATION_BUCKETS_MS.to_vec(),
         }
     }
 }
@@ -59,6 +68,16 @@ impl MetricsConfig {
         self.pushgateway_endpoint = Some(endpoint.into());
         self
     }
+
+    /// Set the bucket upper bounds (milliseconds) for duration histograms
+    pub fn duration_buckets(mut self, buckets: &[f64]) -> Self {
+        let mut buckets = buckets.to_vec();
+        buckets.retain(|b| b.is_finite());
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+        self.duration_buckets = buckets;
+        self
+    }
 }
 
 /// Types of metrics
@@ -81,6 +100,10 @@ pub struct HistogramValue {
     pub count: u64,
     /// Bucket counts (le -> count)
     pub buckets: Vec<(f64, u64)>,
+    /// Smallest observed value (0 when empty)
+    pub min: f64,
+    /// Largest observed value (0 when empty)
+    pub max: f64,
 }
 
 impl HistogramValue {
@@ -95,11 +118,20 @@ impl HistogramValue {
             sum: 0.0,
             count: 0,
             buckets: buckets.iter().map(|&b| (b, 0)).collect(),
+            min: 0.0,
+            max: 0.0,
         }
     }
 
     /// Observe a value
     pub fn observe(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
         self.sum += value;
         self.count += 1;
 
@@ -118,6 +150,63 @@ impl HistogramValue {
             self.sum / self.count as f64
         }
     }
+
+    /// Estimate the `q`-quantile (`0.0..=1.0`) of the observed values.
+    ///
+    /// Interpolates linearly within the bucket holding the target rank, as
+    /// Prometheus' `histogram_quantile` does, and clamps to the observed
+    /// min/max. Ranks above the last bucket resolve to the maximum. Returns
+    /// `None` for an empty histogram.
+    pub fn percentile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 || !q.is_finite() {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) * self.count as f64;
+        let mut lower = (f64::NEG_INFINITY, 0u64);
+        for &(le, cumulative) in &self.buckets {
+            if cumulative as f64 >= rank && cumulative > lower.1 {
+                let lower_bound = lower.0.max(self.min);
+                let upper_bound = le.min(self.max);
+                let fraction = (rank - lower.1 as f64) / (cumulative - lower.1) as f64;
+                let estimate = lower_bound + (upper_bound - lower_bound) * fraction;
+                return Some(estimate.clamp(self.min, self.max));
+            }
+            lower = (le, cumulative);
+        }
+        Some(self.max)
+    }
+
+    /// Add another histogram's observations into this one.
+    ///
+    /// Returns `false` (leaving `self` untouched) when the bucket bounds
+    /// differ, since the counts can't be combined.
+    pub fn merge(&mut self, other: &HistogramValue) -> bool {
+        let same_bounds = self.buckets.len() == other.buckets.len()
+            && self
+                .buckets
+                .iter()
+                .zip(&other.buckets)
+                .all(|(a, b)| a.0 == b.0);
+        if !same_bounds {
+            return false;
+        }
+        if other.count == 0 {
+            return true;
+        }
+        if self.count == 0 {
+            self.min = other.min;
+            self.max = other.max;
+        } else {
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+        }
+        self.sum += other.sum;
+        self.count += other.count;
+        for (mine, theirs) in self.buckets.iter_mut().zip(&other.buckets) {
+            mine.1 += theirs.1;
+        }
+        true
+    }
 }
 
 impl Default for HistogramValue {
@@ -183,65 +272,200 @@ impl MetricsSnapshot {
         })
     }
 
+    /// Estimate the `q`-quantile of histogram `name`, e.g.
+    /// `percentile("step_duration_ms", 0.95)`.
+    ///
+    /// `name` may be a metric key or a metric name; with a name, every
+    /// labelled series of that histogram is merged first, so the estimate
+    /// covers all steps. Series whose buckets differ from the first one
+    /// found are skipped.
+    pub fn percentile(&self, name: &str, q: f64) -> Option<f64> {
+        self.merged_histogram(name)?.percentile(q)
+    }
+
+    /// The histogram `name` with all of its labelled series merged; see
+    /// [`percentile`](Self::percentile).
+    pub fn merged_histogram(&self, name: &str) -> Option<HistogramValue> {
+        if let Some(h) = self.get_histogram(name) {
+            return Some(h.clone());
+        }
+        let mut keys: Vec<&String> = self
+            .metrics
+            .iter()
+            .filter(|(_, m)| m.name == name && matches!(m.value, MetricValue::Histogram(_)))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        let mut merged: Option<HistogramValue> = None;
+        for key in keys {
+            let h = self.get_histogram(key)?;
+            match merged.as_mut() {
+                Some(merged) => {
+                    merged.merge(h);
+                }
+                None => merged = Some(h.clone()),
+            }
+        }
+        merged
+    }
+
     /// Format as Prometheus text format
     pub fn to_prometheus_text(&self) -> String {
-        let mut output = String::new();
-
-        for metric in self.metrics.values() {
-            // Help line
-            output.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
-
-            // Type line
-            let type_str = match &metric.value {
-                MetricValue::Counter(_) => "counter",
-                MetricValue::Gauge(_) => "gauge",
-                MetricValue::Histogram(_) => "histogram",
-            };
-            output.push_str(&format!("# TYPE {} {}\n", metric.name, type_str));
-
-            // Value line(s)
-            let labels_str = if metric.labels.is_empty() {
-                String::new()
-            } else {
-                let pairs: Vec<_> = metric
-                    .labels
-                    .iter()
-                    .map(|(k, v)| format!("{}=\"{}\"", k, v))
-                    .collect();
-                format!("{{{}}}", pairs.join(","))
-            };
+        render_prometheus_text(self.metrics.values().map(|m| (m, &[][..])))
+    }
+}
 
-            match &metric.value {
-                MetricValue::Counter(v) | MetricValue::Gauge(v) => {
-                    output.push_str(&format!("{}{} {}\n", metric.name, labels_str, v));
-                }
-                MetricValue::Histogram(h) => {
-                    for (le, count) in &h.buckets {
-                        output.push_str(&format!(
-                            "{}_bucket{{le=\"{}\"{}}} {}\n",
-                            metric.name,
-                            le,
-                            if labels_str.is_empty() {
-                                String::new()
-                            } else {
-                                format!(",{}", &labels_str[1..labels_str.len() - 1])
-                            },
+/// Render metrics in the Prometheus text exposition format.
+///
+/// Each metric comes with extra labels to attach (labels already on the
+/// metric win). Series sharing a name are grouped under one `HELP`/`TYPE`
+/// header, names and label keys are sanitized to the Prometheus charset,
+/// and output is sorted so repeated scrapes diff cleanly.
+pub(crate) fn render_prometheus_text<'a>(
+    series: impl IntoIterator<Item = (&'a Metric, &'a [(String, String)])>,
+) -> String {
+    type Labels = Vec<(String, String)>;
+    struct Family {
+        help: String,
+        kind: &'static str,
+        series: Vec<(Labels, Vec<String>)>,
+    }
+
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+    for (metric, extra) in series {
+        let name = sanitize_name(&metric.name, true);
+        let kind = match &metric.value {
+            MetricValue::Counter(_) => "counter",
+            MetricValue::Gauge(_) => "gauge",
+            MetricValue::Histogram(_) => "histogram",
+        };
+        let family = families.entry(name.clone()).or_insert_with(|| Family {
+            help: metric.help.clone(),
+            kind,
+            series: Vec::new(),
+        });
+        if family.kind != kind {
+            // Same sanitized name registered with two types; Prometheus
+            // would reject the whole scrape, so keep the first.
+            continue;
+        }
+
+        let mut labels: Vec<(String, String)> = metric
+            .labels
+            .iter()
+            .map(|(k, v)| (sanitize_name(k, false), v.clone()))
+            .collect();
+        for (k, v) in extra {
+            if !metric.labels.contains_key(k) {
+                labels.push((sanitize_name(k, false), v.clone()));
+            }
+        }
+        labels.sort();
+
+        let lines = match &metric.value {
+            MetricValue::Counter(v) | MetricValue::Gauge(v) => {
+                vec![format!("{}{} {}", name, format_labels(&labels, None), v)]
+            }
+            MetricValue::Histogram(h) => {
+                let mut lines: Vec<String> = h
+                    .buckets
+                    .iter()
+                    .map(|(le, count)| {
+                        format!(
+                            "{}_bucket{} {}",
+                            name,
+                            format_labels(&labels, Some(&le.to_string())),
                             count
-                        ));
-                    }
-                    output.push_str(&format!("{}_sum{} {}\n", metric.name, labels_str, h.sum));
-                    output.push_str(&format!(
-                        "{}_count{} {}\n",
-                        metric.name, labels_str, h.count
-                    ));
-                }
+                        )
+                    })
+                    .collect();
+                lines.push(format!(
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(&labels, Some("+Inf")),
+                    h.count
+                ));
+                lines.push(format!(
+                    "{}_sum{} {}",
+                    name,
+                    format_labels(&labels, None),
+                    h.sum
+                ));
+                lines.push(format!(
+                    "{}_count{} {}",
+                    name,
+                    format_labels(&labels, None),
+                    h.count
+                ));
+                lines
+            }
+        };
+        family.series.push((labels, lines));
+    }
+
+    let mut output = String::new();
+    for (name, mut family) in families {
+        output.push_str(&format!(
+            "# HELP {} {}\n",
+            name,
+            family.help.replace('\\', "\\\\").replace('\n', "\\n")
+        ));
+        output.push_str(&format!("# TYPE {} {}\n", name, family.kind));
+        family.series.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, lines) in family.series {
+            for line in lines {
+                output.push_str(&line);
+                output.push('\n');
             }
         }
+    }
+    output
+}
 
-        output
+/// Map a metric (or label) name onto `[a-zA-Z_:][a-zA-Z0-9_:]*` (labels
+/// additionally exclude `:`).
+fn sanitize_name(name: &str, allow_colon: bool) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
     }
+    out
 }
 
+fn format_labels(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Buckets for `cpu_usage_percent_histogram`
+const CPU_PERCENT_BUCKETS: &[f64] = &[5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 100.0];
+
 /// Metrics collector -- stores metrics in-memory and optionally exports via OTel.
 pub struct MetricsCollector {
     config: MetricsConfig,
@@ -287,9 +511,9 @@ impl MetricsCollector {
             .or_insert_with(|| Metric {
                 name: metric_name.clone(),
                 help: format!("Duration of {} in milliseconds", name),
-                value: MetricValue::Histogram(HistogramValue::with_buckets(&[
-                    1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0,
-                ])),
+                value: MetricValue::Histogram(HistogramValue::with_buckets(
+                    &self.config.duration_buckets,
+                )),
                 labels: HashMap::new(),
             });
 
@@ -300,11 +524,34 @@ impl MetricsCollector {
         // Also record via OTel histogram
         #[cfg(feature = "opentelemetry")]
         if let Some(ref meter) = self.otel_meter {
-            let histogram = meter.f64_histogram(metric_name).build();
+            let histogram = meter
+                .f64_histogram(metric_name)
+                .with_unit("ms")
+                .with_boundaries(self.config.duration_buckets.clone())
+                .build();
             histogram.record(duration_ms, &[]);
         }
     }
 
+    /// Record a duration into the labelled `<name>_duration_ms` histogram,
+    /// using the configured duration buckets.
+    ///
+    /// Unlike [`record_duration`](Self::record_duration), one histogram
+    /// covers every label set, so e.g. `step_duration_ms{step="..."}`
+    /// yields a p95 across all steps via [`MetricsSnapshot::percentile`].
+    pub fn observe_duration(&self, name: &str, duration: Duration, labels: &[(&str, &str)]) {
+        if !self.config.step_duration {
+            return;
+        }
+        let buckets = self.config.duration_buckets.clone();
+        self.observe_histogram(
+            &format!("{}_duration_ms", name),
+            duration.as_secs_f64() * 1000.0,
+            &buckets,
+            labels,
+        );
+    }
+
     /// Increment a counter
     pub fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
         self.add_counter(name, 1.0, labels);
@@ -386,6 +633,53 @@ impl MetricsCollector {
         }
     }
 
+    /// Observe a value into a labelled histogram, creating it with `buckets`
+    /// on first use
+    pub fn observe_histogram(
+        &self,
+        name: &str,
+        value: f64,
+        buckets: &[f64],
+        labels: &[(&str, &str)],
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let mut metrics = self.metrics.lock().unwrap();
+        let label_map: HashMap<String, String> = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let key = format!("{}:{:?}", name, label_map);
+
+        let metric = metrics.entry(key).or_insert_with(|| Metric {
+            name: name.to_string(),
+            help: format!("Histogram for {}", name),
+            value: MetricValue::Histogram(HistogramValue::with_buckets(buckets)),
+            labels: label_map,
+        });
+
+        if let MetricValue::Histogram(h) = &mut metric.value {
+            h.observe(value);
+        }
+
+        #[cfg(feature = "opentelemetry")]
+        if let Some(ref meter) = self.otel_meter {
+            use opentelemetry::KeyValue;
+            let histogram = meter
+                .f64_histogram(name.to_string())
+                .with_boundaries(buckets.to_vec())
+                .build();
+            let otel_labels: Vec<KeyValue> = labels
+                .iter()
+                .map(|(k, v)| KeyValue::new(k.to_string(), v.to_string()))
+                .collect();
+            histogram.record(value, &otel_labels);
+        }
+    }
+
     /// Record memory usage
     pub fn record_memory_usage(&self, bytes: u64, labels: &[(&str, &str)]) {
         if self.config.memory_usage {
@@ -422,9 +716,7 @@ impl MetricsCollector {
         let metric = metrics.entry(key).or_insert_with(|| Metric {
             name: "cpu_usage_percent_histogram".to_string(),
             help: "CPU usage distribution".to_string(),
-            value: MetricValue::Histogram(HistogramValue::with_buckets(&[
-                5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 100.0,
-            ])),
+            value: MetricValue::Histogram(HistogramValue::with_buckets(CPU_PERCENT_BUCKETS)),
             labels: label_map,
         });
 
@@ -435,7 +727,10 @@ impl MetricsCollector {
         #[cfg(feature = "opentelemetry")]
         if let Some(ref meter) = self.otel_meter {
             use opentelemetry::KeyValue;
-            let histogram = meter.f64_histogram("cpu_usage_percent_histogram").build();
+            let histogram = meter
+                .f64_histogram("cpu_usage_percent_histogram")
+                .with_boundaries(CPU_PERCENT_BUCKETS.to_vec())
+                .build();
             let otel_labels: Vec<KeyValue> = labels
                 .iter()
                 .map(|(k, v)| KeyValue::new(k.to_string(), v.to_string()))
@@ -489,6 +784,71 @@ mod tests {
         assert_eq!(hist.buckets[2].1, 3); // le=10.0: 0.5, 3.0, 7.0
     }
 
+    #[test]
+    fn test_histogram_percentile() {
+        let mut hist = HistogramValue::with_buckets(&[10.0, 100.0, 1000.0]);
+        assert_eq!(hist.percentile(0.5), None);
+
+        // 90 fast observations in (0, 10], 10 slow ones in (100, 1000]
+        for i in 0..90 {
+            hist.observe(1.0 + (i % 9) as f64);
+        }
+        for i in 0..10 {
+            hist.observe(200.0 + 50.0 * i as f64);
+        }
+
+        let p50 = hist.percentile(0.5).unwrap();
+        assert!((1.0..=10.0).contains(&p50), "p50 = {}", p50);
+        let p95 = hist.percentile(0.95).unwrap();
+        assert!((200.0..=650.0).contains(&p95), "p95 = {}", p95);
+        assert_eq!(hist.percentile(1.0), Some(650.0));
+        assert_eq!(hist.percentile(0.0), Some(1.0));
+
+        // Beyond the last bucket, the max is the best estimate
+        hist.observe(5000.0);
+        assert_eq!(hist.percentile(1.0), Some(5000.0));
+    }
+
+    #[test]
+    fn test_histogram_merge_requires_same_buckets() {
+        let mut a = HistogramValue::with_buckets(&[1.0, 10.0]);
+        a.observe(0.5);
+        let mut b = HistogramValue::with_buckets(&[1.0, 10.0]);
+        b.observe(8.0);
+        b.observe(20.0);
+
+        assert!(a.merge(&b));
+        assert_eq!(a.count, 3);
+        assert_eq!(a.buckets, vec![(1.0, 1), (10.0, 2)]);
+        assert_eq!((a.min, a.max), (0.5, 20.0));
+
+        assert!(!a.merge(&HistogramValue::with_buckets(&[5.0])));
+        assert_eq!(a.count, 3);
+    }
+
+    #[test]
+    fn test_snapshot_percentile_across_labels() {
+        let collector = MetricsCollector::new(
+            MetricsConfig::in_memory().duration_buckets(&[100.0, 10.0, 1000.0, 10.0]),
+        );
+        for ms in [5, 6, 7, 8, 9, 50, 60, 70, 80] {
+            collector.observe_duration("step", Duration::from_millis(ms), &[("step", "lint")]);
+        }
+        collector.observe_duration("step", Duration::from_millis(900), &[("step", "build")]);
+
+        let snapshot = collector.snapshot();
+        let merged = snapshot.merged_histogram("step_duration_ms").unwrap();
+        assert_eq!(merged.count, 10);
+        assert_eq!(
+            merged.buckets.iter().map(|b| b.0).collect::<Vec<_>>(),
+            vec![10.0, 100.0, 1000.0]
+        );
+        let p95 = snapshot.percentile("step_duration_ms", 0.95).unwrap();
+        assert!((100.0..=900.0).contains(&p95), "p95 = {}", p95);
+        assert!(snapshot.percentile("step_duration_ms", 0.4).unwrap() <= 10.0);
+        assert_eq!(snapshot.percentile("missing_duration_ms", 0.95), None);
+    }
+
     #[test]
     fn test_metrics_collector_duration() {
         let collector = MetricsCollector::new(MetricsConfig::in_memory());
@@ -542,6 +902,37 @@ mod tests {
         assert!(text.contains("test_duration_ms_bucket"));
     }
 
+    #[test]
+    fn test_prometheus_format_groups_and_sanitizes() {
+        let collector = MetricsCollector::new(MetricsConfig::in_memory());
+
+        collector.set_gauge("guest.rss_bytes", 1.0, &[("vm_cid", "3")]);
+        collector.set_gauge("guest.rss_bytes", 2.0, &[("vm_cid", "4")]);
+        collector.increment_counter("execs", &[("program", "say \"hi\"\n")]);
+        collector.observe_histogram("latency", 7.0, &[5.0, 10.0], &[]);
+
+        let snapshot = collector.snapshot();
+        let extra = [("sandbox".to_string(), "sb-1".to_string())];
+        let text = render_prometheus_text(snapshot.metrics.values().map(|m| (m, &extra[..])));
+
+        assert_eq!(text.matches("# TYPE guest_rss_bytes gauge").count(), 1);
+        let rss: Vec<_> = text
+            .lines()
+            .filter(|l| l.starts_with("guest_rss_bytes{"))
+            .collect();
+        assert_eq!(
+            rss,
+            vec![
+                "guest_rss_bytes{sandbox=\"sb-1\",vm_cid=\"3\"} 1",
+                "guest_rss_bytes{sandbox=\"sb-1\",vm_cid=\"4\"} 2",
+            ]
+        );
+        assert!(text.contains("execs{program=\"say \\\"hi\\\"\\n\",sandbox=\"sb-1\"} 1"));
+        assert!(text.contains("latency_bucket{sandbox=\"sb-1\",le=\"5\"} 0"));
+        assert!(text.contains("latency_bucket{sandbox=\"sb-1\",le=\"+Inf\"} 1"));
+        assert!(text.contains("latency_count{sandbox=\"sb-1\"} 1"));
+    }
+
     #[test]
     fn test_disabled_metrics() {
         let mut config = MetricsConfig::in_memory();
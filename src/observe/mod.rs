@@ -4,6 +4,7 @@
 //! - OpenTelemetry tracing integration
 //! - Prometheus-compatible metrics
 //! - Structured logging with correlation IDs
+//! - Cost/latency SLO alerts via pluggable sinks
 //!
 //! These capabilities are built in and available for every observed workflow run.
 //!
@@ -21,12 +22,23 @@
 //! // Traces, metrics, and logs are automatically captured during workflow execution
 //! ```
 
+pub mod boot;
 pub mod claude;
 pub mod codex;
+pub mod console;
+pub mod crash;
+pub mod exec_span;
 pub mod host_metrics;
 pub mod logs;
 pub mod metrics;
+pub mod network;
+pub mod openai;
 pub mod otlp;
+pub mod persist;
+pub mod pricing;
+pub mod prometheus;
+pub mod provenance;
+pub mod slo;
 pub mod telemetry;
 pub mod tracer;
 
@@ -39,6 +51,8 @@ use std::time::Instant;
 
 pub use logs::{LogConfig, LogEntry, LogLevel, StructuredLogger};
 pub use metrics::{MetricsCollector, MetricsConfig, MetricsSnapshot};
+pub use provenance::{ManifestSigningKey, RunManifest};
+pub use slo::{AlertSink, SloAlert, SloMonitor, SloPolicy, SloViolation};
 pub use tracer::{Span, SpanContext, SpanStatus, Tracer, TracerConfig};
 
 /// Returns the name of the VM backend for the current platform.
@@ -73,6 +87,14 @@ pub struct ObserveConfig {
     pub enable_websocket: bool,
     /// Enable point-in-time snapshots
     pub enable_snapshot: bool,
+    /// Destinations for SLO violation alerts
+    pub alert_sinks: Vec<Arc<dyn AlertSink>>,
+    /// Address for the embedded Prometheus `/metrics` endpoint
+    pub prometheus_listen: Option<String>,
+    /// Record guest serial console output as logs and a boot span event
+    pub capture_console: bool,
+    /// Sign the [`RunManifest`] of every observed run with this key
+    pub manifest_signing_key: Option<ManifestSigningKey>,
 }
 
 impl Default for ObserveConfig {
@@ -83,6 +105,10 @@ impl Default for ObserveConfig {
             logs: LogConfig::default(),
             enable_websocket: false,
             enable_snapshot: true,
+            alert_sinks: Vec::new(),
+            prometheus_listen: None,
+            capture_console: false,
+            manifest_signing_key: None,
         }
     }
 }
@@ -116,6 +142,10 @@ impl ObserveConfig {
             logs: LogConfig::in_memory(),
             enable_websocket: false,
             enable_snapshot: true,
+            alert_sinks: Vec::new(),
+            prometheus_listen: None,
+            capture_console: false,
+            manifest_signing_key: None,
         }
     }
 
@@ -148,12 +178,44 @@ impl ObserveConfig {
         self.enable_websocket = enable;
         self
     }
+
+    /// Add a destination for SLO violation alerts
+    pub fn alert_sink(mut self, sink: impl AlertSink + 'static) -> Self {
+        self.alert_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Serve collected metrics for Prometheus to scrape at
+    /// `http://<addr>/metrics`.
+    ///
+    /// Observers and sandboxes sharing an address share one listener; see
+    /// [`prometheus`] for what gets exported.
+    pub fn prometheus_listen(mut self, addr: impl Into<String>) -> Self {
+        self.prometheus_listen = Some(addr.into());
+        self
+    }
+
+    /// Capture the guest serial console into the sandbox's observer.
+    ///
+    /// Each line becomes a `console` log entry tagged with its boot phase,
+    /// and the boot transcript is attached to a `sandbox.boot` span; see
+    /// [`console`]. KVM only.
+    pub fn capture_console(mut self, enable: bool) -> Self {
+        self.capture_console = enable;
+        self
+    }
+
+    /// Sign the [`RunManifest`] of every observed run with `key`; see
+    /// [`provenance`].
+    pub fn sign_manifests(mut self, key: ManifestSigningKey) -> Self {
+        self.manifest_signing_key = Some(key);
+        self
+    }
 }
 
 /// Observer instance that collects traces, metrics, and logs
 #[derive(Clone)]
 pub struct Observer {
-    #[allow(dead_code)]
     config: ObserveConfig,
     tracer: Arc<Tracer>,
     metrics: Arc<MetricsCollector>,
@@ -224,6 +286,9 @@ impl Observer {
         maybe_init_global_otel(&config);
         let tracer = Arc::new(Tracer::new(config.tracer.clone()));
         let metrics = Arc::new(build_metrics_collector(&config));
+        if let Some(listen) = &config.prometheus_listen {
+            prometheus::register_or_warn(listen, &metrics, &[]);
+        }
         let logger = Arc::new(StructuredLogger::new(config.logs.clone()));
 
         Self {
@@ -270,9 +335,15 @@ impl Observer {
             logger: self.logger.clone(),
             start_time: Instant::now(),
             name: name.to_string(),
+            kind: "workflow",
         }
     }
 
+    /// Start a span for a command sent to the guest; see [`exec_span`].
+    pub fn start_exec_span(&self, program: &str, args: &[&str]) -> exec_span::ExecSpan {
+        exec_span::ExecSpan::start(self.tracer.clone(), program, args)
+    }
+
     /// Start a new span for a workflow step
     pub fn start_step_span(&self, name: &str, parent: Option<&SpanContext>) -> SpanGuard {
         let span = if let Some(parent) = parent {
@@ -289,7 +360,18 @@ impl Observer {
             logger: self.logger.clone(),
             start_time: Instant::now(),
             name: name.to_string(),
+            kind: "step",
+        }
+    }
+
+    /// Sign `manifest` if the configuration asks for it.
+    pub(crate) fn seal_manifest(&self, mut manifest: RunManifest) -> RunManifest {
+        if let Some(key) = &self.config.manifest_signing_key {
+            if let Err(e) = manifest.sign(key) {
+                tracing::warn!("Failed to sign run manifest: {}", e);
+            }
         }
+        manifest
     }
 
     /// Get collected traces
@@ -307,6 +389,35 @@ impl Observer {
         self.logger.get_entries()
     }
 
+    /// Record an SLO violation and deliver it to every configured alert sink.
+    ///
+    /// Sink failures are logged rather than propagated so that alert
+    /// delivery never fails the run being observed.
+    pub async fn raise_slo_alert(&self, alert: &SloAlert) {
+        let kind = alert.violation.kind();
+        let span_ids = alert.span_ids.join(",");
+        self.logger.warn(
+            &alert.to_string(),
+            &[
+                ("trace_id", &alert.trace_id),
+                ("span_ids", &span_ids),
+                ("slo.kind", kind),
+            ],
+        );
+        self.metrics.increment_counter(
+            slo::SLO_VIOLATIONS_METRIC,
+            &[("run", &alert.run), ("kind", kind)],
+        );
+        for sink in &self.config.alert_sinks {
+            if let Err(e) = sink.send(alert).await {
+                self.logger.error(
+                    &format!("SLO alert sink {:?} failed: {}", sink, e),
+                    &[("slo.kind", kind)],
+                );
+            }
+        }
+    }
+
     /// Check if a span with the given name exists
     pub fn has_span(&self, name: &str) -> bool {
         self.tracer.get_spans().iter().any(|s| s.name == name)
@@ -376,6 +487,8 @@ pub struct SpanGuard {
     logger: Arc<StructuredLogger>,
     start_time: Instant,
     name: String,
+    /// `workflow` or `step`; durations also go to `<kind>_duration_ms`
+    kind: &'static str,
 }
 
 impl SpanGuard {
@@ -403,6 +516,15 @@ impl SpanGuard {
         self.span.attributes.insert(key.to_string(), value.into());
     }
 
+    /// Add an event with attributes to the span
+    pub fn add_event_with_attrs(
+        &mut self,
+        name: impl Into<String>,
+        attrs: std::collections::HashMap<String, String>,
+    ) {
+        self.span.add_event_with_attrs(name, attrs);
+    }
+
     /// Record stdout output
     pub fn record_stdout(&mut self, size: usize) {
         self.span
@@ -417,18 +539,29 @@ impl SpanGuard {
             .insert("stderr_bytes".to_string(), size.to_string());
     }
 
+    /// Record a summary of guest filesystem changes (`fs_diff.*` attributes)
+    pub fn record_fs_diff(&mut self, diff: &crate::sandbox::FsDiff) {
+        self.span.attributes.extend(diff.span_attributes());
+    }
+
     /// Record the command that was executed
     pub fn record_exec(&mut self, program: &str, args: &[&str]) {
         let cmd = format!("{} {}", program, args.join(" "));
         self.span.attributes.insert("exec".to_string(), cmd);
     }
 
+    fn record_duration(&self, duration: std::time::Duration) {
+        self.metrics.record_duration(&self.name, duration);
+        self.metrics
+            .observe_duration(self.kind, duration, &[(self.kind, &self.name)]);
+    }
+
     fn finish(mut self) {
         let duration = self.start_time.elapsed();
         self.span.duration = Some(duration);
 
         // Record metrics
-        self.metrics.record_duration(&self.name, duration);
+        self.record_duration(duration);
 
         // Record to tracer
         self.tracer.finish_span(self.span.clone());
@@ -446,7 +579,7 @@ impl Drop for SpanGuard {
         if self.span.duration.is_none() {
             let duration = self.start_time.elapsed();
             self.span.duration = Some(duration);
-            self.metrics.record_duration(&self.name, duration);
+            self.record_duration(duration);
             self.tracer.finish_span(self.span.clone());
         }
     }
@@ -463,6 +596,8 @@ pub struct ObservedResult<T> {
     metrics: MetricsSnapshot,
     /// Collected logs
     logs: Vec<LogEntry>,
+    /// Provenance of the run
+    manifest: Option<RunManifest>,
 }
 
 impl<T> ObservedResult<T> {
@@ -473,9 +608,16 @@ impl<T> ObservedResult<T> {
             traces: observer.get_traces(),
             metrics: observer.get_metrics(),
             logs: observer.get_logs(),
+            manifest: None,
         }
     }
 
+    /// Attach the run's provenance, signed if the observer is configured to.
+    pub(crate) fn with_manifest(mut self, manifest: RunManifest, observer: &Observer) -> Self {
+        self.manifest = Some(observer.seal_manifest(manifest));
+        self
+    }
+
     /// Get the traces
     pub fn traces(&self) -> &[Span] {
         &self.traces
@@ -491,6 +633,32 @@ impl<T> ObservedResult<T> {
         &self.logs
     }
 
+    /// Get the run's provenance; see [`provenance`].
+    pub fn manifest(&self) -> Option<&RunManifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Flatten the run into a [`persist::RunRecord`].
+    pub fn record(&self) -> persist::RunRecord
+    where
+        T: persist::Outcome,
+    {
+        persist::RunRecord::new(
+            self.result.outcome(),
+            &self.traces,
+            &self.metrics,
+            self.manifest.as_ref(),
+        )
+    }
+
+    /// Store the run in `sink`; see [`persist`].
+    pub async fn persist(&self, sink: &dyn persist::ResultSink) -> crate::Result<()>
+    where
+        T: persist::Outcome,
+    {
+        sink.store(&self.record()).await
+    }
+
     /// Map the result value
     pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> ObservedResult<U> {
         ObservedResult {
@@ -498,6 +666,7 @@ impl<T> ObservedResult<T> {
             traces: self.traces,
             metrics: self.metrics,
             logs: self.logs,
+            manifest: self.manifest,
         }
     }
 }
@@ -561,6 +730,11 @@ mod tests {
 
         let traces = observer.get_traces();
         assert!(traces.iter().any(|s| s.name == "step:step1"));
+
+        let metrics = observer.get_metrics();
+        let steps = metrics.merged_histogram("step_duration_ms").unwrap();
+        assert_eq!(steps.count, 1);
+        assert!(metrics.percentile("workflow_duration_ms", 0.95).is_some());
     }
 
     #[test]
@@ -0,0 +1,330 @@
+//! Guest network connection log.
+//!
+//! The KVM backend's SLIRP stack reports every outbound flow the guest
+//! opened, or tried to open and was refused, as a [`ConnectionRecord`] once
+//! the flow closes. A sandbox collects them into a [`NetworkLog`], queryable
+//! through [`Sandbox::network_log`](crate::sandbox::Sandbox::network_log),
+//! so a run can be audited for what the agent talked to. When the sandbox
+//! is observed, the records are also attached to a `sandbox.network` span as
+//! `network-connection` events each time the sandbox stops.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::tracer::SpanEvent;
+use super::{Observer, SpanStatus};
+
+/// Name of the span event carrying one connection record.
+pub const NETWORK_CONNECTION_EVENT: &str = "network-connection";
+
+/// Records kept by [`NetworkLog::default`]; the oldest are dropped first.
+pub const DEFAULT_NETWORK_LOG_CAPACITY: usize = 10_000;
+
+/// Transport protocol of a guest flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkProtocol {
+    Tcp,
+    Udp,
+}
+
+impl NetworkProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkProtocol::Tcp => "tcp",
+            NetworkProtocol::Udp => "udp",
+        }
+    }
+}
+
+impl std::fmt::Display for NetworkProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One outbound guest flow, reported when it closed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionRecord {
+    pub protocol: NetworkProtocol,
+    /// Destination as the guest addressed it, before NAT translation.
+    pub dst_ip: Ipv4Addr,
+    pub dst_port: u16,
+    /// Bytes delivered to the guest.
+    pub bytes_in: u64,
+    /// Bytes the guest sent to the destination.
+    pub bytes_out: u64,
+    /// Unix epoch milliseconds when the guest opened the flow.
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    /// Refused by network policy (deny list, egress policy, connection
+    /// rate or concurrency limit) without reaching the destination.
+    pub denied: bool,
+    /// Violated a [log-only](crate::backend::NetworkPolicy::log_only)
+    /// egress policy, which let it through.
+    pub policy_violation: bool,
+}
+
+impl ConnectionRecord {
+    /// A flow that ran for `duration` and just closed.
+    pub fn closed(
+        protocol: NetworkProtocol,
+        dst_ip: Ipv4Addr,
+        dst_port: u16,
+        bytes_in: u64,
+        bytes_out: u64,
+        duration: Duration,
+    ) -> Self {
+        let started = SystemTime::now()
+            .checked_sub(duration)
+            .unwrap_or(UNIX_EPOCH);
+        Self {
+            protocol,
+            dst_ip,
+            dst_port,
+            bytes_in,
+            bytes_out,
+            started_ms: unix_ms(started),
+            duration_ms: duration.as_millis() as u64,
+            denied: false,
+            policy_violation: false,
+        }
+    }
+
+    /// A flow refused by network policy just now.
+    pub fn denied(protocol: NetworkProtocol, dst_ip: Ipv4Addr, dst_port: u16) -> Self {
+        Self {
+            denied: true,
+            ..Self::closed(protocol, dst_ip, dst_port, 0, 0, Duration::ZERO)
+        }
+    }
+
+    fn span_event(&self) -> SpanEvent {
+        SpanEvent {
+            name: NETWORK_CONNECTION_EVENT.to_string(),
+            timestamp: UNIX_EPOCH + Duration::from_millis(self.started_ms + self.duration_ms),
+            attributes: HashMap::from([
+                ("net.protocol".to_string(), self.protocol.to_string()),
+                ("net.peer.ip".to_string(), self.dst_ip.to_string()),
+                ("net.peer.port".to_string(), self.dst_port.to_string()),
+                ("bytes_in".to_string(), self.bytes_in.to_string()),
+                ("bytes_out".to_string(), self.bytes_out.to_string()),
+                ("duration_ms".to_string(), self.duration_ms.to_string()),
+                ("denied".to_string(), self.denied.to_string()),
+                (
+                    "policy_violation".to_string(),
+                    self.policy_violation.to_string(),
+                ),
+            ]),
+        }
+    }
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+struct LogState {
+    records: VecDeque<ConnectionRecord>,
+    dropped: u64,
+    /// Records not yet attached to a `sandbox.network` span.
+    unflushed: VecDeque<ConnectionRecord>,
+}
+
+/// Bounded, queryable log of a sandbox's guest connections.
+pub struct NetworkLog {
+    capacity: usize,
+    observer: Option<Observer>,
+    state: Mutex<LogState>,
+}
+
+impl Default for NetworkLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_NETWORK_LOG_CAPACITY)
+    }
+}
+
+impl std::fmt::Debug for NetworkLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetworkLog")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .field("observer", &self.observer.is_some())
+            .finish()
+    }
+}
+
+impl NetworkLog {
+    /// A log keeping at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            observer: None,
+            state: Mutex::new(LogState {
+                records: VecDeque::new(),
+                dropped: 0,
+                unflushed: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Also attach records to spans on `observer` at each [`flush`](Self::flush).
+    pub fn with_observer(mut self, observer: Observer) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Append a record, dropping the oldest once the log is full.
+    pub fn record(&self, record: ConnectionRecord) {
+        let mut state = self.state.lock().unwrap();
+        if state.records.len() >= self.capacity {
+            state.records.pop_front();
+            state.dropped += 1;
+        }
+        if self.observer.is_some() {
+            if state.unflushed.len() >= self.capacity {
+                state.unflushed.pop_front();
+            }
+            state.unflushed.push_back(record.clone());
+        }
+        state.records.push_back(record);
+    }
+
+    /// Every retained record, oldest first.
+    pub fn records(&self) -> Vec<ConnectionRecord> {
+        self.query(|_| true)
+    }
+
+    /// Retained records matching `predicate`, oldest first.
+    pub fn query(&self, predicate: impl Fn(&ConnectionRecord) -> bool) -> Vec<ConnectionRecord> {
+        let state = self.state.lock().unwrap();
+        state
+            .records
+            .iter()
+            .filter(|r| predicate(r))
+            .cloned()
+            .collect()
+    }
+
+    /// Retained records refused by network policy.
+    pub fn denied(&self) -> Vec<ConnectionRecord> {
+        self.query(|r| r.denied)
+    }
+
+    /// Retained records to `dst_ip`, on any port.
+    pub fn to_destination(&self, dst_ip: Ipv4Addr) -> Vec<ConnectionRecord> {
+        self.query(|r| r.dst_ip == dst_ip)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records evicted because the log was full.
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    /// Attach records logged since the last flush to a `sandbox.network`
+    /// span on the observer. No-op without an observer or new records.
+    pub fn flush(&self) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        let records: Vec<ConnectionRecord> = {
+            let mut state = self.state.lock().unwrap();
+            state.unflushed.drain(..).collect()
+        };
+        let Some(first) = records.first() else {
+            return;
+        };
+
+        let tracer = observer.tracer();
+        let mut span = tracer.start_span("sandbox.network");
+        span.start_time = UNIX_EPOCH + Duration::from_millis(first.started_ms);
+        span.set_attribute("backend_type", super::backend_type());
+        span.set_attribute("net.connections", records.len().to_string());
+        span.set_attribute(
+            "net.denied",
+            records.iter().filter(|r| r.denied).count().to_string(),
+        );
+        span.events
+            .extend(records.iter().map(ConnectionRecord::span_event));
+        span.status = SpanStatus::Ok;
+        tracer.finish_span(span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(dst: [u8; 4], port: u16, bytes_in: u64) -> ConnectionRecord {
+        ConnectionRecord::closed(
+            NetworkProtocol::Tcp,
+            Ipv4Addr::from(dst),
+            port,
+            bytes_in,
+            10,
+            Duration::from_millis(250),
+        )
+    }
+
+    #[test]
+    fn test_network_log_query_and_eviction() {
+        let log = NetworkLog::new(2);
+        log.record(record([1, 1, 1, 1], 443, 100));
+        log.record(ConnectionRecord::denied(
+            NetworkProtocol::Udp,
+            Ipv4Addr::new(169, 254, 169, 254),
+            80,
+        ));
+        log.record(record([1, 1, 1, 1], 80, 5));
+
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.dropped(), 1);
+        let denied = log.denied();
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].protocol, NetworkProtocol::Udp);
+        assert_eq!((denied[0].bytes_in, denied[0].duration_ms), (0, 0));
+        let to_one = log.to_destination(Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(to_one.len(), 1);
+        assert_eq!(to_one[0].dst_port, 80);
+    }
+
+    #[test]
+    fn test_network_log_flush_records_span_events() {
+        let log = NetworkLog::default().with_observer(Observer::test());
+        log.flush();
+        log.record(record([93, 184, 216, 34], 443, 4096));
+        log.record(ConnectionRecord::denied(
+            NetworkProtocol::Tcp,
+            Ipv4Addr::new(10, 0, 0, 1),
+            22,
+        ));
+        log.flush();
+        log.flush();
+
+        let observer = log.observer.as_ref().unwrap();
+        let spans = observer.tracer().find_spans("sandbox.network");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].attributes["net.connections"], "2");
+        assert_eq!(spans[0].attributes["net.denied"], "1");
+        let events = &spans[0].events;
+        assert!(events.iter().all(|e| e.name == NETWORK_CONNECTION_EVENT));
+        assert_eq!(events[0].attributes["net.peer.ip"], "93.184.216.34");
+        assert_eq!(events[0].attributes["bytes_in"], "4096");
+        assert_eq!(events[1].attributes["denied"], "true");
+        assert_eq!(log.len(), 2);
+    }
+}
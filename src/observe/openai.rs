@@ -0,0 +1,278 @@
+//! Stream parser for OpenAI-compatible `/chat/completions` responses.
+//!
+//! With `"stream": true` the server answers with server-sent events: one
+//! `data: {json}` line per chunk, ending with `data: [DONE]`. Each chunk
+//! carries a delta for the single choice requested:
+//!
+//! - `delta.content` → appended to [`OpenAiTurn::content`]
+//! - `delta.tool_calls[]` → fragments keyed by `index`; the first fragment
+//!   of a call carries its `id` and function `name`, and every fragment may
+//!   carry a piece of the JSON `arguments` string, concatenated in order
+//! - `finish_reason` → `"tool_calls"` when the model wants tools run,
+//!   `"stop"` when it has answered
+//! - `usage` → token counts, sent in a final chunk with empty `choices`
+//!   when the request sets `stream_options.include_usage`
+//! - `error { message }` → some servers report mid-stream failures inline
+//!
+//! One response is one model turn. The host-side agent loop in
+//! [`crate::openai_agent`] folds turns into the same
+//! [`AgentExecResult`](crate::observe::claude::AgentExecResult) the
+//! claude-code and Codex parsers produce.
+
+use serde::Deserialize;
+
+/// One streamed chat-completions response, accumulated.
+#[derive(Debug, Clone, Default)]
+pub struct OpenAiTurn {
+    /// Model that answered, as the server reports it.
+    pub model: String,
+    /// Text the model wrote.
+    pub content: String,
+    /// Tool calls the model asked for, in index order.
+    pub tool_calls: Vec<OpenAiToolCall>,
+    pub finish_reason: Option<String>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Error the server reported inside the stream.
+    pub error: Option<String>,
+    /// Whether `data: [DONE]` has arrived.
+    pub done: bool,
+}
+
+/// A tool call assembled from its streamed fragments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpenAiToolCall {
+    pub id: String,
+    pub name: String,
+    /// JSON-encoded arguments, as the model wrote them.
+    pub arguments: String,
+}
+
+/// Parse a single line of the SSE stream and update the accumulator.
+///
+/// Blank lines, comments and non-`data` fields are ignored, as are data
+/// lines that fail to parse — proxies in front of these servers sometimes
+/// inject keep-alives the parser should not trip on.
+pub fn parse_openai_sse_line(line: &str, turn: &mut OpenAiTurn) {
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        return;
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        turn.done = true;
+        return;
+    }
+
+    let chunk: ChatChunk = match serde_json::from_str(data) {
+        Ok(chunk) => chunk,
+        Err(error) => {
+            tracing::debug!(
+                error = %error,
+                line = data,
+                "openai parser: skipping unparseable chunk"
+            );
+            return;
+        }
+    };
+
+    if let Some(model) = chunk.model.filter(|m| !m.is_empty()) {
+        turn.model = model;
+    }
+    if let Some(usage) = chunk.usage {
+        turn.prompt_tokens = usage.prompt_tokens;
+        turn.completion_tokens = usage.completion_tokens;
+    }
+    if let Some(error) = chunk.error {
+        turn.error = Some(error.message);
+    }
+
+    for choice in chunk.choices.unwrap_or_default() {
+        if let Some(reason) = choice.finish_reason {
+            turn.finish_reason = Some(reason);
+        }
+        let Some(delta) = choice.delta else {
+            continue;
+        };
+        if let Some(content) = delta.content {
+            turn.content.push_str(&content);
+        }
+        for fragment in delta.tool_calls.unwrap_or_default() {
+            apply_tool_call_fragment(fragment, turn);
+        }
+    }
+}
+
+/// Fold one tool-call fragment into its call. Servers that omit `index`
+/// start a new call on each new `id` and otherwise extend the last one.
+fn apply_tool_call_fragment(fragment: ToolCallDelta, turn: &mut OpenAiTurn) {
+    let index = match fragment.index {
+        Some(index) => index,
+        None => match (&fragment.id, turn.tool_calls.last()) {
+            (Some(id), Some(last)) if *id == last.id => turn.tool_calls.len() - 1,
+            (Some(_), _) | (None, None) => turn.tool_calls.len(),
+            (None, Some(_)) => turn.tool_calls.len() - 1,
+        },
+    };
+    if turn.tool_calls.len() <= index {
+        turn.tool_calls
+            .resize_with(index + 1, OpenAiToolCall::default);
+    }
+    let call = &mut turn.tool_calls[index];
+    if let Some(id) = fragment.id {
+        call.id = id;
+    }
+    if let Some(function) = fragment.function {
+        if let Some(name) = function.name {
+            call.name.push_str(&name);
+        }
+        if let Some(arguments) = function.arguments {
+            call.arguments.push_str(&arguments);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Wire types
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    model: Option<String>,
+    choices: Option<Vec<ChunkChoice>>,
+    usage: Option<ChunkUsage>,
+    error: Option<ChunkError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    delta: Option<ChunkDelta>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+    tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCallDelta {
+    index: Option<usize>,
+    id: Option<String>,
+    function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkError {
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(lines: &[&str]) -> OpenAiTurn {
+        let mut turn = OpenAiTurn::default();
+        for line in lines {
+            parse_openai_sse_line(line, &mut turn);
+        }
+        turn
+    }
+
+    #[test]
+    fn content_deltas_are_concatenated() {
+        let turn = parse_all(&[
+            r#"data: {"model":"gpt-4.1","choices":[{"index":0,"delta":{"role":"assistant","content":"Hel"}}]}"#,
+            "",
+            r#"data: {"choices":[{"index":0,"delta":{"content":"lo"},"finish_reason":null}]}"#,
+            r#"data: {"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+            "data: [DONE]",
+        ]);
+        assert_eq!(turn.model, "gpt-4.1");
+        assert_eq!(turn.content, "Hello");
+        assert_eq!(turn.finish_reason.as_deref(), Some("stop"));
+        assert!(turn.tool_calls.is_empty());
+        assert!(turn.done);
+    }
+
+    #[test]
+    fn tool_call_fragments_are_assembled_by_index() {
+        let turn = parse_all(&[
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"Bash","arguments":""}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"command\":"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"Read","arguments":"{\"file_path\":\"a\"}"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"ls\"}"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+        ]);
+        assert_eq!(
+            turn.tool_calls,
+            vec![
+                OpenAiToolCall {
+                    id: "call_a".into(),
+                    name: "Bash".into(),
+                    arguments: r#"{"command":"ls"}"#.into(),
+                },
+                OpenAiToolCall {
+                    id: "call_b".into(),
+                    name: "Read".into(),
+                    arguments: r#"{"file_path":"a"}"#.into(),
+                },
+            ]
+        );
+        assert_eq!(turn.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn fragments_without_index_follow_ids() {
+        let turn = parse_all(&[
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"id":"a","function":{"name":"Bash","arguments":"{}"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"id":"b","function":{"name":"Read","arguments":"{"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"function":{"arguments":"}"}}]}}]}"#,
+        ]);
+        assert_eq!(turn.tool_calls.len(), 2);
+        assert_eq!(turn.tool_calls[1].arguments, "{}");
+    }
+
+    #[test]
+    fn usage_chunk_sets_token_counts() {
+        let turn = parse_all(&[
+            r#"data: {"choices":[],"usage":{"prompt_tokens":120,"completion_tokens":30,"total_tokens":150}}"#,
+        ]);
+        assert_eq!(turn.prompt_tokens, 120);
+        assert_eq!(turn.completion_tokens, 30);
+    }
+
+    #[test]
+    fn inline_error_is_recorded() {
+        let turn =
+            parse_all(&[r#"data: {"error":{"message":"model overloaded","type":"server_error"}}"#]);
+        assert_eq!(turn.error.as_deref(), Some("model overloaded"));
+    }
+
+    #[test]
+    fn non_data_and_malformed_lines_are_skipped() {
+        let turn = parse_all(&[
+            ": keep-alive",
+            "event: message",
+            "data: { not json",
+            r#"data: {"choices":null,"tool_calls":null}"#,
+        ]);
+        assert!(turn.content.is_empty());
+        assert!(turn.tool_calls.is_empty());
+        assert!(!turn.done);
+    }
+}
@@ -0,0 +1,671 @@
+//! Persisting observed runs.
+//!
+//! [`ObservedResult::persist`](super::ObservedResult::persist) flattens a run
+//! into a [`RunRecord`] — its outcome, spans, metrics and tool calls, plus
+//! the [`RunManifest`] when there is one — and hands it to a
+//! [`ResultSink`]. Teams can then query how their agents performed over time
+//! without running an OpenTelemetry collector.
+//!
+//! Built-in sinks:
+//! - [`JsonDirSink`]: one `<run_id>.json` file per run.
+//! - [`JsonlSink`]: one line per run appended to a single file.
+//! - `SqliteSink` (feature `sqlite`): `runs`, `spans`, `metrics` and
+//!   `tool_calls` tables in an SQLite database.
+//!
+//! ```no_run
+//! use void_box::observe::persist::JsonlSink;
+//! use void_box::observe::ObserveConfig;
+//! use void_box::sandbox::Sandbox;
+//! use void_box::workflow::{Workflow, WorkflowExt};
+//!
+//! # async fn run() -> void_box::Result<()> {
+//! let workflow = Workflow::define("nightly")
+//!     .step("test", |ctx| async move { ctx.exec("make", &["test"]).await })
+//!     .build();
+//! let observed = workflow
+//!     .observe(ObserveConfig::default())
+//!     .run_in(Sandbox::mock().build()?)
+//!     .await?;
+//! observed.persist(&JsonlSink::new("runs.jsonl")).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::metrics::{MetricValue, MetricsSnapshot};
+use super::provenance::RunManifest;
+use super::tracer::{Span, SpanStatus};
+use crate::agent_box::ChatTurn;
+use crate::pipeline::PipelineResult;
+use crate::workflow::WorkflowResult;
+use crate::Result;
+
+/// Summary of a run's result, stored next to its telemetry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunOutcome {
+    pub success: bool,
+    /// The run's final output as text.
+    pub output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+}
+
+/// Results that can be persisted: they summarize into a [`RunOutcome`].
+pub trait Outcome {
+    fn outcome(&self) -> RunOutcome;
+}
+
+impl Outcome for WorkflowResult {
+    fn outcome(&self) -> RunOutcome {
+        RunOutcome {
+            success: self.success(),
+            output: self.output_str(),
+            ..RunOutcome::default()
+        }
+    }
+}
+
+impl Outcome for PipelineResult {
+    fn outcome(&self) -> RunOutcome {
+        RunOutcome {
+            success: self.success(),
+            output: self.output.clone(),
+            cost_usd: Some(self.total_cost_usd()),
+            input_tokens: Some(self.total_input_tokens()),
+            output_tokens: Some(self.total_output_tokens()),
+        }
+    }
+}
+
+impl Outcome for ChatTurn {
+    fn outcome(&self) -> RunOutcome {
+        RunOutcome {
+            success: !self.result.is_error,
+            output: self.result.result_text.clone(),
+            cost_usd: Some(self.result.total_cost_usd),
+            input_tokens: Some(self.result.input_tokens),
+            output_tokens: Some(self.result.output_tokens),
+        }
+    }
+}
+
+/// One persisted run; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// The manifest's run id, or a fresh one for runs without a manifest.
+    pub run_id: String,
+    pub name: String,
+    /// `workflow`, `pipeline` or `chat`, when the run has a manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// RFC 3339.
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub outcome: RunOutcome,
+    pub spans: Vec<SpanRecord>,
+    pub metrics: Vec<MetricRecord>,
+    pub tool_calls: Vec<ToolCallRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<RunManifest>,
+}
+
+/// A finished span of a persisted run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpanRecord {
+    pub trace_id: String,
+    pub span_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub start_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /// `unset`, `ok` or `error`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// A metric of a persisted run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricRecord {
+    pub name: String,
+    /// `counter`, `gauge` or `histogram`.
+    pub kind: String,
+    /// The counter or gauge value, or the histogram's sum.
+    pub value: f64,
+    /// The histogram's observation count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    pub labels: BTreeMap<String, String>,
+}
+
+/// An agent tool call of a persisted run, taken from its `tool.name` span.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub span_id: String,
+    pub tool_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub start_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Tool input as recorded on the span (truncated to 2000 bytes).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    /// Tool output as recorded on the span (truncated to 2000 bytes).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl RunRecord {
+    pub(crate) fn new(
+        outcome: RunOutcome,
+        traces: &[Span],
+        metrics: &MetricsSnapshot,
+        manifest: Option<&RunManifest>,
+    ) -> Self {
+        let spans: Vec<SpanRecord> = traces.iter().map(SpanRecord::from_span).collect();
+        let tool_calls = traces
+            .iter()
+            .filter_map(ToolCallRecord::from_span)
+            .collect();
+        let mut metrics: Vec<MetricRecord> = metrics
+            .metrics
+            .values()
+            .map(|metric| {
+                let (kind, value, count) = match &metric.value {
+                    MetricValue::Counter(v) => ("counter", *v, None),
+                    MetricValue::Gauge(v) => ("gauge", *v, None),
+                    MetricValue::Histogram(h) => ("histogram", h.sum, Some(h.count)),
+                };
+                MetricRecord {
+                    name: metric.name.clone(),
+                    kind: kind.to_string(),
+                    value,
+                    count,
+                    labels: metric.labels.clone().into_iter().collect(),
+                }
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let (run_id, name, kind, started_at, duration_ms) = match manifest {
+            Some(m) => (
+                m.run_id.clone(),
+                m.name.clone(),
+                serde_json::to_value(m.kind)
+                    .ok()
+                    .and_then(|kind| kind.as_str().map(str::to_string)),
+                m.started_at.clone(),
+                m.duration_ms,
+            ),
+            None => {
+                // Without a manifest, the root span names the run and the
+                // spans bound its duration.
+                let start = traces.iter().map(|s| s.start_time).min();
+                let end = traces
+                    .iter()
+                    .map(|s| s.start_time + s.duration.unwrap_or_default())
+                    .max();
+                let root = traces.iter().find(|s| s.context.parent_span_id.is_none());
+                let duration = match (start, end) {
+                    (Some(start), Some(end)) => end.duration_since(start).unwrap_or_default(),
+                    _ => Duration::ZERO,
+                };
+                (
+                    uuid::Uuid::now_v7().to_string(),
+                    root.map(|s| s.name.clone()).unwrap_or_default(),
+                    None,
+                    humantime::format_rfc3339_millis(start.unwrap_or_else(SystemTime::now))
+                        .to_string(),
+                    duration.as_millis() as u64,
+                )
+            }
+        };
+
+        Self {
+            run_id,
+            name,
+            kind,
+            started_at,
+            duration_ms,
+            outcome,
+            spans,
+            metrics,
+            tool_calls,
+            manifest: manifest.cloned(),
+        }
+    }
+}
+
+impl SpanRecord {
+    fn from_span(span: &Span) -> Self {
+        let (status, error) = match &span.status {
+            SpanStatus::Unset => ("unset", None),
+            SpanStatus::Ok => ("ok", None),
+            SpanStatus::Error(message) => ("error", Some(message.clone())),
+        };
+        Self {
+            trace_id: span.context.trace_id.clone(),
+            span_id: span.context.span_id.clone(),
+            parent_span_id: span.context.parent_span_id.clone(),
+            name: span.name.clone(),
+            start_ms: unix_ms(span.start_time),
+            duration_ms: span.duration.map(|d| d.as_secs_f64() * 1000.0),
+            status: status.to_string(),
+            error,
+            attributes: span.attributes.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl ToolCallRecord {
+    fn from_span(span: &Span) -> Option<Self> {
+        let tool_name = span.attributes.get("tool.name")?.clone();
+        let error = match &span.status {
+            SpanStatus::Error(message) => Some(message.clone()),
+            _ => None,
+        };
+        Some(Self {
+            span_id: span.context.span_id.clone(),
+            tool_name,
+            tool_use_id: span.attributes.get("tool.use_id").cloned(),
+            start_ms: unix_ms(span.start_time),
+            duration_ms: span.duration.map(|d| d.as_secs_f64() * 1000.0),
+            error,
+            input: span.attributes.get("tool.input").cloned(),
+            output: span.attributes.get("tool.output").cloned(),
+        })
+    }
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Destination for [`RunRecord`]s.
+#[async_trait::async_trait]
+pub trait ResultSink: Send + Sync + fmt::Debug {
+    /// Store one run. Storing a run again replaces it where the sink can.
+    async fn store(&self, record: &RunRecord) -> Result<()>;
+}
+
+/// Sink that writes each run as `<run_id>.json` under a directory, creating
+/// it if needed.
+#[derive(Debug, Clone)]
+pub struct JsonDirSink {
+    dir: PathBuf,
+}
+
+impl JsonDirSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Where `record` is written.
+    pub fn path_for(&self, record: &RunRecord) -> PathBuf {
+        self.dir.join(format!("{}.json", record.run_id))
+    }
+}
+
+#[async_trait::async_trait]
+impl ResultSink for JsonDirSink {
+    async fn store(&self, record: &RunRecord) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let body = serde_json::to_vec_pretty(record)?;
+        tokio::fs::write(self.path_for(record), body).await?;
+        Ok(())
+    }
+}
+
+/// Sink that appends each run as one JSON line to a file, creating it (and
+/// its directory) if needed.
+#[derive(Debug)]
+pub struct JsonlSink {
+    path: PathBuf,
+    /// Keeps concurrent runs of this process from interleaving lines.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl JsonlSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Read back every run stored in the file at `path`.
+    pub fn read_all(path: impl AsRef<std::path::Path>) -> Result<Vec<RunRecord>> {
+        let body = std::fs::read_to_string(path)?;
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl ResultSink for JsonlSink {
+    async fn store(&self, record: &RunRecord) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let _guard = self.lock.lock().await;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSink;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use rusqlite::{params, Connection};
+
+    use super::{ResultSink, RunRecord};
+    use crate::{Error, Result};
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS runs (
+            run_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            kind TEXT,
+            started_at TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            success INTEGER NOT NULL,
+            output TEXT NOT NULL,
+            cost_usd REAL,
+            input_tokens INTEGER,
+            output_tokens INTEGER,
+            manifest TEXT
+        );
+        CREATE TABLE IF NOT EXISTS spans (
+            run_id TEXT NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+            trace_id TEXT NOT NULL,
+            span_id TEXT NOT NULL,
+            parent_span_id TEXT,
+            name TEXT NOT NULL,
+            start_ms INTEGER NOT NULL,
+            duration_ms REAL,
+            status TEXT NOT NULL,
+            error TEXT,
+            attributes TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS spans_run_id ON spans(run_id);
+        CREATE TABLE IF NOT EXISTS metrics (
+            run_id TEXT NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            value REAL NOT NULL,
+            count INTEGER,
+            labels TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS metrics_run_id ON metrics(run_id);
+        CREATE TABLE IF NOT EXISTS tool_calls (
+            run_id TEXT NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+            span_id TEXT NOT NULL,
+            tool_name TEXT NOT NULL,
+            tool_use_id TEXT,
+            start_ms INTEGER NOT NULL,
+            duration_ms REAL,
+            error TEXT,
+            input TEXT,
+            output TEXT
+        );
+        CREATE INDEX IF NOT EXISTS tool_calls_run_id ON tool_calls(run_id);
+    ";
+
+    /// Sink that stores runs in an SQLite database with `runs`, `spans`,
+    /// `metrics` and `tool_calls` tables keyed by `run_id`.
+    #[derive(Debug, Clone)]
+    pub struct SqliteSink {
+        conn: Arc<Mutex<Connection>>,
+    }
+
+    impl SqliteSink {
+        /// Open (or create) the database at `path` and create the tables.
+        pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+            let conn = Connection::open(path).map_err(sqlite_error)?;
+            conn.execute_batch("PRAGMA foreign_keys = ON;")
+                .and_then(|()| conn.execute_batch(SCHEMA))
+                .map_err(sqlite_error)?;
+            Ok(Self {
+                conn: Arc::new(Mutex::new(conn)),
+            })
+        }
+    }
+
+    fn sqlite_error(e: rusqlite::Error) -> Error {
+        Error::Observe(format!("sqlite: {}", e))
+    }
+
+    fn insert(conn: &mut Connection, record: &RunRecord) -> Result<()> {
+        let manifest = record
+            .manifest
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        // Replacing the run cascades to its old spans, metrics and tool calls.
+        tx.execute(
+            "INSERT OR REPLACE INTO runs (run_id, name, kind, started_at, duration_ms, success, \
+             output, cost_usd, input_tokens, output_tokens, manifest) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                record.run_id,
+                record.name,
+                record.kind,
+                record.started_at,
+                record.duration_ms as i64,
+                record.outcome.success,
+                record.outcome.output,
+                record.outcome.cost_usd,
+                record.outcome.input_tokens.map(|n| n as i64),
+                record.outcome.output_tokens.map(|n| n as i64),
+                manifest,
+            ],
+        )
+        .map_err(sqlite_error)?;
+        for span in &record.spans {
+            tx.execute(
+                "INSERT INTO spans (run_id, trace_id, span_id, parent_span_id, name, start_ms, \
+                 duration_ms, status, error, attributes) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    record.run_id,
+                    span.trace_id,
+                    span.span_id,
+                    span.parent_span_id,
+                    span.name,
+                    span.start_ms as i64,
+                    span.duration_ms,
+                    span.status,
+                    span.error,
+                    serde_json::to_string(&span.attributes)?,
+                ],
+            )
+            .map_err(sqlite_error)?;
+        }
+        for metric in &record.metrics {
+            tx.execute(
+                "INSERT INTO metrics (run_id, name, kind, value, count, labels) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    record.run_id,
+                    metric.name,
+                    metric.kind,
+                    metric.value,
+                    metric.count.map(|n| n as i64),
+                    serde_json::to_string(&metric.labels)?,
+                ],
+            )
+            .map_err(sqlite_error)?;
+        }
+        for call in &record.tool_calls {
+            tx.execute(
+                "INSERT INTO tool_calls (run_id, span_id, tool_name, tool_use_id, start_ms, \
+                 duration_ms, error, input, output) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    record.run_id,
+                    call.span_id,
+                    call.tool_name,
+                    call.tool_use_id,
+                    call.start_ms as i64,
+                    call.duration_ms,
+                    call.error,
+                    call.input,
+                    call.output,
+                ],
+            )
+            .map_err(sqlite_error)?;
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    #[async_trait::async_trait]
+    impl ResultSink for SqliteSink {
+        async fn store(&self, record: &RunRecord) -> Result<()> {
+            let conn = self.conn.clone();
+            let record = record.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut conn = conn
+                    .lock()
+                    .map_err(|_| Error::Observe("sqlite: connection lock poisoned".into()))?;
+                insert(&mut conn, &record)
+            })
+            .await
+            .map_err(|e| Error::Observe(format!("sqlite: {}", e)))?
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::tests::sample_record;
+        use super::*;
+
+        #[tokio::test]
+        async fn test_sqlite_sink_stores_and_replaces_runs() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("runs.db");
+            let sink = SqliteSink::open(&path).unwrap();
+            let record = sample_record();
+            sink.store(&record).await.unwrap();
+            sink.store(&record).await.unwrap();
+
+            let conn = Connection::open(&path).unwrap();
+            let count = |table: &str| -> i64 {
+                conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+            };
+            assert_eq!(count("runs"), 1);
+            assert_eq!(count("spans"), record.spans.len() as i64);
+            assert_eq!(count("metrics"), record.metrics.len() as i64);
+            let tool: String = conn
+                .query_row("SELECT tool_name FROM tool_calls", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(tool, "Bash");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::Observer;
+
+    pub(super) fn sample_record() -> RunRecord {
+        let observer = Observer::test();
+        {
+            let workflow = observer.start_workflow_span("persisted");
+            let ctx = workflow.context();
+            let step = observer.start_step_span("step1", Some(&ctx));
+            step.set_ok();
+        }
+        let mut tool = Span::new("claude.tool.Bash");
+        tool.set_attribute("tool.name", "Bash");
+        tool.set_attribute("tool.input", r#"{"command":"ls"}"#);
+        tool.end();
+        let mut traces = observer.get_traces();
+        traces.push(tool);
+        observer.metrics().increment_counter("runs", &[]);
+
+        let outcome = RunOutcome {
+            success: true,
+            output: "done".into(),
+            ..RunOutcome::default()
+        };
+        RunRecord::new(outcome, &traces, &observer.get_metrics(), None)
+    }
+
+    #[test]
+    fn test_run_record_without_manifest() {
+        let record = sample_record();
+        assert_eq!(record.name, "workflow:persisted");
+        assert!(record.kind.is_none());
+        assert_eq!(record.spans.len(), 3);
+        assert_eq!(record.tool_calls.len(), 1);
+        assert_eq!(
+            record.tool_calls[0].input.as_deref(),
+            Some(r#"{"command":"ls"}"#)
+        );
+        assert!(record.metrics.iter().any(|m| m.name == "runs"));
+    }
+
+    #[tokio::test]
+    async fn test_json_sinks_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = sample_record();
+
+        let json = JsonDirSink::new(dir.path().join("runs"));
+        json.store(&record).await.unwrap();
+        let written: RunRecord =
+            serde_json::from_slice(&std::fs::read(json.path_for(&record)).unwrap()).unwrap();
+        assert_eq!(written.run_id, record.run_id);
+        assert_eq!(written.spans.len(), record.spans.len());
+        assert_eq!(written.tool_calls[0].tool_name, "Bash");
+
+        let path = dir.path().join("runs.jsonl");
+        let jsonl = JsonlSink::new(&path);
+        jsonl.store(&record).await.unwrap();
+        jsonl.store(&record).await.unwrap();
+        let lines = JsonlSink::read_all(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.run_id == record.run_id));
+    }
+}
@@ -0,0 +1,311 @@
+//! Token pricing for agent runs.
+//!
+//! claude-code reports a run's cost in its final event, but not every
+//! provider or CLI version does: OpenAI-compatible servers report none, and
+//! a run cut short never reaches the final event. A [`PricingTable`] holds
+//! per-model token rates so [`AgentExecResult::fill_missing_cost`] can price
+//! the tokens instead, and [`AgentExecResult::cost_breakdown`] splits a
+//! run's cost per turn and per tool call.
+//!
+//! ```
+//! use void_box::observe::pricing::{ModelPricing, PricingTable};
+//!
+//! // Anthropic list prices plus a self-hosted model at a flat rate.
+//! let pricing = PricingTable::anthropic().model("qwen", ModelPricing::new(0.2, 0.6));
+//! assert!(pricing.pricing_for("qwen2.5-coder:32b").is_some());
+//! assert!(pricing.pricing_for("claude-sonnet-4-5").is_some());
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use super::claude::{AgentExecResult, TokenUsage};
+
+/// Token rates of one model, in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_usd_per_mtok: f64,
+    pub output_usd_per_mtok: f64,
+    /// Input tokens written to the prompt cache.
+    pub cache_write_usd_per_mtok: f64,
+    /// Input tokens read from the prompt cache.
+    pub cache_read_usd_per_mtok: f64,
+}
+
+impl ModelPricing {
+    /// Rates for input and output tokens. Cache writes and reads are priced
+    /// at 1.25x and 0.1x the input rate, as Anthropic prices them; override
+    /// them with [`cache_rates`](Self::cache_rates).
+    pub fn new(input_usd_per_mtok: f64, output_usd_per_mtok: f64) -> Self {
+        Self {
+            input_usd_per_mtok,
+            output_usd_per_mtok,
+            cache_write_usd_per_mtok: input_usd_per_mtok * 1.25,
+            cache_read_usd_per_mtok: input_usd_per_mtok * 0.1,
+        }
+    }
+
+    pub fn cache_rates(mut self, write_usd_per_mtok: f64, read_usd_per_mtok: f64) -> Self {
+        self.cache_write_usd_per_mtok = write_usd_per_mtok;
+        self.cache_read_usd_per_mtok = read_usd_per_mtok;
+        self
+    }
+
+    /// Cost of `usage` in USD.
+    pub fn cost_usd(&self, usage: &TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input_usd_per_mtok
+            + usage.output_tokens as f64 * self.output_usd_per_mtok
+            + usage.cache_creation_input_tokens as f64 * self.cache_write_usd_per_mtok
+            + usage.cache_read_input_tokens as f64 * self.cache_read_usd_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// Token rates by model name pattern.
+///
+/// A model is priced by the longest pattern its name contains, ignoring
+/// case, so `"opus-4-5"` wins over `"opus"` for `claude-opus-4-5`.
+/// Serializes as a map of pattern to [`ModelPricing`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PricingTable {
+    models: BTreeMap<String, ModelPricing>,
+}
+
+impl PricingTable {
+    /// Anthropic's list prices for the Claude model families.
+    pub fn anthropic() -> Self {
+        let opus_4_5 = ModelPricing::new(5.0, 25.0);
+        Self::default()
+            .model("opus", ModelPricing::new(15.0, 75.0))
+            .model("opus-4-5", opus_4_5)
+            .model("opus-4.5", opus_4_5)
+            .model("sonnet", ModelPricing::new(3.0, 15.0))
+            .model("haiku", ModelPricing::new(1.0, 5.0))
+    }
+
+    /// Price models whose name contains `pattern` at `pricing`, replacing
+    /// any earlier rates for the same pattern.
+    pub fn model(mut self, pattern: impl Into<String>, pricing: ModelPricing) -> Self {
+        self.models
+            .insert(pattern.into().to_ascii_lowercase(), pricing);
+        self
+    }
+
+    /// Rates for `model`, if any pattern matches it.
+    pub fn pricing_for(&self, model: &str) -> Option<&ModelPricing> {
+        let model = model.to_ascii_lowercase();
+        self.models
+            .iter()
+            .filter(|(pattern, _)| model.contains(pattern.as_str()))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, pricing)| pricing)
+    }
+
+    /// Cost of `usage` on `model` in USD, if the model has a price.
+    pub fn cost_usd(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        self.pricing_for(model)
+            .map(|pricing| pricing.cost_usd(usage))
+    }
+
+    /// The [`anthropic`](Self::anthropic) table, built once.
+    pub(crate) fn anthropic_list_prices() -> &'static Self {
+        static LIST_PRICES: OnceLock<PricingTable> = OnceLock::new();
+        LIST_PRICES.get_or_init(Self::anthropic)
+    }
+}
+
+/// A run's cost split per turn and per tool call; see
+/// [`AgentExecResult::cost_breakdown`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    /// One entry per API message, in order.
+    pub turns: Vec<TurnCost>,
+    /// One entry per tool call, in order.
+    pub tool_calls: Vec<ToolCallCost>,
+    /// Sum of the priced turns in USD.
+    pub total_usd: f64,
+}
+
+/// Cost of one API message.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnCost {
+    pub message_id: String,
+    pub model: String,
+    /// The `Task` tool call whose subagent sent the message, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_tool_use_id: Option<String>,
+    pub usage: TokenUsage,
+    /// `None` when the table has no price for the model.
+    pub cost_usd: Option<f64>,
+}
+
+/// Cost attributed to one tool call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallCost {
+    pub tool_name: String,
+    pub tool_use_id: String,
+    pub cost_usd: f64,
+}
+
+impl AgentExecResult {
+    /// Price the run's tokens with `pricing` when claude-code reported no
+    /// cost. Returns whether [`total_cost_usd`](Self::total_cost_usd) was
+    /// set; a run whose model has no price keeps a zero cost.
+    pub fn fill_missing_cost(&mut self, pricing: &PricingTable) -> bool {
+        if self.total_cost_usd > 0.0 {
+            return false;
+        }
+        let cost = if self.messages.is_empty() {
+            // Runners that report only totals (Codex, OpenAI-compatible).
+            let usage = TokenUsage {
+                input_tokens: self.input_tokens,
+                output_tokens: self.output_tokens,
+                cache_creation_input_tokens: self.cache_creation_input_tokens,
+                cache_read_input_tokens: self.cache_read_input_tokens,
+            };
+            pricing.cost_usd(&self.model, &usage)
+        } else {
+            let breakdown = self.cost_breakdown(pricing);
+            breakdown
+                .turns
+                .iter()
+                .any(|turn| turn.cost_usd.is_some())
+                .then_some(breakdown.total_usd)
+        };
+        match cost {
+            Some(cost) if cost > 0.0 => {
+                self.total_cost_usd = cost;
+                self.cost_estimated = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Price each turn with `pricing`, and attribute the cost to tool calls
+    /// the way [`tool_usage`](Self::tool_usage) attributes tokens: a call
+    /// gets its share of the output cost of the message that made it, and a
+    /// `Task` call also gets every turn of its subagent.
+    ///
+    /// Turns that name no model are priced as the session's model.
+    pub fn cost_breakdown(&self, pricing: &PricingTable) -> CostBreakdown {
+        let turns: Vec<TurnCost> = self
+            .messages
+            .iter()
+            .map(|message| {
+                let model = if message.model.is_empty() {
+                    &self.model
+                } else {
+                    &message.model
+                };
+                TurnCost {
+                    message_id: message.message_id.clone(),
+                    model: model.clone(),
+                    parent_tool_use_id: message.parent_tool_use_id.clone(),
+                    usage: message.usage,
+                    cost_usd: pricing.cost_usd(model, &message.usage),
+                }
+            })
+            .collect();
+
+        let mut calls_per_message: HashMap<&str, u64> = HashMap::new();
+        for call in &self.tool_calls {
+            if let Some(id) = call.message_id.as_deref() {
+                *calls_per_message.entry(id).or_default() += 1;
+            }
+        }
+        let tool_calls = self
+            .tool_calls
+            .iter()
+            .map(|call| {
+                let mut cost_usd = 0.0;
+                if let Some(id) = call.message_id.as_deref() {
+                    if let Some(turn) = turns.iter().find(|t| t.message_id == id) {
+                        let output = TokenUsage {
+                            output_tokens: turn.usage.output_tokens,
+                            ..Default::default()
+                        };
+                        cost_usd += pricing.cost_usd(&turn.model, &output).unwrap_or(0.0)
+                            / calls_per_message[id] as f64;
+                    }
+                }
+                cost_usd += turns
+                    .iter()
+                    .filter(|t| t.parent_tool_use_id.as_deref() == Some(&call.tool_use_id))
+                    .filter_map(|t| t.cost_usd)
+                    .sum::<f64>();
+                ToolCallCost {
+                    tool_name: call.tool_name.clone(),
+                    tool_use_id: call.tool_use_id.clone(),
+                    cost_usd,
+                }
+            })
+            .collect();
+
+        CostBreakdown {
+            total_usd: turns.iter().filter_map(|t| t.cost_usd).sum(),
+            turns,
+            tool_calls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::claude::parse_stream_json;
+
+    #[test]
+    fn test_pricing_prefers_longest_pattern() {
+        let table = PricingTable::anthropic();
+        let opus = table.pricing_for("claude-opus-4-1").unwrap();
+        assert_eq!(opus.output_usd_per_mtok, 75.0);
+        let opus_4_5 = table.pricing_for("Claude-Opus-4-5-20251101").unwrap();
+        assert_eq!(opus_4_5.output_usd_per_mtok, 25.0);
+        assert!(table.pricing_for("gpt-4o").is_none());
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_creation_input_tokens: 0,
+            cache_read_input_tokens: 1_000_000,
+        };
+        // $3 input + $1.50 output + $0.30 cache reads.
+        let cost = table.cost_usd("claude-sonnet-4-5", &usage).unwrap();
+        assert!((cost - 4.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fill_missing_cost_and_breakdown() {
+        let jsonl = r#"{"type":"system","subtype":"init","session_id":"s1","model":"claude-sonnet-4-5"}
+{"type":"assistant","message":{"id":"msg_1","model":"claude-sonnet-4-5","content":[{"type":"tool_use","id":"toolu_task","name":"Task","input":{"subagent_type":"explore"}},{"type":"tool_use","id":"toolu_ls","name":"Bash","input":{"command":"ls"}}],"usage":{"input_tokens":1000,"output_tokens":2000}}}
+{"type":"assistant","parent_tool_use_id":"toolu_task","message":{"id":"msg_2","model":"claude-haiku-4-5","content":[],"usage":{"input_tokens":4000,"output_tokens":1000}}}
+{"type":"assistant","message":{"id":"msg_3","model":"local-model","content":[],"usage":{"input_tokens":10,"output_tokens":10}}}"#;
+        let mut result = parse_stream_json(jsonl.as_bytes());
+        let pricing = PricingTable::anthropic();
+
+        let breakdown = result.cost_breakdown(&pricing);
+        assert_eq!(breakdown.turns.len(), 3);
+        // $0.003 input + $0.03 output on sonnet.
+        assert!((breakdown.turns[0].cost_usd.unwrap() - 0.033).abs() < 1e-9);
+        // $0.004 input + $0.005 output on haiku.
+        assert!((breakdown.turns[1].cost_usd.unwrap() - 0.009).abs() < 1e-9);
+        assert_eq!(breakdown.turns[2].cost_usd, None);
+        assert!((breakdown.total_usd - 0.042).abs() < 1e-9);
+        // Each call of msg_1 gets half its $0.03 output; Task adds the
+        // subagent's turn.
+        assert_eq!(breakdown.tool_calls[0].tool_name, "Task");
+        assert!((breakdown.tool_calls[0].cost_usd - 0.024).abs() < 1e-9);
+        assert!((breakdown.tool_calls[1].cost_usd - 0.015).abs() < 1e-9);
+
+        assert!(result.fill_missing_cost(&pricing));
+        assert!(result.cost_estimated);
+        assert!((result.total_cost_usd - 0.042).abs() < 1e-9);
+        // A reported cost is kept.
+        assert!(!result.fill_missing_cost(&PricingTable::default()));
+        assert!((result.total_cost_usd - 0.042).abs() < 1e-9);
+    }
+}
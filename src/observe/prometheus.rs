@@ -0,0 +1,241 @@
+//! Embedded Prometheus scrape endpoint.
+//!
+//! Setting [`ObserveConfig::prometheus_listen`](super::ObserveConfig::prometheus_listen)
+//! makes every [`Observer`](super::Observer) and sandbox built from that
+//! config register its [`MetricsCollector`] here. One HTTP listener is
+//! started per distinct address and serves the merged metrics of all live
+//! collectors on `GET /metrics`. Collectors are held weakly, so a dropped
+//! observer or sandbox disappears from the next scrape.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http::{Method, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+use super::metrics::{render_prometheus_text, MetricsCollector};
+use crate::{Error, Result};
+
+/// Path the exporter serves metrics on.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Content type of the Prometheus text exposition format.
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+struct Source {
+    collector: Weak<MetricsCollector>,
+    labels: Vec<(String, String)>,
+}
+
+/// A running `/metrics` listener and the collectors it exposes.
+pub struct PrometheusExporter {
+    local_addr: SocketAddr,
+    sources: Mutex<Vec<Source>>,
+}
+
+impl PrometheusExporter {
+    /// The address the listener is bound to (useful with port `0`).
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Expose `collector` on this endpoint, adding `labels` to every series
+    /// it reports.
+    pub fn register(&self, collector: &Arc<MetricsCollector>, labels: &[(&str, &str)]) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.retain(|s| s.collector.strong_count() > 0);
+        sources.push(Source {
+            collector: Arc::downgrade(collector),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        });
+    }
+
+    /// Render the current scrape body.
+    pub fn render(&self) -> String {
+        let snapshots: Vec<_> = {
+            let mut sources = self.sources.lock().unwrap();
+            sources.retain(|s| s.collector.strong_count() > 0);
+            sources
+                .iter()
+                .filter_map(|s| Some((s.collector.upgrade()?.snapshot(), s.labels.clone())))
+                .collect()
+        };
+        render_prometheus_text(snapshots.iter().flat_map(|(snapshot, labels)| {
+            snapshot
+                .metrics
+                .values()
+                .map(move |m| (m, labels.as_slice()))
+        }))
+    }
+}
+
+fn exporters() -> &'static Mutex<HashMap<String, Arc<PrometheusExporter>>> {
+    static EXPORTERS: OnceLock<Mutex<HashMap<String, Arc<PrometheusExporter>>>> = OnceLock::new();
+    EXPORTERS.get_or_init(Default::default)
+}
+
+/// Return the exporter for `listen`, binding it on first use.
+///
+/// The listener runs on its own thread and lives for the rest of the
+/// process, so it keeps serving regardless of which runtime (if any) the
+/// caller is on.
+pub fn exporter(listen: &str) -> Result<Arc<PrometheusExporter>> {
+    let mut exporters = exporters().lock().unwrap();
+    if let Some(exporter) = exporters.get(listen) {
+        return Ok(exporter.clone());
+    }
+
+    let listener = std::net::TcpListener::bind(listen).map_err(|e| {
+        Error::Network(format!(
+            "Failed to bind Prometheus exporter on {}: {}",
+            listen, e
+        ))
+    })?;
+    listener.set_nonblocking(true)?;
+    let exporter = Arc::new(PrometheusExporter {
+        local_addr: listener.local_addr()?,
+        sources: Mutex::new(Vec::new()),
+    });
+
+    let serving = exporter.clone();
+    std::thread::Builder::new()
+        .name("prometheus-exporter".into())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    warn!("Prometheus exporter runtime failed to start: {}", e);
+                    return;
+                }
+            };
+            runtime.block_on(serve(listener, serving));
+        })?;
+
+    exporters.insert(listen.to_string(), exporter.clone());
+    Ok(exporter)
+}
+
+/// Register `collector` with the exporter on `listen`, logging instead of
+/// failing when the listener can't be bound so metrics never block a run.
+pub(crate) fn register_or_warn(
+    listen: &str,
+    collector: &Arc<MetricsCollector>,
+    labels: &[(&str, &str)],
+) {
+    match exporter(listen) {
+        Ok(exporter) => exporter.register(collector, labels),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+async fn serve(listener: std::net::TcpListener, exporter: Arc<PrometheusExporter>) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Prometheus exporter listener failed: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                debug!("Prometheus exporter accept failed: {}", e);
+                continue;
+            }
+        };
+        let exporter = exporter.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let exporter = exporter.clone();
+                async move { Ok::<_, Infallible>(respond(&exporter, &req)) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Prometheus exporter connection ended: {}", e);
+            }
+        });
+    }
+}
+
+fn respond(exporter: &PrometheusExporter, req: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let (status, content_type, body) =
+        if req.method() == Method::GET && req.uri().path() == METRICS_PATH {
+            (StatusCode::OK, TEXT_FORMAT, exporter.render())
+        } else {
+            (
+                StatusCode::NOT_FOUND,
+                "text/plain",
+                "not found\n".to_string(),
+            )
+        };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(body)))
+        .expect("static response parts are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::MetricsConfig;
+    use std::io::{Read, Write};
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_exporter_serves_registered_collectors() {
+        let exporter = exporter("127.0.0.1:0").unwrap();
+        let kept = Arc::new(MetricsCollector::new(MetricsConfig::in_memory()));
+        kept.increment_counter("scrape_test_execs", &[]);
+        exporter.register(&kept, &[("sandbox", "kept")]);
+        let dropped = Arc::new(MetricsCollector::new(MetricsConfig::in_memory()));
+        dropped.increment_counter("scrape_test_execs", &[]);
+        exporter.register(&dropped, &[("sandbox", "dropped")]);
+        drop(dropped);
+
+        let response = get(exporter.local_addr(), METRICS_PATH);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(response.contains("scrape_test_execs{sandbox=\"kept\"} 1"));
+        assert!(!response.contains("sandbox=\"dropped\""));
+
+        assert!(get(exporter.local_addr(), "/").starts_with("HTTP/1.1 404"));
+        assert!(Arc::ptr_eq(
+            &exporter,
+            &super::exporter("127.0.0.1:0").unwrap()
+        ));
+    }
+}
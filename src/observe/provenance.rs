@@ -0,0 +1,311 @@
+//! Provenance records for observed runs.
+//!
+//! Every observed workflow, pipeline and chat turn returns a
+//! [`RunManifest`] on its [`ObservedResult`](super::ObservedResult): what
+//! ran (the sandbox images and configuration as a
+//! [`SandboxManifest`], the skills and prompts), which void-box and
+//! protocol versions ran it, and how long each step took. Secret values
+//! never appear in it; env values containing one are redacted.
+//!
+//! Manifests export as JSON, and with
+//! [`ObserveConfig::sign_manifests`](super::ObserveConfig::sign_manifests)
+//! carry an Ed25519 signature over their content, so an audit trail of
+//! autonomous agent actions can be checked against the key that produced
+//! it.
+//!
+//! ```no_run
+//! use void_box::observe::provenance::ManifestSigningKey;
+//! use void_box::observe::ObserveConfig;
+//!
+//! # fn demo() -> void_box::Result<()> {
+//! let pkcs8 = ManifestSigningKey::generate_pkcs8()?;
+//! std::fs::write("manifest-key.p8", &pkcs8)?;
+//! let config = ObserveConfig::new().sign_manifests(ManifestSigningKey::from_pkcs8(&pkcs8)?);
+//! # let _ = config;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Span;
+use crate::sandbox::SandboxManifest;
+use crate::skill::{Skill, SkillKind};
+use crate::{Error, Result};
+
+/// What kind of run a manifest describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    Workflow,
+    Pipeline,
+    Chat,
+}
+
+/// A skill the run provisioned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkillDigest {
+    pub name: String,
+    /// SHA-256 over the skill's definition and, for file skills, the file.
+    /// Remote and registry skills count by id or spec, not by the content
+    /// they resolve to.
+    pub sha256: String,
+}
+
+impl SkillDigest {
+    pub(crate) fn of(skill: &Skill) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&skill.kind)?);
+        if let SkillKind::File { path } = &skill.kind {
+            let content = std::fs::read(path).map_err(|e| {
+                Error::Config(format!(
+                    "Failed to read skill file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            hasher.update(content);
+        }
+        Ok(Self {
+            name: skill.name.clone(),
+            sha256: format!("{:x}", hasher.finalize()),
+        })
+    }
+}
+
+/// One step, stage or turn of a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub name: String,
+    pub duration_ms: u64,
+    /// The agent prompt, for pipeline stages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+/// Ed25519 signature over a manifest's [`digest`](RunManifest::digest).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    /// Always `ed25519`.
+    pub algorithm: String,
+    /// Hex-encoded public key of the signer.
+    pub public_key: String,
+    /// Hex-encoded signature.
+    pub signature: String,
+}
+
+/// Key that signs run manifests.
+#[derive(Clone)]
+pub struct ManifestSigningKey {
+    key: Arc<Ed25519KeyPair>,
+}
+
+impl ManifestSigningKey {
+    /// A new random key, PKCS#8-encoded, for
+    /// [`from_pkcs8`](Self::from_pkcs8). Store it like any private key.
+    pub fn generate_pkcs8() -> Result<Vec<u8>> {
+        Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map(|doc| doc.as_ref().to_vec())
+            .map_err(|_| Error::Observe("cannot generate manifest signing key".into()))
+    }
+
+    /// Load a PKCS#8-encoded Ed25519 key.
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8)
+            .map_err(|e| Error::Config(format!("invalid manifest signing key: {e}")))?;
+        Ok(Self { key: Arc::new(key) })
+    }
+
+    /// Hex-encoded public key, as recorded in signed manifests.
+    pub fn public_key(&self) -> String {
+        to_hex(self.key.public_key().as_ref())
+    }
+}
+
+impl std::fmt::Debug for ManifestSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManifestSigningKey")
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+/// Provenance of one observed run; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub kind: RunKind,
+    pub name: String,
+    /// RFC 3339.
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub void_box_version: String,
+    pub protocol_version: u32,
+    /// The prompt of a chat turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    pub skills: Vec<SkillDigest>,
+    /// The sandboxes the run used: one per pipeline stage Box, else one.
+    pub sandboxes: Vec<SandboxManifest>,
+    pub steps: Vec<StepRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ManifestSignature>,
+}
+
+impl RunManifest {
+    pub(crate) fn new(
+        kind: RunKind,
+        name: &str,
+        started_at: SystemTime,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            run_id: uuid::Uuid::now_v7().to_string(),
+            kind,
+            name: name.to_string(),
+            started_at: humantime::format_rfc3339_millis(started_at).to_string(),
+            duration_ms: duration.as_millis() as u64,
+            void_box_version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: void_box_protocol::PROTOCOL_VERSION,
+            prompt: None,
+            skills: Vec::new(),
+            sandboxes: Vec::new(),
+            steps: Vec::new(),
+            signature: None,
+        }
+    }
+
+    /// Record finished spans named `<prefix><step>` as steps.
+    pub(crate) fn record_spans(&mut self, traces: &[Span], prefix: &str) {
+        for span in traces {
+            let (Some(name), Some(duration)) = (span.name.strip_prefix(prefix), span.duration)
+            else {
+                continue;
+            };
+            self.steps.push(StepRecord {
+                name: name.to_string(),
+                duration_ms: duration.as_millis() as u64,
+                prompt: None,
+            });
+        }
+    }
+
+    /// Add `manifest` unless an identical sandbox is already listed.
+    pub(crate) fn add_sandbox(&mut self, manifest: SandboxManifest) {
+        if !self.sandboxes.iter().any(|s| s.hash == manifest.hash) {
+            self.sandboxes.push(manifest);
+        }
+    }
+
+    /// Add `skill` unless it is already listed.
+    pub(crate) fn add_skill(&mut self, skill: SkillDigest) {
+        if !self.skills.contains(&skill) {
+            self.skills.push(skill);
+        }
+    }
+
+    /// SHA-256 over the manifest without its signature; what gets signed.
+    pub fn digest(&self) -> Result<String> {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+        Ok(format!(
+            "{:x}",
+            Sha256::digest(serde_json::to_vec(&unsigned)?)
+        ))
+    }
+
+    /// Sign the manifest with `key`, replacing any earlier signature.
+    pub fn sign(&mut self, key: &ManifestSigningKey) -> Result<()> {
+        let digest = self.digest()?;
+        self.signature = Some(ManifestSignature {
+            algorithm: "ed25519".into(),
+            public_key: key.public_key(),
+            signature: to_hex(key.key.sign(digest.as_bytes()).as_ref()),
+        });
+        Ok(())
+    }
+
+    /// Check the signature against the manifest's content and `public_key`
+    /// (hex-encoded, see [`ManifestSigningKey::public_key`]).
+    pub fn verify(&self, public_key: &str) -> Result<()> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| Error::Observe("manifest is not signed".into()))?;
+        if signature.algorithm != "ed25519" || signature.public_key != public_key {
+            return Err(Error::Observe("manifest was signed by another key".into()));
+        }
+        let public_key = from_hex(public_key)?;
+        let bytes = from_hex(&signature.signature)?;
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(self.digest()?.as_bytes(), &bytes)
+            .map_err(|_| Error::Observe("manifest signature does not match".into()))
+    }
+
+    /// The manifest as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Write the manifest to `path` as JSON.
+    pub fn write_json(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return Err(Error::Observe(format!("invalid hex '{text}'")));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| Error::Observe(format!("invalid hex '{text}'")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_manifest_verifies_until_edited() {
+        let key =
+            ManifestSigningKey::from_pkcs8(&ManifestSigningKey::generate_pkcs8().unwrap()).unwrap();
+        let mut manifest = RunManifest::new(
+            RunKind::Chat,
+            "reviewer",
+            SystemTime::UNIX_EPOCH,
+            Duration::from_millis(1500),
+        );
+        manifest.prompt = Some("Review the diff".into());
+        assert!(manifest.verify(&key.public_key()).is_err());
+
+        manifest.sign(&key).unwrap();
+        manifest.verify(&key.public_key()).unwrap();
+        let exported: RunManifest = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+        exported.verify(&key.public_key()).unwrap();
+
+        let other =
+            ManifestSigningKey::from_pkcs8(&ManifestSigningKey::generate_pkcs8().unwrap()).unwrap();
+        assert!(manifest.verify(&other.public_key()).is_err());
+
+        manifest.prompt = Some("Approve the diff".into());
+        assert!(manifest.verify(&key.public_key()).is_err());
+    }
+}
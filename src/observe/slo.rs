@@ -0,0 +1,620 @@
+//! Service-Level Objectives and Alerting
+//!
+//! Lets a workflow or pipeline declare cost/latency/error budgets and raise a
+//! structured [`SloAlert`] through pluggable [`AlertSink`]s when a run breaks
+//! one:
+//! - Per-step duration ceiling (`max_step_duration`)
+//! - Cumulative run cost ceiling in USD (`max_run_cost_usd`)
+//! - Failed-step ratio ceiling (`max_error_rate`)
+//!
+//! Evaluation is incremental: the executor feeds each finished step into an
+//! [`SloMonitor`] as it completes, so duration and cost violations fire while
+//! the run is still in progress. The error rate is only meaningful over the
+//! whole run and is checked by [`SloMonitor::finish`]. Span storage is not
+//! consulted — in-memory span collection is a test-only mode, so the monitor
+//! keeps its own running totals.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use void_box::observe::slo::{LogAlertSink, SloPolicy};
+//! use void_box::observe::ObserveConfig;
+//! use void_box::workflow::Workflow;
+//!
+//! let workflow = Workflow::define("nightly")
+//!     .step("build", |ctx| async move { ctx.exec("make", &[]).await })
+//!     .slo(
+//!         SloPolicy::new()
+//!             .max_step_duration(Duration::from_secs(600))
+//!             .max_error_rate(0.25),
+//!     )
+//!     .build();
+//!
+//! let config = ObserveConfig::default().alert_sink(LogAlertSink);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::tracer::SpanContext;
+use crate::{Error, Result};
+
+/// Span event name under which violations are recorded on the run's root span.
+pub const SLO_VIOLATION_EVENT: &str = "slo.violation";
+
+/// Counter incremented once per raised alert, labelled by `run` and `kind`.
+pub const SLO_VIOLATIONS_METRIC: &str = "slo_violations";
+
+/// Cost, latency, and error-rate objectives for one run.
+///
+/// Every objective is optional; an empty policy never alerts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SloPolicy {
+    /// Maximum wall-clock duration of any single step.
+    #[serde(default, with = "humantime_serde_opt")]
+    pub max_step_duration: Option<Duration>,
+    /// Maximum cumulative cost of the run in USD.
+    #[serde(default)]
+    pub max_run_cost_usd: Option<f64>,
+    /// Maximum fraction of steps that may fail, in `[0.0, 1.0]`.
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+}
+
+impl SloPolicy {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Alert when any step runs longer than `limit`.
+    pub fn max_step_duration(mut self, limit: Duration) -> Self {
+        self.max_step_duration = Some(limit);
+        self
+    }
+
+    /// Alert when the run's cumulative cost exceeds `limit_usd`.
+    pub fn max_run_cost_usd(mut self, limit_usd: f64) -> Self {
+        self.max_run_cost_usd = Some(limit_usd);
+        self
+    }
+
+    /// Alert when the fraction of failed steps exceeds `limit`.
+    pub fn max_error_rate(mut self, limit: f64) -> Self {
+        self.max_error_rate = Some(limit.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Whether the policy declares no objectives.
+    pub fn is_empty(&self) -> bool {
+        self.max_step_duration.is_none()
+            && self.max_run_cost_usd.is_none()
+            && self.max_error_rate.is_none()
+    }
+}
+
+/// `Option<Duration>` as a humantime string (`"90s"`, `"10m"`) for specs.
+mod humantime_serde_opt {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(duration) => s.serialize_str(&humantime::format_duration(*duration).to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        let raw: Option<String> = Option::deserialize(d)?;
+        raw.map(|text| humantime::parse_duration(&text).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// The objective a run broke, with the observed and allowed values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SloViolation {
+    /// A single step ran longer than `max_step_duration`.
+    StepDuration {
+        step: String,
+        duration_ms: u64,
+        limit_ms: u64,
+    },
+    /// The run's cumulative cost crossed `max_run_cost_usd`.
+    RunCost { cost_usd: f64, limit_usd: f64 },
+    /// The fraction of failed steps exceeded `max_error_rate`.
+    ErrorRate {
+        failed_steps: usize,
+        total_steps: usize,
+        limit: f64,
+    },
+}
+
+impl SloViolation {
+    /// Stable short name, used as the `kind` metric label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SloViolation::StepDuration { .. } => "step_duration",
+            SloViolation::RunCost { .. } => "run_cost",
+            SloViolation::ErrorRate { .. } => "error_rate",
+        }
+    }
+}
+
+impl fmt::Display for SloViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SloViolation::StepDuration {
+                step,
+                duration_ms,
+                limit_ms,
+            } => write!(
+                f,
+                "step \"{}\" took {}ms (limit {}ms)",
+                step, duration_ms, limit_ms
+            ),
+            SloViolation::RunCost {
+                cost_usd,
+                limit_usd,
+            } => write!(f, "run cost ${:.4} (limit ${:.4})", cost_usd, limit_usd),
+            SloViolation::ErrorRate {
+                failed_steps,
+                total_steps,
+                limit,
+            } => write!(
+                f,
+                "{}/{} steps failed (limit {:.0}%)",
+                failed_steps,
+                total_steps,
+                limit * 100.0
+            ),
+        }
+    }
+}
+
+/// A structured alert for one SLO violation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SloAlert {
+    /// Workflow or pipeline name.
+    pub run: String,
+    /// Trace the offending spans belong to.
+    pub trace_id: String,
+    /// Spans that caused the violation (the slow step, the costly or failed
+    /// steps).
+    pub span_ids: Vec<String>,
+    /// What was violated.
+    pub violation: SloViolation,
+    /// Unix timestamp in milliseconds when the violation was detected.
+    pub timestamp_ms: u64,
+}
+
+impl SloAlert {
+    fn new(run: &str, trace_id: &str, span_ids: Vec<String>, violation: SloViolation) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            run: run.to_string(),
+            trace_id: trace_id.to_string(),
+            span_ids,
+            violation,
+            timestamp_ms,
+        }
+    }
+
+    /// Span-event attributes describing this alert.
+    pub fn event_attributes(&self) -> HashMap<String, String> {
+        let mut attrs = HashMap::new();
+        attrs.insert("slo.kind".to_string(), self.violation.kind().to_string());
+        attrs.insert("slo.message".to_string(), self.violation.to_string());
+        attrs.insert("slo.span_ids".to_string(), self.span_ids.join(","));
+        attrs
+    }
+}
+
+impl fmt::Display for SloAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SLO violated in {}: {}", self.run, self.violation)
+    }
+}
+
+/// Destination for [`SloAlert`]s.
+///
+/// Sinks are attached to an [`ObserveConfig`](super::ObserveConfig) and
+/// invoked in registration order. A failing sink is logged and does not stop
+/// delivery to the remaining sinks or fail the run.
+#[async_trait::async_trait]
+pub trait AlertSink: Send + Sync + fmt::Debug {
+    /// Deliver one alert.
+    async fn send(&self, alert: &SloAlert) -> Result<()>;
+}
+
+/// Sink that writes alerts to the `tracing` log at `WARN`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogAlertSink;
+
+#[async_trait::async_trait]
+impl AlertSink for LogAlertSink {
+    async fn send(&self, alert: &SloAlert) -> Result<()> {
+        tracing::warn!(
+            run = %alert.run,
+            trace_id = %alert.trace_id,
+            span_ids = %alert.span_ids.join(","),
+            kind = alert.violation.kind(),
+            "{}",
+            alert
+        );
+        Ok(())
+    }
+}
+
+/// Sink that POSTs each alert as JSON to an HTTP endpoint.
+#[derive(Debug, Clone)]
+pub struct WebhookAlertSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookAlertSink {
+    /// Create a sink posting to `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AlertSink for WebhookAlertSink {
+    async fn send(&self, alert: &SloAlert) -> Result<()> {
+        let body = serde_json::to_vec(alert)?;
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("SLO webhook {}: {}", self.url, e)))?;
+        if !response.status().is_success() {
+            return Err(Error::Network(format!(
+                "SLO webhook {} returned {}",
+                self.url,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Sink that hands each alert to a caller-supplied closure.
+#[derive(Clone)]
+pub struct CallbackAlertSink {
+    callback: Arc<dyn Fn(&SloAlert) + Send + Sync>,
+}
+
+impl CallbackAlertSink {
+    /// Create a sink invoking `callback` for every alert.
+    pub fn new(callback: impl Fn(&SloAlert) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+impl fmt::Debug for CallbackAlertSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackAlertSink").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl AlertSink for CallbackAlertSink {
+    async fn send(&self, alert: &SloAlert) -> Result<()> {
+        (self.callback)(alert);
+        Ok(())
+    }
+}
+
+/// Outcome of one finished step, as fed to [`SloMonitor::record_step`].
+#[derive(Debug, Clone)]
+pub struct StepOutcome<'a> {
+    /// Step (or stage) name.
+    pub name: &'a str,
+    /// The step's span.
+    pub span: &'a SpanContext,
+    /// Wall-clock duration of the step.
+    pub duration: Duration,
+    /// Whether the step succeeded.
+    pub succeeded: bool,
+    /// Cost attributed to the step in USD (0 when not applicable).
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    total_steps: usize,
+    failed_span_ids: Vec<String>,
+    cost_usd: f64,
+    costly_span_ids: Vec<String>,
+    cost_alerted: bool,
+    alerts: Vec<SloAlert>,
+}
+
+/// Running evaluation of an [`SloPolicy`] over one run.
+///
+/// Shared behind an `Arc` by executors that finish steps concurrently.
+#[derive(Debug)]
+pub struct SloMonitor {
+    run: String,
+    trace_id: String,
+    policy: SloPolicy,
+    state: Mutex<MonitorState>,
+}
+
+impl SloMonitor {
+    /// Start monitoring the run `run`, whose root span lives in `trace_id`.
+    pub fn new(run: impl Into<String>, trace_id: impl Into<String>, policy: SloPolicy) -> Self {
+        Self {
+            run: run.into(),
+            trace_id: trace_id.into(),
+            policy,
+            state: Mutex::new(MonitorState::default()),
+        }
+    }
+
+    /// Account for one finished step and return any violations it caused.
+    ///
+    /// The run-cost alert fires at most once, on the step that crosses the
+    /// limit.
+    pub fn record_step(&self, outcome: StepOutcome<'_>) -> Vec<SloAlert> {
+        let span_id = outcome.span.span_id.clone();
+        let mut alerts = Vec::new();
+        let mut state = self.state.lock().unwrap();
+
+        state.total_steps += 1;
+        if !outcome.succeeded {
+            state.failed_span_ids.push(span_id.clone());
+        }
+
+        if let Some(limit) = self.policy.max_step_duration {
+            if outcome.duration > limit {
+                alerts.push(SloAlert::new(
+                    &self.run,
+                    &self.trace_id,
+                    vec![span_id.clone()],
+                    SloViolation::StepDuration {
+                        step: outcome.name.to_string(),
+                        duration_ms: outcome.duration.as_millis() as u64,
+                        limit_ms: limit.as_millis() as u64,
+                    },
+                ));
+            }
+        }
+
+        if outcome.cost_usd > 0.0 {
+            state.cost_usd += outcome.cost_usd;
+            state.costly_span_ids.push(span_id);
+        }
+        if let Some(limit_usd) = self.policy.max_run_cost_usd {
+            if !state.cost_alerted && state.cost_usd > limit_usd {
+                state.cost_alerted = true;
+                alerts.push(SloAlert::new(
+                    &self.run,
+                    &self.trace_id,
+                    state.costly_span_ids.clone(),
+                    SloViolation::RunCost {
+                        cost_usd: state.cost_usd,
+                        limit_usd,
+                    },
+                ));
+            }
+        }
+
+        state.alerts.extend(alerts.iter().cloned());
+        alerts
+    }
+
+    /// Evaluate the whole-run objectives once every step has been recorded.
+    pub fn finish(&self) -> Vec<SloAlert> {
+        let mut alerts = Vec::new();
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(limit) = self.policy.max_error_rate {
+            let failed_steps = state.failed_span_ids.len();
+            let total_steps = state.total_steps;
+            if total_steps > 0 && failed_steps as f64 / total_steps as f64 > limit {
+                alerts.push(SloAlert::new(
+                    &self.run,
+                    &self.trace_id,
+                    state.failed_span_ids.clone(),
+                    SloViolation::ErrorRate {
+                        failed_steps,
+                        total_steps,
+                        limit,
+                    },
+                ));
+            }
+        }
+
+        state.alerts.extend(alerts.iter().cloned());
+        alerts
+    }
+
+    /// Every alert raised so far, in detection order.
+    pub fn alerts(&self) -> Vec<SloAlert> {
+        self.state.lock().unwrap().alerts.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: &str) -> SpanContext {
+        SpanContext {
+            trace_id: "0".repeat(32),
+            span_id: id.to_string(),
+            parent_span_id: None,
+            trace_flags: 1,
+        }
+    }
+
+    fn outcome<'a>(
+        name: &'a str,
+        span: &'a SpanContext,
+        duration_ms: u64,
+        succeeded: bool,
+        cost_usd: f64,
+    ) -> StepOutcome<'a> {
+        StepOutcome {
+            name,
+            span,
+            duration: Duration::from_millis(duration_ms),
+            succeeded,
+            cost_usd,
+        }
+    }
+
+    #[test]
+    fn test_step_duration_violation_names_offending_span() {
+        let policy = SloPolicy::new().max_step_duration(Duration::from_millis(100));
+        let monitor = SloMonitor::new("wf", "trace", policy);
+
+        let fast = span("a");
+        assert!(monitor
+            .record_step(outcome("fast", &fast, 50, true, 0.0))
+            .is_empty());
+
+        let slow = span("b");
+        let alerts = monitor.record_step(outcome("slow", &slow, 250, true, 0.0));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].span_ids, vec!["b".to_string()]);
+        assert_eq!(
+            alerts[0].violation,
+            SloViolation::StepDuration {
+                step: "slow".into(),
+                duration_ms: 250,
+                limit_ms: 100,
+            }
+        );
+    }
+
+    #[test]
+    fn test_run_cost_alerts_once_when_crossed() {
+        let policy = SloPolicy::new().max_run_cost_usd(1.0);
+        let monitor = SloMonitor::new("pipe", "trace", policy);
+
+        let (a, b, c) = (span("a"), span("b"), span("c"));
+        assert!(monitor
+            .record_step(outcome("a", &a, 1, true, 0.6))
+            .is_empty());
+        let alerts = monitor.record_step(outcome("b", &b, 1, true, 0.6));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].span_ids, vec!["a".to_string(), "b".to_string()]);
+        assert!(matches!(
+            alerts[0].violation,
+            SloViolation::RunCost { limit_usd, .. } if limit_usd == 1.0
+        ));
+
+        assert!(monitor
+            .record_step(outcome("c", &c, 1, true, 0.6))
+            .is_empty());
+        assert_eq!(monitor.alerts().len(), 1);
+    }
+
+    #[test]
+    fn test_error_rate_checked_on_finish() {
+        let policy = SloPolicy::new().max_error_rate(0.25);
+        let monitor = SloMonitor::new("wf", "trace", policy);
+
+        let (a, b) = (span("a"), span("b"));
+        assert!(monitor
+            .record_step(outcome("a", &a, 1, true, 0.0))
+            .is_empty());
+        assert!(monitor
+            .record_step(outcome("b", &b, 1, false, 0.0))
+            .is_empty());
+
+        let alerts = monitor.finish();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].span_ids, vec!["b".to_string()]);
+        assert_eq!(
+            alerts[0].violation,
+            SloViolation::ErrorRate {
+                failed_steps: 1,
+                total_steps: 2,
+                limit: 0.25,
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_policy_never_alerts() {
+        let monitor = SloMonitor::new("wf", "trace", SloPolicy::new());
+        let a = span("a");
+        assert!(monitor
+            .record_step(outcome("a", &a, 10_000, false, 100.0))
+            .is_empty());
+        assert!(monitor.finish().is_empty());
+        assert!(SloPolicy::new().is_empty());
+    }
+
+    #[test]
+    fn test_policy_deserializes_humantime_duration() {
+        let policy: SloPolicy = serde_json::from_str(
+            r#"{"max_step_duration":"90s","max_run_cost_usd":2.5,"max_error_rate":0.1}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.max_step_duration, Some(Duration::from_secs(90)));
+        assert_eq!(policy.max_run_cost_usd, Some(2.5));
+        assert_eq!(policy.max_error_rate, Some(0.1));
+    }
+
+    #[test]
+    fn test_alert_serializes_with_kind_tag() {
+        let alert = SloAlert::new(
+            "wf",
+            "trace",
+            vec!["a".into()],
+            SloViolation::RunCost {
+                cost_usd: 2.0,
+                limit_usd: 1.0,
+            },
+        );
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["violation"]["kind"], "run_cost");
+        assert_eq!(json["span_ids"][0], "a");
+    }
+
+    #[tokio::test]
+    async fn test_callback_sink_receives_alert() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let received = received.clone();
+            CallbackAlertSink::new(move |alert| received.lock().unwrap().push(alert.clone()))
+        };
+        let alert = SloAlert::new(
+            "wf",
+            "trace",
+            Vec::new(),
+            SloViolation::ErrorRate {
+                failed_steps: 1,
+                total_steps: 1,
+                limit: 0.0,
+            },
+        );
+        sink.send(&alert).await.unwrap();
+        assert_eq!(received.lock().unwrap().as_slice(), &[alert]);
+    }
+}
@@ -3,14 +3,28 @@
 //! Ingests telemetry batches from the guest VM and feeds them into the
 //! existing Observer's MetricsCollector. This bridges the guest-to-host
 //! telemetry pipeline without introducing new metric backends.
+//!
+//! The aggregator also keeps the most recent batches so callers can query
+//! a time window ([`TelemetryAggregator::window`]), summarize it
+//! ([`TelemetryStats`]), or dump it as JSON alongside workflow results.
+//!
+//! An aggregator fed by a live guest can also steer the subscription:
+//! change its interval, narrow the per-process metrics, or pause it, so
+//! high-frequency sampling can be limited to the phases worth watching.
 
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
 use serde::Serialize;
 
 use super::Observer;
-use crate::guest::protocol::{SystemMetrics, TelemetryBatch};
+use crate::backend::control_channel::ControlChannel;
+use crate::guest::protocol::{
+    DiskUsage, SystemMetrics, TelemetryBatch, TelemetryControlRequest, TelemetryProcessFilter,
+    TopProcesses,
+};
+use crate::{Error, Result};
 
 /// Shared telemetry ring buffer handle, threaded from the daemon down to the
 /// `TelemetryAggregator` so guest samples appear alongside host samples in the
@@ -45,6 +59,8 @@ pub struct GuestMetricsSample {
     pub net_tx_bytes: u64,
     pub procs_running: u32,
     pub open_fds: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskUsage>,
 }
 
 /// Host daemon metrics snapshot.
@@ -122,6 +138,7 @@ impl GuestMetricsSample {
             net_tx_bytes: sys.net_tx_bytes,
             procs_running: sys.procs_running,
             open_fds: sys.open_fds,
+            disks: sys.disks.clone(),
         }
     }
 }
@@ -130,22 +147,111 @@ impl GuestMetricsSample {
 pub struct TelemetryAggregator {
     observer: Observer,
     cid: u32,
-    latest: Mutex<Option<TelemetryBatch>>,
+    /// Recent batches, oldest first; the newest is the latest batch.
+    history: Mutex<VecDeque<TelemetryBatch>>,
+    history_capacity: usize,
     /// Optional ring buffer for the `/v1/runs/{id}/telemetry` endpoint.
     ring_buffer: Option<Arc<Mutex<TelemetryRingBuffer>>>,
     /// Current stage name — updated externally when StageStarted events arrive.
     current_stage: Arc<Mutex<String>>,
+    /// Optional callback run after each batch is ingested.
+    batch_hook: OnceLock<BatchHook>,
+    /// Channel of the subscription feeding this aggregator, for
+    /// `TelemetryControl` requests.
+    control: OnceLock<Arc<ControlChannel>>,
+}
+
+/// Batches retained by default: one hour at the guest's 1s interval.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 3600;
+
+/// Summary of a run of telemetry batches, e.g. from
+/// [`TelemetryAggregator::stats`].
+///
+/// CPU and memory figures come from batches carrying system metrics;
+/// they are zero when none did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TelemetryStats {
+    /// Batches summarized
+    pub batches: usize,
+    /// Guest timestamp of the oldest batch, Unix milliseconds
+    pub first_timestamp_ms: Option<u64>,
+    /// Guest timestamp of the newest batch, Unix milliseconds
+    pub last_timestamp_ms: Option<u64>,
+    /// Mean guest CPU utilization
+    pub cpu_avg_percent: f64,
+    /// Peak guest CPU utilization
+    pub cpu_max_percent: f64,
+    /// Highest guest memory usage seen
+    pub memory_high_water_bytes: u64,
+    /// Guest memory size
+    pub memory_total_bytes: u64,
+    /// Highest RSS of any single guest process
+    pub process_rss_high_water_bytes: u64,
+}
+
+impl TelemetryStats {
+    /// Summarize `batches`.
+    pub fn from_batches<'a>(batches: impl IntoIterator<Item = &'a TelemetryBatch>) -> Self {
+        let mut stats = Self::default();
+        let mut cpu_sum = 0.0;
+        let mut system_samples = 0usize;
+        for batch in batches {
+            stats.batches += 1;
+            stats.first_timestamp_ms = Some(
+                stats
+                    .first_timestamp_ms
+                    .map_or(batch.timestamp_ms, |t| t.min(batch.timestamp_ms)),
+            );
+            stats.last_timestamp_ms = Some(
+                stats
+                    .last_timestamp_ms
+                    .map_or(batch.timestamp_ms, |t| t.max(batch.timestamp_ms)),
+            );
+            if let Some(sys) = &batch.system {
+                system_samples += 1;
+                cpu_sum += sys.cpu_percent;
+                stats.cpu_max_percent = stats.cpu_max_percent.max(sys.cpu_percent);
+                stats.memory_high_water_bytes =
+                    stats.memory_high_water_bytes.max(sys.memory_used_bytes);
+                stats.memory_total_bytes = stats.memory_total_bytes.max(sys.memory_total_bytes);
+            }
+            for proc in &batch.processes {
+                stats.process_rss_high_water_bytes =
+                    stats.process_rss_high_water_bytes.max(proc.rss_bytes);
+            }
+        }
+        if system_samples > 0 {
+            stats.cpu_avg_percent = cpu_sum / system_samples as f64;
+        }
+        stats
+    }
+}
+
+/// One telemetry reading for a single guest process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessSample {
+    /// Guest wall-clock time of the batch, Unix milliseconds.
+    pub timestamp_ms: u64,
+    pub pid: u32,
+    pub rss_bytes: u64,
+    /// Cumulative utime + stime, in clock ticks.
+    pub cpu_jiffies: u64,
 }
 
+type BatchHook = Box<dyn Fn(&TelemetryBatch) + Send + Sync>;
+
 impl TelemetryAggregator {
     /// Create a new aggregator for a guest VM with the given CID.
     pub fn new(observer: Observer, cid: u32) -> Self {
         Self {
             observer,
             cid,
-            latest: Mutex::new(None),
+            history: Mutex::new(VecDeque::new()),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
             ring_buffer: None,
             current_stage: Arc::new(Mutex::new(String::new())),
+            batch_hook: OnceLock::new(),
+            control: OnceLock::new(),
         }
     }
 
@@ -158,12 +264,22 @@ impl TelemetryAggregator {
         Self {
             observer,
             cid,
-            latest: Mutex::new(None),
+            history: Mutex::new(VecDeque::new()),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
             ring_buffer: Some(ring_buffer),
             current_stage: Arc::new(Mutex::new(String::new())),
+            batch_hook: OnceLock::new(),
+            control: OnceLock::new(),
         }
     }
 
+    /// Keep at most `capacity` batches of history (at least one, so
+    /// [`latest_batch`](Self::latest_batch) keeps working).
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history_capacity = capacity.max(1);
+        self
+    }
+
     /// Set the current stage name (called when StageStarted event is observed).
     pub fn set_current_stage(&self, stage_name: &str) {
         if let Ok(mut s) = self.current_stage.lock() {
@@ -179,6 +295,154 @@ impl TelemetryAggregator {
             .unwrap_or_default()
     }
 
+    /// Run `hook` after every subsequently ingested batch.
+    ///
+    /// Only the first hook is kept; later calls are ignored.
+    pub fn set_batch_hook(&self, hook: impl Fn(&TelemetryBatch) + Send + Sync + 'static) {
+        let _ = self.batch_hook.set(Box::new(hook));
+    }
+
+    /// Route [`set_interval`](Self::set_interval) and the other controls
+    /// to the guest subscription behind `channel`.
+    pub(crate) fn attach_control_channel(&self, channel: Arc<ControlChannel>) {
+        let _ = self.control.set(channel);
+    }
+
+    /// Collect a batch every `interval` (at least 100 ms) from now on.
+    pub async fn set_interval(&self, interval: Duration) -> Result<()> {
+        self.control(TelemetryControlRequest {
+            interval_ms: Some(interval.as_millis() as u64),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Stop the guest collecting and sending batches until
+    /// [`resume`](Self::resume).
+    pub async fn pause(&self) -> Result<()> {
+        self.control(TelemetryControlRequest {
+            paused: Some(true),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Restart a [paused](Self::pause) subscription. The first batch
+    /// arrives one interval later.
+    pub async fn resume(&self) -> Result<()> {
+        self.control(TelemetryControlRequest {
+            paused: Some(false),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Report only the processes `filter` matches in later batches; an
+    /// empty filter reports all of them again. System metrics are
+    /// unaffected.
+    pub async fn filter_processes(&self, filter: TelemetryProcessFilter) -> Result<()> {
+        self.control(TelemetryControlRequest {
+            process_filter: Some(filter),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Report only the `top.count` processes ranking highest by CPU or
+    /// memory in later batches; a `count` of 0 reports all of them again.
+    pub async fn top_processes(&self, top: TopProcesses) -> Result<()> {
+        self.control(TelemetryControlRequest {
+            top_processes: Some(top),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn control(&self, request: TelemetryControlRequest) -> Result<()> {
+        let channel = self.control.get().ok_or(Error::VmNotRunning)?;
+        let response = channel.send_telemetry_control(&request).await?;
+        if response.success {
+            Ok(())
+        } else {
+            Err(Error::Guest(format!(
+                "Failed to change telemetry settings: {}",
+                response.error.unwrap_or_default()
+            )))
+        }
+    }
+
+    /// Retained samples for `pid` taken at or after `since_ms` (Unix
+    /// milliseconds), oldest first.
+    pub fn process_samples(&self, pid: u32, since_ms: u64) -> Vec<ProcessSample> {
+        self.history
+            .lock()
+            .map(|history| {
+                history
+                    .iter()
+                    .filter(|batch| batch.timestamp_ms >= since_ms)
+                    .flat_map(|batch| {
+                        batch
+                            .processes
+                            .iter()
+                            .filter(|p| p.pid == pid)
+                            .map(|p| ProcessSample {
+                                timestamp_ms: batch.timestamp_ms,
+                                pid: p.pid,
+                                rss_bytes: p.rss_bytes,
+                                cpu_jiffies: p.cpu_jiffies,
+                            })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Retained batches from the last `duration`, oldest first.
+    ///
+    /// The window is measured back from the newest batch's guest timestamp,
+    /// so it is unaffected by skew between guest and host clocks.
+    pub fn window(&self, duration: Duration) -> Vec<TelemetryBatch> {
+        let Ok(history) = self.history.lock() else {
+            return Vec::new();
+        };
+        let Some(newest) = history.back() else {
+            return Vec::new();
+        };
+        let since_ms = newest
+            .timestamp_ms
+            .saturating_sub(duration.as_millis() as u64);
+        history
+            .iter()
+            .filter(|batch| batch.timestamp_ms >= since_ms)
+            .cloned()
+            .collect()
+    }
+
+    /// Every retained batch, oldest first.
+    pub fn history(&self) -> Vec<TelemetryBatch> {
+        self.history
+            .lock()
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Summarize the last `duration` of telemetry; see [`window`](Self::window).
+    pub fn stats(&self, duration: Duration) -> TelemetryStats {
+        TelemetryStats::from_batches(&self.window(duration))
+    }
+
+    /// Dump the retained history with its summary, for attaching to
+    /// workflow results:
+    /// `{"cid": .., "stats": {..}, "batches": [..]}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let batches = self.history();
+        serde_json::json!({
+            "cid": self.cid,
+            "stats": TelemetryStats::from_batches(&batches),
+            "batches": batches,
+        })
+    }
+
     /// Ingest a telemetry batch from the guest and record into the Observer's MetricsCollector.
     pub fn ingest(&self, batch: &TelemetryBatch) {
         let cid_str = self.cid.to_string();
@@ -205,11 +469,18 @@ impl TelemetryAggregator {
                 proc.cpu_jiffies as f64,
                 proc_labels,
             );
+            self.observer.metrics().set_gauge(
+                "guest.process.cpu_percent",
+                proc.cpu_percent,
+                proc_labels,
+            );
         }
 
-        // Store latest batch
-        if let Ok(mut latest) = self.latest.lock() {
-            *latest = Some(batch.clone());
+        if let Ok(mut history) = self.history.lock() {
+            while history.len() >= self.history_capacity {
+                history.pop_front();
+            }
+            history.push_back(batch.clone());
         }
 
         // Push into ring buffer if configured
@@ -230,6 +501,10 @@ impl TelemetryAggregator {
                 });
             }
         }
+
+        if let Some(hook) = self.batch_hook.get() {
+            hook(batch);
+        }
     }
 
     fn ingest_system(&self, sys: &SystemMetrics, labels: &[(&str, &str)]) {
@@ -246,11 +521,33 @@ impl TelemetryAggregator {
         );
         metrics.set_gauge("guest.procs_running", sys.procs_running as f64, labels);
         metrics.set_gauge("guest.open_fds", sys.open_fds as f64, labels);
+        for disk in &sys.disks {
+            let disk_labels: Vec<(&str, &str)> = labels
+                .iter()
+                .copied()
+                .chain([("mount", disk.mount.as_str())])
+                .collect();
+            metrics.set_gauge(
+                "guest.disk_used_bytes",
+                disk.used_bytes as f64,
+                &disk_labels,
+            );
+            metrics.set_gauge(
+                "guest.disk_total_bytes",
+                disk.total_bytes as f64,
+                &disk_labels,
+            );
+            metrics.set_gauge(
+                "guest.disk_available_bytes",
+                disk.available_bytes as f64,
+                &disk_labels,
+            );
+        }
     }
 
     /// Get the latest telemetry batch received from the guest.
     pub fn latest_batch(&self) -> Option<TelemetryBatch> {
-        self.latest.lock().ok().and_then(|g| g.clone())
+        self.history.lock().ok().and_then(|h| h.back().cloned())
     }
 
     /// Get the CID this aggregator is tracking.
@@ -264,6 +561,12 @@ mod tests {
     use super::*;
     use crate::guest::protocol::{ProcessMetrics, SystemMetrics, TelemetryBatch};
 
+    #[tokio::test]
+    async fn test_control_without_subscription_fails() {
+        let aggregator = TelemetryAggregator::new(Observer::test(), 42);
+        assert!(matches!(aggregator.pause().await, Err(Error::VmNotRunning)));
+    }
+
     #[test]
     fn test_ingest_system_metrics() {
         let observer = Observer::test();
@@ -280,6 +583,7 @@ mod tests {
                 net_tx_bytes: 2000,
                 procs_running: 3,
                 open_fds: 64,
+                disks: Vec::new(),
             }),
             processes: vec![],
             trace_context: None,
@@ -299,6 +603,56 @@ mod tests {
             .any(|m| m.name == "memory_usage_bytes"));
     }
 
+    #[test]
+    fn test_ingest_disk_usage_labels_by_mount() {
+        let observer = Observer::test();
+        let aggregator = TelemetryAggregator::new(observer.clone(), 42);
+
+        let batch = TelemetryBatch {
+            seq: 0,
+            timestamp_ms: 1700000000000,
+            system: Some(SystemMetrics {
+                disks: vec![
+                    DiskUsage {
+                        mount: "/".to_string(),
+                        used_bytes: 100,
+                        total_bytes: 1000,
+                        available_bytes: 900,
+                    },
+                    DiskUsage {
+                        mount: crate::guest::protocol::OVERLAY_UPPER_DISK.to_string(),
+                        used_bytes: 700,
+                        total_bytes: 800,
+                        available_bytes: 100,
+                    },
+                ],
+                cpu_percent: 0.0,
+                memory_used_bytes: 0,
+                memory_total_bytes: 0,
+                net_rx_bytes: 0,
+                net_tx_bytes: 0,
+                procs_running: 0,
+                open_fds: 0,
+            }),
+            processes: vec![],
+            trace_context: None,
+        };
+
+        aggregator.ingest(&batch);
+
+        let snapshot = observer.get_metrics();
+        let used: Vec<_> = snapshot
+            .metrics
+            .values()
+            .filter(|m| m.name == "guest.disk_used_bytes")
+            .collect();
+        assert_eq!(used.len(), 2);
+        assert!(used.iter().any(|m| {
+            m.labels.get("mount").map(String::as_str) == Some("overlay-upper")
+                && m.labels.get("vm_cid").map(String::as_str) == Some("42")
+        }));
+    }
+
     #[test]
     fn test_ingest_process_metrics() {
         let observer = Observer::test();
@@ -314,6 +668,7 @@ mod tests {
                 rss_bytes: 8192,
                 cpu_jiffies: 100,
                 state: 'S',
+                ..Default::default()
             }],
             trace_context: None,
         };
@@ -481,6 +836,7 @@ mod tests {
                 net_tx_bytes: 200,
                 procs_running: 2,
                 open_fds: 32,
+                disks: Vec::new(),
             }),
             processes: vec![],
             trace_context: None,
@@ -536,6 +892,7 @@ mod tests {
             net_tx_bytes: 600,
             procs_running: 7,
             open_fds: 128,
+            disks: Vec::new(),
         };
 
         let sample = GuestMetricsSample::from_system_metrics(&sys);
@@ -564,6 +921,7 @@ mod tests {
                 net_tx_bytes: 0,
                 procs_running: 1,
                 open_fds: 5,
+                disks: Vec::new(),
             }),
             processes: vec![],
             trace_context: None,
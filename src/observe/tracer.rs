@@ -425,6 +425,25 @@ impl SpanContext {
             trace_flags,
         })
     }
+
+    /// Run `fut` with `self` as the active span context.
+    ///
+    /// Sandbox execs started inside `fut` (on the same task) become children
+    /// of this context and pass it to the guest as `TRACEPARENT`. The scope
+    /// is task-local, so concurrent steps sharing one sandbox each keep
+    /// their own parent.
+    pub async fn scope<F: std::future::Future>(self, fut: F) -> F::Output {
+        ACTIVE_SPAN_CONTEXT.scope(self, fut).await
+    }
+
+    /// The span context made active by [`SpanContext::scope`], if any.
+    pub fn current() -> Option<Self> {
+        ACTIVE_SPAN_CONTEXT.try_with(Clone::clone).ok()
+    }
+}
+
+tokio::task_local! {
+    static ACTIVE_SPAN_CONTEXT: SpanContext;
 }
 
 /// Builder for creating spans with a fluent API
@@ -571,4 +590,22 @@ mod tests {
         assert!(span.duration.is_some());
         assert!(span.duration.unwrap().as_millis() >= 10);
     }
+
+    #[tokio::test]
+    async fn test_active_span_context_scope() {
+        assert!(SpanContext::current().is_none());
+        let outer = Span::new("outer").context;
+        let inner = Span::child("inner", &outer).context;
+
+        let seen = outer
+            .clone()
+            .scope(async {
+                let before = SpanContext::current();
+                let nested = inner.clone().scope(async { SpanContext::current() }).await;
+                (before, nested, SpanContext::current())
+            })
+            .await;
+        assert_eq!(seen, (Some(outer.clone()), Some(inner), Some(outer)));
+        assert!(SpanContext::current().is_none());
+    }
 }
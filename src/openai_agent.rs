@@ -0,0 +1,479 @@
+//! Host-side agent loop for OpenAI-compatible servers.
+//!
+//! claude-code and Codex run inside the guest and report what they did on
+//! stdout. No such agent ships for plain chat-completions servers, so for
+//! [`LlmProvider::OpenAi`] the loop runs on the host instead:
+//!
+//! 1. Stream a completion for the conversation so far, with the `Bash`,
+//!    `Read` and `Write` tools offered (parsed by
+//!    [`parse_openai_sse_line`]).
+//! 2. Run each tool call the model makes in the sandbox and append its
+//!    output to the conversation.
+//! 3. Repeat until the model answers without calling a tool.
+//!
+//! The tools use claude-code's names and input shapes, so the resulting
+//! [`AgentExecResult`] — tool calls, tokens, turns, final text — reads the
+//! same as a claude-code run, and tool hooks and budgets work unchanged.
+//! Servers do not report a price, so `total_cost_usd` stays zero and a
+//! budget's cost limit is never reached.
+//!
+//! Callers go through
+//! [`Sandbox::exec_agent`](crate::sandbox::Sandbox::exec_agent) and
+//! [`Sandbox::exec_agent_streaming`](crate::sandbox::Sandbox::exec_agent_streaming),
+//! which dispatch here.
+
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+use crate::budget::{BudgetExceeded, BudgetUsage};
+use crate::llm::LlmProvider;
+use crate::observe::claude::{AgentExecOpts, AgentExecResult, AgentStreamEvent, ClaudeToolCall};
+use crate::observe::openai::{parse_openai_sse_line, OpenAiTurn};
+use crate::sandbox::Sandbox;
+use crate::{Error, Result};
+
+/// Run time allowed when the caller sets no timeout, matching the guest
+/// exec default.
+const DEFAULT_TIMEOUT_SECS: u64 = 1200;
+
+/// Model turns before a run that keeps calling tools is failed.
+const MAX_TURNS: u32 = 100;
+
+/// Tool output beyond this many bytes is cut before it goes back to the
+/// model.
+const MAX_TOOL_OUTPUT: usize = 32 * 1024;
+
+const WORKSPACE: &str = "/workspace";
+
+const SYSTEM_PROMPT: &str = "You are an autonomous agent working in an isolated Linux sandbox. \
+Use the Bash, Read and Write tools to inspect and change files; the working directory is \
+/workspace. When the task is done, reply with your final answer and no tool calls.";
+
+/// Run `prompt` to completion against the OpenAI-compatible server of
+/// `provider`, executing its tool calls in `sandbox`.
+pub(crate) async fn exec_openai_agent<F>(
+    sandbox: &Sandbox,
+    provider: &LlmProvider,
+    prompt: &str,
+    opts: AgentExecOpts,
+    on_event: F,
+) -> Result<AgentExecResult>
+where
+    F: FnMut(AgentStreamEvent),
+{
+    let (LlmProvider::OpenAi { model, .. }, Some((endpoint, api_key))) =
+        (provider, provider.openai_endpoint())
+    else {
+        return Err(Error::Config(format!(
+            "{} is not an OpenAI-compatible provider",
+            provider.description()
+        )));
+    };
+    let run = AgentLoop {
+        sandbox,
+        client: reqwest::Client::new(),
+        endpoint,
+        api_key,
+        model: model.clone(),
+        opts,
+    };
+
+    let started = Instant::now();
+    let result = match run.opts.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS) {
+        0 => run.run(prompt, on_event).await,
+        secs => tokio::time::timeout(Duration::from_secs(secs), run.run(prompt, on_event))
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "{} did not finish within {}s",
+                    provider.description(),
+                    secs
+                ))
+            })?,
+    };
+    result.map(|mut result| {
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        result
+    })
+}
+
+struct AgentLoop<'a> {
+    sandbox: &'a Sandbox,
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+    opts: AgentExecOpts,
+}
+
+impl AgentLoop<'_> {
+    async fn run<F>(&self, prompt: &str, mut on_event: F) -> Result<AgentExecResult>
+    where
+        F: FnMut(AgentStreamEvent),
+    {
+        let mut result = AgentExecResult {
+            model: self.model.clone(),
+            session_id: uuid::Uuid::now_v7().to_string(),
+            ..Default::default()
+        };
+        let mut messages = vec![
+            json!({ "role": "system", "content": SYSTEM_PROMPT }),
+            json!({ "role": "user", "content": prompt }),
+        ];
+
+        loop {
+            if result.num_turns >= MAX_TURNS {
+                result.is_error = true;
+                result.error = Some(format!("agent did not finish within {MAX_TURNS} turns"));
+                return Ok(result);
+            }
+
+            let api_started = Instant::now();
+            let turn = match self.complete(&messages).await? {
+                Ok(turn) => turn,
+                Err(message) => {
+                    result.is_error = true;
+                    result.error = Some(message);
+                    return Ok(result);
+                }
+            };
+            result.duration_api_ms += api_started.elapsed().as_millis() as u64;
+            result.num_turns += 1;
+            result.input_tokens += turn.prompt_tokens;
+            result.output_tokens += turn.completion_tokens;
+            if !turn.model.is_empty() {
+                result.model = turn.model.clone();
+            }
+            if let Some(message) = turn.error {
+                result.is_error = true;
+                result.error = Some(message);
+                return Ok(result);
+            }
+
+            if let Some(budget) = self.opts.budget {
+                let usage = BudgetUsage::of(&result);
+                if let Some(limit) = budget.exceeded_by(&usage) {
+                    return Err(Error::BudgetExceeded(Box::new(BudgetExceeded::new(
+                        limit, result, &usage,
+                    ))));
+                }
+            }
+
+            if turn.tool_calls.is_empty() {
+                result.result_text = turn.content;
+                return Ok(result);
+            }
+
+            messages.push(json!({
+                "role": "assistant",
+                "content": (!turn.content.is_empty()).then_some(&turn.content),
+                "tool_calls": turn.tool_calls.iter().map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": call.arguments },
+                })).collect::<Vec<_>>(),
+            }));
+
+            for call in turn.tool_calls {
+                let mut tool = ClaudeToolCall {
+                    tool_name: call.name,
+                    tool_use_id: call.id,
+                    // Models occasionally emit arguments that are not JSON;
+                    // keep them so the tool can report the problem.
+                    input: serde_json::from_str(&call.arguments)
+                        .unwrap_or(Value::String(call.arguments)),
+                    output: None,
+                    ..Default::default()
+                };
+                let decision = self.opts.tool_hook.as_ref().map(|hook| hook.decide(&tool));
+                on_event(AgentStreamEvent::ToolUse(tool.clone()));
+                if let Some(decision) = decision {
+                    // The model waits on this loop, so holding the loop
+                    // holds the agent; there is no process to signal.
+                    let denied =
+                        crate::tool_hook::settle(decision, |_| std::future::ready(())).await;
+                    if let Some(reason) = denied {
+                        return Err(Error::ToolDenied {
+                            tool: tool.tool_name,
+                            reason,
+                        });
+                    }
+                }
+
+                let output = self.run_tool(&tool).await;
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": tool.tool_use_id,
+                    "content": output,
+                }));
+                tool.output = Some(output);
+                result.tool_calls.push(tool);
+            }
+        }
+    }
+
+    /// Stream one completion. The outer error is a transport failure; the
+    /// inner one is the server refusing the request, which ends the run
+    /// like a claude-code API error does.
+    async fn complete(
+        &self,
+        messages: &[Value],
+    ) -> Result<std::result::Result<OpenAiTurn, String>> {
+        let body = json!({
+            "model": self.model,
+            "messages": messages,
+            "tools": tool_definitions(),
+            "stream": true,
+            "stream_options": { "include_usage": true },
+        });
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let network_error = |e: reqwest::Error| {
+            Error::Network(format!("request to {} failed: {}", self.endpoint, e))
+        };
+
+        let response = request.send().await.map_err(network_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Ok(Err(format!("{}: {}", status, text.trim())));
+        }
+
+        let mut turn = OpenAiTurn::default();
+        let mut buf: Vec<u8> = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            buf.extend_from_slice(&chunk.map_err(network_error)?);
+            while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=pos).collect();
+                parse_openai_sse_line(&String::from_utf8_lossy(&line), &mut turn);
+            }
+        }
+        if !buf.is_empty() {
+            parse_openai_sse_line(&String::from_utf8_lossy(&buf), &mut turn);
+        }
+        Ok(Ok(turn))
+    }
+
+    /// Run one tool call in the sandbox. Failures go back to the model as
+    /// the tool's output so it can correct itself.
+    async fn run_tool(&self, tool: &ClaudeToolCall) -> String {
+        let arg = |key: &str| tool.input.get(key).and_then(|v| v.as_str());
+        let outcome = match tool.tool_name.as_str() {
+            "Bash" => match arg("command") {
+                Some(command) => {
+                    let script = format!("cd {WORKSPACE} || exit\n{command}");
+                    self.sandbox
+                        .exec_with_options("sh", &["-lc", &script], &[], self.opts.timeout_secs)
+                        .await
+                        .map(|out| {
+                            let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+                            text.push_str(&String::from_utf8_lossy(&out.stderr));
+                            if out.exit_code != 0 {
+                                text.push_str(&format!("\n[exit code {}]", out.exit_code));
+                            }
+                            text
+                        })
+                }
+                None => Err(Error::Config("missing `command`".into())),
+            },
+            "Read" => match arg("file_path") {
+                Some(path) => self
+                    .sandbox
+                    .read_file(&workspace_path(path))
+                    .await
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+                None => Err(Error::Config("missing `file_path`".into())),
+            },
+            "Write" => match (arg("file_path"), arg("content")) {
+                (Some(path), Some(content)) => {
+                    let path = workspace_path(path);
+                    self.sandbox
+                        .write_file(&path, content.as_bytes())
+                        .await
+                        .map(|()| format!("Wrote {} bytes to {}", content.len(), path))
+                }
+                _ => Err(Error::Config("missing `file_path` or `content`".into())),
+            },
+            other => Err(Error::Config(format!("unknown tool `{other}`"))),
+        };
+        match outcome {
+            Ok(output) => clip(output),
+            Err(e) => format!("error: {e}"),
+        }
+    }
+}
+
+/// Resolve a relative path against the workspace.
+fn workspace_path(path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{WORKSPACE}/{path}")
+    }
+}
+
+/// Cut `output` to [`MAX_TOOL_OUTPUT`] bytes on a char boundary.
+fn clip(mut output: String) -> String {
+    if output.len() > MAX_TOOL_OUTPUT {
+        let mut end = MAX_TOOL_OUTPUT;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n[output truncated]");
+    }
+    output
+}
+
+fn tool_definitions() -> Value {
+    let function = |name: &str, description: &str, properties: Value, required: &[&str]| {
+        json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": description,
+                "parameters": {
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                },
+            },
+        })
+    };
+    json!([
+        function(
+            "Bash",
+            "Run a shell command in /workspace and return its stdout and stderr.",
+            json!({ "command": { "type": "string" } }),
+            &["command"],
+        ),
+        function(
+            "Read",
+            "Read a file. Relative paths are under /workspace.",
+            json!({ "file_path": { "type": "string" } }),
+            &["file_path"],
+        ),
+        function(
+            "Write",
+            "Create or overwrite a file. Relative paths are under /workspace.",
+            json!({
+                "file_path": { "type": "string" },
+                "content": { "type": "string" },
+            }),
+            &["file_path", "content"],
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve each of `responses` as an SSE body to one request in turn, and
+    /// hand back the request bodies.
+    async fn serve(
+        responses: Vec<Vec<&'static str>>,
+    ) -> (String, tokio::task::JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for events in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut raw = Vec::new();
+                let body = loop {
+                    let mut buf = [0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&raw).into_owned();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse().unwrap())
+                        })
+                        .unwrap();
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                };
+                requests.push(serde_json::from_str(&body).unwrap());
+                let sse: String = events.iter().map(|e| format!("data: {e}\n\n")).collect();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n{sse}data: [DONE]\n\n"
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+            requests
+        });
+        (base_url, server)
+    }
+
+    #[tokio::test]
+    async fn test_runs_tool_calls_in_sandbox_until_answered() {
+        let (base_url, server) = serve(vec![
+            vec![
+                r#"{"model":"test-model","choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"Bash","arguments":"{\"command\":"}}]}}]}"#,
+                r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"echo hi\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+                r#"{"choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}"#,
+            ],
+            vec![
+                r#"{"model":"test-model","choices":[{"delta":{"content":"all "}}]}"#,
+                r#"{"choices":[{"delta":{"content":"done"},"finish_reason":"stop"}]}"#,
+                r#"{"choices":[],"usage":{"prompt_tokens":20,"completion_tokens":2}}"#,
+            ],
+        ])
+        .await;
+        let sandbox = Sandbox::mock().build().unwrap();
+        let provider = LlmProvider::openai_compatible(base_url, "test-model");
+
+        let mut seen = Vec::new();
+        let result = sandbox
+            .exec_agent_streaming(&provider, "say hi", AgentExecOpts::default(), |event| {
+                let AgentStreamEvent::ToolUse(call) = event;
+                seen.push(call.tool_summary());
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(seen, vec!["echo hi"]);
+        assert_eq!(result.result_text, "all done");
+        assert_eq!(result.model, "test-model");
+        assert_eq!(result.num_turns, 2);
+        assert_eq!((result.input_tokens, result.output_tokens), (30, 7));
+        assert_eq!(result.tool_calls.len(), 1);
+        assert_eq!(result.tool_calls[0].output.as_deref(), Some("hi\n"));
+        assert!(!result.is_error);
+
+        let requests = server.await.unwrap();
+        let followup = requests[1]["messages"].as_array().unwrap();
+        assert_eq!(followup[2]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(followup[3]["role"], "tool");
+        assert_eq!(followup[3]["tool_call_id"], "call_1");
+        assert_eq!(followup[3]["content"], "hi\n");
+    }
+
+    #[test]
+    fn test_clip_respects_char_boundaries() {
+        let output = "é".repeat(MAX_TOOL_OUTPUT);
+        let clipped = clip(output);
+        assert!(clipped.ends_with("[output truncated]"));
+        assert!(clipped.len() <= MAX_TOOL_OUTPUT + "\n[output truncated]".len());
+    }
+}
@@ -0,0 +1,175 @@
+//! JSON Schema enforcement for agent results.
+//!
+//! [`VoidBox::expect_json_schema`](crate::agent_box::VoidBox::expect_json_schema)
+//! turns a free-text agent into one that answers with a JSON value:
+//!
+//! 1. The schema is appended to the prompt, with an instruction to answer
+//!    with only the JSON.
+//! 2. The final result text is parsed — bare, inside a ```` ```json ````
+//!    fence, or as the outermost `{...}` / `[...]` in surrounding prose — and
+//!    validated against the schema.
+//! 3. On a mismatch the agent is asked again, with the validation errors and
+//!    its previous answer, up to the configured number of retries.
+//!
+//! A valid answer lands in
+//! [`StageResult::structured_output`](crate::pipeline::StageResult::structured_output).
+//! If every attempt fails, the stage fails like any other agent error.
+
+use serde_json::Value;
+
+/// Retries after the first answer, unless set with
+/// [`VoidBox::schema_retries`](crate::agent_box::VoidBox::schema_retries).
+pub const DEFAULT_SCHEMA_RETRIES: u32 = 2;
+
+/// Errors listed back to the agent per retry.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// A compiled output schema.
+pub(crate) struct OutputSchema {
+    schema: Value,
+    validator: jsonschema::Validator,
+}
+
+impl OutputSchema {
+    pub(crate) fn compile(schema: &Value) -> crate::Result<Self> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| crate::Error::Config(format!("invalid output JSON schema: {e}")))?;
+        Ok(Self {
+            schema: schema.clone(),
+            validator,
+        })
+    }
+
+    /// Instructions appended to the prompt.
+    pub(crate) fn instructions(&self) -> String {
+        format!(
+            "Your final answer must be a single JSON value that matches this JSON Schema:\n\
+             {}\n\
+             Reply with only that JSON, without any other text.",
+            serde_json::to_string_pretty(&self.schema).unwrap_or_default()
+        )
+    }
+
+    /// The JSON value in `text`, or why it does not match the schema.
+    pub(crate) fn check(&self, text: &str) -> std::result::Result<Value, Vec<String>> {
+        let value = extract_json(text)
+            .ok_or_else(|| vec!["the answer does not contain a JSON value".to_string()])?;
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .take(MAX_REPORTED_ERRORS)
+            .map(|e| match e.instance_path().as_str() {
+                "" => e.to_string(),
+                path => format!("{path}: {e}"),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Prompt asking the agent to correct `previous`.
+    pub(crate) fn retry_prompt(&self, previous: &str, errors: &[String]) -> String {
+        let mut prompt =
+            String::from("Your previous answer did not match the required JSON Schema:\n");
+        for error in errors {
+            prompt.push_str(&format!("- {error}\n"));
+        }
+        prompt.push_str(&format!(
+            "\n--- Previous answer ---\n{previous}\n--- End previous answer ---\n\n{}",
+            self.instructions()
+        ));
+        prompt
+    }
+}
+
+/// The JSON value `text` holds, tolerating a code fence or surrounding prose.
+fn extract_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+    if let Some(start) = text.find("```") {
+        let fenced = &text[start + 3..];
+        // Skip the info string (`json`) up to the end of the line.
+        let body = &fenced[fenced.find('\n')? + 1..];
+        if let Some(end) = body.find("```") {
+            if let Ok(value) = serde_json::from_str(body[..end].trim()) {
+                return Some(value);
+            }
+        }
+    }
+    for (open, close) in [('{', '}'), ('[', ']')] {
+        if let (Some(start), Some(end)) = (text.find(open), text.rfind(close)) {
+            if start < end {
+                if let Ok(value) = serde_json::from_str(&text[start..=end]) {
+                    return Some(value);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> OutputSchema {
+        OutputSchema::compile(&json!({
+            "type": "object",
+            "required": ["ticker", "score"],
+            "properties": {
+                "ticker": {"type": "string"},
+                "score": {"type": "number", "minimum": 0, "maximum": 1}
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_accepts_bare_fenced_and_embedded_json() {
+        let schema = schema();
+        for text in [
+            r#"{"ticker": "AAPL", "score": 0.7}"#,
+            "```json\n{\"ticker\": \"AAPL\", \"score\": 0.7}\n```",
+            "Here you go: {\"ticker\": \"AAPL\", \"score\": 0.7}. Done.",
+        ] {
+            assert_eq!(
+                schema.check(text).unwrap(),
+                json!({"ticker": "AAPL", "score": 0.7}),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_reports_schema_errors() {
+        let schema = schema();
+        let errors = schema.check(r#"{"ticker": 7, "score": 3}"#).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors.iter().any(|e| e.starts_with("/ticker: ")),
+            "{errors:?}"
+        );
+        assert!(
+            errors.iter().any(|e| e.starts_with("/score: ")),
+            "{errors:?}"
+        );
+
+        let errors = schema.check("I could not find the data.").unwrap_err();
+        assert_eq!(errors, ["the answer does not contain a JSON value"]);
+
+        let retry = schema.retry_prompt("I could not find the data.", &errors);
+        assert!(retry.contains("- the answer does not contain a JSON value"));
+        assert!(retry.contains("\"ticker\""));
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_schema() {
+        assert!(OutputSchema::compile(&json!({"type": "no-such-type"})).is_err());
+    }
+}
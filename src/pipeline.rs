@@ -15,10 +15,32 @@
 //! Fan-out merges all stage `result_text` values into a JSON array (`["...","..."]`) for the next stage.
 //! Fan-out results are collected in completion order (not input order)
 //!
+//! ## Concurrency
+//! Piped stages depend on the previous stage's output, so they run one after
+//! another. The boxes of a fan-out are independent and each runs in its own
+//! sandbox, all at once by default. [`Pipeline::max_parallel`] caps how many
+//! of those sandboxes run at the same time; the rest wait for a slot.
+//!
 //! ## Failure semantics
 //! - The pipeline stops early on the first failing stage.
 //! - A fan-out stops the pipeline if **any** box in the group fails.
 //!
+//! ## Budget
+//! A pipeline [`Budget`] covers the whole run. Each stage gets what earlier
+//! stages left over, split evenly across a fan-out, and never more than its
+//! own box's budget. A stage that goes over fails the pipeline with
+//! [`Error::BudgetExceeded`](crate::Error::BudgetExceeded).
+//!
+//! ## Caching
+//! With [`Pipeline::with_cache`], a stage whose Box and input hash to the same
+//! key as an earlier successful run is not run again: its stored output is
+//! reused, with zero cost, tokens and duration. The key covers the prompt,
+//! carry input, skills, provider and guest image digests (see
+//! [`VoidBox::cache_key`]). Failed stages are never cached, and neither are
+//! stages whose git workspace is not pinned to a commit id. Hits and misses
+//! are counted on [`PipelineResult`] and, when observed, exported as the
+//! `pipeline.stage.cache_hits` / `pipeline.stage.cache_misses` counters.
+//!
 //! ## Streaming vs non-streaming
 //! `run_streaming` delivers at least one output event per stage by emitting a synthetic
 //! `ExecOutputChunk` from the final `result_text` (in addition to any live output produced by the VM).
@@ -31,18 +53,26 @@
 //! - Avoid duplicating stage loops in `Pipeline` vs `ObservablePipeline`.
 //! - If you change carry semantics or fan-out merge format, update module docs and tests.
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
 
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::agent_box::VoidBox;
+use crate::agent_box::{BoxDryRun, VoidBox};
+use crate::budget::{Budget, BudgetUsage};
 use crate::guest::protocol::ExecOutputChunk;
 use crate::observe::claude::{create_otel_spans, AgentExecResult};
+use crate::observe::provenance::{RunKind, StepRecord};
+use crate::observe::slo::{StepOutcome, SLO_VIOLATION_EVENT};
 use crate::observe::telemetry::TelemetryBuffer;
-use crate::observe::tracer::SpanStatus;
-use crate::observe::{ObserveConfig, ObservedResult, Observer};
+use crate::observe::tracer::{SpanContext, SpanStatus};
+use crate::observe::{ObserveConfig, ObservedResult, Observer, RunManifest, SloMonitor, SloPolicy};
 use crate::persistence::{PersistenceProvider, RunEvent};
+use crate::sandbox::GitPatch;
 
 /// Result of running a full pipeline.
 #[derive(Debug)]
@@ -54,6 +84,10 @@ pub struct PipelineResult {
     pub stages: Vec<StageResult>,
     /// The final stage output text
     pub output: String,
+    /// Stages reused from the [stage cache](Pipeline::with_cache).
+    pub cache_hits: usize,
+    /// Stages looked up in the stage cache and run.
+    pub cache_misses: usize,
 }
 
 /// Result from a single pipeline stage.
@@ -65,6 +99,30 @@ pub struct StageResult {
     pub agent_result: AgentExecResult,
     /// Raw file output read from the Box (if any)
     pub file_output: Option<Vec<u8>>,
+    /// The answer as JSON, when the Box has an
+    /// [output schema](VoidBox::expect_json_schema) and the answer matched it.
+    pub structured_output: Option<serde_json::Value>,
+    /// What the agent changed in the workspace, when the Box
+    /// [captures a patch](VoidBox::capture_patch).
+    pub patch: Option<GitPatch>,
+    /// Whether this result was reused from the stage cache instead of run.
+    pub cached: bool,
+}
+
+impl StageResult {
+    /// [`structured_output`](Self::structured_output) deserialized as `T`.
+    pub fn output_as<T: serde::de::DeserializeOwned>(&self) -> crate::Result<T> {
+        let value = self.structured_output.clone().ok_or_else(|| {
+            crate::Error::StructuredOutput(match &self.agent_result.error {
+                Some(error) if self.agent_result.is_error => {
+                    format!("{}: {}", self.box_name, error)
+                }
+                _ => format!("{}: no output schema was set", self.box_name),
+            })
+        })?;
+        serde_json::from_value(value)
+            .map_err(|e| crate::Error::StructuredOutput(format!("{}: {}", self.box_name, e)))
+    }
 }
 
 impl PipelineResult {
@@ -106,6 +164,33 @@ impl PipelineResult {
     }
 }
 
+/// What [`Pipeline::dry_run`] found, one entry per Box of each stage.
+#[derive(Debug, Clone)]
+pub struct PipelineDryRun {
+    pub name: String,
+    pub stages: Vec<Vec<BoxDryRun>>,
+}
+
+impl PipelineDryRun {
+    /// Whether every skill of every Box passed validation.
+    pub fn is_ok(&self) -> bool {
+        self.stages.iter().flatten().all(BoxDryRun::is_ok)
+    }
+}
+
+impl std::fmt::Display for PipelineDryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pipeline {}:", self.name)?;
+        for (i, stage) in self.stages.iter().enumerate() {
+            writeln!(f, "stage {}:", i + 1)?;
+            for b in stage {
+                write!(f, "{b}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A single stage in a pipeline — either one Box or multiple Boxes in parallel.
 enum PipelineStage {
     /// A single Box executed sequentially.
@@ -119,6 +204,10 @@ enum PipelineStage {
 pub struct Pipeline {
     name: String,
     stages: Vec<PipelineStage>,
+    slo: Option<SloPolicy>,
+    budget: Option<Budget>,
+    cache: Option<StageCache>,
+    max_parallel: Option<usize>,
 }
 
 impl Pipeline {
@@ -127,6 +216,10 @@ impl Pipeline {
         Self {
             name: first.name.clone(),
             stages: vec![PipelineStage::Single(Box::new(first))],
+            slo: None,
+            budget: None,
+            cache: None,
+            max_parallel: None,
         }
     }
 
@@ -135,6 +228,10 @@ impl Pipeline {
         Self {
             name: name.into(),
             stages: vec![PipelineStage::Single(Box::new(first))],
+            slo: None,
+            budget: None,
+            cache: None,
+            max_parallel: None,
         }
     }
 
@@ -151,6 +248,36 @@ impl Pipeline {
         self
     }
 
+    /// Attach cost/latency SLOs, checked per stage and over the whole run.
+    ///
+    /// Evaluated only on the observed path ([`Pipeline::observe`]), whose
+    /// [`ObserveConfig`] carries the alert sinks.
+    pub fn slo(mut self, policy: SloPolicy) -> Self {
+        self.slo = Some(policy);
+        self
+    }
+
+    /// Limit cost, tokens and turns across the whole run. See the
+    /// [module docs](self#budget) for how it is shared between stages.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Run at most `n` sandboxes at once within a fan-out. See the
+    /// [module docs](self#concurrency). `0` is treated as `1`.
+    pub fn max_parallel(mut self, n: usize) -> Self {
+        self.max_parallel = Some(n.max(1));
+        self
+    }
+
+    /// Reuse stage outputs stored in `dir` when a stage's inputs are
+    /// unchanged. See the [module docs](self#caching).
+    pub fn with_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache = Some(StageCache { dir: dir.into() });
+        self
+    }
+
     /// Execute the pipeline: run each stage in order, piping output forward.
     ///
     /// For `PipelineStage::Single` stages, a single Box is booted and run.
@@ -159,7 +286,19 @@ impl Pipeline {
     /// array for the next stage.
     pub async fn run(self) -> crate::Result<PipelineResult> {
         let mut hook = NoopOutputHook;
-        run_pipeline_core(self.name, self.stages, &mut hook, None, None, None).await
+        run_pipeline_core(
+            self.name,
+            self.stages,
+            &mut hook,
+            None,
+            None,
+            self.budget,
+            self.cache.as_ref(),
+            self.max_parallel,
+            None,
+            None,
+        )
+        .await
     }
 
     /// Execute the pipeline with a streaming callback for output chunks.
@@ -174,7 +313,19 @@ impl Pipeline {
         F: FnMut(&str, &ExecOutputChunk) + Send,
     {
         let mut hook = StreamingOutputHook(on_output);
-        run_pipeline_core(self.name, self.stages, &mut hook, None, None, None).await
+        run_pipeline_core(
+            self.name,
+            self.stages,
+            &mut hook,
+            None,
+            None,
+            self.budget,
+            self.cache.as_ref(),
+            self.max_parallel,
+            None,
+            None,
+        )
+        .await
     }
 
     /// Number of stages in the pipeline.
@@ -187,6 +338,23 @@ impl Pipeline {
         self.stages.is_empty()
     }
 
+    /// Report what each stage would provision, without booting any VM.
+    /// See [`VoidBox::dry_run`].
+    pub fn dry_run(&self) -> PipelineDryRun {
+        let stages = self
+            .stages
+            .iter()
+            .map(|stage| match stage {
+                PipelineStage::Single(b) => vec![b.dry_run()],
+                PipelineStage::Parallel(boxes) => boxes.iter().map(VoidBox::dry_run).collect(),
+            })
+            .collect();
+        PipelineDryRun {
+            name: self.name.clone(),
+            stages,
+        }
+    }
+
     /// Execute the pipeline with a stage event sender for telemetry.
     ///
     /// Stage lifecycle events (`StageStarted`, `StageSucceeded`, `StageFailed`,
@@ -203,6 +371,10 @@ impl Pipeline {
             self.stages,
             &mut hook,
             None,
+            None,
+            self.budget,
+            self.cache.as_ref(),
+            self.max_parallel,
             stage_tx,
             telemetry_buffer,
         )
@@ -226,6 +398,10 @@ impl Pipeline {
             self.stages,
             &mut hook,
             None,
+            None,
+            self.budget,
+            self.cache.as_ref(),
+            self.max_parallel,
             stage_tx,
             telemetry_buffer,
         )
@@ -315,11 +491,16 @@ impl<F: FnMut(&str, &ExecOutputChunk) + Send> OutputHook for StreamingOutputHook
 /// Design: streaming and observability are orthogonal concerns injected via:
 /// - `OutputHook` for streaming callbacks
 /// - `Option<&Observer>` for tracing/metrics
+#[allow(clippy::too_many_arguments)]
 async fn run_pipeline_core(
     pipeline_name: String,
     pipeline_stages: Vec<PipelineStage>,
     output_hook: &mut dyn OutputHook,
     observer: Option<&Observer>,
+    slo: Option<&SloPolicy>,
+    budget: Option<Budget>,
+    cache: Option<&StageCache>,
+    max_parallel: Option<usize>,
     stage_tx: Option<UnboundedSender<RunEvent>>,
     telemetry_buffer: Option<TelemetryBuffer>,
 ) -> crate::Result<PipelineResult> {
@@ -339,14 +520,30 @@ async fn run_pipeline_core(
         root_span = Some((span, Instant::now()));
     }
 
+    let slo_monitor = match (slo, root_ctx.as_ref()) {
+        (Some(policy), Some(root)) => Some(SloMonitor::new(
+            &pipeline_name,
+            &root.trace_id,
+            policy.clone(),
+        )),
+        _ => None,
+    };
+
     let mut stages: Vec<StageResult> = Vec::new();
     let mut carry_data: Option<Vec<u8>> = None;
     let mut had_pipeline_error = false;
+    let mut spent = BudgetUsage::default();
+    let mut cache_hits = 0;
+    let mut cache_misses = 0;
 
     for (i, stage) in pipeline_stages.into_iter().enumerate() {
         let group_id = format!("g{}", i);
         match stage {
             PipelineStage::Single(agent_box) => {
+                let agent_box = match budget {
+                    Some(budget) => agent_box.cap_budget(budget.remaining(&spent)),
+                    None => *agent_box,
+                };
                 let box_name = agent_box.name.clone();
                 eprintln!(
                     "[pipeline] Stage {}/{}: [vm:{}] starting ...",
@@ -365,18 +562,43 @@ async fn run_pipeline_core(
                     ));
                 }
 
+                let cache_key = cache
+                    .map(|_| agent_box.cache_key(carry_data.as_deref()))
+                    .transpose()?
+                    .flatten();
+                let cached = cache.zip(cache_key.as_deref()).and_then(|(c, k)| c.get(k));
+
+                let started_at = SystemTime::now();
                 let stage_start = Instant::now();
-                let stage_result = agent_box
-                    .run(carry_data.as_deref(), telemetry_buffer.clone())
-                    .await?;
+                let stage_result = match cached {
+                    Some(result) => result,
+                    None => {
+                        agent_box
+                            .run(carry_data.as_deref(), telemetry_buffer.clone())
+                            .await?
+                    }
+                };
                 let elapsed = stage_start.elapsed();
+                if let (Some(cache), Some(key)) = (cache, cache_key.as_deref()) {
+                    cache.record(observer, key, &stage_result);
+                    if stage_result.cached {
+                        cache_hits += 1;
+                    } else {
+                        cache_misses += 1;
+                    }
+                }
+                spent.add(&BudgetUsage::of(&stage_result.agent_result));
 
                 output_hook.on_stage_result(&box_name, &stage_result);
 
                 if let (Some(t), Some(obs), Some(root)) =
                     (tracer.as_ref(), observer, root_ctx.as_ref())
                 {
-                    finish_single_stage_span(t, obs, root, &stage_result, elapsed);
+                    let stage_ctx =
+                        finish_stage_span(t, obs, root, &stage_result, started_at, elapsed);
+                    if let Some(monitor) = slo_monitor.as_ref() {
+                        record_slo_stage(obs, monitor, &stage_ctx, &stage_result, elapsed).await;
+                    }
                 }
 
                 carry_data = extract_carry_data(&stage_result);
@@ -425,12 +647,17 @@ async fn run_pipeline_core(
                 let names_pretty = names.join(" | ");
                 let names_compact = names.join("|"); // for span name
 
+                let slots = match max_parallel {
+                    Some(n) if n < boxes.len() => format!(", at most {n} at a time"),
+                    _ => String::new(),
+                };
                 eprintln!(
-                    "[pipeline] Stage {}/{}: fan-out [{}] ({} VMs in parallel)",
+                    "[pipeline] Stage {}/{}: fan-out [{}] ({} VMs in parallel{})",
                     i + 1,
                     total_stages,
                     names_pretty,
-                    boxes.len()
+                    boxes.len(),
+                    slots
                 );
 
                 // Optional fan-out parent span (only when observed)
@@ -444,14 +671,31 @@ async fn run_pipeline_core(
                     fan_out_span = Some((span, Instant::now()));
                 }
 
+                let share = budget.map(|budget| budget.remaining(&spent).split(boxes.len()));
+                let slots = max_parallel.map(|n| Arc::new(tokio::sync::Semaphore::new(n)));
                 let mut join_set = tokio::task::JoinSet::new();
                 for agent_box in boxes {
+                    let agent_box = match share {
+                        Some(share) => agent_box.cap_budget(share),
+                        None => agent_box,
+                    };
+                    let cache_key = cache
+                        .map(|_| agent_box.cache_key(carry_data.as_deref()))
+                        .transpose()?
+                        .flatten();
+                    let cached = cache.zip(cache_key.as_deref()).and_then(|(c, k)| c.get(k));
                     let input = carry_data.clone();
                     let stx = stage_tx.clone();
                     let gid = group_id.clone();
                     let bname = agent_box.name.clone();
                     let tb = telemetry_buffer.clone();
+                    let slots = slots.clone();
                     join_set.spawn(async move {
+                        // A cached box needs no sandbox, so takes no slot.
+                        let _slot = match (&slots, &cached) {
+                            (Some(slots), None) => slots.clone().acquire_owned().await.ok(),
+                            _ => None,
+                        };
                         // Emit StageStarted
                         if let Some(ref tx) = stx {
                             let _ = tx.send(crate::persistence::stage_event_started(
@@ -461,8 +705,12 @@ async fn run_pipeline_core(
                                 1,
                             ));
                         }
+                        let started_at = SystemTime::now();
                         let start = Instant::now();
-                        let result = agent_box.run(input.as_deref(), tb).await;
+                        let result = match cached {
+                            Some(result) => Ok(result),
+                            None => agent_box.run(input.as_deref(), tb).await,
+                        };
                         let elapsed_ms = start.elapsed().as_millis() as u64;
 
                         // Emit StageSucceeded or StageFailed
@@ -506,23 +754,38 @@ async fn run_pipeline_core(
                                 }
                             }
                         }
-                        result
+                        (cache_key, started_at, start.elapsed(), result)
                     });
                 }
 
                 let mut parallel_results: Vec<StageResult> = Vec::new();
                 let mut had_error = false;
 
-                while let Some(result) = join_set.join_next().await {
-                    let stage_result =
-                        result.map_err(|e| crate::Error::Guest(format!("Join error: {}", e)))??;
+                while let Some(joined) = join_set.join_next().await {
+                    let (cache_key, started_at, elapsed, result) =
+                        joined.map_err(|e| crate::Error::Guest(format!("Join error: {}", e)))?;
+                    let stage_result = result?;
+                    if let (Some(cache), Some(key)) = (cache, cache_key.as_deref()) {
+                        cache.record(observer, key, &stage_result);
+                        if stage_result.cached {
+                            cache_hits += 1;
+                        } else {
+                            cache_misses += 1;
+                        }
+                    }
+                    spent.add(&BudgetUsage::of(&stage_result.agent_result));
 
                     output_hook.on_stage_result(&stage_result.box_name, &stage_result);
 
                     if let (Some(t), Some(obs), Some(fo_ctx)) =
                         (tracer.as_ref(), observer, fan_out_ctx.as_ref())
                     {
-                        finish_parallel_stage_span(t, obs, fo_ctx, &stage_result);
+                        let stage_ctx =
+                            finish_stage_span(t, obs, fo_ctx, &stage_result, started_at, elapsed);
+                        if let Some(monitor) = slo_monitor.as_ref() {
+                            record_slo_stage(obs, monitor, &stage_ctx, &stage_result, elapsed)
+                                .await;
+                        }
                     }
 
                     eprintln!(
@@ -569,7 +832,18 @@ async fn run_pipeline_core(
         .map(|s| s.agent_result.result_text.clone())
         .unwrap_or_default();
 
+    if let (Some(obs), Some(monitor)) = (observer, slo_monitor.as_ref()) {
+        for alert in monitor.finish() {
+            obs.raise_slo_alert(&alert).await;
+        }
+    }
+
     if let (Some(t), Some((mut span, start))) = (tracer.as_ref(), root_span.take()) {
+        if let Some(monitor) = slo_monitor.as_ref() {
+            for alert in monitor.alerts() {
+                span.add_event_with_attrs(SLO_VIOLATION_EVENT, alert.event_attributes());
+            }
+        }
         span.duration = Some(start.elapsed());
         span.status = if had_pipeline_error {
             SpanStatus::Error("pipeline had errors".into())
@@ -590,44 +864,169 @@ async fn run_pipeline_core(
         name: pipeline_name,
         stages,
         output,
+        cache_hits,
+        cache_misses,
     })
 }
 
-/// Create and finish the OTel span for a single (sequential) stage.
-fn finish_single_stage_span(
+/// Version of the [`CachedStage`] entry format.
+const STAGE_CACHE_VERSION: u32 = 1;
+
+/// Stage results stored by [`Pipeline::with_cache`].
+///
+/// Each entry is `<key>.json`, plus `<key>.out` holding the stage's
+/// `file_output` if it had one. The JSON is written last, so an interrupted
+/// write leaves a miss rather than a partial entry.
+struct StageCache {
+    dir: PathBuf,
+}
+
+/// On-disk form of a cached [`StageResult`].
+#[derive(Serialize, Deserialize)]
+struct CachedStage {
+    version: u32,
+    box_name: String,
+    agent_result: AgentExecResult,
+    has_file_output: bool,
+    #[serde(default)]
+    structured_output: Option<serde_json::Value>,
+    #[serde(default)]
+    patch: Option<GitPatch>,
+}
+
+impl StageCache {
+    /// The stored result for `key`, with its usage zeroed since this run
+    /// spends nothing on it.
+    fn get(&self, key: &str) -> Option<StageResult> {
+        let entry = match std::fs::read(self.dir.join(format!("{key}.json"))) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!("stage cache: failed to read entry {key}: {e}");
+                return None;
+            }
+        };
+        let entry: CachedStage = match serde_json::from_slice(&entry) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("stage cache: ignoring corrupt entry {key}: {e}");
+                return None;
+            }
+        };
+        if entry.version != STAGE_CACHE_VERSION {
+            return None;
+        }
+        let file_output = if entry.has_file_output {
+            Some(std::fs::read(self.dir.join(format!("{key}.out"))).ok()?)
+        } else {
+            None
+        };
+        let mut agent_result = entry.agent_result;
+        agent_result.total_cost_usd = 0.0;
+        agent_result.input_tokens = 0;
+        agent_result.output_tokens = 0;
+        agent_result.num_turns = 0;
+        agent_result.duration_ms = 0;
+        agent_result.duration_api_ms = 0;
+        Some(StageResult {
+            box_name: entry.box_name,
+            agent_result,
+            file_output,
+            structured_output: entry.structured_output,
+            patch: entry.patch,
+            cached: true,
+        })
+    }
+
+    fn put(&self, key: &str, result: &StageResult) -> crate::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        if let Some(data) = &result.file_output {
+            let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+            std::io::Write::write_all(&mut file, data)?;
+            file.persist(self.dir.join(format!("{key}.out")))
+                .map_err(|e| e.error)?;
+        }
+        let entry = CachedStage {
+            version: STAGE_CACHE_VERSION,
+            box_name: result.box_name.clone(),
+            agent_result: result.agent_result.clone(),
+            has_file_output: result.file_output.is_some(),
+            structured_output: result.structured_output.clone(),
+            patch: result.patch.clone(),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        serde_json::to_writer(&mut file, &entry)?;
+        file.persist(self.dir.join(format!("{key}.json")))
+            .map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Count the lookup of `key`, and store `result` if it was a successful run.
+    fn record(&self, observer: Option<&Observer>, key: &str, result: &StageResult) {
+        let box_name = result.box_name.as_str();
+        if result.cached {
+            eprintln!("[pipeline] [vm:{box_name}] cache hit ({})", &key[..12]);
+        } else if !result.agent_result.is_error {
+            if let Err(e) = self.put(key, result) {
+                tracing::warn!("stage cache: failed to store {box_name}: {e}");
+            }
+        }
+        if let Some(obs) = observer {
+            let counter = if result.cached {
+                "pipeline.stage.cache_hits"
+            } else {
+                "pipeline.stage.cache_misses"
+            };
+            obs.metrics()
+                .add_counter(counter, 1.0, &[("stage", box_name)]);
+        }
+    }
+}
+
+/// Create and finish the OTel span for one stage, under `parent` (the
+/// pipeline root, or the fan-out span for a box of a fan-out).
+///
+/// The span is built once the stage is done, so its start is backdated to
+/// `started_at`; concurrent fan-out boxes then overlap in the trace as they
+/// did in time.
+fn finish_stage_span(
     tracer: &crate::observe::tracer::Tracer,
     observer: &Observer,
-    root_ctx: &crate::observe::tracer::SpanContext,
+    parent: &crate::observe::tracer::SpanContext,
     stage_result: &StageResult,
+    started_at: SystemTime,
     elapsed: std::time::Duration,
-) {
+) -> SpanContext {
     let mut span =
-        tracer.start_span_with_parent(&format!("stage:{}", stage_result.box_name), root_ctx);
+        tracer.start_span_with_parent(&format!("stage:{}", stage_result.box_name), parent);
+    span.start_time = started_at;
     let ctx = span.context.clone();
     instrument_stage_result(stage_result, &ctx, observer);
     set_stage_span_attrs(&mut span, stage_result);
     span.duration = Some(elapsed);
     span.status = stage_status(stage_result);
     tracer.finish_span(span);
+    ctx
 }
 
-/// Create and finish the OTel span for one box within a fan-out stage.
-fn finish_parallel_stage_span(
-    tracer: &crate::observe::tracer::Tracer,
+/// Feed a finished stage into the SLO monitor and raise any violations.
+async fn record_slo_stage(
     observer: &Observer,
-    fan_out_ctx: &crate::observe::tracer::SpanContext,
+    monitor: &SloMonitor,
+    stage_ctx: &SpanContext,
     stage_result: &StageResult,
+    elapsed: std::time::Duration,
 ) {
-    let mut span =
-        tracer.start_span_with_parent(&format!("stage:{}", stage_result.box_name), fan_out_ctx);
-    let ctx = span.context.clone();
-    instrument_stage_result(stage_result, &ctx, observer);
-    set_stage_span_attrs(&mut span, stage_result);
-    span.duration = Some(std::time::Duration::from_millis(
-        stage_result.agent_result.duration_ms,
-    ));
-    span.status = stage_status(stage_result);
-    tracer.finish_span(span);
+    let alerts = monitor.record_step(StepOutcome {
+        name: &stage_result.box_name,
+        span: stage_ctx,
+        duration: elapsed,
+        succeeded: !stage_result.agent_result.is_error,
+        cost_usd: stage_result.agent_result.total_cost_usd,
+    });
+    for alert in alerts {
+        observer.raise_slo_alert(&alert).await;
+    }
 }
 
 fn stage_status(result: &StageResult) -> SpanStatus {
@@ -668,6 +1067,7 @@ impl ObservablePipeline {
     /// Execute the observed pipeline, instrumenting each stage with spans and metrics.
     pub async fn run(self) -> crate::Result<ObservedResult<PipelineResult>> {
         let observer = self.observer;
+        let provenance = PipelineProvenance::start(&self.pipeline);
 
         let mut hook = NoopOutputHook;
         let result = run_pipeline_core(
@@ -675,12 +1075,17 @@ impl ObservablePipeline {
             self.pipeline.stages,
             &mut hook,
             Some(&observer),
+            self.pipeline.slo.as_ref(),
+            self.pipeline.budget,
+            self.pipeline.cache.as_ref(),
+            self.pipeline.max_parallel,
             None,
             None,
         )
         .await?;
 
-        Ok(ObservedResult::new(result, &observer))
+        let manifest = provenance.finish(&result);
+        Ok(ObservedResult::new(result, &observer).with_manifest(manifest, &observer))
     }
 
     /// Execute the observed pipeline with a streaming callback for output chunks.
@@ -692,6 +1097,7 @@ impl ObservablePipeline {
         F: FnMut(&str, &ExecOutputChunk) + Send,
     {
         let observer = self.observer;
+        let provenance = PipelineProvenance::start(&self.pipeline);
 
         let mut hook = StreamingOutputHook(on_output);
         let result = run_pipeline_core(
@@ -699,12 +1105,66 @@ impl ObservablePipeline {
             self.pipeline.stages,
             &mut hook,
             Some(&observer),
+            self.pipeline.slo.as_ref(),
+            self.pipeline.budget,
+            self.pipeline.cache.as_ref(),
+            self.pipeline.max_parallel,
             None,
             None,
         )
         .await?;
 
-        Ok(ObservedResult::new(result, &observer))
+        let manifest = provenance.finish(&result);
+        Ok(ObservedResult::new(result, &observer).with_manifest(manifest, &observer))
+    }
+}
+
+/// A pipeline's [`RunManifest`] in the making: the Boxes are consumed by
+/// the run, so their part is recorded up front.
+struct PipelineProvenance {
+    manifest: RunManifest,
+    prompts: HashMap<String, String>,
+    started: Instant,
+}
+
+impl PipelineProvenance {
+    fn start(pipeline: &Pipeline) -> Self {
+        let mut manifest = RunManifest::new(
+            RunKind::Pipeline,
+            &pipeline.name,
+            SystemTime::now(),
+            Duration::ZERO,
+        );
+        let mut prompts = HashMap::new();
+        for stage in &pipeline.stages {
+            let boxes = match stage {
+                PipelineStage::Single(vbox) => std::slice::from_ref(vbox.as_ref()),
+                PipelineStage::Parallel(boxes) => boxes.as_slice(),
+            };
+            for vbox in boxes {
+                vbox.record_provenance(&mut manifest);
+                prompts.insert(vbox.name.clone(), vbox.prompt.clone());
+            }
+        }
+        Self {
+            manifest,
+            prompts,
+            started: Instant::now(),
+        }
+    }
+
+    fn finish(mut self, result: &PipelineResult) -> RunManifest {
+        self.manifest.duration_ms = self.started.elapsed().as_millis() as u64;
+        self.manifest.steps = result
+            .stages
+            .iter()
+            .map(|stage| StepRecord {
+                name: stage.box_name.clone(),
+                duration_ms: stage.agent_result.duration_ms,
+                prompt: self.prompts.get(&stage.box_name).cloned(),
+            })
+            .collect();
+        self.manifest
     }
 }
 
@@ -836,6 +1296,7 @@ mod tests {
             is_error: true,
             error: None,
             tool_calls: Vec::new(),
+            ..Default::default()
         };
         assert!(looks_like_login_error(&r));
     }
@@ -855,6 +1316,7 @@ mod tests {
             is_error: true,
             error: Some("Please run /login".into()),
             tool_calls: Vec::new(),
+            ..Default::default()
         };
         assert!(looks_like_login_error(&r));
     }
@@ -874,7 +1336,38 @@ mod tests {
             is_error: true,
             error: Some("rate limit exceeded".into()),
             tool_calls: Vec::new(),
+            ..Default::default()
         };
         assert!(!looks_like_login_error(&r));
     }
+
+    fn mock_box(name: &str) -> VoidBox {
+        VoidBox::new(name)
+            .skill(crate::skill::Skill::agent("claude-code"))
+            .prompt("Do something")
+            .mock()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_budget_spans_stages() {
+        let budget = Budget::default().max_turns(1);
+
+        let result = Pipeline::from(mock_box("one")).budget(budget).run().await;
+        assert!(result.is_ok(), "{result:?}");
+
+        // The first stage spends the only turn, leaving none for the second.
+        let err = Pipeline::from(mock_box("one"))
+            .pipe(mock_box("two"))
+            .budget(budget)
+            .run()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, crate::Error::BudgetExceeded(ref e)
+                if e.limit == crate::budget::BudgetLimit::Turns { limit: 0, used: 1 }),
+            "{err:?}"
+        );
+    }
 }
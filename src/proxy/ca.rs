@@ -44,11 +44,26 @@ pub struct ProxyCa {
     ca_key: KeyPair,
     /// Hosts this CA is name-constrained to, mirrored for leaf-SAN validation.
     allowed_upstreams: Vec<String>,
+    /// `false` for a CA from [`ProxyCa::generate_unconstrained`], which mints a
+    /// leaf for any host.
+    constrained: bool,
 }
 
 impl ProxyCa {
     /// Generate a fresh per-sandbox CA name-constrained to `allowed_upstreams`.
     pub fn generate(allowed_upstreams: Vec<String>) -> Result<Self> {
+        Self::build(allowed_upstreams, true)
+    }
+
+    /// Generate a fresh CA with no name constraints, for the HTTP recording
+    /// proxy ([`crate::proxy::recording`]): it terminates TLS for whatever hosts
+    /// the guest talks to, which are not known up front. Only for test-time
+    /// recording and replay — never pair it with credential injection.
+    pub fn generate_unconstrained() -> Result<Self> {
+        Self::build(Vec::new(), false)
+    }
+
+    fn build(allowed_upstreams: Vec<String>, constrained: bool) -> Result<Self> {
         let ca_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)
             .map_err(|e| Error::Network(format!("proxy CA keygen failed: {e}")))?;
 
@@ -62,13 +77,15 @@ impl ProxyCa {
             KeyUsagePurpose::KeyCertSign,
             KeyUsagePurpose::DigitalSignature,
         ];
-        params.name_constraints = Some(NameConstraints {
-            permitted_subtrees: allowed_upstreams
-                .iter()
-                .map(|host| GeneralSubtree::DnsName(host.clone()))
-                .collect(),
-            excluded_subtrees: Vec::new(),
-        });
+        if constrained {
+            params.name_constraints = Some(NameConstraints {
+                permitted_subtrees: allowed_upstreams
+                    .iter()
+                    .map(|host| GeneralSubtree::DnsName(host.clone()))
+                    .collect(),
+                excluded_subtrees: Vec::new(),
+            });
+        }
 
         let ca_cert = params
             .self_signed(&ca_key)
@@ -80,6 +97,7 @@ impl ProxyCa {
             ca_cert,
             ca_key,
             allowed_upstreams,
+            constrained,
         })
     }
 
@@ -90,9 +108,11 @@ impl ProxyCa {
 
     /// Whether `host` is within this CA's name constraints.
     pub fn permits_host(&self, host: &str) -> bool {
-        self.allowed_upstreams
-            .iter()
-            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        !self.constrained
+            || self
+                .allowed_upstreams
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host))
     }
 
     /// Mint a leaf certificate + signing key for `host`, which must be within
@@ -165,6 +185,7 @@ impl std::fmt::Debug for ProxyCa {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ProxyCa")
             .field("allowed_upstreams", &self.allowed_upstreams)
+            .field("constrained", &self.constrained)
             .finish_non_exhaustive()
     }
 }
@@ -218,6 +239,13 @@ mod tests {
         assert!(ca.mint_certified_key("evil.example.com").is_err());
     }
 
+    #[test]
+    fn unconstrained_ca_mints_any_host() {
+        let ca = ProxyCa::generate_unconstrained().expect("generate CA");
+        assert!(ca.permits_host("example.com"));
+        assert!(ca.mint_certified_key("example.com").is_ok());
+    }
+
     #[test]
     fn server_config_builds() {
         let ca = Arc::new(ProxyCa::generate(vec!["api.anthropic.com".into()]).expect("CA"));
@@ -72,6 +72,7 @@ use tracing::debug;
 pub mod ca;
 pub mod injector;
 pub mod provision;
+pub mod recording;
 pub mod server;
 pub mod ssrf;
 pub mod token;
@@ -82,6 +83,7 @@ pub use provision::{
     assert_no_real_credential, build_guest_provisioning, render_guest_hosts, ProxiedUpstream,
     GUEST_HOSTS_PATH,
 };
+pub use recording::{HttpExchange, HttpRecording, HttpRecordingProxy, RecordedBody};
 pub use server::{start_proxy, ProxyHandle, SandboxBinding};
 pub use token::{ProxyToken, PROXY_TOKEN_HEADER};
 
@@ -87,8 +87,10 @@ impl ProxiedUpstream {
             // `https://host:port` redirect would drop — both need handling and a
             // VM test before it ships. Codex API-key mode needs `config.toml`
             // redirection, so it lands in M1b with the rest of codex. Local + OAuth
-            // providers inject no host-held key here.
+            // providers inject no host-held key here, and OpenAi's key never
+            // leaves the host.
             LlmProvider::Custom { .. }
+            | LlmProvider::OpenAi { .. }
             | LlmProvider::Codex
             | LlmProvider::ClaudePersonal
             | LlmProvider::Ollama { .. }
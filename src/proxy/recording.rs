@@ -0,0 +1,754 @@
+//! HTTP(S) recording proxy for deterministic agent tests.
+//!
+//! An explicit forward proxy the guest reaches through `HTTP_PROXY` /
+//! `HTTPS_PROXY`. Plain-HTTP requests arrive in absolute form; HTTPS arrives
+//! as a `CONNECT` tunnel whose TLS the proxy terminates with a per-sandbox CA
+//! (see [`ProxyCa::generate_unconstrained`]) that the guest is told to trust.
+//! Every exchange then goes through one of two modes:
+//!
+//! - **Record** forwards the request to the real upstream and appends the
+//!   request/response pair to a cassette file as one JSON line.
+//! - **Replay** answers from a cassette and never opens an upstream
+//!   connection. Requests match on method, URL, and body; repeated identical
+//!   requests get their recorded responses in order, the last one repeating
+//!   once they run out. An unmatched request gets a `502`.
+//!
+//! Bodies are buffered whole (up to [`MAX_RECORDED_BODY_BYTES`]), so a
+//! streamed (SSE) response reaches the guest only once it completes.
+//! Credential-bearing request headers are never written to a cassette.
+//!
+//! The proxy listens on the SLIRP gateway, which the sandbox's
+//! [`NetworkPolicy`] exempts, so record mode applies the egress controls
+//! itself: upstream names resolve through the [`SsrfGuardResolver`], IP
+//! literals in the internal ranges are refused, and the sandbox's policy is
+//! evaluated against every upstream host and port before forwarding.
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use http::header::{CONTENT_LENGTH, HOST};
+use http::uri::Authority;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode};
+use http_body_util::{BodyExt, Empty, Full, Limited};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::backend::{guest_accessible_bind_addr, NetworkPolicy, PolicyAction};
+use crate::error::{Error, Result};
+use crate::proxy::server::{is_hop_by_hop, strip_hop_by_hop, text_response, ProxyBody};
+use crate::proxy::ssrf::{is_internal_ip, SsrfGuardResolver};
+use crate::proxy::ProxyCa;
+
+/// Guest path the recording proxy's CA PEM is written to (an allowed guest
+/// write root, like the credential proxy's CA).
+pub const GUEST_RECORDING_CA_PATH: &str = "/home/sandbox/.voidbox-http-recording-ca.pem";
+
+/// Largest request or response body the proxy buffers. Larger bodies are
+/// refused rather than recorded truncated.
+pub const MAX_RECORDED_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Same bound as the credential proxy on hyper's header read buffer.
+const MAX_HEADER_BUF_BYTES: usize = 64 * 1024;
+
+/// Request headers left out of a cassette so recordings never carry
+/// credentials.
+const REDACTED_REQUEST_HEADERS: &[&str] = &["authorization", "cookie", "x-api-key"];
+
+/// Connect timeout for record-mode upstream requests.
+const UPSTREAM_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Whether a sandbox records its outbound HTTP traffic or replays it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpRecording {
+    /// Forward to the real upstreams and write every exchange to this file.
+    Record(PathBuf),
+    /// Serve exchanges from this file without network access.
+    Replay(PathBuf),
+}
+
+/// A message body in a cassette: text when it is valid UTF-8, so recordings
+/// of JSON APIs stay readable and diffable, hex otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordedBody {
+    Text(String),
+    Hex(String),
+}
+
+impl RecordedBody {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_string()),
+            Err(_) => Self::Hex(bytes.iter().map(|b| format!("{b:02x}")).collect()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Text(text) => Ok(text.as_bytes().to_vec()),
+            Self::Hex(hex) => (0..hex.len())
+                .step_by(2)
+                .map(|i| {
+                    hex.get(i..i + 2)
+                        .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                        .ok_or_else(|| Error::Config("invalid hex body in HTTP recording".into()))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One recorded request/response pair: a line of a cassette file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpExchange {
+    pub method: String,
+    /// Full request URL; default ports are omitted.
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<RecordedBody>,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: RecordedBody,
+}
+
+impl HttpExchange {
+    fn request_body_bytes(&self) -> Result<Vec<u8>> {
+        self.request_body
+            .as_ref()
+            .map_or(Ok(Vec::new()), RecordedBody::to_bytes)
+    }
+
+    fn to_response(&self) -> Response<ProxyBody> {
+        let body = match self.response_body.to_bytes() {
+            Ok(body) => body,
+            Err(e) => return text_response(StatusCode::BAD_GATEWAY, &e.to_string()),
+        };
+        let mut builder = Response::builder().status(self.status);
+        if let Some(headers) = builder.headers_mut() {
+            for (name, value) in &self.response_headers {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) {
+                    headers.append(name, value);
+                }
+            }
+        }
+        builder
+            .body(full_body(body))
+            .unwrap_or_else(|_| text_response(StatusCode::BAD_GATEWAY, "malformed recording"))
+    }
+}
+
+type RequestKey = (String, String, Vec<u8>);
+
+/// Recorded exchanges, indexed for replay.
+#[derive(Debug)]
+pub struct Cassette {
+    exchanges: Vec<HttpExchange>,
+    /// Unserved exchange indices per request, in recorded order.
+    queues: Mutex<HashMap<RequestKey, VecDeque<usize>>>,
+}
+
+impl Cassette {
+    /// Load a cassette written by a recording run.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            Error::Config(format!(
+                "failed to open HTTP recording {}: {e}",
+                path.display()
+            ))
+        })?;
+        let mut exchanges = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange = serde_json::from_str(&line).map_err(|e| {
+                Error::Config(format!(
+                    "invalid exchange on line {} of {}: {e}",
+                    index + 1,
+                    path.display()
+                ))
+            })?;
+            exchanges.push(exchange);
+        }
+        Self::new(exchanges)
+    }
+
+    pub fn new(exchanges: Vec<HttpExchange>) -> Result<Self> {
+        let mut queues: HashMap<RequestKey, VecDeque<usize>> = HashMap::new();
+        for (index, exchange) in exchanges.iter().enumerate() {
+            let key = (
+                exchange.method.to_ascii_uppercase(),
+                exchange.url.clone(),
+                exchange.request_body_bytes()?,
+            );
+            queues.entry(key).or_default().push_back(index);
+        }
+        Ok(Self {
+            exchanges,
+            queues: Mutex::new(queues),
+        })
+    }
+
+    pub fn exchanges(&self) -> &[HttpExchange] {
+        &self.exchanges
+    }
+
+    /// The next recorded exchange for a request, or `None` if it was never
+    /// recorded.
+    pub fn next_response(&self, method: &str, url: &str, body: &[u8]) -> Option<&HttpExchange> {
+        let key = (method.to_ascii_uppercase(), url.to_string(), body.to_vec());
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.get_mut(&key)?;
+        let index = if queue.len() > 1 {
+            queue.pop_front()?
+        } else {
+            *queue.front()?
+        };
+        Some(&self.exchanges[index])
+    }
+}
+
+enum Mode {
+    Record {
+        upstream: reqwest::Client,
+        guard: UpstreamGuard,
+        file: Mutex<File>,
+    },
+    Replay(Cassette),
+}
+
+/// Egress checks applied to a record-mode upstream before forwarding.
+struct UpstreamGuard {
+    network_policy: Option<NetworkPolicy>,
+    /// Refuse IP-literal upstreams in the internal ranges, which `reqwest`
+    /// connects to without consulting the SSRF resolver.
+    refuse_internal_literals: bool,
+}
+
+struct RecordingState {
+    acceptor: TlsAcceptor,
+    mode: Mode,
+}
+
+/// A running recording proxy. Stops when dropped.
+pub struct HttpRecordingProxy {
+    port: u16,
+    ca_pem: String,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for HttpRecordingProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpRecordingProxy")
+            .field("port", &self.port)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpRecordingProxy {
+    /// Bind a guest-reachable listener and start serving `recording`. Record
+    /// mode truncates the cassette; replay mode loads it up front so a
+    /// missing or corrupt file fails here rather than mid-run.
+    ///
+    /// `network_policy` is the sandbox's egress policy; record mode refuses
+    /// any upstream it denies, with a `403` to the guest.
+    pub async fn start(
+        recording: &HttpRecording,
+        network_policy: Option<NetworkPolicy>,
+    ) -> Result<Self> {
+        let upstream = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .connect_timeout(UPSTREAM_CONNECT_TIMEOUT)
+            .dns_resolver(Arc::new(SsrfGuardResolver))
+            .build()
+            .map_err(|e| Error::Network(format!("recording proxy client build failed: {e}")))?;
+        let guard = UpstreamGuard {
+            network_policy,
+            refuse_internal_literals: true,
+        };
+        Self::launch(recording, upstream, guard).await
+    }
+
+    /// Like [`start`](Self::start), but record mode forwards with `upstream`
+    /// and allows internal IP literals. Exposed so tests can point the proxy
+    /// at a loopback mock; `network_policy` is still enforced.
+    pub async fn with_upstream(
+        recording: &HttpRecording,
+        network_policy: Option<NetworkPolicy>,
+        upstream: reqwest::Client,
+    ) -> Result<Self> {
+        let guard = UpstreamGuard {
+            network_policy,
+            refuse_internal_literals: false,
+        };
+        Self::launch(recording, upstream, guard).await
+    }
+
+    async fn launch(
+        recording: &HttpRecording,
+        upstream: reqwest::Client,
+        guard: UpstreamGuard,
+    ) -> Result<Self> {
+        let mode = match recording {
+            HttpRecording::Record(path) => {
+                let file = File::create(path).map_err(|e| {
+                    Error::Config(format!(
+                        "failed to create HTTP recording {}: {e}",
+                        path.display()
+                    ))
+                })?;
+                Mode::Record {
+                    upstream,
+                    guard,
+                    file: Mutex::new(file),
+                }
+            }
+            HttpRecording::Replay(path) => Mode::Replay(Cassette::load(path)?),
+        };
+
+        // CA keygen and the server config are CPU-bound; keep them off the
+        // async runtime, like the credential proxy.
+        let ca = tokio::task::spawn_blocking(ProxyCa::generate_unconstrained)
+            .await
+            .map_err(|e| Error::Network(format!("recording proxy CA task join failed: {e}")))??;
+        let ca = Arc::new(ca);
+        let ca_pem = ca.ca_cert_pem().to_string();
+        let server_config = tokio::task::spawn_blocking(move || ca.server_config())
+            .await
+            .map_err(|e| Error::Network(format!("recording proxy config task join failed: {e}")))?;
+
+        let listener = TcpListener::bind(guest_accessible_bind_addr(0))
+            .await
+            .map_err(|e| Error::Network(format!("recording proxy bind failed: {e}")))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| Error::Network(format!("recording proxy addr failed: {e}")))?
+            .port();
+
+        let state = Arc::new(RecordingState {
+            acceptor: TlsAcceptor::from(server_config),
+            mode,
+        });
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let task = tokio::spawn(accept_loop(listener, state, shutdown_rx));
+        info!(port, ?recording, "HTTP recording proxy started");
+        Ok(Self {
+            port,
+            ca_pem,
+            shutdown,
+            task,
+        })
+    }
+
+    /// Host-side port the proxy listens on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// PEM of the CA the guest must trust for HTTPS.
+    pub fn ca_cert_pem(&self) -> &str {
+        &self.ca_pem
+    }
+
+    /// Guest env that routes HTTP clients through the proxy at
+    /// `gateway_ip` and trusts its CA, which is expected at
+    /// [`GUEST_RECORDING_CA_PATH`].
+    pub fn guest_env(&self, gateway_ip: &str) -> Vec<(String, String)> {
+        let proxy_url = format!("http://{gateway_ip}:{}", self.port);
+        let mut env: Vec<(String, String)> =
+            ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]
+                .into_iter()
+                .map(|key| (key.to_string(), proxy_url.clone()))
+                .collect();
+        for key in ["NO_PROXY", "no_proxy"] {
+            env.push((key.to_string(), "localhost,127.0.0.1".to_string()));
+        }
+        for key in [
+            "SSL_CERT_FILE",
+            "CURL_CA_BUNDLE",
+            "REQUESTS_CA_BUNDLE",
+            "NODE_EXTRA_CA_CERTS",
+        ] {
+            env.push((key.to_string(), GUEST_RECORDING_CA_PATH.to_string()));
+        }
+        env
+    }
+}
+
+impl Drop for HttpRecordingProxy {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+        self.task.abort();
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    state: Arc<RecordingState>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((stream, _peer)) => {
+                        tokio::spawn(serve_client(stream, state.clone()));
+                    }
+                    Err(e) => warn!("recording proxy accept error: {e}"),
+                }
+            }
+        }
+    }
+}
+
+/// Serve one guest connection: absolute-form HTTP requests, or `CONNECT`.
+async fn serve_client(stream: TcpStream, state: Arc<RecordingState>) {
+    let service = service_fn(move |req: Request<Incoming>| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(route_request(req, state).await) }
+    });
+    if let Err(e) = http1::Builder::new()
+        .max_buf_size(MAX_HEADER_BUF_BYTES)
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades()
+        .await
+    {
+        debug!("recording proxy connection ended: {e}");
+    }
+}
+
+async fn route_request(req: Request<Incoming>, state: Arc<RecordingState>) -> Response<ProxyBody> {
+    let Some(authority) = req.uri().authority().cloned() else {
+        return text_response(
+            StatusCode::BAD_REQUEST,
+            "recording proxy expects absolute-form or CONNECT requests",
+        );
+    };
+    if req.method() != Method::CONNECT {
+        return handle_exchange(req, "http", &authority, &state).await;
+    }
+
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(upgraded) => {
+                if let Err(e) = serve_tunnel(TokioIo::new(upgraded), authority, state).await {
+                    debug!("recording proxy tunnel ended: {e}");
+                }
+            }
+            Err(e) => debug!("recording proxy CONNECT upgrade failed: {e}"),
+        }
+    });
+    Response::new(Empty::new().map_err(|never| match never {}).boxed())
+}
+
+/// Terminate the guest's TLS inside a `CONNECT` tunnel and serve the HTTPS
+/// requests carried over it.
+async fn serve_tunnel(
+    tunnel: TokioIo<hyper::upgrade::Upgraded>,
+    authority: Authority,
+    state: Arc<RecordingState>,
+) -> Result<()> {
+    let tls = state
+        .acceptor
+        .accept(tunnel)
+        .await
+        .map_err(|e| Error::Network(format!("recording proxy TLS handshake failed: {e}")))?;
+    let service = service_fn(move |req: Request<Incoming>| {
+        let state = state.clone();
+        let authority = authority.clone();
+        async move { Ok::<_, Infallible>(handle_exchange(req, "https", &authority, &state).await) }
+    });
+    http1::Builder::new()
+        .max_buf_size(MAX_HEADER_BUF_BYTES)
+        .serve_connection(TokioIo::new(tls), service)
+        .await
+        .map_err(|e| Error::Network(format!("recording proxy HTTPS serve failed: {e}")))
+}
+
+async fn handle_exchange(
+    req: Request<Incoming>,
+    scheme: &str,
+    authority: &Authority,
+    state: &RecordingState,
+) -> Response<ProxyBody> {
+    let (parts, body) = req.into_parts();
+    let body = match Limited::new(body, MAX_RECORDED_BODY_BYTES).collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            return text_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("request body not recordable: {e}"),
+            )
+        }
+    };
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let url = request_url(scheme, authority, path);
+
+    match &state.mode {
+        Mode::Replay(cassette) => {
+            match cassette.next_response(parts.method.as_str(), &url, &body) {
+                Some(exchange) => exchange.to_response(),
+                None => {
+                    warn!(method = %parts.method, %url, "no recorded response");
+                    text_response(
+                        StatusCode::BAD_GATEWAY,
+                        &format!("no recorded response for {} {url}", parts.method),
+                    )
+                }
+            }
+        }
+        Mode::Record {
+            upstream,
+            guard,
+            file,
+        } => {
+            if let Err(reason) = check_upstream(guard, scheme, authority).await {
+                warn!(%url, "recording proxy refused upstream: {reason}");
+                return text_response(StatusCode::FORBIDDEN, &reason);
+            }
+            let mut headers = parts.headers;
+            strip_hop_by_hop(&mut headers);
+            headers.remove(HOST);
+            headers.remove(CONTENT_LENGTH);
+            let exchange = match forward(upstream, &parts.method, &url, &headers, body).await {
+                Ok(exchange) => exchange,
+                Err(e) => {
+                    warn!(%url, "recording proxy upstream request failed: {e}");
+                    return text_response(StatusCode::BAD_GATEWAY, "upstream request failed");
+                }
+            };
+            if let Err(e) = append_exchange(file, &exchange) {
+                warn!(%url, "failed to write HTTP recording: {e}");
+            }
+            exchange.to_response()
+        }
+    }
+}
+
+async fn forward(
+    upstream: &reqwest::Client,
+    method: &Method,
+    url: &str,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Result<HttpExchange> {
+    let request_headers = headers
+        .iter()
+        .filter(|(name, _)| !REDACTED_REQUEST_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let request_body = (!body.is_empty()).then(|| RecordedBody::from_bytes(&body));
+
+    let mut response = upstream
+        .request(method.clone(), url)
+        .headers(headers.clone())
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| Error::Network(e.to_string()))?;
+    let status = response.status().as_u16();
+    let response_headers = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| !is_hop_by_hop(name) && *name != CONTENT_LENGTH)
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let mut response_body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| Error::Network(e.to_string()))?
+    {
+        if response_body.len() + chunk.len() > MAX_RECORDED_BODY_BYTES {
+            return Err(Error::Network(format!(
+                "response body exceeds {MAX_RECORDED_BODY_BYTES} bytes"
+            )));
+        }
+        response_body.extend_from_slice(&chunk);
+    }
+
+    Ok(HttpExchange {
+        method: method.to_string(),
+        url: url.to_string(),
+        request_headers,
+        request_body,
+        status,
+        response_headers,
+        response_body: RecordedBody::from_bytes(&response_body),
+    })
+}
+
+/// Apply the SSRF literal check and the sandbox's network policy to the
+/// upstream at `authority`. The guest's own network is IPv4-only, so under a
+/// policy an upstream with no IPv4 address is refused.
+async fn check_upstream(
+    guard: &UpstreamGuard,
+    scheme: &str,
+    authority: &Authority,
+) -> std::result::Result<(), String> {
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = authority
+        .port_u16()
+        .unwrap_or(if scheme == "https" { 443 } else { 80 });
+    let literal = host.parse::<IpAddr>().ok();
+    if let Some(ip) = literal {
+        if guard.refuse_internal_literals && is_internal_ip(ip) {
+            return Err(format!("upstream {ip} is an internal address"));
+        }
+    }
+
+    let Some(policy) = &guard.network_policy else {
+        return Ok(());
+    };
+    let addrs: Vec<Ipv4Addr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("failed to resolve upstream {host}: {e}"))?
+        .filter_map(|addr| match addr.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let names = match literal {
+        Some(_) => Vec::new(),
+        None => vec![host.trim_end_matches('.').to_ascii_lowercase()],
+    };
+    let denied = addrs.is_empty()
+        || addrs
+            .iter()
+            .any(|ip| policy.evaluate(*ip, port, &names) == PolicyAction::Deny);
+    if !denied {
+        Ok(())
+    } else if policy.is_log_only() {
+        warn!(host, port, "network policy would deny recorded upstream");
+        Ok(())
+    } else {
+        Err(format!("network policy denies upstream {host}:{port}"))
+    }
+}
+
+/// Append one exchange and flush, so a run that dies midway keeps every
+/// exchange recorded so far.
+fn append_exchange(file: &Mutex<File>, exchange: &HttpExchange) -> Result<()> {
+    let mut line = serde_json::to_vec(exchange)?;
+    line.push(b'\n');
+    let mut file = file.lock().unwrap_or_else(|poison| poison.into_inner());
+    file.write_all(&line)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// `scheme://authority/path`, dropping the scheme's default port so
+/// `CONNECT host:443` and a recorded `https://host/` agree.
+fn request_url(scheme: &str, authority: &Authority, path: &str) -> String {
+    let default_port = if scheme == "https" { 443 } else { 80 };
+    match authority.port_u16() {
+        Some(port) if port != default_port => format!("{scheme}://{authority}{path}"),
+        _ => format!("{scheme}://{}{path}", authority.host()),
+    }
+}
+
+fn full_body(bytes: Vec<u8>) -> ProxyBody {
+    Full::new(Bytes::from(bytes))
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(url: &str, body: Option<&str>, response: &str) -> HttpExchange {
+        HttpExchange {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            request_headers: Vec::new(),
+            request_body: body.map(|b| RecordedBody::Text(b.to_string())),
+            status: 200,
+            response_headers: Vec::new(),
+            response_body: RecordedBody::Text(response.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_cassette_replays_in_order_and_repeats_last() {
+        let url = "https://api.example.com/v1/messages";
+        let cassette = Cassette::new(vec![
+            exchange(url, Some("a"), "first"),
+            exchange(url, Some("b"), "other"),
+            exchange(url, Some("a"), "second"),
+        ])
+        .unwrap();
+
+        let next = |body: &str| {
+            cassette
+                .next_response("post", url, body.as_bytes())
+                .map(|e| e.response_body.clone())
+        };
+        assert_eq!(next("a"), Some(RecordedBody::Text("first".into())));
+        assert_eq!(next("a"), Some(RecordedBody::Text("second".into())));
+        assert_eq!(next("a"), Some(RecordedBody::Text("second".into())));
+        assert_eq!(next("b"), Some(RecordedBody::Text("other".into())));
+        assert_eq!(next("c"), None);
+    }
+
+    #[test]
+    fn test_recorded_body_round_trips_binary() {
+        let bytes = [0xff, 0x00, 0x7f];
+        let body = RecordedBody::from_bytes(&bytes);
+        assert_eq!(body, RecordedBody::Hex("ff007f".into()));
+        assert_eq!(body.to_bytes().unwrap(), bytes);
+        assert!(RecordedBody::Hex("f".into()).to_bytes().is_err());
+        assert_eq!(
+            RecordedBody::from_bytes(b"{}"),
+            RecordedBody::Text("{}".into())
+        );
+    }
+
+    #[test]
+    fn test_request_url_drops_default_port() {
+        let url =
+            |scheme, authority: &str| request_url(scheme, &authority.parse().unwrap(), "/a?b=1");
+        assert_eq!(url("https", "example.com:443"), "https://example.com/a?b=1");
+        assert_eq!(
+            url("https", "example.com:8443"),
+            "https://example.com:8443/a?b=1"
+        );
+        assert_eq!(url("http", "example.com"), "http://example.com/a?b=1");
+    }
+}
@@ -61,7 +61,7 @@ const UPSTREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Body type the proxy hands back to hyper: a boxed stream of bytes whose error
 /// is normalised to `std::io::Error`.
-type ProxyBody = BoxBody<Bytes, std::io::Error>;
+pub(super) type ProxyBody = BoxBody<Bytes, std::io::Error>;
 
 /// What [`ProxyHandle::register_sandbox`] returns: how the guest reaches this sandbox's
 /// proxy listener and the token it must present.
@@ -410,7 +410,7 @@ fn relay_upstream_response(upstream_resp: reqwest::Response) -> Response<ProxyBo
 }
 
 /// Build a plain-text response with a boxed body.
-fn text_response(status: StatusCode, message: &str) -> Response<ProxyBody> {
+pub(super) fn text_response(status: StatusCode, message: &str) -> Response<ProxyBody> {
     let body = Full::new(Bytes::from(message.to_owned()))
         .map_err(|never| match never {})
         .boxed();
@@ -423,7 +423,7 @@ fn text_response(status: StatusCode, message: &str) -> Response<ProxyBody> {
 
 /// Whether `name` is a hop-by-hop header that must not be forwarded across the
 /// proxy boundary (RFC 7230 §6.1, plus the proxy token).
-fn is_hop_by_hop(name: &HeaderName) -> bool {
+pub(super) fn is_hop_by_hop(name: &HeaderName) -> bool {
     name == CONNECTION
         || name == TE
         || name == TRAILER
@@ -439,7 +439,7 @@ fn is_hop_by_hop(name: &HeaderName) -> bool {
 /// RFC 7230 §6.1 set, plus any header the inbound `Connection` header nominates
 /// as connection-scoped — those are hop-by-hop by nomination and must not cross
 /// the proxy boundary either.
-fn strip_hop_by_hop(headers: &mut HeaderMap) {
+pub(super) fn strip_hop_by_hop(headers: &mut HeaderMap) {
     let mut to_remove: Vec<HeaderName> = headers
         .keys()
         .filter(|name| is_hop_by_hop(name))
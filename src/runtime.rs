@@ -21,6 +21,7 @@ use crate::spec::{
     load_spec, AgentMode, BoxSandboxOverride, LlmSpec, MountSpec, PipelineBoxSpec,
     PipelineStageSpec, RunKind, RunSpec, SkillEntry, StepMode,
 };
+use crate::ssh::{SshBridge, SshBridgeConfig};
 
 /// Tracks host directories created for pipeline stage outputs.
 /// Maps output_name -> host_path.
@@ -145,7 +146,7 @@ pub async fn run_spec_service(
         None
     } else if let Some(ref image) = spec.sandbox.image {
         eprintln!("[void-box] Resolving OCI base image: {}", image);
-        let host_rootfs = resolve_oci_base_image(image).await?;
+        let host_rootfs = resolve_oci_base_image(image, spec.sandbox.rosetta).await?;
         Some(resolve_oci_rootfs_plan(image, host_rootfs).await?)
     } else {
         None
@@ -252,8 +253,10 @@ async fn run_sandbox(spec: &RunSpec) -> Result<RunReport> {
 
     let sandbox = builder.build()?;
     let _ = sandbox.exec("echo", &["ready"]).await;
+    let ssh_bridge = start_ssh_bridge(spec, &sandbox).await?;
 
     tokio::signal::ctrl_c().await.ok();
+    drop(ssh_bridge);
     let _ = sandbox.stop().await;
     drop(staged_creds);
     drop(staged_codex_creds);
@@ -270,6 +273,17 @@ async fn run_sandbox(spec: &RunSpec) -> Result<RunReport> {
     })
 }
 
+/// Start an SSH bridge into `sandbox` when the spec asks for one. It
+/// serves until dropped.
+async fn start_ssh_bridge(spec: &RunSpec, sandbox: &Arc<Sandbox>) -> Result<Option<SshBridge>> {
+    if !spec.sandbox.ssh {
+        return Ok(None);
+    }
+    let bridge = SshBridge::start(sandbox.clone(), SshBridgeConfig::new(&spec.name)).await?;
+    eprintln!("[void-box] SSH: {}", bridge.access().ssh_command());
+    Ok(Some(bridge))
+}
+
 fn uses_mock_sandbox(spec: &RunSpec) -> bool {
     spec.sandbox.mode.eq_ignore_ascii_case("mock")
 }
@@ -320,7 +334,7 @@ async fn run_agent(
         None
     } else if let Some(ref image) = spec.sandbox.image {
         eprintln!("[void-box] Resolving OCI base image: {}", image);
-        let host_rootfs = resolve_oci_base_image(image).await?;
+        let host_rootfs = resolve_oci_base_image(image, spec.sandbox.rosetta).await?;
         Some(resolve_oci_rootfs_plan(image, host_rootfs).await?)
     } else {
         None
@@ -456,7 +470,7 @@ async fn run_pipeline(
         None
     } else if let Some(ref image) = spec.sandbox.image {
         eprintln!("[void-box] Resolving OCI base image: {}", image);
-        let host_rootfs = resolve_oci_base_image(image).await?;
+        let host_rootfs = resolve_oci_base_image(image, spec.sandbox.rosetta).await?;
         Some(resolve_oci_rootfs_plan(image, host_rootfs).await?)
     } else {
         None
@@ -631,7 +645,7 @@ async fn run_workflow(
         None
     } else if let Some(ref image) = spec.sandbox.image {
         eprintln!("[void-box] Resolving OCI base image: {}", image);
-        let host_rootfs = resolve_oci_base_image(image).await?;
+        let host_rootfs = resolve_oci_base_image(image, spec.sandbox.rosetta).await?;
         Some(resolve_oci_rootfs_plan(image, host_rootfs).await?)
     } else {
         None
@@ -679,7 +693,7 @@ async fn run_workflow(
                 .or_else(|| policy.as_ref().map(|p| p.stage_timeout_secs)),
         };
         if let Some(t) = effective_timeout {
-            builder = builder.timeout(&step.name, t);
+            builder = builder.exec_timeout(&step.name, t);
         }
     }
 
@@ -696,6 +710,7 @@ async fn run_workflow(
     );
 
     let sandbox = build_shared_sandbox(spec, oci_rootfs_plan.as_ref(), guest.as_ref())?;
+    let _ssh_bridge = start_ssh_bridge(spec, &sandbox).await?;
 
     // Emit StageQueued for all workflow steps using the execution plan
     if stage_tx.is_some() {
@@ -925,6 +940,11 @@ fn build_shared_sandbox(
         builder = builder.mount(mount_spec_to_config(m));
     }
 
+    for dir in &spec.sandbox.write_roots {
+        builder = builder.allow_write_root(dir);
+    }
+    builder = builder.rosetta(spec.sandbox.rosetta);
+
     // OCI rootfs mount + pivot_root flag
     if let Some(plan) = oci_rootfs_plan {
         builder = apply_oci_rootfs_sandbox(builder, plan);
@@ -977,6 +997,11 @@ fn apply_box_sandbox(mut builder: VoidBox, spec: &RunSpec, guest: Option<&GuestF
         builder = builder.mount(mount_spec_to_config(m));
     }
 
+    for dir in &spec.sandbox.write_roots {
+        builder = builder.allow_write_root(dir);
+    }
+    builder = builder.rosetta(spec.sandbox.rosetta);
+
     // Snapshot restore (explicit opt-in only)
     if let Some(snap_dir) = resolve_snapshot(spec) {
         builder = builder.snapshot(snap_dir);
@@ -1304,6 +1329,19 @@ fn apply_box_llm(builder: VoidBox, llm: Option<&LlmSpec>) -> VoidBox {
             }
             p
         }
+        "openai" => {
+            let model = llm.model.clone().unwrap_or_else(|| "gpt-4.1".into());
+            let mut p = match &llm.base_url {
+                Some(base_url) => LlmProvider::openai_compatible(base_url, model),
+                None => LlmProvider::openai(model),
+            };
+            if let Some(api_key_env) = &llm.api_key_env {
+                if let Ok(k) = std::env::var(api_key_env) {
+                    p = p.api_key(k);
+                }
+            }
+            p
+        }
         _ => LlmProvider::Claude,
     };
 
@@ -1479,10 +1517,14 @@ fn mount_spec_to_config(spec: &MountSpec) -> MountConfig {
 /// Resolve an OCI base image to a host directory containing the extracted rootfs.
 ///
 /// Uses `~/.voidbox/oci/` as the content-addressed cache directory.
-/// Returns the path to the extracted rootfs on the host.
-async fn resolve_oci_base_image(image_ref: &str) -> Result<PathBuf> {
+/// Returns the path to the extracted rootfs on the host. With `rosetta`,
+/// images that publish no manifest for the host fall back to `linux/amd64`.
+async fn resolve_oci_base_image(image_ref: &str, rosetta: bool) -> Result<PathBuf> {
     let cache_dir = oci_cache_dir();
-    let client = voidbox_oci::OciClient::new(cache_dir);
+    let mut client = voidbox_oci::OciClient::new(cache_dir);
+    if rosetta {
+        client = client.with_fallback_platform(voidbox_oci::manifest::Platform::linux("amd64"));
+    }
     client.resolve_rootfs(image_ref).await.map_err(|e| {
         Error::Config(format!(
             "failed to resolve OCI image '{}': {}",
@@ -0,0 +1,342 @@
+//! Files collected from a sandbox's `/workspace`.
+//!
+//! Produced by [`Sandbox::export_workspace`](super::Sandbox::export_workspace)
+//! from the tar archive the guest streams back. The archive comes from inside
+//! the VM, so [`ArtifactBundle::from_tar`] rejects entries that would land
+//! outside the bundle root before anything is written to the host.
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// One file (or symlink) exported from the guest workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactFile {
+    /// Path relative to `/workspace`, `/`-separated.
+    pub path: String,
+    /// Unix permission bits recorded in the archive.
+    pub mode: u32,
+    /// File contents; empty for symlinks.
+    pub data: Vec<u8>,
+    /// Hex SHA-256 of `data`.
+    pub sha256: String,
+    /// Symlink target, when the entry is a symlink.
+    pub link_target: Option<String>,
+}
+
+impl ArtifactFile {
+    /// Size of `data` in bytes.
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// Manifest line for one [`ArtifactFile`], without its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+}
+
+/// The exported workspace: files sorted by path plus a hash manifest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArtifactBundle {
+    /// Exported entries, sorted by path.
+    pub files: Vec<ArtifactFile>,
+}
+
+impl ArtifactBundle {
+    /// Parse the tar archive produced by the guest's `ExportWorkspace`
+    /// handler.
+    ///
+    /// Only regular files and symlinks are accepted. Absolute paths, `..`
+    /// components, duplicate paths, and files nested under an exported
+    /// symlink are rejected, so [`write_to_dir`](Self::write_to_dir) cannot
+    /// be steered outside its target directory.
+    pub fn from_tar(bytes: &[u8]) -> Result<Self> {
+        let invalid = |msg: String| Error::Guest(format!("Invalid workspace archive: {}", msg));
+        let mut archive = tar::Archive::new(bytes);
+        let mut files = Vec::new();
+        let mut seen = HashSet::new();
+        let mut symlinks = HashSet::new();
+
+        for entry in archive.entries().map_err(|e| invalid(e.to_string()))? {
+            let mut entry = entry.map_err(|e| invalid(e.to_string()))?;
+            let path = entry
+                .path()
+                .map_err(|e| invalid(e.to_string()))?
+                .into_owned();
+            if path.as_os_str().is_empty()
+                || !path.components().all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Err(invalid(format!("unsafe path {}", path.display())));
+            }
+            if path.ancestors().skip(1).any(|a| symlinks.contains(a)) {
+                return Err(invalid(format!(
+                    "{} is nested under an exported symlink",
+                    path.display()
+                )));
+            }
+            if !seen.insert(path.clone()) {
+                return Err(invalid(format!("duplicate entry {}", path.display())));
+            }
+
+            let header = entry.header();
+            let mode = header.mode().map_err(|e| invalid(e.to_string()))?;
+            let entry_type = header.entry_type();
+            let mut data = Vec::new();
+            let link_target = if entry_type.is_symlink() {
+                let target = entry
+                    .link_name()
+                    .map_err(|e| invalid(e.to_string()))?
+                    .ok_or_else(|| invalid(format!("symlink {} has no target", path.display())))?;
+                if seen.iter().any(|p| p != &path && p.starts_with(&path)) {
+                    return Err(invalid(format!(
+                        "symlink {} shadows earlier entries",
+                        path.display()
+                    )));
+                }
+                symlinks.insert(path.clone());
+                Some(target.to_string_lossy().into_owned())
+            } else if entry_type.is_file() {
+                entry.read_to_end(&mut data)?;
+                None
+            } else {
+                return Err(invalid(format!(
+                    "unsupported entry type for {}",
+                    path.display()
+                )));
+            };
+
+            files.push(ArtifactFile {
+                path: path.to_string_lossy().into_owned(),
+                mode,
+                sha256: format!("{:x}", Sha256::digest(&data)),
+                data,
+                link_target,
+            });
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { files })
+    }
+
+    /// Whether the export matched no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Look up an exported entry by its workspace-relative path.
+    pub fn file(&self, path: &str) -> Option<&ArtifactFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Total size of all file contents, in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(ArtifactFile::size).sum()
+    }
+
+    /// Path, size, and hash of every entry.
+    pub fn manifest(&self) -> Vec<BundleManifestEntry> {
+        self.files
+            .iter()
+            .map(|f| BundleManifestEntry {
+                path: f.path.clone(),
+                size: f.size(),
+                sha256: f.sha256.clone(),
+                link_target: f.link_target.clone(),
+            })
+            .collect()
+    }
+
+    /// Recreate the bundle under `dir`, creating parent directories as
+    /// needed and restoring the `rwx` permission bits. Setuid, setgid and
+    /// sticky bits from the guest are dropped.
+    ///
+    /// Files are created with `O_CREAT | O_EXCL | O_NOFOLLOW`, so an entry
+    /// that already exists under `dir` (including a symlink) is an error
+    /// rather than something to write through.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<()> {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        for file in &self.files {
+            let dest = dir.join(&file.path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match &file.link_target {
+                Some(target) => std::os::unix::fs::symlink(target, &dest)?,
+                None => {
+                    let mut out = std::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .custom_flags(libc::O_NOFOLLOW)
+                        .open(&dest)?;
+                    out.write_all(&file.data)?;
+                    out.set_permissions(std::fs::Permissions::from_mode(file.mode & 0o777))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8], mode: u32) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    fn append_symlink(builder: &mut tar::Builder<Vec<u8>>, path: &str, target: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_entry_type(tar::EntryType::Symlink);
+        builder.append_link(&mut header, path, target).unwrap();
+    }
+
+    /// Raw ustar header with an arbitrary name, bypassing the builder's own
+    /// path checks.
+    fn raw_entry(name: &str) -> Vec<u8> {
+        let mut header = tar::Header::new_ustar();
+        header.as_mut_bytes()[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(0);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(&[0u8; 1024]);
+        bytes
+    }
+
+    #[test]
+    fn test_bundle_from_tar_hashes_and_manifest() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "out/result.json", b"{\"ok\":true}", 0o644);
+        append_file(&mut builder, "run.sh", b"echo hi", 0o755);
+        append_symlink(&mut builder, "latest", "out/result.json");
+        let bundle = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap();
+
+        assert_eq!(bundle.files.len(), 3);
+        assert_eq!(bundle.total_bytes(), 18);
+        let result = bundle.file("out/result.json").unwrap();
+        assert_eq!(result.data, b"{\"ok\":true}");
+        assert_eq!(
+            result.sha256,
+            format!("{:x}", Sha256::digest(b"{\"ok\":true}"))
+        );
+        assert_eq!(
+            bundle.file("latest").unwrap().link_target.as_deref(),
+            Some("out/result.json")
+        );
+
+        let manifest = bundle.manifest();
+        let paths: Vec<_> = manifest.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["latest", "out/result.json", "run.sh"]);
+        let json = serde_json::to_value(&manifest[2]).unwrap();
+        assert_eq!(json["size"], 7);
+        assert!(json.get("link_target").is_none());
+    }
+
+    #[test]
+    fn test_bundle_rejects_escaping_paths() {
+        for name in ["../escape", "/etc/passwd"] {
+            let err = ArtifactBundle::from_tar(&raw_entry(name)).unwrap_err();
+            assert!(err.to_string().contains("unsafe path"), "{}: {}", name, err);
+        }
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_symlink(&mut builder, "out", "/etc");
+        append_file(&mut builder, "out/passwd", b"x", 0o644);
+        let err = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("nested under an exported symlink"));
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_symlink(&mut builder, "x", "/etc/whatever");
+        append_file(&mut builder, "x", b"owned", 0o644);
+        let err = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("duplicate entry"), "{}", err);
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "out/passwd", b"x", 0o644);
+        append_symlink(&mut builder, "out", "/etc");
+        let err = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap_err();
+        assert!(
+            err.to_string().contains("shadows earlier entries"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_bundle_write_to_dir_refuses_existing_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        std::os::unix::fs::symlink(&target, out.join("x")).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "x", b"owned", 0o644);
+        let bundle = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap();
+        assert!(bundle.write_to_dir(&out).is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_bundle_write_to_dir() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "a/b/c.txt", b"hello", 0o600);
+        append_symlink(&mut builder, "link", "a/b/c.txt");
+        let bundle = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        bundle.write_to_dir(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("a/b/c.txt")).unwrap(),
+            b"hello"
+        );
+        assert_eq!(std::fs::read(dir.path().join("link")).unwrap(), b"hello");
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join("a/b/c.txt"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_bundle_write_to_dir_drops_special_bits() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "suid", b"#!/bin/sh", 0o4755);
+        let bundle = ArtifactBundle::from_tar(&builder.into_inner().unwrap()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        bundle.write_to_dir(dir.path()).unwrap();
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join("suid"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7000, 0);
+        assert_eq!(mode & 0o777, 0o755);
+    }
+}
@@ -0,0 +1,134 @@
+//! Keeping the guest's wall clock in step with the host's.
+//!
+//! The guest sets its clock once at boot, from whole seconds on the kernel
+//! cmdline. After that it drifts, and it stands still while the host is
+//! suspended or the VM is paused for a snapshot — enough to fail TLS
+//! certificate checks in a long-lived sandbox.
+//! [`Sandbox::sync_clock`](super::Sandbox::sync_clock) resets it on demand;
+//! with [`SandboxBuilder::clock_sync`](super::SandboxBuilder::clock_sync) a
+//! local sandbox also does so in the background, right after the
+//! guest-agent's handshake and then every `interval`.
+
+use std::sync::Weak;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use super::LocalSandbox;
+use crate::guest::protocol::{SyncClockRequest, SyncClockResponse};
+
+/// Outcome of one clock sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    /// Host clock minus the guest clock it replaced, in milliseconds;
+    /// positive when the guest was behind.
+    pub drift_ms: i64,
+}
+
+impl ClockSync {
+    pub(crate) fn from_response(request: &SyncClockRequest, response: &SyncClockResponse) -> Self {
+        let millis = |secs: i64, nanos: u32| secs * 1000 + i64::from(nanos / 1_000_000);
+        Self {
+            drift_ms: millis(request.unix_secs, request.nanos)
+                - millis(response.guest_unix_secs, response.guest_nanos),
+        }
+    }
+}
+
+/// A [`SyncClockRequest`] for the host's clock now.
+pub(crate) fn host_clock() -> SyncClockRequest {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    SyncClockRequest {
+        unix_secs: now.as_secs() as i64,
+        nanos: now.subsec_nanos(),
+    }
+}
+
+/// Drift past which a background sync is logged at `info` rather than
+/// `debug`.
+const NOTABLE_DRIFT_MS: i64 = 1000;
+
+/// Background task syncing a local sandbox's guest clock. Holds only a
+/// weak reference, and is aborted when dropped with the sandbox.
+pub(crate) struct ClockSyncMonitor {
+    task: JoinHandle<()>,
+}
+
+impl ClockSyncMonitor {
+    pub(crate) fn spawn(sandbox: Weak<LocalSandbox>, interval: Duration) -> Self {
+        Self {
+            task: tokio::spawn(run(sandbox, interval)),
+        }
+    }
+}
+
+impl Drop for ClockSyncMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// How often to look for a newly ready guest-agent that still needs its
+/// first sync.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+async fn run(sandbox: Weak<LocalSandbox>, interval: Duration) {
+    // Boot and time of the last successful sync; a restart or snapshot
+    // restore is synced as soon as its agent is ready.
+    let mut last_sync: Option<(u32, Instant)> = None;
+    let mut ticks = tokio::time::interval(READY_POLL_INTERVAL.min(interval));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let Some(sandbox) = sandbox.upgrade() else {
+            return;
+        };
+        let boot = sandbox.boot_count();
+        if last_sync.is_some_and(|(b, at)| b == boot && at.elapsed() < interval) {
+            continue;
+        }
+        match sandbox.sync_clock_if_ready().await {
+            None => {}
+            Some(Ok(sync)) => {
+                if sync.drift_ms.abs() >= NOTABLE_DRIFT_MS {
+                    tracing::info!("Guest clock was off by {} ms; synced", sync.drift_ms);
+                } else {
+                    tracing::debug!("Guest clock synced (drift {} ms)", sync.drift_ms);
+                }
+                last_sync = Some((boot, Instant::now()));
+            }
+            Some(Err(e)) => {
+                tracing::warn!("Guest clock sync failed: {}", e);
+                last_sync = Some((boot, Instant::now()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_is_host_minus_guest() {
+        let request = SyncClockRequest {
+            unix_secs: 1_700_000_010,
+            nanos: 250_000_000,
+        };
+        let behind = SyncClockResponse {
+            guest_unix_secs: 1_700_000_000,
+            guest_nanos: 0,
+            error: None,
+        };
+        assert_eq!(ClockSync::from_response(&request, &behind).drift_ms, 10_250);
+        let ahead = SyncClockResponse {
+            guest_unix_secs: 1_700_000_011,
+            guest_nanos: 0,
+            error: None,
+        };
+        assert_eq!(ClockSync::from_response(&request, &ahead).drift_ms, -750);
+    }
+}
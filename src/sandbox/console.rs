@@ -0,0 +1,114 @@
+//! Attaching to the guest serial console.
+//!
+//! [`Sandbox::attach_console`](super::Sandbox::attach_console) connects
+//! the caller to the guest's serial console as a byte stream: reads return
+//! what the guest writes to the console, from the kernel and from init, and
+//! writes reach the guest as console input. Unlike
+//! [`attach_pty`](super::Sandbox::attach_pty) it needs no guest-agent, so
+//! it still works when a boot hangs before the control channel comes up.
+//!
+//! With [`SandboxBuilder::console_shell`](super::SandboxBuilder::console_shell)
+//! the guest-agent also runs a root shell on the console.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+use crate::backend::ConsoleInput;
+
+/// Guest serial console of the boot it was attached to.
+///
+/// Reads see console output from the moment of attaching and end when the
+/// VM stops. Writes never block; they are queued on the console's receive
+/// buffer.
+pub struct ConsoleStream {
+    output: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// Output received but not yet read.
+    pending: Vec<u8>,
+    input: ConsoleInput,
+}
+
+impl ConsoleStream {
+    pub(crate) fn new(output: mpsc::UnboundedReceiver<Vec<u8>>, input: ConsoleInput) -> Self {
+        Self {
+            output,
+            pending: Vec::new(),
+            input,
+        }
+    }
+}
+
+impl AsyncRead for ConsoleStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.pending.is_empty() {
+            match self.output.poll_recv(cx) {
+                Poll::Ready(Some(bytes)) => self.pending = bytes,
+                // The VM stopped: end of file.
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let n = self.pending.len().min(buf.remaining());
+        buf.put_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ConsoleStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.input.write(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_console_stream_reads_output_and_writes_input() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let mut stream = ConsoleStream::new(
+            rx,
+            ConsoleInput::new(move |bytes| sink.lock().unwrap().extend_from_slice(bytes)),
+        );
+
+        stream.write_all(b"uname -r\n").await.unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"uname -r\n");
+
+        tx.send(b"6.12.0".to_vec()).unwrap();
+        tx.send(b"\r\n# ".to_vec()).unwrap();
+        drop(tx);
+        let mut first = [0u8; 4];
+        stream.read_exact(&mut first).await.unwrap();
+        assert_eq!(&first, b"6.12");
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b".0\r\n# ");
+    }
+}
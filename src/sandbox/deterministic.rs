@@ -0,0 +1,239 @@
+//! Deterministic runs, for reproducible agent evaluations.
+//!
+//! [`SandboxBuilder::deterministic`](super::SandboxBuilder::deterministic)
+//! removes the sources of variation void-box controls:
+//!
+//! - The guest clock starts every boot at [`CLOCK_BASELINE_UNIX_SECS`]
+//!   rather than the host's time.
+//! - Execs see the seed as `VOID_BOX_SEED`, and as `PYTHONHASHSEED` so
+//!   Python's hash randomization is fixed; `SOURCE_DATE_EPOCH` and `TZ` are
+//!   pinned too. The kernel's own entropy cannot be seeded.
+//! - The VM gets a single vCPU, so guest threads are not scheduled in
+//!   parallel.
+//! - Network access is replay-only: the sandbox must use
+//!   [`replay_http`](super::SandboxBuilder::replay_http).
+//!
+//! [`Sandbox::manifest`](super::Sandbox::manifest) describes
+//! everything else a run depends on, with a hash to compare across runs.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use void_box_protocol::SyncClockRequest;
+
+use super::SandboxConfig;
+use crate::proxy::recording::HttpRecording;
+use crate::secret::Redactor;
+use crate::{Error, Result};
+
+/// Where the guest clock starts in deterministic mode:
+/// 2024-01-01T00:00:00Z.
+pub const CLOCK_BASELINE_UNIX_SECS: i64 = 1_704_067_200;
+
+/// Clock setting sent to the guest on every deterministic boot.
+pub(crate) fn clock_baseline() -> SyncClockRequest {
+    SyncClockRequest {
+        unix_secs: CLOCK_BASELINE_UNIX_SECS,
+        nanos: 0,
+    }
+}
+
+/// Environment every exec of a deterministic sandbox sees, before the
+/// sandbox's own.
+pub(crate) fn guest_env(seed: u64) -> Vec<(String, String)> {
+    vec![
+        ("VOID_BOX_SEED".into(), seed.to_string()),
+        // Python accepts 0..=4294967295.
+        ("PYTHONHASHSEED".into(), (seed % (1 << 32)).to_string()),
+        (
+            "SOURCE_DATE_EPOCH".into(),
+            CLOCK_BASELINE_UNIX_SECS.to_string(),
+        ),
+        ("TZ".into(), "UTC".into()),
+    ]
+}
+
+/// Check the settings that would undo deterministic mode.
+pub(crate) fn validate(config: &SandboxConfig) -> Result<()> {
+    if config.deterministic.is_none() {
+        return Ok(());
+    }
+    if config.vcpus != 1 {
+        return Err(Error::Config(
+            "deterministic mode runs on a single vCPU".into(),
+        ));
+    }
+    if !matches!(config.http_recording, Some(HttpRecording::Replay(_))) {
+        return Err(Error::Config(
+            "deterministic mode requires replay_http".into(),
+        ));
+    }
+    if config.clock_sync.is_some() {
+        return Err(Error::Config(
+            "deterministic mode fixes the guest clock; clock_sync cannot be used".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// What a run depends on, for comparing runs.
+///
+/// Two runs with the same [`hash`](Self::hash) started from the same
+/// inputs: seed, images, HTTP cassette and configuration. Secret values are
+/// left out: only their names count, and env values containing one are
+/// redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxManifest {
+    /// `None` outside deterministic mode.
+    pub seed: Option<u64>,
+    /// `None` outside deterministic mode.
+    pub clock_baseline_unix_secs: Option<i64>,
+    pub vcpus: usize,
+    pub memory_mb: usize,
+    pub kernel_sha256: Option<String>,
+    pub initramfs_sha256: Option<String>,
+    /// Hash of the archive built from the
+    /// [`initramfs_overlay`](super::SandboxBuilder::initramfs_overlay)
+    /// directories.
+    pub initramfs_overlay_sha256: Option<String>,
+    pub oci_rootfs: Option<String>,
+    pub oci_rootfs_disk_sha256: Option<String>,
+    /// Hash of the [`replay_http`](super::SandboxBuilder::replay_http)
+    /// cassette.
+    pub http_cassette_sha256: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub secrets: Vec<String>,
+    pub working_dir: Option<String>,
+    pub path_prepend: Vec<String>,
+    pub exec_policy: Option<String>,
+    pub users: Vec<String>,
+    pub read_only: bool,
+    /// SHA-256 over every other field.
+    pub hash: String,
+}
+
+impl SandboxManifest {
+    /// Describe a sandbox built from `config`, hashing the files it names.
+    pub(crate) fn from_config(config: &SandboxConfig, redactor: &Redactor) -> Result<Self> {
+        let cassette = match &config.http_recording {
+            Some(HttpRecording::Replay(path)) => Some(file_sha256(path)?),
+            _ => None,
+        };
+        let mut manifest = Self {
+            seed: config.deterministic,
+            clock_baseline_unix_secs: config.deterministic.map(|_| CLOCK_BASELINE_UNIX_SECS),
+            vcpus: config.vcpus,
+            memory_mb: config.memory_mb,
+            kernel_sha256: config.kernel.as_deref().map(file_sha256).transpose()?,
+            initramfs_sha256: config.initramfs.as_deref().map(file_sha256).transpose()?,
+            initramfs_overlay_sha256: match config.initramfs_overlays.as_slice() {
+                [] => None,
+                dirs => Some(format!(
+                    "{:x}",
+                    Sha256::digest(crate::guest_image::overlay_dirs(dirs)?)
+                )),
+            },
+            oci_rootfs: config.oci_rootfs.clone(),
+            oci_rootfs_disk_sha256: config
+                .oci_rootfs_disk
+                .as_deref()
+                .map(file_sha256)
+                .transpose()?,
+            http_cassette_sha256: cassette,
+            env: config
+                .env
+                .iter()
+                .map(|(k, v)| (k.clone(), redactor.redact(v).into_owned()))
+                .collect(),
+            secrets: config
+                .secrets
+                .iter()
+                .map(|s| s.name().to_string())
+                .collect(),
+            working_dir: config.working_dir.clone(),
+            path_prepend: config.path_prepend.clone(),
+            exec_policy: config
+                .exec_policy
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            users: config.users.clone(),
+            read_only: config.read_only,
+            hash: String::new(),
+        };
+        manifest.hash = manifest.compute_hash()?;
+        Ok(manifest)
+    }
+
+    fn compute_hash(&self) -> Result<String> {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        Ok(format!(
+            "{:x}",
+            Sha256::digest(serde_json::to_vec(&unhashed)?)
+        ))
+    }
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let data = std::fs::read(path).map_err(|e| {
+        Error::Config(format!(
+            "cannot read {} for the run manifest: {e}",
+            path.display()
+        ))
+    })?;
+    Ok(format!("{:x}", Sha256::digest(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_hash_tracks_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cassette = dir.path().join("run.jsonl");
+        std::fs::write(&cassette, "{}\n").unwrap();
+        let config = SandboxConfig {
+            deterministic: Some(7),
+            http_recording: Some(HttpRecording::Replay(cassette.clone())),
+            ..SandboxConfig::default()
+        };
+        validate(&config).unwrap();
+
+        let redactor = Redactor::new(&[]);
+        let first = SandboxManifest::from_config(&config, &redactor).unwrap();
+        assert_eq!(
+            first,
+            SandboxManifest::from_config(&config, &redactor).unwrap()
+        );
+        assert_eq!(
+            first.clock_baseline_unix_secs,
+            Some(CLOCK_BASELINE_UNIX_SECS)
+        );
+
+        std::fs::write(&cassette, "{\"changed\":true}\n").unwrap();
+        let edited = SandboxManifest::from_config(&config, &redactor).unwrap();
+        assert_ne!(first.hash, edited.hash);
+
+        let reseeded = SandboxConfig {
+            deterministic: Some(8),
+            ..config.clone()
+        };
+        assert_ne!(
+            edited.hash,
+            SandboxManifest::from_config(&reseeded, &redactor)
+                .unwrap()
+                .hash
+        );
+
+        let recording = SandboxConfig {
+            http_recording: Some(HttpRecording::Record(cassette)),
+            ..config
+        };
+        assert!(validate(&recording).is_err());
+    }
+}
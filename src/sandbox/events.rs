@@ -0,0 +1,757 @@
+//! Typed lifecycle events for a single sandbox.
+//!
+//! Every [`Sandbox`](super::Sandbox) owns a [`SandboxEvents`] broadcast
+//! channel; [`Sandbox::events`](super::Sandbox::events) hands out receivers.
+//! Events are fire-and-forget: emitting with no subscribers is free, and a
+//! subscriber that falls more than [`EVENT_CHANNEL_CAPACITY`] events behind
+//! sees `RecvError::Lagged` rather than stalling the sandbox.
+//!
+//! When the sandbox's [`ObserveConfig`] sets
+//! [`prometheus_listen`](ObserveConfig::prometheus_listen), the same events
+//! are also folded into a per-sandbox [`MetricsCollector`] (exec counts and
+//! durations, bytes written, connections, guest CPU/memory) exported with a
+//! `sandbox` label.
+//!
+//! The exec tracker also counts execs in flight, which is how a sandbox
+//! bounds them: past its limit, a new exec fails with [`Error::Busy`]
+//! instead of queueing behind the others.
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, oneshot};
+
+use crate::guest::protocol::{ExecResponse, ShutdownAck, TelemetryBatch};
+use crate::observe::crash::PendingExec;
+use crate::observe::exec_span::ExecSpan;
+use crate::observe::network::ConnectionRecord;
+use crate::observe::{prometheus, MetricsCollector, ObserveConfig};
+use crate::{Error, ExecOutput, ExecTimeline, ExecUsage, Result};
+
+/// Events buffered per subscriber before the oldest are dropped.
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Something that happened in a sandbox's lifetime.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SandboxEvent {
+    /// The VM was started (cold boot or snapshot restore).
+    Boot {
+        memory_mb: usize,
+        vcpus: usize,
+        network: bool,
+        from_snapshot: bool,
+    },
+    /// The guest-agent completed its handshake; `boot_ms` is measured from
+    /// the start of VM creation.
+    AgentReady { boot_ms: u64 },
+    /// A command was sent to the guest. `exec_id` pairs it with its
+    /// [`ExecFinished`](Self::ExecFinished).
+    ExecStarted {
+        exec_id: u64,
+        program: String,
+        args: Vec<String>,
+    },
+    /// A command finished; `exit_code` is `None` when the exec itself failed.
+    ExecFinished {
+        exec_id: u64,
+        program: String,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        error: Option<String>,
+        /// CPU, memory, and I/O reported by the guest-agent.
+        #[serde(flatten)]
+        usage: ExecUsage,
+    },
+    /// A file was written into the guest.
+    FileWritten { path: String, bytes: u64 },
+    /// A guest telemetry sample arrived.
+    TelemetryTick {
+        seq: u64,
+        cpu_percent: Option<f64>,
+        memory_used_bytes: Option<u64>,
+        process_count: usize,
+    },
+    /// The guest opened an outbound TCP connection (KVM/SLIRP only).
+    NetworkConnection { dst_ip: Ipv4Addr, dst_port: u16 },
+    /// An outbound guest flow closed or was refused by network policy
+    /// (KVM/SLIRP only).
+    NetworkFlow {
+        #[serde(flatten)]
+        record: ConnectionRecord,
+    },
+    /// The guest-agent failed a heartbeat; `missed` counts consecutive
+    /// failures (see [`HealthCheck`](super::health::HealthCheck)).
+    HealthCheckFailed { missed: u32 },
+    /// The guest went `silent_ms` without petting its watchdog device (see
+    /// [`SandboxBuilder::watchdog`](super::SandboxBuilder::watchdog)).
+    WatchdogExpired { silent_ms: u64 },
+    /// The VM was replaced with a fresh boot under a
+    /// [`RestartPolicy`](super::health::RestartPolicy); `restart` counts
+    /// restarts since the sandbox was built.
+    Restarted { restart: u32, reason: String },
+    /// The guest kernel was restarted in place by
+    /// [`Sandbox::reboot`](super::Sandbox::reboot). `graceful` is the
+    /// guest's summary of stopping its processes; `reboot_ms` runs from the
+    /// request to the new boot being provisioned.
+    Rebooted {
+        graceful: ShutdownAck,
+        reboot_ms: u64,
+    },
+    /// The sandbox was stopped. `graceful` is the guest's shutdown summary,
+    /// `None` when the guest did not acknowledge and the VM was stopped hard.
+    Shutdown {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        graceful: Option<ShutdownAck>,
+    },
+}
+
+impl SandboxEvent {
+    pub(crate) fn telemetry_tick(batch: &TelemetryBatch) -> Self {
+        Self::TelemetryTick {
+            seq: batch.seq,
+            cpu_percent: batch.system.as_ref().map(|s| s.cpu_percent),
+            memory_used_bytes: batch.system.as_ref().map(|s| s.memory_used_bytes),
+            process_count: batch.processes.len(),
+        }
+    }
+}
+
+/// Buckets for `sandbox_exec_duration_ms`.
+const EXEC_DURATION_BUCKETS_MS: &[f64] = &[
+    10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 30000.0, 120000.0, 600000.0,
+];
+
+/// Buckets for `sandbox_exec_max_rss_bytes` (16 MiB to 8 GiB).
+const EXEC_RSS_BUCKETS_BYTES: &[f64] = &[
+    16.0 * 1048576.0,
+    64.0 * 1048576.0,
+    256.0 * 1048576.0,
+    512.0 * 1048576.0,
+    1024.0 * 1048576.0,
+    2048.0 * 1048576.0,
+    4096.0 * 1048576.0,
+    8192.0 * 1048576.0,
+];
+
+/// Sending half of a sandbox's event stream. Cheap to clone.
+#[derive(Clone)]
+pub struct SandboxEvents {
+    tx: broadcast::Sender<SandboxEvent>,
+    next_exec_id: Arc<AtomicU64>,
+    in_flight: Arc<AtomicUsize>,
+    pending: Arc<Mutex<BTreeMap<u64, PendingEntry>>>,
+    sandbox_id: Arc<str>,
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+impl std::fmt::Debug for SandboxEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxEvents")
+            .field("sandbox_id", &self.sandbox_id)
+            .field("subscribers", &self.tx.receiver_count())
+            .field("in_flight", &self.execs_in_flight())
+            .field("metrics", &self.metrics.is_some())
+            .finish()
+    }
+}
+
+impl Default for SandboxEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SandboxEvents {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            tx,
+            next_exec_id: Arc::new(AtomicU64::new(1)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            pending: Arc::new(Mutex::new(BTreeMap::new())),
+            sandbox_id: uuid::Uuid::now_v7().to_string().into(),
+            metrics: None,
+        }
+    }
+
+    /// Event stream for a sandbox observed with `observe`, exporting
+    /// per-sandbox metrics when it sets a Prometheus listen address.
+    pub(crate) fn with_observe(observe: Option<&ObserveConfig>) -> Self {
+        let mut events = Self::new();
+        if let Some(listen) = observe.and_then(|o| o.prometheus_listen.as_deref()) {
+            let metrics = Arc::new(MetricsCollector::new(
+                observe.map(|o| o.metrics.clone()).unwrap_or_default(),
+            ));
+            prometheus::register_or_warn(listen, &metrics, &[("sandbox", &events.sandbox_id)]);
+            events.metrics = Some(metrics);
+        }
+        events
+    }
+
+    /// Identifier used as the `sandbox` metrics label.
+    pub fn sandbox_id(&self) -> &str {
+        &self.sandbox_id
+    }
+
+    /// Per-sandbox metrics, when exported.
+    pub fn metrics(&self) -> Option<&Arc<MetricsCollector>> {
+        self.metrics.as_ref()
+    }
+
+    /// A receiver for every event emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SandboxEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Broadcast `event` to current subscribers, if any.
+    pub fn emit(&self, event: SandboxEvent) {
+        if let Some(metrics) = &self.metrics {
+            record_metrics(metrics, &event);
+        }
+        let _ = self.tx.send(event);
+    }
+
+    /// Execs started and not yet finished.
+    pub fn execs_in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Execs started and not yet finished, oldest first.
+    pub fn pending_execs(&self) -> Vec<PendingExec> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&exec_id, entry)| PendingExec {
+                exec_id,
+                program: entry.program.clone(),
+                args: entry.args.clone(),
+                running_ms: entry.started.elapsed().as_millis() as u64,
+            })
+            .collect()
+    }
+
+    /// Emit `ExecStarted` and return the tracker that emits `ExecFinished`,
+    /// or fail with [`Error::Busy`] when `max_in_flight` execs are already
+    /// running.
+    pub(crate) fn exec_started(
+        &self,
+        program: &str,
+        args: &[&str],
+        max_in_flight: usize,
+    ) -> Result<ExecTracker> {
+        let reserved = self
+            .in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max_in_flight).then_some(n + 1)
+            });
+        let exec_id = match reserved {
+            Ok(previous) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.set_gauge("sandbox_execs_in_flight", (previous + 1) as f64, &[]);
+                }
+                self.next_exec_id.fetch_add(1, Ordering::Relaxed)
+            }
+            Err(current) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.increment_counter("sandbox_execs_rejected_total", &[]);
+                }
+                return Err(Error::Busy(format!(
+                    "{} execs already in flight (limit {}); '{}' not started",
+                    current, max_in_flight, program
+                )));
+            }
+        };
+
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        self.pending.lock().unwrap().insert(
+            exec_id,
+            PendingEntry {
+                program: program.to_string(),
+                args: args.clone(),
+                started: Instant::now(),
+            },
+        );
+        let slot = InFlightSlot {
+            exec_id,
+            in_flight: self.in_flight.clone(),
+            pending: self.pending.clone(),
+            metrics: self.metrics.clone(),
+        };
+        self.emit(SandboxEvent::ExecStarted {
+            exec_id,
+            program: program.to_string(),
+            args,
+        });
+        Ok(ExecTracker {
+            events: self.clone(),
+            exec_id,
+            program: program.to_string(),
+            started: Instant::now(),
+            span: None,
+            _slot: slot,
+        })
+    }
+}
+
+/// What [`SandboxEvents::pending_execs`] reports about a running exec.
+struct PendingEntry {
+    program: String,
+    args: Vec<String>,
+    started: Instant,
+}
+
+/// An exec's share of the sandbox's in-flight limit, released on drop.
+struct InFlightSlot {
+    exec_id: u64,
+    in_flight: Arc<AtomicUsize>,
+    pending: Arc<Mutex<BTreeMap<u64, PendingEntry>>>,
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.exec_id);
+        let remaining = self.in_flight.fetch_sub(1, Ordering::SeqCst) - 1;
+        if let Some(metrics) = &self.metrics {
+            metrics.set_gauge("sandbox_execs_in_flight", remaining as f64, &[]);
+        }
+    }
+}
+
+fn record_metrics(metrics: &MetricsCollector, event: &SandboxEvent) {
+    match event {
+        SandboxEvent::Boot { .. } => metrics.increment_counter("sandbox_boots_total", &[]),
+        SandboxEvent::AgentReady { boot_ms } => {
+            metrics.set_gauge("sandbox_boot_ms", *boot_ms as f64, &[])
+        }
+        SandboxEvent::ExecStarted { .. } => {}
+        SandboxEvent::ExecFinished {
+            program,
+            exit_code,
+            duration_ms,
+            usage,
+            ..
+        } => {
+            let status = match exit_code {
+                Some(0) => "success",
+                Some(_) => "failure",
+                None => "error",
+            };
+            metrics.increment_counter(
+                "sandbox_execs_total",
+                &[("program", program), ("status", status)],
+            );
+            metrics.observe_histogram(
+                "sandbox_exec_duration_ms",
+                *duration_ms as f64,
+                EXEC_DURATION_BUCKETS_MS,
+                &[("program", program)],
+            );
+            if let Some(cpu_ms) = usage.cpu_time_ms {
+                metrics.add_counter(
+                    "sandbox_exec_cpu_seconds_total",
+                    cpu_ms as f64 / 1000.0,
+                    &[("program", program)],
+                );
+            }
+            if let Some(io) = usage.io_bytes {
+                metrics.add_counter(
+                    "sandbox_exec_io_bytes_total",
+                    io as f64,
+                    &[("program", program)],
+                );
+            }
+            if let Some(rss) = usage.max_rss_bytes {
+                metrics.observe_histogram(
+                    "sandbox_exec_max_rss_bytes",
+                    rss as f64,
+                    EXEC_RSS_BUCKETS_BYTES,
+                    &[("program", program)],
+                );
+            }
+        }
+        SandboxEvent::FileWritten { bytes, .. } => {
+            metrics.increment_counter("sandbox_files_written_total", &[]);
+            metrics.add_counter("sandbox_file_bytes_written_total", *bytes as f64, &[]);
+        }
+        SandboxEvent::TelemetryTick {
+            cpu_percent,
+            memory_used_bytes,
+            process_count,
+            ..
+        } => {
+            if let Some(cpu) = cpu_percent {
+                metrics.set_gauge("sandbox_guest_cpu_percent", *cpu, &[]);
+            }
+            if let Some(memory) = memory_used_bytes {
+                metrics.set_gauge("sandbox_guest_memory_used_bytes", *memory as f64, &[]);
+            }
+            metrics.set_gauge("sandbox_guest_processes", *process_count as f64, &[]);
+        }
+        SandboxEvent::NetworkConnection { .. } => {
+            metrics.increment_counter("sandbox_network_connections_total", &[])
+        }
+        SandboxEvent::NetworkFlow { record } => {
+            if record.denied {
+                metrics.increment_counter(
+                    "sandbox_network_denied_total",
+                    &[("protocol", record.protocol.as_str())],
+                );
+            }
+            metrics.add_counter(
+                "sandbox_network_bytes_total",
+                record.bytes_in as f64,
+                &[("direction", "in")],
+            );
+            metrics.add_counter(
+                "sandbox_network_bytes_total",
+                record.bytes_out as f64,
+                &[("direction", "out")],
+            );
+        }
+        SandboxEvent::HealthCheckFailed { .. } => {
+            metrics.increment_counter("sandbox_health_checks_failed_total", &[])
+        }
+        SandboxEvent::WatchdogExpired { .. } => {
+            metrics.increment_counter("sandbox_watchdog_expirations_total", &[])
+        }
+        SandboxEvent::Restarted { .. } => metrics.increment_counter("sandbox_restarts_total", &[]),
+        SandboxEvent::Rebooted { .. } => metrics.increment_counter("sandbox_reboots_total", &[]),
+        SandboxEvent::Shutdown { graceful } => {
+            let outcome = if graceful.is_some() {
+                "graceful"
+            } else {
+                "forced"
+            };
+            metrics.increment_counter("sandbox_shutdowns_total", &[("outcome", outcome)]);
+        }
+    }
+}
+
+/// An exec in flight, between its `ExecStarted` and `ExecFinished` events.
+pub(crate) struct ExecTracker {
+    events: SandboxEvents,
+    exec_id: u64,
+    program: String,
+    started: Instant,
+    span: Option<ExecSpan>,
+    _slot: InFlightSlot,
+}
+
+impl ExecTracker {
+    /// Also close `span` with the exec's timeline when it finishes.
+    pub(crate) fn traced(mut self, span: ExecSpan) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Run the backend call under the exec span's context, so the guest
+    /// process gets a `TRACEPARENT` chained to it. Untraced execs keep the
+    /// caller's active context.
+    pub(crate) async fn scope<F: std::future::Future>(&self, fut: F) -> F::Output {
+        match &self.span {
+            Some(span) => span.context().scope(fut).await,
+            None => fut.await,
+        }
+    }
+
+    fn finish(
+        self,
+        exit_code: Option<i32>,
+        error: Option<String>,
+        timeline: &ExecTimeline,
+        usage: ExecUsage,
+    ) {
+        if let Some(span) = self.span {
+            span.finish(exit_code, error.as_deref(), timeline);
+        }
+        self.events.emit(SandboxEvent::ExecFinished {
+            exec_id: self.exec_id,
+            program: self.program,
+            exit_code,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            error,
+            usage,
+        });
+    }
+
+    /// Emit `ExecFinished` for an exec that failed before producing output.
+    pub(crate) fn finish_error(self, error: &Error) {
+        self.finish(
+            None,
+            Some(error.to_string()),
+            &ExecTimeline::default(),
+            ExecUsage::default(),
+        );
+    }
+
+    /// Emit `ExecFinished` for a completed exec and pass the result through.
+    pub(crate) fn finish_output(self, result: Result<ExecOutput>) -> Result<ExecOutput> {
+        match &result {
+            Ok(output) => self.finish(Some(output.exit_code), None, &output.timeline, output.usage),
+            Err(e) => self.finish_error(e),
+        }
+        result
+    }
+
+    /// Relay a streaming exec's final response, emitting `ExecFinished` when
+    /// it arrives.
+    pub(crate) fn finish_streaming(
+        self,
+        response_rx: oneshot::Receiver<Result<ExecResponse>>,
+    ) -> oneshot::Receiver<Result<ExecResponse>> {
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            match response_rx.await {
+                Ok(response) => {
+                    match &response {
+                        Ok(r) => self.finish(
+                            Some(r.exit_code),
+                            r.error.clone(),
+                            &ExecTimeline::from(r),
+                            ExecUsage::from(r),
+                        ),
+                        Err(e) => self.finish(
+                            None,
+                            Some(e.to_string()),
+                            &ExecTimeline::default(),
+                            ExecUsage::default(),
+                        ),
+                    }
+                    let _ = tx.send(response);
+                }
+                Err(_) => self.finish(
+                    None,
+                    Some("exec response channel closed".into()),
+                    &ExecTimeline::default(),
+                    ExecUsage::default(),
+                ),
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::network::NetworkProtocol;
+
+    #[tokio::test]
+    async fn test_exec_tracker_pairs_start_and_finish() {
+        let events = SandboxEvents::new();
+        let mut rx = events.subscribe();
+
+        let tracker = events.exec_started("echo", &["hi"], usize::MAX).unwrap();
+        let _ = tracker.finish_output(Ok(ExecOutput::new(b"hi\n".to_vec(), Vec::new(), 0)));
+        let tracker = events.exec_started("false", &[], usize::MAX).unwrap();
+        let _ = tracker.finish_output(Ok(ExecOutput::new(Vec::new(), Vec::new(), 1)));
+
+        let SandboxEvent::ExecStarted { exec_id: first, .. } = rx.recv().await.unwrap() else {
+            panic!("expected ExecStarted");
+        };
+        match rx.recv().await.unwrap() {
+            SandboxEvent::ExecFinished {
+                exec_id, exit_code, ..
+            } => {
+                assert_eq!(exec_id, first);
+                assert_eq!(exit_code, Some(0));
+            }
+            other => panic!("expected ExecFinished, got {:?}", other),
+        }
+        let SandboxEvent::ExecStarted {
+            exec_id: second, ..
+        } = rx.recv().await.unwrap()
+        else {
+            panic!("expected ExecStarted");
+        };
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_finish_relays_response() {
+        let events = SandboxEvents::new();
+        let mut rx = events.subscribe();
+        let (tx, response_rx) = oneshot::channel();
+
+        let relayed = events
+            .exec_started("sleep", &["1"], usize::MAX)
+            .unwrap()
+            .finish_streaming(response_rx);
+        tx.send(Ok(ExecResponse::success(Vec::new(), Vec::new(), 3, 0)))
+            .unwrap();
+        assert_eq!(relayed.await.unwrap().unwrap().exit_code, 3);
+
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            SandboxEvent::ExecStarted { .. }
+        ));
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            SandboxEvent::ExecFinished {
+                exit_code: Some(3),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_exec_limit_rejects_with_busy_until_a_slot_frees() {
+        let observe = ObserveConfig::test().prometheus_listen("127.0.0.1:0");
+        let events = SandboxEvents::with_observe(Some(&observe));
+
+        let running = events.exec_started("sleep", &["10"], 1).unwrap();
+        assert_eq!(events.execs_in_flight(), 1);
+        let pending = events.pending_execs();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].program, "sleep");
+        assert_eq!(pending[0].args, vec!["10".to_string()]);
+        assert!(matches!(
+            events.exec_started("ls", &[], 1),
+            Err(Error::Busy(_))
+        ));
+        let _ = running.finish_output(Ok(ExecOutput::new(Vec::new(), Vec::new(), 0)));
+        assert_eq!(events.execs_in_flight(), 0);
+        assert!(events.pending_execs().is_empty());
+        assert!(events.exec_started("ls", &[], 1).is_ok());
+
+        let text = prometheus::exporter("127.0.0.1:0").unwrap().render();
+        let sandbox = format!("sandbox=\"{}\"", events.sandbox_id());
+        assert!(text.contains(&format!("sandbox_execs_rejected_total{{{}}} 1", sandbox)));
+        assert!(text.contains(&format!("sandbox_execs_in_flight{{{}}} 0", sandbox)));
+    }
+
+    #[test]
+    fn test_events_record_sandbox_metrics() {
+        let observe = ObserveConfig::test().prometheus_listen("127.0.0.1:0");
+        let events = SandboxEvents::with_observe(Some(&observe));
+        let mut output = ExecOutput::new(Vec::new(), Vec::new(), 2);
+        output.usage = ExecUsage {
+            cpu_time_ms: Some(1500),
+            max_rss_bytes: Some(32 * 1048576),
+            io_bytes: Some(4096),
+        };
+        let _ = events
+            .exec_started("ls", &[], usize::MAX)
+            .unwrap()
+            .finish_output(Ok(output));
+        events.emit(SandboxEvent::FileWritten {
+            path: "/workspace/a".into(),
+            bytes: 10,
+        });
+        events.emit(SandboxEvent::TelemetryTick {
+            seq: 1,
+            cpu_percent: Some(12.5),
+            memory_used_bytes: Some(4096),
+            process_count: 3,
+        });
+        events.emit(SandboxEvent::NetworkFlow {
+            record: ConnectionRecord::closed(
+                NetworkProtocol::Tcp,
+                Ipv4Addr::new(93, 184, 216, 34),
+                443,
+                2048,
+                512,
+                std::time::Duration::from_millis(40),
+            ),
+        });
+        events.emit(SandboxEvent::NetworkFlow {
+            record: ConnectionRecord::denied(
+                NetworkProtocol::Tcp,
+                Ipv4Addr::new(169, 254, 169, 254),
+                80,
+            ),
+        });
+        events.emit(SandboxEvent::HealthCheckFailed { missed: 1 });
+        events.emit(SandboxEvent::WatchdogExpired { silent_ms: 31_000 });
+        events.emit(SandboxEvent::Restarted {
+            restart: 1,
+            reason: "guest-agent missed 3 heartbeats".into(),
+        });
+
+        let text = prometheus::exporter("127.0.0.1:0").unwrap().render();
+        let sandbox = format!("sandbox=\"{}\"", events.sandbox_id());
+        for expected in [
+            format!(
+                "sandbox_execs_total{{program=\"ls\",{},status=\"failure\"}} 1",
+                sandbox
+            ),
+            format!(
+                "sandbox_exec_duration_ms_count{{program=\"ls\",{}}} 1",
+                sandbox
+            ),
+            format!(
+                "sandbox_exec_cpu_seconds_total{{program=\"ls\",{}}} 1.5",
+                sandbox
+            ),
+            format!(
+                "sandbox_exec_io_bytes_total{{program=\"ls\",{}}} 4096",
+                sandbox
+            ),
+            format!(
+                "sandbox_exec_max_rss_bytes_bucket{{program=\"ls\",{},le=\"67108864\"}} 1",
+                sandbox
+            ),
+            format!("sandbox_file_bytes_written_total{{{}}} 10", sandbox),
+            format!("sandbox_guest_cpu_percent{{{}}} 12.5", sandbox),
+            format!("sandbox_guest_memory_used_bytes{{{}}} 4096", sandbox),
+            format!(
+                "sandbox_network_bytes_total{{direction=\"in\",{}}} 2048",
+                sandbox
+            ),
+            format!(
+                "sandbox_network_bytes_total{{direction=\"out\",{}}} 512",
+                sandbox
+            ),
+            format!(
+                "sandbox_network_denied_total{{protocol=\"tcp\",{}}} 1",
+                sandbox
+            ),
+            format!("sandbox_health_checks_failed_total{{{}}} 1", sandbox),
+            format!("sandbox_watchdog_expirations_total{{{}}} 1", sandbox),
+            format!("sandbox_restarts_total{{{}}} 1", sandbox),
+        ] {
+            assert!(text.contains(&expected), "missing {}", expected);
+        }
+        assert!(SandboxEvents::new().metrics().is_none());
+    }
+
+    #[test]
+    fn test_event_serializes_with_type_tag() {
+        let json = serde_json::to_value(SandboxEvent::NetworkConnection {
+            dst_ip: Ipv4Addr::new(93, 184, 216, 34),
+            dst_port: 443,
+        })
+        .unwrap();
+        assert_eq!(json["type"], "network_connection");
+        assert_eq!(json["dst_ip"], "93.184.216.34");
+        let json = serde_json::to_value(SandboxEvent::NetworkFlow {
+            record: ConnectionRecord::denied(NetworkProtocol::Udp, Ipv4Addr::new(10, 0, 0, 1), 53),
+        })
+        .unwrap();
+        assert_eq!(json["type"], "network_flow");
+        assert_eq!(json["protocol"], "udp");
+        assert_eq!(json["denied"], true);
+        assert_eq!(
+            serde_json::to_value(SandboxEvent::Shutdown { graceful: None }).unwrap()["type"],
+            "shutdown"
+        );
+        let json = serde_json::to_value(SandboxEvent::Shutdown {
+            graceful: Some(ShutdownAck {
+                processes_terminated: 2,
+                processes_killed: 1,
+                bytes_flushed: 8192,
+                duration_ms: 40,
+            }),
+        })
+        .unwrap();
+        assert_eq!(json["graceful"]["processes_killed"], 1);
+        assert_eq!(json["graceful"]["bytes_flushed"], 8192);
+    }
+}
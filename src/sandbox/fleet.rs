@@ -0,0 +1,202 @@
+//! Booting many sandboxes at once.
+//!
+//! A batch evaluation that boots twenty sandboxes with an unbounded
+//! `join_all` spikes host memory and CPU while every guest decompresses its
+//! initramfs at the same time. [`SandboxFleet::start`] boots at most
+//! `max_concurrent` at a time and yields each sandbox as soon as its
+//! guest-agent answers. A sandbox that fails to build or boot is reported
+//! and stopped; the rest of the fleet keeps booting.
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use void_box::sandbox::{Sandbox, SandboxFleet};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let builders = (0..20)
+//!     .map(|_| Ok(Sandbox::local().from_env()?.memory_mb(512)))
+//!     .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+//! let mut fleet = SandboxFleet::start(builders, 4)?;
+//! while let Some(member) = fleet.next().await {
+//!     match member.result {
+//!         Ok(sandbox) => {
+//!             sandbox.exec("sh", &["-c", "echo ready"]).await?;
+//!         }
+//!         Err(e) => eprintln!("sandbox {} failed to boot: {}", member.index, e),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+
+use super::{Sandbox, SandboxBuilder};
+use crate::{Error, Result};
+
+/// Boots a batch of sandboxes with bounded concurrency.
+pub struct SandboxFleet;
+
+impl SandboxFleet {
+    /// Build and boot one sandbox per builder, at most `max_concurrent` at
+    /// a time. The returned stream yields each sandbox once it is ready, in
+    /// completion order; [`FleetMember::index`] is its position in
+    /// `builders`.
+    pub fn start(
+        builders: impl IntoIterator<Item = SandboxBuilder>,
+        max_concurrent: usize,
+    ) -> Result<FleetStart> {
+        if max_concurrent == 0 {
+            return Err(Error::Config(
+                "fleet max_concurrent must be at least 1".into(),
+            ));
+        }
+        let builders: Vec<SandboxBuilder> = builders.into_iter().collect();
+        let total = builders.len();
+        let members = stream::iter(builders.into_iter().enumerate())
+            .map(|(index, builder)| start_member(index, builder))
+            .buffer_unordered(max_concurrent)
+            .boxed();
+        Ok(FleetStart {
+            members,
+            total,
+            yielded: 0,
+        })
+    }
+}
+
+async fn start_member(index: usize, builder: SandboxBuilder) -> FleetMember {
+    let started = Instant::now();
+    let result = match builder.build() {
+        Ok(sandbox) => match sandbox.start().await {
+            Ok(()) => Ok(sandbox),
+            Err(e) => {
+                // Release the half-booted VM; the boot error is the one
+                // worth reporting.
+                if let Err(stop_err) = sandbox.stop().await {
+                    tracing::warn!("Failed to stop fleet sandbox {}: {}", index, stop_err);
+                }
+                Err(e)
+            }
+        },
+        Err(e) => Err(e),
+    };
+    FleetMember {
+        index,
+        result,
+        boot_time: started.elapsed(),
+    }
+}
+
+/// One sandbox of a fleet, ready or failed.
+pub struct FleetMember {
+    /// Position of the sandbox's builder in the fleet.
+    pub index: usize,
+    pub result: Result<Arc<Sandbox>>,
+    /// From starting to build the sandbox to its guest-agent answering (or
+    /// the failure).
+    pub boot_time: Duration,
+}
+
+/// Sandboxes of a fleet as they become ready; see [`SandboxFleet::start`].
+///
+/// Dropping the stream cancels the boots still in flight. Sandboxes
+/// already yielded are unaffected.
+pub struct FleetStart {
+    members: BoxStream<'static, FleetMember>,
+    total: usize,
+    yielded: usize,
+}
+
+impl FleetStart {
+    /// Sandboxes not yet yielded.
+    pub fn remaining(&self) -> usize {
+        self.total - self.yielded
+    }
+
+    /// Wait for the whole fleet, collecting the ready sandboxes and the
+    /// failures in builder order.
+    pub async fn collect_report(mut self) -> FleetReport {
+        let mut report = FleetReport::default();
+        while let Some(member) = self.next().await {
+            match member.result {
+                Ok(sandbox) => report.ready.push((member.index, sandbox)),
+                Err(e) => report.failed.push((member.index, e)),
+            }
+        }
+        report.ready.sort_by_key(|(index, _)| *index);
+        report.failed.sort_by_key(|(index, _)| *index);
+        report
+    }
+}
+
+impl Stream for FleetStart {
+    type Item = FleetMember;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FleetMember>> {
+        let polled = self.members.poll_next_unpin(cx);
+        if let Poll::Ready(Some(_)) = &polled {
+            self.yielded += 1;
+        }
+        polled
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+}
+
+/// Outcome of a whole fleet; see [`FleetStart::collect_report`].
+#[derive(Default)]
+pub struct FleetReport {
+    /// Booted sandboxes by builder index.
+    pub ready: Vec<(usize, Arc<Sandbox>)>,
+    /// Sandboxes that failed to build or boot, by builder index.
+    pub failed: Vec<(usize, Error)>,
+}
+
+impl FleetReport {
+    /// Whether every sandbox booted.
+    pub fn all_ready(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Stop every booted sandbox, returning the first error.
+    pub async fn stop_all(&self) -> Result<()> {
+        let results =
+            futures_util::future::join_all(self.ready.iter().map(|(_, sandbox)| sandbox.stop()))
+                .await;
+        results.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fleet_reports_failures_without_aborting_the_rest() {
+        let builders = vec![
+            Sandbox::mock(),
+            Sandbox::mock().max_in_flight_execs(0),
+            Sandbox::mock(),
+        ];
+        let fleet = SandboxFleet::start(builders, 2).unwrap();
+        assert_eq!(fleet.remaining(), 3);
+
+        let report = fleet.collect_report().await;
+        assert!(!report.all_ready());
+        let ready: Vec<usize> = report.ready.iter().map(|(index, _)| *index).collect();
+        assert_eq!(ready, [0, 2]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, 1);
+        assert!(matches!(report.failed[0].1, Error::Config(_)));
+        report.stop_all().await.unwrap();
+
+        assert!(SandboxFleet::start(vec![Sandbox::mock()], 0).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Structured report of what changed inside a sandbox's filesystem.
+//!
+//! Produced by [`Sandbox::fs_diff`](super::Sandbox::fs_diff) and
+//! [`Sandbox::fs_diff_at`](super::Sandbox::fs_diff_at) from the guest's
+//! `FsDiff` response; see [`FsDiffRequest`] for how the guest picks its
+//! baseline.
+//!
+//! [`FsDiffRequest`]: crate::guest::protocol::FsDiffRequest
+
+use serde::{Deserialize, Serialize};
+
+use crate::guest::protocol::FsDiffResponse;
+pub use crate::guest::protocol::{FsChange, FsChangeKind};
+
+/// Maximum number of changed paths listed in the `fs_diff.paths` span
+/// attribute; the counts always cover every change.
+const SPAN_PATHS_LIMIT: usize = 50;
+
+/// Files created, modified, or deleted in the guest relative to a baseline.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FsDiff {
+    /// Directory the diff covers (`/` for the whole root filesystem).
+    pub root: String,
+    /// Changed paths, sorted by path.
+    pub changes: Vec<FsChange>,
+    /// True when this call recorded the baseline for `root`; `changes` is
+    /// empty and the next call reports changes since now.
+    pub baseline_created: bool,
+    /// True when the guest dropped changes beyond its per-response cap.
+    pub truncated: bool,
+}
+
+impl From<FsDiffResponse> for FsDiff {
+    fn from(response: FsDiffResponse) -> Self {
+        Self {
+            root: response.root,
+            changes: response.changes,
+            baseline_created: response.baseline_created,
+            truncated: response.truncated,
+        }
+    }
+}
+
+impl FsDiff {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Changes of the given kind.
+    pub fn of_kind(&self, kind: FsChangeKind) -> impl Iterator<Item = &FsChange> {
+        self.changes.iter().filter(move |c| c.kind == kind)
+    }
+
+    /// Paths that did not exist in the baseline.
+    pub fn created(&self) -> impl Iterator<Item = &FsChange> {
+        self.of_kind(FsChangeKind::Created)
+    }
+
+    /// Paths whose content or metadata changed.
+    pub fn modified(&self) -> impl Iterator<Item = &FsChange> {
+        self.of_kind(FsChangeKind::Modified)
+    }
+
+    /// Paths that no longer exist.
+    pub fn deleted(&self) -> impl Iterator<Item = &FsChange> {
+        self.of_kind(FsChangeKind::Deleted)
+    }
+
+    /// Current size of everything created or modified, in bytes.
+    pub fn bytes_written(&self) -> u64 {
+        self.changes
+            .iter()
+            .filter(|c| c.kind != FsChangeKind::Deleted)
+            .map(|c| c.size)
+            .sum()
+    }
+
+    /// Summary attributes for a span (`fs_diff.*`).
+    ///
+    /// Counts and byte totals cover the whole diff; `fs_diff.paths` lists at
+    /// most the first 50 changed paths so that a large diff does not bloat
+    /// the span.
+    pub fn span_attributes(&self) -> Vec<(String, String)> {
+        let mut paths: Vec<&str> = self
+            .changes
+            .iter()
+            .take(SPAN_PATHS_LIMIT)
+            .map(|c| c.path.as_str())
+            .collect();
+        if self.changes.len() > SPAN_PATHS_LIMIT {
+            paths.push("...");
+        }
+        vec![
+            ("fs_diff.root".into(), self.root.clone()),
+            ("fs_diff.created".into(), self.created().count().to_string()),
+            (
+                "fs_diff.modified".into(),
+                self.modified().count().to_string(),
+            ),
+            ("fs_diff.deleted".into(), self.deleted().count().to_string()),
+            (
+                "fs_diff.bytes_written".into(),
+                self.bytes_written().to_string(),
+            ),
+            ("fs_diff.truncated".into(), self.truncated.to_string()),
+            ("fs_diff.paths".into(), paths.join(",")),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, kind: FsChangeKind, size: u64) -> FsChange {
+        FsChange {
+            path: path.into(),
+            kind,
+            size,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn test_fs_diff_summaries() {
+        let diff = FsDiff {
+            root: "/".into(),
+            changes: vec![
+                change("/a", FsChangeKind::Created, 10),
+                change("/b", FsChangeKind::Modified, 5),
+                change("/c", FsChangeKind::Deleted, 100),
+            ],
+            baseline_created: false,
+            truncated: false,
+        };
+        assert_eq!(diff.created().count(), 1);
+        assert_eq!(diff.modified().count(), 1);
+        assert_eq!(diff.deleted().count(), 1);
+        assert_eq!(diff.bytes_written(), 15);
+
+        let attrs: std::collections::HashMap<_, _> = diff.span_attributes().into_iter().collect();
+        assert_eq!(attrs["fs_diff.created"], "1");
+        assert_eq!(attrs["fs_diff.deleted"], "1");
+        assert_eq!(attrs["fs_diff.bytes_written"], "15");
+        assert_eq!(attrs["fs_diff.paths"], "/a,/b,/c");
+    }
+
+    #[test]
+    fn test_fs_diff_span_paths_are_capped() {
+        let diff = FsDiff {
+            root: "/workspace".into(),
+            changes: (0..SPAN_PATHS_LIMIT + 5)
+                .map(|i| change(&format!("/workspace/{}", i), FsChangeKind::Created, 1))
+                .collect(),
+            ..Default::default()
+        };
+        let attrs: std::collections::HashMap<_, _> = diff.span_attributes().into_iter().collect();
+        assert_eq!(attrs["fs_diff.created"], (SPAN_PATHS_LIMIT + 5).to_string());
+        assert_eq!(
+            attrs["fs_diff.paths"].split(',').count(),
+            SPAN_PATHS_LIMIT + 1
+        );
+        assert!(attrs["fs_diff.paths"].ends_with(",..."));
+    }
+}
@@ -0,0 +1,380 @@
+//! Changes to a guest workspace as a git patch.
+//!
+//! [`Sandbox::git_baseline`](super::Sandbox::git_baseline) records the
+//! current state of the workspace as a commit under
+//! `refs/void-box/baseline`, running `git init` first when the workspace is
+//! not a repository. A [`GitWorkspace`](super::GitWorkspace) records one
+//! right after the checkout. [`Sandbox::git_patch`](super::Sandbox::git_patch)
+//! then diffs the workspace against it, untracked files included (ignored
+//! files are left out), and returns a [`GitPatch`] that can be reviewed file
+//! by file and applied to the host repository with [`GitPatch::apply`].
+//!
+//! Neither step touches the workspace's branch, `HEAD` or index: both stage
+//! into a private index file, and the baseline is a detached commit.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::{Error, Result};
+
+/// Ref the baseline commit is stored under.
+pub const BASELINE_REF: &str = "refs/void-box/baseline";
+
+/// Shared setup: trust the workspace whoever owns it, `cd` into it
+/// (argument 1), and define `stage_all`, which stages the whole workspace
+/// into a private index.
+const SCRIPT_PRELUDE: &str = r#"set -e
+export GIT_CONFIG_COUNT=2
+export GIT_CONFIG_KEY_0=safe.directory GIT_CONFIG_VALUE_0='*'
+export GIT_CONFIG_KEY_1=core.quotepath GIT_CONFIG_VALUE_1=false
+cd "$1"
+stage_all() {
+  export GIT_INDEX_FILE="$(git rev-parse --git-dir)/void-box-index"
+  # Hand new objects back to the workspace owner; execs may run as root.
+  trap 'rm -f "$GIT_INDEX_FILE"; chown -R "$(stat -c %u:%g .)" .git 2>/dev/null || true' EXIT
+  git add -A
+}
+"#;
+
+/// Records the baseline; prints its commit id.
+const BASELINE_SCRIPT: &str = r#"[ -d .git ] || git init -q
+stage_all
+tree=$(git write-tree)
+if parent=$(git rev-parse -q --verify HEAD); then set -- -p "$parent"; else set --; fi
+export GIT_AUTHOR_NAME=void-box GIT_AUTHOR_EMAIL=void-box@localhost
+export GIT_COMMITTER_NAME=void-box GIT_COMMITTER_EMAIL=void-box@localhost
+commit=$(echo "void-box baseline" | git commit-tree "$tree" "$@")
+git update-ref refs/void-box/baseline "$commit"
+echo "$commit"
+"#;
+
+/// Prints the baseline commit id on the first line, then the diff.
+/// Falls back to `HEAD` for repositories without a recorded baseline.
+const DIFF_SCRIPT: &str = r#"base=$(git rev-parse -q --verify refs/void-box/baseline 2>/dev/null ||
+  git rev-parse -q --verify HEAD 2>/dev/null) || {
+  echo "no git baseline in $PWD; call git_baseline() first" >&2
+  exit 3
+}
+stage_all
+echo "$base"
+git diff --cached --binary --find-renames --no-color --no-ext-diff "$base"
+"#;
+
+/// `sh -c` arguments recording the baseline of `dir`.
+pub(crate) fn baseline_args(dir: &str) -> [String; 4] {
+    script_args(BASELINE_SCRIPT, dir)
+}
+
+/// `sh -c` arguments printing the diff of `dir` (see [`GitPatch::parse`]).
+pub(crate) fn diff_args(dir: &str) -> [String; 4] {
+    script_args(DIFF_SCRIPT, dir)
+}
+
+fn script_args(body: &str, dir: &str) -> [String; 4] {
+    [
+        "-c".into(),
+        format!("{SCRIPT_PRELUDE}{body}"),
+        "sh".into(),
+        dir.into(),
+    ]
+}
+
+/// How a file changed relative to the baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    /// Moved, possibly with edits; see [`FileDiff::old_path`].
+    Renamed,
+}
+
+/// One file's share of a [`GitPatch`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileDiff {
+    /// Path relative to the workspace root, after the change.
+    pub path: String,
+    /// Path before a rename.
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    /// Lines added; 0 for binary files.
+    pub additions: usize,
+    /// Lines removed; 0 for binary files.
+    pub deletions: usize,
+    pub binary: bool,
+    /// This file's section of the patch, from its `diff --git` line.
+    pub diff: String,
+}
+
+/// Everything that changed in a workspace since its baseline.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GitPatch {
+    /// Commit id of the baseline the patch applies to.
+    pub base: String,
+    /// Changed files, in patch order (sorted by path).
+    pub files: Vec<FileDiff>,
+    /// The whole patch, as `git apply` takes it.
+    pub diff: String,
+}
+
+impl GitPatch {
+    /// Parse the output of the guest diff script: the base commit id on the
+    /// first line, then `git diff --binary` output.
+    pub(crate) fn parse(output: &str) -> Self {
+        let (base, diff) = output.split_once('\n').unwrap_or((output, ""));
+        let mut files = Vec::new();
+        // Patch lines start with ' ', '+' or '-', so a `diff --git` at the
+        // start of a line always opens a file.
+        let mut sections = diff
+            .match_indices("diff --git ")
+            .map(|(i, _)| i)
+            .filter(|&i| i == 0 || diff.as_bytes()[i - 1] == b'\n')
+            .peekable();
+        while let Some(start) = sections.next() {
+            let end = sections.peek().copied().unwrap_or(diff.len());
+            files.push(FileDiff::parse(&diff[start..end]));
+        }
+        Self {
+            base: base.trim().to_string(),
+            files,
+            diff: diff.to_string(),
+        }
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Total lines added across text files.
+    pub fn additions(&self) -> usize {
+        self.files.iter().map(|f| f.additions).sum()
+    }
+
+    /// Total lines removed across text files.
+    pub fn deletions(&self) -> usize {
+        self.files.iter().map(|f| f.deletions).sum()
+    }
+
+    /// The file at `path` (its post-change path), if it changed.
+    pub fn file(&self, path: &str) -> Option<&FileDiff> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Apply the patch to the working tree of the host repository at
+    /// `repo`, which should be at [`base`](Self::base)'s content. Nothing is
+    /// applied unless every file applies cleanly.
+    pub async fn apply(&self, repo: impl AsRef<Path>) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut child = tokio::process::Command::new("git")
+            .args(["apply", "--whitespace=nowarn", "-"])
+            .current_dir(repo.as_ref())
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(self.diff.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(Error::Sandbox(format!(
+                "git apply in {} failed: {}",
+                repo.as_ref().display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FileDiff {
+    fn parse(section: &str) -> Self {
+        let mut lines = section.lines();
+        let header = lines.next().unwrap_or_default();
+        // `diff --git a/<path> b/<path>`: both halves are equal unless the
+        // file was renamed, which the `rename from/to` lines then cover.
+        let paths = header.trim_start_matches("diff --git ");
+        let split = 2 + paths.len().saturating_sub(5) / 2;
+        let mut path = paths
+            .get(2..split)
+            .filter(|_| paths.get(split..split + 3) == Some(" b/"))
+            .unwrap_or(paths)
+            .to_string();
+        let mut old_path = None;
+        let mut status = FileStatus::Modified;
+        let (mut additions, mut deletions, mut binary) = (0, 0, false);
+        let mut in_hunk = false;
+        for line in lines {
+            if in_hunk {
+                match line.as_bytes().first() {
+                    Some(b'+') => additions += 1,
+                    Some(b'-') => deletions += 1,
+                    _ => {}
+                }
+            } else if line.starts_with("new file mode") {
+                status = FileStatus::Added;
+            } else if line.starts_with("deleted file mode") {
+                status = FileStatus::Deleted;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                status = FileStatus::Renamed;
+                old_path = Some(from.to_string());
+            } else if let Some(to) = line.strip_prefix("rename to ") {
+                path = to.to_string();
+            } else if line == "GIT binary patch" || line.starts_with("Binary files ") {
+                binary = true;
+                break;
+            } else if line.starts_with("@@") {
+                in_hunk = true;
+            }
+        }
+        Self {
+            path,
+            old_path,
+            status,
+            additions,
+            deletions,
+            binary,
+            diff: section.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a guest script against a host directory.
+    fn run(args: [String; 4]) -> String {
+        let output = std::process::Command::new("sh")
+            .args(&args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    }
+
+    #[test]
+    fn test_parse_classifies_files() {
+        let patch = GitPatch::parse(
+            "abc123\n\
+             diff --git a/src/lib.rs b/src/lib.rs\n\
+             index 1..2 100644\n\
+             --- a/src/lib.rs\n\
+             +++ b/src/lib.rs\n\
+             @@ -1,2 +1,2 @@\n\
+             -fn old() {}\n\
+             +fn new() {}\n \
+             // same\n\
+             diff --git a/docs/new notes.md b/docs/new notes.md\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/docs/new notes.md\n\
+             @@ -0,0 +1 @@\n\
+             +hello\n\
+             diff --git a/a.txt b/b.txt\n\
+             similarity index 100%\n\
+             rename from a.txt\n\
+             rename to b.txt\n\
+             diff --git a/logo.png b/logo.png\n\
+             deleted file mode 100644\n\
+             GIT binary patch\n\
+             literal 0\n",
+        );
+        assert_eq!(patch.base, "abc123");
+        let summary: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.as_str(),
+                    f.status,
+                    f.additions,
+                    f.deletions,
+                    f.binary,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("src/lib.rs", FileStatus::Modified, 1, 1, false),
+                ("docs/new notes.md", FileStatus::Added, 1, 0, false),
+                ("b.txt", FileStatus::Renamed, 0, 0, false),
+                ("logo.png", FileStatus::Deleted, 0, 0, true),
+            ]
+        );
+        assert_eq!(
+            patch.file("b.txt").unwrap().old_path.as_deref(),
+            Some("a.txt")
+        );
+        assert!(patch.files[1]
+            .diff
+            .starts_with("diff --git a/docs/new notes.md"));
+        assert!(GitPatch::parse("abc123\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scripts_diff_against_baseline_and_apply_on_host() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path().to_str().unwrap();
+        std::fs::write(workspace.path().join("keep.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(workspace.path().join("gone.txt"), "bye\n").unwrap();
+
+        let base = run(baseline_args(dir));
+        let unchanged = GitPatch::parse(&run(diff_args(dir)));
+        assert_eq!(unchanged.base, base.trim());
+        assert!(unchanged.is_empty());
+
+        let host = tempfile::tempdir().unwrap();
+        for entry in ["keep.txt", "gone.txt"] {
+            std::fs::copy(workspace.path().join(entry), host.path().join(entry)).unwrap();
+        }
+
+        std::fs::write(workspace.path().join("keep.txt"), "one\n2\n").unwrap();
+        std::fs::remove_file(workspace.path().join("gone.txt")).unwrap();
+        std::fs::write(workspace.path().join("new.txt"), "fresh\n").unwrap();
+        let patch = GitPatch::parse(&run(diff_args(dir)));
+        assert_eq!(patch.base, base.trim());
+        let statuses: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("gone.txt", FileStatus::Deleted),
+                ("keep.txt", FileStatus::Modified),
+                ("new.txt", FileStatus::Added),
+            ]
+        );
+        assert_eq!((patch.additions(), patch.deletions()), (2, 2));
+        // No index of the workspace's own was written.
+        assert!(!workspace.path().join(".git/index").exists());
+
+        git(host.path(), &["init", "-q"]);
+        patch.apply(host.path()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(host.path().join("keep.txt")).unwrap(),
+            "one\n2\n"
+        );
+        assert!(!host.path().join("gone.txt").exists());
+        assert!(patch.apply(host.path()).await.is_err());
+    }
+}
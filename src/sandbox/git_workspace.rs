@@ -0,0 +1,620 @@
+//! Seeding the guest workspace from a git repository.
+//!
+//! A [`GitWorkspace`] is checked out into the guest (at `/workspace` by
+//! default) every time the VM boots, before the first exec runs, so
+//! workflows and agents start from the repository instead of an empty
+//! directory. A restarted VM is seeded again.
+//!
+//! The checkout can happen in two places:
+//!
+//! - [`CloneLocation::Host`] (default): `git` runs on the host, once per
+//!   sandbox, and the working tree — `.git` included — is uploaded as a tar
+//!   archive with the chunked file transfer and unpacked in the guest. The
+//!   guest needs no `git` and no network, and the token never enters it.
+//!   The archive is staged inside the checkout directory, so that directory
+//!   must be under one of the guest-agent's write roots.
+//! - [`CloneLocation::Guest`]: `git` runs inside the guest, which needs the
+//!   binary and network access to the remote. The token is passed to that one
+//!   exec through the environment.
+//!
+//! Either way the checkout is the same: `git init`, `git fetch` of the
+//! requested revision (a branch, tag or commit id; `HEAD` when unset),
+//! `git checkout FETCH_HEAD`, then submodules if asked for. Fetches are
+//! shallow (depth 1) unless [`GitWorkspace::depth`] says otherwise. The
+//! token is supplied through a credential helper set in the environment
+//! (`GIT_CONFIG_*`), so it is never written to `.git/config`.
+//!
+//! When the guest has `git`, the checkout is then recorded as the baseline
+//! for [`Sandbox::git_patch`](super::Sandbox::git_patch).
+
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::backend::VmmBackend;
+use crate::{Error, Result};
+
+/// File name the host-side archive is uploaded to, inside the checkout
+/// directory, before unpacking.
+const GUEST_ARCHIVE_NAME: &str = ".void-box-workspace.tar";
+
+/// Checkout script shared by both locations. Arguments: target directory,
+/// URL, revision, depth (empty for a full fetch), `1` for submodules. The
+/// URL and revision follow `--`, so a value like `--upload-pack=<cmd>` is
+/// not taken for an option.
+const CHECKOUT_SCRIPT: &str = r#"set -e
+dir=$1 url=$2 rev=$3 depth=$4 submodules=$5
+mkdir -p "$dir"
+cd "$dir"
+git init -q
+git remote add -- origin "$url"
+git fetch -q ${depth:+--depth "$depth"} -- origin "$rev"
+git -c advice.detachedHead=false checkout -q FETCH_HEAD
+if [ "$submodules" = 1 ]; then
+  git submodule update -q --init --recursive ${depth:+--depth "$depth"}
+fi
+"#;
+
+/// Credential helper answering with `$GIT_TOKEN`, for HTTPS remotes.
+const CREDENTIAL_HELPER: &str =
+    r#"!f() { echo username=x-access-token; echo "password=$GIT_TOKEN"; }; f"#;
+
+/// Where a [`GitWorkspace`] is checked out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CloneLocation {
+    /// Clone on the host and upload the tree as an archive.
+    #[default]
+    Host,
+    /// Clone inside the guest.
+    Guest,
+}
+
+/// A git repository to check out into the guest workspace.
+///
+/// ```no_run
+/// use void_box::sandbox::{CloneLocation, GitWorkspace, Sandbox};
+///
+/// let sandbox = Sandbox::local()
+///     .git_workspace_with(
+///         GitWorkspace::new("https://github.com/the-void-ia/void-box", Some("main"))
+///             .submodules(true)
+///             .location(CloneLocation::Guest),
+///     )
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GitWorkspace {
+    url: String,
+    rev: Option<String>,
+    depth: Option<u32>,
+    submodules: bool,
+    location: CloneLocation,
+    token: Option<SecretString>,
+    path: String,
+}
+
+impl GitWorkspace {
+    /// Check out `rev` (a branch, tag or commit id; the remote's `HEAD` when
+    /// `None`) of the repository at `url`.
+    pub fn new(url: impl Into<String>, rev: Option<&str>) -> Self {
+        Self {
+            url: url.into(),
+            rev: rev.map(str::to_string),
+            depth: Some(1),
+            submodules: false,
+            location: CloneLocation::Host,
+            token: None,
+            path: "/workspace".to_string(),
+        }
+    }
+
+    /// Fetch only the last `depth` commits; `None` fetches full history.
+    /// Default `Some(1)`.
+    pub fn depth(mut self, depth: Option<u32>) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Also check out submodules, recursively, at the same depth.
+    pub fn submodules(mut self, enable: bool) -> Self {
+        self.submodules = enable;
+        self
+    }
+
+    /// Clone on the host (default) or inside the guest.
+    pub fn location(mut self, location: CloneLocation) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// Token for HTTPS remotes, sent as the password for `x-access-token`
+    /// (GitHub and GitLab style).
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(SecretString::from(token.into()));
+        self
+    }
+
+    /// Guest directory to check out into. Default `/workspace`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// The repository URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The requested revision, if any.
+    pub fn rev(&self) -> Option<&str> {
+        self.rev.as_deref()
+    }
+
+    /// Whether the revision is a full commit id (SHA-1 or SHA-256), so every
+    /// checkout gets the same tree. Branches, tags and `HEAD` can move.
+    pub(crate) fn is_pinned(&self) -> bool {
+        self.rev.as_deref().is_some_and(|rev| {
+            matches!(rev.len(), 40 | 64) && rev.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+    }
+
+    pub(crate) fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    pub(crate) fn checkout_path(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn clone_location(&self) -> CloneLocation {
+        self.location
+    }
+
+    /// Guest path the host-side archive is staged at. The guest-agent only
+    /// accepts uploads inside its write roots, which the checkout directory
+    /// has to be in anyway.
+    fn archive_path(&self) -> String {
+        format!("{}/{}", self.path.trim_end_matches('/'), GUEST_ARCHIVE_NAME)
+    }
+
+    /// Positional arguments for [`CHECKOUT_SCRIPT`], checking out into `dir`.
+    fn script_args<'a>(&'a self, dir: &'a str, depth: &'a str) -> [&'a str; 7] {
+        [
+            "-c",
+            CHECKOUT_SCRIPT,
+            "sh",
+            dir,
+            &self.url,
+            self.rev.as_deref().unwrap_or("HEAD"),
+            depth,
+        ]
+    }
+
+    /// Environment that gives every `git` in the script the token, if any.
+    fn credential_env(&self) -> Vec<(String, String)> {
+        let Some(token) = &self.token else {
+            return Vec::new();
+        };
+        vec![
+            ("GIT_TOKEN".to_string(), token.expose_secret().to_string()),
+            ("GIT_TERMINAL_PROMPT".to_string(), "0".to_string()),
+            ("GIT_CONFIG_COUNT".to_string(), "1".to_string()),
+            (
+                "GIT_CONFIG_KEY_0".to_string(),
+                "credential.helper".to_string(),
+            ),
+            (
+                "GIT_CONFIG_VALUE_0".to_string(),
+                CREDENTIAL_HELPER.to_string(),
+            ),
+        ]
+    }
+
+    fn depth_arg(&self) -> String {
+        self.depth.map(|d| d.to_string()).unwrap_or_default()
+    }
+
+    fn submodules_arg(&self) -> &'static str {
+        if self.submodules {
+            "1"
+        } else {
+            "0"
+        }
+    }
+
+    /// Check the repository out on the host and return the tree as a tar
+    /// archive, paths relative to the checkout root.
+    pub(crate) async fn archive_on_host(&self) -> Result<Vec<u8>> {
+        let dir = tempfile::tempdir()?;
+        let dir_str = dir.path().to_string_lossy().into_owned();
+        let depth = self.depth_arg();
+        let output = tokio::process::Command::new("sh")
+            .args(self.script_args(&dir_str, &depth))
+            .arg(self.submodules_arg())
+            .envs(self.credential_env())
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await
+            .map_err(|e| Error::Config(format!("failed to run git on the host: {e}")))?;
+        if !output.status.success() {
+            return Err(Error::Config(format!(
+                "git checkout of {} failed: {}",
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+            let mut builder = tar::Builder::new(Vec::new());
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", dir.path())?;
+            Ok(builder.into_inner()?)
+        })
+        .await
+        .map_err(|e| Error::Config(format!("workspace archive task failed: {e}")))?
+    }
+
+    /// Populate the guest checkout: unpack `archive` (from
+    /// [`archive_on_host`](Self::archive_on_host)) if given, otherwise run
+    /// the checkout in the guest.
+    pub(crate) async fn seed(
+        &self,
+        backend: &dyn VmmBackend,
+        archive: Option<&[u8]>,
+    ) -> Result<()> {
+        let output = match archive {
+            Some(archive) => {
+                let archive_path = self.archive_path();
+                super::local::stream_file(backend, &archive_path, archive).await?;
+                backend
+                    .exec(
+                        "sh",
+                        &[
+                            "-c",
+                            r#"mkdir -p "$1" && tar -xf "$2" -C "$1"; status=$?; rm -f "$2"; exit $status"#,
+                            "sh",
+                            &self.path,
+                            &archive_path,
+                        ],
+                        &[],
+                        &[],
+                        None,
+                        None,
+                    )
+                    .await?
+            }
+            None => {
+                let depth = self.depth_arg();
+                let mut args = self.script_args(&self.path, &depth).to_vec();
+                args.push(self.submodules_arg());
+                backend
+                    .exec("sh", &args, &[], &self.credential_env(), None, None)
+                    .await?
+            }
+        };
+        if output.exit_code != 0 {
+            return Err(Error::Guest(format!(
+                "seeding {} from {} failed: {}",
+                self.path,
+                self.url,
+                output.stderr_str().trim()
+            )));
+        }
+        // Best-effort: the guest may have no git when cloning on the host.
+        let baseline = super::git_patch::baseline_args(&self.path);
+        let baseline: Vec<&str> = baseline.iter().map(String::as_str).collect();
+        match backend.exec("sh", &baseline, &[], &[], None, None).await {
+            Ok(output) if output.exit_code == 0 => {}
+            Ok(output) => tracing::debug!(
+                "no git baseline for {}: {}",
+                self.path,
+                output.stderr_str().trim()
+            ),
+            Err(e) => tracing::debug!("no git baseline for {}: {}", self.path, e),
+        }
+        // The guest-agent runs as root; the agent runs as the sandbox user.
+        let _ = backend
+            .exec(
+                "sh",
+                &[
+                    "-c",
+                    r#"chown -R 1000:1000 "$1" 2>/dev/null; true"#,
+                    "sh",
+                    &self.path,
+                ],
+                &[],
+                &[],
+                None,
+                None,
+            )
+            .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn archived_file(archive: &[u8], name: &str) -> Option<String> {
+        let mut archive = tar::Archive::new(archive);
+        archive.entries().unwrap().find_map(|entry| {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            (path.trim_start_matches("./") == name).then(|| {
+                let mut content = String::new();
+                std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+                content
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_archive_on_host_checks_out_requested_rev() {
+        let repo = tempfile::tempdir().unwrap();
+        git(repo.path(), &["init", "-q", "-b", "main"]);
+        std::fs::write(repo.path().join("README.md"), "v1").unwrap();
+        git(repo.path(), &["add", "."]);
+        git(repo.path(), &["commit", "-q", "-m", "v1"]);
+        let first = git(repo.path(), &["rev-parse", "HEAD"]);
+        std::fs::write(repo.path().join("README.md"), "v2").unwrap();
+        git(repo.path(), &["commit", "-q", "-am", "v2"]);
+        let url = format!("file://{}", repo.path().display());
+
+        let head = GitWorkspace::new(&url, None)
+            .archive_on_host()
+            .await
+            .unwrap();
+        assert_eq!(archived_file(&head, "README.md").as_deref(), Some("v2"));
+        assert!(archived_file(&head, ".git/HEAD").is_some());
+
+        let pinned = GitWorkspace::new(&url, Some(&first))
+            .depth(None)
+            .archive_on_host()
+            .await
+            .unwrap();
+        assert_eq!(archived_file(&pinned, "README.md").as_deref(), Some("v1"));
+
+        let err = GitWorkspace::new(&url, Some("no-such-branch"))
+            .archive_on_host()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("git checkout of"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_archive_on_host_does_not_take_arguments_for_options() {
+        let repo = tempfile::tempdir().unwrap();
+        git(repo.path(), &["init", "-q", "-b", "main"]);
+        std::fs::write(repo.path().join("README.md"), "v1").unwrap();
+        git(repo.path(), &["add", "."]);
+        git(repo.path(), &["commit", "-q", "-m", "v1"]);
+        let url = format!("file://{}", repo.path().display());
+
+        let marker = repo.path().join("pwned");
+        let inject = format!("--upload-pack=touch {}; git-upload-pack", marker.display());
+        let err = GitWorkspace::new(&inject, None)
+            .archive_on_host()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("git checkout of"), "{err}");
+        let err = GitWorkspace::new(&url, Some(&inject))
+            .archive_on_host()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("git checkout of"), "{err}");
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_only_full_commit_ids_are_pinned() {
+        let url = "https://example.com/r.git";
+        assert!(GitWorkspace::new(url, Some(&"a".repeat(40))).is_pinned());
+        assert!(GitWorkspace::new(url, Some(&"0f".repeat(32))).is_pinned());
+        assert!(!GitWorkspace::new(url, Some("main")).is_pinned());
+        assert!(!GitWorkspace::new(url, Some("deadbeef")).is_pinned());
+        assert!(!GitWorkspace::new(url, None).is_pinned());
+    }
+
+    /// Backend that refuses uploads outside the guest-agent's default write
+    /// roots, as `fs_guard` does, and records the execs it is asked to run.
+    #[derive(Default)]
+    struct RootedBackend {
+        uploads: std::sync::Mutex<Vec<String>>,
+        execs: std::sync::Mutex<Vec<Vec<String>>>,
+    }
+
+    impl RootedBackend {
+        fn check_root(path: &str) -> Result<()> {
+            let allowed = ["/workspace", "/home", "/etc/voidbox"]
+                .iter()
+                .any(|root| std::path::Path::new(path).starts_with(root));
+            if allowed {
+                Ok(())
+            } else {
+                Err(Error::Guest(format!(
+                    "Refusing write outside allowed roots: {path}"
+                )))
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl VmmBackend for RootedBackend {
+        async fn start(&mut self, _config: crate::backend::BackendConfig) -> Result<()> {
+            Ok(())
+        }
+
+        async fn exec(
+            &self,
+            program: &str,
+            args: &[&str],
+            _stdin: &[u8],
+            _env: &[(String, String)],
+            _working_dir: Option<&str>,
+            _timeout_secs: Option<u64>,
+        ) -> Result<crate::ExecOutput> {
+            let mut argv = vec![program.to_string()];
+            argv.extend(args.iter().map(|a| a.to_string()));
+            self.execs.lock().unwrap().push(argv);
+            Ok(crate::ExecOutput::new(Vec::new(), Vec::new(), 0))
+        }
+
+        async fn exec_streaming(
+            &self,
+            _program: &str,
+            _args: &[&str],
+            _env: &[(String, String)],
+            _working_dir: Option<&str>,
+            _timeout_secs: Option<u64>,
+            _exec_id: Option<&str>,
+        ) -> Result<(
+            tokio::sync::mpsc::Receiver<crate::guest::protocol::ExecOutputChunk>,
+            tokio::sync::oneshot::Receiver<Result<crate::guest::protocol::ExecResponse>>,
+        )> {
+            unimplemented!()
+        }
+
+        async fn write_file(&self, path: &str, _content: &[u8]) -> Result<()> {
+            Self::check_root(path)
+        }
+
+        async fn write_file_chunk(&self, path: &str, offset: u64, data: &[u8]) -> Result<u64> {
+            Self::check_root(path)?;
+            Ok(offset + data.len() as u64)
+        }
+
+        async fn finalize_write_file(&self, path: &str, _total_size: u64) -> Result<()> {
+            Self::check_root(path)?;
+            self.uploads.lock().unwrap().push(path.to_string());
+            Ok(())
+        }
+
+        async fn mkdir_p(&self, path: &str) -> Result<()> {
+            Self::check_root(path)
+        }
+
+        async fn file_stat(&self, _path: &str) -> Result<crate::guest::protocol::FileStatResponse> {
+            unimplemented!()
+        }
+
+        async fn read_file_native(&self, _path: &str) -> Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn hash_files(
+            &self,
+            _paths: &[String],
+        ) -> Result<Vec<crate::guest::protocol::FileHash>> {
+            unimplemented!()
+        }
+
+        async fn symlink(&self, _request: crate::guest::protocol::SymlinkRequest) -> Result<()> {
+            unimplemented!()
+        }
+
+        async fn chmod(&self, _request: crate::guest::protocol::ChmodRequest) -> Result<()> {
+            unimplemented!()
+        }
+
+        async fn read_link(&self, _path: &str) -> Result<String> {
+            unimplemented!()
+        }
+
+        async fn sysinfo(&self) -> Result<crate::guest::protocol::SysInfo> {
+            unimplemented!()
+        }
+
+        async fn fs_diff(
+            &self,
+            _root: Option<&str>,
+        ) -> Result<crate::guest::protocol::FsDiffResponse> {
+            unimplemented!()
+        }
+
+        async fn export_workspace(&self, _path_filter: Option<&str>) -> Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn start_telemetry(
+            &mut self,
+            _observer: crate::observe::Observer,
+            _opts: crate::guest::protocol::TelemetrySubscribeRequest,
+            _ring_buffer: Option<crate::observe::telemetry::TelemetryBuffer>,
+        ) -> Result<std::sync::Arc<crate::observe::telemetry::TelemetryAggregator>> {
+            unimplemented!()
+        }
+
+        fn set_span_context(&mut self, _ctx: crate::observe::tracer::SpanContext) {}
+
+        fn control_channel(
+            &self,
+        ) -> Option<std::sync::Arc<crate::backend::control_channel::ControlChannel>> {
+            None
+        }
+
+        async fn attach_pty(
+            &self,
+            _request: void_box_protocol::PtyOpenRequest,
+        ) -> Result<crate::backend::pty_session::PtySession> {
+            unimplemented!()
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn cid(&self) -> u32 {
+            3
+        }
+
+        fn kind(&self) -> crate::backend::Backend {
+            crate::backend::Backend::Kvm
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seed_stages_archive_inside_write_roots() {
+        let backend = RootedBackend::default();
+        GitWorkspace::new("https://example.com/r.git", None)
+            .seed(&backend, Some(b"archive"))
+            .await
+            .unwrap();
+        let staged = "/workspace/.void-box-workspace.tar".to_string();
+        assert_eq!(*backend.uploads.lock().unwrap(), vec![staged.clone()]);
+        let unpack = backend.execs.lock().unwrap()[0].clone();
+        assert_eq!(unpack[4..], ["/workspace".to_string(), staged]);
+
+        let err = GitWorkspace::new("https://example.com/r.git", None)
+            .path("/srv/repo")
+            .seed(&RootedBackend::default(), Some(b"archive"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("allowed roots"), "{err}");
+    }
+
+    #[test]
+    fn test_token_only_reaches_git_through_env() {
+        let workspace = GitWorkspace::new("https://example.com/r.git", None).token("s3cret");
+        assert!(!format!("{workspace:?}").contains("s3cret"));
+        let env = workspace.credential_env();
+        assert!(env.contains(&("GIT_TOKEN".to_string(), "s3cret".to_string())));
+        assert!(!CREDENTIAL_HELPER.contains("s3cret"));
+        assert!(GitWorkspace::new("https://example.com/r.git", None)
+            .credential_env()
+            .is_empty());
+    }
+}
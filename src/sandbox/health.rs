@@ -0,0 +1,276 @@
+//! Guest health checks and automatic VM restarts.
+//!
+//! With a [`HealthCheck`] configured, a local sandbox runs a health monitor
+//! that pings the guest-agent every `interval` once it has finished its
+//! handshake. A probe that fails or outlasts `timeout` is a missed
+//! heartbeat; `max_missed` in a row mark the guest
+//! [`HealthStatus::Unhealthy`] and fail the execs still running on it with
+//! a [`CrashKind::Unresponsive`](crate::observe::crash::CrashKind::Unresponsive)
+//! crash report.
+//!
+//! Under [`RestartPolicy::OnFailure`] the monitor then replaces the VM with
+//! a fresh boot, and [`Sandbox::recover`](super::Sandbox::recover) does the
+//! same for a caller whose exec died with the guest, so the interrupted work
+//! can be re-run. Nothing written into the old guest survives: callers that
+//! provisioned files or skills provision them again before re-running.
+//!
+//! Heartbeats go through the guest-agent's RPC loop, so they cannot tell a
+//! busy agent from a hung kernel and take `interval * max_missed` to
+//! notice either. With a [`watchdog`](super::SandboxBuilder::watchdog) the
+//! VM also gets a [watchdog device](crate::devices::watchdog) the agent
+//! pets from its own thread; a guest that misses the deadline gets a
+//! [`CrashKind::WatchdogExpired`](crate::observe::crash::CrashKind::WatchdogExpired)
+//! crash report, even with no exec running, and goes through the same
+//! restart path.
+
+use std::sync::Weak;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use super::LocalSandbox;
+use crate::{Error, Result};
+
+/// How often, and how patiently, the guest-agent is pinged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthCheck {
+    /// Time between heartbeats.
+    pub interval: Duration,
+    /// How long one heartbeat may take before it counts as missed.
+    pub timeout: Duration,
+    /// Consecutive misses after which the guest is unhealthy.
+    pub max_missed: u32,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(2),
+            max_missed: 3,
+        }
+    }
+}
+
+impl HealthCheck {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.interval.is_zero() || self.timeout.is_zero() {
+            return Err(Error::Config(
+                "health check interval and timeout must be non-zero".into(),
+            ));
+        }
+        if self.max_missed == 0 {
+            return Err(Error::Config(
+                "health check max_missed must be at least 1".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What to do when the guest dies or stops answering heartbeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// Leave the VM down; execs keep failing until the sandbox is stopped.
+    #[default]
+    Never,
+    /// Boot a fresh VM, at most `max_restarts` times over the sandbox's
+    /// lifetime.
+    OnFailure { max_restarts: u32 },
+}
+
+impl RestartPolicy {
+    /// Whether another restart is allowed after `restarts` so far.
+    pub fn allows(&self, restarts: u32) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure { max_restarts } => restarts < *max_restarts,
+        }
+    }
+}
+
+/// The guest-agent's liveness as seen by the health monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HealthStatus {
+    /// No VM is running, or health checks are not configured.
+    #[default]
+    Stopped,
+    /// The VM is up and the agent has not answered a heartbeat yet.
+    Starting,
+    /// The last heartbeat was answered.
+    Healthy,
+    /// Fewer than `max_missed` heartbeats in a row went unanswered.
+    Degraded { missed: u32 },
+    /// `max_missed` or more heartbeats in a row went unanswered.
+    Unhealthy { missed: u32 },
+}
+
+/// Counts consecutive missed heartbeats.
+#[derive(Debug)]
+pub(crate) struct HeartbeatTracker {
+    max_missed: u32,
+    missed: u32,
+}
+
+impl HeartbeatTracker {
+    pub(crate) fn new(max_missed: u32) -> Self {
+        Self {
+            max_missed,
+            missed: 0,
+        }
+    }
+
+    /// Record one heartbeat outcome and return the resulting status.
+    pub(crate) fn record(&mut self, answered: bool) -> HealthStatus {
+        if answered {
+            self.missed = 0;
+            return HealthStatus::Healthy;
+        }
+        self.missed = self.missed.saturating_add(1);
+        if self.missed >= self.max_missed {
+            HealthStatus::Unhealthy {
+                missed: self.missed,
+            }
+        } else {
+            HealthStatus::Degraded {
+                missed: self.missed,
+            }
+        }
+    }
+
+    /// Whether the last [`record`](Self::record) crossed into unhealthy.
+    pub(crate) fn just_failed(&self) -> bool {
+        self.missed == self.max_missed
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.missed = 0;
+    }
+}
+
+/// Background task pinging a local sandbox's guest-agent. Holds only a weak
+/// reference, and is aborted when dropped with the sandbox.
+pub(crate) struct HealthMonitor {
+    task: JoinHandle<()>,
+}
+
+impl HealthMonitor {
+    pub(crate) fn spawn(sandbox: Weak<LocalSandbox>, check: HealthCheck) -> Self {
+        Self {
+            task: tokio::spawn(run(sandbox, check)),
+        }
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Background task polling a local sandbox's watchdog device. Holds only a
+/// weak reference, and is aborted when dropped with the sandbox.
+pub(crate) struct WatchdogMonitor {
+    task: JoinHandle<()>,
+}
+
+impl WatchdogMonitor {
+    pub(crate) fn spawn(sandbox: Weak<LocalSandbox>, timeout: Duration) -> Self {
+        Self {
+            task: tokio::spawn(watch(sandbox, timeout)),
+        }
+    }
+}
+
+impl Drop for WatchdogMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn watch(sandbox: Weak<LocalSandbox>, timeout: Duration) {
+    // The device keeps the time of the last pet; polling only bounds how
+    // late past the deadline an expiry is noticed.
+    let mut ticks = tokio::time::interval(timeout / 4);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let Some(sandbox) = sandbox.upgrade() else {
+            return;
+        };
+        if let Some(silent) = sandbox.watchdog_check().await {
+            sandbox.watchdog_expired(silent).await;
+        }
+    }
+}
+
+async fn run(sandbox: Weak<LocalSandbox>, check: HealthCheck) {
+    let mut tracker = HeartbeatTracker::new(check.max_missed);
+    let mut ticks = tokio::time::interval(check.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let Some(sandbox) = sandbox.upgrade() else {
+            return;
+        };
+        // Nothing to probe until the agent of the current boot is ready.
+        let Some(result) = sandbox.heartbeat(check.timeout).await else {
+            tracker.reset();
+            continue;
+        };
+        let status = tracker.record(result.is_ok());
+        sandbox.set_health(status);
+        if let Err(e) = result {
+            tracing::warn!("Guest heartbeat missed ({}): {}", tracker.missed, e);
+            sandbox.heartbeat_missed(tracker.missed);
+        }
+        if tracker.just_failed() {
+            sandbox.unhealthy(tracker.missed).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_marks_unhealthy_after_max_missed() {
+        let mut tracker = HeartbeatTracker::new(3);
+        assert_eq!(tracker.record(false), HealthStatus::Degraded { missed: 1 });
+        assert_eq!(tracker.record(true), HealthStatus::Healthy);
+        assert_eq!(tracker.record(false), HealthStatus::Degraded { missed: 1 });
+        assert_eq!(tracker.record(false), HealthStatus::Degraded { missed: 2 });
+        assert!(!tracker.just_failed());
+        assert_eq!(tracker.record(false), HealthStatus::Unhealthy { missed: 3 });
+        assert!(tracker.just_failed());
+        assert_eq!(tracker.record(false), HealthStatus::Unhealthy { missed: 4 });
+        assert!(!tracker.just_failed());
+    }
+
+    #[test]
+    fn test_restart_policy_allows() {
+        assert!(!RestartPolicy::Never.allows(0));
+        let policy = RestartPolicy::OnFailure { max_restarts: 2 };
+        assert!(policy.allows(0));
+        assert!(policy.allows(1));
+        assert!(!policy.allows(2));
+    }
+
+    #[test]
+    fn test_health_check_validation() {
+        assert!(HealthCheck::default().validate().is_ok());
+        let zero_missed = HealthCheck {
+            max_missed: 0,
+            ..HealthCheck::default()
+        };
+        assert!(zero_missed.validate().is_err());
+        let zero_interval = HealthCheck {
+            interval: Duration::ZERO,
+            ..HealthCheck::default()
+        };
+        assert!(zero_interval.validate().is_err());
+    }
+}
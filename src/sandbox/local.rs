@@ -3,23 +3,72 @@
 //! Uses the platform-appropriate VM backend (KVM on Linux, VZ on macOS)
 //! via the `VmmBackend` trait.
 
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
-use tokio::sync::Mutex;
+use secrecy::ExposeSecret;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{mpsc, Mutex};
 
 use void_box_protocol::SessionSecret;
 
-use super::SandboxConfig;
-use crate::backend::{BackendConfig, BackendSecurityConfig, VmmBackend};
-use crate::guest::protocol::TelemetrySubscribeRequest;
-use crate::observe::telemetry::{TelemetryAggregator, TelemetryBuffer};
-use crate::observe::{ObserveConfig, Observer};
+use super::clock::{ClockSync, ClockSyncMonitor};
+use super::console::ConsoleStream;
+use super::git_workspace::CloneLocation;
+use super::health::{HealthMonitor, HealthStatus, WatchdogMonitor};
+use super::stdin::ExecStdin;
+use super::users::GuestUser;
+use super::{
+    ArtifactBundle, DiskSpec, FsDiff, SandboxConfig, SandboxEvent, SandboxEvents, SysInfo,
+};
+use crate::backend::control_channel::{self, ControlChannel};
+use crate::backend::{
+    guest_host_gateway, Backend, BackendCaps, BackendConfig, BackendSecurityConfig,
+    ConnectionObserver, ConsoleObserver, DiskConfig, DnsConfig, MountConfig, NetworkPolicy,
+    ProtocolTap, VmmBackend, DEFAULT_SHUTDOWN_TIMEOUT,
+};
+use crate::guest::protocol::{
+    ExecPolicy, ExecResponse, ExecSignal, ShutdownAck, TelemetrySubscribeRequest,
+    WRITE_FILE_CHUNK_SIZE,
+};
+use crate::observe::boot::{
+    BootDiagnostics, BootTimeline, PHASE_FIRST_SERIAL_BYTE, PHASE_HANDSHAKE,
+};
+use crate::observe::console::ConsoleCapture;
+use crate::observe::crash::{self, ConsoleTail, CrashKind, CrashReport, CrashSink};
+use crate::observe::exec_span::ExecSpan;
+use crate::observe::network::NetworkLog;
+use crate::observe::telemetry::{TelemetryAggregator, TelemetryBuffer, TelemetryStats};
+use crate::observe::{ObserveConfig, Observer, SpanContext};
+use crate::proxy::recording::{HttpRecording, HttpRecordingProxy, GUEST_RECORDING_CA_PATH};
+use crate::secret::{Redactor, ResolvedSecret, Secret, SecretDelivery, GUEST_SECRETS_DIR};
+use crate::volume::{Volume, VolumeBacking, DEFAULT_VOLUME_SIZE_GB};
 use crate::{Error, ExecOutput, Result};
 
 const DEFAULT_NETWORK_DENY_LIST: &[&str] = &["169.254.0.0/16"];
 const DEFAULT_MAX_CONNECTIONS_PER_SECOND: u32 = 50;
 const DEFAULT_MAX_CONCURRENT_CONNECTIONS: usize = 64;
 
+/// Attempts per chunk in [`LocalSandbox::write_file_streaming`]. Chunks carry
+/// their own offset, so resending one after a failed or timed-out RPC is
+/// idempotent on the guest side.
+const WRITE_FILE_CHUNK_ATTEMPTS: u32 = 3;
+
+/// Guest mount point of the first scratch disk; later ones get an index
+/// suffix (`/scratch1`, …).
+const SCRATCH_MOUNT_POINT: &str = "/scratch";
+
+/// How long a restart waits for execs on the old VM to let go of it before
+/// leaving it to be torn down by the last one.
+const RESTART_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+const RESTART_DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long a crash report waits for the rest of a kernel panic report
+/// once its first line has been printed.
+const PANIC_REPORT_WAIT: Duration = Duration::from_secs(2);
+
 fn default_network_deny_list() -> Vec<String> {
     DEFAULT_NETWORK_DENY_LIST
         .iter()
@@ -34,22 +83,258 @@ pub struct LocalSandbox {
     /// concurrent operational access. Operational methods clone the Arc and
     /// drop the lock immediately so long-running execs don't block file RPC.
     backend: Mutex<Option<Arc<dyn VmmBackend>>>,
-    started: std::sync::atomic::AtomicBool,
+    started: AtomicBool,
+    /// Set once the current boot's guest-agent finished its handshake.
+    agent_ready: Arc<AtomicBool>,
+    events: SandboxEvents,
+    /// Observer built from `config.observe`, if set.
+    observer: Option<Observer>,
+    /// Guest console tap, when `observe.capture_console` is enabled.
+    console: Option<Arc<ConsoleCapture>>,
+    /// Streams handed out by [`attach_console`](Self::attach_console);
+    /// closed ones are dropped on the next output.
+    console_taps: Arc<std::sync::Mutex<Vec<mpsc::UnboundedSender<Vec<u8>>>>>,
+    /// Console tail and crash report for the current boot.
+    crash: Arc<CrashWatch>,
+    /// Phase timings of the current boot, once its guest-agent answered.
+    boot_timeline: Arc<std::sync::Mutex<Option<BootTimeline>>>,
+    /// Aggregator from the last `start_telemetry`, sampled by exec spans.
+    telemetry: std::sync::Mutex<Weak<TelemetryAggregator>>,
+    /// Guest connections reported by the network stack.
+    network_log: Arc<NetworkLog>,
+    /// Proxy for `config.http_recording`, started with the first boot and
+    /// kept across restarts so a recording is not truncated.
+    http_proxy: std::sync::OnceLock<HttpRecordingProxy>,
+    /// `config.dns_servers` and `config.host_aliases`, validated.
+    dns: DnsConfig,
+    /// Holds the scratch disk images and the layered initramfs, created
+    /// with the first boot and kept across restarts; removed when the
+    /// sandbox is dropped.
+    scratch_dir: std::sync::OnceLock<tempfile::TempDir>,
+    /// Handle the health monitor reaches the sandbox through, set by
+    /// [`attach`](Self::attach).
+    this: std::sync::OnceLock<Weak<LocalSandbox>>,
+    /// Spawned with the first boot when `config.health_check` is set.
+    monitor: std::sync::OnceLock<HealthMonitor>,
+    health: std::sync::Mutex<HealthStatus>,
+    /// VM restarts under `config.restart_policy` so far.
+    restarts: AtomicU32,
+    /// Successful boots so far, restarts included.
+    boots: AtomicU32,
+    /// Spawned with the first boot when `config.clock_sync` is set.
+    clock_monitor: std::sync::OnceLock<ClockSyncMonitor>,
+    /// Spawned with the first boot when `config.watchdog` is set.
+    watchdog_monitor: std::sync::OnceLock<WatchdogMonitor>,
+    /// Serializes restarts from the monitor and [`recover`](Self::recover).
+    restart_lock: Mutex<()>,
+    /// Host-side checkout of `config.git_workspace`, made with the first
+    /// boot and unpacked again on restarts.
+    workspace_archive: tokio::sync::OnceCell<Vec<u8>>,
+    /// `config.secrets`, read when the sandbox was created.
+    secrets: Vec<ResolvedSecret>,
+    redactor: Arc<Redactor>,
+    /// Exec policy sent to the guest-agent after each boot: starts as
+    /// `config.exec_policy`, replaced by [`set_exec_policy`](Self::set_exec_policy).
+    exec_policy: std::sync::Mutex<Option<ExecPolicy>>,
+    /// Users created after each boot, in uid order: `config.users`, then
+    /// those added by [`create_user`](Self::create_user).
+    users: std::sync::Mutex<Vec<String>>,
 }
 
 impl LocalSandbox {
     pub fn new(config: SandboxConfig) -> Result<Self> {
+        let dns = DnsConfig::parse(&config.dns_servers, &config.host_aliases)?;
+        if config.disks.contains(&DiskSpec::Scratch { size_gb: 0 }) {
+            return Err(Error::Config(
+                "scratch disk size must be at least 1 GiB".into(),
+            ));
+        }
+        let secrets = config
+            .secrets
+            .iter()
+            .map(Secret::resolve)
+            .collect::<Result<Vec<_>>>()?;
+        let redactor = Arc::new(Redactor::new(&secrets));
+        let events = SandboxEvents::with_observe(config.observe.as_ref());
+        let observer = config.observe.clone().map(Observer::new);
+        let console = observer
+            .as_ref()
+            .filter(|_| config.observe.as_ref().is_some_and(|o| o.capture_console))
+            .map(|o| Arc::new(ConsoleCapture::new(o.clone()).with_redactor(redactor.clone())));
+        let network_log = match &observer {
+            Some(observer) => NetworkLog::default().with_observer(observer.clone()),
+            None => NetworkLog::default(),
+        };
+        let crash = Arc::new(CrashWatch {
+            tail: ConsoleTail::new(),
+            report: std::sync::Mutex::new(None),
+            boot_failure: std::sync::Mutex::new(None),
+            hung: tokio::sync::watch::channel(None).0,
+            watchdog_expired: AtomicBool::new(false),
+            sink: config.crash_sink.clone(),
+            events: events.clone(),
+            redactor: redactor.clone(),
+        });
         Ok(Self {
+            exec_policy: std::sync::Mutex::new(config.exec_policy.clone()),
+            users: std::sync::Mutex::new(config.users.clone()),
             config,
             backend: Mutex::new(None),
-            started: std::sync::atomic::AtomicBool::new(false),
+            started: AtomicBool::new(false),
+            agent_ready: Arc::new(AtomicBool::new(false)),
+            events,
+            observer,
+            console,
+            console_taps: Arc::default(),
+            boot_timeline: Arc::default(),
+            crash,
+            telemetry: std::sync::Mutex::new(Weak::new()),
+            network_log: Arc::new(network_log),
+            http_proxy: std::sync::OnceLock::new(),
+            dns,
+            scratch_dir: std::sync::OnceLock::new(),
+            this: std::sync::OnceLock::new(),
+            monitor: std::sync::OnceLock::new(),
+            health: std::sync::Mutex::new(HealthStatus::Stopped),
+            restarts: AtomicU32::new(0),
+            boots: AtomicU32::new(0),
+            clock_monitor: std::sync::OnceLock::new(),
+            watchdog_monitor: std::sync::OnceLock::new(),
+            restart_lock: Mutex::new(()),
+            workspace_archive: tokio::sync::OnceCell::new(),
+            secrets,
+            redactor,
         })
     }
 
-    /// Start the sandbox VM
+    /// Give the sandbox a handle to itself, so its health monitor can be
+    /// started with the first boot. Without it no monitor runs.
+    pub(crate) fn attach(self: &Arc<Self>) {
+        let _ = self.this.set(Arc::downgrade(self));
+    }
+
+    /// The observer built from the sandbox's [`ObserveConfig`], which
+    /// receives captured console output.
+    pub fn observer(&self) -> Option<&Observer> {
+        self.observer.as_ref()
+    }
+
+    /// Start an exec span on the sandbox's observer, sampling the child's
+    /// CPU/RSS from telemetry if it is running.
+    pub(crate) fn start_exec_span(&self, program: &str, args: &[&str]) -> Option<ExecSpan> {
+        let span = self.observer.as_ref()?.start_exec_span(program, args);
+        Some(span.with_telemetry(self.telemetry.lock().unwrap().clone()))
+    }
+
+    /// Guest telemetry over the last `window`, if telemetry is running.
+    pub(crate) fn telemetry_stats(&self, window: Duration) -> Option<TelemetryStats> {
+        let aggregator = self.telemetry.lock().unwrap().upgrade()?;
+        Some(aggregator.stats(window))
+    }
+
+    /// Connections the guest opened or was refused, across restarts.
+    pub fn network_log(&self) -> &Arc<NetworkLog> {
+        &self.network_log
+    }
+
+    /// The lifecycle event stream this sandbox emits into.
+    pub fn events(&self) -> &SandboxEvents {
+        &self.events
+    }
+
+    fn scratch_dir(&self) -> Result<&Path> {
+        if self.scratch_dir.get().is_none() {
+            let dir = tempfile::Builder::new()
+                .prefix("void-box-scratch-")
+                .tempdir()?;
+            let _ = self.scratch_dir.set(dir);
+        }
+        Ok(self
+            .scratch_dir
+            .get()
+            .expect("scratch dir initialized")
+            .path())
+    }
+
+    /// `config.protocol_tap`, keeping frame metadata only when the sandbox
+    /// hands the guest secrets or a git token: exec environments and file
+    /// writes would otherwise carry them into the trace.
+    fn resolve_protocol_tap(&self) -> Option<ProtocolTap> {
+        let tap = self.config.protocol_tap.clone()?;
+        let git_token = self
+            .config
+            .git_workspace
+            .as_ref()
+            .is_some_and(|workspace| workspace.has_token());
+        if self.secrets.is_empty() && !git_token {
+            Some(tap)
+        } else {
+            Some(tap.max_payload_bytes(0))
+        }
+    }
+
+    /// `config.initramfs` with `config.initramfs_overlays` appended, written
+    /// to the scratch dir the first time it is needed.
+    fn resolve_initramfs(&self) -> Result<Option<PathBuf>> {
+        let Some(base) = &self.config.initramfs else {
+            return Ok(None);
+        };
+        if self.config.initramfs_overlays.is_empty() {
+            return Ok(Some(base.clone()));
+        }
+        let path = self.scratch_dir()?.join("initramfs.cpio.gz");
+        if !path.exists() {
+            let mut image = std::fs::read(base).map_err(|e| {
+                Error::Config(format!("cannot read initramfs {}: {}", base.display(), e))
+            })?;
+            image.extend(crate::guest_image::overlay_dirs(
+                &self.config.initramfs_overlays,
+            )?);
+            std::fs::write(&path, image)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// `config.disks` as backend disks, creating scratch images the first
+    /// time they are needed.
+    fn resolve_disks(&self) -> Result<Vec<DiskConfig>> {
+        let mut disks = Vec::with_capacity(self.config.disks.len());
+        let mut scratch_count = 0;
+        for (i, spec) in self.config.disks.iter().enumerate() {
+            match spec {
+                DiskSpec::Scratch { size_gb } => {
+                    let path = self.scratch_dir()?.join(format!("disk{i}.img"));
+                    if !path.exists() {
+                        crate::volume::create_ext4_image(&path, *size_gb)?;
+                    }
+                    let guest_path = match scratch_count {
+                        0 => SCRATCH_MOUNT_POINT.to_string(),
+                        n => format!("{SCRATCH_MOUNT_POINT}{n}"),
+                    };
+                    scratch_count += 1;
+                    disks.push(DiskConfig {
+                        path,
+                        read_only: false,
+                        guest_path: Some(guest_path),
+                    });
+                }
+                DiskSpec::Attach { path, read_only } => disks.push(DiskConfig {
+                    path: path.clone(),
+                    read_only: *read_only,
+                    guest_path: None,
+                }),
+            }
+        }
+        Ok(disks)
+    }
+
+    /// Start the sandbox VM. A guest that never answers its handshake fails
+    /// with [`Error::BootFailed`].
     async fn ensure_started(&self) -> Result<()> {
-        use std::sync::atomic::Ordering;
+        self.start_vm().await.map_err(|e| self.crash.boot_failed(e))
+    }
 
+    async fn start_vm(&self) -> Result<()> {
         if self.started.load(Ordering::SeqCst) {
             return Ok(());
         }
@@ -72,25 +357,71 @@ impl LocalSandbox {
         getrandom::fill(&mut session_secret_bytes)
             .map_err(|e| Error::Config(format!("Failed to generate session secret: {}", e)))?;
 
+        if let Some(recording) = &self.config.http_recording {
+            if self.http_proxy.get().is_none() {
+                let _ = self.http_proxy.set(
+                    HttpRecordingProxy::start(recording, self.config.network_policy.clone())
+                        .await?,
+                );
+            }
+        }
+        // Replay must not reach the real network; the proxy sits on the
+        // gateway, which the policy exempts.
+        let network_policy = self.config.network_policy.clone().or_else(|| {
+            matches!(self.config.http_recording, Some(HttpRecording::Replay(_)))
+                .then(NetworkPolicy::deny_all)
+        });
+
+        let read_only = self.config.read_only;
+        let mut disks = self.resolve_disks()?;
+        let mut mounts = self.config.mounts.clone();
+        for volume in &self.config.volumes {
+            let resolved = Volume::open_or_create(&volume.name, DEFAULT_VOLUME_SIZE_GB)?;
+            match resolved.info().backing {
+                VolumeBacking::Disk => disks.push(DiskConfig {
+                    path: resolved.disk_path(),
+                    read_only,
+                    guest_path: Some(volume.guest_path.clone()),
+                }),
+                VolumeBacking::Directory => mounts.push(MountConfig {
+                    host_path: resolved.data_path().to_string_lossy().into_owned(),
+                    guest_path: volume.guest_path.clone(),
+                    read_only,
+                }),
+            }
+        }
+        // Nothing a read-only run does may outlive it on the host.
+        if read_only {
+            for mount in &mut mounts {
+                mount.read_only = true;
+            }
+        }
+
         let backend_config = BackendConfig {
             memory_mb: self.config.memory_mb,
             vcpus: self.config.vcpus,
             kernel,
-            initramfs: self.config.initramfs.clone(),
+            initramfs: self.resolve_initramfs()?,
             rootfs: self.config.rootfs.clone(),
             network: self.config.network,
+            network_mode: self.config.network_mode.clone(),
             enable_vsock: self.config.enable_vsock,
             guest_console: self.config.guest_console.clone(),
+            console_shell: self.config.console_shell,
+            boot_profile: self.config.boot_profile,
             shared_dir: self.config.shared_dir.clone(),
-            mounts: self.config.mounts.clone(),
+            mounts,
             oci_rootfs: self.config.oci_rootfs.clone(),
             oci_rootfs_dev: self.config.oci_rootfs_dev.clone(),
             oci_rootfs_disk: self.config.oci_rootfs_disk.clone(),
+            disks,
             env: self.config.env.clone(),
+            dns: self.dns.clone(),
             security: BackendSecurityConfig {
                 session_secret: SessionSecret::new(session_secret_bytes),
                 command_allowlist: Vec::new(), // Set via provisioning
                 network_deny_list: default_network_deny_list(),
+                network_policy,
                 max_connections_per_second: self
                     .config
                     .network_max_connections_per_second
@@ -100,21 +431,435 @@ impl LocalSandbox {
                     .network_max_concurrent_connections
                     .unwrap_or(DEFAULT_MAX_CONCURRENT_CONNECTIONS),
                 seccomp: true,
+                write_roots: self.config.write_roots.clone(),
             },
             snapshot: self.config.snapshot.clone(),
             enable_snapshots: self.config.enable_snapshots || self.config.snapshot.is_some(),
+            resource_policy: self.config.resource_policy.clone(),
+            protocol_tap: self.resolve_protocol_tap(),
+            watchdog: self.config.watchdog,
+            extra_cmdline: self.config.extra_cmdline.clone(),
+            rosetta: self.config.rosetta,
         };
 
         // Create platform-appropriate backend
-        let mut backend = crate::backend::create_backend();
-        backend.start(backend_config).await?;
+        let boot_started = std::time::Instant::now();
+        let mut backend = crate::backend::create_backend()?;
+        let events = self.events.clone();
+        let closed_events = self.events.clone();
+        let network_log = self.network_log.clone();
+        backend.set_connection_observer(
+            ConnectionObserver::new(move |dst_ip, dst_port| {
+                events.emit(SandboxEvent::NetworkConnection { dst_ip, dst_port });
+            })
+            .on_close(move |record| {
+                network_log.record(record.clone());
+                closed_events.emit(SandboxEvent::NetworkFlow {
+                    record: record.clone(),
+                });
+            }),
+        );
+        self.crash.reset();
+        self.agent_ready.store(false, Ordering::SeqCst);
+        *self.boot_timeline.lock().unwrap() = None;
+        let crash = self.crash.clone();
+        let console = self.console.clone();
+        let console_taps = self.console_taps.clone();
+        let first_serial_byte = Arc::new(std::sync::OnceLock::new());
+        let first_byte = first_serial_byte.clone();
+        backend.set_console_observer(ConsoleObserver::new(move |bytes| {
+            first_byte.get_or_init(Instant::now);
+            crash.tail.feed(bytes);
+            if let Some(console) = &console {
+                console.feed(bytes);
+            }
+            console_taps
+                .lock()
+                .unwrap()
+                .retain(|tap| tap.send(bytes.to_vec()).is_ok());
+        }));
+        if let Err(e) = backend.start(backend_config).await {
+            if let Some(console) = &self.console {
+                console.mark_failed(&e.to_string());
+            }
+            return Err(e);
+        }
+        let vm_running = Instant::now();
+        let mut timeline = BootTimeline::new(boot_started);
+        backend.record_boot_phases(&mut timeline);
+        if let Err(e) = self.provision_guest(&*backend).await {
+            let _ = backend.stop().await;
+            return Err(e);
+        }
+        self.events.emit(SandboxEvent::Boot {
+            memory_mb: self.config.memory_mb,
+            vcpus: self.config.vcpus,
+            network: self.config.network,
+            from_snapshot: self.config.snapshot.is_some(),
+        });
+
+        // The handshake completes in the background; report it without
+        // holding a backend reference, which would block stop().
+        if let Some(channel) = backend.control_channel() {
+            let events = self.events.clone();
+            let console = self.console.clone();
+            let agent_ready = self.agent_ready.clone();
+            let crash = self.crash.clone();
+            let observer = self.observer.clone();
+            let boot_timeline = self.boot_timeline.clone();
+            let boot_profile = self.config.boot_profile;
+            tokio::spawn(async move {
+                match channel.ensure_connected().await {
+                    Ok(_) => {
+                        if let Some(&first_byte) = first_serial_byte.get() {
+                            let guest_started = boot_started + timeline.guest_origin();
+                            timeline.record(PHASE_FIRST_SERIAL_BYTE, guest_started, first_byte);
+                        }
+                        timeline.record_guest_phases(&crash.tail.contents());
+                        let connected = channel.first_connected_at().unwrap_or_else(Instant::now);
+                        timeline.record(PHASE_HANDSHAKE, vm_running, connected);
+                        if let Some(observer) = &observer {
+                            timeline.record_spans(
+                                observer.tracer(),
+                                &[
+                                    ("sandbox", events.sandbox_id()),
+                                    ("backend_type", crate::observe::backend_type()),
+                                    ("boot.profile", boot_profile.as_str()),
+                                ],
+                            );
+                        }
+                        *boot_timeline.lock().unwrap() = Some(timeline);
+                        agent_ready.store(true, Ordering::SeqCst);
+                        if let Some(console) = console {
+                            console.mark_ready();
+                        }
+                        events.emit(SandboxEvent::AgentReady {
+                            boot_ms: boot_started.elapsed().as_millis() as u64,
+                        });
+                    }
+                    Err(e) => {
+                        let e = crash.boot_failed(e);
+                        if let Some(console) = console {
+                            console.mark_failed(&e.to_string());
+                        }
+                    }
+                }
+            });
+        }
 
         *backend_lock = Some(Arc::from(backend));
         self.started.store(true, Ordering::SeqCst);
+        self.boots.fetch_add(1, Ordering::SeqCst);
+
+        if let Some(check) = self.config.health_check {
+            self.set_health(HealthStatus::Starting);
+            if let Some(this) = self.this.get() {
+                self.monitor
+                    .get_or_init(|| HealthMonitor::spawn(this.clone(), check));
+            }
+        }
+        if let (Some(interval), Some(this)) = (self.config.clock_sync, self.this.get()) {
+            self.clock_monitor
+                .get_or_init(|| ClockSyncMonitor::spawn(this.clone(), interval));
+        }
+        if let (Some(timeout), Some(this)) = (self.config.watchdog, self.this.get()) {
+            self.watchdog_monitor
+                .get_or_init(|| WatchdogMonitor::spawn(this.clone(), timeout));
+        }
 
         Ok(())
     }
 
+    /// Lay down what a fresh guest needs before the sandbox's execs run:
+    /// clock baseline, recording CA, secret files, workspace, users, exec
+    /// policy and read-only mode. Runs after every boot and reboot.
+    async fn provision_guest(&self, backend: &dyn VmmBackend) -> Result<()> {
+        // First, so everything written during boot gets the same times.
+        if self.config.deterministic.is_some() {
+            set_clock_baseline(backend).await?;
+        }
+        if let Some(proxy) = self.http_proxy.get() {
+            backend.mkdir_p("/home/sandbox").await?;
+            backend
+                .write_file(GUEST_RECORDING_CA_PATH, proxy.ca_cert_pem().as_bytes())
+                .await?;
+        }
+        self.write_secret_files(backend).await?;
+        if let Some(workspace) = &self.config.git_workspace {
+            let archive = match workspace.clone_location() {
+                CloneLocation::Host => Some(
+                    self.workspace_archive
+                        .get_or_try_init(|| workspace.archive_on_host())
+                        .await?
+                        .as_slice(),
+                ),
+                CloneLocation::Guest => None,
+            };
+            workspace.seed(backend, archive).await?;
+        }
+        let users = self.users.lock().unwrap().clone();
+        for name in &users {
+            create_user(backend, name).await?;
+        }
+        // Last, so seeding the workspace is not subject to them.
+        let policy = self.exec_policy.lock().unwrap().clone();
+        if let Some(policy) = policy {
+            send_exec_policy(backend, &policy).await?;
+        }
+        if self.config.read_only {
+            enter_read_only(backend).await?;
+        }
+        Ok(())
+    }
+
+    /// The guest-agent's liveness as last seen by the health monitor.
+    pub fn health_status(&self) -> HealthStatus {
+        *self.health.lock().unwrap()
+    }
+
+    pub(crate) fn set_health(&self, status: HealthStatus) {
+        *self.health.lock().unwrap() = status;
+    }
+
+    /// Phase timings of the current boot, once its guest-agent answered.
+    pub fn boot_timeline(&self) -> Option<BootTimeline> {
+        self.boot_timeline.lock().unwrap().clone()
+    }
+
+    /// VM restarts under the sandbox's restart policy so far.
+    pub fn restart_count(&self) -> u32 {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    /// Ping the current boot's guest-agent. `None` until it has finished
+    /// its handshake, or when the backend has no control channel.
+    pub(crate) async fn heartbeat(&self, timeout: Duration) -> Option<Result<()>> {
+        if !self.agent_ready.load(Ordering::SeqCst) {
+            return None;
+        }
+        // Hold the channel, not the backend, so a restart is not blocked.
+        let channel = self.backend.lock().await.as_ref()?.control_channel()?;
+        Some(channel.heartbeat(timeout).await)
+    }
+
+    pub(crate) fn heartbeat_missed(&self, missed: u32) {
+        self.events.emit(SandboxEvent::HealthCheckFailed { missed });
+    }
+
+    /// Successful boots so far; changes with every restart.
+    pub(crate) fn boot_count(&self) -> u32 {
+        self.boots.load(Ordering::SeqCst)
+    }
+
+    /// Set the guest clock to the host's, booting the VM first if needed.
+    pub(crate) async fn sync_clock(&self) -> Result<ClockSync> {
+        let backend = self.get_backend().await?;
+        let channel = backend.control_channel().ok_or(Error::VmNotRunning)?;
+        sync_clock(&channel).await
+    }
+
+    /// [`sync_clock`](Self::sync_clock) for the clock monitor: `None`
+    /// until the current boot's agent has finished its handshake.
+    pub(crate) async fn sync_clock_if_ready(&self) -> Option<Result<ClockSync>> {
+        if !self.agent_ready.load(Ordering::SeqCst) {
+            return None;
+        }
+        let channel = self.backend.lock().await.as_ref()?.control_channel()?;
+        Some(sync_clock(&channel).await)
+    }
+
+    /// The guest stopped answering heartbeats: fail the execs running on
+    /// it, and restart it if the policy allows.
+    pub(crate) async fn unhealthy(&self, missed: u32) {
+        let reason = format!("guest-agent missed {missed} heartbeats");
+        tracing::error!("Sandbox {} unhealthy: {}", self.events.sandbox_id(), reason);
+        self.crash.mark_hung(reason.clone());
+        if let Err(e) = self.restart(&reason).await {
+            tracing::error!("Failed to restart sandbox VM: {}", e);
+        }
+    }
+
+    /// How long the current boot has gone without petting its watchdog,
+    /// once that exceeds the timeout; reported once per expiry.
+    pub(crate) async fn watchdog_check(&self) -> Option<Duration> {
+        self.backend.lock().await.as_ref()?.watchdog_expired()
+    }
+
+    /// The guest stopped petting its watchdog: report the crash, fail the
+    /// execs running on it, and restart it if the policy allows.
+    pub(crate) async fn watchdog_expired(&self, silent: Duration) {
+        let reason = format!(
+            "guest stopped petting its watchdog ({}s without a pet)",
+            silent.as_secs()
+        );
+        tracing::error!("Sandbox {} hung: {}", self.events.sandbox_id(), reason);
+        self.events.emit(SandboxEvent::WatchdogExpired {
+            silent_ms: silent.as_millis() as u64,
+        });
+        self.crash.watchdog_expired(reason.clone()).await;
+        if let Err(e) = self.restart(&reason).await {
+            tracing::error!("Failed to restart sandbox VM: {}", e);
+        }
+    }
+
+    /// Whether the current boot is dead: it crashed under an exec, failed
+    /// its health checks, or its VM has exited.
+    async fn needs_restart(&self) -> bool {
+        if matches!(self.health_status(), HealthStatus::Unhealthy { .. })
+            || self.crash.report.lock().unwrap().is_some()
+        {
+            return true;
+        }
+        self.backend
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|backend| !backend.is_running())
+    }
+
+    /// Restart the VM after `err` if the guest died under it and the
+    /// restart policy allows. Returns `true` when a fresh VM is running, so
+    /// the failed work can be re-provisioned and re-run; `false` when `err`
+    /// was an ordinary failure or no restarts are left.
+    pub async fn recover(&self, err: &Error) -> Result<bool> {
+        if !matches!(err, Error::GuestCrashed(_)) && !self.needs_restart().await {
+            return Ok(false);
+        }
+        self.restart(&err.to_string()).await
+    }
+
+    /// Replace a dead VM with a fresh boot. A VM already replaced by a
+    /// concurrent caller counts as restarted.
+    async fn restart(&self, reason: &str) -> Result<bool> {
+        let _restarting = self.restart_lock.lock().await;
+        if !self.needs_restart().await {
+            return Ok(true);
+        }
+        let restarts = self.restarts.load(Ordering::SeqCst);
+        if !self.config.restart_policy.allows(restarts) {
+            return Ok(false);
+        }
+        // Execs still waiting on the old guest fail now rather than at
+        // their timeouts, and release it.
+        self.crash.mark_hung(reason.to_string());
+        {
+            let mut backend_lock = self.backend.lock().await;
+            self.started.store(false, Ordering::SeqCst);
+            if let Some(backend) = backend_lock.take() {
+                retire(backend).await;
+            }
+        }
+        self.set_health(HealthStatus::Stopped);
+        let restart = restarts + 1;
+        self.restarts.store(restart, Ordering::SeqCst);
+        tracing::warn!(
+            "Restarting sandbox {} VM ({}): {}",
+            self.events.sandbox_id(),
+            restart,
+            reason
+        );
+        self.events.emit(SandboxEvent::Restarted {
+            restart,
+            reason: reason.to_string(),
+        });
+        self.ensure_started().await?;
+        Ok(true)
+    }
+
+    /// Environment for a guest exec: the HTTP recording proxy's settings, the
+    /// deterministic seed, the sandbox env and secrets, then `extra`, then a
+    /// `TRACEPARENT` for the active span context unless one was given.
+    fn exec_env(&self, extra: &[(String, String)]) -> Vec<(String, String)> {
+        let mut env = self
+            .http_proxy
+            .get()
+            .map(|proxy| proxy.guest_env(guest_host_gateway()))
+            .unwrap_or_default();
+        if let Some(seed) = self.config.deterministic {
+            env.extend(super::deterministic::guest_env(seed));
+        }
+        env.extend(self.config.env.iter().cloned());
+        env.extend(self.secrets.iter().map(ResolvedSecret::env_entry));
+        env.extend(extra.iter().cloned());
+        env.extend(crate::guest::protocol::current_exec_overrides().env);
+        if !self.config.path_prepend.is_empty() {
+            // The guest applies entries in order, so the last PATH wins.
+            let base = env
+                .iter()
+                .rev()
+                .find(|(k, _)| k == "PATH")
+                .map_or(void_box_protocol::GUEST_PATH, |(_, v)| v.as_str());
+            let path = format!("{}:{}", self.config.path_prepend.join(":"), base);
+            env.push(("PATH".to_string(), path));
+        }
+        if let Some(ctx) = SpanContext::current() {
+            if !env.iter().any(|(k, _)| k == "TRACEPARENT") {
+                env.push(("TRACEPARENT".to_string(), ctx.to_traceparent()));
+            }
+        }
+        env
+    }
+
+    /// Working directory for an exec: the caller's, else the sandbox's.
+    fn exec_working_dir(&self) -> Option<String> {
+        crate::guest::protocol::current_exec_overrides()
+            .working_dir
+            .or_else(|| self.config.working_dir.clone())
+    }
+
+    /// Write the file-delivered secrets into a directory only the sandbox
+    /// user can read.
+    ///
+    /// The directory and files are created by the guest-agent itself (as
+    /// root, inside its write roots, handed to the sandbox user); execs run
+    /// as the sandbox user, so the shell steps only tighten permissions.
+    async fn write_secret_files(&self, backend: &dyn VmmBackend) -> Result<()> {
+        let files: Vec<&ResolvedSecret> = self
+            .secrets
+            .iter()
+            .filter(|s| s.secret.delivery() == SecretDelivery::File)
+            .collect();
+        if files.is_empty() {
+            return Ok(());
+        }
+        let run = |script: &'static str| async move {
+            let output = backend
+                .exec(
+                    "sh",
+                    &["-c", script, "sh", GUEST_SECRETS_DIR],
+                    &[],
+                    &[],
+                    None,
+                    None,
+                )
+                .await?;
+            if output.exit_code != 0 {
+                return Err(Error::Guest(format!(
+                    "cannot write secrets to {}: {}",
+                    GUEST_SECRETS_DIR,
+                    output.stderr_str().trim()
+                )));
+            }
+            Ok(())
+        };
+        backend.mkdir_p(GUEST_SECRETS_DIR).await?;
+        run(r#"chmod 700 "$1""#).await?;
+        for secret in files {
+            backend
+                .write_file(
+                    &secret.secret.guest_path(),
+                    secret.value.expose_secret().as_bytes(),
+                )
+                .await?;
+        }
+        run(r#"chmod 600 "$1"/*"#).await
+    }
+
+    /// Redactor for this sandbox's secrets.
+    pub(crate) fn redactor(&self) -> &Arc<Redactor> {
+        &self.redactor
+    }
+
     /// Returns a cloned Arc to the backend, dropping the mutex immediately.
     async fn get_backend(&self) -> Result<Arc<dyn VmmBackend>> {
         self.ensure_started().await?;
@@ -144,8 +889,14 @@ impl LocalSandbox {
 
         let backend = self.get_backend().await?;
 
-        let env: Vec<(String, String)> = self.config.env.clone();
-        backend.exec(program, args, stdin, &env, None, None).await
+        let env = self.exec_env(&[]);
+        let working_dir = self.exec_working_dir();
+        self.crash
+            .guard(
+                &backend,
+                backend.exec(program, args, stdin, &env, working_dir.as_deref(), None),
+            )
+            .await
     }
 
     /// Execute a command with stdin input and an explicit timeout.
@@ -162,9 +913,20 @@ impl LocalSandbox {
 
         let backend = self.get_backend().await?;
 
-        let env: Vec<(String, String)> = self.config.env.clone();
-        backend
-            .exec(program, args, stdin, &env, None, timeout_secs)
+        let env = self.exec_env(&[]);
+        let working_dir = self.exec_working_dir();
+        self.crash
+            .guard(
+                &backend,
+                backend.exec(
+                    program,
+                    args,
+                    stdin,
+                    &env,
+                    working_dir.as_deref(),
+                    timeout_secs,
+                ),
+            )
             .await
     }
 
@@ -268,6 +1030,27 @@ impl LocalSandbox {
         backend.write_file(path, content).await
     }
 
+    /// Stream a file of arbitrary size into the guest filesystem.
+    ///
+    /// `reader` is consumed in [`WRITE_FILE_CHUNK_SIZE`] chunks, each sent as
+    /// a `WriteFileChunk` at its byte offset and retried up to
+    /// [`WRITE_FILE_CHUNK_ATTEMPTS`] times; a final `WriteFileFinalize`
+    /// atomically moves the staged file onto `path`. Returns the number of
+    /// bytes written. In simulation mode (no kernel), the reader is drained
+    /// and nothing is written.
+    pub async fn write_file_streaming<R>(&self, path: &str, reader: R) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        if self.config.kernel.is_none() {
+            let mut reader = reader;
+            return Ok(tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?);
+        }
+
+        let backend = self.get_backend().await?;
+        stream_file(&*backend, path, reader).await
+    }
+
     /// Create directories in the guest filesystem (mkdir -p).
     /// In simulation mode (no kernel), this is a no-op success.
     pub async fn mkdir_p(&self, path: &str) -> Result<()> {
@@ -288,6 +1071,82 @@ impl LocalSandbox {
         backend.file_stat(path).await
     }
 
+    /// Create a symbolic link in the guest filesystem.
+    /// In simulation mode (no kernel), this is a no-op success.
+    pub async fn symlink(&self, request: crate::guest::protocol::SymlinkRequest) -> Result<()> {
+        if self.config.kernel.is_none() {
+            return Ok(());
+        }
+
+        let backend = self.get_backend().await?;
+        backend.symlink(request).await
+    }
+
+    /// Change the mode and/or owner of a guest path.
+    /// In simulation mode (no kernel), this is a no-op success.
+    pub async fn chmod(&self, request: crate::guest::protocol::ChmodRequest) -> Result<()> {
+        if self.config.kernel.is_none() {
+            return Ok(());
+        }
+
+        let backend = self.get_backend().await?;
+        backend.chmod(request).await
+    }
+
+    /// Hashes guest files via native RPC, at most
+    /// [`MAX_HASH_FILES`](crate::guest::protocol::MAX_HASH_FILES) per call.
+    pub(crate) async fn hash_files_native(
+        &self,
+        paths: &[String],
+    ) -> Result<Vec<crate::guest::protocol::FileHash>> {
+        let backend = self.get_backend().await?;
+        let mut hashes = Vec::with_capacity(paths.len());
+        for batch in paths.chunks(crate::guest::protocol::MAX_HASH_FILES) {
+            hashes.extend(backend.hash_files(batch).await?);
+        }
+        Ok(hashes)
+    }
+
+    /// Returns a guest symlink's target via native RPC.
+    pub(crate) async fn read_link_native(&self, path: &str) -> Result<String> {
+        let backend = self.get_backend().await?;
+        backend.read_link(path).await
+    }
+
+    /// Reads the guest's kernel, load, memory and mount state.
+    pub async fn sysinfo(&self) -> Result<SysInfo> {
+        if self.config.kernel.is_none() {
+            return Ok(SysInfo::default());
+        }
+
+        let backend = self.get_backend().await?;
+        backend.sysinfo().await
+    }
+
+    /// Reports guest filesystem changes under `root` (whole rootfs if `None`).
+    pub async fn fs_diff(&self, root: Option<&str>) -> Result<FsDiff> {
+        if self.config.kernel.is_none() {
+            return Ok(FsDiff {
+                root: root.unwrap_or("/").to_string(),
+                ..Default::default()
+            });
+        }
+
+        let backend = self.get_backend().await?;
+        Ok(backend.fs_diff(root).await?.into())
+    }
+
+    /// Exports `/workspace` (optionally narrowed by a glob) from the guest.
+    pub async fn export_workspace(&self, path_filter: Option<&str>) -> Result<ArtifactBundle> {
+        if self.config.kernel.is_none() {
+            return Ok(ArtifactBundle::default());
+        }
+
+        let backend = self.get_backend().await?;
+        let archive = backend.export_workspace(path_filter).await?;
+        ArtifactBundle::from_tar(&archive)
+    }
+
     /// Reads a file from the guest filesystem via native RPC.
     pub(crate) async fn read_file_native(&self, path: &str) -> Result<Vec<u8>> {
         let backend = self.get_backend().await?;
@@ -308,10 +1167,20 @@ impl LocalSandbox {
 
         let backend = self.get_backend().await?;
 
-        let mut env = self.config.env.clone();
-        env.extend(extra_env.iter().cloned());
-        backend
-            .exec(binary, args, &[], &env, None, timeout_secs)
+        let env = self.exec_env(extra_env);
+        let working_dir = self.exec_working_dir();
+        self.crash
+            .guard(
+                &backend,
+                backend.exec(
+                    binary,
+                    args,
+                    &[],
+                    &env,
+                    working_dir.as_deref(),
+                    timeout_secs,
+                ),
+            )
             .await
     }
 
@@ -356,10 +1225,19 @@ impl LocalSandbox {
 
         let backend = self.get_backend().await?;
 
-        let env: Vec<(String, String)> = self.config.env.clone();
-        backend
-            .exec_streaming(program, args, &env, None, timeout_secs)
-            .await
+        let env = self.exec_env(&[]);
+        let working_dir = self.exec_working_dir();
+        let (chunk_rx, response_rx) = backend
+            .exec_streaming(
+                program,
+                args,
+                &env,
+                working_dir.as_deref(),
+                timeout_secs,
+                None,
+            )
+            .await?;
+        Ok((chunk_rx, self.crash.guard_streaming(backend, response_rx)))
     }
 
     /// Streaming variant of `exec_agent_internal`.
@@ -373,6 +1251,7 @@ impl LocalSandbox {
         args: &[&str],
         extra_env: &[(String, String)],
         timeout_secs: Option<u64>,
+        exec_id: Option<&str>,
     ) -> Result<(
         tokio::sync::mpsc::Receiver<crate::guest::protocol::ExecOutputChunk>,
         tokio::sync::oneshot::Receiver<Result<crate::guest::protocol::ExecResponse>>,
@@ -405,11 +1284,90 @@ impl LocalSandbox {
 
         let backend = self.get_backend().await?;
 
-        let mut env = self.config.env.clone();
-        env.extend(extra_env.iter().cloned());
-        backend
-            .exec_streaming(binary, args, &env, Some("/workspace"), timeout_secs)
+        let env = self.exec_env(extra_env);
+        let working_dir = self.exec_working_dir();
+        let (chunk_rx, response_rx) = backend
+            .exec_streaming(
+                binary,
+                args,
+                &env,
+                Some(working_dir.as_deref().unwrap_or("/workspace")),
+                timeout_secs,
+                exec_id,
+            )
+            .await?;
+        Ok((chunk_rx, self.crash.guard_streaming(backend, response_rx)))
+    }
+
+    /// Whether execs are simulated on the host, for want of a kernel.
+    pub(crate) fn simulated(&self) -> bool {
+        self.config.kernel.is_none()
+    }
+
+    /// Writer for the stdin of the running exec `exec_id`, started with a
+    /// streamed stdin.
+    pub(crate) async fn exec_stdin(&self, exec_id: String) -> Result<ExecStdin> {
+        let channel = self
+            .backend
+            .lock()
             .await
+            .as_ref()
+            .and_then(|backend| backend.control_channel())
+            .ok_or(Error::VmNotRunning)?;
+        Ok(ExecStdin::new(channel, exec_id))
+    }
+
+    /// Signal a running exec started with an exec id.
+    pub(crate) async fn signal_exec(&self, exec_id: &str, signal: ExecSignal) -> Result<()> {
+        let channel = self
+            .backend
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|backend| backend.control_channel())
+            .ok_or(Error::VmNotRunning)?;
+        let response = channel.send_signal_exec(exec_id, signal).await?;
+        if response.delivered {
+            Ok(())
+        } else {
+            Err(Error::Guest(response.error.unwrap_or_else(|| {
+                format!("exec {exec_id} was not signalled")
+            })))
+        }
+    }
+
+    /// Create a guest user, booting the VM first if needed. It is
+    /// re-created, with the same uid, after restarts.
+    pub(crate) async fn create_user(&self, name: &str) -> Result<GuestUser> {
+        let backend = self.get_backend().await?;
+        let user = create_user(&*backend, name).await?;
+        let mut users = self.users.lock().unwrap();
+        if !users.iter().any(|u| u == name) {
+            users.push(name.to_string());
+        }
+        Ok(user)
+    }
+
+    /// Replace the exec policy, in the running guest and for later boots.
+    pub(crate) async fn set_exec_policy(&self, policy: ExecPolicy) -> Result<()> {
+        *self.exec_policy.lock().unwrap() = Some(policy.clone());
+        let backend = self.backend.lock().await.clone();
+        match backend {
+            Some(backend) if self.started.load(Ordering::SeqCst) => {
+                send_exec_policy(&*backend, &policy).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// [`signal_exec`](Self::signal_exec) on behalf of a tool hook or
+    /// budget. A signal that cannot be delivered is logged: the agent may
+    /// simply have finished, and the caller's decision still stands.
+    pub(crate) async fn signal_exec_logged(&self, exec_id: &str, signal: ExecSignal, why: &str) {
+        match self.signal_exec(exec_id, signal).await {
+            Ok(()) => tracing::info!("Sent {:?} to agent ({})", signal, why),
+            Err(e) => tracing::warn!("Failed to send {:?} to agent ({}): {}", signal, why, e),
+        }
     }
 
     /// Start guest telemetry collection.
@@ -432,11 +1390,44 @@ impl LocalSandbox {
         let opts = TelemetrySubscribeRequest {
             interval_ms: 1000,
             include_kernel_threads: false,
+            top_processes: None,
         };
-        backend.start_telemetry(observer, opts, ring_buffer).await
+        let aggregator = backend.start_telemetry(observer, opts, ring_buffer).await?;
+        let events = self.events.clone();
+        aggregator.set_batch_hook(move |batch| events.emit(SandboxEvent::telemetry_tick(batch)));
+        *self.telemetry.lock().unwrap() = Arc::downgrade(&aggregator);
+        Ok(aggregator)
     }
 
     /// Opens a PTY session on the guest via the backend.
+    /// Boot the VM if it is not running and wait for its guest-agent to
+    /// complete the handshake. Without a kernel execs are simulated and
+    /// there is nothing to boot.
+    pub async fn start(&self) -> Result<()> {
+        if self.config.kernel.is_none() {
+            return Ok(());
+        }
+        let backend = self.get_backend().await?;
+        let Some(channel) = backend.control_channel() else {
+            return Ok(());
+        };
+        self.crash.guard(&backend, channel.ensure_connected()).await
+    }
+
+    pub async fn attach_console(&self) -> Result<ConsoleStream> {
+        if self.config.kernel.is_none() {
+            return Err(Error::Config("serial console requires a kernel".into()));
+        }
+        // Subscribe before booting so the stream sees the whole boot.
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.console_taps.lock().unwrap().push(tx);
+        let backend = self.get_backend().await?;
+        let input = backend
+            .console_input()
+            .ok_or_else(|| backend.unsupported(BackendCaps::CONSOLE_INPUT))?;
+        Ok(ConsoleStream::new(rx, input))
+    }
+
     pub async fn attach_pty(
         &self,
         request: void_box_protocol::PtyOpenRequest,
@@ -463,9 +1454,82 @@ impl LocalSandbox {
             .await
     }
 
-    pub async fn stop(&self) -> Result<()> {
-        use std::sync::atomic::Ordering;
+    /// Restart the guest kernel in the running VM, then provision the new
+    /// boot as [`start`](Self::start) does. The VM, its devices and vsock
+    /// CID are kept; guest processes and anything outside mounts and disks
+    /// are not.
+    pub async fn reboot(&self) -> Result<ShutdownAck> {
+        let started = Instant::now();
+        let mut backend_lock = self.backend.lock().await;
+        let Some(ref mut arc) = *backend_lock else {
+            return Err(Error::VmNotRunning);
+        };
+        let Some(backend) = Arc::get_mut(arc) else {
+            return Err(Error::Config(
+                "cannot reboot: backend has concurrent users".into(),
+            ));
+        };
+        self.agent_ready.store(false, Ordering::SeqCst);
+        self.crash.reset();
+        let ack = backend.reboot(DEFAULT_SHUTDOWN_TIMEOUT).await?;
+        self.provision_guest(&**arc).await?;
+        self.agent_ready.store(true, Ordering::SeqCst);
+        self.events.emit(SandboxEvent::Rebooted {
+            graceful: ack.clone(),
+            reboot_ms: started.elapsed().as_millis() as u64,
+        });
+        Ok(ack)
+    }
+
+    /// What the running VM's backend can do, or else the one this host
+    /// would boot; empty when the host cannot run VMs.
+    pub async fn capabilities(&self) -> BackendCaps {
+        if let Some(backend) = self.backend.lock().await.as_ref() {
+            return backend.capabilities();
+        }
+        Backend::detect()
+            .map(Backend::capabilities)
+            .unwrap_or_default()
+    }
+
+    /// The running VM's backend, without booting one.
+    async fn running_backend(&self) -> Result<Arc<dyn VmmBackend>> {
+        self.backend
+            .lock()
+            .await
+            .as_ref()
+            .cloned()
+            .ok_or(Error::VmNotRunning)
+    }
+
+    pub async fn set_memory_target(&self, mb: usize) -> Result<()> {
+        self.running_backend().await?.set_memory_target(mb)
+    }
+
+    pub async fn memory_actual_mb(&self) -> Option<usize> {
+        self.backend.lock().await.as_ref()?.memory_actual_mb()
+    }
+
+    /// Pause the VM. Heartbeats and clock syncs wait for the resume, so
+    /// the health monitor does not take the paused guest for a hung one.
+    pub async fn suspend(&self) -> Result<()> {
+        let backend = self.running_backend().await?;
+        let ready = self.agent_ready.swap(false, Ordering::SeqCst);
+        if let Err(e) = backend.suspend().await {
+            self.agent_ready.store(ready, Ordering::SeqCst);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub async fn resume(&self) -> Result<()> {
+        self.running_backend().await?.resume().await?;
+        self.agent_ready.store(true, Ordering::SeqCst);
+        Ok(())
+    }
 
+    pub async fn stop(&self) -> Result<Option<ShutdownAck>> {
+        let mut ack = None;
         let mut backend_lock = self.backend.lock().await;
         if let Some(ref mut arc) = *backend_lock {
             let Some(backend) = Arc::get_mut(arc) else {
@@ -473,15 +1537,320 @@ impl LocalSandbox {
                     "cannot stop: backend has concurrent users".into(),
                 ));
             };
-            backend.stop().await?;
+            ack = backend.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await?;
         }
         *backend_lock = None;
         self.started.store(false, Ordering::SeqCst);
+        self.agent_ready.store(false, Ordering::SeqCst);
+        self.set_health(HealthStatus::Stopped);
+        if let Some(console) = &self.console {
+            console.flush();
+        }
+        self.network_log.flush();
 
-        Ok(())
+        Ok(ack)
+    }
+}
+
+/// Watches a sandbox's console for a guest crash and turns one into
+/// [`Error::GuestCrashed`] for the execs running under it.
+struct CrashWatch {
+    tail: ConsoleTail,
+    /// The report for the current boot, built by the first exec to notice
+    /// the crash and shared with the rest.
+    report: std::sync::Mutex<Option<CrashReport>>,
+    /// Why the current boot never completed its handshake, once known.
+    boot_failure: std::sync::Mutex<Option<BootDiagnostics>>,
+    /// Why the guest was declared unresponsive, once it has been.
+    hung: tokio::sync::watch::Sender<Option<String>>,
+    /// Set when the unresponsive guest is one that stopped petting its
+    /// watchdog.
+    watchdog_expired: AtomicBool,
+    sink: Option<Arc<dyn CrashSink>>,
+    events: SandboxEvents,
+    redactor: Arc<Redactor>,
+}
+
+impl CrashWatch {
+    /// Forget the previous boot's console and report.
+    fn reset(&self) {
+        self.tail.reset();
+        *self.report.lock().unwrap() = None;
+        *self.boot_failure.lock().unwrap() = None;
+        self.hung.send_replace(None);
+        self.watchdog_expired.store(false, Ordering::SeqCst);
+    }
+
+    /// Fail the execs running on this boot: the guest stopped answering.
+    fn mark_hung(&self, reason: String) {
+        self.hung.send_replace(Some(reason));
+    }
+
+    /// [`mark_hung`](Self::mark_hung) for a guest that stopped petting its
+    /// watchdog, and report the crash even if no exec is running.
+    async fn watchdog_expired(&self, reason: String) {
+        self.watchdog_expired.store(true, Ordering::SeqCst);
+        self.mark_hung(reason.clone());
+        self.crashed(Some(reason)).await;
+    }
+
+    /// Resolves with the reason once [`mark_hung`](Self::mark_hung) is called.
+    async fn hung(&self) -> String {
+        let mut rx = self.hung.subscribe();
+        let reason = rx
+            .wait_for(Option::is_some)
+            .await
+            .map(|reason| reason.clone().unwrap_or_default());
+        match reason {
+            Ok(reason) => reason,
+            Err(_) => std::future::pending().await,
+        }
+    }
+
+    /// Run an exec's backend call. A kernel panic on the console fails it
+    /// as soon as it is printed rather than at the exec's timeout, and an
+    /// error once the VM has exited is reported as a crash.
+    async fn guard<T>(
+        &self,
+        backend: &Arc<dyn VmmBackend>,
+        exec: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let result = tokio::select! {
+            result = exec => result,
+            _ = self.tail.panicked() => return Err(self.crashed(None).await),
+            reason = self.hung() => return Err(self.crashed(Some(reason)).await),
+        };
+        match result {
+            Err(e) if !backend.is_running() || self.tail.panic_line().is_some() => {
+                Err(self.crashed(Some(e.to_string())).await)
+            }
+            Err(e) => Err(self.boot_failed(e)),
+            other => other,
+        }
+    }
+
+    /// [`guard`](Self::guard) for the final response of a streaming exec.
+    fn guard_streaming(
+        self: &Arc<Self>,
+        backend: Arc<dyn VmmBackend>,
+        response_rx: tokio::sync::oneshot::Receiver<Result<ExecResponse>>,
+    ) -> tokio::sync::oneshot::Receiver<Result<ExecResponse>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let watch = self.clone();
+        tokio::spawn(async move {
+            let response = watch
+                .guard(&backend, async {
+                    response_rx
+                        .await
+                        .map_err(|_| Error::Guest("exec response channel closed".into()))?
+                })
+                .await;
+            let _ = tx.send(response);
+        });
+        rx
+    }
+
+    /// Turn `err` into [`Error::BootFailed`] if it is the handshake giving
+    /// up, classifying the console once per boot.
+    fn boot_failed(&self, err: Error) -> Error {
+        if !control_channel::is_handshake_deadline(&err) {
+            return err;
+        }
+        let mut slot = self.boot_failure.lock().unwrap();
+        let diagnostics = slot.get_or_insert_with(|| {
+            let mut diagnostics =
+                BootDiagnostics::new(self.events.sandbox_id(), &self.tail, err.to_string());
+            diagnostics.redact(&self.redactor);
+            tracing::error!("Guest boot failed: {}", diagnostics);
+            diagnostics
+        });
+        Error::BootFailed(Box::new(diagnostics.clone()))
+    }
+
+    /// Build (once per boot) and deliver the crash report.
+    async fn crashed(&self, cause: Option<String>) -> Error {
+        // The panic line comes first; give the kernel a moment to print the
+        // call trace after it.
+        if self.report.lock().unwrap().is_none() && self.tail.panic_line().is_some() {
+            let _ = tokio::time::timeout(PANIC_REPORT_WAIT, self.tail.panic_finished()).await;
+        }
+        let report = {
+            let mut slot = self.report.lock().unwrap();
+            if let Some(report) = slot.as_ref() {
+                return crash::crashed(report.clone());
+            }
+            let mut report = CrashReport::new(
+                self.events.sandbox_id(),
+                &self.tail,
+                self.events.pending_execs(),
+                cause,
+            );
+            report.redact(&self.redactor);
+            if report.panic_line.is_none() && self.hung.borrow().is_some() {
+                report.kind = if self.watchdog_expired.load(Ordering::SeqCst) {
+                    CrashKind::WatchdogExpired
+                } else {
+                    CrashKind::Unresponsive
+                };
+            }
+            *slot = Some(report.clone());
+            report
+        };
+        tracing::error!("Guest crashed: {}", report);
+        if let Some(sink) = &self.sink {
+            crash::store_report(sink.as_ref(), &report).await;
+        }
+        crash::crashed(report)
+    }
+}
+
+/// Stop a VM being replaced, once the execs still holding it let go.
+async fn retire(mut backend: Arc<dyn VmmBackend>) {
+    let deadline = Instant::now() + RESTART_DRAIN_TIMEOUT;
+    loop {
+        if let Some(backend) = Arc::get_mut(&mut backend) {
+            if let Err(e) = backend.shutdown(Duration::ZERO).await {
+                tracing::warn!("Failed to stop replaced VM: {}", e);
+            }
+            return;
+        }
+        if Instant::now() >= deadline {
+            tracing::warn!("Replaced VM still in use; it stops with its last user");
+            return;
+        }
+        tokio::time::sleep(RESTART_DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+/// Install `policy` in the guest-agent.
+async fn send_exec_policy(backend: &dyn VmmBackend, policy: &ExecPolicy) -> Result<()> {
+    let channel = backend.control_channel().ok_or(Error::VmNotRunning)?;
+    let response = channel.send_set_exec_policy(policy).await?;
+    match response.error {
+        Some(e) => Err(Error::Guest(format!("cannot set exec policy: {e}"))),
+        None => Ok(()),
     }
 }
 
+/// Set the guest clock through `channel`.
+async fn sync_clock(channel: &ControlChannel) -> Result<ClockSync> {
+    let request = super::clock::host_clock();
+    let response = channel.send_sync_clock(&request).await?;
+    if let Some(e) = response.error {
+        return Err(Error::Guest(format!("cannot set guest clock: {e}")));
+    }
+    Ok(ClockSync::from_response(&request, &response))
+}
+
+/// Set the guest clock to the deterministic baseline.
+async fn set_clock_baseline(backend: &dyn VmmBackend) -> Result<()> {
+    let channel = backend.control_channel().ok_or(Error::VmNotRunning)?;
+    let response = channel
+        .send_sync_clock(&super::deterministic::clock_baseline())
+        .await?;
+    match response.error {
+        Some(e) => Err(Error::Guest(format!("cannot set guest clock: {e}"))),
+        None => Ok(()),
+    }
+}
+
+/// Create the guest user `name`.
+async fn create_user(backend: &dyn VmmBackend, name: &str) -> Result<GuestUser> {
+    let channel = backend.control_channel().ok_or(Error::VmNotRunning)?;
+    let response = channel.send_create_user(name).await?;
+    if let Some(e) = response.error {
+        return Err(Error::Guest(format!("cannot create user {name}: {e}")));
+    }
+    Ok(GuestUser {
+        name: name.to_string(),
+        uid: response.uid,
+        gid: response.gid,
+        home: response.home,
+    })
+}
+
+/// Put the guest-agent in read-only mode.
+async fn enter_read_only(backend: &dyn VmmBackend) -> Result<()> {
+    let channel = backend.control_channel().ok_or(Error::VmNotRunning)?;
+    if let Some(e) = channel.send_enter_read_only().await?.error {
+        return Err(Error::Guest(format!("cannot enter read-only mode: {e}")));
+    }
+    // Without an OCI rootfs, rootfs changes are diffed against a baseline
+    // taken by the first fs_diff; take it before any exec runs.
+    if let Err(e) = backend.fs_diff(None).await {
+        tracing::warn!("read-only: failed to record rootfs baseline: {}", e);
+    }
+    Ok(())
+}
+
+/// Write `reader` to `path` in the guest in chunks; see
+/// [`LocalSandbox::write_file_streaming`].
+pub(super) async fn stream_file<R>(
+    backend: &dyn VmmBackend,
+    path: &str,
+    mut reader: R,
+) -> Result<u64>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut buffer = vec![0u8; WRITE_FILE_CHUNK_SIZE];
+    let mut offset = 0u64;
+
+    loop {
+        let chunk_len = read_chunk(&mut reader, &mut buffer).await?;
+        // The chunk at offset 0 creates the staging file, so it is sent
+        // even when the source is empty.
+        if chunk_len == 0 && offset > 0 {
+            break;
+        }
+
+        let chunk = &buffer[..chunk_len];
+        let mut attempt = 1;
+        loop {
+            match backend.write_file_chunk(path, offset, chunk).await {
+                Ok(_) => break,
+                Err(e) if attempt < WRITE_FILE_CHUNK_ATTEMPTS => {
+                    tracing::warn!(
+                        path,
+                        offset,
+                        attempt,
+                        "write_file_streaming: retrying chunk: {}",
+                        e
+                    );
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        offset += chunk_len as u64;
+        if chunk_len < buffer.len() {
+            break;
+        }
+    }
+
+    backend.finalize_write_file(path, offset).await?;
+    Ok(offset)
+}
+
+/// Fill `buffer` from `reader`, stopping early only at EOF. Returns the
+/// number of bytes read; anything shorter than `buffer.len()` means the
+/// reader is exhausted.
+async fn read_chunk<R>(reader: &mut R, buffer: &mut [u8]) -> Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = reader.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
 impl Drop for LocalSandbox {
     fn drop(&mut self) {
         // Backend will be stopped when dropped through its Drop impl
@@ -493,6 +1862,138 @@ mod tests {
     use super::*;
     use crate::sandbox::SandboxConfig;
 
+    #[test]
+    fn test_initramfs_overlay_is_appended() {
+        let base = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(base.path(), b"base").unwrap();
+        let overlay = tempfile::tempdir().unwrap();
+        std::fs::write(overlay.path().join("motd"), b"hi").unwrap();
+        let config = SandboxConfig {
+            initramfs: Some(base.path().to_path_buf()),
+            initramfs_overlays: vec![overlay.path().to_path_buf()],
+            ..SandboxConfig::default()
+        };
+        let sandbox = LocalSandbox::new(config).unwrap();
+        let layered = sandbox.resolve_initramfs().unwrap().unwrap();
+        assert_ne!(layered, base.path());
+        let image = std::fs::read(&layered).unwrap();
+        assert_eq!(&image[..4], b"base");
+        // The overlay is a gzip member of its own.
+        assert_eq!(&image[4..6], &[0x1f, 0x8b]);
+        assert_eq!(sandbox.resolve_initramfs().unwrap().unwrap(), layered);
+    }
+
+    /// Guest end that swallows writes and has nothing to read.
+    struct SinkStream;
+
+    impl std::io::Read for SinkStream {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl std::io::Write for SinkStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl control_channel::GuestStream for SinkStream {
+        fn set_read_timeout(&self, _timeout: Option<Duration>) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+            -1
+        }
+
+        fn try_clone_box(&self) -> std::io::Result<Box<dyn control_channel::GuestStream>> {
+            Ok(Box::new(SinkStream))
+        }
+    }
+
+    #[test]
+    fn test_protocol_trace_never_holds_secret_values() {
+        use crate::secret::SecretSource;
+        use void_box_protocol::{Message, MessageType};
+
+        let sandbox = crate::sandbox::Sandbox::local()
+            .secret("API_KEY", SecretSource::value("s3cret-value"))
+            .protocol_tap(ProtocolTap::new())
+            .build()
+            .unwrap();
+        let super::super::SandboxInner::Local(local) = &sandbox.inner else {
+            unreachable!("local sandbox");
+        };
+        let tap = local.resolve_protocol_tap().unwrap();
+        let connector: control_channel::GuestConnector =
+            Arc::new(|| Ok(Box::new(SinkStream) as Box<dyn control_channel::GuestStream>));
+        let mut stream = tap.wrap(connector)().unwrap();
+        let mut payload = 7u32.to_le_bytes().to_vec();
+        payload.extend_from_slice(br#"{"program":"env","env":[["API_KEY","s3cret-value"]]}"#);
+        let frame = Message {
+            msg_type: MessageType::ExecRequest,
+            payload,
+        };
+        std::io::Write::write_all(&mut stream, &frame.serialize()).unwrap();
+
+        let trace = sandbox.protocol_trace();
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0].request_id, Some(7));
+        assert!(trace
+            .iter()
+            .all(|f| !f.to_string().contains("s3cret") && f.payload.is_empty()));
+    }
+
+    #[test]
+    fn test_scratch_disk_created_once_and_removed_on_drop() {
+        let image = tempfile::NamedTempFile::new().unwrap();
+        let config = SandboxConfig {
+            disks: vec![
+                DiskSpec::Scratch { size_gb: 1 },
+                DiskSpec::Attach {
+                    path: image.path().to_path_buf(),
+                    read_only: true,
+                },
+            ],
+            ..SandboxConfig::default()
+        };
+        let sandbox = LocalSandbox::new(config).unwrap();
+        let disks = match sandbox.resolve_disks() {
+            Ok(disks) => disks,
+            Err(e) => {
+                eprintln!("skipping: cannot create scratch disk: {e}");
+                return;
+            }
+        };
+        assert_eq!(disks[0].guest_path.as_deref(), Some("/scratch"));
+        assert!(!disks[0].read_only);
+        assert_eq!(std::fs::metadata(&disks[0].path).unwrap().len(), 1 << 30);
+        assert_eq!(disks[1].path, image.path());
+        assert!(disks[1].read_only && disks[1].guest_path.is_none());
+
+        // A restart reuses the image rather than reformatting it.
+        let again = sandbox.resolve_disks().unwrap();
+        assert_eq!(again[0].path, disks[0].path);
+
+        let scratch_dir = disks[0].path.parent().unwrap().to_path_buf();
+        drop(sandbox);
+        assert!(!scratch_dir.exists());
+    }
+
+    #[test]
+    fn test_zero_size_scratch_disk_rejected() {
+        let config = SandboxConfig {
+            disks: vec![DiskSpec::Scratch { size_gb: 0 }],
+            ..SandboxConfig::default()
+        };
+        assert!(LocalSandbox::new(config).is_err());
+    }
+
     #[tokio::test]
     async fn test_simulate_echo() {
         let config = SandboxConfig::default();
@@ -503,6 +2004,84 @@ mod tests {
         assert_eq!(output.stdout_str().trim(), "hello world");
     }
 
+    #[tokio::test]
+    async fn test_exec_env_carries_active_traceparent() {
+        let config = SandboxConfig {
+            env: vec![("HOME".into(), "/root".into())],
+            ..SandboxConfig::default()
+        };
+        let sandbox = LocalSandbox::new(config).unwrap();
+        assert!(!sandbox
+            .exec_env(&[])
+            .iter()
+            .any(|(k, _)| k == "TRACEPARENT"));
+
+        let ctx = crate::observe::Span::new("step:build").context;
+        let traceparent = ctx.to_traceparent();
+        let (env, explicit) = ctx
+            .scope(async {
+                (
+                    sandbox.exec_env(&[("LANG".into(), "C".into())]),
+                    sandbox.exec_env(&[("TRACEPARENT".into(), "caller".into())]),
+                )
+            })
+            .await;
+        assert_eq!(
+            env,
+            vec![
+                ("HOME".to_string(), "/root".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+                ("TRACEPARENT".to_string(), traceparent),
+            ]
+        );
+        assert_eq!(explicit.last().unwrap().1, "caller");
+    }
+
+    #[tokio::test]
+    async fn test_exec_defaults_yield_to_per_call_overrides() {
+        let config = SandboxConfig {
+            env: vec![("LANG".into(), "C".into())],
+            working_dir: Some("/workspace/project".into()),
+            path_prepend: vec!["/opt/tools/bin".into(), "/opt/node/bin".into()],
+            ..SandboxConfig::default()
+        };
+        let sandbox = LocalSandbox::new(config).unwrap();
+        let path = |env: &[(String, String)]| {
+            env.iter()
+                .rev()
+                .find(|(k, _)| k == "PATH")
+                .map(|(_, v)| v.clone())
+        };
+
+        assert_eq!(
+            path(&sandbox.exec_env(&[])).unwrap(),
+            format!(
+                "/opt/tools/bin:/opt/node/bin:{}",
+                void_box_protocol::GUEST_PATH
+            )
+        );
+        assert_eq!(
+            sandbox.exec_working_dir().as_deref(),
+            Some("/workspace/project")
+        );
+
+        let env = vec![
+            ("LANG".to_string(), "C.UTF-8".to_string()),
+            ("PATH".to_string(), "/bin".to_string()),
+        ];
+        let (env, dir) = crate::guest::protocol::scope_exec_env(
+            env,
+            crate::guest::protocol::scope_exec_working_dir("/tmp".into(), async {
+                (sandbox.exec_env(&[]), sandbox.exec_working_dir())
+            }),
+        )
+        .await;
+        let lang = env.iter().rev().find(|(k, _)| k == "LANG").unwrap();
+        assert_eq!(lang.1, "C.UTF-8");
+        assert_eq!(path(&env).unwrap(), "/opt/tools/bin:/opt/node/bin:/bin");
+        assert_eq!(dir.as_deref(), Some("/tmp"));
+    }
+
     #[tokio::test]
     async fn test_simulate_cat_stdin() {
         let config = SandboxConfig::default();
@@ -529,6 +2108,34 @@ mod tests {
         assert_eq!(output.stdout, b"HELLO");
     }
 
+    #[tokio::test]
+    async fn test_read_chunk_fills_across_short_reads() {
+        let (mut writer, mut reader) = tokio::io::duplex(4);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            writer.write_all(b"0123456789").await.unwrap();
+        });
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(read_chunk(&mut reader, &mut buffer).await.unwrap(), 8);
+        assert_eq!(&buffer, b"01234567");
+        assert_eq!(read_chunk(&mut reader, &mut buffer).await.unwrap(), 2);
+        assert_eq!(&buffer[..2], b"89");
+    }
+
+    #[tokio::test]
+    async fn test_simulate_write_file_streaming_counts_bytes() {
+        let config = SandboxConfig::default();
+        let sandbox = LocalSandbox::new(config).unwrap();
+
+        let content = vec![7u8; 1024];
+        let written = sandbox
+            .write_file_streaming("/workspace/blob.bin", content.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(written, 1024);
+    }
+
     #[tokio::test]
     async fn test_simulate_curl() {
         let config = SandboxConfig::default();
@@ -0,0 +1,389 @@
+//! Scripted scenarios for mock sandboxes.
+//!
+//! [`MockSandbox::queue_response`](super::MockSandbox::queue_response)
+//! answers execs in whatever order they arrive, so a test of a multi-step
+//! agent workflow breaks as soon as the workflow adds or reorders a
+//! command. A [`MockScript`] answers by what the call is instead: each
+//! [`on_exec`](MockScript::on_exec) or
+//! [`on_write_file`](MockScript::on_write_file) rule pairs a
+//! [`MockMatcher`] with a responder, and calls no rule matches fall back to
+//! the mock's built-in command simulations. Every call is recorded, and
+//! [`expect_call_order`](MockScript::expect_call_order) is checked when the
+//! last handle to the script is dropped (or at [`MockScript::verify`]),
+//! failing the test with the calls that were actually made.
+//!
+//! ```
+//! use void_box::sandbox::{MockSandbox, Sandbox};
+//! use void_box::ExecOutput;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> void_box::Result<()> {
+//! let script = MockSandbox::script()
+//!     .on_exec("git rev-parse HEAD", |_| {
+//!         Ok(ExecOutput::new(b"abc123\n".to_vec(), Vec::new(), 0))
+//!     })
+//!     .on_exec("make test", |_| {
+//!         Ok(ExecOutput::new(Vec::new(), b"1 failed\n".to_vec(), 1))
+//!     })
+//!     .expect_call_order(["git rev-parse", "/workspace/report.md", "make test"]);
+//! let sandbox = Sandbox::mock().mock_script(script).build()?;
+//!
+//! sandbox.exec("git", &["rev-parse", "HEAD"]).await?;
+//! sandbox.write_file("/workspace/report.md", b"# Report").await?;
+//! assert_eq!(sandbox.exec("make", &["test"]).await?.exit_code, 1);
+//! assert_eq!(sandbox.read_file("/workspace/report.md").await?, b"# Report");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::normalize_mock_path;
+use crate::{ExecOutput, Result};
+
+type ExecResponder = Arc<dyn Fn(&MockExec) -> Result<ExecOutput> + Send + Sync>;
+type WriteResponder = Arc<dyn Fn(&MockWrite) -> Result<()> + Send + Sync>;
+
+/// A command run in a mock sandbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockExec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub stdin: Vec<u8>,
+}
+
+/// A file written to a mock sandbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockWrite {
+    /// Absolute guest path; relative paths are taken from `/workspace`.
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+/// A call a [`MockScript`] recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    Exec(MockExec),
+    WriteFile(MockWrite),
+}
+
+impl fmt::Display for MockCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockCall::Exec(exec) => {
+                write!(f, "exec {}", exec.program)?;
+                for arg in &exec.args {
+                    write!(f, " {arg}")?;
+                }
+                Ok(())
+            }
+            MockCall::WriteFile(write) => write!(
+                f,
+                "write_file {} ({} bytes)",
+                write.path,
+                write.content.len()
+            ),
+        }
+    }
+}
+
+/// Which calls a [`MockScript`] rule or expectation applies to.
+///
+/// A `&str` matches an exec whose program and leading arguments are its
+/// words (`"git"` matches every git command, `"git status"` also matches
+/// `git status --short`), and a file write to exactly that path or, when it
+/// ends in `/`, anywhere under it.
+#[derive(Clone)]
+pub struct MockMatcher {
+    description: String,
+    matches: Arc<dyn Fn(&MockCall) -> bool + Send + Sync>,
+}
+
+impl MockMatcher {
+    /// Match calls for which `predicate` returns true. `description` names
+    /// the matcher in verification failures.
+    pub fn when<F>(description: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(&MockCall) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            description: description.into(),
+            matches: Arc::new(predicate),
+        }
+    }
+
+    /// Match every call.
+    pub fn any() -> Self {
+        Self::when("any call", |_| true)
+    }
+
+    pub fn matches(&self, call: &MockCall) -> bool {
+        (self.matches)(call)
+    }
+}
+
+impl From<&str> for MockMatcher {
+    fn from(pattern: &str) -> Self {
+        let words: Vec<String> = pattern.split_whitespace().map(String::from).collect();
+        let path = normalize_mock_path(pattern.trim());
+        Self::when(pattern, move |call| match call {
+            MockCall::Exec(exec) => {
+                !words.is_empty()
+                    && words.len() <= exec.args.len() + 1
+                    && std::iter::once(&exec.program)
+                        .chain(&exec.args)
+                        .zip(&words)
+                        .all(|(actual, expected)| actual == expected)
+            }
+            MockCall::WriteFile(write) => {
+                write.path == path || (path.ends_with('/') && write.path.starts_with(&path))
+            }
+        })
+    }
+}
+
+impl From<String> for MockMatcher {
+    fn from(pattern: String) -> Self {
+        Self::from(pattern.as_str())
+    }
+}
+
+impl fmt::Debug for MockMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MockMatcher")
+            .field(&self.description)
+            .finish()
+    }
+}
+
+/// A programmable scenario for a mock sandbox; see the
+/// [module docs](self).
+///
+/// Clones share the same rules and recorded calls. Rules are tried in the
+/// order they were added and the first match answers.
+#[derive(Clone, Default)]
+pub struct MockScript {
+    state: Arc<ScriptState>,
+}
+
+#[derive(Default)]
+struct ScriptState {
+    exec_rules: Mutex<Vec<(MockMatcher, ExecResponder)>>,
+    write_rules: Mutex<Vec<(MockMatcher, WriteResponder)>>,
+    expected_order: Mutex<Vec<MockMatcher>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer execs matching `matcher` with `responder`.
+    pub fn on_exec<F>(self, matcher: impl Into<MockMatcher>, responder: F) -> Self
+    where
+        F: Fn(&MockExec) -> Result<ExecOutput> + Send + Sync + 'static,
+    {
+        self.state
+            .exec_rules
+            .lock()
+            .unwrap()
+            .push((matcher.into(), Arc::new(responder)));
+        self
+    }
+
+    /// Handle file writes matching `matcher` with `responder`. A write the
+    /// responder accepts is stored, so later reads of the path return it;
+    /// an error fails the write.
+    pub fn on_write_file<F>(self, matcher: impl Into<MockMatcher>, responder: F) -> Self
+    where
+        F: Fn(&MockWrite) -> Result<()> + Send + Sync + 'static,
+    {
+        self.state
+            .write_rules
+            .lock()
+            .unwrap()
+            .push((matcher.into(), Arc::new(responder)));
+        self
+    }
+
+    /// Require calls matching `matchers` to happen in this order. Other
+    /// calls may come before, between and after them.
+    pub fn expect_call_order<M>(self, matchers: impl IntoIterator<Item = M>) -> Self
+    where
+        M: Into<MockMatcher>,
+    {
+        self.state
+            .expected_order
+            .lock()
+            .unwrap()
+            .extend(matchers.into_iter().map(Into::into));
+        self
+    }
+
+    /// Calls recorded so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// Check the expected call order now.
+    ///
+    /// # Panics
+    ///
+    /// If an expected call is missing or out of order.
+    pub fn verify(&self) {
+        if let Err(message) = self.state.check_order() {
+            panic!("{message}");
+        }
+    }
+
+    /// Record an exec and answer it if a rule matches.
+    pub(crate) fn exec(&self, exec: MockExec) -> Option<Result<ExecOutput>> {
+        let call = MockCall::Exec(exec.clone());
+        let responder = first_match(&self.state.exec_rules, &call);
+        self.state.calls.lock().unwrap().push(call);
+        responder.map(|responder| responder(&exec))
+    }
+
+    /// Record a file write and run the matching rule's responder, if any.
+    pub(crate) fn write_file(&self, write: MockWrite) -> Result<()> {
+        let call = MockCall::WriteFile(write.clone());
+        let responder = first_match(&self.state.write_rules, &call);
+        self.state.calls.lock().unwrap().push(call);
+        responder.map_or(Ok(()), |responder| responder(&write))
+    }
+}
+
+fn first_match<R: Clone>(rules: &Mutex<Vec<(MockMatcher, R)>>, call: &MockCall) -> Option<R> {
+    rules
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(matcher, _)| matcher.matches(call))
+        .map(|(_, responder)| responder.clone())
+}
+
+impl fmt::Debug for MockScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockScript")
+            .field("calls", &self.state.calls.lock().unwrap().len())
+            .field(
+                "expected_order",
+                &*self.state.expected_order.lock().unwrap(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptState {
+    fn check_order(&self) -> std::result::Result<(), String> {
+        let expected = self.expected_order.lock().unwrap();
+        let calls = self.calls.lock().unwrap();
+        let mut remaining = calls.iter();
+        for (position, matcher) in expected.iter().enumerate() {
+            if !remaining.any(|call| matcher.matches(call)) {
+                let made = calls
+                    .iter()
+                    .map(|call| format!("  {call}"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                return Err(format!(
+                    "mock script expected call {} ('{}') after the calls before it, \
+                     but it never came; calls made:\n{}",
+                    position + 1,
+                    matcher.description,
+                    if made.is_empty() { "  (none)" } else { &made }
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ScriptState {
+    fn drop(&mut self) {
+        // A test already failing should report its own panic.
+        if std::thread::panicking() {
+            return;
+        }
+        if let Err(message) = self.check_order() {
+            panic!("{message}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::Sandbox;
+
+    #[tokio::test]
+    async fn test_script_answers_by_call_and_checks_order() {
+        let script = MockScript::new()
+            .on_exec("git status", |exec| {
+                Ok(ExecOutput::new(
+                    format!("{} args\n", exec.args.len()).into_bytes(),
+                    Vec::new(),
+                    0,
+                ))
+            })
+            .on_write_file("/etc/", |write| {
+                Err(crate::Error::Guest(format!("{} is read-only", write.path)))
+            })
+            .expect_call_order(["git status", "out.json"]);
+        let sandbox = Sandbox::mock().mock_script(script.clone()).build().unwrap();
+
+        let output = sandbox.exec("git", &["status", "--short"]).await.unwrap();
+        assert_eq!(output.stdout, b"2 args\n");
+        // Unmatched execs fall back to the built-in simulations.
+        let output = sandbox.exec("echo", &["hi"]).await.unwrap();
+        assert_eq!(output.stdout, b"hi\n");
+        assert!(sandbox.write_file("/etc/hosts", b"x").await.is_err());
+        sandbox.write_file("out.json", b"{}").await.unwrap();
+        assert_eq!(
+            sandbox.read_file("/workspace/out.json").await.unwrap(),
+            b"{}"
+        );
+
+        script.verify();
+        assert_eq!(script.calls()[0].to_string(), "exec git status --short");
+        assert!(matches!(
+            &script.calls()[3],
+            MockCall::WriteFile(write) if write.path == "/workspace/out.json"
+        ));
+    }
+
+    #[test]
+    fn test_script_reports_missing_and_out_of_order_calls() {
+        let script = MockScript::new().expect_call_order(["git clone", "make"]);
+        script.state.calls.lock().unwrap().extend([
+            MockCall::Exec(MockExec {
+                program: "make".into(),
+                args: Vec::new(),
+                stdin: Vec::new(),
+            }),
+            MockCall::Exec(MockExec {
+                program: "git".into(),
+                args: vec!["clone".into(), "repo".into()],
+                stdin: Vec::new(),
+            }),
+        ]);
+        let message = script.state.check_order().unwrap_err();
+        assert!(message.contains("call 2 ('make')"), "{message}");
+        assert!(message.contains("exec git clone repo"), "{message}");
+
+        // Satisfy the order so dropping the script does not panic.
+        script
+            .state
+            .calls
+            .lock()
+            .unwrap()
+            .push(MockCall::Exec(MockExec {
+                program: "make".into(),
+                args: Vec::new(),
+                stdin: Vec::new(),
+            }));
+        script.verify();
+    }
+}
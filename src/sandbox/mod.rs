@@ -26,24 +26,95 @@
 //! }
 //! ```
 
+pub mod artifact;
+pub mod clock;
+pub mod console;
+pub mod deterministic;
+pub mod events;
+pub mod fleet;
+pub mod fs_diff;
+pub mod git_patch;
+pub mod git_workspace;
+pub mod health;
 pub mod local;
+pub mod mock_script;
+pub mod read_only;
+pub mod stdin;
+pub mod users;
 
+use std::borrow::Cow;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-/// Tracing target for agent stdout lines. All providers (Claude, Codex, …)
-/// forward stdout lines through this target for structured logging. Providers
-/// with a dedicated observer (e.g. `ObserverKind::Codex`) parse lines AND
-/// forward; providers without one forward only.
-const AGENT_STDOUT_TARGET: &str = "agent_stdout";
-
+use sha2::{Digest, Sha256};
+
+pub use artifact::{ArtifactBundle, ArtifactFile, BundleManifestEntry};
+pub use clock::ClockSync;
+pub use console::ConsoleStream;
+pub use deterministic::SandboxManifest;
+pub use events::{SandboxEvent, SandboxEvents};
+pub use fleet::{FleetMember, FleetReport, FleetStart, SandboxFleet};
+pub use fs_diff::{FsChange, FsChangeKind, FsDiff};
+pub use git_patch::{FileDiff, FileStatus, GitPatch};
+pub use git_workspace::{CloneLocation, GitWorkspace};
+pub use health::{HealthCheck, HealthStatus, RestartPolicy};
 pub use local::LocalSandbox;
-
-use crate::backend::GuestConsoleSink;
+pub use mock_script::{MockCall, MockExec, MockMatcher, MockScript, MockWrite};
+pub use read_only::{ReadOnlyReport, RejectedWrite, WriteOp};
+pub use stdin::ExecStdin;
+pub use users::{ExecUser, GuestUser};
+pub use void_box_protocol::{
+    ExecAction, ExecDenial, ExecPolicy, ExecRule, ExecSignal, FileHash, MemoryInfo, MountInfo,
+    SysInfo,
+};
+
+use crate::agent_runner::{AgentExit, AgentRunState, AgentRunner};
+use crate::backend::{
+    BackendCaps, BootProfile, FrameRecord, GuestConsoleSink, NetworkMode, NetworkPolicy,
+    ProtocolTap, ResourcePolicy,
+};
+use crate::guest::protocol::{ChmodRequest, ShutdownAck, SymlinkRequest};
+use crate::idle::{IdlePolicy, IDLE_STOP_GRACE};
+use crate::observe::boot::BootTimeline;
+use crate::observe::crash::CrashSink;
+use crate::observe::network::NetworkLog;
 use crate::observe::telemetry::{TelemetryAggregator, TelemetryBuffer};
 use crate::observe::{ObserveConfig, Observer};
+use crate::proxy::HttpRecording;
+use crate::secret::{Redactor, Secret, SecretSource};
 use crate::{Error, ExecOutput, Result};
 
+/// A virtio-blk data disk for the guest (KVM only).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiskSpec {
+    /// A sparse ext4 image of `size_gb` GiB, created on first boot, mounted
+    /// writable at `/scratch` (`/scratch1`, … for later ones) and deleted
+    /// with the sandbox. It survives restarts of the same sandbox.
+    Scratch {
+        /// Capacity in GiB; host space is only used as the guest writes.
+        size_gb: u64,
+    },
+    /// An existing host image, exposed unmounted as `/dev/vdX`.
+    Attach {
+        /// Host path to the raw image.
+        path: PathBuf,
+        /// Reject guest writes.
+        read_only: bool,
+    },
+}
+
+/// A named [`Volume`](crate::volume::Volume) mounted into the guest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeMount {
+    /// Volume name; created with the default size on first use.
+    pub name: String,
+    /// Mount point inside the guest.
+    pub guest_path: String,
+}
+
+/// Default [`SandboxConfig::max_in_flight_execs`].
+pub const DEFAULT_MAX_IN_FLIGHT_EXECS: usize = 32;
+
 /// Sandbox configuration
 #[derive(Debug, Clone)]
 pub struct SandboxConfig {
@@ -57,12 +128,19 @@ pub struct SandboxConfig {
     pub kernel: Option<PathBuf>,
     /// Path to initramfs
     pub initramfs: Option<PathBuf>,
+    /// Host directories appended to `initramfs` at boot, in order; see
+    /// [`SandboxBuilder::initramfs_overlay`].
+    pub initramfs_overlays: Vec<PathBuf>,
     /// Path to root filesystem
     pub rootfs: Option<PathBuf>,
     /// Enable vsock for communication
     pub enable_vsock: bool,
     /// Host-side routing for guest serial console output.
     pub guest_console: GuestConsoleSink,
+    /// Run a root shell on the guest serial console; see [`console`].
+    pub console_shell: bool,
+    /// Startup latency versus guest features; see [`BootProfile`].
+    pub boot_profile: BootProfile,
     /// Observability configuration
     pub observe: Option<ObserveConfig>,
     /// Shared directory to mount in guest
@@ -75,8 +153,16 @@ pub struct SandboxConfig {
     pub oci_rootfs_dev: Option<String>,
     /// Host path to OCI rootfs disk image for virtio-blk (KVM).
     pub oci_rootfs_disk: Option<PathBuf>,
+    /// Data disks, in the order the guest sees them.
+    pub disks: Vec<DiskSpec>,
+    /// Persistent volumes, attached after `disks`.
+    pub volumes: Vec<VolumeMount>,
     /// Environment variables
     pub env: Vec<(String, String)>,
+    /// Working directory for execs that do not pick their own.
+    pub working_dir: Option<String>,
+    /// Directories put in front of every exec's `PATH`, in order.
+    pub path_prepend: Vec<String>,
     /// Path to a snapshot directory to restore from (skips cold boot).
     pub snapshot: Option<PathBuf>,
     /// Opt-in that the caller plans to save a snapshot later in this run.
@@ -95,6 +181,62 @@ pub struct SandboxConfig {
     /// `max_concurrent_connections` ceiling.  `None` keeps the
     /// production default (64).
     pub network_max_concurrent_connections: Option<usize>,
+    /// SLIRP (default) or a host TAP device for guest networking.
+    pub network_mode: NetworkMode,
+    /// Egress policy enforced on top of the default deny list.
+    pub network_policy: Option<NetworkPolicy>,
+    /// Record the guest's outbound HTTP(S) traffic, or replay a recording.
+    pub http_recording: Option<HttpRecording>,
+    /// Upstream DNS resolvers (IPv4 addresses); empty keeps the defaults.
+    pub dns_servers: Vec<String>,
+    /// Static `(name, IPv4 address)` entries answered by the guest's DNS.
+    pub host_aliases: Vec<(String, String)>,
+    /// Host-enforced vCPU pinning and CPU quota (KVM only).
+    pub resource_policy: ResourcePolicy,
+    /// Execs allowed in flight at once; past it, new execs fail with
+    /// [`Error::Busy`] instead of queueing.
+    pub max_in_flight_execs: usize,
+    /// Where crash reports go when the guest dies under an exec.
+    pub crash_sink: Option<Arc<dyn CrashSink>>,
+    /// Heartbeat the guest-agent on this schedule (local sandboxes only).
+    pub health_check: Option<HealthCheck>,
+    /// Whether a dead or unresponsive guest is replaced with a fresh VM.
+    pub restart_policy: RestartPolicy,
+    /// Attach a watchdog the guest-agent must pet within this timeout
+    /// (local KVM sandboxes only); see [`health`].
+    pub watchdog: Option<std::time::Duration>,
+    /// Extra guest kernel cmdline arguments (local sandboxes only); see
+    /// [`crate::backend::cmdline`].
+    pub extra_cmdline: Vec<String>,
+    /// Guest directories `write_file`, `mkdir_p` and friends may touch on
+    /// top of `/workspace`, `/home` and `/etc/voidbox` (local sandboxes
+    /// only).
+    pub write_roots: Vec<String>,
+    /// Run x86_64 binaries through Rosetta (local VZ sandboxes on Apple
+    /// silicon only).
+    pub rosetta: bool,
+    /// Repository checked out into the guest on every boot (local
+    /// sandboxes only).
+    pub git_workspace: Option<GitWorkspace>,
+    /// Secrets injected into every exec; see [`crate::secret`].
+    pub secrets: Vec<Secret>,
+    /// Which commands execs may run. `None` leaves the guest's provisioned
+    /// command allowlist in force.
+    pub exec_policy: Option<ExecPolicy>,
+    /// Refuse file writes once booted; see the
+    /// [module docs](crate::sandbox::read_only).
+    pub read_only: bool,
+    /// Guest users created on every boot; see [`users`].
+    pub users: Vec<String>,
+    /// Re-sync the guest clock this often (local sandboxes only); see
+    /// [`clock`].
+    pub clock_sync: Option<std::time::Duration>,
+    /// Seed of a deterministic run; see [`deterministic`].
+    pub deterministic: Option<u64>,
+    /// Scenario a mock sandbox answers from; see [`mock_script`].
+    pub mock_script: Option<MockScript>,
+    /// Records host↔guest control-channel frames; see [`ProtocolTap`].
+    pub protocol_tap: Option<ProtocolTap>,
 }
 
 impl Default for SandboxConfig {
@@ -105,20 +247,50 @@ impl Default for SandboxConfig {
             network: false,
             kernel: None,
             initramfs: None,
+            initramfs_overlays: Vec::new(),
             rootfs: None,
             enable_vsock: true,
             guest_console: GuestConsoleSink::Stderr,
+            console_shell: false,
+            boot_profile: BootProfile::Standard,
             observe: None,
             shared_dir: None,
             mounts: Vec::new(),
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
+            volumes: Vec::new(),
             env: Vec::new(),
+            working_dir: None,
+            path_prepend: Vec::new(),
             snapshot: None,
             enable_snapshots: false,
             network_max_connections_per_second: None,
             network_max_concurrent_connections: None,
+            network_mode: NetworkMode::default(),
+            network_policy: None,
+            http_recording: None,
+            dns_servers: Vec::new(),
+            host_aliases: Vec::new(),
+            resource_policy: ResourcePolicy::default(),
+            max_in_flight_execs: DEFAULT_MAX_IN_FLIGHT_EXECS,
+            crash_sink: None,
+            health_check: None,
+            restart_policy: RestartPolicy::Never,
+            watchdog: None,
+            extra_cmdline: Vec::new(),
+            write_roots: Vec::new(),
+            rosetta: false,
+            git_workspace: None,
+            secrets: Vec::new(),
+            exec_policy: None,
+            read_only: false,
+            users: Vec::new(),
+            clock_sync: None,
+            deterministic: None,
+            mock_script: None,
+            protocol_tap: None,
         }
     }
 }
@@ -129,42 +301,127 @@ pub struct Sandbox {
     config: SandboxConfig,
     /// The underlying implementation
     inner: SandboxInner,
+    /// Lifecycle event stream, shared with the local implementation
+    events: SandboxEvents,
+    /// Scrubs secret values from exec arguments before they are reported
+    redactor: Arc<Redactor>,
+    /// Checked before every exec, so denials fail fast with the rule
+    exec_policy: std::sync::RwLock<Option<ExecPolicy>>,
+    /// Writes refused in read-only mode, for the report
+    rejected_writes: std::sync::Mutex<Vec<RejectedWrite>>,
 }
 
 enum SandboxInner {
     /// Local KVM-based sandbox
-    Local(Box<LocalSandbox>),
+    Local(Arc<LocalSandbox>),
     /// Mock sandbox for testing
     Mock(Box<MockSandbox>),
 }
 
-/// Produce a human-readable fallback error message when the agent reported
-/// `is_error=true` but left `error` empty.  Tries, in order: guest stderr,
-/// agent `result_text`, the optional exec-layer error (e.g. from the
-/// streaming `response.error` field), and a default `"<binary> exited with
-/// an unspecified error"` string.  Callers pass whichever signals are
-/// available for their code path.
-fn fallback_error_message(
-    stderr: &str,
-    result_text: &str,
-    response_error: Option<&str>,
-    binary_name: &str,
-) -> String {
-    let trimmed_stderr = stderr.trim();
-    if !trimmed_stderr.is_empty() {
-        return trimmed_stderr.to_string();
-    }
-    let trimmed_result = result_text.trim();
-    if !trimmed_result.is_empty() {
-        return trimmed_result.to_string();
-    }
-    if let Some(err) = response_error {
-        let trimmed = err.trim();
-        if !trimmed.is_empty() {
-            return trimmed.to_string();
-        }
-    }
-    format!("{} exited with an unspecified error", binary_name)
+/// Fold one agent stdout line into `state`, run the tool hook on each tool
+/// call it reports and check the budget. `running` addresses the agent
+/// while it can still be held or killed.
+async fn handle_agent_line<F>(
+    runner: &dyn AgentRunner,
+    line: &str,
+    state: &mut AgentRunState,
+    opts: &crate::observe::claude::AgentExecOpts,
+    on_event: &mut F,
+    running: Option<(&LocalSandbox, &str)>,
+) -> Result<()>
+where
+    F: FnMut(crate::observe::claude::AgentStreamEvent),
+{
+    use crate::observe::claude::AgentStreamEvent;
+
+    for event in runner.parse_event(line, state) {
+        let decision = match (&opts.tool_hook, &event) {
+            (Some(hook), AgentStreamEvent::ToolUse(call)) => {
+                Some((call.tool_name.clone(), hook.decide(call)))
+            }
+            _ => None,
+        };
+        on_event(event);
+        let Some((tool, decision)) = decision else {
+            continue;
+        };
+        let why = format!("tool hook on {tool}");
+        let denied = crate::tool_hook::settle(decision, |signal| {
+            let why = &why;
+            async move {
+                if let Some((local, exec_id)) = running {
+                    local.signal_exec_logged(exec_id, signal, why).await;
+                }
+            }
+        })
+        .await;
+        if let Some(reason) = denied {
+            return Err(Error::ToolDenied { tool, reason });
+        }
+    }
+
+    let Some(budget) = opts.budget else {
+        return Ok(());
+    };
+    let usage = runner.usage(state);
+    let Some(limit) = budget.exceeded_by(&usage) else {
+        return Ok(());
+    };
+    if let Some((local, exec_id)) = running {
+        local
+            .signal_exec_logged(
+                exec_id,
+                void_box_protocol::ExecSignal::Kill,
+                &format!("budget: {limit}"),
+            )
+            .await;
+    }
+    Err(Error::BudgetExceeded(Box::new(
+        crate::budget::BudgetExceeded::new(limit, std::mem::take(&mut state.result), &usage),
+    )))
+}
+
+/// Whether an agent that has printed nothing for `policy.after` is idle:
+/// no tool call it reported is still waiting for its result, and the guest
+/// CPU is low if telemetry is running.
+fn agent_is_idle(
+    runner: &dyn AgentRunner,
+    state: &AgentRunState,
+    policy: &IdlePolicy,
+    local: &LocalSandbox,
+) -> bool {
+    let pending_tool_calls = if runner.streams_tool_calls() {
+        state
+            .result
+            .tool_calls
+            .iter()
+            .filter(|call| call.output.is_none())
+            .count()
+    } else {
+        0
+    };
+    policy.is_idle(
+        pending_tool_calls,
+        local.telemetry_stats(policy.after).as_ref(),
+    )
+}
+
+/// Guest directories given to the builder must be absolute.
+fn validate_guest_dir(dir: &str) -> Result<()> {
+    if !dir.starts_with('/') {
+        return Err(Error::Config(format!(
+            "guest directory '{dir}' must be an absolute path"
+        )));
+    }
+    Ok(())
+}
+
+/// Exec ids name a running exec for [`Sandbox::signal`].
+fn validate_exec_id(exec_id: &str) -> Result<()> {
+    if exec_id.is_empty() {
+        return Err(Error::Config("exec id must not be empty".into()));
+    }
+    Ok(())
 }
 
 impl Sandbox {
@@ -188,6 +445,285 @@ impl Sandbox {
         self.exec_with_stdin(program, args, &[]).await
     }
 
+    /// Execute a command as `user` rather than the `sandbox` user.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(sandbox: &void_box::sandbox::Sandbox) -> void_box::Result<()> {
+    /// use void_box::sandbox::ExecUser;
+    /// sandbox
+    ///     .exec_as(ExecUser::Root, "apk", &["add", "--no-cache", "jq"])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_as(
+        &self,
+        user: ExecUser,
+        program: &str,
+        args: &[&str],
+    ) -> Result<ExecOutput> {
+        crate::guest::protocol::scope_exec_user(user, self.exec(program, args)).await
+    }
+
+    /// Execute a command in `working_dir` instead of the sandbox's default
+    /// (see [`SandboxBuilder::working_dir`]).
+    pub async fn exec_in(
+        &self,
+        working_dir: &str,
+        program: &str,
+        args: &[&str],
+    ) -> Result<ExecOutput> {
+        validate_guest_dir(working_dir)?;
+        crate::guest::protocol::scope_exec_working_dir(
+            working_dir.to_string(),
+            self.exec(program, args),
+        )
+        .await
+    }
+
+    /// Execute a command with `env` applied over the sandbox's environment
+    /// (see [`SandboxBuilder::env`]).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(sandbox: &void_box::sandbox::Sandbox) -> void_box::Result<()> {
+    /// sandbox
+    ///     .exec_with_env("cargo", &["test"], &[("RUST_LOG", "debug")])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_with_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(&str, &str)],
+    ) -> Result<ExecOutput> {
+        let env = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        crate::guest::protocol::scope_exec_env(env, self.exec(program, args)).await
+    }
+
+    /// Execute a command that [`signal`](Self::signal) can reach as
+    /// `exec_id` while it runs.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(sandbox: std::sync::Arc<void_box::sandbox::Sandbox>) -> void_box::Result<()> {
+    /// use void_box::sandbox::ExecSignal;
+    /// let server = tokio::spawn({
+    ///     let sandbox = sandbox.clone();
+    ///     async move { sandbox.exec_with_id("dev-server", "npm", &["run", "dev"]).await }
+    /// });
+    /// // ...
+    /// sandbox.signal("dev-server", ExecSignal::Terminate).await?;
+    /// let output = server.await.unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_with_id(
+        &self,
+        exec_id: &str,
+        program: &str,
+        args: &[&str],
+    ) -> Result<ExecOutput> {
+        validate_exec_id(exec_id)?;
+        crate::guest::protocol::scope_exec_id(exec_id.to_string(), self.exec(program, args)).await
+    }
+
+    /// [`exec_streaming`](Self::exec_streaming) with the command reachable
+    /// by [`signal`](Self::signal) as `exec_id`.
+    pub async fn exec_streaming_with_id(
+        &self,
+        exec_id: &str,
+        program: &str,
+        args: &[&str],
+        timeout_secs: Option<u64>,
+    ) -> Result<(
+        tokio::sync::mpsc::Receiver<crate::guest::protocol::ExecOutputChunk>,
+        tokio::sync::oneshot::Receiver<Result<crate::guest::protocol::ExecResponse>>,
+    )> {
+        validate_exec_id(exec_id)?;
+        crate::guest::protocol::scope_exec_id(
+            exec_id.to_string(),
+            self.exec_streaming(program, args, timeout_secs),
+        )
+        .await
+    }
+
+    /// [`exec_streaming`](Self::exec_streaming) with the command's stdin
+    /// left open for the returned [`ExecStdin`]; see [`stdin`]. Needs a
+    /// guest VM: mock and simulated sandboxes return [`Error::Config`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(sandbox: &void_box::sandbox::Sandbox) -> void_box::Result<()> {
+    /// let (mut stdin, mut output, response) = sandbox
+    ///     .exec_streaming_with_stdin("wc", &["-l"], None)
+    ///     .await?;
+    /// stdin.write(b"one\ntwo\n").await?;
+    /// stdin.close().await?;
+    /// while let Some(chunk) = output.recv().await {
+    ///     print!("{}", String::from_utf8_lossy(&chunk.data));
+    /// }
+    /// let _ = response.await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_streaming_with_stdin(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout_secs: Option<u64>,
+    ) -> Result<(
+        ExecStdin,
+        tokio::sync::mpsc::Receiver<crate::guest::protocol::ExecOutputChunk>,
+        tokio::sync::oneshot::Receiver<Result<crate::guest::protocol::ExecResponse>>,
+    )> {
+        let local = match &self.inner {
+            SandboxInner::Local(local) if !local.simulated() => local,
+            _ => return Err(Error::Config("streamed stdin needs a guest VM".into())),
+        };
+        let exec_id = uuid::Uuid::now_v7().to_string();
+        let (chunk_rx, response_rx) = crate::guest::protocol::scope_exec_stdin_stream(
+            exec_id.clone(),
+            self.exec_streaming(program, args, timeout_secs),
+        )
+        .await?;
+        let stdin = local.exec_stdin(exec_id).await?;
+        Ok((stdin, chunk_rx, response_rx))
+    }
+
+    /// Send `signal` to the running exec started as `exec_id` (see
+    /// [`exec_with_id`](Self::exec_with_id)) and every process it spawned.
+    /// Fails if no such exec is running; mock execs finish before they can
+    /// be signalled.
+    pub async fn signal(&self, exec_id: &str, signal: ExecSignal) -> Result<()> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.signal_exec(exec_id, signal).await,
+            SandboxInner::Mock(_) => {
+                Err(Error::Guest(format!("no running exec with id {exec_id}")))
+            }
+        }
+    }
+
+    /// Set the guest's wall clock to the host's, e.g. after the host
+    /// resumed from suspend, and report how far off it was. See [`clock`].
+    pub async fn sync_clock(&self) -> Result<ClockSync> {
+        if self.config.deterministic.is_some() {
+            return Err(Error::Config(
+                "deterministic mode fixes the guest clock".into(),
+            ));
+        }
+        match &self.inner {
+            SandboxInner::Local(local) => local.sync_clock().await,
+            SandboxInner::Mock(_) => Ok(ClockSync { drift_ms: 0 }),
+        }
+    }
+
+    /// Describe what this sandbox's runs depend on, with a hash to compare
+    /// them by. See [`deterministic`].
+    pub fn manifest(&self) -> Result<SandboxManifest> {
+        SandboxManifest::from_config(&self.config, &self.redactor)
+    }
+
+    /// Create the guest user `name` (see [`users`]), or return it if it
+    /// exists. Run commands as it with [`GuestUser::exec_user`].
+    pub async fn create_user(&self, name: &str) -> Result<GuestUser> {
+        if !void_box_protocol::valid_user_name(name) {
+            return Err(Error::Config(format!("invalid user name '{name}'")));
+        }
+        match &self.inner {
+            SandboxInner::Local(local) => local.create_user(name).await,
+            SandboxInner::Mock(mock) => Ok(mock.create_user(name)),
+        }
+    }
+
+    /// Agents run unprivileged: refuse an agent started under
+    /// [`ExecUser::Root`].
+    fn ensure_unprivileged_agent() -> Result<()> {
+        match crate::guest::protocol::current_exec_overrides().user {
+            Some(ExecUser::Root) => Err(Error::Config(
+                "agents cannot run as root; run setup as root in a separate step".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Execute a command under `policy` instead of the sandbox's exec
+    /// policy. The guest-agent enforces it for this command only.
+    pub async fn exec_with_policy(
+        &self,
+        program: &str,
+        args: &[&str],
+        policy: ExecPolicy,
+    ) -> Result<ExecOutput> {
+        crate::guest::protocol::scope_exec_policy(policy, self.exec(program, args)).await
+    }
+
+    /// Replace the exec policy: in the running guest, and for execs and
+    /// restarts from now on.
+    pub async fn set_exec_policy(&self, policy: ExecPolicy) -> Result<()> {
+        *self.exec_policy.write().unwrap() = Some(policy.clone());
+        match &self.inner {
+            SandboxInner::Local(local) => local.set_exec_policy(policy).await,
+            SandboxInner::Mock(_) => Ok(()),
+        }
+    }
+
+    /// The exec policy in force, if any.
+    pub fn exec_policy(&self) -> Option<ExecPolicy> {
+        self.exec_policy.read().unwrap().clone()
+    }
+
+    /// Refuse `program args...` if the per-exec or sandbox policy denies it.
+    fn check_exec_policy(&self, program: &str, args: &[&str]) -> Result<()> {
+        let scoped = crate::guest::protocol::current_exec_overrides().policy;
+        let sandbox = self.exec_policy.read().unwrap();
+        let Some(policy) = scoped.as_ref().or(sandbox.as_ref()) else {
+            return Ok(());
+        };
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        policy.check(program, &args).map_err(|denial| {
+            let command = std::iter::once(program)
+                .chain(args.iter().map(|a| a.as_str()))
+                .map(|part| self.redactor.redact(part))
+                .collect::<Vec<_>>()
+                .join(" ");
+            Error::ExecDenied {
+                command,
+                rule: denial.to_string(),
+            }
+        })
+    }
+
+    /// Emit `ExecStarted`, and open an exec span when the sandbox is
+    /// observed. Fails without either if the exec policy denies the command.
+    fn track_exec(&self, program: &str, args: &[&str]) -> Result<events::ExecTracker> {
+        self.check_exec_policy(program, args)?;
+        let redacted: Vec<Cow<'_, str>> = args.iter().map(|a| self.redactor.redact(a)).collect();
+        let args: Vec<&str> = redacted.iter().map(|a| a.as_ref()).collect();
+        let args = args.as_slice();
+        let tracker = self
+            .events
+            .exec_started(program, args, self.config.max_in_flight_execs)?;
+        let span = match &self.inner {
+            SandboxInner::Local(local) => local.start_exec_span(program, args),
+            SandboxInner::Mock(_) => None,
+        };
+        Ok(match span {
+            Some(span) => tracker.traced(span),
+            None => tracker,
+        })
+    }
+
     /// Execute a command with stdin input
     pub async fn exec_with_stdin(
         &self,
@@ -195,10 +731,16 @@ impl Sandbox {
         args: &[&str],
         stdin: &[u8],
     ) -> Result<ExecOutput> {
-        match &self.inner {
-            SandboxInner::Local(local) => local.exec_with_stdin(program, args, stdin).await,
+        let tracker = self.track_exec(program, args)?;
+        let result = match &self.inner {
+            SandboxInner::Local(local) => {
+                tracker
+                    .scope(local.exec_with_stdin(program, args, stdin))
+                    .await
+            }
             SandboxInner::Mock(mock) => mock.exec_with_stdin(program, args, stdin).await,
-        }
+        };
+        tracker.finish_output(result)
     }
 
     /// Execute a command with stdin input and an explicit timeout.
@@ -214,14 +756,16 @@ impl Sandbox {
         stdin: &[u8],
         timeout_secs: Option<u64>,
     ) -> Result<ExecOutput> {
-        match &self.inner {
+        let tracker = self.track_exec(program, args)?;
+        let result = match &self.inner {
             SandboxInner::Local(local) => {
-                local
-                    .exec_with_options(program, args, stdin, timeout_secs)
+                tracker
+                    .scope(local.exec_with_options(program, args, stdin, timeout_secs))
                     .await
             }
             SandboxInner::Mock(mock) => mock.exec_with_stdin(program, args, stdin).await,
-        }
+        };
+        tracker.finish_output(result)
     }
 
     /// Execute a command with streaming output.
@@ -238,12 +782,26 @@ impl Sandbox {
         tokio::sync::mpsc::Receiver<crate::guest::protocol::ExecOutputChunk>,
         tokio::sync::oneshot::Receiver<Result<crate::guest::protocol::ExecResponse>>,
     )> {
+        let tracker = self.track_exec(program, args)?;
         match &self.inner {
-            SandboxInner::Local(local) => local.exec_streaming(program, args, timeout_secs).await,
+            SandboxInner::Local(local) => {
+                let (chunk_rx, response_rx) = match tracker
+                    .scope(local.exec_streaming(program, args, timeout_secs))
+                    .await
+                {
+                    Ok(streams) => streams,
+                    Err(e) => {
+                        tracker.finish_error(&e);
+                        return Err(e);
+                    }
+                };
+                Ok((chunk_rx, tracker.finish_streaming(response_rx)))
+            }
             SandboxInner::Mock(mock) => {
                 use crate::guest::protocol::{ExecOutputChunk, ExecResponse};
 
-                let output = mock.exec_with_stdin(program, args, &[]).await?;
+                let output =
+                    tracker.finish_output(mock.exec_with_stdin(program, args, &[]).await)?;
                 let (chunk_tx, chunk_rx) = tokio::sync::mpsc::channel(1);
                 let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
 
@@ -281,6 +839,21 @@ impl Sandbox {
         }
     }
 
+    /// SHA-256, size and mtime of each of `paths`, in order, without
+    /// reading the files back to the host.
+    ///
+    /// Cheap enough to check what a previous provisioning run left in
+    /// place before re-sending it. Missing paths come back with
+    /// [`exists`](FileHash::exists) false; paths the guest refuses (outside
+    /// its read roots, or symlinks) carry an [`error`](FileHash::error).
+    pub async fn hash_files(&self, paths: &[&str]) -> Result<Vec<FileHash>> {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        match &self.inner {
+            SandboxInner::Local(local) => local.hash_files_native(&paths).await,
+            SandboxInner::Mock(mock) => Ok(mock.hash_files(&paths)),
+        }
+    }
+
     /// Reads a file from the sandbox.
     pub async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
         match &self.inner {
@@ -305,31 +878,300 @@ impl Sandbox {
     /// which writes it in Rust without needing `sh`, `echo`, or `base64`.
     /// Parent directories are created automatically.
     pub async fn write_file(&self, path: &str, content: &[u8]) -> Result<()> {
+        self.refuse_in_read_only(WriteOp::WriteFile, path)?;
         match &self.inner {
-            SandboxInner::Local(local) => local.write_file_native(path, content).await,
-            SandboxInner::Mock(_mock) => {
-                // Mock: no-op success
-                Ok(())
-            }
+            SandboxInner::Local(local) => local.write_file_native(path, content).await?,
+            SandboxInner::Mock(mock) => mock.write_file(path, content)?,
         }
+        self.events.emit(SandboxEvent::FileWritten {
+            path: path.to_string(),
+            bytes: content.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// Stream a file of arbitrary size into the sandbox.
+    ///
+    /// Unlike [`write_file`](Self::write_file), the content is not bounded by
+    /// the protocol's message size: it is sent in offset-tagged chunks and
+    /// committed atomically once complete, so multi-hundred-MB artifacts can
+    /// be pushed without buffering them in memory. Returns the number of
+    /// bytes written.
+    pub async fn write_file_streaming<R>(&self, path: &str, reader: R) -> Result<u64>
+    where
+        R: tokio::io::AsyncRead + Unpin + Send,
+    {
+        self.refuse_in_read_only(WriteOp::WriteFile, path)?;
+        let bytes = match &self.inner {
+            SandboxInner::Local(local) => local.write_file_streaming(path, reader).await?,
+            SandboxInner::Mock(mock) => {
+                let mut content = Vec::new();
+                let mut reader = reader;
+                let bytes = tokio::io::copy(&mut reader, &mut content).await?;
+                mock.write_file(path, &content)?;
+                bytes
+            }
+        };
+        self.events.emit(SandboxEvent::FileWritten {
+            path: path.to_string(),
+            bytes,
+        });
+        Ok(bytes)
     }
 
     /// Create directories in the guest filesystem (mkdir -p).
     pub async fn mkdir_p(&self, path: &str) -> Result<()> {
+        self.refuse_in_read_only(WriteOp::MkdirP, path)?;
         match &self.inner {
             SandboxInner::Local(local) => local.mkdir_p(path).await,
             SandboxInner::Mock(_mock) => Ok(()),
         }
     }
 
+    /// Create a symbolic link at `link_path` pointing to `target`, replacing
+    /// a file or link already there (like `ln -sf`).
+    ///
+    /// Lays down linked tool directories without a shell in the guest. Like
+    /// [`write_file`](Self::write_file), the link must be under an allowed
+    /// write root and is owned by the sandbox user.
+    pub async fn symlink(&self, target: &str, link_path: &str) -> Result<()> {
+        self.refuse_in_read_only(WriteOp::Symlink, link_path)?;
+        match &self.inner {
+            SandboxInner::Local(local) => {
+                local
+                    .symlink(SymlinkRequest {
+                        target: target.to_string(),
+                        link_path: link_path.to_string(),
+                        replace: true,
+                    })
+                    .await
+            }
+            SandboxInner::Mock(_mock) => Ok(()),
+        }
+    }
+
+    /// Set the permission bits of `path`, e.g. `0o755` for a script.
+    pub async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
+        self.set_file_attrs(ChmodRequest {
+            path: path.to_string(),
+            mode: Some(mode),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Change the owner of `path`.
+    pub async fn chown(&self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        self.set_file_attrs(ChmodRequest {
+            path: path.to_string(),
+            uid: Some(uid),
+            gid: Some(gid),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn set_file_attrs(&self, request: ChmodRequest) -> Result<()> {
+        self.refuse_in_read_only(WriteOp::Chmod, &request.path)?;
+        match &self.inner {
+            SandboxInner::Local(local) => local.chmod(request).await,
+            SandboxInner::Mock(_mock) => Ok(()),
+        }
+    }
+
+    /// Reads the target of the symbolic link at `path`, without following it.
+    pub async fn read_link(&self, path: &str) -> Result<String> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.read_link_native(path).await,
+            SandboxInner::Mock(mock) => {
+                let output = mock.exec_with_stdin("readlink", &[path], &[]).await?;
+                if output.exit_code == 0 {
+                    Ok(String::from_utf8_lossy(&output.stdout)
+                        .trim_end_matches('\n')
+                        .to_string())
+                } else {
+                    Err(crate::Error::Guest(format!(
+                        "Failed to read link: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Record and refuse `op` on `path` if the sandbox is read-only.
+    fn refuse_in_read_only(&self, op: WriteOp, path: &str) -> Result<()> {
+        if !self.config.read_only {
+            return Ok(());
+        }
+        self.rejected_writes.lock().unwrap().push(RejectedWrite {
+            op,
+            path: path.to_string(),
+        });
+        Err(Error::ReadOnly {
+            op: op.as_str().to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// What this [read-only](SandboxBuilder::read_only) sandbox has been
+    /// asked to write so far: refused file writes, and the root filesystem
+    /// changes that will be discarded with the VM.
+    pub async fn read_only_report(&self) -> Result<ReadOnlyReport> {
+        if !self.config.read_only {
+            return Err(Error::Config("sandbox is not read-only".into()));
+        }
+        let rejected = self.rejected_writes.lock().unwrap().clone();
+        Ok(ReadOnlyReport {
+            rejected,
+            discarded: self.fs_diff().await?,
+        })
+    }
+
+    /// Kernel version, uptime, load average, memory breakdown and mount
+    /// table of the guest, read in one round trip instead of an exec per
+    /// `/proc` file. Mock sandboxes report an empty [`SysInfo`].
+    pub async fn sysinfo(&self) -> Result<SysInfo> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.sysinfo().await,
+            SandboxInner::Mock(_mock) => Ok(SysInfo::default()),
+        }
+    }
+
+    /// Report what changed in the guest's root filesystem.
+    ///
+    /// On an OCI rootfs this compares against the image as it stood at the
+    /// end of boot and needs no earlier call. Otherwise the first call
+    /// records a baseline (see [`FsDiff::baseline_created`]) and later calls
+    /// report changes since then.
+    pub async fn fs_diff(&self) -> Result<FsDiff> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.fs_diff(None).await,
+            SandboxInner::Mock(_mock) => Ok(FsDiff {
+                root: "/".into(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Report what changed under `root` since the first call for that root.
+    ///
+    /// `root` must lie under the guest's readable roots (`/workspace`,
+    /// `/home`, `/etc/voidbox`). Call once before a run to record the
+    /// baseline and again afterwards for the diff.
+    pub async fn fs_diff_at(&self, root: &str) -> Result<FsDiff> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.fs_diff(Some(root)).await,
+            SandboxInner::Mock(_mock) => Ok(FsDiff {
+                root: root.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Record the workspace's current state as the baseline
+    /// [`git_patch`](Self::git_patch) diffs against, running `git init`
+    /// first if it is not a repository, and return the baseline commit id.
+    /// A [`git_workspace`](SandboxBuilder::git_workspace) records one after
+    /// its checkout. The workspace is `/workspace`, or the git workspace's
+    /// path. Needs `git` in the guest; mock sandboxes return an empty id.
+    pub async fn git_baseline(&self) -> Result<String> {
+        if let SandboxInner::Mock(_) = &self.inner {
+            return Ok(String::new());
+        }
+        let args = git_patch::baseline_args(self.workspace_dir());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.exec("sh", &args).await?;
+        if !output.success() {
+            return Err(Error::Guest(format!(
+                "git baseline failed: {}",
+                output.stderr_str().trim()
+            )));
+        }
+        Ok(output.stdout_str().trim().to_string())
+    }
+
+    /// Everything that changed in the workspace since
+    /// [`git_baseline`](Self::git_baseline) (or since `HEAD` in a repository
+    /// without one), untracked files included, split per file. See
+    /// [`git_patch`] for details. Mock sandboxes report no changes.
+    pub async fn git_patch(&self) -> Result<GitPatch> {
+        if let SandboxInner::Mock(_) = &self.inner {
+            return Ok(GitPatch::default());
+        }
+        let args = git_patch::diff_args(self.workspace_dir());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.exec("sh", &args).await?;
+        if !output.success() {
+            return Err(Error::Guest(format!(
+                "git diff failed: {}",
+                output.stderr_str().trim()
+            )));
+        }
+        Ok(GitPatch::parse(&output.stdout_str()))
+    }
+
+    /// The workspace changes since the baseline as a patch for `git apply`;
+    /// the text of [`git_patch`](Self::git_patch).
+    pub async fn git_diff(&self) -> Result<String> {
+        Ok(self.git_patch().await?.diff)
+    }
+
+    fn workspace_dir(&self) -> &str {
+        self.config
+            .git_workspace
+            .as_ref()
+            .map_or("/workspace", GitWorkspace::checkout_path)
+    }
+
+    /// Collect `/workspace` from the guest as an [`ArtifactBundle`].
+    ///
+    /// `path_filter` is a glob over workspace-relative paths (`*` and `?`
+    /// stay within one path segment, `**` spans segments), e.g.
+    /// `Some("out/**/*.json")`; `None` exports every file.
+    pub async fn export_workspace(&self, path_filter: Option<&str>) -> Result<ArtifactBundle> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.export_workspace(path_filter).await,
+            SandboxInner::Mock(_mock) => Ok(ArtifactBundle::default()),
+        }
+    }
+
+    /// Write an [`export_workspace`](Self::export_workspace) bundle back
+    /// into `/workspace`, e.g. to carry a checkpoint over to a replacement
+    /// sandbox. Files and links already there are overwritten; other files
+    /// are left alone.
+    pub async fn restore_workspace(&self, bundle: &ArtifactBundle) -> Result<()> {
+        let root = self.workspace_dir().to_string();
+        for file in &bundle.files {
+            let path = format!("{}/{}", root, file.path);
+            match &file.link_target {
+                Some(target) => {
+                    if let Some((parent, _)) = path.rsplit_once('/') {
+                        self.mkdir_p(parent).await?;
+                    }
+                    self.symlink(target, &path).await?;
+                }
+                None => {
+                    self.write_file(&path, &file.data).await?;
+                    self.chmod(&path, file.mode & 0o7777).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Execute an LLM agent binary and parse the result.
     ///
     /// This is a high-level wrapper that:
-    /// 1. Builds CLI args via `provider.build_exec_args()`
-    /// 2. For claude-code: parses the JSONL stdout into structured `AgentExecResult`
-    /// 3. For other providers: forwards stdout as `result_text`
+    /// 1. Picks the provider's [`AgentRunner`] via `provider.runner()`
+    /// 2. Runs the command line the runner builds
+    /// 3. Folds every stdout line into a structured `AgentExecResult`
     /// 4. Returns both the text result and full telemetry (tokens, cost, tool calls)
     ///
+    /// OpenAI-compatible providers run no guest binary: their agent loop
+    /// runs on the host and executes tool calls in this sandbox (see
+    /// [`openai_agent`](crate::openai_agent)).
+    ///
     /// When the `opentelemetry` feature is enabled, OTel spans are created
     /// for the execution and each tool call.
     pub async fn exec_agent(
@@ -338,34 +1180,45 @@ impl Sandbox {
         prompt: &str,
         opts: crate::observe::claude::AgentExecOpts,
     ) -> Result<crate::observe::claude::AgentExecResult> {
-        if let SandboxInner::Local(local) = &self.inner {
-            if provider.observer_kind() == crate::llm::ObserverKind::ClaudeStreamJson {
-                self.verify_claude_code_compat(local, &opts.env).await?;
-            }
+        Self::ensure_unprivileged_agent()?;
+        let Some(runner) = provider.runner() else {
+            return crate::openai_agent::exec_openai_agent(self, provider, prompt, opts, |_| {})
+                .await;
+        };
+
+        // Tool hooks, budgets and idle policies act as events arrive, which
+        // needs the streaming path.
+        if opts.tool_hook.is_some() || opts.budget.is_some() || opts.idle.is_some() {
+            return self.exec_runner(&*runner, prompt, opts, |_| {}).await;
         }
 
-        let args: Vec<String> =
-            provider.build_exec_args(prompt, opts.dangerously_skip_permissions, &opts.extra_args);
-        let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        let command = runner.build_command(prompt, &opts);
+        if let SandboxInner::Local(local) = &self.inner {
+            self.verify_agent_installed(local, &*runner, &command.program)
+                .await?;
+        }
+        let args_refs: Vec<&str> = command.args.iter().map(|s| s.as_str()).collect();
 
         // Execute via the normal sandbox path
+        let tracker = self.track_exec(&command.program, &args_refs)?;
         let output = match &self.inner {
             SandboxInner::Local(local) => {
                 // For local sandbox, pass extra env and timeout through
-                local
-                    .exec_agent_internal(
-                        provider.binary_name(),
+                tracker
+                    .scope(local.exec_agent_internal(
+                        &command.program,
                         &args_refs,
                         &opts.env,
                         opts.timeout_secs,
-                    )
-                    .await?
+                    ))
+                    .await
             }
             SandboxInner::Mock(mock) => {
-                mock.exec_with_stdin(provider.binary_name(), &args_refs, &[])
-                    .await?
+                mock.exec_with_stdin(&command.program, &args_refs, &[])
+                    .await
             }
         };
+        let output = tracker.finish_output(output)?;
 
         // Log raw output for debugging (always at debug, stderr at warn on failure)
         {
@@ -381,7 +1234,7 @@ impl Sandbox {
                 tracing::warn!(
                     exit_code = output.exit_code,
                     "{} failed; stderr={}, stdout_head={}",
-                    provider.binary_name(),
+                    command.program,
                     if stderr_str.is_empty() {
                         "(empty)"
                     } else {
@@ -395,7 +1248,7 @@ impl Sandbox {
                     stdout_len = output.stdout.len(),
                     stderr_len = output.stderr.len(),
                     "{} finished; stdout_head={}, stderr={}",
-                    provider.binary_name(),
+                    command.program,
                     stdout_preview,
                     if stderr_str.is_empty() {
                         "(empty)"
@@ -406,326 +1259,287 @@ impl Sandbox {
             }
         }
 
-        // Dispatch to the provider-appropriate stdout parser.
-        // ClaudeStreamJson: parse stream-json output even on non-zero exit codes —
-        // claude-code exits 1 when the task fails but still produces valid stream-json.
-        // Codex: parse codex JSONL events; fall back to is_error on non-zero exit.
-        let mut result = match provider.observer_kind() {
-            crate::llm::ObserverKind::ClaudeStreamJson => {
-                crate::observe::claude::parse_stream_json(&output.stdout)
-            }
-            crate::llm::ObserverKind::Codex => {
-                let stdout_text = String::from_utf8_lossy(&output.stdout);
-                let mut result = crate::observe::claude::AgentExecResult::default();
-                for line in stdout_text.lines() {
-                    crate::observe::codex::parse_codex_line(line, &mut result);
-                }
-                if output.exit_code != 0 && !result.is_error {
-                    result.is_error = true;
-                }
-                result
-            }
-        };
-
-        let no_stream_output = result.session_id.is_empty()
-            && result.model.is_empty()
-            && result.result_text.is_empty()
-            && result.tool_calls.is_empty()
-            && result.input_tokens == 0
-            && result.output_tokens == 0
-            && !result.is_error;
-
-        if provider.observer_kind() == crate::llm::ObserverKind::ClaudeStreamJson
-            && no_stream_output
-        {
-            let stderr_str = String::from_utf8_lossy(&output.stderr);
-            let stdout_str = String::from_utf8_lossy(&output.stdout);
-            let stdout_preview = if stdout_str.len() > 500 {
-                format!("{}...", &stdout_str[..500])
-            } else {
-                stdout_str.to_string()
-            };
-            return Err(Error::Guest(format!(
-                "claude-code returned no stream-json events (exit_code={}). stderr: {}. stdout_head: {}",
-                output.exit_code,
-                if stderr_str.trim().is_empty() {
-                    "(empty)"
-                } else {
-                    stderr_str.trim()
-                },
-                if stdout_preview.trim().is_empty() {
-                    "(empty)"
-                } else {
-                    stdout_preview.trim()
-                }
-            )));
-        }
-
-        if provider.observer_kind() == crate::llm::ObserverKind::ClaudeStreamJson
-            && result.is_error
-            && result.error.as_deref().is_none_or(str::is_empty)
-        {
-            let stderr_str = String::from_utf8_lossy(&output.stderr);
-            result.error = Some(fallback_error_message(
-                &stderr_str,
-                &result.result_text,
-                None,
-                provider.binary_name(),
-            ));
-        }
-
-        Ok(result)
+        self.replay_agent_output(&*runner, &output, &opts, |_| {})
+            .await
     }
 
     /// Execute an LLM agent binary with streaming output and incremental parsing.
     ///
-    /// Like [`exec_agent()`](Self::exec_agent), but parses JSONL lines as
+    /// Like [`exec_agent()`](Self::exec_agent), but parses stdout lines as
     /// they arrive from the guest VM and calls `on_event` for each tool-use
-    /// event in real-time.  For claude-code this uses the stream-json observer;
-    /// for other providers stdout lines are forwarded to tracing and
-    /// accumulated into `result_text`.  Returns the same `AgentExecResult` as
-    /// the non-streaming variant.
+    /// event in real-time, through the provider's [`AgentRunner`] (see
+    /// [`exec_runner()`](Self::exec_runner)).  Returns the same
+    /// `AgentExecResult` as the non-streaming variant.
     pub async fn exec_agent_streaming<F>(
         &self,
         provider: &crate::llm::LlmProvider,
         prompt: &str,
         opts: crate::observe::claude::AgentExecOpts,
-        mut on_event: F,
+        on_event: F,
     ) -> Result<crate::observe::claude::AgentExecResult>
     where
         F: FnMut(crate::observe::claude::AgentStreamEvent),
     {
-        use crate::observe::claude::{parse_jsonl_line, AgentExecResult, AgentStreamEvent};
-        use std::collections::HashMap;
-
-        if let SandboxInner::Local(local) = &self.inner {
-            if provider.observer_kind() == crate::llm::ObserverKind::ClaudeStreamJson {
-                self.verify_claude_code_compat(local, &opts.env).await?;
+        Self::ensure_unprivileged_agent()?;
+        match provider.runner() {
+            Some(runner) => self.exec_runner(&*runner, prompt, opts, on_event).await,
+            // The OpenAI-compatible agent loop runs on the host.
+            None => {
+                crate::openai_agent::exec_openai_agent(self, provider, prompt, opts, on_event).await
             }
         }
+    }
 
-        let args: Vec<String> =
-            provider.build_exec_args(prompt, opts.dangerously_skip_permissions, &opts.extra_args);
-        let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-
-        match &self.inner {
-            SandboxInner::Local(local) => {
-                let (mut chunk_rx, response_rx) = local
-                    .exec_agent_streaming_internal(
-                        provider.binary_name(),
-                        &args_refs,
-                        &opts.env,
-                        opts.timeout_secs,
-                    )
-                    .await?;
-
-                match provider.observer_kind() {
-                    crate::llm::ObserverKind::ClaudeStreamJson => {
-                        let mut state = AgentExecResult {
-                            result_text: String::new(),
-                            model: String::new(),
-                            session_id: String::new(),
-                            total_cost_usd: 0.0,
-                            duration_ms: 0,
-                            duration_api_ms: 0,
-                            num_turns: 0,
-                            input_tokens: 0,
-                            output_tokens: 0,
-                            is_error: false,
-                            error: None,
-                            tool_calls: Vec::new(),
-                        };
-                        let mut tool_id_map: HashMap<String, usize> = HashMap::new();
-                        let mut line_buf = String::new();
-
-                        // Process stdout chunks as they arrive
-                        while let Some(chunk) = chunk_rx.recv().await {
-                            if chunk.stream != "stdout" {
-                                continue;
-                            }
-
-                            let text = String::from_utf8_lossy(&chunk.data);
-                            line_buf.push_str(&text);
-
-                            // Process all complete lines in the buffer
-                            while let Some(newline_pos) = line_buf.find('\n') {
-                                let line: String = line_buf.drain(..=newline_pos).collect();
-                                for event in parse_jsonl_line(&line, &mut state, &mut tool_id_map) {
-                                    on_event(event);
-                                }
-                            }
-                        }
+    /// Run the agent CLI `runner` describes, streaming its stdout through
+    /// [`AgentRunner::parse_event`] and calling `on_event` for each event.
+    ///
+    /// Tool hooks need a runner that
+    /// [streams its tool calls](AgentRunner::streams_tool_calls); budgets
+    /// are checked against [`AgentRunner::usage`] after every line. With an
+    /// [`IdlePolicy`], an agent that goes quiet is stopped and the run
+    /// returns what it produced.
+    pub async fn exec_runner<F>(
+        &self,
+        runner: &dyn AgentRunner,
+        prompt: &str,
+        opts: crate::observe::claude::AgentExecOpts,
+        mut on_event: F,
+    ) -> Result<crate::observe::claude::AgentExecResult>
+    where
+        F: FnMut(crate::observe::claude::AgentStreamEvent),
+    {
+        Self::ensure_unprivileged_agent()?;
+        let command = runner.build_command(prompt, &opts);
+        if opts.tool_hook.is_some() && !runner.streams_tool_calls() {
+            return Err(Error::Config(format!(
+                "tool hooks need an agent that reports tool calls before running them; {} does not",
+                command.program
+            )));
+        }
 
-                        // Process any remaining partial line
-                        if !line_buf.trim().is_empty() {
-                            for event in parse_jsonl_line(&line_buf, &mut state, &mut tool_id_map) {
-                                on_event(event);
-                            }
-                        }
+        if let SandboxInner::Local(local) = &self.inner {
+            self.verify_agent_installed(local, runner, &command.program)
+                .await?;
+        }
+        let args_refs: Vec<&str> = command.args.iter().map(|s| s.as_str()).collect();
 
-                        // Wait for the final response (for exit code / error info)
-                        let response = response_rx.await.map_err(|_| {
-                            Error::Guest("Failed to receive streaming response".into())
-                        })?;
-
-                        // Log raw output on failure
-                        if let Ok(ref resp) = response {
-                            if resp.exit_code != 0 {
-                                let stderr_str = String::from_utf8_lossy(&resp.stderr);
-                                tracing::warn!(
-                                    exit_code = resp.exit_code,
-                                    "claude-code failed; stderr={}",
-                                    if stderr_str.is_empty() {
-                                        "(empty)"
-                                    } else {
-                                        stderr_str.trim()
-                                    },
-                                );
-                            }
-                        }
+        let tracker = self.track_exec(&command.program, &args_refs)?;
+        let local = match &self.inner {
+            SandboxInner::Local(local) => local,
+            SandboxInner::Mock(mock) => {
+                // Mock: run to completion, then replay the output
+                let output = tracker.finish_output(
+                    mock.exec_with_stdin(&command.program, &args_refs, &[])
+                        .await,
+                )?;
+                return self
+                    .replay_agent_output(runner, &output, &opts, on_event)
+                    .await;
+            }
+        };
 
-                        // Check for empty stream output
-                        let no_stream_output = state.session_id.is_empty()
-                            && state.model.is_empty()
-                            && state.result_text.is_empty()
-                            && state.tool_calls.is_empty()
-                            && state.input_tokens == 0
-                            && state.output_tokens == 0
-                            && !state.is_error;
-
-                        if no_stream_output {
-                            let (stderr_str, exit_code, error_str) = match &response {
-                                Ok(resp) => (
-                                    String::from_utf8_lossy(&resp.stderr).to_string(),
-                                    resp.exit_code,
-                                    resp.error.clone().unwrap_or_default(),
-                                ),
-                                Err(e) => (format!("{}", e), -1, String::new()),
-                            };
-                            return Err(Error::Guest(format!(
-                            "claude-code returned no stream-json events (exit_code={}). stderr: {}. error: {}",
-                            exit_code,
-                            if stderr_str.trim().is_empty() { "(empty)" } else { stderr_str.trim() },
-                            if error_str.trim().is_empty() { "(empty)" } else { error_str.trim() },
-                        )));
+        // Only an exec a hook, budget or idle policy may hold or kill needs
+        // to be addressable, unless the caller named it to signal it
+        // themselves.
+        let exec_id = crate::guest::protocol::current_exec_overrides()
+            .exec_id
+            .or_else(|| {
+                (opts.tool_hook.is_some() || opts.budget.is_some() || opts.idle.is_some())
+                    .then(|| uuid::Uuid::now_v7().to_string())
+            });
+        let (mut chunk_rx, response_rx) = match tracker
+            .scope(local.exec_agent_streaming_internal(
+                &command.program,
+                &args_refs,
+                &opts.env,
+                opts.timeout_secs,
+                exec_id.as_deref(),
+            ))
+            .await
+        {
+            Ok(streams) => streams,
+            Err(e) => {
+                tracker.finish_error(&e);
+                return Err(e);
+            }
+        };
+        let response_rx = tracker.finish_streaming(response_rx);
+        let running = exec_id.as_deref().map(|exec_id| (&**local, exec_id));
+
+        let mut state = AgentRunState::default();
+        let mut line_buf = String::new();
+
+        // Once the agent is found idle it is sent SIGTERM, and killed if it
+        // has not exited by `kill_at`.
+        let idle = opts.idle.zip(running);
+        let mut kill_at: Option<tokio::time::Instant> = None;
+        let mut killed = false;
+
+        // Process stdout chunks as they arrive
+        loop {
+            let chunk = match idle {
+                Some((policy, (local, exec_id))) if !killed => {
+                    let deadline =
+                        kill_at.unwrap_or_else(|| tokio::time::Instant::now() + policy.after);
+                    match tokio::time::timeout_at(deadline, chunk_rx.recv()).await {
+                        Ok(chunk) => chunk,
+                        Err(_) if kill_at.is_some() => {
+                            local
+                                .signal_exec_logged(
+                                    exec_id,
+                                    void_box_protocol::ExecSignal::Kill,
+                                    "idle: still running after SIGTERM",
+                                )
+                                .await;
+                            killed = true;
+                            continue;
                         }
-
-                        if state.is_error && state.error.as_deref().is_none_or(str::is_empty) {
-                            let fallback_error = match &response {
-                                Ok(resp) => {
-                                    let stderr_str = String::from_utf8_lossy(&resp.stderr);
-                                    fallback_error_message(
-                                        &stderr_str,
-                                        &state.result_text,
-                                        resp.error.as_deref(),
-                                        provider.binary_name(),
+                        Err(_) => {
+                            if agent_is_idle(runner, &state, &policy, local) {
+                                local
+                                    .signal_exec_logged(
+                                        exec_id,
+                                        void_box_protocol::ExecSignal::Terminate,
+                                        &format!("idle for {}s", policy.after.as_secs()),
                                     )
-                                }
-                                Err(err) => err.to_string(),
-                            };
-                            state.error = Some(fallback_error);
+                                    .await;
+                                kill_at = Some(tokio::time::Instant::now() + IDLE_STOP_GRACE);
+                            }
+                            continue;
                         }
-
-                        Ok(state)
                     }
-                    crate::llm::ObserverKind::Codex => {
-                        let mut result = AgentExecResult::default();
-                        let mut line_buf = String::new();
+                }
+                _ => chunk_rx.recv().await,
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            if chunk.stream != "stdout" {
+                continue;
+            }
 
-                        while let Some(chunk) = chunk_rx.recv().await {
-                            if chunk.stream != "stdout" {
-                                continue;
-                            }
+            let text = String::from_utf8_lossy(&chunk.data);
+            line_buf.push_str(&text);
 
-                            let text = String::from_utf8_lossy(&chunk.data);
-                            line_buf.push_str(&text);
+            // Process all complete lines in the buffer
+            while let Some(newline_pos) = line_buf.find('\n') {
+                let line: String = line_buf.drain(..=newline_pos).collect();
+                handle_agent_line(runner, &line, &mut state, &opts, &mut on_event, running).await?;
+            }
+        }
 
-                            while let Some(newline_pos) = line_buf.find('\n') {
-                                let line: String = line_buf.drain(..=newline_pos).collect();
-                                tracing::info!(target: AGENT_STDOUT_TARGET, "{}", line.trim_end());
-                                crate::observe::codex::parse_codex_line(&line, &mut result);
-                            }
-                        }
+        // Process any remaining partial line. The agent has exited;
+        // nothing is left to stop.
+        if !line_buf.trim().is_empty() {
+            handle_agent_line(runner, &line_buf, &mut state, &opts, &mut on_event, None).await?;
+        }
 
-                        if !line_buf.trim().is_empty() {
-                            tracing::info!(target: AGENT_STDOUT_TARGET, "{}", line_buf.trim_end());
-                            crate::observe::codex::parse_codex_line(&line_buf, &mut result);
-                        }
+        // Wait for the final response (for exit code / error info)
+        let response = response_rx
+            .await
+            .map_err(|_| Error::Guest("Failed to receive streaming response".into()))?;
+        let idle_finalized = kill_at.is_some();
+        let exit = match response {
+            // The agent was stopped on purpose; how it exited says nothing
+            // about the run.
+            Ok(resp) if idle_finalized => AgentExit {
+                exit_code: 0,
+                stderr: String::from_utf8_lossy(&resp.stderr).into_owned(),
+                error: None,
+            },
+            Ok(resp) => AgentExit {
+                exit_code: resp.exit_code,
+                stderr: String::from_utf8_lossy(&resp.stderr).into_owned(),
+                error: resp.error,
+            },
+            Err(e) if state.is_empty() => return Err(e),
+            Err(e) => AgentExit {
+                exit_code: -1,
+                stderr: String::new(),
+                error: Some(e.to_string()),
+            },
+        };
 
-                        let response = response_rx.await.map_err(|_| {
-                            Error::Guest("Failed to receive codex streaming response".into())
-                        })??;
-
-                        if response.exit_code != 0 {
-                            let stderr_str = String::from_utf8_lossy(&response.stderr);
-                            tracing::warn!(
-                                exit_code = response.exit_code,
-                                "{} failed; stderr={}",
-                                provider.binary_name(),
-                                if stderr_str.is_empty() {
-                                    "(empty)"
-                                } else {
-                                    stderr_str.trim()
-                                },
-                            );
-                            if !result.is_error {
-                                result.is_error = true;
-                            }
-                        }
+        // Log raw output on failure
+        if exit.exit_code != 0 {
+            tracing::warn!(
+                exit_code = exit.exit_code,
+                "{} failed; stderr={}",
+                command.program,
+                if exit.stderr.is_empty() {
+                    "(empty)"
+                } else {
+                    exit.stderr.trim()
+                },
+            );
+        }
 
-                        Ok(result)
-                    }
-                }
-            }
-            SandboxInner::Mock(mock) => {
-                // Mock: fall back to non-streaming, emit events from batch result
-                let output = mock
-                    .exec_with_stdin(provider.binary_name(), &args_refs, &[])
-                    .await?;
-                let result = crate::observe::claude::parse_stream_json(&output.stdout);
-
-                for tc in &result.tool_calls {
-                    on_event(AgentStreamEvent::ToolUse(tc.clone()));
-                }
+        let mut result = runner.summarize(state, &exit)?;
+        result.idle_finalized = idle_finalized;
+        Ok(result)
+    }
 
-                Ok(result)
-            }
+    /// Parse the buffered output of a finished agent run, applying hooks
+    /// and budgets as if it had streamed. Nothing is running to hold or
+    /// kill.
+    async fn replay_agent_output<F>(
+        &self,
+        runner: &dyn AgentRunner,
+        output: &ExecOutput,
+        opts: &crate::observe::claude::AgentExecOpts,
+        mut on_event: F,
+    ) -> Result<crate::observe::claude::AgentExecResult>
+    where
+        F: FnMut(crate::observe::claude::AgentStreamEvent),
+    {
+        let mut state = AgentRunState::default();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            handle_agent_line(runner, line, &mut state, opts, &mut on_event, None).await?;
         }
+        runner.summarize(
+            state,
+            &AgentExit {
+                exit_code: output.exit_code,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                error: None,
+            },
+        )
     }
 
-    /// Lightweight check that `claude-code` exists in the guest PATH.
+    /// Lightweight check that the runner's program exists in the guest PATH,
+    /// for runners with an [install hint](AgentRunner::install_hint).
     ///
     /// Previously this ran `claude-code --help` which booted the full Node.js
     /// runtime and wrote state to `~/.claude/`, corrupting guest config for
-    /// the subsequent real execution. Now we run `sh -c "command -v claude-code"`
+    /// the subsequent real execution. Now we run `sh -c "command -v <program>"`
     /// which is side-effect-free and sub-second. We use `sh` (which is in the
     /// guest command allowlist) with the `command -v` builtin.
-    async fn verify_claude_code_compat(
+    async fn verify_agent_installed(
         &self,
         local: &LocalSandbox,
-        _extra_env: &[(String, String)],
+        runner: &dyn AgentRunner,
+        program: &str,
     ) -> Result<()> {
+        let Some(hint) = runner.install_hint() else {
+            return Ok(());
+        };
+        let probe = format!("command -v {program}");
         let probe_output = local
-            .exec_with_stdin("sh", &["-c", "command -v claude-code"], &[])
+            .exec_with_stdin("sh", &["-c", &probe], &[])
             .await
             .map_err(|e| {
-                Error::Guest(format!(
-                    "failed to probe guest for `claude-code` binary: {e}"
-                ))
+                Error::Guest(format!("failed to probe guest for `{program}` binary: {e}"))
             })?;
 
         if probe_output.exit_code == 0 {
             let path = String::from_utf8_lossy(&probe_output.stdout);
-            tracing::debug!("claude-code found at: {}", path.trim());
+            tracing::debug!("{} found at: {}", program, path.trim());
             return Ok(());
         }
 
-        Err(Error::Guest(
-            "guest does not have `claude-code` in PATH. \
-Build a production guest image with claude-code and set VOID_BOX_INITRAMFS: \
-`scripts/build_claude_rootfs.sh` then `export VOID_BOX_INITRAMFS=target/void-box-rootfs.cpio.gz`."
-                .to_string(),
-        ))
+        Err(Error::Guest(format!(
+            "guest does not have `{program}` in PATH. {hint}"
+        )))
     }
 
     /// Start guest telemetry collection (CPU, memory, IO metrics).
@@ -750,6 +1564,27 @@ Build a production guest image with claude-code and set VOID_BOX_INITRAMFS: \
         &self.config
     }
 
+    /// Boot the sandbox now rather than on its first exec, and wait until
+    /// its guest-agent answers. Booting many at once is what
+    /// [`SandboxFleet`] is for.
+    pub async fn start(&self) -> Result<()> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.start().await,
+            SandboxInner::Mock(_) => Ok(()),
+        }
+    }
+
+    /// Connect to the guest serial console, booting the VM if needed; see
+    /// [`console`].
+    pub async fn attach_console(&self) -> Result<ConsoleStream> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.attach_console().await,
+            SandboxInner::Mock(_) => Err(Error::Config(
+                "serial console not supported on mock sandbox".into(),
+            )),
+        }
+    }
+
     /// Opens a PTY session on the guest, returning a handle for interactive I/O.
     pub async fn attach_pty(
         &self,
@@ -775,13 +1610,180 @@ Build a production guest image with claude-code and set VOID_BOX_INITRAMFS: \
         }
     }
 
-    /// Stop the sandbox and cleanup resources gracefully
-    pub async fn stop(&self) -> Result<()> {
+    /// Subscribe to this sandbox's lifecycle events.
+    ///
+    /// The receiver sees every [`SandboxEvent`] emitted after this call:
+    /// boot and agent readiness, each exec's start and finish, files
+    /// written, telemetry samples (once [`start_telemetry`](Self::start_telemetry)
+    /// is running), outbound guest connections (KVM only), and shutdown.
+    pub fn events(&self) -> tokio::sync::broadcast::Receiver<SandboxEvent> {
+        self.events.subscribe()
+    }
+
+    /// Execs (including agent runs) started and not yet finished. Also
+    /// exported as the `sandbox_execs_in_flight` gauge.
+    pub fn execs_in_flight(&self) -> usize {
+        self.events.execs_in_flight()
+    }
+
+    /// Unique identifier for this sandbox, used as the `sandbox` label on
+    /// exported Prometheus metrics.
+    pub fn id(&self) -> &str {
+        self.events.sandbox_id()
+    }
+
+    /// The observer built from this sandbox's [`ObserveConfig`].
+    ///
+    /// With [`ObserveConfig::capture_console`] enabled, guest console lines
+    /// land in its logs (source `console`) and the boot transcript in a
+    /// `sandbox.boot` span. `None` for mock sandboxes or when no observe
+    /// config was given.
+    pub fn observer(&self) -> Option<&Observer> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.observer(),
+            SandboxInner::Mock(_) => None,
+        }
+    }
+
+    /// Outbound connections the guest opened, or was refused by network
+    /// policy, each recorded once it closed. `None` for mock sandboxes.
+    ///
+    /// With an observer, records are also attached to a `sandbox.network`
+    /// span as `network-connection` events when the sandbox stops.
+    pub fn network_log(&self) -> Option<&Arc<NetworkLog>> {
         match &self.inner {
-            SandboxInner::Local(local) => local.stop().await,
-            SandboxInner::Mock(_) => Ok(()), // Mock sandbox has no cleanup needed
+            SandboxInner::Local(local) => Some(local.network_log()),
+            SandboxInner::Mock(_) => None,
         }
     }
+
+    /// The guest-agent's liveness as last seen by the health monitor;
+    /// [`HealthStatus::Stopped`] without a [`HealthCheck`] or for mock
+    /// sandboxes.
+    pub fn health_status(&self) -> HealthStatus {
+        match &self.inner {
+            SandboxInner::Local(local) => local.health_status(),
+            SandboxInner::Mock(_) => HealthStatus::Stopped,
+        }
+    }
+
+    /// Phase timings of the current boot, once its guest-agent has
+    /// answered. `None` before that and for mock sandboxes.
+    pub fn boot_timeline(&self) -> Option<BootTimeline> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.boot_timeline(),
+            SandboxInner::Mock(_) => None,
+        }
+    }
+
+    /// Control-channel frames recorded by the sandbox's [`ProtocolTap`],
+    /// oldest first, across VM restarts. Empty without a tap and for mock
+    /// sandboxes, which have no wire.
+    pub fn protocol_trace(&self) -> Vec<FrameRecord> {
+        self.config
+            .protocol_tap
+            .as_ref()
+            .map(ProtocolTap::frames)
+            .unwrap_or_default()
+    }
+
+    /// VM restarts under the sandbox's [`RestartPolicy`] so far.
+    pub fn restart_count(&self) -> u32 {
+        match &self.inner {
+            SandboxInner::Local(local) => local.restart_count(),
+            SandboxInner::Mock(_) => 0,
+        }
+    }
+
+    /// After an exec failed with `err`, restart the VM if the guest died
+    /// (crashed, exited, or failed its health checks) and the
+    /// [`RestartPolicy`] allows.
+    ///
+    /// `true` means a fresh VM is running: files and skills provisioned
+    /// into the old guest are gone and must be provisioned again before the
+    /// failed work is re-run. `false` means `err` was an ordinary failure,
+    /// or no restarts are left.
+    pub async fn recover(&self, err: &Error) -> Result<bool> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.recover(err).await,
+            SandboxInner::Mock(_) => Ok(false),
+        }
+    }
+
+    /// Restart the guest kernel without tearing the VM down.
+    ///
+    /// The guest stops its processes and syncs as on [`stop`](Self::stop),
+    /// then boots again in the same VM, keeping its devices and vsock CID,
+    /// and is provisioned as on first start. Guest processes and files
+    /// outside mounts and disks do not survive. Returns the guest's
+    /// summary of stopping its processes.
+    pub async fn reboot(&self) -> Result<ShutdownAck> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.reboot().await,
+            SandboxInner::Mock(_) => Err(Error::Config("mock sandboxes cannot reboot".into())),
+        }
+    }
+
+    /// Optional VM features this sandbox's backend has, such as snapshots,
+    /// suspend or virtiofs. Asking for one it lacks fails with
+    /// [`Error::Unsupported`]. Mock sandboxes have none.
+    pub async fn capabilities(&self) -> BackendCaps {
+        match &self.inner {
+            SandboxInner::Local(local) => local.capabilities().await,
+            SandboxInner::Mock(_) => BackendCaps::empty(),
+        }
+    }
+
+    /// Shrink the running guest to about `mb` MiB of usable memory through
+    /// its memory balloon, returning the rest to the host; the sandbox's
+    /// full `memory_mb` gives it all back. The guest gives memory up
+    /// asynchronously; see [`memory_actual_mb`](Self::memory_actual_mb).
+    ///
+    /// Fails with [`Error::Config`] on a fast-boot VM, which has no balloon.
+    pub async fn set_memory_target(&self, mb: usize) -> Result<()> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.set_memory_target(mb).await,
+            SandboxInner::Mock(_) => Err(Error::Config("mock sandboxes have no memory".into())),
+        }
+    }
+
+    /// Guest memory not held by the balloon, in MiB, as last reported by
+    /// the guest. `None` without a running VM, or on VZ, which does not
+    /// report it.
+    pub async fn memory_actual_mb(&self) -> Option<usize> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.memory_actual_mb().await,
+            SandboxInner::Mock(_) => None,
+        }
+    }
+
+    /// Pause the running VM, keeping its memory, processes and
+    /// connections, until [`resume`](Self::resume). Only the VZ backend can
+    /// pause a guest; KVM returns [`Error::Unsupported`].
+    pub async fn suspend(&self) -> Result<()> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.suspend().await,
+            SandboxInner::Mock(_) => Err(Error::Config("mock sandboxes cannot suspend".into())),
+        }
+    }
+
+    /// Let a VM paused by [`suspend`](Self::suspend) run again.
+    pub async fn resume(&self) -> Result<()> {
+        match &self.inner {
+            SandboxInner::Local(local) => local.resume().await,
+            SandboxInner::Mock(_) => Err(Error::Config("mock sandboxes cannot suspend".into())),
+        }
+    }
+
+    /// Stop the sandbox and cleanup resources gracefully
+    pub async fn stop(&self) -> Result<()> {
+        let graceful = match &self.inner {
+            SandboxInner::Local(local) => local.stop().await?,
+            SandboxInner::Mock(_) => None, // Mock sandbox has no cleanup needed
+        };
+        self.events.emit(SandboxEvent::Shutdown { graceful });
+        Ok(())
+    }
 }
 
 /// Types of sandboxes
@@ -820,12 +1822,50 @@ impl SandboxBuilder {
         self
     }
 
+    /// Pin vCPU threads and cap their host CPU time. Enforced by the VMM on
+    /// the host, independent of the guest's own limits; KVM only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::backend::ResourcePolicy;
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().vcpus(2).resource_policy(ResourcePolicy {
+    ///     cpu_quota: Some(0.5),
+    ///     cpu_affinity: vec![2, 3],
+    /// });
+    /// ```
+    pub fn resource_policy(mut self, policy: ResourcePolicy) -> Self {
+        self.config.resource_policy = policy;
+        self
+    }
+
     /// Enable or disable networking
     pub fn network(mut self, enable: bool) -> Self {
         self.config.network = enable;
         self
     }
 
+    /// Choose how the guest reaches the network and enable networking.
+    /// [`NetworkMode::Tap`] trades SLIRP's built-in egress controls for
+    /// near-native throughput; KVM only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::backend::NetworkMode;
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().network_mode(NetworkMode::Tap {
+    ///     name: "vbtap0".into(),
+    ///     bridge: Some("br0".into()),
+    /// });
+    /// ```
+    pub fn network_mode(mut self, mode: NetworkMode) -> Self {
+        self.config.network = true;
+        self.config.network_mode = mode;
+        self
+    }
+
     /// Overrides the SLIRP backend's per-second new-connection rate
     /// limit.  The production default (50/s) protects the host from
     /// guest-side connection floods; benches that intentionally
@@ -852,12 +1892,351 @@ impl SandboxBuilder {
     ///
     /// ```no_run
     /// use void_box::sandbox::Sandbox;
-    /// let _ = Sandbox::local()
-    ///     .network(true)
-    ///     .network_max_concurrent_connections(1024);
+    /// let _ = Sandbox::local()
+    ///     .network(true)
+    ///     .network_max_concurrent_connections(1024);
+    /// ```
+    pub fn network_max_concurrent_connections(mut self, count: usize) -> Self {
+        self.config.network_max_concurrent_connections = Some(count);
+        self
+    }
+
+    /// Caps how many execs may run at once (default
+    /// [`DEFAULT_MAX_IN_FLIGHT_EXECS`]). Once the cap is reached, further
+    /// execs fail immediately with [`Error::Busy`] rather than queueing
+    /// behind the ones already running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().max_in_flight_execs(4);
+    /// ```
+    pub fn max_in_flight_execs(mut self, count: usize) -> Self {
+        self.config.max_in_flight_execs = count;
+        self
+    }
+
+    /// Answer execs and file writes from `script` (mock sandboxes only);
+    /// see [`mock_script`].
+    pub fn mock_script(mut self, script: MockScript) -> Self {
+        self.config.mock_script = Some(script);
+        self
+    }
+
+    /// Record every frame on the control channel in `tap`, for debugging
+    /// hangs between host and guest; read them back with
+    /// [`Sandbox::protocol_trace`]. When the sandbox has
+    /// [`secret`](Self::secret)s or a git workspace token, only frame
+    /// metadata is recorded, since payloads would carry them.
+    pub fn protocol_tap(mut self, tap: ProtocolTap) -> Self {
+        self.config.protocol_tap = Some(tap);
+        self
+    }
+
+    /// Store a [`CrashReport`](crate::observe::crash::CrashReport) in `sink`
+    /// whenever the guest kernel panics or the VM exits under a running
+    /// exec. The failing exec returns the same report as
+    /// [`Error::GuestCrashed`] either way.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::observe::crash::DirCrashSink;
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().crash_sink(DirCrashSink::new("/var/log/void-box/crashes"));
+    /// ```
+    pub fn crash_sink(mut self, sink: impl CrashSink + 'static) -> Self {
+        self.config.crash_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Ping the guest-agent on `check`'s schedule; `check.max_missed`
+    /// unanswered heartbeats in a row fail the execs running on it with
+    /// [`Error::GuestCrashed`]. See [`health`] for what happens next.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::{HealthCheck, Sandbox};
+    /// let _ = Sandbox::local().health_check(HealthCheck::default());
+    /// ```
+    pub fn health_check(mut self, check: HealthCheck) -> Self {
+        self.config.health_check = Some(check);
+        self
+    }
+
+    /// Replace a guest that crashed or failed its health checks with a
+    /// fresh VM, up to `max_restarts` times. Workflow steps and agent runs
+    /// interrupted by the crash are re-run on the new VM.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::{HealthCheck, RestartPolicy, Sandbox};
+    /// let _ = Sandbox::local()
+    ///     .health_check(HealthCheck::default())
+    ///     .restart_policy(RestartPolicy::OnFailure { max_restarts: 3 });
+    /// ```
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.config.restart_policy = policy;
+        self
+    }
+
+    /// Give the guest a watchdog device that its agent pets from its own
+    /// thread. A guest that goes `timeout` without petting it, such as one
+    /// whose kernel has hung, gets a crash report and is handled under the
+    /// [`restart_policy`](Self::restart_policy), without waiting for a
+    /// heartbeat RPC to time out. Whole seconds, at least one. See
+    /// [`health`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use void_box::sandbox::{RestartPolicy, Sandbox};
+    /// let _ = Sandbox::local()
+    ///     .watchdog(Duration::from_secs(30))
+    ///     .restart_policy(RestartPolicy::OnFailure { max_restarts: 3 });
+    /// ```
+    pub fn watchdog(mut self, timeout: std::time::Duration) -> Self {
+        self.config.watchdog = Some(timeout);
+        self
+    }
+
+    /// Append arguments to the guest kernel cmdline, e.g.
+    /// `"transparent_hugepage=never mitigations=off"`. Whitespace separates
+    /// arguments; quoting is not supported. Calls accumulate.
+    ///
+    /// `voidbox.*`, `init` and `rdinit` are reserved, and an argument that
+    /// changes a value the backend sets itself (such as `panic=` or the
+    /// console) is rejected when the VM starts, on KVM and VZ alike.
+    pub fn extra_cmdline(mut self, args: impl AsRef<str>) -> Self {
+        self.config
+            .extra_cmdline
+            .extend(crate::backend::cmdline::split(args.as_ref()));
+        self
+    }
+
+    /// Let [`write_file`](Sandbox::write_file), [`mkdir_p`](Sandbox::mkdir_p),
+    /// [`chmod`](Sandbox::chmod) and the other file calls write under
+    /// `dir` too, e.g. `/opt` for tool installs or `/etc/systemd/system`
+    /// for unit files. By default only `/workspace`, `/home` and
+    /// `/etc/voidbox` are writable. The guest-agent reads the list at boot,
+    /// so no rebuild is needed. `dir` must be an absolute, normalized path
+    /// other than `/`, outside `/proc`, `/sys` and `/dev`. Calls accumulate.
+    pub fn allow_write_root(mut self, dir: impl Into<String>) -> Self {
+        self.config.write_roots.push(dir.into());
+        self
+    }
+
+    /// Share Apple's Rosetta translator with the guest so x86_64 binaries,
+    /// including those of `linux/amd64` OCI images, run in the arm64 VM.
+    ///
+    /// Needs the VZ backend on Apple silicon with Rosetta installed
+    /// (`softwareupdate --install-rosetta`); starting the VM fails
+    /// otherwise. [`rosetta_availability`](crate::backend::rosetta_availability)
+    /// checks ahead of time.
+    pub fn rosetta(mut self, enabled: bool) -> Self {
+        self.config.rosetta = enabled;
+        self
+    }
+
+    /// Check out `rev` of the repository at `url` into `/workspace` before
+    /// the first exec runs: shallow, cloned on the host and uploaded. Use
+    /// [`git_workspace_with`](Self::git_workspace_with) for submodules,
+    /// credentials or an in-guest clone. Mock sandboxes ignore it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().git_workspace("https://github.com/the-void-ia/void-box", "main");
+    /// ```
+    pub fn git_workspace(self, url: impl Into<String>, rev: &str) -> Self {
+        self.git_workspace_with(GitWorkspace::new(url, Some(rev)))
+    }
+
+    /// Seed `/workspace` (or [`GitWorkspace::path`]) from `workspace`. See
+    /// [`git_workspace`](Self::git_workspace) for the options.
+    pub fn git_workspace_with(mut self, workspace: GitWorkspace) -> Self {
+        self.config.git_workspace = Some(workspace);
+        self
+    }
+
+    /// Keep the guest clock in step with the host's: sync it once each
+    /// boot's guest-agent is ready, then every `interval`. Mock sandboxes
+    /// ignore it. See [`clock`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().clock_sync(Duration::from_secs(60));
+    /// ```
+    pub fn clock_sync(mut self, interval: std::time::Duration) -> Self {
+        self.config.clock_sync = Some(interval);
+        self
+    }
+
+    /// Make runs reproducible from `seed`: fix the guest clock, seed the
+    /// randomness execs can see and run on a single vCPU. Network access
+    /// must come from [`replay_http`](Self::replay_http). See
+    /// [`deterministic`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local()
+    ///     .deterministic(42)
+    ///     .replay_http("fixtures/agent-run.jsonl");
+    /// ```
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.config.deterministic = Some(seed);
+        self.config.vcpus = 1;
+        self
+    }
+
+    /// Create the guest user `name` on every boot; run commands as it with
+    /// [`ExecUser::Named`]. See [`users`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().user("reviewer");
+    /// ```
+    pub fn user(mut self, name: impl Into<String>) -> Self {
+        self.config.users.push(name.into());
+        self
+    }
+
+    /// Run in read-only (audit) mode: once the sandbox has booted and its
+    /// secrets, git workspace and exec policy are in place, file writes
+    /// fail with [`Error::ReadOnly`], `/workspace` is remounted read-only,
+    /// and shares and volumes are attached read-only. Scratch disks and the
+    /// root filesystem stay writable but are discarded with the VM. See
+    /// the [module docs](crate::sandbox::read_only) and
+    /// [`Sandbox::read_only_report`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().read_only(true);
+    /// ```
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.config.read_only = read_only;
+        self
+    }
+
+    /// Restrict which commands execs may run, replacing the guest's
+    /// provisioned command allowlist. Denials fail with
+    /// [`Error::ExecDenied`] naming the rule that matched. Change it later
+    /// with [`Sandbox::set_exec_policy`], or per exec with
+    /// [`Sandbox::exec_with_policy`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::{ExecPolicy, ExecRule, Sandbox};
+    /// // git, but not git push
+    /// let _ = Sandbox::local().exec_policy(
+    ///     ExecPolicy::new()
+    ///         .allow("git")
+    ///         .allow("sh")
+    ///         .rule(ExecRule::deny("git").any_arg("push")),
+    /// );
+    /// ```
+    pub fn exec_policy(mut self, policy: ExecPolicy) -> Self {
+        self.config.exec_policy = Some(policy);
+        self
+    }
+
+    /// Restrict guest egress with a [`NetworkPolicy`]: allowlists, DNS-name
+    /// and per-port rules, or a log-only audit mode. Enforced by the KVM
+    /// SLIRP stack; VZ ignores it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::backend::{NetworkPolicy, NetworkRule};
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().network(true).network_policy(
+    ///     NetworkPolicy::deny_all()
+    ///         .allow(NetworkRule::domain("api.anthropic.com").ports([443]))
+    ///         .allow(NetworkRule::domain("*.github.com")),
+    /// );
+    /// ```
+    pub fn network_policy(mut self, policy: NetworkPolicy) -> Self {
+        self.config.network_policy = Some(policy);
+        self
+    }
+
+    /// Resolve guest DNS through these IPv4 resolvers instead of the host's
+    /// (KVM, where the SLIRP DNS server forwards to them) or `8.8.8.8` (VZ,
+    /// where they go into the guest's `resolv.conf`). Invalid addresses fail
+    /// [`build`](Self::build).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().network(true).dns(["1.1.1.1", "9.9.9.9"]);
+    /// ```
+    pub fn dns(mut self, servers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.config.dns_servers = servers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Make `name` resolve to `ip` inside the guest. The SLIRP DNS server
+    /// answers it without asking an upstream resolver; KVM only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local()
+    ///     .network(true)
+    ///     .host_alias("api.internal", "10.0.2.100");
+    /// ```
+    pub fn host_alias(mut self, name: impl Into<String>, ip: impl Into<String>) -> Self {
+        self.config.host_aliases.push((name.into(), ip.into()));
+        self
+    }
+
+    /// Route the guest's HTTP and HTTPS traffic through a recording proxy
+    /// that writes every request/response pair to `path` (JSON lines).
+    /// Enables networking. See [`crate::proxy::recording`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().record_http("fixtures/agent-run.jsonl");
+    /// ```
+    pub fn record_http(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.network = true;
+        self.config.http_recording = Some(HttpRecording::Record(path.into()));
+        self
+    }
+
+    /// Serve the guest's HTTP and HTTPS requests from a recording made by
+    /// [`record_http`](Self::record_http), without real network access:
+    /// unless a [`network_policy`](Self::network_policy) is set, every
+    /// destination other than the proxy is refused.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().replay_http("fixtures/agent-run.jsonl");
     /// ```
-    pub fn network_max_concurrent_connections(mut self, count: usize) -> Self {
-        self.config.network_max_concurrent_connections = Some(count);
+    pub fn replay_http(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.network = true;
+        self.config.http_recording = Some(HttpRecording::Replay(path.into()));
         self
     }
 
@@ -873,6 +2252,19 @@ impl SandboxBuilder {
         self
     }
 
+    /// Copy the host directory `dir` into the guest's `/` at boot, e.g. a
+    /// tree with `usr/local/bin/jq` and `etc/gitconfig`, without rebuilding
+    /// the rootfs image. The directory is packed as a second cpio archive
+    /// and appended to the [`initramfs`](Self::initramfs), so its files
+    /// replace the base image's; with several overlays, later ones win.
+    ///
+    /// The directory is read with the first boot; restarts reuse that
+    /// image. Files keep their permissions and are owned by root.
+    pub fn initramfs_overlay(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.initramfs_overlays.push(dir.into());
+        self
+    }
+
     /// Set the rootfs path
     pub fn rootfs(mut self, path: impl Into<PathBuf>) -> Self {
         self.config.rootfs = Some(path.into());
@@ -891,6 +2283,21 @@ impl SandboxBuilder {
         self
     }
 
+    /// Run a root shell on the guest serial console, for debugging boots
+    /// with [`Sandbox::attach_console`]. The shell is respawned when it
+    /// exits. KVM only.
+    pub fn console_shell(mut self, enable: bool) -> Self {
+        self.config.console_shell = enable;
+        self
+    }
+
+    /// Trade guest features for startup latency; see [`BootProfile`].
+    /// Every boot is timed into `boot.*` spans on the observer either way.
+    pub fn boot_profile(mut self, profile: BootProfile) -> Self {
+        self.config.boot_profile = profile;
+        self
+    }
+
     /// Set observability configuration
     pub fn observe(mut self, config: ObserveConfig) -> Self {
         self.config.observe = Some(config);
@@ -927,6 +2334,57 @@ impl SandboxBuilder {
         self
     }
 
+    /// Add a writable scratch disk of `size_gb` GiB, mounted at `/scratch`.
+    ///
+    /// Unlike the tmpfs overlay, a scratch disk is not backed by guest RAM,
+    /// so builds can write more than `memory_mb`. The host image is sparse,
+    /// formatted with `mkfs.ext4` on first boot, and removed with the
+    /// sandbox. KVM only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().memory_mb(512).scratch_disk(20);
+    /// ```
+    pub fn scratch_disk(mut self, size_gb: u64) -> Self {
+        self.config.disks.push(DiskSpec::Scratch { size_gb });
+        self
+    }
+
+    /// Attach an existing host disk image as a raw block device (`/dev/vdX`,
+    /// in attach order after the OCI rootfs disk). KVM only.
+    pub fn attach_disk(mut self, path: impl Into<PathBuf>, read_only: bool) -> Self {
+        self.config.disks.push(DiskSpec::Attach {
+            path: path.into(),
+            read_only,
+        });
+        self
+    }
+
+    /// Mount the persistent volume `name` at `guest_path`, creating it on
+    /// first use.
+    ///
+    /// The volume lives under `$VOIDBOX_HOME/volumes` (default
+    /// `~/.void-box/volumes`) and keeps its contents after the sandbox is
+    /// gone. It is an ext4 image attached via virtio-blk on KVM, or a
+    /// directory shared via virtiofs on macOS. See
+    /// [`Volume`](crate::volume::Volume) to list, delete or clone volumes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().volume("my-project", "/workspace");
+    /// ```
+    pub fn volume(mut self, name: impl Into<String>, guest_path: impl Into<String>) -> Self {
+        self.config.volumes.push(VolumeMount {
+            name: name.into(),
+            guest_path: guest_path.into(),
+        });
+        self
+    }
+
     /// Set the snapshot directory to restore from (skips cold boot).
     pub fn snapshot(mut self, path: impl Into<PathBuf>) -> Self {
         self.config.snapshot = Some(path.into());
@@ -949,6 +2407,67 @@ impl SandboxBuilder {
         self
     }
 
+    /// Run execs in `dir` unless they pick their own with
+    /// [`Sandbox::exec_in`]. Agents default to `/workspace` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().working_dir("/workspace/project");
+    /// ```
+    pub fn working_dir(mut self, dir: impl Into<String>) -> Self {
+        self.config.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Put `dir` in front of every exec's `PATH`, so tools installed there
+    /// resolve by name. Directories added earlier are searched first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// let _ = Sandbox::local().path_prepend("/opt/tools/bin");
+    /// ```
+    pub fn path_prepend(mut self, dir: impl Into<String>) -> Self {
+        self.config.path_prepend.push(dir.into());
+        self
+    }
+
+    /// Inject the secret `name`, read from `source` when the sandbox is
+    /// built, into every exec's environment. Unlike [`env`](Self::env), the
+    /// value is redacted from events, traces, console capture and crash
+    /// reports. Use [`secret_with`](Self::secret_with) to deliver it as a
+    /// file instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// use void_box::secret::SecretSource;
+    /// let _ = Sandbox::local().secret("ANTHROPIC_API_KEY", SecretSource::env("ANTHROPIC_API_KEY"));
+    /// ```
+    pub fn secret(self, name: impl Into<String>, source: SecretSource) -> Self {
+        self.secret_with(Secret::new(name, source))
+    }
+
+    /// Inject `secret`; see [`crate::secret`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// use void_box::secret::{Secret, SecretSource};
+    /// let _ = Sandbox::local().secret_with(
+    ///     Secret::new("GITHUB_TOKEN", SecretSource::keychain("github")).as_file(),
+    /// );
+    /// ```
+    pub fn secret_with(mut self, secret: Secret) -> Self {
+        self.config.secrets.push(secret);
+        self
+    }
+
     /// Use pre-built artifacts from GitHub releases.
     ///
     /// # Deprecated
@@ -1001,20 +2520,90 @@ impl SandboxBuilder {
 
     /// Build the sandbox
     pub fn build(self) -> Result<Arc<Sandbox>> {
-        let inner = match self.sandbox_type {
+        self.config.resource_policy.validate()?;
+        if let Some(check) = &self.config.health_check {
+            check.validate()?;
+        }
+        if let Some(dir) = &self.config.working_dir {
+            validate_guest_dir(dir)?;
+        }
+        for dir in &self.config.path_prepend {
+            validate_guest_dir(dir)?;
+            if dir.contains(':') {
+                return Err(Error::Config(format!(
+                    "PATH entry '{dir}' must not contain ':'"
+                )));
+            }
+        }
+        if self
+            .config
+            .watchdog
+            .is_some_and(|t| t < std::time::Duration::from_secs(1))
+        {
+            return Err(Error::Config(
+                "watchdog timeout must be at least 1 second".into(),
+            ));
+        }
+        crate::backend::cmdline::validate(&[], &self.config.extra_cmdline)?;
+        for dir in &self.config.write_roots {
+            void_box_protocol::validate_write_root(dir).map_err(Error::Config)?;
+        }
+        if self.config.clock_sync.is_some_and(|i| i.is_zero()) {
+            return Err(Error::Config("clock sync interval must be non-zero".into()));
+        }
+        if !self.config.initramfs_overlays.is_empty() && self.config.initramfs.is_none() {
+            return Err(Error::Config(
+                "initramfs_overlay needs an initramfs to append to".into(),
+            ));
+        }
+        for dir in &self.config.initramfs_overlays {
+            if !dir.is_dir() {
+                return Err(Error::Config(format!(
+                    "initramfs overlay {} is not a directory",
+                    dir.display()
+                )));
+            }
+        }
+        deterministic::validate(&self.config)?;
+        if self.config.max_in_flight_execs == 0 {
+            return Err(Error::Config(
+                "max_in_flight_execs must be at least 1".into(),
+            ));
+        }
+        if self.config.mock_script.is_some() && !matches!(self.sandbox_type, SandboxType::Mock) {
+            return Err(Error::Config("mock_script needs a mock sandbox".into()));
+        }
+        let (inner, events, redactor) = match self.sandbox_type {
             SandboxType::Local => {
-                let local = LocalSandbox::new(self.config.clone())?;
-                SandboxInner::Local(Box::new(local))
+                let local = Arc::new(LocalSandbox::new(self.config.clone())?);
+                local.attach();
+                let events = local.events().clone();
+                let redactor = local.redactor().clone();
+                (SandboxInner::Local(local), events, redactor)
             }
             SandboxType::Mock => {
+                let secrets = self
+                    .config
+                    .secrets
+                    .iter()
+                    .map(Secret::resolve)
+                    .collect::<Result<Vec<_>>>()?;
                 let mock = MockSandbox::new(self.config.clone());
-                SandboxInner::Mock(Box::new(mock))
+                (
+                    SandboxInner::Mock(Box::new(mock)),
+                    SandboxEvents::with_observe(self.config.observe.as_ref()),
+                    Arc::new(Redactor::new(&secrets)),
+                )
             }
         };
 
         Ok(Arc::new(Sandbox {
+            exec_policy: std::sync::RwLock::new(self.config.exec_policy.clone()),
+            rejected_writes: std::sync::Mutex::new(Vec::new()),
             config: self.config,
             inner,
+            events,
+            redactor,
         }))
     }
 }
@@ -1025,23 +2614,84 @@ pub struct MockSandbox {
     config: SandboxConfig,
     responses: std::sync::Mutex<Vec<ExecOutput>>,
     files: std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>,
+    users: std::sync::Mutex<Vec<String>>,
+    script: Option<MockScript>,
 }
 
 impl MockSandbox {
     /// Create a new mock sandbox
     pub fn new(config: SandboxConfig) -> Self {
         Self {
+            users: std::sync::Mutex::new(config.users.clone()),
+            script: config.mock_script.clone(),
             config,
             responses: std::sync::Mutex::new(Vec::new()),
             files: std::sync::Mutex::new(std::collections::HashMap::new()),
         }
     }
 
+    /// Mock user creation, numbering uids the way the guest-agent does.
+    fn create_user(&self, name: &str) -> GuestUser {
+        let mut users = self.users.lock().unwrap();
+        let index = match users.iter().position(|u| u == name) {
+            Some(index) => index,
+            None => {
+                users.push(name.to_string());
+                users.len() - 1
+            }
+        };
+        let uid = users::SANDBOX_UID + 1 + index as u32;
+        GuestUser {
+            name: name.to_string(),
+            uid,
+            gid: uid,
+            home: format!("/home/{name}"),
+        }
+    }
+
+    /// Start a scripted scenario; install it with
+    /// [`SandboxBuilder::mock_script`].
+    pub fn script() -> MockScript {
+        MockScript::new()
+    }
+
     /// Queue a response for the next exec call
     pub fn queue_response(&self, output: ExecOutput) {
         self.responses.lock().unwrap().push(output);
     }
 
+    /// Store a written file, unless the script refuses it.
+    fn write_file(&self, path: &str, content: &[u8]) -> Result<()> {
+        let path = normalize_mock_path(path);
+        if let Some(script) = &self.script {
+            script.write_file(MockWrite {
+                path: path.clone(),
+                content: content.to_vec(),
+            })?;
+        }
+        self.files.lock().unwrap().insert(path, content.to_vec());
+        Ok(())
+    }
+
+    /// Hash stored files; the mock keeps no mtimes.
+    fn hash_files(&self, paths: &[String]) -> Vec<FileHash> {
+        let files = self.files.lock().unwrap();
+        paths
+            .iter()
+            .map(|path| {
+                let content = files.get(&normalize_mock_path(path));
+                FileHash {
+                    path: path.clone(),
+                    exists: content.is_some(),
+                    size: content.map_or(0, |content| content.len() as u64),
+                    mtime_ms: None,
+                    sha256: content.map(|content| format!("{:x}", Sha256::digest(content))),
+                    error: None,
+                }
+            })
+            .collect()
+    }
+
     /// Execute a command (returns queued response or default)
     pub async fn exec_with_stdin(
         &self,
@@ -1049,6 +2699,16 @@ impl MockSandbox {
         args: &[&str],
         stdin: &[u8],
     ) -> Result<ExecOutput> {
+        if let Some(script) = &self.script {
+            let exec = MockExec {
+                program: program.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                stdin: stdin.to_vec(),
+            };
+            if let Some(response) = script.exec(exec) {
+                return response;
+            }
+        }
         let mut responses = self.responses.lock().unwrap();
         if let Some(response) = responses.pop() {
             return Ok(response);
@@ -1145,7 +2805,13 @@ impl MockSandbox {
 
                     if output_format == "stream-json" {
                         // Minimal JSONL: system event + result event (no fake tool calls)
-                        let prompt_preview = args.get(1).copied().unwrap_or("").replace('"', "'");
+                        // Keep the result line valid JSON for multi-line prompts.
+                        let prompt_preview = args
+                            .get(1)
+                            .copied()
+                            .unwrap_or("")
+                            .replace('"', "'")
+                            .replace(['\\', '\n', '\r', '\t'], " ");
                         let preview = &prompt_preview[..prompt_preview.len().min(120)];
                         let jsonl = format!(
                             "{}\n{}\n",
@@ -1287,6 +2953,28 @@ fn base64_encode(data: &[u8]) -> String {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_tool_hook_requires_streamed_tool_calls() {
+        use crate::observe::claude::AgentExecOpts;
+        use crate::tool_hook::{ToolDecision, ToolHook};
+
+        let sandbox = Sandbox::mock().build().unwrap();
+        let opts = AgentExecOpts {
+            tool_hook: Some(ToolHook::new(|_| ToolDecision::Allow)),
+            ..Default::default()
+        };
+        let result = sandbox
+            .exec_agent(&crate::llm::LlmProvider::Codex, "hi", opts.clone())
+            .await;
+        assert!(matches!(result, Err(Error::Config(_))));
+
+        let result = sandbox
+            .exec_agent(&crate::llm::LlmProvider::Claude, "hi", opts)
+            .await
+            .unwrap();
+        assert_eq!(result.session_id, "mock_sess");
+    }
+
     #[test]
     fn test_sandbox_builder() {
         let sandbox = Sandbox::mock()
@@ -1301,6 +2989,108 @@ mod tests {
         assert!(sandbox.config().network);
     }
 
+    #[test]
+    fn test_sandbox_builder_rejects_invalid_resource_policy() {
+        let result = Sandbox::mock()
+            .resource_policy(ResourcePolicy {
+                cpu_quota: Some(0.0),
+                ..Default::default()
+            })
+            .build();
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_sandbox_builder_rejects_invalid_health_check() {
+        let result = Sandbox::mock()
+            .health_check(HealthCheck {
+                max_missed: 0,
+                ..Default::default()
+            })
+            .build();
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_sandbox_builder_rejects_reserved_cmdline_keys() {
+        let result = Sandbox::mock()
+            .extra_cmdline("quiet voidbox.network=1")
+            .build();
+        assert!(matches!(result, Err(Error::Config(_))));
+        assert!(Sandbox::mock()
+            .extra_cmdline("transparent_hugepage=never")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_sandbox_builder_validates_write_roots() {
+        assert!(Sandbox::mock()
+            .allow_write_root("/opt")
+            .allow_write_root("/etc/systemd/system")
+            .build()
+            .is_ok());
+        for dir in ["/", "opt", "/proc/sys", "/opt/../etc"] {
+            let result = Sandbox::mock().allow_write_root(dir).build();
+            assert!(matches!(result, Err(Error::Config(_))), "{dir}");
+        }
+    }
+
+    #[test]
+    fn test_sandbox_builder_rejects_sub_second_watchdog() {
+        let result = Sandbox::mock()
+            .watchdog(std::time::Duration::from_millis(500))
+            .build();
+        assert!(matches!(result, Err(Error::Config(_))));
+        assert!(Sandbox::mock()
+            .watchdog(std::time::Duration::from_secs(10))
+            .build()
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_recover_ignores_ordinary_failures() {
+        let sandbox = Sandbox::local()
+            .restart_policy(RestartPolicy::OnFailure { max_restarts: 1 })
+            .build()
+            .unwrap();
+        let recovered = sandbox
+            .recover(&Error::Guest("exit status 1".into()))
+            .await
+            .unwrap();
+        assert!(!recovered);
+        assert_eq!(sandbox.restart_count(), 0);
+        assert_eq!(sandbox.health_status(), HealthStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_elasticity_needs_a_running_vm() {
+        let sandbox = Sandbox::local().build().unwrap();
+        assert!(matches!(
+            sandbox.set_memory_target(128).await,
+            Err(Error::VmNotRunning)
+        ));
+        assert!(matches!(sandbox.suspend().await, Err(Error::VmNotRunning)));
+        assert!(matches!(sandbox.resume().await, Err(Error::VmNotRunning)));
+        assert_eq!(sandbox.memory_actual_mb().await, None);
+        assert!(Sandbox::mock()
+            .build()
+            .unwrap()
+            .capabilities()
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exec_past_in_flight_limit_is_busy() {
+        let sandbox = Sandbox::mock().max_in_flight_execs(1).build().unwrap();
+        let _running = sandbox.track_exec("sleep", &["10"]).unwrap();
+        assert_eq!(sandbox.execs_in_flight(), 1);
+
+        let result = sandbox.exec("echo", &["hi"]).await;
+        assert!(matches!(result, Err(Error::Busy(_))));
+    }
+
     #[tokio::test]
     async fn test_mock_sandbox_exec() {
         let sandbox = Sandbox::mock().build().unwrap();
@@ -1333,6 +3123,323 @@ mod tests {
         assert!(agg.latest_batch().is_none());
     }
 
+    #[tokio::test]
+    async fn test_secret_values_are_redacted_from_exec_events() {
+        let sandbox = Sandbox::mock()
+            .secret("API_KEY", SecretSource::value("sk-live-123"))
+            .build()
+            .unwrap();
+        let mut events = sandbox.events();
+
+        sandbox
+            .exec("curl", &["-H", "Authorization: Bearer sk-live-123"])
+            .await
+            .unwrap();
+        match events.recv().await.unwrap() {
+            SandboxEvent::ExecStarted { args, .. } => {
+                assert_eq!(args, ["-H", "Authorization: Bearer [REDACTED:API_KEY]"]);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(!format!("{:?}", sandbox.config()).contains("sk-live-123"));
+
+        let missing = Sandbox::mock()
+            .secret("API_KEY", SecretSource::env("VOID_BOX_TEST_SECRET_UNSET"))
+            .build();
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exec_policy_denials_name_the_rule() {
+        let sandbox = Sandbox::mock()
+            .exec_policy(
+                ExecPolicy::new()
+                    .allow("git")
+                    .allow("echo")
+                    .rule(ExecRule::deny("git").args(["push"])),
+            )
+            .build()
+            .unwrap();
+        let mut events = sandbox.events();
+
+        sandbox.exec("git", &["status"]).await.unwrap();
+        match sandbox.exec("git", &["push", "origin"]).await {
+            Err(Error::ExecDenied { command, rule }) => {
+                assert_eq!(command, "git push origin");
+                assert_eq!(rule, "denied by rule #2 (deny git push)");
+            }
+            other => panic!("expected ExecDenied, got {other:?}"),
+        }
+        assert!(matches!(
+            sandbox.exec("curl", &[]).await,
+            Err(Error::ExecDenied { .. })
+        ));
+
+        // A per-exec override replaces the sandbox policy for that exec.
+        sandbox
+            .exec_with_policy("curl", &["-s"], ExecPolicy::new().allow("curl"))
+            .await
+            .unwrap();
+        assert!(sandbox
+            .exec_with_policy("echo", &["hi"], ExecPolicy::new().deny("echo"))
+            .await
+            .is_err());
+
+        sandbox
+            .set_exec_policy(ExecPolicy::new().deny("git"))
+            .await
+            .unwrap();
+        assert!(sandbox.exec("git", &["status"]).await.is_err());
+        sandbox.exec("curl", &[]).await.unwrap();
+
+        // Denied execs never start.
+        let mut started = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let SandboxEvent::ExecStarted { program, .. } = event {
+                started.push(program);
+            }
+        }
+        assert_eq!(started, ["git", "curl", "curl"]);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_sandbox_refuses_and_reports_writes() {
+        let sandbox = Sandbox::mock().read_only(true).build().unwrap();
+
+        match sandbox.write_file("/workspace/out.txt", b"x").await {
+            Err(Error::ReadOnly { op, path }) => {
+                assert_eq!(op, "write_file");
+                assert_eq!(path, "/workspace/out.txt");
+            }
+            other => panic!("expected ReadOnly, got {other:?}"),
+        }
+        assert!(sandbox.mkdir_p("/workspace/dir").await.is_err());
+        assert!(sandbox
+            .symlink("/usr/bin/tool", "/workspace/tool")
+            .await
+            .is_err());
+        assert!(sandbox.chmod("/workspace/run.sh", 0o755).await.is_err());
+        sandbox.exec("echo", &["reading is fine"]).await.unwrap();
+
+        let report = sandbox.read_only_report().await.unwrap();
+        assert_eq!(
+            report.rejected,
+            [
+                RejectedWrite {
+                    op: WriteOp::WriteFile,
+                    path: "/workspace/out.txt".into()
+                },
+                RejectedWrite {
+                    op: WriteOp::MkdirP,
+                    path: "/workspace/dir".into()
+                },
+                RejectedWrite {
+                    op: WriteOp::Symlink,
+                    path: "/workspace/tool".into()
+                },
+                RejectedWrite {
+                    op: WriteOp::Chmod,
+                    path: "/workspace/run.sh".into()
+                },
+            ]
+        );
+        assert!(!report.is_clean());
+
+        let writable = Sandbox::mock().build().unwrap();
+        writable.write_file("/workspace/a", b"a").await.unwrap();
+        assert!(writable.read_only_report().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hash_files_reports_each_path() {
+        let sandbox = Sandbox::mock().build().unwrap();
+        sandbox
+            .write_file("/workspace/a.txt", b"hello")
+            .await
+            .unwrap();
+
+        let hashes = sandbox
+            .hash_files(&["/workspace/a.txt", "/workspace/missing"])
+            .await
+            .unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes[0].exists);
+        assert_eq!(hashes[0].size, 5);
+        assert_eq!(
+            hashes[0].sha256.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(hashes[1].path, "/workspace/missing");
+        assert!(!hashes[1].exists);
+        assert!(hashes[1].sha256.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mock_links_and_modes() {
+        let script = MockScript::new().on_exec("readlink /workspace/bin", |_| {
+            Ok(ExecOutput::new(b"/opt/tools/bin\n".to_vec(), Vec::new(), 0))
+        });
+        let sandbox = Sandbox::mock().mock_script(script).build().unwrap();
+
+        sandbox
+            .symlink("/opt/tools/bin", "/workspace/bin")
+            .await
+            .unwrap();
+        sandbox.chmod("/workspace/run.sh", 0o755).await.unwrap();
+        sandbox
+            .chown("/workspace/run.sh", 1000, 1000)
+            .await
+            .unwrap();
+        assert_eq!(
+            sandbox.read_link("/workspace/bin").await.unwrap(),
+            "/opt/tools/bin"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_workspace_writes_the_bundle_back() {
+        let file = |path: &str, data: &[u8], link_target: Option<&str>| ArtifactFile {
+            path: path.to_string(),
+            mode: 0o100644,
+            data: data.to_vec(),
+            sha256: String::new(),
+            link_target: link_target.map(str::to_string),
+        };
+        let bundle = ArtifactBundle {
+            files: vec![
+                file("src/main.rs", b"fn main() {}\n", None),
+                file("latest", b"", Some("src/main.rs")),
+            ],
+        };
+        let sandbox = Sandbox::mock().build().unwrap();
+
+        sandbox.restore_workspace(&bundle).await.unwrap();
+        assert_eq!(
+            sandbox.read_file("/workspace/src/main.rs").await.unwrap(),
+            b"fn main() {}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_numbers_uids_and_agents_refuse_root() {
+        let sandbox = Sandbox::mock().user("builder").build().unwrap();
+
+        let reviewer = sandbox.create_user("reviewer").await.unwrap();
+        assert_eq!((reviewer.uid, reviewer.gid), (1002, 1002));
+        assert_eq!(reviewer.home, "/home/reviewer");
+        assert_eq!(reviewer.exec_user(), ExecUser::named("reviewer"));
+        assert_eq!(sandbox.create_user("builder").await.unwrap().uid, 1001);
+        assert!(sandbox.create_user("root").await.is_err());
+
+        let provider = crate::llm::LlmProvider::default();
+        let opts = crate::observe::claude::AgentExecOpts::default();
+        let as_root = crate::guest::protocol::scope_exec_user(
+            ExecUser::Root,
+            sandbox.exec_agent(&provider, "hi", opts.clone()),
+        )
+        .await;
+        assert!(matches!(as_root, Err(Error::Config(_))));
+        sandbox.exec_agent(&provider, "hi", opts).await.unwrap();
+        sandbox
+            .exec_as(ExecUser::Root, "echo", &["installed"])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signal_needs_a_running_exec() {
+        let sandbox = Sandbox::mock().build().unwrap();
+
+        let output = sandbox.exec_with_id("job", "echo", &["hi"]).await.unwrap();
+        assert!(output.success());
+        assert!(matches!(
+            sandbox.exec_with_id("", "echo", &["hi"]).await,
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            sandbox.signal("job", ExecSignal::Interrupt).await,
+            Err(Error::Guest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_stdin_needs_a_guest() {
+        let sandbox = Sandbox::mock().build().unwrap();
+        assert!(matches!(
+            sandbox.exec_streaming_with_stdin("cat", &[], None).await,
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clock_sync_interval_is_validated() {
+        let zero = Sandbox::mock()
+            .clock_sync(std::time::Duration::ZERO)
+            .build();
+        assert!(matches!(zero, Err(Error::Config(_))));
+
+        let sandbox = Sandbox::mock()
+            .clock_sync(std::time::Duration::from_secs(60))
+            .build()
+            .unwrap();
+        assert_eq!(sandbox.sync_clock().await.unwrap().drift_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_mode_requires_replay() {
+        let live = Sandbox::mock().deterministic(42).build();
+        assert!(matches!(live, Err(Error::Config(_))));
+
+        let dir = tempfile::tempdir().unwrap();
+        let cassette = dir.path().join("run.jsonl");
+        std::fs::write(&cassette, "").unwrap();
+        let sandbox = Sandbox::mock()
+            .vcpus(4)
+            .deterministic(42)
+            .replay_http(&cassette)
+            .build()
+            .unwrap();
+        assert!(sandbox.sync_clock().await.is_err());
+        let manifest = sandbox.manifest().unwrap();
+        assert_eq!((manifest.seed, manifest.vcpus), (Some(42), 1));
+    }
+
+    #[tokio::test]
+    async fn test_mock_sandbox_events() {
+        let sandbox = Sandbox::mock().build().unwrap();
+        let mut events = sandbox.events();
+
+        sandbox.exec("echo", &["hi"]).await.unwrap();
+        sandbox
+            .write_file("/workspace/a.txt", b"abc")
+            .await
+            .unwrap();
+        sandbox.stop().await.unwrap();
+
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            SandboxEvent::ExecStarted { ref program, .. } if program == "echo"
+        ));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            SandboxEvent::ExecFinished {
+                exit_code: Some(0),
+                ..
+            }
+        ));
+        assert_eq!(
+            events.recv().await.unwrap(),
+            SandboxEvent::FileWritten {
+                path: "/workspace/a.txt".into(),
+                bytes: 3,
+            }
+        );
+        assert_eq!(
+            events.recv().await.unwrap(),
+            SandboxEvent::Shutdown { graceful: None }
+        );
+    }
+
     #[test]
     fn test_base64_encode() {
         assert_eq!(base64_encode(b"hello"), "aGVsbG8=");
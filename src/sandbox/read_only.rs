@@ -0,0 +1,67 @@
+//! Read-only (audit) runs.
+//!
+//! A sandbox built with [`SandboxBuilder::read_only`](super::SandboxBuilder::read_only)
+//! boots as usual — secrets, git workspace and exec policy are put in
+//! place first — and then stops accepting writes:
+//!
+//! - [`Sandbox::write_file`](super::Sandbox::write_file),
+//!   [`write_file_streaming`](super::Sandbox::write_file_streaming) and
+//!   [`mkdir_p`](super::Sandbox::mkdir_p) fail with
+//!   [`Error::ReadOnly`](crate::Error::ReadOnly), and the guest-agent
+//!   refuses the underlying RPCs as well.
+//! - `/workspace` is remounted read-only, and host shares and volumes are
+//!   attached read-only.
+//! - Anything else written inside the guest lands in the root
+//!   filesystem's tmpfs upper layer and is discarded with the VM.
+//!
+//! [`Sandbox::read_only_report`](super::Sandbox::read_only_report) lists
+//! both kinds of attempted write.
+
+use serde::{Deserialize, Serialize};
+
+use super::FsDiff;
+
+/// A host file operation refused by a read-only sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteOp {
+    WriteFile,
+    MkdirP,
+    Symlink,
+    Chmod,
+}
+
+impl WriteOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WriteFile => "write_file",
+            Self::MkdirP => "mkdir_p",
+            Self::Symlink => "symlink",
+            Self::Chmod => "chmod",
+        }
+    }
+}
+
+/// One refused write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedWrite {
+    pub op: WriteOp,
+    pub path: String,
+}
+
+/// What a read-only run tried to write.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadOnlyReport {
+    /// File writes refused, in the order they were attempted.
+    pub rejected: Vec<RejectedWrite>,
+    /// Changes guest processes made to the root filesystem, which are
+    /// discarded when the VM stops.
+    pub discarded: FsDiff,
+}
+
+impl ReadOnlyReport {
+    /// Whether the run attempted no writes at all.
+    pub fn is_clean(&self) -> bool {
+        self.rejected.is_empty() && self.discarded.changes.is_empty()
+    }
+}
@@ -0,0 +1,89 @@
+//! Streamed stdin for guest commands.
+//!
+//! [`Sandbox::exec_streaming_with_stdin`](super::Sandbox::exec_streaming_with_stdin)
+//! starts a command whose stdin stays open and hands back an [`ExecStdin`]
+//! to feed it, e.g. to pipe a dataset too large to buffer or to drive an
+//! interactive protocol. Each write returns once the guest has put the data
+//! in the command's stdin pipe, so a slow reader slows the writer down.
+
+use std::sync::Arc;
+
+use void_box_protocol::EXEC_STDIN_CHUNK_SIZE;
+
+use crate::backend::control_channel::ControlChannel;
+use crate::{Error, Result};
+
+/// Writer for the stdin of a running guest command.
+///
+/// Dropping it without [`close`](Self::close) still closes the command's
+/// stdin, in the background.
+pub struct ExecStdin {
+    channel: Arc<ControlChannel>,
+    exec_id: String,
+    closed: bool,
+}
+
+impl ExecStdin {
+    pub(crate) fn new(channel: Arc<ControlChannel>, exec_id: String) -> Self {
+        Self {
+            channel,
+            exec_id,
+            closed: false,
+        }
+    }
+
+    /// The command's exec id, which [`Sandbox::signal`](super::Sandbox::signal)
+    /// also takes.
+    pub fn exec_id(&self) -> &str {
+        &self.exec_id
+    }
+
+    /// Write `data` to the command's stdin. Fails once the command has
+    /// exited.
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(EXEC_STDIN_CHUNK_SIZE) {
+            let response = self.channel.send_exec_stdin(&self.exec_id, chunk).await?;
+            if let Some(e) = response.error {
+                return Err(Error::Guest(format!("cannot write stdin: {e}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the command's stdin, so it reads end-of-file.
+    pub async fn close(mut self) -> Result<()> {
+        self.closed = true;
+        let response = self.channel.send_exec_stdin_close(&self.exec_id).await?;
+        match response.error {
+            Some(e) => Err(Error::Guest(format!("cannot close stdin: {e}"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ExecStdin {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let channel = self.channel.clone();
+        let exec_id = std::mem::take(&mut self.exec_id);
+        handle.spawn(async move {
+            if let Err(e) = channel.send_exec_stdin_close(&exec_id).await {
+                tracing::debug!("Failed to close stdin of exec {}: {}", exec_id, e);
+            }
+        });
+    }
+}
+
+impl std::fmt::Debug for ExecStdin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExecStdin")
+            .field("exec_id", &self.exec_id)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
@@ -0,0 +1,30 @@
+//! Guest users beyond the built-in `sandbox` user.
+//!
+//! Execs run as the unprivileged `sandbox` user (uid 1000) unless asked
+//! otherwise: [`Sandbox::exec_as`](super::Sandbox::exec_as) picks an
+//! [`ExecUser`] for one command, and a workflow step can run as another
+//! user with [`WorkflowBuilder::run_as`](crate::workflow::WorkflowBuilder::run_as).
+//! [`ExecUser::Root`] is meant for setup such as package installation;
+//! agent runs always refuse it.
+//!
+//! Additional users, each with its own group and a 0700 home under
+//! `/home`, are made with [`SandboxBuilder::user`](super::SandboxBuilder::user)
+//! (re-created on every boot) or [`Sandbox::create_user`](super::Sandbox::create_user).
+
+pub use void_box_protocol::{ExecUser, SANDBOX_UID};
+
+/// A user made by [`Sandbox::create_user`](super::Sandbox::create_user).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestUser {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+impl GuestUser {
+    /// The [`ExecUser`] that runs commands as this user.
+    pub fn exec_user(&self) -> ExecUser {
+        ExecUser::named(&self.name)
+    }
+}
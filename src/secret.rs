@@ -0,0 +1,337 @@
+//! Secrets injected into the guest.
+//!
+//! Plain [`SandboxBuilder::env`](crate::sandbox::SandboxBuilder::env)
+//! values are ordinary configuration: they show up in `Debug` output and
+//! wherever the config is logged. A [`Secret`] is read from its
+//! [`SecretSource`] when the sandbox is built, held as a
+//! [`SecretString`], and reaches the guest in one of two ways:
+//!
+//! - [`SecretDelivery::Env`] (default): set in the environment of every
+//!   exec, like an `env` entry.
+//! - [`SecretDelivery::File`]: written to `/etc/voidbox/secrets/<NAME>` (mode 0600,
+//!   owned by the sandbox user, in a 0700 directory) after each boot, and
+//!   `<NAME>_FILE` set to that path instead, so the value never sits in a
+//!   process environment.
+//!
+//! Every secret value is also redacted — replaced by `[REDACTED:<NAME>]` —
+//! from the exec arguments carried by sandbox events and exec spans, from
+//! captured guest console lines, and from crash reports.
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{Error, Result};
+
+/// Guest directory file-delivered secrets are written to. It sits under
+/// `/etc/voidbox` because the guest-agent only accepts writes inside its
+/// write roots.
+pub const GUEST_SECRETS_DIR: &str = "/etc/voidbox/secrets";
+
+/// Where a [`Secret`]'s value comes from.
+#[derive(Debug, Clone)]
+pub enum SecretSource {
+    /// A value already in hand.
+    Value(SecretString),
+    /// A host environment variable.
+    Env(String),
+    /// A host file; one trailing newline is dropped.
+    File(PathBuf),
+    /// The OS keychain: the macOS Keychain (`security`), or the Secret
+    /// Service on Linux (`secret-tool`, looked up by `service` and
+    /// `account` attributes).
+    Keychain {
+        service: String,
+        account: Option<String>,
+    },
+}
+
+impl SecretSource {
+    pub fn value(value: impl Into<String>) -> Self {
+        Self::Value(SecretString::from(value.into()))
+    }
+
+    pub fn env(var: impl Into<String>) -> Self {
+        Self::Env(var.into())
+    }
+
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::File(path.into())
+    }
+
+    pub fn keychain(service: impl Into<String>) -> Self {
+        Self::Keychain {
+            service: service.into(),
+            account: None,
+        }
+    }
+
+    /// Read the value on the host.
+    pub fn resolve(&self) -> Result<SecretString> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Env(var) => std::env::var(var)
+                .map(SecretString::from)
+                .map_err(|_| Error::Config(format!("secret env var {var} is not set"))),
+            Self::File(path) => {
+                let mut value = std::fs::read_to_string(path).map_err(|e| {
+                    Error::Config(format!("cannot read secret file {}: {e}", path.display()))
+                })?;
+                if value.ends_with('\n') {
+                    value.pop();
+                    if value.ends_with('\r') {
+                        value.pop();
+                    }
+                }
+                Ok(SecretString::from(value))
+            }
+            Self::Keychain { service, account } => keychain_lookup(service, account.as_deref()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_lookup(service: &str, account: Option<&str>) -> Result<SecretString> {
+    let mut command = std::process::Command::new("security");
+    command.args(["find-generic-password", "-s", service, "-w"]);
+    if let Some(account) = account {
+        command.args(["-a", account]);
+    }
+    run_keychain_tool(command, service)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_lookup(service: &str, account: Option<&str>) -> Result<SecretString> {
+    let mut command = std::process::Command::new("secret-tool");
+    command.args(["lookup", "service", service]);
+    if let Some(account) = account {
+        command.args(["account", account]);
+    }
+    run_keychain_tool(command, service)
+}
+
+fn run_keychain_tool(mut command: std::process::Command, service: &str) -> Result<SecretString> {
+    let output = command.output().map_err(|e| {
+        Error::Config(format!(
+            "cannot query the keychain for {service}: {e} ({:?} not available?)",
+            command.get_program()
+        ))
+    })?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(Error::Config(format!(
+            "no keychain entry for {service}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let value = String::from_utf8(output.stdout)
+        .map_err(|_| Error::Config(format!("keychain entry for {service} is not UTF-8")))?;
+    Ok(SecretString::from(value.trim_end_matches('\n').to_string()))
+}
+
+/// How a [`Secret`] reaches the guest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SecretDelivery {
+    /// In the environment of every exec.
+    #[default]
+    Env,
+    /// In a 0600 file under [`GUEST_SECRETS_DIR`], its path in
+    /// `<NAME>_FILE`.
+    File,
+}
+
+/// A named secret for the guest. See the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Secret {
+    name: String,
+    source: SecretSource,
+    delivery: SecretDelivery,
+}
+
+impl Secret {
+    /// The secret `name` (an environment variable name), read from
+    /// `source`.
+    pub fn new(name: impl Into<String>, source: SecretSource) -> Self {
+        Self {
+            name: name.into(),
+            source,
+            delivery: SecretDelivery::Env,
+        }
+    }
+
+    /// Deliver as a file rather than an environment variable.
+    pub fn as_file(mut self) -> Self {
+        self.delivery = SecretDelivery::File;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn delivery(&self) -> SecretDelivery {
+        self.delivery
+    }
+
+    /// Guest path of a file-delivered secret.
+    pub fn guest_path(&self) -> String {
+        format!("{GUEST_SECRETS_DIR}/{}", self.name)
+    }
+
+    /// Check the name and read the value.
+    pub(crate) fn resolve(&self) -> Result<ResolvedSecret> {
+        let valid = !self.name.is_empty()
+            && !self.name.starts_with(|c: char| c.is_ascii_digit())
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(Error::Config(format!(
+                "secret name '{}' must be a valid environment variable name",
+                self.name
+            )));
+        }
+        Ok(ResolvedSecret {
+            secret: self.clone(),
+            value: self.source.resolve()?,
+        })
+    }
+}
+
+/// A [`Secret`] with its value read.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedSecret {
+    pub(crate) secret: Secret,
+    pub(crate) value: SecretString,
+}
+
+impl ResolvedSecret {
+    /// The environment entry every exec gets for this secret.
+    pub(crate) fn env_entry(&self) -> (String, String) {
+        match self.secret.delivery {
+            SecretDelivery::Env => (
+                self.secret.name.clone(),
+                self.value.expose_secret().to_string(),
+            ),
+            SecretDelivery::File => (
+                format!("{}_FILE", self.secret.name),
+                self.secret.guest_path(),
+            ),
+        }
+    }
+}
+
+/// Replaces secret values in text bound for events, traces and logs.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    secrets: Vec<(String, SecretString)>,
+}
+
+impl Redactor {
+    pub(crate) fn new(secrets: &[ResolvedSecret]) -> Self {
+        let mut secrets: Vec<(String, SecretString)> = secrets
+            .iter()
+            .filter(|s| !s.value.expose_secret().is_empty())
+            .map(|s| (s.secret.name.clone(), s.value.clone()))
+            .collect();
+        // Longest first, so a secret containing another is replaced whole.
+        secrets.sort_by_key(|(_, value)| std::cmp::Reverse(value.expose_secret().len()));
+        Self { secrets }
+    }
+
+    /// Whether there is nothing to redact.
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// `text` with every secret value replaced by `[REDACTED:<NAME>]`.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (name, value) in &self.secrets {
+            let value = value.expose_secret();
+            if text.contains(value) {
+                text = Cow::Owned(text.replace(value, &format!("[REDACTED:{name}]")));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(name: &str, value: &str) -> ResolvedSecret {
+        Secret::new(name, SecretSource::value(value))
+            .resolve()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sources_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, "from-file\n").unwrap();
+        let value = SecretSource::file(&path).resolve().unwrap();
+        assert_eq!(value.expose_secret(), "from-file");
+
+        std::env::set_var("VOID_BOX_TEST_SECRET_SOURCE", "from-env");
+        let value = SecretSource::env("VOID_BOX_TEST_SECRET_SOURCE")
+            .resolve()
+            .unwrap();
+        assert_eq!(value.expose_secret(), "from-env");
+        assert!(SecretSource::env("VOID_BOX_TEST_SECRET_UNSET")
+            .resolve()
+            .is_err());
+        assert!(SecretSource::file(dir.path().join("missing"))
+            .resolve()
+            .is_err());
+    }
+
+    #[test]
+    fn test_resolve_rejects_bad_names_and_debug_hides_values() {
+        assert!(Secret::new("API-KEY", SecretSource::value("x"))
+            .resolve()
+            .is_err());
+        assert!(Secret::new("1KEY", SecretSource::value("x"))
+            .resolve()
+            .is_err());
+        let secret = resolved("API_KEY", "sk-live-123");
+        assert!(!format!("{secret:?}").contains("sk-live-123"));
+    }
+
+    #[test]
+    fn test_env_entry_follows_delivery() {
+        assert_eq!(
+            resolved("API_KEY", "sk-1").env_entry(),
+            ("API_KEY".to_string(), "sk-1".to_string())
+        );
+        let file = Secret::new("API_KEY", SecretSource::value("sk-1"))
+            .as_file()
+            .resolve()
+            .unwrap();
+        assert_eq!(
+            file.env_entry(),
+            (
+                "API_KEY_FILE".to_string(),
+                "/etc/voidbox/secrets/API_KEY".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_redactor_replaces_longest_first() {
+        let redactor = Redactor::new(&[
+            resolved("SHORT", "abc"),
+            resolved("LONG", "abcdef"),
+            resolved("EMPTY", ""),
+        ]);
+        assert_eq!(
+            redactor.redact("k=abcdef j=abc"),
+            "k=[REDACTED:LONG] j=[REDACTED:SHORT]"
+        );
+        assert!(matches!(redactor.redact("clean"), Cow::Borrowed("clean")));
+        assert!(Redactor::default().is_empty());
+    }
+}
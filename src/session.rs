@@ -0,0 +1,185 @@
+//! Saved [`VoidBox`](crate::agent_box::VoidBox) sessions.
+//!
+//! A `VoidBox` and its VM live only as long as the host process. An
+//! [`AgentSession`] is the part worth keeping across a redeploy: the
+//! conversation so far, the skills the box provisions, the LLM provider, and
+//! the persistent volume mounted at `/workspace`. It is written as JSON by
+//! [`VoidBox::save_session`](crate::agent_box::VoidBox::save_session) (or
+//! after every run with [`VoidBox::session_file`](crate::agent_box::VoidBox::session_file))
+//! and turned back into a box by
+//! [`VoidBox::resume_session`](crate::agent_box::VoidBox::resume_session).
+//!
+//! API keys are never written. Skill env vars are, so the file is created
+//! readable by its owner only.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::LlmProvider;
+use crate::observe::claude::AgentExecResult;
+use crate::skill::Skill;
+use crate::{Error, Result};
+
+/// Format version written to, and required of, session files.
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
+/// One run of a box: what it was asked and what it answered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTurn {
+    pub prompt: String,
+    /// The agent's final answer.
+    pub response: String,
+    /// Whether the agent reported the run as failed.
+    pub is_error: bool,
+    /// Unix time the run finished, in milliseconds.
+    pub timestamp_ms: u64,
+}
+
+impl SessionTurn {
+    pub(crate) fn new(prompt: &str, result: &AgentExecResult) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            response: result.result_text.clone(),
+            is_error: result.is_error,
+            timestamp_ms: now_ms(),
+        }
+    }
+}
+
+/// Everything needed to continue a box's session in another process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSession {
+    /// [`SESSION_FORMAT_VERSION`] at save time.
+    pub version: u32,
+    /// The box's name.
+    pub name: String,
+    /// The prompt of the next run.
+    pub prompt: String,
+    /// Provider config, without its API key.
+    pub llm: LlmProvider,
+    /// Skills provisioned into the guest on every run.
+    pub skills: Vec<Skill>,
+    /// Persistent [`Volume`](crate::volume::Volume) mounted at `/workspace`.
+    pub workspace_volume: Option<String>,
+    /// The agent's own id for its last conversation (claude-code session,
+    /// Codex thread), if it reported one.
+    pub agent_session_id: Option<String>,
+    /// Completed runs, oldest first.
+    pub history: Vec<SessionTurn>,
+    /// Unix time of the save, in milliseconds.
+    pub saved_at_ms: u64,
+}
+
+impl AgentSession {
+    /// Write the session to `path`, replacing any previous save atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+        // Temp files are created 0600 on unix; the rename keeps the mode.
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Read a session saved by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let session: Self = serde_json::from_slice(&data)?;
+        if session.version != SESSION_FORMAT_VERSION {
+            return Err(Error::Config(format!(
+                "{}: unsupported session format version {} (expected {})",
+                path.display(),
+                session.version,
+                SESSION_FORMAT_VERSION
+            )));
+        }
+        Ok(session)
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> AgentSession {
+        AgentSession {
+            version: SESSION_FORMAT_VERSION,
+            name: "analyst".into(),
+            prompt: "Summarize the findings".into(),
+            llm: LlmProvider::custom("https://openrouter.ai/api/v1").api_key("sk-secret"),
+            skills: vec![
+                Skill::agent("claude-code"),
+                Skill::inline("notes", "# Notes"),
+            ],
+            workspace_volume: Some("analyst-ws".into()),
+            agent_session_id: Some("sess-1".into()),
+            history: vec![SessionTurn {
+                prompt: "Fetch the data".into(),
+                response: "Fetched 30 rows".into(),
+                is_error: false,
+                timestamp_ms: 1,
+            }],
+            saved_at_ms: 2,
+        }
+    }
+
+    #[test]
+    fn test_session_round_trips_without_api_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions/analyst.json");
+        sample().save(&path).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("sk-secret"));
+
+        let loaded = AgentSession::load(&path).unwrap();
+        assert_eq!(loaded.name, "analyst");
+        assert_eq!(loaded.skills.len(), 2);
+        assert_eq!(loaded.workspace_volume.as_deref(), Some("analyst-ws"));
+        assert_eq!(loaded.history, sample().history);
+        match loaded.llm {
+            LlmProvider::Custom {
+                base_url, api_key, ..
+            } => {
+                assert_eq!(base_url, "https://openrouter.ai/api/v1");
+                assert!(api_key.is_none());
+            }
+            other => panic!("unexpected provider {other:?}"),
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        AgentSession {
+            version: SESSION_FORMAT_VERSION + 1,
+            ..sample()
+        }
+        .save(&path)
+        .unwrap();
+        assert!(matches!(AgentSession::load(&path), Err(Error::Config(_))));
+    }
+}
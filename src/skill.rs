@@ -11,6 +11,8 @@
 //! - **`Skill::agent(name)`** -- LLM agent (the reasoning engine)
 //! - **`Skill::remote(id)`** -- Procedural knowledge from [skills.sh](https://skills.sh)
 //! - **`Skill::file(path)`** -- Local SKILL.md with procedural knowledge
+//! - **`Skill::registry(spec)`** -- Versioned SKILL.md from a
+//!   [`SkillRegistry`](crate::skill_registry::SkillRegistry), e.g. `"rust-refactor@^1"`
 //!
 //! # Example
 //!
@@ -30,8 +32,10 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use serde::{Deserialize, Serialize};
+
 /// A declared capability that gets installed into a Box.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Skill {
     /// The kind of skill
     pub kind: SkillKind,
@@ -42,7 +46,8 @@ pub struct Skill {
 }
 
 /// The type of skill and its configuration.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum SkillKind {
     /// MCP server -- structured tools via Model Context Protocol (stdio transport).
     /// The binary must be available in the guest filesystem.
@@ -87,6 +92,13 @@ pub enum SkillKind {
         /// Whether the mount is read-only (default: true)
         readonly: bool,
     },
+    /// Registry skill -- a versioned SKILL.md resolved by the Box's
+    /// [`SkillRegistry`](crate::skill_registry::SkillRegistry) when it is
+    /// provisioned.
+    Registry {
+        /// `name` or `name@<version requirement>` (e.g. "rust-refactor@^1")
+        spec: String,
+    },
     /// Inline skill -- content provided directly, not from a file.
     /// Written to the guest as a SKILL.md file.
     Inline {
@@ -198,6 +210,24 @@ impl Skill {
         }
     }
 
+    /// Create a skill resolved from the Box's skill registry.
+    ///
+    /// ```
+    /// use void_box::skill::Skill;
+    ///
+    /// let refactor = Skill::registry("rust-refactor@^1");
+    /// assert_eq!(refactor.name, "rust-refactor");
+    /// ```
+    pub fn registry(spec: impl Into<String>) -> Self {
+        let spec = spec.into();
+        let name = spec.split('@').next().unwrap_or(&spec).trim().to_string();
+        Self {
+            kind: SkillKind::Registry { spec },
+            name,
+            description_text: None,
+        }
+    }
+
     /// Create an inline skill with content provided directly.
     pub fn inline(name: impl Into<String>, content: impl Into<String>) -> Self {
         Self {
@@ -327,6 +357,24 @@ impl Skill {
             _ => None,
         }
     }
+
+    /// Check this skill without booting a VM.
+    ///
+    /// Lints SKILL.md frontmatter, linked files, `allowed-tools` and
+    /// prompt-injection patterns, and the configuration of MCP, CLI and OCI
+    /// skills. See [`skill_lint`](crate::skill_lint) for the individual
+    /// checks. Provisioning runs the same checks and refuses skills with
+    /// errors.
+    ///
+    /// ```
+    /// use void_box::skill::Skill;
+    ///
+    /// let report = Skill::inline("notes", "---\nname: notes\ndescription: Take notes\n---\n").validate();
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn validate(&self) -> crate::skill_lint::SkillReport {
+        crate::skill_lint::validate(self)
+    }
 }
 
 #[cfg(test)]
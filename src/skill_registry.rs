@@ -0,0 +1,619 @@
+//! Versioned skills from git repositories and OCI registries.
+//!
+//! A [`SkillRegistry`] resolves specs like `rust-refactor@^1` against one
+//! or more sources and can pin what it picked in a lockfile, so later runs
+//! install the same SKILL.md after newer versions are published.
+//!
+//! # Sources
+//!
+//! - **git** ([`SkillRegistry::git`]) — a repository laid out as
+//!   `skills/<name>/<version>/SKILL.md`. It is shallow-cloned into the
+//!   cache with the host `git` and pulled once per registry.
+//! - **OCI** ([`SkillRegistry::oci`]) — one artifact per skill at
+//!   `<repository>/<name>`, one tag per version (a leading `v` is allowed),
+//!   with `SKILL.md` at the root of its filesystem.
+//! - **directory** ([`SkillRegistry::dir`]) — a local checkout with the git
+//!   layout.
+//!
+//! Sources are tried in order; the first with a matching version wins.
+//!
+//! # Specs
+//!
+//! `name` takes the newest version, `name@1.2.0` exactly that version and
+//! `name@^1`, `name@~1.2` or `name@>=1.1, <2` the newest match.
+//! Pre-releases only match a requirement that names one.
+//!
+//! # Lockfile
+//!
+//! With [`lockfile`](SkillRegistry::lockfile), each resolved skill is
+//! recorded with its version, source and SHA-256. A locked skill whose
+//! version still satisfies the spec is installed at that version from the
+//! same source, and fails if its content changed; otherwise it is resolved
+//! again and its entry updated. Entries are added or updated, never
+//! removed, so the boxes of a pipeline can share one lockfile.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use void_box::agent_box::VoidBox;
+//! use void_box::skill_registry::SkillRegistry;
+//!
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let registry = SkillRegistry::new()
+//!     .git("https://github.com/acme/agent-skills")
+//!     .oci("ghcr.io/acme/skills")
+//!     .lockfile("skills.lock");
+//!
+//! let reviewer = VoidBox::new("reviewer")
+//!     .skill_registry(registry)
+//!     .skills_from_registry(["rust-refactor@^1", "code-review@2.0.1"])
+//!     .prompt("Refactor the parser module")
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::skill::Skill;
+use crate::{Error, Result};
+
+/// Lockfile format version written by [`SkillLock::save`].
+pub const SKILL_LOCK_VERSION: u32 = 1;
+
+/// Where versioned skills are published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegistrySource {
+    /// A git repository with `skills/<name>/<version>/SKILL.md`.
+    Git { url: String },
+    /// An OCI repository prefix; skill `name` lives at `<repository>/<name>`.
+    Oci { repository: String },
+    /// A local directory with the git layout.
+    Dir { path: PathBuf },
+}
+
+impl fmt::Display for RegistrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistrySource::Git { url } => write!(f, "git+{url}"),
+            RegistrySource::Oci { repository } => write!(f, "oci://{repository}"),
+            RegistrySource::Dir { path } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A requested skill: `name` or `name@<version requirement>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkillSpec {
+    pub name: String,
+    pub req: VersionReq,
+}
+
+impl FromStr for SkillSpec {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (name, req) = match spec.split_once('@') {
+            Some((name, req)) => (name.trim(), Some(req.trim())),
+            None => (spec.trim(), None),
+        };
+        let valid_name = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(Error::Config(format!(
+                "invalid skill spec '{spec}': name must be letters, digits, '-', '_' or '.'"
+            )));
+        }
+        let req = match req {
+            None => VersionReq::STAR,
+            // A bare version pins exactly; semver alone would read it as `^`.
+            Some(req) => match Version::parse(req) {
+                Ok(version) => VersionReq::parse(&format!("={version}")),
+                Err(_) => VersionReq::parse(req),
+            }
+            .map_err(|e| Error::Config(format!("invalid skill spec '{spec}': {e}")))?,
+        };
+        Ok(SkillSpec {
+            name: name.to_string(),
+            req,
+        })
+    }
+}
+
+impl fmt::Display for SkillSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.req)
+    }
+}
+
+/// A skill picked by [`SkillRegistry::resolve`].
+#[derive(Debug, Clone)]
+pub struct ResolvedSkill {
+    pub name: String,
+    pub version: Version,
+    pub source: RegistrySource,
+    /// SKILL.md content.
+    pub content: String,
+    /// Hex SHA-256 of `content`.
+    pub sha256: String,
+}
+
+impl ResolvedSkill {
+    /// An inline [`Skill`] carrying this version's SKILL.md.
+    pub fn skill(&self) -> Skill {
+        Skill::inline(&self.name, &self.content).description(format!(
+            "{}@{} from {}",
+            self.name, self.version, self.source
+        ))
+    }
+}
+
+/// The lockfile [`SkillRegistry::lockfile`] reads and writes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkillLock {
+    pub version: u32,
+    pub skills: Vec<LockedSkill>,
+}
+
+/// One pinned skill.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedSkill {
+    pub name: String,
+    pub version: Version,
+    pub source: RegistrySource,
+    pub sha256: String,
+}
+
+impl SkillLock {
+    /// Read a lockfile; a missing file is an empty lock.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let lock: Self = serde_json::from_slice(&data)?;
+        if lock.version != SKILL_LOCK_VERSION {
+            return Err(Error::Config(format!(
+                "{}: unsupported skill lock version {} (expected {})",
+                path.display(),
+                lock.version,
+                SKILL_LOCK_VERSION
+            )));
+        }
+        Ok(lock)
+    }
+
+    /// Write the lockfile atomically, skills sorted by name.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+        let mut lock = self.clone();
+        lock.version = SKILL_LOCK_VERSION;
+        lock.skills.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, &lock)?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedSkill> {
+        self.skills.iter().find(|s| s.name == name)
+    }
+
+    fn upsert(&mut self, skill: LockedSkill) {
+        match self.skills.iter_mut().find(|s| s.name == skill.name) {
+            Some(entry) => *entry = skill,
+            None => self.skills.push(skill),
+        }
+    }
+}
+
+/// Resolves versioned skills from its sources; see the [module docs](self).
+#[derive(Debug)]
+pub struct SkillRegistry {
+    sources: Vec<RegistrySource>,
+    cache_dir: PathBuf,
+    lockfile: Option<PathBuf>,
+    /// Git sources pulled by this registry already.
+    synced: tokio::sync::Mutex<HashSet<String>>,
+    /// Serializes lockfile updates from boxes sharing this registry.
+    lock_update: tokio::sync::Mutex<()>,
+}
+
+impl Default for SkillRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SkillRegistry {
+    /// A registry with no sources, caching under `$VOIDBOX_CACHE_DIR/skills`
+    /// (default `~/.voidbox/skills`).
+    pub fn new() -> Self {
+        let cache_dir = match std::env::var("VOIDBOX_CACHE_DIR") {
+            Ok(dir) => PathBuf::from(dir).join("skills"),
+            Err(_) => {
+                let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+                PathBuf::from(home).join(".voidbox/skills")
+            }
+        };
+        Self {
+            sources: Vec::new(),
+            cache_dir,
+            lockfile: None,
+            synced: tokio::sync::Mutex::new(HashSet::new()),
+            lock_update: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Add a git repository source.
+    pub fn git(mut self, url: impl Into<String>) -> Self {
+        self.sources.push(RegistrySource::Git { url: url.into() });
+        self
+    }
+
+    /// Add an OCI repository prefix, e.g. `ghcr.io/acme/skills`.
+    pub fn oci(mut self, repository: impl Into<String>) -> Self {
+        self.sources.push(RegistrySource::Oci {
+            repository: repository.into(),
+        });
+        self
+    }
+
+    /// Add a local directory source.
+    pub fn dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(RegistrySource::Dir { path: path.into() });
+        self
+    }
+
+    /// Where git clones and OCI artifacts are cached.
+    pub fn cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = path.into();
+        self
+    }
+
+    /// Pin resolved versions in `path`, reading it if it exists.
+    pub fn lockfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lockfile = Some(path.into());
+        self
+    }
+
+    /// Resolve `specs` and fetch their SKILL.md files, updating the
+    /// lockfile if one is set.
+    pub async fn resolve<I, S>(&self, specs: I) -> Result<Vec<ResolvedSkill>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let specs = specs
+            .into_iter()
+            .map(|spec| spec.as_ref().parse())
+            .collect::<Result<Vec<SkillSpec>>>()?;
+        if specs.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.sources.is_empty() {
+            return Err(Error::Config("skill registry has no sources".into()));
+        }
+
+        let _guard = self.lock_update.lock().await;
+        let mut lock = match &self.lockfile {
+            Some(path) => SkillLock::load(path)?,
+            None => SkillLock::default(),
+        };
+
+        let mut resolved = Vec::with_capacity(specs.len());
+        for spec in &specs {
+            let locked = lock
+                .get(&spec.name)
+                .filter(|locked| spec.req.matches(&locked.version))
+                .cloned();
+            let skill = match locked {
+                Some(locked) => self.fetch_locked(&locked).await?,
+                None => self.resolve_one(spec).await?,
+            };
+            lock.upsert(LockedSkill {
+                name: skill.name.clone(),
+                version: skill.version.clone(),
+                source: skill.source.clone(),
+                sha256: skill.sha256.clone(),
+            });
+            resolved.push(skill);
+        }
+
+        if let Some(path) = &self.lockfile {
+            lock.save(path)?;
+        }
+        Ok(resolved)
+    }
+
+    /// [`resolve`](Self::resolve), as inline [`Skill`]s.
+    pub async fn skills<I, S>(&self, specs: I) -> Result<Vec<Skill>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Ok(self
+            .resolve(specs)
+            .await?
+            .iter()
+            .map(ResolvedSkill::skill)
+            .collect())
+    }
+
+    async fn resolve_one(&self, spec: &SkillSpec) -> Result<ResolvedSkill> {
+        for source in &self.sources {
+            let versions = self.versions(source, &spec.name).await?;
+            let Some(version) = versions.into_iter().filter(|v| spec.req.matches(v)).max() else {
+                continue;
+            };
+            tracing::debug!("skill {} resolved to {} from {}", spec, version, source);
+            return self.fetch(source, &spec.name, version).await;
+        }
+        let sources: Vec<String> = self.sources.iter().map(ToString::to_string).collect();
+        Err(Error::Config(format!(
+            "no version of skill '{}' matches '{}' in {}",
+            spec.name,
+            spec.req,
+            sources.join(", ")
+        )))
+    }
+
+    async fn fetch_locked(&self, locked: &LockedSkill) -> Result<ResolvedSkill> {
+        let skill = self
+            .fetch(&locked.source, &locked.name, locked.version.clone())
+            .await?;
+        if skill.sha256 != locked.sha256 {
+            return Err(Error::Config(format!(
+                "skill {}@{} from {} changed since it was locked (sha256 {} != {})",
+                locked.name, locked.version, locked.source, skill.sha256, locked.sha256
+            )));
+        }
+        Ok(skill)
+    }
+
+    /// Published versions of `name` in `source`; none if it has no such skill.
+    async fn versions(&self, source: &RegistrySource, name: &str) -> Result<Vec<Version>> {
+        match source {
+            RegistrySource::Dir { path } => Ok(dir_versions(path, name)),
+            RegistrySource::Git { url } => Ok(dir_versions(&self.git_checkout(url).await?, name)),
+            RegistrySource::Oci { repository } => {
+                let client = voidbox_oci::OciClient::new(self.cache_dir.join("oci"));
+                let tags = match client.list_tags(&format!("{repository}/{name}")).await {
+                    Ok(tags) => tags,
+                    Err(voidbox_oci::OciError::NotFound(_)) => return Ok(Vec::new()),
+                    Err(e) => {
+                        return Err(Error::Network(format!(
+                            "failed to list skill tags in {source}: {e}"
+                        )))
+                    }
+                };
+                Ok(tags
+                    .iter()
+                    .filter_map(|tag| Version::parse(tag.trim_start_matches('v')).ok())
+                    .collect())
+            }
+        }
+    }
+
+    async fn fetch(
+        &self,
+        source: &RegistrySource,
+        name: &str,
+        version: Version,
+    ) -> Result<ResolvedSkill> {
+        let path = match source {
+            RegistrySource::Dir { path } => skill_path(path, name, &version),
+            RegistrySource::Git { url } => {
+                skill_path(&self.git_checkout(url).await?, name, &version)
+            }
+            RegistrySource::Oci { repository } => self
+                .oci_rootfs(repository, name, &version)
+                .await?
+                .join("SKILL.md"),
+        };
+        let content = tokio::fs::read_to_string(&path).await.map_err(|e| {
+            Error::Config(format!(
+                "skill {name}@{version} not found in {source} ({}): {e}",
+                path.display()
+            ))
+        })?;
+        Ok(ResolvedSkill {
+            name: name.to_string(),
+            version,
+            source: source.clone(),
+            sha256: format!("{:x}", Sha256::digest(content.as_bytes())),
+            content,
+        })
+    }
+
+    /// Clone `url` into the cache, or pull it the first time this registry
+    /// uses it.
+    async fn git_checkout(&self, url: &str) -> Result<PathBuf> {
+        let dir = self
+            .cache_dir
+            .join("git")
+            .join(&format!("{:x}", Sha256::digest(url.as_bytes()))[..16]);
+        let mut synced = self.synced.lock().await;
+        if synced.contains(url) {
+            return Ok(dir);
+        }
+        if dir.join(".git").is_dir() {
+            run_git(&["-C", &dir.to_string_lossy(), "pull", "--ff-only", "--quiet"]).await?;
+        } else {
+            tokio::fs::create_dir_all(self.cache_dir.join("git")).await?;
+            run_git(&[
+                "clone",
+                "--depth",
+                "1",
+                "--quiet",
+                url,
+                &dir.to_string_lossy(),
+            ])
+            .await?;
+        }
+        synced.insert(url.to_string());
+        Ok(dir)
+    }
+
+    /// Pull and unpack `<repository>/<name>:<version>`, reusing an earlier
+    /// unpack.
+    async fn oci_rootfs(&self, repository: &str, name: &str, version: &Version) -> Result<PathBuf> {
+        let dest = self
+            .cache_dir
+            .join("oci-skills")
+            .join(name)
+            .join(version.to_string());
+        if dest.join("SKILL.md").is_file() {
+            return Ok(dest);
+        }
+        let client = voidbox_oci::OciClient::new(self.cache_dir.join("oci"));
+        let mut last_error = None;
+        // Tags may or may not carry a `v` prefix.
+        for tag in [version.to_string(), format!("v{version}")] {
+            let image_ref = format!("{repository}/{name}:{tag}");
+            match client.pull(&image_ref).await {
+                Ok(image) => {
+                    return client.unpack(&image, &dest).await.map_err(|e| {
+                        Error::Config(format!("failed to unpack skill {image_ref}: {e}"))
+                    });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(Error::Network(format!(
+            "failed to pull skill {name}@{version} from oci://{repository}: {}",
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        )))
+    }
+}
+
+fn skill_path(root: &Path, name: &str, version: &Version) -> PathBuf {
+    root.join("skills")
+        .join(name)
+        .join(version.to_string())
+        .join("SKILL.md")
+}
+
+/// Versions under `root/skills/<name>/` that have a SKILL.md.
+fn dir_versions(root: &Path, name: &str) -> Vec<Version> {
+    let Ok(entries) = std::fs::read_dir(root.join("skills").join(name)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().join("SKILL.md").is_file())
+        .filter_map(|entry| Version::parse(entry.file_name().to_str()?).ok())
+        .collect()
+}
+
+async fn run_git(args: &[&str]) -> Result<()> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .await
+        .map_err(|e| Error::Config(format!("failed to run git: {e}")))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(Error::Network(format!(
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(root: &Path, name: &str, version: &str, content: &str) {
+        let dir = root.join("skills").join(name).join(version);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("SKILL.md"), content).unwrap();
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let any: SkillSpec = "rust-refactor".parse().unwrap();
+        assert_eq!(any.req, VersionReq::STAR);
+        let exact: SkillSpec = "rust-refactor@1.2.0".parse().unwrap();
+        assert!(exact.req.matches(&Version::new(1, 2, 0)));
+        assert!(!exact.req.matches(&Version::new(1, 3, 0)));
+        let caret: SkillSpec = "rust-refactor@^1".parse().unwrap();
+        assert!(caret.req.matches(&Version::new(1, 9, 0)));
+        assert!(!caret.req.matches(&Version::new(2, 0, 0)));
+
+        assert!("../etc@1".parse::<SkillSpec>().is_err());
+        assert!("a/b".parse::<SkillSpec>().is_err());
+        assert!("x@not-a-version".parse::<SkillSpec>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_newest_match_and_lock() {
+        let root = tempfile::tempdir().unwrap();
+        publish(root.path(), "rust-refactor", "1.0.0", "# v1.0");
+        publish(root.path(), "rust-refactor", "1.4.2", "# v1.4");
+        publish(root.path(), "rust-refactor", "2.0.0", "# v2");
+        let lock_path = root.path().join("skills.lock");
+
+        let registry = SkillRegistry::new().dir(root.path()).lockfile(&lock_path);
+        let resolved = registry.resolve(["rust-refactor@^1"]).await.unwrap();
+        assert_eq!(resolved[0].version, Version::new(1, 4, 2));
+        assert_eq!(resolved[0].content, "# v1.4");
+
+        // A newer 1.x is published; the lock keeps 1.4.2.
+        publish(root.path(), "rust-refactor", "1.5.0", "# v1.5");
+        let resolved = registry.resolve(["rust-refactor@^1"]).await.unwrap();
+        assert_eq!(resolved[0].version, Version::new(1, 4, 2));
+
+        // A spec the locked version no longer satisfies re-resolves.
+        let resolved = registry.resolve(["rust-refactor@2"]).await.unwrap();
+        assert_eq!(resolved[0].version, Version::new(2, 0, 0));
+        let lock = SkillLock::load(&lock_path).unwrap();
+        assert_eq!(
+            lock.get("rust-refactor").unwrap().version,
+            Version::new(2, 0, 0)
+        );
+
+        // Content changed under a locked version.
+        publish(root.path(), "rust-refactor", "2.0.0", "# tampered");
+        let err = registry.resolve(["rust-refactor@2"]).await.unwrap_err();
+        assert!(err.to_string().contains("changed since it was locked"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_tries_sources_in_order() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        publish(first.path(), "lint", "0.1.0", "first");
+        publish(second.path(), "lint", "0.2.0", "second");
+        publish(second.path(), "docs", "1.0.0", "docs");
+
+        let registry = SkillRegistry::new().dir(first.path()).dir(second.path());
+        let skills = registry.resolve(["lint", "docs"]).await.unwrap();
+        assert_eq!(skills[0].content, "first");
+        assert_eq!(skills[1].content, "docs");
+
+        let err = registry.resolve(["lint@>=1"]).await.unwrap_err();
+        assert!(err.to_string().contains("no version of skill 'lint'"));
+    }
+}
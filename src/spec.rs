@@ -64,6 +64,18 @@ pub struct SandboxSpec {
     /// If not set, the sandbox cold-boots normally.
     #[serde(default)]
     pub snapshot: Option<String>,
+    /// Guest directories the host may write to besides `/workspace`,
+    /// `/home` and `/etc/voidbox` (e.g. `/opt`).
+    #[serde(default)]
+    pub write_roots: Vec<String>,
+    /// Run x86_64 binaries, and `linux/amd64` images, through Rosetta
+    /// (VZ on Apple silicon only).
+    #[serde(default)]
+    pub rosetta: bool,
+    /// Serve SSH into the sandbox from the host while it runs (`sandbox`
+    /// and `workflow` runs); see [`crate::ssh`].
+    #[serde(default)]
+    pub ssh: bool,
 }
 
 /// Specification for a host directory mount into the guest VM.
@@ -310,6 +322,9 @@ impl Default for SandboxSpec {
             image: None,
             guest_image: None,
             snapshot: None,
+            write_roots: Vec::new(),
+            rosetta: false,
+            ssh: false,
         }
     }
 }
@@ -0,0 +1,139 @@
+//! Audit log of SSH access to a sandbox.
+//!
+//! One JSON object per line, appended to `audit.jsonl` in the bridge
+//! directory and mirrored to `tracing`. The log records who connected,
+//! which key authenticated, and which sessions and commands ran; it does
+//! not record terminal contents.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+/// Something that happened on the bridge.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The bridge started listening.
+    Listening { addr: SocketAddr },
+    /// A client opened a TCP connection.
+    Connected,
+    /// A key was accepted for `user`.
+    AuthAccepted { user: String, fingerprint: String },
+    /// An authentication attempt was turned down.
+    AuthRejected { user: String, method: String },
+    /// A session started: a login shell when `command` is `None`.
+    SessionStarted {
+        channel: u32,
+        command: Option<String>,
+        pty: bool,
+    },
+    /// A forwarded connection to `host:port` from inside the guest started
+    /// on `channel`.
+    ForwardStarted {
+        channel: u32,
+        host: String,
+        port: u16,
+    },
+    /// A session or forwarded connection ended; `exit_code` is `None` when
+    /// the client closed it before the process exited.
+    SessionEnded {
+        channel: u32,
+        exit_code: Option<i32>,
+        bytes_in: u64,
+        bytes_out: u64,
+    },
+    /// The connection ended.
+    Disconnected { reason: String },
+    /// The bridge stopped.
+    Stopped,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: String,
+    sandbox: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peer: Option<SocketAddr>,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// Appends [`AuditEvent`]s for one sandbox.
+pub struct AuditLog {
+    sandbox: String,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Open `path` for appending, creating it owner-readable only.
+    pub fn open(path: &Path, sandbox: impl Into<String>) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)?;
+        Ok(Self {
+            sandbox: sandbox.into(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record `event`, attributed to `peer` when it came from a connection.
+    pub fn record(&self, peer: Option<SocketAddr>, event: AuditEvent) {
+        tracing::info!(sandbox = %self.sandbox, ?peer, ?event, "ssh audit");
+        let record = Record {
+            ts: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            sandbox: &self.sandbox,
+            peer,
+            event: &event,
+        };
+        let Ok(mut line) = serde_json::to_vec(&record) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(&line) {
+            tracing::warn!("failed to write ssh audit log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_one_json_line_per_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, "box").unwrap();
+        let peer: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        log.record(Some(peer), AuditEvent::Connected);
+        log.record(
+            Some(peer),
+            AuditEvent::SessionStarted {
+                channel: 0,
+                command: Some("uptime".into()),
+                pty: false,
+            },
+        );
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "connected");
+        assert_eq!(lines[0]["peer"], "127.0.0.1:50000");
+        assert_eq!(lines[1]["sandbox"], "box");
+        assert_eq!(lines[1]["command"], "uptime");
+        assert!(lines[1]["ts"].as_str().unwrap().ends_with('Z'));
+    }
+}
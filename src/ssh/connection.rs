@@ -0,0 +1,626 @@
+//! One client connection: who may log in, and what its channels run.
+//!
+//! [`russh`] speaks the protocol and calls back into [`Connection`]. A
+//! `session` channel collects its terminal and environment requests until
+//! `shell` or `exec` starts a guest process; a `direct-tcpip` channel
+//! connects as soon as it opens. Each process runs from its own task, which
+//! writes output through the channel as the client's window allows and
+//! ends the channel when the process exits.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use russh::keys::PublicKey;
+use russh::server::{Auth, Config, Msg, Response, Session};
+use russh::{ChannelId, ChannelWriteHalf, Disconnect, MethodKind, MethodSet, Pty, Sig, SshId};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use super::audit::AuditEvent;
+use super::keys::{self, Ed25519Key};
+use super::target::{self, GuestProcess, ProcessInput, ProcessOutput, ProcessRequest, PtySize};
+use super::{BridgeState, SshError};
+
+/// Time a client has to authenticate, as OpenSSH's `LoginGraceTime`.
+const LOGIN_GRACE: Duration = Duration::from_secs(120);
+
+/// Failed authentication attempts before the connection is dropped.
+const MAX_AUTH_ATTEMPTS: usize = 6;
+
+/// Open channels per connection. Editors forward a connection per
+/// request, so this is well above what interactive use needs.
+const MAX_CHANNELS: usize = 64;
+
+/// Bytes a client may send on a channel before it has to wait for a
+/// window adjustment.
+const WINDOW: u32 = 2 * 1024 * 1024;
+
+/// Largest `SSH_MSG_CHANNEL_DATA` payload accepted or sent.
+const MAX_DATA: u32 = 32 * 1024;
+
+/// Client input queued for one guest process. The client's window is
+/// handed back as input arrives, not as the guest takes it, so past this
+/// the whole connection stops reading until the guest catches up.
+const MAX_PENDING_INPUT: usize = 16 * 1024 * 1024;
+
+/// How long the guest gets to start a session's process.
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Server settings shared by every connection of a bridge.
+pub(crate) fn config(host_key: &Ed25519Key) -> Config {
+    Config {
+        server_id: SshId::Standard(format!("SSH-2.0-voidbox_{}", env!("CARGO_PKG_VERSION"))),
+        methods: MethodSet::from(&[MethodKind::PublicKey][..]),
+        // Clients start with `none` to learn the methods; only real
+        // attempts are slowed down.
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        keys: vec![host_key.private_key().clone()],
+        window_size: WINDOW,
+        maximum_packet_size: MAX_DATA,
+        max_auth_attempts: MAX_AUTH_ATTEMPTS,
+        // An idle shell stays open; `LOGIN_GRACE` bounds the rest.
+        inactivity_timeout: None,
+        ..Config::default()
+    }
+}
+
+/// Serve one client until it disconnects or `cancel` fires.
+pub(crate) async fn serve(
+    stream: TcpStream,
+    peer: SocketAddr,
+    state: &Arc<BridgeState>,
+    cancel: CancellationToken,
+) -> Result<(), SshError> {
+    stream.set_nodelay(true)?;
+    let login_deadline = tokio::time::Instant::now() + LOGIN_GRACE;
+    let authenticated = Arc::new(AtomicBool::new(false));
+    let handler = Connection {
+        peer,
+        state: state.clone(),
+        authenticated: authenticated.clone(),
+        channels: HashMap::new(),
+    };
+    let running = tokio::time::timeout_at(
+        login_deadline,
+        russh::server::run_stream(state.config.clone(), stream, handler),
+    )
+    .await
+    .map_err(|_| russh::Error::ConnectionTimeout)??;
+    let handle = running.handle();
+    let mut running = std::pin::pin!(running);
+
+    let mut login_pending = true;
+    loop {
+        tokio::select! {
+            result = &mut running => return result,
+            _ = cancel.cancelled() => {
+                let _ = handle
+                    .disconnect(Disconnect::ByApplication, "sandbox stopped".into(), String::new())
+                    .await;
+                return running.await;
+            }
+            _ = tokio::time::sleep_until(login_deadline), if login_pending => {
+                login_pending = false;
+                if !authenticated.load(Ordering::Acquire) {
+                    let _ = handle
+                        .disconnect(
+                            Disconnect::ProtocolError,
+                            "authentication timed out".into(),
+                            String::new(),
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+/// What a channel runs in the guest.
+enum Start {
+    /// A `session` channel's shell or command.
+    Process(ProcessRequest),
+    /// A `direct-tcpip` channel's connection.
+    Connect { host: String, port: u16 },
+}
+
+/// Client input for a channel's process, holding its share of
+/// [`MAX_PENDING_INPUT`] until the process has taken it.
+type Input = (ProcessInput, Option<OwnedSemaphorePermit>);
+
+struct Channel {
+    /// Where output goes; taken when the process starts.
+    writer: Option<ChannelWriteHalf<Msg>>,
+    /// A `direct-tcpip` channel rather than a session.
+    forward: bool,
+    pty: Option<PtySize>,
+    env: Vec<(String, String)>,
+    /// Set once a shell or command started.
+    input: Option<mpsc::UnboundedSender<Input>>,
+    pending: Arc<Semaphore>,
+    /// Fired when the client closes the channel or the connection ends.
+    closed: CancellationToken,
+}
+
+impl Channel {
+    fn new(channel: russh::Channel<Msg>, forward: bool) -> Self {
+        // Input arrives through the handler; the read half is not needed.
+        let (_, writer) = channel.split();
+        Self {
+            writer: Some(writer),
+            forward,
+            pty: None,
+            env: Vec::new(),
+            input: None,
+            pending: Arc::new(Semaphore::new(MAX_PENDING_INPUT)),
+            closed: CancellationToken::new(),
+        }
+    }
+
+    /// Whether `pty-req` and `env` may still change what starts.
+    fn configurable(&self) -> bool {
+        !self.forward && self.input.is_none()
+    }
+
+    fn send(&self, input: ProcessInput) {
+        if let Some(tx) = &self.input {
+            let _ = tx.send((input, None));
+        }
+    }
+}
+
+/// The [`russh::server::Handler`] for one connection.
+struct Connection {
+    peer: SocketAddr,
+    state: Arc<BridgeState>,
+    authenticated: Arc<AtomicBool>,
+    channels: HashMap<ChannelId, Channel>,
+}
+
+impl Connection {
+    fn reject(&self, user: &str, method: &str) -> Auth {
+        self.state.audit.record(
+            Some(self.peer),
+            AuditEvent::AuthRejected {
+                user: user.to_string(),
+                method: method.to_string(),
+            },
+        );
+        Auth::reject()
+    }
+
+    fn accepts(&self, key: &PublicKey) -> bool {
+        key.key_data() == self.state.client_key.key_data()
+    }
+
+    fn open(&mut self, channel: russh::Channel<Msg>, forward: bool) -> Option<ChannelId> {
+        if self.channels.len() >= MAX_CHANNELS {
+            return None;
+        }
+        let id = channel.id();
+        self.channels.insert(id, Channel::new(channel, forward));
+        Some(id)
+    }
+
+    fn start(&mut self, id: ChannelId, start: Start) -> bool {
+        let Some(channel) = self.channels.get_mut(&id) else {
+            return false;
+        };
+        let Some(writer) = channel.writer.take() else {
+            return false;
+        };
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        channel.input = Some(input_tx);
+        tokio::spawn(run_session(
+            self.state.clone(),
+            self.peer,
+            start,
+            writer,
+            input_rx,
+            channel.closed.clone(),
+        ));
+        true
+    }
+
+    /// Start the shell, or `command` in it, on session channel `id`.
+    fn start_process(&mut self, id: ChannelId, command: Option<String>) -> bool {
+        let Some(channel) = self.channels.get_mut(&id).filter(|c| c.configurable()) else {
+            return false;
+        };
+        let args = match &command {
+            Some(command) => vec!["-c".to_string(), command.clone()],
+            None => vec!["-l".to_string()],
+        };
+        let pty = channel.pty;
+        let process = ProcessRequest {
+            program: self.state.shell.clone(),
+            args,
+            env: std::mem::take(&mut channel.env),
+            pty,
+        };
+        if !self.start(id, Start::Process(process)) {
+            return false;
+        }
+        self.state.audit.record(
+            Some(self.peer),
+            AuditEvent::SessionStarted {
+                channel: id.into(),
+                command,
+                pty: pty.is_some(),
+            },
+        );
+        true
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for channel in self.channels.values() {
+            channel.closed.cancel();
+        }
+    }
+}
+
+fn reply(session: &mut Session, id: ChannelId, ok: bool) -> Result<(), SshError> {
+    if ok {
+        session.channel_success(id)?;
+    } else {
+        session.channel_failure(id)?;
+    }
+    Ok(())
+}
+
+impl russh::server::Handler for Connection {
+    type Error = SshError;
+
+    async fn auth_password(&mut self, user: &str, _password: &str) -> Result<Auth, SshError> {
+        Ok(self.reject(user, "password"))
+    }
+
+    async fn auth_keyboard_interactive<'a>(
+        &'a mut self,
+        user: &str,
+        _submethods: &str,
+        _response: Option<Response<'a>>,
+    ) -> Result<Auth, SshError> {
+        Ok(self.reject(user, "keyboard-interactive"))
+    }
+
+    /// Called for a key before its signature is checked.
+    async fn auth_publickey_offered(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> Result<Auth, SshError> {
+        if self.accepts(public_key) {
+            return Ok(Auth::Accept);
+        }
+        Ok(self.reject(user, "publickey"))
+    }
+
+    /// Called once the client proved it holds the key.
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> Result<Auth, SshError> {
+        if !self.accepts(public_key) {
+            return Ok(self.reject(user, "publickey"));
+        }
+        self.authenticated.store(true, Ordering::Release);
+        self.state.audit.record(
+            Some(self.peer),
+            AuditEvent::AuthAccepted {
+                user: user.to_string(),
+                fingerprint: keys::fingerprint(public_key),
+            },
+        );
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: russh::Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, SshError> {
+        Ok(self.open(channel, false).is_some())
+    }
+
+    /// Local forwarding (`ssh -L`, `ssh -D`): a connection from the guest
+    /// to `host:port` (RFC 4254 §7.2).
+    async fn channel_open_direct_tcpip(
+        &mut self,
+        channel: russh::Channel<Msg>,
+        host: &str,
+        port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut Session,
+    ) -> Result<bool, SshError> {
+        let Ok(port) = u16::try_from(port) else {
+            return Ok(false);
+        };
+        let Some(id) = self.open(channel, true) else {
+            return Ok(false);
+        };
+        self.state.audit.record(
+            Some(self.peer),
+            AuditEvent::ForwardStarted {
+                channel: id.into(),
+                host: host.to_string(),
+                port,
+            },
+        );
+        let host = host.to_string();
+        Ok(self.start(id, Start::Connect { host, port }))
+    }
+
+    async fn channel_close(
+        &mut self,
+        id: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), SshError> {
+        if let Some(channel) = self.channels.remove(&id) {
+            channel.closed.cancel();
+        }
+        Ok(())
+    }
+
+    async fn channel_eof(&mut self, id: ChannelId, _session: &mut Session) -> Result<(), SshError> {
+        if let Some(channel) = self.channels.get(&id) {
+            channel.send(ProcessInput::Eof);
+        }
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        id: ChannelId,
+        data: &[u8],
+        _session: &mut Session,
+    ) -> Result<(), SshError> {
+        let Some(channel) = self.channels.get(&id) else {
+            return Ok(());
+        };
+        // Input for a process that is gone goes nowhere.
+        let Some(input) = channel.input.as_ref().filter(|tx| !tx.is_closed()) else {
+            return Ok(());
+        };
+        // `data` is at most `MAX_DATA` bytes, well under the budget.
+        let permit = channel
+            .pending
+            .clone()
+            .acquire_many_owned(data.len() as u32)
+            .await
+            .ok();
+        let _ = input.send((ProcessInput::Data(data.to_vec()), permit));
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn pty_request(
+        &mut self,
+        id: ChannelId,
+        term: &str,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        let ok = match self.channels.get_mut(&id).filter(|c| c.configurable()) {
+            Some(channel) => {
+                channel.pty = Some(pty_size(cols, rows));
+                channel.env.push(("TERM".to_string(), term.to_string()));
+                true
+            }
+            None => false,
+        };
+        reply(session, id, ok)
+    }
+
+    async fn env_request(
+        &mut self,
+        id: ChannelId,
+        name: &str,
+        value: &str,
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        let ok = match self.channels.get_mut(&id).filter(|c| c.configurable()) {
+            Some(channel) => {
+                channel.env.push((name.to_string(), value.to_string()));
+                true
+            }
+            None => false,
+        };
+        reply(session, id, ok)
+    }
+
+    async fn shell_request(
+        &mut self,
+        id: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        let ok = self.start_process(id, None);
+        reply(session, id, ok)
+    }
+
+    async fn exec_request(
+        &mut self,
+        id: ChannelId,
+        command: &[u8],
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        let command = String::from_utf8_lossy(command).into_owned();
+        let ok = self.start_process(id, Some(command));
+        reply(session, id, ok)
+    }
+
+    async fn subsystem_request(
+        &mut self,
+        id: ChannelId,
+        _name: &str,
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        reply(session, id, false)
+    }
+
+    async fn x11_request(
+        &mut self,
+        id: ChannelId,
+        _single_connection: bool,
+        _protocol: &str,
+        _cookie: &str,
+        _screen: u32,
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        reply(session, id, false)
+    }
+
+    async fn window_change_request(
+        &mut self,
+        id: ChannelId,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        session: &mut Session,
+    ) -> Result<(), SshError> {
+        let ok = match self.channels.get(&id).filter(|c| !c.forward) {
+            Some(channel) => {
+                channel.send(ProcessInput::Resize(pty_size(cols, rows)));
+                true
+            }
+            None => false,
+        };
+        reply(session, id, ok)
+    }
+
+    async fn signal(
+        &mut self,
+        id: ChannelId,
+        signal: Sig,
+        _session: &mut Session,
+    ) -> Result<(), SshError> {
+        let name = match &signal {
+            Sig::INT => "INT",
+            Sig::TERM => "TERM",
+            Sig::KILL => "KILL",
+            Sig::Custom(name) => name.as_str(),
+            _ => return Ok(()),
+        };
+        if let (Some(signal), Some(channel)) = (target::exec_signal(name), self.channels.get(&id)) {
+            channel.send(ProcessInput::Signal(signal));
+        }
+        Ok(())
+    }
+}
+
+fn pty_size(cols: u32, rows: u32) -> PtySize {
+    let clamp = |n: u32| n.clamp(1, u16::MAX as u32) as u16;
+    PtySize {
+        cols: clamp(cols),
+        rows: clamp(rows),
+    }
+}
+
+/// Start the guest process or connection for a channel and shuttle its
+/// input and output until it ends or the channel closes.
+async fn run_session(
+    state: Arc<BridgeState>,
+    peer: SocketAddr,
+    start: Start,
+    writer: ChannelWriteHalf<Msg>,
+    mut input_rx: mpsc::UnboundedReceiver<Input>,
+    closed: CancellationToken,
+) {
+    let id = u32::from(writer.id());
+    let forward = matches!(start, Start::Connect { .. });
+    let bytes_in = Arc::new(AtomicU64::new(0));
+    let mut bytes_out = 0;
+
+    let run = async {
+        let spawned = match start {
+            Start::Process(request) => {
+                tokio::time::timeout(SPAWN_TIMEOUT, state.target.clone().spawn(request)).await
+            }
+            Start::Connect { host, port } => {
+                tokio::time::timeout(SPAWN_TIMEOUT, state.target.clone().connect(host, port)).await
+            }
+        };
+        let GuestProcess { input, mut output } = match spawned {
+            Ok(Ok(process)) => process,
+            failed => {
+                let reason = match failed {
+                    Ok(Err(e)) => e.to_string(),
+                    _ => "timed out".to_string(),
+                };
+                tracing::warn!("ssh: cannot start session: {}", reason);
+                if !forward {
+                    let message = format!("voidbox: cannot start session: {reason}\r\n");
+                    let _ = writer
+                        .make_writer_ext(Some(1))
+                        .write_all(message.as_bytes())
+                        .await;
+                }
+                return Ok(None);
+            }
+        };
+
+        let consumed = bytes_in.clone();
+        tokio::spawn(async move {
+            while let Some((message, _permit)) = input_rx.recv().await {
+                if let ProcessInput::Data(data) = &message {
+                    consumed.fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+                if input.send(message).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut stdout = writer.make_writer_ext(None);
+        let mut stderr = writer.make_writer_ext(Some(1));
+        while let Some(message) = output.recv().await {
+            let (pipe, data) = match message {
+                ProcessOutput::Stdout(data) => (&mut stdout, data),
+                ProcessOutput::Stderr(data) => (&mut stderr, data),
+                ProcessOutput::Exit(code) => return Ok(Some(code)),
+            };
+            if !data.is_empty() {
+                pipe.write_all(&data).await?;
+                bytes_out += data.len() as u64;
+            }
+        }
+        Ok::<_, std::io::Error>(None)
+    };
+
+    let exit_code = tokio::select! {
+        _ = closed.cancelled() => None,
+        ended = run => match ended {
+            Ok(code) => {
+                if let (Some(code), false) = (code, forward) {
+                    let _ = writer.exit_status(code.rem_euclid(256) as u32).await;
+                }
+                let _ = writer.eof().await;
+                let _ = writer.close().await;
+                code
+            }
+            Err(_) => None,
+        },
+    };
+    state.audit.record(
+        Some(peer),
+        AuditEvent::SessionEnded {
+            channel: id,
+            exit_code,
+            bytes_in: bytes_in.load(Ordering::Relaxed),
+            bytes_out,
+        },
+    );
+}
@@ -0,0 +1,236 @@
+//! Editor attach files for a running [`SshBridge`](super::SshBridge).
+//!
+//! VS Code Remote - SSH (and editors that read `devcontainer.json`) open a
+//! sandbox's workspace through the bridge: the generated `ssh_config`
+//! names the `voidbox-<name>` host, and `devcontainer.json` points the
+//! editor at it, at the workspace folder and at settings that suit a
+//! sandbox (Linux, server downloaded on the host and sent over the
+//! connection, since the guest may have no network).
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use super::{ssh_config, SshAccess};
+
+/// Folder an editor opens by default: the guest's workspace.
+pub const DEFAULT_WORKSPACE_FOLDER: &str = "/workspace";
+
+/// Every bridge with an `access.json` under `root` (usually
+/// [`default_ssh_dir`](super::default_ssh_dir)), sorted by name. A bridge
+/// whose process died without stopping may still be listed.
+pub fn list_bridges(root: &Path) -> Vec<SshAccess> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut bridges: Vec<SshAccess> = entries
+        .flatten()
+        .filter_map(|entry| SshAccess::load(&entry.path()).ok())
+        .collect();
+    bridges.sort_by(|a, b| a.name.cmp(&b.name));
+    bridges
+}
+
+/// A `devcontainer.json` attaching to `access` with `workspace_folder`
+/// open. `ssh_config` is the config file the editor should read, usually
+/// the one [`write_attach_files`] writes next to it.
+pub fn devcontainer_json(
+    access: &SshAccess,
+    workspace_folder: &str,
+    ssh_config: &Path,
+) -> serde_json::Value {
+    json!({
+        "name": access.host_alias,
+        "workspaceFolder": workspace_folder,
+        "remoteUser": access.user,
+        "customizations": {
+            "vscode": {
+                "settings": {
+                    "remote.SSH.configFile": ssh_config,
+                    "remote.SSH.remotePlatform": { &access.host_alias: "linux" },
+                    "remote.SSH.localServerDownload": "always",
+                }
+            },
+            "voidbox": {
+                "sandbox": access.name,
+                "sshHost": access.host_alias,
+                "hostKeyFingerprint": access.host_key_fingerprint,
+                "auditLog": access.audit_log,
+            }
+        }
+    })
+}
+
+/// The URI that opens `folder` over the bridge:
+/// `code --folder-uri <uri>`.
+pub fn vscode_folder_uri(access: &SshAccess, folder: &str) -> String {
+    format!("vscode-remote://ssh-remote+{}{}", access.host_alias, folder)
+}
+
+/// Files [`write_attach_files`] wrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachFiles {
+    pub devcontainer_json: PathBuf,
+    pub ssh_config: PathBuf,
+}
+
+/// Write `devcontainer.json` and `ssh_config` for `access` into `dir`,
+/// replacing earlier ones. Rerun after the sandbox restarts: its port and
+/// keys change.
+pub fn write_attach_files(
+    access: &SshAccess,
+    dir: &Path,
+    workspace_folder: &str,
+) -> crate::Result<AttachFiles> {
+    std::fs::create_dir_all(dir)?;
+    let dir = std::path::absolute(dir)?;
+    let files = AttachFiles {
+        devcontainer_json: dir.join("devcontainer.json"),
+        ssh_config: dir.join("ssh_config"),
+    };
+    std::fs::write(&files.ssh_config, ssh_config(access))?;
+    let json = devcontainer_json(access, workspace_folder, &files.ssh_config);
+    std::fs::write(
+        &files.devcontainer_json,
+        serde_json::to_string_pretty(&json)? + "\n",
+    )?;
+    Ok(files)
+}
+
+/// Make `user_config` (usually `~/.ssh/config`) include `config`, so `ssh`
+/// and editors that read the user's config know the `voidbox-*` hosts.
+/// Returns `false` when the include is already there.
+///
+/// The line goes first in the file, where it applies to every host.
+/// OpenSSH skips an `Include` whose file does not exist, so the line is
+/// harmless once the bridge stops.
+pub fn install_include(user_config: &Path, config: &Path) -> std::io::Result<bool> {
+    // Write through a symlinked config (dotfile managers) rather than
+    // replacing the link.
+    let user_config = &std::fs::canonicalize(user_config).unwrap_or_else(|_| user_config.into());
+    let line = format!("Include \"{}\"", config.display());
+    let existing = match std::fs::read_to_string(user_config) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if existing.lines().any(|l| l.trim() == line) {
+        return Ok(false);
+    }
+    if let Some(parent) = user_config.parent() {
+        use std::os::unix::fs::DirBuilderExt;
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    }
+
+    // Write a sibling and rename it over, so a failure never leaves the
+    // user's config cut short.
+    let tmp = user_config.with_extension("voidbox-tmp");
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)?;
+        writeln!(file, "# Added by voidbox devcontainer\n{line}\n")?;
+        file.write_all(existing.as_bytes())?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, user_config)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(dir: &Path) -> SshAccess {
+        SshAccess {
+            name: "review".into(),
+            host: "127.0.0.1".into(),
+            port: 40022,
+            user: "sandbox".into(),
+            host_alias: "voidbox-review".into(),
+            identity_file: dir.join("id_ed25519"),
+            known_hosts_file: dir.join("known_hosts"),
+            config_file: dir.join("ssh_config"),
+            audit_log: dir.join("audit.jsonl"),
+            host_key_fingerprint: "SHA256:abc".into(),
+        }
+    }
+
+    #[test]
+    fn attach_files_point_at_the_bridge_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let access = access(&dir.path().join("bridge"));
+        let out = dir.path().join(".devcontainer");
+        let files = write_attach_files(&access, &out, "/workspace/app").unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&files.devcontainer_json).unwrap())
+                .unwrap();
+        assert_eq!(json["workspaceFolder"], "/workspace/app");
+        let settings = &json["customizations"]["vscode"]["settings"];
+        assert_eq!(
+            settings["remote.SSH.configFile"],
+            files.ssh_config.display().to_string()
+        );
+        assert_eq!(
+            settings["remote.SSH.remotePlatform"]["voidbox-review"],
+            "linux"
+        );
+        assert!(std::fs::read_to_string(&files.ssh_config)
+            .unwrap()
+            .starts_with("Host voidbox-review\n"));
+        assert_eq!(
+            vscode_folder_uri(&access, "/workspace"),
+            "vscode-remote://ssh-remote+voidbox-review/workspace"
+        );
+    }
+
+    #[test]
+    fn include_is_prepended_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_config = dir.path().join(".ssh/config");
+        let include = dir.path().join("bridge/ssh_config");
+        assert!(install_include(&user_config, &include).unwrap());
+        std::fs::write(
+            &user_config,
+            std::fs::read_to_string(&user_config).unwrap() + "Host other\n  Port 2200\n",
+        )
+        .unwrap();
+        assert!(!install_include(&user_config, &include).unwrap());
+
+        let contents = std::fs::read_to_string(&user_config).unwrap();
+        assert_eq!(contents.matches("Include").count(), 1);
+        assert!(contents.starts_with("# Added by voidbox devcontainer\nInclude \""));
+        assert!(contents.ends_with("Host other\n  Port 2200\n"));
+    }
+
+    #[test]
+    fn lists_bridges_with_access_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b", "a"] {
+            let bridge = dir.path().join(name);
+            std::fs::create_dir(&bridge).unwrap();
+            let mut access = access(&bridge);
+            access.name = name.into();
+            std::fs::write(
+                bridge.join("access.json"),
+                serde_json::to_vec(&access).unwrap(),
+            )
+            .unwrap();
+        }
+        std::fs::create_dir(dir.path().join("stopped")).unwrap();
+        let names: Vec<String> = list_bridges(dir.path())
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+}
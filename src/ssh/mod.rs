@@ -0,0 +1,443 @@
+//! SSH access to a running sandbox, for developers who want to look inside
+//! with their usual tools.
+//!
+//! [`SshBridge`] runs an SSH server on the host, by default on loopback.
+//! Each session it accepts runs in the guest over the same vsock channel
+//! the rest of the runtime uses: a session with a terminal gets a guest PTY
+//! (as `voidbox shell` does), `ssh host command` gets a streamed exec. No
+//! SSH daemon runs in the guest and the guest needs no network.
+//!
+//! Every bridge mints its own host key and client key when it starts and
+//! accepts only that client key. The keys, a `known_hosts` entry and an
+//! `ssh_config` block are written to the bridge directory:
+//!
+//! ```text
+//! ~/.void-box/ssh/<name>/
+//!   id_ed25519        client key, mode 0600
+//!   id_ed25519.pub
+//!   known_hosts       the bridge's host key, pinned
+//!   ssh_config        `Host voidbox-<name>`, for `ssh -F`
+//!   access.json       the same, for tools; see [`SshAccess`]
+//!   audit.jsonl       connections, logins and sessions; see [`audit`]
+//! ```
+//!
+//! so `ssh -F ~/.void-box/ssh/<name>/ssh_config voidbox-<name>` logs in
+//! with host key checking on. Stopping the bridge deletes everything but
+//! the audit log, so the client key is useless once the sandbox is gone.
+//!
+//! The SSH protocol itself (key exchange, rekeying, encryption and the
+//! signature check on login) is [`russh`]'s; this module decides who may
+//! log in and what a channel runs (see [`connection`]). Besides sessions
+//! the server takes local port forwards (`ssh -L`, `ssh -D`), which
+//! [`SshTarget::connect`] opens from inside the guest; remote forwarding,
+//! agent forwarding and subsystems such as SFTP are refused.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use russh::keys::PublicKey;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+pub mod audit;
+mod connection;
+pub mod devcontainer;
+mod keys;
+pub mod target;
+
+pub use audit::{AuditEvent, AuditLog};
+pub use target::{GuestProcess, ProcessInput, ProcessOutput, ProcessRequest, PtySize, SshTarget};
+
+use keys::Ed25519Key;
+
+/// Concurrent connections one bridge serves.
+const MAX_CONNECTIONS: usize = 16;
+
+/// User name written to the generated `ssh_config`. The bridge accepts any
+/// user name; sessions run as the guest's own user.
+const SSH_USER: &str = "sandbox";
+
+/// Errors inside the SSH server. They end a connection, never the bridge.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SshError {
+    #[error(transparent)]
+    Protocol(#[from] russh::Error),
+    #[error(transparent)]
+    Key(#[from] russh::keys::ssh_key::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<SshError> for crate::Error {
+    fn from(e: SshError) -> Self {
+        match e {
+            SshError::Io(e) => crate::Error::Io(e),
+            e => crate::Error::Network(format!("ssh: {e}")),
+        }
+    }
+}
+
+/// Default parent directory for bridge directories: `$VOIDBOX_HOME/ssh` or
+/// `~/.void-box/ssh`.
+pub fn default_ssh_dir() -> PathBuf {
+    if let Ok(home) = std::env::var("VOIDBOX_HOME") {
+        return PathBuf::from(home).join("ssh");
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".void-box").join("ssh")
+}
+
+/// How to start an [`SshBridge`].
+#[derive(Debug, Clone)]
+pub struct SshBridgeConfig {
+    /// Address to listen on. Default: `127.0.0.1:0`, a free loopback port.
+    pub listen: SocketAddr,
+    /// Names the bridge directory and the `voidbox-<name>` host alias.
+    /// Characters other than letters, digits, `.`, `_` and `-` become `-`.
+    pub name: String,
+    /// Bridge directory. Default: `<default_ssh_dir()>/<name>`.
+    pub dir: Option<PathBuf>,
+    /// Shell for sessions that ask for no command. Default: `/bin/sh`.
+    pub shell: String,
+}
+
+impl SshBridgeConfig {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+            name: name.into(),
+            dir: None,
+            shell: "/bin/sh".to_string(),
+        }
+    }
+
+    pub fn listen(mut self, addr: SocketAddr) -> Self {
+        self.listen = addr;
+        self
+    }
+
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    pub fn shell(mut self, shell: impl Into<String>) -> Self {
+        self.shell = shell.into();
+        self
+    }
+}
+
+/// How to reach a bridge: what `access.json` holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshAccess {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    /// `Host` alias in `config_file`
+    pub host_alias: String,
+    pub identity_file: PathBuf,
+    pub known_hosts_file: PathBuf,
+    pub config_file: PathBuf,
+    pub audit_log: PathBuf,
+    /// `SHA256:...`, as `ssh-keygen -l` prints it
+    pub host_key_fingerprint: String,
+}
+
+impl SshAccess {
+    /// Read `access.json` from a running bridge's directory.
+    pub fn load(dir: &Path) -> crate::Result<Self> {
+        let path = dir.join("access.json");
+        let data = std::fs::read(&path).map_err(|e| {
+            crate::Error::Config(format!("no SSH bridge at {}: {}", dir.display(), e))
+        })?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// The command that logs in: `ssh -F <config_file> <host_alias>`.
+    pub fn ssh_command(&self) -> String {
+        format!("ssh -F {} {}", self.config_file.display(), self.host_alias)
+    }
+}
+
+/// What every connection of one bridge shares.
+pub(crate) struct BridgeState {
+    pub(crate) target: Arc<dyn SshTarget>,
+    /// Server settings, holding the host key.
+    pub(crate) config: Arc<russh::server::Config>,
+    /// The only client key accepted.
+    pub(crate) client_key: PublicKey,
+    pub(crate) shell: String,
+    pub(crate) audit: AuditLog,
+}
+
+/// A running SSH server bridging sessions into one sandbox.
+///
+/// Dropping the bridge stops it and deletes its keys.
+pub struct SshBridge {
+    access: SshAccess,
+    dir: PathBuf,
+    local_addr: SocketAddr,
+    state: Arc<BridgeState>,
+    cancel: CancellationToken,
+}
+
+impl SshBridge {
+    /// Mint keys, write the bridge directory and start listening.
+    pub async fn start(target: Arc<dyn SshTarget>, config: SshBridgeConfig) -> crate::Result<Self> {
+        let name = safe_name(&config.name);
+        let dir = config
+            .dir
+            .clone()
+            .unwrap_or_else(|| default_ssh_dir().join(&name));
+        create_private_dir(&dir)?;
+
+        let listener = TcpListener::bind(config.listen).await?;
+        let local_addr = listener.local_addr()?;
+        let host_alias = format!("voidbox-{name}");
+        let host_key = Ed25519Key::generate(&host_alias)?;
+        let client_key = Ed25519Key::generate(&host_alias)?;
+        let access = SshAccess {
+            name: name.clone(),
+            host: local_addr.ip().to_string(),
+            port: local_addr.port(),
+            user: SSH_USER.to_string(),
+            host_alias,
+            identity_file: dir.join("id_ed25519"),
+            known_hosts_file: dir.join("known_hosts"),
+            config_file: dir.join("ssh_config"),
+            audit_log: dir.join("audit.jsonl"),
+            host_key_fingerprint: keys::fingerprint(host_key.public_key()),
+        };
+        write_access_files(&access, &host_key, &client_key)?;
+
+        let audit = AuditLog::open(&access.audit_log, &name)?;
+        audit.record(None, AuditEvent::Listening { addr: local_addr });
+        let state = Arc::new(BridgeState {
+            target,
+            config: Arc::new(connection::config(&host_key)),
+            client_key: client_key.public_key().clone(),
+            shell: config.shell,
+            audit,
+        });
+        let cancel = CancellationToken::new();
+        tokio::spawn(accept_loop(listener, state.clone(), cancel.clone()));
+
+        Ok(Self {
+            access,
+            dir,
+            local_addr,
+            state,
+            cancel,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn access(&self) -> &SshAccess {
+        &self.access
+    }
+
+    /// Stop accepting, close every connection and delete the keys.
+    pub fn stop(&self) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        self.cancel.cancel();
+        for path in [
+            &self.access.identity_file,
+            &self.dir.join("id_ed25519.pub"),
+            &self.access.known_hosts_file,
+            &self.access.config_file,
+            &self.dir.join("access.json"),
+        ] {
+            let _ = std::fs::remove_file(path);
+        }
+        self.state.audit.record(None, AuditEvent::Stopped);
+    }
+}
+
+impl Drop for SshBridge {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn accept_loop(listener: TcpListener, state: Arc<BridgeState>, cancel: CancellationToken) {
+    let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = cancel.cancelled() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("ssh: accept failed: {}", e);
+                    continue;
+                }
+            },
+        };
+        let Ok(slot) = slots.clone().try_acquire_owned() else {
+            tracing::warn!("ssh: refusing {}: too many connections", peer);
+            continue;
+        };
+        let state = state.clone();
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            let _slot = slot;
+            state.audit.record(Some(peer), AuditEvent::Connected);
+            let reason = match connection::serve(stream, peer, &state, cancel).await {
+                Ok(()) => "closed".to_string(),
+                Err(e) => e.to_string(),
+            };
+            state
+                .audit
+                .record(Some(peer), AuditEvent::Disconnected { reason });
+        });
+    }
+}
+
+/// `name` with anything that could upset a path or an `ssh_config` `Host`
+/// pattern replaced by `-`.
+fn safe_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '-',
+        })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "sandbox".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+fn write_file(path: &Path, contents: &str, mode: u32) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    // Replace rather than truncate, so a key left by an earlier bridge
+    // never keeps its old mode.
+    let _ = std::fs::remove_file(path);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+fn write_access_files(
+    access: &SshAccess,
+    host_key: &Ed25519Key,
+    client_key: &Ed25519Key,
+) -> crate::Result<()> {
+    let dir = access
+        .identity_file
+        .parent()
+        .expect("identity file is inside the bridge directory");
+    write_file(
+        &access.identity_file,
+        client_key.openssh_private()?.expose_secret(),
+        0o600,
+    )?;
+    write_file(
+        &dir.join("id_ed25519.pub"),
+        &format!("{}\n", client_key.openssh_public()?),
+        0o644,
+    )?;
+    write_file(
+        &access.known_hosts_file,
+        &format!(
+            "{} {}\n",
+            known_hosts_host(&access.host, access.port),
+            host_key.openssh_public()?
+        ),
+        0o644,
+    )?;
+    write_file(&access.config_file, &ssh_config(access), 0o644)?;
+    write_file(
+        &dir.join("access.json"),
+        &serde_json::to_string_pretty(access)?,
+        0o600,
+    )?;
+    Ok(())
+}
+
+/// The host as `known_hosts` spells it: bracketed with the port unless
+/// the port is 22.
+fn known_hosts_host(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+fn ssh_config(access: &SshAccess) -> String {
+    format!(
+        "Host {alias}\n\
+         \x20 HostName {host}\n\
+         \x20 Port {port}\n\
+         \x20 User {user}\n\
+         \x20 IdentityFile \"{identity}\"\n\
+         \x20 IdentitiesOnly yes\n\
+         \x20 UserKnownHostsFile \"{known_hosts}\"\n\
+         \x20 StrictHostKeyChecking yes\n",
+        alias = access.host_alias,
+        host = access.host,
+        port = access.port,
+        user = access.user,
+        identity = access.identity_file.display(),
+        known_hosts = access.known_hosts_file.display(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_safe_for_paths_and_host_patterns() {
+        assert_eq!(safe_name("review-box_2.1"), "review-box_2.1");
+        assert_eq!(safe_name("my box/../*"), "my-box-..--");
+        assert_eq!(safe_name("..."), "sandbox");
+    }
+
+    #[test]
+    fn ssh_config_pins_the_host_key() {
+        let dir = Path::new("/tmp/bridge dir");
+        let access = SshAccess {
+            name: "box".into(),
+            host: "127.0.0.1".into(),
+            port: 2222,
+            user: SSH_USER.into(),
+            host_alias: "voidbox-box".into(),
+            identity_file: dir.join("id_ed25519"),
+            known_hosts_file: dir.join("known_hosts"),
+            config_file: dir.join("ssh_config"),
+            audit_log: dir.join("audit.jsonl"),
+            host_key_fingerprint: "SHA256:x".into(),
+        };
+        let config = ssh_config(&access);
+        assert!(config.starts_with("Host voidbox-box\n  HostName 127.0.0.1\n  Port 2222\n"));
+        assert!(config.contains("  IdentityFile \"/tmp/bridge dir/id_ed25519\"\n"));
+        assert!(config.contains("  StrictHostKeyChecking yes\n"));
+        assert_eq!(known_hosts_host("127.0.0.1", 2222), "[127.0.0.1]:2222");
+        assert_eq!(known_hosts_host("10.0.0.1", 22), "10.0.0.1");
+    }
+}
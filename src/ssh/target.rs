@@ -0,0 +1,285 @@
+//! What an SSH session runs: a process in the guest.
+//!
+//! [`SshTarget`] is implemented for [`Sandbox`]: sessions with a terminal
+//! get a guest PTY (the same one `voidbox shell` uses), sessions without one
+//! get a streamed exec with separate stdout and stderr, so `ssh host cmd`
+//! pipes binary data unchanged.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use crate::backend::pty_session::PtyEvent;
+use crate::guest::protocol::{ExecSignal, PtyOpenRequest};
+use crate::sandbox::Sandbox;
+use crate::Result;
+
+/// Queued input or output messages per process before the sender waits.
+const QUEUE_DEPTH: usize = 16;
+
+/// Terminal size in character cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtySize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// A process to start for a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessRequest {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Run under a terminal of this size
+    pub pty: Option<PtySize>,
+}
+
+/// Input from the SSH client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessInput {
+    Data(Vec<u8>),
+    /// The client will send no more data.
+    Eof,
+    Resize(PtySize),
+    Signal(ExecSignal),
+}
+
+/// Output for the SSH client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessOutput {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    /// The process exited; nothing follows.
+    Exit(i32),
+}
+
+/// A running process. Dropping `input` ends it.
+pub struct GuestProcess {
+    pub input: mpsc::Sender<ProcessInput>,
+    pub output: mpsc::Receiver<ProcessOutput>,
+}
+
+/// Starts the processes SSH sessions talk to.
+#[async_trait]
+pub trait SshTarget: Send + Sync {
+    async fn spawn(self: Arc<Self>, request: ProcessRequest) -> Result<GuestProcess>;
+
+    /// Open a TCP connection from inside the guest, for a forwarded port:
+    /// data written to `input` goes to `host:port`, what comes back is
+    /// `Stdout`. The default relays through `nc` in the guest, so the
+    /// guest's own network rules apply.
+    async fn connect(self: Arc<Self>, host: String, port: u16) -> Result<GuestProcess> {
+        self.spawn(ProcessRequest {
+            program: "nc".to_string(),
+            args: vec![host, port.to_string()],
+            env: Vec::new(),
+            pty: None,
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl SshTarget for Sandbox {
+    async fn spawn(self: Arc<Self>, request: ProcessRequest) -> Result<GuestProcess> {
+        match request.pty {
+            Some(size) => spawn_pty(&self, request, size).await,
+            None => spawn_exec(self, request).await,
+        }
+    }
+}
+
+async fn spawn_pty(
+    sandbox: &Sandbox,
+    request: ProcessRequest,
+    size: PtySize,
+) -> Result<GuestProcess> {
+    let session = sandbox
+        .attach_pty(PtyOpenRequest {
+            cols: size.cols,
+            rows: size.rows,
+            program: request.program,
+            args: request.args,
+            env: request.env,
+            working_dir: None,
+            interactive: true,
+        })
+        .await?;
+    let (mut reader, mut writer) = session.split()?;
+    let (input_tx, mut input_rx) = mpsc::channel(QUEUE_DEPTH);
+    let (output_tx, output_rx) = mpsc::channel(QUEUE_DEPTH);
+
+    tokio::task::spawn_blocking(move || loop {
+        let output = match reader.next_event() {
+            Ok(PtyEvent::Data(data)) => ProcessOutput::Stdout(data),
+            Ok(PtyEvent::Closed(exit_code)) => {
+                let _ = output_tx.blocking_send(ProcessOutput::Exit(exit_code));
+                return;
+            }
+            Err(e) => {
+                tracing::debug!("ssh: PTY session ended: {}", e);
+                return;
+            }
+        };
+        if output_tx.blocking_send(output).is_err() {
+            return;
+        }
+    });
+    // Dropping the writer when the client goes away hangs up the PTY.
+    tokio::task::spawn_blocking(move || {
+        while let Some(input) = input_rx.blocking_recv() {
+            let result = match input {
+                ProcessInput::Data(data) => writer.write(&data),
+                ProcessInput::Resize(size) => writer.resize(size.cols, size.rows),
+                // The terminal line discipline turns ^C and ^D into signals
+                // and end-of-file; there is no separate channel for them.
+                ProcessInput::Eof | ProcessInput::Signal(_) => Ok(()),
+            };
+            if let Err(e) = result {
+                tracing::debug!("ssh: PTY write failed: {}", e);
+                return;
+            }
+        }
+    });
+
+    Ok(GuestProcess {
+        input: input_tx,
+        output: output_rx,
+    })
+}
+
+async fn spawn_exec(sandbox: Arc<Sandbox>, request: ProcessRequest) -> Result<GuestProcess> {
+    let (program, args) = with_env(request.program, request.args, &request.env);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (stdin, mut chunks, response) = sandbox
+        .exec_streaming_with_stdin(&program, &args, None)
+        .await?;
+    let exec_id = stdin.exec_id().to_string();
+    let (input_tx, mut input_rx) = mpsc::channel(QUEUE_DEPTH);
+    let (output_tx, output_rx) = mpsc::channel(QUEUE_DEPTH);
+
+    tokio::spawn(async move {
+        while let Some(chunk) = chunks.recv().await {
+            let output = match chunk.stream.as_str() {
+                "stderr" => ProcessOutput::Stderr(chunk.data),
+                _ => ProcessOutput::Stdout(chunk.data),
+            };
+            if output_tx.send(output).await.is_err() {
+                return;
+            }
+        }
+        let exit_code = match response.await {
+            Ok(Ok(response)) => response.exit_code,
+            _ => -1,
+        };
+        let _ = output_tx.send(ProcessOutput::Exit(exit_code)).await;
+    });
+    tokio::spawn(async move {
+        let mut stdin = Some(stdin);
+        while let Some(input) = input_rx.recv().await {
+            match input {
+                ProcessInput::Data(data) => {
+                    if let Some(writer) = stdin.as_mut() {
+                        if writer.write(&data).await.is_err() {
+                            stdin = None;
+                        }
+                    }
+                }
+                ProcessInput::Eof => {
+                    if let Some(writer) = stdin.take() {
+                        let _ = writer.close().await;
+                    }
+                }
+                ProcessInput::Signal(signal) => {
+                    let _ = sandbox.signal(&exec_id, signal).await;
+                }
+                ProcessInput::Resize(_) => {}
+            }
+        }
+        // The session is gone; don't leave the command running. This fails
+        // harmlessly when it already exited.
+        let _ = sandbox.signal(&exec_id, ExecSignal::Kill).await;
+    });
+
+    Ok(GuestProcess {
+        input: input_tx,
+        output: output_rx,
+    })
+}
+
+/// Execs carry no environment of their own, so variables the client sent
+/// are exported by a `sh` wrapper that then execs the program.
+fn with_env(program: String, args: Vec<String>, env: &[(String, String)]) -> (String, Vec<String>) {
+    let exports: Vec<String> = env
+        .iter()
+        .filter(|(name, _)| is_env_name(name))
+        .map(|(name, value)| format!("export {name}={}; ", shell_quote(value)))
+        .collect();
+    if exports.is_empty() {
+        return (program, args);
+    }
+    let mut wrapped = vec![
+        "-c".to_string(),
+        format!("{}exec \"$0\" \"$@\"", exports.concat()),
+        program,
+    ];
+    wrapped.extend(args);
+    ("/bin/sh".to_string(), wrapped)
+}
+
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// `ExecSignal` for an SSH signal name (RFC 4254 §6.10).
+pub(crate) fn exec_signal(name: &str) -> Option<ExecSignal> {
+    match name {
+        "INT" => Some(ExecSignal::Interrupt),
+        "TERM" => Some(ExecSignal::Terminate),
+        "KILL" => Some(ExecSignal::Kill),
+        "STOP" => Some(ExecSignal::Stop),
+        "CONT" => Some(ExecSignal::Continue),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_is_exported_by_a_shell_wrapper() {
+        let (program, args) = with_env(
+            "/bin/sh".into(),
+            vec!["-c".into(), "echo $LANG".into()],
+            &[
+                ("LANG".into(), "C.UTF-8".into()),
+                ("BAD NAME".into(), "x".into()),
+                ("QUOTE".into(), "it's".into()),
+            ],
+        );
+        assert_eq!(program, "/bin/sh");
+        assert_eq!(
+            args,
+            [
+                "-c",
+                "export LANG='C.UTF-8'; export QUOTE='it'\\''s'; exec \"$0\" \"$@\"",
+                "/bin/sh",
+                "-c",
+                "echo $LANG",
+            ]
+        );
+
+        let (program, args) = with_env("true".into(), Vec::new(), &[]);
+        assert_eq!((program.as_str(), args.len()), ("true", 0));
+    }
+}
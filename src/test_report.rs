@@ -0,0 +1,217 @@
+//! Structured test results reported from inside the guest.
+//!
+//! A test adapter in the guest (`void-test`) appends one case per line to the
+//! file named by [`TEST_REPORT_ENV`]. The guest-agent returns the cases with
+//! the exec's response, and they surface as
+//! [`ExecOutput::test_report`](crate::ExecOutput::test_report) and
+//! [`StepOutput::test_report`](crate::workflow::StepOutput::test_report).
+//! [`TestReport::to_junit_xml`] renders them for CI systems.
+
+use std::path::Path;
+
+pub use crate::guest::protocol::{TestCaseResult, TestStatus, TEST_REPORT_ENV};
+
+/// Test cases reported by one or more execs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestReport {
+    /// Cases in the order they were reported
+    pub cases: Vec<TestCaseResult>,
+}
+
+impl TestReport {
+    /// A report of `cases`, or `None` when nothing was reported.
+    pub fn from_cases(cases: Vec<TestCaseResult>) -> Option<Self> {
+        (!cases.is_empty()).then_some(Self { cases })
+    }
+
+    /// Append the cases of `other`.
+    pub fn merge(&mut self, other: TestReport) {
+        self.cases.extend(other.cases);
+    }
+
+    /// Number of cases with `status`.
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.cases.iter().filter(|c| c.status == status).count()
+    }
+
+    /// True when no case failed or errored.
+    pub fn success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Cases that failed or errored.
+    pub fn failures(&self) -> impl Iterator<Item = &TestCaseResult> {
+        self.cases
+            .iter()
+            .filter(|c| matches!(c.status, TestStatus::Failed | TestStatus::Error))
+    }
+
+    /// Render as JUnit XML, one `<testsuite>` per suite in first-seen order.
+    pub fn to_junit_xml(&self) -> String {
+        let mut suites: Vec<(&str, Vec<&TestCaseResult>)> = Vec::new();
+        for case in &self.cases {
+            match suites.iter_mut().find(|(name, _)| *name == case.suite) {
+                Some((_, cases)) => cases.push(case),
+                None => suites.push((&case.suite, vec![case])),
+            }
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites{}>\n",
+            counts_attrs(self.cases.iter())
+        ));
+        for (name, cases) in suites {
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\"{}>\n",
+                escape(name),
+                counts_attrs(cases.iter().copied())
+            ));
+            for case in cases {
+                xml.push_str(&format!(
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                    escape(&case.name),
+                    escape(&case.suite),
+                    seconds(case.duration_ms.unwrap_or(0.0))
+                ));
+                let element = match case.status {
+                    TestStatus::Passed => {
+                        xml.push_str("/>\n");
+                        continue;
+                    }
+                    TestStatus::Failed => "failure",
+                    TestStatus::Error => "error",
+                    TestStatus::Skipped => "skipped",
+                };
+                xml.push_str(">\n");
+                match case.message.as_deref() {
+                    Some(message) => xml.push_str(&format!(
+                        "      <{element} message=\"{}\">{}</{element}>\n",
+                        escape(message.lines().next().unwrap_or_default()),
+                        escape(message)
+                    )),
+                    None => xml.push_str(&format!("      <{element}/>\n")),
+                }
+                xml.push_str("    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    /// Write [`to_junit_xml`](Self::to_junit_xml) to `path`.
+    pub fn write_junit(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_junit_xml())
+    }
+}
+
+/// `tests`/`failures`/`errors`/`skipped`/`time` attributes over `cases`.
+fn counts_attrs<'a>(cases: impl Iterator<Item = &'a TestCaseResult>) -> String {
+    let (mut tests, mut failures, mut errors, mut skipped, mut ms) = (0, 0, 0, 0, 0.0);
+    for case in cases {
+        tests += 1;
+        ms += case.duration_ms.unwrap_or(0.0);
+        match case.status {
+            TestStatus::Passed => {}
+            TestStatus::Failed => failures += 1,
+            TestStatus::Error => errors += 1,
+            TestStatus::Skipped => skipped += 1,
+        }
+    }
+    format!(
+        " tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" time=\"{}\"",
+        seconds(ms)
+    )
+}
+
+fn seconds(ms: f64) -> String {
+    format!("{:.3}", ms / 1000.0)
+}
+
+/// Escape text for an XML attribute or element, dropping characters XML 1.0
+/// cannot carry.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' | '\r' | '\t' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(suite: &str, name: &str, status: TestStatus, message: Option<&str>) -> TestCaseResult {
+        TestCaseResult {
+            suite: suite.into(),
+            name: name.into(),
+            status,
+            duration_ms: Some(1500.0),
+            message: message.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn from_cases_is_none_when_empty() {
+        assert_eq!(TestReport::from_cases(Vec::new()), None);
+    }
+
+    #[test]
+    fn counts_and_success() {
+        let mut report = TestReport::from_cases(vec![
+            case("a", "one", TestStatus::Passed, None),
+            case("a", "two", TestStatus::Skipped, None),
+        ])
+        .unwrap();
+        assert!(report.success());
+
+        report.merge(TestReport {
+            cases: vec![case("b", "three", TestStatus::Error, Some("setup"))],
+        });
+        assert!(!report.success());
+        assert_eq!(report.count(TestStatus::Passed), 1);
+        assert_eq!(report.count(TestStatus::Error), 1);
+        assert_eq!(report.failures().count(), 1);
+    }
+
+    #[test]
+    fn junit_groups_suites_and_escapes() {
+        let report = TestReport {
+            cases: vec![
+                case("tests/api.rs", "ok", TestStatus::Passed, None),
+                case(
+                    "pkg",
+                    "cmp<1>",
+                    TestStatus::Failed,
+                    Some("left != \"right\"\nmore"),
+                ),
+                case("tests/api.rs", "later", TestStatus::Skipped, None),
+            ],
+        };
+        let xml = report.to_junit_xml();
+
+        assert!(xml.contains(
+            "<testsuites tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"4.500\">"
+        ));
+        assert!(xml.contains(
+            "<testsuite name=\"tests/api.rs\" tests=\"2\" failures=\"0\" errors=\"0\" skipped=\"1\" time=\"3.000\">"
+        ));
+        assert!(xml.contains("<testcase name=\"cmp&lt;1&gt;\" classname=\"pkg\" time=\"1.500\">"));
+        assert!(xml.contains(
+            "<failure message=\"left != &quot;right&quot;\">left != &quot;right&quot;\nmore</failure>"
+        ));
+        assert!(xml.contains("<skipped/>"));
+        assert!(xml.find("name=\"tests/api.rs\"").unwrap() < xml.find("name=\"pkg\"").unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+//! Host-side approval of agent tool calls.
+//!
+//! A [`ToolHook`] set with
+//! [`VoidBox::on_tool_use`](crate::agent_box::VoidBox::on_tool_use) sees
+//! every tool call the agent makes, as the stream-json event arrives, and
+//! decides what happens next:
+//!
+//! - [`ToolDecision::Allow`] lets the agent carry on.
+//! - [`ToolDecision::Deny`] kills the agent; the run fails with
+//!   [`Error::ToolDenied`](crate::Error::ToolDenied).
+//! - [`ToolDecision::Pause`] stops the agent's process group in the guest
+//!   (`SIGSTOP`) until the given future settles on allow or deny, e.g. once
+//!   a human has answered an approval prompt. Time spent paused counts
+//!   toward the exec timeout.
+//!
+//! claude-code reports a tool call just before running it, so a fast tool
+//! may already have started when the stop lands. The hook is an approval
+//! workflow, not a sandbox boundary: pair it with the guest command
+//! allowlist for hard guarantees. Hooks apply to agent runners that
+//! [stream tool calls](crate::agent_runner::AgentRunner::streams_tool_calls)
+//! and to OpenAI-compatible providers; Codex does not stream tool calls. For
+//! OpenAI-compatible providers the agent loop runs on the host, so a pause
+//! holds the loop and the call is decided before it runs.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::guest::protocol::ExecSignal;
+use crate::observe::claude::ClaudeToolCall;
+
+/// What to do about one tool call.
+pub enum ToolDecision {
+    /// Let the agent run the tool.
+    Allow,
+    /// Stop the agent.
+    Deny { reason: String },
+    /// Hold the agent until the future decides. A future that resolves to
+    /// `Pause` again keeps it held.
+    Pause(Pin<Box<dyn Future<Output = ToolDecision> + Send>>),
+}
+
+impl ToolDecision {
+    pub fn deny(reason: impl Into<String>) -> Self {
+        ToolDecision::Deny {
+            reason: reason.into(),
+        }
+    }
+
+    /// Hold the agent until `decision` resolves.
+    pub fn pause<F>(decision: F) -> Self
+    where
+        F: Future<Output = ToolDecision> + Send + 'static,
+    {
+        ToolDecision::Pause(Box::pin(decision))
+    }
+}
+
+impl fmt::Debug for ToolDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolDecision::Allow => f.write_str("Allow"),
+            ToolDecision::Deny { reason } => {
+                f.debug_struct("Deny").field("reason", reason).finish()
+            }
+            ToolDecision::Pause(_) => f.write_str("Pause"),
+        }
+    }
+}
+
+/// Callback deciding on each tool call an agent makes.
+#[derive(Clone)]
+pub struct ToolHook(Arc<dyn Fn(&ClaudeToolCall) -> ToolDecision + Send + Sync>);
+
+impl ToolHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&ClaudeToolCall) -> ToolDecision + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+
+    pub fn decide(&self, call: &ClaudeToolCall) -> ToolDecision {
+        (self.0)(call)
+    }
+}
+
+impl fmt::Debug for ToolHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ToolHook")
+    }
+}
+
+/// Wait for `decision` to settle, holding the agent with `signal` while it
+/// is paused. Returns the reason if the call was denied, after the agent
+/// has been killed.
+pub(crate) async fn settle<F, Fut>(mut decision: ToolDecision, mut signal: F) -> Option<String>
+where
+    F: FnMut(ExecSignal) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut paused = false;
+    loop {
+        match decision {
+            ToolDecision::Allow => {
+                if paused {
+                    signal(ExecSignal::Continue).await;
+                }
+                return None;
+            }
+            ToolDecision::Deny { reason } => {
+                signal(ExecSignal::Kill).await;
+                return Some(reason);
+            }
+            ToolDecision::Pause(pending) => {
+                if !paused {
+                    signal(ExecSignal::Stop).await;
+                    paused = true;
+                }
+                decision = pending.await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    async fn signals_for(decision: ToolDecision) -> (Option<String>, Vec<ExecSignal>) {
+        let sent = Mutex::new(Vec::new());
+        let denied = settle(decision, |signal| {
+            sent.lock().unwrap().push(signal);
+            std::future::ready(())
+        })
+        .await;
+        (denied, sent.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_settle_holds_agent_while_paused() {
+        assert_eq!(signals_for(ToolDecision::Allow).await, (None, vec![]));
+
+        let approved =
+            ToolDecision::pause(async { ToolDecision::pause(async { ToolDecision::Allow }) });
+        assert_eq!(
+            signals_for(approved).await,
+            (None, vec![ExecSignal::Stop, ExecSignal::Continue])
+        );
+
+        let rejected = ToolDecision::pause(async { ToolDecision::deny("not on a Friday") });
+        assert_eq!(
+            signals_for(rejected).await,
+            (
+                Some("not on a Friday".to_string()),
+                vec![ExecSignal::Stop, ExecSignal::Kill]
+            )
+        );
+    }
+}
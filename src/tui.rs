@@ -0,0 +1,736 @@
+//! Terminal dashboard for live sandbox monitoring (feature `voidbox-tui`).
+//!
+//! [`Dashboard`] is `top` for an agent sandbox: it subscribes to the
+//! sandbox's [`SandboxEvent`]s and its guest telemetry and redraws, a few
+//! times a second, the guest CPU and memory, the execs in flight and
+//! recently finished, the output of the current exec, the agent's recent
+//! tool calls and a waterfall of the latest trace. Press `q` or `Esc` to
+//! leave.
+//!
+//! Exec output is not part of the sandbox event stream; forward the chunks
+//! of an [`exec_streaming`](Sandbox::exec_streaming) call through an
+//! [`OutputFeed`] to show them. Tool calls and the trace come from the
+//! sandbox's [`Observer`], or the one given to [`Dashboard::observer`].
+//!
+//! ```no_run
+//! use void_box::sandbox::Sandbox;
+//! use void_box::tui::Dashboard;
+//!
+//! # async fn run() -> void_box::Result<()> {
+//! let sandbox = Sandbox::mock().build()?;
+//! let dashboard = Dashboard::new(sandbox.clone());
+//! let feed = dashboard.output_feed();
+//! tokio::spawn(async move {
+//!     let (mut chunks, _done) = sandbox.exec_streaming("make", &["test"], None).await?;
+//!     while let Some(chunk) = chunks.recv().await {
+//!         feed.send(&chunk);
+//!     }
+//!     void_box::Result::Ok(())
+//! });
+//! dashboard.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span as TextSpan};
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+use crate::guest::protocol::{ExecOutputChunk, ProcessMetrics, TelemetryBatch};
+use crate::observe::tracer::{Span, SpanStatus};
+use crate::observe::Observer;
+use crate::sandbox::{Sandbox, SandboxEvent};
+use crate::Result;
+
+/// Telemetry samples kept for the CPU and memory sparklines.
+const HISTORY_LEN: usize = 120;
+/// Finished execs listed under the running ones.
+const FINISHED_LEN: usize = 8;
+/// Output lines retained for the output pane.
+const OUTPUT_LINES: usize = 500;
+/// Tool calls listed.
+const TOOL_CALLS_LEN: usize = 10;
+/// Redraw interval.
+const DEFAULT_TICK: Duration = Duration::from_millis(250);
+
+/// Live terminal view of one sandbox; see the [module docs](self).
+pub struct Dashboard {
+    sandbox: Arc<Sandbox>,
+    observer: Option<Observer>,
+    output_tx: mpsc::UnboundedSender<ExecOutputChunk>,
+    output_rx: mpsc::UnboundedReceiver<ExecOutputChunk>,
+    tick: Duration,
+}
+
+impl Dashboard {
+    pub fn new(sandbox: Arc<Sandbox>) -> Self {
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        Self {
+            observer: sandbox.observer().cloned(),
+            sandbox,
+            output_tx,
+            output_rx,
+            tick: DEFAULT_TICK,
+        }
+    }
+
+    /// Show the tool calls and latest trace recorded by `observer` instead
+    /// of the sandbox's.
+    pub fn observer(mut self, observer: Observer) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Redraw every `tick` instead of every 250ms.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// A handle for forwarding exec output to the output pane.
+    pub fn output_feed(&self) -> OutputFeed {
+        OutputFeed {
+            tx: self.output_tx.clone(),
+        }
+    }
+
+    /// Take over the terminal until the user quits, then restore it.
+    pub async fn run(self) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal).await;
+        ratatui::restore();
+        result
+    }
+
+    async fn event_loop(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut state = DashboardState::new(self.sandbox.id());
+        let mut events = self.sandbox.events();
+        let telemetry = match self.sandbox.start_telemetry(None).await {
+            Ok(aggregator) => Some(aggregator),
+            Err(e) => {
+                tracing::warn!("Dashboard runs without guest telemetry: {}", e);
+                None
+            }
+        };
+        let mut ticker = tokio::time::interval(self.tick);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => state.apply(&event),
+                    Err(RecvError::Lagged(missed)) => state.missed_events += missed,
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                Some(chunk) = self.output_rx.recv() => state.push_output(&chunk),
+                _ = ticker.tick() => {
+                    if let Some(batch) = telemetry.as_ref().and_then(|t| t.latest_batch()) {
+                        state.set_processes(&batch);
+                    }
+                    if let Some(observer) = &self.observer {
+                        state.set_traces(&observer.get_traces());
+                    }
+                    state.execs_in_flight = self.sandbox.execs_in_flight();
+                    terminal.draw(|frame| state.render(frame))?;
+                    while event::poll(Duration::ZERO)? {
+                        if let Event::Key(key) = event::read()? {
+                            let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                                || (key.code == KeyCode::Char('c')
+                                    && key.modifiers.contains(KeyModifiers::CONTROL));
+                            if quit && key.kind == KeyEventKind::Press {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Forwards exec output to a [`Dashboard`]. Cheap to clone; sending after
+/// the dashboard exits is a no-op.
+#[derive(Clone)]
+pub struct OutputFeed {
+    tx: mpsc::UnboundedSender<ExecOutputChunk>,
+}
+
+impl OutputFeed {
+    pub fn send(&self, chunk: &ExecOutputChunk) {
+        let _ = self.tx.send(chunk.clone());
+    }
+}
+
+struct RunningExec {
+    program: String,
+    args: Vec<String>,
+    started: Instant,
+}
+
+struct FinishedExec {
+    program: String,
+    exit_code: Option<i32>,
+    duration_ms: u64,
+    error: Option<String>,
+}
+
+struct ToolCallRow {
+    name: String,
+    duration_ms: Option<u64>,
+    error: bool,
+}
+
+struct WaterfallRow {
+    depth: usize,
+    name: String,
+    offset_ms: u64,
+    duration_ms: u64,
+    error: bool,
+}
+
+/// What the dashboard shows, folded from events, telemetry and traces.
+///
+/// Kept apart from the terminal so it can be fed and rendered in tests.
+pub struct DashboardState {
+    sandbox_id: String,
+    boot: Option<String>,
+    status: Option<String>,
+    cpu: VecDeque<u64>,
+    memory: VecDeque<u64>,
+    memory_total: u64,
+    processes: Vec<ProcessMetrics>,
+    running: BTreeMap<u64, RunningExec>,
+    finished: VecDeque<FinishedExec>,
+    output: VecDeque<String>,
+    partial_line: String,
+    tool_calls: Vec<ToolCallRow>,
+    waterfall: Vec<WaterfallRow>,
+    execs_in_flight: usize,
+    missed_events: u64,
+}
+
+impl DashboardState {
+    pub fn new(sandbox_id: impl Into<String>) -> Self {
+        Self {
+            sandbox_id: sandbox_id.into(),
+            boot: None,
+            status: None,
+            cpu: VecDeque::with_capacity(HISTORY_LEN),
+            memory: VecDeque::with_capacity(HISTORY_LEN),
+            memory_total: 0,
+            processes: Vec::new(),
+            running: BTreeMap::new(),
+            finished: VecDeque::with_capacity(FINISHED_LEN),
+            output: VecDeque::with_capacity(OUTPUT_LINES),
+            partial_line: String::new(),
+            tool_calls: Vec::new(),
+            waterfall: Vec::new(),
+            execs_in_flight: 0,
+            missed_events: 0,
+        }
+    }
+
+    /// Fold one sandbox event into the view.
+    pub fn apply(&mut self, event: &SandboxEvent) {
+        match event {
+            SandboxEvent::Boot {
+                memory_mb,
+                vcpus,
+                network,
+                from_snapshot,
+            } => {
+                self.boot = Some(format!(
+                    "{} MiB · {} vCPU · net {}{}",
+                    memory_mb,
+                    vcpus,
+                    if *network { "on" } else { "off" },
+                    if *from_snapshot { " · snapshot" } else { "" }
+                ));
+                self.status = Some("booting".into());
+            }
+            SandboxEvent::AgentReady { boot_ms } => {
+                self.status = Some(format!("ready in {}ms", boot_ms));
+            }
+            SandboxEvent::ExecStarted {
+                exec_id,
+                program,
+                args,
+            } => {
+                self.running.insert(
+                    *exec_id,
+                    RunningExec {
+                        program: program.clone(),
+                        args: args.clone(),
+                        started: Instant::now(),
+                    },
+                );
+            }
+            SandboxEvent::ExecFinished {
+                exec_id,
+                program,
+                exit_code,
+                duration_ms,
+                error,
+                ..
+            } => {
+                self.running.remove(exec_id);
+                if self.finished.len() == FINISHED_LEN {
+                    self.finished.pop_back();
+                }
+                self.finished.push_front(FinishedExec {
+                    program: program.clone(),
+                    exit_code: *exit_code,
+                    duration_ms: *duration_ms,
+                    error: error.clone(),
+                });
+            }
+            SandboxEvent::TelemetryTick {
+                cpu_percent,
+                memory_used_bytes,
+                ..
+            } => {
+                if let Some(cpu) = cpu_percent {
+                    push_bounded(&mut self.cpu, cpu.round() as u64);
+                }
+                if let Some(memory) = memory_used_bytes {
+                    push_bounded(&mut self.memory, *memory);
+                }
+            }
+            SandboxEvent::HealthCheckFailed { missed } => {
+                self.status = Some(format!("{} heartbeats missed", missed));
+            }
+            SandboxEvent::WatchdogExpired { silent_ms } => {
+                self.status = Some(format!("watchdog expired after {}ms", silent_ms));
+            }
+            SandboxEvent::Restarted { restart, reason } => {
+                self.status = Some(format!("restarted (#{}): {}", restart, reason));
+                self.running.clear();
+            }
+            SandboxEvent::Rebooted { reboot_ms, .. } => {
+                self.status = Some(format!("rebooted in {}ms", reboot_ms));
+                self.running.clear();
+            }
+            SandboxEvent::Shutdown { .. } => {
+                self.status = Some("stopped".into());
+                self.running.clear();
+            }
+            SandboxEvent::FileWritten { .. }
+            | SandboxEvent::NetworkConnection { .. }
+            | SandboxEvent::NetworkFlow { .. } => {}
+        }
+    }
+
+    /// Append exec output to the output pane.
+    pub fn push_output(&mut self, chunk: &ExecOutputChunk) {
+        self.partial_line
+            .push_str(&String::from_utf8_lossy(&chunk.data));
+        while let Some(newline) = self.partial_line.find('\n') {
+            let line: String = self.partial_line.drain(..=newline).collect();
+            if self.output.len() == OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output
+                .push_back(line.trim_end_matches(['\r', '\n']).to_string());
+        }
+    }
+
+    /// Take the guest memory size and busiest processes from `batch`.
+    pub fn set_processes(&mut self, batch: &TelemetryBatch) {
+        if let Some(system) = &batch.system {
+            self.memory_total = system.memory_total_bytes;
+        }
+        self.processes = batch.processes.clone();
+        self.processes
+            .sort_by_key(|proc| std::cmp::Reverse(proc.rss_bytes));
+    }
+
+    /// Rebuild the tool-call list and the waterfall of the latest trace.
+    pub fn set_traces(&mut self, spans: &[Span]) {
+        let mut tools: Vec<&Span> = spans
+            .iter()
+            .filter(|span| span.attributes.contains_key("tool.name"))
+            .collect();
+        tools.sort_by_key(|span| std::cmp::Reverse(span.start_time));
+        self.tool_calls = tools
+            .into_iter()
+            .take(TOOL_CALLS_LEN)
+            .map(|span| ToolCallRow {
+                name: span.attributes["tool.name"].clone(),
+                duration_ms: span.duration.map(|d| d.as_millis() as u64),
+                error: matches!(span.status, SpanStatus::Error(_)),
+            })
+            .collect();
+
+        let Some(latest) = spans.iter().max_by_key(|span| span.start_time) else {
+            self.waterfall.clear();
+            return;
+        };
+        let mut trace: Vec<&Span> = spans
+            .iter()
+            .filter(|span| span.context.trace_id == latest.context.trace_id)
+            .collect();
+        trace.sort_by_key(|span| span.start_time);
+        let origin = trace[0].start_time;
+        let parents: HashMap<&str, Option<&str>> = trace
+            .iter()
+            .map(|span| {
+                (
+                    span.context.span_id.as_str(),
+                    span.context.parent_span_id.as_deref(),
+                )
+            })
+            .collect();
+        self.waterfall = trace
+            .iter()
+            .map(|span| {
+                let mut depth = 0;
+                let mut parent = span.context.parent_span_id.as_deref();
+                while let Some(id) = parent {
+                    depth += 1;
+                    parent = parents.get(id).copied().flatten();
+                }
+                WaterfallRow {
+                    depth,
+                    name: span.name.clone(),
+                    offset_ms: elapsed_ms(origin, span.start_time),
+                    duration_ms: span.duration.map_or_else(
+                        || elapsed_ms(span.start_time, SystemTime::now()),
+                        |d| d.as_millis() as u64,
+                    ),
+                    error: matches!(span.status, SpanStatus::Error(_)),
+                }
+            })
+            .collect();
+    }
+
+    /// Draw the whole dashboard into `frame`.
+    pub fn render(&self, frame: &mut Frame) {
+        let [header, gauges, middle, bottom, waterfall] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(6),
+            Constraint::Min(6),
+            Constraint::Min(6),
+            Constraint::Length(self.waterfall.len().clamp(1, 10) as u16 + 2),
+        ])
+        .areas(frame.area());
+        self.render_header(frame, header);
+
+        let [cpu, memory] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(gauges);
+        self.render_usage(frame, cpu, memory);
+
+        let [execs, processes] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(middle);
+        self.render_execs(frame, execs);
+        self.render_processes(frame, processes);
+
+        let [output, tools] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(bottom);
+        self.render_output(frame, output);
+        self.render_tool_calls(frame, tools);
+        self.render_waterfall(frame, waterfall);
+    }
+
+    fn render_header(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![
+            TextSpan::styled("voidbox ", Style::new().add_modifier(Modifier::BOLD)),
+            TextSpan::raw(self.sandbox_id.clone()),
+        ];
+        for part in [self.boot.as_deref(), self.status.as_deref()]
+            .into_iter()
+            .flatten()
+        {
+            spans.push(TextSpan::raw(format!(" · {}", part)));
+        }
+        spans.push(TextSpan::raw(format!(
+            " · {} exec(s) in flight",
+            self.execs_in_flight
+        )));
+        if self.missed_events > 0 {
+            spans.push(TextSpan::styled(
+                format!(" · {} events dropped", self.missed_events),
+                Style::new().fg(Color::Yellow),
+            ));
+        }
+        spans.push(TextSpan::styled(
+            "  q quit",
+            Style::new().fg(Color::DarkGray),
+        ));
+        frame.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+
+    fn render_usage(&self, frame: &mut Frame, cpu: Rect, memory: Rect) {
+        let cpu_title = match self.cpu.back() {
+            Some(percent) => format!(" CPU {}% ", percent),
+            None => " CPU ".to_string(),
+        };
+        let cpu_data: Vec<u64> = self.cpu.iter().copied().collect();
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::default().borders(Borders::ALL).title(cpu_title))
+                .data(&cpu_data)
+                .max(100)
+                .style(Style::new().fg(Color::Green)),
+            cpu,
+        );
+
+        let memory_title = match (self.memory.back(), self.memory_total) {
+            (Some(used), 0) => format!(" Memory {} ", mib(*used)),
+            (Some(used), total) => format!(" Memory {} / {} ", mib(*used), mib(total)),
+            (None, _) => " Memory ".to_string(),
+        };
+        let memory_data: Vec<u64> = self.memory.iter().copied().collect();
+        let mut sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(memory_title))
+            .data(&memory_data)
+            .style(Style::new().fg(Color::Cyan));
+        if self.memory_total > 0 {
+            sparkline = sparkline.max(self.memory_total);
+        }
+        frame.render_widget(sparkline, memory);
+    }
+
+    fn render_execs(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = self
+            .running
+            .values()
+            .map(|exec| {
+                Line::from(vec![
+                    TextSpan::styled("▶ ", Style::new().fg(Color::Yellow)),
+                    TextSpan::raw(format!(
+                        "{} {} ({:.1}s)",
+                        exec.program,
+                        exec.args.join(" "),
+                        exec.started.elapsed().as_secs_f64()
+                    )),
+                ])
+            })
+            .collect();
+        lines.extend(self.finished.iter().map(|exec| {
+            let (mark, color, outcome) = match (exec.exit_code, &exec.error) {
+                (Some(0), _) => ("✓ ", Color::Green, "exit 0".to_string()),
+                (Some(code), _) => ("✗ ", Color::Red, format!("exit {}", code)),
+                (None, Some(error)) => ("✗ ", Color::Red, error.clone()),
+                (None, None) => ("✗ ", Color::Red, "failed".to_string()),
+            };
+            Line::from(vec![
+                TextSpan::styled(mark, Style::new().fg(color)),
+                TextSpan::raw(format!(
+                    "{} {} ({}ms)",
+                    exec.program, outcome, exec.duration_ms
+                )),
+            ])
+        }));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Execs ")),
+            area,
+        );
+    }
+
+    fn render_processes(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self
+            .processes
+            .iter()
+            .map(|proc| {
+                Line::raw(format!(
+                    "{:>6} {} {:>9} {}",
+                    proc.pid,
+                    proc.state,
+                    mib(proc.rss_bytes),
+                    proc.comm
+                ))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title(" Processes ")),
+            area,
+        );
+    }
+
+    fn render_output(&self, frame: &mut Frame, area: Rect) {
+        // Show the newest lines that fit, the partial line last.
+        let visible = area.height.saturating_sub(2) as usize;
+        let mut lines: Vec<&str> = self.output.iter().map(String::as_str).collect();
+        if !self.partial_line.is_empty() {
+            lines.push(&self.partial_line);
+        }
+        let start = lines.len().saturating_sub(visible);
+        let lines: Vec<Line> = lines[start..].iter().map(|line| Line::raw(*line)).collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Output ")),
+            area,
+        );
+    }
+
+    fn render_tool_calls(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self
+            .tool_calls
+            .iter()
+            .map(|call| {
+                let duration = call
+                    .duration_ms
+                    .map_or_else(|| "…".to_string(), |ms| format!("{}ms", ms));
+                let style = if call.error {
+                    Style::new().fg(Color::Red)
+                } else {
+                    Style::new()
+                };
+                Line::styled(format!("{} ({})", call.name, duration), style)
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title(" Tool calls ")),
+            area,
+        );
+    }
+
+    fn render_waterfall(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Trace ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let label_width = (inner.width as usize / 3).max(1);
+        let bar_width = (inner.width as usize)
+            .saturating_sub(label_width + 1)
+            .max(1);
+        let total = self
+            .waterfall
+            .iter()
+            .map(|row| row.offset_ms + row.duration_ms)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let lines: Vec<Line> = self
+            .waterfall
+            .iter()
+            .take(inner.height as usize)
+            .map(|row| {
+                let label: String = format!("{}{}", "  ".repeat(row.depth), row.name)
+                    .chars()
+                    .take(label_width)
+                    .collect();
+                let start = (row.offset_ms as usize * bar_width) / total as usize;
+                let len = ((row.duration_ms as usize * bar_width) / total as usize).max(1);
+                let color = if row.error { Color::Red } else { Color::Blue };
+                Line::from(vec![
+                    TextSpan::raw(format!("{:<width$} ", label, width = label_width)),
+                    TextSpan::raw(" ".repeat(start.min(bar_width - 1))),
+                    TextSpan::styled(
+                        "█".repeat(len.min(bar_width - start.min(bar_width - 1))),
+                        Style::new().fg(color),
+                    ),
+                    TextSpan::styled(
+                        format!(" {}ms", row.duration_ms),
+                        Style::new().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+}
+
+fn push_bounded(history: &mut VecDeque<u64>, value: u64) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+fn elapsed_ms(from: SystemTime, to: SystemTime) -> u64 {
+    to.duration_since(from)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{:.0} MiB", bytes as f64 / 1048576.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn screen(state: &DashboardState) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| state.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_dashboard_state_renders_events_output_and_traces() {
+        let mut state = DashboardState::new("sb-1");
+        state.apply(&SandboxEvent::Boot {
+            memory_mb: 512,
+            vcpus: 2,
+            network: false,
+            from_snapshot: false,
+        });
+        state.apply(&SandboxEvent::AgentReady { boot_ms: 840 });
+        state.apply(&SandboxEvent::ExecStarted {
+            exec_id: 1,
+            program: "make".into(),
+            args: vec!["test".into()],
+        });
+        state.apply(&SandboxEvent::TelemetryTick {
+            seq: 1,
+            cpu_percent: Some(42.4),
+            memory_used_bytes: Some(128 * 1048576),
+            process_count: 3,
+        });
+        state.push_output(&ExecOutputChunk {
+            stream: "stdout".into(),
+            data: b"running 3 tests\ntest a ... ".to_vec(),
+            seq: 0,
+        });
+
+        let root = Span::new("workflow:ci");
+        let mut tool = Span::child("claude.tool.Bash", &root.context);
+        tool.set_attribute("tool.name", "Bash");
+        tool.end();
+        state.set_traces(&[root, tool]);
+
+        let text = screen(&state);
+        assert!(text.contains("512 MiB · 2 vCPU · net off"), "{}", text);
+        assert!(text.contains("ready in 840ms"));
+        assert!(text.contains("CPU 42%"));
+        assert!(text.contains("make test"));
+        assert!(text.contains("running 3 tests"));
+        assert!(text.contains("test a ..."));
+        assert!(text.contains("Bash ("));
+        assert!(text.contains("  claude.tool.Bash"));
+
+        state.apply(&SandboxEvent::ExecFinished {
+            exec_id: 1,
+            program: "make".into(),
+            exit_code: Some(2),
+            duration_ms: 1500,
+            error: None,
+            usage: Default::default(),
+        });
+        assert!(state.running.is_empty());
+        assert!(screen(&state).contains("make exit 2 (1500ms)"));
+    }
+}
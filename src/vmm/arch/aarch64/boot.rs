@@ -65,13 +65,6 @@ const GIC_FDT_IRQ_TYPE_PPI: u32 = 1;
 const GIC_FDT_IRQ_TYPE_SPI: u32 = 0;
 /// Interrupt-specifier trigger flags: level-triggered, active low.
 const IRQ_TYPE_LEVEL_LOW: u32 = 8;
-/// Interrupt-specifier trigger flags: level-triggered, active high.
-const IRQ_TYPE_LEVEL_HIGH: u32 = 4;
-
-/// UART interrupt: GIC SPI 1 (INTID 33). Declared in the DTB but never
-/// injected — kernel console writes poll the line-status register, matching
-/// the x86 path, where no serial IRQ is injected either.
-const UART_SPI: u32 = 1;
 
 /// UART input clock advertised in the DTB. Required by the 8250 OF binding;
 /// it only feeds baud-divisor math the register model ignores.
@@ -80,11 +73,6 @@ const UART_CLOCK_HZ: u32 = 1_843_200;
 /// Interrupt-specifier trigger flags: edge-triggered, rising.
 const IRQ_TYPE_EDGE_RISING: u32 = 1;
 
-/// virtio-mmio register window advertised per device (the virtio-mmio
-/// register file; matches the `512@` in the x86 cmdline convention and the
-/// devices' `mmio_size`).
-const VIRTIO_MMIO_WINDOW_SIZE: u64 = 0x200;
-
 /// Load kernel (Image) and optionally initramfs into guest memory.
 ///
 /// Generates the guest DTB (memory, chosen, cpus, psci, GIC, UART, virtio,
@@ -123,7 +111,7 @@ pub fn load_kernel(
         dtb.len(),
         gic_version,
         platform.vcpu_count,
-        platform.virtio_slots.len()
+        platform.virtio_windows.len()
     );
 
     Ok(kernel.entry)
@@ -513,17 +501,21 @@ fn generate_dtb(
     // create gets no node, matching the conditional cmdline args on x86_64.
     // dma-coherent: KVM guest memory shares the host cache hierarchy, so
     // the guest can skip per-transfer cache maintenance.
-    for slot in &platform.virtio_slots {
+    for window in &platform.virtio_windows {
         let node = fdt
-            .begin_node(&format!("virtio_mmio@{:x}", slot.mmio_base()))
+            .begin_node(&format!("virtio_mmio@{:x}", window.base))
             .map_err(|e| Error::Boot(format!("begin virtio_mmio: {}", e)))?;
         fdt.property_string("compatible", "virtio,mmio")
             .map_err(|e| Error::Boot(format!("virtio compatible: {}", e)))?;
-        fdt.property_array_u64("reg", &[slot.mmio_base(), VIRTIO_MMIO_WINDOW_SIZE])
+        fdt.property_array_u64("reg", &[window.base, window.size])
             .map_err(|e| Error::Boot(format!("virtio reg: {}", e)))?;
         fdt.property_array_u32(
             "interrupts",
-            &[GIC_FDT_IRQ_TYPE_SPI, slot.spi(), IRQ_TYPE_EDGE_RISING],
+            &[
+                GIC_FDT_IRQ_TYPE_SPI,
+                window.slot.spi(),
+                IRQ_TYPE_EDGE_RISING,
+            ],
         )
         .map_err(|e| Error::Boot(format!("virtio interrupts: {}", e)))?;
         fdt.property_null("dma-coherent")
@@ -546,7 +538,7 @@ fn generate_dtb(
         .map_err(|e| Error::Boot(format!("serial clock-frequency: {}", e)))?;
     fdt.property_array_u32(
         "interrupts",
-        &[GIC_FDT_IRQ_TYPE_SPI, UART_SPI, IRQ_TYPE_LEVEL_HIGH],
+        &[GIC_FDT_IRQ_TYPE_SPI, layout::UART_SPI, IRQ_TYPE_EDGE_RISING],
     )
     .map_err(|e| Error::Boot(format!("serial interrupts: {}", e)))?;
     fdt.end_node(uart)
@@ -604,6 +596,7 @@ fn generate_dtb(
 mod tests {
     use super::*;
     use crate::vmm::arch::VirtioSlot;
+    use crate::vmm::layout::MemoryLayout;
 
     fn header_bytes(text_offset: u64, image_size: u64, magic: u32) -> [u8; IMAGE_HEADER_LEN] {
         let mut header = [0u8; IMAGE_HEADER_LEN];
@@ -669,14 +662,22 @@ mod tests {
                 GicVersion::V3 => layout::MAX_VCPUS,
                 GicVersion::V2 => layout::GICV2_MAX_VCPUS,
             };
+            let slots = [
+                VirtioSlot::Net,
+                VirtioSlot::Vsock,
+                VirtioSlot::P9,
+                VirtioSlot::Blk,
+                VirtioSlot::Disk0,
+                VirtioSlot::Disk1,
+                VirtioSlot::Disk2,
+                VirtioSlot::Disk3,
+                VirtioSlot::Rng,
+                VirtioSlot::Balloon,
+                VirtioSlot::Console,
+            ];
             let platform = BootPlatform {
                 vcpu_count,
-                virtio_slots: vec![
-                    VirtioSlot::Net,
-                    VirtioSlot::Vsock,
-                    VirtioSlot::P9,
-                    VirtioSlot::Blk,
-                ],
+                virtio_windows: MemoryLayout::new(1 << 30, None, &slots).windows().to_vec(),
             };
             let dtb = generate_dtb(
                 1024 * 1024 * 1024,
@@ -697,7 +698,7 @@ mod tests {
         let long_cmdline = "x".repeat(BOOTARGS_MAX_LEN + 1);
         let platform = BootPlatform {
             vcpu_count: 1,
-            virtio_slots: Vec::new(),
+            virtio_windows: Vec::new(),
         };
         let result = generate_dtb(1 << 30, &long_cmdline, None, &platform, GicVersion::V3);
         assert!(result.is_err());
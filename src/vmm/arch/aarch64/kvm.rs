@@ -58,6 +58,9 @@ pub mod layout {
     /// UART MMIO window size.
     pub const UART_SIZE: u64 = 0x1000; // 4 KB
 
+    /// UART interrupt: GIC SPI 1 (INTID 33), edge-triggered.
+    pub const UART_SPI: u32 = 1;
+
     /// Base of the virtio-mmio device slots (below RAM).
     pub const VIRTIO_MMIO_BASE: u64 = 0x0A00_0000;
 
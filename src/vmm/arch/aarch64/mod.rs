@@ -45,6 +45,16 @@ impl Arch for Aarch64 {
         cpu::configure_vcpu(vcpu_fd, vcpu_id, entry_point, vm)
     }
 
+    fn reset_vcpu(
+        vcpu_fd: &VcpuFd,
+        vcpu_id: u64,
+        entry_point: u64,
+        vm: &Vm,
+        _power_on: &VcpuState,
+    ) -> Result<()> {
+        cpu::configure_vcpu(vcpu_fd, vcpu_id, entry_point, vm)
+    }
+
     fn capture_vcpu_state(vcpu_fd: &VcpuFd) -> Result<VcpuState> {
         cpu::capture_vcpu_state(vcpu_fd)
     }
@@ -15,6 +15,7 @@ use kvm_ioctls::{VcpuFd, VmFd};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::vmm::kvm::Vm;
+use crate::vmm::layout::DeviceWindow;
 use crate::Result;
 
 /// Memory layout for the guest physical address space.
@@ -46,27 +47,53 @@ pub enum VirtioSlot {
     P9 = 2,
     /// virtio-blk (OCI rootfs disk).
     Blk = 3,
+    /// virtio-blk data disks (scratch images and attached files), in
+    /// attach order.
+    Disk0 = 4,
+    Disk1 = 5,
+    Disk2 = 6,
+    Disk3 = 7,
+    /// virtio-rng (guest entropy).
+    Rng = 8,
+    /// virtio-balloon (guest memory reclaim).
+    Balloon = 9,
+    /// virtio-console (control channel over a serial port, when vsock is
+    /// not used for it).
+    Console = 10,
 }
 
 impl VirtioSlot {
+    /// Slots for data disks; their count caps the disks one VM can attach.
+    pub const DATA_DISKS: [VirtioSlot; 4] = [
+        VirtioSlot::Disk0,
+        VirtioSlot::Disk1,
+        VirtioSlot::Disk2,
+        VirtioSlot::Disk3,
+    ];
+
     fn index(self) -> u32 {
         self as u32
     }
 
-    /// Guest-physical base of this slot's virtio-mmio window.
+    /// Guest-physical base of this slot's virtio-mmio window in the default
+    /// layout. A VM configured with another base places it at that base
+    /// plus [`mmio_offset`](Self::mmio_offset); see
+    /// [`MemoryLayout`](crate::vmm::layout::MemoryLayout).
     pub fn mmio_base(self) -> u64 {
         #[cfg(target_arch = "x86_64")]
-        let layout_base = (
-            x86_64::kvm::layout::VIRTIO_MMIO_BASE,
-            x86_64::kvm::layout::VIRTIO_MMIO_STRIDE,
-        );
+        let base = x86_64::kvm::layout::VIRTIO_MMIO_BASE;
+        #[cfg(target_arch = "aarch64")]
+        let base = aarch64::kvm::layout::VIRTIO_MMIO_BASE;
+        base + self.mmio_offset()
+    }
+
+    /// Offset of this slot's window from the first slot's.
+    pub fn mmio_offset(self) -> u64 {
+        #[cfg(target_arch = "x86_64")]
+        let stride = x86_64::kvm::layout::VIRTIO_MMIO_STRIDE;
         #[cfg(target_arch = "aarch64")]
-        let layout_base = (
-            aarch64::kvm::layout::VIRTIO_MMIO_BASE,
-            aarch64::kvm::layout::VIRTIO_MMIO_STRIDE,
-        );
-        let (base, stride) = layout_base;
-        base + stride * u64::from(self.index())
+        let stride = aarch64::kvm::layout::VIRTIO_MMIO_STRIDE;
+        stride * u64::from(self.index())
     }
 
     /// GSI to register a `KVM_IRQFD` eventfd against.
@@ -107,23 +134,43 @@ impl VirtioSlot {
         }
         #[cfg(target_arch = "aarch64")]
         {
-            use kvm_bindings::{KVM_ARM_IRQ_TYPE_SHIFT, KVM_ARM_IRQ_TYPE_SPI};
-
-            const SPI_INTID_BASE: u32 = 32;
-            (KVM_ARM_IRQ_TYPE_SPI << KVM_ARM_IRQ_TYPE_SHIFT) | (SPI_INTID_BASE + self.spi())
+            spi_irq_line_value(self.spi())
         }
     }
 }
 
+/// `KVM_IRQ_LINE` value of the serial console's interrupt, raised for host
+/// input and transmitter-empty events: ISA IRQ 4 (COM1) on x86_64, the
+/// UART's SPI on aarch64.
+pub fn uart_irq_line_value() -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86_64::kvm::layout::UART_IRQ
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        spi_irq_line_value(aarch64::kvm::layout::UART_SPI)
+    }
+}
+
+/// `KVM_IRQ_LINE` encoding of SPI index `spi` on arm64.
+#[cfg(target_arch = "aarch64")]
+fn spi_irq_line_value(spi: u32) -> u32 {
+    use kvm_bindings::{KVM_ARM_IRQ_TYPE_SHIFT, KVM_ARM_IRQ_TYPE_SPI};
+
+    const SPI_INTID_BASE: u32 = 32;
+    (KVM_ARM_IRQ_TYPE_SPI << KVM_ARM_IRQ_TYPE_SHIFT) | (SPI_INTID_BASE + spi)
+}
+
 /// Facts about the virtual platform the arch boot code needs at kernel-load
 /// time. The aarch64 DTB describes CPUs and every device the VMM creates;
-/// x86_64 carries the same facts in the kernel cmdline instead and ignores
-/// this struct.
+/// x86_64 describes the CPUs in an MP table and carries the devices in the
+/// kernel cmdline instead.
 pub struct BootPlatform {
     /// Number of vCPUs the VM will have.
     pub vcpu_count: usize,
-    /// Populated virtio-mmio slots, in slot order.
-    pub virtio_slots: Vec<VirtioSlot>,
+    /// Windows of the populated virtio-mmio slots, in slot order.
+    pub virtio_windows: Vec<DeviceWindow>,
 }
 
 /// Trait that abstracts architecture-specific KVM operations.
@@ -165,7 +212,8 @@ pub trait Arch {
     /// `platform` describes the vCPUs and populated virtio slots — aarch64
     /// needs both at load time because the generated DTB carries one
     /// `/cpus` node per vCPU, sizes the GICv3 redistributor region, and
-    /// declares one virtio-mmio node per populated slot; x86_64 ignores it.
+    /// declares one virtio-mmio node per populated slot; x86_64 only uses
+    /// the vCPU count, for its MP table.
     fn load_kernel(
         vm: &Vm,
         kernel: &Path,
@@ -177,6 +225,21 @@ pub trait Arch {
     /// Configure a freshly-created vCPU for cold boot.
     fn configure_vcpu(vcpu_fd: &VcpuFd, vcpu_id: u64, entry_point: u64, vm: &Vm) -> Result<()>;
 
+    /// Return a vCPU that has already run to its cold-boot state, entering
+    /// at `entry_point`, for an in-place guest reboot.
+    ///
+    /// `power_on` is what [`capture_vcpu_state`](Self::capture_vcpu_state)
+    /// returned right after [`configure_vcpu`](Self::configure_vcpu). x86_64
+    /// replays its LAPIC, MSRs, FPU and MP state; aarch64 ignores it, since
+    /// repeating `KVM_ARM_VCPU_INIT` already resets the vCPU.
+    fn reset_vcpu(
+        vcpu_fd: &VcpuFd,
+        vcpu_id: u64,
+        entry_point: u64,
+        vm: &Vm,
+        power_on: &Self::VcpuState,
+    ) -> Result<()>;
+
     // -- Snapshot capture --
 
     /// Capture full vCPU register state.
@@ -234,6 +297,15 @@ mod tests {
         assert_eq!(VirtioSlot::Vsock.irq_line_value(), 11);
         assert_eq!(VirtioSlot::P9.irq_line_value(), 12);
         assert_eq!(VirtioSlot::Blk.irq_line_value(), 13);
+        assert_eq!(VirtioSlot::Disk0.mmio_base(), 0xd200_0000);
+        assert_eq!(VirtioSlot::Disk3.mmio_base(), 0xd380_0000);
+        assert_eq!(VirtioSlot::Disk3.irq_line_value(), 17);
+        assert_eq!(VirtioSlot::Rng.mmio_base(), 0xd400_0000);
+        assert_eq!(VirtioSlot::Rng.irq_line_value(), 18);
+        assert_eq!(VirtioSlot::Balloon.mmio_base(), 0xd480_0000);
+        assert_eq!(VirtioSlot::Balloon.irq_line_value(), 19);
+        assert_eq!(VirtioSlot::Console.mmio_base(), 0xd500_0000);
+        assert_eq!(VirtioSlot::Console.irq_line_value(), 20);
         // TX-notify ioeventfd doorbell: net base + QUEUE_NOTIFY offset.
         assert_eq!(VirtioSlot::Net.mmio_base() + 0x50, 0xd000_0050);
     }
@@ -252,5 +324,11 @@ mod tests {
         assert_eq!(VirtioSlot::Net.irq_line_value(), (1 << 24) | 48);
         assert_eq!(VirtioSlot::Vsock.irq_line_value(), (1 << 24) | 49);
         assert_eq!(VirtioSlot::Blk.irq_line_value(), (1 << 24) | 51);
+        assert_eq!(VirtioSlot::Disk3.mmio_base(), 0x0a00_7000);
+        assert_eq!(VirtioSlot::Disk3.irq_line_value(), (1 << 24) | 55);
+        assert_eq!(VirtioSlot::Rng.irq_line_value(), (1 << 24) | 56);
+        assert_eq!(VirtioSlot::Balloon.irq_line_value(), (1 << 24) | 57);
+        assert_eq!(VirtioSlot::Console.mmio_base(), 0x0a00_a000);
+        assert_eq!(VirtioSlot::Console.irq_line_value(), (1 << 24) | 58);
     }
 }
@@ -27,6 +27,7 @@ pub fn load_kernel(
     kernel_path: &Path,
     initramfs_path: Option<&Path>,
     cmdline: &str,
+    vcpu_count: usize,
 ) -> Result<u64> {
     let guest_memory = vm.guest_memory();
     let memory_size = vm.memory_size();
@@ -112,6 +113,9 @@ pub fn load_kernel(
     // Set up initial page tables for 64-bit mode
     setup_page_tables(guest_memory)?;
 
+    // Describe the vCPUs and IOAPIC so the guest boots SMP
+    super::mptable::setup_mptable(guest_memory, vcpu_count)?;
+
     Ok(kernel_entry)
 }
 
@@ -62,13 +62,58 @@ const SNAPSHOT_MSR_INDICES: &[u32] = &[
 ];
 
 /// Configure a freshly-created vCPU for cold boot (CPUID + sregs + regs).
-pub fn configure_vcpu(vcpu_fd: &VcpuFd, _vcpu_id: u64, entry_point: u64, vm: &Vm) -> Result<()> {
-    configure_cpuid(vm, vcpu_fd)?;
+pub fn configure_vcpu(vcpu_fd: &VcpuFd, vcpu_id: u64, entry_point: u64, vm: &Vm) -> Result<()> {
+    configure_cpuid(vm, vcpu_fd, vcpu_id)?;
     configure_sregs(vcpu_fd)?;
     configure_regs(vcpu_fd, entry_point)?;
     Ok(())
 }
 
+/// Return a vCPU that has run to the state [`configure_vcpu`] left it in.
+///
+/// Unlike [`restore_vcpu_state`] nothing is patched up for a guest resuming
+/// mid-flight: the LAPIC, MSRs, FPU and MP state go back to `power_on`
+/// as-is, and the new kernel starts from scratch.
+pub fn reset_vcpu(
+    vcpu_fd: &VcpuFd,
+    vcpu_id: u64,
+    entry_point: u64,
+    vm: &Vm,
+    power_on: &VcpuState,
+) -> Result<()> {
+    use kvm_bindings::{kvm_lapic_state, kvm_mp_state, kvm_msr_entry, kvm_sregs, kvm_xsave};
+
+    // The TSC keeps counting, so guest time stays monotonic.
+    const IA32_TSC: u32 = 0x0000_0010;
+
+    let sregs: kvm_sregs = kvm_struct_from_bytes(&power_on.sregs)?;
+    let lapic: kvm_lapic_state = kvm_struct_from_bytes(&power_on.lapic)?;
+    let xsave: kvm_xsave = kvm_struct_from_bytes(&power_on.xsave)?;
+
+    for &(index, data) in power_on.msrs.iter().filter(|(index, _)| *index != IA32_TSC) {
+        let mut msrs = Msrs::new(1).map_err(|e| Error::Vcpu(format!("Msrs::new: {:?}", e)))?;
+        msrs.as_mut_slice()[0] = kvm_msr_entry {
+            index,
+            data,
+            ..Default::default()
+        };
+        if let Err(e) = vcpu_fd.set_msrs(&msrs) {
+            debug!("Failed to reset MSR {:#x}: {}", index, e);
+        }
+    }
+    // `configure_vcpu` only rewrites the segments and control registers it
+    // needs; the descriptor tables must not survive from the old kernel.
+    vcpu_fd.set_sregs(&sregs).map_err(Error::Kvm)?;
+    vcpu_fd.set_lapic(&lapic).map_err(Error::Kvm)?;
+    vcpu_fd.set_xsave(&xsave).map_err(Error::Kvm)?;
+    if let Some(mp_state) = power_on.mp_state {
+        vcpu_fd
+            .set_mp_state(kvm_mp_state { mp_state })
+            .map_err(Error::Kvm)?;
+    }
+    configure_vcpu(vcpu_fd, vcpu_id, entry_point, vm)
+}
+
 /// Capture the full register state of a vCPU for snapshotting.
 pub fn capture_vcpu_state(vcpu_fd: &VcpuFd) -> Result<VcpuState> {
     let regs = vcpu_fd.get_regs().map_err(Error::Kvm)?;
@@ -329,16 +374,25 @@ pub fn restore_vcpu_state(vcpu_fd: &VcpuFd, state: &VcpuState, vcpu_id: u64) ->
 }
 
 /// Configure CPUID for the vCPU.
-fn configure_cpuid(vm: &Vm, vcpu_fd: &VcpuFd) -> Result<()> {
+///
+/// The host's CPUID reports the host CPU's APIC ID; it is rewritten to the
+/// vCPU index, which is the LAPIC ID KVM assigns and the one the MP table
+/// lists. Otherwise every vCPU claims the same ID and Linux builds a
+/// broken topology on SMP guests.
+fn configure_cpuid(vm: &Vm, vcpu_fd: &VcpuFd, vcpu_id: u64) -> Result<()> {
     let mut cpuid = vm
         .kvm()
         .get_supported_cpuid(KVM_MAX_CPUID_ENTRIES)
         .map_err(Error::Kvm)?;
 
+    let apic_id = vcpu_id as u32;
     for entry in cpuid.as_mut_slice().iter_mut() {
-        if entry.function == 1 {
-            // Clear hypervisor bit if desired (optional)
-            // entry.ecx &= !(1 << 31);
+        match entry.function {
+            // Initial APIC ID in EBX[31:24]
+            1 => entry.ebx = (entry.ebx & 0x00FF_FFFF) | (apic_id << 24),
+            // Extended topology leaves carry the x2APIC ID in EDX
+            0xB | 0x1F => entry.edx = apic_id,
+            _ => {}
         }
     }
 
@@ -45,6 +45,16 @@ pub mod layout {
     /// Maximum kernel command line size.
     pub const CMDLINE_MAX_SIZE: usize = 4096;
 
+    /// ISA interrupt of the COM1 serial console.
+    pub const UART_IRQ: u32 = 4;
+
+    /// MP floating pointer + configuration table, in the reserved BIOS
+    /// area that Linux scans for it (0xF0000–0xFFFFF).
+    pub const MPTABLE_START: GuestAddress = GuestAddress(0x000F_0000);
+
+    /// Space reserved for the MP table.
+    pub const MPTABLE_MAX_SIZE: usize = 0x1_0000;
+
     /// PCI MMIO space start.
     pub const PCI_MMIO_START: u64 = 0xC000_0000;
 
@@ -3,6 +3,7 @@
 pub mod boot;
 pub mod cpu;
 pub mod kvm;
+pub mod mptable;
 pub mod snapshot;
 
 use std::path::Path;
@@ -36,15 +37,25 @@ impl Arch for X86_64 {
         kernel: &Path,
         initramfs: Option<&Path>,
         cmdline: &str,
-        _platform: &crate::vmm::arch::BootPlatform,
+        platform: &crate::vmm::arch::BootPlatform,
     ) -> Result<u64> {
-        boot::load_kernel(vm, kernel, initramfs, cmdline)
+        boot::load_kernel(vm, kernel, initramfs, cmdline, platform.vcpu_count)
     }
 
     fn configure_vcpu(vcpu_fd: &VcpuFd, vcpu_id: u64, entry_point: u64, vm: &Vm) -> Result<()> {
         cpu::configure_vcpu(vcpu_fd, vcpu_id, entry_point, vm)
     }
 
+    fn reset_vcpu(
+        vcpu_fd: &VcpuFd,
+        vcpu_id: u64,
+        entry_point: u64,
+        vm: &Vm,
+        power_on: &VcpuState,
+    ) -> Result<()> {
+        cpu::reset_vcpu(vcpu_fd, vcpu_id, entry_point, vm, power_on)
+    }
+
     fn capture_vcpu_state(vcpu_fd: &VcpuFd) -> Result<VcpuState> {
         cpu::capture_vcpu_state(vcpu_fd)
     }
@@ -0,0 +1,187 @@
+//! Intel MultiProcessor Specification (v1.4) tables for SMP boot.
+//!
+//! The minimal x86_64 boot has no ACPI, so without an MP table Linux
+//! assumes a uniprocessor machine: it never sends startup IPIs to the
+//! other vCPUs and leaves the IOAPIC disabled, which also makes virtio
+//! GSIs above 15 undeliverable. The table written here lists one processor
+//! per vCPU (LAPIC ID = vCPU index), the in-kernel IOAPIC, and an identity
+//! ISA IRQ → IOAPIC pin map that matches KVM's default GSI routing.
+
+use tracing::debug;
+use vm_memory::{Address, GuestMemoryMmap};
+
+use crate::vmm::memory::write_to_guest;
+use crate::{Error, Result};
+
+use super::kvm::layout;
+
+/// Most vCPUs the table can describe. xAPIC IDs are 8 bits wide, the
+/// IOAPIC takes the ID after the last CPU, and 0xFF is the broadcast ID.
+pub const MAX_CPUS: usize = 254;
+
+const SPEC_REV: u8 = 4;
+const FLOATING_POINTER_LEN: usize = 16;
+const HEADER_LEN: usize = 44;
+
+const APIC_DEFAULT_PHYS_BASE: u32 = 0xFEE0_0000;
+const IO_APIC_DEFAULT_PHYS_BASE: u32 = 0xFEC0_0000;
+const APIC_VERSION: u8 = 0x14;
+const IOAPIC_VERSION: u8 = 0x11;
+/// Pins on KVM's in-kernel IOAPIC.
+const IOAPIC_PINS: u8 = 24;
+const BUS_ID_ISA: u8 = 0;
+
+const MP_PROCESSOR: u8 = 0;
+const MP_BUS: u8 = 1;
+const MP_IOAPIC: u8 = 2;
+const MP_INTSRC: u8 = 3;
+const MP_LINTSRC: u8 = 4;
+
+const MP_IRQ_INT: u8 = 0;
+const MP_IRQ_NMI: u8 = 1;
+const MP_IRQ_EXTINT: u8 = 3;
+
+const CPU_ENABLED: u8 = 1 << 0;
+const CPU_BOOTPROCESSOR: u8 = 1 << 1;
+/// Family 6 signature with the FPU and APIC feature bits; Linux only
+/// sanity-checks these, real values come from CPUID.
+const CPU_SIGNATURE: u32 = 0x600;
+const CPU_FEATURES: u32 = (1 << 9) | (1 << 0);
+
+/// Write the MP floating pointer and configuration table for `num_cpus`
+/// vCPUs into the reserved BIOS area at [`layout::MPTABLE_START`].
+pub fn setup_mptable(guest_memory: &GuestMemoryMmap, num_cpus: usize) -> Result<()> {
+    let table = build(num_cpus)?;
+    write_to_guest(guest_memory, layout::MPTABLE_START, &table)?;
+    debug!(
+        "Wrote MP table at {:#x} ({} CPUs, {} bytes)",
+        layout::MPTABLE_START.raw_value(),
+        num_cpus,
+        table.len()
+    );
+    Ok(())
+}
+
+/// Serialize the floating pointer followed by the configuration table.
+fn build(num_cpus: usize) -> Result<Vec<u8>> {
+    if num_cpus == 0 || num_cpus > MAX_CPUS {
+        return Err(Error::Boot(format!(
+            "MP table supports 1..={} vCPUs, got {}",
+            MAX_CPUS, num_cpus
+        )));
+    }
+    let ioapic_id = num_cpus as u8;
+
+    let mut entries = Vec::new();
+    let mut entry_count: u16 = 0;
+    for cpu in 0..num_cpus {
+        let flags = CPU_ENABLED | if cpu == 0 { CPU_BOOTPROCESSOR } else { 0 };
+        entries.extend_from_slice(&[MP_PROCESSOR, cpu as u8, APIC_VERSION, flags]);
+        entries.extend_from_slice(&CPU_SIGNATURE.to_le_bytes());
+        entries.extend_from_slice(&CPU_FEATURES.to_le_bytes());
+        entries.extend_from_slice(&[0; 8]);
+        entry_count += 1;
+    }
+
+    entries.extend_from_slice(&[MP_BUS, BUS_ID_ISA]);
+    entries.extend_from_slice(b"ISA   ");
+    entry_count += 1;
+
+    entries.extend_from_slice(&[MP_IOAPIC, ioapic_id, IOAPIC_VERSION, CPU_ENABLED]);
+    entries.extend_from_slice(&IO_APIC_DEFAULT_PHYS_BASE.to_le_bytes());
+    entry_count += 1;
+
+    // ISA IRQ n → IOAPIC pin n, bus-default (edge, active-high) polarity.
+    for pin in 0..IOAPIC_PINS {
+        entries.extend_from_slice(&[MP_INTSRC, MP_IRQ_INT, 0, 0, BUS_ID_ISA, pin, ioapic_id, pin]);
+        entry_count += 1;
+    }
+
+    // LINT0 is the 8259 ExtINT line, LINT1 the NMI line on every LAPIC.
+    entries.extend_from_slice(&[MP_LINTSRC, MP_IRQ_EXTINT, 0, 0, BUS_ID_ISA, 0, 0xFF, 0]);
+    entries.extend_from_slice(&[MP_LINTSRC, MP_IRQ_NMI, 0, 0, BUS_ID_ISA, 0, 0xFF, 1]);
+    entry_count += 2;
+
+    let table_addr = layout::MPTABLE_START.raw_value() + FLOATING_POINTER_LEN as u64;
+    let base_len = (HEADER_LEN + entries.len()) as u16;
+
+    let mut table = Vec::with_capacity(FLOATING_POINTER_LEN + base_len as usize);
+    table.extend_from_slice(b"_MP_");
+    table.extend_from_slice(&(table_addr as u32).to_le_bytes());
+    // Length in 16-byte units, spec revision, checksum, then feature bytes
+    // (all zero: a configuration table is present, no IMCR).
+    table.extend_from_slice(&[1, SPEC_REV, 0, 0, 0, 0, 0, 0]);
+    table[10] = checksum(&table);
+
+    let header_start = table.len();
+    table.extend_from_slice(b"PCMP");
+    table.extend_from_slice(&base_len.to_le_bytes());
+    table.extend_from_slice(&[SPEC_REV, 0]);
+    table.extend_from_slice(b"VOIDBOX ");
+    table.extend_from_slice(b"MICROVM     ");
+    // No OEM table.
+    table.extend_from_slice(&[0; 6]);
+    table.extend_from_slice(&entry_count.to_le_bytes());
+    table.extend_from_slice(&APIC_DEFAULT_PHYS_BASE.to_le_bytes());
+    // No extended table.
+    table.extend_from_slice(&[0; 4]);
+    debug_assert_eq!(table.len() - header_start, HEADER_LEN);
+    table.extend_from_slice(&entries);
+    table[header_start + 7] = checksum(&table[header_start..]);
+
+    Ok(table)
+}
+
+/// Byte that makes `bytes` (with a zero placeholder for itself) sum to 0.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sum(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0u8, |s, b| s.wrapping_add(*b))
+    }
+
+    #[test]
+    fn test_mptable_layout_and_checksums() {
+        let table = build(4).unwrap();
+        let (fp, config) = table.split_at(FLOATING_POINTER_LEN);
+
+        assert_eq!(&fp[..4], b"_MP_");
+        assert_eq!(sum(fp), 0);
+        let config_addr = u32::from_le_bytes(fp[4..8].try_into().unwrap());
+        assert_eq!(
+            u64::from(config_addr),
+            layout::MPTABLE_START.raw_value() + FLOATING_POINTER_LEN as u64
+        );
+
+        assert_eq!(&config[..4], b"PCMP");
+        assert_eq!(sum(config), 0);
+        let base_len = u16::from_le_bytes(config[4..6].try_into().unwrap());
+        assert_eq!(base_len as usize, config.len());
+        let entry_count = u16::from_le_bytes(config[34..36].try_into().unwrap());
+        assert_eq!(entry_count, 4 + 1 + 1 + u16::from(IOAPIC_PINS) + 2);
+
+        // Only vCPU 0 is the bootstrap processor; LAPIC IDs follow the index.
+        let cpus: Vec<&[u8]> = config[HEADER_LEN..].chunks(20).take(4).collect();
+        for (i, cpu) in cpus.iter().enumerate() {
+            assert_eq!(cpu[0], MP_PROCESSOR);
+            assert_eq!(cpu[1], i as u8);
+            assert_eq!(cpu[3] & CPU_BOOTPROCESSOR != 0, i == 0);
+        }
+    }
+
+    #[test]
+    fn test_mptable_rejects_unrepresentable_cpu_counts() {
+        assert!(build(0).is_err());
+        assert!(build(MAX_CPUS + 1).is_err());
+        let table = build(MAX_CPUS).unwrap();
+        assert!(table.len() <= layout::MPTABLE_MAX_SIZE);
+    }
+}
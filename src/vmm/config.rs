@@ -11,12 +11,13 @@ pub use crate::backend::{ResourceLimits, DEFAULT_COMMAND_ALLOWLIST};
 
 /// Maximum vCPU count. On aarch64 the ceiling comes from the guest memory
 /// map — the GICv3 redistributor region grows 128 KB per vCPU and must stay
-/// below the UART window (see `arch::aarch64::kvm::layout::MAX_VCPUS`);
-/// x86_64 has no layout-derived limit.
+/// below the UART window (see `arch::aarch64::kvm::layout::MAX_VCPUS`); on
+/// x86_64 from the 8-bit xAPIC IDs in the MP table (see
+/// `arch::x86_64::mptable::MAX_CPUS`).
 #[cfg(target_arch = "aarch64")]
 const MAX_VCPUS: usize = crate::vmm::arch::aarch64::kvm::layout::MAX_VCPUS;
-#[cfg(not(target_arch = "aarch64"))]
-const MAX_VCPUS: usize = 256;
+#[cfg(target_arch = "x86_64")]
+const MAX_VCPUS: usize = crate::vmm::arch::x86_64::mptable::MAX_CPUS;
 
 /// Backend type for the virtio-vsock device.
 ///
@@ -48,12 +49,17 @@ pub struct SecurityConfig {
     pub resource_limits: ResourceLimits,
     /// Network deny list in CIDR notation.
     pub network_deny_list: Vec<String>,
+    /// Egress policy enforced by SLIRP on top of the deny list.
+    pub network_policy: Option<crate::backend::NetworkPolicy>,
     /// Maximum new TCP connections per second from the guest.
     pub max_connections_per_second: u32,
     /// Maximum concurrent TCP connections from the guest.
     pub max_concurrent_connections: usize,
     /// Whether to install seccomp-bpf filter on the VMM process.
     pub seccomp: bool,
+    /// Guest directories the agent may write to on top of
+    /// [`DEFAULT_WRITE_ROOTS`](void_box_protocol::DEFAULT_WRITE_ROOTS).
+    pub write_roots: Vec<String>,
 }
 
 impl Default for SecurityConfig {
@@ -68,9 +74,11 @@ impl Default for SecurityConfig {
                 .collect(),
             resource_limits: ResourceLimits::default(),
             network_deny_list: vec!["169.254.0.0/16".to_string()],
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         }
     }
 }
@@ -92,6 +100,8 @@ pub struct VoidBoxConfig {
     pub network: bool,
     /// TAP device name for networking
     pub tap_name: Option<String>,
+    /// SLIRP or a host TAP device for the virtio-net backend.
+    pub network_mode: crate::backend::NetworkMode,
     /// Host directory to share with guest
     pub shared_dir: Option<PathBuf>,
     /// Host directory mounts (virtio-9p on Linux).
@@ -102,16 +112,42 @@ pub struct VoidBoxConfig {
     pub oci_rootfs_dev: Option<String>,
     /// Host path to OCI rootfs disk image attached via virtio-blk.
     pub oci_rootfs_disk: Option<PathBuf>,
+    /// Data disks attached via virtio-blk, one per data-disk slot.
+    pub disks: Vec<crate::backend::DiskConfig>,
+    /// Attach a virtio-rng device so the guest seeds its CRNG at boot.
+    pub enable_rng: bool,
+    /// Attach a virtio-balloon device so the host can reclaim idle guest memory.
+    pub enable_balloon: bool,
+    /// Host-enforced vCPU pinning and CPU quota.
+    pub resource_policy: crate::backend::ResourcePolicy,
+    /// Guest-physical base of the virtio-mmio device windows (architecture
+    /// default when `None`). See [`crate::vmm::layout::MemoryLayout`].
+    pub virtio_mmio_base: Option<u64>,
+    /// Attach a watchdog the guest-agent must pet within this timeout.
+    /// See [`crate::devices::watchdog`].
+    pub watchdog: Option<std::time::Duration>,
+    /// Carry the guest-agent control channel over a virtio-console port
+    /// instead of vsock. See [`crate::devices::virtio_console`].
+    pub control_port: bool,
     /// Enable vsock for host-guest communication
     pub enable_vsock: bool,
     /// Vsock backend type (Vhost = default, Userspace = for snapshot/restore)
     pub vsock_backend: VsockBackendType,
     /// Vsock context ID (auto-generated if not specified)
     pub cid: Option<u32>,
-    /// Additional kernel command line arguments
+    /// User kernel cmdline fragments, appended after the generated
+    /// arguments and checked by [`validate`](Self::validate); see
+    /// [`crate::backend::cmdline`].
     pub extra_cmdline: Vec<String>,
+    /// `voidbox.*` arguments the backend passes to the guest-agent. Part of
+    /// the generated cmdline, so not subject to the fragment checks.
+    pub agent_cmdline: Vec<String>,
     /// Security configuration (auth, allowlists, limits, seccomp).
     pub security: SecurityConfig,
+    /// Upstream resolvers and static host entries for the SLIRP DNS server.
+    pub dns: crate::backend::DnsConfig,
+    /// Callback for outbound guest TCP connections through SLIRP.
+    pub connection_observer: Option<crate::backend::ConnectionObserver>,
 }
 
 impl Default for VoidBoxConfig {
@@ -124,16 +160,27 @@ impl Default for VoidBoxConfig {
             rootfs: None,
             network: false,
             tap_name: None,
+            network_mode: Default::default(),
             shared_dir: None,
             mounts: Vec::new(),
             oci_rootfs: None,
             oci_rootfs_dev: None,
             oci_rootfs_disk: None,
+            disks: Vec::new(),
+            enable_rng: true,
+            enable_balloon: true,
+            resource_policy: Default::default(),
+            virtio_mmio_base: None,
+            watchdog: None,
+            control_port: false,
             enable_vsock: true,
             vsock_backend: VsockBackendType::default(),
             cid: None,
             extra_cmdline: Vec::new(),
+            agent_cmdline: Vec::new(),
             security: SecurityConfig::default(),
+            dns: Default::default(),
+            connection_observer: None,
         }
     }
 }
@@ -186,12 +233,61 @@ impl VoidBoxConfig {
         self
     }
 
+    /// Set how the virtio-net device reaches the host network
+    pub fn network_mode(mut self, mode: crate::backend::NetworkMode) -> Self {
+        self.network_mode = mode;
+        self
+    }
+
     /// Set the shared directory path
     pub fn shared_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.shared_dir = Some(path.into());
         self
     }
 
+    /// Attach a virtio-blk data disk
+    pub fn disk(mut self, disk: crate::backend::DiskConfig) -> Self {
+        self.disks.push(disk);
+        self
+    }
+
+    /// Enable or disable the virtio-rng entropy device
+    pub fn enable_rng(mut self, enable: bool) -> Self {
+        self.enable_rng = enable;
+        self
+    }
+
+    /// Enable or disable the virtio-balloon memory reclaim device
+    pub fn enable_balloon(mut self, enable: bool) -> Self {
+        self.enable_balloon = enable;
+        self
+    }
+
+    /// Set the host-enforced vCPU pinning and CPU quota
+    pub fn resource_policy(mut self, policy: crate::backend::ResourcePolicy) -> Self {
+        self.resource_policy = policy;
+        self
+    }
+
+    /// Move the virtio-mmio device windows to start at `base`
+    pub fn virtio_mmio_base(mut self, base: u64) -> Self {
+        self.virtio_mmio_base = Some(base);
+        self
+    }
+
+    /// Attach a watchdog the guest-agent pets; the host notices a guest
+    /// that goes `timeout` without petting it
+    pub fn watchdog(mut self, timeout: std::time::Duration) -> Self {
+        self.watchdog = Some(timeout);
+        self
+    }
+
+    /// Attach a virtio-console port for the guest-agent control channel
+    pub fn control_port(mut self, enable: bool) -> Self {
+        self.control_port = enable;
+        self
+    }
+
     /// Enable or disable vsock
     pub fn enable_vsock(mut self, enable: bool) -> Self {
         self.enable_vsock = enable;
@@ -210,9 +306,13 @@ impl VoidBoxConfig {
         self
     }
 
-    /// Add extra kernel command line arguments
+    /// Add kernel command line arguments, split on whitespace (e.g.
+    /// `"transparent_hugepage=never quiet"`). [`validate`](Self::validate)
+    /// refuses reserved keys and conflicts with the generated arguments;
+    /// see [`crate::backend::cmdline`].
     pub fn extra_cmdline<S: Into<String>>(mut self, args: S) -> Self {
-        self.extra_cmdline.push(args.into());
+        self.extra_cmdline
+            .extend(crate::backend::cmdline::split(&args.into()));
         self
     }
 
@@ -236,11 +336,47 @@ impl VoidBoxConfig {
         if self.oci_rootfs_disk.is_some() {
             slots.push(VirtioSlot::Blk);
         }
+        slots.extend(VirtioSlot::DATA_DISKS.iter().take(self.disks.len()));
+        if self.enable_rng {
+            slots.push(VirtioSlot::Rng);
+        }
+        if self.enable_balloon {
+            slots.push(VirtioSlot::Balloon);
+        }
+        if self.control_port {
+            slots.push(VirtioSlot::Console);
+        }
         slots
     }
 
+    /// Guest-physical address map for this configuration: RAM plus one
+    /// window per [populated slot](Self::populated_virtio_slots).
+    pub fn memory_layout(&self) -> crate::vmm::layout::MemoryLayout {
+        crate::vmm::layout::MemoryLayout::new(
+            (self.memory_mb as u64) << 20,
+            self.virtio_mmio_base,
+            &self.populated_virtio_slots(),
+        )
+    }
+
+    /// Guest block device name of data disk `index`. virtio-blk names
+    /// devices in probe order, which follows slot order, so the OCI rootfs
+    /// disk (when present) takes `/dev/vda`.
+    pub fn disk_guest_dev(&self, index: usize) -> String {
+        let letter = b'a' + self.oci_rootfs_disk.is_some() as u8 + index as u8;
+        format!("/dev/vd{}", letter as char)
+    }
+
     /// Build the kernel command line string
     pub fn kernel_cmdline(&self) -> String {
+        let mut cmdline = self.generated_cmdline();
+        crate::backend::cmdline::extend(&mut cmdline, &self.extra_cmdline);
+        cmdline.join(" ")
+    }
+
+    /// The arguments void-box generates, before
+    /// [`extra_cmdline`](Self::extra_cmdline).
+    fn generated_cmdline(&self) -> Vec<String> {
         // The x86_64 list is byte-identical to the pre-RFC-0003 cmdline —
         // the guest-agent matches some of these tokens exactly. The
         // aarch64 list drops the x86 hardware quirks (i8042, cmos, PCI,
@@ -281,24 +417,33 @@ impl VoidBoxConfig {
         // the Linux virtio_mmio driver). On aarch64, `voidbox.network=1` is
         // the platform-neutral marker the guest-agent reads (macOS/VZ
         // already uses it).
+        #[cfg(target_arch = "x86_64")]
+        let layout = self.memory_layout();
+        #[cfg(target_arch = "x86_64")]
+        let mut windows = layout.windows().iter().peekable();
         if self.network {
             #[cfg(target_arch = "x86_64")]
-            cmdline.push("virtio_mmio.device=512@0xd0000000:10".to_string());
+            if let Some(net) = windows.next_if(|w| w.slot == crate::vmm::arch::VirtioSlot::Net) {
+                cmdline.push(net.kernel_cmdline_arg());
+            }
             #[cfg(target_arch = "aarch64")]
             cmdline.push("voidbox.network=1".to_string());
             // Disable IPv6 - our SLIRP stack only supports IPv4
             cmdline.push("ipv6.disable=1".to_string());
         }
         #[cfg(target_arch = "x86_64")]
-        {
-            if self.enable_vsock {
-                cmdline.push("virtio_mmio.device=512@0xd0800000:11".to_string());
-            }
-            if !self.mounts.is_empty() {
-                cmdline.push("virtio_mmio.device=512@0xd1000000:12".to_string());
-            }
-            if self.oci_rootfs_disk.is_some() {
-                cmdline.push("virtio_mmio.device=512@0xd1800000:13".to_string());
+        cmdline.extend(windows.map(|w| w.kernel_cmdline_arg()));
+
+        // Data disks the guest-agent mounts: voidbox.disk<N>=<dev>:<path>:<ro|rw>
+        for (i, disk) in self.disks.iter().enumerate() {
+            if let Some(ref guest_path) = disk.guest_path {
+                cmdline.push(format!(
+                    "voidbox.disk{}={}:{}:{}",
+                    i,
+                    self.disk_guest_dev(i),
+                    guest_path,
+                    if disk.read_only { "ro" } else { "rw" }
+                ));
             }
         }
 
@@ -309,6 +454,18 @@ impl VoidBoxConfig {
             cmdline.push("rw".to_string());
         }
 
+        if let Some(timeout) = self.watchdog {
+            cmdline.push(format!(
+                "{}{}",
+                void_box_protocol::WATCHDOG_CMDLINE_KEY,
+                timeout.as_secs()
+            ));
+        }
+
+        if self.control_port {
+            cmdline.push(void_box_protocol::CONTROL_CMDLINE_ARG.to_string());
+        }
+
         // Inject host wall-clock so the guest can set its system time.
         // Without this, the guest starts at epoch (1970) and TLS cert
         // validation fails.
@@ -322,15 +479,22 @@ impl VoidBoxConfig {
             epoch_secs,
             self.network,
             false,
+            // Under SLIRP the guest keeps the SLIRP DNS server, which
+            // forwards to `self.dns.servers`; a TAP guest uses them directly
+            // (and ignores them if vhost-net falls back to SLIRP).
+            match self.network_mode {
+                crate::backend::NetworkMode::Slirp => &[],
+                crate::backend::NetworkMode::Tap { .. }
+                | crate::backend::NetworkMode::VhostNet { .. } => &self.dns.servers,
+            },
             &self.mounts,
             self.oci_rootfs.as_deref(),
             self.oci_rootfs_dev.as_deref(),
+            &self.security.write_roots,
         );
 
-        // Add extra arguments
-        cmdline.extend(self.extra_cmdline.clone());
-
-        cmdline.join(" ")
+        cmdline.extend(self.agent_cmdline.iter().cloned());
+        cmdline
     }
 
     /// Validate the configuration
@@ -392,6 +556,36 @@ impl VoidBoxConfig {
             )));
         }
 
+        if self
+            .watchdog
+            .is_some_and(|timeout| timeout < std::time::Duration::from_secs(1))
+        {
+            return Err(Error::Config(
+                "watchdog timeout must be at least 1 second".into(),
+            ));
+        }
+
+        crate::backend::cmdline::validate(&self.generated_cmdline(), &self.extra_cmdline)?;
+        self.resource_policy.validate()?;
+        self.memory_layout().validate()?;
+        crate::vmm::throttle::validate_affinity(&self.resource_policy.cpu_affinity)?;
+
+        let max_disks = crate::vmm::arch::VirtioSlot::DATA_DISKS.len();
+        if self.disks.len() > max_disks {
+            return Err(Error::Config(format!(
+                "At most {} data disks supported",
+                max_disks
+            )));
+        }
+        for disk in &self.disks {
+            if !disk.path.is_file() {
+                return Err(Error::Config(format!(
+                    "Disk image not found: {}",
+                    disk.path.display()
+                )));
+            }
+        }
+
         // Validate CID if specified (must be > 2)
         if let Some(cid) = self.cid {
             if cid < 3 {
@@ -454,6 +648,12 @@ mod tests {
         ));
         assert!(cmdline.contains("virtio_mmio.device=512@0xd0000000:10 ipv6.disable=1"));
         assert!(cmdline.contains("virtio_mmio.device=512@0xd0800000:11"));
+        assert!(cmdline.contains("virtio_mmio.device=512@0xd4000000:18"));
+        assert!(cmdline.contains("virtio_mmio.device=512@0xd4800000:19"));
+        assert!(!VoidBoxConfig::new()
+            .enable_rng(false)
+            .kernel_cmdline()
+            .contains("0xd4000000"));
     }
 
     /// aarch64 devices are declared in the DTB; the cmdline must not also
@@ -476,13 +676,94 @@ mod tests {
     fn test_populated_virtio_slots_match_config() {
         use crate::vmm::arch::VirtioSlot;
 
-        let config = VoidBoxConfig::new().network(true); // vsock on by default
+        let config = VoidBoxConfig::new().network(true); // vsock, rng, balloon on by default
         assert_eq!(
             config.populated_virtio_slots(),
-            vec![VirtioSlot::Net, VirtioSlot::Vsock]
+            vec![
+                VirtioSlot::Net,
+                VirtioSlot::Vsock,
+                VirtioSlot::Rng,
+                VirtioSlot::Balloon
+            ]
         );
-        let config = VoidBoxConfig::new().enable_vsock(false);
+        let config = VoidBoxConfig::new()
+            .enable_vsock(false)
+            .enable_rng(false)
+            .enable_balloon(false);
         assert!(config.populated_virtio_slots().is_empty());
+        let config = VoidBoxConfig::new().enable_balloon(false).control_port(true);
+        assert_eq!(
+            config.populated_virtio_slots(),
+            vec![VirtioSlot::Vsock, VirtioSlot::Rng, VirtioSlot::Console]
+        );
+        assert!(config.kernel_cmdline().contains("voidbox.control=serial"));
+    }
+
+    #[test]
+    fn test_data_disks_follow_oci_disk() {
+        use crate::backend::DiskConfig;
+        use crate::vmm::arch::VirtioSlot;
+
+        let disk = |guest_path: Option<&str>| DiskConfig {
+            path: PathBuf::from("/tmp/disk.img"),
+            read_only: false,
+            guest_path: guest_path.map(String::from),
+        };
+        let mut config = VoidBoxConfig::new()
+            .enable_vsock(false)
+            .enable_rng(false)
+            .enable_balloon(false)
+            .disk(disk(Some("/scratch")))
+            .disk(disk(None));
+        config.oci_rootfs_disk = Some(PathBuf::from("/tmp/rootfs.img"));
+
+        assert_eq!(
+            config.populated_virtio_slots(),
+            vec![VirtioSlot::Blk, VirtioSlot::Disk0, VirtioSlot::Disk1]
+        );
+        let cmdline = config.kernel_cmdline();
+        assert!(cmdline.contains("voidbox.disk0=/dev/vdb:/scratch:rw"));
+        assert!(!cmdline.contains("voidbox.disk1="));
+        #[cfg(target_arch = "x86_64")]
+        assert!(cmdline
+            .contains("virtio_mmio.device=512@0xd2000000:14 virtio_mmio.device=512@0xd2800000:15"));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_virtio_mmio_base_moves_cmdline_windows() {
+        let config = VoidBoxConfig::new()
+            .network(true)
+            .enable_rng(false)
+            .enable_balloon(false)
+            .virtio_mmio_base(0x8000_0000);
+        let cmdline = config.kernel_cmdline();
+        assert!(cmdline.contains("virtio_mmio.device=512@0x80000000:10 ipv6.disable=1"));
+        assert!(cmdline.contains("virtio_mmio.device=512@0x80800000:11"));
+        assert!(!cmdline.contains("0xd0000000"));
+    }
+
+    #[test]
+    fn test_watchdog_timeout_is_announced_on_cmdline() {
+        let config = VoidBoxConfig::new().watchdog(std::time::Duration::from_secs(15));
+        assert!(config.kernel_cmdline().contains("voidbox.watchdog=15"));
+        assert!(!VoidBoxConfig::new()
+            .kernel_cmdline()
+            .contains("voidbox.watchdog"));
+    }
+
+    #[test]
+    fn test_extra_cmdline_conflicts_with_generated_args() {
+        let config = VoidBoxConfig::new().extra_cmdline("panic=10");
+        assert!(crate::backend::cmdline::validate(
+            &config.generated_cmdline(),
+            &config.extra_cmdline
+        )
+        .is_err());
+        let config = VoidBoxConfig::new().extra_cmdline("panic=1 mitigations=off");
+        crate::backend::cmdline::validate(&config.generated_cmdline(), &config.extra_cmdline)
+            .unwrap();
+        assert_eq!(config.kernel_cmdline().matches("panic=1").count(), 1);
     }
 
     #[test]
@@ -4,20 +4,27 @@
 //! delegated to [`crate::vmm::arch::CurrentArch`].
 
 use std::os::unix::io::AsRawFd;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use kvm_ioctls::VcpuExit;
 use tracing::{debug, error, trace, warn};
 
 use crate::devices::serial::SerialDevice;
 use crate::devices::virtio_9p::Virtio9pDevice;
+use crate::devices::virtio_balloon::VirtioBalloonDevice;
+use crate::devices::virtio_console::VirtioConsoleDevice;
 use crate::devices::virtio_blk::VirtioBlkDevice;
 use crate::devices::virtio_net::VirtioNetDevice;
+use crate::devices::virtio_net_vhost::VhostNetDevice;
+use crate::devices::virtio_rng::VirtioRngDevice;
 use crate::devices::vsock_backend::VsockMmioDevice;
+use crate::devices::watchdog::WatchdogDevice;
 use crate::vmm::arch::{self, Arch, CurrentArch};
 use crate::vmm::kvm::Vm;
+use crate::vmm::throttle::{self, CpuThrottle, VcpuLimits};
 use crate::{Error, Result};
 
 /// Handle to a running vCPU thread
@@ -28,6 +35,8 @@ pub struct VcpuHandle {
     exit_state: Arc<Mutex<Option<arch::VcpuState>>>,
     /// Native pthread ID for signaling the vCPU thread out of KVM_RUN.
     pthread_id: Arc<std::sync::atomic::AtomicU64>,
+    /// Kernel thread ID, for reading the thread's CPU time from procfs.
+    tid: Arc<std::sync::atomic::AtomicU32>,
 }
 
 impl VcpuHandle {
@@ -56,6 +65,11 @@ impl VcpuHandle {
         self.pthread_id.clone()
     }
 
+    /// Get a clone of the kernel thread ID Arc; zero until the thread starts.
+    pub fn tid(&self) -> Arc<std::sync::atomic::AtomicU32> {
+        self.tid.clone()
+    }
+
     /// Send a signal to the vCPU thread to kick it out of KVM_RUN (causes EINTR).
     pub fn kick(&self) {
         let tid = self.pthread_id.load(std::sync::atomic::Ordering::SeqCst);
@@ -70,9 +84,131 @@ impl VcpuHandle {
 /// MMIO device bundle passed into the vCPU run loop for dispatch
 pub struct MmioDevices {
     pub virtio_net: Option<Arc<Mutex<VirtioNetDevice>>>,
+    /// Occupies the net slot instead of `virtio_net` when vhost-net is active.
+    pub vhost_net: Option<Arc<Mutex<VhostNetDevice>>>,
     pub virtio_vsock: Option<Arc<Mutex<dyn VsockMmioDevice>>>,
     pub virtio_9p: Option<Arc<Mutex<Virtio9pDevice>>>,
     pub virtio_blk: Option<Arc<Mutex<VirtioBlkDevice>>>,
+    /// virtio-blk data disks, each with the slot it occupies.
+    pub data_disks: Vec<(arch::VirtioSlot, Arc<Mutex<VirtioBlkDevice>>)>,
+    pub virtio_rng: Option<Arc<Mutex<VirtioRngDevice>>>,
+    pub virtio_balloon: Option<Arc<Mutex<VirtioBalloonDevice>>>,
+    pub virtio_console: Option<Arc<Mutex<VirtioConsoleDevice>>>,
+    pub watchdog: Option<Arc<WatchdogDevice>>,
+}
+
+/// Coordinates an in-place guest reboot across the vCPU threads.
+///
+/// A guest reset normally ends the VM: it is how an x86 guest powers off
+/// (`reboot=k`) and how a panicking guest leaves (`panic=1`). Once the host
+/// [`arm`](Self::arm)s a reboot, the next reset instead parks every vCPU
+/// until the host has reloaded the kernel and
+/// [`release`](Self::release)s them at its entry point.
+#[derive(Default)]
+pub struct GuestReset {
+    state: Mutex<ResetState>,
+    changed: Condvar,
+    /// Set while a reset is in progress; polled by every run loop.
+    pending: AtomicBool,
+    /// pthread IDs of the vCPU threads, for kicking them out of KVM_RUN.
+    vcpus: Mutex<Vec<Arc<AtomicU64>>>,
+}
+
+#[derive(Default)]
+struct ResetState {
+    armed: bool,
+    parked: usize,
+    /// Bumped by every release; parked vCPUs wait for it to change.
+    generation: u64,
+    entry_point: u64,
+}
+
+/// How often a parked vCPU rechecks whether the VM was stopped instead.
+const RESET_PARK_POLL: Duration = Duration::from_millis(50);
+
+impl GuestReset {
+    /// Treat the next guest reset as a reboot rather than the guest leaving.
+    pub fn arm(&self) {
+        self.state.lock().unwrap().armed = true;
+    }
+
+    /// Withdraw an [`arm`](Self::arm) that no reset has consumed.
+    pub fn disarm(&self) {
+        self.state.lock().unwrap().armed = false;
+    }
+
+    /// Block until `vcpus` vCPU threads have parked, or `timeout` passes.
+    /// Returns whether they all did.
+    pub fn wait_parked(&self, vcpus: usize, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| state.parked < vcpus)
+            .unwrap();
+        state.parked >= vcpus
+    }
+
+    /// Start the parked vCPUs again at `entry_point`.
+    pub fn release(&self, entry_point: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.entry_point = entry_point;
+        state.parked = 0;
+        state.generation += 1;
+        self.pending.store(false, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+
+    fn register(&self, pthread_id: Arc<AtomicU64>) {
+        self.vcpus.lock().unwrap().push(pthread_id);
+    }
+
+    fn pending(&self) -> bool {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Called by the vCPU that saw the guest reset. `false` means no reboot
+    /// was armed and the VM should stop.
+    fn begin(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !std::mem::take(&mut state.armed) {
+            return false;
+        }
+        self.pending.store(true, Ordering::SeqCst);
+        for pthread_id in self.vcpus.lock().unwrap().iter() {
+            let tid = pthread_id.load(Ordering::SeqCst);
+            if tid != 0 {
+                unsafe {
+                    libc::pthread_kill(tid as libc::pthread_t, libc::SIGRTMIN());
+                }
+            }
+        }
+        true
+    }
+
+    /// Park the calling vCPU until [`release`](Self::release). Returns the
+    /// entry point to restart at, or `None` once `running` is cleared.
+    fn park(&self, running: &AtomicBool) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state.parked += 1;
+        self.changed.notify_all();
+        while state.generation == generation {
+            if !running.load(Ordering::SeqCst) {
+                return None;
+            }
+            state = self.changed.wait_timeout(state, RESET_PARK_POLL).unwrap().0;
+        }
+        Some(state.entry_point)
+    }
+}
+
+/// Why a vCPU run loop returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunExit {
+    /// The VM is stopping.
+    Stopped,
+    /// An armed [`GuestReset`] is in progress.
+    Reset,
 }
 
 /// A vCPU that has been created and configured but not started.
@@ -84,6 +220,9 @@ pub struct MmioDevices {
 pub struct PreparedVcpu {
     vcpu_fd: kvm_ioctls::VcpuFd,
     id: u64,
+    /// State right after cold-boot configuration, which a reboot returns
+    /// the vCPU to. `None` for restored vCPUs.
+    power_on: Option<arch::VcpuState>,
 }
 
 /// Create and configure a vCPU with fresh register state (cold boot).
@@ -93,10 +232,12 @@ pub fn prepare_vcpu(vm: &Vm, vcpu_id: u64, entry_point: u64) -> Result<PreparedV
 
     // Delegate arch-specific configuration (CPUID + regs on x86, vcpu_init on aarch64)
     CurrentArch::configure_vcpu(&vcpu_fd, vcpu_id, entry_point, vm)?;
+    let power_on = CurrentArch::capture_vcpu_state(&vcpu_fd)?;
 
     Ok(PreparedVcpu {
         vcpu_fd,
         id: vcpu_id,
+        power_on: Some(power_on),
     })
 }
 
@@ -125,6 +266,7 @@ pub fn prepare_vcpu_restored(
     Ok(PreparedVcpu {
         vcpu_fd,
         id: vcpu_id,
+        power_on: None,
     })
 }
 
@@ -135,15 +277,10 @@ pub fn start_vcpu(
     running: Arc<AtomicBool>,
     serial: SerialDevice,
     mmio_devices: MmioDevices,
+    limits: VcpuLimits,
+    reset: Arc<GuestReset>,
 ) -> Result<VcpuHandle> {
-    spawn_vcpu_thread(
-        vm,
-        prepared.vcpu_fd,
-        prepared.id,
-        running,
-        serial,
-        mmio_devices,
-    )
+    spawn_vcpu_thread(vm, prepared, running, serial, mmio_devices, limits, reset)
 }
 
 /// Install a no-op signal handler for SIGRTMIN so that `pthread_kill(SIGRTMIN)`
@@ -162,16 +299,25 @@ pub fn install_vcpu_signal_handler() {
 
 fn spawn_vcpu_thread(
     vm: Arc<Vm>,
-    vcpu_fd: kvm_ioctls::VcpuFd,
-    vcpu_id: u64,
+    prepared: PreparedVcpu,
     running: Arc<AtomicBool>,
-    serial: SerialDevice,
+    mut serial: SerialDevice,
     mmio_devices: MmioDevices,
+    limits: VcpuLimits,
+    reset: Arc<GuestReset>,
 ) -> Result<VcpuHandle> {
+    let PreparedVcpu {
+        mut vcpu_fd,
+        id: vcpu_id,
+        power_on,
+    } = prepared;
     let exit_state: Arc<Mutex<Option<arch::VcpuState>>> = Arc::new(Mutex::new(None));
     let exit_state_clone = exit_state.clone();
     let pthread_id = Arc::new(std::sync::atomic::AtomicU64::new(0));
     let pthread_id_clone = pthread_id.clone();
+    let tid = Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let tid_clone = tid.clone();
+    reset.register(pthread_id.clone());
 
     let thread = thread::Builder::new()
         .name(format!("vcpu-{}", vcpu_id))
@@ -181,15 +327,57 @@ fn spawn_vcpu_thread(
                 unsafe { libc::pthread_self() } as u64,
                 std::sync::atomic::Ordering::SeqCst,
             );
-            vcpu_run_loop(
-                vcpu_fd,
-                vcpu_id,
-                running,
-                serial,
-                vm,
-                mmio_devices,
-                exit_state_clone,
+            tid_clone.store(
+                unsafe { libc::gettid() } as u32,
+                std::sync::atomic::Ordering::SeqCst,
             );
+            if let Some(cpu) = limits.cpu {
+                match throttle::pin_current_thread(cpu) {
+                    Ok(()) => debug!("vCPU {} pinned to host CPU {}", vcpu_id, cpu),
+                    Err(e) => warn!("vCPU {}: {}", vcpu_id, e),
+                }
+            }
+            let mut cpu_throttle = limits.budget.map(CpuThrottle::new);
+            while vcpu_run_loop(
+                &mut vcpu_fd,
+                vcpu_id,
+                &running,
+                &mut serial,
+                &vm,
+                &mmio_devices,
+                &mut cpu_throttle,
+                &reset,
+            ) == RunExit::Reset
+            {
+                let Some(entry_point) = reset.park(&running) else {
+                    break;
+                };
+                let restarted = match &power_on {
+                    Some(power_on) => {
+                        CurrentArch::reset_vcpu(&vcpu_fd, vcpu_id, entry_point, &vm, power_on)
+                    }
+                    None => Err(Error::Vcpu("restored vCPU has no power-on state".into())),
+                };
+                match restarted {
+                    Ok(()) => debug!("vCPU {} restarting at {:#x}", vcpu_id, entry_point),
+                    Err(e) => {
+                        error!("vCPU {} reset failed: {}", vcpu_id, e);
+                        running.store(false, Ordering::SeqCst);
+                        break;
+                    }
+                }
+            }
+
+            // Capture vCPU state before exiting (for snapshot support).
+            match CurrentArch::capture_vcpu_state(&vcpu_fd) {
+                Ok(state) => {
+                    *exit_state_clone.lock().unwrap() = Some(state);
+                }
+                Err(e) => {
+                    debug!("vCPU {}: state capture failed (non-fatal): {}", vcpu_id, e);
+                }
+            }
+            debug!("vCPU {} exiting run loop", vcpu_id);
         })
         .map_err(|e| Error::Vcpu(format!("Failed to spawn vCPU thread: {}", e)))?;
 
@@ -198,24 +386,30 @@ fn spawn_vcpu_thread(
         id: vcpu_id,
         exit_state,
         pthread_id,
+        tid,
     })
 }
 
-/// vCPU run loop - executes vCPU and handles VM exits
+/// vCPU run loop - executes vCPU and handles VM exits until the VM stops
+/// or an armed [`GuestReset`] begins.
 #[allow(clippy::too_many_arguments)]
 fn vcpu_run_loop(
-    mut vcpu_fd: kvm_ioctls::VcpuFd,
+    vcpu_fd: &mut kvm_ioctls::VcpuFd,
     vcpu_id: u64,
-    running: Arc<AtomicBool>,
-    mut serial: SerialDevice,
-    vm: Arc<Vm>,
-    mmio_devices: MmioDevices,
-    exit_state: Arc<Mutex<Option<arch::VcpuState>>>,
-) {
+    running: &AtomicBool,
+    serial: &mut SerialDevice,
+    vm: &Vm,
+    mmio_devices: &MmioDevices,
+    cpu_throttle: &mut Option<CpuThrottle>,
+    reset: &GuestReset,
+) -> RunExit {
     debug!("vCPU {} entering run loop", vcpu_id);
     let guest_memory = vm.guest_memory();
     let mut p9_irq_notified = false;
     let mut blk_irq_notified = false;
+    let mut disk_irq_notified = vec![false; mmio_devices.data_disks.len()];
+    let mut rng_irq_notified = false;
+    let mut balloon_irq_notified = false;
     let mut exit_count: u64 = 0;
     let mut hlt_count: u64 = 0;
 
@@ -231,6 +425,9 @@ fn vcpu_run_loop(
     }
 
     while running.load(Ordering::SeqCst) {
+        if reset.pending() {
+            return RunExit::Reset;
+        }
         // Device polling/IRQ injection is handled by vCPU0 only.
         if vcpu_id == 0 {
             if let Some(ref dev) = mmio_devices.virtio_9p {
@@ -254,6 +451,40 @@ fn vcpu_run_loop(
                     blk_irq_notified = false;
                 }
             }
+
+            for ((slot, dev), notified) in mmio_devices
+                .data_disks
+                .iter()
+                .zip(disk_irq_notified.iter_mut())
+            {
+                let pending = dev.lock().unwrap().has_pending_interrupt();
+                if pending && !*notified {
+                    inject_irq(vm.vm_fd().as_raw_fd(), *slot);
+                    *notified = true;
+                } else if !pending {
+                    *notified = false;
+                }
+            }
+
+            if let Some(ref dev) = mmio_devices.virtio_rng {
+                let pending = dev.lock().unwrap().has_pending_interrupt();
+                if pending && !rng_irq_notified {
+                    inject_irq(vm.vm_fd().as_raw_fd(), arch::VirtioSlot::Rng);
+                    rng_irq_notified = true;
+                } else if !pending {
+                    rng_irq_notified = false;
+                }
+            }
+
+            if let Some(ref dev) = mmio_devices.virtio_balloon {
+                let pending = dev.lock().unwrap().has_pending_interrupt();
+                if pending && !balloon_irq_notified {
+                    inject_irq(vm.vm_fd().as_raw_fd(), arch::VirtioSlot::Balloon);
+                    balloon_irq_notified = true;
+                } else if !pending {
+                    balloon_irq_notified = false;
+                }
+            }
         }
 
         match vcpu_fd.run() {
@@ -263,16 +494,39 @@ fn vcpu_run_loop(
 
                 match exit_reason {
                     VcpuExit::IoOut(port, data) => {
-                        handle_io_out(port, data, &mut serial);
+                        if let Some(ref watchdog) = mmio_devices.watchdog {
+                            if watchdog.io_out(port) {
+                                continue;
+                            }
+                        }
+                        if handle_io_out(port, data, serial) {
+                            debug!("vCPU {} guest reset via port 0x64", vcpu_id);
+                            if reset.begin() {
+                                return RunExit::Reset;
+                            }
+                            running.store(false, Ordering::SeqCst);
+                            break;
+                        }
                     }
                     VcpuExit::IoIn(port, data) => {
-                        handle_io_in(port, data, &serial);
+                        if let Some(ref watchdog) = mmio_devices.watchdog {
+                            if watchdog.io_in(port, data) {
+                                continue;
+                            }
+                        }
+                        handle_io_in(port, data, serial);
                     }
                     VcpuExit::MmioRead(addr, data) => {
                         #[cfg(target_arch = "aarch64")]
-                        if uart_mmio::try_read(&serial, addr, data) {
+                        if uart_mmio::try_read(serial, addr, data) {
                             continue;
                         }
+                        #[cfg(target_arch = "aarch64")]
+                        if let Some(ref watchdog) = mmio_devices.watchdog {
+                            if watchdog.mmio_read(addr, data) {
+                                continue;
+                            }
+                        }
                         let handled = if let Some(ref dev) = mmio_devices.virtio_net {
                             let mut guard = dev.lock().unwrap();
                             if guard.handles_mmio(addr) {
@@ -291,6 +545,19 @@ fn vcpu_run_loop(
                         } else {
                             false
                         };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.vhost_net {
+                                let guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_read(offset, data);
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
                         let handled = handled
                             || if let Some(ref dev) = mmio_devices.virtio_vsock {
                                 let guard = dev.lock().unwrap();
@@ -317,6 +584,56 @@ fn vcpu_run_loop(
                             } else {
                                 false
                             };
+                        let handled = handled
+                            || mmio_devices.data_disks.iter().any(|(_, dev)| {
+                                let guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_read(offset, data);
+                                    true
+                                } else {
+                                    false
+                                }
+                            });
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_rng {
+                                let guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_read(offset, data);
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_balloon {
+                                let guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_read(offset, data);
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_console {
+                                let guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_read(offset, data);
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
 
                         if !handled {
                             if let Some(ref dev) = mmio_devices.virtio_9p {
@@ -334,9 +651,15 @@ fn vcpu_run_loop(
                     }
                     VcpuExit::MmioWrite(addr, data) => {
                         #[cfg(target_arch = "aarch64")]
-                        if uart_mmio::try_write(&mut serial, addr, data) {
+                        if uart_mmio::try_write(serial, addr, data) {
                             continue;
                         }
+                        #[cfg(target_arch = "aarch64")]
+                        if let Some(ref watchdog) = mmio_devices.watchdog {
+                            if watchdog.mmio_write(addr) {
+                                continue;
+                            }
+                        }
                         let handled = if let Some(ref dev) = mmio_devices.virtio_net {
                             let mut guard = dev.lock().unwrap();
                             if guard.handles_mmio(addr) {
@@ -354,6 +677,21 @@ fn vcpu_run_loop(
                         } else {
                             false
                         };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.vhost_net {
+                                let mut guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    if let Err(e) = guard.mmio_write(offset, data, guest_memory) {
+                                        debug!("vhost-net MMIO write error: {}", e);
+                                    }
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
                         let handled = handled
                             || if let Some(ref dev) = mmio_devices.virtio_vsock {
                                 let mut guard = dev.lock().unwrap();
@@ -385,6 +723,77 @@ fn vcpu_run_loop(
                             } else {
                                 false
                             };
+                        let handled = handled
+                            || mmio_devices.data_disks.iter().any(|(slot, dev)| {
+                                let mut guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_write(offset, data, Some(guest_memory));
+                                    if guard.has_pending_interrupt() {
+                                        inject_irq(vm.vm_fd().as_raw_fd(), *slot);
+                                    }
+                                    true
+                                } else {
+                                    false
+                                }
+                            });
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_rng {
+                                let mut guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_write(offset, data, Some(guest_memory));
+                                    if guard.has_pending_interrupt() {
+                                        inject_irq(vm.vm_fd().as_raw_fd(), arch::VirtioSlot::Rng);
+                                    }
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_balloon {
+                                let mut guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_write(offset, data, Some(guest_memory));
+                                    if guard.has_pending_interrupt() {
+                                        inject_irq(
+                                            vm.vm_fd().as_raw_fd(),
+                                            arch::VirtioSlot::Balloon,
+                                        );
+                                    }
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
+                        let handled = handled
+                            || if let Some(ref dev) = mmio_devices.virtio_console {
+                                let mut guard = dev.lock().unwrap();
+                                if guard.handles_mmio(addr) {
+                                    let offset = addr - guard.mmio_base();
+                                    guard.mmio_write(offset, data, Some(guest_memory));
+                                    // The I/O thread raises interrupts too;
+                                    // take_interrupt keeps the two from
+                                    // swallowing each other's.
+                                    if guard.take_interrupt() {
+                                        inject_irq(
+                                            vm.vm_fd().as_raw_fd(),
+                                            arch::VirtioSlot::Console,
+                                        );
+                                    }
+                                    true
+                                } else {
+                                    false
+                                }
+                            } else {
+                                false
+                            };
 
                         if !handled {
                             if let Some(ref dev) = mmio_devices.virtio_9p {
@@ -420,6 +829,9 @@ fn vcpu_run_loop(
                             "vCPU {} system event {} (guest shutdown/reset)",
                             vcpu_id, event_type
                         );
+                        if event_type == kvm_bindings::KVM_SYSTEM_EVENT_RESET && reset.begin() {
+                            return RunExit::Reset;
+                        }
                         running.store(false, Ordering::SeqCst);
                         break;
                     }
@@ -444,6 +856,9 @@ fn vcpu_run_loop(
             Err(e) => {
                 if e.errno() == libc::EINTR {
                     debug!("vCPU {} interrupted (EINTR), exits={}", vcpu_id, exit_count);
+                    if let Some(ref mut cpu_throttle) = cpu_throttle {
+                        cpu_throttle.throttle();
+                    }
                     continue;
                 }
                 error!("vCPU {} run error: {}", vcpu_id, e);
@@ -453,17 +868,7 @@ fn vcpu_run_loop(
         }
     }
 
-    // Capture vCPU state before exiting (for snapshot support).
-    match CurrentArch::capture_vcpu_state(&vcpu_fd) {
-        Ok(state) => {
-            *exit_state.lock().unwrap() = Some(state);
-        }
-        Err(e) => {
-            debug!("vCPU {}: state capture failed (non-fatal): {}", vcpu_id, e);
-        }
-    }
-
-    debug!("vCPU {} exiting run loop", vcpu_id);
+    RunExit::Stopped
 }
 
 /// MMIO-mapped UART dispatch (aarch64 has no port I/O). The 16550 register
@@ -523,13 +928,17 @@ mod uart_mmio {
 /// site — including the vsock-irq thread and the net-poll fallback in
 /// `vmm/mod.rs` — goes through here so no site can carry a stale encoding.
 pub(crate) fn inject_irq(vm_fd: i32, slot: arch::VirtioSlot) {
+    inject_irq_line(vm_fd, slot.irq_line_value());
+}
+
+/// Pulse the interrupt line with `KVM_IRQ_LINE` value `irq`.
+pub(crate) fn inject_irq_line(vm_fd: i32, irq: u32) {
     #[repr(C)]
     struct KvmIrqLevel {
         irq: u32,
         level: u32,
     }
     const KVM_IRQ_LINE: libc::c_ulong = 0x4008_AE61;
-    let irq = slot.irq_line_value();
     let assert = KvmIrqLevel { irq, level: 1 };
     unsafe {
         libc::ioctl(vm_fd, KVM_IRQ_LINE as _, &assert);
@@ -558,8 +967,10 @@ unsafe fn set_kvm_signal_mask(vcpu_fd: i32) {
     libc::ioctl(vcpu_fd, KVM_SET_SIGNAL_MASK as _, &mask);
 }
 
-/// Handle I/O port output (guest writing to port)
-fn handle_io_out(port: u16, data: &[u8], serial: &mut SerialDevice) {
+/// Handle I/O port output (guest writing to port). Returns true when the
+/// guest asked for a reset through the keyboard controller, which is how an
+/// x86 guest without ACPI leaves (`reboot=k`): the VM is done either way.
+fn handle_io_out(port: u16, data: &[u8], serial: &mut SerialDevice) -> bool {
     if (0x3f8..=0x3ff).contains(&port) {
         let offset = port - 0x3f8;
         for &byte in data {
@@ -567,9 +978,11 @@ fn handle_io_out(port: u16, data: &[u8], serial: &mut SerialDevice) {
         }
     } else if port == 0x64 && data.first() == Some(&0xFE) {
         debug!("Guest wrote 0xFE to port 0x64 (reboot via KB controller)");
+        return true;
     } else {
         trace!("Unhandled IO out: port={:#x}, data={:?}", port, data);
     }
+    false
 }
 
 /// Handle I/O port input (guest reading from port)
@@ -589,8 +1002,58 @@ fn handle_io_in(port: u16, data: &mut [u8], serial: &SerialDevice) {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_segment_constants() {
         // Verified in arch/x86_64/cpu.rs tests
     }
+
+    #[test]
+    fn unarmed_guest_reset_stops_the_vm() {
+        let reset = GuestReset::default();
+        assert!(!reset.begin());
+        assert!(!reset.pending());
+
+        reset.arm();
+        reset.disarm();
+        assert!(!reset.begin());
+    }
+
+    #[test]
+    fn armed_guest_reset_parks_until_released() {
+        let reset = Arc::new(GuestReset::default());
+        let running = Arc::new(AtomicBool::new(true));
+        reset.arm();
+        assert!(reset.begin());
+        assert!(reset.pending());
+
+        let parked = {
+            let (reset, running) = (reset.clone(), running.clone());
+            thread::spawn(move || reset.park(&running))
+        };
+        assert!(reset.wait_parked(1, Duration::from_secs(5)));
+        reset.release(0x20_0000);
+        assert_eq!(parked.join().unwrap(), Some(0x20_0000));
+        assert!(!reset.pending());
+        // One arm covers one reset.
+        assert!(!reset.begin());
+    }
+
+    #[test]
+    fn parked_vcpu_leaves_when_the_vm_stops() {
+        let reset = Arc::new(GuestReset::default());
+        let running = Arc::new(AtomicBool::new(true));
+        reset.arm();
+        assert!(reset.begin());
+
+        let parked = {
+            let (reset, running) = (reset.clone(), running.clone());
+            thread::spawn(move || reset.park(&running))
+        };
+        assert!(reset.wait_parked(1, Duration::from_secs(5)));
+        running.store(false, Ordering::SeqCst);
+        assert_eq!(parked.join().unwrap(), None);
+        assert!(!reset.wait_parked(2, Duration::from_millis(10)));
+    }
 }
@@ -0,0 +1,136 @@
+//! Host-side resource sampling for a running [`MicroVm`](super::MicroVm).
+//!
+//! A [`VmmStatsProbe`] holds shared handles to the VM's vCPU threads and
+//! devices, so it can be sampled from a background task while the
+//! `MicroVm` itself is borrowed elsewhere.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::backend::control_channel::ControlChannel;
+use crate::devices::virtio_net::VirtioNetDevice;
+use crate::network::NetworkBackend;
+use crate::observe::host_metrics::{thread_cpu_ticks, HostMetricsCollector, VmmStats};
+
+use super::kvm::Vm;
+
+/// Samples [`VmmStats`] for one VM. Obtained from
+/// [`MicroVm::stats_probe`](super::MicroVm::stats_probe).
+pub struct VmmStatsProbe {
+    vm: Arc<Vm>,
+    running: Arc<AtomicBool>,
+    vcpu_tids: Vec<Arc<AtomicU32>>,
+    virtio_net: Option<Arc<Mutex<VirtioNetDevice>>>,
+    network_backend: Option<Arc<Mutex<dyn NetworkBackend>>>,
+    control_channel: Option<Arc<ControlChannel>>,
+    process: HostMetricsCollector,
+    /// vCPU thread CPU ticks at the previous sample.
+    prev_vcpu_ticks: Mutex<Option<(Instant, Vec<u64>)>>,
+}
+
+impl VmmStatsProbe {
+    pub(super) fn new(
+        vm: Arc<Vm>,
+        running: Arc<AtomicBool>,
+        vcpu_tids: Vec<Arc<AtomicU32>>,
+        virtio_net: Option<Arc<Mutex<VirtioNetDevice>>>,
+    ) -> Self {
+        let network_backend = virtio_net
+            .as_ref()
+            .map(|net| net.lock().unwrap().slirp_arc());
+        Self {
+            vm,
+            running,
+            vcpu_tids,
+            virtio_net,
+            network_backend,
+            control_channel: None,
+            process: HostMetricsCollector::new(),
+            prev_vcpu_ticks: Mutex::new(None),
+        }
+    }
+
+    /// Count vsock traffic on `channel`, the one the backend talks to the
+    /// guest-agent over.
+    pub fn with_control_channel(mut self, channel: Arc<ControlChannel>) -> Self {
+        self.control_channel = Some(channel);
+        self
+    }
+
+    /// Whether the VM is still running.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Take a sample. vCPU utilization covers the time since the previous
+    /// sample and reads zero on the first.
+    pub fn sample(&self) -> VmmStats {
+        let mut stats = VmmStats {
+            process: self.process.collect(),
+            vcpu_percent: self.vcpu_percent(),
+            ..VmmStats::default()
+        };
+        if let Some(net) = &self.virtio_net {
+            let net = net.lock().unwrap();
+            stats.net_tx_pending = u64::from(net.tx_pending(self.vm.guest_memory()));
+            stats.net_rx_backlog = net.rx_backlog() as u64;
+        }
+        if let Some(backend) = &self.network_backend {
+            let counts = backend.lock().unwrap().socket_counts();
+            stats.slirp_sockets = vec![
+                ("tcp", counts.tcp as u64),
+                ("udp", counts.udp as u64),
+                ("icmp", counts.icmp as u64),
+                ("listener", counts.listeners as u64),
+            ];
+        }
+        if let Some(channel) = &self.control_channel {
+            let traffic = channel.traffic();
+            stats.vsock_bytes_sent = traffic.bytes_sent;
+            stats.vsock_bytes_received = traffic.bytes_received;
+        }
+        stats
+    }
+
+    fn vcpu_percent(&self) -> Vec<f64> {
+        let now = Instant::now();
+        let ticks: Vec<u64> = self
+            .vcpu_tids
+            .iter()
+            .map(|tid| match tid.load(Ordering::SeqCst) {
+                0 => 0,
+                tid => thread_cpu_ticks(tid).unwrap_or(0),
+            })
+            .collect();
+        let mut prev = self.prev_vcpu_ticks.lock().unwrap();
+        let percent = match prev.as_ref() {
+            Some((prev_at, prev_ticks)) => {
+                let elapsed_ticks = now.duration_since(*prev_at).as_secs_f64() * clock_ticks();
+                ticks
+                    .iter()
+                    .zip(prev_ticks)
+                    .map(|(curr, prev)| {
+                        if elapsed_ticks <= 0.0 {
+                            0.0
+                        } else {
+                            curr.saturating_sub(*prev) as f64 / elapsed_ticks * 100.0
+                        }
+                    })
+                    .collect()
+            }
+            None => vec![0.0; ticks.len()],
+        };
+        *prev = Some((now, ticks));
+        percent
+    }
+}
+
+fn clock_ticks() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks <= 0 {
+        100.0
+    } else {
+        ticks as f64
+    }
+}
@@ -0,0 +1,280 @@
+//! Guest-physical address map planning.
+//!
+//! [`MemoryLayout`] places guest RAM and one virtio-mmio window per
+//! populated [`VirtioSlot`], and rejects a map in which any window overlaps
+//! RAM, another window, or a region the architecture reserves (interrupt
+//! controllers, the UART, the watchdog). Device construction, the x86_64
+//! `virtio_mmio.device=` cmdline arguments and the aarch64 DTB nodes all
+//! take their addresses from the same layout, so they cannot disagree.
+//!
+//! Windows sit at `base + stride * slot index`. The stride is fixed per
+//! architecture; the base defaults to the historical address and can be
+//! moved with [`VoidBoxConfig::virtio_mmio_base`](super::config::VoidBoxConfig::virtio_mmio_base).
+
+use std::fmt;
+
+use crate::vmm::arch::{Arch, CurrentArch, VirtioSlot};
+use crate::{Error, Result};
+
+/// Size of each virtio-mmio register window (the `512@` of the x86_64
+/// cmdline convention and the devices' `mmio_size`).
+pub const VIRTIO_MMIO_WINDOW_SIZE: u64 = 0x200;
+
+/// Page size MMIO window bases must be aligned to.
+const MMIO_ALIGN: u64 = 0x1000;
+
+/// Guest-physical ranges the architecture claims for itself.
+#[cfg(target_arch = "x86_64")]
+const RESERVED_REGIONS: &[(&str, u64, u64)] = &[
+    // IOAPIC, LAPIC and the reset-vector BIOS area, up to 4 GiB.
+    ("interrupt controllers", 0xFEC0_0000, 0x0140_0000),
+];
+#[cfg(target_arch = "aarch64")]
+const RESERVED_REGIONS: &[(&str, u64, u64)] = {
+    use crate::vmm::arch::aarch64::kvm::layout;
+    &[
+        (
+            "GIC distributor",
+            layout::GIC_DIST_ADDR,
+            layout::GIC_DIST_SIZE,
+        ),
+        (
+            "GIC CPU interface",
+            layout::GIC_CPU_ADDR,
+            layout::GIC_CPU_SIZE,
+        ),
+        (
+            "GIC redistributors",
+            layout::GIC_REDIST_ADDR,
+            layout::UART_ADDR - layout::GIC_REDIST_ADDR,
+        ),
+        ("UART", layout::UART_ADDR, layout::UART_SIZE),
+        (
+            "watchdog",
+            void_box_protocol::WATCHDOG_MMIO_ADDR,
+            crate::devices::watchdog::WATCHDOG_MMIO_SIZE,
+        ),
+    ]
+};
+
+/// The MMIO window of one virtio device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceWindow {
+    pub slot: VirtioSlot,
+    /// Guest-physical base address.
+    pub base: u64,
+    pub size: u64,
+}
+
+impl DeviceWindow {
+    /// One past the last byte of the window.
+    pub fn end(&self) -> u64 {
+        self.base + self.size
+    }
+
+    /// The `virtio_mmio.device=size@base:irq` argument declaring this
+    /// window to an x86_64 guest kernel.
+    #[cfg(target_arch = "x86_64")]
+    pub fn kernel_cmdline_arg(&self) -> String {
+        format!(
+            "virtio_mmio.device={}@{:#x}:{}",
+            self.size,
+            self.base,
+            self.slot.irqfd_gsi()
+        )
+    }
+}
+
+impl fmt::Display for DeviceWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} window {:#x}..{:#x}",
+            self.slot,
+            self.base,
+            self.end()
+        )
+    }
+}
+
+/// Planned guest-physical address map of one VM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryLayout {
+    ram_start: u64,
+    ram_size: u64,
+    virtio_base: u64,
+    windows: Vec<DeviceWindow>,
+}
+
+impl MemoryLayout {
+    /// Lay out `memory_size` bytes of RAM and a window for each of `slots`,
+    /// starting at `virtio_base` (the architecture default when `None`).
+    ///
+    /// The result is not checked; see [`validate`](Self::validate).
+    pub fn new(memory_size: u64, virtio_base: Option<u64>, slots: &[VirtioSlot]) -> Self {
+        let arch = CurrentArch::memory_layout();
+        // RAM stops at the MMIO gap on x86_64; see `Vm::create_guest_memory`.
+        let ram_size = match arch.mmio_gap_start {
+            Some(gap_start) => memory_size.min(gap_start - arch.ram_start),
+            None => memory_size,
+        };
+        let virtio_base = virtio_base.unwrap_or(VirtioSlot::Net.mmio_base());
+        let windows = slots
+            .iter()
+            .map(|&slot| DeviceWindow {
+                slot,
+                base: virtio_base.saturating_add(slot.mmio_offset()),
+                size: VIRTIO_MMIO_WINDOW_SIZE,
+            })
+            .collect();
+        Self {
+            ram_start: arch.ram_start,
+            ram_size,
+            virtio_base,
+            windows,
+        }
+    }
+
+    /// Check that every window is page-aligned and overlaps neither guest
+    /// RAM, another window, nor a reserved region.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] naming the first conflicting pair.
+    pub fn validate(&self) -> Result<()> {
+        if !self.virtio_base.is_multiple_of(MMIO_ALIGN) {
+            return Err(Error::Config(format!(
+                "virtio-mmio base {:#x} is not aligned to {:#x}",
+                self.virtio_base, MMIO_ALIGN
+            )));
+        }
+        let ram_end = self.ram_start + self.ram_size;
+        for (i, window) in self.windows.iter().enumerate() {
+            if window.base.checked_add(window.size).is_none() {
+                return Err(Error::Config(format!(
+                    "{:?} window at {:#x} runs past the end of the address space",
+                    window.slot, window.base
+                )));
+            }
+            if overlaps(window.base, window.end(), self.ram_start, ram_end) {
+                return Err(Error::Config(format!(
+                    "{} overlaps guest RAM {:#x}..{:#x}",
+                    window, self.ram_start, ram_end
+                )));
+            }
+            for (name, start, size) in RESERVED_REGIONS {
+                if overlaps(window.base, window.end(), *start, start + size) {
+                    return Err(Error::Config(format!(
+                        "{} overlaps the {} at {:#x}..{:#x}",
+                        window,
+                        name,
+                        start,
+                        start + size
+                    )));
+                }
+            }
+            if let Some(other) = self.windows[..i]
+                .iter()
+                .find(|other| overlaps(window.base, window.end(), other.base, other.end()))
+            {
+                return Err(Error::Config(format!("{} overlaps {}", window, other)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Guest RAM as `(start, size)`.
+    pub fn ram(&self) -> (u64, u64) {
+        (self.ram_start, self.ram_size)
+    }
+
+    /// The planned device windows, in slot order.
+    pub fn windows(&self) -> &[DeviceWindow] {
+        &self.windows
+    }
+
+    /// Base address of the window for `slot` under this layout, whether or
+    /// not the slot is populated.
+    pub fn mmio_base(&self, slot: VirtioSlot) -> u64 {
+        self.virtio_base.saturating_add(slot.mmio_offset())
+    }
+
+    /// The configured virtio-mmio base, if it differs from the
+    /// architecture default.
+    pub fn custom_virtio_base(&self) -> Option<u64> {
+        (self.virtio_base != VirtioSlot::Net.mmio_base()).then_some(self.virtio_base)
+    }
+}
+
+fn overlaps(a_start: u64, a_end: u64, b_start: u64, b_end: u64) -> bool {
+    a_start < b_end && b_start < a_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+
+    #[test]
+    fn default_layout_matches_fixed_slots_and_validates() {
+        let slots = [VirtioSlot::Net, VirtioSlot::Vsock, VirtioSlot::Balloon];
+        let layout = MemoryLayout::new(512 * MIB, None, &slots);
+        layout.validate().unwrap();
+        for (window, slot) in layout.windows().iter().zip(slots) {
+            assert_eq!(window.base, slot.mmio_base());
+        }
+        assert_eq!(layout.custom_virtio_base(), None);
+    }
+
+    #[test]
+    fn window_inside_ram_is_rejected() {
+        let (ram_start, _) = MemoryLayout::new(512 * MIB, None, &[]).ram();
+        let layout = MemoryLayout::new(512 * MIB, Some(ram_start + 256 * MIB), &[VirtioSlot::Net]);
+        let err = layout.validate().unwrap_err().to_string();
+        assert!(err.contains("overlaps guest RAM"), "{err}");
+    }
+
+    #[test]
+    fn window_on_reserved_region_is_rejected() {
+        let (_, start, _) = RESERVED_REGIONS[0];
+        let layout = MemoryLayout::new(128 * MIB, Some(start), &[VirtioSlot::Net]);
+        let err = layout.validate().unwrap_err().to_string();
+        assert!(err.contains(RESERVED_REGIONS[0].0), "{err}");
+    }
+
+    #[test]
+    fn unaligned_base_is_rejected() {
+        let base = VirtioSlot::Net.mmio_base() + 0x10;
+        let layout = MemoryLayout::new(128 * MIB, Some(base), &[VirtioSlot::Net]);
+        assert!(layout.validate().is_err());
+    }
+
+    #[test]
+    fn overlapping_windows_are_rejected() {
+        let mut layout = MemoryLayout::new(128 * MIB, None, &[VirtioSlot::Net, VirtioSlot::Vsock]);
+        layout.windows[1].base = layout.windows[0].base + 0x100;
+        let err = layout.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("Vsock window") && err.contains("Net window"),
+            "{err}"
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn moved_base_shifts_every_window_and_cmdline() {
+        let layout = MemoryLayout::new(
+            128 * MIB,
+            Some(0x8000_0000),
+            &[VirtioSlot::Net, VirtioSlot::Vsock],
+        );
+        layout.validate().unwrap();
+        assert_eq!(layout.mmio_base(VirtioSlot::Vsock), 0x8080_0000);
+        assert_eq!(
+            layout.windows()[1].kernel_cmdline_arg(),
+            "virtio_mmio.device=512@0x80800000:11"
+        );
+        assert_eq!(layout.custom_virtio_base(), Some(0x8000_0000));
+    }
+}
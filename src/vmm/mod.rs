@@ -10,33 +10,44 @@ pub mod arch;
 pub mod boot;
 pub mod config;
 pub mod cpu;
+pub mod host_stats;
 pub mod kvm;
+pub mod layout;
 pub mod memory;
 pub mod snapshot;
+pub mod throttle;
 
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::Instant;
 
 use tokio::sync::{mpsc, oneshot};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use void_box_protocol::SessionSecret;
 
 use crate::devices::serial::SerialDevice;
 use crate::devices::virtio_9p::Virtio9pDevice;
+use crate::devices::virtio_balloon::{VirtioBalloonDevice, BALLOON_PAGE_SIZE};
+use crate::devices::virtio_console::VirtioConsoleDevice;
 use crate::devices::virtio_blk::VirtioBlkDevice;
 use crate::devices::virtio_net::VirtioNetDevice;
+use crate::devices::virtio_net_vhost::VhostNetDevice;
+use crate::devices::virtio_rng::VirtioRngDevice;
 use crate::devices::virtio_vsock::VsockDevice;
 use crate::devices::virtio_vsock_mmio::VirtioVsockMmio;
 use crate::devices::virtio_vsock_userspace::VirtioVsockUserspace;
 use crate::devices::vsock_backend::VsockMmioDevice;
+use crate::devices::watchdog::WatchdogDevice;
 use crate::guest::protocol::{
-    ExecOutputChunk, ExecRequest, ExecResponse, MkdirPRequest, MkdirPResponse,
+    ExecOutputChunk, ExecRequest, ExecResponse, MkdirPRequest, MkdirPResponse, ShutdownAck,
     TelemetrySubscribeRequest, WriteFileRequest, WriteFileResponse,
 };
 use crate::network::slirp::SlirpBackend;
+use crate::network::tap::TapBackend;
+use crate::observe::boot::{BootTimeline, PHASE_KERNEL_LOAD, PHASE_VCPU_START};
 use crate::observe::telemetry::TelemetryAggregator;
 use crate::observe::Observer;
 use crate::vmm::arch::{Arch, CurrentArch, VirtioSlot};
@@ -45,9 +56,13 @@ use crate::{Error, ExecOutput, Result};
 
 use self::config::VoidBoxConfig;
 use self::cpu::VcpuHandle;
+use self::host_stats::VmmStatsProbe;
 use self::kvm::Vm;
+use self::layout::MemoryLayout;
+use self::throttle::VcpuLimits;
 
 use crate::backend::control_channel::ControlChannel;
+use crate::backend::DEFAULT_SHUTDOWN_TIMEOUT;
 
 /// Dispatches one [`VmCommand`] through the persistent [`ControlChannel`].
 ///
@@ -116,6 +131,7 @@ async fn dispatch_vm_command(
         }
         VmCommand::SubscribeTelemetry { aggregator, opts } => {
             let channel = Arc::clone(channel);
+            aggregator.attach_control_channel(channel.clone());
             tokio::spawn(async move {
                 let subscription = channel
                     .subscribe_telemetry(&opts, move |batch| {
@@ -142,6 +158,10 @@ pub struct MicroVm {
     running: Arc<AtomicBool>,
     /// Serial output receiver
     serial_output: Option<mpsc::Receiver<u8>>,
+    /// The serial console, for host input.
+    serial: SerialDevice,
+    /// Boot phases timed while creating the VM: name, start, end.
+    boot_phases: Vec<(&'static str, Instant, Instant)>,
     /// Context ID for vsock communication
     cid: u32,
     /// Vsock device — connector factory for [`ControlChannel`].
@@ -165,14 +185,30 @@ pub struct MicroVm {
     /// virtio-net device for SLIRP networking
     #[allow(dead_code)]
     virtio_net: Option<Arc<Mutex<VirtioNetDevice>>>,
+    /// virtio-net device backed by kernel vhost-net (not snapshotted)
+    vhost_net: Option<Arc<Mutex<VhostNetDevice>>>,
+    /// virtio-rng entropy device (kept for snapshot state capture)
+    virtio_rng: Option<Arc<Mutex<VirtioRngDevice>>>,
+    /// virtio-balloon device (driven by `set_memory_target`)
+    virtio_balloon: Option<Arc<Mutex<VirtioBalloonDevice>>>,
+    /// virtio-console control port (cold boots with `control_port` set;
+    /// not snapshotted)
+    virtio_console: Option<Arc<Mutex<VirtioConsoleDevice>>>,
+    /// Watchdog the guest-agent pets (cold boots with one configured)
+    watchdog: Option<Arc<WatchdogDevice>>,
     /// Channel to send commands to the VM event loop
     command_tx: mpsc::Sender<VmCommand>,
     /// Handle to the VM event loop thread
     event_loop_handle: Option<JoinHandle<()>>,
     /// Handle to the vsock IRQ handler thread (if vsock enabled)
     vsock_irq_handle: Option<JoinHandle<()>>,
-    /// Handle to the network polling thread (SLIRP RX relay)
+    /// Handle to the network polling thread (SLIRP RX relay), or the
+    /// vhost-net IRQ relay when vhost-net is active
     net_poll_handle: Option<JoinHandle<()>>,
+    /// Handle to the vCPU throttle ticker (if a CPU quota is set)
+    throttle_handle: Option<JoinHandle<()>>,
+    /// Handle to the control port I/O thread (if the control port is attached)
+    console_io_handle: Option<JoinHandle<()>>,
     /// Guest telemetry aggregator (if telemetry is active)
     telemetry: Option<Arc<TelemetryAggregator>>,
     /// Active span context for trace propagation into the guest.
@@ -180,8 +216,24 @@ pub struct MicroVm {
     active_span_context: Option<crate::observe::tracer::SpanContext>,
     /// Socket path for the userspace vsock backend (unique per restore instance).
     vsock_socket_path: Option<PathBuf>,
+    /// Guest-physical address map the devices were placed by.
+    layout: MemoryLayout,
+    /// Parks the vCPUs across an in-place reboot.
+    guest_reset: Arc<cpu::GuestReset>,
+    /// Cold-boot configuration, for reloading the kernel on reboot. `None`
+    /// for a VM restored from a snapshot.
+    boot_config: Option<VoidBoxConfig>,
 }
 
+/// Commands the VM event loop will queue before an exec fails with
+/// [`Error::Busy`]. The loop runs execs one at a time, so this is also
+/// how many may wait behind the one running.
+const COMMAND_QUEUE_DEPTH: usize = 32;
+
+/// How often [`MicroVm::shutdown`] checks whether an acked guest has
+/// powered off.
+const SHUTDOWN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
 /// Commands that can be sent to the VM event loop
 enum VmCommand {
     /// Execute a command in the guest
@@ -228,14 +280,16 @@ impl MicroVm {
 
         // Set up serial device for console output
         let (serial_tx, serial_rx) = mpsc::channel(4096);
-        let serial = SerialDevice::new(serial_tx);
+        let serial = SerialDevice::new(serial_tx).with_interrupt(uart_interrupt(&vm));
         debug!("Created serial device");
 
         // Load kernel and initramfs
+        let layout = config.memory_layout();
         let boot_platform = arch::BootPlatform {
             vcpu_count: config.vcpus,
-            virtio_slots: config.populated_virtio_slots(),
+            virtio_windows: layout.windows().to_vec(),
         };
+        let kernel_load_started = Instant::now();
         let entry_point = boot::load_kernel(
             &vm,
             &config.kernel,
@@ -243,6 +297,7 @@ impl MicroVm {
             &config.kernel_cmdline(),
             &boot_platform,
         )?;
+        let mut boot_phases = vec![(PHASE_KERNEL_LOAD, kernel_load_started, Instant::now())];
         debug!("Loaded kernel at entry point: {:#x}", entry_point);
 
         // CID for vsock (must be > 2; 0-2 reserved)
@@ -282,7 +337,7 @@ impl MicroVm {
             match config.vsock_backend {
                 config::VsockBackendType::Userspace => match VirtioVsockUserspace::new(cid) {
                     Ok(mut dev) => {
-                        dev.set_mmio_base(VirtioSlot::Vsock.mmio_base());
+                        dev.set_mmio_base(layout.mmio_base(VirtioSlot::Vsock));
                         debug!(
                             "virtio-vsock-userspace MMIO at {:#x}, CID {}",
                             dev.mmio_base(),
@@ -300,7 +355,7 @@ impl MicroVm {
                 config::VsockBackendType::Vhost => {
                     match VirtioVsockMmio::new_with_require_vhost(cid, true) {
                         Ok(mut dev) => {
-                            dev.set_mmio_base(VirtioSlot::Vsock.mmio_base());
+                            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Vsock));
                             debug!("virtio-vsock MMIO at {:#x}, CID {}", dev.mmio_base(), cid);
                             Some(Arc::new(Mutex::new(dev)))
                         }
@@ -318,20 +373,72 @@ impl MicroVm {
             None
         };
 
-        // Virtio-net with SLIRP backend if networking is enabled
-        let virtio_net = if config.network {
-            debug!("Setting up SLIRP networking");
-            let slirp: Arc<Mutex<dyn crate::network::NetworkBackend>> =
-                Arc::new(Mutex::new(SlirpBackend::with_security(
-                    config.security.max_concurrent_connections,
-                    config.security.max_connections_per_second,
-                    &config.security.network_deny_list,
-                    // TODO(5.5b): wire port_forwards from NetworkConfig once VoidBoxConfig
-                    // carries the field; for now no host listeners are spawned.
-                    &[],
-                )?));
-            let mut net_device = VirtioNetDevice::new(slirp)?;
-            net_device.set_mmio_base(VirtioSlot::Net.mmio_base());
+        if config.network
+            && !matches!(config.network_mode, crate::backend::NetworkMode::Slirp)
+            && config.security.network_policy.is_some()
+        {
+            // Policy is enforced inside SLIRP; refuse rather than silently
+            // run the guest unfiltered.
+            return Err(Error::Config(
+                "a network policy requires SLIRP networking; TAP and vhost-net modes cannot enforce it"
+                    .into(),
+            ));
+        }
+
+        // vhost-net takes the net slot when requested and /dev/vhost-net is
+        // usable; otherwise the userspace virtio-net device below does.
+        let vhost_net = match &config.network_mode {
+            crate::backend::NetworkMode::VhostNet { name, bridge } if config.network => {
+                match VhostNetDevice::new(name, bridge.as_deref())? {
+                    Some(mut dev) => {
+                        dev.set_mmio_base(layout.mmio_base(VirtioSlot::Net));
+                        debug!(
+                            "vhost-net enabled at MMIO {:#x} on TAP {}",
+                            dev.mmio_base(),
+                            dev.tap_name()
+                        );
+                        Some(Arc::new(Mutex::new(dev)))
+                    }
+                    None => {
+                        warn!("/dev/vhost-net unavailable; falling back to SLIRP networking");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // Virtio-net with a SLIRP or TAP backend if networking is enabled
+        let virtio_net = if config.network && vhost_net.is_none() {
+            let backend: Arc<Mutex<dyn crate::network::NetworkBackend>> = match &config.network_mode
+            {
+                crate::backend::NetworkMode::Slirp
+                | crate::backend::NetworkMode::VhostNet { .. } => {
+                    debug!("Setting up SLIRP networking");
+                    let mut slirp_backend = SlirpBackend::with_security(
+                        config.security.max_concurrent_connections,
+                        config.security.max_connections_per_second,
+                        &config.security.network_deny_list,
+                        // TODO(5.5b): wire port_forwards from NetworkConfig once VoidBoxConfig
+                        // carries the field; for now no host listeners are spawned.
+                        &[],
+                    )?;
+                    if let Some(observer) = config.connection_observer.clone() {
+                        slirp_backend.set_connection_observer(observer);
+                    }
+                    if let Some(policy) = config.security.network_policy.clone() {
+                        slirp_backend.set_network_policy(policy);
+                    }
+                    slirp_backend.set_dns_config(&config.dns);
+                    Arc::new(Mutex::new(slirp_backend))
+                }
+                crate::backend::NetworkMode::Tap { name, bridge } => {
+                    debug!("Setting up TAP networking on {name} (bridge: {bridge:?})");
+                    Arc::new(Mutex::new(TapBackend::new(name, bridge.as_deref())?))
+                }
+            };
+            let mut net_device = VirtioNetDevice::new(backend)?;
+            net_device.set_mmio_base(layout.mmio_base(VirtioSlot::Net));
             debug!(
                 "virtio-net enabled at MMIO {:#x}, MAC={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
                 net_device.mmio_base(),
@@ -355,7 +462,7 @@ impl MicroVm {
             let first_mount = &config.mounts[0];
             let mut dev =
                 Virtio9pDevice::new(&first_mount.host_path, "mount0", first_mount.read_only);
-            dev.set_mmio_base(VirtioSlot::P9.mmio_base());
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::P9));
             // RW mounts: map metadata uid/gid to the guest sandbox uid (1000)
             // so the sandboxed guest user sees consistent ownership of the
             // mount, regardless of whether the host runtime process runs as
@@ -386,7 +493,7 @@ impl MicroVm {
 
         let virtio_blk = if let Some(ref disk_path) = config.oci_rootfs_disk {
             let mut dev = VirtioBlkDevice::new(disk_path)?;
-            dev.set_mmio_base(VirtioSlot::Blk.mmio_base());
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Blk));
             debug!(
                 "virtio-blk MMIO at {:#x}, disk={}",
                 dev.mmio_base(),
@@ -397,11 +504,62 @@ impl MicroVm {
             None
         };
 
+        let mut data_disks = Vec::with_capacity(config.disks.len());
+        for (disk, slot) in config.disks.iter().zip(VirtioSlot::DATA_DISKS) {
+            let mut dev = VirtioBlkDevice::open(&disk.path, disk.read_only)?;
+            dev.set_mmio_base(layout.mmio_base(slot));
+            debug!(
+                "virtio-blk data disk MMIO at {:#x}, disk={}, ro={}",
+                dev.mmio_base(),
+                disk.path.display(),
+                disk.read_only
+            );
+            data_disks.push((slot, Arc::new(Mutex::new(dev))));
+        }
+
+        let virtio_rng = if config.enable_rng {
+            let mut dev = VirtioRngDevice::new();
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Rng));
+            debug!("virtio-rng MMIO at {:#x}", dev.mmio_base());
+            Some(Arc::new(Mutex::new(dev)))
+        } else {
+            None
+        };
+
+        let virtio_balloon = if config.enable_balloon {
+            let mut dev = VirtioBalloonDevice::new();
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Balloon));
+            debug!("virtio-balloon MMIO at {:#x}", dev.mmio_base());
+            Some(Arc::new(Mutex::new(dev)))
+        } else {
+            None
+        };
+
+        let virtio_console = if config.control_port {
+            let mut dev = VirtioConsoleDevice::new()?;
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Console));
+            debug!("virtio-console MMIO at {:#x}", dev.mmio_base());
+            Some(Arc::new(Mutex::new(dev)))
+        } else {
+            None
+        };
+
+        let watchdog = config.watchdog.map(|timeout| {
+            debug!("Watchdog attached, timeout {:?}", timeout);
+            Arc::new(WatchdogDevice::new(timeout))
+        });
+
         let mmio_devices = MmioDevices {
             virtio_net,
+            vhost_net,
             virtio_vsock: virtio_vsock_mmio,
             virtio_9p,
             virtio_blk,
+            data_disks,
+            virtio_rng,
+            virtio_balloon,
+            virtio_console,
+            watchdog,
         };
 
         // Install no-op signal handler so pthread_kill(SIGRTMIN) causes EINTR
@@ -411,6 +569,7 @@ impl MicroVm {
         // Create and configure all vCPUs before starting any of them: the
         // aarch64 vGIC must be initialized after every vCPU exists and
         // before any vCPU runs (see `Arch::setup_vm_post_vcpus`).
+        let vcpu_start_started = Instant::now();
         let mut prepared_vcpus = Vec::with_capacity(config.vcpus);
         for vcpu_id in 0..config.vcpus {
             prepared_vcpus.push(cpu::prepare_vcpu(&vm, vcpu_id as u64, entry_point)?);
@@ -419,8 +578,10 @@ impl MicroVm {
 
         // Start vCPU threads (with MMIO dispatch to virtio-net and virtio-vsock)
         let running = Arc::new(AtomicBool::new(true));
+        let guest_reset = Arc::new(cpu::GuestReset::default());
         let mut vcpu_handles = Vec::with_capacity(config.vcpus);
-        for prepared in prepared_vcpus {
+        for (vcpu_id, prepared) in prepared_vcpus.into_iter().enumerate() {
+            let limits = VcpuLimits::for_vcpu(&config.resource_policy, vcpu_id, config.vcpus);
             let handle = cpu::start_vcpu(
                 prepared,
                 vm.clone(),
@@ -428,15 +589,39 @@ impl MicroVm {
                 serial.clone(),
                 MmioDevices {
                     virtio_net: mmio_devices.virtio_net.clone(),
+                    vhost_net: mmio_devices.vhost_net.clone(),
                     virtio_vsock: mmio_devices.virtio_vsock.clone(),
                     virtio_9p: mmio_devices.virtio_9p.clone(),
                     virtio_blk: mmio_devices.virtio_blk.clone(),
+                    data_disks: mmio_devices.data_disks.clone(),
+                    virtio_rng: mmio_devices.virtio_rng.clone(),
+                    virtio_balloon: mmio_devices.virtio_balloon.clone(),
+                    virtio_console: mmio_devices.virtio_console.clone(),
+                    watchdog: mmio_devices.watchdog.clone(),
                 },
+                limits,
+                guest_reset.clone(),
             )?;
             vcpu_handles.push(handle);
         }
+        boot_phases.push((PHASE_VCPU_START, vcpu_start_started, Instant::now()));
         debug!("Created {} vCPUs", config.vcpus);
 
+        // Kick throttled vCPUs so they check their CPU budget even when the
+        // guest never exits on its own.
+        let throttle_handle = if config
+            .resource_policy
+            .cpu_quota
+            .is_some_and(|quota| quota < config.vcpus as f64)
+        {
+            Some(throttle::spawn_ticker(
+                vcpu_handles.iter().map(VcpuHandle::pthread_id).collect(),
+                running.clone(),
+            )?)
+        } else {
+            None
+        };
+
         // Spawn a background thread to handle vhost-vsock interrupts.
         // When the vhost backend writes to a call eventfd, we must:
         //   1. Set INTERRUPT_STATUS |= 1 on the virtio-mmio device (so the guest ISR sees it)
@@ -454,7 +639,18 @@ impl MicroVm {
                 let handle = std::thread::Builder::new()
                     .name("vsock-irq".into())
                     .spawn(move || {
-                        vsock_irq_thread(call_fds, vsock_mmio_clone, vm_fd_raw, running_irq);
+                        vhost_irq_thread(
+                            "vsock-irq",
+                            call_fds,
+                            |bits| {
+                                if let Ok(mut dev) = vsock_mmio_clone.lock() {
+                                    dev.set_interrupt_status(bits);
+                                }
+                            },
+                            VirtioSlot::Vsock,
+                            vm_fd_raw,
+                            running_irq,
+                        );
                     })
                     .expect("Failed to spawn vsock-irq thread");
                 debug!("Spawned vsock-irq handler thread");
@@ -482,12 +678,56 @@ impl MicroVm {
                 .expect("Failed to spawn net-poll thread");
             debug!("Spawned net-poll thread for SLIRP RX relay");
             Some(handle)
+        } else if let Some(ref vhost_net) = mmio_devices.vhost_net {
+            // vhost-net moves frames in the kernel; only its used-buffer
+            // signals need relaying to the guest.
+            let call_fds = vhost_net.lock().unwrap().call_eventfds();
+            let vhost_net_clone = vhost_net.clone();
+            let vm_fd_raw = vm.vm_fd().as_raw_fd();
+            let running_irq = running.clone();
+            let handle = std::thread::Builder::new()
+                .name("vhost-net-irq".into())
+                .spawn(move || {
+                    vhost_irq_thread(
+                        "vhost-net-irq",
+                        call_fds,
+                        |bits| {
+                            if let Ok(mut dev) = vhost_net_clone.lock() {
+                                dev.set_interrupt_status(bits);
+                            }
+                        },
+                        VirtioSlot::Net,
+                        vm_fd_raw,
+                        running_irq,
+                    );
+                })
+                .expect("Failed to spawn vhost-net-irq thread");
+            debug!("Spawned vhost-net-irq handler thread");
+            Some(handle)
+        } else {
+            None
+        };
+
+        // Serve the control port's host connection and raise its interrupts
+        // while the guest runs without exits, as net-poll does for SLIRP.
+        let console_io_handle = if let Some(ref console) = mmio_devices.virtio_console {
+            let console_clone = console.clone();
+            let vm_clone3 = vm.clone();
+            let running_console = running.clone();
+            let handle = std::thread::Builder::new()
+                .name("console-io".into())
+                .spawn(move || {
+                    console_io_thread(console_clone, vm_clone3, running_console);
+                })
+                .expect("Failed to spawn console-io thread");
+            debug!("Spawned console-io thread for the control port");
+            Some(handle)
         } else {
             None
         };
 
         // Create command channel
-        let (command_tx, mut command_rx) = mpsc::channel::<VmCommand>(32);
+        let (command_tx, mut command_rx) = mpsc::channel::<VmCommand>(COMMAND_QUEUE_DEPTH);
 
         // Build the persistent multiplex control channel over the
         // vsock connector. Lazy: first RPC triggers the handshake.
@@ -499,18 +739,33 @@ impl MicroVm {
             config::VsockBackendType::Vhost => std::time::Duration::from_millis(250),
             config::VsockBackendType::Userspace => std::time::Duration::ZERO,
         };
-        let control_channel = vsock.as_ref().map(|device| {
-            Arc::new(ControlChannel::with_boot_wait(
-                device.connector(),
-                device.session_secret().clone(),
-                boot_wait,
-            ))
-        });
+        //
+        // With the control port attached the channel runs over it instead.
+        // The port carries one connection at a time, and a backend that
+        // builds its own channel on it takes the port over; this one then
+        // reconnects only when next used, so it is not warmed up.
+        let control_channel = if let Some(ref console) = mmio_devices.virtio_console {
+            Some(Arc::new(ControlChannel::new(
+                crate::devices::virtio_console::connector(console.clone()),
+                config.security.session_secret.clone(),
+            )))
+        } else {
+            vsock.as_ref().map(|device| {
+                Arc::new(ControlChannel::with_boot_wait(
+                    device.connector(),
+                    device.session_secret().clone(),
+                    boot_wait,
+                ))
+            })
+        };
 
         // Eagerly fire the handshake in parallel with the rest of
         // `new`'s work so the first RPC finds the channel already live.
         // Failures are swallowed; the first real RPC re-attempts.
-        if let Some(channel) = control_channel.as_ref() {
+        if let Some(channel) = control_channel
+            .as_ref()
+            .filter(|_| mmio_devices.virtio_console.is_none())
+        {
             let warm = Arc::clone(channel);
             tokio::spawn(async move {
                 warm.warm_handshake().await;
@@ -573,18 +828,30 @@ impl MicroVm {
             vcpu_handles,
             running,
             serial_output: Some(serial_rx),
+            serial,
+            boot_phases,
             cid,
             vsock,
             control_channel,
             virtio_vsock_mmio: mmio_devices.virtio_vsock,
             virtio_net: mmio_devices.virtio_net,
+            vhost_net: mmio_devices.vhost_net,
+            virtio_rng: mmio_devices.virtio_rng,
+            virtio_balloon: mmio_devices.virtio_balloon,
+            virtio_console: mmio_devices.virtio_console,
+            watchdog: mmio_devices.watchdog,
             command_tx,
             event_loop_handle: Some(event_loop_handle),
             vsock_irq_handle,
             net_poll_handle,
+            throttle_handle,
+            console_io_handle,
             telemetry: None,
             active_span_context: None,
             vsock_socket_path: cold_boot_socket_path,
+            layout,
+            guest_reset,
+            boot_config: Some(config),
         })
     }
 
@@ -607,6 +874,11 @@ impl MicroVm {
         let t0 = std::time::Instant::now();
         let vm = Arc::new(kvm::Vm::new(snap.config.memory_mb)?);
         let t_vm_new = t0.elapsed();
+        let layout = MemoryLayout::new(
+            (snap.config.memory_mb as u64) << 20,
+            snap.config.virtio_mmio_base,
+            &[],
+        );
 
         // 2. Restore memory contents
         match snap.snapshot_type {
@@ -653,7 +925,7 @@ impl MicroVm {
 
         // 4. Serial device (fresh — no state to restore)
         let (serial_tx, serial_rx) = mpsc::channel(4096);
-        let serial = SerialDevice::new(serial_tx);
+        let serial = SerialDevice::new(serial_tx).with_interrupt(uart_interrupt(&vm));
 
         // 5. Use the CID from the snapshot — the guest kernel has it cached
         let cid = snap.config.cid;
@@ -712,7 +984,7 @@ impl MicroVm {
                     Arc::new(Mutex::new(SlirpBackend::new()?));
                 let mut net_dev = VirtioNetDevice::new(slirp)?;
                 net_dev.restore_state(net_state);
-                net_dev.set_mmio_base(VirtioSlot::Net.mmio_base());
+                net_dev.set_mmio_base(layout.mmio_base(VirtioSlot::Net));
                 debug!("Restored virtio-net MMIO at {:#x}", net_dev.mmio_base());
                 Some(Arc::new(Mutex::new(net_dev)))
             } else {
@@ -724,11 +996,35 @@ impl MicroVm {
             None
         };
 
+        // 7c. Restore virtio-rng if the snapshot had it
+        let virtio_rng = snap.rng_state.as_ref().map(|rng_state| {
+            let mut dev = VirtioRngDevice::new();
+            dev.restore_state(rng_state);
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Rng));
+            debug!("Restored virtio-rng MMIO at {:#x}", dev.mmio_base());
+            Arc::new(Mutex::new(dev))
+        });
+
+        // 7d. Restore virtio-balloon if the snapshot had it
+        let virtio_balloon = snap.balloon_state.as_ref().map(|balloon_state| {
+            let mut dev = VirtioBalloonDevice::new();
+            dev.restore_state(balloon_state);
+            dev.set_mmio_base(layout.mmio_base(VirtioSlot::Balloon));
+            debug!("Restored virtio-balloon MMIO at {:#x}", dev.mmio_base());
+            Arc::new(Mutex::new(dev))
+        });
+
         let mmio_devices = cpu::MmioDevices {
             virtio_net: virtio_net.clone(),
+            vhost_net: None,
             virtio_vsock: virtio_vsock_mmio,
             virtio_9p: None,
             virtio_blk: None,
+            data_disks: Vec::new(),
+            virtio_rng,
+            virtio_balloon,
+            virtio_console: None,
+            watchdog: None,
         };
 
         // 8. Restore vCPUs from snapshot state. As on the cold-boot path,
@@ -743,6 +1039,7 @@ impl MicroVm {
         CurrentArch::setup_vm_post_vcpus(vm.vm_fd(), prepared_vcpus.len())?;
 
         let running = Arc::new(AtomicBool::new(true));
+        let guest_reset = Arc::new(cpu::GuestReset::default());
         let mut vcpu_handles = Vec::with_capacity(prepared_vcpus.len());
         for prepared in prepared_vcpus {
             let handle = cpu::start_vcpu(
@@ -752,14 +1049,23 @@ impl MicroVm {
                 serial.clone(),
                 cpu::MmioDevices {
                     virtio_net: mmio_devices.virtio_net.clone(),
+                    vhost_net: mmio_devices.vhost_net.clone(),
                     virtio_vsock: mmio_devices.virtio_vsock.clone(),
                     virtio_9p: mmio_devices.virtio_9p.clone(),
                     virtio_blk: mmio_devices.virtio_blk.clone(),
+                    data_disks: mmio_devices.data_disks.clone(),
+                    virtio_rng: mmio_devices.virtio_rng.clone(),
+                    virtio_balloon: mmio_devices.virtio_balloon.clone(),
+                    virtio_console: mmio_devices.virtio_console.clone(),
+                    watchdog: mmio_devices.watchdog.clone(),
                 },
+                VcpuLimits::default(),
+                guest_reset.clone(),
             )?;
             vcpu_handles.push(handle);
         }
         let t_vcpu = t_vcpu_start.elapsed();
+        let boot_phases = vec![(PHASE_VCPU_START, t_vcpu_start, t_vcpu_start + t_vcpu)];
         debug!("Restored {} vCPUs", vcpu_handles.len());
         debug!(
             "restore phases: load_state={:?} vm_new={:?} mem={:?} irq={:?} vcpu={:?} total_to_vcpu={:?}",
@@ -784,7 +1090,18 @@ impl MicroVm {
                 let handle = std::thread::Builder::new()
                     .name("vsock-irq".into())
                     .spawn(move || {
-                        vsock_irq_thread(call_fds, vsock_mmio_clone, vm_fd_raw, running_irq);
+                        vhost_irq_thread(
+                            "vsock-irq",
+                            call_fds,
+                            |bits| {
+                                if let Ok(mut dev) = vsock_mmio_clone.lock() {
+                                    dev.set_interrupt_status(bits);
+                                }
+                            },
+                            VirtioSlot::Vsock,
+                            vm_fd_raw,
+                            running_irq,
+                        );
                     })
                     .expect("Failed to spawn vsock-irq thread");
                 debug!("Spawned vsock-irq handler thread (restore)");
@@ -814,7 +1131,7 @@ impl MicroVm {
         };
 
         // 11. Start event loop (same as cold boot)
-        let (command_tx, mut command_rx) = mpsc::channel::<VmCommand>(32);
+        let (command_tx, mut command_rx) = mpsc::channel::<VmCommand>(COMMAND_QUEUE_DEPTH);
         let running_clone = running.clone();
         let control_channel = Arc::new(ControlChannel::new_restored(
             vsock.connector(),
@@ -857,18 +1174,30 @@ impl MicroVm {
             vcpu_handles,
             running,
             serial_output: Some(serial_rx),
+            serial,
+            boot_phases,
             cid,
             vsock: Some(vsock),
             control_channel: Some(control_channel),
             virtio_vsock_mmio: mmio_devices.virtio_vsock,
             virtio_net,
+            vhost_net: None,
+            virtio_rng: mmio_devices.virtio_rng,
+            virtio_balloon: mmio_devices.virtio_balloon,
+            virtio_console: None,
+            watchdog: mmio_devices.watchdog,
             command_tx,
             event_loop_handle: Some(event_loop_handle),
             vsock_irq_handle,
             net_poll_handle,
+            throttle_handle: None,
+            console_io_handle: None,
             telemetry: None,
             active_span_context: None,
             vsock_socket_path: Some(socket_path),
+            layout,
+            guest_reset,
+            boot_config: None,
         })
     }
 
@@ -930,6 +1259,13 @@ impl MicroVm {
         is_diff: bool,
         parent_id: Option<String>,
     ) -> Result<std::path::PathBuf> {
+        if self.virtio_console.is_some() {
+            self.hard_stop().await?;
+            return Err(Error::Snapshot(
+                "a VM with the virtio-console control port cannot be snapshotted".into(),
+            ));
+        }
+
         info!(
             "Creating {} snapshot (stopping VM)...",
             if is_diff { "diff" } else { "base" }
@@ -946,11 +1282,12 @@ impl MicroVm {
 
         // 2–3. Wait for vCPU + background threads (blocking joins).
         // Wrapped in block_in_place to avoid stalling the tokio worker thread.
-        let (vcpu_states, event_loop_handle, vsock_irq_handle, net_poll_handle) = (
+        let (vcpu_states, event_loop_handle, vsock_irq_handle, net_poll_handle, throttle_handle) = (
             &mut self.vcpu_handles,
             &mut self.event_loop_handle,
             &mut self.vsock_irq_handle,
             &mut self.net_poll_handle,
+            &mut self.throttle_handle,
         );
         let vcpu_states = tokio::task::block_in_place(|| {
             let mut states = Vec::with_capacity(vcpu_states.len());
@@ -974,6 +1311,9 @@ impl MicroVm {
             if let Some(handle) = net_poll_handle.take() {
                 let _ = handle.join();
             }
+            if let Some(handle) = throttle_handle.take() {
+                let _ = handle.join();
+            }
             Ok(states)
         })?;
         debug!("Captured {} vCPU states", vcpu_states.len());
@@ -1016,6 +1356,18 @@ impl MicroVm {
             .as_ref()
             .map(|dev| dev.lock().unwrap().snapshot_state());
 
+        // 5c. Capture virtio-rng device state
+        let rng_state = self
+            .virtio_rng
+            .as_ref()
+            .map(|dev| dev.lock().unwrap().snapshot_state());
+
+        // 5d. Capture virtio-balloon device state
+        let balloon_state = self
+            .virtio_balloon
+            .as_ref()
+            .map(|dev| dev.lock().unwrap().snapshot_state());
+
         // 6. Get session secret from vsock device.
         // expose: serializing into snapshot metadata.
         let session_secret = self
@@ -1057,6 +1409,8 @@ impl MicroVm {
             },
             session_secret,
             net_state,
+            rng_state,
+            balloon_state,
         };
         snap.save(snapshot_dir)?;
 
@@ -1068,6 +1422,23 @@ impl MicroVm {
         Ok(snapshot_dir.to_path_buf())
     }
 
+    /// Commands waiting for the VM event loop, including execs queued
+    /// behind the one running.
+    pub fn pending_commands(&self) -> usize {
+        COMMAND_QUEUE_DEPTH - self.command_tx.capacity()
+    }
+
+    /// Hand an exec to the event loop without waiting for queue space.
+    fn queue_exec(&self, cmd: VmCommand) -> Result<()> {
+        self.command_tx.try_send(cmd).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => Error::Busy(format!(
+                "VM command queue full ({} pending)",
+                COMMAND_QUEUE_DEPTH
+            )),
+            mpsc::error::TrySendError::Closed(_) => Error::Guest("Failed to send command".into()),
+        })
+    }
+
     /// Execute a command in the guest VM
     pub async fn exec(&self, program: &str, args: &[&str]) -> Result<ExecOutput> {
         self.exec_with_stdin(program, args, &[]).await
@@ -1125,6 +1496,7 @@ impl MicroVm {
             }
         }
 
+        let overrides = crate::guest::protocol::current_exec_overrides();
         let request = ExecRequest {
             program: program.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
@@ -1132,27 +1504,24 @@ impl MicroVm {
             env: exec_env,
             working_dir: working_dir.map(String::from),
             timeout_secs,
+            exec_id: overrides.exec_id,
+            policy: overrides.policy,
+            user: overrides.user,
+            stdin_stream: overrides.stdin_stream,
         };
 
         let (response_tx, response_rx) = oneshot::channel();
 
-        self.command_tx
-            .send(VmCommand::Exec {
-                request,
-                response_tx,
-            })
-            .await
-            .map_err(|_| Error::Guest("Failed to send command".into()))?;
+        self.queue_exec(VmCommand::Exec {
+            request,
+            response_tx,
+        })?;
 
         let response = response_rx
             .await
             .map_err(|_| Error::Guest("Failed to receive response".into()))??;
 
-        Ok(ExecOutput::new(
-            response.stdout,
-            response.stderr,
-            response.exit_code,
-        ))
+        ExecOutput::from_response(program, response)
     }
 
     /// Execute a command with streaming output.
@@ -1182,6 +1551,7 @@ impl MicroVm {
             }
         }
 
+        let overrides = crate::guest::protocol::current_exec_overrides();
         let request = ExecRequest {
             program: program.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
@@ -1189,19 +1559,20 @@ impl MicroVm {
             env: exec_env,
             working_dir: working_dir.map(String::from),
             timeout_secs,
+            exec_id: overrides.exec_id,
+            policy: overrides.policy,
+            user: overrides.user,
+            stdin_stream: overrides.stdin_stream,
         };
 
         let (chunk_tx, chunk_rx) = mpsc::channel(256);
         let (response_tx, response_rx) = oneshot::channel();
 
-        self.command_tx
-            .send(VmCommand::ExecStreaming {
-                request,
-                response_tx,
-                chunk_tx,
-            })
-            .await
-            .map_err(|_| Error::Guest("Failed to send streaming command".into()))?;
+        self.queue_exec(VmCommand::ExecStreaming {
+            request,
+            response_tx,
+            chunk_tx,
+        })?;
 
         Ok((chunk_rx, response_rx))
     }
@@ -1306,6 +1677,28 @@ impl MicroVm {
         Ok(aggregator)
     }
 
+    /// A probe sampling this VM's host-side resource usage — vCPU threads,
+    /// virtio-net queues, SLIRP sockets — for as long as the VM runs.
+    pub fn stats_probe(&self) -> VmmStatsProbe {
+        VmmStatsProbe::new(
+            self.vm.clone(),
+            self.running.clone(),
+            self.vcpu_handles.iter().map(VcpuHandle::tid).collect(),
+            self.virtio_net.clone(),
+        )
+    }
+
+    /// The watchdog device, when the VM was booted with one. Restored VMs
+    /// have none.
+    pub fn watchdog(&self) -> Option<Arc<WatchdogDevice>> {
+        self.watchdog.clone()
+    }
+
+    /// The guest-physical address map this VM's devices were placed by.
+    pub fn memory_layout(&self) -> &MemoryLayout {
+        &self.layout
+    }
+
     /// Get the telemetry aggregator, if telemetry has been started.
     pub fn telemetry(&self) -> Option<&Arc<TelemetryAggregator>> {
         self.telemetry.as_ref()
@@ -1332,7 +1725,7 @@ impl MicroVm {
 
     /// Whether this VM has virtio-net enabled.
     pub fn has_network(&self) -> bool {
-        self.virtio_net.is_some()
+        self.virtio_net.is_some() || self.vhost_net.is_some()
     }
 
     /// Get the vsock Unix socket path (set on restored VMs).
@@ -1350,6 +1743,59 @@ impl MicroVm {
         self.vsock.as_ref().map(|v| v.connector())
     }
 
+    /// Returns a [`GuestConnector`] over the virtio-console control port,
+    /// if the VM was configured with one.
+    ///
+    /// Each connection replaces the previous one; see
+    /// [`crate::devices::virtio_console`].
+    ///
+    /// [`GuestConnector`]: crate::backend::control_channel::GuestConnector
+    pub fn control_port_connector(
+        &self,
+    ) -> Option<crate::backend::control_channel::GuestConnector> {
+        self.virtio_console
+            .as_ref()
+            .map(|dev| crate::devices::virtio_console::connector(dev.clone()))
+    }
+
+    /// Ask the guest to shrink (or grow back) to about `mb` MiB of usable
+    /// memory by resizing the virtio-balloon.
+    ///
+    /// Pages the guest hands over are released to the host. Passing the
+    /// VM's full `memory_mb` deflates the balloon entirely. The guest
+    /// inflates asynchronously; see [`MicroVm::memory_actual_mb`].
+    pub fn set_memory_target(&self, mb: usize) -> Result<()> {
+        let balloon = self
+            .virtio_balloon
+            .as_ref()
+            .ok_or_else(|| Error::Config("virtio-balloon is disabled for this VM".into()))?;
+        let total_mb = (self.vm.memory_size() >> 20) as usize;
+        if mb == 0 || mb > total_mb {
+            return Err(Error::Config(format!(
+                "memory target {} MiB must be between 1 and {} MiB",
+                mb, total_mb
+            )));
+        }
+        let pages = ((total_mb - mb) as u64 * (1 << 20) / BALLOON_PAGE_SIZE) as u32;
+
+        let mut dev = balloon.lock().unwrap();
+        dev.set_target_pages(pages);
+        // A halted vCPU never reaches the run-loop IRQ poll, so deliver the
+        // config-change interrupt from here.
+        if dev.has_pending_interrupt() {
+            cpu::inject_irq(self.vm.vm_fd().as_raw_fd(), VirtioSlot::Balloon);
+        }
+        Ok(())
+    }
+
+    /// Guest memory not currently held by the balloon, in MiB, as last
+    /// reported by the guest driver. `None` if the balloon is disabled.
+    pub fn memory_actual_mb(&self) -> Option<usize> {
+        let balloon = self.virtio_balloon.as_ref()?;
+        let held = u64::from(balloon.lock().unwrap().actual_pages()) * BALLOON_PAGE_SIZE;
+        Some((self.vm.memory_size().saturating_sub(held) >> 20) as usize)
+    }
+
     /// Check if the VM is currently running
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
@@ -1371,14 +1817,173 @@ impl MicroVm {
         self.serial_output.take()
     }
 
-    /// Stop the VM
+    /// The guest serial console, for queueing host input with
+    /// [`SerialDevice::queue_input`].
+    pub fn serial(&self) -> SerialDevice {
+        self.serial.clone()
+    }
+
+    /// Add the phases timed while creating the VM to `timeline`.
+    pub fn record_boot_phases(&self, timeline: &mut BootTimeline) {
+        for (name, start, end) in &self.boot_phases {
+            timeline.record(name, *start, *end);
+        }
+    }
+
+    /// Stop the VM, letting the guest shut down cleanly first (see
+    /// [`shutdown`](Self::shutdown)) with [`DEFAULT_SHUTDOWN_TIMEOUT`].
     pub async fn stop(&mut self) -> Result<()> {
+        self.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await.map(|_| ())
+    }
+
+    /// Ask the guest-agent to terminate its processes, sync filesystems, and
+    /// power off, then tear the VM down.
+    ///
+    /// Half of `timeout` is the guest's SIGTERM grace period; the rest
+    /// covers the sync and power-off. Returns the guest's summary, or `None`
+    /// when the guest did not acknowledge in time (or has no vsock) and the
+    /// VM was stopped hard.
+    pub async fn shutdown(&mut self, timeout: std::time::Duration) -> Result<Option<ShutdownAck>> {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut ack = None;
+        if self.running.load(Ordering::SeqCst) {
+            if let Some(channel) = self.control_channel.clone() {
+                match channel.send_shutdown(timeout / 2, timeout).await {
+                    Ok(summary) => {
+                        info!(
+                            "Guest shut down: {} processes terminated ({} killed), {} bytes flushed in {}ms",
+                            summary.processes_terminated,
+                            summary.processes_killed,
+                            summary.bytes_flushed,
+                            summary.duration_ms
+                        );
+                        ack = Some(summary);
+                    }
+                    Err(e) => warn!("Graceful guest shutdown failed, stopping hard: {}", e),
+                }
+            }
+        }
+        if ack.is_some() {
+            // The guest powers off right after acking; give its vCPUs the
+            // rest of the timeout to exit on their own.
+            while self.running.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        }
+        self.hard_stop().await?;
+        Ok(ack)
+    }
+
+    /// Restart the guest kernel without rebuilding the VM.
+    ///
+    /// The guest-agent stops its processes and syncs as for
+    /// [`shutdown`](Self::shutdown), then resets the guest. Instead of
+    /// exiting, the vCPUs park while the kernel and initramfs are loaded
+    /// into the same guest memory again, then re-enter it: devices, the
+    /// memory layout and the vsock CID carry over. Returns once the new
+    /// boot's guest-agent has completed its handshake.
+    ///
+    /// Half of `timeout` is the guest's SIGTERM grace period; the whole of
+    /// it bounds the wait for the guest to reset. A VM restored from a
+    /// snapshot has no kernel to reload and cannot reboot. A reboot that
+    /// fails after the guest went down stops the VM.
+    pub async fn reboot(&mut self, timeout: std::time::Duration) -> Result<ShutdownAck> {
+        let config = self
+            .boot_config
+            .clone()
+            .ok_or_else(|| Error::Config("a VM restored from a snapshot cannot reboot".into()))?;
         if !self.running.load(Ordering::SeqCst) {
+            return Err(Error::VmNotRunning);
+        }
+        let channel = self
+            .control_channel
+            .clone()
+            .ok_or_else(|| Error::Guest("no control channel".into()))?;
+        let deadline = std::time::Instant::now() + timeout;
+
+        self.guest_reset.arm();
+        let ack = match channel.send_reboot(timeout / 2, timeout).await {
+            Ok(ack) => ack,
+            Err(e) => {
+                self.guest_reset.disarm();
+                return Err(e);
+            }
+        };
+        info!(
+            "Guest rebooting: {} processes terminated ({} killed), {} bytes flushed in {}ms",
+            ack.processes_terminated, ack.processes_killed, ack.bytes_flushed, ack.duration_ms
+        );
+        if let Err(e) = self.restart_guest(&config, &channel, deadline).await {
+            warn!("Guest reboot failed, stopping VM: {}", e);
+            self.hard_stop().await?;
+            return Err(e);
+        }
+        Ok(ack)
+    }
+
+    /// Wait for the vCPUs to park on the guest's reset, reload the kernel
+    /// and start them again.
+    async fn restart_guest(
+        &self,
+        config: &VoidBoxConfig,
+        channel: &ControlChannel,
+        deadline: std::time::Instant,
+    ) -> Result<()> {
+        let reset = self.guest_reset.clone();
+        let vcpus = self.vcpu_handles.len();
+        let wait = deadline.saturating_duration_since(std::time::Instant::now());
+        let parked = tokio::task::spawn_blocking(move || reset.wait_parked(vcpus, wait))
+            .await
+            .map_err(|e| Error::Vcpu(format!("reboot wait panicked: {e}")))?;
+        if !parked {
+            return Err(Error::Guest(format!(
+                "guest did not reset within {:?}",
+                wait
+            )));
+        }
+
+        // A fresh cmdline carries the current wall-clock time.
+        let platform = arch::BootPlatform {
+            vcpu_count: config.vcpus,
+            virtio_windows: self.layout.windows().to_vec(),
+        };
+        let entry_point = boot::load_kernel(
+            &self.vm,
+            &config.kernel,
+            config.initramfs.as_deref(),
+            &config.kernel_cmdline(),
+            &platform,
+        )?;
+        // The parked guest can no longer pet it; the next boot's agent
+        // arms it again once it is up.
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.disarm();
+        }
+        channel.reset().await;
+        self.guest_reset.release(entry_point);
+        info!("Guest kernel reloaded, entry point {:#x}", entry_point);
+        channel.ensure_connected().await
+    }
+
+    /// Stop the vCPUs and join every VM thread without involving the guest.
+    async fn hard_stop(&mut self) -> Result<()> {
+        // A guest that powered off on its own has cleared `running`, but its
+        // threads still need joining.
+        if !self.running.load(Ordering::SeqCst)
+            && self.vcpu_handles.is_empty()
+            && self.event_loop_handle.is_none()
+        {
             return Ok(());
         }
 
         info!("Stopping MicroVm");
 
+        // The guest-agent's connection is about to drop for good; streams
+        // must not try to resume on a new one.
+        if let Some(channel) = &self.control_channel {
+            channel.close();
+        }
+
         // Signal stop through command channel
         let _ = self.command_tx.send(VmCommand::Stop).await;
 
@@ -1428,6 +2033,16 @@ impl MicroVm {
                 .join()
                 .map_err(|_| Error::Vcpu("net-poll thread panic".into()))?;
         }
+        if let Some(handle) = self.throttle_handle.take() {
+            handle
+                .join()
+                .map_err(|_| Error::Vcpu("vcpu-throttle thread panic".into()))?;
+        }
+        if let Some(handle) = self.console_io_handle.take() {
+            handle
+                .join()
+                .map_err(|_| Error::Vcpu("console-io thread panic".into()))?;
+        }
         Ok(())
     }
 }
@@ -1465,6 +2080,7 @@ fn install_seccomp_filter() -> Result<()> {
         libc::SYS_epoll_ctl,
         libc::SYS_epoll_create1,
         libc::SYS_socket, // AF_VSOCK, AF_INET
+        libc::SYS_socketpair, // virtio-console control port connections
         libc::SYS_connect,
         libc::SYS_close,
         libc::SYS_clock_gettime,
@@ -1545,14 +2161,23 @@ fn install_seccomp_filter() -> Result<()> {
     Ok(())
 }
 
-/// Background thread that bridges vhost-vsock call eventfds to virtio-mmio interrupts.
+/// Interrupt callback for the serial console: pulses the UART's line.
+fn uart_interrupt(vm: &Arc<Vm>) -> Arc<dyn Fn() + Send + Sync> {
+    let vm = Arc::clone(vm);
+    Arc::new(move || cpu::inject_irq_line(vm.vm_fd().as_raw_fd(), arch::uart_irq_line_value()))
+}
+
+/// Background thread that bridges vhost call eventfds to virtio-mmio interrupts.
 ///
-/// When the vhost backend has data for the guest, it writes to a call eventfd.
-/// This thread detects that signal, sets the MMIO device's INTERRUPT_STATUS register,
-/// and injects IRQ 11 into the guest via the in-kernel irqchip (KVM_IRQ_LINE).
-fn vsock_irq_thread(
+/// When a vhost backend (vsock or net) has data for the guest, it writes to a
+/// call eventfd. This thread detects that signal, sets the MMIO device's
+/// INTERRUPT_STATUS register via `set_interrupt_status`, and injects the
+/// device's IRQ into the guest via the in-kernel irqchip (KVM_IRQ_LINE).
+fn vhost_irq_thread(
+    label: &'static str,
     call_fds: Vec<RawFd>,
-    vsock_mmio: Arc<Mutex<dyn VsockMmioDevice>>,
+    set_interrupt_status: impl Fn(u32),
+    slot: VirtioSlot,
     vm_fd: RawFd,
     running: Arc<AtomicBool>,
 ) {
@@ -1563,7 +2188,8 @@ fn vsock_irq_thread(
     let epfd = unsafe { epoll_create1(EPOLL_CLOEXEC) };
     if epfd < 0 {
         error!(
-            "vsock-irq: epoll_create1 failed: {}",
+            "{}: epoll_create1 failed: {}",
+            label,
             std::io::Error::last_os_error()
         );
         return;
@@ -1577,7 +2203,8 @@ fn vsock_irq_thread(
         let ret = unsafe { epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &mut ev) };
         if ret < 0 {
             error!(
-                "vsock-irq: epoll_ctl ADD fd={} failed: {}",
+                "{}: epoll_ctl ADD fd={} failed: {}",
+                label,
                 fd,
                 std::io::Error::last_os_error()
             );
@@ -1596,7 +2223,7 @@ fn vsock_irq_thread(
             if e.raw_os_error() == Some(libc::EINTR) {
                 continue;
             }
-            error!("vsock-irq: epoll_wait failed: {}", e);
+            error!("{}: epoll_wait failed: {}", label, e);
             break;
         }
 
@@ -1609,12 +2236,10 @@ fn vsock_irq_thread(
                     unsafe { libc::read(call_fds[idx], buf.as_mut_ptr() as *mut libc::c_void, 8) };
 
                 // Set INTERRUPT_STATUS so the guest ISR sees a used-buffer notification
-                if let Ok(mut dev) = vsock_mmio.lock() {
-                    dev.set_interrupt_status(1);
-                }
+                set_interrupt_status(1);
 
-                // Inject IRQ 11 (vsock) via KVM_IRQ_LINE
-                cpu::inject_irq(vm_fd, VirtioSlot::Vsock);
+                // Inject the device's IRQ via KVM_IRQ_LINE
+                cpu::inject_irq(vm_fd, slot);
             }
         }
     }
@@ -1622,7 +2247,7 @@ fn vsock_irq_thread(
     unsafe {
         libc::close(epfd);
     }
-    debug!("vsock-irq thread exiting");
+    debug!("{} thread exiting", label);
 }
 
 /// Background thread that polls SLIRP for host→guest TCP data.
@@ -1743,10 +2368,10 @@ fn net_poll_thread(net_dev: Arc<Mutex<VirtioNetDevice>>, vm: Arc<Vm>, running: A
     // Obtain the epoll Arc from the backend without holding the device lock
     // across the blocking wait.  Falls back to None if the backend is not
     // a SlirpBackend (e.g. in unit tests or future alternative backends).
-    let epoll_arc = {
+    let (epoll_arc, net_mmio_base) = {
         match net_dev.lock() {
-            Ok(guard) => guard.epoll_arc(),
-            Err(_) => None,
+            Ok(guard) => (guard.epoll_arc(), guard.mmio_base()),
+            Err(_) => (None, 0),
         }
     };
 
@@ -1806,8 +2431,8 @@ fn net_poll_thread(net_dev: Arc<Mutex<VirtioNetDevice>>, vm: Arc<Vm>, running: A
     // index 1 = transmit queue).  Notifies for queue 0 (RX) still take the
     // slow path through MMIO; they're rare (only when guest adds new RX
     // buffers) so the optimisation isn't needed there.  The doorbell
-    // address derives from the same slot table as the device's mmio_base,
-    // so the two cannot drift apart (a mismatched datamatch would silently
+    // address is read back from the device's own mmio_base, so the two
+    // cannot drift apart (a mismatched datamatch would silently
     // push every TX back to the MMIO-exit path).
     const VIRTIO_NET_QUEUE_NOTIFY_OFFSET: u64 = 0x050;
     const TX_NOTIFY_QUEUE_IDX: u32 = 1;
@@ -1819,7 +2444,7 @@ fn net_poll_thread(net_dev: Arc<Mutex<VirtioNetDevice>>, vm: Arc<Vm>, running: A
     let tx_notify_eventfd = setup_tx_notify_ioeventfd(
         vm.as_ref(),
         epoll_arc.as_ref(),
-        VirtioSlot::Net.mmio_base() + VIRTIO_NET_QUEUE_NOTIFY_OFFSET,
+        net_mmio_base + VIRTIO_NET_QUEUE_NOTIFY_OFFSET,
         TX_NOTIFY_QUEUE_IDX,
         TX_NOTIFY_TOKEN,
     );
@@ -1990,6 +2615,60 @@ fn net_poll_thread(net_dev: Arc<Mutex<VirtioNetDevice>>, vm: Arc<Vm>, running: A
     debug!("net-poll thread exiting");
 }
 
+/// Background thread serving the virtio-console control port: moves data
+/// between the host connection and the guest's queues when the socket or
+/// the device's wake eventfd is ready, and raises the device's interrupt.
+fn console_io_thread(dev: Arc<Mutex<VirtioConsoleDevice>>, vm: Arc<Vm>, running: Arc<AtomicBool>) {
+    let vm_fd = vm.vm_fd().as_raw_fd();
+    let guest_memory = vm.guest_memory();
+    let wake_fd = dev.lock().unwrap().wake_fd();
+
+    // Same 20ms ceiling as vhost_irq_thread, so `stop()` reclaims the
+    // thread promptly; real work wakes the poll immediately.
+    while running.load(Ordering::Relaxed) {
+        let host = dev.lock().unwrap().host_poll_events();
+        let mut fds = [
+            libc::pollfd {
+                fd: wake_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: host.map_or(-1, |(fd, _)| fd),
+                events: host.map_or(0, |(_, events)| events),
+                revents: 0,
+            },
+        ];
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 20) };
+        if ret < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            error!("console-io: poll failed: {}", e);
+            break;
+        }
+        if ret == 0 {
+            continue;
+        }
+
+        let mut guard = dev.lock().unwrap();
+        guard.process_host_io(guest_memory);
+        if guard.take_interrupt() {
+            cpu::inject_irq(vm_fd, VirtioSlot::Console);
+        }
+        drop(guard);
+
+        // A hung-up socket polls ready until the guest drains what the host
+        // sent before closing; don't spin meanwhile.
+        if fds[0].revents == 0 && fds[1].revents & fds[1].events == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    debug!("console-io thread exiting");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
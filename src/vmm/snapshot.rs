@@ -24,7 +24,12 @@ use crate::{Error, Result};
 /// option/enum encoding — is not compatible with pre-v4 snapshots. Old
 /// `state.bin` files fail to decode before the version check ever runs;
 /// delete `~/.void-box/snapshots/` to recover.
-pub const SNAPSHOT_VERSION: u32 = 4;
+///
+/// Bumped to 5 for `rng_state`: postcard is not self-describing, so v4
+/// files cannot decode the new trailing field either.
+///
+/// Bumped to 6 for `balloon_state`, for the same reason.
+pub const SNAPSHOT_VERSION: u32 = 6;
 
 // Re-export cross-platform snapshot utilities from `snapshot_store`.
 pub use crate::snapshot_store::{
@@ -51,6 +56,9 @@ pub struct SnapshotConfig {
     pub cid: u32,
     pub vsock_mmio_base: u64,
     pub network: bool,
+    /// Custom virtio-mmio window base, when the VM did not use the default.
+    #[serde(default)]
+    pub virtio_mmio_base: Option<u64>,
 }
 
 /// Snapshot of a single virtio queue's software state.
@@ -99,6 +107,31 @@ pub struct VsockSnapshotState {
     pub queues: Vec<QueueSnapshotState>,
 }
 
+/// Serializable virtio-rng MMIO device state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RngSnapshotState {
+    pub driver_features: u64,
+    pub status: u32,
+    pub interrupt_status: u32,
+    /// The single request queue.
+    pub queue: QueueSnapshotState,
+}
+
+/// Serializable virtio-balloon MMIO device state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalloonSnapshotState {
+    pub driver_features: u64,
+    pub status: u32,
+    pub interrupt_status: u32,
+    pub config_generation: u32,
+    /// Host-requested balloon size in 4 KiB pages.
+    pub num_pages: u32,
+    /// Guest-reported balloon size in 4 KiB pages.
+    pub actual: u32,
+    /// inflate(0), deflate(1) queue state.
+    pub queues: Vec<QueueSnapshotState>,
+}
+
 /// Top-level VM snapshot.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VmSnapshot {
@@ -127,6 +160,12 @@ pub struct VmSnapshot {
     /// Virtio-net device state (None if networking was disabled).
     #[serde(default)]
     pub net_state: Option<NetSnapshotState>,
+    /// Virtio-rng device state (None if the device was disabled).
+    #[serde(default)]
+    pub rng_state: Option<RngSnapshotState>,
+    /// Virtio-balloon device state (None if the device was disabled).
+    #[serde(default)]
+    pub balloon_state: Option<BalloonSnapshotState>,
 }
 
 impl VmSnapshot {
@@ -754,12 +793,15 @@ mod tests {
                 vcpus: 1,
                 cid: 42,
                 vsock_mmio_base: 0xd080_0000,
+                virtio_mmio_base: None,
                 network: false,
             },
             config_hash: "abc123".into(),
             snapshot_type: SnapshotType::Base,
             session_secret: vec![0xAA; 32],
             net_state: None,
+            rng_state: None,
+            balloon_state: None,
         };
         let bytes = postcard::to_allocvec(&snap).unwrap();
         let restored: VmSnapshot = postcard::from_bytes(&bytes).unwrap();
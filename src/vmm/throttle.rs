@@ -0,0 +1,193 @@
+//! Host-side CPU limits for vCPU threads (see [`ResourcePolicy`]).
+//!
+//! Pinning is a `sched_setaffinity` on each vCPU thread. The CPU quota is
+//! enforced by thread scheduling rather than a cgroup, so it needs no
+//! cgroup delegation and stays per-VM when several VMs share one process:
+//! a ticker thread kicks every vCPU out of `KVM_RUN` each [`TICK`], and a
+//! vCPU that has used its share of the current [`PERIOD`] sleeps until the
+//! period ends.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::backend::ResourcePolicy;
+use crate::{Error, Result};
+
+/// Accounting window for the CPU quota.
+pub const PERIOD: Duration = Duration::from_millis(100);
+
+/// How often throttled vCPUs are kicked to check their budget. Bounds how
+/// far a guest that never exits can overrun its share of a period.
+pub const TICK: Duration = Duration::from_millis(10);
+
+/// Scheduling limits for one vCPU thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VcpuLimits {
+    /// Host CPU the thread is pinned to.
+    pub cpu: Option<usize>,
+    /// CPU time the thread may use per [`PERIOD`].
+    pub budget: Option<Duration>,
+}
+
+impl VcpuLimits {
+    /// Limits for vCPU `vcpu_id` of a `vcpus`-vCPU VM under `policy`.
+    ///
+    /// The quota is split evenly; a share of a full CPU or more needs no
+    /// throttling.
+    pub fn for_vcpu(policy: &ResourcePolicy, vcpu_id: usize, vcpus: usize) -> Self {
+        let cpu = (!policy.cpu_affinity.is_empty())
+            .then(|| policy.cpu_affinity[vcpu_id % policy.cpu_affinity.len()]);
+        let budget = policy
+            .cpu_quota
+            .map(|quota| quota / vcpus as f64)
+            .filter(|&share| share < 1.0)
+            .map(|share| PERIOD.mul_f64(share));
+        Self { cpu, budget }
+    }
+}
+
+/// Check that every CPU in `cpus` is one this process may run on.
+pub fn validate_affinity(cpus: &[usize]) -> Result<()> {
+    if cpus.is_empty() {
+        return Ok(());
+    }
+    let allowed = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(Error::Config(format!(
+                "sched_getaffinity: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        set
+    };
+    for &cpu in cpus {
+        if !unsafe { libc::CPU_ISSET(cpu, &allowed) } {
+            return Err(Error::Config(format!(
+                "cpu_affinity: host CPU {} is not available to this process",
+                cpu
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Pin the calling thread to host CPU `cpu`.
+pub fn pin_current_thread(cpu: usize) -> Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(Error::Vcpu(format!(
+                "failed to pin to CPU {}: {}",
+                cpu,
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// CPU time consumed by the calling thread.
+fn thread_cpu_time() -> Duration {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Per-period CPU accounting for the calling vCPU thread.
+pub struct CpuThrottle {
+    budget: Duration,
+    period_start: Instant,
+    used_at_period_start: Duration,
+}
+
+impl CpuThrottle {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            period_start: Instant::now(),
+            used_at_period_start: thread_cpu_time(),
+        }
+    }
+
+    /// Sleep out the rest of the current period if this thread has spent
+    /// its budget, then start a new one.
+    pub fn throttle(&mut self) {
+        let period_end = self.period_start + PERIOD;
+        let now = Instant::now();
+        if now < period_end {
+            if thread_cpu_time().saturating_sub(self.used_at_period_start) < self.budget {
+                return;
+            }
+            thread::sleep(period_end - now);
+        }
+        self.period_start = Instant::now();
+        self.used_at_period_start = thread_cpu_time();
+    }
+}
+
+/// Spawn the thread that kicks every vCPU each [`TICK`] until `running`
+/// clears, so throttled vCPUs check their budget even when the guest
+/// never exits on its own.
+pub fn spawn_ticker(
+    pthread_ids: Vec<Arc<AtomicU64>>,
+    running: Arc<AtomicBool>,
+) -> Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("vcpu-throttle".into())
+        .spawn(move || {
+            debug!("vCPU throttle ticker started");
+            while running.load(Ordering::SeqCst) {
+                thread::sleep(TICK);
+                for tid in &pthread_ids {
+                    let tid = tid.load(Ordering::SeqCst);
+                    if tid != 0 {
+                        unsafe {
+                            libc::pthread_kill(tid as libc::pthread_t, libc::SIGRTMIN());
+                        }
+                    }
+                }
+            }
+            debug!("vCPU throttle ticker exiting");
+        })
+        .map_err(|e| Error::Vcpu(format!("Failed to spawn throttle thread: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vcpu_limits_split_quota_and_round_robin_affinity() {
+        let policy = ResourcePolicy {
+            cpu_quota: Some(1.0),
+            cpu_affinity: vec![2, 5],
+        };
+        assert_eq!(
+            VcpuLimits::for_vcpu(&policy, 2, 4),
+            VcpuLimits {
+                cpu: Some(2),
+                budget: Some(PERIOD / 4),
+            }
+        );
+        assert_eq!(VcpuLimits::for_vcpu(&policy, 3, 4).cpu, Some(5));
+
+        // A share of a whole CPU or more is not throttled.
+        let generous = ResourcePolicy {
+            cpu_quota: Some(4.0),
+            ..Default::default()
+        };
+        assert_eq!(VcpuLimits::for_vcpu(&generous, 0, 4), VcpuLimits::default());
+    }
+
+    #[test]
+    fn test_validate_affinity_rejects_unavailable_cpu() {
+        assert!(validate_affinity(&[]).is_ok());
+        assert!(validate_affinity(&[1023]).is_err());
+    }
+}
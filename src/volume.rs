@@ -0,0 +1,442 @@
+//! Named, host-managed volumes that outlive the sandboxes using them.
+//!
+//! A volume is a directory under [`default_volume_dir`] holding a
+//! `meta.json` and its data: an ext4 `disk.img` attached via virtio-blk
+//! (KVM), or a `data/` directory shared into the guest (virtiofs on macOS).
+//! Sandboxes mount volumes with `SandboxBuilder::volume`; everything written
+//! there survives VM teardown.
+//!
+//! A volume must not be mounted by two running sandboxes at once — for
+//! parallel experiments, [`Volume::clone_as`] makes a copy-on-write clone
+//! (reflink / `clonefile(2)` where the host filesystem supports it, a plain
+//! copy otherwise).
+//!
+//! Like [`crate::snapshot_store`], this is pure filesystem code with no KVM
+//! or VZ dependencies.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{Error, Result};
+
+/// Capacity of a volume created implicitly by `SandboxBuilder::volume`.
+pub const DEFAULT_VOLUME_SIZE_GB: u64 = 10;
+
+const META_FILE: &str = "meta.json";
+const DISK_FILE: &str = "disk.img";
+const DATA_DIR: &str = "data";
+
+/// Default volume storage directory.
+///
+/// Checks `VOIDBOX_HOME` first, then falls back to `$HOME/.void-box/volumes`.
+pub fn default_volume_dir() -> PathBuf {
+    if let Ok(home) = std::env::var("VOIDBOX_HOME") {
+        return PathBuf::from(home).join("volumes");
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".void-box").join("volumes")
+}
+
+/// How a volume's data is stored on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeBacking {
+    /// Sparse ext4 image attached as a virtio-blk data disk (KVM).
+    Disk,
+    /// Host directory shared into the guest (virtiofs on macOS, 9p on KVM).
+    Directory,
+}
+
+impl VolumeBacking {
+    /// The backing new volumes get on this platform.
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "linux") {
+            VolumeBacking::Disk
+        } else {
+            VolumeBacking::Directory
+        }
+    }
+}
+
+/// Metadata persisted in each volume's `meta.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeInfo {
+    pub name: String,
+    pub backing: VolumeBacking,
+    /// Capacity of a disk-backed volume; unused for directories.
+    pub size_gb: u64,
+    /// Unix seconds.
+    pub created_at: u64,
+    /// Volume this one was cloned from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloned_from: Option<String>,
+}
+
+/// A volume on disk. Cheap to construct; all state lives in its directory.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    dir: PathBuf,
+    info: VolumeInfo,
+}
+
+impl Volume {
+    /// Open volume `name` in the default directory, creating it with the
+    /// platform's default backing and `size_gb` if it does not exist yet.
+    pub fn open_or_create(name: &str, size_gb: u64) -> Result<Self> {
+        Self::open_or_create_in(&default_volume_dir(), name, size_gb)
+    }
+
+    /// [`Volume::open_or_create`] under a custom base directory.
+    pub fn open_or_create_in(base: &Path, name: &str, size_gb: u64) -> Result<Self> {
+        match Self::open_in(base, name)? {
+            Some(volume) => Ok(volume),
+            None => Self::create_in(base, name, VolumeBacking::platform_default(), size_gb),
+        }
+    }
+
+    /// Open an existing volume in the default directory.
+    pub fn open(name: &str) -> Result<Option<Self>> {
+        Self::open_in(&default_volume_dir(), name)
+    }
+
+    /// [`Volume::open`] under a custom base directory.
+    pub fn open_in(base: &Path, name: &str) -> Result<Option<Self>> {
+        validate_name(name)?;
+        let dir = base.join(name);
+        let meta_path = dir.join(META_FILE);
+        if !meta_path.exists() {
+            return Ok(None);
+        }
+        let info = read_info(&meta_path)?;
+        Ok(Some(Self { dir, info }))
+    }
+
+    /// Create volume `name` under `base`. A disk-backed volume is a sparse
+    /// `size_gb` GiB image formatted with `mkfs.ext4`.
+    pub fn create_in(
+        base: &Path,
+        name: &str,
+        backing: VolumeBacking,
+        size_gb: u64,
+    ) -> Result<Self> {
+        validate_name(name)?;
+        if backing == VolumeBacking::Disk && size_gb == 0 {
+            return Err(Error::Config("volume size must be at least 1 GiB".into()));
+        }
+        let dir = base.join(name);
+        if dir.exists() {
+            return Err(Error::Config(format!("volume '{}' already exists", name)));
+        }
+        fs::create_dir_all(&dir)?;
+
+        let created = match backing {
+            VolumeBacking::Disk => create_ext4_image(&dir.join(DISK_FILE), size_gb),
+            VolumeBacking::Directory => fs::create_dir(dir.join(DATA_DIR)).map_err(Error::from),
+        };
+        let info = VolumeInfo {
+            name: name.to_string(),
+            backing,
+            size_gb,
+            created_at: unix_now(),
+            cloned_from: None,
+        };
+        // meta.json goes last: a directory without it is not a volume.
+        if let Err(e) = created.and_then(|()| write_info(&dir.join(META_FILE), &info)) {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        info!(
+            "Created volume '{}' ({:?}) at {}",
+            name,
+            backing,
+            dir.display()
+        );
+        Ok(Self { dir, info })
+    }
+
+    /// List all volumes in the default directory, sorted by name.
+    pub fn list() -> Result<Vec<VolumeInfo>> {
+        Self::list_in(&default_volume_dir())
+    }
+
+    /// [`Volume::list`] under a custom base directory.
+    pub fn list_in(base: &Path) -> Result<Vec<VolumeInfo>> {
+        if !base.exists() {
+            return Ok(Vec::new());
+        }
+        let mut infos = Vec::new();
+        for entry in fs::read_dir(base)? {
+            let meta_path = entry?.path().join(META_FILE);
+            if !meta_path.exists() {
+                continue;
+            }
+            match read_info(&meta_path) {
+                Ok(info) => infos.push(info),
+                Err(e) => debug!("Skipping unreadable volume {}: {}", meta_path.display(), e),
+            }
+        }
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(infos)
+    }
+
+    /// Delete volume `name` and its data from the default directory.
+    /// Returns `false` if it did not exist.
+    pub fn delete(name: &str) -> Result<bool> {
+        Self::delete_in(&default_volume_dir(), name)
+    }
+
+    /// [`Volume::delete`] under a custom base directory.
+    pub fn delete_in(base: &Path, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        let dir = base.join(name);
+        if !dir.join(META_FILE).exists() {
+            return Ok(false);
+        }
+        fs::remove_dir_all(&dir)?;
+        info!("Deleted volume '{}'", name);
+        Ok(true)
+    }
+
+    /// Clone this volume as `name`, next to it. The clone shares unchanged
+    /// blocks with the original where the host filesystem supports it.
+    pub fn clone_as(&self, name: &str) -> Result<Self> {
+        validate_name(name)?;
+        let base = self.dir.parent().unwrap_or(Path::new("."));
+        let dir = base.join(name);
+        if dir.exists() {
+            return Err(Error::Config(format!("volume '{}' already exists", name)));
+        }
+        fs::create_dir_all(&dir)?;
+
+        let copied = match self.info.backing {
+            VolumeBacking::Disk => clone_file(&self.disk_path(), &dir.join(DISK_FILE)),
+            VolumeBacking::Directory => clone_dir(&self.data_path(), &dir.join(DATA_DIR)),
+        };
+        let info = VolumeInfo {
+            name: name.to_string(),
+            created_at: unix_now(),
+            cloned_from: Some(self.info.name.clone()),
+            ..self.info.clone()
+        };
+        if let Err(e) = copied.and_then(|()| write_info(&dir.join(META_FILE), &info)) {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        info!("Cloned volume '{}' as '{}'", self.info.name, name);
+        Ok(Self { dir, info })
+    }
+
+    /// Volume name.
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// Persisted metadata.
+    pub fn info(&self) -> &VolumeInfo {
+        &self.info
+    }
+
+    /// Path of the ext4 image of a [`VolumeBacking::Disk`] volume.
+    pub fn disk_path(&self) -> PathBuf {
+        self.dir.join(DISK_FILE)
+    }
+
+    /// Path of the data directory of a [`VolumeBacking::Directory`] volume.
+    pub fn data_path(&self) -> PathBuf {
+        self.dir.join(DATA_DIR)
+    }
+}
+
+/// Names become directory names: 1–64 of `[A-Za-z0-9._-]`, not starting
+/// with `.`.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "invalid volume name '{}': use 1-64 of [A-Za-z0-9._-], not starting with '.'",
+            name
+        )))
+    }
+}
+
+fn read_info(path: &Path) -> Result<VolumeInfo> {
+    let data = fs::read_to_string(path)?;
+    serde_json::from_str(&data)
+        .map_err(|e| Error::Config(format!("invalid volume metadata {}: {}", path.display(), e)))
+}
+
+fn write_info(path: &Path, info: &VolumeInfo) -> Result<()> {
+    let data = serde_json::to_string_pretty(info)
+        .map_err(|e| Error::Config(format!("serialize volume metadata: {}", e)))?;
+    fs::write(path, data)?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Create a sparse `size_gb` GiB image at `path` and format it ext4.
+pub(crate) fn create_ext4_image(path: &Path, size_gb: u64) -> Result<()> {
+    let file = fs::File::create(path)?;
+    file.set_len(size_gb << 30)?;
+    drop(file);
+
+    let status = std::process::Command::new("mkfs.ext4")
+        .arg("-q")
+        .arg("-F")
+        .arg(path)
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        result => {
+            let _ = fs::remove_file(path);
+            let detail = match result {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            Err(Error::Config(format!(
+                "failed to format {} with mkfs.ext4 ({detail}); install e2fsprogs",
+                path.display()
+            )))
+        }
+    }
+}
+
+/// Copy `src` to `dst`, sharing extents via `FICLONE` when the filesystem
+/// supports reflinks.
+fn clone_file(src: &Path, dst: &Path) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let from = fs::File::open(src)?;
+        let to = fs::File::create(dst)?;
+        // SAFETY: FICLONE takes the source fd as its argument; both fds are
+        // open for the duration of the call.
+        let ret = unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) };
+        if ret == 0 {
+            debug!("Reflinked {} -> {}", src.display(), dst.display());
+            return Ok(());
+        }
+        debug!(
+            "FICLONE {} unsupported ({}), copying",
+            src.display(),
+            std::io::Error::last_os_error()
+        );
+    }
+    fs::copy(src, dst)?;
+    Ok(())
+}
+
+/// Recursively copy directory `src` to `dst` (which must not exist). On
+/// macOS, `clonefile(2)` clones the whole tree copy-on-write in one call.
+fn clone_dir(src: &Path, dst: &Path) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let src_c = std::ffi::CString::new(src.as_os_str().as_bytes())
+            .map_err(|e| Error::Config(e.to_string()))?;
+        let dst_c = std::ffi::CString::new(dst.as_os_str().as_bytes())
+            .map_err(|e| Error::Config(e.to_string()))?;
+        // SAFETY: both arguments are NUL-terminated paths that outlive the call.
+        if unsafe { libc::clonefile(src_c.as_ptr(), dst_c.as_ptr(), 0) } == 0 {
+            return Ok(());
+        }
+    }
+    copy_dir(src, dst)
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_volume_lifecycle() {
+        let base = tempfile::tempdir().unwrap();
+        let volume = Volume::create_in(base.path(), "proj", VolumeBacking::Directory, 0).unwrap();
+        fs::write(volume.data_path().join("main.rs"), b"fn main() {}").unwrap();
+        fs::create_dir(volume.data_path().join("src")).unwrap();
+
+        let clone = volume.clone_as("proj-exp").unwrap();
+        assert_eq!(clone.info().cloned_from.as_deref(), Some("proj"));
+        fs::write(clone.data_path().join("main.rs"), b"changed").unwrap();
+        assert_eq!(
+            fs::read(volume.data_path().join("main.rs")).unwrap(),
+            b"fn main() {}"
+        );
+        assert!(clone.data_path().join("src").is_dir());
+
+        let names: Vec<_> = Volume::list_in(base.path())
+            .unwrap()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        assert_eq!(names, ["proj", "proj-exp"]);
+
+        let reopened = Volume::open_in(base.path(), "proj").unwrap().unwrap();
+        assert_eq!(reopened.info(), volume.info());
+
+        assert!(Volume::delete_in(base.path(), "proj").unwrap());
+        assert!(!Volume::delete_in(base.path(), "proj").unwrap());
+        assert!(Volume::open_in(base.path(), "proj").unwrap().is_none());
+        assert!(clone.data_path().join("main.rs").exists());
+    }
+
+    #[test]
+    fn test_disk_volume_clone_keeps_capacity() {
+        let base = tempfile::tempdir().unwrap();
+        let volume = match Volume::create_in(base.path(), "disk", VolumeBacking::Disk, 1) {
+            Ok(volume) => volume,
+            Err(e) => {
+                eprintln!("skipping: cannot create disk volume: {e}");
+                return;
+            }
+        };
+        let clone = volume.clone_as("disk-2").unwrap();
+        assert_eq!(fs::metadata(clone.disk_path()).unwrap().len(), 1 << 30);
+        assert!(Volume::create_in(base.path(), "disk", VolumeBacking::Disk, 1).is_err());
+    }
+
+    #[test]
+    fn test_invalid_names_rejected() {
+        let base = tempfile::tempdir().unwrap();
+        for name in ["", ".hidden", "a/b", "../x", &"x".repeat(65)] {
+            assert!(Volume::open_in(base.path(), name).is_err(), "{name:?}");
+        }
+        assert!(Volume::open_in(base.path(), "ok_name-1.0")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -175,7 +175,13 @@ pub fn resolve_pipe_input(
         if let CompositionOp::Pipe { from, to } = op {
             if to == step_name {
                 if let Some(output) = outputs.get(from) {
-                    return Some(output.stdout.clone());
+                    return match output.stdout_bytes() {
+                        Ok(stdout) => Some(stdout.into_owned()),
+                        Err(e) => {
+                            tracing::warn!("Failed to read the output of step \"{}\": {}", from, e);
+                            None
+                        }
+                    };
                 }
             }
         }
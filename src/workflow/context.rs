@@ -5,21 +5,36 @@
 //! - Sandbox execution methods
 //! - Input data and environment
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::events::{self, WorkflowEvent};
+use crate::guest::protocol::TestCaseResult;
 use crate::sandbox::Sandbox;
+use crate::test_report::TestReport;
 use crate::{Error, ExecOutput, Result};
 
 /// Output from a step execution
 #[derive(Debug, Clone)]
 pub struct StepOutput {
-    /// Standard output
+    /// Standard output; empty when spilled to `stdout_path`
     pub stdout: Vec<u8>,
-    /// Standard error
+    /// Standard error; empty when spilled to `stderr_path`
     pub stderr: Vec<u8>,
     /// Exit code
     pub exit_code: i32,
+    /// File holding stdout, when the run's
+    /// [`OutputRetention`](super::OutputRetention) spilled it
+    pub stdout_path: Option<PathBuf>,
+    /// File holding stderr, when spilled
+    pub stderr_path: Option<PathBuf>,
+    /// Test cases the step's execs reported, if any
+    pub test_report: Option<TestReport>,
 }
 
 impl StepOutput {
@@ -29,6 +44,9 @@ impl StepOutput {
             stdout,
             stderr,
             exit_code,
+            stdout_path: None,
+            stderr_path: None,
+            test_report: None,
         }
     }
 
@@ -38,23 +56,64 @@ impl StepOutput {
             stdout: output.stdout,
             stderr: output.stderr,
             exit_code: output.exit_code,
+            stdout_path: None,
+            stderr_path: None,
+            test_report: output.test_report,
         }
     }
 
-    /// Get stdout as string
+    /// Get stdout, reading it back from disk if it was spilled
+    pub fn stdout_bytes(&self) -> Result<Cow<'_, [u8]>> {
+        load(&self.stdout, self.stdout_path.as_ref())
+    }
+
+    /// Get stderr, reading it back from disk if it was spilled
+    pub fn stderr_bytes(&self) -> Result<Cow<'_, [u8]>> {
+        load(&self.stderr, self.stderr_path.as_ref())
+    }
+
+    /// Get stdout as string (empty if a spilled stdout cannot be read)
     pub fn stdout_str(&self) -> String {
-        String::from_utf8_lossy(&self.stdout).into_owned()
+        lossy(self.stdout_bytes())
     }
 
-    /// Get stderr as string
+    /// Get stderr as string (empty if a spilled stderr cannot be read)
     pub fn stderr_str(&self) -> String {
-        String::from_utf8_lossy(&self.stderr).into_owned()
+        lossy(self.stderr_bytes())
+    }
+
+    /// Whether stdout or stderr lives on disk rather than in memory
+    pub fn is_spilled(&self) -> bool {
+        self.stdout_path.is_some() || self.stderr_path.is_some()
     }
 
     /// Check if step succeeded
     pub fn success(&self) -> bool {
         self.exit_code == 0
     }
+
+    /// Attach the test cases the step reported
+    pub fn with_test_report(mut self, test_report: Option<TestReport>) -> Self {
+        self.test_report = test_report;
+        self
+    }
+}
+
+fn load<'a>(data: &'a [u8], path: Option<&PathBuf>) -> Result<Cow<'a, [u8]>> {
+    match path {
+        Some(path) => Ok(Cow::Owned(std::fs::read(path)?)),
+        None => Ok(Cow::Borrowed(data)),
+    }
+}
+
+fn lossy(data: Result<Cow<'_, [u8]>>) -> String {
+    match data {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => {
+            tracing::warn!("Failed to read spilled step output: {}", e);
+            String::new()
+        }
+    }
 }
 
 /// Context for executing a workflow step
@@ -68,12 +127,20 @@ pub struct StepContext {
     previous_outputs: Arc<HashMap<String, StepOutput>>,
     /// Input data for this step (from piped step)
     input: Option<Vec<u8>>,
+    /// Item this run of the workflow handles (see `Workflow::map_over`)
+    item: Option<Arc<[u8]>>,
     /// Environment variables
     env: HashMap<String, String>,
     /// Working directory
     working_dir: Option<String>,
     /// Timeout in seconds for sandbox exec calls
     timeout_secs: Option<u64>,
+    /// Where exec output goes during `run_with_events`
+    events: Option<UnboundedSender<WorkflowEvent>>,
+    /// Fires when the run is cancelled
+    cancel: CancellationToken,
+    /// Test cases reported by the step's execs, shared across clones
+    test_results: Arc<Mutex<Vec<TestCaseResult>>>,
 }
 
 impl StepContext {
@@ -88,9 +155,13 @@ impl StepContext {
             sandbox,
             previous_outputs: Arc::new(previous_outputs),
             input: None,
+            item: None,
             env: HashMap::new(),
             working_dir: None,
             timeout_secs: None,
+            events: None,
+            cancel: CancellationToken::new(),
+            test_results: Arc::default(),
         }
     }
 
@@ -127,12 +198,21 @@ impl StepContext {
         self.input.as_deref()
     }
 
+    /// Get the item this run handles, when the workflow is mapped over a
+    /// dataset with `Workflow::map_over`
+    pub fn item(&self) -> Option<&[u8]> {
+        self.item.as_deref()
+    }
+
     /// Execute a command in the sandbox
     pub async fn exec(&self, program: &str, args: &[&str]) -> Result<Vec<u8>> {
         let output = self
             .sandbox
             .exec_with_options(program, args, &[], self.timeout_secs)
             .await?;
+        self.emit_output("stdout", &output.stdout);
+        self.emit_output("stderr", &output.stderr);
+        self.record_tests(&output);
         if output.success() {
             Ok(output.stdout)
         } else {
@@ -155,6 +235,9 @@ impl StepContext {
             .sandbox
             .exec_with_options(program, args, stdin, self.timeout_secs)
             .await?;
+        self.emit_output("stdout", &output.stdout);
+        self.emit_output("stderr", &output.stderr);
+        self.record_tests(&output);
         if output.success() {
             Ok(output.stdout)
         } else {
@@ -179,6 +262,7 @@ impl StepContext {
 
         let mut line_buf = String::new();
         while let Some(chunk) = chunk_rx.recv().await {
+            self.emit_output(&chunk.stream, &chunk.data);
             let text = String::from_utf8_lossy(&chunk.data);
             line_buf.push_str(&text);
             while let Some(newline_pos) = line_buf.find('\n') {
@@ -194,6 +278,7 @@ impl StepContext {
         let response = resp_rx
             .await
             .map_err(|_| Error::Guest("Streaming response channel closed".into()))??;
+        self.record_cases(&response.test_results);
 
         if response.exit_code == 0 {
             Ok(response.stdout)
@@ -214,7 +299,9 @@ impl StepContext {
 
     /// Execute a raw command (returns full output including exit code)
     pub async fn exec_raw(&self, program: &str, args: &[&str]) -> Result<ExecOutput> {
-        self.sandbox.exec(program, args).await
+        let output = self.sandbox.exec(program, args).await?;
+        self.record_tests(&output);
+        Ok(output)
     }
 
     /// Execute a raw command with stdin
@@ -224,13 +311,66 @@ impl StepContext {
         args: &[&str],
         stdin: &[u8],
     ) -> Result<ExecOutput> {
-        self.sandbox.exec_with_stdin(program, args, stdin).await
+        let output = self.sandbox.exec_with_stdin(program, args, stdin).await?;
+        self.record_tests(&output);
+        Ok(output)
+    }
+
+    /// Fires when the workflow run is cancelled. The scheduler already
+    /// kills the step's guest processes; steps doing long work on the host
+    /// can wait on it, or poll [`is_cancelled`](Self::is_cancelled), to
+    /// stop early.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Whether the workflow run has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Test cases this step's execs have reported so far, retries included
+    /// (see [`crate::test_report`])
+    pub fn test_report(&self) -> Option<TestReport> {
+        TestReport::from_cases(self.test_results.lock().unwrap().clone())
     }
 
     /// Get the sandbox reference
     pub fn sandbox(&self) -> &Arc<Sandbox> {
         &self.sandbox
     }
+
+    /// The same context on another sandbox, for re-running the step on a
+    /// replacement
+    pub(crate) fn with_sandbox(mut self, sandbox: Arc<Sandbox>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    fn record_tests(&self, output: &ExecOutput) {
+        if let Some(report) = &output.test_report {
+            self.record_cases(&report.cases);
+        }
+    }
+
+    fn record_cases(&self, cases: &[TestCaseResult]) {
+        if !cases.is_empty() {
+            self.test_results.lock().unwrap().extend_from_slice(cases);
+        }
+    }
+
+    fn emit_output(&self, stream: &str, data: &[u8]) {
+        if !data.is_empty() {
+            events::emit(
+                &self.events,
+                WorkflowEvent::StepOutputChunk {
+                    step: self.step_name.clone(),
+                    stream: stream.to_string(),
+                    data: String::from_utf8_lossy(data).into_owned(),
+                },
+            );
+        }
+    }
 }
 
 /// Builder for creating step contexts (used by scheduler)
@@ -239,9 +379,12 @@ pub struct StepContextBuilder {
     sandbox: Arc<Sandbox>,
     previous_outputs: HashMap<String, StepOutput>,
     input: Option<Vec<u8>>,
+    item: Option<Arc<[u8]>>,
     env: HashMap<String, String>,
     working_dir: Option<String>,
     timeout_secs: Option<u64>,
+    events: Option<UnboundedSender<WorkflowEvent>>,
+    cancel: Option<CancellationToken>,
 }
 
 impl StepContextBuilder {
@@ -252,9 +395,12 @@ impl StepContextBuilder {
             sandbox,
             previous_outputs: HashMap::new(),
             input: None,
+            item: None,
             env: HashMap::new(),
             working_dir: None,
             timeout_secs: None,
+            events: None,
+            cancel: None,
         }
     }
 
@@ -276,6 +422,12 @@ impl StepContextBuilder {
         self
     }
 
+    /// Set the mapped-over item
+    pub fn with_item(mut self, item: Option<Arc<[u8]>>) -> Self {
+        self.item = item;
+        self
+    }
+
     /// Set environment variables
     pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
         self.env = env;
@@ -294,6 +446,18 @@ impl StepContextBuilder {
         self
     }
 
+    /// Report exec output as [`WorkflowEvent::StepOutputChunk`]s
+    pub fn with_events(mut self, events: Option<UnboundedSender<WorkflowEvent>>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Hand the step the run's cancellation token
+    pub fn with_cancellation(mut self, cancel: Option<CancellationToken>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     /// Build the context
     pub fn build(self) -> StepContext {
         StepContext {
@@ -301,9 +465,13 @@ impl StepContextBuilder {
             sandbox: self.sandbox,
             previous_outputs: Arc::new(self.previous_outputs),
             input: self.input,
+            item: self.item,
             env: self.env,
             working_dir: self.working_dir,
             timeout_secs: self.timeout_secs,
+            events: self.events,
+            cancel: self.cancel.unwrap_or_default(),
+            test_results: Arc::default(),
         }
     }
 }
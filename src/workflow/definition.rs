@@ -6,9 +6,12 @@ use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use super::composition::CompositionOp;
 use super::context::StepContext;
+use crate::observe::SloPolicy;
+use crate::sandbox::ExecUser;
 use crate::{Error, Result};
 
 /// Type alias for step functions
@@ -24,10 +27,14 @@ pub struct Step {
     pub func: StepFn,
     /// Steps that must complete before this one
     pub depends_on: Vec<String>,
-    /// Timeout for this step in seconds
+    /// Timeout for each of this step's execs, in seconds
     pub timeout_secs: Option<u64>,
+    /// Time limit for the whole step, retries included
+    pub deadline: Option<Duration>,
     /// Retry configuration
     pub retry: Option<RetryConfig>,
+    /// Guest user the step's execs run as (default: the `sandbox` user)
+    pub user: Option<ExecUser>,
 }
 
 impl std::fmt::Debug for Step {
@@ -36,7 +43,9 @@ impl std::fmt::Debug for Step {
             .field("name", &self.name)
             .field("depends_on", &self.depends_on)
             .field("timeout_secs", &self.timeout_secs)
+            .field("deadline", &self.deadline)
             .field("retry", &self.retry)
+            .field("user", &self.user)
             .finish()
     }
 }
@@ -76,6 +85,10 @@ pub struct Workflow {
     pub compositions: Vec<CompositionOp>,
     /// Final step that produces the output
     pub output_step: Option<String>,
+    /// Cost/latency objectives evaluated while the workflow runs
+    pub slo: Option<SloPolicy>,
+    /// Time limit for the whole workflow
+    pub timeout: Option<Duration>,
 }
 
 impl std::fmt::Debug for Workflow {
@@ -85,6 +98,8 @@ impl std::fmt::Debug for Workflow {
             .field("steps", &self.steps.keys().collect::<Vec<_>>())
             .field("compositions", &self.compositions)
             .field("output_step", &self.output_step)
+            .field("slo", &self.slo)
+            .field("timeout", &self.timeout)
             .finish()
     }
 }
@@ -160,6 +175,8 @@ pub struct WorkflowBuilder {
     steps: HashMap<String, Step>,
     compositions: Vec<CompositionOp>,
     output_step: Option<String>,
+    slo: Option<SloPolicy>,
+    timeout: Option<Duration>,
 }
 
 impl WorkflowBuilder {
@@ -170,6 +187,8 @@ impl WorkflowBuilder {
             steps: HashMap::new(),
             compositions: Vec::new(),
             output_step: None,
+            slo: None,
+            timeout: None,
         }
     }
 
@@ -192,7 +211,9 @@ impl WorkflowBuilder {
                 func,
                 depends_on: Vec::new(),
                 timeout_secs: None,
+                deadline: None,
                 retry: None,
+                user: None,
             },
         );
 
@@ -223,7 +244,9 @@ impl WorkflowBuilder {
                 func,
                 depends_on: depends_on.iter().map(|s| s.to_string()).collect(),
                 timeout_secs: None,
+                deadline: None,
                 retry: None,
+                user: None,
             },
         );
 
@@ -247,8 +270,9 @@ impl WorkflowBuilder {
         self
     }
 
-    /// Set a step's timeout
-    pub fn timeout(mut self, step_name: impl Into<String>, secs: u64) -> Self {
+    /// Time limit for each exec a step runs; see
+    /// [`step_timeout`](Self::step_timeout) to limit the step as a whole
+    pub fn exec_timeout(mut self, step_name: impl Into<String>, secs: u64) -> Self {
         let name = step_name.into();
         if let Some(step) = self.steps.get_mut(&name) {
             step.timeout_secs = Some(secs);
@@ -256,6 +280,38 @@ impl WorkflowBuilder {
         self
     }
 
+    /// Time limit for a step, retries included. When it runs out the
+    /// step fails with [`Error::DeadlineExceeded`] and its guest processes
+    /// are killed.
+    pub fn step_timeout(mut self, step_name: impl Into<String>, limit: Duration) -> Self {
+        let name = step_name.into();
+        if let Some(step) = self.steps.get_mut(&name) {
+            step.deadline = Some(limit);
+        }
+        self
+    }
+
+    /// Time limit for the whole workflow. The step running when it runs
+    /// out fails like one past its [`step_timeout`](Self::step_timeout),
+    /// and steps that have not started yet fail without running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use void_box::workflow::Workflow;
+    /// let workflow = Workflow::define("ci")
+    ///     .step("build", |ctx| async move { ctx.exec("make", &[]).await })
+    ///     .step_timeout("build", Duration::from_secs(600))
+    ///     .timeout(Duration::from_secs(1800))
+    ///     .build();
+    /// assert_eq!(workflow.timeout, Some(Duration::from_secs(1800)));
+    /// ```
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.timeout = Some(limit);
+        self
+    }
+
     /// Configure retry for a step
     pub fn retry(mut self, step_name: impl Into<String>, config: RetryConfig) -> Self {
         let name = step_name.into();
@@ -265,12 +321,29 @@ impl WorkflowBuilder {
         self
     }
 
+    /// Run a step's execs as `user`, e.g. [`ExecUser::Root`] for a package
+    /// installation step; other steps keep running unprivileged. Agents
+    /// refuse to run as root.
+    pub fn run_as(mut self, step_name: impl Into<String>, user: ExecUser) -> Self {
+        let name = step_name.into();
+        if let Some(step) = self.steps.get_mut(&name) {
+            step.user = Some(user);
+        }
+        self
+    }
+
     /// Set the output step (determines final workflow output)
     pub fn output(mut self, step_name: impl Into<String>) -> Self {
         self.output_step = Some(step_name.into());
         self
     }
 
+    /// Attach SLOs; violations are raised through the observer's alert sinks
+    pub fn slo(mut self, policy: SloPolicy) -> Self {
+        self.slo = Some(policy);
+        self
+    }
+
     /// Build the workflow
     pub fn build(mut self) -> Workflow {
         // Auto-detect output step if not specified
@@ -295,6 +368,8 @@ impl WorkflowBuilder {
             steps: self.steps,
             compositions: self.compositions,
             output_step: self.output_step,
+            slo: self.slo,
+            timeout: self.timeout,
         }
     }
 }
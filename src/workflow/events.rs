@@ -0,0 +1,120 @@
+//! Live workflow progress
+//!
+//! [`ObservableWorkflow::run_with_events`] runs a workflow in the
+//! background and yields [`WorkflowEvent`]s while it runs, instead of
+//! nothing until the last step is done. Events serialize to one JSON
+//! object each, tagged by `"type"`, so a UI or a `--json` CLI mode can
+//! forward them as they arrive:
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use void_box::observe::ObserveConfig;
+//! use void_box::sandbox::Sandbox;
+//! use void_box::workflow::{Workflow, WorkflowExt};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let workflow = Workflow::define("ci")
+//!     .step("test", |ctx| async move { ctx.exec_streaming("make", &["test"]).await })
+//!     .build();
+//! let mut events = workflow
+//!     .observe(ObserveConfig::test())
+//!     .run_with_events(Sandbox::mock().build()?);
+//! while let Some(event) = events.next().await {
+//!     println!("{}", serde_json::to_string(&event)?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Steps that use [`StepContext::exec_streaming`](super::StepContext::exec_streaming)
+//! report output chunks as the guest produces them; other execs report
+//! their output as one chunk when they finish.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Progress of a workflow run; see [`WorkflowEvents`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkflowEvent {
+    /// A step began running.
+    StepStarted {
+        /// Step name
+        step: String,
+    },
+    /// Output from one of the step's execs.
+    StepOutputChunk {
+        /// Step name
+        step: String,
+        /// `"stdout"` or `"stderr"`
+        stream: String,
+        /// The chunk, decoded as UTF-8 (lossily)
+        data: String,
+    },
+    /// A step did not run because a dependency failed.
+    StepSkipped {
+        /// Step name
+        step: String,
+        /// Why it was skipped
+        reason: String,
+    },
+    /// A step finished, retries included.
+    StepFinished {
+        /// Step name
+        step: String,
+        /// 0 on success
+        exit_code: i32,
+        /// Time the step took in milliseconds
+        duration_ms: u64,
+        /// Why the step failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// The run is over; always the last event.
+    WorkflowFinished {
+        /// 0 on success
+        exit_code: i32,
+        /// Output of the workflow's output step, decoded as UTF-8 (lossily)
+        output: String,
+        /// Time the run took in milliseconds
+        duration_ms: u64,
+        /// Why the run could not complete, e.g. an invalid step graph
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Send `event` if anyone is listening.
+pub(crate) fn emit(events: &Option<UnboundedSender<WorkflowEvent>>, event: WorkflowEvent) {
+    if let Some(tx) = events {
+        let _ = tx.send(event);
+    }
+}
+
+/// Events of a running workflow, ending with
+/// [`WorkflowEvent::WorkflowFinished`]; see
+/// [`ObservableWorkflow::run_with_events`](super::ObservableWorkflow::run_with_events).
+///
+/// Dropping the stream cancels the run.
+pub struct WorkflowEvents {
+    pub(crate) events: UnboundedReceiver<WorkflowEvent>,
+    pub(crate) run: JoinHandle<()>,
+}
+
+impl Stream for WorkflowEvents {
+    type Item = WorkflowEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WorkflowEvent>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Drop for WorkflowEvents {
+    fn drop(&mut self) {
+        self.run.abort();
+    }
+}
@@ -0,0 +1,303 @@
+//! Workflow fan-out over a dataset
+//!
+//! [`Workflow::map_over`] runs the same step graph once per input item —
+//! "run this agent on 500 GitHub issues" — across a pool of sandboxes. Each
+//! sandbox runs one item at a time, so the pool size bounds concurrency.
+//! Steps read their item through [`StepContext::item`](super::StepContext::item).
+//! A failed item is recorded and the remaining items keep running.
+//!
+//! ```no_run
+//! use void_box::sandbox::{Sandbox, SandboxFleet};
+//! use void_box::workflow::Workflow;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let workflow = Workflow::define("triage")
+//!     .step("label", |ctx| async move {
+//!         let issue = ctx.item().unwrap_or_default().to_vec();
+//!         ctx.exec_with_stdin("triage-issue", &[], &issue).await
+//!     })
+//!     .build();
+//!
+//! let pool = SandboxFleet::start((0..8).map(|_| Sandbox::mock()), 4)?
+//!     .collect_report()
+//!     .await;
+//! let pool = pool.ready.iter().map(|(_, sandbox)| sandbox.clone());
+//!
+//! let issues = vec![r#"{"id":1}"#, r#"{"id":2}"#, r#"{"id":3}"#];
+//! let result = workflow.map_over(issues).run_in(pool).await?;
+//! println!("{:?}", result.metrics());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::definition::Workflow;
+use super::scheduler::Scheduler;
+use super::WorkflowResult;
+use crate::observe::{ObserveConfig, Observer};
+use crate::sandbox::Sandbox;
+use crate::{Error, Result};
+
+impl Workflow {
+    /// Run this workflow once per item in `items`; see [`WorkflowMap`].
+    pub fn map_over<I>(self, items: I) -> WorkflowMap
+    where
+        I: IntoIterator,
+        I::Item: Into<Vec<u8>>,
+    {
+        WorkflowMap {
+            workflow: self,
+            items: items
+                .into_iter()
+                .map(|item| Arc::from(item.into()))
+                .collect(),
+            observer: Observer::new(ObserveConfig::default()),
+        }
+    }
+}
+
+/// A workflow mapped over a dataset, ready to run
+pub struct WorkflowMap {
+    workflow: Workflow,
+    items: Vec<Arc<[u8]>>,
+    observer: Observer,
+}
+
+impl WorkflowMap {
+    /// Record the item runs with this observability configuration
+    pub fn observe(mut self, config: ObserveConfig) -> Self {
+        self.observer = Observer::new(config);
+        self
+    }
+
+    /// Get the observer for inspection
+    pub fn observer(&self) -> &Observer {
+        &self.observer
+    }
+
+    /// Run every item, handing the next pending item to whichever sandbox
+    /// of `sandboxes` frees up first.
+    ///
+    /// Items run one after another in the same sandbox share its
+    /// filesystem; steps that leave state behind should clean it up.
+    pub async fn run_in(
+        self,
+        sandboxes: impl IntoIterator<Item = Arc<Sandbox>>,
+    ) -> Result<MapResult> {
+        let sandboxes: Vec<Arc<Sandbox>> = sandboxes.into_iter().collect();
+        if sandboxes.is_empty() {
+            return Err(Error::Config(format!(
+                "workflow '{}' was mapped over an empty sandbox pool",
+                self.workflow.name
+            )));
+        }
+
+        let started = Instant::now();
+        let next = AtomicUsize::new(0);
+        self.observer.logger().info(
+            &format!(
+                "[workflow:{}] mapping over {} items in {} sandboxes",
+                self.workflow.name,
+                self.items.len(),
+                sandboxes.len()
+            ),
+            &[],
+        );
+
+        let workers = sandboxes
+            .iter()
+            .enumerate()
+            .map(|(slot, sandbox)| self.worker(slot, sandbox, &next));
+        let mut items: Vec<MapItemResult> = futures_util::future::join_all(workers)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        items.sort_by_key(|item| item.index);
+
+        Ok(MapResult {
+            items,
+            sandboxes: sandboxes.len(),
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Run items in one sandbox until none are left.
+    async fn worker(
+        &self,
+        slot: usize,
+        sandbox: &Arc<Sandbox>,
+        next: &AtomicUsize,
+    ) -> Vec<MapItemResult> {
+        let mut results = Vec::new();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = self.items.get(index) else {
+                return results;
+            };
+            let started = Instant::now();
+            let result = Scheduler::new(self.observer.clone(), None)
+                .with_item(item.clone())
+                .execute(&self.workflow, sandbox.clone())
+                .await;
+            let duration = started.elapsed();
+            let outcome = match &result {
+                Ok(run) if run.success() => "ok".to_string(),
+                Ok(run) => format!("exit code {}", run.exit_code),
+                Err(e) => e.to_string(),
+            };
+            self.observer.logger().info(
+                &format!(
+                    "[workflow:{}] item {}/{}: {} ({:.1}s)",
+                    self.workflow.name,
+                    index + 1,
+                    self.items.len(),
+                    outcome,
+                    duration.as_secs_f64()
+                ),
+                &[("item", &index.to_string())],
+            );
+            results.push(MapItemResult {
+                index,
+                sandbox: slot,
+                result,
+                duration,
+            });
+        }
+    }
+}
+
+/// Outcome of one item of a [`WorkflowMap`]
+pub struct MapItemResult {
+    /// Position of the item in the mapped-over dataset
+    pub index: usize,
+    /// Position in the pool of the sandbox that ran the item
+    pub sandbox: usize,
+    /// The workflow run for this item
+    pub result: Result<WorkflowResult>,
+    /// Wall-clock time of the run
+    pub duration: Duration,
+}
+
+impl MapItemResult {
+    /// Whether the workflow ran and every step succeeded
+    pub fn success(&self) -> bool {
+        matches!(&self.result, Ok(run) if run.success())
+    }
+}
+
+/// Per-item results of a [`WorkflowMap`], in dataset order
+pub struct MapResult {
+    /// One entry per item
+    pub items: Vec<MapItemResult>,
+    /// Size of the sandbox pool
+    pub sandboxes: usize,
+    /// Wall-clock time of the whole map
+    pub duration: Duration,
+}
+
+impl MapResult {
+    /// Whether every item succeeded
+    pub fn success(&self) -> bool {
+        self.items.iter().all(MapItemResult::success)
+    }
+
+    /// Items that failed
+    pub fn failures(&self) -> impl Iterator<Item = &MapItemResult> {
+        self.items.iter().filter(|item| !item.success())
+    }
+
+    /// Aggregate counts and item latencies
+    pub fn metrics(&self) -> MapMetrics {
+        let mut durations: Vec<Duration> = self.items.iter().map(|item| item.duration).collect();
+        durations.sort();
+        let percentile = |q: f64| {
+            let rank = ((durations.len() as f64 * q).ceil() as usize).max(1);
+            durations
+                .get(rank - 1)
+                .map_or(0, |duration| duration.as_millis() as u64)
+        };
+        let succeeded = self.items.iter().filter(|item| item.success()).count();
+        MapMetrics {
+            items: self.items.len(),
+            succeeded,
+            failed: self.items.len() - succeeded,
+            sandboxes: self.sandboxes,
+            duration_ms: self.duration.as_millis() as u64,
+            item_p50_ms: percentile(0.5),
+            item_p95_ms: percentile(0.95),
+            item_max_ms: durations.last().map_or(0, |d| d.as_millis() as u64),
+        }
+    }
+}
+
+/// Aggregate metrics of a [`MapResult`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MapMetrics {
+    /// Items in the dataset
+    pub items: usize,
+    /// Items whose workflow run succeeded
+    pub succeeded: usize,
+    /// Items whose workflow run failed or returned a non-zero exit code
+    pub failed: usize,
+    /// Size of the sandbox pool
+    pub sandboxes: usize,
+    /// Wall-clock time of the whole map in milliseconds
+    pub duration_ms: u64,
+    /// Median item run time in milliseconds
+    pub item_p50_ms: u64,
+    /// 95th-percentile item run time in milliseconds
+    pub item_p95_ms: u64,
+    /// Slowest item run time in milliseconds
+    pub item_max_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_map_over_runs_each_item_and_keeps_going_after_failures() {
+        let workflow = Workflow::define("per-item")
+            .step("check", |ctx| async move {
+                let item = ctx.item().unwrap().to_vec();
+                if item == b"bad" {
+                    return Err(Error::Config("bad item".into()));
+                }
+                Ok(item)
+            })
+            .build();
+        let pool = vec![
+            Sandbox::mock().build().unwrap(),
+            Sandbox::mock().build().unwrap(),
+        ];
+
+        let result = workflow
+            .map_over(["a", "bad", "c", "d"])
+            .observe(ObserveConfig::test())
+            .run_in(pool)
+            .await
+            .unwrap();
+
+        let indices: Vec<usize> = result.items.iter().map(|item| item.index).collect();
+        assert_eq!(indices, [0, 1, 2, 3]);
+        assert_eq!(result.items[2].result.as_ref().unwrap().output, b"c");
+        assert!(!result.success());
+        assert_eq!(
+            result.failures().map(|item| item.index).collect::<Vec<_>>(),
+            [1]
+        );
+        let metrics = result.metrics();
+        assert_eq!(
+            (metrics.items, metrics.succeeded, metrics.failed),
+            (4, 3, 1)
+        );
+        assert_eq!(metrics.sandboxes, 2);
+
+        let empty = Workflow::define("empty").build().map_over(["x"]);
+        assert!(empty.run_in(Vec::new()).await.is_err());
+    }
+}
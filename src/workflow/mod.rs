@@ -36,22 +36,35 @@
 pub mod composition;
 pub mod context;
 pub mod definition;
+pub mod events;
+pub mod map;
+pub mod replace;
+pub mod retention;
 pub mod scheduler;
+pub mod script;
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
 use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 pub use composition::{CompositionOp, Pipeline};
 pub use context::{StepContext, StepOutput};
 pub use definition::{Step, StepFn, Workflow, WorkflowBuilder};
+pub use events::{WorkflowEvent, WorkflowEvents};
+pub use map::{MapItemResult, MapMetrics, MapResult, WorkflowMap};
+pub use replace::{ReplaceSandboxPolicy, SandboxFactory};
+pub use retention::OutputRetention;
 pub use scheduler::{ExecutionPlan, Scheduler};
 
-use crate::observe::{ObserveConfig, ObservedResult, Observer};
+use crate::observe::provenance::RunKind;
+use crate::observe::{ObserveConfig, ObservedResult, Observer, RunManifest};
 use crate::persistence::RunEvent;
 use crate::sandbox::Sandbox;
-use crate::Result;
+use crate::{Error, Result};
 
 /// Result of executing a workflow
 #[derive(Debug, Clone)]
@@ -64,6 +77,11 @@ pub struct WorkflowResult {
     pub step_outputs: HashMap<String, StepOutput>,
     /// Total execution duration in milliseconds
     pub duration_ms: u64,
+    /// The run was cancelled before every step succeeded; `step_outputs`
+    /// holds what finished before that
+    pub cancelled: bool,
+    /// Sandboxes replaced after infrastructure failures; see [`replace`]
+    pub sandbox_replacements: u32,
 }
 
 impl WorkflowResult {
@@ -81,6 +99,18 @@ impl WorkflowResult {
     pub fn step_output(&self, name: &str) -> Option<&StepOutput> {
         self.step_outputs.get(name)
     }
+
+    /// Test cases reported by every step, ordered by step name
+    pub fn test_report(&self) -> Option<crate::test_report::TestReport> {
+        let mut steps: Vec<_> = self.step_outputs.iter().collect();
+        steps.sort_by_key(|(name, _)| *name);
+        let cases = steps
+            .into_iter()
+            .filter_map(|(_, output)| output.test_report.as_ref())
+            .flat_map(|report| report.cases.iter().cloned())
+            .collect();
+        crate::test_report::TestReport::from_cases(cases)
+    }
 }
 
 /// A workflow that can be observed and executed
@@ -88,6 +118,9 @@ pub struct ObservableWorkflow {
     workflow: Workflow,
     observer: Observer,
     stage_tx: Option<UnboundedSender<RunEvent>>,
+    cancel: Option<CancellationToken>,
+    replace: Option<ReplaceSandboxPolicy>,
+    retention: OutputRetention,
 }
 
 impl ObservableWorkflow {
@@ -97,15 +130,129 @@ impl ObservableWorkflow {
             workflow,
             observer: Observer::new(config),
             stage_tx: None,
+            cancel: None,
+            replace: None,
+            retention: OutputRetention::default(),
         }
     }
 
+    /// Stop the run when `token` is cancelled, e.g. by a budget monitor;
+    /// see [`WorkflowHandle::cancel`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Re-run steps that fail on broken infrastructure (a dead VM, a lost
+    /// connection) on a new sandbox booted by `policy`; see [`replace`].
+    pub fn replace_sandbox(mut self, policy: ReplaceSandboxPolicy) -> Self {
+        self.replace = Some(policy);
+        self
+    }
+
+    /// Keep step outputs on disk rather than in memory as `policy` says;
+    /// see [`retention`].
+    pub fn with_output_retention(mut self, policy: OutputRetention) -> Self {
+        self.retention = policy;
+        self
+    }
+
+    fn scheduler(&self) -> Scheduler {
+        let mut scheduler = Scheduler::new(self.observer.clone(), self.stage_tx.clone())
+            .with_output_retention(self.retention.clone());
+        if let Some(cancel) = &self.cancel {
+            scheduler = scheduler.with_cancellation(cancel.clone());
+        }
+        if let Some(policy) = &self.replace {
+            scheduler = scheduler.with_replacement(policy.clone());
+        }
+        scheduler
+    }
+
     /// Run the workflow in a sandbox
     pub async fn run_in(self, sandbox: Arc<Sandbox>) -> Result<ObservedResult<WorkflowResult>> {
-        let scheduler = Scheduler::new(self.observer.clone(), self.stage_tx);
-        let result = scheduler.execute(&self.workflow, sandbox).await?;
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        let result = self
+            .scheduler()
+            .execute(&self.workflow, sandbox.clone())
+            .await?;
+
+        let mut manifest = RunManifest::new(
+            RunKind::Workflow,
+            &self.workflow.name,
+            started_at,
+            started.elapsed(),
+        );
+        manifest.record_spans(&self.observer.get_traces(), "step:");
+        match sandbox.manifest() {
+            Ok(sandbox) => manifest.add_sandbox(sandbox),
+            Err(e) => tracing::warn!("Run manifest is missing the sandbox: {}", e),
+        }
+        Ok(ObservedResult::new(result, &self.observer).with_manifest(manifest, &self.observer))
+    }
 
-        Ok(ObservedResult::new(result, &self.observer))
+    /// Run the workflow in a sandbox in the background, returning a
+    /// handle that can cancel it. Must be called within a Tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use void_box::observe::ObserveConfig;
+    /// use void_box::sandbox::Sandbox;
+    /// use void_box::workflow::{Workflow, WorkflowExt};
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let workflow = Workflow::define("ci")
+    ///     .step("test", |ctx| async move { ctx.exec("make", &["test"]).await })
+    ///     .build();
+    /// let handle = workflow
+    ///     .observe(ObserveConfig::test())
+    ///     .start(Sandbox::local().from_env()?.build()?);
+    /// tokio::time::sleep(Duration::from_secs(60)).await;
+    /// handle.cancel();
+    /// let observed = handle.wait().await?;
+    /// assert!(observed.result.cancelled);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start(mut self, sandbox: Arc<Sandbox>) -> WorkflowHandle {
+        let cancel = self
+            .cancel
+            .get_or_insert_with(CancellationToken::new)
+            .clone();
+        WorkflowHandle {
+            cancel,
+            run: tokio::spawn(self.run_in(sandbox)),
+        }
+    }
+
+    /// Run the workflow in a sandbox in the background, yielding
+    /// [`WorkflowEvent`]s as steps start, produce output and finish; see
+    /// [`events`]. Must be called within a Tokio runtime.
+    pub fn run_with_events(self, sandbox: Arc<Sandbox>) -> WorkflowEvents {
+        let (tx, events) = tokio::sync::mpsc::unbounded_channel();
+        let run = tokio::spawn(async move {
+            let started = Instant::now();
+            let scheduler = self.scheduler().with_events(tx.clone());
+            let finished = match scheduler.execute(&self.workflow, sandbox).await {
+                Ok(result) => WorkflowEvent::WorkflowFinished {
+                    exit_code: result.exit_code,
+                    output: result.output_str(),
+                    duration_ms: result.duration_ms,
+                    error: None,
+                },
+                Err(e) => WorkflowEvent::WorkflowFinished {
+                    exit_code: 1,
+                    output: String::new(),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    error: Some(e.to_string()),
+                },
+            };
+            let _ = tx.send(finished);
+        });
+        WorkflowEvents { events, run }
     }
 
     /// Get the observer for inspection
@@ -114,6 +261,43 @@ impl ObservableWorkflow {
     }
 }
 
+/// A workflow running in the background; see [`ObservableWorkflow::start`].
+///
+/// Dropping the handle leaves the run going.
+pub struct WorkflowHandle {
+    cancel: CancellationToken,
+    run: JoinHandle<Result<ObservedResult<WorkflowResult>>>,
+}
+
+impl WorkflowHandle {
+    /// Stop the run: the running steps' guest processes are killed, they
+    /// fail with [`Error::Cancelled`](crate::Error::Cancelled), and the
+    /// steps that have not started are skipped. [`wait`](Self::wait)
+    /// then returns the partial result with
+    /// [`cancelled`](WorkflowResult::cancelled) set.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// The token [`cancel`](Self::cancel) fires, for cancelling from
+    /// elsewhere or for linking to a parent token.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Whether the run is over.
+    pub fn is_finished(&self) -> bool {
+        self.run.is_finished()
+    }
+
+    /// Wait for the run to end.
+    pub async fn wait(self) -> Result<ObservedResult<WorkflowResult>> {
+        self.run
+            .await
+            .map_err(|e| Error::Workflow(format!("workflow task failed: {}", e)))?
+    }
+}
+
 /// Extension trait for workflow to add observability
 pub trait WorkflowExt {
     /// Attach observability to this workflow
@@ -141,6 +325,9 @@ impl WorkflowExt for Workflow {
             workflow: self,
             observer: Observer::new(config),
             stage_tx,
+            cancel: None,
+            replace: None,
+            retention: OutputRetention::default(),
         }
     }
 }
@@ -156,6 +343,8 @@ mod tests {
             exit_code: 0,
             step_outputs: HashMap::new(),
             duration_ms: 100,
+            cancelled: false,
+            sandbox_replacements: 0,
         };
 
         result.step_outputs.insert(
@@ -164,6 +353,9 @@ mod tests {
                 stdout: b"output".to_vec(),
                 stderr: Vec::new(),
                 exit_code: 0,
+                stdout_path: None,
+                stderr_path: None,
+                test_report: None,
             },
         );
 
@@ -172,4 +364,143 @@ mod tests {
         assert!(result.step_output("step1").is_some());
         assert!(result.step_output("missing").is_none());
     }
+
+    #[test]
+    fn test_workflow_result_merges_step_test_reports() {
+        use crate::test_report::{TestCaseResult, TestReport, TestStatus};
+
+        let case = |name: &str, status| TestCaseResult {
+            suite: "suite".into(),
+            name: name.into(),
+            status,
+            duration_ms: None,
+            message: None,
+        };
+        let mut step_outputs = HashMap::new();
+        step_outputs.insert(
+            "unit".to_string(),
+            StepOutput::new(Vec::new(), Vec::new(), 1)
+                .with_test_report(TestReport::from_cases(vec![case("b", TestStatus::Failed)])),
+        );
+        step_outputs.insert(
+            "build".to_string(),
+            StepOutput::new(Vec::new(), Vec::new(), 0),
+        );
+        step_outputs.insert(
+            "lint".to_string(),
+            StepOutput::new(Vec::new(), Vec::new(), 0)
+                .with_test_report(TestReport::from_cases(vec![case("a", TestStatus::Passed)])),
+        );
+        let result = WorkflowResult {
+            output: Vec::new(),
+            exit_code: 1,
+            step_outputs,
+            duration_ms: 0,
+            cancelled: false,
+            sandbox_replacements: 0,
+        };
+
+        let report = result.test_report().unwrap();
+        let names: Vec<_> = report.cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(!report.success());
+    }
+
+    #[tokio::test]
+    async fn test_observed_run_carries_a_manifest() {
+        let workflow = Workflow::define("audited")
+            .step(
+                "greet",
+                |ctx| async move { ctx.exec("echo", &["hi"]).await },
+            )
+            .build();
+        let sandbox = Sandbox::mock().build().unwrap();
+        let observed = workflow
+            .observe(ObserveConfig::test())
+            .run_in(sandbox)
+            .await
+            .unwrap();
+
+        let manifest = observed.manifest().unwrap();
+        assert_eq!(manifest.name, "audited");
+        assert_eq!(manifest.steps[0].name, "greet");
+        assert_eq!(manifest.sandboxes.len(), 1);
+        assert!(manifest.signature.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_the_run_with_partial_results() {
+        let workflow = Workflow::define("cancelled")
+            .step("quick", |_ctx| async { Ok(b"done".to_vec()) })
+            .step_depends("stuck", &["quick"], |_ctx| async {
+                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                Ok(vec![])
+            })
+            .step_depends("after", &["stuck"], |_ctx| async { Ok(vec![]) })
+            .build();
+        let handle = workflow
+            .observe(ObserveConfig::test())
+            .start(Sandbox::mock().build().unwrap());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        handle.cancel();
+        let observed = handle.wait().await.unwrap();
+
+        let result = &observed.result;
+        assert!(result.cancelled);
+        assert!(!result.success());
+        assert_eq!(result.step_outputs["quick"].stdout_str(), "done");
+        assert!(result.step_outputs["stuck"]
+            .stderr_str()
+            .contains("was cancelled"));
+        assert_eq!(
+            result.step_outputs["after"].stderr_str(),
+            "workflow cancelled"
+        );
+        let span = |name: &str| observed.traces().iter().find(|s| s.name == name).unwrap();
+        assert_eq!(span("step:stuck").attributes["cancelled"], "true");
+        assert_eq!(span("workflow:cancelled").attributes["cancelled"], "true");
+    }
+
+    #[tokio::test]
+    async fn test_run_with_events_reports_progress_as_it_happens() {
+        use futures_util::StreamExt;
+
+        let workflow = Workflow::define("events")
+            .step("greet", |ctx| async move {
+                ctx.exec_streaming("echo", &["hi"]).await
+            })
+            .step_depends("check", &["greet"], |ctx| async move {
+                ctx.exec("test", &["-e", "/missing"]).await
+            })
+            .step_depends("report", &["check"], |_ctx| async { Ok(Vec::new()) })
+            .build();
+        let events: Vec<WorkflowEvent> = workflow
+            .observe(ObserveConfig::test())
+            .run_with_events(Sandbox::mock().build().unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(
+            events[..2],
+            [
+                WorkflowEvent::StepStarted {
+                    step: "greet".into()
+                },
+                WorkflowEvent::StepOutputChunk {
+                    step: "greet".into(),
+                    stream: "stdout".into(),
+                    data: "hi\n".into(),
+                },
+            ]
+        );
+        assert!(matches!(
+            &events[4],
+            WorkflowEvent::StepFinished { step, exit_code: 1, error: Some(_), .. } if step == "check"
+        ));
+        assert!(matches!(&events[5], WorkflowEvent::StepSkipped { step, .. } if step == "report"));
+        let last = serde_json::to_value(events.last().unwrap()).unwrap();
+        assert_eq!(last["type"], "workflow_finished");
+        assert_eq!(last["exit_code"], 1);
+        assert!(last.get("error").is_none());
+    }
 }
@@ -0,0 +1,215 @@
+//! Replacing a sandbox whose VM failed under a step
+//!
+//! A sandbox's [`RestartPolicy`](crate::sandbox::RestartPolicy) reboots its
+//! VM in place. When that is not enough (no restarts left, or the failure
+//! was in the connection rather than the guest), a [`ReplaceSandboxPolicy`]
+//! lets the scheduler boot a new sandbox, restore `/workspace` from the last
+//! checkpoint and re-run the failed step there. Later steps run on the
+//! replacement too.
+//!
+//! Only failures in [`FailureDomain::Infrastructure`] lead to a
+//! replacement: a step whose command failed fails as before. Checkpoints
+//! are [`Sandbox::export_workspace`] bundles taken after every group of
+//! steps that all succeeded, so a replacement starts from the workspace
+//! the last completed steps left behind.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use void_box::observe::ObserveConfig;
+//! use void_box::sandbox::Sandbox;
+//! use void_box::workflow::{ReplaceSandboxPolicy, Workflow, WorkflowExt};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let boot = || {
+//!     Sandbox::local()
+//!         .from_env()
+//!         .map_err(|e| void_box::Error::Config(e.to_string()))?
+//!         .build()
+//! };
+//! let policy = ReplaceSandboxPolicy::new(2, boot);
+//! let workflow = Workflow::define("ci")
+//!     .step("test", |ctx| async move { ctx.exec("make", &["test"]).await })
+//!     .build();
+//! let observed = workflow
+//!     .observe(ObserveConfig::test())
+//!     .replace_sandbox(policy.clone())
+//!     .run_in(boot()?)
+//!     .await?;
+//! if let Some(replacement) = policy.latest_sandbox() {
+//!     replacement.stop().await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::sandbox::{ArtifactBundle, Sandbox};
+use crate::{Error, FailureDomain, Result};
+
+/// Boots a fresh sandbox for [`ReplaceSandboxPolicy`].
+pub type SandboxFactory = Arc<dyn Fn() -> Result<Arc<Sandbox>> + Send + Sync>;
+
+/// When and how a workflow run replaces a failed sandbox; see the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct ReplaceSandboxPolicy {
+    max_replacements: u32,
+    factory: SandboxFactory,
+    checkpoint: bool,
+    latest: Arc<Mutex<Option<Arc<Sandbox>>>>,
+}
+
+impl std::fmt::Debug for ReplaceSandboxPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplaceSandboxPolicy")
+            .field("max_replacements", &self.max_replacements)
+            .field("checkpoint", &self.checkpoint)
+            .finish()
+    }
+}
+
+impl ReplaceSandboxPolicy {
+    /// Replace the sandbox up to `max_replacements` times per run, booting
+    /// each replacement with `factory`.
+    pub fn new<F>(max_replacements: u32, factory: F) -> Self
+    where
+        F: Fn() -> Result<Arc<Sandbox>> + Send + Sync + 'static,
+    {
+        Self {
+            max_replacements,
+            factory: Arc::new(factory),
+            checkpoint: true,
+            latest: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether to checkpoint `/workspace` after each group of steps and
+    /// restore it into replacements (default: on). Turn it off for
+    /// workflows that keep no state in the workspace, to save the exports.
+    pub fn checkpoint_workspace(mut self, enabled: bool) -> Self {
+        self.checkpoint = enabled;
+        self
+    }
+
+    /// Replacements allowed per run.
+    pub fn max_replacements(&self) -> u32 {
+        self.max_replacements
+    }
+
+    /// The last sandbox this policy booted, if any. The run leaves it
+    /// running, with the workspace its steps produced; stopping it is up to
+    /// the caller, as for the sandbox the run started with.
+    pub fn latest_sandbox(&self) -> Option<Arc<Sandbox>> {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+/// The sandbox a run is using, replaced under a [`ReplaceSandboxPolicy`].
+pub(crate) struct SandboxSlot {
+    original: Arc<Sandbox>,
+    current: Mutex<Arc<Sandbox>>,
+    policy: Option<ReplaceSandboxPolicy>,
+    replacements: AtomicU32,
+    /// Held while booting a replacement, so concurrent steps that fail on
+    /// the same sandbox replace it once.
+    replacing: tokio::sync::Mutex<()>,
+    checkpoint: Mutex<Option<ArtifactBundle>>,
+}
+
+impl SandboxSlot {
+    pub(crate) fn new(sandbox: Arc<Sandbox>, policy: Option<ReplaceSandboxPolicy>) -> Arc<Self> {
+        Arc::new(Self {
+            original: sandbox.clone(),
+            current: Mutex::new(sandbox),
+            policy,
+            replacements: AtomicU32::new(0),
+            replacing: tokio::sync::Mutex::new(()),
+            checkpoint: Mutex::new(None),
+        })
+    }
+
+    /// The sandbox steps should run on now.
+    pub(crate) fn current(&self) -> Arc<Sandbox> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Sandboxes replaced so far in this run.
+    pub(crate) fn replacements(&self) -> u32 {
+        self.replacements.load(Ordering::SeqCst)
+    }
+
+    /// Record the current workspace for later replacements, if the policy
+    /// asks for checkpoints. A failed export keeps the previous checkpoint.
+    pub(crate) async fn checkpoint(&self) {
+        if !self.policy.as_ref().is_some_and(|policy| policy.checkpoint) {
+            return;
+        }
+        match self.current().export_workspace(None).await {
+            Ok(bundle) => *self.checkpoint.lock().unwrap() = Some(bundle),
+            Err(e) => tracing::warn!("Failed to checkpoint the workspace: {}", e),
+        }
+    }
+
+    /// After `step` failed with `err` on `failed`, boot a replacement if
+    /// the failure was the infrastructure's and the policy allows one.
+    /// Returns the sandbox to re-run the step on.
+    pub(crate) async fn replace_after(
+        &self,
+        failed: &Arc<Sandbox>,
+        step: &str,
+        err: &Error,
+    ) -> Option<Arc<Sandbox>> {
+        let policy = self.policy.as_ref()?;
+        if err.failure_domain() != FailureDomain::Infrastructure {
+            return None;
+        }
+        let _replacing = self.replacing.lock().await;
+        let current = self.current();
+        if !Arc::ptr_eq(&current, failed) {
+            // Another step already replaced it.
+            return Some(current);
+        }
+        let replacement = self.replacements.load(Ordering::SeqCst) + 1;
+        if replacement > policy.max_replacements {
+            return None;
+        }
+        self.replacements.store(replacement, Ordering::SeqCst);
+        tracing::warn!(
+            "Replacing the sandbox for step \"{}\" ({}/{}): {}",
+            step,
+            replacement,
+            policy.max_replacements,
+            err
+        );
+        let sandbox = match self.boot(policy).await {
+            Ok(sandbox) => sandbox,
+            Err(e) => {
+                tracing::error!("Failed to boot a replacement sandbox: {}", e);
+                return None;
+            }
+        };
+        *self.current.lock().unwrap() = sandbox.clone();
+        *policy.latest.lock().unwrap() = Some(sandbox.clone());
+        if !Arc::ptr_eq(failed, &self.original) {
+            let failed = failed.clone();
+            tokio::spawn(async move {
+                if let Err(e) = failed.stop().await {
+                    tracing::debug!("Failed to stop the replaced sandbox: {}", e);
+                }
+            });
+        }
+        Some(sandbox)
+    }
+
+    async fn boot(&self, policy: &ReplaceSandboxPolicy) -> Result<Arc<Sandbox>> {
+        let sandbox = (policy.factory)()?;
+        sandbox.start().await?;
+        let checkpoint = self.checkpoint.lock().unwrap().clone();
+        if let Some(bundle) = checkpoint {
+            sandbox.restore_workspace(&bundle).await?;
+        }
+        Ok(sandbox)
+    }
+}
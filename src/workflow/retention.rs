@@ -0,0 +1,242 @@
+//! Step output retention
+//!
+//! A [`WorkflowResult`](super::WorkflowResult) keeps every step's stdout and
+//! stderr for as long as it lives. For steps that print build logs or dump
+//! datasets, an [`OutputRetention`] policy moves outputs to files instead:
+//! outputs over a size threshold are spilled, and so is anything that would
+//! push the run's in-memory total over a cap. A spilled output has an empty
+//! buffer and a path in [`StepOutput::stdout_path`] /
+//! [`StepOutput::stderr_path`]; [`StepOutput::stdout_bytes`] and friends
+//! read it back when asked.
+//!
+//! Spill files go under `<spill dir>/<workflow>-<trace id>/` and outlive the
+//! run; deleting them is up to the caller.
+//!
+//! ```no_run
+//! use void_box::observe::ObserveConfig;
+//! use void_box::sandbox::Sandbox;
+//! use void_box::workflow::{OutputRetention, Workflow, WorkflowExt};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let workflow = Workflow::define("ci")
+//!     .step("build", |ctx| async move { ctx.exec("make", &["all"]).await })
+//!     .build();
+//! let observed = workflow
+//!     .observe(ObserveConfig::test())
+//!     .with_output_retention(
+//!         OutputRetention::new()
+//!             .spill_over(1 << 20)
+//!             .max_in_memory_bytes(64 << 20),
+//!     )
+//!     .run_in(Sandbox::local().from_env()?.build()?)
+//!     .await?;
+//! let build = observed.result.step_output("build").unwrap();
+//! if let Some(path) = &build.stdout_path {
+//!     println!("build log spilled to {}", path.display());
+//! }
+//! println!("{}", build.stdout_str());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::context::StepOutput;
+
+/// Where step outputs are kept; see the [module docs](self). The default
+/// keeps everything in memory.
+#[derive(Debug, Clone)]
+pub struct OutputRetention {
+    spill_over: Option<usize>,
+    max_in_memory_bytes: Option<usize>,
+    spill_dir: PathBuf,
+}
+
+impl Default for OutputRetention {
+    fn default() -> Self {
+        Self {
+            spill_over: None,
+            max_in_memory_bytes: None,
+            spill_dir: std::env::temp_dir().join("voidbox-step-outputs"),
+        }
+    }
+}
+
+impl OutputRetention {
+    /// Keep everything in memory until limits are set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spill any stdout or stderr larger than `bytes` to disk
+    pub fn spill_over(mut self, bytes: usize) -> Self {
+        self.spill_over = Some(bytes);
+        self
+    }
+
+    /// Keep at most `bytes` of output in memory across the run's steps;
+    /// outputs that would go over are spilled
+    pub fn max_in_memory_bytes(mut self, bytes: usize) -> Self {
+        self.max_in_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Directory for spill files (default: `voidbox-step-outputs` under the
+    /// system temp directory)
+    pub fn spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+
+    fn spills(&self, len: usize, in_memory: usize) -> bool {
+        len > 0
+            && (self.spill_over.is_some_and(|limit| len > limit)
+                || self
+                    .max_in_memory_bytes
+                    .is_some_and(|limit| in_memory + len > limit))
+    }
+}
+
+/// Applies an [`OutputRetention`] to the outputs of one run.
+pub(crate) struct OutputSpiller {
+    policy: OutputRetention,
+    run_dir: PathBuf,
+    in_memory: AtomicUsize,
+}
+
+impl OutputSpiller {
+    pub(crate) fn new(policy: OutputRetention, workflow: &str, run_id: &str) -> Self {
+        let run_dir =
+            policy
+                .spill_dir
+                .join(format!("{}-{}", file_safe(workflow), file_safe(run_id)));
+        Self {
+            policy,
+            run_dir,
+            in_memory: AtomicUsize::new(0),
+        }
+    }
+
+    /// `output` with whatever the policy moves to disk moved there. A spill
+    /// that fails keeps the output in memory.
+    pub(crate) fn retain(&self, step: &str, mut output: StepOutput) -> StepOutput {
+        if let Some(path) = self.spill(step, "stdout", &output.stdout) {
+            output.stdout = Vec::new();
+            output.stdout_path = Some(path);
+        }
+        if let Some(path) = self.spill(step, "stderr", &output.stderr) {
+            output.stderr = Vec::new();
+            output.stderr_path = Some(path);
+        }
+        output
+    }
+
+    fn spill(&self, step: &str, stream: &str, data: &[u8]) -> Option<PathBuf> {
+        let in_memory = self.in_memory.load(Ordering::SeqCst);
+        if !self.policy.spills(data.len(), in_memory) {
+            self.in_memory.fetch_add(data.len(), Ordering::SeqCst);
+            return None;
+        }
+        let path = self.run_dir.join(format!("{}.{}", file_safe(step), stream));
+        match write_spill(&path, data) {
+            Ok(()) => Some(path),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to spill {} of step \"{}\" to {}: {}",
+                    stream,
+                    step,
+                    path.display(),
+                    e
+                );
+                self.in_memory.fetch_add(data.len(), Ordering::SeqCst);
+                None
+            }
+        }
+    }
+}
+
+fn write_spill(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, data)
+}
+
+/// `name` with everything but ASCII alphanumerics, `-` and `_` replaced,
+/// so step names cannot leave the run directory.
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_outputs_and_outputs_over_the_cap_spill() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = OutputRetention::new()
+            .spill_over(8)
+            .max_in_memory_bytes(10)
+            .spill_dir(dir.path());
+        let spiller = OutputSpiller::new(policy, "ci", "abc");
+
+        let small = spiller.retain("lint", StepOutput::new(b"ok\n".to_vec(), Vec::new(), 0));
+        assert!(!small.is_spilled());
+
+        let large = spiller.retain(
+            "../build",
+            StepOutput::new(b"many lines\n".to_vec(), b"warn\n".to_vec(), 0),
+        );
+        let path = large.stdout_path.clone().unwrap();
+        assert_eq!(path, dir.path().join("ci-abc").join("___build.stdout"));
+        assert!(large.stdout.is_empty());
+        assert_eq!(large.stdout_bytes().unwrap().as_ref(), b"many lines\n");
+        assert_eq!(large.stdout_str(), "many lines\n");
+        assert_eq!(large.stderr_str(), "warn\n");
+        assert!(large.stderr_path.is_none());
+
+        // Small enough on its own, but over the cap with 8 bytes in memory.
+        let capped = spiller.retain("test", StepOutput::new(b"passed\n".to_vec(), Vec::new(), 0));
+        assert!(capped.stdout_path.is_some());
+        assert_eq!(capped.stdout_str(), "passed\n");
+    }
+
+    #[tokio::test]
+    async fn test_later_steps_and_the_result_read_spilled_outputs() {
+        use crate::observe::ObserveConfig;
+        use crate::sandbox::Sandbox;
+        use crate::workflow::{Workflow, WorkflowExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let workflow = Workflow::define("spill")
+            .step("produce", |ctx| async move {
+                ctx.exec("echo", &["a long line of output"]).await
+            })
+            .step_depends("consume", &["produce"], |ctx| async move {
+                let produced = ctx.output("produce").unwrap();
+                assert!(produced.stdout.is_empty());
+                Ok(produced.stdout_bytes()?.to_ascii_uppercase())
+            })
+            .build();
+        let observed = workflow
+            .observe(ObserveConfig::test())
+            .with_output_retention(OutputRetention::new().spill_over(4).spill_dir(dir.path()))
+            .run_in(Sandbox::mock().build().unwrap())
+            .await
+            .unwrap();
+
+        let result = observed.result;
+        assert!(result.step_output("produce").unwrap().is_spilled());
+        assert_eq!(result.output_str(), "A LONG LINE OF OUTPUT\n");
+    }
+}
@@ -5,17 +5,22 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 
 use super::composition::resolve_pipe_input;
 use super::context::{StepContext, StepContextBuilder, StepOutput};
 use super::definition::{Step, Workflow};
+use super::events::{self, WorkflowEvent};
+use super::replace::{ReplaceSandboxPolicy, SandboxSlot};
+use super::retention::{OutputRetention, OutputSpiller};
 use super::WorkflowResult;
-use crate::observe::Observer;
+use crate::observe::slo::{StepOutcome, SLO_VIOLATION_EVENT};
+use crate::observe::{Observer, SloMonitor, SpanContext};
 use crate::persistence::RunEvent;
-use crate::sandbox::Sandbox;
+use crate::sandbox::{ExecSignal, Sandbox};
 use crate::{Error, Result};
 
 /// Execution plan for a workflow
@@ -100,16 +105,75 @@ fn first_failed_dependency_static(
     None
 }
 
+/// Why a step must not run: the workflow was cancelled, or one of its
+/// dependencies failed.
+fn skip_reason(
+    cancel: Option<&CancellationToken>,
+    failed_dependency: Option<String>,
+) -> Option<String> {
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Some("workflow cancelled".to_string());
+    }
+    failed_dependency.map(|dep| format!("dependency \"{}\" failed", dep))
+}
+
 /// Scheduler for executing workflows
 pub struct Scheduler {
     observer: Observer,
     stage_tx: Option<UnboundedSender<RunEvent>>,
+    events: Option<UnboundedSender<WorkflowEvent>>,
+    cancel: Option<CancellationToken>,
+    replace: Option<ReplaceSandboxPolicy>,
+    retention: OutputRetention,
+    item: Option<Arc<[u8]>>,
 }
 
 impl Scheduler {
     /// Create a new scheduler
     pub fn new(observer: Observer, stage_tx: Option<UnboundedSender<RunEvent>>) -> Self {
-        Self { observer, stage_tx }
+        Self {
+            observer,
+            stage_tx,
+            events: None,
+            cancel: None,
+            replace: None,
+            retention: OutputRetention::default(),
+            item: None,
+        }
+    }
+
+    /// Spill step outputs to disk as `policy` says; see
+    /// [`super::retention`].
+    pub fn with_output_retention(mut self, policy: OutputRetention) -> Self {
+        self.retention = policy;
+        self
+    }
+
+    /// Re-run steps that fail on broken infrastructure on a replacement
+    /// sandbox; see [`super::replace`].
+    pub fn with_replacement(mut self, policy: ReplaceSandboxPolicy) -> Self {
+        self.replace = Some(policy);
+        self
+    }
+
+    /// Stop the run when `token` is cancelled: the running steps' guest
+    /// processes are killed and the remaining steps are skipped.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Report progress as [`WorkflowEvent`]s, except the final
+    /// [`WorkflowEvent::WorkflowFinished`], which is up to the caller.
+    pub fn with_events(mut self, events: UnboundedSender<WorkflowEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Hand every step `item` through [`StepContext::item`](super::StepContext::item).
+    pub fn with_item(mut self, item: Arc<[u8]>) -> Self {
+        self.item = Some(item);
+        self
     }
 
     /// Helper to emit a stage event via the channel (fire-and-forget).
@@ -131,13 +195,24 @@ impl Scheduler {
         sandbox: Arc<Sandbox>,
     ) -> Result<WorkflowResult> {
         let start_time = Instant::now();
+        let deadline = workflow.timeout.map(|limit| start_time + limit);
 
         // Start workflow span
-        let workflow_span = self.observer.start_workflow_span(&workflow.name);
+        let mut workflow_span = self.observer.start_workflow_span(&workflow.name);
         let workflow_ctx = workflow_span.context();
+        let slo_monitor = workflow
+            .slo
+            .as_ref()
+            .map(|policy| SloMonitor::new(&workflow.name, &workflow_ctx.trace_id, policy.clone()));
 
         // Get execution plan (with parallel groups)
         let plan = ExecutionPlan::from_workflow(workflow)?;
+        let slot = SandboxSlot::new(sandbox, self.replace.clone());
+        let spiller = OutputSpiller::new(
+            self.retention.clone(),
+            &workflow.name,
+            &workflow_ctx.trace_id,
+        );
 
         // Build step -> group_id mapping from plan
         let mut step_group_id: HashMap<String, String> = HashMap::new();
@@ -196,8 +271,10 @@ impl Scheduler {
 
                 // Check dependency health — skip if any dependency failed
                 let outputs_snapshot = step_outputs.read().await.clone();
-                if let Some(failed_dep) = first_failed_dependency(step, &outputs_snapshot) {
-                    let skip_msg = format!("dependency \"{}\" failed", failed_dep);
+                if let Some(skip_msg) = skip_reason(
+                    self.cancel.as_ref(),
+                    first_failed_dependency(step, &outputs_snapshot),
+                ) {
                     let step_output = StepOutput::new(Vec::new(), skip_msg.as_bytes().to_vec(), 1);
                     step_outputs
                         .write()
@@ -208,6 +285,13 @@ impl Scheduler {
                     self.emit(crate::persistence::stage_event_skipped(
                         step_name, None, &gid, &skip_msg, 1,
                     ));
+                    events::emit(
+                        &self.events,
+                        WorkflowEvent::StepSkipped {
+                            step: step_name.clone(),
+                            reason: skip_msg.clone(),
+                        },
+                    );
                     self.observer.logger().info(
                         &format!(
                             "[workflow:{}] step {}/{}: \"{}\" SKIPPED ({})",
@@ -222,10 +306,19 @@ impl Scheduler {
                 self.emit(crate::persistence::stage_event_started(
                     step_name, None, &gid, 1,
                 ));
+                events::emit(
+                    &self.events,
+                    WorkflowEvent::StepStarted {
+                        step: step_name.clone(),
+                    },
+                );
 
-                let mut ctx_builder = StepContextBuilder::new(step_name, sandbox.clone())
+                let mut ctx_builder = StepContextBuilder::new(step_name, slot.current())
                     .with_outputs(outputs_snapshot.clone())
-                    .with_timeout(step.timeout_secs);
+                    .with_item(self.item.clone())
+                    .with_timeout(step.timeout_secs)
+                    .with_events(self.events.clone())
+                    .with_cancellation(self.cancel.clone());
 
                 if let Some(input) =
                     resolve_pipe_input(step_name, &workflow.compositions, &outputs_snapshot)
@@ -234,24 +327,60 @@ impl Scheduler {
                 }
 
                 let ctx = ctx_builder.build();
+                let step_ctx = step_span.context();
                 let func = step.func.clone();
-                let result = if let Some(ref retry_config) = step.retry {
-                    self.execute_with_retry(func.clone(), ctx.clone(), retry_config.max_attempts)
-                        .await
-                } else {
-                    func(ctx).await
-                };
+                // Execs issued by the step are children of its span and hand
+                // the guest a TRACEPARENT chained to it.
+                let step_run = step_ctx.clone().scope(async {
+                    let mut ctx = ctx.clone();
+                    loop {
+                        let result = if let Some(ref retry_config) = step.retry {
+                            self.execute_with_retry(
+                                func.clone(),
+                                ctx.clone(),
+                                retry_config.max_attempts,
+                            )
+                            .await
+                        } else {
+                            func(ctx.clone()).await
+                        };
+                        match result {
+                            Err(e) => match recover_step(&slot, &ctx, step_name, &e).await {
+                                Some(next) => ctx = next,
+                                None => break Err(e),
+                            },
+                            ok => break ok,
+                        }
+                    }
+                });
+                let result = run_with_deadline(
+                    &slot,
+                    step_name,
+                    step_limit(step.deadline, deadline),
+                    self.cancel.as_ref(),
+                    crate::guest::protocol::maybe_scope_exec_user(step.user.clone(), step_run),
+                )
+                .await;
 
                 match result {
                     Ok(output) => {
                         let elapsed = step_start.elapsed();
-                        let step_output = StepOutput::new(output.clone(), Vec::new(), 0);
+                        let step_output = StepOutput::new(output.clone(), Vec::new(), 0)
+                            .with_test_report(ctx.test_report());
                         step_span.record_stdout(output.len());
                         step_outputs
                             .write()
                             .await
-                            .insert(step_name.clone(), step_output);
+                            .insert(step_name.clone(), spiller.retain(step_name, step_output));
                         step_span.set_ok();
+                        self.record_slo_step(
+                            slo_monitor.as_ref(),
+                            step_name,
+                            &step_ctx,
+                            elapsed,
+                            true,
+                        )
+                        .await;
                         // Emit StageSucceeded
                         self.emit(crate::persistence::stage_event_succeeded(
                             step_name,
@@ -261,6 +390,7 @@ impl Scheduler {
                             0,
                             1,
                         ));
+                        events::emit(&self.events, step_finished(step_name, elapsed, None));
                         self.observer.logger().info(
                             &format!(
                                 "[workflow:{}] step {}/{}: \"{}\" ok ({:.1}s)",
@@ -280,13 +410,23 @@ impl Scheduler {
                         let elapsed = step_start.elapsed();
                         let error_msg = e.to_string();
                         let step_output =
-                            StepOutput::new(Vec::new(), error_msg.as_bytes().to_vec(), 1);
+                            StepOutput::new(Vec::new(), error_msg.as_bytes().to_vec(), 1)
+                                .with_test_report(ctx.test_report());
                         step_span.record_stderr(error_msg.len());
+                        mark_stopped(&mut step_span, &e);
                         step_outputs
                             .write()
                             .await
-                            .insert(step_name.clone(), step_output);
+                            .insert(step_name.clone(), spiller.retain(step_name, step_output));
                         step_span.set_error(&error_msg);
+                        self.record_slo_step(
+                            slo_monitor.as_ref(),
+                            step_name,
+                            &step_ctx,
+                            elapsed,
+                            false,
+                        )
+                        .await;
                         // Emit StageFailed
                         self.emit(crate::persistence::stage_event_failed(
                             step_name,
@@ -297,6 +437,10 @@ impl Scheduler {
                             &error_msg,
                             1,
                         ));
+                        events::emit(
+                            &self.events,
+                            step_finished(step_name, elapsed, Some(&error_msg)),
+                        );
                         self.observer.logger().error(
                             &format!(
                                 "[workflow:{}] step {}/{}: \"{}\" FAILED ({:.1}s): {}",
@@ -342,12 +486,17 @@ impl Scheduler {
                     let func = step.func.clone();
                     let retry = step.retry.clone();
                     let step_timeout = step.timeout_secs;
+                    let step_user = step.user.clone();
+                    let step_deadline = step.deadline;
                     let depends_on_list = step.depends_on.clone();
-                    let sb = sandbox.clone();
+                    let slot = slot.clone();
                     let compositions = workflow.compositions.clone();
                     let outputs_snap = outputs_snapshot.clone();
                     let observer = self.observer.clone();
                     let stx = self.stage_tx.clone();
+                    let events = self.events.clone();
+                    let cancel = self.cancel.clone();
+                    let item = self.item.clone();
                     let wf_ctx = workflow_ctx.clone();
                     let wf_name = workflow_name.clone();
 
@@ -355,10 +504,10 @@ impl Scheduler {
                         let mut step_span = observer.start_step_span(&name, Some(&wf_ctx));
 
                         // Check dependency health
-                        if let Some(failed_dep) =
-                            first_failed_dependency_static(&depends_on_list, &outputs_snap)
-                        {
-                            let skip_msg = format!("dependency \"{}\" failed", failed_dep);
+                        if let Some(skip_msg) = skip_reason(
+                            cancel.as_ref(),
+                            first_failed_dependency_static(&depends_on_list, &outputs_snap),
+                        ) {
                             step_span.set_error(&skip_msg);
                             // Emit StageSkipped
                             if let Some(ref tx) = stx {
@@ -366,6 +515,13 @@ impl Scheduler {
                                     &name, None, &gid, &skip_msg, 1,
                                 ));
                             }
+                            events::emit(
+                                &events,
+                                WorkflowEvent::StepSkipped {
+                                    step: name.clone(),
+                                    reason: skip_msg.clone(),
+                                },
+                            );
                             observer.logger().info(
                                 &format!(
                                     "[workflow:{}] step \"{}\" SKIPPED ({})",
@@ -376,6 +532,7 @@ impl Scheduler {
                             return (
                                 name,
                                 StepOutput::new(Vec::new(), skip_msg.as_bytes().to_vec(), 1),
+                                None,
                             );
                         }
 
@@ -385,12 +542,16 @@ impl Scheduler {
                                 &name, None, &gid, 1,
                             ));
                         }
+                        events::emit(&events, WorkflowEvent::StepStarted { step: name.clone() });
 
                         let step_start = Instant::now();
 
-                        let mut ctx_builder = StepContextBuilder::new(&name, sb)
+                        let mut ctx_builder = StepContextBuilder::new(&name, slot.current())
                             .with_outputs(outputs_snap.clone())
-                            .with_timeout(step_timeout);
+                            .with_item(item)
+                            .with_timeout(step_timeout)
+                            .with_events(events.clone())
+                            .with_cancellation(cancel.clone());
 
                         if let Some(input) = resolve_pipe_input(&name, &compositions, &outputs_snap)
                         {
@@ -398,39 +559,70 @@ impl Scheduler {
                         }
 
                         let ctx = ctx_builder.build();
-                        let result = if let Some(ref retry_config) = retry {
-                            // Inline retry logic since we can't call &self methods
-                            let mut last_error = None;
-                            let mut res = Err(Error::Guest("Unknown error".into()));
-                            for attempt in 0..retry_config.max_attempts {
-                                match func(ctx.clone()).await {
-                                    Ok(r) => {
-                                        res = Ok(r);
-                                        last_error = None;
-                                        break;
-                                    }
-                                    Err(e) => {
-                                        last_error = Some(e);
-                                        if attempt + 1 < retry_config.max_attempts {
-                                            tokio::time::sleep(tokio::time::Duration::from_millis(
-                                                100 * (attempt as u64 + 1),
-                                            ))
-                                            .await;
+                        let step_ctx = step_span.context();
+                        let step_attempt = |ctx: StepContext| {
+                            let (func, retry) = (&func, &retry);
+                            async move {
+                                if let Some(ref retry_config) = retry {
+                                    // Inline retry logic since we can't call &self methods
+                                    let mut last_error = None;
+                                    let mut res = Err(Error::Guest("Unknown error".into()));
+                                    for attempt in 0..retry_config.max_attempts {
+                                        match func(ctx.clone()).await {
+                                            Ok(r) => {
+                                                res = Ok(r);
+                                                last_error = None;
+                                                break;
+                                            }
+                                            Err(e) => {
+                                                last_error = Some(e);
+                                                if attempt + 1 < retry_config.max_attempts {
+                                                    tokio::time::sleep(
+                                                        tokio::time::Duration::from_millis(
+                                                            100 * (attempt as u64 + 1),
+                                                        ),
+                                                    )
+                                                    .await;
+                                                }
+                                            }
                                         }
                                     }
+                                    if let Some(e) = last_error {
+                                        res = Err(e);
+                                    }
+                                    res
+                                } else {
+                                    func(ctx.clone()).await
                                 }
                             }
-                            if let Some(e) = last_error {
-                                res = Err(e);
+                        };
+                        let step_run = async {
+                            let mut ctx = ctx.clone();
+                            loop {
+                                match step_attempt(ctx.clone()).await {
+                                    Err(e) => match recover_step(&slot, &ctx, &name, &e).await {
+                                        Some(next) => ctx = next,
+                                        None => break Err(e),
+                                    },
+                                    ok => break ok,
+                                }
                             }
-                            res
-                        } else {
-                            func(ctx).await
                         };
+                        let result = run_with_deadline(
+                            &slot,
+                            &name,
+                            step_limit(step_deadline, deadline),
+                            cancel.as_ref(),
+                            crate::guest::protocol::maybe_scope_exec_user(
+                                step_user,
+                                step_ctx.clone().scope(step_run),
+                            ),
+                        )
+                        .await;
 
+                        let elapsed = step_start.elapsed();
                         let step_output = match result {
                             Ok(output) => {
-                                let elapsed = step_start.elapsed();
                                 step_span.record_stdout(output.len());
                                 step_span.set_ok();
                                 // Emit StageSucceeded
@@ -444,6 +636,7 @@ impl Scheduler {
                                         1,
                                     ));
                                 }
+                                events::emit(&events, step_finished(&name, elapsed, None));
                                 observer.logger().info(
                                     &format!(
                                         "[workflow:{}] step \"{}\" ok ({:.1}s)",
@@ -454,11 +647,12 @@ impl Scheduler {
                                     &[("step", name.as_str())],
                                 );
                                 StepOutput::new(output, Vec::new(), 0)
+                                    .with_test_report(ctx.test_report())
                             }
                             Err(e) => {
-                                let elapsed = step_start.elapsed();
                                 let error_msg = e.to_string();
                                 step_span.record_stderr(error_msg.len());
+                                mark_stopped(&mut step_span, &e);
                                 step_span.set_error(&error_msg);
                                 // Emit StageFailed
                                 if let Some(ref tx) = stx {
@@ -472,6 +666,10 @@ impl Scheduler {
                                         1,
                                     ));
                                 }
+                                events::emit(
+                                    &events,
+                                    step_finished(&name, elapsed, Some(&error_msg)),
+                                );
                                 observer.logger().error(
                                     &format!(
                                         "[workflow:{}] step \"{}\" FAILED ({:.1}s): {}",
@@ -483,22 +681,43 @@ impl Scheduler {
                                     &[("step", name.as_str())],
                                 );
                                 StepOutput::new(Vec::new(), error_msg.as_bytes().to_vec(), 1)
+                                    .with_test_report(ctx.test_report())
                             }
                         };
 
-                        (name, step_output)
+                        (name, step_output, Some((step_ctx, elapsed)))
                     });
                 }
 
                 // Collect results from all parallel tasks
                 while let Some(result) = join_set.join_next().await {
-                    let (name, output) =
+                    let (name, output, executed) =
                         result.map_err(|e| Error::Guest(format!("Join error: {}", e)))?;
+                    if let Some((step_ctx, elapsed)) = executed {
+                        self.record_slo_step(
+                            slo_monitor.as_ref(),
+                            &name,
+                            &step_ctx,
+                            elapsed,
+                            output.exit_code == 0,
+                        )
+                        .await;
+                    }
+                    let output = spiller.retain(&name, output);
                     step_outputs.write().await.insert(name, output);
                 }
 
                 step_counter += group.len();
             }
+
+            // Checkpoint the workspace a replacement sandbox would resume from
+            let outputs = step_outputs.read().await;
+            if group
+                .iter()
+                .all(|name| outputs.get(name).is_some_and(StepOutput::success))
+            {
+                slot.checkpoint().await;
+            }
         }
 
         // Workflow completion summary
@@ -529,13 +748,19 @@ impl Scheduler {
         // Get final output
         let (output, exit_code) = if let Some(output_step) = &workflow.output_step {
             if let Some(step_output) = outputs.get(output_step) {
-                (step_output.stdout.clone(), step_output.exit_code)
+                (
+                    step_output.stdout_bytes()?.into_owned(),
+                    step_output.exit_code,
+                )
             } else {
                 (Vec::new(), 1)
             }
         } else if let Some(last_step) = plan.steps.last() {
             if let Some(step_output) = outputs.get(last_step) {
-                (step_output.stdout.clone(), step_output.exit_code)
+                (
+                    step_output.stdout_bytes()?.into_owned(),
+                    step_output.exit_code,
+                )
             } else {
                 (Vec::new(), 1)
             }
@@ -545,16 +770,67 @@ impl Scheduler {
 
         let duration_ms = start_time.elapsed().as_millis() as u64;
 
-        workflow_span.set_ok();
+        if let Some(monitor) = &slo_monitor {
+            for alert in monitor.finish() {
+                self.observer.raise_slo_alert(&alert).await;
+            }
+            for alert in monitor.alerts() {
+                workflow_span.add_event_with_attrs(SLO_VIOLATION_EVENT, alert.event_attributes());
+            }
+        }
+
+        let cancelled = self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+            && outputs.values().any(|output| !output.success());
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            workflow_span.set_attribute(TIMED_OUT_ATTRIBUTE, "true");
+            workflow_span.set_error("workflow exceeded its deadline");
+        } else if cancelled {
+            workflow_span.set_attribute(CANCELLED_ATTRIBUTE, "true");
+            workflow_span.set_error("workflow cancelled");
+        } else {
+            workflow_span.set_ok();
+        }
 
         Ok(WorkflowResult {
             output,
             exit_code,
             step_outputs: outputs.clone(),
             duration_ms,
+            cancelled,
+            sandbox_replacements: slot.replacements(),
         })
     }
 
+    /// Feed a finished step into the SLO monitor and raise any violations.
+    ///
+    /// Skipped steps never reach here: they did not run, so they say nothing
+    /// about the step's own latency or reliability.
+    async fn record_slo_step(
+        &self,
+        monitor: Option<&SloMonitor>,
+        name: &str,
+        span: &SpanContext,
+        duration: Duration,
+        succeeded: bool,
+    ) {
+        let Some(monitor) = monitor else {
+            return;
+        };
+        let alerts = monitor.record_step(StepOutcome {
+            name,
+            span,
+            duration,
+            succeeded,
+            cost_usd: 0.0,
+        });
+        for alert in alerts {
+            self.observer.raise_slo_alert(&alert).await;
+        }
+    }
+
     async fn execute_with_retry(
         &self,
         func: super::definition::StepFn,
@@ -593,6 +869,126 @@ impl Scheduler {
     }
 }
 
+fn step_finished(step: &str, elapsed: Duration, error: Option<&str>) -> WorkflowEvent {
+    WorkflowEvent::StepFinished {
+        step: step.to_string(),
+        exit_code: i32::from(error.is_some()),
+        duration_ms: elapsed.as_millis() as u64,
+        error: error.map(str::to_string),
+    }
+}
+
+/// Span attribute set on steps (and workflows) that ran out of time.
+const TIMED_OUT_ATTRIBUTE: &str = "timed_out";
+
+/// Span attribute set on steps (and workflows) stopped by cancellation.
+const CANCELLED_ATTRIBUTE: &str = "cancelled";
+
+/// Time a step may run: its own deadline, capped by what is left of the
+/// workflow's.
+fn step_limit(step: Option<Duration>, workflow: Option<Instant>) -> Option<Duration> {
+    let left = workflow.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+    match (step, left) {
+        (Some(step), Some(left)) => Some(step.min(left)),
+        (step, left) => step.or(left),
+    }
+}
+
+/// Run a step within `limit`, or until `cancel` fires. Its execs share an
+/// exec id, so either way its processes are killed in the guest instead of
+/// being left running.
+async fn run_with_deadline<F>(
+    slot: &SandboxSlot,
+    step: &str,
+    limit: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+    run: F,
+) -> Result<Vec<u8>>
+where
+    F: std::future::Future<Output = Result<Vec<u8>>>,
+{
+    if limit.is_none() && cancel.is_none() {
+        return run.await;
+    }
+    let exceeded = |limit| Error::DeadlineExceeded {
+        step: step.to_string(),
+        limit,
+    };
+    let cancelled = || Error::Cancelled {
+        step: step.to_string(),
+    };
+    if let Some(limit) = limit.filter(|limit| limit.is_zero()) {
+        return Err(exceeded(limit));
+    }
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(cancelled());
+    }
+    let exec_id = format!("step:{}:{}", step, uuid::Uuid::now_v7());
+    let run = crate::guest::protocol::scope_exec_id(exec_id.clone(), run);
+    let timer = async {
+        match limit {
+            Some(limit) => tokio::time::sleep(limit).await,
+            None => std::future::pending().await,
+        }
+    };
+    let cancelled_wait = async {
+        match cancel {
+            Some(cancel) => cancel.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    let err = tokio::select! {
+        result = run => return result,
+        _ = timer => exceeded(limit.unwrap_or_default()),
+        _ = cancelled_wait => cancelled(),
+    };
+    if let Err(e) = slot.current().signal(&exec_id, ExecSignal::Kill).await {
+        tracing::debug!("No guest processes to kill for step \"{}\": {}", step, e);
+    }
+    Err(err)
+}
+
+fn mark_stopped(span: &mut crate::observe::SpanGuard, err: &Error) {
+    match err {
+        Error::DeadlineExceeded { .. } => span.set_attribute(TIMED_OUT_ATTRIBUTE, "true"),
+        Error::Cancelled { .. } => span.set_attribute(CANCELLED_ATTRIBUTE, "true"),
+        _ => {}
+    }
+}
+
+/// After `step` failed with `err` on `ctx`'s sandbox, the context to re-run
+/// it with: the same one if the VM was restarted in place, or one on a
+/// replacement sandbox. `None` when the step has failed for good.
+async fn recover_step(
+    slot: &SandboxSlot,
+    ctx: &StepContext,
+    step: &str,
+    err: &Error,
+) -> Option<StepContext> {
+    if restarted_for(ctx.sandbox(), step, err).await {
+        return Some(ctx.clone());
+    }
+    let replacement = slot.replace_after(ctx.sandbox(), step, err).await?;
+    Some(ctx.clone().with_sandbox(replacement))
+}
+
+/// Whether `step` failed because the guest died under it and the sandbox's
+/// [`RestartPolicy`](crate::sandbox::RestartPolicy) brought up a fresh VM,
+/// in which case the step is re-run from the start.
+async fn restarted_for(sandbox: &Sandbox, step: &str, err: &Error) -> bool {
+    match sandbox.recover(err).await {
+        Ok(true) => {
+            tracing::warn!("Re-running step \"{}\" on a restarted VM: {}", step, err);
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            tracing::error!("Failed to restart the VM for step \"{}\": {}", step, e);
+            false
+        }
+    }
+}
+
 /// Execute a single step (utility function for testing)
 pub async fn execute_step(
     step_name: &str,
@@ -732,4 +1128,195 @@ mod tests {
             c_stderr
         );
     }
+
+    #[tokio::test]
+    async fn test_run_as_scopes_the_step_user() {
+        use crate::sandbox::ExecUser;
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = |step: &'static str| {
+            let seen = seen.clone();
+            move |_ctx| {
+                let user = crate::guest::protocol::current_exec_overrides().user;
+                seen.lock().unwrap().push((step, user));
+                async { Ok(vec![]) }
+            }
+        };
+        let workflow = Workflow::define("users")
+            .step("install", record("install"))
+            .step_depends("agent", &["install"], record("agent"))
+            .run_as("install", ExecUser::Root)
+            .build();
+
+        let sandbox = crate::sandbox::Sandbox::mock().build().unwrap();
+        let scheduler = Scheduler::new(crate::observe::Observer::test(), None);
+        scheduler.execute(&workflow, sandbox).await.unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [("install", Some(ExecUser::Root)), ("agent", None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deadlines_fail_steps_and_mark_spans() {
+        let slow = |_ctx| async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(vec![])
+        };
+        let workflow = Workflow::define("deadlines")
+            .step("quick", |_ctx| async { Ok(b"done".to_vec()) })
+            .step("stuck", slow)
+            .step_depends("after", &["stuck"], |_ctx| async { Ok(vec![]) })
+            .step_timeout("stuck", Duration::from_millis(50))
+            .timeout(Duration::from_secs(60))
+            .build();
+
+        let observer = crate::observe::Observer::test();
+        let sandbox = crate::sandbox::Sandbox::mock().build().unwrap();
+        let scheduler = Scheduler::new(observer.clone(), None);
+        let result = scheduler.execute(&workflow, sandbox.clone()).await.unwrap();
+
+        assert_eq!(result.step_outputs["quick"].exit_code, 0);
+        let stuck = &result.step_outputs["stuck"];
+        assert_eq!(stuck.exit_code, 1);
+        assert!(stuck.stderr_str().contains("exceeded its deadline"));
+        assert_eq!(result.step_outputs["after"].exit_code, 1);
+
+        let spans = observer.get_traces();
+        let span = |name: &str| spans.iter().find(|s| s.name == name).unwrap();
+        assert_eq!(span("step:stuck").attributes["timed_out"], "true");
+        assert!(!span("step:quick").attributes.contains_key("timed_out"));
+        assert!(!span("workflow:deadlines")
+            .attributes
+            .contains_key("timed_out"));
+
+        // The workflow's deadline caps steps without one of their own.
+        let workflow = Workflow::define("late")
+            .step("stuck", slow)
+            .step_depends("next", &["stuck"], |_ctx| async { Ok(vec![]) })
+            .timeout(Duration::from_millis(50))
+            .build();
+        let result = scheduler.execute(&workflow, sandbox).await.unwrap();
+        assert!(result.step_outputs["stuck"]
+            .stderr_str()
+            .contains("exceeded its deadline"));
+        let spans = observer.get_traces();
+        let workflow_span = spans.iter().find(|s| s.name == "workflow:late").unwrap();
+        assert_eq!(workflow_span.attributes["timed_out"], "true");
+    }
+
+    #[test]
+    fn test_step_limit_is_capped_by_the_workflow_deadline() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(step_limit(None, None), None);
+        assert_eq!(step_limit(Some(minute), None), Some(minute));
+        let soon = Instant::now() + Duration::from_secs(1);
+        assert!(step_limit(Some(minute), Some(soon)).unwrap() <= Duration::from_secs(1));
+        let past = Instant::now() - Duration::from_secs(1);
+        assert_eq!(step_limit(None, Some(past)), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_execs_chain_to_step_spans() {
+        // a -> (b, c in parallel) -> d; every exec must hang off its own step
+        let workflow = Workflow::define("traced")
+            .step("a", |ctx| async move { ctx.exec("echo", &["a"]).await })
+            .step_depends(
+                "b",
+                &["a"],
+                |ctx| async move { ctx.exec("echo", &["b"]).await },
+            )
+            .step_depends(
+                "c",
+                &["a"],
+                |ctx| async move { ctx.exec("echo", &["c"]).await },
+            )
+            .step_depends("d", &["b", "c"], |ctx| async move {
+                ctx.exec("echo", &["d1"]).await?;
+                ctx.exec("echo", &["d2"]).await
+            })
+            .build();
+
+        let observer = crate::observe::Observer::test();
+        let sandbox = crate::sandbox::Sandbox::local()
+            .observe(crate::observe::ObserveConfig::test())
+            .build()
+            .unwrap();
+        let scheduler = Scheduler::new(observer.clone(), None);
+        scheduler.execute(&workflow, sandbox.clone()).await.unwrap();
+
+        let spans = observer.get_traces();
+        let workflow_span = spans.iter().find(|s| s.name == "workflow:traced").unwrap();
+        let trace_id = &workflow_span.context.trace_id;
+        let step_span_id = |step: &str| {
+            let span = spans
+                .iter()
+                .find(|s| s.name == format!("step:{}", step))
+                .unwrap();
+            assert_eq!(&span.context.trace_id, trace_id);
+            assert_eq!(
+                span.context.parent_span_id.as_ref(),
+                Some(&workflow_span.context.span_id)
+            );
+            span.context.span_id.clone()
+        };
+
+        let execs = sandbox.observer().unwrap().get_traces();
+        assert_eq!(execs.len(), 5);
+        for exec in &execs {
+            assert_eq!(exec.name, "exec:echo");
+            assert_eq!(&exec.context.trace_id, trace_id);
+            let step = &exec.attributes["exec"]["echo ".len()..][..1];
+            assert_eq!(
+                exec.context.parent_span_id,
+                Some(step_span_id(step)),
+                "{} should be a child of step:{}",
+                exec.attributes["exec"],
+                step
+            );
+        }
+        assert!(crate::observe::SpanContext::current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_infrastructure_failure_reruns_the_step_on_a_replacement() {
+        use crate::workflow::ReplaceSandboxPolicy;
+
+        let original = crate::sandbox::Sandbox::mock().build().unwrap();
+        let failing = original.clone();
+        let workflow = Workflow::define("replaced")
+            .step(
+                "setup",
+                |ctx| async move { ctx.exec("echo", &["setup"]).await },
+            )
+            .step_depends("build", &["setup"], move |ctx| {
+                let failing = failing.clone();
+                async move {
+                    if Arc::ptr_eq(ctx.sandbox(), &failing) {
+                        return Err(Error::VmNotRunning);
+                    }
+                    ctx.exec("echo", &["built"]).await
+                }
+            })
+            .step_depends("report", &["build"], |_ctx| async move {
+                Err(Error::Guest("report exited with status 1".into()))
+            })
+            .build();
+
+        let policy = ReplaceSandboxPolicy::new(2, || crate::sandbox::Sandbox::mock().build());
+        let scheduler = Scheduler::new(Observer::new(crate::observe::ObserveConfig::test()), None)
+            .with_replacement(policy.clone());
+        let result = scheduler
+            .execute(&workflow, original.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(result.sandbox_replacements, 1);
+        assert_eq!(result.step_output("build").unwrap().stdout_str(), "built\n");
+        // A command failure is not the infrastructure's: no second replacement.
+        assert_ne!(result.step_output("report").unwrap().exit_code, 0);
+        let replacement = policy.latest_sandbox().unwrap();
+        assert!(!Arc::ptr_eq(&replacement, &original));
+    }
 }
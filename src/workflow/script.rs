@@ -0,0 +1,249 @@
+//! Plain scripts as workflows
+//!
+//! [`Workflow::from_script`] turns an existing shell or Python script into a
+//! workflow without rewriting it: comment lines of the form `# step: <name>`
+//! cut it into steps that run one after another, each with its own span
+//! and [`StepOutput`](super::StepOutput). A team can keep its `ci.sh`, add
+//! markers, and move steps into Rust one at a time.
+//!
+//! ```text
+//! #!/bin/bash
+//! set -euo pipefail
+//! export TARGET=release
+//!
+//! # step: build
+//! cargo build --profile "$TARGET"
+//!
+//! # step: test
+//! cargo test --profile "$TARGET"
+//! ```
+//!
+//! Each step is a separate exec, so shell variables do not carry from one
+//! step to the next. Everything above the first marker (shebang, `set`
+//! options, functions, exports) is the preamble and runs at the start of
+//! every step. The shebang picks the interpreter (`sh` without one), which
+//! reads the step from stdin. A script without markers is a single step
+//! named after the file.
+
+use std::path::Path;
+
+use super::definition::Workflow;
+use crate::{Error, Result};
+
+/// Comment that starts a new step.
+const STEP_MARKER: &str = "# step:";
+
+/// Interpreter for scripts without a shebang.
+const DEFAULT_INTERPRETER: &str = "sh";
+
+/// A script cut into steps at its markers.
+#[derive(Debug, PartialEq)]
+struct ParsedScript {
+    /// Program and arguments from the shebang.
+    interpreter: Vec<String>,
+    /// Lines before the first marker, shebang excluded.
+    preamble: String,
+    /// Step names and bodies, in script order.
+    steps: Vec<(String, String)>,
+}
+
+impl Workflow {
+    /// Build a workflow from the script at `path`, one step per
+    /// `# step: <name>` marker, each depending on the one before. The
+    /// workflow is named after the file; see the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if the script cannot be read, or if a
+    /// marker has no name, repeats a name, or has nothing to run.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use void_box::sandbox::Sandbox;
+    /// use void_box::observe::ObserveConfig;
+    /// use void_box::workflow::{Workflow, WorkflowExt};
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let workflow = Workflow::from_script("scripts/ci.sh")?;
+    /// let result = workflow
+    ///     .observe(ObserveConfig::test())
+    ///     .run_in(Sandbox::local().from_env()?.build()?)
+    ///     .await?;
+    /// println!("{}", result.result.step_output("test").unwrap().stdout_str());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_script(path: impl AsRef<Path>) -> Result<Workflow> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            Error::Config(format!("failed to read script {}: {}", path.display(), e))
+        })?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "script".to_string());
+        let script = parse(&name, &source)?;
+
+        let mut builder = Workflow::define(name);
+        let mut previous: Option<String> = None;
+        for (step, body) in script.steps {
+            let interpreter = script.interpreter.clone();
+            let source = format!("{}{}", script.preamble, body).into_bytes();
+            let func = move |ctx: super::StepContext| {
+                let interpreter = interpreter.clone();
+                let source = source.clone();
+                async move {
+                    let args: Vec<&str> = interpreter[1..].iter().map(String::as_str).collect();
+                    ctx.exec_with_stdin(&interpreter[0], &args, &source).await
+                }
+            };
+            builder = match previous.replace(step.clone()) {
+                Some(previous) => builder.step_depends(step, &[previous.as_str()], func),
+                None => builder.step(step, func),
+            };
+        }
+        Ok(builder.build())
+    }
+}
+
+fn parse(name: &str, source: &str) -> Result<ParsedScript> {
+    let mut lines = source.lines().peekable();
+    let interpreter = match lines.peek().and_then(|line| line.strip_prefix("#!")) {
+        Some(shebang) => {
+            lines.next();
+            parse_shebang(shebang)
+        }
+        None => vec![DEFAULT_INTERPRETER.to_string()],
+    };
+    if interpreter.is_empty() {
+        return Err(Error::Config(format!("script {name} has an empty shebang")));
+    }
+
+    let mut preamble = String::new();
+    let mut steps: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        if let Some(step) = line.trim_start().strip_prefix(STEP_MARKER) {
+            let step = step.trim();
+            if step.is_empty() {
+                return Err(Error::Config(format!(
+                    "script {name} has a step marker without a name"
+                )));
+            }
+            if steps.iter().any(|(existing, _)| existing == step) {
+                return Err(Error::Config(format!(
+                    "script {name} declares step '{step}' twice"
+                )));
+            }
+            steps.push((step.to_string(), String::new()));
+            continue;
+        }
+        let body = match steps.last_mut() {
+            Some((_, body)) => body,
+            None => &mut preamble,
+        };
+        body.push_str(line);
+        body.push('\n');
+    }
+
+    if steps.is_empty() {
+        steps.push((name.to_string(), std::mem::take(&mut preamble)));
+    }
+    for (step, body) in &steps {
+        if body.lines().all(is_blank_or_comment) {
+            return Err(Error::Config(format!(
+                "step '{step}' in script {name} has nothing to run"
+            )));
+        }
+    }
+    Ok(ParsedScript {
+        interpreter,
+        preamble,
+        steps,
+    })
+}
+
+/// `/usr/bin/env python3 -u` runs `python3 -u`; anything else runs as
+/// written.
+fn parse_shebang(shebang: &str) -> Vec<String> {
+    let mut words: Vec<String> = shebang.split_whitespace().map(str::to_string).collect();
+    if words
+        .first()
+        .is_some_and(|program| program == "/usr/bin/env")
+        && words.len() > 1
+    {
+        words.remove(0);
+    }
+    words
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::ObserveConfig;
+    use crate::sandbox::Sandbox;
+    use crate::workflow::WorkflowExt;
+
+    const CI: &str = "#!/usr/bin/env bash -e\nset -u\n\n# step: build\nmake\n  # step: test\n# run the suite\nmake test\n";
+
+    #[test]
+    fn test_markers_split_steps_and_share_the_preamble() {
+        let script = parse("ci", CI).unwrap();
+        assert_eq!(script.interpreter, vec!["bash", "-e"]);
+        assert_eq!(script.preamble, "set -u\n\n");
+        assert_eq!(
+            script.steps,
+            vec![
+                ("build".to_string(), "make\n".to_string()),
+                (
+                    "test".to_string(),
+                    "# run the suite\nmake test\n".to_string()
+                ),
+            ]
+        );
+
+        let plain = parse("deploy", "./deploy.sh\n").unwrap();
+        assert_eq!(plain.interpreter, vec!["sh"]);
+        assert_eq!(plain.steps[0].0, "deploy");
+    }
+
+    #[test]
+    fn test_bad_markers_are_rejected() {
+        assert!(parse("ci", "# step:\nmake\n").is_err());
+        assert!(parse("ci", "# step: a\nmake\n# step: a\nmake\n").is_err());
+        assert!(parse("ci", "# step: a\n# nothing\n# step: b\nmake\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_script_steps_run_in_order_with_their_own_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ci.sh");
+        // `cat` echoes back what the step feeds its interpreter.
+        std::fs::write(
+            &path,
+            "#!/usr/bin/env cat\nset -e\n# step: build\nmake\n# step: test\nmake test\n",
+        )
+        .unwrap();
+        let workflow = Workflow::from_script(&path).unwrap();
+        assert_eq!(workflow.name, "ci");
+        assert_eq!(workflow.execution_order().unwrap(), vec!["build", "test"]);
+        assert_eq!(workflow.output_step(), Some("test"));
+
+        let observed = workflow
+            .observe(ObserveConfig::test())
+            .run_in(Sandbox::mock().build().unwrap())
+            .await
+            .unwrap();
+        let result = observed.result;
+        assert_eq!(
+            result.step_output("build").unwrap().stdout_str(),
+            "set -e\nmake\n"
+        );
+        assert_eq!(result.output_str(), "set -e\nmake test\n");
+    }
+}
@@ -6,6 +6,7 @@ use std::process::Command;
 
 #[cfg(target_os = "linux")]
 use kvm_ioctls::{Cap, Kvm};
+use void_box::backend::{BackendConfig, VmmBackend};
 
 #[allow(dead_code)]
 pub fn require_kernel_artifacts(kernel: &Path, initramfs: Option<&Path>) -> Result<(), String> {
@@ -26,6 +27,48 @@ pub fn require_kernel_artifacts(kernel: &Path, initramfs: Option<&Path>) -> Resu
     Ok(())
 }
 
+/// Create the host's backend and start it with `config`. Prints why the
+/// test is skipped and returns `None` if either step fails.
+///
+/// On Linux, `VOID_BOX_CONTROL=serial` runs the control channel over the
+/// virtio-console port instead of vsock.
+#[allow(dead_code)]
+pub async fn start_backend(config: BackendConfig) -> Option<Box<dyn VmmBackend>> {
+    let mut backend = match create_backend() {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("skipping: no usable backend: {e}");
+            return None;
+        }
+    };
+    match backend.start(config).await {
+        Ok(()) => Some(backend),
+        Err(e) => {
+            eprintln!("skipping: backend start failed: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn create_backend() -> void_box::Result<Box<dyn VmmBackend>> {
+    use void_box::backend::{kvm::KvmBackend, Backend};
+
+    if std::env::var("VOID_BOX_CONTROL").as_deref() != Ok("serial") {
+        return void_box::backend::create_backend();
+    }
+    let backend = match Backend::detect()? {
+        Backend::KvmUserspaceVsock => KvmBackend::new().with_userspace_vsock(),
+        _ => KvmBackend::new(),
+    };
+    Ok(Box::new(backend.with_serial_control()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_backend() -> void_box::Result<Box<dyn VmmBackend>> {
+    void_box::backend::create_backend()
+}
+
 #[cfg(target_os = "linux")]
 pub fn require_kvm_usable() -> Result<(), String> {
     if !Path::new("/dev/kvm").exists() {
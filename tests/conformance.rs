@@ -91,22 +91,34 @@ fn backend_config_with(
         network: true,
         enable_vsock: true,
         guest_console: GuestConsoleSink::Stderr,
+        console_shell: false,
+        boot_profile: Default::default(),
         shared_dir: None,
         mounts: vec![],
         oci_rootfs: None,
         oci_rootfs_dev: None,
         oci_rootfs_disk: None,
+        disks: Vec::new(),
         env: vec![],
+        dns: Default::default(),
+        network_mode: Default::default(),
         security: BackendSecurityConfig {
             session_secret: SessionSecret::new(secret),
             command_allowlist,
             network_deny_list,
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         },
         snapshot: None,
         enable_snapshots: false,
+        resource_policy: Default::default(),
+        protocol_tap: None,
+        watchdog: None,
+        extra_cmdline: Vec::new(),
+        rosetta: false,
     })
 }
 
@@ -124,14 +136,7 @@ async fn create_started_backend() -> Option<Box<dyn VmmBackend>> {
         }
     };
 
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {}", e);
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 async fn create_started_backend_with_config(config: BackendConfig) -> Option<Box<dyn VmmBackend>> {
@@ -140,14 +145,7 @@ async fn create_started_backend_with_config(config: BackendConfig) -> Option<Box
         return None;
     }
 
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {}", e);
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 async fn guest_sh(backend: &dyn VmmBackend, script: &str) -> Option<void_box::ExecOutput> {
@@ -298,7 +296,7 @@ async fn conformance_exec_streaming() {
     };
 
     let (mut chunk_rx, done_rx) = backend
-        .exec_streaming("echo", &["streaming-test"], &[], None, Some(30))
+        .exec_streaming("echo", &["streaming-test"], &[], None, Some(30), None)
         .await
         .expect("exec_streaming failed");
 
@@ -578,7 +576,7 @@ async fn conformance_file_rpc_while_exec_running() {
         .expect("write_file failed");
 
     let (_chunk_rx, _response_rx) = backend
-        .exec_streaming("sh", &["-c", "sleep 10"], &[], None, Some(15))
+        .exec_streaming("sh", &["-c", "sleep 10"], &[], None, Some(15), None)
         .await
         .expect("exec_streaming failed");
 
@@ -603,3 +601,58 @@ async fn conformance_file_rpc_while_exec_running() {
         .expect("file_stat on missing file must work during exec");
     assert!(!stat.exists);
 }
+
+/// A running exec started with an exec id can be stopped and killed.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[ignore = "requires VM backend + kernel/initramfs artifacts"]
+async fn conformance_signal_exec_stops_and_kills() {
+    use void_box::guest::protocol::ExecSignal;
+
+    let backend = match create_started_backend().await {
+        Some(b) => b,
+        None => return,
+    };
+    let channel = backend.control_channel().expect("control channel");
+
+    let (_chunk_rx, mut response_rx) = backend
+        .exec_streaming(
+            "sh",
+            &["-c", "sleep 2; echo done"],
+            &[],
+            None,
+            Some(30),
+            Some("conformance-signal"),
+        )
+        .await
+        .expect("exec_streaming failed");
+
+    let stopped = channel
+        .send_signal_exec("conformance-signal", ExecSignal::Stop)
+        .await
+        .expect("SignalExec failed");
+    assert!(stopped.delivered, "{:?}", stopped.error);
+
+    // Stopped, the command outlives its sleep.
+    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+    assert!(response_rx.try_recv().is_err(), "stopped exec finished");
+
+    let killed = channel
+        .send_signal_exec("conformance-signal", ExecSignal::Kill)
+        .await
+        .expect("SignalExec failed");
+    assert!(killed.delivered, "{:?}", killed.error);
+
+    let response = tokio::time::timeout(std::time::Duration::from_secs(10), response_rx)
+        .await
+        .expect("killed exec did not finish")
+        .expect("response channel closed")
+        .expect("exec failed");
+    assert_ne!(response.exit_code, 0);
+    assert!(!String::from_utf8_lossy(&response.stdout).contains("done"));
+
+    let gone = channel
+        .send_signal_exec("conformance-signal", ExecSignal::Continue)
+        .await
+        .expect("SignalExec failed");
+    assert!(!gone.delivered);
+}
@@ -148,7 +148,7 @@ async fn real_claude_uses_void_mcp_tools() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg)) if msg.contains("control_channel: deadline reached") => {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             handle.stop().await;
             return;
@@ -273,30 +273,40 @@ async fn diagnostic_void_mcp_starts_in_guest() {
         network: true,
         enable_vsock: true,
         guest_console: GuestConsoleSink::Stderr,
+        console_shell: false,
+        boot_profile: Default::default(),
         shared_dir: None,
         mounts: vec![],
         oci_rootfs: None,
         oci_rootfs_dev: None,
         oci_rootfs_disk: None,
+        disks: Vec::new(),
         env: vec![],
+        dns: Default::default(),
+        network_mode: Default::default(),
         security: BackendSecurityConfig {
             session_secret: SessionSecret::new(secret),
             command_allowlist: vec!["sh".into(), "void-mcp".into(), "echo".into(), "cat".into()],
             network_deny_list: vec!["169.254.0.0/16".into()],
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         },
         snapshot: None,
         enable_snapshots: false,
+        resource_policy: Default::default(),
+        protocol_tap: None,
+        watchdog: None,
+        extra_cmdline: Vec::new(),
+        rosetta: false,
     };
 
-    let mut backend = void_box::backend::create_backend();
-    if let Err(e) = backend.start(config).await {
-        eprintln!("skipping: backend start failed: {e}");
+    let Some(backend) = vm_preflight::start_backend(config).await else {
         handle.stop().await;
         return;
-    }
+    };
 
     // Test 1: void-mcp binary exists
     let out = backend
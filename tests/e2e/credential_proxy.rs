@@ -101,32 +101,37 @@ async fn start_backend() -> Option<Box<dyn VmmBackend>> {
         network: true,
         enable_vsock: true,
         guest_console: GuestConsoleSink::Stderr,
+        console_shell: false,
+        boot_profile: Default::default(),
         shared_dir: None,
         mounts: vec![],
         oci_rootfs: None,
         oci_rootfs_dev: None,
         oci_rootfs_disk: None,
+        disks: Vec::new(),
         env: vec![],
+        dns: Default::default(),
+        network_mode: Default::default(),
         security: BackendSecurityConfig {
             session_secret: SessionSecret::new(secret),
             command_allowlist: vec!["sh".into(), "wget".into(), "cat".into(), "echo".into()],
             network_deny_list: vec!["169.254.0.0/16".into()],
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         },
         snapshot: None,
         enable_snapshots: false,
+        resource_policy: Default::default(),
+        protocol_tap: None,
+        watchdog: None,
+        extra_cmdline: Vec::new(),
+        rosetta: false,
     };
 
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {e}");
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 /// Mock TLS upstream recording request headers.
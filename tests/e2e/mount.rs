@@ -95,6 +95,8 @@ fn build_config_with_mount(
         network: true,
         enable_vsock: true,
         guest_console: GuestConsoleSink::Stderr,
+        console_shell: false,
+        boot_profile: Default::default(),
         shared_dir: None,
         mounts: vec![MountConfig {
             host_path: host_dir.to_string_lossy().into_owned(),
@@ -104,7 +106,10 @@ fn build_config_with_mount(
         oci_rootfs: None,
         oci_rootfs_dev: None,
         oci_rootfs_disk: None,
+        disks: Vec::new(),
         env: vec![],
+        dns: Default::default(),
+        network_mode: Default::default(),
         security: BackendSecurityConfig {
             session_secret: SessionSecret::new(secret),
             command_allowlist: vec![
@@ -123,12 +128,19 @@ fn build_config_with_mount(
                 "grep".into(),
             ],
             network_deny_list: vec!["169.254.0.0/16".into()],
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         },
         snapshot: None,
         enable_snapshots: false,
+        resource_policy: Default::default(),
+        protocol_tap: None,
+        watchdog: None,
+        extra_cmdline: Vec::new(),
+        rosetta: false,
     })
 }
 
@@ -139,14 +151,7 @@ async fn create_started_backend_with_mount(
     read_only: bool,
 ) -> Option<Box<dyn VmmBackend>> {
     let config = build_config_with_mount(host_dir, guest_path, read_only)?;
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {e}");
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 /// Execute a shell command inside the guest, returning the ExecOutput.
@@ -85,22 +85,34 @@ fn build_network_config_with_deny_list(deny_list: Vec<String>) -> Option<Backend
         network: true,
         enable_vsock: true,
         guest_console: GuestConsoleSink::Stderr,
+        console_shell: false,
+        boot_profile: Default::default(),
         shared_dir: None,
         mounts: vec![],
         oci_rootfs: None,
         oci_rootfs_dev: None,
         oci_rootfs_disk: None,
+        disks: Vec::new(),
         env: vec![],
+        dns: Default::default(),
+        network_mode: Default::default(),
         security: BackendSecurityConfig {
             session_secret: SessionSecret::new(secret),
             command_allowlist: vec!["sh".into(), "wget".into(), "cat".into(), "echo".into()],
             network_deny_list: deny_list,
+            network_policy: None,
             max_connections_per_second: 50,
             max_concurrent_connections: 64,
             seccomp: true,
+            write_roots: Vec::new(),
         },
         snapshot: None,
         enable_snapshots: false,
+        resource_policy: Default::default(),
+        protocol_tap: None,
+        watchdog: None,
+        extra_cmdline: Vec::new(),
+        rosetta: false,
     })
 }
 
@@ -110,26 +122,12 @@ fn build_network_config() -> Option<BackendConfig> {
 
 async fn start_backend() -> Option<Box<dyn VmmBackend>> {
     let config = build_network_config()?;
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {e}");
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 async fn start_backend_with_deny_list(deny_list: Vec<String>) -> Option<Box<dyn VmmBackend>> {
     let config = build_network_config_with_deny_list(deny_list)?;
-    let mut backend = void_box::backend::create_backend();
-    match backend.start(config).await {
-        Ok(()) => Some(backend),
-        Err(e) => {
-            eprintln!("skipping: backend start failed: {e}");
-            None
-        }
-    }
+    vm_preflight::start_backend(config).await
 }
 
 async fn guest_sh(backend: &dyn VmmBackend, script: &str) -> Option<void_box::ExecOutput> {
@@ -597,7 +595,7 @@ async fn claudio_discovers_injected_messaging_skill() {
     // Run claudio — it scans skills dir and reports discoveries
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg)) if msg.contains("control_channel: deadline reached") => {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             handle.stop().await;
             return;
@@ -783,7 +781,7 @@ async fn claudio_discovers_void_mcp_tools() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg)) if msg.contains("control_channel: deadline reached") => {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             handle.stop().await;
             return;
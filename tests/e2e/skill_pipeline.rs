@@ -118,9 +118,7 @@ async fn test_agent_box_with_local_skill() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
@@ -176,9 +174,7 @@ async fn test_agent_box_with_multiple_skills() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
@@ -231,9 +227,7 @@ async fn test_agent_box_with_mcp_skill() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
@@ -292,9 +286,7 @@ async fn test_agent_box_mixed_skills() {
 
     let result = match ab.run(None, None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
@@ -352,9 +344,7 @@ async fn test_pipeline_two_stages_kvm() {
         .await
     {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
@@ -414,9 +404,7 @@ async fn test_agent_box_with_input_data_kvm() {
     let input = br#"{"symbols": ["AAPL", "NVDA"], "period": "30d"}"#;
     let result = match ab.run(Some(input), None).await {
         Ok(r) => r,
-        Err(void_box::Error::Guest(msg))
-            if msg.contains("control_channel: deadline reached (connect or handshake)") =>
-        {
+        Err(msg @ void_box::Error::BootFailed(_)) => {
             eprintln!("skipping: guest control channel unavailable: {msg}");
             return;
         }
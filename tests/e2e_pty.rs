@@ -165,4 +165,55 @@ mod pty_tests {
         assert_eq!(exit_code, 42);
         let _ = sandbox.stop().await;
     }
+
+    /// `ssh -tt` through the SSH bridge lands in a guest PTY, and a plain
+    /// `ssh host command` in a streamed exec. Also needs the `ssh` client.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn ssh_bridge_reaches_the_guest() {
+        use tokio::io::AsyncWriteExt;
+        use void_box::ssh::{SshBridge, SshBridgeConfig};
+
+        if let Some(reason) = skip_reason() {
+            eprintln!("SKIP: {}", reason);
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = test_sandbox().unwrap();
+        let bridge = SshBridge::start(
+            sandbox.clone(),
+            SshBridgeConfig::new("e2e").dir(dir.path().join("ssh")),
+        )
+        .await
+        .unwrap();
+        let access = bridge.access().clone();
+        let ssh = |extra: &[&str]| {
+            let mut cmd = tokio::process::Command::new("ssh");
+            cmd.arg("-F")
+                .arg(&access.config_file)
+                .args(["-o", "BatchMode=yes"])
+                .args(extra)
+                .arg(&access.host_alias)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped());
+            cmd
+        };
+
+        let mut child = ssh(&["-tt"]).spawn().unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin
+            .write_all(b"echo in-$((6*7))-pty; exit 5\n")
+            .await
+            .unwrap();
+        let output = child.wait_with_output().await.unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("in-42-pty"));
+        assert_eq!(output.status.code(), Some(5));
+
+        let output = ssh(&[]).arg("uname -s").output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "Linux\n");
+        assert_eq!(output.status.code(), Some(0));
+
+        drop(bridge);
+        let _ = sandbox.stop().await;
+    }
 }
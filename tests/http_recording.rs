@@ -0,0 +1,230 @@
+//! In-process integration test for the HTTP recording proxy.
+//!
+//! Drives the proxy the way a guest HTTP client would — through
+//! `HTTP_PROXY`-style absolute-form requests and `CONNECT` tunnels — with a
+//! mock plain-HTTP upstream and no VM. Asserts that:
+//! - record mode forwards to the upstream and writes the exchange without
+//!   credential headers,
+//! - replay mode answers from the cassette without contacting the upstream,
+//! - HTTPS replay works through a `CONNECT` tunnel trusting the proxy's CA,
+//! - record mode refuses upstreams the network policy or SSRF guard denies.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use void_box::backend::{NetworkPolicy, NetworkRule};
+use void_box::proxy::{HttpExchange, HttpRecording, HttpRecordingProxy, RecordedBody};
+
+/// Stand up a plain-HTTP upstream that echoes the request path and counts
+/// the requests it served.
+async fn start_mock_upstream() -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .expect("bind mock");
+    let addr = listener.local_addr().expect("mock addr");
+    let hits = Arc::new(AtomicUsize::new(0));
+
+    let hits_for_task = hits.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let hits = hits_for_task.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req: Request<Incoming>| {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    let body = format!("upstream saw {}", req.uri().path());
+                    async move {
+                        Ok::<_, std::convert::Infallible>(
+                            Response::builder()
+                                .header("x-upstream", "mock")
+                                .body(Full::new(Bytes::from(body)))
+                                .unwrap(),
+                        )
+                    }
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (addr, hits)
+}
+
+fn client_via(proxy: &HttpRecordingProxy) -> reqwest::Client {
+    let proxy_url = format!("http://127.0.0.1:{}", proxy.port());
+    let ca = reqwest::Certificate::from_pem(proxy.ca_cert_pem().as_bytes()).expect("CA pem");
+    reqwest::Client::builder()
+        .proxy(reqwest::Proxy::all(proxy_url).expect("proxy url"))
+        .tls_certs_only([ca])
+        .build()
+        .expect("client")
+}
+
+/// Upstream client for record mode that can reach the loopback mock.
+fn mock_upstream_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .no_proxy()
+        .build()
+        .expect("upstream client")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn records_then_replays_without_upstream() {
+    let (upstream, hits) = start_mock_upstream().await;
+    let dir = tempfile::tempdir().expect("tempdir");
+    let cassette = dir.path().join("cassette.jsonl");
+    let url = format!("http://{upstream}/v1/items?page=2");
+
+    let recorder = HttpRecordingProxy::with_upstream(
+        &HttpRecording::Record(cassette.clone()),
+        None,
+        mock_upstream_client(),
+    )
+    .await
+    .expect("start recorder");
+    let resp = client_via(&recorder)
+        .get(&url)
+        .header("authorization", "Bearer secret-token")
+        .send()
+        .await
+        .expect("recorded request");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.unwrap(), "upstream saw /v1/items");
+    drop(recorder);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let contents = std::fs::read_to_string(&cassette).expect("read cassette");
+    assert!(!contents.contains("secret-token"));
+    let recorded: Vec<HttpExchange> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("exchange line"))
+        .collect();
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].method, "GET");
+    assert_eq!(recorded[0].url, url);
+
+    let replayer = HttpRecordingProxy::start(&HttpRecording::Replay(cassette), None)
+        .await
+        .expect("start replayer");
+    let client = client_via(&replayer);
+    let resp = client.get(&url).send().await.expect("replayed request");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["x-upstream"], "mock");
+    assert_eq!(resp.text().await.unwrap(), "upstream saw /v1/items");
+    assert_eq!(
+        hits.load(Ordering::SeqCst),
+        1,
+        "replay must not reach upstream"
+    );
+
+    let missing = client
+        .get(format!("http://{upstream}/never-recorded"))
+        .send()
+        .await
+        .expect("unmatched request");
+    assert_eq!(missing.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replays_https_through_connect_tunnel() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let cassette = dir.path().join("cassette.jsonl");
+    let exchange = HttpExchange {
+        method: "POST".to_string(),
+        url: "https://api.example.test/v1/messages".to_string(),
+        request_headers: Vec::new(),
+        request_body: Some(RecordedBody::Text(r#"{"prompt":"hi"}"#.to_string())),
+        status: 201,
+        response_headers: vec![("content-type".to_string(), "application/json".to_string())],
+        response_body: RecordedBody::Text(r#"{"reply":"hello"}"#.to_string()),
+    };
+    std::fs::write(&cassette, serde_json::to_string(&exchange).unwrap() + "\n").unwrap();
+
+    let replayer = HttpRecordingProxy::start(&HttpRecording::Replay(cassette), None)
+        .await
+        .expect("start replayer");
+    let resp = client_via(&replayer)
+        .post("https://api.example.test/v1/messages")
+        .body(r#"{"prompt":"hi"}"#)
+        .send()
+        .await
+        .expect("replayed https request");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    assert_eq!(resp.text().await.unwrap(), r#"{"reply":"hello"}"#);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_fails_fast_on_missing_cassette() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let err = HttpRecordingProxy::start(
+        &HttpRecording::Replay(dir.path().join("missing.jsonl")),
+        None,
+    )
+    .await
+    .expect_err("missing cassette");
+    assert!(err.to_string().contains("missing.jsonl"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn record_mode_enforces_network_policy() {
+    let (upstream, hits) = start_mock_upstream().await;
+    let dir = tempfile::tempdir().expect("tempdir");
+    let url = format!("http://{upstream}/v1/items");
+
+    let denied = HttpRecordingProxy::with_upstream(
+        &HttpRecording::Record(dir.path().join("denied.jsonl")),
+        Some(NetworkPolicy::deny_all()),
+        mock_upstream_client(),
+    )
+    .await
+    .expect("start recorder");
+    let resp = client_via(&denied).get(&url).send().await.expect("request");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    let allowed = HttpRecordingProxy::with_upstream(
+        &HttpRecording::Record(dir.path().join("allowed.jsonl")),
+        Some(NetworkPolicy::deny_all().allow(NetworkRule::parse("127.0.0.1").unwrap())),
+        mock_upstream_client(),
+    )
+    .await
+    .expect("start recorder");
+    let resp = client_via(&allowed)
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn record_mode_refuses_internal_upstreams() {
+    let (upstream, hits) = start_mock_upstream().await;
+    let dir = tempfile::tempdir().expect("tempdir");
+
+    let recorder = HttpRecordingProxy::start(
+        &HttpRecording::Record(dir.path().join("cassette.jsonl")),
+        None,
+    )
+    .await
+    .expect("start recorder");
+    let resp = client_via(&recorder)
+        .get(format!("http://{upstream}/v1/items"))
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
@@ -6,6 +6,7 @@
 //! - Observability capture
 //! - Command and workflow parity across sandbox modes
 
+use void_box::observe::slo::{CallbackAlertSink, SloPolicy, SloViolation};
 use void_box::observe::{ObserveConfig, Observer};
 use void_box::sandbox::Sandbox;
 use void_box::workflow::{Workflow, WorkflowExt};
@@ -215,6 +216,63 @@ fn test_observability_logs() {
     assert!(logs.iter().any(|l| l.message.contains("Test error")));
 }
 
+/// Test that a workflow breaking its error-rate SLO alerts with the failed span
+#[tokio::test]
+async fn test_workflow_slo_alert() {
+    let sandbox = Sandbox::mock().build().unwrap();
+    let alerts = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = {
+        let alerts = alerts.clone();
+        CallbackAlertSink::new(move |alert| alerts.lock().unwrap().push(alert.clone()))
+    };
+
+    let workflow = Workflow::define("slo-test")
+        .step("ok", |_ctx| async { Ok(b"ok".to_vec()) })
+        .step("broken", |_ctx| async {
+            Err(void_box::Error::Guest("boom".into()))
+        })
+        .output("ok")
+        .slo(SloPolicy::new().max_error_rate(0.25))
+        .build();
+
+    let result = workflow
+        .observe(ObserveConfig::test().alert_sink(sink))
+        .run_in(sandbox)
+        .await
+        .unwrap();
+
+    let alerts = alerts.lock().unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert!(matches!(
+        alerts[0].violation,
+        SloViolation::ErrorRate {
+            failed_steps: 1,
+            total_steps: 2,
+            ..
+        }
+    ));
+
+    let broken_span = result
+        .traces()
+        .iter()
+        .find(|s| s.name == "step:broken")
+        .unwrap();
+    assert_eq!(
+        alerts[0].span_ids,
+        vec![broken_span.context.span_id.clone()]
+    );
+
+    let workflow_span = result
+        .traces()
+        .iter()
+        .find(|s| s.name == "workflow:slo-test")
+        .unwrap();
+    assert!(workflow_span
+        .events
+        .iter()
+        .any(|e| e.name == "slo.violation"));
+}
+
 // =============================================================================
 // CLAUDE-IN-VOID WORKFLOW (MOCK)
 // =============================================================================
@@ -28,7 +28,8 @@ use std::sync::Arc;
 mod vm_preflight;
 
 use void_box::observe::ObserveConfig;
-use void_box::sandbox::Sandbox;
+use void_box::sandbox::{Sandbox, SandboxBuilder};
+use void_box::secret::{Secret, SecretSource};
 use void_box::vmm::config::VoidBoxConfig;
 use void_box::vmm::MicroVm;
 use void_box::workflow::{Workflow, WorkflowExt};
@@ -52,6 +53,18 @@ fn kvm_artifacts_from_env() -> Option<(PathBuf, Option<PathBuf>)> {
 /// Returns `None` if KVM or artifacts are not available, printing a reason
 /// to stderr so the caller test can early-return without failing.
 fn build_local_kvm_sandbox() -> Option<Arc<Sandbox>> {
+    match local_kvm_builder()?.build() {
+        Ok(sb) => Some(sb),
+        Err(e) => {
+            eprintln!("skipping KVM sandbox test: failed to build sandbox: {e}");
+            None
+        }
+    }
+}
+
+/// A `Sandbox::local()` builder pointed at the KVM artifacts, or `None`
+/// (with a reason on stderr) when they are not available.
+fn local_kvm_builder() -> Option<SandboxBuilder> {
     if let Err(e) = vm_preflight::require_kvm_usable() {
         eprintln!("skipping KVM sandbox test: {e}");
         return None;
@@ -79,14 +92,7 @@ fn build_local_kvm_sandbox() -> Option<Arc<Sandbox>> {
     if let Some(ref initramfs_path) = initramfs {
         builder = builder.initramfs(initramfs_path);
     }
-
-    match builder.build() {
-        Ok(sb) => Some(sb),
-        Err(e) => {
-            eprintln!("skipping KVM sandbox test: failed to build sandbox: {e}");
-            None
-        }
-    }
+    Some(builder)
 }
 
 /// Basic smoke test: boot a real VM and run a trivial command inside it.
@@ -159,6 +165,59 @@ async fn kvm_real_vm_exec_uname() {
     vm.stop().await.expect("failed to stop VM cleanly");
 }
 
+/// SMP boot: a 4-vCPU VM must expose all four CPUs to the guest (x86_64
+/// via the MP table, aarch64 via DTB `/cpus` + PSCI).
+#[tokio::test]
+#[ignore = "requires KVM + kernel/initramfs artifacts; see module docs"]
+async fn kvm_smp_guest_sees_all_vcpus() {
+    let Some((kernel, initramfs)) = kvm_artifacts_from_env() else {
+        eprintln!(
+            "skipping kvm_smp_guest_sees_all_vcpus: \
+             set VOID_BOX_KERNEL and (optionally) VOID_BOX_INITRAMFS"
+        );
+        return;
+    };
+
+    let mut cfg = VoidBoxConfig::new()
+        .memory_mb(256)
+        .vcpus(4)
+        .kernel(&kernel)
+        .enable_vsock(true);
+    if let Some(ref initramfs_path) = initramfs {
+        cfg = cfg.initramfs(initramfs_path);
+    }
+    cfg.validate().expect("invalid VoidBoxConfig for KVM test");
+
+    let mut vm = MicroVm::new(cfg)
+        .await
+        .expect("failed to create KVM-backed MicroVm");
+
+    let output = match vm.exec("nproc", &[]).await {
+        Ok(out) => out,
+        Err(Error::VmNotRunning) => {
+            let serial_bytes = vm.read_serial_output();
+            let console = String::from_utf8_lossy(&serial_bytes);
+            eprintln!("kvm_smp_guest_sees_all_vcpus: VM not running, guest console:\n{console}");
+            return;
+        }
+        Err(Error::Guest(msg)) => {
+            eprintln!("kvm_smp_guest_sees_all_vcpus: guest communication error: {msg}");
+            return;
+        }
+        Err(e) => panic!("failed to execute nproc inside guest: {e}"),
+    };
+
+    assert!(
+        output.success(),
+        "guest nproc failed: exit_code={}, stderr={}",
+        output.exit_code,
+        output.stderr_str()
+    );
+    assert_eq!(output.stdout_str().trim(), "4");
+
+    vm.stop().await.expect("failed to stop VM cleanly");
+}
+
 /// KVM-backed equivalent of the echo parity test:
 /// run `echo hello world` inside a real VM using `Sandbox::local()`.
 #[tokio::test]
@@ -217,6 +276,53 @@ async fn kvm_sandbox_stdin_pipe() {
     assert_eq!(output.stdout, msg);
 }
 
+/// File-delivered secrets go through the guest-agent's `WriteFile`, which
+/// only accepts its write roots: the secret must land and be readable by the
+/// sandbox user through `<NAME>_FILE`.
+#[tokio::test]
+#[ignore = "requires KVM + kernel/initramfs artifacts; see module docs"]
+async fn kvm_sandbox_file_secret() {
+    let Some(builder) = local_kvm_builder() else {
+        return;
+    };
+    let sandbox = match builder
+        .secret_with(Secret::new("API_TOKEN", SecretSource::value("s3cr3t-value")).as_file())
+        .build()
+    {
+        Ok(sb) => sb,
+        Err(e) => {
+            eprintln!("skipping KVM sandbox test: failed to build sandbox: {e}");
+            return;
+        }
+    };
+
+    let output = match sandbox
+        .exec(
+            "sh",
+            &[
+                "-c",
+                r#"cat "$API_TOKEN_FILE"; stat -c %a "$API_TOKEN_FILE""#,
+            ],
+        )
+        .await
+    {
+        Ok(out) => out,
+        Err(Error::VmNotRunning) => {
+            eprintln!("kvm_sandbox_file_secret: VM not running; skipping test");
+            return;
+        }
+        Err(e) => panic!("failed to read file secret in KVM sandbox: {e}"),
+    };
+
+    assert!(
+        output.success(),
+        "reading file secret failed: exit_code={}, stderr={}",
+        output.exit_code,
+        output.stderr_str()
+    );
+    assert_eq!(output.stdout_str(), "s3cr3t-value600\n");
+}
+
 /// KVM-backed equivalent of `test_parity_text_transform` and `test_workflow_pipe`:
 /// use a workflow where step1 echoes, step2 uppercases via `tr`, and pipe output.
 #[tokio::test]
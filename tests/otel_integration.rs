@@ -458,6 +458,7 @@ mod otel_sdk_tests {
                 net_tx_bytes: 2000,
                 procs_running: 3,
                 open_fds: 64,
+                disks: Vec::new(),
             }),
             processes: vec![],
             trace_context: None,
@@ -504,6 +505,7 @@ mod otel_sdk_tests {
                     net_tx_bytes: 0,
                     procs_running: 1,
                     open_fds: 10,
+                    disks: Vec::new(),
                 }),
                 processes: vec![],
                 trace_context: None,
@@ -566,6 +568,7 @@ mod otel_sdk_tests {
                 rss_bytes: 8192,
                 cpu_jiffies: 100,
                 state: 'S',
+                ..Default::default()
             }],
             trace_context: None,
         };
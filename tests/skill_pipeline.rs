@@ -3,14 +3,20 @@
 //! Covers:
 //! - Skill provisioning (all 5 types: agent, file, mcp, cli, remote)
 //! - Remote skill fetching (live + fallback)
+//! - Registry skills resolved from a local registry
+//! - Skill validation and dry runs
+//! - Stage caching
+//! - Fan-out concurrency limits and tracing
 //! - Pipeline composition (single, multi-stage)
 //! - Trading pipeline integration (mock mode)
 //!
 //! All tests run with mock sandbox (no KVM required) unless marked `#[ignore]`.
 
 use void_box::agent_box::VoidBox;
+use void_box::observe::ObserveConfig;
 use void_box::pipeline::Pipeline;
 use void_box::skill::Skill;
+use void_box::skill_registry::{SkillLock, SkillRegistry};
 
 // ─── Skill Provisioning ─────────────────────────────────────────────────────
 
@@ -35,6 +41,53 @@ async fn test_provision_local_skill_file() {
     assert!(!result.agent_result.is_error);
 }
 
+#[tokio::test]
+async fn test_provision_registry_skill_writes_lockfile() {
+    let registry_dir = tempfile::tempdir().unwrap();
+    for (version, content) in [("1.0.0", "# Refactor v1.0"), ("1.2.0", "# Refactor v1.2")] {
+        let dir = registry_dir
+            .path()
+            .join("skills/rust-refactor")
+            .join(version);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("SKILL.md"), content).unwrap();
+    }
+    let lock_path = registry_dir.path().join("skills.lock");
+
+    let ab = VoidBox::new("refactorer")
+        .skill_registry(
+            SkillRegistry::new()
+                .dir(registry_dir.path())
+                .lockfile(&lock_path),
+        )
+        .skills_from_registry(["rust-refactor@^1"])
+        .prompt("Refactor")
+        .mock()
+        .build()
+        .unwrap();
+    assert_eq!(ab.skills[0].name, "rust-refactor");
+
+    let result = ab.run(None, None).await.unwrap();
+    assert!(!result.agent_result.is_error);
+    let lock = SkillLock::load(&lock_path).unwrap();
+    assert_eq!(
+        lock.get("rust-refactor").unwrap().version.to_string(),
+        "1.2.0"
+    );
+}
+
+#[tokio::test]
+async fn test_registry_skill_without_registry_fails() {
+    let ab = VoidBox::new("refactorer")
+        .skills_from_registry(["rust-refactor@^1"])
+        .prompt("Refactor")
+        .mock()
+        .build()
+        .unwrap();
+    let err = ab.run(None, None).await.unwrap_err();
+    assert!(err.to_string().contains("need a skill registry"), "{err}");
+}
+
 #[tokio::test]
 async fn test_provision_mcp_skill() {
     let mcp = Skill::mcp("market-data-mcp")
@@ -241,10 +294,10 @@ async fn test_pipeline_result_accessors() {
         .await
         .unwrap();
 
-    // Mock sandbox produces 0 tokens/cost, but accessors should work
+    // Mock sandbox reports one input and one output token per stage, at no cost
     assert_eq!(result.total_cost_usd(), 0.0);
-    assert_eq!(result.total_input_tokens(), 0);
-    assert_eq!(result.total_output_tokens(), 0);
+    assert_eq!(result.total_input_tokens(), 2);
+    assert_eq!(result.total_output_tokens(), 2);
     assert_eq!(result.total_tool_calls(), 0);
     assert!(result.success());
     assert_eq!(result.stages.len(), 2);
@@ -270,6 +323,157 @@ async fn test_pipeline_len() {
     assert!(!p.is_empty());
 }
 
+#[test]
+fn test_pipeline_dry_run() {
+    let analyst = VoidBox::new("analyst")
+        .skill(Skill::file(
+            "examples/trading_pipeline/skills/financial-data-analysis.md",
+        ))
+        .skill(Skill::mcp("market-data-mcp").args(&["--mode", "mock"]))
+        .prompt("Analyze");
+    let writer = VoidBox::new("writer").prompt("Write");
+    let pipeline = Pipeline::named("dry", analyst).fan_out(vec![
+        writer,
+        VoidBox::new("tainted").skill(Skill::inline("bad", "Hi\u{202e}dlrow")),
+    ]);
+
+    let dry = pipeline.dry_run();
+    assert!(!dry.is_ok());
+    assert_eq!(dry.stages.len(), 2);
+    let analyst = &dry.stages[0][0];
+    assert!(analyst.is_ok());
+    assert_eq!(
+        analyst.mcp_servers,
+        ["market-data-mcp: market-data-mcp --mode mock"]
+    );
+    assert!(analyst
+        .files
+        .contains(&"/workspace/.claude/skills/financial-data-analysis.md".to_string()));
+    assert!(analyst.files.contains(&"/workspace/.mcp.json".to_string()));
+    assert!(!dry.stages[1][1].is_ok());
+    assert!(dry.to_string().contains("skill bad:"), "{dry}");
+}
+
+#[tokio::test]
+async fn test_provision_refuses_invalid_skill() {
+    let ab = VoidBox::new("tainted")
+        .skill(Skill::inline("bad", "Hi\u{200b}there"))
+        .prompt("Go")
+        .mock()
+        .build()
+        .unwrap();
+    let err = ab.run(None, None).await.unwrap_err();
+    assert!(err.to_string().contains("failed validation"), "{err}");
+}
+
+#[tokio::test]
+async fn test_pipeline_stage_cache() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let pipeline = |summary_prompt: &str| {
+        let fetch = VoidBox::new("fetch")
+            .skill(Skill::agent("claude-code"))
+            .prompt("Fetch")
+            .mock()
+            .build()
+            .unwrap();
+        let summarize = VoidBox::new("summarize")
+            .skill(Skill::agent("claude-code"))
+            .prompt(summary_prompt)
+            .mock()
+            .build()
+            .unwrap();
+        Pipeline::named("cached", fetch)
+            .pipe(summarize)
+            .with_cache(cache_dir.path())
+    };
+
+    let first = pipeline("Summarize").run().await.unwrap();
+    assert_eq!((first.cache_hits, first.cache_misses), (0, 2));
+
+    let second = pipeline("Summarize").run().await.unwrap();
+    assert_eq!((second.cache_hits, second.cache_misses), (2, 0));
+    assert!(second.stages.iter().all(|s| s.cached));
+    assert_eq!(second.output, first.output);
+    assert_eq!(second.total_input_tokens(), 0);
+
+    let changed = pipeline("Summarize briefly").run().await.unwrap();
+    assert_eq!((changed.cache_hits, changed.cache_misses), (1, 1));
+    assert!(changed.stages[0].cached);
+    assert!(!changed.stages[1].cached);
+}
+
+fn mock_box(name: &str) -> VoidBox {
+    VoidBox::new(name)
+        .skill(Skill::agent("claude-code"))
+        .prompt(name)
+        .mock()
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_pipeline_max_parallel_limits_fan_out() {
+    // Starting an MCP server waits for it to bind, so these boxes overlap
+    // unless the limit holds them back.
+    let slow_box = |name: &str| {
+        VoidBox::new(name)
+            .skill(Skill::mcp("data-mcp"))
+            .prompt(name)
+            .mock()
+            .build()
+            .unwrap()
+    };
+    let pipeline = Pipeline::named("limited", mock_box("source"))
+        .fan_out(vec![slow_box("a"), slow_box("b"), slow_box("c")])
+        .max_parallel(1);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let result = pipeline.run_with_stage_tx(Some(tx), None).await.unwrap();
+    assert_eq!(result.stages.len(), 4);
+
+    let mut running = 0;
+    while let Ok(event) = rx.try_recv() {
+        match event.event_type.as_str() {
+            "stage.started" => running += 1,
+            "stage.completed" => running -= 1,
+            _ => {}
+        }
+        assert!(running <= 1, "more than one box ran at once");
+    }
+}
+
+#[tokio::test]
+async fn test_observed_fan_out_spans_share_one_trace() {
+    let result = Pipeline::named("traced", mock_box("source"))
+        .fan_out(vec![mock_box("a"), mock_box("b")])
+        .max_parallel(2)
+        .observe(ObserveConfig::test())
+        .run()
+        .await
+        .unwrap();
+
+    let spans = result.traces();
+    let find = |name: &str| spans.iter().find(|s| s.name == name).unwrap();
+    let root = find("pipeline:traced");
+    let fan_out = find("fan_out:[a|b]");
+    assert_eq!(
+        find("stage:source").context.parent_span_id,
+        Some(root.context.span_id.clone())
+    );
+    assert_eq!(
+        fan_out.context.parent_span_id,
+        Some(root.context.span_id.clone())
+    );
+    for name in ["stage:a", "stage:b"] {
+        let stage = find(name);
+        assert_eq!(stage.context.trace_id, root.context.trace_id);
+        assert_eq!(
+            stage.context.parent_span_id,
+            Some(fan_out.context.span_id.clone())
+        );
+        assert!(stage.start_time >= fan_out.start_time);
+    }
+}
+
 // ─── Trading Pipeline Integration ───────────────────────────────────────────
 
 #[tokio::test]
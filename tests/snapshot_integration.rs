@@ -163,6 +163,7 @@ fn snap_config_vcpus(vcpus: usize) -> SnapshotConfig {
         cid: 0, // overwritten by snapshot_internal()
         vsock_mmio_base: 0xd080_0000,
         network: false,
+        virtio_mmio_base: None,
     }
 }
 
@@ -193,6 +194,7 @@ fn snap_config_net() -> SnapshotConfig {
         cid: 0,
         vsock_mmio_base: 0xd080_0000,
         network: true,
+        virtio_mmio_base: None,
     }
 }
 
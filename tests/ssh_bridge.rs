@@ -0,0 +1,397 @@
+//! The SSH bridge driven by the system OpenSSH client.
+//!
+//! A fake [`SshTarget`] stands in for the guest: commands run on the host
+//! with piped stdio, and terminal sessions report the size and `TERM` they
+//! were given. The client logs in exactly as a developer would, through
+//! the `ssh_config` the bridge writes, so key exchange, host key pinning,
+//! public key authentication and the session channel are all exercised.
+//!
+//! Skipped when `ssh` is not installed.
+
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use void_box::ssh::{
+    GuestProcess, ProcessInput, ProcessOutput, ProcessRequest, SshAccess, SshBridge,
+    SshBridgeConfig, SshTarget,
+};
+
+struct HostTarget;
+
+#[async_trait]
+impl SshTarget for HostTarget {
+    async fn spawn(self: Arc<Self>, request: ProcessRequest) -> void_box::Result<GuestProcess> {
+        let (input_tx, mut input_rx) = mpsc::channel(16);
+        let (output_tx, output_rx) = mpsc::channel(16);
+
+        if let Some(size) = request.pty {
+            let term = request
+                .env
+                .iter()
+                .find(|(name, _)| name == "TERM")
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default();
+            tokio::spawn(async move {
+                let banner = format!("pty {}x{} {}\r\n", size.cols, size.rows, term);
+                let _ = output_tx
+                    .send(ProcessOutput::Stdout(banner.into_bytes()))
+                    .await;
+                // Echo until ^D, as a terminal would.
+                while let Some(ProcessInput::Data(data)) = input_rx.recv().await {
+                    let end = data.iter().position(|&b| b == 4);
+                    let echo = data[..end.unwrap_or(data.len())].to_vec();
+                    let _ = output_tx.send(ProcessOutput::Stdout(echo)).await;
+                    if end.is_some() {
+                        break;
+                    }
+                }
+                let _ = output_tx.send(ProcessOutput::Exit(3)).await;
+            });
+            return Ok(GuestProcess {
+                input: input_tx,
+                output: output_rx,
+            });
+        }
+
+        let mut child = tokio::process::Command::new(&request.program)
+            .args(&request.args)
+            .envs(request.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdin = child.stdin.take();
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        tokio::spawn(async move {
+            while let Some(input) = input_rx.recv().await {
+                match input {
+                    ProcessInput::Data(data) => {
+                        if let Some(pipe) = stdin.as_mut() {
+                            let _ = pipe.write_all(&data).await;
+                        }
+                    }
+                    ProcessInput::Eof => stdin = None,
+                    _ => {}
+                }
+            }
+        });
+        tokio::spawn(async move {
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let _ = tokio::join!(stdout.read_to_end(&mut out), stderr.read_to_end(&mut err));
+            let status = child.wait().await.unwrap();
+            let _ = output_tx.send(ProcessOutput::Stdout(out)).await;
+            let _ = output_tx.send(ProcessOutput::Stderr(err)).await;
+            let _ = output_tx
+                .send(ProcessOutput::Exit(status.code().unwrap_or(-1)))
+                .await;
+        });
+        Ok(GuestProcess {
+            input: input_tx,
+            output: output_rx,
+        })
+    }
+
+    /// Host TCP in place of the guest's network.
+    async fn connect(self: Arc<Self>, host: String, port: u16) -> void_box::Result<GuestProcess> {
+        let stream = tokio::net::TcpStream::connect((host.as_str(), port)).await?;
+        let (mut read, mut write) = stream.into_split();
+        let (input_tx, mut input_rx) = mpsc::channel(16);
+        let (output_tx, output_rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(input) = input_rx.recv().await {
+                match input {
+                    ProcessInput::Data(data) if write.write_all(&data).await.is_err() => return,
+                    ProcessInput::Eof => {
+                        let _ = write.shutdown().await;
+                    }
+                    _ => {}
+                }
+            }
+        });
+        tokio::spawn(async move {
+            let mut buf = vec![0; 8192];
+            while let Ok(n @ 1..) = read.read(&mut buf).await {
+                if output_tx
+                    .send(ProcessOutput::Stdout(buf[..n].to_vec()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            let _ = output_tx.send(ProcessOutput::Exit(0)).await;
+        });
+        Ok(GuestProcess {
+            input: input_tx,
+            output: output_rx,
+        })
+    }
+}
+
+fn have_ssh() -> bool {
+    std::process::Command::new("ssh").arg("-V").output().is_ok()
+}
+
+async fn start_bridge(dir: &Path) -> SshBridge {
+    SshBridge::start(
+        Arc::new(HostTarget),
+        SshBridgeConfig::new("test").dir(dir.join("bridge")),
+    )
+    .await
+    .expect("start bridge")
+}
+
+async fn ssh(access: &SshAccess, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = tokio::process::Command::new("ssh")
+        .arg("-F")
+        .arg(&access.config_file)
+        .args(["-o", "BatchMode=yes", "-o", "LogLevel=ERROR"])
+        // Rekey every 256 KiB, so bulk transfers renegotiate keys midway.
+        .args(["-o", "RekeyLimit=256K"])
+        .arg(&access.host_alias)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run ssh");
+    let mut pipe = child.stdin.take().unwrap();
+    pipe.write_all(stdin).await.unwrap();
+    drop(pipe);
+    tokio::time::timeout(std::time::Duration::from_secs(30), child.wait_with_output())
+        .await
+        .expect("ssh timed out")
+        .unwrap()
+}
+
+fn audit_events(access: &SshAccess) -> Vec<String> {
+    std::fs::read_to_string(&access.audit_log)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["event"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn exec_pipes_stdio_and_exit_status() {
+    if !have_ssh() {
+        eprintln!("skipping: ssh not installed");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = start_bridge(dir.path()).await;
+    let access = bridge.access().clone();
+
+    let output = ssh(
+        &access,
+        &["tr a-z A-Z; echo oops >&2; exit 7"],
+        b"hello bridge\n",
+    )
+    .await;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "HELLO BRIDGE\n",
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "oops\n");
+    assert_eq!(output.status.code(), Some(7));
+
+    // Bulk output crosses many packets and window adjustments.
+    let output = ssh(&access, &["head -c 3000000 /dev/zero"], b"").await;
+    assert_eq!(output.stdout.len(), 3_000_000);
+    assert_eq!(output.status.code(), Some(0));
+
+    let events = audit_events(&access);
+    assert_eq!(events[0], "listening");
+    for expected in [
+        "connected",
+        "auth_accepted",
+        "session_started",
+        "session_ended",
+    ] {
+        assert!(events.iter().any(|e| e == expected), "{events:?}");
+    }
+}
+
+#[tokio::test]
+async fn terminal_sessions_get_the_client_size_and_term() {
+    if !have_ssh() {
+        eprintln!("skipping: ssh not installed");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = start_bridge(dir.path()).await;
+    let access = bridge.access().clone();
+
+    let mut child = tokio::process::Command::new("ssh")
+        .arg("-F")
+        .arg(&access.config_file)
+        .args(["-tt", "-o", "BatchMode=yes", "-o", "LogLevel=ERROR"])
+        .arg(&access.host_alias)
+        .env("TERM", "xterm-test")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"ping\x04").await.unwrap();
+    let output = tokio::time::timeout(std::time::Duration::from_secs(30), child.wait_with_output())
+        .await
+        .expect("ssh timed out")
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("xterm-test"), "stdout: {stdout}");
+    assert!(stdout.contains("ping"), "stdout: {stdout}");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn forwarded_connections_reach_the_target() {
+    if !have_ssh() {
+        eprintln!("skipping: ssh not installed");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = start_bridge(dir.path()).await;
+    let access = bridge.access().clone();
+
+    // An echo server stands in for a port inside the guest.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (mut read, mut write) = stream.split();
+        let _ = tokio::io::copy(&mut read, &mut write).await;
+    });
+
+    // `-W` is stdio forwarding: one direct-tcpip channel, as VS Code opens
+    // to reach its server.
+    let target = format!("127.0.0.1:{port}");
+    let output = ssh(&access, &["-W", &target], b"forwarded\n").await;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "forwarded\n",
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let events = audit_events(&access);
+    assert!(events.iter().any(|e| e == "forward_started"), "{events:?}");
+}
+
+#[tokio::test]
+async fn other_keys_are_refused_and_stop_removes_the_keys() {
+    if !have_ssh() {
+        eprintln!("skipping: ssh not installed");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = start_bridge(dir.path()).await;
+    let access = bridge.access().clone();
+    assert_eq!(SshAccess::load(&dir.path().join("bridge")).unwrap(), access);
+
+    // The key on disk is one OpenSSH itself can read.
+    let public = std::process::Command::new("ssh-keygen")
+        .arg("-y")
+        .arg("-f")
+        .arg(&access.identity_file)
+        .output();
+    if let Ok(public) = public {
+        let expected = std::fs::read_to_string(dir.path().join("bridge/id_ed25519.pub")).unwrap();
+        assert!(expected.starts_with(String::from_utf8_lossy(&public.stdout).trim()));
+    }
+
+    // A second bridge's key does not open this one.
+    let other_dir = tempfile::tempdir().unwrap();
+    let other = start_bridge(other_dir.path()).await;
+    let output = tokio::process::Command::new("ssh")
+        .args(["-F", "none", "-o", "BatchMode=yes", "-o", "LogLevel=ERROR"])
+        .args([
+            "-o",
+            "IdentitiesOnly=yes",
+            "-o",
+            "StrictHostKeyChecking=yes",
+        ])
+        .arg("-o")
+        .arg(format!(
+            "UserKnownHostsFile={}",
+            access.known_hosts_file.display()
+        ))
+        .arg("-i")
+        .arg(&other.access().identity_file)
+        .arg("-p")
+        .arg(access.port.to_string())
+        .arg(format!("{}@{}", access.user, access.host))
+        .arg("true")
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(255));
+
+    bridge.stop();
+    assert!(!access.identity_file.exists());
+    assert!(!access.config_file.exists());
+    assert!(access.audit_log.exists());
+    let events = audit_events(&access);
+    assert!(events.iter().any(|e| e == "auth_rejected"), "{events:?}");
+    assert_eq!(events.last().map(String::as_str), Some("stopped"));
+}
+
+#[tokio::test]
+async fn broken_clients_do_not_take_the_bridge_down() {
+    if !have_ssh() {
+        eprintln!("skipping: ssh not installed");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = start_bridge(dir.path()).await;
+    let access = bridge.access().clone();
+
+    // A client that stops halfway through its first packet.
+    let mut stream = tokio::net::TcpStream::connect(bridge.local_addr())
+        .await
+        .unwrap();
+    let mut banner = [0u8; 8];
+    stream.read_exact(&mut banner).await.unwrap();
+    assert_eq!(&banner, b"SSH-2.0-");
+    stream
+        .write_all(b"SSH-2.0-broken\r\n\x00\x00\x01\x00\x0a\x14")
+        .await
+        .unwrap();
+    drop(stream);
+
+    // A client that sends a packet longer than any the server accepts.
+    let mut stream = tokio::net::TcpStream::connect(bridge.local_addr())
+        .await
+        .unwrap();
+    stream
+        .write_all(b"SSH-2.0-broken\r\n\xff\xff\xff\xff\x0a\x14")
+        .await
+        .unwrap();
+    let mut rest = Vec::new();
+    let _ = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        stream.read_to_end(&mut rest),
+    )
+    .await
+    .expect("server hangs up");
+
+    let output = ssh(&access, &["echo still here"], b"").await;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "still here\n");
+    let events = audit_events(&access);
+    assert!(
+        events.iter().filter(|e| *e == "disconnected").count() >= 2,
+        "{events:?}"
+    );
+}
@@ -23,12 +23,13 @@
 //! ```
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 use void_box::guest::protocol::{
     ExecResponse, Message, MessageType, ProcessMetrics, SystemMetrics, TelemetryBatch,
     TelemetrySubscribeRequest,
 };
-use void_box::observe::telemetry::TelemetryAggregator;
+use void_box::observe::telemetry::{TelemetryAggregator, TelemetryStats};
 use void_box::observe::Observer;
 
 // =============================================================================
@@ -129,6 +130,7 @@ fn subscribe_telemetry_custom_opts_in_message_frame() {
     let opts = TelemetrySubscribeRequest {
         interval_ms: 500,
         include_kernel_threads: true,
+        ..Default::default()
     };
     let payload = serde_json::to_vec(&opts).unwrap();
 
@@ -199,6 +201,7 @@ fn telemetry_batch_no_system_metrics() {
             rss_bytes: 4096,
             cpu_jiffies: 100,
             state: 'S',
+            ..Default::default()
         }],
         trace_context: None,
     };
@@ -224,6 +227,7 @@ fn telemetry_batch_empty_processes() {
             net_tx_bytes: 0,
             procs_running: 1,
             open_fds: 10,
+            disks: Vec::new(),
         }),
         processes: vec![],
         trace_context: None,
@@ -338,6 +342,72 @@ fn aggregator_latest_batch() {
     assert_eq!(latest.seq, 5);
 }
 
+/// Verify window() selects by guest timestamp, and stats() summarizes it.
+#[test]
+fn aggregator_window_and_stats() {
+    let observer = Observer::test();
+    let agg = TelemetryAggregator::new(observer, 42);
+    assert!(agg.window(Duration::from_secs(60)).is_empty());
+    assert_eq!(
+        agg.stats(Duration::from_secs(60)),
+        TelemetryStats::default()
+    );
+
+    // Batches are 2s apart: seq 0..10 spans 18s of guest time.
+    for seq in 0..10 {
+        let mut batch = make_sample_batch(seq);
+        if let Some(ref mut sys) = batch.system {
+            sys.memory_used_bytes = if seq == 3 { 900 << 20 } else { 512 << 20 };
+        }
+        agg.ingest(&batch);
+    }
+
+    let recent: Vec<u64> = agg
+        .window(Duration::from_secs(4))
+        .iter()
+        .map(|b| b.seq)
+        .collect();
+    assert_eq!(recent, vec![7, 8, 9]);
+    assert_eq!(agg.window(Duration::from_secs(3600)).len(), 10);
+
+    let stats = agg.stats(Duration::from_secs(4));
+    assert_eq!(stats.batches, 3);
+    assert_eq!(stats.cpu_max_percent, 34.5);
+    assert!((stats.cpu_avg_percent - 33.5).abs() < 1e-9);
+    assert_eq!(stats.memory_high_water_bytes, 512 << 20);
+    assert_eq!(stats.first_timestamp_ms, Some(1700000000000 + 14_000));
+
+    let all = agg.stats(Duration::from_secs(3600));
+    assert_eq!(all.memory_high_water_bytes, 900 << 20);
+    assert_eq!(all.process_rss_high_water_bytes, 1024 * 1024);
+}
+
+/// Verify history is capped and to_json() dumps it with its summary.
+#[test]
+fn aggregator_history_capacity_and_json() {
+    let observer = Observer::test();
+    let agg = TelemetryAggregator::new(observer, 42).with_history_capacity(4);
+    for seq in 0..6 {
+        agg.ingest(&make_sample_batch(seq));
+    }
+
+    let history = agg.history();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[0].seq, 2);
+    assert_eq!(agg.latest_batch().unwrap().seq, 5);
+    assert!(agg
+        .process_samples(42, 0)
+        .iter()
+        .all(|s| s.timestamp_ms >= history[0].timestamp_ms));
+
+    let json = agg.to_json();
+    assert_eq!(json["cid"], 42);
+    assert_eq!(json["stats"]["batches"], 4);
+    assert_eq!(json["stats"]["cpu_max_percent"], 30.5);
+    assert_eq!(json["batches"].as_array().unwrap().len(), 4);
+    assert_eq!(json["batches"][3]["seq"], 5);
+}
+
 /// Verify CID is correctly stored.
 #[test]
 fn aggregator_cid() {
@@ -389,6 +459,7 @@ fn aggregator_ingest_no_system_metrics() {
             rss_bytes: 8192,
             cpu_jiffies: 50,
             state: 'S',
+            ..Default::default()
         }],
         trace_context: None,
     };
@@ -730,6 +801,7 @@ async fn kvm_telemetry_with_kernel_threads() {
     let opts = TelemetrySubscribeRequest {
         interval_ms: 1000,
         include_kernel_threads: true,
+        ..Default::default()
     };
     let telemetry_observer = Observer::test();
     match vm.start_telemetry(telemetry_observer, opts).await {
@@ -802,6 +874,7 @@ fn make_sample_batch(seq: u64) -> TelemetryBatch {
             net_tx_bytes: 2000 * (seq + 1),
             procs_running: 3,
             open_fds: 128,
+            disks: Vec::new(),
         }),
         processes: vec![
             ProcessMetrics {
@@ -810,6 +883,7 @@ fn make_sample_batch(seq: u64) -> TelemetryBatch {
                 rss_bytes: 4096,
                 cpu_jiffies: 100 + seq * 10,
                 state: 'S',
+                ..Default::default()
             },
             ProcessMetrics {
                 pid: 42,
@@ -817,6 +891,7 @@ fn make_sample_batch(seq: u64) -> TelemetryBatch {
                 rss_bytes: 1024 * 1024,
                 cpu_jiffies: 500 + seq * 20,
                 state: 'R',
+                ..Default::default()
             },
         ],
         trace_context: None,
@@ -0,0 +1,105 @@
+#![cfg(target_os = "macos")]
+//! virtiofs mount integration tests for the VZ backend.
+//!
+//! Boots a VM with several [`MountConfig`]s, one virtiofs device each, and
+//! checks the guest sees every tag at its path with the right mode.
+//!
+//! ## Prerequisites
+//!
+//! ```bash
+//! export VOID_BOX_KERNEL=/tmp/void-box-kernel
+//! export VOID_BOX_INITRAMFS=/tmp/void-box-test-rootfs.cpio.gz
+//!
+//! cargo test --test vz_virtiofs --features vz-tests -- --test-threads=1
+//! ```
+
+use std::path::Path;
+
+use void_box::backend::vz::VzBackend;
+use void_box::backend::{BackendConfig, MountConfig, VmmBackend};
+
+fn mount(host_dir: &Path, guest_path: &str, read_only: bool) -> MountConfig {
+    MountConfig {
+        host_path: host_dir.to_string_lossy().into_owned(),
+        guest_path: guest_path.to_string(),
+        read_only,
+    }
+}
+
+fn config(mounts: Vec<MountConfig>) -> BackendConfig {
+    let kernel = std::env::var("VOID_BOX_KERNEL").expect("vz-tests needs VOID_BOX_KERNEL");
+    let initramfs = std::env::var("VOID_BOX_INITRAMFS").expect("vz-tests needs VOID_BOX_INITRAMFS");
+    let mut config = BackendConfig::minimal(kernel, 256, 1).initramfs(initramfs);
+    config.mounts = mounts;
+    config
+}
+
+async fn sh(backend: &VzBackend, script: &str) -> void_box::ExecOutput {
+    backend
+        .exec("sh", &["-c", script], &[], &[], None, Some(30))
+        .await
+        .expect("guest exec")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_mount_is_shared_with_its_mode() {
+    let src = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    std::fs::write(src.path().join("input.txt"), "from host\n").unwrap();
+
+    let mut backend = VzBackend::new();
+    backend
+        .start(config(vec![
+            mount(src.path(), "/workspace/src", true),
+            mount(out.path(), "/workspace/out", false),
+        ]))
+        .await
+        .unwrap();
+
+    let read = sh(&backend, "cat /workspace/src/input.txt").await;
+    assert!(read.success(), "{}", read.stderr_str());
+    assert_eq!(read.stdout_str(), "from host\n");
+
+    let denied = sh(&backend, "echo nope > /workspace/src/output.txt").await;
+    assert!(!denied.success());
+    assert!(!src.path().join("output.txt").exists());
+
+    let copied = sh(
+        &backend,
+        "mkdir -p /workspace/out/a/b && cp /workspace/src/input.txt /workspace/out/a/b/",
+    )
+    .await;
+    assert!(copied.success(), "{}", copied.stderr_str());
+    assert_eq!(
+        std::fs::read_to_string(out.path().join("a/b/input.txt")).unwrap(),
+        "from host\n"
+    );
+
+    let mounts = sh(&backend, "grep virtiofs /proc/mounts").await;
+    let mounts = mounts.stdout_str();
+    assert!(
+        mounts.contains("mount0 /workspace/src virtiofs ro"),
+        "{mounts}"
+    );
+    assert!(
+        mounts.contains("mount1 /workspace/out virtiofs rw"),
+        "{mounts}"
+    );
+
+    backend.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missing_host_directories_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut backend = VzBackend::new();
+    let err = backend
+        .start(config(vec![mount(
+            &dir.path().join("missing"),
+            "/workspace/src",
+            true,
+        )]))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, void_box::Error::Config(_)), "{err}");
+}
@@ -0,0 +1,31 @@
+#![no_main]
+
+//! Feeds byte streams to the guest-agent's frame reader, both before and
+//! after authentication, until it closes or rejects the connection.
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use void_box_protocol::MessageType;
+
+#[allow(dead_code)]
+#[path = "../../../guest-agent/src/conn.rs"]
+mod conn;
+
+fuzz_target!(|data: &[u8]| {
+    for authenticated in [false, true] {
+        let mut reader = Cursor::new(data);
+        while let Ok(incoming) = conn::read_request(&mut reader, authenticated) {
+            match incoming {
+                conn::Incoming::Closed => break,
+                conn::Incoming::Unknown(msg_type) => {
+                    assert!(MessageType::try_from(msg_type).is_err());
+                }
+                conn::Incoming::Request(frame) => {
+                    assert!(authenticated || frame.message_type == MessageType::Ping);
+                    let _ = frame.body();
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use void_box_protocol::fuzzing::check_deserialize;
+
+fuzz_target!(|data: &[u8]| check_deserialize(data));
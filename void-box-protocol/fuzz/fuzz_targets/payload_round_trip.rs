@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use void_box_protocol::fuzzing::{check_payload_bytes, check_payload_round_trips, FuzzRng};
+
+fuzz_target!(|data: &[u8]| {
+    check_payload_bytes(data);
+    check_payload_round_trips(&mut FuzzRng::from_bytes(data));
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use void_box_protocol::fuzzing::{check_read_from_sync, mutate_frame, FuzzRng};
+
+fuzz_target!(|data: &[u8]| {
+    check_read_from_sync(data);
+    // Also damage the input the way a broken peer would, so truncated and
+    // oversized frames are reached without waiting for the fuzzer to find
+    // a valid header first.
+    let mut rng = FuzzRng::from_bytes(data);
+    check_read_from_sync(&mutate_frame(&mut rng, data));
+});
@@ -0,0 +1,666 @@
+//! Generators and property checks for fuzzing the wire protocol.
+//!
+//! Shared by the cargo-fuzz targets in `void-box-protocol/fuzz`, the
+//! property tests below, and the guest-agent's connection tests. Enabled by
+//! the `fuzzing` feature.
+//!
+//! [`arbitrary`] builds a random instance of any payload type by driving its
+//! `Deserialize` impl from a seeded [`FuzzRng`], so new payload structs are
+//! covered as soon as they are added to [`for_each_payload!`]. The `check_*`
+//! functions take raw fuzzer input and panic when a property is violated.
+
+use std::io::Cursor;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::Serialize;
+
+use crate::*;
+
+/// Small, seedable xorshift generator; reproducible from the seed alone.
+#[derive(Debug, Clone)]
+pub struct FuzzRng(u64);
+
+impl FuzzRng {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift.
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Seed from fuzzer input.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let seed = data.iter().fold(0xCBF2_9CE4_8422_2325u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        });
+        Self::new(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `0..n`; `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, one_in: usize) -> bool {
+        self.below(one_in) == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    /// An integer that is often an edge value.
+    fn edge_u64(&mut self) -> u64 {
+        match self.below(6) {
+            0 => 0,
+            1 => u64::MAX,
+            2 => self.below(256) as u64,
+            _ => self.next_u64() >> self.below(64),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', '/', '.', '-', ' ', '"', '\\', '\n', 'é', '🦀',
+        ];
+        (0..self.below(16))
+            .map(|_| ALPHABET[self.below(ALPHABET.len())])
+            .collect()
+    }
+}
+
+/// Every message type the protocol defines.
+pub fn message_types() -> Vec<MessageType> {
+    (0..=u8::MAX)
+        .filter_map(|byte| MessageType::try_from(byte).ok())
+        .collect()
+}
+
+/// A random well-formed message with a payload of up to 256 bytes.
+pub fn arbitrary_message(rng: &mut FuzzRng) -> Message {
+    let types = message_types();
+    let len = rng.below(257);
+    Message {
+        msg_type: types[rng.below(types.len())],
+        payload: rng.bytes(len),
+    }
+}
+
+/// `frame` damaged the way a broken or hostile peer would: truncated,
+/// with a length field past [`MAX_MESSAGE_SIZE`] or past the data, with an
+/// unknown type byte, or with trailing garbage.
+pub fn mutate_frame(rng: &mut FuzzRng, frame: &[u8]) -> Vec<u8> {
+    let mut out = frame.to_vec();
+    match rng.below(5) {
+        0 => out.truncate(rng.below(frame.len() + 1)),
+        1 if out.len() >= 4 => {
+            let oversized = MAX_MESSAGE_SIZE as u64 + 1 + rng.edge_u64() % (u32::MAX as u64);
+            let oversized = oversized.min(u32::MAX as u64) as u32;
+            out[..4].copy_from_slice(&oversized.to_le_bytes());
+        }
+        2 if out.len() >= 4 => {
+            let len = u32::from_le_bytes([out[0], out[1], out[2], out[3]]);
+            let longer = len.saturating_add(1 + rng.below(64) as u32);
+            out[..4].copy_from_slice(&longer.to_le_bytes());
+        }
+        3 if out.len() >= HEADER_SIZE => {
+            out[4] = [0, 50, 200, u8::MAX][rng.below(4)];
+        }
+        _ => {
+            let extra = rng.below(16);
+            out.extend(rng.bytes(extra));
+        }
+    }
+    out
+}
+
+/// A random instance of `T`, or `None` for the rare draws `T` rejects
+/// (for example a value a custom `Deserialize` validates).
+pub fn arbitrary<T: DeserializeOwned>(rng: &mut FuzzRng) -> Option<T> {
+    T::deserialize(RngDeserializer { rng, depth: 0 }).ok()
+}
+
+/// Check [`Message::deserialize`] on arbitrary bytes: it never panics, and
+/// whatever it accepts serializes back to the bytes it read.
+pub fn check_deserialize(data: &[u8]) {
+    let Ok(message) = Message::deserialize(data) else {
+        return;
+    };
+    let consumed = HEADER_SIZE + message.payload.len();
+    assert!(message.payload.len() <= MAX_MESSAGE_SIZE);
+    assert_eq!(message.serialize(), &data[..consumed]);
+}
+
+/// Check [`Message::read_from_sync`] on arbitrary bytes: it agrees with
+/// [`Message::deserialize`] frame by frame, consumes exactly one frame per
+/// message, and rejects oversized lengths before reading the payload.
+pub fn check_read_from_sync(data: &[u8]) {
+    let mut reader = Cursor::new(data);
+    loop {
+        let start = reader.position() as usize;
+        match Message::read_from_sync(&mut reader) {
+            Ok(message) => {
+                let end = reader.position() as usize;
+                assert_eq!(end - start, HEADER_SIZE + message.payload.len());
+                let expected = Message::deserialize(&data[start..]).expect("frame accepted");
+                assert_eq!(expected.msg_type, message.msg_type);
+                assert_eq!(expected.payload, message.payload);
+            }
+            Err(ProtocolError::PayloadTooLarge { size, max }) => {
+                assert!(size > max);
+                assert_eq!(reader.position() as usize, start + HEADER_SIZE);
+                return;
+            }
+            Err(_) => {
+                assert!(Message::deserialize(&data[start..]).is_err());
+                return;
+            }
+        }
+    }
+}
+
+/// Check every payload type on arbitrary bytes: whatever parses survives a
+/// serialize-parse round trip unchanged, framed or not.
+pub fn check_payload_bytes(data: &[u8]) {
+    macro_rules! check {
+        ($($ty:ty),*) => {$(
+            if let Ok(payload) = serde_json::from_slice::<$ty>(data) {
+                assert_round_trip(&payload);
+            }
+        )*};
+    }
+    for_each_payload!(check);
+}
+
+/// Check every payload type with instances drawn from `rng`.
+pub fn check_payload_round_trips(rng: &mut FuzzRng) {
+    macro_rules! check {
+        ($($ty:ty),*) => {$(
+            if let Some(payload) = arbitrary::<$ty>(rng) {
+                assert_round_trip(&payload);
+            }
+        )*};
+    }
+    for_each_payload!(check);
+}
+
+fn assert_round_trip<T: Serialize + DeserializeOwned>(payload: &T) {
+    let json = serde_json::to_value(payload).expect("payload serializes");
+    let bytes = serde_json::to_vec(payload).expect("payload serializes");
+    let framed = Message {
+        msg_type: MessageType::ExecRequest,
+        payload: bytes,
+    }
+    .serialize();
+    let unframed = Message::deserialize(&framed).expect("frame parses").payload;
+    let reparsed: T = serde_json::from_slice(&unframed).unwrap_or_else(|e| {
+        panic!(
+            "{} does not parse its own JSON {json}: {e}",
+            std::any::type_name::<T>()
+        )
+    });
+    let again = serde_json::to_value(&reparsed).expect("payload serializes");
+    assert!(
+        same_json(&again, &json),
+        "{} changed across a round trip: {json} became {again}",
+        std::any::type_name::<T>()
+    );
+}
+
+/// Structural equality, allowing floats to differ in the last bit:
+/// serde_json's default float parser is not exactly round-tripping.
+fn same_json(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) if x.is_f64() || y.is_f64() => {
+            let (x, y) = (
+                x.as_f64().unwrap_or(f64::NAN),
+                y.as_f64().unwrap_or(f64::NAN),
+            );
+            x == y || (x - y).abs() <= f64::EPSILON * x.abs().max(y.abs()) * 2.0
+        }
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| same_json(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(key, x)| y.get(key).is_some_and(|y| same_json(x, y)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Invoke `$m!` with every payload type that crosses the wire.
+macro_rules! for_each_payload {
+    ($m:ident) => {
+        $m!(
+            ExecRequest,
+            ExecResponse,
+            ExecOutputChunk,
+            SyncClockRequest,
+            SyncClockResponse,
+            CreateUserRequest,
+            CreateUserResponse,
+            ExecPolicy,
+            ExecDenial,
+            SetExecPolicyRequest,
+            SetExecPolicyResponse,
+            EnterReadOnlyResponse,
+            WriteFileRequest,
+            WriteFileResponse,
+            WriteFileChunkRequest,
+            WriteFileChunkResponse,
+            WriteFileFinalizeRequest,
+            FsDiffRequest,
+            FsDiffResponse,
+            ExportWorkspaceRequest,
+            ExportWorkspaceResponse,
+            ShutdownRequest,
+            ShutdownAck,
+            SignalExecRequest,
+            SignalExecResponse,
+            ExecStdinChunk,
+            ExecStdinClose,
+            ExecStdinResponse,
+            AttachExecRequest,
+            MkdirPRequest,
+            MkdirPResponse,
+            ReadFileRequest,
+            ReadFileResponse,
+            FileStatRequest,
+            FileStatResponse,
+            SymlinkRequest,
+            SymlinkResponse,
+            ChmodRequest,
+            ChmodResponse,
+            ReadLinkRequest,
+            ReadLinkResponse,
+            HashFilesRequest,
+            HashFilesResponse,
+            FileHash,
+            SysInfoResponse,
+            TelemetryControlRequest,
+            TelemetryControlResponse,
+            PtyOpenRequest,
+            PtyOpenedResponse,
+            PtyResizeRequest,
+            PtyClosedResponse,
+            TelemetryBatch,
+            TelemetrySubscribeRequest
+        )
+    };
+}
+use for_each_payload;
+
+// ---------------------------------------------------------------------------
+// Random-value deserializer
+// ---------------------------------------------------------------------------
+
+/// Collections stop growing below this nesting depth.
+const MAX_DEPTH: u32 = 4;
+
+struct RngDeserializer<'a> {
+    rng: &'a mut FuzzRng,
+    depth: u32,
+}
+
+impl RngDeserializer<'_> {
+    fn nested(&mut self) -> RngDeserializer<'_> {
+        RngDeserializer {
+            rng: self.rng,
+            depth: self.depth + 1,
+        }
+    }
+
+    fn collection_len(&mut self) -> usize {
+        if self.depth >= MAX_DEPTH {
+            0
+        } else {
+            self.rng.below(4)
+        }
+    }
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident => $visit:ident($ty:ty)),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            visitor.$visit(self.rng.edge_u64() as $ty)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for RngDeserializer<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.rng.below(3) {
+            0 => visitor.visit_bool(self.rng.chance(2)),
+            1 => visitor.visit_u64(self.rng.edge_u64()),
+            _ => visitor.visit_string(self.rng.string()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_bool(self.rng.chance(2))
+    }
+
+    deserialize_int! {
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f32((self.rng.next_u64() % 1_000_000) as f32 / 7.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f64((self.rng.next_u64() % 1_000_000_000) as f64 / 7.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_char(self.rng.string().chars().next().unwrap_or('x'))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.rng.string())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.rng.string())
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let len = self.rng.below(32);
+        visitor.visit_byte_buf(self.rng.bytes(len))
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.rng.chance(2) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        let len = self.collection_len();
+        visitor.visit_seq(RngSeq {
+            de: self.nested(),
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(RngSeq {
+            de: self.nested(),
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        let len = self.collection_len();
+        visitor.visit_map(RngMap {
+            de: self.nested(),
+            fields: None,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(RngMap {
+            de: self.nested(),
+            fields: Some(fields),
+            remaining: fields.len(),
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = variants[self.rng.below(variants.len())];
+        visitor.visit_enum(RngEnum {
+            de: self.nested(),
+            variant,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct RngSeq<'a> {
+    de: RngDeserializer<'a>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for RngSeq<'_> {
+    type Error = de::value::Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(self.de.nested()).map(Some)
+    }
+}
+
+struct RngMap<'a> {
+    de: RngDeserializer<'a>,
+    /// A struct's fields, handed out in order; `None` for a map, whose keys
+    /// are drawn at random.
+    fields: Option<&'static [&'static str]>,
+    remaining: usize,
+}
+
+impl<'de> de::MapAccess<'de> for RngMap<'_> {
+    type Error = de::value::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        match self.fields {
+            Some(fields) => {
+                let field = fields[fields.len() - 1 - self.remaining];
+                seed.deserialize(field.into_deserializer()).map(Some)
+            }
+            None => seed.deserialize(self.de.nested()).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(self.de.nested())
+    }
+}
+
+struct RngEnum<'a> {
+    de: RngDeserializer<'a>,
+    variant: &'static str,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for RngEnum<'a> {
+    type Error = de::value::Error;
+    type Variant = RngDeserializer<'a>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.de))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for RngDeserializer<'_> {
+    type Error = de::value::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASES: u64 = 512;
+
+    #[test]
+    fn test_frames_survive_damage() {
+        for seed in 0..CASES {
+            let mut rng = FuzzRng::new(seed);
+            let frame = arbitrary_message(&mut rng).serialize();
+            check_deserialize(&frame);
+            check_read_from_sync(&frame);
+
+            let damaged = mutate_frame(&mut rng, &frame);
+            check_deserialize(&damaged);
+            check_read_from_sync(&damaged);
+            let len = rng.below(64);
+            let noise = rng.bytes(len);
+            check_deserialize(&noise);
+            check_read_from_sync(&noise);
+        }
+    }
+
+    #[test]
+    fn test_streams_of_frames_decode_in_order() {
+        let mut rng = FuzzRng::new(7);
+        let messages: Vec<Message> = (0..32).map(|_| arbitrary_message(&mut rng)).collect();
+        let stream: Vec<u8> = messages.iter().flat_map(Message::serialize).collect();
+        check_read_from_sync(&stream);
+
+        let mut reader = Cursor::new(&stream);
+        for message in &messages {
+            let decoded = Message::read_from_sync(&mut reader).unwrap();
+            assert_eq!(decoded.msg_type, message.msg_type);
+            assert_eq!(decoded.payload, message.payload);
+        }
+        assert!(Message::read_from_sync(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_payloads_round_trip() {
+        for seed in 0..CASES {
+            check_payload_round_trips(&mut FuzzRng::new(seed));
+        }
+        // The generator reaches nested and optional fields.
+        let requests: Vec<ExecRequest> = (0..64)
+            .filter_map(|seed| arbitrary(&mut FuzzRng::new(seed)))
+            .collect();
+        assert!(requests.len() > 32);
+        assert!(requests.iter().any(|r| r.policy.is_some()));
+        assert!(requests.iter().any(|r| !r.env.is_empty()));
+    }
+
+    #[test]
+    fn test_payload_bytes_from_mutated_json() {
+        let mut rng = FuzzRng::new(11);
+        for _ in 0..CASES {
+            let Some(request) = arbitrary::<ExecRequest>(&mut rng) else {
+                continue;
+            };
+            let json = serde_json::to_vec(&request).unwrap();
+            check_payload_bytes(&json);
+            check_payload_bytes(&mutate_frame(&mut rng, &json));
+        }
+    }
+}
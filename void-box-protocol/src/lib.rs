@@ -21,6 +21,7 @@
 use secrecy::{ExposeSecret, SecretBox};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::time::Duration;
 
 // ---------------------------------------------------------------------------
 // SessionSecret
@@ -127,6 +128,42 @@ pub const HEADER_SIZE: usize = 5;
 /// can send `0xFFFFFFFF` as the length and force a 4 GB allocation.
 pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
 
+/// Raw data bytes carried by one [`WriteFileChunkRequest`] (4 MB).
+///
+/// `Vec<u8>` is JSON-encoded as a number array, which inflates each byte to
+/// at most four characters; 4 MB of data therefore stays well under
+/// [`MAX_MESSAGE_SIZE`] regardless of content.
+pub const WRITE_FILE_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Most data bytes carried by one [`ExecStdinChunk`] (1 MB); larger host
+/// writes are split.
+pub const EXEC_STDIN_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of changes carried by one [`FsDiffResponse`].
+///
+/// Paths are bounded by `PATH_MAX` (4 KB), so this keeps a worst-case
+/// response under [`MAX_MESSAGE_SIZE`]; anything beyond it sets
+/// [`FsDiffResponse::truncated`].
+pub const MAX_FS_DIFF_CHANGES: usize = 10_000;
+
+/// Most paths one [`HashFilesRequest`] may name.
+pub const MAX_HASH_FILES: usize = 4096;
+
+/// Most test cases one [`ExecResponse::test_results`] carries; cases
+/// reported past it are dropped.
+pub const MAX_TEST_RESULTS: usize = 10_000;
+
+/// Environment variable naming the file an exec's test adapter appends
+/// [`TestCaseResult`] JSON lines to. The guest-agent sets it for every exec
+/// and returns what was written in [`ExecResponse::test_results`].
+pub const TEST_REPORT_ENV: &str = "VOIDBOX_TEST_REPORT";
+
+/// Archive bytes carried by one `ExportWorkspaceChunk` frame (1 MB).
+///
+/// Chunks are sent as raw bytes rather than JSON, so this only bounds how
+/// much the guest buffers between writes.
+pub const EXPORT_CHUNK_SIZE: usize = 1024 * 1024;
+
 /// Protocol version for host↔guest wire format negotiation.
 ///
 /// The version is exchanged during the Ping/Pong handshake:
@@ -260,7 +297,8 @@ pub enum MessageType {
     Ping = 3,
     /// Pong response (health check)
     Pong = 4,
-    /// Shutdown request
+    /// Graceful shutdown request (see [`ShutdownRequest`]); answered with
+    /// `ShutdownAck` before the guest powers off.
     Shutdown = 5,
     /// File transfer request
     FileTransfer = 6,
@@ -306,6 +344,81 @@ pub enum MessageType {
     PtyClose = 26,
     /// Confirms that a PTY session has been closed and reports its exit code.
     PtyClosed = 27,
+    /// Writes one chunk of a large file at a byte offset (see [`WriteFileChunkRequest`]).
+    WriteFileChunk = 28,
+    /// Response to WriteFileChunk.
+    WriteFileChunkResponse = 29,
+    /// Commits a chunked transfer into place; answered with `WriteFileResponse`.
+    WriteFileFinalize = 30,
+    /// Reports files created, modified, or deleted in the guest (see [`FsDiffRequest`]).
+    FsDiff = 31,
+    /// Response to FsDiff.
+    FsDiffResponse = 32,
+    /// Archives `/workspace` into a tar stream (see [`ExportWorkspaceRequest`]).
+    ExportWorkspace = 33,
+    /// Raw tar bytes of an in-progress export (not JSON).
+    ExportWorkspaceChunk = 34,
+    /// Terminal frame of an export, sent after the last chunk.
+    ExportWorkspaceResponse = 35,
+    /// Confirms a graceful shutdown (see [`ShutdownAck`]); the guest powers
+    /// off right after sending it.
+    ShutdownAck = 36,
+    /// Sends a signal to a running exec's process group (see
+    /// [`SignalExecRequest`]).
+    SignalExec = 37,
+    /// Response to SignalExec.
+    SignalExecResponse = 38,
+    /// Replaces the guest's exec policy (see [`SetExecPolicyRequest`]).
+    SetExecPolicy = 39,
+    /// Response to SetExecPolicy.
+    SetExecPolicyResponse = 40,
+    /// Makes the guest read-only for the rest of the boot (empty payload).
+    EnterReadOnly = 41,
+    /// Response to EnterReadOnly (see [`EnterReadOnlyResponse`]).
+    EnterReadOnlyResponse = 42,
+    /// Creates a guest user (see [`CreateUserRequest`]).
+    CreateUser = 43,
+    /// Response to CreateUser.
+    CreateUserResponse = 44,
+    /// Sets the guest's wall clock (see [`SyncClockRequest`]).
+    SyncClock = 45,
+    /// Response to SyncClock.
+    SyncClockResponse = 46,
+    /// Writes to a running exec's stdin (see [`ExecStdinChunk`]).
+    ExecStdinChunk = 47,
+    /// Closes a running exec's stdin (see [`ExecStdinClose`]).
+    ExecStdinClose = 48,
+    /// Response to ExecStdinChunk and ExecStdinClose.
+    ExecStdinResponse = 49,
+    /// Creates a symbolic link (see [`SymlinkRequest`]).
+    Symlink = 50,
+    /// Response to Symlink.
+    SymlinkResponse = 51,
+    /// Changes a file's mode and/or owner (see [`ChmodRequest`]).
+    Chmod = 52,
+    /// Response to Chmod.
+    ChmodResponse = 53,
+    /// Reads the target of a symbolic link (see [`ReadLinkRequest`]).
+    ReadLink = 54,
+    /// Response to ReadLink.
+    ReadLinkResponse = 55,
+    /// Hashes a batch of files (see [`HashFilesRequest`]).
+    HashFiles = 56,
+    /// Response to HashFiles.
+    HashFilesResponse = 57,
+    /// Requests a [`SysInfo`] snapshot (empty payload).
+    SysInfo = 58,
+    /// Response to SysInfo (see [`SysInfoResponse`]).
+    SysInfoResponse = 59,
+    /// Adjusts the running telemetry subscription (interval, process
+    /// filter, pause/resume) without resubscribing.
+    TelemetryControl = 60,
+    /// Response to TelemetryControl.
+    TelemetryControlResponse = 61,
+    /// Moves a running exec's output to this connection after the one that
+    /// started it dropped (see [`AttachExecRequest`]). Answered like an
+    /// ExecRequest: ExecOutputChunks, then the exec's ExecResponse.
+    AttachExec = 62,
 }
 
 impl TryFrom<u8> for MessageType {
@@ -340,6 +453,41 @@ impl TryFrom<u8> for MessageType {
             25 => Ok(MessageType::PtyResize),
             26 => Ok(MessageType::PtyClose),
             27 => Ok(MessageType::PtyClosed),
+            28 => Ok(MessageType::WriteFileChunk),
+            29 => Ok(MessageType::WriteFileChunkResponse),
+            30 => Ok(MessageType::WriteFileFinalize),
+            31 => Ok(MessageType::FsDiff),
+            32 => Ok(MessageType::FsDiffResponse),
+            33 => Ok(MessageType::ExportWorkspace),
+            34 => Ok(MessageType::ExportWorkspaceChunk),
+            35 => Ok(MessageType::ExportWorkspaceResponse),
+            36 => Ok(MessageType::ShutdownAck),
+            37 => Ok(MessageType::SignalExec),
+            38 => Ok(MessageType::SignalExecResponse),
+            39 => Ok(MessageType::SetExecPolicy),
+            40 => Ok(MessageType::SetExecPolicyResponse),
+            41 => Ok(MessageType::EnterReadOnly),
+            42 => Ok(MessageType::EnterReadOnlyResponse),
+            43 => Ok(MessageType::CreateUser),
+            44 => Ok(MessageType::CreateUserResponse),
+            45 => Ok(MessageType::SyncClock),
+            46 => Ok(MessageType::SyncClockResponse),
+            47 => Ok(MessageType::ExecStdinChunk),
+            48 => Ok(MessageType::ExecStdinClose),
+            49 => Ok(MessageType::ExecStdinResponse),
+            50 => Ok(MessageType::Symlink),
+            51 => Ok(MessageType::SymlinkResponse),
+            52 => Ok(MessageType::Chmod),
+            53 => Ok(MessageType::ChmodResponse),
+            54 => Ok(MessageType::ReadLink),
+            55 => Ok(MessageType::ReadLinkResponse),
+            56 => Ok(MessageType::HashFiles),
+            57 => Ok(MessageType::HashFilesResponse),
+            58 => Ok(MessageType::SysInfo),
+            59 => Ok(MessageType::SysInfoResponse),
+            60 => Ok(MessageType::TelemetryControl),
+            61 => Ok(MessageType::TelemetryControlResponse),
+            62 => Ok(MessageType::AttachExec),
             _ => Err(ProtocolError::UnknownMessageType(byte)),
         }
     }
@@ -428,6 +576,9 @@ impl Message {
 // Data types: Exec
 // ---------------------------------------------------------------------------
 
+/// `PATH` the guest-agent runs commands with unless the exec sets its own.
+pub const GUEST_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/sbin:/usr/sbin";
+
 /// Request to execute a command in the guest.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ExecRequest {
@@ -445,6 +596,22 @@ pub struct ExecRequest {
     pub working_dir: Option<String>,
     /// Timeout in seconds (optional).
     pub timeout_secs: Option<u64>,
+    /// Host-chosen id that [`SignalExecRequest`]s can address the command
+    /// by while it runs. Unset for commands that are never signalled.
+    /// Commands may share an id; a signal reaches all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec_id: Option<String>,
+    /// Checked for this command instead of the guest's exec policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ExecPolicy>,
+    /// Who the command runs as; `None` is [`ExecUser::Sandbox`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<ExecUser>,
+    /// Keep stdin open after writing `stdin`: more arrives in
+    /// [`ExecStdinChunk`]s addressed by `exec_id`, which must be set, until
+    /// an [`ExecStdinClose`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stdin_stream: bool,
 }
 
 /// Patterns that indicate a sensitive environment variable key.
@@ -479,12 +646,19 @@ impl fmt::Debug for ExecRequest {
             .field("env", &redacted_env)
             .field("working_dir", &self.working_dir)
             .field("timeout_secs", &self.timeout_secs)
+            .field("exec_id", &self.exec_id)
+            .field("policy", &self.policy)
+            .field("user", &self.user)
+            .field("stdin_stream", &self.stdin_stream)
             .finish()
     }
 }
 
 /// Response from command execution.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The `*_ms` timeline fields are measured by the guest-agent from the
+/// moment it received the request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExecResponse {
     /// Standard output.
     pub stdout: Vec<u8>,
@@ -496,6 +670,34 @@ pub struct ExecResponse {
     pub error: Option<String>,
     /// Execution duration in milliseconds.
     pub duration_ms: Option<u64>,
+    /// Guest PID of the spawned process (`None` if it never spawned).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Milliseconds until the process was spawned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn_ms: Option<u64>,
+    /// Milliseconds until the first byte arrived on stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_stdout_ms: Option<u64>,
+    /// Milliseconds until the timeout watchdog killed the process.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// User + system CPU time of the process and its reaped descendants.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    /// Peak resident set size of the process.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rss_bytes: Option<u64>,
+    /// Block I/O (read + written) by the process.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_bytes: Option<u64>,
+    /// The guest kernel's OOM killer killed the process.
+    #[serde(default)]
+    pub oom_killed: bool,
+    /// Test cases the process reported through [`TEST_REPORT_ENV`], in
+    /// the order they were written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub test_results: Vec<TestCaseResult>,
 }
 
 impl ExecResponse {
@@ -507,6 +709,7 @@ impl ExecResponse {
             exit_code,
             error: None,
             duration_ms: Some(duration_ms),
+            ..Default::default()
         }
     }
 
@@ -518,6 +721,7 @@ impl ExecResponse {
             exit_code: -1,
             error: Some(message),
             duration_ms: None,
+            ..Default::default()
         }
     }
 }
@@ -538,6 +742,363 @@ pub struct ExecOutputChunk {
     pub seq: u64,
 }
 
+/// Outcome of one test case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    /// An assertion in the test failed.
+    Failed,
+    /// Ignored, skipped, or an expected failure.
+    Skipped,
+    /// The test could not run, e.g. a fixture failed.
+    Error,
+}
+
+/// One test case, as a test adapter in the guest reports it: a JSON line
+/// appended to the file named by [`TEST_REPORT_ENV`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestCaseResult {
+    /// Group the case belongs to, e.g. a test binary or a Python module.
+    pub suite: String,
+    /// Case name within the suite.
+    pub name: String,
+    pub status: TestStatus,
+    /// Wall time of the case, when the test runner reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /// Failure or skip reason.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Data types: Clock
+// ---------------------------------------------------------------------------
+
+/// Sets the guest's `CLOCK_REALTIME` to the host's wall clock.
+///
+/// The boot-time `voidbox.clock` cmdline value is only whole seconds and
+/// is not re-read after a snapshot restore or a host suspend; this keeps
+/// long-lived guests (and their TLS certificate checks) in step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncClockRequest {
+    /// Seconds since the Unix epoch.
+    pub unix_secs: i64,
+    pub nanos: u32,
+}
+
+/// Response to [`SyncClockRequest`]: the guest clock just before it was
+/// set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncClockResponse {
+    pub guest_unix_secs: i64,
+    pub guest_nanos: u32,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Data types: Guest users
+// ---------------------------------------------------------------------------
+
+/// uid and gid of the built-in `sandbox` user execs run as by default.
+pub const SANDBOX_UID: u32 = 1000;
+
+/// Who an exec runs as.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecUser {
+    /// The unprivileged `sandbox` user (uid [`SANDBOX_UID`]).
+    #[default]
+    Sandbox,
+    /// root, e.g. to install packages.
+    Root,
+    /// A user made with [`CreateUserRequest`].
+    Named(String),
+}
+
+impl ExecUser {
+    pub fn named(name: impl Into<String>) -> Self {
+        Self::Named(name.into())
+    }
+}
+
+impl fmt::Display for ExecUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sandbox => f.write_str("sandbox"),
+            Self::Root => f.write_str("root"),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Adds an unprivileged guest user with its own group and a 0700 home
+/// directory under `/home`. Creating a user that exists returns it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateUserRequest {
+    /// Lowercase letters, digits, `_` and `-`, starting with a letter or
+    /// `_`; at most 32 characters. `root` and `sandbox` are reserved.
+    pub name: String,
+}
+
+/// Response to [`CreateUserRequest`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateUserResponse {
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Whether `name` is acceptable to [`CreateUserRequest`].
+pub fn valid_user_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    name.len() <= 32
+        && (first.is_ascii_lowercase() || first == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        && name != "root"
+        && name != "sandbox"
+}
+
+// ---------------------------------------------------------------------------
+// Data types: Exec policy
+// ---------------------------------------------------------------------------
+
+/// What an [`ExecRule`] does with the commands it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecAction {
+    Allow,
+    Deny,
+}
+
+/// One rule of an [`ExecPolicy`].
+///
+/// `program` is a [`glob_match`] pattern against the program's basename,
+/// or against the full program path when the pattern contains a `/`.
+/// `args` constrains the leading arguments position by position (each a
+/// glob, so `*` matches any single argument); `any_arg` matches when some
+/// argument, anywhere, matches it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecRule {
+    pub action: ExecAction,
+    pub program: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub any_arg: Option<String>,
+}
+
+impl ExecRule {
+    /// Allow `program` (a glob).
+    pub fn allow(program: impl Into<String>) -> Self {
+        Self {
+            action: ExecAction::Allow,
+            program: program.into(),
+            args: Vec::new(),
+            any_arg: None,
+        }
+    }
+
+    /// Deny `program` (a glob).
+    pub fn deny(program: impl Into<String>) -> Self {
+        Self {
+            action: ExecAction::Deny,
+            ..Self::allow(program)
+        }
+    }
+
+    /// Only match when the leading arguments match `args`, in order.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only match when some argument matches `pattern`.
+    pub fn any_arg(mut self, pattern: impl Into<String>) -> Self {
+        self.any_arg = Some(pattern.into());
+        self
+    }
+
+    /// Whether this rule covers `program args...`.
+    pub fn matches(&self, program: &str, args: &[String]) -> bool {
+        let name = if self.program.contains('/') {
+            program
+        } else {
+            program.rsplit('/').next().unwrap_or(program)
+        };
+        glob_match(&self.program, name)
+            && args.len() >= self.args.len()
+            && self.args.iter().zip(args).all(|(p, a)| glob_match(p, a))
+            && self
+                .any_arg
+                .as_ref()
+                .is_none_or(|p| args.iter().any(|a| glob_match(p, a)))
+    }
+}
+
+impl fmt::Display for ExecRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            ExecAction::Allow => "allow",
+            ExecAction::Deny => "deny",
+        };
+        write!(f, "{} {}", action, self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        if let Some(arg) = &self.any_arg {
+            write!(f, " [any arg {}]", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which commands the guest-agent may spawn.
+///
+/// A command is denied if any [`ExecAction::Deny`] rule matches it.
+/// Otherwise it is allowed if an [`ExecAction::Allow`] rule matches, or if
+/// the policy has no allow rules at all; an empty policy allows everything.
+/// Rule order only decides which rule a denial reports.
+///
+/// "`git`, but not `git push`":
+///
+/// ```
+/// use void_box_protocol::{ExecPolicy, ExecRule};
+///
+/// let policy = ExecPolicy::new()
+///     .allow("git")
+///     .rule(ExecRule::deny("git").any_arg("push"));
+/// assert!(policy.check("/usr/bin/git", &["status".into()]).is_ok());
+/// assert!(policy.check("git", &["-C".into(), "repo".into(), "push".into()]).is_err());
+/// assert!(policy.check("curl", &[]).is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecPolicy {
+    pub rules: Vec<ExecRule>,
+}
+
+impl ExecPolicy {
+    /// A policy that allows everything until rules are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow exactly the programs named in a legacy command allowlist.
+    pub fn from_allowlist<I, S>(programs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            rules: programs.into_iter().map(ExecRule::allow).collect(),
+        }
+    }
+
+    /// Add an [`ExecRule`].
+    pub fn rule(mut self, rule: ExecRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Allow `program` (a glob) with any arguments.
+    pub fn allow(self, program: impl Into<String>) -> Self {
+        self.rule(ExecRule::allow(program))
+    }
+
+    /// Deny `program` (a glob) with any arguments.
+    pub fn deny(self, program: impl Into<String>) -> Self {
+        self.rule(ExecRule::deny(program))
+    }
+
+    /// Decide on `program args...`.
+    pub fn check(&self, program: &str, args: &[String]) -> Result<(), ExecDenial> {
+        if let Some((index, rule)) = self
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.action == ExecAction::Deny && r.matches(program, args))
+        {
+            return Err(ExecDenial {
+                rule_index: Some(index),
+                rule: rule.to_string(),
+            });
+        }
+        let mut allow_rules = self
+            .rules
+            .iter()
+            .filter(|r| r.action == ExecAction::Allow)
+            .peekable();
+        if allow_rules.peek().is_none() || allow_rules.any(|r| r.matches(program, args)) {
+            return Ok(());
+        }
+        Err(ExecDenial {
+            rule_index: None,
+            rule: "no allow rule matches".to_string(),
+        })
+    }
+}
+
+/// Why an [`ExecPolicy`] refused a command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecDenial {
+    /// Index of the deny rule that matched; `None` when no allow rule did.
+    pub rule_index: Option<usize>,
+    /// The matching rule, as displayed by [`ExecRule`].
+    pub rule: String,
+}
+
+impl fmt::Display for ExecDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rule_index {
+            Some(index) => write!(f, "denied by rule #{} ({})", index, self.rule),
+            None => f.write_str("not allowed: no allow rule matches"),
+        }
+    }
+}
+
+/// Replaces the guest-agent's exec policy, including one loaded from the
+/// legacy `allowed_commands.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetExecPolicyRequest {
+    pub policy: ExecPolicy,
+}
+
+/// Response to [`SetExecPolicyRequest`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SetExecPolicyResponse {
+    /// Rules now in force.
+    pub rules: usize,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Data types: Read-only mode
+// ---------------------------------------------------------------------------
+
+/// Response to [`MessageType::EnterReadOnly`].
+///
+/// From then on the guest-agent refuses `WriteFile`, `WriteFileChunk`,
+/// `WriteFileFinalize` and `MkdirP`, and `/workspace` is remounted
+/// read-only. `error` reports a remount failure; the refusals apply
+/// regardless.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnterReadOnlyResponse {
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
 // ---------------------------------------------------------------------------
 // Data types: File operations (native, no shell required)
 // ---------------------------------------------------------------------------
@@ -570,6 +1131,292 @@ pub struct WriteFileResponse {
     pub error: Option<String>,
 }
 
+/// One chunk of a chunked file transfer.
+///
+/// Files larger than a single message are sent as a sequence of chunks,
+/// each written with `pwrite` at `offset` into a staging file next to
+/// `path`. The chunk at offset 0 creates (or truncates) the staging file;
+/// later chunks require it to exist. Because every chunk names its own
+/// offset, re-sending a chunk after a failed or timed-out RPC is
+/// idempotent, which is what makes the transfer resumable. Nothing is
+/// visible at `path` until a [`WriteFileFinalizeRequest`] commits it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteFileChunkRequest {
+    /// Absolute destination path in the guest filesystem.
+    pub path: String,
+    /// Byte offset of `data` within the destination file.
+    pub offset: u64,
+    /// Chunk content, at most [`WRITE_FILE_CHUNK_SIZE`] bytes.
+    pub data: Vec<u8>,
+    /// If true, create parent directories automatically (first chunk only).
+    #[serde(default = "default_true")]
+    pub create_parents: bool,
+}
+
+/// Response to a [`WriteFileChunkRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteFileChunkResponse {
+    /// Whether the chunk was written.
+    pub success: bool,
+    /// Size of the staging file after the write; a sender resuming an
+    /// interrupted transfer continues from here.
+    #[serde(default)]
+    pub staged_size: u64,
+    /// Error message if the write failed.
+    pub error: Option<String>,
+}
+
+/// Commits a chunked transfer: verifies the staged size and atomically
+/// renames the staging file onto `path`.
+///
+/// Answered with a [`WriteFileResponse`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteFileFinalizeRequest {
+    /// Absolute destination path, as used by the preceding chunks.
+    pub path: String,
+    /// Total number of bytes the sender transferred.
+    pub total_size: u64,
+}
+
+/// Requests the files created, modified, or deleted in the guest.
+///
+/// Without a `root`, a guest booted on an OCI rootfs diffs the overlay's
+/// writable upper layer against its state at the end of boot, which covers
+/// the whole root filesystem at the cost of one directory walk. A guest
+/// without an overlay — or any request naming a `root`, which must lie
+/// under the guest's readable roots — diffs the tree against a baseline
+/// manifest of sizes and modification times instead. The first request for
+/// such a root records the baseline and reports no changes (see
+/// [`FsDiffResponse::baseline_created`]); later requests diff against it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FsDiffRequest {
+    /// Absolute directory to diff; `None` selects the whole root filesystem.
+    #[serde(default)]
+    pub root: Option<String>,
+}
+
+/// How a path changed relative to the diff baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsChangeKind {
+    /// The path did not exist in the baseline.
+    Created,
+    /// The path existed in the baseline with different content or metadata.
+    Modified,
+    /// The path existed in the baseline and is gone.
+    Deleted,
+}
+
+/// One changed path in an [`FsDiffResponse`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsChange {
+    /// Absolute path in the guest.
+    pub path: String,
+    /// How the path changed.
+    pub kind: FsChangeKind,
+    /// Current size in bytes, or the last known size of a deleted path.
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the current content of a created or
+    /// modified regular file.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Response to an [`FsDiffRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsDiffResponse {
+    /// Whether the diff was computed.
+    pub success: bool,
+    /// Directory the diff covers (`/` for the whole root filesystem).
+    #[serde(default)]
+    pub root: String,
+    /// Changed paths, sorted by path.
+    #[serde(default)]
+    pub changes: Vec<FsChange>,
+    /// True when this request recorded the baseline for `root`, so
+    /// `changes` is necessarily empty.
+    #[serde(default)]
+    pub baseline_created: bool,
+    /// True when more than [`MAX_FS_DIFF_CHANGES`] paths changed and the
+    /// remainder was dropped.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Error message if the diff failed.
+    pub error: Option<String>,
+}
+
+/// Requests a tar archive of the guest's `/workspace`.
+///
+/// The guest answers with a stream of `ExportWorkspaceChunk` frames whose
+/// payloads concatenate to a ustar archive with paths relative to
+/// `/workspace`, followed by one [`ExportWorkspaceResponse`]. Only regular
+/// files and symlinks are archived, and symlinks are stored as links rather
+/// than followed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportWorkspaceRequest {
+    /// Glob over paths relative to `/workspace` selecting what to archive
+    /// (`*` and `?` stay within one path segment, `**` spans segments).
+    /// `None` archives everything.
+    #[serde(default)]
+    pub path_filter: Option<String>,
+}
+
+/// Terminal frame of a workspace export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportWorkspaceResponse {
+    /// Whether the archive is complete.
+    pub success: bool,
+    /// Number of entries archived.
+    #[serde(default)]
+    pub entries: u64,
+    /// Total archive bytes sent in chunk frames.
+    #[serde(default)]
+    pub archive_bytes: u64,
+    /// Error message if the export failed; chunks already sent are partial.
+    pub error: Option<String>,
+}
+
+/// Asks the guest to stop its workload and power off.
+///
+/// The guest sends SIGTERM to every process, waits up to `grace_ms` for them
+/// to exit, SIGKILLs the rest, syncs filesystems, answers with a
+/// [`ShutdownAck`], and powers off — or restarts, when `reboot` is set. An
+/// empty payload (from hosts that predate this struct) uses the default
+/// grace period.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShutdownRequest {
+    /// How long processes get to exit after SIGTERM before being killed.
+    /// `None` uses [`DEFAULT_SHUTDOWN_GRACE_MS`].
+    #[serde(default)]
+    pub grace_ms: Option<u64>,
+    /// Restart the kernel instead of powering off. The host keeps the VM
+    /// and boots the guest again in place.
+    #[serde(default)]
+    pub reboot: bool,
+}
+
+/// Grace period between SIGTERM and SIGKILL when [`ShutdownRequest`] does
+/// not set one.
+pub const DEFAULT_SHUTDOWN_GRACE_MS: u64 = 3_000;
+
+/// Summary of a graceful shutdown, sent just before the guest powers off.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownAck {
+    /// Processes that were sent SIGTERM.
+    #[serde(default)]
+    pub processes_terminated: u32,
+    /// Processes still running after the grace period, sent SIGKILL.
+    #[serde(default)]
+    pub processes_killed: u32,
+    /// Dirty and writeback page-cache bytes pending when `sync` started.
+    #[serde(default)]
+    pub bytes_flushed: u64,
+    /// Time from receiving the request to sending this ack.
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+/// Signal delivered by a [`SignalExecRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecSignal {
+    /// `SIGSTOP`: suspend the command until [`ExecSignal::Continue`].
+    Stop,
+    /// `SIGCONT`: resume a stopped command.
+    Continue,
+    /// `SIGKILL`: end the command.
+    Kill,
+    /// `SIGINT`: what Ctrl-C sends; interactive commands usually stop the
+    /// current operation or exit.
+    Interrupt,
+    /// `SIGTERM`: ask the command to shut down gracefully.
+    Terminate,
+}
+
+/// Signals the process group of a running exec started with
+/// [`ExecRequest::exec_id`] set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalExecRequest {
+    pub exec_id: String,
+    pub signal: ExecSignal,
+}
+
+/// Response to [`SignalExecRequest`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SignalExecResponse {
+    /// Whether the signal was sent; `false` if no such exec is running.
+    pub delivered: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// More stdin for a running exec started with
+/// [`ExecRequest::stdin_stream`] set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecStdinChunk {
+    pub exec_id: String,
+    /// At most [`EXEC_STDIN_CHUNK_SIZE`] bytes.
+    pub data: Vec<u8>,
+}
+
+/// Closes the stdin of a running exec started with
+/// [`ExecRequest::stdin_stream`] set, so the command reads end-of-file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecStdinClose {
+    pub exec_id: String,
+}
+
+/// Re-attaches to a running exec started with [`ExecRequest::exec_id`] set.
+///
+/// Output the command writes while no connection is attached is not
+/// streamed, but still ends up in its final [`ExecResponse`]. Attaching
+/// fails, with an error `ExecResponse`, if no exec or more than one runs
+/// under the id, or if the command has already finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachExecRequest {
+    pub exec_id: String,
+}
+
+/// Response to [`ExecStdinChunk`] and [`ExecStdinClose`], sent once the
+/// data has been written to the command's stdin pipe.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecStdinResponse {
+    /// Why the data was not written, e.g. the command has exited.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Whether `path` matches the glob `pattern`.
+///
+/// `*` matches any run of characters other than `/`, `?` matches one such
+/// character, and `**` matches any run including `/` (so `**/*.json`
+/// matches at any depth, including the top level). Everything else is
+/// literal.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                matches(rest, path)
+                    || path
+                        .iter()
+                        .enumerate()
+                        .any(|(i, &c)| c == b'/' && matches(rest, &path[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            [b'*', rest @ ..] => {
+                let segment_end = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+                (0..=segment_end).any(|i| matches(rest, &path[i..]))
+            }
+            [b'?', rest @ ..] => {
+                matches!(path, [c, tail @ ..] if *c != b'/' && matches(rest, tail))
+            }
+            [p, rest @ ..] => matches!(path, [c, tail @ ..] if c == p && matches(rest, tail)),
+        }
+    }
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
 /// Request to create directories in the guest filesystem (mkdir -p).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MkdirPRequest {
@@ -614,6 +1461,168 @@ pub struct FileStatResponse {
     pub error: Option<String>,
 }
 
+/// Request to create a symbolic link at `link_path` pointing to `target`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymlinkRequest {
+    /// What the link points to, stored as given; it need not exist.
+    pub target: String,
+    /// Absolute path of the link to create.
+    pub link_path: String,
+    /// Replace a file or link already at `link_path` (like `ln -sf`).
+    /// Directories are never replaced.
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// Response to a [`SymlinkRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymlinkResponse {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Guest system state, read from procfs in one round trip.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SysInfo {
+    /// Kernel release (`uname -r`).
+    pub kernel_version: String,
+    /// Seconds since the guest booted.
+    pub uptime_secs: f64,
+    /// 1, 5 and 15 minute load averages.
+    pub load_average: [f64; 3],
+    /// Online CPUs.
+    pub cpus: u32,
+    pub memory: MemoryInfo,
+    /// Mounted filesystems, in `/proc/mounts` order.
+    #[serde(default)]
+    pub mounts: Vec<MountInfo>,
+}
+
+/// The `/proc/meminfo` fields most often asked for, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryInfo {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    /// Memory available to new work without swapping (`MemAvailable`).
+    pub available_bytes: u64,
+    pub buffers_bytes: u64,
+    /// Page cache (`Cached`).
+    pub cached_bytes: u64,
+    /// tmpfs and shared memory (`Shmem`); the initramfs root counts here.
+    pub shmem_bytes: u64,
+    pub swap_total_bytes: u64,
+    pub swap_free_bytes: u64,
+}
+
+/// One line of `/proc/mounts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountInfo {
+    /// Device or source, e.g. `/dev/vda` or `tmpfs`.
+    pub source: String,
+    /// Mount point.
+    pub target: String,
+    pub fs_type: String,
+    /// Comma-separated mount options as the kernel reports them.
+    pub options: String,
+}
+
+impl MountInfo {
+    /// Whether the filesystem is mounted read-only.
+    pub fn read_only(&self) -> bool {
+        self.options.split(',').any(|option| option == "ro")
+    }
+}
+
+/// Response to a `SysInfo` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SysInfoResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub info: Option<SysInfo>,
+    pub error: Option<String>,
+}
+
+/// Requests the SHA-256, size and mtime of each of `paths`, so the host
+/// can check what is already in place without reading the files back.
+///
+/// Paths resolve against the same allowlist as [`ReadFileRequest`] and a
+/// symbolic link is reported as an error rather than followed. At most
+/// [`MAX_HASH_FILES`] paths per request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HashFilesRequest {
+    pub paths: Vec<String>,
+}
+
+/// One path in a [`HashFilesResponse`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHash {
+    /// The path as requested.
+    pub path: String,
+    /// False when nothing is at `path`; the other fields are then unset.
+    pub exists: bool,
+    /// Size in bytes.
+    #[serde(default)]
+    pub size: u64,
+    /// Last modification, Unix milliseconds. `None` when unknown.
+    #[serde(default)]
+    pub mtime_ms: Option<u64>,
+    /// Lowercase hex SHA-256 of a regular file's content; `None` for
+    /// directories and other file types.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Why the path could not be inspected, e.g. it is outside the
+    /// allowed roots.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Response to a [`HashFilesRequest`], one entry per requested path in
+/// order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashFilesResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub files: Vec<FileHash>,
+    pub error: Option<String>,
+}
+
+/// Request to change the mode and/or owner of a file or directory.
+/// Fields left `None` are unchanged. A symbolic link at `path` is refused
+/// rather than followed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChmodRequest {
+    /// Absolute path to change.
+    pub path: String,
+    /// Permission bits, e.g. `0o755`.
+    #[serde(default)]
+    pub mode: Option<u32>,
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub gid: Option<u32>,
+}
+
+/// Response to a [`ChmodRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChmodResponse {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Requests the target of a symbolic link in the guest filesystem.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadLinkRequest {
+    pub path: String,
+}
+
+/// Response to a [`ReadLinkRequest`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadLinkResponse {
+    /// The link's target, as stored; `None` on error.
+    pub target: Option<String>,
+    pub error: Option<String>,
+}
+
 /// Request to open a pseudo-terminal session in the guest.
 ///
 /// The host sends this to spawn a program under a PTY with the given
@@ -706,6 +1715,27 @@ pub struct SystemMetrics {
     pub procs_running: u32,
     /// Number of open file descriptors (from /proc/sys/fs/file-nr).
     pub open_fds: u32,
+    /// Usage of the guest filesystems (`/`, `/workspace`, and the overlay
+    /// upper layer when running on an OCI rootfs), from `statvfs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskUsage>,
+}
+
+/// Mount name reported for the overlay upper layer, where all writes to
+/// an OCI rootfs land (a tmpfs, so it is bounded by guest memory).
+pub const OVERLAY_UPPER_DISK: &str = "overlay-upper";
+
+/// Space usage of one guest filesystem.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// Mount point, or [`OVERLAY_UPPER_DISK`].
+    pub mount: String,
+    /// Bytes in use.
+    pub used_bytes: u64,
+    /// Filesystem size in bytes.
+    pub total_bytes: u64,
+    /// Bytes still writable by unprivileged processes.
+    pub available_bytes: u64,
 }
 
 /// Subscription options sent by the host with `SubscribeTelemetry`.
@@ -721,6 +1751,42 @@ pub struct TelemetrySubscribeRequest {
     /// Include kernel threads in per-process metrics. Default: false.
     #[serde(default)]
     pub include_kernel_threads: bool,
+    /// Report only the busiest processes. Default: all of them.
+    #[serde(default)]
+    pub top_processes: Option<TopProcesses>,
+}
+
+/// Limits per-process metrics to the `count` processes ranking highest
+/// `by` CPU or memory, keeping batches small on busy guests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopProcesses {
+    pub count: u32,
+    #[serde(default)]
+    pub by: ProcessRanking,
+}
+
+/// What [`TopProcesses`] ranks by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessRanking {
+    /// [`ProcessMetrics::cpu_percent`] over the last interval.
+    #[default]
+    Cpu,
+    /// [`ProcessMetrics::rss_bytes`].
+    Memory,
+}
+
+impl TopProcesses {
+    /// Keep the top [`count`](Self::count) of `processes`, busiest first.
+    pub fn apply(&self, processes: &mut Vec<ProcessMetrics>) {
+        match self.by {
+            ProcessRanking::Cpu => {
+                processes.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent))
+            }
+            ProcessRanking::Memory => processes.sort_by_key(|p| std::cmp::Reverse(p.rss_bytes)),
+        }
+        processes.truncate(self.count as usize);
+    }
 }
 
 fn default_interval_ms() -> u64 {
@@ -732,12 +1798,67 @@ impl Default for TelemetrySubscribeRequest {
         Self {
             interval_ms: 1000,
             include_kernel_threads: false,
+            top_processes: None,
         }
     }
 }
 
-/// Per-process metrics collected from procfs.
+/// Changes to a running telemetry subscription, sent with
+/// `TelemetryControl`. Fields left `None` keep their current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TelemetryControlRequest {
+    /// New collection interval in milliseconds (floored at 100).
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// Stop (`true`) or restart (`false`) sending batches.
+    #[serde(default)]
+    pub paused: Option<bool>,
+    /// Report only matching processes; an empty filter reports all.
+    #[serde(default)]
+    pub process_filter: Option<TelemetryProcessFilter>,
+    /// Report only the busiest processes; a `count` of 0 lifts the limit.
+    #[serde(default)]
+    pub top_processes: Option<TopProcesses>,
+}
+
+/// Selects the processes reported in [`TelemetryBatch::processes`].
+///
+/// A process matches if its pid is in `pids` or its `comm` is in `names`.
+/// An empty filter matches every process.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryProcessFilter {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub pids: Vec<u32>,
+}
+
+impl TelemetryProcessFilter {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.pids.is_empty()
+    }
+
+    pub fn matches(&self, process: &ProcessMetrics) -> bool {
+        self.is_empty() || self.pids.contains(&process.pid) || self.names.contains(&process.comm)
+    }
+}
+
+/// Response to `TelemetryControl`, reporting the settings now in effect.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryControlResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub interval_ms: u64,
+    #[serde(default)]
+    pub paused: bool,
+    pub error: Option<String>,
+}
+
+/// Longest command line reported in [`ProcessMetrics::cmdline`], in bytes.
+pub const MAX_PROCESS_CMDLINE: usize = 512;
+
+/// Per-process metrics collected from procfs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProcessMetrics {
     /// Process ID.
     pub pid: u32,
@@ -749,8 +1870,217 @@ pub struct ProcessMetrics {
     pub cpu_jiffies: u64,
     /// Process state (R, S, D, Z, etc.).
     pub state: char,
+    /// Parent process ID (from /proc/PID/stat).
+    #[serde(default)]
+    pub ppid: u32,
+    /// Arguments joined by spaces (from /proc/PID/cmdline), cut to
+    /// [`MAX_PROCESS_CMDLINE`] bytes.
+    #[serde(default)]
+    pub cmdline: String,
+    /// Share of one CPU used since the previous batch, in percent; above
+    /// 100 for a process busy on several CPUs. Zero in the first batch of
+    /// a subscription.
+    #[serde(default)]
+    pub cpu_percent: f64,
+}
+
+// ---------------------------------------------------------------------------
+// Boot status (serial console)
+// ---------------------------------------------------------------------------
+
+/// Marks the boot-status lines the guest-agent writes to the serial
+/// console, at `KERN_EMERG` so the `loglevel=0` cmdline does not hide them.
+/// They report failures from before the control channel exists; the host
+/// reads them when a boot never completes its handshake.
+///
+/// A line reads `<marker> <status> [detail]`, e.g.
+/// `voidbox-boot: module-failed vsock.ko: No such file or directory`.
+pub const BOOT_STATUS_MARKER: &str = "voidbox-boot:";
+
+/// What a boot-status line reports; see [`BOOT_STATUS_MARKER`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootStatus {
+    /// The guest-agent is running as init.
+    AgentStarted,
+    /// A required kernel module did not load.
+    ModuleFailed,
+    /// The guest-agent could not listen on vsock.
+    VsockListenFailed,
+    /// The kernel cmdline carried no session secret.
+    SecretMissing,
+    /// A handshake carried the wrong session secret.
+    SecretMismatch,
+    /// Setting up the OCI rootfs failed.
+    OciRootfsFailed,
+    /// A boot step finished; the detail is a [`GuestBootPhase`].
+    Phase,
+}
+
+impl BootStatus {
+    const ALL: [BootStatus; 7] = [
+        BootStatus::AgentStarted,
+        BootStatus::ModuleFailed,
+        BootStatus::VsockListenFailed,
+        BootStatus::SecretMissing,
+        BootStatus::SecretMismatch,
+        BootStatus::OciRootfsFailed,
+        BootStatus::Phase,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BootStatus::AgentStarted => "agent-started",
+            BootStatus::ModuleFailed => "module-failed",
+            BootStatus::VsockListenFailed => "vsock-listen-failed",
+            BootStatus::SecretMissing => "secret-missing",
+            BootStatus::SecretMismatch => "secret-mismatch",
+            BootStatus::OciRootfsFailed => "oci-rootfs-failed",
+            BootStatus::Phase => "phase",
+        }
+    }
+
+    /// The status a console line reports, with its detail, if the line is
+    /// a boot-status line. Anything before the marker (kernel timestamp,
+    /// `guest-agent:` prefix) is skipped.
+    pub fn parse_line(line: &str) -> Option<(BootStatus, &str)> {
+        let start = line.find(BOOT_STATUS_MARKER)? + BOOT_STATUS_MARKER.len();
+        let rest = line[start..].trim();
+        let (token, detail) = rest.split_once(' ').unwrap_or((rest, ""));
+        let status = Self::ALL.into_iter().find(|s| s.as_str() == token)?;
+        Some((status, detail.trim()))
+    }
+}
+
+/// A boot step the guest-agent timed, reported on a [`BootStatus::Phase`]
+/// line as `<name> <start_us> <duration_us>`. `start` is `CLOCK_BOOTTIME`,
+/// i.e. time since the guest kernel started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestBootPhase {
+    pub name: String,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+impl GuestBootPhase {
+    /// The detail of the phase's boot-status line.
+    pub fn to_detail(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.name,
+            self.start.as_micros(),
+            self.duration.as_micros()
+        )
+    }
+
+    pub fn parse_detail(detail: &str) -> Option<Self> {
+        let mut fields = detail.split_whitespace();
+        let name = fields.next()?.to_string();
+        let start = fields.next()?.parse().ok()?;
+        let duration = fields.next()?.parse().ok()?;
+        Some(Self {
+            name,
+            start: Duration::from_micros(start),
+            duration: Duration::from_micros(duration),
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Control port
+// ---------------------------------------------------------------------------
+
+/// Kernel cmdline argument telling the guest-agent to serve the control
+/// channel on the virtio-console port named [`CONTROL_PORT_NAME`] as well
+/// as on vsock.
+pub const CONTROL_CMDLINE_ARG: &str = "voidbox.control=serial";
+
+/// Name of the virtio-console port carrying the control channel.
+pub const CONTROL_PORT_NAME: &str = "org.voidbox.control";
+
+// ---------------------------------------------------------------------------
+// Watchdog device
+// ---------------------------------------------------------------------------
+
+/// Kernel cmdline key announcing the host watchdog, as
+/// `voidbox.watchdog=<timeout_secs>`. The guest-agent pets the device at a
+/// fraction of the timeout from a dedicated thread.
+pub const WATCHDOG_CMDLINE_KEY: &str = "voidbox.watchdog=";
+
+/// x86_64 I/O port whose writes pet (and arm) the watchdog. The port pair
+/// follows the ib700 convention.
+pub const WATCHDOG_PET_PORT: u16 = 0x443;
+
+/// x86_64 I/O port whose writes disarm the watchdog.
+pub const WATCHDOG_STOP_PORT: u16 = 0x441;
+
+/// aarch64 guest-physical page of the watchdog registers.
+pub const WATCHDOG_MMIO_ADDR: u64 = 0x0901_0000;
+
+/// Offset of the pet register in [`WATCHDOG_MMIO_ADDR`].
+pub const WATCHDOG_MMIO_PET: u64 = 0x0;
+
+/// Offset of the stop register in [`WATCHDOG_MMIO_ADDR`].
+pub const WATCHDOG_MMIO_STOP: u64 = 0x4;
+
+// ---------------------------------------------------------------------------
+// Write roots
+// ---------------------------------------------------------------------------
+
+/// Guest directories the agent's write RPCs (`WriteFile`, `MkdirP`,
+/// `Chmod`, ...) may always touch.
+pub const DEFAULT_WRITE_ROOTS: [&str; 3] = ["/workspace", "/home", "/etc/voidbox"];
+
+/// Kernel cmdline key adding write roots on top of [`DEFAULT_WRITE_ROOTS`],
+/// as `voidbox.write_roots=/opt,/etc/systemd/system`.
+pub const WRITE_ROOTS_CMDLINE_KEY: &str = "voidbox.write_roots=";
+
+/// Pseudo filesystems no write root may cover.
+const FORBIDDEN_WRITE_ROOTS: [&str; 3] = ["/proc", "/sys", "/dev"];
+
+/// Check that `root` can be an extra write root: an absolute, normalized
+/// directory other than `/`, outside `/proc`, `/sys` and `/dev`, and
+/// without the commas or whitespace the cmdline encoding relies on.
+pub fn validate_write_root(root: &str) -> Result<(), String> {
+    if !root.starts_with('/') {
+        return Err(format!("write root '{root}' is not absolute"));
+    }
+    if root == "/" {
+        return Err("write root '/' would allow writing anywhere".into());
+    }
+    if root.contains(',') || root.chars().any(char::is_whitespace) {
+        return Err(format!(
+            "write root '{root}' contains a comma or whitespace"
+        ));
+    }
+    if root[1..]
+        .split('/')
+        .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        return Err(format!("write root '{root}' is not a normalized path"));
+    }
+    if let Some(forbidden) = FORBIDDEN_WRITE_ROOTS
+        .iter()
+        .find(|forbidden| root == **forbidden || root.starts_with(&format!("{forbidden}/")))
+    {
+        return Err(format!("write root '{root}' is under {forbidden}"));
+    }
+    Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Rosetta
+// ---------------------------------------------------------------------------
+
+/// Kernel cmdline flag, `voidbox.rosetta=1`, telling the guest-agent that
+/// the host shares Apple's Rosetta translator (VZ on Apple silicon only).
+pub const ROSETTA_CMDLINE_KEY: &str = "voidbox.rosetta=";
+
+/// virtiofs tag of the Rosetta share.
+pub const ROSETTA_SHARE_TAG: &str = "rosetta";
+
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -759,6 +2089,30 @@ pub struct ProcessMetrics {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_boot_status_parse_line() {
+        assert_eq!(
+            BootStatus::parse_line(
+                "[    0.912] guest-agent: voidbox-boot: module-failed vsock.ko: ENOENT\r"
+            ),
+            Some((BootStatus::ModuleFailed, "vsock.ko: ENOENT"))
+        );
+        assert_eq!(
+            BootStatus::parse_line("guest-agent: voidbox-boot: secret-missing"),
+            Some((BootStatus::SecretMissing, ""))
+        );
+        let (status, detail) =
+            BootStatus::parse_line("guest-agent: voidbox-boot: phase module-load 41000 2500")
+                .unwrap();
+        assert_eq!(status, BootStatus::Phase);
+        let phase = GuestBootPhase::parse_detail(detail).unwrap();
+        assert_eq!(phase.name, "module-load");
+        assert_eq!(phase.start, Duration::from_millis(41));
+        assert_eq!(phase.to_detail(), "module-load 41000 2500");
+        assert_eq!(BootStatus::parse_line("voidbox-boot: rebooting"), None);
+        assert_eq!(BootStatus::parse_line("guest-agent: Modules loaded"), None);
+    }
+
     #[test]
     fn message_round_trip() {
         let msg = Message {
@@ -804,7 +2158,7 @@ mod tests {
     #[test]
     fn message_type_try_from_invalid() {
         assert!(MessageType::try_from(0).is_err());
-        assert!(MessageType::try_from(28).is_err());
+        assert!(MessageType::try_from(63).is_err());
         assert!(MessageType::try_from(255).is_err());
     }
 
@@ -829,6 +2183,10 @@ mod tests {
             env: Vec::new(),
             working_dir: None,
             timeout_secs: Some(30),
+            exec_id: None,
+            policy: None,
+            user: None,
+            stdin_stream: false,
         };
         let json = serde_json::to_string(&req).unwrap();
         let decoded: ExecRequest = serde_json::from_str(&json).unwrap();
@@ -849,6 +2207,55 @@ mod tests {
         assert_eq!(err.exit_code, -1);
     }
 
+    #[test]
+    fn exec_response_timeline_is_optional_on_the_wire() {
+        let old: ExecResponse = serde_json::from_str(
+            r#"{"stdout":[],"stderr":[],"exit_code":0,"error":null,"duration_ms":5}"#,
+        )
+        .unwrap();
+        assert_eq!(old.pid, None);
+        assert_eq!(old.timeout_ms, None);
+        assert_eq!(old.cpu_time_ms, None);
+        assert_eq!(old.max_rss_bytes, None);
+
+        let json = serde_json::to_value(ExecResponse {
+            pid: Some(42),
+            spawn_ms: Some(3),
+            ..ExecResponse::success(Vec::new(), Vec::new(), 0, 10)
+        })
+        .unwrap();
+        assert_eq!(json["pid"], 42);
+        assert!(json.get("first_stdout_ms").is_none());
+        assert!(json.get("io_bytes").is_none());
+        assert_eq!(json["oom_killed"], false);
+        assert!(!old.oom_killed);
+        assert!(old.test_results.is_empty());
+        assert!(json.get("test_results").is_none());
+    }
+
+    #[test]
+    fn test_case_result_json_line() {
+        let case: TestCaseResult = serde_json::from_str(
+            r#"{"suite":"tests/api.rs","name":"lists_runs","status":"failed","message":"boom"}"#,
+        )
+        .unwrap();
+        assert_eq!(case.status, TestStatus::Failed);
+        assert_eq!(case.duration_ms, None);
+        assert_eq!(case.message.as_deref(), Some("boom"));
+
+        let line = serde_json::to_string(&TestCaseResult {
+            status: TestStatus::Skipped,
+            duration_ms: Some(1.5),
+            message: None,
+            ..case
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"suite":"tests/api.rs","name":"lists_runs","status":"skipped","duration_ms":1.5}"#
+        );
+    }
+
     #[test]
     fn telemetry_batch_json_round_trip() {
         let batch = TelemetryBatch {
@@ -862,6 +2269,7 @@ mod tests {
                 net_tx_bytes: 2000,
                 procs_running: 3,
                 open_fds: 128,
+                disks: Vec::new(),
             }),
             processes: vec![ProcessMetrics {
                 pid: 1,
@@ -869,6 +2277,7 @@ mod tests {
                 rss_bytes: 4096,
                 cpu_jiffies: 100,
                 state: 'S',
+                ..Default::default()
             }],
             trace_context: None,
         };
@@ -965,6 +2374,10 @@ mod tests {
             ],
             working_dir: None,
             timeout_secs: None,
+            exec_id: None,
+            policy: None,
+            user: None,
+            stdin_stream: false,
         };
         let debug_output = format!("{:?}", req);
         assert!(debug_output.contains("[REDACTED]"));
@@ -981,6 +2394,7 @@ mod tests {
         let req = TelemetrySubscribeRequest {
             interval_ms: 500,
             include_kernel_threads: true,
+            ..Default::default()
         };
         let json = serde_json::to_vec(&req).unwrap();
         let decoded: TelemetrySubscribeRequest = serde_json::from_slice(&json).unwrap();
@@ -1003,6 +2417,68 @@ mod tests {
         assert!(!req.include_kernel_threads);
     }
 
+    #[test]
+    fn telemetry_control_request_fields_are_optional() {
+        let req: TelemetryControlRequest = serde_json::from_slice(b"{}").unwrap();
+        assert!(req.interval_ms.is_none());
+        assert!(req.paused.is_none());
+        assert!(req.process_filter.is_none());
+    }
+
+    #[test]
+    fn telemetry_process_filter_matches_pid_or_name() {
+        let process = ProcessMetrics {
+            pid: 42,
+            comm: "node".into(),
+            rss_bytes: 0,
+            cpu_jiffies: 0,
+            state: 'S',
+            ..Default::default()
+        };
+        assert!(TelemetryProcessFilter::default().matches(&process));
+        let by_pid = TelemetryProcessFilter {
+            pids: vec![42],
+            ..Default::default()
+        };
+        assert!(by_pid.matches(&process));
+        let by_name = TelemetryProcessFilter {
+            names: vec!["python3".into()],
+            ..Default::default()
+        };
+        assert!(!by_name.matches(&process));
+    }
+
+    #[test]
+    fn top_processes_keeps_busiest_first() {
+        let process = |pid, cpu_percent, rss_bytes| ProcessMetrics {
+            pid,
+            cpu_percent,
+            rss_bytes,
+            ..Default::default()
+        };
+        let mut processes = vec![
+            process(1, 5.0, 300),
+            process(2, 80.0, 100),
+            process(3, 20.0, 200),
+        ];
+        let by_cpu = TopProcesses {
+            count: 2,
+            by: ProcessRanking::Cpu,
+        };
+        by_cpu.apply(&mut processes);
+        assert_eq!(processes.iter().map(|p| p.pid).collect::<Vec<_>>(), [2, 3]);
+
+        let mut processes = vec![
+            process(1, 5.0, 300),
+            process(2, 80.0, 100),
+            process(3, 20.0, 200),
+        ];
+        let by_memory: TopProcesses = serde_json::from_str(r#"{"count":1,"by":"memory"}"#).unwrap();
+        by_memory.apply(&mut processes);
+        assert_eq!(processes[0].pid, 1);
+        assert_eq!(processes.len(), 1);
+    }
+
     #[test]
     fn protocol_version_is_nonzero() {
         const { assert!(PROTOCOL_VERSION > 0, "PROTOCOL_VERSION must be > 0") };
@@ -1083,6 +2559,40 @@ mod tests {
         assert!(!decoded.exists);
     }
 
+    #[test]
+    fn link_and_mode_message_types() {
+        for &(byte, expected) in &[
+            (50u8, MessageType::Symlink),
+            (51, MessageType::SymlinkResponse),
+            (52, MessageType::Chmod),
+            (53, MessageType::ChmodResponse),
+            (54, MessageType::ReadLink),
+            (55, MessageType::ReadLinkResponse),
+            (56, MessageType::HashFiles),
+            (57, MessageType::HashFilesResponse),
+            (58, MessageType::SysInfo),
+            (59, MessageType::SysInfoResponse),
+            (60, MessageType::TelemetryControl),
+            (61, MessageType::TelemetryControlResponse),
+            (62, MessageType::AttachExec),
+        ] {
+            assert_eq!(MessageType::try_from(byte).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn chmod_request_defaults_to_unchanged() {
+        let decoded: ChmodRequest =
+            serde_json::from_str(r#"{"path":"/workspace/run.sh","mode":493}"#).unwrap();
+        assert_eq!(decoded.mode, Some(0o755));
+        assert_eq!((decoded.uid, decoded.gid), (None, None));
+
+        let decoded: SymlinkRequest =
+            serde_json::from_str(r#"{"target":"../bin/tool","link_path":"/workspace/tool"}"#)
+                .unwrap();
+        assert!(!decoded.replace);
+    }
+
     #[test]
     fn read_file_request_round_trip() {
         let req = ReadFileRequest {
@@ -1133,6 +2643,323 @@ mod tests {
         );
     }
 
+    #[test]
+    fn write_file_chunk_message_types() {
+        for &(byte, expected) in &[
+            (28u8, MessageType::WriteFileChunk),
+            (29, MessageType::WriteFileChunkResponse),
+            (30, MessageType::WriteFileFinalize),
+        ] {
+            assert_eq!(MessageType::try_from(byte).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn write_file_chunk_request_json_round_trip() {
+        let req = WriteFileChunkRequest {
+            path: "/workspace/model.bin".into(),
+            offset: 8 * 1024 * 1024,
+            data: vec![0, 1, 2, 255],
+            create_parents: false,
+        };
+        let json = serde_json::to_vec(&req).unwrap();
+        let decoded: WriteFileChunkRequest = serde_json::from_slice(&json).unwrap();
+        assert_eq!(decoded.path, "/workspace/model.bin");
+        assert_eq!(decoded.offset, 8 * 1024 * 1024);
+        assert_eq!(decoded.data, vec![0, 1, 2, 255]);
+        assert!(!decoded.create_parents);
+
+        let minimal: WriteFileChunkRequest =
+            serde_json::from_str(r#"{"path":"/workspace/a","offset":0,"data":[]}"#).unwrap();
+        assert!(minimal.create_parents);
+    }
+
+    #[test]
+    fn write_file_chunk_max_payload_fits_in_message() {
+        let req = WriteFileChunkRequest {
+            path: "/workspace/large.bin".into(),
+            offset: u64::MAX,
+            data: vec![255u8; WRITE_FILE_CHUNK_SIZE],
+            create_parents: true,
+        };
+        let json = serde_json::to_vec(&req).unwrap();
+        assert!(json.len() < MAX_MESSAGE_SIZE);
+    }
+
+    #[test]
+    fn write_file_finalize_request_json_round_trip() {
+        let req = WriteFileFinalizeRequest {
+            path: "/workspace/model.bin".into(),
+            total_size: 300 * 1024 * 1024,
+        };
+        let json = serde_json::to_vec(&req).unwrap();
+        let decoded: WriteFileFinalizeRequest = serde_json::from_slice(&json).unwrap();
+        assert_eq!(decoded.path, "/workspace/model.bin");
+        assert_eq!(decoded.total_size, 300 * 1024 * 1024);
+    }
+
+    #[test]
+    fn fs_diff_message_types() {
+        assert_eq!(MessageType::try_from(31u8).unwrap(), MessageType::FsDiff);
+        assert_eq!(
+            MessageType::try_from(32u8).unwrap(),
+            MessageType::FsDiffResponse
+        );
+    }
+
+    #[test]
+    fn fs_diff_response_json_round_trip() {
+        let resp = FsDiffResponse {
+            success: true,
+            root: "/".into(),
+            changes: vec![FsChange {
+                path: "/etc/hosts".into(),
+                kind: FsChangeKind::Modified,
+                size: 42,
+                sha256: Some("ab".repeat(32)),
+            }],
+            baseline_created: false,
+            truncated: false,
+            error: None,
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        assert!(json.contains(r#""kind":"modified""#));
+        let decoded: FsDiffResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.changes, resp.changes);
+
+        let request: FsDiffRequest = serde_json::from_str("{}").unwrap();
+        assert!(request.root.is_none());
+    }
+
+    #[test]
+    fn fs_diff_max_changes_fit_in_message() {
+        let resp = FsDiffResponse {
+            success: true,
+            root: "/".into(),
+            changes: vec![
+                FsChange {
+                    path: format!("/{}", "a".repeat(4095)),
+                    kind: FsChangeKind::Created,
+                    size: u64::MAX,
+                    sha256: Some("f".repeat(64)),
+                };
+                MAX_FS_DIFF_CHANGES
+            ],
+            baseline_created: false,
+            truncated: true,
+            error: None,
+        };
+        assert!(serde_json::to_vec(&resp).unwrap().len() < MAX_MESSAGE_SIZE);
+    }
+
+    #[test]
+    fn export_workspace_message_types() {
+        for &(byte, expected) in &[
+            (33u8, MessageType::ExportWorkspace),
+            (34, MessageType::ExportWorkspaceChunk),
+            (35, MessageType::ExportWorkspaceResponse),
+        ] {
+            assert_eq!(MessageType::try_from(byte).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn shutdown_request_accepts_empty_payload_and_ack_roundtrips() {
+        assert_eq!(MessageType::try_from(36).unwrap(), MessageType::ShutdownAck);
+
+        let req: ShutdownRequest = serde_json::from_slice(b"{}").unwrap();
+        assert_eq!(req.grace_ms, None);
+        assert!(!req.reboot);
+
+        let ack = ShutdownAck {
+            processes_terminated: 3,
+            processes_killed: 1,
+            bytes_flushed: 4096,
+            duration_ms: 250,
+        };
+        let decoded: ShutdownAck =
+            serde_json::from_slice(&serde_json::to_vec(&ack).unwrap()).unwrap();
+        assert_eq!(decoded, ack);
+    }
+
+    #[test]
+    fn signal_exec_wire_format() {
+        assert_eq!(MessageType::try_from(37).unwrap(), MessageType::SignalExec);
+        assert_eq!(
+            MessageType::try_from(38).unwrap(),
+            MessageType::SignalExecResponse
+        );
+
+        let req = SignalExecRequest {
+            exec_id: "exec-1".into(),
+            signal: ExecSignal::Stop,
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"exec_id":"exec-1","signal":"stop"}"#);
+        assert_eq!(
+            serde_json::to_string(&ExecSignal::Interrupt).unwrap(),
+            r#""interrupt""#
+        );
+        assert_eq!(
+            serde_json::to_string(&ExecSignal::Terminate).unwrap(),
+            r#""terminate""#
+        );
+
+        // Requests from hosts that never signal omit the id entirely.
+        let exec: ExecRequest = serde_json::from_str(
+            r#"{"program":"ls","args":[],"working_dir":null,"timeout_secs":null}"#,
+        )
+        .unwrap();
+        assert!(exec.exec_id.is_none());
+        assert!(!serde_json::to_string(&exec).unwrap().contains("exec_id"));
+    }
+
+    #[test]
+    fn exec_stdin_wire_format() {
+        assert_eq!(
+            MessageType::try_from(47).unwrap(),
+            MessageType::ExecStdinChunk
+        );
+        assert_eq!(
+            MessageType::try_from(49).unwrap(),
+            MessageType::ExecStdinResponse
+        );
+
+        let close = ExecStdinClose {
+            exec_id: "exec-1".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&close).unwrap(),
+            r#"{"exec_id":"exec-1"}"#
+        );
+
+        // Only streamed-stdin requests carry the flag.
+        let exec: ExecRequest = serde_json::from_str(
+            r#"{"program":"cat","args":[],"working_dir":null,"timeout_secs":null}"#,
+        )
+        .unwrap();
+        assert!(!exec.stdin_stream);
+        assert!(!serde_json::to_string(&exec)
+            .unwrap()
+            .contains("stdin_stream"));
+    }
+
+    #[test]
+    fn exec_policy_deny_overrides_allow_and_reports_rule() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let policy = ExecPolicy::new()
+            .allow("git")
+            .allow("/usr/local/bin/*")
+            .rule(ExecRule::deny("git").args(["push"]))
+            .rule(ExecRule::deny("git").any_arg("--force*"));
+
+        assert!(policy.check("/usr/bin/git", &args(&["status"])).is_ok());
+        assert!(policy.check("/usr/local/bin/tool", &[]).is_ok());
+        assert!(policy.check("/opt/tool", &[]).is_err());
+        assert_eq!(
+            policy.check("git", &args(&["push", "origin"])).unwrap_err(),
+            ExecDenial {
+                rule_index: Some(2),
+                rule: "deny git push".into()
+            }
+        );
+        let denial = policy
+            .check("git", &args(&["fetch", "--force-with-lease"]))
+            .unwrap_err();
+        assert_eq!(
+            denial.to_string(),
+            "denied by rule #3 (deny git [any arg --force*])"
+        );
+        assert_eq!(
+            policy.check("curl", &[]).unwrap_err().to_string(),
+            "not allowed: no allow rule matches"
+        );
+        assert!(ExecPolicy::new().deny("curl").check("wget", &[]).is_ok());
+        assert!(ExecPolicy::new().check("anything", &[]).is_ok());
+    }
+
+    #[test]
+    fn set_exec_policy_wire_format() {
+        assert_eq!(
+            MessageType::try_from(39).unwrap(),
+            MessageType::SetExecPolicy
+        );
+        assert_eq!(
+            MessageType::try_from(40).unwrap(),
+            MessageType::SetExecPolicyResponse
+        );
+        let req = SetExecPolicyRequest {
+            policy: ExecPolicy::from_allowlist(["ls"]).rule(ExecRule::deny("rm").any_arg("-rf")),
+        };
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"policy":{"rules":[{"action":"allow","program":"ls"},{"action":"deny","program":"rm","any_arg":"-rf"}]}}"#
+        );
+
+        // Execs from hosts without per-exec policies carry none.
+        let exec: ExecRequest = serde_json::from_str(
+            r#"{"program":"ls","args":[],"working_dir":null,"timeout_secs":null}"#,
+        )
+        .unwrap();
+        assert!(exec.policy.is_none());
+        assert!(!serde_json::to_string(&exec).unwrap().contains("policy"));
+    }
+
+    #[test]
+    fn exec_user_wire_format() {
+        let exec = |user: Option<ExecUser>| {
+            serde_json::to_value(ExecRequest {
+                program: "id".into(),
+                args: vec![],
+                stdin: Vec::new(),
+                env: Vec::new(),
+                working_dir: None,
+                timeout_secs: None,
+                exec_id: None,
+                policy: None,
+                user,
+                stdin_stream: false,
+            })
+            .unwrap()
+        };
+        assert!(exec(None).get("user").is_none());
+        assert_eq!(exec(Some(ExecUser::Root))["user"], "root");
+        assert_eq!(
+            exec(Some(ExecUser::named("builder")))["user"],
+            serde_json::json!({"named": "builder"})
+        );
+
+        assert!(valid_user_name("builder-2"));
+        assert!(valid_user_name("_svc"));
+        for bad in [
+            "",
+            "root",
+            "sandbox",
+            "Builder",
+            "2x",
+            "a b",
+            &"a".repeat(33),
+        ] {
+            assert!(!valid_user_name(bad), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn glob_match_segments_and_recursion() {
+        assert!(glob_match("*.json", "result.json"));
+        assert!(!glob_match("*.json", "out/result.json"));
+        assert!(glob_match("out/*", "out/a.txt"));
+        assert!(!glob_match("out/*", "out/sub/a.txt"));
+        assert!(glob_match("out/**", "out/sub/a.txt"));
+        assert!(glob_match("**/*.json", "result.json"));
+        assert!(glob_match("**/*.json", "a/b/result.json"));
+        assert!(glob_match("report-?.md", "report-1.md"));
+        assert!(!glob_match("report-?.md", "report-10.md"));
+        assert!(!glob_match("a?b", "a/b"));
+        assert!(glob_match("exact/path", "exact/path"));
+        assert!(!glob_match("exact/path", "exact/path2"));
+    }
+
     #[test]
     fn build_ping_payload_layout() {
         let secret = [0xABu8; 32];
@@ -1393,4 +3220,24 @@ mod tests {
         assert_eq!(decoded.msg_type, MessageType::PtyData);
         assert_eq!(decoded.payload, raw);
     }
+
+    #[test]
+    fn write_roots_must_be_normalized_directories() {
+        assert!(validate_write_root("/opt").is_ok());
+        assert!(validate_write_root("/etc/systemd/system").is_ok());
+        assert!(validate_write_root("/devices").is_ok());
+        for bad in [
+            "opt",
+            "/",
+            "/opt/",
+            "/opt//x",
+            "/opt/../etc",
+            "/a,b",
+            "/a b",
+            "/proc",
+            "/sys/fs",
+        ] {
+            assert!(validate_write_root(bad).is_err(), "{bad}");
+        }
+    }
 }
@@ -0,0 +1,684 @@
+//! void-test: In-guest adapter reporting structured test results.
+//!
+//! Runs a test command with its output passed through unchanged, parses
+//! that output, and appends one JSON line per test case to the file named
+//! by VOIDBOX_TEST_REPORT. The guest-agent sets that variable for every
+//! exec and returns the cases to the host with the exec's response.
+//!
+//! Usage:
+//!   void-test [--format libtest|pytest] [--suite NAME] -- COMMAND [ARGS...]
+//!   void-test record --suite S --name N --status STATUS [--duration-ms MS] [--message TEXT]
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc;
+
+use void_box_protocol::{TestCaseResult, TestStatus, TEST_REPORT_ENV};
+
+/// Arguments added to a pytest command: per-case status lines, failure
+/// summaries, and the duration of every phase of every case.
+const PYTEST_ARGS: &[&str] = &["-v", "-rfE", "--durations=0", "--durations-min=0"];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        None | Some("--help" | "-h" | "help") => {
+            usage();
+            process::exit(if args.is_empty() { 1 } else { 0 });
+        }
+        Some("record") => cmd_record(&args[1..]).map(|()| 0),
+        Some(_) => cmd_run(&args),
+    };
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("void-test: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+fn usage() {
+    eprintln!("Usage: void-test [options] -- <command> [args...]");
+    eprintln!("       void-test record --suite S --name N --status STATUS [options]");
+    eprintln!();
+    eprintln!("Runs a test command and reports each case to void-box.");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --format libtest|pytest  Output format (detected from cargo / pytest)");
+    eprintln!("  --suite NAME             Suite for every case (default: from the output)");
+    eprintln!();
+    eprintln!("record options:");
+    eprintln!("  --status STATUS          passed, failed, skipped, or error");
+    eprintln!("  --duration-ms MS         Wall time of the case");
+    eprintln!("  --message TEXT           Failure or skip reason");
+    eprintln!();
+    eprintln!("Environment:");
+    eprintln!("  {TEST_REPORT_ENV}  Report file (set automatically)");
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Libtest,
+    Pytest,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "libtest" => Ok(Self::Libtest),
+            "pytest" => Ok(Self::Pytest),
+            other => Err(format!("unknown format: {other} (use libtest or pytest)")),
+        }
+    }
+
+    /// The format of `command`'s output: `cargo test` prints libtest's, and
+    /// `pytest` or `python -m pytest` pytest's.
+    fn detect(command: &[String]) -> Option<Self> {
+        let program = Path::new(command.first()?).file_name()?.to_str()?;
+        match program {
+            "cargo" => Some(Self::Libtest),
+            "pytest" | "py.test" => Some(Self::Pytest),
+            _ if program.starts_with("python")
+                && command.windows(2).any(|w| w[0] == "-m" && w[1] == "pytest") =>
+            {
+                Some(Self::Pytest)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn cmd_run(args: &[String]) -> Result<i32, String> {
+    let mut format: Option<Format> = None;
+    let mut suite: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--" => {
+                i += 1;
+                break;
+            }
+            "--format" => {
+                i += 1;
+                format = Some(Format::parse(
+                    args.get(i).ok_or("--format requires a value")?,
+                )?);
+            }
+            "--suite" => {
+                i += 1;
+                suite = Some(args.get(i).ok_or("--suite requires a value")?.clone());
+            }
+            other => {
+                return Err(format!(
+                    "unknown option: {other} (put the command after --)"
+                ))
+            }
+        }
+        i += 1;
+    }
+
+    let mut command = args[i..].to_vec();
+    if command.is_empty() {
+        return Err("no command given (put it after --)".to_string());
+    }
+    let format = match format {
+        Some(format) => format,
+        None => Format::detect(&command).ok_or_else(|| {
+            format!(
+                "cannot tell the output format of '{}'; pass --format",
+                command[0]
+            )
+        })?,
+    };
+    if format == Format::Pytest {
+        command.extend(PYTEST_ARGS.iter().map(|arg| arg.to_string()));
+    }
+
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run '{}': {e}", command[0]))?;
+
+    // Both streams feed one parser: cargo names the test binary on stderr
+    // and the binary reports its cases on stdout.
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().map(|pipe| {
+        let tx = tx.clone();
+        std::thread::spawn(move || tee(pipe, std::io::stdout(), tx))
+    });
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| std::thread::spawn(move || tee(pipe, std::io::stderr(), tx)));
+
+    let mut parser: Box<dyn Parser> = match format {
+        Format::Libtest => Box::new(Libtest::new(suite)),
+        Format::Pytest => Box::new(Pytest::new(suite)),
+    };
+    for line in rx {
+        parser.line(&line);
+    }
+    for thread in [stdout, stderr].into_iter().flatten() {
+        let _ = thread.join();
+    }
+    let status = child
+        .wait()
+        .map_err(|e| format!("failed to wait for '{}': {e}", command[0]))?;
+
+    let cases = parser.finish();
+    eprintln!("void-test: {}", summary(&cases));
+    write_report(&cases)?;
+
+    use std::os::unix::process::ExitStatusExt;
+    Ok(status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0)))
+}
+
+fn cmd_record(args: &[String]) -> Result<(), String> {
+    let mut suite: Option<String> = None;
+    let mut name: Option<String> = None;
+    let mut status: Option<TestStatus> = None;
+    let mut duration_ms: Option<f64> = None;
+    let mut message: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        i += 1;
+        let value = args.get(i);
+        match args[i - 1].as_str() {
+            "--suite" => suite = Some(value.ok_or("--suite requires a value")?.clone()),
+            "--name" => name = Some(value.ok_or("--name requires a value")?.clone()),
+            "--status" => {
+                let v = value.ok_or("--status requires a value")?;
+                status = Some(match v.as_str() {
+                    "passed" => TestStatus::Passed,
+                    "failed" => TestStatus::Failed,
+                    "skipped" => TestStatus::Skipped,
+                    "error" => TestStatus::Error,
+                    _ => {
+                        return Err(format!(
+                            "invalid status: {v} (use passed, failed, skipped, or error)"
+                        ))
+                    }
+                });
+            }
+            "--duration-ms" => {
+                duration_ms = Some(
+                    value
+                        .ok_or("--duration-ms requires a value")?
+                        .parse()
+                        .map_err(|_| "--duration-ms must be a number")?,
+                );
+            }
+            "--message" => message = Some(value.ok_or("--message requires a value")?.clone()),
+            other => return Err(format!("unknown option: {other}")),
+        }
+        i += 1;
+    }
+
+    write_report(&[TestCaseResult {
+        suite: suite.ok_or("--suite is required")?,
+        name: name.ok_or("--name is required")?,
+        status: status.ok_or("--status is required")?,
+        duration_ms,
+        message,
+    }])
+}
+
+/// Copy `pipe` to `out` as it arrives, sending each line to `lines`.
+fn tee(pipe: impl Read, mut out: impl Write, lines: mpsc::Sender<String>) {
+    let mut reader = BufReader::new(pipe);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let _ = out.write_all(&buf);
+                let _ = out.flush();
+                let line = String::from_utf8_lossy(&buf);
+                let _ = lines.send(line.trim_end_matches(['\n', '\r']).to_string());
+            }
+        }
+    }
+}
+
+fn summary(cases: &[TestCaseResult]) -> String {
+    let count = |status| cases.iter().filter(|c| c.status == status).count();
+    format!(
+        "{} passed, {} failed, {} skipped, {} errors",
+        count(TestStatus::Passed),
+        count(TestStatus::Failed),
+        count(TestStatus::Skipped),
+        count(TestStatus::Error)
+    )
+}
+
+/// Append `cases` to the report file, one JSON line each.
+fn write_report(cases: &[TestCaseResult]) -> Result<(), String> {
+    let Some(path) = env::var_os(TEST_REPORT_ENV) else {
+        eprintln!("void-test: {TEST_REPORT_ENV} not set, results not reported");
+        return Ok(());
+    };
+    let mut lines = String::new();
+    for case in cases {
+        lines.push_str(&serde_json::to_string(case).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .map_err(|e| format!("write {}: {e}", Path::new(&path).display()))
+}
+
+// ---------------------------------------------------------------------------
+// Output parsers
+// ---------------------------------------------------------------------------
+
+trait Parser {
+    /// Take one line of the command's output, without its newline.
+    fn line(&mut self, line: &str);
+
+    /// The cases seen, in the order they finished.
+    fn finish(self: Box<Self>) -> Vec<TestCaseResult>;
+}
+
+fn case(suite: &str, name: &str, status: TestStatus) -> TestCaseResult {
+    TestCaseResult {
+        suite: suite.to_string(),
+        name: name.to_string(),
+        status,
+        duration_ms: None,
+        message: None,
+    }
+}
+
+/// The human-readable output of Rust's built-in test harness, as printed
+/// by `cargo test`:
+///
+/// ```text
+///      Running tests/api.rs (target/debug/deps/api-1234)
+/// test lists_runs ... ok
+/// test rejects_bad_token ... FAILED
+///
+/// ---- rejects_bad_token stdout ----
+/// thread 'rejects_bad_token' panicked at tests/api.rs:12:5:
+/// ```
+///
+/// Per-case times are read when the harness prints them
+/// (`-Z unstable-options --report-time`).
+struct Libtest {
+    fixed_suite: Option<String>,
+    suite: String,
+    cases: Vec<TestCaseResult>,
+    /// Case whose captured output is being read, and the lines so far.
+    failure: Option<(String, Vec<String>)>,
+}
+
+impl Libtest {
+    fn new(fixed_suite: Option<String>) -> Self {
+        Self {
+            suite: fixed_suite.clone().unwrap_or_else(|| "tests".to_string()),
+            fixed_suite,
+            cases: Vec::new(),
+            failure: None,
+        }
+    }
+
+    fn set_suite(&mut self, suite: &str) {
+        if self.fixed_suite.is_none() {
+            self.suite = suite.to_string();
+        }
+    }
+
+    /// Attach the captured output being read to its case.
+    fn end_failure(&mut self) {
+        let Some((name, lines)) = self.failure.take() else {
+            return;
+        };
+        let message = lines
+            .iter()
+            .filter(|line| !line.starts_with("note: run with `RUST_BACKTRACE"))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        let suite = &self.suite;
+        if let Some(case) = self
+            .cases
+            .iter_mut()
+            .rev()
+            .find(|c| c.name == name && c.suite == *suite)
+        {
+            case.message = Some(message.trim().to_string()).filter(|m| !m.is_empty());
+        }
+    }
+}
+
+impl Parser for Libtest {
+    fn line(&mut self, line: &str) {
+        let trimmed = line.trim_start();
+        if let Some(rest) = trimmed.strip_prefix("Running ") {
+            let rest = rest.strip_prefix("unittests ").unwrap_or(rest);
+            self.end_failure();
+            self.set_suite(rest.split(" (").next().unwrap_or(rest));
+            return;
+        }
+        if let Some(krate) = trimmed.strip_prefix("Doc-tests ") {
+            self.end_failure();
+            self.set_suite(&format!("doc-tests {}", krate.trim()));
+            return;
+        }
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            self.end_failure();
+            self.failure = Some((name.to_string(), Vec::new()));
+            return;
+        }
+        if let Some((_, lines)) = &mut self.failure {
+            if line == "failures:" || line.starts_with("test result:") {
+                self.end_failure();
+            } else {
+                lines.push(line.to_string());
+            }
+            return;
+        }
+
+        let Some((name, outcome)) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.split_once(" ... "))
+        else {
+            return;
+        };
+        let (outcome, duration_ms) = match outcome.rsplit_once(" <") {
+            Some((outcome, time)) => (
+                outcome,
+                time.strip_suffix("s>")
+                    .and_then(|secs| secs.parse::<f64>().ok())
+                    .map(|secs| secs * 1000.0),
+            ),
+            None => (outcome, None),
+        };
+        let (status, message) = if outcome == "ok" {
+            (TestStatus::Passed, None)
+        } else if outcome.starts_with("FAILED") {
+            (TestStatus::Failed, None)
+        } else if let Some(reason) = outcome.strip_prefix("ignored") {
+            let reason = reason.trim_start_matches(',').trim();
+            (TestStatus::Skipped, Some(reason.to_string()))
+        } else {
+            // Benchmarks and anything else not a pass/fail.
+            return;
+        };
+        let mut case = case(&self.suite, name, status);
+        case.duration_ms = duration_ms;
+        case.message = message.filter(|m| !m.is_empty());
+        self.cases.push(case);
+    }
+
+    fn finish(mut self: Box<Self>) -> Vec<TestCaseResult> {
+        self.end_failure();
+        self.cases
+    }
+}
+
+/// pytest's output with [`PYTEST_ARGS`]:
+///
+/// ```text
+/// tests/test_api.py::test_lists_runs PASSED                  [ 50%]
+/// tests/test_api.py::test_bad_token FAILED                   [100%]
+/// ...
+/// 0.12s call     tests/test_api.py::test_bad_token
+/// ...
+/// FAILED tests/test_api.py::test_bad_token - assert 401 == 200
+/// ```
+///
+/// The suite of a case is its file; the name, the rest of its node id.
+struct Pytest {
+    fixed_suite: Option<String>,
+    cases: Vec<TestCaseResult>,
+}
+
+/// pytest's verbose outcome words, and what each means here.
+const PYTEST_OUTCOMES: &[(&str, TestStatus)] = &[
+    ("PASSED", TestStatus::Passed),
+    ("FAILED", TestStatus::Failed),
+    ("SKIPPED", TestStatus::Skipped),
+    ("ERROR", TestStatus::Error),
+    ("XFAIL", TestStatus::Skipped),
+    ("XPASS", TestStatus::Passed),
+];
+
+impl Pytest {
+    fn new(fixed_suite: Option<String>) -> Self {
+        Self {
+            fixed_suite,
+            cases: Vec::new(),
+        }
+    }
+
+    fn split(&self, node_id: &str) -> (String, String) {
+        let (file, name) = node_id.split_once("::").unwrap_or(("", node_id));
+        match &self.fixed_suite {
+            Some(suite) => (suite.clone(), node_id.to_string()),
+            None => (file.to_string(), name.to_string()),
+        }
+    }
+
+    fn find(&mut self, node_id: &str) -> Option<&mut TestCaseResult> {
+        let (suite, name) = self.split(node_id);
+        self.cases
+            .iter_mut()
+            .find(|c| c.suite == suite && c.name == name)
+    }
+
+    /// `<node id> <OUTCOME> [(reason)] [ NN%]`
+    fn outcome_line(&mut self, line: &str) -> bool {
+        if line.starts_with(' ') || !line.contains("::") {
+            return false;
+        }
+        for &(word, status) in PYTEST_OUTCOMES {
+            let Some((node_id, rest)) = line.split_once(&format!(" {word}")) else {
+                continue;
+            };
+            if !(rest.is_empty() || rest.starts_with(' ')) || node_id.contains(" - ") {
+                continue;
+            }
+            let reason = rest
+                .trim()
+                .strip_prefix('(')
+                .and_then(|r| r.split_once(')'))
+                .map(|(reason, _)| reason.to_string());
+            match self.find(node_id) {
+                // A teardown error after the call passed.
+                Some(case) => {
+                    if case.status == TestStatus::Passed {
+                        case.status = status;
+                    }
+                }
+                None => {
+                    let (suite, name) = self.split(node_id);
+                    let mut case = case(&suite, &name, status);
+                    case.message = reason;
+                    self.cases.push(case);
+                }
+            }
+            return true;
+        }
+        false
+    }
+}
+
+impl Parser for Pytest {
+    fn line(&mut self, line: &str) {
+        // `FAILED <node id> - <message>`, from the short test summary.
+        for prefix in ["FAILED ", "ERROR "] {
+            if let Some((node_id, message)) = line
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.split_once(" - "))
+            {
+                if let Some(case) = self.find(node_id) {
+                    case.message = Some(message.to_string());
+                }
+                return;
+            }
+        }
+        if self.outcome_line(line) {
+            return;
+        }
+        // `<secs>s <phase> <node id>`, from --durations.
+        let mut words = line.splitn(3, char::is_whitespace);
+        if let (Some(secs), Some("setup" | "call" | "teardown"), Some(node_id)) =
+            (words.next(), words.next(), words.next().map(str::trim))
+        {
+            if let Some(secs) = secs.strip_suffix('s').and_then(|s| s.parse::<f64>().ok()) {
+                if let Some(case) = self.find(node_id) {
+                    *case.duration_ms.get_or_insert(0.0) += secs * 1000.0;
+                }
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<TestCaseResult> {
+        self.cases
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(mut parser: Box<dyn Parser>, output: &str) -> Vec<TestCaseResult> {
+        for line in output.lines() {
+            parser.line(line);
+        }
+        parser.finish()
+    }
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_format_from_command() {
+        assert_eq!(
+            Format::detect(&args(&["cargo", "test"])),
+            Some(Format::Libtest)
+        );
+        assert_eq!(
+            Format::detect(&args(&["/usr/bin/pytest", "tests"])),
+            Some(Format::Pytest)
+        );
+        assert_eq!(
+            Format::detect(&args(&["python3", "-m", "pytest"])),
+            Some(Format::Pytest)
+        );
+        assert_eq!(Format::detect(&args(&["make", "test"])), None);
+    }
+
+    #[test]
+    fn libtest_cases_suites_and_failures() {
+        let output = "\
+     Running unittests src/lib.rs (target/debug/deps/demo-1234)
+
+running 3 tests
+test tests::adds ... ok
+test tests::slow ... ignored, needs network
+test tests::breaks ... FAILED
+
+failures:
+
+---- tests::breaks stdout ----
+thread 'tests::breaks' panicked at src/lib.rs:12:9:
+assertion `left == right` failed
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    tests::breaks
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+     Running tests/api.rs (target/debug/deps/api-5678)
+test lists_runs ... ok <0.250s>
+";
+        let cases = parse(Box::new(Libtest::new(None)), output);
+        assert_eq!(cases.len(), 4);
+        assert_eq!(cases[0].suite, "src/lib.rs");
+        assert_eq!(cases[0].status, TestStatus::Passed);
+        assert_eq!(cases[1].status, TestStatus::Skipped);
+        assert_eq!(cases[1].message.as_deref(), Some("needs network"));
+        assert_eq!(cases[2].status, TestStatus::Failed);
+        assert_eq!(
+            cases[2].message.as_deref(),
+            Some(
+                "thread 'tests::breaks' panicked at src/lib.rs:12:9:\n\
+                 assertion `left == right` failed"
+            )
+        );
+        assert_eq!(cases[3].suite, "tests/api.rs");
+        assert_eq!(cases[3].duration_ms, Some(250.0));
+    }
+
+    #[test]
+    fn pytest_cases_durations_and_messages() {
+        let output = "\
+tests/test_api.py::test_lists_runs PASSED                                [ 25%]
+tests/test_api.py::test_bad_token FAILED                                 [ 50%]
+tests/test_api.py::test_later SKIPPED (needs db)                         [ 75%]
+tests/test_api.py::test_param[a b] PASSED                                [100%]
+tests/test_api.py::test_param[a b] ERROR                                 [100%]
+
+============================= slowest durations ==============================
+0.12s call     tests/test_api.py::test_bad_token
+0.01s setup    tests/test_api.py::test_bad_token
+0.02s teardown tests/test_api.py::test_param[a b]
+=========================== short test summary info ===========================
+FAILED tests/test_api.py::test_bad_token - assert 401 == 200
+ERROR tests/test_api.py::test_param[a b] - RuntimeError: db gone
+";
+        let cases = parse(Box::new(Pytest::new(None)), output);
+        assert_eq!(cases.len(), 4);
+        assert_eq!(cases[0].suite, "tests/test_api.py");
+        assert_eq!(cases[0].name, "test_lists_runs");
+        assert_eq!(cases[1].status, TestStatus::Failed);
+        assert_eq!(cases[1].message.as_deref(), Some("assert 401 == 200"));
+        let duration = cases[1].duration_ms.unwrap();
+        assert!((duration - 130.0).abs() < 1e-6, "{duration}");
+        assert_eq!(cases[2].status, TestStatus::Skipped);
+        assert_eq!(cases[2].message.as_deref(), Some("needs db"));
+        assert_eq!(cases[3].name, "test_param[a b]");
+        assert_eq!(cases[3].status, TestStatus::Error);
+        assert_eq!(cases[3].duration_ms, Some(20.0));
+        assert_eq!(cases[3].message.as_deref(), Some("RuntimeError: db gone"));
+    }
+
+    #[test]
+    fn fixed_suite_overrides_detected_suite() {
+        let cases = parse(
+            Box::new(Libtest::new(Some("unit".to_string()))),
+            "     Running tests/api.rs (target/x)\ntest a ... ok\n",
+        );
+        assert_eq!(cases[0].suite, "unit");
+    }
+}
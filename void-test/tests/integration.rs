@@ -0,0 +1,89 @@
+//! Integration tests: run the void-test binary the way an exec would, with
+//! VOIDBOX_TEST_REPORT pointing at a report file.
+
+use std::process::Command;
+
+use void_box_protocol::{TestCaseResult, TestStatus, TEST_REPORT_ENV};
+
+fn void_test(report: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_void-test"))
+        .args(args)
+        .env(TEST_REPORT_ENV, report)
+        .output()
+        .expect("failed to run void-test")
+}
+
+fn read_report(report: &std::path::Path) -> Vec<TestCaseResult> {
+    std::fs::read_to_string(report)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn run_passes_output_and_exit_code_through_and_reports_cases() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = dir.path().join("report.jsonl");
+    let output = void_test(
+        &report,
+        &[
+            "--format",
+            "libtest",
+            "--",
+            "sh",
+            "-c",
+            "printf 'test a ... ok\\ntest b ... FAILED\\n'; exit 3",
+        ],
+    );
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "test a ... ok\ntest b ... FAILED\n"
+    );
+    let cases = read_report(&report);
+    assert_eq!(cases.len(), 2);
+    assert_eq!(cases[0].status, TestStatus::Passed);
+    assert_eq!(cases[1].name, "b");
+    assert_eq!(cases[1].status, TestStatus::Failed);
+}
+
+#[test]
+fn record_appends_one_case() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = dir.path().join("report.jsonl");
+    for name in ["first", "second"] {
+        let output = void_test(
+            &report,
+            &[
+                "record",
+                "--suite",
+                "smoke",
+                "--name",
+                name,
+                "--status",
+                "skipped",
+                "--duration-ms",
+                "1.5",
+                "--message",
+                "no network",
+            ],
+        );
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let cases = read_report(&report);
+    assert_eq!(cases.len(), 2);
+    assert_eq!(cases[1].name, "second");
+    assert_eq!(cases[1].duration_ms, Some(1.5));
+    assert_eq!(cases[1].message.as_deref(), Some("no network"));
+}
+
+#[test]
+fn unknown_command_format_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = void_test(&dir.path().join("report.jsonl"), &["--", "true"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass --format"));
+}
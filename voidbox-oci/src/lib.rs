@@ -15,6 +15,7 @@ pub struct OciClient {
     cache_dir: PathBuf,
     registry: registry::RegistryClient,
     platform: manifest::Platform,
+    fallback_platform: Option<manifest::Platform>,
 }
 
 /// Resolved guest image files (kernel + initramfs) on disk.
@@ -38,9 +39,17 @@ impl OciClient {
             cache_dir,
             registry: registry::RegistryClient::new(),
             platform: manifest::Platform::host(),
+            fallback_platform: None,
         }
     }
 
+    /// Pull `platform` for images that publish nothing for the host's
+    /// platform, e.g. `linux/amd64` when the guest runs it through Rosetta.
+    pub fn with_fallback_platform(mut self, platform: manifest::Platform) -> Self {
+        self.fallback_platform = Some(platform);
+        self
+    }
+
     /// Pull an image manifest and all layers, returning [`PulledImage`]
     /// metadata.  Layers are downloaded into the content-addressed blob cache
     /// and are not yet extracted.
@@ -58,7 +67,7 @@ impl OciClient {
         // 1. Resolve manifest (handles image index → platform selection).
         let manifest = self
             .registry
-            .resolve_manifest(&parsed, &self.platform)
+            .resolve_manifest(&parsed, &self.platform, self.fallback_platform.as_ref())
             .await?;
 
         // 2. Download the image config blob.
@@ -91,6 +100,12 @@ impl OciClient {
         })
     }
 
+    /// List the tags published for `image_ref`'s repository.
+    pub async fn list_tags(&self, image_ref: &str) -> Result<Vec<String>> {
+        let parsed = registry::ImageRef::parse(image_ref)?;
+        self.registry.list_tags(&parsed).await
+    }
+
     /// Unpack a previously pulled image's layers into `dest`, producing a
     /// merged root filesystem.  Returns the rootfs path.
     pub async fn unpack(&self, image: &PulledImage, dest: &Path) -> Result<PathBuf> {
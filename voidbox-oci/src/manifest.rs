@@ -61,6 +61,15 @@ impl Platform {
             variant: None,
         }
     }
+
+    /// Build a `linux` platform for an OCI architecture such as `amd64`.
+    pub fn linux(architecture: impl Into<String>) -> Self {
+        Self {
+            architecture: architecture.into(),
+            os: "linux".to_string(),
+            variant: None,
+        }
+    }
 }
 
 /// Map Rust `std::env::consts::ARCH` values to OCI / Docker platform strings.
@@ -110,6 +119,28 @@ impl ImageIndex {
                 ))
             })
     }
+
+    /// Select the descriptor for `target`, or for `fallback` when the index
+    /// has none for `target`. Returns the platform that matched.
+    pub fn select_platform_or<'a>(
+        &'a self,
+        target: &'a Platform,
+        fallback: Option<&'a Platform>,
+    ) -> Result<(&'a Descriptor, &'a Platform)> {
+        match (self.select_platform(target), fallback) {
+            (Ok(desc), _) => Ok((desc, target)),
+            (Err(_), Some(fallback)) => self
+                .select_platform(fallback)
+                .map(|desc| (desc, fallback))
+                .map_err(|_| {
+                    OciError::Manifest(format!(
+                        "no manifest found for platform {}/{} or {}/{}",
+                        target.os, target.architecture, fallback.os, fallback.architecture,
+                    ))
+                }),
+            (Err(e), None) => Err(e),
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -249,6 +280,24 @@ mod tests {
         assert!(idx.select_platform(&target).is_err());
     }
 
+    #[test]
+    fn select_platform_falls_back() {
+        let idx: ImageIndex = serde_json::from_str(SAMPLE_INDEX).unwrap();
+        let amd64 = Platform::linux("amd64");
+        let arm64 = Platform::linux("arm64");
+        let s390x = Platform::linux("s390x");
+
+        let (desc, platform) = idx.select_platform_or(&arm64, Some(&amd64)).unwrap();
+        assert_eq!(desc.digest, "sha256:arm64digest");
+        assert_eq!(platform, &arm64);
+
+        let (desc, platform) = idx.select_platform_or(&s390x, Some(&amd64)).unwrap();
+        assert_eq!(desc.digest, "sha256:amd64digest");
+        assert_eq!(platform, &amd64);
+
+        assert!(idx.select_platform_or(&s390x, None).is_err());
+    }
+
     #[test]
     fn media_type_helpers() {
         assert!(is_index_media_type(MEDIA_TYPE_OCI_INDEX));
@@ -179,6 +179,24 @@ impl RegistryClient {
         Ok(m)
     }
 
+    /// List the tags of `image_ref`'s repository (its reference is ignored).
+    pub async fn list_tags(&self, image_ref: &ImageRef) -> Result<Vec<String>> {
+        #[derive(serde::Deserialize)]
+        struct TagList {
+            #[serde(default)]
+            tags: Option<Vec<String>>,
+        }
+
+        let scheme = registry_scheme(&image_ref.registry);
+        let url = format!(
+            "{}://{}/v2/{}/tags/list",
+            scheme, image_ref.registry, image_ref.repository,
+        );
+        let body = self.authenticated_get(&url, image_ref, None).await?;
+        let list: TagList = serde_json::from_slice(&body)?;
+        Ok(list.tags.unwrap_or_default())
+    }
+
     /// Download a blob by digest.  Returns the raw bytes.
     pub async fn fetch_blob(&self, image_ref: &ImageRef, digest: &str) -> Result<Vec<u8>> {
         let scheme = registry_scheme(&image_ref.registry);
@@ -319,16 +337,18 @@ impl RegistryClient {
 
     /// Resolve an image reference to a concrete [`OciManifest`] by first
     /// fetching the manifest (which may be an index) and selecting the
-    /// platform-appropriate entry if needed.
+    /// platform-appropriate entry if needed. An index without `platform`
+    /// resolves to `fallback`, when given.
     pub async fn resolve_manifest(
         &self,
         image_ref: &ImageRef,
         platform: &Platform,
+        fallback: Option<&Platform>,
     ) -> Result<OciManifest> {
         match self.fetch_manifest(image_ref).await? {
             ManifestResponse::Manifest(m) => Ok(m),
             ManifestResponse::Index(idx) => {
-                let desc = idx.select_platform(platform)?;
+                let (desc, platform) = idx.select_platform_or(platform, fallback)?;
                 info!(
                     digest = %desc.digest,
                     "resolved platform {}/{}",